@@ -0,0 +1,293 @@
+//! Open documents of the editor
+//!
+//! Each open graph is a document with its own dirty flag and revision. Edits
+//! bump the revision; the background task validates documents whose latest
+//! revision has not been validated yet and autosaves dirty documents to the
+//! autosave directory. Autosaves are removed on save and close, so any left
+//! behind at startup belong to a session that crashed and are offered for
+//! recovery.
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use canvas_contracts::{
+    error::{CanvasError, CanvasResult},
+    nodes::load_graph,
+    types::VisualGraph,
+};
+use serde::{Deserialize, Serialize};
+
+pub type DocumentId = u64;
+
+/// Result of validating one revision of a document
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DocumentValidation {
+    pub revision: u64,
+    pub is_valid: bool,
+    pub errors: Vec<String>,
+    pub warnings: Vec<String>,
+}
+
+/// An open graph
+#[derive(Debug, Clone)]
+pub struct Document {
+    pub id: DocumentId,
+    /// File the document was opened from or last saved to
+    pub path: Option<PathBuf>,
+    pub graph: VisualGraph,
+    pub dirty: bool,
+    pub revision: u64,
+    pub validation: Option<DocumentValidation>,
+}
+
+/// What the frontend lists for an open document
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DocumentInfo {
+    pub id: DocumentId,
+    pub name: String,
+    pub path: Option<PathBuf>,
+    pub dirty: bool,
+    pub revision: u64,
+    pub validation: Option<DocumentValidation>,
+}
+
+/// Contents of an autosave file
+#[derive(Debug, Serialize, Deserialize)]
+struct Autosave {
+    path: Option<PathBuf>,
+    graph: VisualGraph,
+}
+
+/// All documents open in the editor
+pub struct DocumentManager {
+    documents: BTreeMap<DocumentId, Document>,
+    next_id: DocumentId,
+    autosave_dir: PathBuf,
+}
+
+impl DocumentManager {
+    pub fn new(autosave_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            documents: BTreeMap::new(),
+            next_id: 1,
+            autosave_dir: autosave_dir.into(),
+        }
+    }
+
+    fn insert(&mut self, path: Option<PathBuf>, graph: VisualGraph, dirty: bool) -> DocumentId {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.documents.insert(
+            id,
+            Document {
+                id,
+                path,
+                graph,
+                dirty,
+                revision: 0,
+                validation: None,
+            },
+        );
+        id
+    }
+
+    fn get_mut(&mut self, id: DocumentId) -> CanvasResult<&mut Document> {
+        self.documents
+            .get_mut(&id)
+            .ok_or_else(|| CanvasError::NotFound(format!("Document {} is not open", id)))
+    }
+
+    pub fn get(&self, id: DocumentId) -> Option<&Document> {
+        self.documents.get(&id)
+    }
+
+    /// Create an empty, unsaved document
+    pub fn create(&mut self, name: &str) -> DocumentId {
+        self.insert(None, VisualGraph::new(name), true)
+    }
+
+    /// Open a graph file, or return the document already showing it
+    pub fn open(&mut self, path: &Path) -> CanvasResult<DocumentId> {
+        if let Some(doc) = self.documents.values().find(|d| d.path.as_deref() == Some(path)) {
+            return Ok(doc.id);
+        }
+        let (graph, report) = load_graph(&fs::read_to_string(path)?)?;
+        // Migrated graphs differ from the file until saved
+        Ok(self.insert(Some(path.to_path_buf()), graph, !report.migrated.is_empty()))
+    }
+
+    /// Replace a document's graph after an edit
+    pub fn update(&mut self, id: DocumentId, graph: VisualGraph) -> CanvasResult<u64> {
+        let doc = self.get_mut(id)?;
+        doc.graph = graph;
+        doc.dirty = true;
+        doc.revision += 1;
+        Ok(doc.revision)
+    }
+
+    /// Save a document to `path`, or to the file it came from
+    pub fn save(&mut self, id: DocumentId, path: Option<PathBuf>) -> CanvasResult<PathBuf> {
+        let autosave = self.autosave_path(id);
+        let doc = self.get_mut(id)?;
+        let path = path
+            .or_else(|| doc.path.clone())
+            .ok_or_else(|| CanvasError::Validation(format!("Document {} has never been saved; choose a path", id)))?;
+        fs::write(&path, serde_json::to_string_pretty(&doc.graph)?)?;
+        doc.path = Some(path.clone());
+        doc.dirty = false;
+        remove_if_exists(&autosave)?;
+        Ok(path)
+    }
+
+    /// Close a document; unsaved changes are only discarded with `force`
+    pub fn close(&mut self, id: DocumentId, force: bool) -> CanvasResult<()> {
+        let doc = self
+            .documents
+            .get(&id)
+            .ok_or_else(|| CanvasError::NotFound(format!("Document {} is not open", id)))?;
+        if doc.dirty && !force {
+            return Err(CanvasError::Validation(format!(
+                "'{}' has unsaved changes",
+                doc.graph.name
+            )));
+        }
+        self.documents.remove(&id);
+        remove_if_exists(&self.autosave_path(id))
+    }
+
+    pub fn list(&self) -> Vec<DocumentInfo> {
+        self.documents
+            .values()
+            .map(|doc| DocumentInfo {
+                id: doc.id,
+                name: doc.graph.name.clone(),
+                path: doc.path.clone(),
+                dirty: doc.dirty,
+                revision: doc.revision,
+                validation: doc.validation.clone(),
+            })
+            .collect()
+    }
+
+    /// `(id, revision, graph)` of documents whose latest revision is not validated
+    pub fn pending_validation(&self) -> Vec<(DocumentId, u64, VisualGraph)> {
+        self.documents
+            .values()
+            .filter(|doc| doc.validation.as_ref().map_or(true, |v| v.revision != doc.revision))
+            .map(|doc| (doc.id, doc.revision, doc.graph.clone()))
+            .collect()
+    }
+
+    /// Store a validation result unless the document changed meanwhile
+    pub fn record_validation(&mut self, id: DocumentId, validation: DocumentValidation) -> bool {
+        match self.documents.get_mut(&id) {
+            Some(doc) if doc.revision == validation.revision => {
+                doc.validation = Some(validation);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn autosave_path(&self, id: DocumentId) -> PathBuf {
+        self.autosave_dir.join(format!("document-{}.json", id))
+    }
+
+    /// Write every dirty document to the autosave directory
+    pub fn autosave(&self) -> CanvasResult<usize> {
+        fs::create_dir_all(&self.autosave_dir)?;
+        let mut saved = 0;
+        for doc in self.documents.values().filter(|d| d.dirty) {
+            let autosave = Autosave {
+                path: doc.path.clone(),
+                graph: doc.graph.clone(),
+            };
+            fs::write(self.autosave_path(doc.id), serde_json::to_vec(&autosave)?)?;
+            saved += 1;
+        }
+        Ok(saved)
+    }
+
+    /// Reopen documents autosaved by a session that did not shut down cleanly.
+    ///
+    /// Recovered documents are dirty and are autosaved again right away under
+    /// their new document IDs.
+    pub fn recover(&mut self) -> CanvasResult<Vec<DocumentId>> {
+        if !self.autosave_dir.exists() {
+            return Ok(Vec::new());
+        }
+        let mut files: Vec<PathBuf> = fs::read_dir(&self.autosave_dir)?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| path.extension().map_or(false, |ext| ext == "json"))
+            .collect();
+        files.sort();
+
+        let mut recovered = Vec::new();
+        for file in files {
+            match serde_json::from_slice::<Autosave>(&fs::read(&file)?) {
+                Ok(autosave) => {
+                    fs::remove_file(&file)?;
+                    recovered.push(self.insert(autosave.path, autosave.graph, true));
+                }
+                Err(e) => eprintln!("Skipping unreadable autosave {}: {}", file.display(), e),
+            }
+        }
+        self.autosave()?;
+        Ok(recovered)
+    }
+}
+
+fn remove_if_exists(path: &Path) -> CanvasResult<()> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("canvas-documents-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_dirty_documents_survive_a_crash() {
+        let dir = scratch_dir("crash");
+        let mut manager = DocumentManager::new(dir.join("autosave"));
+        let id = manager.create("Token");
+        assert!(manager.close(id, false).is_err());
+
+        manager.update(id, VisualGraph::new("Token v2")).unwrap();
+        assert_eq!(manager.pending_validation().len(), 1);
+        assert!(!manager.record_validation(id, DocumentValidation {
+            revision: 0,
+            is_valid: true,
+            errors: Vec::new(),
+            warnings: Vec::new(),
+        }));
+        assert_eq!(manager.autosave().unwrap(), 1);
+
+        // A new session finds the autosave and reopens it
+        let mut restarted = DocumentManager::new(dir.join("autosave"));
+        let recovered = restarted.recover().unwrap();
+        assert_eq!(recovered.len(), 1);
+        let doc = restarted.get(recovered[0]).unwrap();
+        assert_eq!(doc.graph.name, "Token v2");
+        assert!(doc.dirty);
+
+        let path = restarted.save(recovered[0], Some(dir.join("token.json"))).unwrap();
+        restarted.close(recovered[0], false).unwrap();
+        assert_eq!(fs::read_dir(dir.join("autosave")).unwrap().count(), 0);
+        assert_eq!(restarted.open(&path).unwrap(), restarted.open(&path).unwrap());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,21 +1,148 @@
 // Prevents additional console window on Windows in release, DO NOT REMOVE!!
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
+mod documents;
+
 use canvas_contracts::{
     Compiler, WasmRuntime, BaalsClient, AiAssistant,
-    types::{VisualGraph, CompilationResult},
+    baals::{
+        impersonation::ImpersonationSession,
+        signer::{signer_from_spec, Signer},
+    },
+    compiler::{DiagnosticsCache, TraceMap},
+    config::{Config, PermissionDecision, PermissionScope},
+    nodes::{custom::CustomNodeRegistry, AssetRef, AssetServer, AssetStore, NodeAppearance, NodeRegistry},
+    types::{ContractABI, VisualGraph, CompilationResult, RevertReason},
     error::CanvasResult,
+    permissions::{AuditEntry, PermissionGate},
+    testing::ScenarioRecorder,
+    wasm::{
+        progress::{CancellationToken, SimulationOptions, SimulationRun},
+        SimulationRequest,
+    },
+    wizard::{builtin_wizards, StepView, WizardDefinition, WizardSession, WIZARDS_DIR},
 };
+use documents::{DocumentId, DocumentInfo, DocumentManager, DocumentValidation};
 use serde::{Deserialize, Serialize};
-use std::sync::Mutex;
-use tauri::State;
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+use tauri::{Manager, State};
+
+/// How often edited documents are re-validated
+const VALIDATION_INTERVAL: Duration = Duration::from_millis(500);
+/// How often dirty documents are written to the autosave directory
+const AUTOSAVE_INTERVAL: Duration = Duration::from_secs(30);
+/// Config file in the data directory the app reads its settings from
+const APP_CONFIG_FILE: &str = "config.toml";
+/// Audit entries returned when the frontend does not ask for a number
+const AUDIT_PAGE_SIZE: usize = 100;
 
 // App state
 struct AppState {
+    /// Settings loaded at startup by `app_config`
+    config: Config,
     compiler: Mutex<Option<Compiler>>,
     runtime: Mutex<Option<WasmRuntime>>,
     baals_client: Mutex<Option<BaalsClient>>,
     ai_assistant: Mutex<Option<AiAssistant>>,
+    documents: Mutex<DocumentManager>,
+    /// Validation results of open documents, reused between edits
+    diagnostics: Mutex<HashMap<DocumentId, DiagnosticsCache>>,
+    /// Cancellation tokens of running simulations, by simulation ID
+    simulations: Mutex<HashMap<String, CancellationToken>>,
+    /// Console calls being recorded into a scenario
+    recorder: Mutex<Option<ScenarioRecorder>>,
+    /// New contract wizards in progress, by session ID
+    wizards: Mutex<HashMap<u64, WizardSession>>,
+    next_wizard_id: AtomicU64,
+    /// Node icons and appearances, also served on the `canvas-asset` protocol
+    assets: Mutex<AssetServer>,
+    /// Policy every invoked command is checked against, and its audit trail
+    permissions: PermissionGate,
+}
+
+/// URI scheme the webview loads node icons from, e.g. `canvas-asset://localhost/assets/<hash>`
+const ASSET_PROTOCOL: &str = "canvas-asset";
+
+/// Settings from the config file in the data directory, or the defaults
+fn app_config() -> Config {
+    let defaults = Config::default();
+    let path = defaults.app.data_dir.join(APP_CONFIG_FILE);
+    if !path.exists() {
+        return defaults;
+    }
+    Config::from_file(&path).and_then(|config| config.validate().map(|_| config)).unwrap_or_else(|e| {
+        eprintln!("Ignoring {}: {}", path.display(), e);
+        defaults
+    })
+}
+
+/// Permission scope of each command the frontend can invoke
+fn command_scope(command: &str) -> Option<PermissionScope> {
+    let scope = match command {
+        "compile_contract" | "validate_graph" | "analyze_patterns" => PermissionScope::Build,
+        "open_document" | "update_document" | "save_document" | "list_documents" | "close_document" => {
+            PermissionScope::Documents
+        }
+        "simulate_contract" | "cancel_simulation" | "call_contract" => PermissionScope::Simulate,
+        "start_recording" | "stop_recording" => PermissionScope::Record,
+        "list_wizards" | "start_wizard" | "answer_wizard" | "wizard_back" | "finish_wizard" | "cancel_wizard" => {
+            PermissionScope::Wizards
+        }
+        "node_appearances" | "import_node_icon" => PermissionScope::Assets,
+        "impersonate_account" | "stop_impersonating" => PermissionScope::Impersonate,
+        "send_transaction" => PermissionScope::Sign,
+        "command_audit" => PermissionScope::Audit,
+        _ => return None,
+    };
+    Some(scope)
+}
+
+type InvokeHandler = Arc<dyn Fn(tauri::Invoke) + Send + Sync>;
+
+/// Run an invoked command if the permission policy allows it, asking the
+/// user in a native dialog when the policy wants it confirmed. The dialog is
+/// outside the webview, so frontend code cannot answer it.
+fn gated_invoke(handler: &InvokeHandler, invoke: tauri::Invoke) {
+    let command = invoke.message.command().to_string();
+    let Some(scope) = command_scope(&command) else {
+        invoke.resolver.reject(format!("Unknown command '{}'", command));
+        return;
+    };
+    let window = invoke.message.window();
+    let (decision, prompt) = {
+        let gate = &window.state::<AppState>().permissions;
+        let decision = gate.decide(scope);
+        if decision != PermissionDecision::Confirm {
+            match gate.settle(&command, scope, decision, false) {
+                Ok(()) => handler(invoke),
+                Err(e) => invoke.resolver.reject(e.to_string()),
+            }
+            return;
+        }
+        (decision, gate.confirmation_prompt(&command, scope))
+    };
+
+    let handler = handler.clone();
+    let parent = window.clone();
+    tauri::api::dialog::ask(Some(&parent), "Confirm action", prompt, move |confirmed| {
+        let settled = window.state::<AppState>().permissions.settle(&command, scope, decision, confirmed);
+        match settled {
+            Ok(()) => handler(invoke),
+            Err(e) => invoke.resolver.reject(e.to_string()),
+        }
+    });
+}
+
+fn installed_nodes(config: &Config) -> CanvasResult<CustomNodeRegistry> {
+    CustomNodeRegistry::load_dir(&config.app.data_dir.join(canvas_contracts::nodes::custom::CUSTOM_NODES_DIR))
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -82,17 +209,556 @@ async fn analyze_patterns(
     Ok(serde_json::to_value(analysis).map_err(|e| e.to_string())?)
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+struct SimulateRequest {
+    /// Chosen by the frontend; progress events and `cancel_simulation` refer to it
+    simulation_id: String,
+    wasm_bytes: Vec<u8>,
+    function: String,
+    #[serde(default)]
+    arguments: Vec<serde_json::Value>,
+    gas_limit: u64,
+    expected_gas: Option<u64>,
+    /// ABI of the build, for the trace map of instrumented builds
+    abi: Option<ContractABI>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct SimulationProgressEvent {
+    simulation_id: String,
+    percent: f64,
+    current_node: Option<canvas_contracts::types::NodeId>,
+    gas_used: u64,
+}
+
+/// Run a simulation off the UI thread, emitting `simulation-progress` events.
+/// A cancelled run resolves with its partial results.
+#[tauri::command]
+async fn simulate_contract(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+    request: SimulateRequest,
+) -> Result<SimulationRun, String> {
+    let mut options = SimulationOptions::new();
+    if let Some(expected_gas) = request.expected_gas {
+        options = options.with_expected_gas(expected_gas);
+    }
+    if let Some(abi) = &request.abi {
+        if let Some(map) = TraceMap::read_from(abi).map_err(|e| e.to_string())? {
+            options = options.with_trace_map(map);
+        }
+    }
+
+    let cancel = CancellationToken::new();
+    state
+        .simulations
+        .lock()
+        .unwrap()
+        .insert(request.simulation_id.clone(), cancel.clone());
+
+    let simulation_id = request.simulation_id.clone();
+    let emitter = app.clone();
+    let config = state.config.clone();
+    let run = tauri::async_runtime::spawn_blocking(move || {
+        let runtime = WasmRuntime::new(&config)?;
+        let call = SimulationRequest::new(request.function, request.arguments, request.gas_limit);
+        runtime.simulate_with_progress(&request.wasm_bytes, &call, &options, &cancel, move |progress| {
+            let _ = emitter.emit_all(
+                "simulation-progress",
+                SimulationProgressEvent {
+                    simulation_id: simulation_id.clone(),
+                    percent: progress.percent,
+                    current_node: progress.current_node,
+                    gas_used: progress.gas_used,
+                },
+            );
+        })
+    })
+    .await;
+
+    app.state::<AppState>().simulations.lock().unwrap().remove(&request.simulation_id);
+    run.map_err(|e| e.to_string())?.map_err(|e| e.to_string())
+}
+
+/// Ask a running simulation to stop; it stops at its next progress check
+#[tauri::command]
+async fn cancel_simulation(state: State<'_, AppState>, simulation_id: String) -> Result<bool, String> {
+    Ok(match state.simulations.lock().unwrap().get(&simulation_id) {
+        Some(token) => {
+            token.cancel();
+            true
+        }
+        None => false,
+    })
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CallRequest {
+    contract: PathBuf,
+    function: String,
+    #[serde(default)]
+    args: serde_json::Value,
+    caller: Option<String>,
+    gas_limit: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CallResponse {
+    output: serde_json::Value,
+    gas_used: u64,
+    revert_reason: Option<RevertReason>,
+    /// Whether the call was added to the active recording
+    recorded: bool,
+}
+
+/// Call a contract function from the console, recording it when a recording
+/// for that contract is active
+#[tauri::command]
+async fn call_contract(state: State<'_, AppState>, request: CallRequest) -> Result<CallResponse, String> {
+    let wasm_bytes = std::fs::read(&request.contract).map_err(|e| e.to_string())?;
+    let arguments = match &request.args {
+        serde_json::Value::Array(items) => items.clone(),
+        serde_json::Value::Null => Vec::new(),
+        other => vec![other.clone()],
+    };
+    let result = {
+        let runtime = state.runtime.lock().unwrap();
+        let runtime = runtime.as_ref().ok_or("Runtime not initialized")?;
+        runtime
+            .execute_function_as(
+                &wasm_bytes,
+                &request.function,
+                arguments,
+                request.gas_limit,
+                request.caller.as_deref(),
+            )
+            .map_err(|e| e.to_string())?
+    };
+
+    let mut recorder = state.recorder.lock().unwrap();
+    let recorded = match recorder.as_mut() {
+        Some(recorder) if recorder.contract() == request.contract.as_path() => {
+            recorder.record(&request.function, request.args, request.caller, request.gas_limit, &result);
+            true
+        }
+        _ => false,
+    };
+    Ok(CallResponse {
+        output: result.output,
+        gas_used: result.gas_used,
+        revert_reason: result.revert_reason,
+        recorded,
+    })
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SendRequest {
+    address: String,
+    function: String,
+    #[serde(default)]
+    args: Vec<serde_json::Value>,
+    /// Key file, `ledger` or `ledger:<derivation path>`
+    key: String,
+}
+
+#[derive(Debug, Serialize)]
+struct SendResponse {
+    transaction_hash: String,
+    gas_used: u64,
+    success: bool,
+    output: serde_json::Value,
+    revert_reason: Option<RevertReason>,
+}
+
+/// Forwards to a signer, emitting `signer-confirmation` with the prompt when
+/// the signature has to be approved on a hardware wallet
+struct ConsoleSigner {
+    inner: Box<dyn Signer>,
+    app: tauri::AppHandle,
+    action: String,
+}
+
+impl Signer for ConsoleSigner {
+    fn describe(&self) -> String {
+        self.inner.describe()
+    }
+
+    fn public_key(&self) -> CanvasResult<[u8; 32]> {
+        self.inner.public_key()
+    }
+
+    fn address(&self) -> CanvasResult<String> {
+        self.inner.address()
+    }
+
+    fn sign(&self, message: &[u8]) -> CanvasResult<[u8; 64]> {
+        if let Some(prompt) = self.inner.confirmation_prompt(&self.action, message) {
+            let _ = self.app.emit_all("signer-confirmation", prompt);
+        }
+        self.inner.sign(message)
+    }
+
+    fn confirmation_prompt(&self, action: &str, message: &[u8]) -> Option<String> {
+        self.inner.confirmation_prompt(action, message)
+    }
+
+    fn impersonated(&self) -> bool {
+        self.inner.impersonated()
+    }
+}
+
+/// Send a signed call from the console to the BaaLS node. Runs off the UI
+/// thread, since a hardware wallet waits for the user to confirm.
+#[tauri::command]
+async fn send_transaction(app: tauri::AppHandle, request: SendRequest) -> Result<SendResponse, String> {
+    let config = app.state::<AppState>().config.clone();
+    let result = tauri::async_runtime::spawn_blocking(move || {
+        let signer = ConsoleSigner {
+            inner: signer_from_spec(&request.key, std::path::Path::new("."), &config)?,
+            app,
+            action: format!("call to {}", request.function),
+        };
+        BaalsClient::new(&config)?.call_contract(&request.address, &request.function, request.args, &signer)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+    .map_err(|e| e.to_string())?;
+
+    Ok(SendResponse {
+        transaction_hash: result.transaction_hash,
+        gas_used: result.gas_used,
+        success: result.success,
+        output: result.output,
+        revert_reason: result.revert_reason,
+    })
+}
+
+fn impersonation_session(config: &Config) -> CanvasResult<ImpersonationSession> {
+    ImpersonationSession::open(&ImpersonationSession::default_path(config))
+}
+
+/// Let the console act as `address` without its key; send as it with the key
+/// `impersonate:<address>`. Local networks only.
+#[tauri::command]
+async fn impersonate_account(state: State<'_, AppState>, address: String) -> Result<Vec<String>, String> {
+    let mut session = impersonation_session(&state.config).map_err(|e| e.to_string())?;
+    session.start(&state.config.baals.network, &address).map_err(|e| e.to_string())?;
+    session.save().map_err(|e| e.to_string())?;
+    Ok(session.accounts().map(str::to_string).collect())
+}
+
+/// Stop acting as `address`, or as every account when `None`
+#[tauri::command]
+async fn stop_impersonating(state: State<'_, AppState>, address: Option<String>) -> Result<Vec<String>, String> {
+    let mut session = impersonation_session(&state.config).map_err(|e| e.to_string())?;
+    match address {
+        Some(address) => {
+            session.stop(&address);
+        }
+        None => session.stop_all(),
+    }
+    session.save().map_err(|e| e.to_string())?;
+    Ok(session.accounts().map(str::to_string).collect())
+}
+
+/// Start recording console calls against `contract`, replacing any recording
+/// in progress
+#[tauri::command]
+async fn start_recording(
+    state: State<'_, AppState>,
+    name: String,
+    contract: PathBuf,
+    gas_margin: Option<u32>,
+) -> Result<(), String> {
+    let mut recorder = ScenarioRecorder::new(name, contract);
+    if let Some(margin) = gas_margin {
+        recorder = recorder.with_gas_margin(margin);
+    }
+    *state.recorder.lock().unwrap() = Some(recorder);
+    Ok(())
+}
+
+/// Stop recording; the scenario is written to `path` if one is given,
+/// otherwise discarded. Returns the number of recorded steps.
+#[tauri::command]
+async fn stop_recording(state: State<'_, AppState>, path: Option<PathBuf>) -> Result<usize, String> {
+    let recorder = state.recorder.lock().unwrap().take().ok_or("Not recording")?;
+    if let Some(path) = path {
+        recorder.save(&path).map_err(|e| e.to_string())?;
+    }
+    Ok(recorder.len())
+}
+
+#[derive(Debug, Serialize)]
+struct WizardSummary {
+    id: String,
+    name: String,
+    description: String,
+}
+
+#[derive(Debug, Serialize)]
+struct WizardResponse {
+    session_id: u64,
+    /// Step to show next; `None` once the wizard can be finished
+    step: Option<StepView>,
+}
+
+/// Built-in wizards plus those of the workspace, if one is open
+fn available_wizards(workspace: Option<&PathBuf>) -> Result<Vec<WizardDefinition>, String> {
+    let mut wizards = builtin_wizards();
+    if let Some(workspace) = workspace {
+        wizards.extend(WizardDefinition::load_dir(&workspace.join(WIZARDS_DIR)).map_err(|e| e.to_string())?);
+    }
+    Ok(wizards)
+}
+
+#[tauri::command]
+async fn list_wizards(workspace: Option<PathBuf>) -> Result<Vec<WizardSummary>, String> {
+    Ok(available_wizards(workspace.as_ref())?
+        .into_iter()
+        .map(|w| WizardSummary {
+            id: w.id,
+            name: w.name,
+            description: w.description,
+        })
+        .collect())
+}
+
+#[tauri::command]
+async fn start_wizard(
+    state: State<'_, AppState>,
+    wizard_id: String,
+    workspace: Option<PathBuf>,
+) -> Result<WizardResponse, String> {
+    let definition = available_wizards(workspace.as_ref())?
+        .into_iter()
+        .find(|w| w.id == wizard_id)
+        .ok_or_else(|| format!("Unknown wizard '{}'", wizard_id))?;
+    let session = WizardSession::new(definition).map_err(|e| e.to_string())?;
+    let session_id = state.next_wizard_id.fetch_add(1, Ordering::Relaxed);
+    let step = session.current_step();
+    state.wizards.lock().unwrap().insert(session_id, session);
+    Ok(WizardResponse { session_id, step })
+}
+
+/// Answer the current step; an invalid answer leaves the session unchanged
+#[tauri::command]
+async fn answer_wizard(
+    state: State<'_, AppState>,
+    session_id: u64,
+    answers: BTreeMap<String, serde_json::Value>,
+) -> Result<WizardResponse, String> {
+    let mut wizards = state.wizards.lock().unwrap();
+    let session = wizards.get_mut(&session_id).ok_or("Unknown wizard session")?;
+    let step = session.answer(answers).map_err(|e| e.to_string())?;
+    Ok(WizardResponse { session_id, step })
+}
+
+#[tauri::command]
+async fn wizard_back(state: State<'_, AppState>, session_id: u64) -> Result<WizardResponse, String> {
+    let mut wizards = state.wizards.lock().unwrap();
+    let session = wizards.get_mut(&session_id).ok_or("Unknown wizard session")?;
+    let step = session.back();
+    Ok(WizardResponse { session_id, step })
+}
+
+/// Generate the graph and open it as a new document; the session ends
+#[tauri::command]
+async fn finish_wizard(
+    state: State<'_, AppState>,
+    session_id: u64,
+    name: String,
+) -> Result<OpenDocumentResponse, String> {
+    let graph = {
+        let wizards = state.wizards.lock().unwrap();
+        let session = wizards.get(&session_id).ok_or("Unknown wizard session")?;
+        session.finish(name).map_err(|e| e.to_string())?
+    };
+    state.wizards.lock().unwrap().remove(&session_id);
+    let mut documents = state.documents.lock().unwrap();
+    let id = documents.create(&graph.name);
+    documents.update(id, graph.clone()).map_err(|e| e.to_string())?;
+    Ok(OpenDocumentResponse { id, graph })
+}
+
+#[tauri::command]
+async fn cancel_wizard(state: State<'_, AppState>, session_id: u64) -> Result<bool, String> {
+    Ok(state.wizards.lock().unwrap().remove(&session_id).is_some())
+}
+
+/// How every node type is drawn, reloading installed custom nodes
+#[tauri::command]
+async fn node_appearances(state: State<'_, AppState>) -> Result<Vec<NodeAppearance>, String> {
+    let installed = installed_nodes(&state.config).map_err(|e| e.to_string())?;
+    let mut assets = state.assets.lock().unwrap();
+    assets.refresh(&installed);
+    Ok(assets.appearances().to_vec())
+}
+
+/// Add an icon image to the asset store, for use in a custom node definition
+#[tauri::command]
+async fn import_node_icon(state: State<'_, AppState>, path: PathBuf) -> Result<AssetRef, String> {
+    let store = state.assets.lock().unwrap().store().clone();
+    store.import_file(&path).map_err(|e| e.to_string())
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct OpenDocumentResponse {
+    id: DocumentId,
+    graph: VisualGraph,
+}
+
+/// Most recent commands checked against the permission policy, oldest first
+#[tauri::command]
+async fn command_audit(state: State<'_, AppState>, limit: Option<usize>) -> Result<Vec<AuditEntry>, String> {
+    state
+        .permissions
+        .audit_log()
+        .recent(limit.unwrap_or(AUDIT_PAGE_SIZE))
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn open_document(
+    state: State<'_, AppState>,
+    path: Option<PathBuf>,
+    name: Option<String>,
+) -> Result<OpenDocumentResponse, String> {
+    let mut documents = state.documents.lock().unwrap();
+    let id = match path {
+        Some(path) => documents.open(&path).map_err(|e| e.to_string())?,
+        None => documents.create(name.as_deref().unwrap_or("Untitled")),
+    };
+    let graph = documents.get(id).map(|doc| doc.graph.clone()).ok_or("Document vanished")?;
+    Ok(OpenDocumentResponse { id, graph })
+}
+
+#[tauri::command]
+async fn update_document(
+    state: State<'_, AppState>,
+    id: DocumentId,
+    graph: VisualGraph,
+) -> Result<u64, String> {
+    state.documents.lock().unwrap().update(id, graph).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn save_document(
+    state: State<'_, AppState>,
+    id: DocumentId,
+    path: Option<PathBuf>,
+) -> Result<PathBuf, String> {
+    state.documents.lock().unwrap().save(id, path).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn list_documents(state: State<'_, AppState>) -> Result<Vec<DocumentInfo>, String> {
+    Ok(state.documents.lock().unwrap().list())
+}
+
+#[tauri::command]
+async fn close_document(
+    state: State<'_, AppState>,
+    id: DocumentId,
+    force: bool,
+) -> Result<(), String> {
+    state.documents.lock().unwrap().close(id, force).map_err(|e| e.to_string())?;
+    state.diagnostics.lock().unwrap().remove(&id);
+    Ok(())
+}
+
+/// Validate edited documents and autosave dirty ones until the app exits
+fn spawn_document_worker(app: tauri::AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let mut since_autosave = Duration::ZERO;
+        loop {
+            tokio::time::sleep(VALIDATION_INTERVAL).await;
+            let state = app.state::<AppState>();
+
+            let pending = state.documents.lock().unwrap().pending_validation();
+            for (id, revision, graph) in pending {
+                let validation = {
+                    let compiler = state.compiler.lock().unwrap();
+                    let Some(compiler) = compiler.as_ref() else { break };
+                    let mut caches = state.diagnostics.lock().unwrap();
+                    let cache = caches.entry(id).or_default();
+                    match compiler.validator().and_then(|v| cache.validate(&v, &graph)) {
+                        Ok(result) => DocumentValidation {
+                            revision,
+                            is_valid: result.is_valid,
+                            errors: result.errors,
+                            warnings: result.warnings,
+                        },
+                        Err(e) => DocumentValidation {
+                            revision,
+                            is_valid: false,
+                            errors: vec![e.to_string()],
+                            warnings: Vec::new(),
+                        },
+                    }
+                };
+                if state.documents.lock().unwrap().record_validation(id, validation.clone()) {
+                    let _ = app.emit_all("document-validated", (id, validation));
+                }
+            }
+
+            since_autosave += VALIDATION_INTERVAL;
+            if since_autosave >= AUTOSAVE_INTERVAL {
+                since_autosave = Duration::ZERO;
+                if let Err(e) = state.documents.lock().unwrap().autosave() {
+                    eprintln!("Autosave failed: {}", e);
+                }
+            }
+        }
+    });
+}
+
 fn main() {
+    let defaults = app_config();
+    let autosave_dir = defaults.app.data_dir.join("autosave");
+    let nodes = NodeRegistry::load(&defaults).unwrap_or_else(|e| {
+        eprintln!("Failed to load the installed node catalog: {}", e);
+        NodeRegistry::with_builtins()
+    });
+    let store = AssetStore::new(AssetStore::default_path(&defaults));
+    // A broken custom node directory is reported when the frontend first asks for `node_appearances`
+    let installed = installed_nodes(&defaults).unwrap_or_else(|_| CustomNodeRegistry::new());
+    let assets = AssetServer::new(store, nodes, &installed);
+
     tauri::Builder::default()
         .manage(AppState {
+            config: defaults.clone(),
             compiler: Mutex::new(None),
             runtime: Mutex::new(None),
             baals_client: Mutex::new(None),
             ai_assistant: Mutex::new(None),
+            documents: Mutex::new(DocumentManager::new(autosave_dir)),
+            diagnostics: Mutex::new(HashMap::new()),
+            simulations: Mutex::new(HashMap::new()),
+            recorder: Mutex::new(None),
+            wizards: Mutex::new(HashMap::new()),
+            next_wizard_id: AtomicU64::new(1),
+            assets: Mutex::new(assets),
+            permissions: PermissionGate::new(&defaults),
+        })
+        .register_uri_scheme_protocol(ASSET_PROTOCOL, |app, request| {
+            let path = request.uri().splitn(4, '/').nth(3).map_or_else(String::new, |p| format!("/{}", p));
+            let if_none_match = request.headers().get("If-None-Match").and_then(|v| v.to_str().ok());
+            let response = app
+                .state::<AppState>()
+                .assets
+                .lock()
+                .unwrap()
+                .handle(request.method().as_str(), &path, if_none_match);
+            let mut builder = tauri::http::ResponseBuilder::new().status(response.status);
+            for (name, value) in &response.headers {
+                builder = builder.header(*name, value.as_str());
+            }
+            builder.body(response.body)
         })
         .setup(|app| {
             // Initialize canvas-contracts components
-            let config = canvas_contracts::config::Config::default();
+            let config = app.state::<AppState>().config.clone();
             
             if let Ok(compiler) = Compiler::new(&config) {
                 *app.state::<AppState>().compiler.lock().unwrap() = Some(compiler);
@@ -109,14 +775,49 @@ fn main() {
             if let Ok(ai) = AiAssistant::new(&config) {
                 *app.state::<AppState>().ai_assistant.lock().unwrap() = Some(ai);
             }
+
+            // Documents autosaved by a session that crashed
+            match app.state::<AppState>().documents.lock().unwrap().recover() {
+                Ok(recovered) if !recovered.is_empty() => {
+                    let _ = app.emit_all("documents-recovered", recovered);
+                }
+                Ok(_) => {}
+                Err(e) => eprintln!("Document recovery failed: {}", e),
+            }
+            spawn_document_worker(app.handle());
             
             Ok(())
         })
-        .invoke_handler(tauri::generate_handler![
-            compile_contract,
-            validate_graph,
-            analyze_patterns,
-        ])
+        .invoke_handler({
+            let handler: InvokeHandler = Arc::new(tauri::generate_handler![
+                compile_contract,
+                validate_graph,
+                analyze_patterns,
+                open_document,
+                update_document,
+                save_document,
+                list_documents,
+                close_document,
+                simulate_contract,
+                cancel_simulation,
+                call_contract,
+                send_transaction,
+                impersonate_account,
+                stop_impersonating,
+                start_recording,
+                stop_recording,
+                list_wizards,
+                start_wizard,
+                answer_wizard,
+                wizard_back,
+                finish_wizard,
+                cancel_wizard,
+                node_appearances,
+                import_node_icon,
+                command_audit,
+            ]);
+            move |invoke| gated_invoke(&handler, invoke)
+        })
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
 } 
\ No newline at end of file
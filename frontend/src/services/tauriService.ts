@@ -1,5 +1,8 @@
 import { invoke } from '@tauri-apps/api/tauri'
-import { VisualGraph, CompilationResult, ValidationResult } from '../types'
+import { VisualGraph, CompilationResult, ValidationResult, AssetRef, NodeAppearance, AuditEntry } from '../types'
+
+/** Base URL the webview loads node icons from */
+export const ASSET_BASE_URL = 'canvas-asset://localhost'
 
 export class TauriService {
     static async compileContract(graph: VisualGraph, optimizationLevel: number = 1): Promise<CompilationResult> {
@@ -33,4 +36,25 @@ export class TauriService {
             throw new Error(`Pattern analysis failed: ${error}`)
         }
     }
+
+    static async nodeAppearances(): Promise<NodeAppearance[]> {
+        return (await invoke('node_appearances')) as NodeAppearance[]
+    }
+
+    static async importNodeIcon(path: string): Promise<AssetRef> {
+        try {
+            return (await invoke('import_node_icon', { path })) as AssetRef
+        } catch (error) {
+            throw new Error(`Icon import failed: ${error}`)
+        }
+    }
+
+    /** Most recent commands checked against the permission policy, oldest first */
+    static async commandAudit(limit?: number): Promise<AuditEntry[]> {
+        return (await invoke('command_audit', { limit })) as AuditEntry[]
+    }
+
+    static assetUrl(asset: AssetRef): string {
+        return `${ASSET_BASE_URL}/assets/${asset.hash}`
+    }
 } 
\ No newline at end of file
@@ -68,4 +68,41 @@ export interface ValidationWarning {
     node_id: string
     message: string
     suggestion?: string
-} 
\ No newline at end of file
+} 
+export interface AssetRef {
+    hash: string
+    media_type: string
+}
+
+export interface ThemeHints {
+    color?: string
+    accent?: string
+    dark_color?: string
+}
+
+export interface NodeAppearance {
+    node_type: string
+    icon_name?: string
+    icon?: AssetRef
+    theme: ThemeHints
+}
+
+export type PermissionScope =
+    | 'build'
+    | 'documents'
+    | 'simulate'
+    | 'record'
+    | 'wizards'
+    | 'assets'
+    | 'impersonate'
+    | 'sign'
+    | 'audit'
+
+export interface AuditEntry {
+    command: string
+    scope: PermissionScope
+    network: string
+    decision: 'allow' | 'confirm' | 'deny'
+    outcome: 'allowed' | 'confirmed' | 'declined' | 'denied'
+    at: string
+}
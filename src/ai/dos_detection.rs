@@ -0,0 +1,267 @@
+//! Gas griefing and denial-of-service detection
+//!
+//! Flags loops whose cost an attacker can drive up: iterations over storage
+//! collections anyone can grow, iterations over caller-supplied arrays without
+//! a tight bound, and external calls made from inside a loop body.
+
+use std::collections::HashSet;
+
+use super::{rule_packs::reachable, SecurityIssue, Severity};
+use crate::types::{NodeId, VisualGraph, VisualNode};
+
+/// Iteration bound above which a loop over caller-supplied data is reported
+pub const MAX_SAFE_ITERATIONS: u64 = 100;
+
+const GUARD_NODES: &[&str] = &["Require", "If"];
+const STORAGE_READS: &[&str] = &["ReadStorage", "BatchReadStorage"];
+const STORAGE_WRITES: &[&str] = &["WriteStorage", "BatchWriteStorage"];
+
+fn node<'a>(graph: &'a VisualGraph, id: &NodeId) -> Option<&'a VisualNode> {
+    graph.nodes.iter().find(|n| n.id == *id)
+}
+
+/// Whether a port carries control flow rather than a value
+fn is_flow_port(port: &str) -> bool {
+    port.starts_with("flow_") || port.ends_with("_flow")
+}
+
+/// Nodes upstream of `start` through flow connections only, or data connections only
+fn upstream(graph: &VisualGraph, start: NodeId, flow: bool) -> HashSet<NodeId> {
+    let mut seen = HashSet::new();
+    let mut queue = vec![start];
+    while let Some(id) = queue.pop() {
+        for connection in graph.connections.iter().filter(|c| c.target_node == id) {
+            if is_flow_port(&connection.target_port) == flow && seen.insert(connection.source_node) {
+                queue.push(connection.source_node);
+            }
+        }
+    }
+    seen
+}
+
+/// Nodes whose values feed a port, directly or through intermediate nodes
+fn sources_of_port(graph: &VisualGraph, target: NodeId, port: &str) -> HashSet<NodeId> {
+    let mut sources = HashSet::new();
+    for connection in graph.connections.iter().filter(|c| c.target_node == target && c.target_port == port) {
+        sources.insert(connection.source_node);
+        sources.extend(upstream(graph, connection.source_node, false));
+    }
+    sources
+}
+
+/// Nodes run by a loop body, i.e. reachable from its `body_flow` output
+fn loop_body(graph: &VisualGraph, loop_node: NodeId) -> HashSet<NodeId> {
+    let mut body = HashSet::new();
+    for connection in graph.connections.iter().filter(|c| c.source_node == loop_node && c.source_port == "body_flow") {
+        body.insert(connection.target_node);
+        body.extend(reachable(graph, connection.target_node, false));
+    }
+    body.remove(&loop_node);
+    body
+}
+
+fn storage_keys(node: &VisualNode) -> Vec<&str> {
+    match node.properties.get("key").and_then(|v| v.as_str()) {
+        Some(key) => vec![key],
+        None => node
+            .properties
+            .get("keys")
+            .and_then(|v| v.as_array())
+            .map(|keys| keys.iter().filter_map(|k| k.as_str()).collect())
+            .unwrap_or_default(),
+    }
+}
+
+/// Whether no Require/If runs before this node in its flow
+fn unguarded(graph: &VisualGraph, id: NodeId) -> bool {
+    !upstream(graph, id, true)
+        .iter()
+        .filter_map(|upstream| node(graph, upstream))
+        .any(|n| GUARD_NODES.contains(&n.node_type.as_str()))
+}
+
+fn iteration_bound(node: &VisualNode) -> Option<u64> {
+    node.properties.get("max_iterations").and_then(|v| v.as_u64())
+}
+
+fn issue(name: &str, description: String, severity: Severity, nodes: Vec<NodeId>, mitigation: &str) -> SecurityIssue {
+    SecurityIssue {
+        name: name.to_string(),
+        description,
+        severity,
+        nodes,
+        cve_reference: None,
+        mitigation: mitigation.to_string(),
+        rule_pack: None,
+    }
+}
+
+/// Detect loops whose cost an attacker can inflate
+pub fn detect_dos_patterns(graph: &VisualGraph) -> Vec<SecurityIssue> {
+    let mut issues = Vec::new();
+
+    for loop_node in graph.nodes.iter().filter(|n| n.node_type == "ForEach") {
+        let sources = sources_of_port(graph, loop_node.id, "array");
+        let source_nodes: Vec<&VisualNode> = sources.iter().filter_map(|id| node(graph, id)).collect();
+
+        // Iterating a storage collection: whoever can append to it controls the loop's cost
+        let storage_reads: Vec<&VisualNode> = source_nodes
+            .iter()
+            .copied()
+            .filter(|n| STORAGE_READS.contains(&n.node_type.as_str()))
+            .collect();
+        if !storage_reads.is_empty() {
+            let keys: HashSet<&str> = storage_reads.iter().flat_map(|n| storage_keys(n)).collect();
+            let open_writers: Vec<NodeId> = graph
+                .nodes
+                .iter()
+                .filter(|n| STORAGE_WRITES.contains(&n.node_type.as_str()))
+                .filter(|n| storage_keys(n).iter().any(|k| keys.contains(k)))
+                .filter(|n| unguarded(graph, n.id))
+                .map(|n| n.id)
+                .collect();
+
+            let mut nodes = vec![loop_node.id];
+            nodes.extend(storage_reads.iter().map(|n| n.id));
+            if open_writers.is_empty() {
+                issues.push(issue(
+                    "Unbounded Storage Iteration",
+                    format!(
+                        "ForEach node {} iterates a storage collection that grows over time; once it is large enough every call exceeds the gas limit",
+                        loop_node.id
+                    ),
+                    Severity::Medium,
+                    nodes,
+                    "Paginate: take a start index and process at most a small page per call, or keep a running aggregate in storage instead of recomputing it",
+                ));
+            } else {
+                nodes.extend(open_writers);
+                issues.push(issue(
+                    "Attacker-Controlled Loop",
+                    format!(
+                        "ForEach node {} iterates a storage collection that anyone can append to without a check, so an attacker can make the loop too expensive to run",
+                        loop_node.id
+                    ),
+                    Severity::High,
+                    nodes,
+                    "Guard appends with a Require (caller allow-list, minimum deposit or per-caller cap) and paginate the iteration",
+                ));
+            }
+        }
+
+        // Iterating caller-supplied data: only safe with a tight bound
+        let from_caller = source_nodes.iter().any(|n| n.node_type == "Start");
+        let bound = iteration_bound(loop_node);
+        if from_caller && bound.map_or(true, |max| max > MAX_SAFE_ITERATIONS) {
+            issues.push(issue(
+                "Unbounded Caller-Supplied Loop",
+                format!(
+                    "ForEach node {} iterates an array passed by the caller with {}",
+                    loop_node.id,
+                    match bound {
+                        Some(max) => format!("a bound of {} iterations", max),
+                        None => "no iteration bound".to_string(),
+                    }
+                ),
+                Severity::Medium,
+                vec![loop_node.id],
+                &format!("Set max_iterations to {} or less and let callers split larger batches", MAX_SAFE_ITERATIONS),
+            ));
+        }
+
+        // External calls in the body: one failing or expensive callee stalls the whole loop
+        let calls: Vec<NodeId> = loop_body(graph, loop_node.id)
+            .into_iter()
+            .filter(|id| node(graph, id).map_or(false, |n| n.node_type == "TryCall"))
+            .collect();
+        if !calls.is_empty() {
+            let mut nodes = vec![loop_node.id];
+            nodes.extend(calls);
+            issues.push(issue(
+                "External Call In Loop",
+                format!(
+                    "ForEach node {} makes external calls on every iteration; a single reverting or gas-hungry callee blocks the whole batch",
+                    loop_node.id
+                ),
+                Severity::High,
+                nodes,
+                "Use a pull pattern: record what each party is owed in storage and let them withdraw individually",
+            ));
+        }
+    }
+
+    issues
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_graphs::{connect, node};
+
+    #[test]
+    fn test_open_storage_collection_and_calls_in_loop() {
+        let mut graph = VisualGraph::new("airdrop");
+        // register: anyone appends to "recipients"
+        let register = node("Start").with_property("function", serde_json::json!("register"));
+        let append = node("WriteStorage").with_property("key", serde_json::json!("recipients"));
+        connect(&mut graph, &register, "flow_out", &append, "flow_in");
+        // distribute: pays every recipient
+        let distribute = node("Start").with_property("function", serde_json::json!("distribute"));
+        let read = node("ReadStorage").with_property("key", serde_json::json!("recipients"));
+        let each = node("ForEach").with_property("max_iterations", serde_json::json!(10_000));
+        let pay = node("TryCall");
+        connect(&mut graph, &distribute, "flow_out", &read, "flow_in");
+        connect(&mut graph, &read, "value", &each, "array");
+        connect(&mut graph, &each, "body_flow", &pay, "flow_in");
+        let (each_id, pay_id) = (each.id, pay.id);
+        for n in [register, append, distribute, read, each, pay] {
+            graph.add_node(n);
+        }
+
+        let issues = detect_dos_patterns(&graph);
+        let names: Vec<&str> = issues.iter().map(|i| i.name.as_str()).collect();
+        assert!(names.contains(&"Attacker-Controlled Loop"));
+        let call_issue = issues.iter().find(|i| i.name == "External Call In Loop").unwrap();
+        assert_eq!(call_issue.nodes, vec![each_id, pay_id]);
+        assert_eq!(call_issue.severity, Severity::High);
+    }
+
+    #[test]
+    fn test_storage_array_is_not_caller_supplied() {
+        let mut graph = VisualGraph::new("payout");
+        // payout: a guarded Start only runs the read; the array comes from storage
+        let payout = node("Start");
+        let guard = node("Require");
+        let read = node("ReadStorage").with_property("key", serde_json::json!("payees"));
+        let each = node("ForEach").with_property("max_iterations", serde_json::json!(10_000));
+        connect(&mut graph, &payout, "flow_out", &guard, "flow_in");
+        connect(&mut graph, &guard, "flow_out", &read, "flow_in");
+        connect(&mut graph, &read, "value", &each, "array");
+        // join: appends without a check, using a value read behind the guard
+        let join = node("Start");
+        let append = node("WriteStorage").with_property("key", serde_json::json!("payees"));
+        connect(&mut graph, &join, "flow_out", &append, "flow_in");
+        connect(&mut graph, &read, "value", &append, "value");
+        for n in [payout, guard, read, each, join, append] {
+            graph.add_node(n);
+        }
+
+        let names: Vec<String> = detect_dos_patterns(&graph).into_iter().map(|i| i.name).collect();
+        assert!(!names.contains(&"Unbounded Caller-Supplied Loop".to_string()));
+        assert!(names.contains(&"Attacker-Controlled Loop".to_string()));
+    }
+
+    #[test]
+    fn test_bounded_caller_loop_is_fine() {
+        let mut graph = VisualGraph::new("batch");
+        let start = node("Start");
+        let each = node("ForEach").with_property("max_iterations", serde_json::json!(20));
+        connect(&mut graph, &start, "items", &each, "array");
+        graph.add_node(start);
+        graph.add_node(each);
+        assert!(detect_dos_patterns(&graph).is_empty());
+
+        graph.nodes[1].properties.remove("max_iterations");
+        assert_eq!(detect_dos_patterns(&graph)[0].name, "Unbounded Caller-Supplied Loop");
+    }
+}
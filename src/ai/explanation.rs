@@ -0,0 +1,315 @@
+//! Natural-language contract summaries
+//!
+//! Summaries are derived from the graph alone: entry points come from Start
+//! and Init nodes, state variables from the storage keys nodes read and write,
+//! and external interactions and trust assumptions from the nodes each entry
+//! point can reach.
+
+use std::collections::{BTreeMap, HashSet, VecDeque};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    compiler::{collect_entry_points, constructor_params, find_init_node, INIT_FUNCTION},
+    error::CanvasResult,
+    types::{NodeId, StateMutability, VisualGraph, VisualNode},
+};
+
+/// Structured summary of what a contract does
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GraphExplanation {
+    pub contract: String,
+    /// One-paragraph overview
+    pub overview: String,
+    pub entry_points: Vec<EntryPointSummary>,
+    pub state_variables: Vec<StateVariableSummary>,
+    pub external_interactions: Vec<ExternalInteraction>,
+    pub trust_assumptions: Vec<String>,
+}
+
+/// An exported function and what it does
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EntryPointSummary {
+    pub name: String,
+    pub parameters: Vec<String>,
+    pub mutability: String,
+    pub description: String,
+}
+
+/// A storage key and the functions touching it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StateVariableSummary {
+    pub key: String,
+    pub read_by: Vec<String>,
+    pub written_by: Vec<String>,
+}
+
+/// A point where the contract depends on something outside itself
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExternalInteraction {
+    pub node_id: NodeId,
+    pub function: String,
+    pub description: String,
+}
+
+impl GraphExplanation {
+    /// Render the summary as Markdown, for docs and marketplace listings
+    pub fn to_markdown(&self) -> String {
+        let mut out = format!("# {}\n\n{}\n", self.contract, self.overview);
+
+        out.push_str("\n## Entry points\n\n");
+        for entry in &self.entry_points {
+            out.push_str(&format!(
+                "- `{}({})` ({}): {}\n",
+                entry.name,
+                entry.parameters.join(", "),
+                entry.mutability,
+                entry.description
+            ));
+        }
+
+        if !self.state_variables.is_empty() {
+            out.push_str("\n## State\n\n");
+            for var in &self.state_variables {
+                out.push_str(&format!(
+                    "- `{}`: read by {}; written by {}\n",
+                    var.key,
+                    list_or_none(&var.read_by),
+                    list_or_none(&var.written_by)
+                ));
+            }
+        }
+
+        if !self.external_interactions.is_empty() {
+            out.push_str("\n## External interactions\n\n");
+            for interaction in &self.external_interactions {
+                out.push_str(&format!("- `{}`: {}\n", interaction.function, interaction.description));
+            }
+        }
+
+        if !self.trust_assumptions.is_empty() {
+            out.push_str("\n## Trust assumptions\n\n");
+            for assumption in &self.trust_assumptions {
+                out.push_str(&format!("- {}\n", assumption));
+            }
+        }
+
+        out
+    }
+}
+
+fn list_or_none(items: &[String]) -> String {
+    if items.is_empty() {
+        "nothing".to_string()
+    } else {
+        items.iter().map(|i| format!("`{}`", i)).collect::<Vec<_>>().join(", ")
+    }
+}
+
+fn plural(count: usize, word: &str) -> String {
+    format!("{} {}{}", count, word, if count == 1 { "" } else { "s" })
+}
+
+/// Nodes reachable from `start` by following connections
+fn reachable<'a>(graph: &'a VisualGraph, start: NodeId) -> Vec<&'a VisualNode> {
+    let mut seen = HashSet::from([start]);
+    let mut queue = VecDeque::from([start]);
+    while let Some(id) = queue.pop_front() {
+        for connection in graph.connections.iter().filter(|c| c.source_node == id) {
+            if seen.insert(connection.target_node) {
+                queue.push_back(connection.target_node);
+            }
+        }
+    }
+    graph.nodes.iter().filter(|n| seen.contains(&n.id) && n.id != start).collect()
+}
+
+/// Storage keys a node reads or writes, from its `key` or `keys` property
+fn storage_keys(node: &VisualNode) -> Vec<String> {
+    if let Some(key) = node.properties.get("key").and_then(|v| v.as_str()) {
+        return vec![key.to_string()];
+    }
+    node.properties
+        .get("keys")
+        .and_then(|v| v.as_array())
+        .map(|keys| keys.iter().filter_map(|k| k.as_str().map(str::to_string)).collect())
+        .unwrap_or_default()
+}
+
+fn mutability_name(mutability: &StateMutability) -> &'static str {
+    match mutability {
+        StateMutability::Pure => "pure",
+        StateMutability::View => "view",
+        StateMutability::NonPayable => "nonpayable",
+        StateMutability::Payable => "payable",
+    }
+}
+
+/// Summarize a contract graph
+pub fn explain_graph(graph: &VisualGraph, pausable: bool) -> CanvasResult<GraphExplanation> {
+    let mut functions: Vec<(String, Vec<String>, String, NodeId)> = Vec::new();
+    if let Some(init) = find_init_node(graph)? {
+        let params = constructor_params(init).into_iter().map(|p| p.name).collect();
+        functions.push((INIT_FUNCTION.to_string(), params, "nonpayable".to_string(), init.id));
+    }
+    for entry in collect_entry_points(graph)? {
+        let params = entry.function.inputs.iter().map(|p| p.name.clone()).collect();
+        let mutability = mutability_name(&entry.function.state_mutability).to_string();
+        functions.push((entry.function.name, params, mutability, entry.node_id));
+    }
+
+    let mut state: BTreeMap<String, (Vec<String>, Vec<String>)> = BTreeMap::new();
+    let mut entry_points = Vec::new();
+    let mut external_interactions = Vec::new();
+    let mut trust_assumptions = Vec::new();
+
+    for (name, parameters, mutability, node_id) in functions {
+        let body = reachable(graph, node_id);
+        let mut reads = Vec::new();
+        let mut writes = Vec::new();
+        let mut actions = Vec::new();
+        let mut guarded = false;
+
+        for node in &body {
+            match node.node_type.as_str() {
+                "ReadStorage" | "BatchReadStorage" => reads.extend(storage_keys(node)),
+                "WriteStorage" | "BatchWriteStorage" => writes.extend(storage_keys(node)),
+                "Require" | "If" => guarded = true,
+                "TryCall" => {
+                    let function = node.properties.get("function").and_then(|v| v.as_str()).unwrap_or("a function");
+                    external_interactions.push(ExternalInteraction {
+                        node_id: node.id,
+                        function: name.clone(),
+                        description: format!("calls `{}` on another contract", function),
+                    });
+                    actions.push(format!("calls `{}` on another contract", function));
+                }
+                "VerifySignature" => {
+                    let scheme = node.properties.get("scheme").and_then(|v| v.as_str()).unwrap_or("a");
+                    actions.push(format!("verifies a {} signature", scheme));
+                    trust_assumptions.push(format!(
+                        "`{}` trusts whoever holds the signing key it verifies against",
+                        name
+                    ));
+                }
+                _ => {}
+            }
+        }
+        reads.sort();
+        reads.dedup();
+        writes.sort();
+        writes.dedup();
+
+        for key in &reads {
+            state.entry(key.clone()).or_default().0.push(name.clone());
+        }
+        for key in &writes {
+            state.entry(key.clone()).or_default().1.push(name.clone());
+        }
+
+        if !writes.is_empty() && !guarded && name != INIT_FUNCTION {
+            trust_assumptions.push(format!(
+                "Anyone can call `{}`, which modifies {} without checking any condition",
+                name,
+                list_or_none(&writes)
+            ));
+        }
+
+        let mut description = Vec::new();
+        if name == INIT_FUNCTION {
+            description.push("runs once at deployment".to_string());
+        }
+        if !reads.is_empty() {
+            description.push(format!("reads {}", list_or_none(&reads)));
+        }
+        if !writes.is_empty() {
+            description.push(format!("updates {}", list_or_none(&writes)));
+        }
+        description.extend(actions);
+        if guarded {
+            description.push("aborts unless its checks pass".to_string());
+        }
+        let description = if description.is_empty() {
+            "does not touch state".to_string()
+        } else {
+            let mut text = description.join(", ");
+            text[..1].make_ascii_uppercase();
+            text
+        };
+
+        entry_points.push(EntryPointSummary {
+            name,
+            parameters,
+            mutability,
+            description,
+        });
+    }
+
+    if !external_interactions.is_empty() {
+        trust_assumptions.push("Results of external calls depend on the called contracts behaving as expected".to_string());
+    }
+    if pausable {
+        trust_assumptions.push("The deployer can pause and unpause all state-changing functions".to_string());
+    }
+
+    let state_variables: Vec<StateVariableSummary> = state
+        .into_iter()
+        .map(|(key, (read_by, written_by))| StateVariableSummary { key, read_by, written_by })
+        .collect();
+
+    let overview = format!(
+        "{} exposes {}, keeps {} in storage and has {}.",
+        graph.name,
+        plural(entry_points.len(), "entry point"),
+        plural(state_variables.len(), "state variable"),
+        plural(external_interactions.len(), "external interaction")
+    );
+
+    Ok(GraphExplanation {
+        contract: graph.name.clone(),
+        overview,
+        entry_points,
+        state_variables,
+        external_interactions,
+        trust_assumptions,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_graphs::{connect, node};
+    use crate::types::{Port, ValueType};
+
+    #[test]
+    fn test_explains_entry_points_state_and_trust() {
+        let mut graph = VisualGraph::new("Counter");
+        let increment = node("Start")
+            .with_property("function", serde_json::json!("increment"))
+            .with_outputs(vec![Port::new("flow_out", "Flow Out", ValueType::Flow)]);
+        let read = node("ReadStorage").with_property("key", serde_json::json!("count"));
+        let write = node("WriteStorage").with_property("key", serde_json::json!("count"));
+        let get = node("Start")
+            .with_property("function", serde_json::json!("get"))
+            .with_property("mutability", serde_json::json!("view"));
+        let read_only = node("ReadStorage").with_property("key", serde_json::json!("count"));
+
+        connect(&mut graph, &increment, "flow_out", &read, "flow_in");
+        connect(&mut graph, &read, "flow_out", &write, "flow_in");
+        connect(&mut graph, &get, "flow_out", &read_only, "flow_in");
+        for n in [increment, read, write, get, read_only] {
+            graph.add_node(n);
+        }
+
+        let explanation = explain_graph(&graph, true).unwrap();
+        assert_eq!(explanation.entry_points.len(), 2);
+        assert_eq!(explanation.state_variables.len(), 1);
+        assert_eq!(explanation.state_variables[0].written_by, vec!["increment".to_string()]);
+        assert!(explanation.trust_assumptions.iter().any(|t| t.contains("Anyone can call `increment`")));
+        assert!(explanation.trust_assumptions.iter().any(|t| t.contains("pause")));
+
+        let markdown = explanation.to_markdown();
+        assert!(markdown.contains("## Entry points"));
+        assert!(markdown.contains("`get()` (view)"));
+    }
+}
@@ -0,0 +1,267 @@
+//! Optional LLM backends for the AI assistant
+//!
+//! The rule engine always produces a result; an LLM only refines it (ranking
+//! suggestions, proposing names, rewriting explanations). Every request has a
+//! hard timeout, and any failure falls back to the rule-based answer. Requests
+//! whose prompt contains graph contents are only sent when
+//! `ai.share_graph_data` is enabled.
+
+use std::time::Duration;
+
+use crate::{
+    config::AiConfig,
+    error::{CanvasError, CanvasResult},
+};
+
+/// A single completion request
+#[derive(Debug, Clone)]
+pub struct LlmRequest {
+    pub system: String,
+    pub prompt: String,
+    pub max_tokens: u32,
+    /// Whether the prompt includes graph contents
+    pub contains_graph_data: bool,
+}
+
+/// A text completion service
+pub trait LlmBackend: Send + Sync {
+    /// Backend name, for logs
+    fn name(&self) -> &str;
+
+    /// Complete a prompt, returning the generated text
+    fn complete(&self, request: &LlmRequest) -> CanvasResult<String>;
+}
+
+fn agent(timeout: Duration) -> ureq::Agent {
+    ureq::AgentBuilder::new().timeout(timeout).build()
+}
+
+fn http_error(backend: &str, e: ureq::Error) -> CanvasError {
+    CanvasError::Network(format!("{} request failed: {}", backend, e))
+}
+
+/// OpenAI-compatible chat completions endpoint
+pub struct OpenAiBackend {
+    base_url: String,
+    model: String,
+    api_key: Option<String>,
+    timeout: Duration,
+}
+
+impl OpenAiBackend {
+    pub fn new(base_url: impl Into<String>, model: impl Into<String>, timeout: Duration) -> Self {
+        Self {
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+            model: model.into(),
+            api_key: None,
+            timeout,
+        }
+    }
+
+    pub fn with_api_key(mut self, api_key: String) -> Self {
+        self.api_key = Some(api_key);
+        self
+    }
+}
+
+impl LlmBackend for OpenAiBackend {
+    fn name(&self) -> &str {
+        "openai"
+    }
+
+    fn complete(&self, request: &LlmRequest) -> CanvasResult<String> {
+        let mut call = agent(self.timeout).post(&format!("{}/v1/chat/completions", self.base_url));
+        if let Some(key) = &self.api_key {
+            call = call.set("Authorization", &format!("Bearer {}", key));
+        }
+        let response: serde_json::Value = call
+            .send_json(serde_json::json!({
+                "model": self.model,
+                "max_tokens": request.max_tokens,
+                "messages": [
+                    {"role": "system", "content": request.system},
+                    {"role": "user", "content": request.prompt},
+                ],
+            }))
+            .map_err(|e| http_error(self.name(), e))?
+            .into_json()?;
+
+        response["choices"][0]["message"]["content"]
+            .as_str()
+            .map(str::to_string)
+            .ok_or_else(|| CanvasError::Network("openai response has no message content".to_string()))
+    }
+}
+
+/// llama.cpp server `/completion` endpoint
+pub struct LlamaCppBackend {
+    base_url: String,
+    timeout: Duration,
+}
+
+impl LlamaCppBackend {
+    pub fn new(base_url: impl Into<String>, timeout: Duration) -> Self {
+        Self {
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+            timeout,
+        }
+    }
+}
+
+impl LlmBackend for LlamaCppBackend {
+    fn name(&self) -> &str {
+        "llamacpp"
+    }
+
+    fn complete(&self, request: &LlmRequest) -> CanvasResult<String> {
+        let response: serde_json::Value = agent(self.timeout)
+            .post(&format!("{}/completion", self.base_url))
+            .send_json(serde_json::json!({
+                "prompt": format!("{}\n\n{}", request.system, request.prompt),
+                "n_predict": request.max_tokens,
+            }))
+            .map_err(|e| http_error(self.name(), e))?
+            .into_json()?;
+
+        response["content"]
+            .as_str()
+            .map(str::to_string)
+            .ok_or_else(|| CanvasError::Network("llama.cpp response has no content".to_string()))
+    }
+}
+
+/// Backend configured in the AI settings, if any
+pub fn backend_from_config(config: &AiConfig) -> CanvasResult<Option<Box<dyn LlmBackend>>> {
+    let timeout = Duration::from_millis(config.llm_timeout_ms);
+    match config.llm_backend.as_deref() {
+        None => Ok(None),
+        Some("openai") => {
+            let mut backend = OpenAiBackend::new(&config.llm_url, &config.llm_model, timeout);
+            if let Ok(key) = std::env::var(&config.llm_api_key_env) {
+                backend = backend.with_api_key(key);
+            }
+            Ok(Some(Box::new(backend)))
+        }
+        Some("llamacpp") => Ok(Some(Box::new(LlamaCppBackend::new(&config.llm_url, timeout)))),
+        Some(other) => Err(CanvasError::Config(format!("Unknown LLM backend: {}", other))),
+    }
+}
+
+/// An LLM backend together with the data-sharing policy
+pub struct LlmAssist {
+    backend: Box<dyn LlmBackend>,
+    share_graph_data: bool,
+}
+
+impl LlmAssist {
+    pub fn new(backend: Box<dyn LlmBackend>, share_graph_data: bool) -> Self {
+        Self {
+            backend,
+            share_graph_data,
+        }
+    }
+
+    /// Run a request, or `None` when policy forbids it or the backend fails
+    pub fn try_complete(&self, request: &LlmRequest) -> Option<String> {
+        if request.contains_graph_data && !self.share_graph_data {
+            log::debug!("Not sending graph data to {}; using the rule engine", self.backend.name());
+            return None;
+        }
+        match self.backend.complete(request) {
+            Ok(text) if !text.trim().is_empty() => Some(text.trim().to_string()),
+            Ok(_) => None,
+            Err(e) => {
+                log::warn!("{} unavailable, falling back to the rule engine: {}", self.backend.name(), e);
+                None
+            }
+        }
+    }
+}
+
+/// Reorder `items` by the ranking an LLM gave as a list of 1-based indices.
+///
+/// Indices that are missing, repeated or out of range are ignored; unranked
+/// items keep their original order after the ranked ones.
+pub fn apply_ranking<T>(items: Vec<T>, ranking: &str) -> Vec<T> {
+    let mut order: Vec<usize> = Vec::new();
+    for index in ranking
+        .split(|c: char| !c.is_ascii_digit())
+        .filter_map(|part| part.parse::<usize>().ok())
+    {
+        if index >= 1 && index <= items.len() && !order.contains(&(index - 1)) {
+            order.push(index - 1);
+        }
+    }
+    for index in 0..items.len() {
+        if !order.contains(&index) {
+            order.push(index);
+        }
+    }
+
+    let mut slots: Vec<Option<T>> = items.into_iter().map(Some).collect();
+    order.into_iter().filter_map(|i| slots[i].take()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    /// Backend returning a canned answer and recording prompts
+    struct ScriptedBackend {
+        answer: CanvasResult<String>,
+        prompts: Mutex<Vec<String>>,
+    }
+
+    impl LlmBackend for ScriptedBackend {
+        fn name(&self) -> &str {
+            "scripted"
+        }
+
+        fn complete(&self, request: &LlmRequest) -> CanvasResult<String> {
+            self.prompts.lock().unwrap().push(request.prompt.clone());
+            match &self.answer {
+                Ok(text) => Ok(text.clone()),
+                Err(e) => Err(CanvasError::Network(e.to_string())),
+            }
+        }
+    }
+
+    fn request(contains_graph_data: bool) -> LlmRequest {
+        LlmRequest {
+            system: String::new(),
+            prompt: "name this".to_string(),
+            max_tokens: 16,
+            contains_graph_data,
+        }
+    }
+
+    #[test]
+    fn test_graph_data_needs_opt_in_and_failures_fall_back() {
+        let assist = LlmAssist::new(
+            Box::new(ScriptedBackend {
+                answer: Ok("balance".to_string()),
+                prompts: Mutex::new(Vec::new()),
+            }),
+            false,
+        );
+        assert_eq!(assist.try_complete(&request(true)), None);
+        assert_eq!(assist.try_complete(&request(false)), Some("balance".to_string()));
+
+        let offline = LlmAssist::new(
+            Box::new(ScriptedBackend {
+                answer: Err(CanvasError::Network("connection refused".to_string())),
+                prompts: Mutex::new(Vec::new()),
+            }),
+            true,
+        );
+        assert_eq!(offline.try_complete(&request(true)), None);
+    }
+
+    #[test]
+    fn test_apply_ranking() {
+        assert_eq!(apply_ranking(vec!['a', 'b', 'c'], "3, 1"), vec!['c', 'a', 'b']);
+        assert_eq!(apply_ranking(vec!['a', 'b'], "no idea"), vec!['a', 'b']);
+        assert_eq!(apply_ranking(vec!['a', 'b'], "2 2 9"), vec!['b', 'a']);
+    }
+}
@@ -3,10 +3,14 @@
 use crate::{
     config::Config,
     error::{CanvasError, CanvasResult},
-    types::{Graph, NodeId, NodeType},
+    types::{Graph, NodeId, NodeType, VisualGraph},
 };
 
+mod dos_detection;
+mod explanation;
+mod llm;
 mod pattern_recognition;
+mod rule_packs;
 mod optimization;
 mod validator;
 
@@ -14,12 +18,24 @@ use pattern_recognition::PatternRecognitionEngine;
 use optimization::OptimizationEngine;
 use validator::RuleBasedValidator;
 
+pub use llm::{apply_ranking, backend_from_config, LlamaCppBackend, LlmAssist, LlmBackend, LlmRequest, OpenAiBackend};
+pub use dos_detection::{detect_dos_patterns, MAX_SAFE_ITERATIONS};
+pub use rule_packs::{
+    sign_rule_pack, Detector, PackRule, RuleFinding, RuleKnowledgeBase, RulePack, RulePackUpdater,
+    SignedRulePack,
+};
+pub use explanation::{
+    explain_graph, EntryPointSummary, ExternalInteraction, GraphExplanation, StateVariableSummary,
+};
+
 /// AI Assistant for analyzing and optimizing contracts
 pub struct AiAssistant {
     config: Config,
     pattern_engine: PatternRecognitionEngine,
     validator: RuleBasedValidator,
     optimizer: OptimizationEngine,
+    llm: Option<LlmAssist>,
+    rule_packs: RuleKnowledgeBase,
 }
 
 /// Pattern recognition result
@@ -60,6 +76,8 @@ pub struct SecurityIssue {
     pub nodes: Vec<NodeId>,
     pub cve_reference: Option<String>,
     pub mitigation: String,
+    /// Rule pack, version and rule that reported the issue, when it came from a pack
+    pub rule_pack: Option<String>,
 }
 
 /// Pattern category
@@ -74,7 +92,8 @@ pub enum PatternCategory {
 }
 
 /// Severity level
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum Severity {
     Low,
     Medium,
@@ -136,11 +155,56 @@ impl AiAssistant {
         Ok(Self {
             config: config.clone(),
             pattern_engine: PatternRecognitionEngine::new(),
-            validator: RuleBasedValidator::new(),
+            validator: RuleBasedValidator::new().with_pausable(config.compiler.pausable),
             optimizer: OptimizationEngine::new(),
+            llm: backend_from_config(&config.ai)?
+                .map(|backend| LlmAssist::new(backend, config.ai.share_graph_data)),
+            rule_packs: RuleKnowledgeBase::load_dir(&Self::rule_pack_dir(config), &config.ai.rule_pack_keys),
         })
     }
 
+    /// Directory holding installed rule packs
+    pub fn rule_pack_dir(config: &Config) -> std::path::PathBuf {
+        config.app.data_dir.join("rule-packs")
+    }
+
+    /// Run the installed security rule packs against a graph
+    pub fn check_security_rules(&self, graph: &VisualGraph) -> Vec<RuleFinding> {
+        self.rule_packs.evaluate(graph)
+    }
+
+    /// Find loops an attacker can make too expensive to execute
+    pub fn detect_dos_patterns(&self, graph: &VisualGraph) -> Vec<SecurityIssue> {
+        log::info!("Checking for gas griefing patterns");
+
+        detect_dos_patterns(graph)
+    }
+
+    /// Installed rule packs
+    pub fn rule_packs(&self) -> &RuleKnowledgeBase {
+        &self.rule_packs
+    }
+
+    /// Fetch the configured rule pack and install it if newer.
+    /// Returns the installed version, or `None` if already up to date.
+    pub fn update_rule_packs(&mut self) -> CanvasResult<Option<semver::Version>> {
+        let url = self.config.ai.rule_pack_url.clone().ok_or_else(|| {
+            CanvasError::Config("ai.rule_pack_url is not set".to_string())
+        })?;
+        let updater = RulePackUpdater::new(
+            url,
+            &self.config.ai.rule_pack_keys,
+            std::time::Duration::from_millis(self.config.ai.llm_timeout_ms),
+        )?;
+        updater.update(&mut self.rule_packs, &Self::rule_pack_dir(&self.config))
+    }
+
+    /// Use an LLM backend to refine the rule engine's results
+    pub fn with_llm_backend(mut self, backend: Box<dyn LlmBackend>) -> Self {
+        self.llm = Some(LlmAssist::new(backend, self.config.ai.share_graph_data));
+        self
+    }
+
     /// Analyze contract patterns
     pub fn analyze_patterns(&self, graph: &Graph) -> CanvasResult<PatternAnalysis> {
         log::info!("Analyzing contract patterns");
@@ -172,14 +236,124 @@ impl AiAssistant {
         self.optimizer.optimize(graph)
     }
 
+    /// Summarize in plain language what a contract does, for docs and marketplace listings
+    pub fn explain_graph(&self, graph: &VisualGraph) -> CanvasResult<GraphExplanation> {
+        log::info!("Explaining contract {}", graph.name);
+
+        let mut explanation = explain_graph(graph, self.config.compiler.pausable)?;
+        if let Some(llm) = &self.llm {
+            let request = LlmRequest {
+                system: "Summarize this smart contract for a non-technical reader in one short paragraph. Do not invent behaviour that is not listed.".to_string(),
+                prompt: serde_json::to_string(&explanation)?,
+                max_tokens: 200,
+                contains_graph_data: true,
+            };
+            if let Some(overview) = llm.try_complete(&request) {
+                explanation.overview = overview;
+            }
+        }
+        Ok(explanation)
+    }
+
     /// Suggest next nodes based on context
     pub fn suggest_next_nodes(&self, graph: &Graph, current_node: NodeId) -> CanvasResult<Vec<NodeSuggestion>> {
         log::info!("Suggesting next nodes for node {}", current_node);
         
         let context = self.analyze_context(graph, current_node)?;
         let suggestions = self.generate_node_suggestions(&context)?;
-        
-        Ok(suggestions)
+
+        Ok(self.rank_suggestions(&context, suggestions))
+    }
+
+    /// Suggest names for a node, e.g. for the storage key or function it defines
+    pub fn suggest_names(&self, graph: &VisualGraph, node_id: NodeId) -> CanvasResult<Vec<String>> {
+        let node = graph
+            .nodes
+            .iter()
+            .find(|n| n.id == node_id)
+            .ok_or_else(|| CanvasError::NodeNotFound(node_id.to_string()))?;
+
+        let mut names = vec![Self::rule_based_name(node)];
+        if let Some(llm) = &self.llm {
+            let neighbours: Vec<&str> = graph
+                .connections
+                .iter()
+                .filter_map(|c| {
+                    let other = if c.source_node == node_id {
+                        c.target_node
+                    } else if c.target_node == node_id {
+                        c.source_node
+                    } else {
+                        return None;
+                    };
+                    graph.nodes.iter().find(|n| n.id == other).map(|n| n.node_type.as_str())
+                })
+                .collect();
+            let request = LlmRequest {
+                system: "You name parts of smart contracts. Answer with up to three snake_case names, one per line.".to_string(),
+                prompt: format!(
+                    "A {} node with properties {} connected to: {}",
+                    node.node_type,
+                    serde_json::Value::Object(node.properties.clone().into_iter().collect()),
+                    neighbours.join(", ")
+                ),
+                max_tokens: 48,
+                contains_graph_data: true,
+            };
+            if let Some(answer) = llm.try_complete(&request) {
+                for name in answer.lines().map(|l| l.trim().trim_start_matches(['-', '*', ' '])) {
+                    let valid = !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+                    if valid && !names.iter().any(|n| n == name) {
+                        names.insert(names.len() - 1, name.to_string());
+                    }
+                }
+            }
+        }
+        Ok(names)
+    }
+
+    /// Name derived from a node's type and key property
+    fn rule_based_name(node: &crate::types::VisualNode) -> String {
+        let mut words = String::new();
+        for (i, c) in node.node_type.chars().enumerate() {
+            if c.is_uppercase() && i > 0 {
+                words.push('_');
+            }
+            words.push(c.to_ascii_lowercase());
+        }
+        match ["key", "function", "error"]
+            .iter()
+            .find_map(|p| node.properties.get(*p).and_then(|v| v.as_str()))
+        {
+            Some(subject) => format!("{}_{}", words, subject.to_lowercase().replace(|c: char| !c.is_ascii_alphanumeric(), "_")),
+            None => words,
+        }
+    }
+
+    /// Let the LLM reorder rule-based suggestions; only the suggestion catalog is sent
+    fn rank_suggestions(&self, context: &NodeContext, suggestions: Vec<NodeSuggestion>) -> Vec<NodeSuggestion> {
+        let Some(llm) = &self.llm else {
+            return suggestions;
+        };
+        if suggestions.len() < 2 {
+            return suggestions;
+        }
+
+        let listing: Vec<String> = suggestions
+            .iter()
+            .enumerate()
+            .map(|(i, s)| format!("{}. {}: {}", i + 1, s.name, s.description))
+            .collect();
+        let request = LlmRequest {
+            system: "Rank the candidate next steps for a visual smart contract. Answer with the numbers only, best first.".to_string(),
+            prompt: format!("After a {:?} node, candidates are:\n{}", context.node_type, listing.join("\n")),
+            max_tokens: 16,
+            contains_graph_data: false,
+        };
+        match llm.try_complete(&request) {
+            Some(ranking) => apply_ranking(suggestions, &ranking),
+            None => suggestions,
+        }
     }
 
     /// Generate suggestions based on analysis
@@ -112,6 +112,7 @@ impl PatternRecognitionEngine {
                     nodes: self.find_security_pattern_nodes(graph, security_pattern_def),
                     cve_reference: security_pattern_def.cve_reference.clone(),
                     mitigation: security_pattern_def.mitigation.clone(),
+                    rule_pack: None,
                 });
             }
         }
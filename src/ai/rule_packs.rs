@@ -0,0 +1,484 @@
+//! Security rule packs
+//!
+//! Security detectors are described as data rather than code: a rule pack is a
+//! TOML or JSON document listing rules, each with a declarative detector, a
+//! severity, references and remediation text. Packs are versioned; every
+//! finding records the pack and version that produced it, so a report can be
+//! reproduced against the same rules later.
+//!
+//! Updated packs are distributed as signed envelopes and only installed when
+//! the signature verifies against one of the configured trusted keys. They are
+//! saved in the same envelope and verified again every time they are loaded,
+//! so a pack edited on disk is never used; files that are unsigned, tampered
+//! with or unreadable are skipped with a warning.
+
+use std::collections::{HashSet, VecDeque};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use semver::Version;
+use serde::{Deserialize, Serialize};
+
+use super::{SecurityIssue, Severity};
+use crate::{
+    error::{CanvasError, CanvasResult},
+    nodes::{decode_hex, encode_hex},
+    types::{NodeId, VisualGraph, VisualNode},
+    wasm::host::{verify_signature, SignatureScheme},
+};
+
+/// Pack shipped with the crate
+const BUILTIN_PACK: &str = include_str!("rules/core.toml");
+
+/// A graph pattern a rule looks for
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Detector {
+    /// Any node of the given types
+    NodePresent { node_types: Vec<String> },
+    /// A node of type `from` with a node of one of the `to` types downstream of it
+    FollowedBy { from: String, to: Vec<String> },
+    /// A node of the given types with none of the `guards` types upstream of it
+    MissingGuard { node_types: Vec<String>, guards: Vec<String> },
+    /// A node of the given types whose property has a specific value
+    PropertyEquals {
+        node_types: Vec<String>,
+        property: String,
+        value: serde_json::Value,
+    },
+}
+
+/// One rule of a pack
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PackRule {
+    pub id: String,
+    pub name: String,
+    pub description: String,
+    pub severity: Severity,
+    #[serde(default)]
+    pub references: Vec<String>,
+    pub remediation: String,
+    pub detector: Detector,
+}
+
+/// A versioned set of rules
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RulePack {
+    pub name: String,
+    pub version: Version,
+    #[serde(default)]
+    pub description: String,
+    pub rules: Vec<PackRule>,
+}
+
+/// A rule match, attributed to the pack version that produced it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RuleFinding {
+    pub pack: String,
+    pub pack_version: Version,
+    pub rule_id: String,
+    pub name: String,
+    pub description: String,
+    pub severity: Severity,
+    pub nodes: Vec<NodeId>,
+    pub references: Vec<String>,
+    pub remediation: String,
+}
+
+impl RuleFinding {
+    /// The finding as a security issue, tagged with its pack
+    pub fn to_security_issue(&self) -> SecurityIssue {
+        SecurityIssue {
+            name: self.name.clone(),
+            description: self.description.clone(),
+            severity: self.severity.clone(),
+            nodes: self.nodes.clone(),
+            cve_reference: self.references.iter().find(|r| r.starts_with("CVE-")).cloned(),
+            mitigation: self.remediation.clone(),
+            rule_pack: Some(format!("{}@{} {}", self.pack, self.pack_version, self.rule_id)),
+        }
+    }
+}
+
+fn nodes_of<'a>(graph: &'a VisualGraph, types: &'a [String]) -> impl Iterator<Item = &'a VisualNode> {
+    graph.nodes.iter().filter(move |n| types.contains(&n.node_type))
+}
+
+/// Nodes reachable from `start` following connections forwards, or backwards when `upstream`
+pub(super) fn reachable(graph: &VisualGraph, start: NodeId, upstream: bool) -> HashSet<NodeId> {
+    let mut seen = HashSet::new();
+    let mut queue = VecDeque::from([start]);
+    while let Some(id) = queue.pop_front() {
+        for connection in &graph.connections {
+            let (from, to) = if upstream {
+                (connection.target_node, connection.source_node)
+            } else {
+                (connection.source_node, connection.target_node)
+            };
+            if from == id && seen.insert(to) {
+                queue.push_back(to);
+            }
+        }
+    }
+    seen
+}
+
+fn node_type_of(graph: &VisualGraph, id: &NodeId) -> Option<&str> {
+    graph.nodes.iter().find(|n| n.id == *id).map(|n| n.node_type.as_str())
+}
+
+impl Detector {
+    /// Nodes matching the detector; empty when the graph is clean
+    pub fn matches(&self, graph: &VisualGraph) -> Vec<NodeId> {
+        match self {
+            Detector::NodePresent { node_types } => nodes_of(graph, node_types).map(|n| n.id).collect(),
+            Detector::FollowedBy { from, to } => graph
+                .nodes
+                .iter()
+                .filter(|n| &n.node_type == from)
+                .flat_map(|n| {
+                    let downstream: Vec<NodeId> = reachable(graph, n.id, false)
+                        .into_iter()
+                        .filter(|id| node_type_of(graph, id).map_or(false, |t| to.iter().any(|x| x == t)))
+                        .collect();
+                    if downstream.is_empty() {
+                        Vec::new()
+                    } else {
+                        std::iter::once(n.id).chain(downstream).collect()
+                    }
+                })
+                .collect(),
+            Detector::MissingGuard { node_types, guards } => nodes_of(graph, node_types)
+                .filter(|n| {
+                    !reachable(graph, n.id, true)
+                        .iter()
+                        .any(|id| node_type_of(graph, id).map_or(false, |t| guards.iter().any(|g| g == t)))
+                })
+                .map(|n| n.id)
+                .collect(),
+            Detector::PropertyEquals { node_types, property, value } => nodes_of(graph, node_types)
+                .filter(|n| n.properties.get(property) == Some(value))
+                .map(|n| n.id)
+                .collect(),
+        }
+    }
+}
+
+impl RulePack {
+    /// Parse a pack from TOML
+    pub fn from_toml(text: &str) -> CanvasResult<Self> {
+        toml::from_str(text).map_err(|e| CanvasError::Config(format!("Invalid rule pack: {}", e)))
+    }
+
+    /// Parse a pack from JSON
+    pub fn from_json(text: &str) -> CanvasResult<Self> {
+        Ok(serde_json::from_str(text)?)
+    }
+
+    /// Load a pack file, choosing the format by extension
+    pub fn load(path: &Path) -> CanvasResult<Self> {
+        let text = std::fs::read_to_string(path)?;
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("toml") => Self::from_toml(&text),
+            Some("json") => Self::from_json(&text),
+            _ => Err(CanvasError::Config(format!(
+                "Rule pack {} must be a .toml or .json file",
+                path.display()
+            ))),
+        }
+    }
+
+    /// The pack shipped with the crate
+    pub fn builtin() -> Self {
+        Self::from_toml(BUILTIN_PACK).expect("built-in rule pack is valid")
+    }
+
+    /// Run every rule against a graph
+    pub fn evaluate(&self, graph: &VisualGraph) -> Vec<RuleFinding> {
+        self.rules
+            .iter()
+            .filter_map(|rule| {
+                let nodes = rule.detector.matches(graph);
+                if nodes.is_empty() {
+                    return None;
+                }
+                Some(RuleFinding {
+                    pack: self.name.clone(),
+                    pack_version: self.version.clone(),
+                    rule_id: rule.id.clone(),
+                    name: rule.name.clone(),
+                    description: rule.description.clone(),
+                    severity: rule.severity.clone(),
+                    nodes,
+                    references: rule.references.clone(),
+                    remediation: rule.remediation.clone(),
+                })
+            })
+            .collect()
+    }
+}
+
+/// The rule packs in effect, one version per pack name
+#[derive(Debug, Clone, Default)]
+pub struct RuleKnowledgeBase {
+    packs: Vec<RulePack>,
+}
+
+impl RuleKnowledgeBase {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Knowledge base with the built-in pack
+    pub fn with_builtin() -> Self {
+        let mut base = Self::new();
+        base.packs.push(RulePack::builtin());
+        base
+    }
+
+    /// Add the built-in pack plus every signed pack in a directory that
+    /// verifies against `trusted_keys` (0x-hex ed25519 public keys)
+    pub fn load_dir(dir: &Path, trusted_keys: &[String]) -> Self {
+        let mut base = Self::with_builtin();
+        if !dir.exists() {
+            return base;
+        }
+        let loaded = decode_keys(trusted_keys).and_then(|keys| {
+            let mut paths: Vec<PathBuf> = std::fs::read_dir(dir)?
+                .map(|entry| entry.map(|e| e.path()))
+                .collect::<Result<_, _>>()?;
+            paths.sort();
+            Ok((keys, paths))
+        });
+        let (keys, paths) = match loaded {
+            Ok(loaded) => loaded,
+            Err(e) => {
+                log::warn!("Not loading rule packs from {}: {}", dir.display(), e);
+                return base;
+            }
+        };
+        for path in paths.iter().filter(|p| p.extension().and_then(|e| e.to_str()) == Some("json")) {
+            let pack = std::fs::read_to_string(path)
+                .map_err(CanvasError::from)
+                .and_then(|text| Ok(serde_json::from_str::<SignedRulePack>(&text)?))
+                .and_then(|envelope| envelope.verify(&keys));
+            match pack {
+                Ok(pack) => {
+                    base.install(pack);
+                }
+                Err(e) => log::warn!("Skipping rule pack {}: {}", path.display(), e),
+            }
+        }
+        base
+    }
+
+    /// Install a pack, replacing an older version of it. Returns false if an
+    /// equal or newer version is already installed.
+    pub fn install(&mut self, pack: RulePack) -> bool {
+        match self.packs.iter_mut().find(|p| p.name == pack.name) {
+            Some(existing) if existing.version >= pack.version => false,
+            Some(existing) => {
+                *existing = pack;
+                true
+            }
+            None => {
+                self.packs.push(pack);
+                true
+            }
+        }
+    }
+
+    /// Installed packs
+    pub fn packs(&self) -> &[RulePack] {
+        &self.packs
+    }
+
+    /// Version of an installed pack
+    pub fn version_of(&self, name: &str) -> Option<&Version> {
+        self.packs.iter().find(|p| p.name == name).map(|p| &p.version)
+    }
+
+    /// Run all packs against a graph
+    pub fn evaluate(&self, graph: &VisualGraph) -> Vec<RuleFinding> {
+        self.packs.iter().flat_map(|pack| pack.evaluate(graph)).collect()
+    }
+}
+
+/// A rule pack as distributed: its text plus an ed25519 signature over that text
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SignedRulePack {
+    /// "toml" or "json"
+    pub format: String,
+    pub content: String,
+    /// 0x-hex ed25519 signature of `content`
+    pub signature: String,
+}
+
+impl SignedRulePack {
+    /// Verify the signature against the trusted keys and parse the pack
+    pub fn verify(&self, trusted_keys: &[Vec<u8>]) -> CanvasResult<RulePack> {
+        let signature = decode_hex(&self.signature)
+            .ok_or_else(|| CanvasError::Validation("Rule pack signature is not valid hex".to_string()))?;
+        let trusted = trusted_keys
+            .iter()
+            .any(|key| verify_signature(SignatureScheme::Ed25519, key, self.content.as_bytes(), &signature));
+        if !trusted {
+            return Err(CanvasError::PermissionDenied(
+                "Rule pack is not signed by a trusted key".to_string(),
+            ));
+        }
+        match self.format.as_str() {
+            "toml" => RulePack::from_toml(&self.content),
+            "json" => RulePack::from_json(&self.content),
+            other => Err(CanvasError::Validation(format!("Unknown rule pack format: {}", other))),
+        }
+    }
+}
+
+/// Fetches signed rule packs and installs verified updates
+pub struct RulePackUpdater {
+    url: String,
+    trusted_keys: Vec<Vec<u8>>,
+    timeout: Duration,
+}
+
+impl RulePackUpdater {
+    /// `trusted_keys` are 0x-hex ed25519 public keys
+    pub fn new(url: impl Into<String>, trusted_keys: &[String], timeout: Duration) -> CanvasResult<Self> {
+        let trusted_keys = decode_keys(trusted_keys)?;
+        if trusted_keys.is_empty() {
+            return Err(CanvasError::Config("Rule pack updates need at least one trusted key".to_string()));
+        }
+        Ok(Self {
+            url: url.into(),
+            trusted_keys,
+            timeout,
+        })
+    }
+
+    /// Download the published pack's signed envelope
+    fn fetch_signed(&self) -> CanvasResult<SignedRulePack> {
+        Ok(ureq::AgentBuilder::new()
+            .timeout(self.timeout)
+            .build()
+            .get(&self.url)
+            .call()
+            .map_err(|e| CanvasError::Network(format!("Rule pack download failed: {}", e)))?
+            .into_json()?)
+    }
+
+    /// Download and verify the published pack
+    pub fn fetch(&self) -> CanvasResult<RulePack> {
+        self.fetch_signed()?.verify(&self.trusted_keys)
+    }
+
+    /// Fetch the published pack and install it if newer, saving its signed
+    /// envelope to `dir`. Returns the installed version, or `None` if already
+    /// up to date.
+    pub fn update(&self, base: &mut RuleKnowledgeBase, dir: &Path) -> CanvasResult<Option<Version>> {
+        let envelope = self.fetch_signed()?;
+        let pack = envelope.verify(&self.trusted_keys)?;
+        install_signed(base, envelope, pack, dir)
+    }
+}
+
+/// Install a verified pack, saving its envelope to `dir` so it can be
+/// verified again on load
+fn install_signed(
+    base: &mut RuleKnowledgeBase,
+    envelope: SignedRulePack,
+    pack: RulePack,
+    dir: &Path,
+) -> CanvasResult<Option<Version>> {
+    let (name, version) = (pack.name.clone(), pack.version.clone());
+    if !base.install(pack) {
+        return Ok(None);
+    }
+    std::fs::create_dir_all(dir)?;
+    std::fs::write(dir.join(format!("{}.json", name)), serde_json::to_string_pretty(&envelope)?)?;
+    log::info!("Installed rule pack {} {}", name, version);
+    Ok(Some(version))
+}
+
+/// Decode 0x-hex ed25519 public keys
+fn decode_keys(keys: &[String]) -> CanvasResult<Vec<Vec<u8>>> {
+    keys.iter()
+        .map(|key| decode_hex(key).ok_or_else(|| CanvasError::Config(format!("Invalid rule pack key: {}", key))))
+        .collect()
+}
+
+/// Sign a pack's content; used by pack authors and tests
+pub fn sign_rule_pack(format: &str, content: &str, signing_key: &ed25519_dalek::SigningKey) -> SignedRulePack {
+    use ed25519_dalek::Signer;
+    SignedRulePack {
+        format: format.to_string(),
+        content: content.to_string(),
+        signature: encode_hex(&signing_key.sign(content.as_bytes()).to_bytes()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_graphs::{connect, node};
+
+    #[test]
+    fn test_builtin_pack_findings_are_attributed() {
+        let mut graph = VisualGraph::new("vault");
+        let call = node("TryCall");
+        let write = node("WriteStorage");
+        connect(&mut graph, &call, "success_flow", &write, "flow_in");
+        graph.add_node(call);
+        graph.add_node(write);
+
+        let findings = RuleKnowledgeBase::with_builtin().evaluate(&graph);
+        let reentrancy = findings.iter().find(|f| f.rule_id == "CORE-001").unwrap();
+        assert_eq!(reentrancy.pack, "core");
+        assert_eq!(reentrancy.nodes.len(), 2);
+        assert!(findings.iter().any(|f| f.rule_id == "CORE-003"));
+        assert!(reentrancy.to_security_issue().rule_pack.unwrap().starts_with("core@1.0.0"));
+    }
+
+    #[test]
+    fn test_signed_pack_update() {
+        let signing_key = ed25519_dalek::SigningKey::from_bytes(&[7u8; 32]);
+        let trusted = vec![signing_key.verifying_key().to_bytes().to_vec()];
+        let content = BUILTIN_PACK.replace("version = \"1.0.0\"", "version = \"1.1.0\"");
+
+        let signed = sign_rule_pack("toml", &content, &signing_key);
+        let pack = signed.verify(&trusted).unwrap();
+        let mut base = RuleKnowledgeBase::with_builtin();
+        assert!(base.install(pack.clone()));
+        assert_eq!(base.version_of("core"), Some(&Version::new(1, 1, 0)));
+        assert!(!base.install(RulePack::builtin()));
+
+        let mut tampered = signed;
+        tampered.content.push_str("\n# edited");
+        assert!(tampered.verify(&trusted).is_err());
+    }
+
+    #[test]
+    fn test_installed_packs_are_verified_on_load() {
+        let signing_key = ed25519_dalek::SigningKey::from_bytes(&[7u8; 32]);
+        let keys = vec![encode_hex(&signing_key.verifying_key().to_bytes())];
+        let content = BUILTIN_PACK.replace("version = \"1.0.0\"", "version = \"1.1.0\"");
+        let signed = sign_rule_pack("toml", &content, &signing_key);
+        let dir = tempfile::tempdir().unwrap();
+
+        let mut base = RuleKnowledgeBase::with_builtin();
+        let pack = signed.verify(&decode_keys(&keys).unwrap()).unwrap();
+        assert_eq!(install_signed(&mut base, signed, pack, dir.path()).unwrap(), Some(Version::new(1, 1, 0)));
+        let loaded = RuleKnowledgeBase::load_dir(dir.path(), &keys);
+        assert_eq!(loaded.version_of("core"), Some(&Version::new(1, 1, 0)));
+
+        // A pack edited after installation, or a plain unsigned pack, is skipped
+        let path = dir.path().join("core.json");
+        let edited = std::fs::read_to_string(&path).unwrap().replace("1.1.0", "1.2.0");
+        std::fs::write(&path, edited).unwrap();
+        std::fs::write(dir.path().join("local.json"), serde_json::to_string(&RulePack::builtin()).unwrap()).unwrap();
+        let loaded = RuleKnowledgeBase::load_dir(dir.path(), &keys);
+        assert_eq!(loaded.version_of("core"), Some(&Version::new(1, 0, 0)));
+        assert_eq!(loaded.packs().len(), 1);
+        assert_eq!(RuleKnowledgeBase::load_dir(dir.path(), &[]).version_of("core"), Some(&Version::new(1, 0, 0)));
+    }
+}
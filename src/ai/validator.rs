@@ -9,6 +9,8 @@ use super::ValidationResult;
 pub struct RuleBasedValidator {
     validation_rules: Vec<ValidationRule>,
     security_rules: Vec<SecurityRule>,
+    /// Whether the contract is compiled with the pausable feature
+    pausable: bool,
 }
 
 /// Validation rule
@@ -75,9 +77,17 @@ impl RuleBasedValidator {
         Self {
             validation_rules,
             security_rules,
+            pausable: false,
         }
     }
 
+    /// Mark the contract as compiled with the pausable feature, which satisfies
+    /// the Circuit Breaker rule through the injected pause guards
+    pub fn with_pausable(mut self, pausable: bool) -> Self {
+        self.pausable = pausable;
+        self
+    }
+
     /// Validate contract structure
     pub fn validate(&self, graph: &Graph) -> CanvasResult<ValidationResult> {
         let mut errors = Vec::new();
@@ -100,6 +110,10 @@ impl RuleBasedValidator {
 
         // Run security rules
         for rule in &self.security_rules {
+            if self.pausable && rule.name == "Circuit Breaker" {
+                info.push("Circuit Breaker: pausable feature enabled; state-mutating entry points are guarded".to_string());
+                continue;
+            }
             let result = (rule.check)(graph);
             if !result.passed {
                 let message = format!("SECURITY: {} - {}", rule.name, result.message);
@@ -324,7 +338,7 @@ impl RuleBasedValidator {
                             message: "Unchecked arithmetic operations detected".to_string(),
                             affected_nodes: vec![],
                             cve_reference: Some("CVE-2018-10299".to_string()),
-                            mitigation: "Add overflow checks or use SafeMath library".to_string(),
+                            mitigation: "Set overflow_mode to \"checked\" or \"saturating\" on arithmetic nodes, or as the project default (compiler.overflow_mode)".to_string(),
                         }
                     } else {
                         SecurityCheckResult {
@@ -337,6 +351,32 @@ impl RuleBasedValidator {
                     }
                 },
             },
+            // Check for an emergency stop
+            SecurityRule {
+                name: "Circuit Breaker".to_string(),
+                description: "Contracts that modify state should be pausable in an emergency".to_string(),
+                cve_reference: None,
+                severity: RuleSeverity::Info,
+                check: |graph| {
+                    if Self::has_unguarded_state_changes(graph) {
+                        SecurityCheckResult {
+                            passed: false,
+                            message: "State modifications cannot be halted in an emergency".to_string(),
+                            affected_nodes: vec![],
+                            cve_reference: None,
+                            mitigation: "Enable the pausable compiler feature (compiler.pausable = true) to add pause/unpause admin functions".to_string(),
+                        }
+                    } else {
+                        SecurityCheckResult {
+                            passed: true,
+                            message: "State modifications are guarded".to_string(),
+                            affected_nodes: vec![],
+                            cve_reference: None,
+                            mitigation: String::new(),
+                        }
+                    }
+                },
+            },
         ]
     }
 
@@ -486,6 +526,24 @@ impl RuleBasedValidator {
         state_nodes.len() > 3
     }
 
+    /// Check for state nodes that no control node (such as a hand-built pause check) feeds into
+    fn has_unguarded_state_changes(graph: &Graph) -> bool {
+        let nodes = graph.get_nodes();
+        let edges = graph.get_edges();
+
+        nodes
+            .iter()
+            .filter(|n| n.node_type == NodeType::State)
+            .any(|state| {
+                !edges.iter().any(|edge| {
+                    edge.target == state.id
+                        && nodes
+                            .iter()
+                            .any(|n| n.id == edge.source && n.node_type == NodeType::Control)
+                })
+            })
+    }
+
     /// Check for unchecked arithmetic
     fn has_unchecked_arithmetic(graph: &Graph) -> bool {
         let nodes = graph.get_nodes();
@@ -0,0 +1,235 @@
+//! Review annotations anchored to graph nodes and connections
+//!
+//! Annotations of a graph are kept in a sidecar file next to it
+//! (`token.json` → `token.annotations.json`), so they travel with the graph in
+//! version control and in project bundles without changing the graph format.
+//! Each annotation is anchored to a node or connection, can collect replies,
+//! and stays open until someone resolves it. Review mode lists the open ones,
+//! flagging those whose anchor has since been deleted from the graph.
+
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    error::{CanvasError, CanvasResult},
+    types::{EdgeId, NodeId, VisualGraph},
+};
+
+/// Suffix of annotation sidecar files, replacing the graph's `.json`
+pub const ANNOTATIONS_SUFFIX: &str = ".annotations.json";
+
+/// Sidecar annotation file of a graph file
+pub fn annotations_path(graph_path: &Path) -> PathBuf {
+    let stem = graph_path.file_stem().map_or_else(String::new, |s| s.to_string_lossy().to_string());
+    graph_path.with_file_name(format!("{}{}", stem, ANNOTATIONS_SUFFIX))
+}
+
+/// What an annotation is attached to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", content = "id", rename_all = "snake_case")]
+pub enum AnnotationAnchor {
+    Node(NodeId),
+    Connection(EdgeId),
+}
+
+impl AnnotationAnchor {
+    /// Whether the anchor still exists in `graph`
+    pub fn exists_in(&self, graph: &VisualGraph) -> bool {
+        match self {
+            Self::Node(id) => graph.nodes.iter().any(|n| n.id == *id),
+            Self::Connection(id) => graph.connections.iter().any(|c| c.id == *id),
+        }
+    }
+}
+
+/// Reply to an annotation
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AnnotationReply {
+    pub author: String,
+    pub text: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Comment anchored to part of a graph
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Annotation {
+    pub id: Uuid,
+    pub anchor: AnnotationAnchor,
+    pub author: String,
+    pub text: String,
+    pub created_at: DateTime<Utc>,
+    #[serde(default)]
+    pub replies: Vec<AnnotationReply>,
+    #[serde(default)]
+    pub resolved_by: Option<String>,
+    #[serde(default)]
+    pub resolved_at: Option<DateTime<Utc>>,
+}
+
+impl Annotation {
+    pub fn is_resolved(&self) -> bool {
+        self.resolved_at.is_some()
+    }
+}
+
+/// Open annotation as shown in review mode
+#[derive(Debug, Clone)]
+pub struct ReviewItem<'a> {
+    pub annotation: &'a Annotation,
+    /// The anchor was deleted from the graph
+    pub orphaned: bool,
+}
+
+/// Annotations of one graph
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct GraphAnnotations {
+    pub graph_id: Option<Uuid>,
+    pub annotations: Vec<Annotation>,
+    #[serde(skip)]
+    path: Option<PathBuf>,
+}
+
+impl GraphAnnotations {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Load the annotations of the graph at `graph_path`, starting empty if it
+    /// has none yet. [`save`](Self::save) writes back to the sidecar file.
+    pub fn open(graph_path: &Path) -> CanvasResult<Self> {
+        let path = annotations_path(graph_path);
+        let mut annotations: Self = if path.exists() {
+            serde_json::from_str(&std::fs::read_to_string(&path)?)?
+        } else {
+            Self::default()
+        };
+        annotations.path = Some(path);
+        Ok(annotations)
+    }
+
+    pub fn save(&self) -> CanvasResult<()> {
+        let path = self
+            .path
+            .as_ref()
+            .ok_or_else(|| CanvasError::InvalidState("Annotations were not opened from a graph file".to_string()))?;
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// Annotate part of `graph`
+    pub fn add(&mut self, graph: &VisualGraph, anchor: AnnotationAnchor, author: &str, text: String) -> CanvasResult<Uuid> {
+        if let Some(graph_id) = self.graph_id.filter(|id| *id != graph.id) {
+            return Err(CanvasError::Validation(format!(
+                "Annotations belong to graph {}, not {}",
+                graph_id, graph.id
+            )));
+        }
+        if !anchor.exists_in(graph) {
+            return Err(CanvasError::Validation(format!("{:?} is not part of graph '{}'", anchor, graph.name)));
+        }
+        self.graph_id = Some(graph.id);
+        let id = Uuid::new_v4();
+        self.annotations.push(Annotation {
+            id,
+            anchor,
+            author: author.to_string(),
+            text,
+            created_at: Utc::now(),
+            replies: Vec::new(),
+            resolved_by: None,
+            resolved_at: None,
+        });
+        Ok(id)
+    }
+
+    fn get_mut(&mut self, id: Uuid) -> CanvasResult<&mut Annotation> {
+        self.annotations
+            .iter_mut()
+            .find(|a| a.id == id)
+            .ok_or_else(|| CanvasError::NotFound(format!("Annotation {} not found", id)))
+    }
+
+    pub fn reply(&mut self, id: Uuid, author: &str, text: String) -> CanvasResult<()> {
+        self.get_mut(id)?.replies.push(AnnotationReply {
+            author: author.to_string(),
+            text,
+            created_at: Utc::now(),
+        });
+        Ok(())
+    }
+
+    pub fn resolve(&mut self, id: Uuid, user: &str) -> CanvasResult<()> {
+        let annotation = self.get_mut(id)?;
+        annotation.resolved_by = Some(user.to_string());
+        annotation.resolved_at = Some(Utc::now());
+        Ok(())
+    }
+
+    pub fn reopen(&mut self, id: Uuid) -> CanvasResult<()> {
+        let annotation = self.get_mut(id)?;
+        annotation.resolved_by = None;
+        annotation.resolved_at = None;
+        Ok(())
+    }
+
+    /// Annotations attached to an anchor, oldest first
+    pub fn for_anchor(&self, anchor: AnnotationAnchor) -> Vec<&Annotation> {
+        self.annotations.iter().filter(|a| a.anchor == anchor).collect()
+    }
+
+    /// Unresolved annotations for review mode, oldest first
+    pub fn review(&self, graph: &VisualGraph) -> Vec<ReviewItem<'_>> {
+        let mut items: Vec<ReviewItem<'_>> = self
+            .annotations
+            .iter()
+            .filter(|a| !a.is_resolved())
+            .map(|annotation| ReviewItem {
+                annotation,
+                orphaned: !annotation.anchor.exists_in(graph),
+            })
+            .collect();
+        items.sort_by_key(|item| item.annotation.created_at);
+        items
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{Position, VisualNode};
+
+    #[test]
+    fn test_annotations_are_stored_next_to_the_graph() {
+        let dir = tempfile::tempdir().unwrap();
+        let graph_path = dir.path().join("token.json");
+        let mut graph = VisualGraph::new("token");
+        let node = Uuid::new_v4();
+        graph.add_node(VisualNode::new(node, "Mint", Position::new(0.0, 0.0)));
+        std::fs::write(&graph_path, serde_json::to_string(&graph).unwrap()).unwrap();
+
+        let mut annotations = GraphAnnotations::open(&graph_path).unwrap();
+        let question = annotations
+            .add(&graph, AnnotationAnchor::Node(node), "alice", "Cap the supply?".to_string())
+            .unwrap();
+        let nit = annotations
+            .add(&graph, AnnotationAnchor::Node(node), "bob", "Rename to MintCapped".to_string())
+            .unwrap();
+        assert!(annotations
+            .add(&graph, AnnotationAnchor::Connection(Uuid::new_v4()), "bob", "?".to_string())
+            .is_err());
+        annotations.reply(question, "bob", "Yes, 1M".to_string()).unwrap();
+        annotations.resolve(nit, "alice").unwrap();
+        annotations.save().unwrap();
+        assert!(dir.path().join("token.annotations.json").exists());
+
+        let annotations = GraphAnnotations::open(&graph_path).unwrap();
+        let review = annotations.review(&graph);
+        assert_eq!(review.len(), 1);
+        assert_eq!(review[0].annotation.replies.len(), 1);
+        assert!(!review[0].orphaned);
+        assert!(annotations.review(&VisualGraph::new("other"))[0].orphaned);
+    }
+}
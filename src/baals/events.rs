@@ -0,0 +1,230 @@
+//! Versioned event schemas
+//!
+//! Contracts change their events across upgrades, so logs emitted by an old
+//! version cannot be decoded with the current ABI. The registry keeps every
+//! version of a contract's event definitions with the block it took effect at,
+//! and decodes a log with the schema that was live when it was emitted.
+//! Versions are added automatically on deploy and can be imported by hand for
+//! contracts deployed elsewhere.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::Config,
+    error::{CanvasError, CanvasResult},
+    types::{ContractABI, ContractAddress, Event, EventABI},
+};
+
+/// File the registry is kept in, under the data directory
+pub const EVENT_SCHEMAS_FILE: &str = "event-schemas.json";
+
+/// Event definitions of one contract version
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EventSchemaVersion {
+    /// 1 for the first registered version
+    pub version: u32,
+    /// First block the version's events can appear in
+    pub from_block: u64,
+    pub events: Vec<EventABI>,
+}
+
+/// An undecoded log: indexed values and data values in declaration order
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RawEvent {
+    pub contract: ContractAddress,
+    pub block_number: u64,
+    pub name: String,
+    #[serde(default)]
+    pub topics: Vec<serde_json::Value>,
+    #[serde(default)]
+    pub data: Vec<serde_json::Value>,
+}
+
+/// Event schemas of every known contract
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EventSchemaRegistry {
+    contracts: BTreeMap<ContractAddress, Vec<EventSchemaVersion>>,
+    #[serde(skip)]
+    path: Option<PathBuf>,
+}
+
+impl EventSchemaRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registry file for a configuration
+    pub fn default_path(config: &Config) -> PathBuf {
+        config.app.data_dir.join(EVENT_SCHEMAS_FILE)
+    }
+
+    /// Load the registry from `path`, starting empty if it does not exist yet.
+    /// [`save`](Self::save) writes back to the same file.
+    pub fn open(path: &Path) -> CanvasResult<Self> {
+        let mut registry: Self = if path.exists() {
+            serde_json::from_str(&std::fs::read_to_string(path)?)?
+        } else {
+            Self::default()
+        };
+        registry.path = Some(path.to_path_buf());
+        Ok(registry)
+    }
+
+    pub fn save(&self) -> CanvasResult<()> {
+        let path = self
+            .path
+            .as_ref()
+            .ok_or_else(|| CanvasError::InvalidState("Event schema registry was not opened from a file".to_string()))?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// Register the events of a contract version live from `from_block` on.
+    ///
+    /// Returns the new version number, or the latest one unchanged when its
+    /// events are identical.
+    pub fn register(&mut self, contract: &str, abi: &ContractABI, from_block: u64) -> CanvasResult<u32> {
+        let versions = self.contracts.entry(contract.to_string()).or_default();
+        if let Some(latest) = versions.last() {
+            if from_block < latest.from_block {
+                return Err(CanvasError::Validation(format!(
+                    "Contract {} already has event schema v{} from block {}; cannot register one from block {}",
+                    contract, latest.version, latest.from_block, from_block
+                )));
+            }
+            if serde_json::to_value(&latest.events)? == serde_json::to_value(&abi.events)? {
+                return Ok(latest.version);
+            }
+        }
+        let version = versions.last().map_or(1, |v| v.version + 1);
+        versions.push(EventSchemaVersion {
+            version,
+            from_block,
+            events: abi.events.clone(),
+        });
+        Ok(version)
+    }
+
+    pub fn versions(&self, contract: &str) -> &[EventSchemaVersion] {
+        self.contracts.get(contract).map_or(&[], Vec::as_slice)
+    }
+
+    /// Schema version live at `block_number`
+    pub fn schema_at(&self, contract: &str, block_number: u64) -> Option<&EventSchemaVersion> {
+        self.versions(contract).iter().rev().find(|v| v.from_block <= block_number)
+    }
+
+    /// Decode a log with the schema live when it was emitted
+    pub fn decode(&self, raw: &RawEvent) -> CanvasResult<Event> {
+        let schema = self.schema_at(&raw.contract, raw.block_number).ok_or_else(|| {
+            CanvasError::Validation(format!(
+                "No event schema for contract {} at block {}",
+                raw.contract, raw.block_number
+            ))
+        })?;
+        let definition = schema.events.iter().find(|e| e.name == raw.name).ok_or_else(|| {
+            CanvasError::Validation(format!(
+                "Event '{}' is not part of schema v{} of contract {}",
+                raw.name, schema.version, raw.contract
+            ))
+        })?;
+
+        let (indexed, plain): (Vec<_>, Vec<_>) = definition.inputs.iter().partition(|p| p.indexed);
+        if indexed.len() != raw.topics.len() || plain.len() != raw.data.len() {
+            return Err(CanvasError::Validation(format!(
+                "Event '{}' (schema v{}) has {} indexed and {} data fields, log has {} and {}",
+                raw.name,
+                schema.version,
+                indexed.len(),
+                plain.len(),
+                raw.topics.len(),
+                raw.data.len()
+            )));
+        }
+
+        let mut data = HashMap::new();
+        for (param, value) in indexed.iter().zip(&raw.topics).chain(plain.iter().zip(&raw.data)) {
+            if !param.value_type.matches_value(value) {
+                return Err(CanvasError::Validation(format!(
+                    "Field '{}' of event '{}' does not match type {:?}",
+                    param.name, raw.name, param.value_type
+                )));
+            }
+            data.insert(param.name.clone(), value.clone());
+        }
+        Ok(Event {
+            name: raw.name.clone(),
+            data,
+            indexed_data: raw.topics.clone(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{ParameterABI, ValueType};
+
+    fn abi(fields: &[(&str, ValueType, bool)]) -> ContractABI {
+        ContractABI {
+            functions: Vec::new(),
+            events: vec![EventABI {
+                name: "Transfer".to_string(),
+                inputs: fields
+                    .iter()
+                    .map(|(name, value_type, indexed)| ParameterABI {
+                        name: name.to_string(),
+                        value_type: value_type.clone(),
+                        indexed: *indexed,
+                    })
+                    .collect(),
+                anonymous: false,
+            }],
+            errors: Vec::new(),
+            storage: Vec::new(),
+            metadata: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_decodes_with_historical_schema() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(EVENT_SCHEMAS_FILE);
+        let mut registry = EventSchemaRegistry::open(&path).unwrap();
+        let v1 = abi(&[("amount", ValueType::Integer, false)]);
+        assert_eq!(registry.register("0x01", &v1, 10).unwrap(), 1);
+        assert_eq!(registry.register("0x01", &v1, 20).unwrap(), 1);
+        let v2 = abi(&[("to", ValueType::String, true), ("amount", ValueType::Integer, false)]);
+        assert_eq!(registry.register("0x01", &v2, 100).unwrap(), 2);
+        assert!(registry.register("0x01", &v1, 50).is_err());
+        registry.save().unwrap();
+
+        let registry = EventSchemaRegistry::open(&path).unwrap();
+        let old = RawEvent {
+            contract: "0x01".to_string(),
+            block_number: 42,
+            name: "Transfer".to_string(),
+            topics: Vec::new(),
+            data: vec![serde_json::json!(5)],
+        };
+        assert_eq!(registry.decode(&old).unwrap().data["amount"], serde_json::json!(5));
+
+        let new = RawEvent {
+            block_number: 100,
+            topics: vec![serde_json::json!("alice")],
+            ..old.clone()
+        };
+        let event = registry.decode(&new).unwrap();
+        assert_eq!(event.data["to"], serde_json::json!("alice"));
+        assert!(registry.decode(&RawEvent { block_number: 5, ..old.clone() }).is_err());
+        assert!(registry.decode(&RawEvent { block_number: 150, ..old }).is_err());
+    }
+}
@@ -0,0 +1,234 @@
+//! Account impersonation on local networks
+//!
+//! Testing a flow that needs a third party's signature (a token holder
+//! approving, a co-signer of a multisig) shouldn't require that party's key.
+//! On a local or dev network an [`ImpersonationSession`] lists the accounts
+//! the console and simulator may act as; an [`ImpersonatedSigner`] then signs
+//! for one of them without a key. Its signatures are markers, not ed25519
+//! signatures: the local node and [`ImpersonationSession::verify_signature`]
+//! accept them for impersonated accounts, and nothing else does.
+//!
+//! Sessions are kept in `impersonation.json` under the data directory until
+//! stopped. Starting one, or signing as an impersonated account, is refused
+//! on any network that isn't local.
+
+use std::{
+    collections::BTreeSet,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use super::signer::Signer;
+use crate::{
+    config::{Config, NetworkProfile},
+    error::{CanvasError, CanvasResult},
+    nodes::{decode_hex, encode_hex},
+    wasm::host::{self, SignatureScheme},
+};
+
+/// Impersonation session file, under the data directory
+pub const IMPERSONATION_FILE: &str = "impersonation.json";
+
+/// Network profiles that allow impersonation
+pub const LOCAL_NETWORKS: &[&str] = &["local", "localhost", "dev", "devnet"];
+
+/// Refuse anything but a local or dev network
+pub fn ensure_local_network(network: &NetworkProfile) -> CanvasResult<()> {
+    if LOCAL_NETWORKS.iter().any(|name| network.name.eq_ignore_ascii_case(name)) {
+        Ok(())
+    } else {
+        Err(CanvasError::PermissionDenied(format!(
+            "Impersonation is only available on local networks, not '{}'",
+            network.name
+        )))
+    }
+}
+
+fn normalize(address: &str) -> CanvasResult<String> {
+    let address = address.trim().to_lowercase();
+    match decode_hex(&address) {
+        Some(bytes) if !bytes.is_empty() => Ok(address),
+        _ => Err(CanvasError::Validation(format!("'{}' is not a 0x-hex address", address))),
+    }
+}
+
+/// Marker signature of an impersonated account over `message`
+pub fn impersonated_signature(address: &str, message: &[u8]) -> [u8; 64] {
+    let mut hasher = blake3::Hasher::new_derive_key("canvas-contracts impersonation");
+    hasher.update(address.to_lowercase().as_bytes());
+    hasher.update(message);
+    let mut signature = [0u8; 64];
+    hasher.finalize_xof().fill(&mut signature);
+    signature
+}
+
+/// Accounts being impersonated
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ImpersonationSession {
+    accounts: BTreeSet<String>,
+    #[serde(skip)]
+    path: Option<PathBuf>,
+}
+
+impl ImpersonationSession {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Session file for a configuration
+    pub fn default_path(config: &Config) -> PathBuf {
+        config.app.data_dir.join(IMPERSONATION_FILE)
+    }
+
+    /// Load the session from `path`, starting empty if it does not exist yet.
+    /// [`save`](Self::save) writes back to the same file.
+    pub fn open(path: &Path) -> CanvasResult<Self> {
+        let mut session: Self = if path.exists() {
+            serde_json::from_str(&std::fs::read_to_string(path)?)?
+        } else {
+            Self::default()
+        };
+        session.path = Some(path.to_path_buf());
+        Ok(session)
+    }
+
+    pub fn save(&self) -> CanvasResult<()> {
+        let path = self
+            .path
+            .as_ref()
+            .ok_or_else(|| {
+                CanvasError::InvalidState("Impersonation session was not opened from a file".to_string())
+            })?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// Start acting as `address`; returns false if it already was
+    pub fn start(&mut self, network: &NetworkProfile, address: &str) -> CanvasResult<bool> {
+        ensure_local_network(network)?;
+        Ok(self.accounts.insert(normalize(address)?))
+    }
+
+    /// Stop acting as `address`; returns false if it wasn't impersonated
+    pub fn stop(&mut self, address: &str) -> bool {
+        self.accounts.remove(&address.trim().to_lowercase())
+    }
+
+    pub fn stop_all(&mut self) {
+        self.accounts.clear();
+    }
+
+    pub fn is_impersonating(&self, address: &str) -> bool {
+        self.accounts.contains(&address.trim().to_lowercase())
+    }
+
+    pub fn accounts(&self) -> impl Iterator<Item = &str> {
+        self.accounts.iter().map(String::as_str)
+    }
+
+    /// Signer acting as `address`, which must be impersonated in this session
+    pub fn signer(&self, network: &NetworkProfile, address: &str) -> CanvasResult<ImpersonatedSigner> {
+        ensure_local_network(network)?;
+        let address = normalize(address)?;
+        if !self.accounts.contains(&address) {
+            return Err(CanvasError::PermissionDenied(format!(
+                "{} is not impersonated; start impersonating it first",
+                address
+            )));
+        }
+        Ok(ImpersonatedSigner { address })
+    }
+
+    /// Sandbox signature check: genuine signatures verify as usual, and so do
+    /// marker signatures of impersonated accounts (keyed by their public key)
+    pub fn verify_signature(
+        &self,
+        scheme: SignatureScheme,
+        public_key: &[u8],
+        message: &[u8],
+        signature: &[u8],
+    ) -> bool {
+        if host::verify_signature(scheme, public_key, message, signature) {
+            return true;
+        }
+        let address = encode_hex(public_key);
+        self.is_impersonating(&address) && signature == impersonated_signature(&address, message)
+    }
+}
+
+/// Acts as an impersonated account without its key
+#[derive(Debug, Clone)]
+pub struct ImpersonatedSigner {
+    address: String,
+}
+
+impl Signer for ImpersonatedSigner {
+    fn describe(&self) -> String {
+        format!("impersonated account {}", self.address)
+    }
+
+    fn public_key(&self) -> CanvasResult<[u8; 32]> {
+        decode_hex(&self.address)
+            .and_then(|bytes| bytes.try_into().ok())
+            .ok_or_else(|| CanvasError::Validation(format!("{} is an address, not an ed25519 key", self.address)))
+    }
+
+    fn address(&self) -> CanvasResult<String> {
+        Ok(self.address.clone())
+    }
+
+    fn sign(&self, message: &[u8]) -> CanvasResult<[u8; 64]> {
+        Ok(impersonated_signature(&self.address, message))
+    }
+
+    fn impersonated(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::baals::signer::LocalSigner;
+
+    #[test]
+    fn test_impersonation_is_local_only_and_session_scoped() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(IMPERSONATION_FILE);
+        let local = NetworkProfile::default();
+        let mainnet = NetworkProfile {
+            name: "mainnet".to_string(),
+            ..Default::default()
+        };
+
+        let holder = LocalSigner::new(ed25519_dalek::SigningKey::from_bytes(&[4u8; 32]));
+        let address = holder.address().unwrap();
+        let mut session = ImpersonationSession::open(&path).unwrap();
+        assert!(matches!(session.start(&mainnet, &address), Err(CanvasError::PermissionDenied(_))));
+        assert!(session.signer(&local, &address).is_err());
+
+        assert!(session.start(&local, &address.to_uppercase().replacen("0X", "0x", 1)).unwrap());
+        session.save().unwrap();
+        let session = ImpersonationSession::open(&path).unwrap();
+        assert_eq!(session.accounts().collect::<Vec<_>>(), [address.as_str()]);
+        assert!(session.signer(&mainnet, &address).is_err());
+
+        // The holder's "signature" verifies in the sandbox without their key
+        let key = holder.public_key().unwrap();
+        let message = b"approve 0xspender 100";
+        let signature = session.signer(&local, &address).unwrap().sign(message).unwrap();
+        assert!(session.verify_signature(SignatureScheme::Ed25519, &key, message, &signature));
+        assert!(!host::verify_signature(SignatureScheme::Ed25519, &key, message, &signature));
+        assert!(!session.verify_signature(SignatureScheme::Ed25519, &key, b"approve 0xspender 999", &signature));
+        let genuine = holder.sign(b"real").unwrap();
+        assert!(session.verify_signature(SignatureScheme::Ed25519, &key, b"real", &genuine));
+
+        let mut session = session;
+        assert!(session.stop(&address));
+        assert!(!session.verify_signature(SignatureScheme::Ed25519, &key, message, &signature));
+    }
+}
@@ -0,0 +1,372 @@
+//! Pre-simulation of pending transactions
+//!
+//! The watcher polls a node for transactions it has accepted but not yet
+//! included, picks out the calls to contracts it watches, and runs each one
+//! against the contract's current on-chain storage in the next block. A call
+//! that would revert, exhaust its gas limit or use more gas than the
+//! contract's bound raises a [`PendingWarning`] while there is still time to
+//! act on it. Simulation writes never leave the watcher.
+//!
+//! Each transaction is simulated once, the first time it is seen. The signer
+//! is credited with exactly the value it sends, since the node has already
+//! checked that it can pay.
+
+use std::collections::{BTreeMap, HashSet};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::{transactions::SignedTransaction, webhooks::DELIVERY_ID_HEADER, BaalsClient, NodeStatus};
+use crate::{
+    config::Config,
+    deployment::environments::ReleaseStore,
+    error::{CanvasError, CanvasResult},
+    types::{ContractAddress, ExecutionContext, Gas, TransactionHash},
+    wasm::{
+        engine::OUT_OF_GAS, shared, BaalsBackend, BlockContext, SandboxAccounts, SharedStorage, SimulationRequest,
+        WasmRuntime,
+    },
+};
+
+/// Alert rule warnings are recorded under
+pub const MEMPOOL_ALERT_RULE: &str = "mempool";
+
+const NOTIFY_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// Where the watcher reads pending transactions and contract state from
+pub trait PendingSource {
+    fn status(&self) -> CanvasResult<NodeStatus>;
+    fn pending_transactions(&self) -> CanvasResult<Vec<SignedTransaction>>;
+    /// Current storage of a deployed contract
+    fn contract_storage(&self, contract: &str) -> CanvasResult<SharedStorage>;
+}
+
+impl PendingSource for BaalsClient {
+    fn status(&self) -> CanvasResult<NodeStatus> {
+        self.node_status()
+    }
+
+    fn pending_transactions(&self) -> CanvasResult<Vec<SignedTransaction>> {
+        self.get_pending_transactions()
+    }
+
+    fn contract_storage(&self, contract: &str) -> CanvasResult<SharedStorage> {
+        Ok(shared(BaalsBackend::new(BaalsClient::new(&self.config)?, contract)))
+    }
+}
+
+/// A deployed contract whose pending calls are simulated
+#[derive(Debug, Clone)]
+pub struct WatchedContract {
+    pub address: ContractAddress,
+    pub wasm_bytes: Vec<u8>,
+    /// Warn when a call would use more gas than this, even within its limit
+    pub max_gas: Option<Gas>,
+}
+
+impl WatchedContract {
+    /// The contracts of the latest release of each environment, or of just
+    /// `environment`
+    pub fn from_releases(store: &ReleaseStore, environment: Option<&str>) -> CanvasResult<Vec<Self>> {
+        let environments = match environment {
+            Some(environment) => vec![environment.to_string()],
+            None => store.environments()?,
+        };
+        let mut contracts: Vec<Self> = Vec::new();
+        for environment in environments {
+            let Some(release) = store.latest(&environment)? else {
+                continue;
+            };
+            if contracts.iter().all(|c| c.address != release.contract_address) {
+                contracts.push(Self {
+                    wasm_bytes: store.artifact(&release)?,
+                    address: release.contract_address,
+                    max_gas: None,
+                });
+            }
+        }
+        Ok(contracts)
+    }
+}
+
+/// What is wrong with a pending call
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum PendingIssue {
+    /// The call would revert
+    Revert { error: String, message: String },
+    /// The call would run out of its gas limit
+    OutOfGas { gas_limit: Gas },
+    /// The call would succeed but use more gas than the contract's bound
+    GasAboveBound { gas_used: Gas, bound: Gas },
+    /// The call could not be made at all, e.g. an unknown function
+    Invalid { message: String },
+}
+
+/// A pending transaction that would not go as its signer expects
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PendingWarning {
+    pub transaction: TransactionHash,
+    pub contract: ContractAddress,
+    pub function: String,
+    pub signer: String,
+    /// Block the call was simulated in
+    pub block_number: u64,
+    pub gas_used: Gas,
+    pub issue: PendingIssue,
+    pub detected_at: DateTime<Utc>,
+}
+
+impl PendingWarning {
+    pub fn message(&self) -> String {
+        let call = format!("Pending {} of {} on {}", self.transaction, self.function, self.contract);
+        match &self.issue {
+            PendingIssue::Revert { error, message } => format!("{} would revert with {}: {}", call, error, message),
+            PendingIssue::OutOfGas { gas_limit } => {
+                format!("{} would run out of its gas limit of {}", call, gas_limit)
+            }
+            PendingIssue::GasAboveBound { gas_used, bound } => {
+                format!("{} would use {} gas, above the bound of {}", call, gas_used, bound)
+            }
+            PendingIssue::Invalid { message } => format!("{} cannot be executed: {}", call, message),
+        }
+    }
+
+    /// POST the warning as JSON to `url`
+    pub fn notify(&self, url: &str) -> CanvasResult<()> {
+        ureq::AgentBuilder::new()
+            .timeout(NOTIFY_TIMEOUT)
+            .build()
+            .post(url)
+            .set(DELIVERY_ID_HEADER, &self.transaction)
+            .send_json(serde_json::to_value(self)?)
+            .map(drop)
+            .map_err(|e| CanvasError::Network(format!("{}: {}", url, e)))
+    }
+}
+
+/// What one [`poll`](MempoolWatcher::poll) did
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct PollReport {
+    /// Transactions to watched contracts seen for the first time
+    pub simulated: usize,
+    pub warnings: Vec<PendingWarning>,
+    /// Transactions that could not be simulated, with the reason; they are
+    /// tried again on the next poll
+    pub errors: Vec<(TransactionHash, String)>,
+}
+
+/// Simulates pending calls to a set of deployed contracts
+pub struct MempoolWatcher {
+    runtime: WasmRuntime,
+    contracts: BTreeMap<ContractAddress, WatchedContract>,
+    /// Hashes already simulated, kept while they are pending
+    seen: HashSet<TransactionHash>,
+}
+
+impl MempoolWatcher {
+    pub fn new(config: &Config, contracts: Vec<WatchedContract>) -> CanvasResult<Self> {
+        Ok(Self {
+            runtime: WasmRuntime::new(config)?,
+            contracts: contracts.into_iter().map(|c| (c.address.clone(), c)).collect(),
+            seen: HashSet::new(),
+        })
+    }
+
+    pub fn contracts(&self) -> impl Iterator<Item = &WatchedContract> {
+        self.contracts.values()
+    }
+
+    /// Simulate a pending transaction in `block`. `None` if it does not call
+    /// a watched contract or would go through within bounds.
+    pub fn check(
+        &self,
+        source: &dyn PendingSource,
+        pending: &SignedTransaction,
+        block: BlockContext,
+    ) -> CanvasResult<Option<PendingWarning>> {
+        let transaction = &pending.transaction;
+        let Some(contract) = self.contracts.get(&transaction.to) else {
+            return Ok(None);
+        };
+        let mut request =
+            SimulationRequest::new(transaction.function.clone(), transaction.args.clone(), transaction.gas_limit);
+        request.set_caller(pending.signer.clone()).set_value(transaction.value).set_block(block);
+        let mut accounts = SandboxAccounts::new()
+            .with_contract(contract.address.clone())
+            .with_balances([(pending.signer.clone(), transaction.value)]);
+        let mut context = ExecutionContext::new(transaction.gas_limit)
+            .with_backend(source.contract_storage(&contract.address)?);
+
+        let (gas_used, issue) =
+            match self.runtime.execute_request_in(&contract.wasm_bytes, &request, &mut accounts, &mut context) {
+                Err(e @ (CanvasError::Validation(_) | CanvasError::NotFound(_))) => {
+                    (0, Some(PendingIssue::Invalid { message: e.to_string() }))
+                }
+                Err(e) => return Err(e),
+                Ok(result) => {
+                    let issue = match result.revert_reason {
+                        Some(reason) if reason.error == OUT_OF_GAS => Some(PendingIssue::OutOfGas {
+                            gas_limit: transaction.gas_limit,
+                        }),
+                        Some(reason) => Some(PendingIssue::Revert {
+                            error: reason.error,
+                            message: reason.message,
+                        }),
+                        None => contract
+                            .max_gas
+                            .filter(|bound| result.gas_used > *bound)
+                            .map(|bound| PendingIssue::GasAboveBound {
+                                gas_used: result.gas_used,
+                                bound,
+                            }),
+                    };
+                    (result.gas_used, issue)
+                }
+            };
+        Ok(issue.map(|issue| PendingWarning {
+            transaction: pending.hash.clone(),
+            contract: contract.address.clone(),
+            function: transaction.function.clone(),
+            signer: pending.signer.clone(),
+            block_number: block.number,
+            gas_used,
+            issue,
+            detected_at: Utc::now(),
+        }))
+    }
+
+    /// Simulate the transactions that joined the node's pool since the last
+    /// poll, in the block after its head
+    pub fn poll(&mut self, source: &dyn PendingSource, now: DateTime<Utc>) -> CanvasResult<PollReport> {
+        let status = source.status()?;
+        let block = BlockContext::new(status.block_number + 1, now.timestamp().max(0) as u64, status.chain_id);
+        let pending = source.pending_transactions()?;
+        // Forget included or dropped transactions, so the set stays the size of the pool
+        let hashes: HashSet<&TransactionHash> = pending.iter().map(|p| &p.hash).collect();
+        self.seen.retain(|hash| hashes.contains(hash));
+
+        let mut report = PollReport::default();
+        for transaction in &pending {
+            if !self.contracts.contains_key(&transaction.transaction.to) || self.seen.contains(&transaction.hash) {
+                continue;
+            }
+            match self.check(source, transaction, block) {
+                Ok(warning) => {
+                    self.seen.insert(transaction.hash.clone());
+                    report.simulated += 1;
+                    report.warnings.extend(warning);
+                }
+                Err(e) => report.errors.push((transaction.hash.clone(), e.to_string())),
+            }
+        }
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        baals::transactions::Transaction,
+        wasm::MemoryBackend,
+    };
+
+    const CONTRACT: &str = r##"(module
+        (import "env" "baals_read_storage" (func $read (param i32 i32 i32) (result i32)))
+        (import "env" "baals_revert" (func $revert (param i32 i32)))
+        (memory (export "memory") 1)
+        (data (i32.const 0) "count")
+        (data (i32.const 16) "locked")
+        (func (export "peek") (result i32)
+            (call $read (i32.const 0) (i32.const 5) (i32.const 128)))
+        (func (export "locked") (call $revert (i32.const 16) (i32.const 6)))
+        (func (export "spin") (loop $forever (br $forever))))"##;
+
+    const VAULT: &str = "0x00000000000000000000000000000000000000aa";
+
+    struct Node {
+        pending: Vec<SignedTransaction>,
+    }
+
+    impl PendingSource for Node {
+        fn status(&self) -> CanvasResult<NodeStatus> {
+            Ok(NodeStatus {
+                chain_id: 1,
+                block_number: 41,
+            })
+        }
+
+        fn pending_transactions(&self) -> CanvasResult<Vec<SignedTransaction>> {
+            Ok(self.pending.clone())
+        }
+
+        fn contract_storage(&self, _contract: &str) -> CanvasResult<SharedStorage> {
+            Ok(shared(MemoryBackend::with_slots([("count".to_string(), serde_json::json!(7))])))
+        }
+    }
+
+    fn pending(hash: &str, to: &str, function: &str, gas_limit: Gas) -> SignedTransaction {
+        SignedTransaction {
+            transaction: Transaction {
+                to: to.to_string(),
+                function: function.to_string(),
+                args: Vec::new(),
+                value: 0,
+                gas_limit,
+                chain: "local".to_string(),
+                nonce: 0,
+                nonce_domain: "direct".to_string(),
+                valid_until: None,
+            },
+            signer: "0x00000000000000000000000000000000000000b0".to_string(),
+            signature: "0x".to_string(),
+            hash: hash.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_poll_warns_once_per_failing_pending_call() {
+        let contract = WatchedContract {
+            address: VAULT.to_string(),
+            wasm_bytes: CONTRACT.as_bytes().to_vec(),
+            max_gas: None,
+        };
+        let mut watcher = MempoolWatcher::new(&Config::default(), vec![contract.clone()]).unwrap();
+        let mut node = Node {
+            pending: vec![
+                pending("0x01", VAULT, "peek", 100_000),
+                pending("0x02", VAULT, "locked", 100_000),
+                pending("0x03", VAULT, "spin", 50_000),
+                pending("0x04", VAULT, "missing", 100_000),
+                pending("0x05", "0x00000000000000000000000000000000000000cc", "locked", 100_000),
+            ],
+        };
+
+        let report = watcher.poll(&node, Utc::now()).unwrap();
+        assert_eq!(report.simulated, 4);
+        assert!(report.errors.is_empty(), "{:?}", report.errors);
+        let issues: BTreeMap<&str, &PendingIssue> =
+            report.warnings.iter().map(|w| (w.transaction.as_str(), &w.issue)).collect();
+        assert_eq!(issues.len(), 3);
+        assert!(matches!(issues["0x02"], PendingIssue::Revert { message, .. } if message == "locked"));
+        assert_eq!(issues["0x03"], &PendingIssue::OutOfGas { gas_limit: 50_000 });
+        assert!(matches!(issues["0x04"], PendingIssue::Invalid { .. }));
+        assert!(report.warnings.iter().all(|w| w.block_number == 42));
+        assert!(report.warnings[0].message().contains(VAULT));
+
+        // Already simulated: nothing new until another transaction arrives
+        assert_eq!(watcher.poll(&node, Utc::now()).unwrap().simulated, 0);
+        node.pending.push(pending("0x06", VAULT, "peek", 100_000));
+        let bounded = WatchedContract {
+            max_gas: Some(1),
+            ..contract
+        };
+        let mut strict = MempoolWatcher::new(&Config::default(), vec![bounded]).unwrap();
+        let report = strict.poll(&node, Utc::now()).unwrap();
+        assert!(report
+            .warnings
+            .iter()
+            .any(|w| w.transaction == "0x06" && matches!(w.issue, PendingIssue::GasAboveBound { bound: 1, .. })));
+    }
+}
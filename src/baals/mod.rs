@@ -1,8 +1,16 @@
 //! BaaLS (Blockchain as a Local Service) integration
 
+pub mod events;
+pub mod impersonation;
+pub mod mempool;
+pub mod signer;
+pub mod transactions;
+pub mod webhooks;
+
 use crate::{
     config::Config,
     error::{CanvasError, CanvasResult},
+    monitoring::budgets::{GasBudgets, SpendKind},
     types::{ContractAddress, TransactionHash, Gas},
 };
 
@@ -11,6 +19,8 @@ pub struct BaalsClient {
     config: Config,
     node_url: String,
     auth_token: Option<String>,
+    /// Team budgets deployments and calls are checked against and debited to
+    budgets: Option<GasBudgets>,
 }
 
 /// Deployment result
@@ -22,6 +32,14 @@ pub struct DeploymentResult {
     pub block_number: u64,
 }
 
+/// What a node reports about itself
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize)]
+pub struct NodeStatus {
+    pub chain_id: u64,
+    #[serde(default)]
+    pub block_number: u64,
+}
+
 /// Transaction result
 #[derive(Debug, Clone)]
 pub struct TransactionResult {
@@ -31,6 +49,26 @@ pub struct TransactionResult {
     pub success: bool,
     pub output: serde_json::Value,
     pub events: Vec<crate::types::Event>,
+    /// Revert reason reported for a failed transaction
+    pub revert_reason: Option<crate::types::RevertReason>,
+}
+
+impl TransactionResult {
+    /// Extract a revert reason from a node response.
+    ///
+    /// BaaLS returns the raw trap payload as hex in `revert_data`; a `revert`
+    /// object is accepted as well for nodes that decode it themselves.
+    pub fn decode_revert_reason(response: &serde_json::Value) -> Option<crate::types::RevertReason> {
+        if let Some(reason) = response.get("revert") {
+            return serde_json::from_value(reason.clone()).ok();
+        }
+        let data = response.get("revert_data")?.as_str()?;
+        let payload = crate::nodes::decode_hex(data)?;
+        match crate::wasm::host::revert(&payload) {
+            CanvasError::Reverted(reason) => Some(reason),
+            _ => None,
+        }
+    }
 }
 
 /// Contract state
@@ -49,17 +87,56 @@ impl BaalsClient {
             config: config.clone(),
             node_url: config.baals.node_url.clone(),
             auth_token: config.baals.auth_token.clone(),
+            budgets: None,
         })
     }
 
+    /// Check deployments and calls against team gas budgets and debit them
+    pub fn with_budgets(mut self, budgets: GasBudgets) -> Self {
+        self.budgets = Some(budgets);
+        self
+    }
+
+    /// Refuse to spend as `signer` once its team's budget is exhausted
+    fn authorize_spend(&self, signer: &dyn signer::Signer) -> CanvasResult<()> {
+        match &self.budgets {
+            Some(budgets) => budgets.authorize(&signer.address()?, &self.config.baals.network.name, chrono::Utc::now()),
+            None => Ok(()),
+        }
+    }
+
+    fn debit_spend(&self, signer: &dyn signer::Signer, kind: SpendKind, target: &str, gas: Gas) -> CanvasResult<()> {
+        let Some(budgets) = &self.budgets else {
+            return Ok(());
+        };
+        let network = &self.config.baals.network.name;
+        for alert in budgets.debit(&signer.address()?, network, kind, target, gas, chrono::Utc::now())? {
+            log::warn!("{}", alert.message);
+        }
+        Ok(())
+    }
+
     /// Deploy a contract
     pub fn deploy_contract(
         &self,
         wasm_bytes: &[u8],
         constructor_args: serde_json::Value,
-        private_key: &str,
+        signer: &dyn signer::Signer,
     ) -> CanvasResult<DeploymentResult> {
-        log::info!("Deploying contract with {} bytes", wasm_bytes.len());
+        log::info!("Deploying contract with {} bytes as {}", wasm_bytes.len(), signer.describe());
+        if signer.impersonated() {
+            impersonation::ensure_local_network(&self.config.baals.network)?;
+        }
+        let code_hash = crate::nodes::encode_hex(&crate::wasm::host::hash(
+            crate::wasm::host::HashAlgorithm::Sha256,
+            wasm_bytes,
+        ));
+        let message = serde_json::to_vec(&serde_json::json!({
+            "deploy": code_hash,
+            "constructor_args": constructor_args,
+        }))?;
+        self.authorize_spend(signer)?;
+        let _signature = signer::sign_with_prompt(signer, "deployment", &message)?;
         
         // TODO: Implement actual contract deployment
         // For now, return a mock deployment result
@@ -71,6 +148,7 @@ impl BaalsClient {
         let transaction_hash = format!("0x{:064x}", rand::random::<u128>());
         let gas_used = wasm_bytes.len() as u64 * 100;
         let block_number = 12345;
+        self.debit_spend(signer, SpendKind::Deploy, &code_hash, gas_used)?;
         
         Ok(DeploymentResult {
             contract_address,
@@ -86,9 +164,20 @@ impl BaalsClient {
         contract_address: &str,
         function_name: &str,
         arguments: Vec<serde_json::Value>,
-        private_key: &str,
+        signer: &dyn signer::Signer,
     ) -> CanvasResult<TransactionResult> {
         log::info!("Calling function '{}' on contract {}", function_name, contract_address);
+        if signer.impersonated() {
+            impersonation::ensure_local_network(&self.config.baals.network)?;
+            log::info!("Acting as {}", signer.describe());
+        }
+        let message = serde_json::to_vec(&serde_json::json!({
+            "to": contract_address,
+            "function": function_name,
+            "arguments": arguments,
+        }))?;
+        self.authorize_spend(signer)?;
+        let _signature = signer::sign_with_prompt(signer, &format!("call to {}", function_name), &message)?;
         
         // TODO: Implement actual contract call
         // For now, return a mock transaction result
@@ -98,6 +187,12 @@ impl BaalsClient {
         let transaction_hash = format!("0x{:064x}", rand::random::<u128>());
         let gas_used = arguments.len() as u64 * 50;
         let block_number = 12346;
+        self.debit_spend(
+            signer,
+            SpendKind::Call,
+            &format!("{}.{}", contract_address, function_name),
+            gas_used,
+        )?;
         
         let output = serde_json::json!({
             "function": function_name,
@@ -120,6 +215,7 @@ impl BaalsClient {
             success: true,
             output,
             events,
+            revert_reason: None,
         })
     }
 
@@ -152,6 +248,59 @@ impl BaalsClient {
         Ok(serde_json::Value::String("mock_storage_value".to_string()))
     }
 
+    /// Pause a contract compiled with the pausable feature.
+    ///
+    /// Only the contract admin (its deployer) can pause; other callers revert.
+    pub fn pause_contract(
+        &self,
+        contract_address: &str,
+        signer: &dyn signer::Signer,
+    ) -> CanvasResult<TransactionResult> {
+        self.call_contract(contract_address, crate::compiler::PAUSE_FUNCTION, Vec::new(), signer)
+    }
+
+    /// Resume a paused contract
+    pub fn unpause_contract(
+        &self,
+        contract_address: &str,
+        signer: &dyn signer::Signer,
+    ) -> CanvasResult<TransactionResult> {
+        self.call_contract(contract_address, crate::compiler::UNPAUSE_FUNCTION, Vec::new(), signer)
+    }
+
+    /// Whether a pausable contract is currently paused
+    pub fn is_paused(&self, contract_address: &str) -> CanvasResult<bool> {
+        let state = self.get_contract_state(contract_address)?;
+        Ok(crate::compiler::is_paused(&state.storage))
+    }
+
+    /// Hash data using the node's hashing host function
+    pub fn hash(&self, algorithm: &str, data: &[u8]) -> CanvasResult<Vec<u8>> {
+        log::info!("Hashing {} bytes with {}", data.len(), algorithm);
+
+        // Hashing is deterministic and the node runs this same host function for
+        // contracts, so computing it locally gives its answer without a round trip
+        let algorithm = crate::wasm::host::HashAlgorithm::from_name(algorithm)
+            .ok_or_else(|| CanvasError::Baals(format!("Unsupported hash algorithm: {}", algorithm)))?;
+        Ok(crate::wasm::host::hash(algorithm, data))
+    }
+
+    /// Verify a signature using the node's signature host function
+    pub fn verify_signature(
+        &self,
+        scheme: &str,
+        public_key: &[u8],
+        message: &[u8],
+        signature: &[u8],
+    ) -> CanvasResult<bool> {
+        log::info!("Verifying {} signature over {} bytes", scheme, message.len());
+
+        // Deterministic, and the same host function the node runs, as for `hash`
+        let scheme = crate::wasm::host::SignatureScheme::from_name(scheme)
+            .ok_or_else(|| CanvasError::Baals(format!("Unsupported signature scheme: {}", scheme)))?;
+        Ok(crate::wasm::host::verify_signature(scheme, public_key, message, signature))
+    }
+
     /// Get transaction status
     pub fn get_transaction_status(&self, transaction_hash: &str) -> CanvasResult<TransactionStatus> {
         log::info!("Getting status for transaction {}", transaction_hash);
@@ -186,6 +335,81 @@ impl BaalsClient {
         })
     }
 
+    /// Ask the node for its chain id and head block
+    pub fn node_status(&self) -> CanvasResult<NodeStatus> {
+        let url = format!("{}/status", self.node_url.trim_end_matches('/'));
+        let mut request = ureq::AgentBuilder::new()
+            .timeout(std::time::Duration::from_secs(self.config.baals.connection_timeout))
+            .build()
+            .get(&url);
+        if let Some(token) = &self.auth_token {
+            request = request.set("Authorization", &format!("Bearer {}", token));
+        }
+        request
+            .call()
+            .map_err(|e| CanvasError::Network(format!("{}: {}", url, e)))?
+            .into_json()
+            .map_err(|e| CanvasError::Baals(format!("Unexpected status response from {}: {}", url, e)))
+    }
+
+    /// Logs a contract emitted in blocks `from_block..=to_block`, oldest first
+    pub fn get_events(&self, contract_address: &str, from_block: u64, to_block: u64) -> CanvasResult<Vec<events::RawEvent>> {
+        let url = format!(
+            "{}/contracts/{}/events?from_block={}&to_block={}",
+            self.node_url.trim_end_matches('/'),
+            contract_address,
+            from_block,
+            to_block
+        );
+        let mut request = ureq::AgentBuilder::new()
+            .timeout(std::time::Duration::from_secs(self.config.baals.connection_timeout))
+            .build()
+            .get(&url);
+        if let Some(token) = &self.auth_token {
+            request = request.set("Authorization", &format!("Bearer {}", token));
+        }
+        request
+            .call()
+            .map_err(|e| CanvasError::Network(format!("{}: {}", url, e)))?
+            .into_json()
+            .map_err(|e| CanvasError::Baals(format!("Unexpected events response from {}: {}", url, e)))
+    }
+
+    /// A transaction by hash, with the meta-transaction it carries if a
+    /// relayer submitted it
+    pub fn get_transaction(&self, transaction_hash: &str) -> CanvasResult<transactions::OnChainTransaction> {
+        let url = format!("{}/transactions/{}", self.node_url.trim_end_matches('/'), transaction_hash);
+        let mut request = ureq::AgentBuilder::new()
+            .timeout(std::time::Duration::from_secs(self.config.baals.connection_timeout))
+            .build()
+            .get(&url);
+        if let Some(token) = &self.auth_token {
+            request = request.set("Authorization", &format!("Bearer {}", token));
+        }
+        request
+            .call()
+            .map_err(|e| CanvasError::Network(format!("{}: {}", url, e)))?
+            .into_json()
+            .map_err(|e| CanvasError::Baals(format!("Unexpected transaction response from {}: {}", url, e)))
+    }
+
+    /// Transactions the node has accepted but not yet included in a block
+    pub fn get_pending_transactions(&self) -> CanvasResult<Vec<transactions::SignedTransaction>> {
+        let url = format!("{}/mempool", self.node_url.trim_end_matches('/'));
+        let mut request = ureq::AgentBuilder::new()
+            .timeout(std::time::Duration::from_secs(self.config.baals.connection_timeout))
+            .build()
+            .get(&url);
+        if let Some(token) = &self.auth_token {
+            request = request.set("Authorization", &format!("Bearer {}", token));
+        }
+        request
+            .call()
+            .map_err(|e| CanvasError::Network(format!("{}: {}", url, e)))?
+            .into_json()
+            .map_err(|e| CanvasError::Baals(format!("Unexpected mempool response from {}: {}", url, e)))
+    }
+
     /// Start local node
     pub fn start_local_node(&self) -> CanvasResult<()> {
         log::info!("Starting local BaaLS node on port {}", self.config.baals.local_node_port);
@@ -304,9 +528,9 @@ mod tests {
         
         let wasm_bytes = b"mock_wasm_bytes";
         let constructor_args = serde_json::json!({"name": "test"});
-        let private_key = "mock_private_key";
+        let signer = signer::LocalSigner::new(ed25519_dalek::SigningKey::from_bytes(&[1u8; 32]));
         
-        let result = client.deploy_contract(wasm_bytes, constructor_args, private_key);
+        let result = client.deploy_contract(wasm_bytes, constructor_args, &signer);
         assert!(result.is_ok());
         
         let result = result.unwrap();
@@ -323,9 +547,9 @@ mod tests {
         let contract_address = "0x1234567890abcdef";
         let function_name = "test_function";
         let arguments = vec![serde_json::Value::String("test".to_string())];
-        let private_key = "mock_private_key";
+        let signer = signer::LocalSigner::new(ed25519_dalek::SigningKey::from_bytes(&[1u8; 32]));
         
-        let result = client.call_contract(contract_address, function_name, arguments, private_key);
+        let result = client.call_contract(contract_address, function_name, arguments, &signer);
         assert!(result.is_ok());
         
         let result = result.unwrap();
@@ -333,6 +557,27 @@ mod tests {
         assert!(!result.transaction_hash.is_empty());
     }
 
+    #[test]
+    fn test_crypto_host_functions() {
+        let config = Config::default();
+        let client = BaalsClient::new(&config).unwrap();
+
+        assert_eq!(client.hash("keccak256", b"data").unwrap().len(), 32);
+        assert!(client.hash("md5", b"data").is_err());
+        assert!(!client.verify_signature("ed25519", &[0u8; 32], b"data", &[0u8; 64]).unwrap());
+    }
+
+    #[test]
+    fn test_revert_reason_decoding() {
+        let reason = crate::types::RevertReason::new("Unauthorized", "not owner");
+        let response = serde_json::json!({
+            "success": false,
+            "revert_data": crate::nodes::encode_hex(&reason.encode()),
+        });
+        assert_eq!(TransactionResult::decode_revert_reason(&response), Some(reason));
+        assert_eq!(TransactionResult::decode_revert_reason(&serde_json::json!({})), None);
+    }
+
     #[test]
     fn test_node_manager() {
         let config = Config::default();
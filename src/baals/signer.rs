@@ -0,0 +1,494 @@
+//! Transaction signers
+//!
+//! Everything that signs for an account — deployments, contract calls,
+//! offline transactions, freeze attestations — goes through [`Signer`], so a
+//! key file and a hardware wallet are interchangeable. [`LocalSigner`] holds
+//! an ed25519 key in memory. [`LedgerSigner`] keeps the key on a Ledger
+//! device and talks to it in APDUs; the key never leaves the device, and
+//! every signature has to be approved on its screen. The account used on the
+//! device comes from the network profile's `derivation_path`.
+//!
+//! Signers are picked by a key spec: a key file path, `ledger` (the network's
+//! derivation path), `ledger:<path>` or, on local networks,
+//! `impersonate:<address>`. The USB transport needs the `ledger` feature; the
+//! APDU protocol itself is always built.
+
+use std::{cell::RefCell, path::Path};
+
+use super::impersonation::ImpersonationSession;
+use crate::{
+    config::Config,
+    error::{CanvasError, CanvasResult},
+    nodes::encode_hex,
+    wasm::host,
+};
+
+/// Account path used when a network profile doesn't set one
+pub const DEFAULT_DERIVATION_PATH: &str = "m/44'/637'/0'/0'/0'";
+
+/// Signs messages for one account
+pub trait Signer: Send {
+    /// Where the key lives, for messages, e.g. `key file deploy.key`
+    fn describe(&self) -> String;
+
+    /// ed25519 public key of the account
+    fn public_key(&self) -> CanvasResult<[u8; 32]>;
+
+    /// 0x-hex public key, which identifies the account
+    fn address(&self) -> CanvasResult<String> {
+        Ok(encode_hex(&self.public_key()?))
+    }
+
+    /// ed25519 signature of `message`
+    fn sign(&self, message: &[u8]) -> CanvasResult<[u8; 64]>;
+
+    /// What to tell the user before signing `message` for `action`, if the
+    /// signature has to be approved on a device
+    fn confirmation_prompt(&self, action: &str, message: &[u8]) -> Option<String> {
+        let _ = (action, message);
+        None
+    }
+
+    /// Whether this signs without a key, for an
+    /// [impersonated](super::impersonation) account
+    fn impersonated(&self) -> bool {
+        false
+    }
+}
+
+/// Short fingerprint of a message, as shown on a hardware wallet's screen
+pub fn message_fingerprint(message: &[u8]) -> String {
+    let digest = host::hash(host::HashAlgorithm::Sha256, message);
+    encode_hex(&digest[..8])
+}
+
+/// Sign `message`, first logging the confirmation prompt if the signer needs one
+pub fn sign_with_prompt(signer: &dyn Signer, action: &str, message: &[u8]) -> CanvasResult<[u8; 64]> {
+    if let Some(prompt) = signer.confirmation_prompt(action, message) {
+        log::warn!("{}", prompt);
+    }
+    signer.sign(message)
+}
+
+/// Key held in memory, read from a key file
+pub struct LocalSigner {
+    key: ed25519_dalek::SigningKey,
+    source: String,
+}
+
+impl LocalSigner {
+    pub fn new(key: ed25519_dalek::SigningKey) -> Self {
+        Self {
+            key,
+            source: "in-memory key".to_string(),
+        }
+    }
+
+    /// Read a 0x-hex seed from a key file
+    pub fn from_file(path: &Path) -> CanvasResult<Self> {
+        let key = crate::deployment::attestations::signing_key_from_hex(&std::fs::read_to_string(path)?)?;
+        Ok(Self {
+            key,
+            source: format!("key file {}", path.display()),
+        })
+    }
+}
+
+impl Signer for LocalSigner {
+    fn describe(&self) -> String {
+        self.source.clone()
+    }
+
+    fn public_key(&self) -> CanvasResult<[u8; 32]> {
+        Ok(self.key.verifying_key().to_bytes())
+    }
+
+    fn sign(&self, message: &[u8]) -> CanvasResult<[u8; 64]> {
+        use ed25519_dalek::Signer as _;
+        Ok(self.key.sign(message).to_bytes())
+    }
+}
+
+const HARDENED: u32 = 0x8000_0000;
+
+/// BIP32 derivation path, e.g. `m/44'/637'/0'/0'/0'`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DerivationPath(Vec<u32>);
+
+impl DerivationPath {
+    /// Parse a path. ed25519 (SLIP-0010) only derives hardened keys, so
+    /// every component must be hardened.
+    pub fn parse(path: &str) -> CanvasResult<Self> {
+        let invalid = |reason: &str| CanvasError::Config(format!("Invalid derivation path '{}': {}", path, reason));
+        let mut components = path.trim().split('/');
+        if components.next() != Some("m") {
+            return Err(invalid("must start with 'm/'"));
+        }
+        let indices = components
+            .map(|component| {
+                let index = component
+                    .strip_suffix('\'')
+                    .or_else(|| component.strip_suffix('h'))
+                    .ok_or_else(|| invalid("ed25519 paths must be fully hardened"))?;
+                match index.parse::<u32>() {
+                    Ok(index) if index < HARDENED => Ok(index | HARDENED),
+                    _ => Err(invalid(&format!("bad component '{}'", component))),
+                }
+            })
+            .collect::<CanvasResult<Vec<_>>>()?;
+        if indices.is_empty() || indices.len() > 10 {
+            return Err(invalid("must have between 1 and 10 components"));
+        }
+        Ok(Self(indices))
+    }
+
+    /// Wire encoding: component count, then each index big-endian
+    pub fn encode(&self) -> Vec<u8> {
+        let mut bytes = vec![self.0.len() as u8];
+        for index in &self.0 {
+            bytes.extend_from_slice(&index.to_be_bytes());
+        }
+        bytes
+    }
+}
+
+impl std::fmt::Display for DerivationPath {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "m")?;
+        for index in &self.0 {
+            write!(f, "/{}'", index & !HARDENED)?;
+        }
+        Ok(())
+    }
+}
+
+/// APDU class of the Canvas Ledger app
+pub const LEDGER_CLA: u8 = 0xE0;
+/// Return the public key of a path
+pub const INS_GET_PUBLIC_KEY: u8 = 0x02;
+/// Sign a message with the key of a path, in chunks
+pub const INS_SIGN: u8 = 0x03;
+/// First chunk of a message (holds the derivation path)
+const P1_FIRST: u8 = 0x00;
+const P1_MORE: u8 = 0x80;
+/// More chunks follow
+const P2_MORE: u8 = 0x80;
+const P2_LAST: u8 = 0x00;
+const MAX_CHUNK: usize = 255;
+
+const SW_OK: u16 = 0x9000;
+const SW_REJECTED: u16 = 0x6985;
+const SW_LOCKED: u16 = 0x5515;
+const SW_WRONG_APP: [u16; 2] = [0x6D00, 0x6E00];
+
+/// Carries APDUs to a Ledger device
+pub trait LedgerTransport: Send {
+    /// Send one command APDU and return the response, status word included
+    fn exchange(&self, apdu: &[u8]) -> CanvasResult<Vec<u8>>;
+}
+
+/// Build a command APDU
+pub fn apdu(ins: u8, p1: u8, p2: u8, data: &[u8]) -> Vec<u8> {
+    let mut apdu = vec![LEDGER_CLA, ins, p1, p2, data.len() as u8];
+    apdu.extend_from_slice(data);
+    apdu
+}
+
+/// Split a response into its data, failing on any status but success
+fn response_data(mut response: Vec<u8>) -> CanvasResult<Vec<u8>> {
+    if response.len() < 2 {
+        return Err(CanvasError::Baals("Ledger sent a truncated response".to_string()));
+    }
+    let status = u16::from_be_bytes([response[response.len() - 2], response[response.len() - 1]]);
+    response.truncate(response.len() - 2);
+    match status {
+        SW_OK => Ok(response),
+        SW_REJECTED => Err(CanvasError::PermissionDenied("Signature rejected on the Ledger".to_string())),
+        SW_LOCKED => Err(CanvasError::InvalidState("Ledger is locked; unlock it with your PIN".to_string())),
+        status if SW_WRONG_APP.contains(&status) => Err(CanvasError::InvalidState(
+            "Open the Canvas app on the Ledger and try again".to_string(),
+        )),
+        status => Err(CanvasError::Baals(format!("Ledger returned status 0x{:04x}", status))),
+    }
+}
+
+/// Account on a Ledger device
+pub struct LedgerSigner {
+    transport: Box<dyn LedgerTransport>,
+    path: DerivationPath,
+    /// Public key, read from the device once
+    public_key: RefCell<Option<[u8; 32]>>,
+}
+
+impl LedgerSigner {
+    pub fn new(transport: Box<dyn LedgerTransport>, path: DerivationPath) -> Self {
+        Self {
+            transport,
+            path,
+            public_key: RefCell::new(None),
+        }
+    }
+
+    /// First Ledger device on USB
+    #[cfg(feature = "ledger")]
+    pub fn connect(path: DerivationPath) -> CanvasResult<Self> {
+        Ok(Self::new(Box::new(hid::HidTransport::open()?), path))
+    }
+
+    #[cfg(not(feature = "ledger"))]
+    pub fn connect(_path: DerivationPath) -> CanvasResult<Self> {
+        Err(CanvasError::Config(
+            "Hardware wallet support is not enabled; rebuild with the `ledger` feature".to_string(),
+        ))
+    }
+
+    pub fn path(&self) -> &DerivationPath {
+        &self.path
+    }
+}
+
+impl Signer for LedgerSigner {
+    fn describe(&self) -> String {
+        format!("Ledger account {}", self.path)
+    }
+
+    fn public_key(&self) -> CanvasResult<[u8; 32]> {
+        if let Some(key) = *self.public_key.borrow() {
+            return Ok(key);
+        }
+        let response = self.transport.exchange(&apdu(INS_GET_PUBLIC_KEY, 0, 0, &self.path.encode()))?;
+        let key: [u8; 32] = response_data(response)?
+            .try_into()
+            .map_err(|_| CanvasError::Baals("Ledger returned a malformed public key".to_string()))?;
+        *self.public_key.borrow_mut() = Some(key);
+        Ok(key)
+    }
+
+    fn sign(&self, message: &[u8]) -> CanvasResult<[u8; 64]> {
+        let mut chunks = vec![self.path.encode()];
+        chunks.extend(message.chunks(MAX_CHUNK).map(<[u8]>::to_vec));
+        let mut response = Vec::new();
+        for (i, chunk) in chunks.iter().enumerate() {
+            let p1 = if i == 0 { P1_FIRST } else { P1_MORE };
+            let p2 = if i + 1 < chunks.len() { P2_MORE } else { P2_LAST };
+            response = response_data(self.transport.exchange(&apdu(INS_SIGN, p1, p2, chunk))?)?;
+        }
+        response
+            .try_into()
+            .map_err(|_| CanvasError::Baals("Ledger returned a malformed signature".to_string()))
+    }
+
+    fn confirmation_prompt(&self, action: &str, message: &[u8]) -> Option<String> {
+        Some(format!(
+            "Confirm the {} on your Ledger ({}): check that it shows {} and press both buttons to sign",
+            action,
+            self.path,
+            message_fingerprint(message)
+        ))
+    }
+}
+
+/// Ledger HID framing: APDUs are split into 64-byte reports
+pub mod framing {
+    use crate::error::{CanvasError, CanvasResult};
+
+    pub const PACKET_SIZE: usize = 64;
+    const CHANNEL: u16 = 0x0101;
+    const TAG_APDU: u8 = 0x05;
+    const HEADER: usize = 5;
+
+    /// Reports carrying `apdu`
+    pub fn frame(apdu: &[u8]) -> Vec<[u8; PACKET_SIZE]> {
+        let mut payload = (apdu.len() as u16).to_be_bytes().to_vec();
+        payload.extend_from_slice(apdu);
+        payload
+            .chunks(PACKET_SIZE - HEADER)
+            .enumerate()
+            .map(|(sequence, chunk)| {
+                let mut packet = [0u8; PACKET_SIZE];
+                packet[..2].copy_from_slice(&CHANNEL.to_be_bytes());
+                packet[2] = TAG_APDU;
+                packet[3..HEADER].copy_from_slice(&(sequence as u16).to_be_bytes());
+                packet[HEADER..HEADER + chunk.len()].copy_from_slice(chunk);
+                packet
+            })
+            .collect()
+    }
+
+    /// Reassemble a response from reports returned by `read`
+    pub fn read(mut read: impl FnMut() -> CanvasResult<[u8; PACKET_SIZE]>) -> CanvasResult<Vec<u8>> {
+        let mut data = Vec::new();
+        let mut length = None;
+        let mut sequence = 0u16;
+        loop {
+            let packet = read()?;
+            if packet[..2] != CHANNEL.to_be_bytes() || packet[2] != TAG_APDU {
+                return Err(CanvasError::Baals("Unexpected HID report from the Ledger".to_string()));
+            }
+            if packet[3..HEADER] != sequence.to_be_bytes() {
+                return Err(CanvasError::Baals("Ledger HID reports arrived out of order".to_string()));
+            }
+            let mut body = &packet[HEADER..];
+            if length.is_none() {
+                length = Some(u16::from_be_bytes([body[0], body[1]]) as usize);
+                body = &body[2..];
+            }
+            let expected = length.unwrap_or_default();
+            let take = body.len().min(expected - data.len());
+            data.extend_from_slice(&body[..take]);
+            if data.len() == expected {
+                return Ok(data);
+            }
+            sequence += 1;
+        }
+    }
+}
+
+#[cfg(feature = "ledger")]
+mod hid {
+    use super::{framing, LedgerTransport};
+    use crate::error::{CanvasError, CanvasResult};
+
+    const LEDGER_VENDOR_ID: u16 = 0x2c97;
+    /// How long to wait for the user to approve on the device
+    const CONFIRMATION_TIMEOUT_MS: i32 = 120_000;
+
+    fn hid_error(e: hidapi::HidError) -> CanvasError {
+        CanvasError::Baals(format!("Ledger HID error: {}", e))
+    }
+
+    pub struct HidTransport {
+        device: hidapi::HidDevice,
+    }
+
+    impl HidTransport {
+        pub fn open() -> CanvasResult<Self> {
+            let api = hidapi::HidApi::new().map_err(hid_error)?;
+            let info = api
+                .device_list()
+                .find(|d| d.vendor_id() == LEDGER_VENDOR_ID && d.interface_number() == 0)
+                .ok_or_else(|| {
+                    CanvasError::NotFound("No Ledger device connected; plug it in and unlock it".to_string())
+                })?;
+            Ok(Self {
+                device: info.open_device(&api).map_err(hid_error)?,
+            })
+        }
+    }
+
+    impl LedgerTransport for HidTransport {
+        fn exchange(&self, apdu: &[u8]) -> CanvasResult<Vec<u8>> {
+            for packet in framing::frame(apdu) {
+                // Leading report ID
+                let mut report = vec![0u8];
+                report.extend_from_slice(&packet);
+                self.device.write(&report).map_err(hid_error)?;
+            }
+            framing::read(|| {
+                let mut packet = [0u8; framing::PACKET_SIZE];
+                let read = self
+                    .device
+                    .read_timeout(&mut packet, CONFIRMATION_TIMEOUT_MS)
+                    .map_err(hid_error)?;
+                if read == 0 {
+                    return Err(CanvasError::Timeout("No answer from the Ledger; was it confirmed?".to_string()));
+                }
+                Ok(packet)
+            })
+        }
+    }
+}
+
+/// Signer for a key spec: `ledger`, `ledger:<derivation path>`,
+/// `impersonate:<address>` or a key file path relative to `root`
+pub fn signer_from_spec(spec: &str, root: &Path, config: &Config) -> CanvasResult<Box<dyn Signer>> {
+    let spec = spec.trim();
+    let network = &config.baals.network;
+    if spec == "ledger" {
+        return Ok(Box::new(LedgerSigner::connect(DerivationPath::parse(&network.derivation_path)?)?));
+    }
+    if let Some(path) = spec.strip_prefix("ledger:") {
+        return Ok(Box::new(LedgerSigner::connect(DerivationPath::parse(path)?)?));
+    }
+    if let Some(address) = spec.strip_prefix("impersonate:") {
+        let session = ImpersonationSession::open(&ImpersonationSession::default_path(config))?;
+        return Ok(Box::new(session.signer(network, address)?));
+    }
+    Ok(Box::new(LocalSigner::from_file(&root.join(spec))?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wasm::host::{verify_signature, SignatureScheme};
+
+    /// Device that answers like the Canvas app, approving or rejecting every signature
+    struct MockLedger {
+        key: ed25519_dalek::SigningKey,
+        approve: bool,
+        message: RefCell<Vec<u8>>,
+    }
+
+    impl LedgerTransport for MockLedger {
+        fn exchange(&self, apdu: &[u8]) -> CanvasResult<Vec<u8>> {
+            // Go through the HID framing as the real transport would
+            let mut packets = framing::frame(apdu).into_iter();
+            let apdu = framing::read(|| Ok(packets.next().unwrap())).unwrap();
+            assert_eq!(apdu[0], LEDGER_CLA);
+            assert_eq!(apdu[4] as usize, apdu.len() - 5);
+            let data = &apdu[5..];
+            let mut response = match (apdu[1], apdu[2], apdu[3]) {
+                (INS_GET_PUBLIC_KEY, ..) => self.key.verifying_key().to_bytes().to_vec(),
+                (INS_SIGN, P1_FIRST, _) => {
+                    assert_eq!(data, DerivationPath::parse(DEFAULT_DERIVATION_PATH).unwrap().encode());
+                    self.message.borrow_mut().clear();
+                    Vec::new()
+                }
+                (INS_SIGN, P1_MORE, P2_MORE) => {
+                    self.message.borrow_mut().extend_from_slice(data);
+                    Vec::new()
+                }
+                (INS_SIGN, P1_MORE, P2_LAST) if self.approve => {
+                    self.message.borrow_mut().extend_from_slice(data);
+                    LocalSigner::new(self.key.clone()).sign(&self.message.borrow()).unwrap().to_vec()
+                }
+                (INS_SIGN, ..) => return Ok(SW_REJECTED.to_be_bytes().to_vec()),
+                _ => return Ok(0x6D00u16.to_be_bytes().to_vec()),
+            };
+            response.extend_from_slice(&SW_OK.to_be_bytes());
+            Ok(response)
+        }
+    }
+
+    fn ledger(approve: bool) -> LedgerSigner {
+        let transport = MockLedger {
+            key: ed25519_dalek::SigningKey::from_bytes(&[9u8; 32]),
+            approve,
+            message: RefCell::new(Vec::new()),
+        };
+        LedgerSigner::new(Box::new(transport), DerivationPath::parse(DEFAULT_DERIVATION_PATH).unwrap())
+    }
+
+    #[test]
+    fn test_ledger_signs_chunked_messages_like_a_local_key() {
+        let path = DerivationPath::parse("m/44'/637'/1'/0'/0'").unwrap();
+        assert_eq!(path.to_string(), "m/44'/637'/1'/0'/0'");
+        assert_eq!(&path.encode()[..5], &[5, 0x80, 0, 0, 44]);
+        assert!(DerivationPath::parse("m/44'/637'/0/0").is_err());
+
+        let signer = ledger(true);
+        let local = LocalSigner::new(ed25519_dalek::SigningKey::from_bytes(&[9u8; 32]));
+        assert_eq!(signer.address().unwrap(), local.address().unwrap());
+
+        // Longer than one APDU and one HID report
+        let message = vec![7u8; 600];
+        let signature = sign_with_prompt(&signer, "deployment", &message).unwrap();
+        assert_eq!(signature, local.sign(&message).unwrap());
+        assert!(verify_signature(SignatureScheme::Ed25519, &signer.public_key().unwrap(), &message, &signature));
+        let prompt = signer.confirmation_prompt("deployment", &message).unwrap();
+        assert!(prompt.contains(&message_fingerprint(&message)));
+        assert!(local.confirmation_prompt("deployment", &message).is_none());
+
+        assert!(matches!(ledger(false).sign(&message), Err(CanvasError::PermissionDenied(_))));
+    }
+}
@@ -0,0 +1,489 @@
+//! Offline-signed transactions and meta-transaction relaying
+//!
+//! A transaction is built and signed without contacting a node, so keys can
+//! stay on an offline machine; the signed transaction is plain JSON. It can
+//! then be handed to a [relayer](crate::config::RelayerConfig), which submits
+//! it on chain and pays the gas. Each relayer keeps its own nonce sequence per
+//! signer (its *nonce domain*), so transactions for different relayers never
+//! race for the same nonce. Relayers are not trusted: the receipt must name
+//! the transaction we signed, the on-chain transaction it points to must carry
+//! exactly that signed transaction, and it must be confirmed in the block it
+//! claims.
+
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+
+use super::{
+    signer::{sign_with_prompt, Signer},
+    BaalsClient, BlockInfo, TransactionState, TransactionStatus,
+};
+use crate::{
+    config::{Config, RelayerConfig},
+    error::{CanvasError, CanvasResult},
+    nodes::{decode_hex, encode_hex},
+    types::{ContractAddress, Gas, TransactionHash},
+    wasm::host::{self, verify_signature, SignatureScheme},
+};
+
+/// Nonce domain of transactions submitted by the signer itself
+pub const DIRECT_NONCE_DOMAIN: &str = "direct";
+/// Nonce store file, under the data directory
+pub const NONCES_FILE: &str = "nonces.json";
+/// Gas limit of a transaction that does not set one
+pub const DEFAULT_TRANSACTION_GAS_LIMIT: Gas = 1_000_000;
+
+/// A contract call, before signing
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Transaction {
+    pub to: ContractAddress,
+    pub function: String,
+    pub args: Vec<serde_json::Value>,
+    /// Encoded as a decimal string, since values can exceed JSON's integer range
+    #[serde(with = "decimal_string")]
+    pub value: u128,
+    pub gas_limit: Gas,
+    /// Network the transaction is meant for, so it can't be replayed elsewhere
+    pub chain: String,
+    pub nonce: u64,
+    /// Relayer name, or [`DIRECT_NONCE_DOMAIN`]
+    pub nonce_domain: String,
+    /// Unix time after which the transaction must not be submitted
+    #[serde(default)]
+    pub valid_until: Option<u64>,
+}
+
+mod decimal_string {
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(value: &u128, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&value.to_string())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u128, D::Error> {
+        String::deserialize(deserializer)?.parse().map_err(D::Error::custom)
+    }
+}
+
+impl Transaction {
+    /// Canonical bytes that are signed: JSON with sorted keys
+    pub fn signing_message(&self) -> CanvasResult<Vec<u8>> {
+        Ok(serde_json::to_vec(&serde_json::to_value(self)?)?)
+    }
+
+    /// Sign offline
+    pub fn sign(self, signer: &dyn Signer) -> CanvasResult<SignedTransaction> {
+        let message = self.signing_message()?;
+        let action = format!("transaction calling {} on {}", self.function, self.to);
+        Ok(SignedTransaction {
+            hash: encode_hex(&host::hash(host::HashAlgorithm::Sha256, &message)),
+            signer: signer.address()?,
+            signature: encode_hex(&sign_with_prompt(signer, &action, &message)?),
+            transaction: self,
+        })
+    }
+}
+
+/// Builds a [`Transaction`]
+#[derive(Debug, Clone)]
+pub struct TransactionBuilder {
+    transaction: Transaction,
+}
+
+impl TransactionBuilder {
+    pub fn new(to: impl Into<ContractAddress>, function: impl Into<String>) -> Self {
+        Self {
+            transaction: Transaction {
+                to: to.into(),
+                function: function.into(),
+                args: Vec::new(),
+                value: 0,
+                gas_limit: DEFAULT_TRANSACTION_GAS_LIMIT,
+                chain: String::new(),
+                nonce: 0,
+                nonce_domain: DIRECT_NONCE_DOMAIN.to_string(),
+                valid_until: None,
+            },
+        }
+    }
+
+    pub fn with_args(mut self, args: Vec<serde_json::Value>) -> Self {
+        self.transaction.args = args;
+        self
+    }
+
+    pub fn with_value(mut self, value: u128) -> Self {
+        self.transaction.value = value;
+        self
+    }
+
+    pub fn with_gas_limit(mut self, gas_limit: Gas) -> Self {
+        self.transaction.gas_limit = gas_limit;
+        self
+    }
+
+    pub fn with_chain(mut self, chain: impl Into<String>) -> Self {
+        self.transaction.chain = chain.into();
+        self
+    }
+
+    pub fn with_valid_until(mut self, valid_until: u64) -> Self {
+        self.transaction.valid_until = Some(valid_until);
+        self
+    }
+
+    /// Submit through `relayer`, using its nonce domain
+    pub fn with_relayer(mut self, relayer: impl Into<String>) -> Self {
+        self.transaction.nonce_domain = relayer.into();
+        self
+    }
+
+    /// Take the signer's next nonce in the transaction's domain
+    pub fn build(mut self, nonces: &mut NonceStore, signer: &str) -> Transaction {
+        self.transaction.nonce = nonces.next(signer, &self.transaction.nonce_domain);
+        self.transaction
+    }
+}
+
+/// A transaction with its signature, as passed between machines
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SignedTransaction {
+    pub transaction: Transaction,
+    /// 0x-hex ed25519 public key
+    pub signer: String,
+    /// 0x-hex ed25519 signature of the transaction's signing message
+    pub signature: String,
+    /// 0x-hex SHA-256 of the signing message; identifies the meta-transaction
+    pub hash: TransactionHash,
+}
+
+impl SignedTransaction {
+    /// Check the hash and signature
+    pub fn verify(&self) -> CanvasResult<()> {
+        let message = self.transaction.signing_message()?;
+        if encode_hex(&host::hash(host::HashAlgorithm::Sha256, &message)) != self.hash {
+            return Err(CanvasError::Validation(format!("Transaction {} does not match its hash", self.hash)));
+        }
+        let signer = decode_hex(&self.signer);
+        let signature = decode_hex(&self.signature);
+        match (signer, signature) {
+            (Some(signer), Some(signature))
+                if verify_signature(SignatureScheme::Ed25519, &signer, &message, &signature) =>
+            {
+                Ok(())
+            }
+            _ => Err(CanvasError::Validation(format!("Transaction {} has a bad signature", self.hash))),
+        }
+    }
+}
+
+/// Next nonce of each signer in each domain
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct NonceStore {
+    /// Keyed by `<signer>@<domain>`
+    nonces: BTreeMap<String, u64>,
+    #[serde(skip)]
+    path: Option<PathBuf>,
+}
+
+impl NonceStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Nonce store file for a configuration
+    pub fn default_path(config: &Config) -> PathBuf {
+        config.app.data_dir.join(NONCES_FILE)
+    }
+
+    /// Load the store from `path`, starting empty if it does not exist yet.
+    /// [`save`](Self::save) writes back to the same file.
+    pub fn open(path: &Path) -> CanvasResult<Self> {
+        let mut store: Self = if path.exists() {
+            serde_json::from_str(&std::fs::read_to_string(path)?)?
+        } else {
+            Self::default()
+        };
+        store.path = Some(path.to_path_buf());
+        Ok(store)
+    }
+
+    pub fn save(&self) -> CanvasResult<()> {
+        let path = self
+            .path
+            .as_ref()
+            .ok_or_else(|| CanvasError::InvalidState("Nonce store was not opened from a file".to_string()))?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    fn key(signer: &str, domain: &str) -> String {
+        format!("{}@{}", signer.to_lowercase(), domain)
+    }
+
+    /// Nonce the next transaction of `signer` in `domain` will use
+    pub fn peek(&self, signer: &str, domain: &str) -> u64 {
+        self.nonces.get(&Self::key(signer, domain)).copied().unwrap_or(0)
+    }
+
+    /// Reserve the next nonce of `signer` in `domain`
+    pub fn next(&mut self, signer: &str, domain: &str) -> u64 {
+        let nonce = self.nonces.entry(Self::key(signer, domain)).or_insert(0);
+        let reserved = *nonce;
+        *nonce += 1;
+        reserved
+    }
+}
+
+/// What a relayer answers for a submitted meta-transaction
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RelayReceipt {
+    pub relayer: String,
+    /// Hash of the meta-transaction the relayer says it relayed
+    pub meta_hash: TransactionHash,
+    /// Hash of the on-chain transaction carrying it
+    pub transaction_hash: TransactionHash,
+}
+
+/// An on-chain transaction as the node reports it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OnChainTransaction {
+    pub hash: TransactionHash,
+    /// Meta-transaction a relayer submitted in this transaction, if any
+    #[serde(default)]
+    pub meta_transaction: Option<SignedTransaction>,
+}
+
+/// Check that a relayer included `signed`: the receipt names it, the
+/// transaction the receipt points to carries it with its signature, and that
+/// transaction is confirmed in the block it reports
+pub fn verify_inclusion(
+    signed: &SignedTransaction,
+    receipt: &RelayReceipt,
+    carrier: &OnChainTransaction,
+    status: &TransactionStatus,
+    block: &BlockInfo,
+) -> CanvasResult<()> {
+    if !receipt.meta_hash.eq_ignore_ascii_case(&signed.hash) {
+        return Err(CanvasError::Validation(format!(
+            "Relayer '{}' reported meta-transaction {}, not {}",
+            receipt.relayer, receipt.meta_hash, signed.hash
+        )));
+    }
+    if !carrier.hash.eq_ignore_ascii_case(&receipt.transaction_hash) {
+        return Err(CanvasError::Validation(format!(
+            "Node returned transaction {} instead of {}",
+            carrier.hash, receipt.transaction_hash
+        )));
+    }
+    // The payload must be our signed transaction, not just any confirmed one
+    let carried = carrier.meta_transaction.as_ref().is_some_and(|meta| {
+        meta.hash.eq_ignore_ascii_case(&signed.hash)
+            && meta.signer.eq_ignore_ascii_case(&signed.signer)
+            && meta.signature.eq_ignore_ascii_case(&signed.signature)
+            && meta.verify().is_ok()
+    });
+    if !carried {
+        return Err(CanvasError::Validation(format!(
+            "Transaction {} relayed by '{}' does not carry meta-transaction {}",
+            receipt.transaction_hash, receipt.relayer, signed.hash
+        )));
+    }
+    if !status.hash.eq_ignore_ascii_case(&receipt.transaction_hash) {
+        return Err(CanvasError::Validation(format!(
+            "Node reported status for {} instead of {}",
+            status.hash, receipt.transaction_hash
+        )));
+    }
+    match status.status {
+        TransactionState::Confirmed => {}
+        TransactionState::Pending => {
+            return Err(CanvasError::InvalidState(format!(
+                "Transaction {} is still pending",
+                receipt.transaction_hash
+            )))
+        }
+        TransactionState::Failed | TransactionState::Reverted => {
+            return Err(CanvasError::Validation(format!(
+                "Transaction {} relayed by '{}' did not succeed ({:?})",
+                receipt.transaction_hash, receipt.relayer, status.status
+            )))
+        }
+    }
+    let included = block.number == status.block_number
+        && block.transactions.iter().any(|hash| hash.eq_ignore_ascii_case(&receipt.transaction_hash));
+    if !included {
+        return Err(CanvasError::Validation(format!(
+            "Transaction {} is not in block {}",
+            receipt.transaction_hash, status.block_number
+        )));
+    }
+    Ok(())
+}
+
+/// Submits signed transactions to a relayer's HTTP API
+pub struct RelayerClient {
+    config: RelayerConfig,
+    agent: ureq::Agent,
+}
+
+impl RelayerClient {
+    pub fn new(config: &RelayerConfig, timeout: Duration) -> Self {
+        Self {
+            config: config.clone(),
+            agent: ureq::AgentBuilder::new().timeout(timeout).build(),
+        }
+    }
+
+    /// Relayer configured under `name`
+    pub fn from_config(config: &Config, name: &str) -> CanvasResult<Self> {
+        let relayer = config
+            .baals
+            .relayers
+            .iter()
+            .find(|r| r.name == name)
+            .ok_or_else(|| CanvasError::Config(format!("No relayer named '{}' is configured", name)))?;
+        Ok(Self::new(relayer, Duration::from_secs(config.baals.connection_timeout)))
+    }
+
+    /// Hand a signed transaction to the relayer
+    pub fn submit(&self, signed: &SignedTransaction) -> CanvasResult<RelayReceipt> {
+        signed.verify()?;
+        if let Some(valid_until) = signed.transaction.valid_until {
+            let now = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_secs();
+            if now > valid_until {
+                return Err(CanvasError::Validation(format!("Transaction {} expired at {}", signed.hash, valid_until)));
+            }
+        }
+        if signed.transaction.nonce_domain != self.config.name {
+            return Err(CanvasError::Validation(format!(
+                "Transaction {} uses nonce domain '{}', not relayer '{}'",
+                signed.hash, signed.transaction.nonce_domain, self.config.name
+            )));
+        }
+
+        let url = format!("{}/relay", self.config.url.trim_end_matches('/'));
+        let mut request = self.agent.post(&url);
+        if let Some(env) = &self.config.api_key_env {
+            let key = std::env::var(env)
+                .map_err(|_| CanvasError::Config(format!("Relayer API key variable {} is not set", env)))?;
+            request = request.set("Authorization", &format!("Bearer {}", key));
+        }
+        let response: serde_json::Value = request
+            .send_json(serde_json::json!({ "transaction": signed }))
+            .map_err(|e| {
+                CanvasError::Network(format!("Relayer '{}' rejected the transaction: {}", self.config.name, e))
+            })?
+            .into_json()?;
+
+        let field = |name: &str| {
+            response.get(name).and_then(|v| v.as_str()).map(str::to_string).ok_or_else(|| {
+                CanvasError::Network(format!("Relayer '{}' response has no '{}'", self.config.name, name))
+            })
+        };
+        Ok(RelayReceipt {
+            relayer: self.config.name.clone(),
+            meta_hash: field("meta_hash")?,
+            transaction_hash: field("transaction_hash")?,
+        })
+    }
+
+    /// Look the relayed transaction up on chain and check it was included
+    pub fn confirm(
+        &self,
+        client: &BaalsClient,
+        signed: &SignedTransaction,
+        receipt: &RelayReceipt,
+    ) -> CanvasResult<TransactionStatus> {
+        let carrier = client.get_transaction(&receipt.transaction_hash)?;
+        let status = client.get_transaction_status(&receipt.transaction_hash)?;
+        let block = client.get_block_info(status.block_number)?;
+        verify_inclusion(signed, receipt, &carrier, &status, &block)?;
+        Ok(status)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sign_offline_and_verify_relayed_inclusion() {
+        let key = crate::baals::signer::LocalSigner::new(ed25519_dalek::SigningKey::from_bytes(&[3u8; 32]));
+        let signer = key.address().unwrap();
+        let mut nonces = NonceStore::new();
+        nonces.next(&signer, DIRECT_NONCE_DOMAIN);
+
+        let transaction = TransactionBuilder::new("0xtoken", "transfer")
+            .with_args(vec![serde_json::json!("0xbob"), serde_json::json!(5)])
+            .with_chain("mainnet")
+            .with_relayer("gasless")
+            .build(&mut nonces, &signer);
+        assert_eq!(transaction.nonce, 0, "relayer domains count separately");
+        assert_eq!(nonces.peek(&signer, "gasless"), 1);
+        assert_eq!(nonces.peek(&signer, DIRECT_NONCE_DOMAIN), 1);
+
+        let signed = transaction.sign(&key).unwrap();
+        let json = serde_json::to_string(&signed).unwrap();
+        let signed: SignedTransaction = serde_json::from_str(&json).unwrap();
+        signed.verify().unwrap();
+        let mut tampered = signed.clone();
+        tampered.transaction.value = 1;
+        assert!(tampered.verify().is_err());
+
+        let receipt = RelayReceipt {
+            relayer: "gasless".to_string(),
+            meta_hash: signed.hash.clone(),
+            transaction_hash: "0xouter".to_string(),
+        };
+        let status = TransactionStatus {
+            hash: "0xouter".to_string(),
+            status: TransactionState::Confirmed,
+            block_number: 9,
+            gas_used: 21_000,
+            confirmations: 1,
+        };
+        let block = BlockInfo {
+            number: 9,
+            hash: "0xb".to_string(),
+            timestamp: 0,
+            transactions: vec!["0xOUTER".to_string()],
+        };
+        let carrier = OnChainTransaction {
+            hash: "0xouter".to_string(),
+            meta_transaction: Some(signed.clone()),
+        };
+        verify_inclusion(&signed, &receipt, &carrier, &status, &block).unwrap();
+
+        let empty = BlockInfo { transactions: Vec::new(), ..block.clone() };
+        assert!(verify_inclusion(&signed, &receipt, &carrier, &status, &empty).is_err());
+        let swapped = RelayReceipt { meta_hash: "0xother".to_string(), ..receipt.clone() };
+        assert!(verify_inclusion(&signed, &swapped, &carrier, &status, &block).is_err());
+        let pending = TransactionStatus { status: TransactionState::Pending, ..status.clone() };
+        assert!(matches!(
+            verify_inclusion(&signed, &receipt, &carrier, &pending, &block),
+            Err(CanvasError::InvalidState(_))
+        ));
+
+        // A relayer naming some other confirmed transaction is caught
+        let unrelated = OnChainTransaction { meta_transaction: None, ..carrier.clone() };
+        assert!(verify_inclusion(&signed, &receipt, &unrelated, &status, &block).is_err());
+        let other = TransactionBuilder::new("0xtoken", "transfer").build(&mut nonces, &signer).sign(&key).unwrap();
+        let decoy = OnChainTransaction { meta_transaction: Some(other), ..carrier.clone() };
+        assert!(verify_inclusion(&signed, &receipt, &decoy, &status, &block).is_err());
+        let mut forged = signed.clone();
+        forged.signature = encode_hex(&[0u8; 64]);
+        let forged = OnChainTransaction { meta_transaction: Some(forged), ..carrier };
+        assert!(verify_inclusion(&signed, &receipt, &forged, &status, &block).is_err());
+    }
+}
@@ -0,0 +1,654 @@
+//! Contract event webhooks
+//!
+//! A webhook rule says "when event X of contract Y fires and its fields match
+//! these filters, POST the decoded event to URL Z". The bridge scans the node
+//! for new logs of each rule's contract, decodes them with the versioned
+//! schemas of [`EventSchemaRegistry`], and queues a delivery per matching
+//! event. Failed deliveries are retried with exponential backoff until they
+//! run out of attempts.
+//!
+//! Each rule keeps the next block it will scan, saved with the bridge, so
+//! events emitted while the bridge was down are delivered when it next runs;
+//! [`replay`](EventWebhooks::replay) rewinds a rule to deliver past events
+//! again. Every delivery has a stable ID so receivers can drop duplicates.
+
+use std::{
+    cmp::Ordering,
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    events::{EventSchemaRegistry, RawEvent},
+    BaalsClient,
+};
+use crate::{
+    config::Config,
+    error::{CanvasError, CanvasResult},
+    types::{ContractAddress, Event},
+};
+
+/// File the rules and their progress are kept in, under the data directory
+pub const EVENT_WEBHOOKS_FILE: &str = "event-webhooks.json";
+/// Attempts before a delivery is given up on
+pub const MAX_DELIVERY_ATTEMPTS: u32 = 8;
+/// Header carrying the delivery ID
+pub const DELIVERY_ID_HEADER: &str = "X-Canvas-Delivery";
+
+/// Delay before the first retry; doubled for every further attempt
+const RETRY_BASE_SECONDS: i64 = 30;
+const MAX_RETRY_SECONDS: i64 = 6 * 3600;
+/// Most blocks fetched from the node in one request
+const MAX_BLOCK_RANGE: u64 = 1000;
+const DELIVERY_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// Comparison of a field filter
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FilterOp {
+    Eq,
+    Ne,
+    Gt,
+    Gte,
+    Lt,
+    Lte,
+    /// Substring of a string field, or element of an array field
+    Contains,
+}
+
+impl FilterOp {
+    /// Operators as written in a filter expression, longest first
+    const SYMBOLS: [(&'static str, FilterOp); 7] = [
+        ("==", FilterOp::Eq),
+        ("!=", FilterOp::Ne),
+        (">=", FilterOp::Gte),
+        ("<=", FilterOp::Lte),
+        (">", FilterOp::Gt),
+        ("<", FilterOp::Lt),
+        ("~", FilterOp::Contains),
+    ];
+}
+
+/// Condition on one field of a decoded event
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FieldFilter {
+    pub field: String,
+    pub op: FilterOp,
+    pub value: serde_json::Value,
+}
+
+impl FieldFilter {
+    /// Parse `field<op>value`, e.g. `amount>=1000` or `to==0xabc`. The value
+    /// is read as JSON when it parses, and as a string otherwise.
+    pub fn parse(expression: &str) -> CanvasResult<Self> {
+        let (index, symbol, op) = FilterOp::SYMBOLS
+            .iter()
+            .filter_map(|(symbol, op)| expression.find(symbol).map(|index| (index, *symbol, *op)))
+            .min_by_key(|(index, symbol, _)| (*index, std::cmp::Reverse(symbol.len())))
+            .ok_or_else(|| {
+                CanvasError::Validation(format!(
+                    "Filter '{}' has no operator (==, !=, >=, <=, >, < or ~)",
+                    expression
+                ))
+            })?;
+        let field = expression[..index].trim();
+        if field.is_empty() {
+            return Err(CanvasError::Validation(format!("Filter '{}' has no field", expression)));
+        }
+        let raw = expression[index + symbol.len()..].trim();
+        let value = serde_json::from_str(raw).unwrap_or_else(|_| serde_json::Value::String(raw.to_string()));
+        Ok(Self {
+            field: field.to_string(),
+            op,
+            value,
+        })
+    }
+
+    /// Whether an event passes the filter; events without the field never do
+    pub fn matches(&self, event: &Event) -> bool {
+        let Some(actual) = event.data.get(&self.field) else {
+            return false;
+        };
+        let ordering = match (actual.as_f64(), self.value.as_f64()) {
+            (Some(a), Some(b)) => a.partial_cmp(&b),
+            _ => match (actual.as_str(), self.value.as_str()) {
+                (Some(a), Some(b)) => Some(a.cmp(b)),
+                _ => None,
+            },
+        };
+        match self.op {
+            FilterOp::Eq => actual == &self.value || ordering == Some(Ordering::Equal),
+            FilterOp::Ne => actual != &self.value && ordering != Some(Ordering::Equal),
+            FilterOp::Gt => ordering == Some(Ordering::Greater),
+            FilterOp::Gte => matches!(ordering, Some(Ordering::Greater | Ordering::Equal)),
+            FilterOp::Lt => ordering == Some(Ordering::Less),
+            FilterOp::Lte => matches!(ordering, Some(Ordering::Less | Ordering::Equal)),
+            FilterOp::Contains => match (actual, &self.value) {
+                (serde_json::Value::String(a), serde_json::Value::String(b)) => a.contains(b.as_str()),
+                (serde_json::Value::Array(items), value) => items.contains(value),
+                _ => false,
+            },
+        }
+    }
+}
+
+/// Deliver an event of a contract to a URL
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WebhookRule {
+    pub id: String,
+    pub contract: ContractAddress,
+    pub event: String,
+    pub url: String,
+    /// Every filter has to match for the event to be delivered
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub filters: Vec<FieldFilter>,
+}
+
+impl WebhookRule {
+    pub fn matches(&self, event: &Event) -> bool {
+        event.name == self.event && self.filters.iter().all(|f| f.matches(event))
+    }
+}
+
+/// A decoded event on its way to a rule's URL
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookDelivery {
+    /// `<rule>:<block>:<log index>`, the same every time the event is delivered
+    pub id: String,
+    pub rule: String,
+    pub url: String,
+    pub contract: ContractAddress,
+    pub block_number: u64,
+    pub event: Event,
+    pub attempts: u32,
+    pub next_attempt: DateTime<Utc>,
+    #[serde(default)]
+    pub last_error: Option<String>,
+}
+
+impl WebhookDelivery {
+    /// Body POSTed to the rule's URL
+    pub fn payload(&self) -> serde_json::Value {
+        serde_json::json!({
+            "delivery": self.id,
+            "rule": self.rule,
+            "contract": self.contract,
+            "block_number": self.block_number,
+            "event": self.event.name,
+            "data": self.event.data,
+        })
+    }
+}
+
+/// Where the bridge reads logs from
+pub trait EventSource {
+    fn head_block(&self) -> CanvasResult<u64>;
+    /// Logs of a contract in blocks `from_block..=to_block`, oldest first
+    fn events(&self, contract: &str, from_block: u64, to_block: u64) -> CanvasResult<Vec<RawEvent>>;
+}
+
+impl EventSource for BaalsClient {
+    fn head_block(&self) -> CanvasResult<u64> {
+        Ok(self.node_status()?.block_number)
+    }
+
+    fn events(&self, contract: &str, from_block: u64, to_block: u64) -> CanvasResult<Vec<RawEvent>> {
+        self.get_events(contract, from_block, to_block)
+    }
+}
+
+/// Where the bridge sends deliveries
+pub trait WebhookSink {
+    fn post(&self, delivery: &WebhookDelivery) -> Result<(), String>;
+}
+
+/// POSTs deliveries as JSON
+pub struct HttpSink {
+    agent: ureq::Agent,
+}
+
+impl HttpSink {
+    pub fn new() -> Self {
+        Self {
+            agent: ureq::AgentBuilder::new().timeout(DELIVERY_TIMEOUT).build(),
+        }
+    }
+}
+
+impl Default for HttpSink {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl WebhookSink for HttpSink {
+    fn post(&self, delivery: &WebhookDelivery) -> Result<(), String> {
+        self.agent
+            .post(&delivery.url)
+            .set(DELIVERY_ID_HEADER, &delivery.id)
+            .send_json(delivery.payload())
+            .map(drop)
+            .map_err(|e| e.to_string())
+    }
+}
+
+/// What one [`sync`](EventWebhooks::sync) did
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SyncReport {
+    /// Deliveries queued for newly seen events
+    pub queued: usize,
+    pub delivered: Vec<String>,
+    /// Deliveries that failed and will be retried
+    pub retrying: Vec<(String, String)>,
+    /// Deliveries that ran out of attempts
+    pub given_up: Vec<(String, String)>,
+    /// Rules whose logs could not be read or decoded, with the reason
+    pub errors: Vec<(String, String)>,
+}
+
+/// Webhook rules, the next block each one scans, and queued deliveries
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct EventWebhooks {
+    rules: Vec<WebhookRule>,
+    next_block: BTreeMap<String, u64>,
+    pending: Vec<WebhookDelivery>,
+    failed: Vec<WebhookDelivery>,
+    #[serde(skip)]
+    path: Option<PathBuf>,
+}
+
+impl EventWebhooks {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Bridge file for a configuration
+    pub fn default_path(config: &Config) -> PathBuf {
+        config.app.data_dir.join(EVENT_WEBHOOKS_FILE)
+    }
+
+    /// Load the bridge from `path`, starting empty if it does not exist yet.
+    /// [`save`](Self::save) writes back to the same file.
+    pub fn open(path: &Path) -> CanvasResult<Self> {
+        let mut bridge: Self = if path.exists() {
+            serde_json::from_str(&std::fs::read_to_string(path)?)?
+        } else {
+            Self::default()
+        };
+        bridge.path = Some(path.to_path_buf());
+        Ok(bridge)
+    }
+
+    pub fn save(&self) -> CanvasResult<()> {
+        let path = self
+            .path
+            .as_ref()
+            .ok_or_else(|| CanvasError::InvalidState("Event webhooks were not opened from a file".to_string()))?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    pub fn rules(&self) -> &[WebhookRule] {
+        &self.rules
+    }
+
+    /// Next block a rule scans
+    pub fn next_block(&self, rule: &str) -> Option<u64> {
+        self.next_block.get(rule).copied()
+    }
+
+    /// Deliveries waiting for their next attempt
+    pub fn pending(&self) -> &[WebhookDelivery] {
+        &self.pending
+    }
+
+    /// Deliveries that ran out of attempts
+    pub fn failed(&self) -> &[WebhookDelivery] {
+        &self.failed
+    }
+
+    /// Add a rule that scans from `from_block` on
+    pub fn add_rule(&mut self, rule: WebhookRule, from_block: u64) -> CanvasResult<()> {
+        if self.rules.iter().any(|r| r.id == rule.id) {
+            return Err(CanvasError::Validation(format!("Webhook rule '{}' already exists", rule.id)));
+        }
+        if !(rule.url.starts_with("http://") || rule.url.starts_with("https://")) {
+            return Err(CanvasError::Validation(format!(
+                "Webhook URL '{}' must be http:// or https://",
+                rule.url
+            )));
+        }
+        self.next_block.insert(rule.id.clone(), from_block);
+        self.rules.push(rule);
+        Ok(())
+    }
+
+    /// Remove a rule and its queued deliveries
+    pub fn remove_rule(&mut self, id: &str) -> bool {
+        let before = self.rules.len();
+        self.rules.retain(|r| r.id != id);
+        self.next_block.remove(id);
+        self.pending.retain(|d| d.rule != id);
+        self.failed.retain(|d| d.rule != id);
+        self.rules.len() != before
+    }
+
+    /// Scan a rule again from `from_block`, delivering past events again
+    pub fn replay(&mut self, id: &str, from_block: u64) -> CanvasResult<()> {
+        if !self.rules.iter().any(|r| r.id == id) {
+            return Err(CanvasError::NotFound(format!("Webhook rule '{}'", id)));
+        }
+        self.next_block.insert(id.to_string(), from_block);
+        Ok(())
+    }
+
+    /// Queue the given-up deliveries again with fresh attempts
+    pub fn retry_failed(&mut self, now: DateTime<Utc>) -> usize {
+        let count = self.failed.len();
+        for mut delivery in self.failed.drain(..) {
+            delivery.attempts = 0;
+            delivery.next_attempt = now;
+            self.pending.push(delivery);
+        }
+        count
+    }
+
+    /// Scan every rule's contract up to the node's head block and queue a
+    /// delivery for each matching event
+    pub fn poll(
+        &mut self,
+        source: &dyn EventSource,
+        schemas: &EventSchemaRegistry,
+        now: DateTime<Utc>,
+        report: &mut SyncReport,
+    ) {
+        let head = match source.head_block() {
+            Ok(head) => head,
+            Err(e) => {
+                report.errors.push(("*".to_string(), e.to_string()));
+                return;
+            }
+        };
+        for rule in &self.rules {
+            let mut from = self.next_block.get(&rule.id).copied().unwrap_or(0);
+            while from <= head {
+                let to = head.min(from.saturating_add(MAX_BLOCK_RANGE - 1));
+                let logs = match source.events(&rule.contract, from, to) {
+                    Ok(logs) => logs,
+                    Err(e) => {
+                        report.errors.push((rule.id.clone(), e.to_string()));
+                        break;
+                    }
+                };
+                let mut index_in_block: BTreeMap<u64, usize> = BTreeMap::new();
+                for raw in &logs {
+                    let index = index_in_block.entry(raw.block_number).or_insert(0);
+                    let id = format!("{}:{}:{}", rule.id, raw.block_number, index);
+                    *index += 1;
+                    if raw.name != rule.event {
+                        continue;
+                    }
+                    let event = match schemas.decode(raw) {
+                        Ok(event) => event,
+                        Err(e) => {
+                            report.errors.push((rule.id.clone(), e.to_string()));
+                            continue;
+                        }
+                    };
+                    if !rule.matches(&event) || self.pending.iter().any(|d| d.id == id) {
+                        continue;
+                    }
+                    self.pending.push(WebhookDelivery {
+                        id,
+                        rule: rule.id.clone(),
+                        url: rule.url.clone(),
+                        contract: rule.contract.clone(),
+                        block_number: raw.block_number,
+                        event,
+                        attempts: 0,
+                        next_attempt: now,
+                        last_error: None,
+                    });
+                    report.queued += 1;
+                }
+                from = to + 1;
+                self.next_block.insert(rule.id.clone(), from);
+            }
+        }
+    }
+
+    /// Attempt every delivery that is due, oldest first
+    pub fn deliver(&mut self, sink: &dyn WebhookSink, now: DateTime<Utc>, report: &mut SyncReport) {
+        let mut waiting = Vec::new();
+        for mut delivery in std::mem::take(&mut self.pending) {
+            if delivery.next_attempt > now {
+                waiting.push(delivery);
+                continue;
+            }
+            delivery.attempts += 1;
+            match sink.post(&delivery) {
+                Ok(()) => report.delivered.push(delivery.id),
+                Err(e) if delivery.attempts >= MAX_DELIVERY_ATTEMPTS => {
+                    report.given_up.push((delivery.id.clone(), e.clone()));
+                    delivery.last_error = Some(e);
+                    self.failed.push(delivery);
+                }
+                Err(e) => {
+                    let backoff = RETRY_BASE_SECONDS
+                        .saturating_mul(1 << (delivery.attempts - 1).min(20))
+                        .min(MAX_RETRY_SECONDS);
+                    delivery.next_attempt = now + Duration::seconds(backoff);
+                    report.retrying.push((delivery.id.clone(), e.clone()));
+                    delivery.last_error = Some(e);
+                    waiting.push(delivery);
+                }
+            }
+        }
+        self.pending = waiting;
+    }
+
+    /// Poll for new events, attempt due deliveries and save the bridge
+    pub fn sync(
+        &mut self,
+        source: &dyn EventSource,
+        schemas: &EventSchemaRegistry,
+        sink: &dyn WebhookSink,
+        now: DateTime<Utc>,
+    ) -> CanvasResult<SyncReport> {
+        let mut report = SyncReport::default();
+        self.poll(source, schemas, now, &mut report);
+        self.deliver(sink, now, &mut report);
+        self.save()?;
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{ContractABI, EventABI, ParameterABI, ValueType};
+    use std::{cell::RefCell, collections::HashMap};
+
+    struct Node {
+        head: u64,
+        logs: Vec<RawEvent>,
+    }
+
+    impl EventSource for Node {
+        fn head_block(&self) -> CanvasResult<u64> {
+            Ok(self.head)
+        }
+
+        fn events(&self, contract: &str, from_block: u64, to_block: u64) -> CanvasResult<Vec<RawEvent>> {
+            Ok(self
+                .logs
+                .iter()
+                .filter(|l| l.contract == contract && (from_block..=to_block).contains(&l.block_number))
+                .cloned()
+                .collect())
+        }
+    }
+
+    /// Records deliveries, failing while `down` is set
+    #[derive(Default)]
+    struct Receiver {
+        down: RefCell<bool>,
+        received: RefCell<Vec<serde_json::Value>>,
+    }
+
+    impl WebhookSink for Receiver {
+        fn post(&self, delivery: &WebhookDelivery) -> Result<(), String> {
+            if *self.down.borrow() {
+                return Err("connection refused".to_string());
+            }
+            self.received.borrow_mut().push(delivery.payload());
+            Ok(())
+        }
+    }
+
+    fn transfer(block_number: u64, amount: i64) -> RawEvent {
+        RawEvent {
+            contract: "0xtoken".to_string(),
+            block_number,
+            name: "Transfer".to_string(),
+            topics: vec![serde_json::json!("0xbob")],
+            data: vec![serde_json::json!(amount)],
+        }
+    }
+
+    fn schemas() -> EventSchemaRegistry {
+        let mut registry = EventSchemaRegistry::new();
+        let abi = ContractABI {
+            functions: Vec::new(),
+            events: vec![EventABI {
+                name: "Transfer".to_string(),
+                inputs: vec![
+                    ParameterABI {
+                        name: "to".to_string(),
+                        value_type: ValueType::String,
+                        indexed: true,
+                    },
+                    ParameterABI {
+                        name: "amount".to_string(),
+                        value_type: ValueType::Integer,
+                        indexed: false,
+                    },
+                ],
+                anonymous: false,
+            }],
+            errors: Vec::new(),
+            storage: Vec::new(),
+            metadata: HashMap::new(),
+        };
+        registry.register("0xtoken", &abi, 0).unwrap();
+        registry
+    }
+
+    #[test]
+    fn test_filter_parsing() {
+        let filter = FieldFilter::parse("amount >= 100").unwrap();
+        assert_eq!((filter.field.as_str(), filter.op), ("amount", FilterOp::Gte));
+        assert_eq!(filter.value, serde_json::json!(100));
+        assert_eq!(FieldFilter::parse("to==0xbob").unwrap().value, serde_json::json!("0xbob"));
+        assert_eq!(FieldFilter::parse("memo~refund").unwrap().op, FilterOp::Contains);
+        assert!(FieldFilter::parse("amount").is_err());
+        assert!(FieldFilter::parse("==5").is_err());
+    }
+
+    #[test]
+    fn test_filters_retries_and_replay_after_downtime() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(EVENT_WEBHOOKS_FILE);
+        let schemas = schemas();
+        let mut bridge = EventWebhooks::open(&path).unwrap();
+        bridge
+            .add_rule(
+                WebhookRule {
+                    id: "big-transfers".to_string(),
+                    contract: "0xtoken".to_string(),
+                    event: "Transfer".to_string(),
+                    url: "https://hooks.example.com/transfers".to_string(),
+                    filters: vec![FieldFilter::parse("amount>=100").unwrap()],
+                },
+                1,
+            )
+            .unwrap();
+
+        let receiver = Receiver::default();
+        let now = Utc::now();
+        let mut node = Node {
+            head: 2,
+            logs: vec![transfer(1, 500), transfer(2, 5)],
+        };
+        let report = bridge.sync(&node, &schemas, &receiver, now).unwrap();
+        assert_eq!(report.delivered, vec!["big-transfers:1:0".to_string()]);
+        assert_eq!(receiver.received.borrow()[0]["data"]["amount"], 500);
+
+        // The receiver is down when the next event fires, so it is retried later
+        node.head = 4;
+        node.logs.push(transfer(4, 250));
+        *receiver.down.borrow_mut() = true;
+        let report = bridge.sync(&node, &schemas, &receiver, now).unwrap();
+        assert_eq!(report.retrying.len(), 1);
+        assert_eq!(bridge.next_block("big-transfers"), Some(5));
+
+        // Progress and the queue survive a restart of the bridge
+        let mut bridge = EventWebhooks::open(&path).unwrap();
+        *receiver.down.borrow_mut() = false;
+        assert!(bridge.sync(&node, &schemas, &receiver, now).unwrap().delivered.is_empty());
+        let later = now + Duration::seconds(RETRY_BASE_SECONDS);
+        let report = bridge.sync(&node, &schemas, &receiver, later).unwrap();
+        assert_eq!(report.delivered, vec!["big-transfers:4:0".to_string()]);
+
+        bridge.replay("big-transfers", 0).unwrap();
+        let report = bridge.sync(&node, &schemas, &receiver, later).unwrap();
+        assert_eq!(report.delivered, vec!["big-transfers:1:0".to_string(), "big-transfers:4:0".to_string()]);
+        assert!(bridge.replay("missing", 0).is_err());
+    }
+
+    #[test]
+    fn test_gives_up_after_max_attempts() {
+        let mut bridge = EventWebhooks::new();
+        let schemas = schemas();
+        bridge
+            .add_rule(
+                WebhookRule {
+                    id: "all".to_string(),
+                    contract: "0xtoken".to_string(),
+                    event: "Transfer".to_string(),
+                    url: "http://localhost:9/hook".to_string(),
+                    filters: Vec::new(),
+                },
+                0,
+            )
+            .unwrap();
+        let node = Node {
+            head: 1,
+            logs: vec![transfer(1, 1)],
+        };
+        let receiver = Receiver::default();
+        *receiver.down.borrow_mut() = true;
+
+        let mut report = SyncReport::default();
+        let mut now = Utc::now();
+        bridge.poll(&node, &schemas, now, &mut report);
+        for _ in 0..MAX_DELIVERY_ATTEMPTS {
+            bridge.deliver(&receiver, now, &mut report);
+            now += Duration::seconds(MAX_RETRY_SECONDS);
+        }
+        assert_eq!(report.given_up.len(), 1);
+        assert_eq!(bridge.failed().len(), 1);
+        assert!(bridge.pending().is_empty());
+
+        assert_eq!(bridge.retry_failed(now), 1);
+        *receiver.down.borrow_mut() = false;
+        bridge.deliver(&receiver, now, &mut report);
+        assert_eq!(report.delivered.len(), 1);
+    }
+}
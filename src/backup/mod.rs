@@ -0,0 +1,285 @@
+//! Backup and restore of platform state
+//!
+//! A backup is a single versioned JSON archive of named entries, each stored
+//! as hex with its SHA-256 so corruption is caught before anything is
+//! restored. It holds the configuration, community and marketplace data,
+//! everything under the data directory (installed nodes, rule packs, the
+//! event schema registry) and the workspace's environment and release
+//! records.
+
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{
+    community::CommunityManager,
+    config::Config,
+    deployment::environments::{ENVIRONMENTS_FILE, RELEASES_DIR},
+    error::{CanvasError, CanvasResult},
+    marketplace::LocalMarketplace,
+    nodes::{decode_hex, encode_hex},
+    wasm::host,
+};
+
+/// Version of the archive layout written by this build
+pub const BACKUP_FORMAT_VERSION: u32 = 1;
+
+const CONFIG_ENTRY: &str = "config.json";
+const COMMUNITY_ENTRY: &str = "community.json";
+const MARKETPLACE_ENTRY: &str = "marketplace.json";
+const DATA_PREFIX: &str = "data/";
+const WORKSPACE_PREFIX: &str = "workspace/";
+
+/// One archived file or document
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupEntry {
+    /// SHA-256 of the content, 0x-hex
+    pub sha256: String,
+    /// Content, 0x-hex
+    pub content: String,
+}
+
+/// A complete backup
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupArchive {
+    pub format_version: u32,
+    /// Version of the tool that wrote the archive
+    pub app_version: String,
+    pub created_at: u64,
+    pub entries: BTreeMap<String, BackupEntry>,
+}
+
+fn sha256(bytes: &[u8]) -> String {
+    encode_hex(&host::hash(host::HashAlgorithm::Sha256, bytes))
+}
+
+/// Files under `dir`, relative to it, sorted
+pub(crate) fn files_under(dir: &Path) -> CanvasResult<Vec<PathBuf>> {
+    let mut found = Vec::new();
+    if !dir.exists() {
+        return Ok(found);
+    }
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(current) = pending.pop() {
+        for entry in std::fs::read_dir(&current)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                pending.push(path);
+            } else if let Ok(relative) = path.strip_prefix(dir) {
+                found.push(relative.to_path_buf());
+            }
+        }
+    }
+    found.sort();
+    Ok(found)
+}
+
+impl BackupArchive {
+    pub fn new() -> Self {
+        Self {
+            format_version: BACKUP_FORMAT_VERSION,
+            app_version: crate::VERSION.to_string(),
+            created_at: std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_secs(),
+            entries: BTreeMap::new(),
+        }
+    }
+
+    pub fn add_bytes(&mut self, name: impl Into<String>, bytes: &[u8]) {
+        self.entries.insert(
+            name.into(),
+            BackupEntry {
+                sha256: sha256(bytes),
+                content: encode_hex(bytes),
+            },
+        );
+    }
+
+    pub fn add_json<T: Serialize>(&mut self, name: impl Into<String>, value: &T) -> CanvasResult<()> {
+        self.add_bytes(name, &serde_json::to_vec(value)?);
+        Ok(())
+    }
+
+    /// Add every file under `dir`, named `prefix` + its relative path
+    pub fn add_dir(&mut self, prefix: &str, dir: &Path) -> CanvasResult<usize> {
+        let files = files_under(dir)?;
+        for relative in &files {
+            let name = format!("{}{}", prefix, relative.to_string_lossy().replace('\\', "/"));
+            self.add_bytes(name, &std::fs::read(dir.join(relative))?);
+        }
+        Ok(files.len())
+    }
+
+    /// Content of an entry, checked against its hash
+    pub fn bytes(&self, name: &str) -> CanvasResult<Vec<u8>> {
+        let entry = self
+            .entries
+            .get(name)
+            .ok_or_else(|| CanvasError::NotFound(format!("Backup entry '{}'", name)))?;
+        let bytes = decode_hex(&entry.content)
+            .ok_or_else(|| CanvasError::Validation(format!("Backup entry '{}' is not valid hex", name)))?;
+        if sha256(&bytes) != entry.sha256 {
+            return Err(CanvasError::Validation(format!("Backup entry '{}' is corrupted", name)));
+        }
+        Ok(bytes)
+    }
+
+    pub fn json<T: DeserializeOwned>(&self, name: &str) -> CanvasResult<Option<T>> {
+        if !self.entries.contains_key(name) {
+            return Ok(None);
+        }
+        Ok(Some(serde_json::from_slice(&self.bytes(name)?)?))
+    }
+
+    /// Check the format version and every entry's hash
+    pub fn verify(&self) -> CanvasResult<()> {
+        if self.format_version > BACKUP_FORMAT_VERSION {
+            return Err(CanvasError::Validation(format!(
+                "Backup format v{} is newer than the supported v{}; upgrade before restoring",
+                self.format_version, BACKUP_FORMAT_VERSION
+            )));
+        }
+        for name in self.entries.keys() {
+            self.bytes(name)?;
+        }
+        Ok(())
+    }
+
+    /// Write the entries named `prefix`... back as files under `dir`
+    pub fn restore_dir(&self, prefix: &str, dir: &Path) -> CanvasResult<usize> {
+        let mut restored = 0;
+        for name in self.entries.keys().filter(|n| n.starts_with(prefix)) {
+            let relative = Path::new(&name[prefix.len()..]);
+            if relative.components().any(|c| !matches!(c, std::path::Component::Normal(_))) {
+                return Err(CanvasError::Validation(format!("Backup entry '{}' escapes its directory", name)));
+            }
+            let path = dir.join(relative);
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::write(path, self.bytes(name)?)?;
+            restored += 1;
+        }
+        Ok(restored)
+    }
+
+    pub fn write(&self, path: &Path) -> CanvasResult<()> {
+        std::fs::write(path, serde_json::to_vec(self)?)?;
+        Ok(())
+    }
+
+    pub fn read(path: &Path) -> CanvasResult<Self> {
+        Ok(serde_json::from_slice(&std::fs::read(path)?)?)
+    }
+}
+
+impl Default for BackupArchive {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// State to back up; parts left unset are not included
+pub struct PlatformState<'a> {
+    pub config: &'a Config,
+    pub community: Option<&'a CommunityManager>,
+    pub marketplace: Option<&'a LocalMarketplace>,
+    /// Workspace root holding environments and release records
+    pub workspace: Option<&'a Path>,
+}
+
+/// Archive the platform state
+pub fn create_backup(state: &PlatformState<'_>) -> CanvasResult<BackupArchive> {
+    let mut archive = BackupArchive::new();
+    archive.add_json(CONFIG_ENTRY, state.config)?;
+    if let Some(community) = state.community {
+        archive.add_json(COMMUNITY_ENTRY, community)?;
+    }
+    if let Some(marketplace) = state.marketplace {
+        archive.add_json(MARKETPLACE_ENTRY, marketplace)?;
+    }
+    let data_files = archive.add_dir(DATA_PREFIX, &state.config.app.data_dir)?;
+    log::info!("Backed up {} files from {}", data_files, state.config.app.data_dir.display());
+
+    if let Some(root) = state.workspace {
+        let environments = root.join(ENVIRONMENTS_FILE);
+        if environments.exists() {
+            archive.add_bytes(format!("{}{}", WORKSPACE_PREFIX, ENVIRONMENTS_FILE), &std::fs::read(environments)?);
+        }
+        archive.add_dir(&format!("{}{}/", WORKSPACE_PREFIX, RELEASES_DIR), &root.join(RELEASES_DIR))?;
+    }
+    Ok(archive)
+}
+
+/// State recovered from a backup
+pub struct RestoredState {
+    pub config: Config,
+    pub community: Option<CommunityManager>,
+    pub marketplace: Option<LocalMarketplace>,
+    /// Files written to the data directory and workspace
+    pub files: usize,
+}
+
+/// Verify an archive, then write its files to the restored config's data
+/// directory (and `workspace`, if given) and decode the rest
+pub fn restore_backup(archive: &BackupArchive, workspace: Option<&Path>) -> CanvasResult<RestoredState> {
+    archive.verify()?;
+    let config: Config = archive
+        .json(CONFIG_ENTRY)?
+        .ok_or_else(|| CanvasError::Validation("Backup has no configuration".to_string()))?;
+
+    let mut files = archive.restore_dir(DATA_PREFIX, &config.app.data_dir)?;
+    if let Some(root) = workspace {
+        files += archive.restore_dir(WORKSPACE_PREFIX, root)?;
+    }
+    Ok(RestoredState {
+        community: archive.json(COMMUNITY_ENTRY)?,
+        marketplace: archive.json(MARKETPLACE_ENTRY)?,
+        config,
+        files,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip_and_corruption() {
+        let source = tempfile::tempdir().unwrap();
+        let mut config = Config::default();
+        config.app.data_dir = source.path().join("data");
+        std::fs::create_dir_all(config.app.data_dir.join("nodes")).unwrap();
+        std::fs::write(config.app.data_dir.join("nodes/counter.json"), b"{}").unwrap();
+        std::fs::write(source.path().join(ENVIRONMENTS_FILE), b"[[environment]]").unwrap();
+
+        let state = PlatformState {
+            config: &config,
+            community: Some(&CommunityManager::new()),
+            marketplace: None,
+            workspace: Some(source.path()),
+        };
+        let archive = create_backup(&state).unwrap();
+        let path = source.path().join("backup.json");
+        archive.write(&path).unwrap();
+
+        let target = tempfile::tempdir().unwrap();
+        let mut archive = BackupArchive::read(&path).unwrap();
+        let mut moved = config.clone();
+        moved.app.data_dir = target.path().join("data");
+        archive.add_json(CONFIG_ENTRY, &moved).unwrap();
+        let restored = restore_backup(&archive, Some(&target.path().join("workspace"))).unwrap();
+        assert_eq!(restored.files, 2);
+        assert!(restored.community.is_some() && restored.marketplace.is_none());
+        assert_eq!(std::fs::read(target.path().join("data/nodes/counter.json")).unwrap(), b"{}");
+        assert!(target.path().join("workspace").join(ENVIRONMENTS_FILE).exists());
+
+        archive.entries.get_mut(COMMUNITY_ENTRY).unwrap().content = encode_hex(b"{\"users\":{}}");
+        assert!(archive.verify().unwrap_err().to_string().contains("corrupted"));
+    }
+}
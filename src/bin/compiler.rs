@@ -4,6 +4,7 @@ use canvas_contracts::{
     compiler::Compiler,
     config::Config,
     error::CanvasResult,
+    nodes::load_graph,
     types::VisualGraph,
 };
 use clap::{Parser, Subcommand};
@@ -63,16 +64,24 @@ fn main() -> CanvasResult<()> {
 fn compile_graph(input: &PathBuf, output: &PathBuf, optimize: u8, config: &Config) -> CanvasResult<()> {
     println!("Compiling graph from {} to {}", input.display(), output.display());
     
-    // TODO: Load graph from file
-    let graph = VisualGraph::new("test");
+    let (graph, _) = load_graph(&std::fs::read_to_string(input)?)?;
     
-    let compiler = Compiler::new(config)?;
+    let mut config = config.clone();
+    config.compiler.optimization_level = optimize;
+    let compiler = Compiler::new(&config)?;
     let result = compiler.compile(&graph)?;
     
-    // TODO: Write WASM to output file
+    std::fs::write(output, &result.wasm_bytes)?;
+    std::fs::write(output.with_extension("abi.json"), serde_json::to_string_pretty(&result.abi)?)?;
     println!("Compilation successful!");
     println!("WASM size: {} bytes", result.wasm_bytes.len());
     println!("Gas estimate: {}", result.gas_estimate);
+    for estimate in &result.gas_breakdown {
+        println!(
+            "  {}: worst case {}, average {}",
+            estimate.function, estimate.worst_case, estimate.average_case
+        );
+    }
     
     Ok(())
 }
@@ -43,6 +43,10 @@ enum Commands {
         #[arg(short, long)]
         input: PathBuf,
         
+        /// Exported function to target (defaults to the whole contract)
+        #[arg(short, long)]
+        function: Option<String>,
+        
         /// Input data (JSON)
         #[arg(short, long)]
         data: String,
@@ -70,8 +74,8 @@ fn main() -> CanvasResult<()> {
         Commands::Execute { input, function, data, gas_limit } => {
             execute_contract(&input, &function, &data, gas_limit, &config)?;
         }
-        Commands::Simulate { input, data, gas_limit } => {
-            simulate_contract(&input, &data, gas_limit, &config)?;
+        Commands::Simulate { input, function, data, gas_limit } => {
+            simulate_contract(&input, function.as_deref(), &data, gas_limit, &config)?;
         }
         Commands::Validate { input } => {
             validate_module(&input, &config)?;
@@ -84,8 +88,7 @@ fn main() -> CanvasResult<()> {
 fn execute_contract(input: &PathBuf, function: &str, data: &str, gas_limit: u64, config: &Config) -> CanvasResult<()> {
     println!("Executing function '{}' from {}", function, input.display());
     
-    // TODO: Load WASM from file
-    let wasm_bytes = b"mock_wasm_bytes";
+    let wasm_bytes = &std::fs::read(input)?;
     
     let runtime = WasmRuntime::new(config)?;
     let arguments = serde_json::from_str(data)?;
@@ -100,16 +103,25 @@ fn execute_contract(input: &PathBuf, function: &str, data: &str, gas_limit: u64,
     Ok(())
 }
 
-fn simulate_contract(input: &PathBuf, data: &str, gas_limit: u64, config: &Config) -> CanvasResult<()> {
+fn simulate_contract(input: &PathBuf, function: Option<&str>, data: &str, gas_limit: u64, config: &Config) -> CanvasResult<()> {
     println!("Simulating contract from {}", input.display());
     
-    // TODO: Load WASM from file
-    let wasm_bytes = b"mock_wasm_bytes";
+    let wasm_bytes = &std::fs::read(input)?;
     
     let runtime = WasmRuntime::new(config)?;
-    let input_data = serde_json::from_str(data)?;
-    
-    let result = runtime.simulate(wasm_bytes, input_data, gas_limit)?;
+    let input_data: serde_json::Value = serde_json::from_str(data)?;
+    
+    let result = match function {
+        Some(function) => {
+            let arguments = match input_data {
+                serde_json::Value::Array(items) => items,
+                serde_json::Value::Null => Vec::new(),
+                other => vec![other],
+            };
+            runtime.execute_function(wasm_bytes, function, arguments, gas_limit)?
+        }
+        None => runtime.simulate(wasm_bytes, input_data, gas_limit)?,
+    };
     
     println!("Simulation successful!");
     println!("Gas used: {}", result.gas_used);
@@ -126,8 +138,7 @@ fn simulate_contract(input: &PathBuf, data: &str, gas_limit: u64, config: &Confi
 fn validate_module(input: &PathBuf, config: &Config) -> CanvasResult<()> {
     println!("Validating WASM module from {}", input.display());
     
-    // TODO: Load WASM from file
-    let wasm_bytes = b"mock_wasm_bytes";
+    let wasm_bytes = &std::fs::read(input)?;
     
     let runtime = WasmRuntime::new(config)?;
     runtime.validate_module(wasm_bytes)?;
@@ -0,0 +1,431 @@
+//! Portable project bundles
+//!
+//! A `.canvasbundle` holds everything needed to rebuild a project elsewhere:
+//! its graphs and their review annotations, the installed custom nodes they
+//! use (with their WASM modules), the project's templates and ABIs, and a build manifest recording the tool
+//! version and compiler settings. Files are stored in the same hash-checked
+//! form as backups, so a bundle attached to a bug report can be verified
+//! before it is unpacked.
+//!
+//! Templates sold without their full logic can be exported obfuscated; see
+//! [`obfuscate`].
+
+mod obfuscate;
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    annotations::annotations_path,
+    backup::{files_under, BackupArchive},
+    compiler::Workspace,
+    config::{CompilerConfig, Config},
+    error::{CanvasError, CanvasResult},
+    nodes::{
+        builtin_node_definitions,
+        custom::{CustomNodeDefinition, CustomNodeImplementation, CustomNodeRegistry},
+    },
+    types::VisualGraph,
+};
+
+pub use obfuscate::{compile_composite, obfuscate_graph, ObfuscationOptions, LABEL_KEYS};
+
+/// File extension of project bundles
+pub const BUNDLE_EXTENSION: &str = "canvasbundle";
+/// Version of the bundle layout written by this build
+pub const BUNDLE_FORMAT_VERSION: u32 = 1;
+/// Project templates, relative to the workspace root
+pub const TEMPLATES_DIR: &str = ".canvas/templates";
+
+const GRAPHS_PREFIX: &str = "graphs/";
+const ANNOTATIONS_PREFIX: &str = "annotations/";
+const ABI_PREFIX: &str = "abi/";
+const NODES_PREFIX: &str = "nodes/";
+const TEMPLATES_PREFIX: &str = "templates/";
+
+/// How the bundled project was built
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BuildManifest {
+    /// Version of the tool that wrote the bundle
+    pub tool_version: String,
+    pub compiler: CompilerConfig,
+    /// Network profile the storage costs were projected for
+    pub network: String,
+}
+
+/// Contents of a bundle
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BundleManifest {
+    pub format_version: u32,
+    pub name: String,
+    /// Graph files, relative to the project root
+    pub graphs: Vec<PathBuf>,
+    /// Annotation files of the graphs, relative to the project root
+    #[serde(default)]
+    pub annotations: Vec<PathBuf>,
+    /// Bundled custom nodes with their versions
+    pub custom_nodes: BTreeMap<String, String>,
+    /// Node types used by the graphs that are neither built in nor bundled
+    #[serde(default)]
+    pub unresolved_nodes: Vec<String>,
+    pub templates: Vec<PathBuf>,
+    pub abis: Vec<PathBuf>,
+    pub build: BuildManifest,
+    /// Graphs and templates had their labels stripped and IDs replaced
+    #[serde(default)]
+    pub obfuscated: bool,
+    /// Composite custom nodes shipped compiled, without their sub-graphs
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub compiled_nodes: Vec<String>,
+}
+
+/// A project packed into one file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProjectBundle {
+    pub manifest: BundleManifest,
+    pub files: BackupArchive,
+}
+
+/// What importing a bundle wrote
+#[derive(Debug, Clone, Default)]
+pub struct BundleImport {
+    pub graphs: usize,
+    pub annotations: usize,
+    pub abis: usize,
+    pub templates: usize,
+    /// Custom nodes installed
+    pub nodes_installed: Vec<String>,
+    /// Custom nodes already installed at the same or a newer version
+    pub nodes_skipped: Vec<String>,
+}
+
+/// Reject a bundled custom node ID that is not a plain file name, so it
+/// cannot name a file outside the nodes directory
+fn check_node_id(id: &str) -> CanvasResult<()> {
+    let mut components = Path::new(id).components();
+    let plain = matches!(components.next(), Some(std::path::Component::Normal(_)))
+        && components.next().is_none()
+        && !id.contains(['/', '\\']);
+    if !plain {
+        return Err(CanvasError::Validation(format!("Bundled custom node ID '{}' is not a plain name", id)));
+    }
+    Ok(())
+}
+
+fn entry_name(prefix: &str, path: &Path) -> String {
+    format!("{}{}", prefix, path.to_string_lossy().replace('\\', "/"))
+}
+
+/// Module files referenced by a definition, rewritten to their file names
+fn bundle_modules(definition: &mut CustomNodeDefinition) -> Vec<(PathBuf, String)> {
+    let mut modules = Vec::new();
+    let mut infos = vec![definition.wasm_module.as_mut()];
+    if let CustomNodeImplementation::Wasm { module_info, .. } = &mut definition.implementation {
+        infos.push(Some(module_info));
+    }
+    for info in infos.into_iter().flatten() {
+        let source = PathBuf::from(&info.module_path);
+        let file = source
+            .file_name()
+            .map_or_else(|| format!("{}.wasm", definition.id), |f| f.to_string_lossy().to_string());
+        info.module_path = file.clone();
+        modules.push((source, file));
+    }
+    modules
+}
+
+impl ProjectBundle {
+    /// Pack the project at `root`, with the custom nodes it uses from
+    /// `nodes_dir` when given
+    pub fn export(name: &str, root: &Path, nodes_dir: Option<&Path>, config: &Config) -> CanvasResult<Self> {
+        Self::pack(name, root, nodes_dir, config, None)
+    }
+
+    /// Pack the project like [`ProjectBundle::export`], but without review
+    /// annotations and with every graph and template obfuscated
+    pub fn export_obfuscated(
+        name: &str,
+        root: &Path,
+        nodes_dir: Option<&Path>,
+        config: &Config,
+        options: &ObfuscationOptions,
+    ) -> CanvasResult<Self> {
+        Self::pack(name, root, nodes_dir, config, Some(options))
+    }
+
+    fn pack(
+        name: &str,
+        root: &Path,
+        nodes_dir: Option<&Path>,
+        config: &Config,
+        obfuscation: Option<&ObfuscationOptions>,
+    ) -> CanvasResult<Self> {
+        let mut files = BackupArchive::new();
+        let workspace = Workspace::load(root)?;
+        let mut graphs = Vec::new();
+        let mut annotations = Vec::new();
+        let mut node_types = BTreeSet::new();
+        for (path, graph) in workspace.graphs() {
+            match obfuscation {
+                Some(_) => files.add_json(entry_name(GRAPHS_PREFIX, path), &obfuscate_graph(graph))?,
+                None => files.add_bytes(entry_name(GRAPHS_PREFIX, path), &std::fs::read(root.join(path))?),
+            }
+            node_types.extend(graph.nodes.iter().map(|n| n.node_type.clone()));
+            let sidecar = annotations_path(path);
+            if obfuscation.is_none() && root.join(&sidecar).exists() {
+                files.add_bytes(entry_name(ANNOTATIONS_PREFIX, &sidecar), &std::fs::read(root.join(&sidecar))?);
+                annotations.push(sidecar);
+            }
+            graphs.push(path.to_path_buf());
+        }
+        if graphs.is_empty() {
+            return Err(CanvasError::Validation(format!("No graphs found in {}", root.display())));
+        }
+
+        let mut abis = Vec::new();
+        for path in files_under(root)? {
+            if path.to_string_lossy().ends_with(".abi.json") {
+                files.add_bytes(entry_name(ABI_PREFIX, &path), &std::fs::read(root.join(&path))?);
+                abis.push(path);
+            }
+        }
+        let templates = files_under(&root.join(TEMPLATES_DIR))?;
+        for path in &templates {
+            let bytes = std::fs::read(root.join(TEMPLATES_DIR).join(path))?;
+            let template = obfuscation.and_then(|_| serde_json::from_slice::<VisualGraph>(&bytes).ok());
+            match template {
+                Some(template) => files.add_json(entry_name(TEMPLATES_PREFIX, path), &obfuscate_graph(&template))?,
+                None => files.add_bytes(entry_name(TEMPLATES_PREFIX, path), &bytes),
+            }
+        }
+
+        let registry = match nodes_dir.filter(|dir| dir.exists()) {
+            Some(dir) => CustomNodeRegistry::load_dir(dir)?,
+            None => CustomNodeRegistry::new(),
+        };
+        let builtin: BTreeSet<String> = builtin_node_definitions().into_iter().map(|d| d.id).collect();
+        let mut custom_nodes = BTreeMap::new();
+        let mut unresolved_nodes = Vec::new();
+        let mut compiled_nodes = Vec::new();
+        for node_type in node_types.iter().filter(|t| !builtin.contains(*t) && t.as_str() != "Import") {
+            let Some(definition) = registry.get_node(node_type) else {
+                log::warn!("Node type '{}' is not built in or installed; the bundle will not be reproducible", node_type);
+                unresolved_nodes.push(node_type.clone());
+                continue;
+            };
+            let compile = obfuscation.is_some_and(|o| o.compiled_composites.contains(node_type));
+            let definition = if compile {
+                let (definition, wasm) = compile_composite(definition, config)?;
+                files.add_bytes(format!("{}{}.wasm", NODES_PREFIX, definition.id), &wasm);
+                compiled_nodes.push(definition.id.clone());
+                definition
+            } else {
+                let mut definition = definition.clone();
+                for (source, file) in bundle_modules(&mut definition) {
+                    files.add_bytes(format!("{}{}", NODES_PREFIX, file), &std::fs::read(&source)?);
+                }
+                definition
+            };
+            files.add_json(format!("{}{}.json", NODES_PREFIX, definition.id), &definition)?;
+            custom_nodes.insert(definition.id.clone(), definition.version.to_string());
+        }
+        if let Some(missing) = obfuscation
+            .into_iter()
+            .flat_map(|o| &o.compiled_composites)
+            .find(|id| !compiled_nodes.contains(id))
+        {
+            return Err(CanvasError::Validation(format!(
+                "Composite node '{}' is not used by the project or not installed",
+                missing
+            )));
+        }
+
+        Ok(Self {
+            manifest: BundleManifest {
+                format_version: BUNDLE_FORMAT_VERSION,
+                name: name.to_string(),
+                graphs,
+                annotations,
+                custom_nodes,
+                unresolved_nodes,
+                templates,
+                abis,
+                build: BuildManifest {
+                    tool_version: crate::VERSION.to_string(),
+                    compiler: config.compiler.clone(),
+                    network: config.baals.network.name.clone(),
+                },
+                obfuscated: obfuscation.is_some(),
+                compiled_nodes,
+            },
+            files,
+        })
+    }
+
+    /// Check the format version and every file's hash
+    pub fn verify(&self) -> CanvasResult<()> {
+        if self.manifest.format_version > BUNDLE_FORMAT_VERSION {
+            return Err(CanvasError::Validation(format!(
+                "Bundle format v{} is newer than the supported v{}",
+                self.manifest.format_version, BUNDLE_FORMAT_VERSION
+            )));
+        }
+        self.files.verify()
+    }
+
+    /// Unpack into the project directory `root`, installing custom nodes into
+    /// `nodes_dir` when given. Existing project files with different content
+    /// are not overwritten.
+    pub fn import(&self, root: &Path, nodes_dir: Option<&Path>) -> CanvasResult<BundleImport> {
+        self.verify()?;
+        for id in self.manifest.custom_nodes.keys() {
+            check_node_id(id)?;
+        }
+        if self.manifest.build.tool_version != crate::VERSION {
+            log::warn!(
+                "Bundle was built with v{}, this is v{}; builds may differ",
+                self.manifest.build.tool_version,
+                crate::VERSION
+            );
+        }
+        for (name, _) in self.files.entries.iter().filter(|(n, _)| !n.starts_with(NODES_PREFIX)) {
+            let relative = name.split_once('/').map_or(name.as_str(), |(_, rest)| rest);
+            let target = if name.starts_with(TEMPLATES_PREFIX) {
+                root.join(TEMPLATES_DIR).join(relative)
+            } else {
+                root.join(relative)
+            };
+            if target.exists() && std::fs::read(&target)? != self.files.bytes(name)? {
+                return Err(CanvasError::Validation(format!(
+                    "{} already exists with different content",
+                    target.display()
+                )));
+            }
+        }
+
+        let mut import = BundleImport {
+            graphs: self.files.restore_dir(GRAPHS_PREFIX, root)?,
+            annotations: self.files.restore_dir(ANNOTATIONS_PREFIX, root)?,
+            abis: self.files.restore_dir(ABI_PREFIX, root)?,
+            templates: self.files.restore_dir(TEMPLATES_PREFIX, &root.join(TEMPLATES_DIR))?,
+            ..BundleImport::default()
+        };
+        let Some(nodes_dir) = nodes_dir else {
+            return Ok(import);
+        };
+
+        for id in self.manifest.custom_nodes.keys() {
+            let definition: CustomNodeDefinition = self
+                .files
+                .json(&format!("{}{}.json", NODES_PREFIX, id))?
+                .ok_or_else(|| CanvasError::Validation(format!("Bundle is missing custom node '{}'", id)))?;
+            if definition.id != *id {
+                return Err(CanvasError::Validation(format!(
+                    "Bundled custom node '{}' contains the definition of '{}'",
+                    id, definition.id
+                )));
+            }
+            let installed_path = nodes_dir.join(format!("{}.json", id));
+            if installed_path.exists() {
+                let installed: CustomNodeDefinition = serde_json::from_str(&std::fs::read_to_string(&installed_path)?)?;
+                if installed.version >= definition.version {
+                    import.nodes_skipped.push(id.clone());
+                    continue;
+                }
+            }
+            let mut modules = definition.clone();
+            for (_, file) in bundle_modules(&mut modules) {
+                std::fs::create_dir_all(nodes_dir)?;
+                std::fs::write(nodes_dir.join(&file), self.files.bytes(&format!("{}{}", NODES_PREFIX, file))?)?;
+            }
+            std::fs::create_dir_all(nodes_dir)?;
+            std::fs::write(installed_path, serde_json::to_string_pretty(&definition)?)?;
+            import.nodes_installed.push(id.clone());
+        }
+        Ok(import)
+    }
+
+    pub fn write(&self, path: &Path) -> CanvasResult<()> {
+        std::fs::write(path, serde_json::to_vec(self)?)?;
+        Ok(())
+    }
+
+    pub fn read(path: &Path) -> CanvasResult<Self> {
+        Ok(serde_json::from_slice(&std::fs::read(path)?)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nodes::custom::CustomNodeBuilder;
+    use crate::types::{Position, VisualGraph, VisualNode};
+    use uuid::Uuid;
+
+    #[test]
+    fn test_export_and_import_round_trip() {
+        let project = tempfile::tempdir().unwrap();
+        let nodes = tempfile::tempdir().unwrap();
+        let mut graph = VisualGraph::new("token");
+        graph.add_node(VisualNode::new(Uuid::new_v4(), "Clamp", Position::new(0.0, 0.0)));
+        graph.add_node(VisualNode::new(Uuid::new_v4(), "Mystery", Position::new(0.0, 0.0)));
+        std::fs::write(project.path().join("token.json"), serde_json::to_vec(&graph).unwrap()).unwrap();
+        std::fs::write(project.path().join("token.abi.json"), b"{}").unwrap();
+        let mut annotations = crate::annotations::GraphAnnotations::open(&project.path().join("token.json")).unwrap();
+        let anchor = crate::annotations::AnnotationAnchor::Node(graph.nodes[0].id);
+        annotations.add(&graph, anchor, "alice", "Why clamp here?".to_string()).unwrap();
+        annotations.save().unwrap();
+        let clamp = CustomNodeBuilder::new("Clamp".to_string(), "Clamp".to_string())
+            .composite("{}".to_string())
+            .build();
+        std::fs::write(nodes.path().join("Clamp.json"), serde_json::to_vec(&clamp).unwrap()).unwrap();
+
+        let bundle = ProjectBundle::export("token", project.path(), Some(nodes.path()), &Config::default()).unwrap();
+        assert_eq!(bundle.manifest.graphs, vec![PathBuf::from("token.json")]);
+        assert!(bundle.manifest.custom_nodes.contains_key("Clamp"));
+        assert_eq!(bundle.manifest.unresolved_nodes, vec!["Mystery".to_string()]);
+        let options = ObfuscationOptions::new();
+        let hidden =
+            ProjectBundle::export_obfuscated("token", project.path(), Some(nodes.path()), &Config::default(), &options)
+                .unwrap();
+        assert!(hidden.manifest.obfuscated);
+        assert!(hidden.manifest.annotations.is_empty(), "review notes stay with the vendor");
+        let shipped: VisualGraph = hidden.files.json("graphs/token.json").unwrap().unwrap();
+        assert!(shipped.nodes.iter().all(|n| graph.nodes.iter().all(|o| o.id != n.id)));
+        let missing = options.with_compiled_composite("Unused");
+        assert!(ProjectBundle::export_obfuscated("token", project.path(), None, &Config::default(), &missing).is_err());
+        let path = project.path().join(format!("token.{}", BUNDLE_EXTENSION));
+        bundle.write(&path).unwrap();
+
+        let target = tempfile::tempdir().unwrap();
+        let installed = tempfile::tempdir().unwrap();
+        let import = ProjectBundle::read(&path).unwrap().import(target.path(), Some(installed.path())).unwrap();
+        assert_eq!((import.graphs, import.abis, import.annotations), (1, 1, 1));
+        assert_eq!(import.nodes_installed, vec!["Clamp".to_string()]);
+        assert!(installed.path().join("Clamp.json").exists());
+
+        std::fs::write(target.path().join("token.json"), b"{}").unwrap();
+        assert!(bundle.import(target.path(), None).is_err());
+
+        // Custom node IDs from the manifest must not leave the nodes directory
+        let fresh = tempfile::tempdir().unwrap();
+        let mut escaping = bundle.clone();
+        escaping.manifest.custom_nodes.insert("../evil".to_string(), "1.0.0".to_string());
+        escaping.files.add_json(format!("{}../evil.json", NODES_PREFIX), &clamp).unwrap();
+        assert!(matches!(
+            escaping.import(fresh.path(), Some(installed.path())),
+            Err(CanvasError::Validation(_))
+        ));
+        assert!(!fresh.path().join("token.json").exists(), "nothing is written for a rejected bundle");
+        let mut renamed = bundle.clone();
+        renamed.manifest.custom_nodes.insert("Other".to_string(), "1.0.0".to_string());
+        renamed.files.add_json(format!("{}Other.json", NODES_PREFIX), &clamp).unwrap();
+        let empty = tempfile::tempdir().unwrap();
+        assert!(renamed.import(fresh.path(), Some(empty.path())).is_err());
+        assert!(!empty.path().join("Other.json").exists());
+    }
+}
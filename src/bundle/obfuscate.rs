@@ -0,0 +1,192 @@
+//! Obfuscated exports of proprietary templates
+//!
+//! Vendors selling templates may not want to hand over their full logic. An
+//! obfuscated export strips what only helps a human read a graph (its
+//! description, labels, comments, port descriptions and DSL variable names),
+//! gives every node and connection an opaque ID and, for selected composite
+//! nodes, ships a compiled WASM module in place of the sub-graph. What users
+//! build against is kept: node types, port IDs and types, the Start, End and
+//! Slot signatures, and the contract ABIs.
+
+use std::collections::{BTreeSet, HashMap};
+
+use uuid::Uuid;
+
+use crate::{
+    compiler::{Compiler, DEFAULT_ENTRY_POINT, SLOT_NODE_TYPE},
+    config::Config,
+    dsl::VARIABLE_METADATA_KEY,
+    error::{CanvasError, CanvasResult},
+    nodes::custom::{CustomNodeDefinition, CustomNodeImplementation, WasmModuleInfo},
+    types::{NodeId, Port, VisualGraph},
+};
+
+/// Property and metadata keys that only label a graph for its readers
+pub const LABEL_KEYS: &[&str] = &["label", "comment", "description", "notes", VARIABLE_METADATA_KEY];
+
+/// Nodes whose ports are a graph's signature and keep their names
+const SIGNATURE_NODE_TYPES: &[&str] = &["Start", "Init", "End", SLOT_NODE_TYPE];
+
+/// What an obfuscated export hides beyond labels and IDs
+#[derive(Debug, Clone, Default)]
+pub struct ObfuscationOptions {
+    /// Composite custom nodes shipped as compiled WASM instead of sub-graphs
+    pub compiled_composites: BTreeSet<String>,
+}
+
+impl ObfuscationOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Ship the composite node `id` compiled
+    pub fn with_compiled_composite(mut self, id: impl Into<String>) -> Self {
+        self.compiled_composites.insert(id.into());
+        self
+    }
+}
+
+/// Copy of `graph` without labels and with opaque node and connection IDs.
+///
+/// IDs are derived from the originals, so exporting the same graph twice
+/// gives the same result. The graph's own ID is kept since imports and slot
+/// fills refer to it.
+pub fn obfuscate_graph(graph: &VisualGraph) -> VisualGraph {
+    let opaque = |id: &Uuid| Uuid::new_v5(&graph.id, id.as_bytes());
+    let ids: HashMap<NodeId, NodeId> = graph.nodes.iter().map(|n| (n.id, opaque(&n.id))).collect();
+    let mut result = graph.clone();
+    result.description = None;
+    strip_labels(&mut result.metadata);
+    for node in &mut result.nodes {
+        node.id = ids[&node.id];
+        node.properties.retain(|key, _| !LABEL_KEYS.contains(&key.as_str()));
+        strip_labels(&mut node.metadata);
+        if !SIGNATURE_NODE_TYPES.contains(&node.node_type.as_str()) {
+            node.inputs.iter_mut().chain(&mut node.outputs).for_each(unlabel_port);
+        }
+    }
+    for connection in &mut result.connections {
+        connection.id = opaque(&connection.id);
+        connection.source_node = ids.get(&connection.source_node).copied().unwrap_or(connection.source_node);
+        connection.target_node = ids.get(&connection.target_node).copied().unwrap_or(connection.target_node);
+        strip_labels(&mut connection.metadata);
+    }
+    result
+}
+
+fn strip_labels(metadata: &mut HashMap<String, String>) {
+    metadata.retain(|key, _| !LABEL_KEYS.contains(&key.as_str()));
+}
+
+fn unlabel_port(port: &mut Port) {
+    port.name = port.id.to_string();
+    port.description = None;
+}
+
+/// Compile a composite node's sub-graph, returning the definition rewritten
+/// to run the module `<id>.wasm` and the module itself. The node keeps its
+/// ports and properties.
+pub fn compile_composite(
+    definition: &CustomNodeDefinition,
+    config: &Config,
+) -> CanvasResult<(CustomNodeDefinition, Vec<u8>)> {
+    let CustomNodeImplementation::Composite { sub_graph } = &definition.implementation else {
+        return Err(CanvasError::Validation(format!(
+            "Custom node '{}' is not a composite node",
+            definition.id
+        )));
+    };
+    let graph: VisualGraph = serde_json::from_str(sub_graph)?;
+    let compiled = Compiler::new(config)?.compile(&graph)?;
+    let exported_functions: Vec<String> = compiled.abi.functions.iter().map(|f| f.name.clone()).collect();
+    if !exported_functions.iter().any(|f| f == DEFAULT_ENTRY_POINT) {
+        return Err(CanvasError::Compilation(format!(
+            "Composite node '{}' must compile to a single '{}' entry point",
+            definition.id, DEFAULT_ENTRY_POINT
+        )));
+    }
+
+    let module_info = WasmModuleInfo {
+        module_path: format!("{}.wasm", definition.id),
+        exported_functions,
+        abi: serde_json::to_string(&compiled.abi)?,
+    };
+    let mut definition = definition.clone();
+    definition.wasm_module = Some(module_info.clone());
+    definition.implementation = CustomNodeImplementation::Wasm {
+        function_name: DEFAULT_ENTRY_POINT.to_string(),
+        module_info,
+    };
+    Ok((definition, compiled.wasm_bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nodes::custom::CustomNodeBuilder;
+    use crate::test_graphs::{connect, node};
+    use crate::types::ValueType;
+
+    fn tripler() -> VisualGraph {
+        let mut graph = VisualGraph::new("tripler").with_description("x3, our secret sauce");
+        let start = node("Start")
+            .with_outputs(vec![
+                Port::new("flow_out", "Flow Out", ValueType::Flow),
+                Port::new("amount", "Amount", ValueType::Integer),
+            ])
+            .with_property("returns", serde_json::json!("integer"));
+        let mut triple = node("Multiply")
+            .with_property("b", serde_json::json!(3))
+            .with_property("comment", serde_json::json!("tuned after the audit"));
+        triple.metadata.insert(VARIABLE_METADATA_KEY.to_string(), "tripled".to_string());
+        triple.outputs = vec![Port::new("result", "Tripled amount", ValueType::Integer)];
+        let end = node("End").with_inputs(vec![
+            Port::new("flow_in", "Flow In", ValueType::Flow),
+            Port::new("result", "Result", ValueType::Integer),
+        ]);
+        connect(&mut graph, &start, "flow_out", &end, "flow_in");
+        connect(&mut graph, &start, "amount", &triple, "a");
+        connect(&mut graph, &triple, "result", &end, "result");
+        for node in [start, triple, end] {
+            graph.add_node(node);
+        }
+        graph
+    }
+
+    #[test]
+    fn test_obfuscation_keeps_signature_and_compiles_composites() {
+        let graph = tripler();
+        let hidden = obfuscate_graph(&graph);
+        assert_eq!(hidden.id, graph.id);
+        assert_eq!(hidden.description, None);
+        assert_eq!(obfuscate_graph(&graph).nodes[1].id, hidden.nodes[1].id, "deterministic");
+        assert!(hidden.nodes.iter().all(|n| graph.nodes.iter().all(|o| o.id != n.id)));
+        let triple = &hidden.nodes[1];
+        assert!(!triple.properties.contains_key("comment"));
+        assert_eq!(triple.properties["b"], 3);
+        assert!(triple.metadata.is_empty());
+        assert_eq!(triple.outputs[0].name, "result");
+        assert_eq!(hidden.nodes[0].outputs[1].name, "Amount", "Start ports are the signature");
+        assert!(hidden
+            .connections
+            .iter()
+            .all(|c| hidden.nodes.iter().any(|n| n.id == c.source_node) && hidden.nodes.iter().any(|n| n.id == c.target_node)));
+
+        let composite = CustomNodeBuilder::new("Triple".to_string(), "Triple".to_string())
+            .composite(serde_json::to_string(&graph).unwrap())
+            .build();
+        let (compiled, wasm) = compile_composite(&composite, &Config::default()).unwrap();
+        assert!(wasm.starts_with(b"\0asm"));
+        let CustomNodeImplementation::Wasm { function_name, module_info } = &compiled.implementation else {
+            panic!("composite should be compiled to a WASM node");
+        };
+        assert_eq!(function_name, DEFAULT_ENTRY_POINT);
+        assert_eq!(module_info.module_path, "Triple.wasm");
+        assert!(!serde_json::to_string(&compiled).unwrap().contains("secret sauce"));
+
+        let script = CustomNodeBuilder::new("Raw".to_string(), "Raw".to_string())
+            .script("rust".to_string(), String::new())
+            .build();
+        assert!(matches!(compile_composite(&script, &Config::default()), Err(CanvasError::Validation(_))));
+    }
+}
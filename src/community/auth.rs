@@ -0,0 +1,306 @@
+//! Passwords, sessions and permission checks
+//!
+//! Passwords are hashed with Argon2id and kept apart from the public user
+//! records. Logging in issues a random session token; only its SHA-256 digest
+//! is stored, so community data does not leak live sessions. Operations that
+//! act as a user (editing their profile and projects, messaging, blocking,
+//! following, triaging issues, subscribing to threads, joining an editing
+//! session, reviewing and voting) take a token rather than a user id and check
+//! the session's user against the content and their [`UserPermissions`].
+//! Operations on a named user's own data (exporting or deleting the account,
+//! conversation read, mute and leave state, leaderboard opt-out, activity and
+//! its visibility, thread and notification read markers) and authoring
+//! comments, forum posts and replies take a token as well, and refuse a
+//! session that belongs to anyone else.
+
+use argon2::{
+    password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
+    Algorithm, Argon2, Params, Version,
+};
+use chrono::{DateTime, Duration, Utc};
+use rand::{rngs::OsRng, RngCore};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use super::{CommunityManager, CommunityUser, UserPermissions};
+use crate::error::{CanvasError, CanvasResult};
+
+/// How long a session lasts after logging in
+pub const SESSION_TTL_DAYS: i64 = 14;
+
+/// Shortest accepted password
+pub const MIN_PASSWORD_LENGTH: usize = 8;
+
+/// Something a user may be allowed to do
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Permission {
+    Publish,
+    Comment,
+    Rate,
+    Moderate,
+    Admin,
+    Message,
+}
+
+impl Permission {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Permission::Publish => "publish",
+            Permission::Comment => "comment",
+            Permission::Rate => "rate",
+            Permission::Moderate => "moderate",
+            Permission::Admin => "admin",
+            Permission::Message => "message",
+        }
+    }
+}
+
+impl UserPermissions {
+    pub fn allows(&self, permission: Permission) -> bool {
+        match permission {
+            Permission::Publish => self.can_publish,
+            Permission::Comment => self.can_comment,
+            Permission::Rate => self.can_rate,
+            Permission::Moderate => self.can_moderate || self.can_admin,
+            Permission::Admin => self.can_admin,
+            Permission::Message => self.can_message,
+        }
+    }
+}
+
+/// A logged-in session. The token is only ever handed out here.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Session {
+    pub token: String,
+    pub user_id: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// A session as stored, keyed by the digest of its token
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(super) struct StoredSession {
+    pub user_id: String,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+fn argon2() -> Argon2<'static> {
+    // Tests hash a password for every user they register; the minimum cost
+    // keeps them fast. Verification reads the cost from the stored hash.
+    #[cfg(test)]
+    let params = Params::new(Params::MIN_M_COST, Params::MIN_T_COST, 1, None).expect("valid Argon2 parameters");
+    #[cfg(not(test))]
+    let params = Params::default();
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+}
+
+/// Argon2id hash of `password` in PHC string format
+pub fn hash_password(password: &str) -> CanvasResult<String> {
+    if password.chars().count() < MIN_PASSWORD_LENGTH {
+        return Err(CanvasError::Validation(format!(
+            "Password must be at least {} characters",
+            MIN_PASSWORD_LENGTH
+        )));
+    }
+    let salt = SaltString::generate(&mut OsRng);
+    argon2()
+        .hash_password(password.as_bytes(), &salt)
+        .map(|hash| hash.to_string())
+        .map_err(|e| CanvasError::InvalidState(format!("Failed to hash password: {}", e)))
+}
+
+/// Whether `password` matches a hash made by [`hash_password`]
+pub fn verify_password(password: &str, hash: &str) -> bool {
+    PasswordHash::new(hash).is_ok_and(|parsed| argon2().verify_password(password.as_bytes(), &parsed).is_ok())
+}
+
+fn token_digest(token: &str) -> String {
+    Sha256::digest(token.as_bytes()).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn new_token() -> String {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+impl CommunityManager {
+    /// Replace a user's password
+    pub(super) fn set_password(&mut self, user_id: &str, password: &str) -> CanvasResult<()> {
+        let hash = hash_password(password)?;
+        self.credentials.insert(user_id.to_string(), hash);
+        Ok(())
+    }
+
+    /// Log in by username or email, starting a new session
+    pub fn login(&mut self, login: &str, password: &str) -> CanvasResult<Session> {
+        // One message for both cases, so logins do not reveal which accounts exist
+        let invalid = || CanvasError::PermissionDenied("Invalid username or password".to_string());
+        let user_id = self
+            .users
+            .values()
+            .find(|u| u.username == login || u.email == login)
+            .map(|u| u.id.clone())
+            .ok_or_else(invalid)?;
+        let hash = self.credentials.get(&user_id).ok_or_else(invalid)?;
+        if !verify_password(password, hash) {
+            return Err(invalid());
+        }
+
+        let now = Utc::now();
+        self.sessions.retain(|_, s| s.expires_at > now);
+        let token = new_token();
+        let expires_at = now + Duration::days(SESSION_TTL_DAYS);
+        self.sessions.insert(
+            token_digest(&token),
+            StoredSession {
+                user_id: user_id.clone(),
+                created_at: now,
+                expires_at,
+            },
+        );
+        if let Some(user) = self.users.get_mut(&user_id) {
+            user.last_active = now;
+        }
+        Ok(Session {
+            token,
+            user_id,
+            expires_at,
+        })
+    }
+
+    /// End a session. Returns whether it was live.
+    pub fn logout(&mut self, token: &str) -> bool {
+        self.sessions.remove(&token_digest(token)).is_some()
+    }
+
+    /// End every session of a user
+    pub fn revoke_sessions(&mut self, user_id: &str) -> usize {
+        let before = self.sessions.len();
+        self.sessions.retain(|_, s| s.user_id != user_id);
+        before - self.sessions.len()
+    }
+
+    /// The user a session token belongs to
+    pub fn authenticate(&self, token: &str) -> CanvasResult<&CommunityUser> {
+        self.sessions
+            .get(&token_digest(token))
+            .filter(|s| s.expires_at > Utc::now())
+            .and_then(|s| self.users.get(&s.user_id))
+            .ok_or_else(|| CanvasError::PermissionDenied("Invalid or expired session".to_string()))
+    }
+
+    /// The user a session token belongs to, provided they have `permission`
+    pub fn authorize(&self, token: &str, permission: Permission) -> CanvasResult<&CommunityUser> {
+        let user = self.authenticate(token)?;
+        if !user.permissions.allows(permission) {
+            return Err(CanvasError::PermissionDenied(format!(
+                "User '{}' lacks the {} permission",
+                user.username,
+                permission.as_str()
+            )));
+        }
+        Ok(user)
+    }
+
+    /// The user a session token belongs to, who must be `user_id`
+    pub fn authenticate_as(&self, token: &str, user_id: &str) -> CanvasResult<&CommunityUser> {
+        same_user(self.authenticate(token)?, user_id)
+    }
+
+    /// The user a session token belongs to, who must be `user_id` and have
+    /// `permission`
+    pub fn authorize_as(&self, token: &str, user_id: &str, permission: Permission) -> CanvasResult<&CommunityUser> {
+        same_user(self.authorize(token, permission)?, user_id)
+    }
+
+    /// Change the password of the session's user. Their other sessions are
+    /// ended; this one stays live.
+    pub fn change_password(&mut self, token: &str, current: &str, new: &str) -> CanvasResult<()> {
+        let user_id = self.authenticate(token)?.id.clone();
+        let verified = self
+            .credentials
+            .get(&user_id)
+            .is_some_and(|hash| verify_password(current, hash));
+        if !verified {
+            return Err(CanvasError::PermissionDenied("Current password is incorrect".to_string()));
+        }
+        self.set_password(&user_id, new)?;
+        let keep = token_digest(token);
+        self.sessions.retain(|digest, s| s.user_id != user_id || *digest == keep);
+        Ok(())
+    }
+}
+
+fn same_user<'a>(user: &'a CommunityUser, user_id: &str) -> CanvasResult<&'a CommunityUser> {
+    if user.id != user_id {
+        return Err(CanvasError::PermissionDenied(format!(
+            "User '{}' may not act for user '{}'",
+            user.username, user_id
+        )));
+    }
+    Ok(user)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        community::{CollaboratorRole, ProjectUpdate},
+        types::Graph,
+    };
+
+    #[test]
+    fn test_sessions_gate_project_updates() {
+        let mut manager = CommunityManager::new();
+        let alice = manager
+            .register_user("alice".to_string(), "alice@example.com".to_string(), "correct horse".to_string())
+            .unwrap();
+        let bob = manager
+            .register_user("bob".to_string(), "bob@example.com".to_string(), "battery staple".to_string())
+            .unwrap();
+        assert!(manager
+            .register_user("carol".to_string(), "carol@example.com".to_string(), "short".to_string())
+            .is_err());
+        assert!(!serde_json::to_string(manager.get_user(&alice).unwrap()).unwrap().contains("argon2"));
+
+        assert!(manager.login("alice", "battery staple").is_err());
+        assert!(manager.login("nobody", "correct horse").is_err());
+        let session = manager.login("alice@example.com", "correct horse").unwrap();
+        assert_eq!(session.user_id, alice);
+        let bob_session = manager.login("bob", "battery staple").unwrap();
+
+        let project = manager
+            .create_project("Vault".to_string(), String::new(), alice.clone(), Graph::new())
+            .unwrap();
+        let rename = |name: &str| ProjectUpdate {
+            name: Some(name.to_string()),
+            description: None,
+            visibility: None,
+            status: None,
+            graph: None,
+        };
+        assert!(matches!(
+            manager.update_project(&project, &alice, rename("Forged")),
+            Err(CanvasError::PermissionDenied(_))
+        ));
+        assert!(manager.update_project(&project, &bob_session.token, rename("Stolen")).is_err());
+        manager.update_project(&project, &session.token, rename("Safe")).unwrap();
+        assert_eq!(manager.get_project(&project).unwrap().name, "Safe");
+
+        manager
+            .add_collaborator(&project, &session.token, &bob, CollaboratorRole::Admin)
+            .unwrap();
+        manager.update_project(&project, &bob_session.token, rename("Shared")).unwrap();
+
+        let other = manager.login("alice", "correct horse").unwrap();
+        manager
+            .change_password(&session.token, "correct horse", "tr0ub4dor&3")
+            .unwrap();
+        assert!(manager.authenticate(&other.token).is_err());
+        assert!(manager.login("alice", "correct horse").is_err());
+        assert!(manager.logout(&session.token));
+        assert!(manager.update_project(&project, &session.token, rename("Late")).is_err());
+        assert!(manager.login("alice", "tr0ub4dor&3").is_ok());
+    }
+}
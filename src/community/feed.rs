@@ -0,0 +1,262 @@
+//! Activity feed and following timeline
+//!
+//! Publishing something (making a project public, listing a marketplace item,
+//! publishing a tutorial) records an activity event. A user's timeline is the
+//! events of the accounts they follow, newest first, paged by passing the
+//! last event ID seen as the `after` cursor. What others see is governed by
+//! each author's activity visibility and by the current state of the subject:
+//! a project made private again, or a tutorial taken down, drops out of every
+//! feed.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::{CommunityManager, ProjectVisibility, TutorialStatus};
+use crate::error::{CanvasError, CanvasResult};
+
+/// Something a user published
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ActivityKind {
+    ProjectPublished { project_id: String },
+    ItemPublished { item_id: String, name: String },
+    TutorialPublished { tutorial_id: String },
+}
+
+/// Recorded activity
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ActivityEvent {
+    pub id: String,
+    pub actor_id: String,
+    pub kind: ActivityKind,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Who may see a user's activity
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ActivityVisibility {
+    #[default]
+    Everyone,
+    Followers,
+    OnlyMe,
+}
+
+/// One page of a feed
+#[derive(Debug, Clone)]
+pub struct FeedPage<'a> {
+    pub events: Vec<&'a ActivityEvent>,
+    /// Pass as `after` to get the next page; `None` on the last page
+    pub next_after: Option<String>,
+}
+
+impl CommunityManager {
+    /// Record a published activity of the session's user
+    pub fn record_activity(&mut self, token: &str, actor_id: &str, kind: ActivityKind) -> CanvasResult<String> {
+        self.authenticate_as(token, actor_id)?;
+        self.push_activity(actor_id, kind)
+    }
+
+    pub(super) fn push_activity(&mut self, actor_id: &str, kind: ActivityKind) -> CanvasResult<String> {
+        if !self.users.contains_key(actor_id) {
+            return Err(CanvasError::NotFound(format!("User '{}' not found", actor_id)));
+        }
+        let event_id = format!("activity_{}", uuid::Uuid::new_v4());
+        self.activities.push(ActivityEvent {
+            id: event_id.clone(),
+            actor_id: actor_id.to_string(),
+            kind,
+            created_at: Utc::now(),
+        });
+        Ok(event_id)
+    }
+
+    pub fn set_activity_visibility(
+        &mut self,
+        token: &str,
+        user_id: &str,
+        visibility: ActivityVisibility,
+    ) -> CanvasResult<()> {
+        self.authenticate_as(token, user_id)?;
+        self.activity_visibility.insert(user_id.to_string(), visibility);
+        Ok(())
+    }
+
+    pub fn activity_visibility(&self, user_id: &str) -> ActivityVisibility {
+        self.activity_visibility.get(user_id).copied().unwrap_or_default()
+    }
+
+    /// Whether `viewer_id` may see an event
+    fn can_see(&self, event: &ActivityEvent, viewer_id: &str) -> bool {
+        if event.actor_id != viewer_id {
+            let follows = self
+                .users
+                .get(viewer_id)
+                .is_some_and(|viewer| viewer.following.contains(&event.actor_id));
+            let allowed = match self.activity_visibility(&event.actor_id) {
+                ActivityVisibility::Everyone => true,
+                ActivityVisibility::Followers => follows,
+                ActivityVisibility::OnlyMe => false,
+            };
+            if !allowed || self.is_blocked(&event.actor_id, viewer_id) {
+                return false;
+            }
+        }
+        match &event.kind {
+            ActivityKind::ProjectPublished { project_id } => self
+                .projects
+                .get(project_id)
+                .is_some_and(|p| p.visibility == ProjectVisibility::Public),
+            ActivityKind::TutorialPublished { tutorial_id } => self
+                .tutorials
+                .get(tutorial_id)
+                .is_some_and(|t| t.status == TutorialStatus::Published),
+            ActivityKind::ItemPublished { .. } => true,
+        }
+    }
+
+    fn page<'a>(
+        &'a self,
+        viewer_id: &str,
+        include: impl Fn(&ActivityEvent) -> bool,
+        after: Option<&str>,
+        limit: usize,
+    ) -> FeedPage<'a> {
+        let newest_first = self.activities.iter().rev();
+        let start = match after {
+            Some(cursor) => newest_first
+                .clone()
+                .position(|e| e.id == cursor)
+                .map_or(self.activities.len(), |i| i + 1),
+            None => 0,
+        };
+        let mut events: Vec<&ActivityEvent> = newest_first
+            .skip(start)
+            .filter(|e| include(e) && self.can_see(e, viewer_id))
+            .take(limit + 1)
+            .collect();
+        let more = events.len() > limit;
+        events.truncate(limit);
+        FeedPage {
+            next_after: if more { events.last().map(|e| e.id.clone()) } else { None },
+            events,
+        }
+    }
+
+    /// Activity of the accounts `user_id` follows, newest first
+    pub fn timeline(&self, user_id: &str, after: Option<&str>, limit: usize) -> CanvasResult<FeedPage<'_>> {
+        let user = self
+            .users
+            .get(user_id)
+            .ok_or_else(|| CanvasError::NotFound(format!("User '{}' not found", user_id)))?;
+        Ok(self.page(user_id, |e| user.following.contains(&e.actor_id), after, limit))
+    }
+
+    /// A user's own activity as `viewer_id` may see it, newest first
+    pub fn user_activity(&self, actor_id: &str, viewer_id: &str, after: Option<&str>, limit: usize) -> FeedPage<'_> {
+        self.page(viewer_id, |e| e.actor_id == actor_id, after, limit)
+    }
+
+    /// Activity a user recorded, for a data export
+    pub(super) fn user_activities(&self, user_id: &str) -> Vec<&ActivityEvent> {
+        self.activities.iter().filter(|e| e.actor_id == user_id).collect()
+    }
+
+    /// Drop a user's activity and feed settings. Returns how many events were removed.
+    pub(super) fn erase_activity(&mut self, user_id: &str) -> usize {
+        self.activity_visibility.remove(user_id);
+        let before = self.activities.len();
+        self.activities.retain(|e| e.actor_id != user_id);
+        before - self.activities.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{community::ProjectUpdate, types::Graph};
+
+    #[test]
+    fn test_timeline_follows_and_respects_privacy() {
+        let mut manager = CommunityManager::new();
+        let mut register = |name: &str| {
+            manager
+                .register_user(name.to_string(), format!("{}@example.com", name), "password".to_string())
+                .unwrap()
+        };
+        let (alice, bob, carol) = (register("alice"), register("bob"), register("carol"));
+        let mut login = |name: &str| manager.login(name, "password").unwrap().token;
+        let (alice_token, bob_token, carol_token) = (login("alice"), login("bob"), login("carol"));
+        manager.follow_user(&bob_token, &alice).unwrap();
+
+        let graph = Graph { nodes: Vec::new(), edges: Vec::new() };
+        let project = manager.create_project("Vault".to_string(), String::new(), alice.clone(), graph).unwrap();
+        assert!(manager.timeline(&bob, None, 10).unwrap().events.is_empty());
+        let public = ProjectUpdate {
+            name: None,
+            description: None,
+            visibility: Some(ProjectVisibility::Public),
+            status: None,
+            graph: None,
+        };
+        manager.update_project(&project, &alice_token, public).unwrap();
+        for i in 0..3 {
+            let kind = ActivityKind::ItemPublished {
+                item_id: format!("item-{}", i),
+                name: format!("Item {}", i),
+            };
+            manager.record_activity(&alice_token, &alice, kind).unwrap();
+        }
+        manager
+            .record_activity(&carol_token, &carol, ActivityKind::ItemPublished { item_id: "x".to_string(), name: "X".to_string() })
+            .unwrap();
+
+        let first = manager.timeline(&bob, None, 3).unwrap();
+        assert_eq!(first.events.len(), 3);
+        assert!(first.events.iter().all(|e| e.actor_id == alice));
+        let rest = manager.timeline(&bob, first.next_after.as_deref(), 3).unwrap();
+        assert!(matches!(rest.events[0].kind, ActivityKind::ProjectPublished { .. }));
+        assert!(rest.next_after.is_none());
+
+        manager.set_activity_visibility(&alice_token, &alice, ActivityVisibility::Followers).unwrap();
+        assert!(manager.user_activity(&alice, &carol, None, 10).events.is_empty());
+        assert_eq!(manager.user_activity(&alice, &bob, None, 10).events.len(), 4);
+        manager.set_activity_visibility(&alice_token, &alice, ActivityVisibility::OnlyMe).unwrap();
+        assert!(manager.timeline(&bob, None, 10).unwrap().events.is_empty());
+        assert_eq!(manager.user_activity(&alice, &alice, None, 10).events.len(), 4);
+    }
+
+    fn alice_and_bob() -> (CommunityManager, [(String, String); 2]) {
+        let mut manager = CommunityManager::new();
+        let users = ["alice", "bob"].map(|name| {
+            let id = manager
+                .register_user(name.to_string(), format!("{}@example.com", name), "password".to_string())
+                .unwrap();
+            (id, manager.login(name, "password").unwrap().token)
+        });
+        (manager, users)
+    }
+
+    #[test]
+    fn test_record_activity_refuses_another_session() {
+        let (mut manager, [(alice, _), (bob, bob_token)]) = alice_and_bob();
+        let kind = ActivityKind::ItemPublished {
+            item_id: "x".to_string(),
+            name: "X".to_string(),
+        };
+        assert!(matches!(
+            manager.record_activity(&bob_token, &alice, kind),
+            Err(CanvasError::PermissionDenied(_))
+        ));
+        assert!(manager.user_activity(&alice, &bob, None, 10).events.is_empty());
+    }
+
+    #[test]
+    fn test_set_activity_visibility_refuses_another_session() {
+        let (mut manager, [(alice, _), (_, bob_token)]) = alice_and_bob();
+        assert!(manager
+            .set_activity_visibility(&bob_token, &alice, ActivityVisibility::OnlyMe)
+            .is_err());
+        assert_eq!(manager.activity_visibility(&alice), ActivityVisibility::Everyone);
+    }
+}
@@ -0,0 +1,458 @@
+//! Forum threads: replies, mentions, subscriptions and unread tracking
+//!
+//! Replies belong to a post and may answer another reply of the same post,
+//! forming a tree. `@username` mentions in a post or reply notify the
+//! mentioned user; everyone subscribed to a thread is notified of new replies.
+//! Authors are subscribed to the threads they start or reply to; anyone else
+//! subscribes with a session token. Each user's last visit to a thread is
+//! remembered so unread replies can be counted.
+
+use std::collections::{BTreeSet, HashMap};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::{CommunityManager, Permission, PostStatus, DELETED_USER};
+use crate::{
+    config::ContentRetention,
+    error::{CanvasError, CanvasResult},
+};
+
+/// Reply in a forum thread
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ForumReply {
+    pub id: String,
+    pub post_id: String,
+    /// Reply this one answers; `None` for a direct reply to the post
+    pub parent_reply_id: Option<String>,
+    pub author_id: String,
+    pub content: String,
+    /// IDs of the users mentioned
+    pub mentions: Vec<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub is_deleted: bool,
+}
+
+/// Reply with its depth in the thread tree
+#[derive(Debug, Clone)]
+pub struct ThreadEntry<'a> {
+    pub reply: &'a ForumReply,
+    /// 0 for direct replies to the post
+    pub depth: usize,
+}
+
+/// Why a user was notified
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum NotificationKind {
+    /// The user was mentioned in a post (`reply_id: None`) or reply
+    Mention {
+        post_id: String,
+        reply_id: Option<String>,
+    },
+    /// Someone replied in a thread the user is subscribed to
+    Reply { post_id: String, reply_id: String },
+    /// New private message
+    Message {
+        conversation_id: String,
+        message_id: String,
+    },
+}
+
+/// Notification for a user
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Notification {
+    pub id: String,
+    pub user_id: String,
+    /// User whose action caused the notification
+    pub actor_id: String,
+    pub kind: NotificationKind,
+    pub created_at: DateTime<Utc>,
+    pub read: bool,
+}
+
+/// Usernames mentioned as `@username`. An `@` preceded by a word character,
+/// as in an email address, is not a mention.
+pub fn parse_mentions(text: &str) -> Vec<String> {
+    let is_name_char = |c: char| c.is_alphanumeric() || c == '_' || c == '-';
+    let mut mentions = Vec::new();
+    let mut previous: Option<char> = None;
+    for (i, c) in text.char_indices() {
+        if c == '@' && !previous.is_some_and(is_name_char) {
+            let rest = &text[i + 1..];
+            let end = rest.find(|c: char| !is_name_char(c)).unwrap_or(rest.len());
+            let name = rest[..end].trim_end_matches('-');
+            if !name.is_empty() && !mentions.iter().any(|m| m == name) {
+                mentions.push(name.to_string());
+            }
+        }
+        previous = Some(c);
+    }
+    mentions
+}
+
+impl CommunityManager {
+    /// IDs of the existing users mentioned in `text`, other than `author_id`
+    fn resolve_mentions(&self, text: &str, author_id: &str) -> Vec<String> {
+        parse_mentions(text)
+            .iter()
+            .filter_map(|name| self.get_user_by_username(name))
+            .map(|user| user.id.clone())
+            .filter(|id| id != author_id)
+            .collect()
+    }
+
+    pub(super) fn notify(&mut self, user_id: &str, actor_id: &str, kind: NotificationKind) {
+        self.notifications.push(Notification {
+            id: format!("notification_{}", uuid::Uuid::new_v4()),
+            user_id: user_id.to_string(),
+            actor_id: actor_id.to_string(),
+            kind,
+            created_at: Utc::now(),
+            read: false,
+        });
+    }
+
+    /// Subscribe the author of a new post and notify the users it mentions
+    pub(super) fn on_forum_post_created(&mut self, post_id: &str) {
+        let Some(post) = self.forum_posts.get(post_id) else {
+            return;
+        };
+        let author_id = post.author_id.clone();
+        let mentioned = self.resolve_mentions(&post.content, &author_id);
+        self.add_thread_subscriber(&author_id, post_id).ok();
+        for user_id in mentioned {
+            let kind = NotificationKind::Mention {
+                post_id: post_id.to_string(),
+                reply_id: None,
+            };
+            self.notify(&user_id, &author_id, kind);
+        }
+    }
+
+    /// Reply to a forum post as the session's user, or to a reply in it when
+    /// `parent_reply_id` is given
+    pub fn reply_to_post(
+        &mut self,
+        post_id: &str,
+        token: &str,
+        author_id: &str,
+        content: String,
+        parent_reply_id: Option<String>,
+    ) -> CanvasResult<String> {
+        self.authorize_as(token, author_id, Permission::Comment)?;
+        let post = self
+            .forum_posts
+            .get(post_id)
+            .ok_or_else(|| CanvasError::NotFound(format!("Post '{}' not found", post_id)))?;
+        if post.is_locked || post.status != PostStatus::Active {
+            return Err(CanvasError::PermissionDenied(format!("Post '{}' is not open for replies", post_id)));
+        }
+        if let Some(parent_id) = &parent_reply_id {
+            match self.forum_replies.get(parent_id) {
+                Some(parent) if parent.post_id == post_id => {}
+                _ => {
+                    return Err(CanvasError::NotFound(format!(
+                        "Reply '{}' not found in post '{}'",
+                        parent_id, post_id
+                    )))
+                }
+            }
+        }
+
+        let reply_id = format!("reply_{}", uuid::Uuid::new_v4());
+        let now = Utc::now();
+        let mentions = self.resolve_mentions(&content, author_id);
+        self.forum_replies.insert(
+            reply_id.clone(),
+            ForumReply {
+                id: reply_id.clone(),
+                post_id: post_id.to_string(),
+                parent_reply_id,
+                author_id: author_id.to_string(),
+                content,
+                mentions: mentions.clone(),
+                created_at: now,
+                updated_at: now,
+                is_deleted: false,
+            },
+        );
+        if let Some(post) = self.forum_posts.get_mut(post_id) {
+            post.replies += 1;
+            post.updated_at = now;
+        }
+
+        for user_id in &mentions {
+            let kind = NotificationKind::Mention {
+                post_id: post_id.to_string(),
+                reply_id: Some(reply_id.clone()),
+            };
+            self.notify(user_id, author_id, kind);
+        }
+        let subscribers: Vec<String> = self
+            .thread_subscribers(post_id)
+            .into_iter()
+            .filter(|id| id != author_id && !mentions.contains(id))
+            .collect();
+        for user_id in subscribers {
+            let kind = NotificationKind::Reply {
+                post_id: post_id.to_string(),
+                reply_id: reply_id.clone(),
+            };
+            self.notify(&user_id, author_id, kind);
+        }
+        self.add_thread_subscriber(author_id, post_id)?;
+        self.record_thread_read(author_id, post_id);
+        Ok(reply_id)
+    }
+
+    /// Replies of a post in thread order: each reply followed by its answers,
+    /// oldest first at every level
+    pub fn get_thread(&self, post_id: &str) -> Vec<ThreadEntry<'_>> {
+        let mut children: HashMap<Option<&str>, Vec<&ForumReply>> = HashMap::new();
+        for reply in self.forum_replies.values().filter(|r| r.post_id == post_id) {
+            children.entry(reply.parent_reply_id.as_deref()).or_default().push(reply);
+        }
+        for replies in children.values_mut() {
+            replies.sort_by(|a, b| a.created_at.cmp(&b.created_at).then_with(|| a.id.cmp(&b.id)));
+        }
+
+        let mut thread = Vec::new();
+        let mut stack: Vec<(&ForumReply, usize)> =
+            children.get(&None).into_iter().flatten().rev().map(|r| (*r, 0)).collect();
+        while let Some((reply, depth)) = stack.pop() {
+            thread.push(ThreadEntry { reply, depth });
+            if let Some(answers) = children.get(&Some(reply.id.as_str())) {
+                stack.extend(answers.iter().rev().map(|r| (*r, depth + 1)));
+            }
+        }
+        thread
+    }
+
+    /// Subscribe the session's user to a thread
+    pub fn subscribe_thread(&mut self, token: &str, post_id: &str) -> CanvasResult<()> {
+        let user_id = self.authenticate(token)?.id.clone();
+        self.add_thread_subscriber(&user_id, post_id)
+    }
+
+    fn add_thread_subscriber(&mut self, user_id: &str, post_id: &str) -> CanvasResult<()> {
+        if !self.forum_posts.contains_key(post_id) {
+            return Err(CanvasError::NotFound(format!("Post '{}' not found", post_id)));
+        }
+        self.thread_subscriptions
+            .entry(post_id.to_string())
+            .or_default()
+            .insert(user_id.to_string());
+        Ok(())
+    }
+
+    /// Unsubscribe the session's user from a thread
+    pub fn unsubscribe_thread(&mut self, token: &str, post_id: &str) -> CanvasResult<()> {
+        let user_id = self.authenticate(token)?.id.clone();
+        if let Some(subscribers) = self.thread_subscriptions.get_mut(post_id) {
+            subscribers.remove(&user_id);
+        }
+        Ok(())
+    }
+
+    pub fn thread_subscribers(&self, post_id: &str) -> BTreeSet<String> {
+        self.thread_subscriptions.get(post_id).cloned().unwrap_or_default()
+    }
+
+    /// Record that the session's user has read a thread up to now
+    pub fn mark_thread_read(&mut self, token: &str, user_id: &str, post_id: &str) -> CanvasResult<()> {
+        self.authenticate_as(token, user_id)?;
+        self.record_thread_read(user_id, post_id);
+        Ok(())
+    }
+
+    fn record_thread_read(&mut self, user_id: &str, post_id: &str) {
+        self.thread_reads
+            .entry(user_id.to_string())
+            .or_default()
+            .insert(post_id.to_string(), Utc::now());
+    }
+
+    /// Replies by others since the user last read the thread
+    pub fn unread_replies(&self, user_id: &str, post_id: &str) -> usize {
+        let last_read = self.thread_reads.get(user_id).and_then(|reads| reads.get(post_id));
+        self.forum_replies
+            .values()
+            .filter(|r| r.post_id == post_id && r.author_id != user_id && !r.is_deleted)
+            .filter(|r| last_read.map_or(true, |at| r.created_at > *at))
+            .count()
+    }
+
+    /// Subscribed threads with unread replies, and how many
+    pub fn unread_threads(&self, user_id: &str) -> Vec<(String, usize)> {
+        let mut unread: Vec<(String, usize)> = self
+            .thread_subscriptions
+            .iter()
+            .filter(|(_, subscribers)| subscribers.contains(user_id))
+            .map(|(post_id, _)| (post_id.clone(), self.unread_replies(user_id, post_id)))
+            .filter(|(_, count)| *count > 0)
+            .collect();
+        unread.sort();
+        unread
+    }
+
+    /// A user's notifications, newest first
+    pub fn get_notifications(&self, user_id: &str, unread_only: bool) -> Vec<&Notification> {
+        let mut notifications: Vec<&Notification> = self
+            .notifications
+            .iter()
+            .filter(|n| n.user_id == user_id && (!unread_only || !n.read))
+            .collect();
+        notifications.reverse();
+        notifications
+    }
+
+    /// Mark all of the session user's notifications read. Returns how many
+    /// were unread.
+    pub fn mark_notifications_read(&mut self, token: &str, user_id: &str) -> CanvasResult<usize> {
+        self.authenticate_as(token, user_id)?;
+        let mut marked = 0;
+        for notification in self.notifications.iter_mut().filter(|n| n.user_id == user_id && !n.read) {
+            notification.read = true;
+            marked += 1;
+        }
+        Ok(marked)
+    }
+
+    /// Forum activity of a user for a data export
+    pub(super) fn user_forum_replies(&self, user_id: &str) -> Vec<&ForumReply> {
+        self.forum_replies.values().filter(|r| r.author_id == user_id).collect()
+    }
+
+    /// Drop a user's subscriptions, read markers and notifications, and
+    /// anonymize or blank their replies. Returns how many replies were affected.
+    pub(super) fn erase_forum_activity(&mut self, user_id: &str, retention: ContentRetention, now: DateTime<Utc>) -> usize {
+        for subscribers in self.thread_subscriptions.values_mut() {
+            subscribers.remove(user_id);
+        }
+        self.thread_reads.remove(user_id);
+        self.notifications.retain(|n| n.user_id != user_id && n.actor_id != user_id);
+
+        let mut affected = 0;
+        for reply in self.forum_replies.values_mut() {
+            reply.mentions.retain(|id| id != user_id);
+            if reply.author_id != user_id {
+                continue;
+            }
+            reply.author_id = DELETED_USER.to_string();
+            reply.updated_at = now;
+            if retention == ContentRetention::Remove {
+                // Kept as a tombstone so answers stay threaded
+                reply.content.clear();
+                reply.is_deleted = true;
+            }
+            affected += 1;
+        }
+        affected
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_threaded_replies_mentions_and_unread() {
+        let mut manager = CommunityManager::new();
+        let mut register = |name: &str| {
+            manager
+                .register_user(name.to_string(), format!("{}@example.com", name), "password".to_string())
+                .unwrap()
+        };
+        let (alice, bob, carol) = (register("alice"), register("bob"), register("carol"));
+        let mut login = |name: &str| manager.login(name, "password").unwrap().token;
+        let (alice_token, bob_token, carol_token) = (login("alice"), login("bob"), login("carol"));
+        assert_eq!(parse_mentions("@bob, ask @carol-. not me@alice @bob"), vec!["bob", "carol"]);
+
+        let post = manager
+            .create_forum_post(
+                &alice_token,
+                "Gas".to_string(),
+                "@bob any ideas?".to_string(),
+                alice.clone(),
+                "general".to_string(),
+                Vec::new(),
+            )
+            .unwrap();
+        assert_eq!(manager.get_notifications(&bob, true).len(), 1);
+
+        let first = manager.reply_to_post(&post, &bob_token, &bob, "Try batching".to_string(), None).unwrap();
+        let answer = manager
+            .reply_to_post(&post, &carol_token, &carol, "@bob that broke for me".to_string(), Some(first.clone()))
+            .unwrap();
+        let thread: Vec<(&str, usize)> = manager.get_thread(&post).iter().map(|e| (e.reply.id.as_str(), e.depth)).collect();
+        assert_eq!(thread, vec![(first.as_str(), 0), (answer.as_str(), 1)]);
+        assert_eq!(manager.get_forum_posts(None)[0].replies, 2);
+
+        // Alice started the thread, so she hears about both replies; Bob was mentioned instead
+        assert_eq!(manager.get_notifications(&alice, true).len(), 2);
+        assert!(matches!(manager.get_notifications(&bob, true)[0].kind, NotificationKind::Mention { .. }));
+        assert_eq!(manager.unread_threads(&alice), vec![(post.clone(), 2)]);
+        manager.mark_thread_read(&alice_token, &alice, &post).unwrap();
+        assert_eq!(manager.unread_replies(&alice, &post), 0);
+        assert_eq!(manager.mark_notifications_read(&alice_token, &alice).unwrap(), 2);
+
+        // Carol replied, so she is subscribed; only her session can change that
+        assert!(manager.unsubscribe_thread(&carol, &post).is_err());
+        manager.unsubscribe_thread(&carol_token, &post).unwrap();
+        assert!(!manager.thread_subscribers(&post).contains(&carol));
+        manager.subscribe_thread(&carol_token, &post).unwrap();
+        assert!(manager.thread_subscribers(&post).contains(&carol));
+        assert!(manager
+            .reply_to_post(&post, &alice_token, &alice, "ok".to_string(), Some("reply_missing".to_string())).is_err());
+    }
+
+    /// A post by Alice, with Alice's and Bob's ids and session tokens
+    fn post_by_alice() -> (CommunityManager, [(String, String); 2], String) {
+        let mut manager = CommunityManager::new();
+        let users = ["alice", "bob"].map(|name| {
+            let id = manager
+                .register_user(name.to_string(), format!("{}@example.com", name), "password".to_string())
+                .unwrap();
+            (id, manager.login(name, "password").unwrap().token)
+        });
+        let [(alice, alice_token), _] = &users;
+        let post = manager
+            .create_forum_post(
+                alice_token,
+                "Gas".to_string(),
+                "@bob?".to_string(),
+                alice.clone(),
+                "general".to_string(),
+                Vec::new(),
+            )
+            .unwrap();
+        (manager, users, post)
+    }
+
+    #[test]
+    fn test_reply_to_post_refuses_another_session() {
+        let (mut manager, [(alice, _), (_, bob_token)], post) = post_by_alice();
+        assert!(matches!(
+            manager.reply_to_post(&post, &bob_token, &alice, "Forged".to_string(), None),
+            Err(CanvasError::PermissionDenied(_))
+        ));
+        assert!(manager.get_thread(&post).is_empty());
+    }
+
+    #[test]
+    fn test_mark_thread_read_refuses_another_session() {
+        let (mut manager, [(alice, _), (bob, bob_token)], post) = post_by_alice();
+        manager.reply_to_post(&post, &bob_token, &bob, "Try batching".to_string(), None).unwrap();
+        assert!(manager.mark_thread_read(&bob_token, &alice, &post).is_err());
+        assert_eq!(manager.unread_replies(&alice, &post), 1);
+    }
+
+    #[test]
+    fn test_mark_notifications_read_refuses_another_session() {
+        let (mut manager, [(alice, alice_token), (bob, _)], _) = post_by_alice();
+        assert!(manager.mark_notifications_read(&alice_token, &bob).is_err());
+        assert_eq!(manager.get_notifications(&bob, true).len(), 1);
+    }
+}
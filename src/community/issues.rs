@@ -0,0 +1,332 @@
+//! Project issues anchored to graph nodes
+//!
+//! An issue may reference nodes of its project's graph. Each reference keeps
+//! a snapshot of the node's connections when the issue was opened; when the
+//! graph is updated and a referenced node is removed or rewired, issues that
+//! asked for it are resolved automatically. Issues link into the editor with
+//! `canvas://projects/<project>/graph?select=<node>,<node>` deep links.
+
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::{CommunityManager, Project, ProjectVisibility};
+use crate::{
+    error::{CanvasError, CanvasResult},
+    types::{Graph, NodeId},
+};
+
+/// Scheme of links the editor opens
+pub const DEEP_LINK_SCHEME: &str = "canvas";
+
+/// Connections of a node, sorted
+type NodeSnapshot = Vec<(NodeId, NodeId)>;
+
+fn snapshot(graph: &Graph, node: NodeId) -> Option<NodeSnapshot> {
+    if !graph.nodes.contains(&node) {
+        return None;
+    }
+    let mut edges: NodeSnapshot = graph.edges.iter().copied().filter(|(from, to)| *from == node || *to == node).collect();
+    edges.sort();
+    Some(edges)
+}
+
+/// Issue state
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IssueStatus {
+    Open,
+    Resolved,
+}
+
+/// Why an issue was resolved
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "reason", rename_all = "snake_case")]
+pub enum Resolution {
+    Manual { user_id: String },
+    /// Referenced nodes were changed by a graph update
+    NodesChanged { nodes: Vec<NodeId> },
+}
+
+/// Issue on a project
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Issue {
+    pub id: String,
+    pub project_id: String,
+    /// Number within the project, from 1
+    pub number: u32,
+    pub title: String,
+    pub description: String,
+    pub author_id: String,
+    /// Referenced nodes and their connections when the issue was opened
+    pub nodes: BTreeMap<NodeId, NodeSnapshot>,
+    /// Resolve the issue when a referenced node changes
+    pub auto_resolve: bool,
+    pub status: IssueStatus,
+    pub resolution: Option<Resolution>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl Issue {
+    /// Editor link selecting the referenced nodes
+    pub fn deep_link(&self) -> String {
+        graph_link(&self.project_id, self.nodes.keys().copied())
+    }
+
+    /// Referenced nodes that were removed or rewired in `graph`
+    pub fn changed_nodes(&self, graph: &Graph) -> Vec<NodeId> {
+        self.nodes
+            .iter()
+            .filter(|(node, before)| snapshot(graph, **node).as_ref() != Some(*before))
+            .map(|(node, _)| *node)
+            .collect()
+    }
+}
+
+/// Project and nodes an editor link points at
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GraphLocation {
+    pub project_id: String,
+    pub nodes: Vec<NodeId>,
+}
+
+/// Editor link selecting `nodes` in a project's graph
+pub fn graph_link(project_id: &str, nodes: impl IntoIterator<Item = NodeId>) -> String {
+    let nodes: Vec<String> = nodes.into_iter().map(|n| n.to_string()).collect();
+    let mut link = format!("{}://projects/{}/graph", DEEP_LINK_SCHEME, project_id);
+    if !nodes.is_empty() {
+        link.push_str("?select=");
+        link.push_str(&nodes.join(","));
+    }
+    link
+}
+
+/// Parse an editor link made by [`graph_link`]
+pub fn parse_graph_link(link: &str) -> CanvasResult<GraphLocation> {
+    let invalid = || CanvasError::Validation(format!("Not a graph link: '{}'", link));
+    let rest = link
+        .strip_prefix(DEEP_LINK_SCHEME)
+        .and_then(|rest| rest.strip_prefix("://projects/"))
+        .ok_or_else(invalid)?;
+    let (path, query) = rest.split_once('?').unwrap_or((rest, ""));
+    let project_id = path.strip_suffix("/graph").filter(|id| !id.is_empty() && !id.contains('/')).ok_or_else(invalid)?;
+    let nodes = match query.strip_prefix("select=") {
+        Some(ids) => ids
+            .split(',')
+            .map(|id| NodeId::parse_str(id).map_err(|_| invalid()))
+            .collect::<CanvasResult<_>>()?,
+        None if query.is_empty() => Vec::new(),
+        None => return Err(invalid()),
+    };
+    Ok(GraphLocation {
+        project_id: project_id.to_string(),
+        nodes,
+    })
+}
+
+fn can_view(project: &Project, user_id: &str) -> bool {
+    project.visibility == ProjectVisibility::Public
+        || project.owner_id == user_id
+        || project.collaborators.iter().any(|c| c.user_id == user_id)
+}
+
+fn can_triage(project: &Project, issue: &Issue, user_id: &str) -> bool {
+    issue.author_id == user_id || project.owner_id == user_id || project.collaborators.iter().any(|c| c.user_id == user_id)
+}
+
+impl CommunityManager {
+    /// Open an issue on a project, optionally referencing nodes of its graph
+    pub fn open_issue(
+        &mut self,
+        project_id: &str,
+        author_id: &str,
+        title: String,
+        description: String,
+        nodes: &[NodeId],
+        auto_resolve: bool,
+    ) -> CanvasResult<String> {
+        if !self.users.contains_key(author_id) {
+            return Err(CanvasError::NotFound(format!("User '{}' not found", author_id)));
+        }
+        let project = self
+            .projects
+            .get(project_id)
+            .filter(|p| can_view(p, author_id))
+            .ok_or_else(|| CanvasError::NotFound(format!("Project '{}' not found", project_id)))?;
+        let nodes = nodes
+            .iter()
+            .map(|node| {
+                snapshot(&project.graph, *node)
+                    .map(|edges| (*node, edges))
+                    .ok_or_else(|| CanvasError::NodeNotFound(node.to_string()))
+            })
+            .collect::<CanvasResult<BTreeMap<_, _>>>()?;
+
+        let number = self.issues.values().filter(|i| i.project_id == project_id).count() as u32 + 1;
+        let issue_id = format!("issue_{}", uuid::Uuid::new_v4());
+        let now = Utc::now();
+        self.issues.insert(
+            issue_id.clone(),
+            Issue {
+                id: issue_id.clone(),
+                project_id: project_id.to_string(),
+                number,
+                title,
+                description,
+                author_id: author_id.to_string(),
+                nodes,
+                auto_resolve,
+                status: IssueStatus::Open,
+                resolution: None,
+                created_at: now,
+                updated_at: now,
+            },
+        );
+        Ok(issue_id)
+    }
+
+    pub fn get_issue(&self, issue_id: &str) -> Option<&Issue> {
+        self.issues.get(issue_id)
+    }
+
+    /// Issues of a project by number, optionally only those with `status`
+    pub fn get_issues(&self, project_id: &str, status: Option<IssueStatus>) -> Vec<&Issue> {
+        let mut issues: Vec<&Issue> = self
+            .issues
+            .values()
+            .filter(|i| i.project_id == project_id && status.map_or(true, |s| i.status == s))
+            .collect();
+        issues.sort_by_key(|i| i.number);
+        issues
+    }
+
+    /// Open issues referencing a node, for badges on the canvas
+    pub fn issues_for_node(&self, project_id: &str, node: NodeId) -> Vec<&Issue> {
+        self.get_issues(project_id, Some(IssueStatus::Open))
+            .into_iter()
+            .filter(|i| i.nodes.contains_key(&node))
+            .collect()
+    }
+
+    fn set_issue_status(&mut self, issue_id: &str, user_id: &str, resolution: Option<Resolution>) -> CanvasResult<()> {
+        let issue = self
+            .issues
+            .get_mut(issue_id)
+            .ok_or_else(|| CanvasError::NotFound(format!("Issue '{}' not found", issue_id)))?;
+        let project = self
+            .projects
+            .get(&issue.project_id)
+            .ok_or_else(|| CanvasError::NotFound(format!("Project '{}' not found", issue.project_id)))?;
+        if !can_triage(project, issue, user_id) {
+            return Err(CanvasError::PermissionDenied("Insufficient permissions".to_string()));
+        }
+        issue.status = if resolution.is_some() { IssueStatus::Resolved } else { IssueStatus::Open };
+        issue.resolution = resolution;
+        issue.updated_at = Utc::now();
+        Ok(())
+    }
+
+    /// Resolve an issue as the session's user, who must have opened it or
+    /// work on its project
+    pub fn resolve_issue(&mut self, issue_id: &str, token: &str) -> CanvasResult<()> {
+        let user_id = self.authenticate(token)?.id.clone();
+        let resolution = Resolution::Manual {
+            user_id: user_id.clone(),
+        };
+        self.set_issue_status(issue_id, &user_id, Some(resolution))
+    }
+
+    /// Reopen an issue as the session's user
+    pub fn reopen_issue(&mut self, issue_id: &str, token: &str) -> CanvasResult<()> {
+        let user_id = self.authenticate(token)?.id.clone();
+        self.set_issue_status(issue_id, &user_id, None)
+    }
+
+    /// Resolve auto-resolving issues whose nodes changed in the project's
+    /// current graph. Returns the IDs of the resolved issues.
+    pub(super) fn resolve_changed_issues(&mut self, project_id: &str) -> Vec<String> {
+        let Some(graph) = self.projects.get(project_id).map(|p| &p.graph) else {
+            return Vec::new();
+        };
+        let now = Utc::now();
+        let mut resolved = Vec::new();
+        for issue in self
+            .issues
+            .values_mut()
+            .filter(|i| i.project_id == project_id && i.status == IssueStatus::Open && i.auto_resolve)
+        {
+            let changed = issue.changed_nodes(graph);
+            if changed.is_empty() {
+                continue;
+            }
+            issue.status = IssueStatus::Resolved;
+            issue.resolution = Some(Resolution::NodesChanged { nodes: changed });
+            issue.updated_at = now;
+            resolved.push(issue.id.clone());
+        }
+        resolved
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::community::ProjectUpdate;
+
+    #[test]
+    fn test_issues_link_to_nodes_and_resolve_on_change() {
+        let mut manager = CommunityManager::new();
+        let alice = manager
+            .register_user("alice".to_string(), "alice@example.com".to_string(), "password".to_string())
+            .unwrap();
+        let (a, b, c) = (NodeId::new_v4(), NodeId::new_v4(), NodeId::new_v4());
+        let graph = Graph {
+            nodes: vec![a, b, c],
+            edges: vec![(a, b)],
+        };
+        let project = manager.create_project("Vault".to_string(), String::new(), alice.clone(), graph).unwrap();
+
+        let rewire = manager
+            .open_issue(&project, &alice, "Check b".to_string(), String::new(), &[b], true)
+            .unwrap();
+        let sticky = manager
+            .open_issue(&project, &alice, "Document c".to_string(), String::new(), &[c], false)
+            .unwrap();
+        assert!(manager
+            .open_issue(&project, &alice, "Ghost".to_string(), String::new(), &[NodeId::new_v4()], true)
+            .is_err());
+
+        let link = manager.get_issue(&rewire).unwrap().deep_link();
+        assert_eq!(
+            parse_graph_link(&link).unwrap(),
+            GraphLocation {
+                project_id: project.clone(),
+                nodes: vec![b]
+            }
+        );
+        assert_eq!(manager.issues_for_node(&project, b).len(), 1);
+
+        let update = ProjectUpdate {
+            name: None,
+            description: None,
+            visibility: None,
+            status: None,
+            graph: Some(Graph {
+                nodes: vec![a, b],
+                edges: vec![(a, b), (b, a)],
+            }),
+        };
+        let session = manager.login("alice", "password").unwrap();
+        manager.update_project(&project, &session.token, update).unwrap();
+        let resolved = manager.get_issue(&rewire).unwrap();
+        assert_eq!(resolved.status, IssueStatus::Resolved);
+        assert_eq!(resolved.resolution, Some(Resolution::NodesChanged { nodes: vec![b] }));
+        assert_eq!(manager.get_issue(&sticky).unwrap().status, IssueStatus::Open);
+        assert!(manager.resolve_issue(&sticky, &alice).is_err());
+        manager.resolve_issue(&sticky, &session.token).unwrap();
+        assert!(manager.get_issues(&project, Some(IssueStatus::Open)).is_empty());
+        assert!(parse_graph_link("https://example.com").is_err());
+    }
+}
@@ -0,0 +1,277 @@
+//! Leaderboards and community statistics
+//!
+//! Statistics are expensive to gather (they walk every item, review and usage
+//! record), so they are computed as a snapshot and served from a cache file
+//! that is refreshed once it is older than the refresh interval. Users who
+//! opt out of leaderboards are left out of every ranking, though their
+//! activity still counts towards the totals.
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::CommunityManager;
+use crate::{
+    config::Config,
+    error::{CanvasError, CanvasResult},
+    marketplace::{DailyUsage, LocalMarketplace, UsageStats},
+};
+
+/// File the statistics snapshot is cached in, under the data directory
+pub const COMMUNITY_STATS_FILE: &str = "community-stats.json";
+/// How long a snapshot is served before it is recomputed
+pub const DEFAULT_REFRESH_INTERVAL_HOURS: i64 = 6;
+/// Period over which item growth is measured, compared with the period before it
+pub const GROWTH_WINDOW_DAYS: i64 = 7;
+
+/// Ranked user or item
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LeaderboardEntry {
+    pub id: String,
+    pub value: f64,
+}
+
+/// Snapshot of community-wide statistics
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CommunityStatistics {
+    pub generated_at: DateTime<Utc>,
+    pub total_users: usize,
+    pub total_projects: usize,
+    pub total_forum_posts: usize,
+    pub total_items: usize,
+    pub total_downloads: u64,
+    /// Users by downloads of the items they authored
+    pub top_authors: Vec<LeaderboardEntry>,
+    /// Users by helpful votes on their reviews
+    pub helpful_reviewers: Vec<LeaderboardEntry>,
+    /// Items by growth in downloads and installs over the last week
+    pub fastest_growing: Vec<LeaderboardEntry>,
+}
+
+fn ranked(values: HashMap<String, f64>, limit: usize) -> Vec<LeaderboardEntry> {
+    let mut entries: Vec<LeaderboardEntry> = values
+        .into_iter()
+        .filter(|(_, value)| *value > 0.0)
+        .map(|(id, value)| LeaderboardEntry { id, value })
+        .collect();
+    entries.sort_by(|a, b| b.value.total_cmp(&a.value).then_with(|| a.id.cmp(&b.id)));
+    entries.truncate(limit);
+    entries
+}
+
+impl CommunityManager {
+    /// Leave the session's user out of, or put them back into, leaderboards
+    pub fn set_leaderboard_opt_out(&mut self, token: &str, user_id: &str, opt_out: bool) -> CanvasResult<()> {
+        self.authenticate_as(token, user_id)?;
+        if opt_out {
+            self.leaderboard_opt_out.insert(user_id.to_string());
+        } else {
+            self.leaderboard_opt_out.remove(user_id);
+        }
+        Ok(())
+    }
+
+    /// ID of the user an item author or reviewer name refers to, if they may
+    /// appear on leaderboards. Names that match no account are listed as-is.
+    fn listed_user(&self, name: &str) -> Option<String> {
+        let id = match self.users.get(name) {
+            Some(user) => user.id.clone(),
+            None => self.get_user_by_username(name).map_or_else(|| name.to_string(), |u| u.id.clone()),
+        };
+        (!self.leaderboard_opt_out.contains(&id)).then_some(id)
+    }
+
+    /// Compute community statistics from the current data
+    pub fn compute_statistics(
+        &self,
+        marketplace: &LocalMarketplace,
+        usage: &UsageStats,
+        now: DateTime<Utc>,
+        limit: usize,
+    ) -> CommunityStatistics {
+        let mut author_downloads: HashMap<String, f64> = HashMap::new();
+        let mut growth: HashMap<String, f64> = HashMap::new();
+        let window = Duration::days(GROWTH_WINDOW_DAYS);
+        let mut total_downloads = 0;
+        let mut total_items = 0;
+        for item in marketplace.items() {
+            total_items += 1;
+            total_downloads += item.downloads;
+            if let Some(author) = self.listed_user(&item.author) {
+                *author_downloads.entry(author).or_default() += item.downloads as f64;
+            }
+            let recent = usage.totals(&item.id, now - window + Duration::days(1), now);
+            let earlier = usage.totals(&item.id, now - window * 2 + Duration::days(1), now - window);
+            let activity = |u: &DailyUsage| (u.downloads + u.installs) as f64;
+            growth.insert(item.id.clone(), activity(&recent) - activity(&earlier));
+        }
+
+        let mut helpful: HashMap<String, f64> = HashMap::new();
+        for review in marketplace.reviews() {
+            if let Some(reviewer) = self.listed_user(&review.user_id) {
+                *helpful.entry(reviewer).or_default() += review.helpful_votes as f64;
+            }
+        }
+
+        CommunityStatistics {
+            generated_at: now,
+            total_users: self.users.len(),
+            total_projects: self.projects.len(),
+            total_forum_posts: self.forum_posts.len(),
+            total_items,
+            total_downloads,
+            top_authors: ranked(author_downloads, limit),
+            helpful_reviewers: ranked(helpful, limit),
+            fastest_growing: ranked(growth, limit),
+        }
+    }
+}
+
+/// Cached statistics snapshot
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StatisticsCache {
+    snapshot: Option<CommunityStatistics>,
+    #[serde(skip)]
+    path: Option<PathBuf>,
+}
+
+impl StatisticsCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cache file for a configuration
+    pub fn default_path(config: &Config) -> PathBuf {
+        config.app.data_dir.join(COMMUNITY_STATS_FILE)
+    }
+
+    /// Load the cache from `path`, starting empty if it does not exist yet.
+    /// [`save`](Self::save) writes back to the same file.
+    pub fn open(path: &Path) -> CanvasResult<Self> {
+        let mut cache: Self = if path.exists() {
+            serde_json::from_str(&std::fs::read_to_string(path)?)?
+        } else {
+            Self::default()
+        };
+        cache.path = Some(path.to_path_buf());
+        Ok(cache)
+    }
+
+    pub fn save(&self) -> CanvasResult<()> {
+        let path = self
+            .path
+            .as_ref()
+            .ok_or_else(|| CanvasError::InvalidState("Statistics cache was not opened from a file".to_string()))?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, serde_json::to_string(self)?)?;
+        Ok(())
+    }
+
+    /// Whether the snapshot is missing or older than `interval`
+    pub fn is_stale(&self, now: DateTime<Utc>, interval: Duration) -> bool {
+        self.snapshot.as_ref().map_or(true, |s| now - s.generated_at >= interval)
+    }
+
+    /// The cached snapshot, recomputed first if it is stale. Returns whether it
+    /// was recomputed along with the snapshot.
+    pub fn get_or_refresh(
+        &mut self,
+        now: DateTime<Utc>,
+        interval: Duration,
+        compute: impl FnOnce() -> CommunityStatistics,
+    ) -> (&CommunityStatistics, bool) {
+        let refreshed = self.is_stale(now, interval);
+        if refreshed {
+            log::info!("Recomputing community statistics");
+            self.snapshot = Some(compute());
+        }
+        (self.snapshot.as_ref().expect("snapshot present after refresh"), refreshed)
+    }
+
+    /// Drop the snapshot so the next request recomputes it, e.g. after a user
+    /// opts out
+    pub fn invalidate(&mut self) {
+        self.snapshot = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::marketplace::{Review, UsageEvent};
+
+    #[test]
+    fn test_rankings_honor_opt_out_and_cache() {
+        let mut manager = CommunityManager::new();
+        let alice = manager
+            .register_user("alice".to_string(), "alice@example.com".to_string(), "password".to_string())
+            .unwrap();
+        let bob = manager
+            .register_user("bob".to_string(), "bob@example.com".to_string(), "password".to_string())
+            .unwrap();
+
+        let mut marketplace = LocalMarketplace::new();
+        let mut item = crate::marketplace::test_package().item;
+        item.metadata.downloads = 40;
+        marketplace.add_custom_node(item).unwrap();
+        let now = Utc::now();
+        marketplace
+            .add_review(Review {
+                id: "r1".to_string(),
+                item_id: "clamp".to_string(),
+                user_id: bob.clone(),
+                rating: 5,
+                title: String::new(),
+                content: String::new(),
+                pros: Vec::new(),
+                cons: Vec::new(),
+                created_at: now,
+                updated_at: now,
+                helpful_votes: 0,
+                verified_purchase: false,
+            })
+            .unwrap();
+        marketplace.vote_review("r1", &alice, true).unwrap();
+        let mut usage = UsageStats::new();
+        usage.record("clamp", UsageEvent::Install, now).unwrap();
+
+        let stats = manager.compute_statistics(&marketplace, &usage, now, 10);
+        assert_eq!(stats.top_authors, vec![LeaderboardEntry { id: alice.clone(), value: 40.0 }]);
+        assert_eq!(stats.helpful_reviewers[0].id, bob);
+        assert_eq!(stats.fastest_growing[0].id, "clamp");
+
+        let alice_token = manager.login("alice", "password").unwrap().token;
+        manager.set_leaderboard_opt_out(&alice_token, &alice, true).unwrap();
+        let mut cache = StatisticsCache::new();
+        let interval = Duration::hours(DEFAULT_REFRESH_INTERVAL_HOURS);
+        let compute = || manager.compute_statistics(&marketplace, &usage, now, 10);
+        let (snapshot, refreshed) = cache.get_or_refresh(now, interval, compute);
+        assert!(refreshed && snapshot.top_authors.is_empty());
+        assert_eq!(snapshot.total_downloads, 40);
+        assert!(!cache.get_or_refresh(now + Duration::hours(1), interval, compute).1);
+        assert!(cache.get_or_refresh(now + interval, interval, compute).1);
+    }
+
+    #[test]
+    fn test_opt_out_refuses_another_session() {
+        let mut manager = CommunityManager::new();
+        let alice = manager
+            .register_user("alice".to_string(), "alice@example.com".to_string(), "password".to_string())
+            .unwrap();
+        manager
+            .register_user("bob".to_string(), "bob@example.com".to_string(), "password".to_string())
+            .unwrap();
+        let bob_token = manager.login("bob", "password").unwrap().token;
+        assert!(matches!(
+            manager.set_leaderboard_opt_out(&bob_token, &alice, true),
+            Err(CanvasError::PermissionDenied(_))
+        ));
+        assert!(manager.listed_user(&alice).is_some());
+    }
+}
@@ -0,0 +1,403 @@
+//! Private messages between community users
+//!
+//! Messages are exchanged in conversations of two or more participants. A user
+//! can block another, which stops messages in either direction, and mute a
+//! conversation, which keeps its messages but stops its notifications. Starting
+//! conversations, sending and blocking act as the user of a session token;
+//! messaging also requires their `can_message` permission.
+
+use std::collections::BTreeSet;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::{CommunityManager, NotificationKind, Permission, DELETED_USER};
+use crate::{
+    config::ContentRetention,
+    error::{CanvasError, CanvasResult},
+};
+
+/// Private conversation
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Conversation {
+    pub id: String,
+    pub participants: BTreeSet<String>,
+    pub title: Option<String>,
+    pub created_at: DateTime<Utc>,
+    /// Time of the latest message
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Message in a conversation
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DirectMessage {
+    pub id: String,
+    pub conversation_id: String,
+    pub sender_id: String,
+    pub content: String,
+    pub sent_at: DateTime<Utc>,
+    /// Participants who have read the message
+    pub read_by: BTreeSet<String>,
+    pub is_deleted: bool,
+}
+
+impl CommunityManager {
+    /// Whether either user has blocked the other
+    pub fn is_blocked(&self, a: &str, b: &str) -> bool {
+        let blocks = |user: &str, other: &str| self.blocked_users.get(user).is_some_and(|set| set.contains(other));
+        blocks(a, b) || blocks(b, a)
+    }
+
+    /// `Err` unless `sender_id` may send messages to `recipient_id`
+    pub fn check_can_message(&self, sender_id: &str, recipient_id: &str) -> CanvasResult<()> {
+        let sender = self
+            .users
+            .get(sender_id)
+            .ok_or_else(|| CanvasError::NotFound(format!("User '{}' not found", sender_id)))?;
+        if !sender.permissions.can_message {
+            return Err(CanvasError::PermissionDenied(format!("User '{}' may not send messages", sender_id)));
+        }
+        if !self.users.contains_key(recipient_id) {
+            return Err(CanvasError::NotFound(format!("User '{}' not found", recipient_id)));
+        }
+        if self.is_blocked(sender_id, recipient_id) {
+            return Err(CanvasError::PermissionDenied(format!(
+                "Messages between '{}' and '{}' are blocked",
+                sender_id, recipient_id
+            )));
+        }
+        Ok(())
+    }
+
+    /// Block a user on behalf of the session's user
+    pub fn block_user(&mut self, token: &str, blocked_id: &str) -> CanvasResult<()> {
+        let user_id = self.authenticate(token)?.id.clone();
+        if user_id == blocked_id {
+            return Err(CanvasError::Validation("Users cannot block themselves".to_string()));
+        }
+        if !self.users.contains_key(blocked_id) {
+            return Err(CanvasError::NotFound(format!("User '{}' not found", blocked_id)));
+        }
+        self.blocked_users.entry(user_id).or_default().insert(blocked_id.to_string());
+        Ok(())
+    }
+
+    /// Lift a block the session's user placed
+    pub fn unblock_user(&mut self, token: &str, blocked_id: &str) -> CanvasResult<()> {
+        let user_id = self.authenticate(token)?.id.clone();
+        if let Some(blocked) = self.blocked_users.get_mut(&user_id) {
+            blocked.remove(blocked_id);
+        }
+        Ok(())
+    }
+
+    /// Start a conversation as the session's user. A conversation between the
+    /// same two users is reused rather than duplicated.
+    pub fn start_conversation(
+        &mut self,
+        token: &str,
+        others: &[String],
+        title: Option<String>,
+    ) -> CanvasResult<String> {
+        let creator = self.authorize(token, Permission::Message)?.id.clone();
+        let creator_id = creator.as_str();
+        if others.is_empty() {
+            return Err(CanvasError::Validation("A conversation needs another participant".to_string()));
+        }
+        for other in others {
+            self.check_can_message(creator_id, other)?;
+        }
+        let mut participants: BTreeSet<String> = others.iter().cloned().collect();
+        participants.insert(creator_id.to_string());
+
+        if participants.len() == 2 && title.is_none() {
+            if let Some(existing) = self
+                .conversations
+                .values()
+                .find(|c| c.participants == participants && c.title.is_none())
+            {
+                return Ok(existing.id.clone());
+            }
+        }
+
+        let conversation_id = format!("conversation_{}", uuid::Uuid::new_v4());
+        let now = Utc::now();
+        self.conversations.insert(
+            conversation_id.clone(),
+            Conversation {
+                id: conversation_id.clone(),
+                participants,
+                title,
+                created_at: now,
+                updated_at: now,
+            },
+        );
+        Ok(conversation_id)
+    }
+
+    fn participant_conversation(&self, conversation_id: &str, user_id: &str) -> CanvasResult<&Conversation> {
+        self.conversations
+            .get(conversation_id)
+            .filter(|c| c.participants.contains(user_id))
+            .ok_or_else(|| CanvasError::NotFound(format!("Conversation '{}' not found", conversation_id)))
+    }
+
+    /// Send a message as the session's user. Fails if the sender is blocked
+    /// by, or has blocked, any other participant.
+    pub fn send_message(&mut self, conversation_id: &str, token: &str, content: String) -> CanvasResult<String> {
+        let sender = self.authorize(token, Permission::Message)?.id.clone();
+        let sender_id = sender.as_str();
+        if content.trim().is_empty() {
+            return Err(CanvasError::Validation("Message is empty".to_string()));
+        }
+        let conversation = self.participant_conversation(conversation_id, sender_id)?;
+        let recipients: Vec<String> = conversation.participants.iter().filter(|p| *p != sender_id).cloned().collect();
+        for recipient in &recipients {
+            self.check_can_message(sender_id, recipient)?;
+        }
+
+        let message_id = format!("message_{}", uuid::Uuid::new_v4());
+        let now = Utc::now();
+        self.messages.entry(conversation_id.to_string()).or_default().push(DirectMessage {
+            id: message_id.clone(),
+            conversation_id: conversation_id.to_string(),
+            sender_id: sender_id.to_string(),
+            content,
+            sent_at: now,
+            read_by: BTreeSet::from([sender_id.to_string()]),
+            is_deleted: false,
+        });
+        if let Some(conversation) = self.conversations.get_mut(conversation_id) {
+            conversation.updated_at = now;
+        }
+
+        for recipient in recipients {
+            if self.is_conversation_muted(&recipient, conversation_id) {
+                continue;
+            }
+            let kind = NotificationKind::Message {
+                conversation_id: conversation_id.to_string(),
+                message_id: message_id.clone(),
+            };
+            self.notify(&recipient, sender_id, kind);
+        }
+        Ok(message_id)
+    }
+
+    /// Messages of a conversation, oldest first
+    pub fn get_messages(&self, conversation_id: &str, user_id: &str) -> CanvasResult<Vec<&DirectMessage>> {
+        self.participant_conversation(conversation_id, user_id)?;
+        Ok(self
+            .messages
+            .get(conversation_id)
+            .map(|messages| messages.iter().filter(|m| !m.is_deleted).collect())
+            .unwrap_or_default())
+    }
+
+    /// A user's conversations, most recently active first
+    pub fn get_conversations(&self, user_id: &str) -> Vec<&Conversation> {
+        let mut conversations: Vec<&Conversation> =
+            self.conversations.values().filter(|c| c.participants.contains(user_id)).collect();
+        conversations.sort_by(|a, b| b.updated_at.cmp(&a.updated_at).then_with(|| a.id.cmp(&b.id)));
+        conversations
+    }
+
+    /// Mark a conversation read for the session's user. Returns how many
+    /// messages were unread.
+    pub fn mark_conversation_read(&mut self, conversation_id: &str, token: &str, user_id: &str) -> CanvasResult<usize> {
+        self.authenticate_as(token, user_id)?;
+        self.participant_conversation(conversation_id, user_id)?;
+        let mut marked = 0;
+        for message in self.messages.get_mut(conversation_id).into_iter().flatten() {
+            if message.read_by.insert(user_id.to_string()) {
+                marked += 1;
+            }
+        }
+        Ok(marked)
+    }
+
+    /// Unread messages across all of a user's conversations
+    pub fn unread_message_count(&self, user_id: &str) -> usize {
+        self.get_conversations(user_id)
+            .iter()
+            .filter_map(|c| self.messages.get(&c.id))
+            .flatten()
+            .filter(|m| !m.is_deleted && !m.read_by.contains(user_id))
+            .count()
+    }
+
+    pub fn mute_conversation(&mut self, token: &str, user_id: &str, conversation_id: &str) -> CanvasResult<()> {
+        self.authenticate_as(token, user_id)?;
+        self.participant_conversation(conversation_id, user_id)?;
+        self.muted_conversations
+            .entry(user_id.to_string())
+            .or_default()
+            .insert(conversation_id.to_string());
+        Ok(())
+    }
+
+    pub fn unmute_conversation(&mut self, token: &str, user_id: &str, conversation_id: &str) -> CanvasResult<()> {
+        self.authenticate_as(token, user_id)?;
+        self.clear_mute(user_id, conversation_id);
+        Ok(())
+    }
+
+    fn clear_mute(&mut self, user_id: &str, conversation_id: &str) {
+        if let Some(muted) = self.muted_conversations.get_mut(user_id) {
+            muted.remove(conversation_id);
+        }
+    }
+
+    pub fn is_conversation_muted(&self, user_id: &str, conversation_id: &str) -> bool {
+        self.muted_conversations.get(user_id).is_some_and(|muted| muted.contains(conversation_id))
+    }
+
+    /// Leave a conversation as the session's user; it is deleted once nobody
+    /// is left in it
+    pub fn leave_conversation(&mut self, conversation_id: &str, token: &str, user_id: &str) -> CanvasResult<()> {
+        self.authenticate_as(token, user_id)?;
+        self.remove_participant(conversation_id, user_id)
+    }
+
+    fn remove_participant(&mut self, conversation_id: &str, user_id: &str) -> CanvasResult<()> {
+        self.participant_conversation(conversation_id, user_id)?;
+        let conversation = self.conversations.get_mut(conversation_id).expect("checked above");
+        conversation.participants.remove(user_id);
+        if conversation.participants.is_empty() {
+            self.conversations.remove(conversation_id);
+            self.messages.remove(conversation_id);
+        }
+        self.clear_mute(user_id, conversation_id);
+        Ok(())
+    }
+
+    /// Messages a user sent, for a data export
+    pub(super) fn user_messages(&self, user_id: &str) -> Vec<&DirectMessage> {
+        self.messages.values().flatten().filter(|m| m.sender_id == user_id).collect()
+    }
+
+    /// Remove a user from conversations and block and mute lists, and
+    /// anonymize or blank the messages they sent. Returns how many messages
+    /// were affected.
+    pub(super) fn erase_messaging(&mut self, user_id: &str, retention: ContentRetention) -> usize {
+        self.blocked_users.remove(user_id);
+        for blocked in self.blocked_users.values_mut() {
+            blocked.remove(user_id);
+        }
+        self.muted_conversations.remove(user_id);
+        let conversations: Vec<String> = self.get_conversations(user_id).iter().map(|c| c.id.clone()).collect();
+        for conversation_id in conversations {
+            self.remove_participant(&conversation_id, user_id).ok();
+        }
+
+        let mut affected = 0;
+        for message in self.messages.values_mut().flatten() {
+            message.read_by.remove(user_id);
+            if message.sender_id != user_id {
+                continue;
+            }
+            message.sender_id = DELETED_USER.to_string();
+            if retention == ContentRetention::Remove {
+                message.content.clear();
+                message.is_deleted = true;
+            }
+            affected += 1;
+        }
+        affected
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_messages_respect_blocks_and_mutes() {
+        let mut manager = CommunityManager::new();
+        let mut register = |name: &str| {
+            manager
+                .register_user(name.to_string(), format!("{}@example.com", name), "password".to_string())
+                .unwrap()
+        };
+        let (alice, bob, carol) = (register("alice"), register("bob"), register("carol"));
+        let mut login = |name: &str| manager.login(name, "password").unwrap().token;
+        let (alice_token, bob_token, carol_token) = (login("alice"), login("bob"), login("carol"));
+
+        // A user id is not a credential
+        assert!(manager.send_message("conversation", &alice, "hi".to_string()).is_err());
+        let direct = manager.start_conversation(&alice_token, &[bob.clone()], None).unwrap();
+        assert_eq!(manager.start_conversation(&bob_token, &[alice.clone()], None).unwrap(), direct);
+        manager.send_message(&direct, &alice_token, "Can you review the oracle node?".to_string()).unwrap();
+        assert_eq!(manager.unread_message_count(&bob), 1);
+        assert_eq!(manager.get_notifications(&bob, true).len(), 1);
+        assert!(manager.get_messages(&direct, &carol).is_err());
+
+        let team = manager
+            .start_conversation(&alice_token, &[bob.clone(), carol.clone()], Some("Oracle v2".to_string()))
+            .unwrap();
+        manager.mute_conversation(&bob_token, &bob, &team).unwrap();
+        manager.send_message(&team, &carol_token, "Shipping Friday".to_string()).unwrap();
+        assert_eq!(manager.get_notifications(&bob, true).len(), 1);
+        assert_eq!(manager.unread_message_count(&bob), 2);
+        assert_eq!(manager.mark_conversation_read(&team, &bob_token, &bob).unwrap(), 1);
+
+        manager.block_user(&bob_token, &alice).unwrap();
+        assert!(manager.send_message(&direct, &alice_token, "ping".to_string()).is_err());
+        assert!(manager.send_message(&direct, &bob_token, "ping".to_string()).is_err());
+        manager.unblock_user(&bob_token, &alice).unwrap();
+        manager.send_message(&direct, &bob_token, "Done".to_string()).unwrap();
+        assert_eq!(manager.get_conversations(&alice)[0].id, direct);
+    }
+
+    /// Alice and Bob in a conversation, with their ids and session tokens
+    fn pair() -> (CommunityManager, [(String, String); 2], String) {
+        let mut manager = CommunityManager::new();
+        let users = ["alice", "bob"].map(|name| {
+            let id = manager
+                .register_user(name.to_string(), format!("{}@example.com", name), "password".to_string())
+                .unwrap();
+            (id, manager.login(name, "password").unwrap().token)
+        });
+        let conversation = manager.start_conversation(&users[0].1, &[users[1].0.clone()], None).unwrap();
+        (manager, users, conversation)
+    }
+
+    #[test]
+    fn test_mark_conversation_read_refuses_another_session() {
+        let (mut manager, [(_, alice_token), (bob, bob_token)], conversation) = pair();
+        manager.send_message(&conversation, &alice_token, "hi".to_string()).unwrap();
+        assert!(matches!(
+            manager.mark_conversation_read(&conversation, &alice_token, &bob),
+            Err(CanvasError::PermissionDenied(_))
+        ));
+        assert_eq!(manager.mark_conversation_read(&conversation, &bob_token, &bob).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_mute_conversation_refuses_another_session() {
+        let (mut manager, [(_, alice_token), (bob, bob_token)], conversation) = pair();
+        assert!(manager.mute_conversation(&alice_token, &bob, &conversation).is_err());
+        assert!(!manager.is_conversation_muted(&bob, &conversation));
+        manager.mute_conversation(&bob_token, &bob, &conversation).unwrap();
+        assert!(manager.is_conversation_muted(&bob, &conversation));
+    }
+
+    #[test]
+    fn test_unmute_conversation_refuses_another_session() {
+        let (mut manager, [(_, alice_token), (bob, bob_token)], conversation) = pair();
+        manager.mute_conversation(&bob_token, &bob, &conversation).unwrap();
+        assert!(manager.unmute_conversation(&alice_token, &bob, &conversation).is_err());
+        assert!(manager.is_conversation_muted(&bob, &conversation));
+        manager.unmute_conversation(&bob_token, &bob, &conversation).unwrap();
+        assert!(!manager.is_conversation_muted(&bob, &conversation));
+    }
+
+    #[test]
+    fn test_leave_conversation_refuses_another_session() {
+        let (mut manager, [(_, alice_token), (bob, bob_token)], conversation) = pair();
+        assert!(manager.leave_conversation(&conversation, &alice_token, &bob).is_err());
+        assert!(manager.get_messages(&conversation, &bob).is_ok());
+        manager.leave_conversation(&conversation, &bob_token, &bob).unwrap();
+        assert!(manager.get_messages(&conversation, &bob).is_err());
+    }
+}
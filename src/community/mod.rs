@@ -1,15 +1,49 @@
 //! Community features for Canvas Contracts
 
 use crate::{
+    config::Config,
     error::{CanvasError, CanvasResult},
     types::{Graph, Node, NodeId},
     marketplace::{MarketplaceItem, UserProfile},
 };
 
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::{
+    collections::{BTreeSet, HashMap},
+    path::{Path, PathBuf},
+};
 use chrono::{DateTime, Utc};
 
+mod auth;
+mod feed;
+mod forum;
+mod issues;
+mod leaderboard;
+mod messaging;
+mod presence;
+mod privacy;
+mod reviews;
+
+pub use auth::{hash_password, verify_password, Permission, Session, MIN_PASSWORD_LENGTH, SESSION_TTL_DAYS};
+pub use feed::{ActivityEvent, ActivityKind, ActivityVisibility, FeedPage};
+pub use forum::{parse_mentions, ForumReply, Notification, NotificationKind, ThreadEntry};
+pub use issues::{
+    graph_link, parse_graph_link, GraphLocation, Issue, IssueStatus, Resolution, DEEP_LINK_SCHEME,
+};
+pub use leaderboard::{
+    CommunityStatistics, LeaderboardEntry, StatisticsCache, COMMUNITY_STATS_FILE, DEFAULT_REFRESH_INTERVAL_HOURS,
+    GROWTH_WINDOW_DAYS,
+};
+pub use messaging::{Conversation, DirectMessage};
+pub use presence::{
+    ClientMessage, EditSession, PresenceEvent, PresenceHub, SoftLock, DEFAULT_LOCK_TTL_SECS,
+    DEFAULT_SESSION_TIMEOUT_SECS,
+};
+pub use privacy::{DeletionReport, DELETED_USER};
+
+/// File community data is kept in, under the data directory
+pub const COMMUNITY_FILE: &str = "community.json";
+
 /// User role in the community
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum UserRole {
@@ -28,6 +62,12 @@ pub struct UserPermissions {
     pub can_rate: bool,
     pub can_moderate: bool,
     pub can_admin: bool,
+    #[serde(default = "default_can_message")]
+    pub can_message: bool,
+}
+
+fn default_can_message() -> bool {
+    true
 }
 
 /// Community user
@@ -113,7 +153,7 @@ pub struct CollaboratorPermissions {
 }
 
 /// Project visibility
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum ProjectVisibility {
     Private,
     Public,
@@ -165,7 +205,7 @@ pub struct ForumPost {
 }
 
 /// Post status
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum PostStatus {
     Active,
     Closed,
@@ -200,7 +240,7 @@ pub enum TutorialDifficulty {
 }
 
 /// Tutorial status
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum TutorialStatus {
     Draft,
     Published,
@@ -208,12 +248,51 @@ pub enum TutorialStatus {
 }
 
 /// Community manager
+#[derive(Default, Serialize, Deserialize)]
 pub struct CommunityManager {
     users: HashMap<String, CommunityUser>,
     projects: HashMap<String, Project>,
     comments: HashMap<String, Comment>,
     forum_posts: HashMap<String, ForumPost>,
     tutorials: HashMap<String, Tutorial>,
+    #[serde(default)]
+    forum_replies: HashMap<String, ForumReply>,
+    /// Subscribers of each forum thread
+    #[serde(default)]
+    thread_subscriptions: HashMap<String, BTreeSet<String>>,
+    /// When each user last read each thread
+    #[serde(default)]
+    thread_reads: HashMap<String, HashMap<String, DateTime<Utc>>>,
+    #[serde(default)]
+    notifications: Vec<Notification>,
+    #[serde(default)]
+    conversations: HashMap<String, Conversation>,
+    /// Messages of each conversation, oldest first
+    #[serde(default)]
+    messages: HashMap<String, Vec<DirectMessage>>,
+    /// Users each user has blocked
+    #[serde(default)]
+    blocked_users: HashMap<String, BTreeSet<String>>,
+    #[serde(default)]
+    muted_conversations: HashMap<String, BTreeSet<String>>,
+    /// Published activity, oldest first
+    #[serde(default)]
+    activities: Vec<ActivityEvent>,
+    #[serde(default)]
+    activity_visibility: HashMap<String, ActivityVisibility>,
+    /// Users left out of leaderboards
+    #[serde(default)]
+    leaderboard_opt_out: BTreeSet<String>,
+    #[serde(default)]
+    issues: HashMap<String, Issue>,
+    /// Argon2 password hash of each user
+    #[serde(default)]
+    credentials: HashMap<String, String>,
+    /// Live sessions, keyed by the digest of their token
+    #[serde(default)]
+    sessions: HashMap<String, auth::StoredSession>,
+    #[serde(skip)]
+    path: Option<PathBuf>,
 }
 
 impl CommunityManager {
@@ -225,15 +304,60 @@ impl CommunityManager {
             comments: HashMap::new(),
             forum_posts: HashMap::new(),
             tutorials: HashMap::new(),
+            forum_replies: HashMap::new(),
+            thread_subscriptions: HashMap::new(),
+            thread_reads: HashMap::new(),
+            notifications: Vec::new(),
+            conversations: HashMap::new(),
+            messages: HashMap::new(),
+            blocked_users: HashMap::new(),
+            muted_conversations: HashMap::new(),
+            activities: Vec::new(),
+            activity_visibility: HashMap::new(),
+            leaderboard_opt_out: BTreeSet::new(),
+            issues: HashMap::new(),
+            credentials: HashMap::new(),
+            sessions: HashMap::new(),
+            path: None,
         }
     }
 
-    /// Register a new user
+    /// Community data file for a configuration
+    pub fn default_path(config: &Config) -> PathBuf {
+        config.app.data_dir.join(COMMUNITY_FILE)
+    }
+
+    /// Load community data from `path`, starting empty if it does not exist
+    /// yet. [`save`](Self::save) writes back to the same file.
+    pub fn open(path: &Path) -> CanvasResult<Self> {
+        let mut manager: Self = if path.exists() {
+            serde_json::from_str(&std::fs::read_to_string(path)?)?
+        } else {
+            Self::new()
+        };
+        manager.path = Some(path.to_path_buf());
+        Ok(manager)
+    }
+
+    pub fn save(&self) -> CanvasResult<()> {
+        let path = self
+            .path
+            .as_ref()
+            .ok_or_else(|| CanvasError::InvalidState("Community data was not opened from a file".to_string()))?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, serde_json::to_string(self)?)?;
+        Ok(())
+    }
+
+    /// Register a new user, storing an Argon2 hash of their password.
+    /// [`login`](Self::login) starts a session for them.
     pub fn register_user(
         &mut self,
         username: String,
         email: String,
-        password_hash: String,
+        password: String,
     ) -> CanvasResult<String> {
         // Check if username already exists
         if self.users.values().any(|u| u.username == username) {
@@ -247,6 +371,7 @@ impl CommunityManager {
 
         let user_id = format!("user_{}", uuid::Uuid::new_v4());
         let now = Utc::now();
+        self.set_password(&user_id, &password)?;
 
         let user = CommunityUser {
             id: user_id.clone(),
@@ -259,6 +384,7 @@ impl CommunityManager {
                 can_rate: true,
                 can_moderate: false,
                 can_admin: false,
+                can_message: true,
             },
             profile: UserProfile {
                 username: user_id.clone(),
@@ -297,13 +423,14 @@ impl CommunityManager {
         self.users.values().find(|u| u.username == username)
     }
 
-    /// Update user profile
+    /// Update the profile of the session's user
     pub fn update_user_profile(
         &mut self,
-        user_id: &str,
+        token: &str,
         profile: UserProfile,
     ) -> CanvasResult<()> {
-        if let Some(user) = self.users.get_mut(user_id) {
+        let user_id = self.authenticate(token)?.id.clone();
+        if let Some(user) = self.users.get_mut(&user_id) {
             user.profile = profile;
             Ok(())
         } else {
@@ -350,16 +477,19 @@ impl CommunityManager {
         self.projects.get(project_id)
     }
 
-    /// Update project
+    /// Update a project as the session's user, who must own it, administer
+    /// it as a collaborator, or be a community admin
     pub fn update_project(
         &mut self,
         project_id: &str,
-        user_id: &str,
+        token: &str,
         updates: ProjectUpdate,
     ) -> CanvasResult<()> {
+        let user = self.authenticate(token)?;
+        let (user_id, is_admin) = (user.id.clone(), user.permissions.allows(Permission::Admin));
         if let Some(project) = self.projects.get_mut(project_id) {
             // Check permissions
-            if project.owner_id != user_id && 
+            if project.owner_id != user_id && !is_admin &&
                !project.collaborators.iter().any(|c| c.user_id == user_id && c.role == CollaboratorRole::Admin) {
                 return Err(CanvasError::PermissionDenied("Insufficient permissions".to_string()));
             }
@@ -371,31 +501,49 @@ impl CommunityManager {
             if let Some(description) = updates.description {
                 project.description = description;
             }
+            // Going public is announced in the owner's activity feed
+            let mut published = None;
             if let Some(visibility) = updates.visibility {
+                if visibility == ProjectVisibility::Public && project.visibility != ProjectVisibility::Public {
+                    published = Some(project.owner_id.clone());
+                }
                 project.visibility = visibility;
             }
             if let Some(status) = updates.status {
                 project.status = status;
             }
+            let graph_changed = updates.graph.is_some();
             if let Some(graph) = updates.graph {
                 project.graph = graph;
             }
 
             project.updated_at = Utc::now();
+            if let Some(owner_id) = published {
+                let kind = ActivityKind::ProjectPublished {
+                    project_id: project_id.to_string(),
+                };
+                self.push_activity(&owner_id, kind)?;
+            }
+            if graph_changed {
+                for issue_id in self.resolve_changed_issues(project_id) {
+                    log::info!("Resolved issue {}: its nodes changed", issue_id);
+                }
+            }
             Ok(())
         } else {
             Err(CanvasError::NotFound(format!("Project '{}' not found", project_id)))
         }
     }
 
-    /// Add collaborator to project
+    /// Add a collaborator to a project owned by the session's user
     pub fn add_collaborator(
         &mut self,
         project_id: &str,
-        owner_id: &str,
+        token: &str,
         collaborator_id: &str,
         role: CollaboratorRole,
     ) -> CanvasResult<()> {
+        let owner_id = self.authenticate(token)?.id.clone();
         if let Some(project) = self.projects.get_mut(project_id) {
             if project.owner_id != owner_id {
                 return Err(CanvasError::PermissionDenied("Only project owner can add collaborators".to_string()));
@@ -443,16 +591,15 @@ impl CommunityManager {
         }
     }
 
-    /// Add comment
+    /// Add a comment as the session's user
     pub fn add_comment(
         &mut self,
+        token: &str,
         author_id: &str,
         content: String,
         parent_id: Option<String>,
     ) -> CanvasResult<String> {
-        if !self.users.contains_key(author_id) {
-            return Err(CanvasError::NotFound(format!("User '{}' not found", author_id)));
-        }
+        self.authorize_as(token, author_id, Permission::Comment)?;
 
         let comment_id = format!("comment_{}", uuid::Uuid::new_v4());
         let now = Utc::now();
@@ -482,18 +629,17 @@ impl CommunityManager {
             .collect()
     }
 
-    /// Create forum post
+    /// Create a forum post as the session's user
     pub fn create_forum_post(
         &mut self,
+        token: &str,
         title: String,
         content: String,
         author_id: String,
         category: String,
         tags: Vec<String>,
     ) -> CanvasResult<String> {
-        if !self.users.contains_key(&author_id) {
-            return Err(CanvasError::NotFound(format!("User '{}' not found", author_id)));
-        }
+        self.authorize_as(token, &author_id, Permission::Comment)?;
 
         let post_id = format!("post_{}", uuid::Uuid::new_v4());
         let now = Utc::now();
@@ -516,6 +662,7 @@ impl CommunityManager {
         };
 
         self.forum_posts.insert(post_id.clone(), post);
+        self.on_forum_post_created(&post_id);
         Ok(post_id)
     }
 
@@ -568,6 +715,28 @@ impl CommunityManager {
         Ok(tutorial_id)
     }
 
+    /// Publish a draft tutorial written by the session's user
+    pub fn publish_tutorial(&mut self, tutorial_id: &str, token: &str) -> CanvasResult<()> {
+        let user_id = self.authorize(token, Permission::Publish)?.id.clone();
+        let tutorial = self
+            .tutorials
+            .get_mut(tutorial_id)
+            .ok_or_else(|| CanvasError::NotFound(format!("Tutorial '{}' not found", tutorial_id)))?;
+        if tutorial.author_id != user_id {
+            return Err(CanvasError::PermissionDenied("Insufficient permissions".to_string()));
+        }
+        if tutorial.status == TutorialStatus::Published {
+            return Ok(());
+        }
+        tutorial.status = TutorialStatus::Published;
+        tutorial.updated_at = Utc::now();
+        let kind = ActivityKind::TutorialPublished {
+            tutorial_id: tutorial_id.to_string(),
+        };
+        self.push_activity(&user_id, kind)?;
+        Ok(())
+    }
+
     /// Get tutorials
     pub fn get_tutorials(&self, difficulty: Option<TutorialDifficulty>) -> Vec<&Tutorial> {
         self.tutorials
@@ -579,8 +748,10 @@ impl CommunityManager {
             .collect()
     }
 
-    /// Follow user
-    pub fn follow_user(&mut self, follower_id: &str, followed_id: &str) -> CanvasResult<()> {
+    /// Follow a user as the session's user
+    pub fn follow_user(&mut self, token: &str, followed_id: &str) -> CanvasResult<()> {
+        let follower = self.authenticate(token)?.id.clone();
+        let follower_id = follower.as_str();
         if follower_id == followed_id {
             return Err(CanvasError::Validation("Cannot follow yourself".to_string()));
         }
@@ -604,8 +775,10 @@ impl CommunityManager {
         Ok(())
     }
 
-    /// Unfollow user
-    pub fn unfollow_user(&mut self, follower_id: &str, followed_id: &str) -> CanvasResult<()> {
+    /// Stop following a user as the session's user
+    pub fn unfollow_user(&mut self, token: &str, followed_id: &str) -> CanvasResult<()> {
+        let follower = self.authenticate(token)?.id.clone();
+        let follower_id = follower.as_str();
         if let Some(follower) = self.users.get_mut(follower_id) {
             follower.following.retain(|id| id != followed_id);
         }
@@ -617,8 +790,9 @@ impl CommunityManager {
         Ok(())
     }
 
-    /// Award badge to user
-    pub fn award_badge(&mut self, user_id: &str, badge: Badge) -> CanvasResult<()> {
+    /// Award a badge to a user; the session's user must be a moderator
+    pub fn award_badge(&mut self, token: &str, user_id: &str, badge: Badge) -> CanvasResult<()> {
+        self.authorize(token, Permission::Moderate)?;
         if let Some(user) = self.users.get_mut(user_id) {
             if !user.badges.iter().any(|b| b.id == badge.id) {
                 user.badges.push(badge);
@@ -689,7 +863,7 @@ mod tests {
         let user_id = manager.register_user(
             "testuser".to_string(),
             "test@example.com".to_string(),
-            "password_hash".to_string(),
+            "password".to_string(),
         ).unwrap();
 
         assert!(manager.get_user(&user_id).is_some());
@@ -703,13 +877,13 @@ mod tests {
         manager.register_user(
             "testuser".to_string(),
             "test1@example.com".to_string(),
-            "password_hash".to_string(),
+            "password".to_string(),
         ).unwrap();
 
         let result = manager.register_user(
             "testuser".to_string(),
             "test2@example.com".to_string(),
-            "password_hash".to_string(),
+            "password".to_string(),
         );
 
         assert!(result.is_err());
@@ -722,7 +896,7 @@ mod tests {
         let user_id = manager.register_user(
             "testuser".to_string(),
             "test@example.com".to_string(),
-            "password_hash".to_string(),
+            "password".to_string(),
         ).unwrap();
 
         let graph = Graph::new();
@@ -743,16 +917,18 @@ mod tests {
         let user1_id = manager.register_user(
             "user1".to_string(),
             "user1@example.com".to_string(),
-            "password_hash".to_string(),
+            "password".to_string(),
         ).unwrap();
 
         let user2_id = manager.register_user(
             "user2".to_string(),
             "user2@example.com".to_string(),
-            "password_hash".to_string(),
+            "password".to_string(),
         ).unwrap();
 
-        manager.follow_user(&user1_id, &user2_id).unwrap();
+        assert!(manager.follow_user(&user1_id, &user2_id).is_err());
+        let session = manager.login("user1", "password").unwrap();
+        manager.follow_user(&session.token, &user2_id).unwrap();
 
         let user1 = manager.get_user(&user1_id).unwrap();
         let user2 = manager.get_user(&user2_id).unwrap();
@@ -768,7 +944,7 @@ mod tests {
         let user_id = manager.register_user(
             "testuser".to_string(),
             "test@example.com".to_string(),
-            "password_hash".to_string(),
+            "password".to_string(),
         ).unwrap();
 
         let stats = manager.get_user_stats(&user_id).unwrap();
@@ -776,4 +952,51 @@ mod tests {
         assert_eq!(stats.followers_count, 0);
         assert_eq!(stats.following_count, 0);
     }
-} 
\ No newline at end of file
+
+    #[test]
+    fn test_add_comment_refuses_another_session() {
+        let mut manager = CommunityManager::new();
+        let user1_id = manager.register_user(
+            "user1".to_string(),
+            "user1@example.com".to_string(),
+            "password".to_string(),
+        ).unwrap();
+        manager.register_user(
+            "user2".to_string(),
+            "user2@example.com".to_string(),
+            "password".to_string(),
+        ).unwrap();
+        let session = manager.login("user2", "password").unwrap();
+
+        let result = manager.add_comment(&session.token, &user1_id, "Forged".to_string(), None);
+        assert!(matches!(result, Err(CanvasError::PermissionDenied(_))));
+        assert!(manager.get_comments(None).is_empty());
+    }
+
+    #[test]
+    fn test_create_forum_post_refuses_another_session() {
+        let mut manager = CommunityManager::new();
+        let user1_id = manager.register_user(
+            "user1".to_string(),
+            "user1@example.com".to_string(),
+            "password".to_string(),
+        ).unwrap();
+        manager.register_user(
+            "user2".to_string(),
+            "user2@example.com".to_string(),
+            "password".to_string(),
+        ).unwrap();
+        let session = manager.login("user2", "password").unwrap();
+
+        let result = manager.create_forum_post(
+            &session.token,
+            "Forged".to_string(),
+            String::new(),
+            user1_id,
+            "general".to_string(),
+            Vec::new(),
+        );
+        assert!(matches!(result, Err(CanvasError::PermissionDenied(_))));
+        assert!(manager.get_forum_posts(None).is_empty());
+    }
+}
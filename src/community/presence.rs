@@ -0,0 +1,429 @@
+//! Presence and advisory locks for shared project editing
+//!
+//! Collaborators who open a project register a session with the
+//! [`PresenceHub`], proving who they are with their community session token. Sessions report which nodes they are editing and can take
+//! soft locks on sets of nodes; an edit touching nodes locked by another
+//! session is refused by [`PresenceHub::check_edit`]. Locks are advisory and
+//! expire, and sessions that stop sending heartbeats are dropped, so a crashed
+//! editor never blocks others for long. Every change is published as a
+//! [`PresenceEvent`] on a broadcast channel for the editor's WebSocket
+//! connections to forward; [`PresenceHub::handle`] processes the
+//! [`ClientMessage`]s they receive.
+
+use std::collections::{BTreeSet, HashMap};
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+
+use super::CommunityManager;
+use crate::{
+    error::{CanvasError, CanvasResult},
+    types::NodeId,
+};
+
+/// Time without a heartbeat after which a session is dropped
+pub const DEFAULT_SESSION_TIMEOUT_SECS: i64 = 60;
+/// Lifetime of a soft lock
+pub const DEFAULT_LOCK_TTL_SECS: i64 = 300;
+const EVENT_CAPACITY: usize = 256;
+
+/// Editing session of one collaborator on one project
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EditSession {
+    pub id: String,
+    pub project_id: String,
+    pub user_id: String,
+    pub joined_at: DateTime<Utc>,
+    pub last_seen: DateTime<Utc>,
+    /// Nodes the collaborator has selected or is changing
+    pub editing: BTreeSet<NodeId>,
+}
+
+/// Soft lock on part of a project's graph
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SoftLock {
+    pub id: String,
+    pub project_id: String,
+    pub session_id: String,
+    pub user_id: String,
+    pub nodes: BTreeSet<NodeId>,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// Change broadcast to everyone with the project open
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum PresenceEvent {
+    Joined {
+        project_id: String,
+        session_id: String,
+        user_id: String,
+    },
+    Left {
+        project_id: String,
+        session_id: String,
+        user_id: String,
+    },
+    Editing {
+        project_id: String,
+        session_id: String,
+        user_id: String,
+        nodes: BTreeSet<NodeId>,
+    },
+    Locked {
+        project_id: String,
+        lock_id: String,
+        user_id: String,
+        nodes: BTreeSet<NodeId>,
+    },
+    Unlocked {
+        project_id: String,
+        lock_id: String,
+    },
+}
+
+impl PresenceEvent {
+    pub fn project_id(&self) -> &str {
+        match self {
+            Self::Joined { project_id, .. }
+            | Self::Left { project_id, .. }
+            | Self::Editing { project_id, .. }
+            | Self::Locked { project_id, .. }
+            | Self::Unlocked { project_id, .. } => project_id,
+        }
+    }
+}
+
+/// Message from an editor connection
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ClientMessage {
+    Join { project_id: String },
+    Leave,
+    Heartbeat,
+    Editing { nodes: BTreeSet<NodeId> },
+    Lock { nodes: BTreeSet<NodeId> },
+    Unlock { lock_id: String },
+}
+
+/// Sessions and locks of every open project
+#[derive(Debug)]
+pub struct PresenceHub {
+    sessions: HashMap<String, EditSession>,
+    locks: HashMap<String, SoftLock>,
+    events: broadcast::Sender<PresenceEvent>,
+    session_timeout: Duration,
+    lock_ttl: Duration,
+}
+
+impl Default for PresenceHub {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PresenceHub {
+    pub fn new() -> Self {
+        Self {
+            sessions: HashMap::new(),
+            locks: HashMap::new(),
+            events: broadcast::channel(EVENT_CAPACITY).0,
+            session_timeout: Duration::seconds(DEFAULT_SESSION_TIMEOUT_SECS),
+            lock_ttl: Duration::seconds(DEFAULT_LOCK_TTL_SECS),
+        }
+    }
+
+    pub fn with_session_timeout(mut self, timeout: Duration) -> Self {
+        self.session_timeout = timeout;
+        self
+    }
+
+    pub fn with_lock_ttl(mut self, ttl: Duration) -> Self {
+        self.lock_ttl = ttl;
+        self
+    }
+
+    /// Events of every project; connections filter by [`PresenceEvent::project_id`]
+    pub fn subscribe(&self) -> broadcast::Receiver<PresenceEvent> {
+        self.events.subscribe()
+    }
+
+    fn publish(&self, event: PresenceEvent) {
+        // No receivers just means nobody is listening
+        let _ = self.events.send(event);
+    }
+
+    fn session(&self, session_id: &str) -> CanvasResult<&EditSession> {
+        self.sessions
+            .get(session_id)
+            .ok_or_else(|| CanvasError::NotFound(format!("Session '{}' not found", session_id)))
+    }
+
+    /// Open a project for editing as the user of a community session token.
+    /// Only the project's owner and collaborators may join.
+    pub fn join(&mut self, community: &CommunityManager, project_id: &str, token: &str) -> CanvasResult<String> {
+        let user_id = community.authenticate(token)?.id.as_str();
+        let project = community
+            .get_project(project_id)
+            .ok_or_else(|| CanvasError::NotFound(format!("Project '{}' not found", project_id)))?;
+        if project.owner_id != user_id && !project.collaborators.iter().any(|c| c.user_id == user_id) {
+            return Err(CanvasError::PermissionDenied(format!(
+                "User '{}' is not a collaborator on '{}'",
+                user_id, project_id
+            )));
+        }
+        let session_id = format!("session_{}", uuid::Uuid::new_v4());
+        let now = Utc::now();
+        self.sessions.insert(
+            session_id.clone(),
+            EditSession {
+                id: session_id.clone(),
+                project_id: project_id.to_string(),
+                user_id: user_id.to_string(),
+                joined_at: now,
+                last_seen: now,
+                editing: BTreeSet::new(),
+            },
+        );
+        self.publish(PresenceEvent::Joined {
+            project_id: project_id.to_string(),
+            session_id: session_id.clone(),
+            user_id: user_id.to_string(),
+        });
+        Ok(session_id)
+    }
+
+    /// Close a session, releasing its locks
+    pub fn leave(&mut self, session_id: &str) -> CanvasResult<()> {
+        let session = self
+            .sessions
+            .remove(session_id)
+            .ok_or_else(|| CanvasError::NotFound(format!("Session '{}' not found", session_id)))?;
+        self.release_locks(|lock| lock.session_id == session_id);
+        self.publish(PresenceEvent::Left {
+            project_id: session.project_id,
+            session_id: session.id,
+            user_id: session.user_id,
+        });
+        Ok(())
+    }
+
+    pub fn heartbeat(&mut self, session_id: &str) -> CanvasResult<()> {
+        let session = self
+            .sessions
+            .get_mut(session_id)
+            .ok_or_else(|| CanvasError::NotFound(format!("Session '{}' not found", session_id)))?;
+        session.last_seen = Utc::now();
+        Ok(())
+    }
+
+    /// Report the nodes a session is working on
+    pub fn set_editing(&mut self, session_id: &str, nodes: BTreeSet<NodeId>) -> CanvasResult<()> {
+        let session = self
+            .sessions
+            .get_mut(session_id)
+            .ok_or_else(|| CanvasError::NotFound(format!("Session '{}' not found", session_id)))?;
+        session.editing = nodes.clone();
+        session.last_seen = Utc::now();
+        let event = PresenceEvent::Editing {
+            project_id: session.project_id.clone(),
+            session_id: session.id.clone(),
+            user_id: session.user_id.clone(),
+            nodes,
+        };
+        self.publish(event);
+        Ok(())
+    }
+
+    /// Locks held by other sessions that overlap `nodes`
+    fn conflicting_locks<'a>(
+        &'a self,
+        project_id: &'a str,
+        session_id: &'a str,
+        nodes: &'a BTreeSet<NodeId>,
+    ) -> impl Iterator<Item = &'a SoftLock> {
+        let now = Utc::now();
+        self.locks.values().filter(move |lock| {
+            lock.project_id == project_id
+                && lock.session_id != session_id
+                && lock.expires_at > now
+                && !lock.nodes.is_disjoint(nodes)
+        })
+    }
+
+    /// `Err` if another session holds a lock on any of `nodes`
+    pub fn check_edit(&self, session_id: &str, nodes: &BTreeSet<NodeId>) -> CanvasResult<()> {
+        let session = self.session(session_id)?;
+        match self.conflicting_locks(&session.project_id, session_id, nodes).next() {
+            Some(lock) => Err(CanvasError::InvalidState(format!(
+                "{} node(s) are locked by '{}' until {}",
+                lock.nodes.intersection(nodes).count(),
+                lock.user_id,
+                lock.expires_at.format("%H:%M:%S")
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    /// Soft-lock a set of nodes for the session
+    pub fn lock(&mut self, session_id: &str, nodes: BTreeSet<NodeId>) -> CanvasResult<String> {
+        if nodes.is_empty() {
+            return Err(CanvasError::Validation("Nothing to lock".to_string()));
+        }
+        self.check_edit(session_id, &nodes)?;
+        let session = self.session(session_id)?;
+        let lock = SoftLock {
+            id: format!("lock_{}", uuid::Uuid::new_v4()),
+            project_id: session.project_id.clone(),
+            session_id: session_id.to_string(),
+            user_id: session.user_id.clone(),
+            nodes,
+            expires_at: Utc::now() + self.lock_ttl,
+        };
+        self.publish(PresenceEvent::Locked {
+            project_id: lock.project_id.clone(),
+            lock_id: lock.id.clone(),
+            user_id: lock.user_id.clone(),
+            nodes: lock.nodes.clone(),
+        });
+        let lock_id = lock.id.clone();
+        self.locks.insert(lock_id.clone(), lock);
+        Ok(lock_id)
+    }
+
+    pub fn unlock(&mut self, session_id: &str, lock_id: &str) -> CanvasResult<()> {
+        match self.locks.get(lock_id) {
+            Some(lock) if lock.session_id == session_id => {}
+            Some(_) => return Err(CanvasError::PermissionDenied("Lock is held by another session".to_string())),
+            None => return Err(CanvasError::NotFound(format!("Lock '{}' not found", lock_id))),
+        }
+        self.release_locks(|lock| lock.id == lock_id);
+        Ok(())
+    }
+
+    fn release_locks(&mut self, released: impl Fn(&SoftLock) -> bool) {
+        let ids: Vec<String> = self.locks.values().filter(|l| released(l)).map(|l| l.id.clone()).collect();
+        for id in ids {
+            if let Some(lock) = self.locks.remove(&id) {
+                self.publish(PresenceEvent::Unlocked {
+                    project_id: lock.project_id,
+                    lock_id: lock.id,
+                });
+            }
+        }
+    }
+
+    /// Drop sessions without a recent heartbeat and expired locks
+    pub fn expire(&mut self, now: DateTime<Utc>) {
+        let stale: Vec<String> = self
+            .sessions
+            .values()
+            .filter(|s| now - s.last_seen > self.session_timeout)
+            .map(|s| s.id.clone())
+            .collect();
+        for session_id in stale {
+            log::info!("Dropping idle editing session {}", session_id);
+            self.leave(&session_id).ok();
+        }
+        self.release_locks(|lock| lock.expires_at <= now);
+    }
+
+    /// Sessions on a project, oldest first
+    pub fn sessions(&self, project_id: &str) -> Vec<&EditSession> {
+        let mut sessions: Vec<&EditSession> = self.sessions.values().filter(|s| s.project_id == project_id).collect();
+        sessions.sort_by_key(|s| s.joined_at);
+        sessions
+    }
+
+    /// Unexpired locks on a project
+    pub fn locks(&self, project_id: &str) -> Vec<&SoftLock> {
+        let now = Utc::now();
+        self.locks
+            .values()
+            .filter(|l| l.project_id == project_id && l.expires_at > now)
+            .collect()
+    }
+
+    /// Process a message from a connection. `token` is the community session
+    /// the connection authenticated with; `session_id` is the connection's
+    /// editing session, if it has joined, and the returned ID is its session
+    /// afterwards.
+    pub fn handle(
+        &mut self,
+        community: &CommunityManager,
+        token: &str,
+        session_id: Option<&str>,
+        message: ClientMessage,
+    ) -> CanvasResult<Option<String>> {
+        let joined = || session_id.ok_or_else(|| CanvasError::InvalidState("Join a project first".to_string()));
+        match message {
+            ClientMessage::Join { project_id } => {
+                if let Some(previous) = session_id {
+                    self.leave(previous).ok();
+                }
+                return self.join(community, &project_id, token).map(Some);
+            }
+            ClientMessage::Leave => {
+                self.leave(joined()?)?;
+                return Ok(None);
+            }
+            ClientMessage::Heartbeat => self.heartbeat(joined()?)?,
+            ClientMessage::Editing { nodes } => self.set_editing(joined()?, nodes)?,
+            ClientMessage::Lock { nodes } => {
+                self.lock(joined()?, nodes)?;
+            }
+            ClientMessage::Unlock { lock_id } => self.unlock(joined()?, &lock_id)?,
+        }
+        Ok(session_id.map(str::to_string))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Graph;
+
+    #[test]
+    fn test_presence_and_soft_locks() {
+        let mut community = CommunityManager::new();
+        let alice = community
+            .register_user("alice".to_string(), "alice@example.com".to_string(), "password".to_string())
+            .unwrap();
+        community
+            .register_user("bob".to_string(), "bob@example.com".to_string(), "password".to_string())
+            .unwrap();
+        let graph = Graph { nodes: Vec::new(), edges: Vec::new() };
+        let project = community.create_project("Vault".to_string(), String::new(), alice.clone(), graph).unwrap();
+
+        let alice_token = community.login("alice", "password").unwrap().token;
+        let bob_token = community.login("bob", "password").unwrap().token;
+
+        let mut hub = PresenceHub::new();
+        let mut events = hub.subscribe();
+        let session = hub
+            .handle(&community, &alice_token, None, ClientMessage::Join { project_id: project.clone() })
+            .unwrap()
+            .unwrap();
+        assert!(hub.join(&community, &project, &bob_token).is_err());
+        assert!(hub.join(&community, &project, &alice).is_err());
+        let other = hub.join(&community, &project, &alice_token).unwrap();
+
+        let node = NodeId::new_v4();
+        let nodes = BTreeSet::from([node]);
+        hub.handle(&community, &alice_token, Some(&session), ClientMessage::Lock { nodes: nodes.clone() })
+            .unwrap();
+        assert!(hub.check_edit(&other, &nodes).is_err());
+        assert!(hub.lock(&other, nodes.clone()).is_err());
+        assert!(hub.check_edit(&session, &nodes).is_ok());
+
+        hub.expire(Utc::now() + Duration::seconds(DEFAULT_SESSION_TIMEOUT_SECS + 1));
+        assert!(hub.sessions(&project).is_empty());
+        assert!(hub.locks(&project).is_empty());
+
+        let received: Vec<PresenceEvent> = std::iter::from_fn(|| events.try_recv().ok()).collect();
+        assert!(matches!(received[0], PresenceEvent::Joined { .. }));
+        assert!(received.iter().any(|e| matches!(e, PresenceEvent::Unlocked { .. })));
+        assert_eq!(received.iter().filter(|e| matches!(e, PresenceEvent::Left { .. })).count(), 2);
+    }
+}
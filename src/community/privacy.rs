@@ -0,0 +1,283 @@
+//! User data export and account deletion
+//!
+//! An export gathers everything a user has created (profile, projects,
+//! comments, forum posts and replies, messages, tutorials and marketplace
+//! reviews) into a portable, hash-checked archive. Deleting an account removes
+//! the user and their projects, and then handles their public content
+//! according to the configured retention policy. Anonymized content is
+//! attributed to [`DELETED_USER`] and purged once its retention period has
+//! passed.
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::{CommunityManager, PostStatus};
+use crate::{
+    backup::BackupArchive,
+    config::{ContentRetention, PrivacyConfig},
+    error::{CanvasError, CanvasResult},
+    marketplace::LocalMarketplace,
+};
+
+/// Author id given to anonymized content
+pub const DELETED_USER: &str = "deleted-user";
+
+/// What deleting an account did
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct DeletionReport {
+    pub user_id: String,
+    pub retention: Option<ContentRetention>,
+    pub projects: usize,
+    pub comments: usize,
+    pub forum_posts: usize,
+    #[serde(default)]
+    pub forum_replies: usize,
+    #[serde(default)]
+    pub messages: usize,
+    #[serde(default)]
+    pub activities: usize,
+    pub tutorials: usize,
+    pub reviews: usize,
+}
+
+impl CommunityManager {
+    /// Everything `user_id` has created, as a portable archive. The session
+    /// must be theirs.
+    pub fn export_user_data(
+        &self,
+        token: &str,
+        user_id: &str,
+        marketplace: Option<&LocalMarketplace>,
+    ) -> CanvasResult<BackupArchive> {
+        let user = self.authenticate_as(token, user_id)?;
+
+        let mut archive = BackupArchive::new();
+        archive.add_json("profile.json", user)?;
+        let projects: Vec<_> = self
+            .projects
+            .values()
+            .filter(|p| p.owner_id == user_id || p.collaborators.iter().any(|c| c.user_id == user_id))
+            .collect();
+        archive.add_json("projects.json", &projects)?;
+        let comments: Vec<_> = self.comments.values().filter(|c| c.author_id == user_id).collect();
+        archive.add_json("comments.json", &comments)?;
+        let posts: Vec<_> = self.forum_posts.values().filter(|p| p.author_id == user_id).collect();
+        archive.add_json("forum_posts.json", &posts)?;
+        archive.add_json("forum_replies.json", &self.user_forum_replies(user_id))?;
+        archive.add_json("messages.json", &self.user_messages(user_id))?;
+        archive.add_json("activity.json", &self.user_activities(user_id))?;
+        let tutorials: Vec<_> = self.tutorials.values().filter(|t| t.author_id == user_id).collect();
+        archive.add_json("tutorials.json", &tutorials)?;
+        if let Some(marketplace) = marketplace {
+            archive.add_json("reviews.json", &marketplace.get_user_reviews(user_id))?;
+        }
+        Ok(archive)
+    }
+
+    /// Delete an account. Owned projects are deleted and the user is dropped
+    /// from other projects and follow lists; comments, forum posts, tutorials
+    /// and marketplace reviews are anonymized or removed per `policy`. The
+    /// session must be the user's own.
+    pub fn delete_account(
+        &mut self,
+        token: &str,
+        user_id: &str,
+        marketplace: Option<&mut LocalMarketplace>,
+        policy: &PrivacyConfig,
+    ) -> CanvasResult<DeletionReport> {
+        self.authenticate_as(token, user_id)?;
+        if self.users.remove(user_id).is_none() {
+            return Err(CanvasError::NotFound(format!("User '{}' not found", user_id)));
+        }
+        self.credentials.remove(user_id);
+        self.revoke_sessions(user_id);
+        let retention = policy.deleted_content;
+        let now = Utc::now();
+        let mut report = DeletionReport {
+            user_id: user_id.to_string(),
+            retention: Some(retention),
+            ..DeletionReport::default()
+        };
+
+        for user in self.users.values_mut() {
+            user.following.retain(|id| id != user_id);
+            user.followers.retain(|id| id != user_id);
+        }
+        let projects = self.projects.len();
+        self.projects.retain(|_, p| p.owner_id != user_id);
+        report.projects = projects - self.projects.len();
+        for project in self.projects.values_mut() {
+            project.collaborators.retain(|c| c.user_id != user_id);
+        }
+        let projects = &self.projects;
+        self.issues.retain(|_, issue| projects.contains_key(&issue.project_id));
+        for issue in self.issues.values_mut().filter(|i| i.author_id == user_id) {
+            issue.author_id = DELETED_USER.to_string();
+        }
+
+        for comment in self.comments.values_mut().filter(|c| c.author_id == user_id) {
+            comment.author_id = DELETED_USER.to_string();
+            comment.updated_at = now;
+            if retention == ContentRetention::Remove {
+                // Kept as a tombstone so replies stay threaded
+                comment.content.clear();
+                comment.is_deleted = true;
+            }
+            report.comments += 1;
+        }
+
+        report.forum_replies = self.erase_forum_activity(user_id, retention, now);
+        report.messages = self.erase_messaging(user_id, retention);
+        report.activities = self.erase_activity(user_id);
+        self.leaderboard_opt_out.remove(user_id);
+
+        match retention {
+            ContentRetention::Anonymize => {
+                for post in self.forum_posts.values_mut().filter(|p| p.author_id == user_id) {
+                    post.author_id = DELETED_USER.to_string();
+                    post.updated_at = now;
+                    report.forum_posts += 1;
+                }
+                for tutorial in self.tutorials.values_mut().filter(|t| t.author_id == user_id) {
+                    tutorial.author_id = DELETED_USER.to_string();
+                    tutorial.updated_at = now;
+                    report.tutorials += 1;
+                }
+            }
+            ContentRetention::Remove => {
+                let posts = self.forum_posts.len();
+                self.forum_posts.retain(|_, p| p.author_id != user_id);
+                report.forum_posts = posts - self.forum_posts.len();
+                let tutorials = self.tutorials.len();
+                self.tutorials.retain(|_, t| t.author_id != user_id);
+                report.tutorials = tutorials - self.tutorials.len();
+            }
+        }
+
+        if let Some(marketplace) = marketplace {
+            report.reviews = marketplace.erase_user_reviews(user_id, retention, DELETED_USER);
+        }
+        log::info!("Deleted account {}: {:?}", user_id, report);
+        Ok(report)
+    }
+
+    /// Remove anonymized content older than the policy's retention period.
+    /// Returns how many items were purged.
+    pub fn purge_anonymized(
+        &mut self,
+        marketplace: Option<&mut LocalMarketplace>,
+        policy: &PrivacyConfig,
+        now: DateTime<Utc>,
+    ) -> usize {
+        if policy.anonymized_retention_days == 0 {
+            return 0;
+        }
+        let cutoff = now - Duration::days(policy.anonymized_retention_days as i64);
+        let expired = |author: &str, updated_at: DateTime<Utc>| author == DELETED_USER && updated_at < cutoff;
+
+        let mut purged = 0;
+        for comment in self.comments.values_mut().filter(|c| !c.is_deleted && expired(&c.author_id, c.updated_at)) {
+            comment.content.clear();
+            comment.is_deleted = true;
+            purged += 1;
+        }
+        for reply in self.forum_replies.values_mut().filter(|r| !r.is_deleted && expired(&r.author_id, r.updated_at)) {
+            reply.content.clear();
+            reply.is_deleted = true;
+            purged += 1;
+        }
+        for post in self.forum_posts.values_mut().filter(|p| expired(&p.author_id, p.updated_at)) {
+            if post.status != PostStatus::Deleted {
+                post.content.clear();
+                post.status = PostStatus::Deleted;
+                purged += 1;
+            }
+        }
+        let tutorials = self.tutorials.len();
+        self.tutorials.retain(|_, t| !expired(&t.author_id, t.updated_at));
+        purged += tutorials - self.tutorials.len();
+        if let Some(marketplace) = marketplace {
+            purged += marketplace.purge_reviews(DELETED_USER, cutoff);
+        }
+        purged
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_export_and_delete_account() {
+        let mut manager = CommunityManager::new();
+        let alice = manager
+            .register_user("alice".to_string(), "alice@example.com".to_string(), "password".to_string())
+            .unwrap();
+        let bob = manager
+            .register_user("bob".to_string(), "bob@example.com".to_string(), "password".to_string())
+            .unwrap();
+        let alice_token = manager.login("alice", "password").unwrap().token;
+        let bob_token = manager.login("bob", "password").unwrap().token;
+        manager.follow_user(&bob_token, &alice).unwrap();
+        manager.add_comment(&alice_token, &alice, "Nice graph".to_string(), None).unwrap();
+        manager
+            .create_forum_post(
+                &alice_token,
+                "Help".to_string(),
+                "...".to_string(),
+                alice.clone(),
+                "general".to_string(),
+                Vec::new(),
+            )
+            .unwrap();
+
+        let export = manager.export_user_data(&alice_token, &alice, None).unwrap();
+        export.verify().unwrap();
+        let comments: Vec<serde_json::Value> = export.json("comments.json").unwrap().unwrap();
+        assert_eq!(comments.len(), 1);
+
+        let mut policy = PrivacyConfig::default();
+        let report = manager.delete_account(&alice_token, &alice, None, &policy).unwrap();
+        assert_eq!((report.comments, report.forum_posts), (1, 1));
+        assert!(manager.get_user(&alice).is_none());
+        assert!(manager.get_user(&bob).unwrap().following.is_empty());
+        assert_eq!(manager.get_comments(None)[0].author_id, DELETED_USER);
+
+        policy.anonymized_retention_days = 30;
+        assert_eq!(manager.purge_anonymized(None, &policy, Utc::now()), 0);
+        assert_eq!(manager.purge_anonymized(None, &policy, Utc::now() + Duration::days(31)), 2);
+        assert!(manager.get_comments(None).is_empty());
+        assert!(manager.delete_account(&alice_token, &alice, None, &policy).is_err());
+    }
+
+    fn alice_and_bob() -> (CommunityManager, [(String, String); 2]) {
+        let mut manager = CommunityManager::new();
+        let users = ["alice", "bob"].map(|name| {
+            let id = manager
+                .register_user(name.to_string(), format!("{}@example.com", name), "password".to_string())
+                .unwrap();
+            (id, manager.login(name, "password").unwrap().token)
+        });
+        (manager, users)
+    }
+
+    #[test]
+    fn test_export_refuses_another_session() {
+        let (manager, [(alice, _), (_, bob_token)]) = alice_and_bob();
+        assert!(matches!(
+            manager.export_user_data(&bob_token, &alice, None),
+            Err(CanvasError::PermissionDenied(_))
+        ));
+    }
+
+    #[test]
+    fn test_delete_account_refuses_another_session() {
+        let (mut manager, [(alice, _), (_, bob_token)]) = alice_and_bob();
+        let policy = PrivacyConfig::default();
+        assert!(matches!(
+            manager.delete_account(&bob_token, &alice, None, &policy),
+            Err(CanvasError::PermissionDenied(_))
+        ));
+        assert!(manager.get_user(&alice).is_some());
+    }
+}
@@ -0,0 +1,92 @@
+//! Marketplace reviews and helpfulness votes by community users
+//!
+//! Reviews and votes are cast as the user of a session token, so nobody can
+//! review or vote in someone else's name. Both need the `rate` permission,
+//! and a review is always attributed to the session's user, whatever
+//! `user_id` it was submitted with.
+
+use super::{CommunityManager, Permission};
+use crate::{
+    error::CanvasResult,
+    marketplace::{LocalMarketplace, Review},
+};
+
+impl CommunityManager {
+    /// Review an item of `marketplace` as the session's user
+    pub fn review_item(&self, token: &str, marketplace: &mut LocalMarketplace, mut review: Review) -> CanvasResult<()> {
+        review.user_id = self.authorize(token, Permission::Rate)?.id.clone();
+        marketplace.add_review(review)
+    }
+
+    /// Vote on whether a review is helpful as the session's user. Returns the
+    /// review's new helpful count.
+    pub fn vote_review(
+        &self,
+        token: &str,
+        marketplace: &mut LocalMarketplace,
+        review_id: &str,
+        helpful: bool,
+    ) -> CanvasResult<u32> {
+        let user = self.authorize(token, Permission::Rate)?;
+        marketplace.vote_review(review_id, &user.id, helpful)
+    }
+
+    /// Withdraw the session's user's vote on a review. Returns the review's
+    /// new helpful count.
+    pub fn retract_review_vote(
+        &self,
+        token: &str,
+        marketplace: &mut LocalMarketplace,
+        review_id: &str,
+    ) -> CanvasResult<u32> {
+        let user = self.authenticate(token)?;
+        marketplace.retract_review_vote(review_id, &user.id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    #[test]
+    fn test_reviews_and_votes_act_as_the_session_user() {
+        let mut manager = CommunityManager::new();
+        let mut register = |name: &str| {
+            manager
+                .register_user(name.to_string(), format!("{}@example.com", name), "password".to_string())
+                .unwrap()
+        };
+        let (alice, bob) = (register("alice"), register("bob"));
+        let alice_token = manager.login("alice", "password").unwrap().token;
+        let bob_token = manager.login("bob", "password").unwrap().token;
+
+        let mut marketplace = LocalMarketplace::new();
+        marketplace.add_custom_node(crate::marketplace::test_package().item).unwrap();
+        let now = Utc::now();
+        let review = Review {
+            id: "r1".to_string(),
+            item_id: "clamp".to_string(),
+            user_id: alice.clone(),
+            rating: 5,
+            title: String::new(),
+            content: String::new(),
+            pros: Vec::new(),
+            cons: Vec::new(),
+            created_at: now,
+            updated_at: now,
+            helpful_votes: 0,
+            verified_purchase: false,
+        };
+        // A user id is not a credential, and a review cannot claim another author
+        assert!(manager.review_item(&alice, &mut marketplace, review.clone()).is_err());
+        manager.review_item(&bob_token, &mut marketplace, review).unwrap();
+        assert_eq!(marketplace.get_reviews("clamp")[0].user_id, bob);
+
+        assert!(manager.vote_review(&bob, &mut marketplace, "r1", true).is_err());
+        assert!(manager.vote_review(&bob_token, &mut marketplace, "r1", true).is_err());
+        assert_eq!(manager.vote_review(&alice_token, &mut marketplace, "r1", true).unwrap(), 1);
+        assert_eq!(marketplace.review_vote("r1", &alice), Some(true));
+        assert_eq!(manager.retract_review_vote(&alice_token, &mut marketplace, "r1").unwrap(), 0);
+    }
+}
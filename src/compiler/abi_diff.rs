@@ -0,0 +1,313 @@
+//! ABI compatibility between contract versions
+//!
+//! Two ABIs are compared item by item. Additions (new functions, events and
+//! errors) are compatible: existing callers and indexers keep working. Removals,
+//! renames and signature changes are breaking. A function that disappears while
+//! another with the same signature appears is reported as a rename.
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::{CanvasError, CanvasResult},
+    types::{ContractABI, FunctionABI, ParameterABI, StateMutability},
+};
+
+/// Whether a change affects existing clients
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Compatibility {
+    Compatible,
+    Breaking,
+}
+
+/// Kind of ABI item a change concerns
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AbiItemKind {
+    Function,
+    Event,
+    Error,
+}
+
+impl fmt::Display for AbiItemKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            AbiItemKind::Function => "function",
+            AbiItemKind::Event => "event",
+            AbiItemKind::Error => "error",
+        })
+    }
+}
+
+/// One difference between two ABIs
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AbiChange {
+    pub kind: AbiItemKind,
+    /// Item name in the old ABI, or the new one for additions
+    pub name: String,
+    pub compatibility: Compatibility,
+    pub description: String,
+}
+
+impl fmt::Display for AbiChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = match self.compatibility {
+            Compatibility::Compatible => "compatible",
+            Compatibility::Breaking => "BREAKING",
+        };
+        write!(f, "[{}] {} {}: {}", label, self.kind, self.name, self.description)
+    }
+}
+
+/// Differences between two ABIs
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AbiDiff {
+    pub changes: Vec<AbiChange>,
+}
+
+impl AbiDiff {
+    pub fn is_breaking(&self) -> bool {
+        self.changes.iter().any(|c| c.compatibility == Compatibility::Breaking)
+    }
+
+    pub fn breaking(&self) -> impl Iterator<Item = &AbiChange> {
+        self.changes.iter().filter(|c| c.compatibility == Compatibility::Breaking)
+    }
+
+    /// `Err` listing the breaking changes, if any
+    pub fn ensure_compatible(&self) -> CanvasResult<()> {
+        if !self.is_breaking() {
+            return Ok(());
+        }
+        let changes: Vec<String> = self.breaking().map(|c| c.to_string()).collect();
+        Err(CanvasError::Validation(format!(
+            "ABI has {} breaking change(s):\n  {}",
+            changes.len(),
+            changes.join("\n  ")
+        )))
+    }
+
+    fn push(&mut self, kind: AbiItemKind, name: &str, compatibility: Compatibility, description: impl Into<String>) {
+        self.changes.push(AbiChange {
+            kind,
+            name: name.to_string(),
+            compatibility,
+            description: description.into(),
+        });
+    }
+}
+
+fn type_list(params: &[ParameterABI]) -> String {
+    let types: Vec<String> = params.iter().map(|p| format!("{:?}", p.value_type)).collect();
+    format!("({})", types.join(", "))
+}
+
+fn same_types(old: &[ParameterABI], new: &[ParameterABI]) -> bool {
+    old.len() == new.len() && old.iter().zip(new).all(|(a, b)| a.value_type == b.value_type)
+}
+
+fn same_signature(old: &FunctionABI, new: &FunctionABI) -> bool {
+    same_types(&old.inputs, &new.inputs) && same_types(&old.outputs, &new.outputs)
+}
+
+/// Mutability changes callers cannot notice: a function only becomes stricter
+fn mutability_compatible(old: &StateMutability, new: &StateMutability) -> bool {
+    use StateMutability::*;
+    matches!((old, new), (NonPayable, View) | (NonPayable, Pure) | (View, Pure)) || old == new
+}
+
+fn diff_functions(old: &ContractABI, new: &ContractABI, diff: &mut AbiDiff) {
+    let added: Vec<&FunctionABI> = new
+        .functions
+        .iter()
+        .filter(|f| !old.functions.iter().any(|o| o.name == f.name))
+        .collect();
+    let mut renamed_to = Vec::new();
+
+    for function in &old.functions {
+        let Some(updated) = new.functions.iter().find(|f| f.name == function.name) else {
+            match added.iter().find(|f| same_signature(function, f) && !renamed_to.contains(&f.name)) {
+                Some(target) => {
+                    renamed_to.push(target.name.clone());
+                    diff.push(
+                        AbiItemKind::Function,
+                        &function.name,
+                        Compatibility::Breaking,
+                        format!("renamed to '{}'", target.name),
+                    );
+                }
+                None => diff.push(AbiItemKind::Function, &function.name, Compatibility::Breaking, "removed"),
+            }
+            continue;
+        };
+
+        if !same_types(&function.inputs, &updated.inputs) {
+            diff.push(
+                AbiItemKind::Function,
+                &function.name,
+                Compatibility::Breaking,
+                format!("inputs changed from {} to {}", type_list(&function.inputs), type_list(&updated.inputs)),
+            );
+        } else if function.inputs.iter().zip(&updated.inputs).any(|(a, b)| a.name != b.name) {
+            diff.push(AbiItemKind::Function, &function.name, Compatibility::Compatible, "parameters renamed");
+        }
+        if !same_types(&function.outputs, &updated.outputs) {
+            diff.push(
+                AbiItemKind::Function,
+                &function.name,
+                Compatibility::Breaking,
+                format!("outputs changed from {} to {}", type_list(&function.outputs), type_list(&updated.outputs)),
+            );
+        }
+        if function.state_mutability != updated.state_mutability {
+            diff.push(
+                AbiItemKind::Function,
+                &function.name,
+                if mutability_compatible(&function.state_mutability, &updated.state_mutability) {
+                    Compatibility::Compatible
+                } else {
+                    Compatibility::Breaking
+                },
+                format!("mutability changed from {:?} to {:?}", function.state_mutability, updated.state_mutability),
+            );
+        }
+    }
+
+    for function in added.into_iter().filter(|f| !renamed_to.contains(&f.name)) {
+        diff.push(AbiItemKind::Function, &function.name, Compatibility::Compatible, "added");
+    }
+}
+
+fn event_signature(inputs: &[ParameterABI]) -> Vec<(String, bool)> {
+    inputs.iter().map(|p| (format!("{:?}", p.value_type), p.indexed)).collect()
+}
+
+fn diff_events(old: &ContractABI, new: &ContractABI, diff: &mut AbiDiff) {
+    for event in &old.events {
+        match new.events.iter().find(|e| e.name == event.name) {
+            None => diff.push(AbiItemKind::Event, &event.name, Compatibility::Breaking, "removed"),
+            Some(updated) if event_signature(&event.inputs) != event_signature(&updated.inputs) => diff.push(
+                AbiItemKind::Event,
+                &event.name,
+                Compatibility::Breaking,
+                format!("signature changed from {} to {}", type_list(&event.inputs), type_list(&updated.inputs)),
+            ),
+            Some(updated) if event.anonymous != updated.anonymous => {
+                diff.push(AbiItemKind::Event, &event.name, Compatibility::Breaking, "anonymity changed")
+            }
+            Some(_) => {}
+        }
+    }
+    for event in new.events.iter().filter(|e| !old.events.iter().any(|o| o.name == e.name)) {
+        diff.push(AbiItemKind::Event, &event.name, Compatibility::Compatible, "added");
+    }
+}
+
+fn diff_errors(old: &ContractABI, new: &ContractABI, diff: &mut AbiDiff) {
+    for error in &old.errors {
+        match new.errors.iter().find(|e| e.name == error.name) {
+            // Clients only decode errors the contract raises; one it no longer raises is harmless
+            None => diff.push(AbiItemKind::Error, &error.name, Compatibility::Compatible, "removed"),
+            Some(updated) if !same_types(&error.inputs, &updated.inputs) => diff.push(
+                AbiItemKind::Error,
+                &error.name,
+                Compatibility::Breaking,
+                format!("fields changed from {} to {}", type_list(&error.inputs), type_list(&updated.inputs)),
+            ),
+            Some(_) => {}
+        }
+    }
+    for error in new.errors.iter().filter(|e| !old.errors.iter().any(|o| o.name == e.name)) {
+        diff.push(AbiItemKind::Error, &error.name, Compatibility::Compatible, "added");
+    }
+}
+
+/// Compare the ABI of a new contract version against the previous one
+pub fn diff_abi(old: &ContractABI, new: &ContractABI) -> AbiDiff {
+    let mut diff = AbiDiff::default();
+    diff_functions(old, new, &mut diff);
+    diff_events(old, new, &mut diff);
+    diff_errors(old, new, &mut diff);
+    diff
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{EventABI, ValueType};
+    use std::collections::HashMap;
+
+    fn param(name: &str, value_type: ValueType) -> ParameterABI {
+        ParameterABI {
+            name: name.to_string(),
+            value_type,
+            indexed: false,
+        }
+    }
+
+    fn function(name: &str, inputs: Vec<ParameterABI>) -> FunctionABI {
+        FunctionABI {
+            name: name.to_string(),
+            inputs,
+            outputs: Vec::new(),
+            state_mutability: StateMutability::NonPayable,
+            gas_estimate: None,
+        }
+    }
+
+    fn abi(functions: Vec<FunctionABI>, events: Vec<EventABI>) -> ContractABI {
+        ContractABI {
+            functions,
+            events,
+            errors: Vec::new(),
+            storage: Vec::new(),
+            metadata: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_classifies_changes() {
+        let transfer = EventABI {
+            name: "Transfer".to_string(),
+            inputs: vec![param("amount", ValueType::Integer)],
+            anonymous: false,
+        };
+        let old = abi(
+            vec![
+                function("transfer", vec![param("to", ValueType::Bytes), param("amount", ValueType::Integer)]),
+                function("burn", vec![param("amount", ValueType::Integer)]),
+                function("pause", Vec::new()),
+            ],
+            vec![transfer.clone()],
+        );
+        let mut indexed = transfer;
+        indexed.inputs[0].indexed = true;
+
+        let same = diff_abi(&old, &old);
+        assert!(same.changes.is_empty());
+        assert!(same.ensure_compatible().is_ok());
+
+        let additive = abi([old.functions.clone(), vec![function("mint", Vec::new())]].concat(), old.events.clone());
+        let diff = diff_abi(&old, &additive);
+        assert!(!diff.is_breaking());
+        assert_eq!(diff.changes[0].description, "added");
+
+        let new = abi(
+            vec![
+                function("transfer", vec![param("to", ValueType::String), param("amount", ValueType::Integer)]),
+                function("destroy", vec![param("value", ValueType::Integer)]),
+            ],
+            vec![indexed],
+        );
+        let diff = diff_abi(&old, &new);
+        let descriptions: Vec<String> = diff.changes.iter().map(|c| format!("{} {}", c.name, c.description)).collect();
+        assert!(descriptions[0].starts_with("transfer inputs changed"));
+        assert_eq!(descriptions[1], "burn renamed to 'destroy'");
+        assert_eq!(descriptions[2], "pause removed");
+        assert!(descriptions[3].starts_with("Transfer signature changed"));
+        assert_eq!(diff.changes.len(), 4);
+        assert!(diff.ensure_compatible().is_err());
+    }
+}
@@ -1,8 +1,52 @@
 //! Abstract Syntax Tree (AST) generation
+//!
+//! Each entry point becomes a `Function` whose body follows the flow
+//! connections from its Start (or Init) node. Flow nodes are statements in
+//! flow order; data nodes are expression trees evaluated where their value is
+//! used, so a value feeding two nodes is computed twice. `If` nodes branch and
+//! the rest of each branch is lowered separately. A `ReadStorage` node on the
+//! flow reads once into a variable at its place in the flow; one off the flow
+//! reads wherever its value is used. Batch storage nodes, which the batching
+//! pass builds from runs of flow storage nodes, become one host call.
+//!
+//! Storage keys and event names are static: keys come from the node's `key`
+//! property (or an unconnected `key` input's property) and event fields from
+//! the EmitEvent node's connected data inputs. An unconnected data input takes
+//! the node property named after the port as a literal.
+//!
+//! Decimals are their units at the arithmetic node's `decimal_scale`. Decimal
+//! multiplication and division call the `baals_decimal_mul` and
+//! `baals_decimal_div` host imports, and unconnected inputs of a decimal node
+//! are parsed as decimals.
+//!
+//! String nodes work on bytes: an unconnected string input is its property's
+//! text, or the bytes it spells when it is `0x` hex, as in the node
+//! interpreter. Every string node needs a `max_length`, which bounds its
+//! inputs and sizes the buffer its result is written to. Hash and
+//! VerifySignature call the `baals_hash` and `baals_verify_signature` host
+//! imports.
+//!
+//! Collection nodes work on arrays and maps of integers or booleans; an
+//! unconnected collection input is its property's JSON. Nodes that build a
+//! collection need a `max_length`, the most elements it may hold. A `ForEach`
+//! runs its `body_flow` once per element, up to its `max_iterations`, then
+//! continues at `done_flow`; its `item` and `index` outputs are variables set
+//! on every iteration.
+//!
+//! A TryCall continues at `success_flow` and hands failures to the Catch
+//! nodes on its `failure_flow`, in connection order. The call's result and
+//! the Catch outputs are not lowered, only their flows.
 
-// TODO: Implement AST generation from Graph IR
-// This module will convert the Graph IR into an AST that represents
-// the program structure for code generation.
+use super::{
+    entry_points::EntryPoint,
+    graph_ir::{GraphIR, GraphIRNode},
+    instrumentation::TraceMap,
+};
+use crate::{
+    error::{CanvasError, CanvasResult},
+    types::{Decimal, OverflowMode, RevertReason, ValueType},
+    wasm::host,
+};
 
 /// AST node types
 #[derive(Debug, Clone)]
@@ -39,7 +83,113 @@ pub enum ASTNode {
         operator: String,
         left: Box<ASTNode>,
         right: Box<ASTNode>,
+        overflow: crate::types::OverflowMode,
+        /// Scale of decimal operands, which are their units
+        decimal_scale: Option<u8>,
     },
+    /// Array or map operation (get, set, push, remove, length). Operations
+    /// building a new collection write it into a fixed buffer of `capacity`
+    /// elements.
+    CollectionOp {
+        operation: String,
+        collection: Box<ASTNode>,
+        arguments: Vec<Box<ASTNode>>,
+        element_type: String,
+        capacity: Option<u32>,
+    },
+    /// String/bytes operation writing into a fixed buffer of `max_length` bytes
+    StringOp {
+        operation: String,
+        arguments: Vec<Box<ASTNode>>,
+        max_length: u32,
+    },
+    /// Abort with a revert reason unless the condition holds
+    Require {
+        condition: Box<ASTNode>,
+        reason: crate::types::RevertReason,
+    },
+    /// External call whose failure runs a catch handler instead of aborting
+    TryCall {
+        target: Box<ASTNode>,
+        function: String,
+        arguments: Vec<Box<ASTNode>>,
+        on_success: Vec<Box<ASTNode>>,
+        handlers: Vec<CatchHandler>,
+    },
+    /// Loop over an array with a compile-time iteration bound
+    BoundedLoop {
+        item: String,
+        index: String,
+        collection: Box<ASTNode>,
+        max_iterations: u64,
+        body: Vec<Box<ASTNode>>,
+    },
+    /// Value of a function parameter or variable
+    Identifier {
+        name: String,
+    },
+    /// Leave the function, with its result if it has one
+    Return {
+        value: Option<Box<ASTNode>>,
+    },
+    /// Code of one graph node, wrapped in its tracepoint in instrumented builds
+    Traced {
+        tracepoint: u32,
+        body: Vec<Box<ASTNode>>,
+    },
+}
+
+impl ASTNode {
+    /// Child nodes, in evaluation order
+    pub fn children(&self) -> Vec<&ASTNode> {
+        match self {
+            ASTNode::Function { body, .. } => body.iter().map(|n| n.as_ref()).collect(),
+            ASTNode::Variable { value, .. } => vec![value.as_ref()],
+            ASTNode::If { condition, then_branch, else_branch } => {
+                let mut children = vec![condition.as_ref()];
+                children.extend(then_branch.iter().map(|n| n.as_ref()));
+                if let Some(else_branch) = else_branch {
+                    children.extend(else_branch.iter().map(|n| n.as_ref()));
+                }
+                children
+            }
+            ASTNode::Call { arguments, .. } => arguments.iter().map(|n| n.as_ref()).collect(),
+            ASTNode::Literal { .. } => Vec::new(),
+            ASTNode::BinaryOp { left, right, .. } => vec![left.as_ref(), right.as_ref()],
+            ASTNode::CollectionOp { collection, arguments, .. } => {
+                let mut children = vec![collection.as_ref()];
+                children.extend(arguments.iter().map(|n| n.as_ref()));
+                children
+            }
+            ASTNode::Require { condition, .. } => vec![condition.as_ref()],
+            ASTNode::StringOp { arguments, .. } => arguments.iter().map(|n| n.as_ref()).collect(),
+            ASTNode::TryCall { target, arguments, on_success, handlers, .. } => {
+                let mut children = vec![target.as_ref()];
+                children.extend(arguments.iter().map(|n| n.as_ref()));
+                children.extend(on_success.iter().map(|n| n.as_ref()));
+                for handler in handlers {
+                    children.extend(handler.body.iter().map(|n| n.as_ref()));
+                }
+                children
+            }
+            ASTNode::BoundedLoop { collection, body, .. } => {
+                let mut children = vec![collection.as_ref()];
+                children.extend(body.iter().map(|n| n.as_ref()));
+                children
+            }
+            ASTNode::Identifier { .. } => Vec::new(),
+            ASTNode::Return { value } => value.iter().map(|n| n.as_ref()).collect(),
+            ASTNode::Traced { body, .. } => body.iter().map(|n| n.as_ref()).collect(),
+        }
+    }
+}
+
+/// Failure handler of a `TryCall`
+#[derive(Debug, Clone)]
+pub struct CatchHandler {
+    /// Revert error this handler matches; `None` catches everything
+    pub error: Option<String>,
+    pub body: Vec<Box<ASTNode>>,
 }
 
 /// AST representation
@@ -54,4 +204,691 @@ impl AST {
             nodes: Vec::new(),
         }
     }
-} 
\ No newline at end of file
+
+    /// Build one function per entry point from the Graph IR.
+    ///
+    /// Arithmetic nodes without an `overflow_mode` property use `overflow`.
+    /// With a trace map, each node's code is wrapped in its tracepoint.
+    pub fn from_ir(
+        ir: &GraphIR,
+        entries: &[EntryPoint],
+        overflow: OverflowMode,
+        trace: Option<&TraceMap>,
+    ) -> CanvasResult<Self> {
+        let mut ast = Self::new();
+        for entry in entries {
+            let mut builder = FunctionBuilder {
+                ir,
+                overflow,
+                trace,
+                entry,
+                root: entry.node_id.to_string(),
+                flow_path: Vec::new(),
+                data_path: Vec::new(),
+            };
+            ast.nodes.push(builder.function()?);
+        }
+        Ok(ast)
+    }
+}
+
+/// Variable a flow `ReadStorage` node reads into
+pub fn read_variable(node_id: &str) -> String {
+    format!("read_{}", node_id)
+}
+
+/// Variable the `index`th key of a `BatchReadStorage` node reads into
+pub fn batch_read_variable(node_id: &str, index: usize) -> String {
+    format!("read_{}_{}", node_id, index)
+}
+
+/// Variable holding the current element of a `ForEach` node's array
+pub fn loop_item_variable(node_id: &str) -> String {
+    format!("item_{}", node_id)
+}
+
+/// Variable holding the current index of a `ForEach` node's loop
+pub fn loop_index_variable(node_id: &str) -> String {
+    format!("index_{}", node_id)
+}
+
+/// Keys of a batch storage node, in order
+fn batch_keys(node: &GraphIRNode) -> CanvasResult<Vec<String>> {
+    node.property("keys")
+        .and_then(|keys| serde_json::from_str(keys).ok())
+        .ok_or_else(|| CanvasError::Compilation(format!("{} node {} needs a 'keys' list", node.node_type, node.id)))
+}
+
+/// Whether a value type has a code generation representation (an i64)
+fn is_lowerable(value_type: &ValueType) -> bool {
+    matches!(value_type, ValueType::Integer | ValueType::Boolean | ValueType::Decimal(_))
+}
+
+/// An arithmetic node's `decimal_scale`, when it works on decimals
+fn decimal_scale(node: &GraphIRNode) -> CanvasResult<Option<u8>> {
+    let Some(scale) = node.property("decimal_scale") else {
+        return Ok(None);
+    };
+    match scale.parse::<u8>() {
+        Ok(scale) if scale <= Decimal::MAX_SCALE => Ok(Some(scale)),
+        _ => Err(CanvasError::Compilation(format!(
+            "{} node {} has invalid decimal_scale '{}'",
+            node.node_type, node.id, scale
+        ))),
+    }
+}
+
+fn unsupported(node: &GraphIRNode) -> CanvasError {
+    CanvasError::Compilation(format!(
+        "Node type '{}' (node {}) is not supported by code generation yet",
+        node.node_type, node.id
+    ))
+}
+
+/// A string node's `max_length`, which bounds its inputs and result
+fn max_length(node: &GraphIRNode) -> CanvasResult<u32> {
+    node.property("max_length")
+        .and_then(|len| len.parse().ok())
+        .filter(|len| *len > 0)
+        .ok_or_else(|| {
+            CanvasError::Compilation(format!("{} node {} needs a positive 'max_length'", node.node_type, node.id))
+        })
+}
+
+/// A collection node's `element_type`, which must be lowerable; integers by default
+fn element_type(node: &GraphIRNode) -> CanvasResult<String> {
+    let name = node.property("element_type").unwrap_or("integer");
+    match ValueType::from_name(name) {
+        Some(value_type) if is_lowerable(&value_type) => Ok(name.to_string()),
+        _ => Err(CanvasError::Compilation(format!(
+            "{} node {} has element type '{}'; compiled collections hold integers and booleans",
+            node.node_type, node.id, name
+        ))),
+    }
+}
+
+fn string_literal(value: &str) -> Box<ASTNode> {
+    Box::new(ASTNode::Literal {
+        value: value.to_string(),
+        value_type: "string".to_string(),
+    })
+}
+
+struct FunctionBuilder<'a> {
+    ir: &'a GraphIR,
+    overflow: OverflowMode,
+    trace: Option<&'a TraceMap>,
+    entry: &'a EntryPoint,
+    root: String,
+    /// Flow nodes on the current path, to reject flow cycles
+    flow_path: Vec<String>,
+    /// Data nodes being evaluated, to reject data cycles
+    data_path: Vec<String>,
+}
+
+impl<'a> FunctionBuilder<'a> {
+    fn function(&mut self) -> CanvasResult<ASTNode> {
+        let function = &self.entry.function;
+        let name = &function.name;
+        for param in &function.inputs {
+            if !is_lowerable(&param.value_type) {
+                return Err(CanvasError::Compilation(format!(
+                    "Parameter '{}' of '{}' has type {:?}, which code generation does not support yet",
+                    param.name, name, param.value_type
+                )));
+            }
+        }
+        if let Some(output) = function.outputs.iter().find(|o| !is_lowerable(&o.value_type)) {
+            return Err(CanvasError::Compilation(format!(
+                "'{}' returns {:?}, which code generation does not support yet",
+                name, output.value_type
+            )));
+        }
+
+        let root = self.node(&self.root.clone())?;
+        let (body, returned) = self.flow(root, "flow_out")?;
+        if !returned && !function.outputs.is_empty() {
+            return Err(CanvasError::Compilation(format!(
+                "Every path of '{}' must end at an End node given its return value",
+                name
+            )));
+        }
+        let mut body = body;
+        if let Some(tracepoint) = self.tracepoint(root) {
+            body.insert(0, Box::new(ASTNode::Traced { tracepoint, body: Vec::new() }));
+        }
+        Ok(ASTNode::Function {
+            name: name.clone(),
+            params: function.inputs.iter().map(|p| p.name.clone()).collect(),
+            body,
+        })
+    }
+
+    fn node(&self, id: &str) -> CanvasResult<&'a GraphIRNode> {
+        self.ir
+            .node(id)
+            .ok_or_else(|| CanvasError::Compilation(format!("Graph IR has no node {}", id)))
+    }
+
+    fn tracepoint(&self, node: &GraphIRNode) -> Option<u32> {
+        let id = uuid::Uuid::parse_str(&node.id).ok()?;
+        self.trace?.tracepoint(&id)
+    }
+
+    fn traced(&self, node: &GraphIRNode, code: Box<ASTNode>) -> Box<ASTNode> {
+        match self.tracepoint(node) {
+            Some(tracepoint) => Box::new(ASTNode::Traced {
+                tracepoint,
+                body: vec![code],
+            }),
+            None => code,
+        }
+    }
+
+    /// Statements following a flow output, and whether every path returns
+    fn flow(&mut self, from: &GraphIRNode, port: &str) -> CanvasResult<(Vec<Box<ASTNode>>, bool)> {
+        let ir = self.ir;
+        let mut targets = ir.outgoing(&from.id, port).filter(|c| c.is_flow());
+        let Some(connection) = targets.next() else {
+            return Ok((Vec::new(), false));
+        };
+        if targets.next().is_some() {
+            return Err(CanvasError::Compilation(format!(
+                "Flow output '{}' of node {} has several connections; branch with an If node",
+                port, from.id
+            )));
+        }
+        let node = self.node(connection.target_port().0)?;
+        if self.flow_path.contains(&node.id) {
+            return Err(CanvasError::Compilation(format!(
+                "Flow loops back to node {}; loops need a ForEach node",
+                node.id
+            )));
+        }
+        self.flow_path.push(node.id.clone());
+        let lowered = self.statement(node);
+        self.flow_path.pop();
+        lowered
+    }
+
+    fn statement(&mut self, node: &'a GraphIRNode) -> CanvasResult<(Vec<Box<ASTNode>>, bool)> {
+        let code = match node.node_type.as_str() {
+            "End" => {
+                let inputs = self.data_inputs(node);
+                let value = match (inputs.first(), self.entry.function.outputs.is_empty()) {
+                    (None, true) => None,
+                    (Some(port), false) => Some(self.input(node, port)?),
+                    (None, false) => {
+                        return Err(CanvasError::Compilation(format!(
+                            "End node {} must be given the return value of '{}'",
+                            node.id, self.entry.function.name
+                        )))
+                    }
+                    (Some(_), true) => {
+                        return Err(CanvasError::Compilation(format!(
+                            "End node {} returns a value but '{}' declares no return type",
+                            node.id, self.entry.function.name
+                        )))
+                    }
+                };
+                let code = self.traced(node, Box::new(ASTNode::Return { value }));
+                return Ok((vec![code], true));
+            }
+            "If" => {
+                let condition = self.input(node, "condition")?;
+                let (then_branch, then_returned) = self.flow(node, "true_flow")?;
+                let (else_branch, else_returned) = self.flow(node, "false_flow")?;
+                let code = ASTNode::If {
+                    condition,
+                    then_branch,
+                    else_branch: Some(else_branch),
+                };
+                return Ok((vec![self.traced(node, Box::new(code))], then_returned && else_returned));
+            }
+            "TryCall" => {
+                let function = node.property("function").ok_or_else(|| {
+                    CanvasError::Compilation(format!("TryCall node {} needs a 'function' name", node.id))
+                })?;
+                let target = self.input(node, "target")?;
+                let arguments = match self.ir.incoming(&node.id, "args").next() {
+                    Some(_) => vec![self.input(node, "args")?],
+                    None => Vec::new(),
+                };
+                let (on_success, mut returned) = self.flow(node, "success_flow")?;
+                let mut handlers = Vec::new();
+                let ir = self.ir;
+                for connection in ir.outgoing(&node.id, "failure_flow").filter(|c| c.is_flow()) {
+                    let catch = self.node(connection.target_port().0)?;
+                    if catch.node_type != "Catch" {
+                        return Err(CanvasError::Compilation(format!(
+                            "Failure flow of TryCall node {} must go to Catch nodes",
+                            node.id
+                        )));
+                    }
+                    self.flow_path.push(catch.id.clone());
+                    let handled = self.flow(catch, "flow_out");
+                    self.flow_path.pop();
+                    let (mut body, handler_returned) = handled?;
+                    if let Some(tracepoint) = self.tracepoint(catch) {
+                        body.insert(0, Box::new(ASTNode::Traced { tracepoint, body: Vec::new() }));
+                    }
+                    returned &= handler_returned;
+                    handlers.push(CatchHandler {
+                        error: catch.property("error").map(str::to_string),
+                        body,
+                    });
+                }
+                // A failure no handler catches is rethrown, so it never falls through
+                let code = ASTNode::TryCall {
+                    target,
+                    function: function.to_string(),
+                    arguments,
+                    on_success,
+                    handlers,
+                };
+                return Ok((vec![self.traced(node, Box::new(code))], returned));
+            }
+            "Require" => {
+                if self.ir.incoming(&node.id, "args").next().is_some() {
+                    return Err(CanvasError::Compilation(format!(
+                        "Require node {} passes error arguments, which code generation does not support yet",
+                        node.id
+                    )));
+                }
+                let error = node.property("error").ok_or_else(|| {
+                    CanvasError::Compilation(format!("Require node {} needs an 'error' name", node.id))
+                })?;
+                ASTNode::Require {
+                    condition: self.input(node, "condition")?,
+                    reason: RevertReason::new(error, node.property("message").unwrap_or_default()),
+                }
+            }
+            "WriteStorage" => ASTNode::Call {
+                function: host::HOST_WRITE_STORAGE.to_string(),
+                arguments: vec![string_literal(&self.storage_key(node)?), self.input(node, "value")?],
+            },
+            "ReadStorage" => ASTNode::Variable {
+                name: read_variable(&node.id),
+                value: Box::new(ASTNode::Call {
+                    function: host::HOST_READ_STORAGE.to_string(),
+                    arguments: vec![string_literal(&self.storage_key(node)?)],
+                }),
+            },
+            "BatchWriteStorage" => {
+                let mut arguments = Vec::new();
+                for (index, key) in batch_keys(node)?.into_iter().enumerate() {
+                    arguments.push(Box::new(ASTNode::Variable {
+                        name: key,
+                        value: self.input(node, &format!("value_{}", index))?,
+                    }));
+                }
+                ASTNode::Call {
+                    function: host::HOST_BATCH_WRITE_STORAGE.to_string(),
+                    arguments,
+                }
+            }
+            "BatchReadStorage" => ASTNode::Call {
+                function: host::HOST_BATCH_READ_STORAGE.to_string(),
+                arguments: batch_keys(node)?
+                    .iter()
+                    .enumerate()
+                    .map(|(index, key)| {
+                        Box::new(ASTNode::Variable {
+                            name: batch_read_variable(&node.id, index),
+                            value: string_literal(key),
+                        })
+                    })
+                    .collect(),
+            },
+            "EmitEvent" => {
+                let event = node.property("event").ok_or_else(|| {
+                    CanvasError::Compilation(format!("EmitEvent node {} needs an 'event' name", node.id))
+                })?;
+                let mut arguments = vec![string_literal(event)];
+                for port in self.data_inputs(node) {
+                    arguments.push(Box::new(ASTNode::Variable {
+                        name: port.clone(),
+                        value: self.input(node, &port)?,
+                    }));
+                }
+                ASTNode::Call {
+                    function: host::HOST_EMIT_EVENT.to_string(),
+                    arguments,
+                }
+            }
+            "ForEach" => {
+                let max_iterations = node
+                    .property("max_iterations")
+                    .and_then(|max| max.parse::<u64>().ok())
+                    .filter(|max| *max > 0)
+                    .ok_or_else(|| {
+                        CanvasError::Compilation(format!("ForEach node {} needs a positive 'max_iterations'", node.id))
+                    })?;
+                element_type(node)?;
+                let collection = self.collection_input(node, "array")?;
+                // The loop may run no times, so only the flow after it decides whether it returns
+                let (body, _) = self.flow(node, "body_flow")?;
+                let code = ASTNode::BoundedLoop {
+                    item: loop_item_variable(&node.id),
+                    index: loop_index_variable(&node.id),
+                    collection,
+                    max_iterations,
+                    body,
+                };
+                let (mut rest, returned) = self.flow(node, "done_flow")?;
+                rest.insert(0, self.traced(node, Box::new(code)));
+                return Ok((rest, returned));
+            }
+            _ => return Err(unsupported(node)),
+        };
+        let (mut rest, returned) = self.flow(node, "flow_out")?;
+        rest.insert(0, self.traced(node, Box::new(code)));
+        Ok((rest, returned))
+    }
+
+    /// Connected data input ports of a node, in port order
+    fn data_inputs(&self, node: &GraphIRNode) -> Vec<String> {
+        let mut ports: Vec<String> = Vec::new();
+        let connected = self.ir.connections.iter().filter(|c| !c.is_flow() && c.target_port().0 == node.id);
+        for connection in connected {
+            let port = connection.target_port().1.to_string();
+            if !ports.contains(&port) {
+                ports.push(port);
+            }
+        }
+        let position = |port: &String| node.inputs.iter().position(|p| p == port).unwrap_or(usize::MAX);
+        ports.sort_by_key(position);
+        ports
+    }
+
+    fn storage_key(&self, node: &GraphIRNode) -> CanvasResult<String> {
+        if self.ir.incoming(&node.id, "key").next().is_some() {
+            return Err(CanvasError::Compilation(format!(
+                "{} node {} computes its key; code generation supports static keys only",
+                node.node_type, node.id
+            )));
+        }
+        node.property("key")
+            .map(str::to_string)
+            .ok_or_else(|| CanvasError::Compilation(format!("{} node {} needs a 'key'", node.node_type, node.id)))
+    }
+
+    /// A string node's operation on the values of `ports`
+    fn string_op(&mut self, node: &GraphIRNode, operation: &str, ports: &[&str]) -> CanvasResult<Box<ASTNode>> {
+        let max_length = max_length(node)?;
+        let mut arguments = Vec::new();
+        for port in ports {
+            arguments.push(match *port {
+                "start" | "end" => self.input(node, port)?,
+                _ => self.bytes_input(node, port, operation != "hex_encode")?,
+            });
+        }
+        Ok(Box::new(ASTNode::StringOp {
+            operation: operation.to_string(),
+            arguments,
+            max_length,
+        }))
+    }
+
+    /// A collection node's operation on the collection at `collection` and the
+    /// values of `ports`
+    fn collection_op(
+        &mut self,
+        node: &GraphIRNode,
+        operation: &str,
+        collection: &str,
+        ports: &[&str],
+    ) -> CanvasResult<Box<ASTNode>> {
+        let element_type = element_type(node)?;
+        let capacity = match operation {
+            "array_set" | "array_push" | "array_remove" | "map_set" | "map_remove" => Some(max_length(node)?),
+            _ => None,
+        };
+        let collection = self.collection_input(node, collection)?;
+        let mut arguments = Vec::new();
+        for port in ports {
+            arguments.push(match *port {
+                "key" => self.bytes_input(node, port, false)?,
+                _ => self.input(node, port)?,
+            });
+        }
+        Ok(Box::new(ASTNode::CollectionOp {
+            operation: operation.to_string(),
+            collection,
+            arguments,
+            element_type,
+            capacity,
+        }))
+    }
+
+    /// Expression for an arithmetic input. With a decimal scale an unconnected
+    /// one is its property parsed as a decimal, as its units.
+    fn arithmetic_input(&mut self, node: &GraphIRNode, port: &str, scale: Option<u8>) -> CanvasResult<Box<ASTNode>> {
+        let Some(scale) = scale else {
+            return self.input(node, port);
+        };
+        if self.ir.incoming(&node.id, port).any(|c| !c.is_flow()) {
+            return self.input(node, port);
+        }
+        let decimal = Decimal::parse(property_input(node, port)?, scale)
+            .map_err(|e| CanvasError::Compilation(format!("Input '{}' of node {}: {}", port, node.id, e)))?;
+        Ok(Box::new(ASTNode::Literal {
+            value: decimal.units.to_string(),
+            value_type: "integer".to_string(),
+        }))
+    }
+
+    /// Expression for an array or map input. An unconnected one is its property's JSON.
+    fn collection_input(&mut self, node: &GraphIRNode, port: &str) -> CanvasResult<Box<ASTNode>> {
+        if self.ir.incoming(&node.id, port).any(|c| !c.is_flow()) {
+            return self.input(node, port);
+        }
+        let value = property_input(node, port)?;
+        let value_type = match serde_json::from_str::<serde_json::Value>(value) {
+            Ok(serde_json::Value::Array(_)) => "array",
+            Ok(serde_json::Value::Object(_)) => "map",
+            _ => {
+                return Err(CanvasError::Compilation(format!(
+                    "Input '{}' of node {} must be an array or map",
+                    port, node.id
+                )))
+            }
+        };
+        Ok(Box::new(ASTNode::Literal {
+            value: value.to_string(),
+            value_type: value_type.to_string(),
+        }))
+    }
+
+    /// Expression for a string or bytes input. An unconnected one is its
+    /// property's text, or with `hex` the bytes it spells when it is `0x` hex.
+    fn bytes_input(&mut self, node: &GraphIRNode, port: &str, hex: bool) -> CanvasResult<Box<ASTNode>> {
+        if self.ir.incoming(&node.id, port).any(|c| !c.is_flow()) {
+            return self.input(node, port);
+        }
+        let value = property_input(node, port)?;
+        let value_type = if hex && crate::nodes::decode_hex(value).is_some() { "bytes" } else { "string" };
+        Ok(Box::new(ASTNode::Literal {
+            value: value.to_string(),
+            value_type: value_type.to_string(),
+        }))
+    }
+
+    /// Expression for the value arriving at a data input
+    fn input(&mut self, node: &GraphIRNode, port: &str) -> CanvasResult<Box<ASTNode>> {
+        let ir = self.ir;
+        let mut sources = ir.incoming(&node.id, port).filter(|c| !c.is_flow());
+        let Some(connection) = sources.next() else {
+            return literal(node, port);
+        };
+        if sources.next().is_some() {
+            return Err(CanvasError::Compilation(format!(
+                "Input '{}' of node {} has several connections",
+                port, node.id
+            )));
+        }
+        let (source, output) = connection.source_port();
+        let source = self.node(source)?;
+        if self.data_path.contains(&source.id) {
+            return Err(CanvasError::Compilation(format!("Data cycle through node {}", source.id)));
+        }
+        self.data_path.push(source.id.clone());
+        let value = self.output(source, output);
+        self.data_path.pop();
+        Ok(self.traced(source, value?))
+    }
+
+    /// Expression for a node's data output
+    fn output(&mut self, node: &'a GraphIRNode, port: &str) -> CanvasResult<Box<ASTNode>> {
+        let binary = |builder: &mut Self, operator: &str, overflow: OverflowMode| -> CanvasResult<Box<ASTNode>> {
+            let decimal_scale = match operator {
+                "add" | "sub" | "mul" | "div" => decimal_scale(node)?,
+                _ => None,
+            };
+            Ok(Box::new(ASTNode::BinaryOp {
+                operator: operator.to_string(),
+                left: builder.arithmetic_input(node, "a", decimal_scale)?,
+                right: builder.arithmetic_input(node, "b", decimal_scale)?,
+                overflow,
+                decimal_scale,
+            }))
+        };
+        let overflow = node
+            .property("overflow_mode")
+            .and_then(OverflowMode::from_name)
+            .unwrap_or(self.overflow);
+        match node.node_type.as_str() {
+            "Start" | "Init" if node.id == self.root => {
+                if !self.entry.function.inputs.iter().any(|p| p.name == port) {
+                    return Err(CanvasError::Compilation(format!(
+                        "'{}' has no parameter '{}'",
+                        self.entry.function.name, port
+                    )));
+                }
+                Ok(Box::new(ASTNode::Identifier { name: port.to_string() }))
+            }
+            "Start" | "Init" => Err(CanvasError::Compilation(format!(
+                "'{}' uses parameter '{}' of another entry point (node {})",
+                self.entry.function.name, port, node.id
+            ))),
+            "Add" => binary(self, "add", overflow),
+            "Subtract" => binary(self, "sub", overflow),
+            "Multiply" => binary(self, "mul", overflow),
+            "Divide" => binary(self, "div", overflow),
+            "And" => binary(self, "and", overflow),
+            "Or" => binary(self, "or", overflow),
+            "CompareIntegers" if port == "ordering" => binary(self, "cmp", overflow),
+            "CompareIntegers" => binary(self, "eq", overflow),
+            "Not" => Ok(Box::new(ASTNode::BinaryOp {
+                operator: "eq".to_string(),
+                left: self.input(node, "input")?,
+                right: Box::new(ASTNode::Literal {
+                    value: "false".to_string(),
+                    value_type: "boolean".to_string(),
+                }),
+                overflow,
+                decimal_scale: None,
+            })),
+            "ReadStorage" if self.ir.incoming(&node.id, "flow_in").next().is_some() => {
+                Ok(Box::new(ASTNode::Identifier { name: read_variable(&node.id) }))
+            }
+            "ReadStorage" => Ok(Box::new(ASTNode::Call {
+                function: host::HOST_READ_STORAGE.to_string(),
+                arguments: vec![string_literal(&self.storage_key(node)?)],
+            })),
+            "BatchReadStorage" if self.ir.incoming(&node.id, "flow_in").next().is_some() => {
+                let index = port
+                    .strip_prefix("value_")
+                    .and_then(|index| index.parse().ok())
+                    .ok_or_else(|| {
+                        CanvasError::Compilation(format!("BatchReadStorage node {} has no output '{}'", node.id, port))
+                    })?;
+                Ok(Box::new(ASTNode::Identifier { name: batch_read_variable(&node.id, index) }))
+            }
+            "ArrayGet" => self.collection_op(node, "array_get", "array", &["index"]),
+            "ArraySet" => self.collection_op(node, "array_set", "array", &["index", "value"]),
+            "ArrayPush" => self.collection_op(node, "array_push", "array", &["value"]),
+            "ArrayRemove" if port == "removed" => self.collection_op(node, "array_removed", "array", &["index"]),
+            "ArrayRemove" => self.collection_op(node, "array_remove", "array", &["index"]),
+            "MapGet" if port == "found" => self.collection_op(node, "map_found", "map", &["key"]),
+            "MapGet" => self.collection_op(node, "map_get", "map", &["key"]),
+            "MapSet" => self.collection_op(node, "map_set", "map", &["key", "value"]),
+            "MapRemove" if port == "removed" => self.collection_op(node, "map_removed", "map", &["key"]),
+            "MapRemove" => self.collection_op(node, "map_remove", "map", &["key"]),
+            "Length" => self.collection_op(node, "length", "collection", &[]),
+            "ForEach" if port == "item" => Ok(Box::new(ASTNode::Identifier { name: loop_item_variable(&node.id) })),
+            "ForEach" if port == "index" => Ok(Box::new(ASTNode::Identifier { name: loop_index_variable(&node.id) })),
+            "Concat" => self.string_op(node, "concat", &["a", "b"]),
+            "Slice" => {
+                let mut ports = vec!["input", "start"];
+                if node.property("end").is_some() || self.ir.incoming(&node.id, "end").next().is_some() {
+                    ports.push("end");
+                }
+                self.string_op(node, "slice", &ports)
+            }
+            "HexEncode" => self.string_op(node, "hex_encode", &["input"]),
+            "HexDecode" => self.string_op(node, "hex_decode", &["input"]),
+            "Compare" if port == "ordering" => self.string_op(node, "compare", &["a", "b"]),
+            "Compare" => self.string_op(node, "equal", &["a", "b"]),
+            "Hash" => {
+                let algorithm = node.property("algorithm").unwrap_or("sha256");
+                if host::HashAlgorithm::from_name(algorithm).is_none() {
+                    return Err(CanvasError::Compilation(format!(
+                        "Hash node {} has unknown algorithm '{}'",
+                        node.id, algorithm
+                    )));
+                }
+                Ok(Box::new(ASTNode::StringOp {
+                    operation: "hash".to_string(),
+                    arguments: vec![string_literal(algorithm), self.bytes_input(node, "input", true)?],
+                    max_length: max_length(node)?,
+                }))
+            }
+            "VerifySignature" => {
+                let scheme = node
+                    .property("scheme")
+                    .filter(|scheme| host::SignatureScheme::from_name(scheme).is_some())
+                    .ok_or_else(|| {
+                        CanvasError::Compilation(format!("VerifySignature node {} needs a known 'scheme'", node.id))
+                    })?;
+                let mut arguments = vec![string_literal(scheme)];
+                for port in ["public_key", "message", "signature"] {
+                    arguments.push(self.bytes_input(node, port, true)?);
+                }
+                Ok(Box::new(ASTNode::Call {
+                    function: host::HOST_VERIFY_SIGNATURE.to_string(),
+                    arguments,
+                }))
+            }
+            "TryCall" | "Catch" => Err(CanvasError::Compilation(format!(
+                "Output '{}' of {} node {} is not available in compiled contracts; only its flows are",
+                port, node.node_type, node.id
+            ))),
+            _ => Err(unsupported(node)),
+        }
+    }
+}
+
+/// Literal for an unconnected input, from the node property named after the port
+fn literal(node: &GraphIRNode, port: &str) -> CanvasResult<Box<ASTNode>> {
+    let value = property_input(node, port)?;
+    let value_type = match value {
+        "true" | "false" => "boolean",
+        _ if value.parse::<i64>().is_ok() => "integer",
+        _ => "string",
+    };
+    Ok(Box::new(ASTNode::Literal {
+        value: value.to_string(),
+        value_type: value_type.to_string(),
+    }))
+} 
+
+/// Value of an unconnected input, the node property named after the port
+fn property_input<'n>(node: &'n GraphIRNode, port: &str) -> CanvasResult<&'n str> {
+    node.property(port).ok_or_else(|| {
+        CanvasError::Compilation(format!(
+            "Input '{}' of node {} is not connected and has no value",
+            port, node.id
+        ))
+    })
+}
\ No newline at end of file
@@ -0,0 +1,284 @@
+//! Inter-contract call graph of a workspace
+//!
+//! Every `TryCall` node is a call from its graph to the contract named by its
+//! target: the node's `target` property, or the `value` property of the node
+//! wired into its `target` port. Targets are matched against the workspace's
+//! graphs by ID, by name, or by the deployed address recorded in the graph's
+//! `address` metadata; anything else is an external contract. `Import` nodes
+//! are recorded too, since an imported graph is compiled into its importer.
+
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+
+use serde::Serialize;
+use uuid::Uuid;
+
+use super::imports::{import_target, Workspace};
+use crate::types::{NodeId, VisualGraph, VisualNode};
+
+/// Graph metadata key holding a contract's deployed address
+pub const ADDRESS_METADATA_KEY: &str = "address";
+
+/// Contract at the receiving end of a call
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(tag = "kind", content = "id", rename_all = "snake_case")]
+pub enum CallTarget {
+    /// Graph of the workspace
+    Contract(Uuid),
+    /// Address or name that matches no workspace graph
+    External(String),
+}
+
+/// How one contract depends on another
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CallKind {
+    /// Runtime call through a `TryCall` node
+    Call,
+    /// Compile-time inlining through an `Import` node
+    Import,
+}
+
+/// Calls from one contract to another, with the nodes making them
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CallEdge {
+    pub caller: Uuid,
+    pub callee: CallTarget,
+    pub kind: CallKind,
+    /// Functions called, if the nodes name them
+    pub functions: BTreeSet<String>,
+    pub via: Vec<NodeId>,
+}
+
+/// Who calls whom across a workspace
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CallGraph {
+    /// Workspace graphs by ID, with their names
+    pub contracts: BTreeMap<Uuid, String>,
+    pub edges: Vec<CallEdge>,
+}
+
+fn call_target_name(graph: &VisualGraph, node: &VisualNode) -> Option<String> {
+    let literal = |node: &VisualNode, key: &str| node.properties.get(key).and_then(|v| v.as_str()).map(str::to_string);
+    literal(node, "target").or_else(|| {
+        graph
+            .connections
+            .iter()
+            .find(|c| c.target_node == node.id && c.target_port == "target")
+            .and_then(|c| graph.nodes.iter().find(|n| n.id == c.source_node))
+            .and_then(|source| literal(source, "value"))
+    })
+}
+
+fn resolve_target(workspace: &Workspace, name: &str) -> CallTarget {
+    workspace
+        .graphs()
+        .into_iter()
+        .map(|(_, graph)| graph)
+        .find(|graph| {
+            graph.id.to_string() == name
+                || graph.name == name
+                || graph
+                    .metadata
+                    .get(ADDRESS_METADATA_KEY)
+                    .is_some_and(|address| address.eq_ignore_ascii_case(name))
+        })
+        .map_or_else(|| CallTarget::External(name.to_string()), |graph| CallTarget::Contract(graph.id))
+}
+
+impl CallGraph {
+    /// Build the call graph of every graph in `workspace`.
+    ///
+    /// Calls whose target cannot be determined statically (wired from a
+    /// computed value) are left out.
+    pub fn build(workspace: &Workspace) -> Self {
+        let mut contracts = BTreeMap::new();
+        let mut edges: BTreeMap<(Uuid, CallTarget, CallKind), CallEdge> = BTreeMap::new();
+        for (_, graph) in workspace.graphs() {
+            contracts.insert(graph.id, graph.name.clone());
+            for node in &graph.nodes {
+                let (callee, kind, function) = match node.node_type.as_str() {
+                    "TryCall" => {
+                        let Some(name) = call_target_name(graph, node) else {
+                            log::debug!("TryCall node {} has no static target", node.id);
+                            continue;
+                        };
+                        let function = node.properties.get("function").and_then(|v| v.as_str()).map(str::to_string);
+                        (resolve_target(workspace, &name), CallKind::Call, function)
+                    }
+                    "Import" => match import_target(node) {
+                        Ok(id) => (CallTarget::Contract(id), CallKind::Import, None),
+                        Err(_) => continue,
+                    },
+                    _ => continue,
+                };
+                let edge = edges.entry((graph.id, callee.clone(), kind)).or_insert_with(|| CallEdge {
+                    caller: graph.id,
+                    callee,
+                    kind,
+                    functions: BTreeSet::new(),
+                    via: Vec::new(),
+                });
+                edge.functions.extend(function);
+                edge.via.push(node.id);
+            }
+        }
+        Self {
+            contracts,
+            edges: edges.into_values().collect(),
+        }
+    }
+
+    /// Contracts `contract` calls or imports
+    pub fn callees(&self, contract: Uuid) -> Vec<&CallEdge> {
+        self.edges.iter().filter(|e| e.caller == contract).collect()
+    }
+
+    /// Contracts that call or import `contract`
+    pub fn callers(&self, contract: Uuid) -> Vec<&CallEdge> {
+        let target = CallTarget::Contract(contract);
+        self.edges.iter().filter(|e| e.callee == target).collect()
+    }
+
+    /// Every contract that depends on `contract`, directly or transitively,
+    /// and so may be affected by upgrading it
+    pub fn blast_radius(&self, contract: Uuid) -> BTreeSet<Uuid> {
+        let mut affected = BTreeSet::new();
+        let mut pending = VecDeque::from([contract]);
+        while let Some(next) = pending.pop_front() {
+            for edge in self.callers(next) {
+                if edge.caller != contract && affected.insert(edge.caller) {
+                    pending.push_back(edge.caller);
+                }
+            }
+        }
+        affected
+    }
+
+    fn label(&self, target: &CallTarget) -> String {
+        match target {
+            CallTarget::Contract(id) => self.contracts.get(id).cloned().unwrap_or_else(|| id.to_string()),
+            CallTarget::External(name) => name.clone(),
+        }
+    }
+
+    fn node_key(target: &CallTarget) -> String {
+        match target {
+            CallTarget::Contract(id) => format!("c_{}", id.simple()),
+            CallTarget::External(name) => {
+                format!("x_{}", name.chars().map(|c| if c.is_ascii_alphanumeric() { c } else { '_' }).collect::<String>())
+            }
+        }
+    }
+
+    fn edge_label(edge: &CallEdge) -> String {
+        match edge.kind {
+            CallKind::Import => "import".to_string(),
+            CallKind::Call if edge.functions.is_empty() => "call".to_string(),
+            CallKind::Call => edge.functions.iter().cloned().collect::<Vec<_>>().join(", "),
+        }
+    }
+
+    fn targets(&self) -> BTreeSet<CallTarget> {
+        self.contracts
+            .keys()
+            .map(|id| CallTarget::Contract(*id))
+            .chain(self.edges.iter().map(|e| e.callee.clone()))
+            .collect()
+    }
+
+    /// Graphviz rendering; external contracts are drawn dashed
+    pub fn to_dot(&self) -> String {
+        let escape = |s: &str| s.replace('\\', "\\\\").replace('"', "\\\"");
+        let mut dot = String::from("digraph calls {\n    rankdir=LR;\n");
+        for target in self.targets() {
+            let style = if matches!(target, CallTarget::External(_)) { ", style=dashed" } else { "" };
+            dot.push_str(&format!(
+                "    {} [label=\"{}\"{}];\n",
+                Self::node_key(&target),
+                escape(&self.label(&target)),
+                style
+            ));
+        }
+        for edge in &self.edges {
+            let style = if edge.kind == CallKind::Import { ", style=dotted" } else { "" };
+            dot.push_str(&format!(
+                "    {} -> {} [label=\"{}\"{}];\n",
+                Self::node_key(&CallTarget::Contract(edge.caller)),
+                Self::node_key(&edge.callee),
+                escape(&Self::edge_label(edge)),
+                style
+            ));
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// Mermaid flowchart rendering
+    pub fn to_mermaid(&self) -> String {
+        let escape = |s: &str| s.replace('"', "#quot;");
+        let mut mermaid = String::from("flowchart LR\n");
+        for target in self.targets() {
+            let key = Self::node_key(&target);
+            let label = escape(&self.label(&target));
+            match target {
+                CallTarget::Contract(_) => mermaid.push_str(&format!("    {}[\"{}\"]\n", key, label)),
+                CallTarget::External(_) => mermaid.push_str(&format!("    {}((\"{}\"))\n", key, label)),
+            }
+        }
+        for edge in &self.edges {
+            let arrow = if edge.kind == CallKind::Import { "-.->" } else { "-->" };
+            mermaid.push_str(&format!(
+                "    {} {}|\"{}\"| {}\n",
+                Self::node_key(&CallTarget::Contract(edge.caller)),
+                arrow,
+                escape(&Self::edge_label(edge)),
+                Self::node_key(&edge.callee)
+            ));
+        }
+        mermaid
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Position;
+
+    fn try_call(target: &str, function: &str) -> VisualNode {
+        let mut node = VisualNode::new(Uuid::new_v4(), "TryCall", Position::new(0.0, 0.0));
+        node.properties.insert("target".to_string(), serde_json::json!(target));
+        node.properties.insert("function".to_string(), serde_json::json!(function));
+        node
+    }
+
+    #[test]
+    fn test_call_graph_and_blast_radius() {
+        let mut token = VisualGraph::new("Token");
+        token.metadata.insert(ADDRESS_METADATA_KEY.to_string(), "0xAB".to_string());
+        let mut vault = VisualGraph::new("Vault");
+        vault.add_node(try_call("0xab", "transfer"));
+        vault.add_node(try_call("0xab", "balanceOf"));
+        let mut router = VisualGraph::new("Router");
+        router.add_node(try_call("Vault", "deposit"));
+        router.add_node(try_call("0xdead", "swap"));
+
+        let mut workspace = Workspace::new();
+        for (path, graph) in [("token.json", &token), ("vault.json", &vault), ("router.json", &router)] {
+            workspace.add_graph(path, graph.clone()).unwrap();
+        }
+        let calls = CallGraph::build(&workspace);
+
+        let vault_calls = calls.callees(vault.id);
+        assert_eq!(vault_calls.len(), 1);
+        assert_eq!(vault_calls[0].callee, CallTarget::Contract(token.id));
+        assert_eq!(vault_calls[0].via.len(), 2);
+        assert_eq!(calls.callees(router.id).len(), 2);
+        assert_eq!(calls.blast_radius(token.id), BTreeSet::from([vault.id, router.id]));
+        assert!(calls.blast_radius(router.id).is_empty());
+
+        let dot = calls.to_dot();
+        assert!(dot.contains("label=\"balanceOf, transfer\""));
+        assert!(dot.contains("x_0xdead [label=\"0xdead\", style=dashed]"));
+        assert!(calls.to_mermaid().contains("x_0xdead((\"0xdead\"))"));
+    }
+}
@@ -0,0 +1,147 @@
+//! Contract constructor
+//!
+//! A graph may contain one `Init` node. Its data output ports are the
+//! constructor parameters, in port order; the flow leaving it is the body of the
+//! contract's `init` entry point, which runs once at deployment.
+
+use crate::{
+    error::{CanvasError, CanvasResult},
+    types::{ContractABI, FunctionABI, ParameterABI, StateMutability, ValueType, VisualGraph, VisualNode},
+};
+
+/// Name of the constructor entry point
+pub const INIT_FUNCTION: &str = "init";
+
+/// The graph's Init node, if it has one
+pub fn find_init_node(graph: &VisualGraph) -> CanvasResult<Option<&VisualNode>> {
+    let mut init_nodes = graph.nodes.iter().filter(|n| n.node_type == "Init");
+    let init = init_nodes.next();
+    if init_nodes.next().is_some() {
+        return Err(CanvasError::Compilation("A contract can have only one Init node".to_string()));
+    }
+    Ok(init)
+}
+
+/// Constructor parameters declared by an Init node's output ports
+pub fn constructor_params(node: &VisualNode) -> Vec<ParameterABI> {
+    node.outputs
+        .iter()
+        .filter(|port| port.value_type != ValueType::Flow)
+        .map(|port| ParameterABI {
+            name: port.id.clone(),
+            value_type: port.value_type.clone(),
+            indexed: false,
+        })
+        .collect()
+}
+
+/// ABI entry of the constructor; contracts without an Init node get a parameterless one
+pub fn constructor_abi(graph: &VisualGraph) -> CanvasResult<FunctionABI> {
+    let inputs = find_init_node(graph)?.map(constructor_params).unwrap_or_default();
+    Ok(FunctionABI {
+        name: INIT_FUNCTION.to_string(),
+        inputs,
+        outputs: Vec::new(),
+        state_mutability: StateMutability::NonPayable,
+        gas_estimate: None,
+    })
+}
+
+/// Add the constructor signature to an ABI
+pub fn add_constructor(graph: &VisualGraph, abi: &mut ContractABI) -> CanvasResult<()> {
+    if abi.functions.iter().any(|f| f.name == INIT_FUNCTION) {
+        return Err(CanvasError::Compilation(format!(
+            "Function '{}' is reserved for the contract constructor",
+            INIT_FUNCTION
+        )));
+    }
+    abi.functions.insert(0, constructor_abi(graph)?);
+    Ok(())
+}
+
+/// The constructor's ABI entry, if the ABI has one
+pub fn find_constructor(abi: &ContractABI) -> Option<&FunctionABI> {
+    abi.functions.iter().find(|f| f.name == INIT_FUNCTION)
+}
+
+/// Check deployment arguments against the constructor signature; see
+/// [`validate_call_args`](super::entry_points::validate_call_args)
+pub fn validate_constructor_args(
+    constructor: &FunctionABI,
+    args: &serde_json::Value,
+) -> CanvasResult<Vec<serde_json::Value>> {
+    super::entry_points::validate_call_args(constructor, args)
+}
+
+/// WASM type of the `init` parameter carrying a value of `value_type`.
+///
+/// Generated code keeps every value in an i64, and the engine passes entry
+/// point arguments as plain numbers, so only integer-like values can be given
+/// to the constructor.
+fn wasm_param(value_type: &ValueType) -> Option<&'static str> {
+    match value_type {
+        ValueType::Boolean | ValueType::Integer | ValueType::Decimal(_) => Some("i64"),
+        _ => None,
+    }
+}
+
+/// WASM types of the `init` export's parameters, in constructor order
+pub fn init_param_types(constructor: &FunctionABI) -> Result<Vec<&'static str>, String> {
+    constructor
+        .inputs
+        .iter()
+        .map(|param| {
+            wasm_param(&param.value_type).ok_or_else(|| {
+                format!(
+                    "Constructor parameter '{}' is a {:?}; only integers, decimals and booleans can be passed to '{}'",
+                    param.name, param.value_type, INIT_FUNCTION
+                )
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{Port, Position};
+
+    fn init_graph() -> VisualGraph {
+        let mut graph = VisualGraph::new("token");
+        graph.add_node(
+            VisualNode::new(uuid::Uuid::new_v4(), "Init", Position::new(0.0, 0.0)).with_outputs(vec![
+                Port::new("flow_out", "Flow Out", ValueType::Flow),
+                Port::new("name", "Name", ValueType::String),
+                Port::new("supply", "Supply", ValueType::Integer),
+            ]),
+        );
+        graph
+    }
+
+    #[test]
+    fn test_constructor_signature_from_ports() {
+        let constructor = constructor_abi(&init_graph()).unwrap();
+        let names: Vec<&str> = constructor.inputs.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, vec!["name", "supply"]);
+
+        // Strings cannot be passed by value
+        assert!(init_param_types(&constructor).unwrap_err().contains("'name'"));
+        let mut numeric = constructor;
+        numeric.inputs.remove(0);
+        assert_eq!(init_param_types(&numeric).unwrap(), vec!["i64"]);
+    }
+
+    #[test]
+    fn test_constructor_args_validation() {
+        let constructor = constructor_abi(&init_graph()).unwrap();
+
+        let named = serde_json::json!({"supply": 1000, "name": "Canvas"});
+        assert_eq!(
+            validate_constructor_args(&constructor, &named).unwrap(),
+            vec![serde_json::json!("Canvas"), serde_json::json!(1000)]
+        );
+        assert!(validate_constructor_args(&constructor, &serde_json::json!(["Canvas", "lots"])).is_err());
+        assert!(validate_constructor_args(&constructor, &serde_json::json!(["Canvas"])).is_err());
+        assert!(validate_constructor_args(&constructor, &serde_json::json!({"name": "Canvas"})).is_err());
+    }
+}
@@ -0,0 +1,232 @@
+//! Incremental validation for live editing
+//!
+//! Re-validating a large graph on every keystroke is too slow for the editor.
+//! [`DiagnosticsCache`] remembers the diagnostics of each node and connection
+//! under a hash of the content they depend on, and reuses them while that
+//! content is unchanged:
+//!
+//! - a node's diagnostics depend on its type, ports, properties and metadata
+//! - a connection's depend on the connection and the ports of its endpoints
+//! - graph-wide rules depend on all of the above
+//!
+//! Positions and sizes are not hashed, so dragging nodes around never
+//! invalidates anything. Hashes are blake3 over canonical JSON (object keys
+//! sorted), so entries survive a graph being re-serialized or re-sent by the
+//! frontend. The merged result is identical to [`Validator::validate`].
+
+use std::collections::{HashMap, HashSet};
+
+use serde::Serialize;
+
+use super::{ValidationResult, Validator};
+use crate::{
+    error::CanvasResult,
+    types::{Connection, EdgeId, NodeId, VisualGraph, VisualNode},
+};
+
+/// Hash of the canonical JSON encoding of `value`
+fn canonical_hash(value: &impl Serialize) -> CanvasResult<blake3::Hash> {
+    // Converting to a `Value` first sorts map keys, so `HashMap` order doesn't leak in
+    let canonical = serde_json::to_value(value)?;
+    Ok(blake3::hash(&serde_json::to_vec(&canonical)?))
+}
+
+/// Hash of everything a node's own diagnostics depend on
+pub fn node_content_hash(node: &VisualNode) -> CanvasResult<blake3::Hash> {
+    canonical_hash(&serde_json::json!({
+        "id": node.id,
+        "node_type": node.node_type,
+        "inputs": node.inputs,
+        "outputs": node.outputs,
+        "properties": node.properties,
+        "metadata": node.metadata,
+    }))
+}
+
+/// Hash of a connection together with the ports it could attach to
+fn connection_hash(connection: &Connection, graph: &VisualGraph) -> CanvasResult<blake3::Hash> {
+    let source = graph.get_node(connection.source_node).map(|n| &n.outputs);
+    let target = graph.get_node(connection.target_node).map(|n| &n.inputs);
+    canonical_hash(&serde_json::json!({
+        "connection": connection,
+        "source_outputs": source,
+        "target_inputs": target,
+    }))
+}
+
+/// Cache hits and misses of the last [`DiagnosticsCache::validate`] call
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    pub hits: usize,
+    pub misses: usize,
+}
+
+#[derive(Debug, Clone)]
+struct Entry {
+    hash: blake3::Hash,
+    diagnostics: ValidationResult,
+}
+
+/// Validation results of one graph, reused across edits
+#[derive(Debug, Default)]
+pub struct DiagnosticsCache {
+    nodes: HashMap<NodeId, Entry>,
+    connections: HashMap<EdgeId, Entry>,
+    graph: Option<Entry>,
+    stats: CacheStats,
+}
+
+impl DiagnosticsCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Forget a node's diagnostics, along with everything that depends on it
+    pub fn invalidate_node(&mut self, id: NodeId) {
+        self.nodes.remove(&id);
+        self.graph = None;
+    }
+
+    /// Forget a connection's diagnostics, along with the graph-wide rules
+    pub fn invalidate_connection(&mut self, id: EdgeId) {
+        self.connections.remove(&id);
+        self.graph = None;
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        self.stats
+    }
+
+    /// Validate `graph`, re-running only the checks whose inputs changed
+    pub fn validate(&mut self, validator: &Validator, graph: &VisualGraph) -> CanvasResult<ValidationResult> {
+        let mut stats = CacheStats::default();
+        let mut result = ValidationResult::valid();
+        // The graph-wide hash is built from the per-item hashes, in graph order
+        let mut graph_hasher = blake3::Hasher::new();
+        graph_hasher.update(canonical_hash(&graph.metadata)?.as_bytes());
+
+        let mut live_nodes = HashSet::with_capacity(graph.nodes.len());
+        for node in &graph.nodes {
+            let hash = node_content_hash(node)?;
+            graph_hasher.update(hash.as_bytes());
+            live_nodes.insert(node.id);
+            let diagnostics = match self.nodes.get(&node.id) {
+                Some(entry) if entry.hash == hash => {
+                    stats.hits += 1;
+                    entry.diagnostics.clone()
+                }
+                _ => {
+                    stats.misses += 1;
+                    let mut diagnostics = ValidationResult::valid();
+                    validator.validate_node(node, &mut diagnostics);
+                    self.nodes.insert(node.id, Entry { hash, diagnostics: diagnostics.clone() });
+                    diagnostics
+                }
+            };
+            merge(&mut result, diagnostics);
+        }
+
+        let mut live_connections = HashSet::with_capacity(graph.connections.len());
+        for connection in &graph.connections {
+            let hash = connection_hash(connection, graph)?;
+            graph_hasher.update(hash.as_bytes());
+            live_connections.insert(connection.id);
+            let diagnostics = match self.connections.get(&connection.id) {
+                Some(entry) if entry.hash == hash => {
+                    stats.hits += 1;
+                    entry.diagnostics.clone()
+                }
+                _ => {
+                    stats.misses += 1;
+                    let mut diagnostics = ValidationResult::valid();
+                    validator.validate_connection(connection, graph, &mut diagnostics);
+                    self.connections.insert(connection.id, Entry { hash, diagnostics: diagnostics.clone() });
+                    diagnostics
+                }
+            };
+            merge(&mut result, diagnostics);
+        }
+
+        // Drop entries for deleted nodes and connections
+        self.nodes.retain(|id, _| live_nodes.contains(id));
+        self.connections.retain(|id, _| live_connections.contains(id));
+
+        let hash = graph_hasher.finalize();
+        let diagnostics = match &self.graph {
+            Some(entry) if entry.hash == hash => {
+                stats.hits += 1;
+                entry.diagnostics.clone()
+            }
+            _ => {
+                stats.misses += 1;
+                let mut diagnostics = ValidationResult::valid();
+                validator.validate_graph_rules(graph, &mut diagnostics);
+                self.graph = Some(Entry { hash, diagnostics: diagnostics.clone() });
+                diagnostics
+            }
+        };
+        merge(&mut result, diagnostics);
+
+        self.stats = stats;
+        Ok(result)
+    }
+}
+
+fn merge(result: &mut ValidationResult, diagnostics: ValidationResult) {
+    result.is_valid &= diagnostics.is_valid;
+    result.errors.extend(diagnostics.errors);
+    result.warnings.extend(diagnostics.warnings);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::types::{Port, Position, ValueType};
+    use uuid::Uuid;
+
+    #[test]
+    fn test_cache_reuses_unchanged_diagnostics() {
+        let mut graph = VisualGraph::new("cached");
+        let start = VisualNode::new(Uuid::new_v4(), "Start", Position::new(0.0, 0.0))
+            .with_outputs(vec![Port::new("out", "out", ValueType::Integer)]);
+        let write = VisualNode::new(Uuid::new_v4(), "WriteStorage", Position::new(200.0, 0.0))
+            .with_inputs(vec![Port::new("value", "value", ValueType::Integer)]);
+        graph.connections.push(Connection::new(Uuid::new_v4(), start.id, "out", write.id, "value"));
+        let write_id = write.id;
+        graph.nodes.extend([start, write]);
+
+        let validator = Validator::new(&Config::default()).unwrap();
+        let mut cache = DiagnosticsCache::new();
+        let first = cache.validate(&validator, &graph).unwrap();
+        assert_eq!(cache.stats(), CacheStats { hits: 0, misses: 4 });
+        assert_eq!(first.errors, validator.validate(&graph).unwrap().errors);
+        assert!(first.errors.iter().any(|e| e.contains("missing required 'key'")));
+
+        // Moving a node reuses everything
+        graph.nodes[1].position = Position::new(400.0, 50.0);
+        cache.validate(&validator, &graph).unwrap();
+        assert_eq!(cache.stats(), CacheStats { hits: 4, misses: 0 });
+
+        // Editing a property re-runs that node and the graph-wide rules only
+        graph.nodes[1].properties.insert("key".to_string(), serde_json::json!("total"));
+        let edited = cache.validate(&validator, &graph).unwrap();
+        assert_eq!(cache.stats(), CacheStats { hits: 2, misses: 2 });
+        assert_eq!(edited.errors, validator.validate(&graph).unwrap().errors);
+        assert_eq!(edited.warnings, validator.validate(&graph).unwrap().warnings);
+
+        // Changing a port the connection attaches to invalidates the connection
+        graph.nodes[1].inputs[0].value_type = ValueType::String;
+        let mismatched = cache.validate(&validator, &graph).unwrap();
+        assert_eq!(cache.stats(), CacheStats { hits: 1, misses: 3 });
+        assert!(mismatched.errors.iter().any(|e| e.starts_with("Type mismatch")));
+
+        cache.invalidate_node(write_id);
+        cache.validate(&validator, &graph).unwrap();
+        assert_eq!(cache.stats(), CacheStats { hits: 2, misses: 2 });
+    }
+}
@@ -0,0 +1,295 @@
+//! Exported entry points and method dispatch
+//!
+//! Every `Start` node is one exported function. Its `function` property names
+//! it and its data output ports are the function's parameters. A graph with a
+//! single unnamed Start node exports it as `main`, which keeps older graphs
+//! compiling unchanged.
+//!
+//! Besides a WASM export per entry point, the compiler generates a `dispatch`
+//! export that routes a 4-byte function selector to the matching entry point,
+//! for hosts that call contracts through a single entry. Its arguments are a
+//! JSON array written into a buffer from the `alloc` export.
+
+use std::collections::HashMap;
+
+use crate::{
+    error::{CanvasError, CanvasResult},
+    types::{ContractABI, FunctionABI, NodeId, ParameterABI, StateMutability, ValueType, VisualGraph, VisualNode},
+    wasm::{
+        engine::{ALLOC_EXPORT, MEMORY_EXPORT},
+        host,
+    },
+};
+
+/// Name given to the entry point of a graph with a single unnamed Start node
+pub const DEFAULT_ENTRY_POINT: &str = "main";
+/// Export that routes a function selector to an entry point
+pub const DISPATCH_EXPORT: &str = "dispatch";
+
+/// An exported function backed by a Start node
+#[derive(Debug, Clone)]
+pub struct EntryPoint {
+    /// Start node the function's flow begins at
+    pub node_id: NodeId,
+    /// ABI signature of the function
+    pub function: FunctionABI,
+    /// Selector used by the dispatcher
+    pub selector: u32,
+}
+
+/// Selector of a function: the first four bytes of the SHA-256 of its name
+pub fn function_selector(name: &str) -> u32 {
+    let digest = host::hash(host::HashAlgorithm::Sha256, name.as_bytes());
+    u32::from_be_bytes([digest[0], digest[1], digest[2], digest[3]])
+}
+
+fn parse_mutability(node: &VisualNode) -> CanvasResult<StateMutability> {
+    match node.properties.get("mutability").and_then(|v| v.as_str()) {
+        None | Some("nonpayable") => Ok(StateMutability::NonPayable),
+        Some("payable") => Ok(StateMutability::Payable),
+        Some("view") => Ok(StateMutability::View),
+        Some("pure") => Ok(StateMutability::Pure),
+        Some(other) => Err(CanvasError::Compilation(format!(
+            "Start node {} has unknown mutability '{}'",
+            node.id, other
+        ))),
+    }
+}
+
+fn entry_point(node: &VisualNode, name: String) -> CanvasResult<EntryPoint> {
+    let inputs = node
+        .outputs
+        .iter()
+        .filter(|port| port.value_type != ValueType::Flow)
+        .map(|port| ParameterABI {
+            name: port.id.clone(),
+            value_type: port.value_type.clone(),
+            indexed: false,
+        })
+        .collect();
+
+    let outputs = match node.properties.get("returns").and_then(|v| v.as_str()) {
+        Some(type_name) => {
+            let value_type = ValueType::from_name(type_name).ok_or_else(|| {
+                CanvasError::Compilation(format!(
+                    "Start node {} has unknown return type '{}'",
+                    node.id, type_name
+                ))
+            })?;
+            vec![ParameterABI {
+                name: "result".to_string(),
+                value_type,
+                indexed: false,
+            }]
+        }
+        None => Vec::new(),
+    };
+
+    Ok(EntryPoint {
+        node_id: node.id,
+        selector: function_selector(&name),
+        function: FunctionABI {
+            name,
+            inputs,
+            outputs,
+            state_mutability: parse_mutability(node)?,
+            gas_estimate: None,
+        },
+    })
+}
+
+/// Collect the graph's entry points, one per Start node
+pub fn collect_entry_points(graph: &VisualGraph) -> CanvasResult<Vec<EntryPoint>> {
+    let starts: Vec<&VisualNode> = graph.nodes.iter().filter(|n| n.node_type == "Start").collect();
+    let mut entries = Vec::new();
+    let mut by_selector: HashMap<u32, String> = HashMap::new();
+
+    for node in &starts {
+        let name = match node.properties.get("function").and_then(|v| v.as_str()) {
+            Some(name) if !name.is_empty() => name.to_string(),
+            _ if starts.len() == 1 => DEFAULT_ENTRY_POINT.to_string(),
+            _ => {
+                return Err(CanvasError::Compilation(format!(
+                    "Start node {} needs a 'function' name when a graph has several entry points",
+                    node.id
+                )))
+            }
+        };
+
+        if [DISPATCH_EXPORT, ALLOC_EXPORT, MEMORY_EXPORT, super::constructor::INIT_FUNCTION].contains(&name.as_str()) {
+            return Err(CanvasError::Compilation(format!(
+                "Entry point name '{}' is reserved",
+                name
+            )));
+        }
+
+        let entry = entry_point(node, name)?;
+        if let Some(existing) = by_selector.insert(entry.selector, entry.function.name.clone()) {
+            let problem = if existing == entry.function.name {
+                format!("Entry point '{}' is defined more than once", existing)
+            } else {
+                format!(
+                    "Entry points '{}' and '{}' have the same selector",
+                    existing, entry.function.name
+                )
+            };
+            return Err(CanvasError::Compilation(problem));
+        }
+        entries.push(entry);
+    }
+
+    Ok(entries)
+}
+
+/// Add the entry point signatures and their selectors to an ABI
+pub fn add_entry_points(graph: &VisualGraph, abi: &mut ContractABI) -> CanvasResult<()> {
+    for entry in collect_entry_points(graph)? {
+        abi.metadata.insert(
+            format!("selector.{}", entry.function.name),
+            format!("0x{:08x}", entry.selector),
+        );
+        abi.functions.push(entry.function);
+    }
+    Ok(())
+}
+
+/// Find a function in an ABI by name
+pub fn find_function<'a>(abi: &'a ContractABI, name: &str) -> Option<&'a FunctionABI> {
+    abi.functions.iter().find(|f| f.name == name)
+}
+
+/// Check call arguments against a function signature.
+///
+/// Arguments may be given positionally as a JSON array or by name as a JSON
+/// object; `null` stands for no arguments. Returns the arguments in parameter
+/// order.
+pub fn validate_call_args(function: &FunctionABI, args: &serde_json::Value) -> CanvasResult<Vec<serde_json::Value>> {
+    let params = &function.inputs;
+    let ordered: Vec<serde_json::Value> = match args {
+        serde_json::Value::Null => Vec::new(),
+        serde_json::Value::Array(items) => items.clone(),
+        serde_json::Value::Object(named) => {
+            if let Some(unknown) = named.keys().find(|k| !params.iter().any(|p| &p.name == *k)) {
+                return Err(CanvasError::Validation(format!(
+                    "Unknown argument '{}' for '{}'",
+                    unknown, function.name
+                )));
+            }
+            params
+                .iter()
+                .map(|p| {
+                    named.get(&p.name).cloned().ok_or_else(|| {
+                        CanvasError::Validation(format!("Missing argument '{}' for '{}'", p.name, function.name))
+                    })
+                })
+                .collect::<CanvasResult<_>>()?
+        }
+        other => {
+            return Err(CanvasError::Validation(format!(
+                "Arguments for '{}' must be an array or object, got {}",
+                function.name, other
+            )))
+        }
+    };
+
+    if ordered.len() != params.len() {
+        return Err(CanvasError::Validation(format!(
+            "'{}' expects {} arguments, got {}",
+            function.name,
+            params.len(),
+            ordered.len()
+        )));
+    }
+
+    for (param, value) in params.iter().zip(&ordered) {
+        if !param.value_type.matches_value(value) {
+            return Err(CanvasError::Validation(format!(
+                "Argument '{}' of '{}' should be {:?}, got {}",
+                param.name, function.name, param.value_type, value
+            )));
+        }
+    }
+
+    Ok(ordered)
+}
+
+/// WAT for the `dispatch` export, routing the selector of `functions[i]` to
+/// `$dispatch<i>`.
+///
+/// Each wrapper `$dispatch<i>` takes the JSON argument buffer, decodes the
+/// function's arguments and calls it. Unknown selectors trap.
+pub fn dispatch_wat(functions: &[&str]) -> String {
+    let mut wat = format!(
+        "  (func (export \"{}\") (param $selector i32) (param $args_ptr i32) (param $args_len i32) (result i32)\n",
+        DISPATCH_EXPORT
+    );
+    for (index, name) in functions.iter().enumerate() {
+        wat.push_str(&format!(
+            "    (if (i32.eq (local.get $selector) (i32.const 0x{:08x}))\n      (then (return (call $dispatch{} (local.get $args_ptr) (local.get $args_len)))))\n",
+            function_selector(name), index
+        ));
+    }
+    wat.push_str("    unreachable)");
+    wat
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{Port, Position};
+
+    fn start(function: Option<&str>) -> VisualNode {
+        let mut node = VisualNode::new(uuid::Uuid::new_v4(), "Start", Position::new(0.0, 0.0)).with_outputs(vec![
+            Port::new("flow_out", "Flow Out", ValueType::Flow),
+            Port::new("amount", "Amount", ValueType::Integer),
+        ]);
+        if let Some(function) = function {
+            node = node.with_property("function", serde_json::json!(function));
+        }
+        node
+    }
+
+    #[test]
+    fn test_single_unnamed_start_is_main() {
+        let mut graph = VisualGraph::new("legacy");
+        graph.add_node(start(None));
+
+        let entries = collect_entry_points(&graph).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].function.name, DEFAULT_ENTRY_POINT);
+        assert_eq!(entries[0].function.inputs.len(), 1);
+    }
+
+    #[test]
+    fn test_multiple_entry_points_need_unique_names() {
+        let mut graph = VisualGraph::new("token");
+        graph.add_node(start(Some("deposit")));
+        graph.add_node(start(Some("withdraw")));
+        let entries = collect_entry_points(&graph).unwrap();
+        assert_ne!(entries[0].selector, entries[1].selector);
+        let names: Vec<&str> = entries.iter().map(|e| e.function.name.as_str()).collect();
+        let dispatch = dispatch_wat(&names);
+        assert!(dispatch.contains(&format!("0x{:08x}", entries[1].selector)) && dispatch.contains("$dispatch1"));
+
+        graph.add_node(start(Some("deposit")));
+        assert!(collect_entry_points(&graph).is_err());
+
+        let mut unnamed = VisualGraph::new("unnamed");
+        unnamed.add_node(start(None));
+        unnamed.add_node(start(Some("withdraw")));
+        assert!(collect_entry_points(&unnamed).is_err());
+    }
+
+    #[test]
+    fn test_call_args_validation() {
+        let mut graph = VisualGraph::new("token");
+        graph.add_node(start(Some("deposit")));
+        let function = collect_entry_points(&graph).unwrap().remove(0).function;
+
+        assert_eq!(
+            validate_call_args(&function, &serde_json::json!({"amount": 5})).unwrap(),
+            vec![serde_json::json!(5)]
+        );
+        assert!(validate_call_args(&function, &serde_json::json!(["five"])).is_err());
+    }
+}
@@ -0,0 +1,404 @@
+//! Static gas bounds for visual graphs
+//!
+//! Besides the per-node bounds, [`GasModel`] estimates each exported function:
+//! the nodes its flow reaches, weighted by how often they run, plus the WASM
+//! instructions generated for its body. Both cost tables can be overridden in
+//! `[compiler.gas_model]`.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use crate::{
+    config::GasModelConfig,
+    nodes::{builtin_node_definitions, NodeDefinition},
+    types::{FunctionGasEstimate, Gas, NodeId, ValueType, VisualGraph, VisualNode},
+    wasm::host,
+};
+
+/// Upper bound on a node's gas cost
+#[derive(Debug, Clone, PartialEq)]
+pub enum GasBound {
+    /// Cost never exceeds this amount
+    Bounded(Gas),
+    /// Cost cannot be bounded statically
+    Unbounded(String),
+}
+
+/// Gas bound for a whole graph
+#[derive(Debug, Clone, Default)]
+pub struct GasReport {
+    /// Sum of all bounded node costs
+    pub total: Gas,
+    /// Nodes whose cost could not be bounded, with the reason
+    pub unbounded: Vec<(NodeId, String)>,
+}
+
+impl GasReport {
+    /// Whether every node in the graph has a static bound
+    pub fn is_bounded(&self) -> bool {
+        self.unbounded.is_empty()
+    }
+}
+
+fn max_length(node: &VisualNode) -> Option<usize> {
+    node.properties
+        .get("max_length")
+        .and_then(|v| v.as_u64())
+        .map(|v| v as usize)
+}
+
+/// Compute the gas bound of a single node
+pub fn node_gas_bound(node: &VisualNode) -> GasBound {
+    match node.node_type.as_str() {
+        "Concat" | "Slice" | "HexEncode" | "HexDecode" | "Compare" => match max_length(node) {
+            Some(len) => GasBound::Bounded(host::string_op_gas(len)),
+            None => GasBound::Unbounded("missing 'max_length' annotation".to_string()),
+        },
+        "Hash" => match max_length(node) {
+            Some(len) => GasBound::Bounded(host::hash_gas(len)),
+            None => GasBound::Unbounded("missing 'max_length' annotation".to_string()),
+        },
+        "ReadStorage" => GasBound::Bounded(host::STORAGE_READ_GAS),
+        "WriteStorage" => GasBound::Bounded(host::STORAGE_WRITE_GAS),
+        "BatchReadStorage" | "BatchWriteStorage" => {
+            let keys = node
+                .properties
+                .get("keys")
+                .and_then(|v| v.as_array())
+                .map(|keys| keys.len())
+                .unwrap_or(host::MAX_BATCH_KEYS);
+            if node.node_type == "BatchReadStorage" {
+                GasBound::Bounded(host::batch_read_gas(keys))
+            } else {
+                GasBound::Bounded(host::batch_write_gas(keys))
+            }
+        }
+        "ForEach" => match node.properties.get("max_iterations").and_then(|v| v.as_u64()) {
+            Some(max) => GasBound::Bounded(crate::nodes::ForEachNode::ITERATION_GAS * (max + 1)),
+            None => GasBound::Unbounded("missing 'max_iterations' bound".to_string()),
+        },
+        other => builtin_node_definitions()
+            .into_iter()
+            .find(|definition| definition.id == other)
+            .and_then(|definition| definition.compiler_hint.gas_cost)
+            .map(GasBound::Bounded)
+            .unwrap_or_else(|| GasBound::Unbounded(format!("no gas information for node type {}", other))),
+    }
+}
+
+/// Sum the gas bounds of every node in a graph
+pub fn estimate_graph_gas(graph: &VisualGraph) -> GasReport {
+    let mut report = GasReport::default();
+    for node in &graph.nodes {
+        match node_gas_bound(node) {
+            GasBound::Bounded(gas) => report.total = report.total.saturating_add(gas),
+            GasBound::Unbounded(reason) => report.unbounded.push((node.id, reason)),
+        }
+    }
+    report
+}
+
+/// Instructions wasmtime meters at no fuel
+const FREE_INSTRUCTIONS: &[&str] = &["nop", "drop", "block", "loop", "unreachable", "return", "else", "end"];
+
+/// Control instructions without a type prefix
+const CONTROL_INSTRUCTIONS: &[&str] = &[
+    "block", "loop", "if", "else", "end", "br", "br_if", "br_table", "return", "call", "call_indirect",
+    "unreachable", "nop", "drop", "select",
+];
+
+/// Prefixes of the `<prefix>.<op>` instructions
+const INSTRUCTION_PREFIXES: &[&str] = &["i32", "i64", "f32", "f64", "local", "global", "memory", "table", "ref"];
+
+fn is_instruction(token: &str) -> bool {
+    if CONTROL_INSTRUCTIONS.contains(&token) {
+        return true;
+    }
+    token
+        .split_once('.')
+        .map_or(false, |(prefix, op)| INSTRUCTION_PREFIXES.contains(&prefix) && !op.is_empty())
+}
+
+/// Count the instructions in a WAT snippet, flat or folded, by mnemonic
+pub fn count_instructions(wat: &str) -> BTreeMap<String, u64> {
+    let mut counts = BTreeMap::new();
+    for line in wat.lines() {
+        let code = line.split(";;").next().unwrap_or_default();
+        let mut in_string = false;
+        let mut token = String::new();
+        for c in code.chars().chain(std::iter::once(' ')) {
+            if c == '"' {
+                in_string = !in_string;
+                continue;
+            }
+            if in_string {
+                continue;
+            }
+            if c.is_whitespace() || c == '(' || c == ')' {
+                if is_instruction(&token) {
+                    *counts.entry(std::mem::take(&mut token)).or_insert(0) += 1;
+                }
+                token.clear();
+            } else {
+                token.push(c);
+            }
+        }
+    }
+    counts
+}
+
+/// How often a reached node runs, relative to one call
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Weight {
+    worst: f64,
+    average: f64,
+}
+
+/// Cost tables for per-function gas estimates
+#[derive(Debug, Clone)]
+pub struct GasModel {
+    config: GasModelConfig,
+    definitions: HashMap<String, NodeDefinition>,
+}
+
+impl GasModel {
+    pub fn new(config: &GasModelConfig) -> Self {
+        Self {
+            config: config.clone(),
+            definitions: builtin_node_definitions().into_iter().map(|d| (d.id.clone(), d)).collect(),
+        }
+    }
+
+    /// Bound of one node, from the configured table or the built-in bound
+    pub fn node_cost(&self, node: &VisualNode) -> GasBound {
+        match self.config.node_costs.get(&node.node_type) {
+            Some(&gas) => GasBound::Bounded(gas),
+            None => node_gas_bound(node),
+        }
+    }
+
+    /// Gas of one execution of an instruction
+    pub fn instruction_cost(&self, instruction: &str) -> Gas {
+        match self.config.instruction_costs.get(instruction) {
+            Some(&gas) => gas,
+            None if FREE_INSTRUCTIONS.contains(&instruction) => 0,
+            None => self.config.default_instruction_cost,
+        }
+    }
+
+    /// Gas of running every counted instruction once
+    pub fn instructions_gas(&self, counts: &BTreeMap<String, u64>) -> Gas {
+        counts
+            .iter()
+            .map(|(instruction, &count)| self.instruction_cost(instruction).saturating_mul(count))
+            .fold(0, Gas::saturating_add)
+    }
+
+    /// Estimate the function entered at `entry`, whose generated body has the
+    /// given instruction counts
+    pub fn estimate_function(
+        &self,
+        graph: &VisualGraph,
+        function: &str,
+        entry: NodeId,
+        instructions: &BTreeMap<String, u64>,
+    ) -> FunctionGasEstimate {
+        let mut unbounded = Vec::new();
+        let weights = match self.weights(graph, entry) {
+            Ok(weights) => weights,
+            Err(weights) => {
+                unbounded.push((entry, "flow cycle without an iteration bound".to_string()));
+                weights
+            }
+        };
+
+        let (mut worst, mut average) = (0.0, 0.0);
+        for node in graph.nodes.iter().filter(|n| weights.contains_key(&n.id)) {
+            match self.node_cost(node) {
+                GasBound::Bounded(gas) => {
+                    worst += gas as f64 * weights[&node.id].worst;
+                    average += gas as f64 * weights[&node.id].average;
+                }
+                GasBound::Unbounded(reason) => unbounded.push((node.id, reason)),
+            }
+        }
+        let node_gas = worst.ceil() as Gas;
+        let instruction_gas = self.instructions_gas(instructions);
+        FunctionGasEstimate {
+            function: function.to_string(),
+            worst_case: node_gas.saturating_add(instruction_gas),
+            average_case: (average.ceil() as Gas).saturating_add(instruction_gas),
+            node_gas,
+            instruction_gas,
+            unbounded,
+        }
+    }
+
+    fn is_flow_output(&self, node: &VisualNode, port: &str) -> bool {
+        node.outputs
+            .iter()
+            .chain(self.definitions.get(&node.node_type).into_iter().flat_map(|d| d.outputs.iter()))
+            .find(|p| p.id == port)
+            .map_or(false, |p| p.value_type == ValueType::Flow)
+    }
+
+    /// Weight of every node reached from `entry`. Flow passes through nodes
+    /// unchanged, splits evenly across the taken outputs of a branch in the
+    /// average case, and multiplies by the iteration bound into a loop body.
+    /// Data nodes run as often as the most frequent node consuming them.
+    /// `Err` carries the weights so far when a flow cycle keeps them growing.
+    fn weights(&self, graph: &VisualGraph, entry: NodeId) -> Result<HashMap<NodeId, Weight>, HashMap<NodeId, Weight>> {
+        let nodes: HashMap<NodeId, &VisualNode> = graph.nodes.iter().map(|n| (n.id, n)).collect();
+        let flow: Vec<bool> = graph
+            .connections
+            .iter()
+            .map(|c| nodes.get(&c.source_node).map_or(false, |n| self.is_flow_output(n, &c.source_port)))
+            .collect();
+        let mut flow_nodes = HashSet::new();
+        // Flow outputs each node actually takes, for branch weighting
+        let mut branches: HashMap<NodeId, HashSet<&str>> = HashMap::new();
+        for (connection, _) in graph.connections.iter().zip(&flow).filter(|(_, is_flow)| **is_flow) {
+            flow_nodes.extend([connection.source_node, connection.target_node]);
+            branches.entry(connection.source_node).or_default().insert(&connection.source_port);
+        }
+
+        let mut weights = HashMap::from([(entry, Weight { worst: 1.0, average: 1.0 })]);
+        for _ in 0..=graph.nodes.len() {
+            let mut changed = false;
+            for (connection, &is_flow) in graph.connections.iter().zip(&flow) {
+                let (from, to) = if is_flow {
+                    (connection.source_node, connection.target_node)
+                } else if !flow_nodes.contains(&connection.source_node) {
+                    (connection.target_node, connection.source_node)
+                } else {
+                    continue;
+                };
+                let Some(&weight) = weights.get(&from) else { continue };
+                let weight = if is_flow {
+                    self.follow(nodes.get(&from).copied(), &connection.source_port, weight, &branches)
+                } else {
+                    weight
+                };
+                let current = weights.entry(to).or_insert(Weight { worst: 0.0, average: 0.0 });
+                if weight.worst > current.worst || weight.average > current.average {
+                    current.worst = current.worst.max(weight.worst);
+                    current.average = current.average.max(weight.average);
+                    changed = true;
+                }
+            }
+            if !changed {
+                return Ok(weights);
+            }
+        }
+        Err(weights)
+    }
+
+    /// Weight of the flow leaving `node` through `port`
+    fn follow(
+        &self,
+        node: Option<&VisualNode>,
+        port: &str,
+        weight: Weight,
+        branches: &HashMap<NodeId, HashSet<&str>>,
+    ) -> Weight {
+        let Some(node) = node else { return weight };
+        if node.node_type == "ForEach" {
+            if port != "body_flow" {
+                return weight;
+            }
+            let iterations = node.properties.get("max_iterations").and_then(|v| v.as_u64()).unwrap_or(1) as f64;
+            return Weight {
+                worst: weight.worst * iterations,
+                average: weight.average * iterations * self.config.average_loop_fraction,
+            };
+        }
+        let taken = branches.get(&node.id).map_or(1, |ports| ports.len()).max(1);
+        Weight {
+            worst: weight.worst,
+            average: weight.average / taken as f64,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{Connection, Port, Position};
+
+    #[test]
+    fn test_string_node_bound_uses_max_length() {
+        let node = VisualNode::new(uuid::Uuid::new_v4(), "Concat", Position::new(0.0, 0.0))
+            .with_property("max_length", serde_json::json!(64));
+        assert_eq!(node_gas_bound(&node), GasBound::Bounded(host::string_op_gas(64)));
+    }
+
+    #[test]
+    fn test_missing_max_length_is_unbounded() {
+        let mut graph = VisualGraph::new("strings");
+        graph.add_node(VisualNode::new(uuid::Uuid::new_v4(), "Hash", Position::new(0.0, 0.0)));
+        graph.add_node(VisualNode::new(uuid::Uuid::new_v4(), "Add", Position::new(0.0, 0.0)));
+
+        let report = estimate_graph_gas(&graph);
+        assert!(!report.is_bounded());
+        assert_eq!(report.total, 3);
+    }
+    #[test]
+    fn test_count_instructions_in_folded_and_flat_wat() {
+        let counts = count_instructions(
+            "(func $entry0 (export \"i64.add\") (param $p0 i64) (result i64)\n  \
+             (i64.add (local.get $p0) (i64.const 1)) ;; i64.mul\n  local.get $p0\n  drop\n  call $canvas_itoa)",
+        );
+        assert_eq!(counts["i64.add"], 1);
+        assert_eq!(counts["local.get"], 2);
+        assert_eq!(counts["i64.const"], 1);
+        assert_eq!(counts["call"], 1);
+        assert!(!counts.contains_key("i64.mul"));
+
+        let mut config = GasModelConfig::default();
+        config.instruction_costs.insert("call".to_string(), 10);
+        assert_eq!(GasModel::new(&config).instructions_gas(&counts), 2 + 1 + 1 + 10);
+    }
+
+    #[test]
+    fn test_branches_and_loops_weight_reached_nodes() {
+        let flow = |id: &str| Port::new(id, id, ValueType::Flow);
+        let new = |node_type: &str| VisualNode::new(uuid::Uuid::new_v4(), node_type, Position::new(0.0, 0.0));
+        let start = new("Start").with_outputs(vec![flow("flow_out")]);
+        let branch = new("If").with_outputs(vec![flow("true_flow"), flow("false_flow")]);
+        let write = new("WriteStorage");
+        let read = new("ReadStorage");
+        let each = new("ForEach")
+            .with_outputs(vec![flow("body_flow"), flow("done_flow")])
+            .with_property("max_iterations", serde_json::json!(10));
+        let body = new("WriteStorage");
+
+        let mut graph = VisualGraph::new("weights");
+        for (from, port, to) in [
+            (&start, "flow_out", &branch),
+            (&branch, "true_flow", &write),
+            (&branch, "false_flow", &read),
+            (&read, "flow_out", &each),
+            (&each, "body_flow", &body),
+        ] {
+            graph.add_connection(Connection::new(uuid::Uuid::new_v4(), from.id, port, to.id, "flow_in"));
+        }
+        let (start_id, write_id) = (start.id, write.id);
+        for node in [start, branch, write, read, each, body] {
+            graph.add_node(node);
+        }
+
+        let mut config = GasModelConfig::default();
+        config.node_costs.insert("Start".to_string(), 0);
+        config.node_costs.insert("If".to_string(), 0);
+        let model = GasModel::new(&config);
+        let weights = model.weights(&graph, start_id).unwrap();
+        assert_eq!(weights[&write_id], Weight { worst: 1.0, average: 0.5 });
+
+        let estimate = model.estimate_function(&graph, "main", start_id, &BTreeMap::new());
+        let each_gas = crate::nodes::ForEachNode::ITERATION_GAS * 11;
+        assert_eq!(
+            estimate.worst_case,
+            host::STORAGE_WRITE_GAS * 11 + host::STORAGE_READ_GAS + each_gas
+        );
+        assert!(estimate.average_case < estimate.worst_case);
+    }
+}
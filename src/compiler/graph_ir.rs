@@ -1,8 +1,20 @@
 //! Graph Intermediate Representation (IR)
+//!
+//! The IR is the visual graph stripped of layout: nodes keep their type, port
+//! IDs and properties (strings as-is, other values as JSON text), and each
+//! connection joins two `node:port` endpoints, typed by its source port. Plugin
+//! passes transform it before the AST is built from it.
 
-// TODO: Implement Graph IR generation from visual graph
-// This module will convert the visual graph into an intermediate representation
-// that can be used for optimization and code generation.
+use std::collections::HashMap;
+
+use crate::{
+    error::{CanvasError, CanvasResult},
+    nodes::{builtin_node_definitions, NodeDefinition},
+    types::{ValueType, VisualGraph},
+};
+
+/// Data type of connections carrying execution flow
+pub const FLOW_DATA_TYPE: &str = "flow";
 
 /// Graph IR node
 #[derive(Debug, Clone)]
@@ -14,6 +26,20 @@ pub struct GraphIRNode {
     pub properties: std::collections::HashMap<String, String>,
 }
 
+impl GraphIRNode {
+    /// String value of a property
+    pub fn property(&self, name: &str) -> Option<&str> {
+        self.properties.get(name).map(String::as_str)
+    }
+
+    /// Declared element type for collection nodes
+    pub fn element_type(&self) -> Option<crate::types::ValueType> {
+        self.properties
+            .get("element_type")
+            .and_then(|name| crate::types::ValueType::from_name(name))
+    }
+}
+
 /// Graph IR connection
 #[derive(Debug, Clone)]
 pub struct GraphIRConnection {
@@ -23,6 +49,48 @@ pub struct GraphIRConnection {
     pub data_type: String,
 }
 
+impl GraphIRConnection {
+    /// `node:port` endpoint in IR connections
+    pub fn endpoint(node: &str, port: &str) -> String {
+        format!("{}:{}", node, port)
+    }
+
+    /// Source node and port
+    pub fn source_port(&self) -> (&str, &str) {
+        split_endpoint(&self.source)
+    }
+
+    /// Target node and port
+    pub fn target_port(&self) -> (&str, &str) {
+        split_endpoint(&self.target)
+    }
+
+    pub fn is_flow(&self) -> bool {
+        self.data_type == FLOW_DATA_TYPE
+    }
+}
+
+fn split_endpoint(endpoint: &str) -> (&str, &str) {
+    endpoint.split_once(':').unwrap_or((endpoint, ""))
+}
+
+/// Name of a value type, as accepted by [`ValueType::from_name`]
+fn type_name(value_type: &ValueType) -> String {
+    match value_type {
+        ValueType::Boolean => "boolean".to_string(),
+        ValueType::Integer => "integer".to_string(),
+        ValueType::Float => "float".to_string(),
+        ValueType::String => "string".to_string(),
+        ValueType::Bytes => "bytes".to_string(),
+        ValueType::Array(inner) => format!("array<{}>", type_name(inner)),
+        ValueType::Object(_) => "object".to_string(),
+        ValueType::Map(inner) => format!("map<{}>", type_name(inner)),
+        ValueType::Decimal(scale) => format!("decimal<{}>", scale),
+        ValueType::Flow => FLOW_DATA_TYPE.to_string(),
+        ValueType::Any => "any".to_string(),
+    }
+}
+
 /// Graph IR representation
 #[derive(Debug, Clone)]
 pub struct GraphIR {
@@ -37,4 +105,83 @@ impl GraphIR {
             connections: Vec::new(),
         }
     }
+
+    /// Lower a visual graph, failing on connections to missing nodes or ports.
+    ///
+    /// A node that doesn't list a port of its type's definition still gets
+    /// connections to it, typed by the definition.
+    pub fn from_graph(graph: &VisualGraph) -> CanvasResult<Self> {
+        let definitions: HashMap<String, NodeDefinition> = builtin_node_definitions()
+            .into_iter()
+            .map(|definition| (definition.id.clone(), definition))
+            .collect();
+        let mut ir = Self::new();
+        for node in &graph.nodes {
+            let properties = node
+                .properties
+                .iter()
+                .map(|(name, value)| {
+                    let text = match value {
+                        serde_json::Value::String(text) => text.clone(),
+                        other => other.to_string(),
+                    };
+                    (name.clone(), text)
+                })
+                .collect::<HashMap<_, _>>();
+            ir.nodes.push(GraphIRNode {
+                id: node.id.to_string(),
+                node_type: node.node_type.clone(),
+                inputs: node.inputs.iter().map(|p| p.id.clone()).collect(),
+                outputs: node.outputs.iter().map(|p| p.id.clone()).collect(),
+                properties,
+            });
+        }
+        for connection in &graph.connections {
+            let source = graph.get_node(connection.source_node).ok_or_else(|| {
+                CanvasError::Compilation(format!(
+                    "Connection {} starts at missing node {}",
+                    connection.id, connection.source_node
+                ))
+            })?;
+            if graph.get_node(connection.target_node).is_none() {
+                return Err(CanvasError::Compilation(format!(
+                    "Connection {} ends at missing node {}",
+                    connection.id, connection.target_node
+                )));
+            }
+            let declared = definitions.get(&source.node_type).map(|d| d.outputs.as_slice()).unwrap_or_default();
+            let port = source
+                .outputs
+                .iter()
+                .chain(declared)
+                .find(|p| p.id == connection.source_port)
+                .ok_or_else(|| {
+                    CanvasError::Compilation(format!(
+                        "Connection {} starts at missing port '{}' of node {}",
+                        connection.id, connection.source_port, source.id
+                    ))
+                })?;
+            ir.connections.push(GraphIRConnection {
+                id: connection.id.to_string(),
+                source: GraphIRConnection::endpoint(&source.id.to_string(), &connection.source_port),
+                target: GraphIRConnection::endpoint(&connection.target_node.to_string(), &connection.target_port),
+                data_type: type_name(&port.value_type),
+            });
+        }
+        Ok(ir)
+    }
+
+    pub fn node(&self, id: &str) -> Option<&GraphIRNode> {
+        self.nodes.iter().find(|n| n.id == id)
+    }
+
+    /// Connections arriving at a node's input port
+    pub fn incoming<'a>(&'a self, node: &'a str, port: &'a str) -> impl Iterator<Item = &'a GraphIRConnection> {
+        self.connections.iter().filter(move |c| c.target_port() == (node, port))
+    }
+
+    /// Connections leaving a node's output port
+    pub fn outgoing<'a>(&'a self, node: &'a str, port: &'a str) -> impl Iterator<Item = &'a GraphIRConnection> {
+        self.connections.iter().filter(move |c| c.source_port() == (node, port))
+    }
 } 
\ No newline at end of file
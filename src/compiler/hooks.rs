@@ -0,0 +1,222 @@
+//! Compiler plugin hooks
+//!
+//! Third-party passes plug into three fixed points of the pipeline: after the
+//! Graph IR is built, before code generation (on the AST) and after code
+//! generation (on the WASM output). Passes run in registration order and a
+//! failing pass aborts the compilation with the plugin and pass named in the
+//! error.
+
+use std::fmt;
+
+use super::{ast::AST, graph_ir::GraphIR, wasm_gen::WasmGenResult};
+use crate::error::{CanvasError, CanvasResult};
+
+/// Version of the hook interface; bumped on any breaking change to the
+/// structures passes receive
+pub const HOOK_API_VERSION: u32 = 1;
+
+/// Point in the pipeline where a pass runs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HookPoint {
+    /// After the visual graph is lowered to Graph IR
+    AfterIr,
+    /// After the AST is built, before WASM is generated from it
+    BeforeCodegen,
+    /// After WASM is generated
+    AfterCodegen,
+}
+
+impl fmt::Display for HookPoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            HookPoint::AfterIr => "after IR construction",
+            HookPoint::BeforeCodegen => "before code generation",
+            HookPoint::AfterCodegen => "after code generation",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// A transformation pass contributed by a plugin.
+///
+/// Implement the `transform_*` method for each point listed in `hook_points`;
+/// the others are never called.
+pub trait CompilerPass: Send + Sync {
+    /// Pass name, for logs and errors
+    fn name(&self) -> &str;
+
+    /// Points this pass runs at
+    fn hook_points(&self) -> Vec<HookPoint>;
+
+    /// Transform the Graph IR
+    fn transform_ir(&self, _ir: &mut GraphIR) -> CanvasResult<()> {
+        Ok(())
+    }
+
+    /// Transform the AST before code generation
+    fn transform_ast(&self, _ast: &mut AST) -> CanvasResult<()> {
+        Ok(())
+    }
+
+    /// Transform the generated WASM
+    fn transform_wasm(&self, _wasm: &mut WasmGenResult) -> CanvasResult<()> {
+        Ok(())
+    }
+}
+
+struct RegisteredPass {
+    plugin: String,
+    pass: Box<dyn CompilerPass>,
+}
+
+/// Passes registered with a compiler
+#[derive(Default)]
+pub struct CompilerHooks {
+    passes: Vec<RegisteredPass>,
+}
+
+impl CompilerHooks {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a pass on behalf of a plugin
+    pub fn register(&mut self, plugin: &str, pass: Box<dyn CompilerPass>) -> CanvasResult<()> {
+        if pass.hook_points().is_empty() {
+            return Err(CanvasError::Validation(format!(
+                "Pass '{}' of plugin '{}' does not declare any hook point",
+                pass.name(),
+                plugin
+            )));
+        }
+        if self.passes.iter().any(|p| p.plugin == plugin && p.pass.name() == pass.name()) {
+            return Err(CanvasError::Validation(format!(
+                "Plugin '{}' already registered a pass named '{}'",
+                plugin,
+                pass.name()
+            )));
+        }
+        self.passes.push(RegisteredPass {
+            plugin: plugin.to_string(),
+            pass,
+        });
+        Ok(())
+    }
+
+    /// Remove every pass a plugin registered, returning how many were removed
+    pub fn unregister_plugin(&mut self, plugin: &str) -> usize {
+        let before = self.passes.len();
+        self.passes.retain(|p| p.plugin != plugin);
+        before - self.passes.len()
+    }
+
+    /// `(plugin, pass)` names of the passes running at a point, in order
+    pub fn passes_at(&self, point: HookPoint) -> Vec<(&str, &str)> {
+        self.at(point).map(|p| (p.plugin.as_str(), p.pass.name())).collect()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.passes.is_empty()
+    }
+
+    fn at(&self, point: HookPoint) -> impl Iterator<Item = &RegisteredPass> {
+        self.passes.iter().filter(move |p| p.pass.hook_points().contains(&point))
+    }
+
+    fn run<T>(
+        &self,
+        point: HookPoint,
+        target: &mut T,
+        apply: impl Fn(&dyn CompilerPass, &mut T) -> CanvasResult<()>,
+    ) -> CanvasResult<()> {
+        for registered in self.at(point) {
+            log::debug!("Running pass '{}' of plugin '{}' {}", registered.pass.name(), registered.plugin, point);
+            apply(registered.pass.as_ref(), target).map_err(|e| {
+                CanvasError::Compilation(format!(
+                    "Pass '{}' of plugin '{}' failed {}: {}",
+                    registered.pass.name(),
+                    registered.plugin,
+                    point,
+                    e
+                ))
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Run the passes hooked after IR construction
+    pub fn run_after_ir(&self, ir: &mut GraphIR) -> CanvasResult<()> {
+        self.run(HookPoint::AfterIr, ir, |pass, ir| pass.transform_ir(ir))
+    }
+
+    /// Run the passes hooked before code generation
+    pub fn run_before_codegen(&self, ast: &mut AST) -> CanvasResult<()> {
+        self.run(HookPoint::BeforeCodegen, ast, |pass, ast| pass.transform_ast(ast))
+    }
+
+    /// Run the passes hooked after code generation
+    pub fn run_after_codegen(&self, wasm: &mut WasmGenResult) -> CanvasResult<()> {
+        self.run(HookPoint::AfterCodegen, wasm, |pass, wasm| pass.transform_wasm(wasm))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::graph_ir::GraphIRNode;
+
+    /// Appends a node to the IR and an export name to the WASM output
+    struct Tagger(&'static str);
+
+    impl CompilerPass for Tagger {
+        fn name(&self) -> &str {
+            self.0
+        }
+
+        fn hook_points(&self) -> Vec<HookPoint> {
+            vec![HookPoint::AfterIr, HookPoint::AfterCodegen]
+        }
+
+        fn transform_ir(&self, ir: &mut GraphIR) -> CanvasResult<()> {
+            ir.nodes.push(GraphIRNode {
+                id: self.0.to_string(),
+                node_type: "Probe".to_string(),
+                inputs: Vec::new(),
+                outputs: Vec::new(),
+                properties: Default::default(),
+            });
+            Ok(())
+        }
+
+        fn transform_wasm(&self, _wasm: &mut WasmGenResult) -> CanvasResult<()> {
+            Err(CanvasError::Validation("export table full".to_string()))
+        }
+    }
+
+    #[test]
+    fn test_passes_run_in_order_and_failures_name_the_plugin() {
+        let mut hooks = CompilerHooks::new();
+        hooks.register("probes", Box::new(Tagger("first"))).unwrap();
+        hooks.register("probes", Box::new(Tagger("second"))).unwrap();
+        assert!(hooks.register("probes", Box::new(Tagger("first"))).is_err());
+        assert!(hooks.passes_at(HookPoint::BeforeCodegen).is_empty());
+
+        let mut ir = GraphIR::new();
+        hooks.run_after_ir(&mut ir).unwrap();
+        let ids: Vec<&str> = ir.nodes.iter().map(|n| n.id.as_str()).collect();
+        assert_eq!(ids, vec!["first", "second"]);
+
+        let mut wasm = WasmGenResult {
+            wasm_bytes: Vec::new(),
+            functions: Vec::new(),
+            imports: Vec::new(),
+            exports: Vec::new(),
+            instruction_counts: Default::default(),
+        };
+        let error = hooks.run_after_codegen(&mut wasm).unwrap_err().to_string();
+        assert!(error.contains("'first' of plugin 'probes' failed after code generation"));
+
+        assert_eq!(hooks.unregister_plugin("probes"), 2);
+        assert!(hooks.is_empty());
+    }
+}
@@ -0,0 +1,300 @@
+//! Inter-graph imports
+//!
+//! An `Import` node stands in for another graph of the same workspace. It is
+//! resolved at compile time by inlining the imported graph: the imported
+//! graph's Start node output ports become the Import node's inputs, and the
+//! input ports of its End node become the Import node's outputs.
+//!
+//! Imports reference graphs by their `VisualGraph::id`, which survives renames
+//! and moves; the optional `path` property is only a hint for humans and error
+//! messages. Inlined nodes get IDs derived from the Import node's ID and their
+//! original ID, so recompiling produces the same node IDs every time.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use uuid::Uuid;
+
+use crate::{
+    error::{CanvasError, CanvasResult},
+    types::{Connection, NodeId, VisualGraph, VisualNode},
+};
+
+/// Extension of graph files picked up when scanning a workspace
+pub const GRAPH_FILE_EXTENSION: &str = "json";
+
+/// Graphs available for import, keyed by graph ID
+#[derive(Debug, Clone, Default)]
+pub struct Workspace {
+    graphs: HashMap<Uuid, (PathBuf, VisualGraph)>,
+}
+
+impl Workspace {
+    /// Create an empty workspace
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Load every graph file in a directory tree.
+    ///
+    /// Files that are not visual graphs (ABIs, configs) are skipped.
+    pub fn load(root: &Path) -> CanvasResult<Self> {
+        let mut workspace = Self::new();
+        let mut pending = vec![root.to_path_buf()];
+        while let Some(dir) = pending.pop() {
+            for entry in std::fs::read_dir(&dir)? {
+                let path = entry?.path();
+                if path.is_dir() {
+                    pending.push(path);
+                    continue;
+                }
+                if path.extension().and_then(|e| e.to_str()) != Some(GRAPH_FILE_EXTENSION) {
+                    continue;
+                }
+                let content = std::fs::read_to_string(&path)?;
+                if let Ok(graph) = serde_json::from_str::<VisualGraph>(&content) {
+                    let relative = path.strip_prefix(root).unwrap_or(&path).to_path_buf();
+                    workspace.add_graph(relative, graph)?;
+                }
+            }
+        }
+        Ok(workspace)
+    }
+
+    /// Add a graph under a workspace-relative path
+    pub fn add_graph(&mut self, path: impl Into<PathBuf>, graph: VisualGraph) -> CanvasResult<()> {
+        let path = path.into();
+        if let Some((existing, _)) = self.graphs.get(&graph.id) {
+            return Err(CanvasError::Compilation(format!(
+                "Graph ID {} is used by both {} and {}",
+                graph.id,
+                existing.display(),
+                path.display()
+            )));
+        }
+        self.graphs.insert(graph.id, (path, graph));
+        Ok(())
+    }
+
+    /// Look up a graph by ID
+    pub fn get(&self, id: &Uuid) -> Option<&VisualGraph> {
+        self.graphs.get(id).map(|(_, graph)| graph)
+    }
+
+    /// Every graph with its workspace-relative path, sorted by path
+    pub fn graphs(&self) -> Vec<(&Path, &VisualGraph)> {
+        let mut graphs: Vec<_> = self.graphs.values().map(|(path, graph)| (path.as_path(), graph)).collect();
+        graphs.sort_by(|a, b| a.0.cmp(b.0));
+        graphs
+    }
+
+    /// Workspace-relative path of a graph
+    pub fn path_of(&self, id: &Uuid) -> Option<&Path> {
+        self.graphs.get(id).map(|(path, _)| path.as_path())
+    }
+
+    fn describe(&self, id: &Uuid) -> String {
+        match self.path_of(id) {
+            Some(path) => path.display().to_string(),
+            None => id.to_string(),
+        }
+    }
+}
+
+/// Graph ID referenced by an Import node
+pub fn import_target(node: &VisualNode) -> CanvasResult<Uuid> {
+    node.properties
+        .get("graph_id")
+        .and_then(|v| v.as_str())
+        .and_then(|id| Uuid::parse_str(id).ok())
+        .ok_or_else(|| CanvasError::Compilation(format!("Import node {} needs a valid 'graph_id' property", node.id)))
+}
+
+/// Stable ID of a node inlined through an Import node
+pub fn inlined_node_id(import_node: NodeId, original: NodeId) -> NodeId {
+    Uuid::new_v5(&import_node, original.as_bytes())
+}
+
+/// Inline every Import node, recursively, producing a self-contained graph
+pub fn resolve_imports(graph: &VisualGraph, workspace: &Workspace) -> CanvasResult<VisualGraph> {
+    resolve(graph, workspace, &mut vec![graph.id])
+}
+
+fn resolve(graph: &VisualGraph, workspace: &Workspace, stack: &mut Vec<Uuid>) -> CanvasResult<VisualGraph> {
+    let mut result = graph.clone();
+
+    let imports: Vec<VisualNode> = graph.nodes.iter().filter(|n| n.node_type == "Import").cloned().collect();
+    for import in imports {
+        let target = import_target(&import)?;
+
+        if let Some(position) = stack.iter().position(|id| *id == target) {
+            let mut chain: Vec<String> = stack[position..].iter().map(|id| workspace.describe(id)).collect();
+            chain.push(workspace.describe(&target));
+            return Err(CanvasError::Compilation(format!("Import cycle: {}", chain.join(" -> "))));
+        }
+
+        let imported = workspace.get(&target).ok_or_else(|| {
+            let hint = import.properties.get("path").and_then(|v| v.as_str()).unwrap_or("unknown path");
+            CanvasError::Compilation(format!(
+                "Import node {} references graph {} ({}) which is not in the workspace",
+                import.id, target, hint
+            ))
+        })?;
+
+        stack.push(target);
+        let imported = resolve(imported, workspace, stack)?;
+        stack.pop();
+
+        inline(&mut result, &import, &imported)?;
+    }
+
+    Ok(result)
+}
+
+/// Replace `import` (an Import or Slot node) in `graph` with the nodes of `imported`
+pub(super) fn inline(graph: &mut VisualGraph, import: &VisualNode, imported: &VisualGraph) -> CanvasResult<()> {
+    let start = imported.nodes.iter().find(|n| n.node_type == "Start").ok_or_else(|| {
+        CanvasError::Compilation(format!("Imported graph '{}' has no Start node", imported.name))
+    })?;
+    let end = imported.nodes.iter().find(|n| n.node_type == "End");
+    let remap = |id: NodeId| inlined_node_id(import.id, id);
+    // Execution flow enters through the Start node's flow_out and leaves through the End node's flow_in
+    let start_port = |port: &str| if port == "flow_in" { "flow_out".to_string() } else { port.to_string() };
+    let end_port = |port: &str| if port == "flow_out" { "flow_in".to_string() } else { port.to_string() };
+
+    // Copy the imported body, minus its boundary nodes
+    for node in &imported.nodes {
+        if node.id == start.id || Some(node.id) == end.map(|e| e.id) {
+            continue;
+        }
+        let mut copy = node.clone();
+        copy.id = remap(node.id);
+        copy.metadata.insert("imported_from".to_string(), imported.id.to_string());
+        graph.nodes.push(copy);
+    }
+
+    let mut connections = Vec::new();
+    for connection in graph.connections.drain(..) {
+        if connection.target_node == import.id {
+            // Values entering the Import node go wherever the imported Start port went
+            for inner in imported
+                .connections
+                .iter()
+                .filter(|c| c.source_node == start.id && c.source_port == start_port(&connection.target_port))
+            {
+                let mut rewired = connection.clone();
+                rewired.id = Uuid::new_v5(&connection.id, inner.id.as_bytes());
+                rewired.target_node = remap(inner.target_node);
+                rewired.target_port = inner.target_port.clone();
+                connections.push(rewired);
+            }
+        } else if connection.source_node == import.id {
+            // Values leaving the Import node come from whatever fed the imported End port
+            let end = end.ok_or_else(|| {
+                CanvasError::Compilation(format!(
+                    "Imported graph '{}' has no End node to take '{}' from",
+                    imported.name, connection.source_port
+                ))
+            })?;
+            for inner in imported
+                .connections
+                .iter()
+                .filter(|c| c.target_node == end.id && c.target_port == end_port(&connection.source_port))
+            {
+                let mut rewired = connection.clone();
+                rewired.id = Uuid::new_v5(&connection.id, inner.id.as_bytes());
+                rewired.source_node = remap(inner.source_node);
+                rewired.source_port = inner.source_port.clone();
+                connections.push(rewired);
+            }
+        } else {
+            connections.push(connection);
+        }
+    }
+
+    // Connections inside the imported body
+    for inner in &imported.connections {
+        let boundary = inner.source_node == start.id || Some(inner.target_node) == end.map(|e| e.id);
+        if boundary {
+            continue;
+        }
+        let mut copy: Connection = inner.clone();
+        copy.id = Uuid::new_v5(&import.id, inner.id.as_bytes());
+        copy.source_node = remap(inner.source_node);
+        copy.target_node = remap(inner.target_node);
+        connections.push(copy);
+    }
+
+    graph.connections = connections;
+    graph.nodes.retain(|n| n.id != import.id);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_graphs::{connect, node};
+
+    fn import_of(graph: &VisualGraph) -> VisualNode {
+        node("Import").with_property("graph_id", serde_json::json!(graph.id.to_string()))
+    }
+
+    /// Library graph computing `x + x`
+    fn library() -> VisualGraph {
+        let mut lib = VisualGraph::new("double");
+        let start = node("Start");
+        let add = node("Add");
+        let end = node("End");
+        connect(&mut lib, &start, "x", &add, "a");
+        connect(&mut lib, &start, "x", &add, "b");
+        connect(&mut lib, &add, "result", &end, "doubled");
+        lib.add_node(start);
+        lib.add_node(add);
+        lib.add_node(end);
+        lib
+    }
+
+    #[test]
+    fn test_import_is_inlined_with_stable_ids() {
+        let lib = library();
+        let mut workspace = Workspace::new();
+        workspace.add_graph("lib/double.json", lib.clone()).unwrap();
+
+        let mut main = VisualGraph::new("main");
+        let source = node("ReadStorage");
+        let import = import_of(&lib);
+        let sink = node("WriteStorage");
+        connect(&mut main, &source, "value", &import, "x");
+        connect(&mut main, &import, "doubled", &sink, "value");
+        let import_id = import.id;
+        main.add_node(source);
+        main.add_node(import);
+        main.add_node(sink);
+
+        let first = resolve_imports(&main, &workspace).unwrap();
+        let second = resolve_imports(&main, &workspace).unwrap();
+        assert_eq!(first.nodes.len(), 3);
+        assert!(first.nodes.iter().all(|n| n.node_type != "Import"));
+        assert_eq!(first.connections.len(), 3);
+
+        let first_ids: Vec<NodeId> = first.nodes.iter().map(|n| n.id).collect();
+        let second_ids: Vec<NodeId> = second.nodes.iter().map(|n| n.id).collect();
+        assert_eq!(first_ids, second_ids);
+        assert!(first_ids.contains(&inlined_node_id(import_id, lib.nodes[1].id)));
+    }
+
+    #[test]
+    fn test_import_cycle_is_reported() {
+        let mut a = VisualGraph::new("a");
+        let mut b = VisualGraph::new("b");
+        a.add_node(import_of(&b));
+        b.add_node(import_of(&a));
+
+        let mut workspace = Workspace::new();
+        workspace.add_graph("a.json", a.clone()).unwrap();
+        workspace.add_graph("b.json", b).unwrap();
+
+        let error = resolve_imports(&a, &workspace).unwrap_err().to_string();
+        assert!(error.contains("a.json -> b.json -> a.json"));
+    }
+}
@@ -0,0 +1,202 @@
+//! Tracepoint instrumentation for debug builds
+//!
+//! With `compiler.instrument` enabled and `compiler.debug_info` set, every
+//! node's generated code is wrapped in entry/exit host calls and storage
+//! writes report their key. Each node gets a tracepoint number; the mapping
+//! back to node IDs is stored in the ABI metadata so the debugger and coverage
+//! tools can read a trace without the source graph. Release builds never emit
+//! tracepoints, and [`ensure_stripped`] rejects any that slipped through.
+
+use std::collections::HashSet;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::{CanvasError, CanvasResult},
+    types::{ContractABI, NodeId, TraceEvent, VisualGraph},
+    wasm::host,
+};
+
+use super::wasm_gen::WasmGenResult;
+
+/// ABI metadata key holding the serialized [`TraceMap`]
+pub const TRACE_MAP_METADATA_KEY: &str = "trace_map";
+
+/// Tracepoint number to node ID mapping of an instrumented build
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TraceMap {
+    nodes: Vec<NodeId>,
+}
+
+impl TraceMap {
+    /// Assign tracepoints to the graph's nodes, in graph order
+    pub fn build(graph: &VisualGraph) -> Self {
+        Self {
+            nodes: graph.nodes.iter().map(|n| n.id).collect(),
+        }
+    }
+
+    pub fn tracepoint(&self, node_id: &NodeId) -> Option<u32> {
+        self.nodes.iter().position(|id| id == node_id).map(|i| i as u32)
+    }
+
+    pub fn node(&self, tracepoint: u32) -> Option<NodeId> {
+        self.nodes.get(tracepoint as usize).copied()
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Store the map in an ABI
+    pub fn write_to(&self, abi: &mut ContractABI) -> CanvasResult<()> {
+        abi.metadata.insert(TRACE_MAP_METADATA_KEY.to_string(), serde_json::to_string(&self.nodes)?);
+        Ok(())
+    }
+
+    /// The map of an instrumented contract, or `None` for a release build
+    pub fn read_from(abi: &ContractABI) -> CanvasResult<Option<Self>> {
+        abi.metadata
+            .get(TRACE_MAP_METADATA_KEY)
+            .map(|json| Ok(Self { nodes: serde_json::from_str(json)? }))
+            .transpose()
+    }
+}
+
+/// WAT imports for the tracing host functions
+pub fn trace_imports_wat() -> Vec<String> {
+    vec![
+        format!("(import \"env\" \"{0}\" (func ${0} (param i32)))", host::HOST_TRACE_ENTER),
+        format!("(import \"env\" \"{0}\" (func ${0} (param i32)))", host::HOST_TRACE_EXIT),
+        format!("(import \"env\" \"{0}\" (func ${0} (param i32 i32 i32)))", host::HOST_TRACE_STORAGE_WRITE),
+    ]
+}
+
+/// Wrap a node's generated code in entry and exit tracepoints
+pub fn instrument_node_wat(tracepoint: u32, code: &str) -> String {
+    format!(
+        "(call ${} (i32.const {tp}))\n{}\n(call ${} (i32.const {tp}))",
+        host::HOST_TRACE_ENTER,
+        code,
+        host::HOST_TRACE_EXIT,
+        tp = tracepoint
+    )
+}
+
+/// Tracepoint placed after a storage write whose key is at `key_ptr`
+pub fn storage_write_trace_wat(tracepoint: u32, key_ptr: u32, key_len: u32) -> String {
+    format!(
+        "(call ${} (i32.const {}) (i32.const {}) (i32.const {}))",
+        host::HOST_TRACE_STORAGE_WRITE,
+        tracepoint,
+        key_ptr,
+        key_len
+    )
+}
+
+/// Whether generated code imports any tracing host function
+pub fn is_instrumented(wasm: &WasmGenResult) -> bool {
+    let trace = host::trace_host_functions();
+    wasm.imports.iter().any(|import| trace.contains(&import.as_str()))
+}
+
+/// Fail a release build that still references tracing host functions
+pub fn ensure_stripped(wasm: &WasmGenResult) -> CanvasResult<()> {
+    if is_instrumented(wasm) {
+        return Err(CanvasError::Compilation(
+            "Release build imports tracing host functions; instrumentation must be disabled".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Node coverage of one or more instrumented runs
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TraceCoverage {
+    pub covered: Vec<NodeId>,
+    pub uncovered: Vec<NodeId>,
+}
+
+impl TraceCoverage {
+    /// Collect the nodes entered in `events`
+    pub fn from_trace(map: &TraceMap, events: &[TraceEvent]) -> Self {
+        let hit: HashSet<u32> = events
+            .iter()
+            .filter(|e| matches!(e, TraceEvent::Enter { .. }))
+            .map(|e| e.tracepoint())
+            .collect();
+        let (covered, uncovered) = (0..map.len() as u32)
+            .filter_map(|tp| map.node(tp).map(|id| (tp, id)))
+            .partition::<Vec<_>, _>(|(tp, _)| hit.contains(tp));
+        Self {
+            covered: covered.into_iter().map(|(_, id)| id).collect(),
+            uncovered: uncovered.into_iter().map(|(_, id)| id).collect(),
+        }
+    }
+
+    /// Percentage of nodes entered at least once
+    pub fn percent(&self) -> f64 {
+        let total = self.covered.len() + self.uncovered.len();
+        if total == 0 {
+            return 100.0;
+        }
+        self.covered.len() as f64 * 100.0 / total as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{Position, VisualNode};
+    use uuid::Uuid;
+
+    #[test]
+    fn test_trace_map_round_trips_through_abi_and_drives_coverage() {
+        let mut graph = VisualGraph::new("traced");
+        for node_type in ["Start", "WriteStorage", "End"] {
+            graph.add_node(VisualNode::new(Uuid::new_v4(), node_type, Position::new(0.0, 0.0)));
+        }
+        let map = TraceMap::build(&graph);
+
+        let mut abi = ContractABI {
+            functions: Vec::new(),
+            events: Vec::new(),
+            errors: Vec::new(),
+            storage: Vec::new(),
+            metadata: Default::default(),
+        };
+        assert_eq!(TraceMap::read_from(&abi).unwrap(), None);
+        map.write_to(&mut abi).unwrap();
+        assert_eq!(TraceMap::read_from(&abi).unwrap(), Some(map.clone()));
+
+        let events = vec![
+            TraceEvent::Enter { tracepoint: 0, gas_used: 0 },
+            TraceEvent::Exit { tracepoint: 0, gas_used: 3 },
+            TraceEvent::Enter { tracepoint: 1, gas_used: 3 },
+            TraceEvent::StorageWrite { tracepoint: 1, key: "count".to_string() },
+        ];
+        let coverage = TraceCoverage::from_trace(&map, &events);
+        assert_eq!(coverage.uncovered, vec![graph.nodes[2].id]);
+        assert!((coverage.percent() - 200.0 / 3.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_release_builds_must_be_stripped() {
+        let mut wasm = WasmGenResult {
+            wasm_bytes: Vec::new(),
+            functions: Vec::new(),
+            imports: vec![host::HOST_WRITE_STORAGE.to_string()],
+            exports: Vec::new(),
+            instruction_counts: Default::default(),
+        };
+        assert!(ensure_stripped(&wasm).is_ok());
+
+        wasm.imports.push(host::HOST_TRACE_ENTER.to_string());
+        assert!(ensure_stripped(&wasm).is_err());
+        assert!(instrument_node_wat(4, "nop").starts_with("(call $baals_trace_enter (i32.const 4))"));
+    }
+}
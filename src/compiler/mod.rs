@@ -4,18 +4,92 @@ mod graph_ir;
 mod ast;
 mod wasm_gen;
 mod validator;
+mod storage_batching;
+mod gas;
+mod safe_math;
+mod units;
+mod pausable;
+mod constructor;
+mod entry_points;
+mod imports;
+mod hooks;
+mod instrumentation;
+mod storage_cost;
+mod storage_abi;
+mod abi_diff;
+mod call_graph;
+mod search;
+mod rename;
+mod slots;
+mod wit;
+mod playground;
+mod diagnostics_cache;
+mod stack_depth;
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+};
 
 use crate::{
     config::Config,
     error::{CanvasError, CanvasResult},
-    types::{CompilationResult, VisualGraph},
+    progress::{self, Progress},
+    types::{
+        CompilationResult, ContractABI, ErrorABI, EventABI, FunctionGasEstimate, ParameterABI, ValueType, VisualGraph,
+    },
 };
 
 pub use validator::Validator;
+pub use diagnostics_cache::{node_content_hash, CacheStats, DiagnosticsCache};
+pub use ast::{ASTNode, CatchHandler, AST};
+pub use graph_ir::{GraphIR, GraphIRConnection, GraphIRNode, FLOW_DATA_TYPE};
+pub use wasm_gen::{WasmGenResult, WasmGenerator};
+pub use hooks::{CompilerHooks, CompilerPass, HookPoint, HOOK_API_VERSION};
+pub use storage_batching::{coalesce_storage_ops, StorageBatchingReport};
+pub use gas::{count_instructions, estimate_graph_gas, node_gas_bound, GasBound, GasModel, GasReport};
+pub use units::{check_units, input_unit, output_unit};
+pub use constructor::{
+    add_constructor, constructor_abi, constructor_params, find_constructor, find_init_node, init_param_types,
+    validate_constructor_args, INIT_FUNCTION,
+};
+pub use entry_points::{
+    add_entry_points, collect_entry_points, dispatch_wat, find_function, function_selector,
+    validate_call_args, EntryPoint, DEFAULT_ENTRY_POINT, DISPATCH_EXPORT,
+};
+pub use instrumentation::{
+    ensure_stripped, instrument_node_wat, is_instrumented, storage_write_trace_wat, trace_imports_wat, TraceCoverage,
+    TraceMap, TRACE_MAP_METADATA_KEY,
+};
+pub use imports::{import_target, inlined_node_id, resolve_imports, Workspace};
+pub use pausable::{
+    inject_pausable_abi, is_pausable, is_paused, pausable_wat, pause_guard_call, PAUSED_FUNCTION,
+    PAUSED_STORAGE_KEY, PAUSE_FUNCTION, UNPAUSE_FUNCTION,
+};
+pub use storage_cost::{
+    estimate_storage_cost, StorageCostProjection, StorageCostReport, StorageSlot, DEFAULT_VALUE_SIZE,
+};
+pub use storage_abi::{add_storage_schema, storage_schema};
+pub use abi_diff::{diff_abi, AbiChange, AbiDiff, AbiItemKind, Compatibility};
+pub use stack_depth::{analyze_stack_depth, Recursion, RecursionKind, StackDepthReport};
+pub use call_graph::{CallEdge, CallGraph, CallKind, CallTarget, ADDRESS_METADATA_KEY};
+pub use rename::{plan_rename, RenameEdit, RenameKind, RenamePlan, ABI_EXTENSION};
+pub use slots::{fill_slots, set_slot_fill, template_slots, SlotSignature, SLOT_NODE_TYPE};
+pub use search::{
+    search_graph, search_workspace, Pattern, SearchHit, SearchQuery, BATCH_STORAGE_NODE_TYPES, STORAGE_NODE_TYPES,
+};
+pub use playground::{generate_playground, PlaygroundOptions, DEFAULT_PLAYGROUND_ENDPOINT};
+pub use wit::{encode_component, generate_wit, wit_name, wit_type, UNTYPED_REVERT_CASE, WIT_NAMESPACE};
+pub use safe_math::{
+    lower_arithmetic, lower_decimal_arithmetic, overflow_metadata, overflow_mode_code, overflow_mode_from_code,
+    resolve_overflow_mode,
+};
 
 /// Main compiler for converting visual graphs to WASM
 pub struct Compiler {
     config: Config,
+    hooks: CompilerHooks,
+    progress: Arc<dyn Progress>,
 }
 
 impl Compiler {
@@ -23,25 +97,372 @@ impl Compiler {
     pub fn new(config: &Config) -> CanvasResult<Self> {
         Ok(Self {
             config: config.clone(),
+            hooks: CompilerHooks::new(),
+            progress: progress::quiet(),
         })
     }
 
+    /// Report the stages of each compilation to `progress`
+    pub fn with_progress(mut self, progress: Arc<dyn Progress>) -> Self {
+        self.progress = progress;
+        self
+    }
+
+    /// Plugin passes run during compilation
+    pub fn hooks(&self) -> &CompilerHooks {
+        &self.hooks
+    }
+
+    /// Register a plugin pass
+    pub fn register_pass(&mut self, plugin: &str, pass: Box<dyn CompilerPass>) -> CanvasResult<()> {
+        self.hooks.register(plugin, pass)
+    }
+
+    /// Remove the passes a plugin registered
+    pub fn unregister_passes(&mut self, plugin: &str) -> usize {
+        self.hooks.unregister_plugin(plugin)
+    }
+
     /// Compile a visual graph to WASM
     pub fn compile(&self, graph: &VisualGraph) -> CanvasResult<CompilationResult> {
-        // TODO: Implement full compilation pipeline
-        // 1. Convert visual graph to Graph IR
-        // 2. Generate AST from Graph IR
-        // 3. Generate WASM from AST
-        // 4. Generate ABI
-        
-        // For now, return a stub implementation
-        Err(CanvasError::Compilation("Compilation pipeline not yet implemented".to_string()))
+        self.progress.stage("optimize", graph.nodes.len() as u64);
+        let graph = self.batch_storage_ops(graph);
+        self.progress.advance(graph.nodes.len() as u64);
+        self.progress.finish();
+
+        self.progress.stage("graph IR", graph.nodes.len() as u64);
+        let ir = GraphIR::from_graph(&graph).and_then(|mut ir| {
+            self.hooks.run_after_ir(&mut ir)?;
+            Ok(ir)
+        });
+        self.progress.finish();
+        let ir = ir?;
+
+        let entries = self.entry_points(&graph)?;
+        let trace_map = self.trace_map(&graph);
+        self.progress.stage("AST", entries.len() as u64);
+        let ast = AST::from_ir(&ir, &entries, self.config.compiler.overflow_mode, trace_map.as_ref()).and_then(|mut ast| {
+            // Contracts without an Init node still export the constructor their ABI declares
+            if !entries.iter().any(|e| e.function.name == INIT_FUNCTION) {
+                ast.nodes.push(ASTNode::Function {
+                    name: INIT_FUNCTION.to_string(),
+                    params: Vec::new(),
+                    body: Vec::new(),
+                });
+            }
+            self.hooks.run_before_codegen(&mut ast)?;
+            Ok(ast)
+        });
+        self.progress.finish();
+        let ast = ast?;
+
+        self.progress.stage("codegen", ast.nodes.len() as u64);
+        let wasm = self.generate_wasm(&ast, &entries, trace_map.is_some());
+        self.progress.finish();
+        let wasm = wasm?;
+        log::info!(
+            "Generated {} bytes of WASM exporting {}",
+            wasm.wasm_bytes.len(),
+            wasm.exports.join(", ")
+        );
+
+        self.progress.stage("ABI", 0);
+        let abi = self.build_abi(&graph);
+        self.progress.finish();
+        let mut abi = abi?;
+
+        let gas = estimate_graph_gas(&graph);
+        let mut warnings: Vec<String> = gas
+            .unbounded
+            .iter()
+            .map(|(node, reason)| format!("Gas of node {} is unbounded: {}", node, reason))
+            .collect();
+        let breakdown = if self.config.compiler.gas_estimation {
+            self.estimate_function_gas(&graph, &entries, &wasm.instruction_counts)
+        } else {
+            Vec::new()
+        };
+        for estimate in &breakdown {
+            if !estimate.is_bounded() {
+                warnings.push(format!(
+                    "Gas estimate of {} leaves out {} unbounded node(s)",
+                    estimate.function,
+                    estimate.unbounded.len()
+                ));
+            }
+            if let Some(function) = abi.functions.iter_mut().find(|f| f.name == estimate.function) {
+                function.gas_estimate.get_or_insert(estimate.worst_case);
+            }
+        }
+        let mut result = CompilationResult {
+            wasm_bytes: wasm.wasm_bytes,
+            abi,
+            gas_estimate: breakdown.iter().map(|e| e.worst_case).max().unwrap_or(gas.total),
+            gas_breakdown: breakdown,
+            warnings,
+            metadata: HashMap::from([
+                ("exports".to_string(), wasm.exports.join(",")),
+                ("imports".to_string(), wasm.imports.join(",")),
+            ]),
+        };
+        let (report, _) = self.storage_cost(&graph);
+        report.annotate(&self.config.baals.network, &mut result);
+        Ok(result)
+    }
+
+    /// Worst- and average-case gas of each entry point under the configured
+    /// gas model, given the instructions generated for each function
+    pub fn estimate_function_gas(
+        &self,
+        graph: &VisualGraph,
+        entries: &[EntryPoint],
+        instruction_counts: &HashMap<String, BTreeMap<String, u64>>,
+    ) -> Vec<FunctionGasEstimate> {
+        let model = GasModel::new(&self.config.compiler.gas_model);
+        let none = BTreeMap::new();
+        entries
+            .iter()
+            .map(|entry| {
+                let name = &entry.function.name;
+                let instructions = instruction_counts.get(name).unwrap_or(&none);
+                model.estimate_function(graph, name, entry.node_id, instructions)
+            })
+            .collect()
+    }
+
+    /// Entry points of a graph: one per Start node, plus the constructor if it has an Init node
+    fn entry_points(&self, graph: &VisualGraph) -> CanvasResult<Vec<EntryPoint>> {
+        let mut entries = collect_entry_points(graph)?;
+        if let Some(init) = find_init_node(graph)? {
+            entries.push(EntryPoint {
+                node_id: init.id,
+                function: constructor_abi(graph)?,
+                selector: function_selector(INIT_FUNCTION),
+            });
+        }
+        Ok(entries)
+    }
+
+    /// Generate WASM from the AST and run the codegen hooks; release builds
+    /// must come out without tracepoints
+    fn generate_wasm(&self, ast: &AST, entries: &[EntryPoint], instrumented: bool) -> CanvasResult<WasmGenResult> {
+        let mut generator = WasmGenerator::new(self.config.compiler.optimization_level);
+        if let Some(init) = entries.iter().find(|e| e.function.name == INIT_FUNCTION) {
+            generator = generator.with_constructor(init.function.clone());
+        }
+        if self.config.compiler.pausable {
+            let guarded = entries
+                .iter()
+                .filter(|e| e.function.name != INIT_FUNCTION && pausable::is_state_mutating(&e.function))
+                .map(|e| e.function.name.clone())
+                .collect();
+            generator = generator.with_pausable(guarded);
+        }
+        let mut wasm = generator.generate(ast).map_err(CanvasError::Compilation)?;
+        self.hooks.run_after_codegen(&mut wasm)?;
+        if !instrumented {
+            ensure_stripped(&wasm)?;
+        }
+        Ok(wasm)
+    }
+
+    /// Inline Import nodes using graphs from the workspace
+    pub fn resolve_imports(&self, graph: &VisualGraph, workspace: &Workspace) -> CanvasResult<VisualGraph> {
+        self.progress.stage("imports", 0);
+        let resolved = resolve_imports(graph, workspace);
+        self.progress.finish();
+        let resolved = resolved?;
+        if resolved.nodes.len() != graph.nodes.len() {
+            log::info!("Resolved imports: {} -> {} nodes", graph.nodes.len(), resolved.nodes.len());
+        }
+        Ok(resolved)
+    }
+
+    /// Replace the slots of a template with the workspace graphs filling them
+    pub fn fill_slots(&self, graph: &VisualGraph, workspace: &Workspace) -> CanvasResult<VisualGraph> {
+        let filled = fill_slots(graph, workspace)?;
+        log::info!("Filled {} slot(s)", graph.nodes.iter().filter(|n| n.node_type == SLOT_NODE_TYPE).count());
+        Ok(filled)
+    }
+
+    /// Worst-case stack and call depth of a graph, failing if it may recurse
+    /// without bound or exceeds the configured network's limits
+    pub fn check_stack_depth(
+        &self,
+        graph: &VisualGraph,
+        registry: &crate::nodes::custom::CustomNodeRegistry,
+        workspace: &Workspace,
+    ) -> CanvasResult<StackDepthReport> {
+        self.progress.stage("stack depth", graph.nodes.len() as u64);
+        let report = analyze_stack_depth(graph, registry, workspace);
+        self.progress.finish();
+        let report = report?;
+        log::info!(
+            "Stack depth: {} nested frame(s), {} cross-contract call level(s)",
+            report.nesting_depth,
+            report.call_depth
+        );
+        report.check(&self.config.baals.network)?;
+        Ok(report)
+    }
+
+    /// Check the graph's custom node version pins against the installed nodes
+    pub fn resolve_custom_nodes(
+        &self,
+        graph: &VisualGraph,
+        registry: &crate::nodes::custom::CustomNodeRegistry,
+    ) -> CanvasResult<crate::nodes::custom::VersionResolution> {
+        let resolution = crate::nodes::custom::resolve_node_versions(graph, registry)?;
+        for node_id in &resolution.unpinned {
+            log::warn!("Custom node {} does not pin a version; using the installed one", node_id);
+        }
+        Ok(resolution)
+    }
+
+    /// Whether this build gets tracepoints: instrumentation requested and a debug build
+    pub fn instrumentation_enabled(&self) -> bool {
+        self.config.compiler.instrument && self.config.compiler.debug_info
+    }
+
+    /// Tracepoint assignment for an instrumented build; `None` in release builds
+    pub fn trace_map(&self, graph: &VisualGraph) -> Option<TraceMap> {
+        if !self.instrumentation_enabled() {
+            if self.config.compiler.instrument {
+                log::debug!("Instrumentation requested without debug_info; building without tracepoints");
+            }
+            return None;
+        }
+        Some(TraceMap::build(graph))
+    }
+
+    /// Storage footprint of a graph, priced for the configured network
+    pub fn storage_cost(&self, graph: &VisualGraph) -> (StorageCostReport, StorageCostProjection) {
+        let report = estimate_storage_cost(graph);
+        for (node, reason) in &report.unbounded_growth {
+            log::warn!("Unbounded storage growth at node {}: {}", node, reason);
+        }
+        let projection = report.project(&self.config.baals.network);
+        (report, projection)
+    }
+
+    /// Coalesce adjacent storage nodes into batch host calls when optimizing
+    fn batch_storage_ops(&self, graph: &VisualGraph) -> VisualGraph {
+        if self.config.compiler.optimization_level == 0 {
+            return graph.clone();
+        }
+
+        let (batched, report) = coalesce_storage_ops(graph);
+        if report.batches_created > 0 {
+            log::info!(
+                "Coalesced {} storage nodes into {} batch calls",
+                report.nodes_coalesced,
+                report.batches_created
+            );
+        }
+        batched
+    }
+
+    /// Build the contract ABI for a graph: constructor and entry point
+    /// signatures, events, errors, the storage schema, overflow metadata and
+    /// any enabled contract features
+    pub fn build_abi(&self, graph: &VisualGraph) -> CanvasResult<ContractABI> {
+        let mut abi = ContractABI {
+            functions: Vec::new(),
+            events: Vec::new(),
+            errors: Vec::new(),
+            storage: Vec::new(),
+            metadata: overflow_metadata(graph, self.config.compiler.overflow_mode),
+        };
+        add_constructor(graph, &mut abi)?;
+        add_entry_points(graph, &mut abi)?;
+        add_events_and_errors(graph, &mut abi);
+        add_storage_schema(graph, &mut abi);
+        self.apply_features(&mut abi)?;
+        if let Some(map) = self.trace_map(graph) {
+            map.write_to(&mut abi)?;
+        }
+        Ok(abi)
+    }
+
+    /// WIT world describing a graph's ABI, for component-model hosts and bindings
+    pub fn wit(&self, graph: &VisualGraph) -> CanvasResult<String> {
+        generate_wit(&self.build_abi(graph)?, &graph.name)
+    }
+
+    /// Wrap a compiled contract into a WASM component when `compiler.component` is set
+    pub fn component(&self, graph: &VisualGraph, result: &CompilationResult) -> CanvasResult<Option<(String, Vec<u8>)>> {
+        if !self.config.compiler.component {
+            return Ok(None);
+        }
+        let wit = generate_wit(&result.abi, &graph.name)?;
+        let component = encode_component(&result.wasm_bytes, &wit)?;
+        Ok(Some((wit, component)))
+    }
+
+    /// Apply optional contract features enabled in the compiler config to an ABI
+    pub fn apply_features(&self, abi: &mut ContractABI) -> CanvasResult<()> {
+        if self.config.compiler.pausable {
+            let guarded = inject_pausable_abi(abi)?;
+            log::info!("Pausable: guarding {} state-mutating entry points", guarded.len());
+        }
+        Ok(())
     }
 
     /// Validate a visual graph
     pub fn validate(&self, graph: &VisualGraph) -> CanvasResult<ValidationResult> {
-        let validator = Validator::new(&self.config)?;
-        validator.validate(graph)
+        self.validator()?.validate(graph)
+    }
+
+    /// Validator using this compiler's configuration
+    pub fn validator(&self) -> CanvasResult<Validator> {
+        Validator::new(&self.config)
+    }
+}
+
+/// Add the events of EmitEvent nodes, with their connected fields, and the
+/// errors Require nodes revert with
+fn add_events_and_errors(graph: &VisualGraph, abi: &mut ContractABI) {
+    for node in &graph.nodes {
+        let name = |property: &str| node.properties.get(property).and_then(|v| v.as_str()).map(str::to_string);
+        match node.node_type.as_str() {
+            "EmitEvent" => {
+                let Some(event) = name("event") else { continue };
+                if abi.events.iter().any(|e| e.name == event) {
+                    continue;
+                }
+                let mut inputs: Vec<ParameterABI> = Vec::new();
+                for connection in graph.connections.iter().filter(|c| c.target_node == node.id) {
+                    let value_type = node
+                        .inputs
+                        .iter()
+                        .find(|p| p.id == connection.target_port)
+                        .map_or(ValueType::Any, |p| p.value_type.clone());
+                    if value_type == ValueType::Flow || inputs.iter().any(|p| p.name == connection.target_port) {
+                        continue;
+                    }
+                    inputs.push(ParameterABI {
+                        name: connection.target_port.clone(),
+                        value_type,
+                        indexed: false,
+                    });
+                }
+                abi.events.push(EventABI {
+                    name: event,
+                    inputs,
+                    anonymous: false,
+                });
+            }
+            "Require" => {
+                let Some(error) = name("error") else { continue };
+                if !abi.errors.iter().any(|e| e.name == error) {
+                    abi.errors.push(ErrorABI {
+                        name: error,
+                        inputs: Vec::new(),
+                    });
+                }
+            }
+            _ => {}
+        }
     }
 }
 
@@ -72,4 +493,537 @@ impl ValidationResult {
         self.is_valid = false;
         self
     }
-} 
\ No newline at end of file
+} 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        test_graphs::{connect, node, node_with},
+        types::{ExecutionContext, Port, TraceEvent, VisualNode},
+        wasm::{engine, host, BlockContext},
+    };
+
+    /// main(amount): require amount != 0, store and emit amount * 2, return it
+    fn doubler() -> VisualGraph {
+        let mut graph = VisualGraph::new("doubler");
+        let start = node("Start")
+            .with_outputs(vec![
+                Port::new("flow_out", "Flow Out", ValueType::Flow),
+                Port::new("amount", "Amount", ValueType::Integer),
+            ])
+            .with_property("returns", serde_json::json!("integer"));
+        let is_zero = node("CompareIntegers").with_property("b", serde_json::json!(0));
+        let non_zero = node("Not");
+        let require = node("Require")
+            .with_property("error", serde_json::json!("ZeroAmount"))
+            .with_property("message", serde_json::json!("amount must not be zero"));
+        let double = node("Multiply").with_property("b", serde_json::json!(2));
+        let store = node("WriteStorage").with_property("key", serde_json::json!("total"));
+        let emit = node("EmitEvent").with_property("event", serde_json::json!("Doubled"));
+        let end = node("End").with_inputs(vec![
+            Port::new("flow_in", "Flow In", ValueType::Flow),
+            Port::new("result", "Result", ValueType::Integer),
+        ]);
+
+        connect(&mut graph, &start, "flow_out", &require, "flow_in");
+        connect(&mut graph, &require, "flow_out", &store, "flow_in");
+        connect(&mut graph, &store, "flow_out", &emit, "flow_in");
+        connect(&mut graph, &emit, "flow_out", &end, "flow_in");
+        connect(&mut graph, &start, "amount", &is_zero, "a");
+        connect(&mut graph, &is_zero, "equal", &non_zero, "input");
+        connect(&mut graph, &non_zero, "result", &require, "condition");
+        connect(&mut graph, &start, "amount", &double, "a");
+        connect(&mut graph, &double, "result", &store, "value");
+        connect(&mut graph, &double, "result", &emit, "value");
+        connect(&mut graph, &double, "result", &end, "result");
+        for node in [start, is_zero, non_zero, require, double, store, emit, end] {
+            graph.add_node(node);
+        }
+        graph
+    }
+
+    /// Start and End nodes of main(amount) returning an integer
+    fn start_and_end() -> (VisualNode, VisualNode) {
+        let start = node("Start")
+            .with_outputs(vec![
+                Port::new("flow_out", "Flow Out", ValueType::Flow),
+                Port::new("amount", "Amount", ValueType::Integer),
+            ])
+            .with_property("returns", serde_json::json!("integer"));
+        let end = node("End").with_inputs(vec![
+            Port::new("flow_in", "Flow In", ValueType::Flow),
+            Port::new("result", "Result", ValueType::Integer),
+        ]);
+        (start, end)
+    }
+
+    fn call(result: &CompilationResult, amount: i64, context: &mut ExecutionContext) -> crate::wasm::SimulationResult {
+        let runtime = engine::engine().unwrap();
+        let block = BlockContext::new(1, 0, 1);
+        engine::execute(&runtime, &result.wasm_bytes, "main", &[serde_json::json!(amount)], 100_000, block, context)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_compile_start_logic_end_graph_to_running_wasm() {
+        let result = Compiler::new(&Config::default()).unwrap().compile(&doubler()).unwrap();
+        let functions: Vec<&str> = result.abi.functions.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(functions, vec![INIT_FUNCTION, DEFAULT_ENTRY_POINT]);
+        assert_eq!(result.abi.events[0].name, "Doubled");
+        assert_eq!(result.abi.errors[0].name, "ZeroAmount");
+
+        let mut context = ExecutionContext::new(100_000);
+        let doubled = call(&result, 21, &mut context);
+        assert_eq!(doubled.output["result"], 42);
+        assert_eq!(context.storage["total"], 42);
+        assert_eq!(doubled.events[0].name, "Doubled");
+        assert_eq!(doubled.events[0].data["value"], 42);
+
+        let rejected = call(&result, 0, &mut ExecutionContext::new(100_000));
+        assert_eq!(rejected.revert_reason.unwrap().error, "ZeroAmount");
+
+        let mut config = Config::default();
+        config.compiler.instrument = true;
+        config.compiler.debug_info = true;
+        let instrumented = Compiler::new(&config).unwrap().compile(&doubler()).unwrap();
+        let mut traced = ExecutionContext::new(100_000);
+        call(&instrumented, -4, &mut traced);
+        assert_eq!(traced.storage["total"], -8);
+        assert!(traced.trace.iter().any(|e| matches!(e, TraceEvent::StorageWrite { key, .. } if key == "total")));
+    }
+
+    #[test]
+    fn test_init_takes_constructor_arguments_through_the_engine() {
+        let init_graph = |supply_type: ValueType| {
+            let mut graph = VisualGraph::new("token");
+            let init = node("Init").with_outputs(vec![
+                Port::new("flow_out", "Flow Out", ValueType::Flow),
+                Port::new("supply", "Supply", supply_type),
+            ]);
+            let store = node("WriteStorage").with_property("key", serde_json::json!("supply"));
+            let end = node("End").with_inputs(vec![Port::new("flow_in", "Flow In", ValueType::Flow)]);
+            connect(&mut graph, &init, "flow_out", &store, "flow_in");
+            connect(&mut graph, &init, "supply", &store, "value");
+            connect(&mut graph, &store, "flow_out", &end, "flow_in");
+            for node in [init, store, end] {
+                graph.add_node(node);
+            }
+            graph
+        };
+
+        let result = Compiler::new(&Config::default()).unwrap().compile(&init_graph(ValueType::Integer)).unwrap();
+        let args = validate_constructor_args(
+            find_constructor(&result.abi).unwrap(),
+            &serde_json::json!({"supply": 1000}),
+        )
+        .unwrap();
+        let mut context = ExecutionContext::new(100_000);
+        let deployed = engine::execute(
+            &engine::engine().unwrap(),
+            &result.wasm_bytes,
+            INIT_FUNCTION,
+            &args,
+            100_000,
+            BlockContext::new(1, 0, 1),
+            &mut context,
+        )
+        .unwrap();
+        assert!(!deployed.reverted());
+        assert_eq!(context.storage["supply"], 1000);
+
+        let error = Compiler::new(&Config::default()).unwrap().compile(&init_graph(ValueType::String)).unwrap_err();
+        assert!(error.to_string().contains("'supply'"));
+    }
+
+    #[test]
+    fn test_pausable_contract_pauses_and_unpauses() {
+        let mut config = Config::default();
+        config.compiler.pausable = true;
+        let result = Compiler::new(&config).unwrap().compile(&doubler()).unwrap();
+        let runtime = engine::engine().unwrap();
+        let admin = "0x00000000000000000000000000000000000000aa";
+        let mut context = ExecutionContext::new(1_000_000);
+        let mut run = |function: &str, args: Vec<serde_json::Value>, caller: &str| {
+            let mut request = crate::wasm::SimulationRequest::new(function, args, 100_000);
+            request.set_caller(caller);
+            engine::execute_request(&runtime, &result.wasm_bytes, &request, &mut context).unwrap()
+        };
+
+        assert!(!run(INIT_FUNCTION, vec![], admin).reverted());
+        let stranger = run(PAUSE_FUNCTION, vec![], "0x00000000000000000000000000000000000000bb");
+        assert_eq!(stranger.revert_reason.unwrap().error, "Unauthorized");
+        assert!(!run(PAUSE_FUNCTION, vec![], &admin.to_uppercase().replace("0X", "0x")).reverted());
+        assert_eq!(run(PAUSED_FUNCTION, vec![], admin).output["result"], 1);
+        let paused = run(DEFAULT_ENTRY_POINT, vec![serde_json::json!(21)], admin);
+        assert_eq!(paused.revert_reason.unwrap().error, "Paused");
+
+        assert!(!run(UNPAUSE_FUNCTION, vec![], admin).reverted());
+        assert_eq!(run(DEFAULT_ENTRY_POINT, vec![serde_json::json!(21)], admin).output["result"], 42);
+        assert_eq!(context.storage[pausable::ADMIN_STORAGE_KEY], admin);
+        assert!(!is_paused(&context.storage));
+    }
+
+    #[test]
+    fn test_consecutive_storage_ops_compile_to_batch_calls() {
+        // store(amount): a = amount, b = 7, return a + b read back
+        let mut graph = VisualGraph::new("batched");
+        let start = node("Start")
+            .with_outputs(vec![
+                Port::new("flow_out", "Flow Out", ValueType::Flow),
+                Port::new("amount", "Amount", ValueType::Integer),
+            ])
+            .with_property("returns", serde_json::json!("integer"));
+        let write_a = node("WriteStorage").with_property("key", serde_json::json!("a"));
+        let write_b = node("WriteStorage")
+            .with_property("key", serde_json::json!("b"))
+            .with_property("value", serde_json::json!(7));
+        let read_a = node("ReadStorage").with_property("key", serde_json::json!("a"));
+        let read_b = node("ReadStorage").with_property("key", serde_json::json!("b"));
+        let sum = node("Add");
+        let end = node("End").with_inputs(vec![
+            Port::new("flow_in", "Flow In", ValueType::Flow),
+            Port::new("result", "Result", ValueType::Integer),
+        ]);
+        connect(&mut graph, &start, "flow_out", &write_a, "flow_in");
+        connect(&mut graph, &write_a, "flow_out", &write_b, "flow_in");
+        connect(&mut graph, &write_b, "flow_out", &read_a, "flow_in");
+        connect(&mut graph, &read_a, "flow_out", &read_b, "flow_in");
+        connect(&mut graph, &read_b, "flow_out", &end, "flow_in");
+        connect(&mut graph, &start, "amount", &write_a, "value");
+        connect(&mut graph, &read_a, "value", &sum, "a");
+        connect(&mut graph, &read_b, "value", &sum, "b");
+        connect(&mut graph, &sum, "result", &end, "result");
+        for node in [start, write_a, write_b, read_a, read_b, sum, end] {
+            graph.add_node(node);
+        }
+
+        let result = Compiler::new(&Config::default()).unwrap().compile(&graph).unwrap();
+        let imports = &result.metadata["imports"];
+        assert!(imports.contains(host::HOST_BATCH_WRITE_STORAGE) && imports.contains(host::HOST_BATCH_READ_STORAGE));
+        let mut context = ExecutionContext::new(100_000);
+        let stored = call(&result, 5, &mut context);
+        assert_eq!(stored.output["result"], 12);
+        assert_eq!(context.storage["a"], 5);
+        assert_eq!(context.storage["b"], 7);
+
+        let mut config = Config::default();
+        config.compiler.optimization_level = 0;
+        let unbatched = Compiler::new(&config).unwrap().compile(&graph).unwrap();
+        assert!(!unbatched.metadata["imports"].contains("batch"));
+        assert_eq!(call(&unbatched, 5, &mut ExecutionContext::new(100_000)).output["result"], 12);
+    }
+
+    #[test]
+    fn test_entry_points_are_callable_through_dispatch() {
+        // double(amount) and triple(amount), returning amount times two or three
+        let mut graph = VisualGraph::new("multiplier");
+        for (function, factor) in [("double", 2), ("triple", 3)] {
+            let start = node("Start")
+                .with_outputs(vec![
+                    Port::new("flow_out", "Flow Out", ValueType::Flow),
+                    Port::new("amount", "Amount", ValueType::Integer),
+                ])
+                .with_property("function", serde_json::json!(function))
+                .with_property("returns", serde_json::json!("integer"));
+            let multiply = node("Multiply").with_property("b", serde_json::json!(factor));
+            let end = node("End").with_inputs(vec![
+                Port::new("flow_in", "Flow In", ValueType::Flow),
+                Port::new("result", "Result", ValueType::Integer),
+            ]);
+            connect(&mut graph, &start, "flow_out", &end, "flow_in");
+            connect(&mut graph, &start, "amount", &multiply, "a");
+            connect(&mut graph, &multiply, "result", &end, "result");
+            for node in [start, multiply, end] {
+                graph.add_node(node);
+            }
+        }
+        let result = Compiler::new(&Config::default()).unwrap().compile(&graph).unwrap();
+        assert!(result.metadata["exports"].split(',').any(|e| e == DISPATCH_EXPORT));
+
+        let runtime = wasmtime::Engine::default();
+        let module = wasmtime::Module::new(&runtime, &result.wasm_bytes).unwrap();
+        let mut store = wasmtime::Store::new(&runtime, ());
+        let mut linker = wasmtime::Linker::new(&runtime);
+        linker.define_unknown_imports_as_traps(&module).unwrap();
+        let instance = linker.instantiate(&mut store, &module).unwrap();
+        let alloc = instance.get_typed_func::<i32, i32>(&mut store, engine::ALLOC_EXPORT).unwrap();
+        let dispatch = instance.get_typed_func::<(i32, i32, i32), i32>(&mut store, DISPATCH_EXPORT).unwrap();
+        let memory = instance.get_memory(&mut store, engine::MEMORY_EXPORT).unwrap();
+        let mut call = |function: &str, args: &str| {
+            let ptr = alloc.call(&mut store, args.len() as i32).unwrap();
+            memory.write(&mut store, ptr as usize, args.as_bytes()).unwrap();
+            dispatch.call(&mut store, (function_selector(function) as i32, ptr, args.len() as i32))
+        };
+        assert_eq!(call("double", "[21]").unwrap(), 42);
+        assert_eq!(call("triple", "[ \"-4\" ]").unwrap(), -12);
+        assert_eq!(call("triple", "[1, 2]").unwrap(), 3);
+        assert!(call("double", "[]").is_err());
+        assert!(call("double", "[{}]").is_err());
+        assert!(call("halve", "[8]").is_err());
+        // Arguments larger than the initial memory grow it
+        let padded = format!("[{}7]", " ".repeat(200_000));
+        assert_eq!(call("double", &padded).unwrap(), 14);
+    }
+
+    #[test]
+    fn test_compile_reports_gas_per_function() {
+        let result = Compiler::new(&Config::default()).unwrap().compile(&doubler()).unwrap();
+        let main = result.gas_breakdown.iter().find(|e| e.function == DEFAULT_ENTRY_POINT).unwrap();
+        assert!(main.instruction_gas > 0);
+        assert!(main.node_gas >= host::STORAGE_WRITE_GAS);
+        assert!(main.average_case <= main.worst_case);
+        assert_eq!(result.gas_estimate, main.worst_case);
+        let abi = result.abi.functions.iter().find(|f| f.name == DEFAULT_ENTRY_POINT).unwrap();
+        assert_eq!(abi.gas_estimate, Some(main.worst_case));
+
+        let mut config = Config::default();
+        config.compiler.gas_model.node_costs.insert("WriteStorage".to_string(), 1_000_000);
+        let priced = Compiler::new(&config).unwrap().compile(&doubler()).unwrap();
+        assert!(priced.gas_breakdown[0].worst_case >= 1_000_000);
+    }
+
+    #[test]
+    fn test_array_nodes_run_in_bounded_buffers() {
+        // main(amount): add every element of [1, 2, 3] + amount to `total`,
+        // then return the pushed array's length
+        let graph = |max_length: u64, max_iterations: u64| {
+            let mut graph = VisualGraph::new("arrays");
+            let (start, end) = start_and_end();
+            let push = node_with("ArrayPush", serde_json::json!({"array": [1, 2, 3], "max_length": max_length}));
+            let each = node_with("ForEach", serde_json::json!({"max_iterations": max_iterations}));
+            let read = node_with("ReadStorage", serde_json::json!({"key": "total"}));
+            let add = node("Add");
+            let write = node_with("WriteStorage", serde_json::json!({"key": "total"}));
+            let length = node("Length");
+            connect(&mut graph, &start, "amount", &push, "value");
+            connect(&mut graph, &start, "flow_out", &each, "flow_in");
+            connect(&mut graph, &push, "array", &each, "array");
+            connect(&mut graph, &each, "body_flow", &read, "flow_in");
+            connect(&mut graph, &read, "value", &add, "a");
+            connect(&mut graph, &each, "item", &add, "b");
+            connect(&mut graph, &read, "flow_out", &write, "flow_in");
+            connect(&mut graph, &add, "result", &write, "value");
+            connect(&mut graph, &each, "done_flow", &end, "flow_in");
+            connect(&mut graph, &push, "array", &length, "collection");
+            connect(&mut graph, &length, "length", &end, "result");
+            for node in [start, push, each, read, add, write, length, end] {
+                graph.add_node(node);
+            }
+            graph
+        };
+        let compiler = Compiler::new(&Config::default()).unwrap();
+
+        let result = compiler.compile(&graph(4, 4)).unwrap();
+        let mut context = ExecutionContext::new(100_000);
+        assert_eq!(call(&result, 4, &mut context).output["result"], 4);
+        assert_eq!(context.storage["total"], 10);
+
+        // Four elements fit neither a three-element buffer nor three iterations
+        let overflowing = compiler.compile(&graph(3, 4)).unwrap();
+        let overflow = call(&overflowing, 4, &mut ExecutionContext::new(100_000));
+        assert_eq!(overflow.revert_reason.unwrap().error, "BufferOverflow");
+        let unbounded = compiler.compile(&graph(4, 3)).unwrap();
+        let exceeded = call(&unbounded, 4, &mut ExecutionContext::new(100_000));
+        assert_eq!(exceeded.revert_reason.unwrap().error, "LoopBoundExceeded");
+    }
+
+    #[test]
+    fn test_map_nodes_run_in_bounded_buffers() {
+        // main(amount): set "b" to amount in {"a": 1}, remove "a", then
+        // return "b" plus whether "a" is still found
+        let mut graph = VisualGraph::new("maps");
+        let (start, end) = start_and_end();
+        let set = node_with("MapSet", serde_json::json!({"map": {"a": 1}, "key": "b", "max_length": 2}));
+        let remove = node_with("MapRemove", serde_json::json!({"key": "a", "max_length": 2}));
+        let get = node_with("MapGet", serde_json::json!({"key": "b"}));
+        let found = node_with("MapGet", serde_json::json!({"key": "a"}));
+        let add = node("Add");
+        connect(&mut graph, &start, "flow_out", &end, "flow_in");
+        connect(&mut graph, &start, "amount", &set, "value");
+        connect(&mut graph, &set, "map", &remove, "map");
+        connect(&mut graph, &remove, "map", &get, "map");
+        connect(&mut graph, &remove, "map", &found, "map");
+        connect(&mut graph, &get, "value", &add, "a");
+        connect(&mut graph, &found, "found", &add, "b");
+        connect(&mut graph, &add, "result", &end, "result");
+        for node in [start, set, remove, get, found, add, end] {
+            graph.add_node(node);
+        }
+
+        let result = Compiler::new(&Config::default()).unwrap().compile(&graph).unwrap();
+        assert_eq!(call(&result, 41, &mut ExecutionContext::new(100_000)).output["result"], 41);
+    }
+
+    #[test]
+    fn test_decimal_nodes_call_the_decimal_host_imports() {
+        // main(amount): amount * 1.50 / divisor at scale 2, amount being in units
+        let graph = |divisor: &str| {
+            let mut graph = VisualGraph::new("decimals");
+            let (start, end) = start_and_end();
+            let multiply = node_with("Multiply", serde_json::json!({"b": "1.50", "decimal_scale": 2}));
+            let divide = node_with("Divide", serde_json::json!({"b": divisor, "decimal_scale": 2}));
+            connect(&mut graph, &start, "flow_out", &end, "flow_in");
+            connect(&mut graph, &start, "amount", &multiply, "a");
+            connect(&mut graph, &multiply, "result", &divide, "a");
+            connect(&mut graph, &divide, "result", &end, "result");
+            for node in [start, multiply, divide, end] {
+                graph.add_node(node);
+            }
+            graph
+        };
+        let compiler = Compiler::new(&Config::default()).unwrap();
+
+        let result = compiler.compile(&graph("0.50")).unwrap();
+        let mut context = ExecutionContext::new(100_000);
+        // 2.00 * 1.50 / 0.50 = 6.00
+        assert_eq!(call(&result, 200, &mut context).output["result"], 600);
+        let imports: Vec<&str> = context.host_calls.iter().map(|c| c.import.as_str()).collect();
+        assert_eq!(imports, vec![host::HOST_DECIMAL_MUL, host::HOST_DECIMAL_DIV]);
+
+        let by_zero = compiler.compile(&graph("0")).unwrap();
+        let reverted = call(&by_zero, 200, &mut ExecutionContext::new(100_000)).revert_reason.unwrap();
+        assert_eq!(reverted.error, engine::TRAP);
+        assert!(reverted.message.contains("division by zero"), "{}", reverted.message);
+    }
+
+    #[test]
+    fn test_try_call_failures_run_the_matching_catch() {
+        // main(amount): call transfer(amount) on 0xdead, storing 1 on success
+        // and amount when the call fails with `caught`
+        let graph = |caught: &str| {
+            let mut graph = VisualGraph::new("caller");
+            let start = node("Start").with_outputs(vec![
+                Port::new("flow_out", "Flow Out", ValueType::Flow),
+                Port::new("amount", "Amount", ValueType::Integer),
+            ]);
+            let try_call = node("TryCall")
+                .with_property("target", serde_json::json!("0xdead"))
+                .with_property("function", serde_json::json!("transfer"));
+            let success = node("WriteStorage")
+                .with_property("key", serde_json::json!("outcome"))
+                .with_property("value", serde_json::json!(1));
+            let catch = node("Catch").with_property("error", serde_json::json!(caught));
+            let failure = node("WriteStorage").with_property("key", serde_json::json!("outcome"));
+            connect(&mut graph, &start, "flow_out", &try_call, "flow_in");
+            connect(&mut graph, &start, "amount", &try_call, "args");
+            connect(&mut graph, &try_call, "success_flow", &success, "flow_in");
+            connect(&mut graph, &try_call, "failure_flow", &catch, "flow_in");
+            connect(&mut graph, &try_call, "error", &catch, "error");
+            connect(&mut graph, &catch, "flow_out", &failure, "flow_in");
+            connect(&mut graph, &start, "amount", &failure, "value");
+            for node in [start, try_call, success, catch, failure] {
+                graph.add_node(node);
+            }
+            graph
+        };
+        let compiler = Compiler::new(&Config::default()).unwrap();
+
+        // Local execution has no other contracts, so the call always fails
+        let caught = compiler.compile(&graph("CallUnavailable")).unwrap();
+        assert!(caught.metadata["imports"].contains(host::HOST_CALL_CONTRACT));
+        let mut context = ExecutionContext::new(100_000);
+        assert!(!call(&caught, 9, &mut context).reverted());
+        assert_eq!(context.storage["outcome"], 9);
+        assert_eq!(context.host_calls[0].result["args"], serde_json::json!([9]));
+
+        let uncaught = compiler.compile(&graph("Unauthorized\"Name")).unwrap();
+        let mut context = ExecutionContext::new(100_000);
+        let rethrown = call(&uncaught, 9, &mut context);
+        assert_eq!(rethrown.revert_reason.unwrap().error, "CallUnavailable");
+        assert!(!context.storage.contains_key("outcome"));
+    }
+
+    #[test]
+    fn test_string_nodes_run_in_static_buffers() {
+        // main(amount): whether "can" + "vas" sliced from amount is "vas"
+        let graph = |max_length: u64| {
+            let mut graph = VisualGraph::new("strings");
+            let (start, end) = start_and_end();
+            let concat = node_with("Concat", serde_json::json!({"a": "can", "b": "vas", "max_length": max_length}));
+            let slice = node_with("Slice", serde_json::json!({"max_length": 6}));
+            let compare = node_with("Compare", serde_json::json!({"b": "vas", "max_length": 6}));
+            connect(&mut graph, &start, "flow_out", &end, "flow_in");
+            connect(&mut graph, &concat, "result", &slice, "input");
+            connect(&mut graph, &start, "amount", &slice, "start");
+            connect(&mut graph, &slice, "result", &compare, "a");
+            connect(&mut graph, &compare, "equal", &end, "result");
+            for node in [start, concat, slice, compare, end] {
+                graph.add_node(node);
+            }
+            graph
+        };
+        let compiler = Compiler::new(&Config::default()).unwrap();
+
+        let result = compiler.compile(&graph(6)).unwrap();
+        assert_eq!(call(&result, 3, &mut ExecutionContext::new(100_000)).output["result"], 1);
+        assert_eq!(call(&result, 2, &mut ExecutionContext::new(100_000)).output["result"], 0);
+        let out_of_bounds = call(&result, 7, &mut ExecutionContext::new(100_000));
+        assert_eq!(out_of_bounds.revert_reason.unwrap().error, "OutOfBounds");
+
+        // Six bytes do not fit a five-byte buffer
+        let overflowing = compiler.compile(&graph(5)).unwrap();
+        let overflow = call(&overflowing, 3, &mut ExecutionContext::new(100_000));
+        assert_eq!(overflow.revert_reason.unwrap().error, "BufferOverflow");
+
+        let error = compiler.compile(&graph(1 << 20)).unwrap_err().to_string();
+        assert!(error.contains("String buffers need"), "{}", error);
+    }
+
+    #[test]
+    fn test_hash_nodes_call_the_hash_host_import() {
+        let mut graph = VisualGraph::new("hashing");
+        let (start, end) = start_and_end();
+        let hash = node_with("Hash", serde_json::json!({"input": "abc", "algorithm": "sha256", "max_length": 64}));
+        let digest = "0xba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+        let compare = node_with("Compare", serde_json::json!({"b": digest, "max_length": 64}));
+        connect(&mut graph, &start, "flow_out", &end, "flow_in");
+        connect(&mut graph, &hash, "hash", &compare, "a");
+        connect(&mut graph, &compare, "equal", &end, "result");
+        for node in [start, hash, compare, end] {
+            graph.add_node(node);
+        }
+
+        let result = Compiler::new(&Config::default()).unwrap().compile(&graph).unwrap();
+        assert!(result.metadata["imports"].contains(host::HOST_HASH));
+        let mut context = ExecutionContext::new(100_000);
+        assert_eq!(call(&result, 0, &mut context).output["result"], 1);
+        assert_eq!(context.host_calls[0].result, serde_json::json!(digest));
+    }
+
+    #[test]
+    fn test_verify_signature_nodes_call_the_signature_host_import() {
+        use ed25519_dalek::{Signer, SigningKey};
+        let signing_key = SigningKey::from_bytes(&[7u8; 32]);
+        let public_key = crate::nodes::encode_hex(&signing_key.verifying_key().to_bytes());
+        let signature = crate::nodes::encode_hex(&signing_key.sign(b"approve").to_bytes());
+        let graph = |message: &str| {
+            let mut graph = VisualGraph::new("signed");
+            let (start, end) = start_and_end();
+            let verify = node_with(
+                "VerifySignature",
+                serde_json::json!({
+                    "scheme": "ed25519",
+                    "public_key": public_key,
+                    "message": message,
+                    "signature": signature,
+                }),
+            );
+            connect(&mut graph, &start, "flow_out", &end, "flow_in");
+            connect(&mut graph, &verify, "valid", &end, "result");
+            for node in [start, verify, end] {
+                graph.add_node(node);
+            }
+            graph
+        };
+        let compiler = Compiler::new(&Config::default()).unwrap();
+
+        let signed = compiler.compile(&graph("approve")).unwrap();
+        assert!(signed.metadata["imports"].contains(host::HOST_VERIFY_SIGNATURE));
+        let mut context = ExecutionContext::new(100_000);
+        assert_eq!(call(&signed, 0, &mut context).output["result"], 1);
+        assert!(context.host_calls[0].gas_used >= host::ED25519_VERIFY_GAS);
+
+        let tampered = compiler.compile(&graph("approve 999")).unwrap();
+        assert_eq!(call(&tampered, 0, &mut ExecutionContext::new(100_000)).output["result"], 0);
+    }
+}
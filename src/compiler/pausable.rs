@@ -0,0 +1,258 @@
+//! Pausable contracts (circuit breaker)
+//!
+//! With `compiler.pausable` enabled the generated contract gets a paused flag in
+//! storage, a guard at the top of every state-mutating entry point, and
+//! admin-only `pause`/`unpause` functions plus a `paused` view. The admin is the
+//! account that deployed the contract.
+
+use std::collections::HashMap;
+
+use crate::{
+    error::{CanvasError, CanvasResult},
+    types::{ContractABI, ErrorABI, FunctionABI, ParameterABI, RevertReason, StateMutability, ValueType},
+    wasm::host,
+};
+
+/// Storage key holding the paused flag
+pub const PAUSED_STORAGE_KEY: &str = "__canvas_paused";
+/// Storage key holding the admin allowed to pause the contract
+pub const ADMIN_STORAGE_KEY: &str = "__canvas_admin";
+/// Admin entry point that pauses the contract
+pub const PAUSE_FUNCTION: &str = "pause";
+/// Admin entry point that resumes the contract
+pub const UNPAUSE_FUNCTION: &str = "unpause";
+/// View reporting whether the contract is paused
+pub const PAUSED_FUNCTION: &str = "paused";
+/// ABI metadata key marking a pausable contract
+pub const PAUSABLE_METADATA_KEY: &str = "pausable";
+
+/// Revert reason of a guarded entry point called while paused
+pub fn paused_revert() -> RevertReason {
+    RevertReason::new("Paused", "contract is paused")
+}
+
+/// Revert reason of a pause/unpause call from someone other than the admin
+pub fn unauthorized_revert() -> RevertReason {
+    RevertReason::new("Unauthorized", "caller is not the contract admin")
+}
+
+/// Whether an entry point can change contract state
+pub fn is_state_mutating(function: &FunctionABI) -> bool {
+    matches!(
+        function.state_mutability,
+        StateMutability::NonPayable | StateMutability::Payable
+    )
+}
+
+/// Whether a compiled contract was built with the pausable feature
+pub fn is_pausable(abi: &ContractABI) -> bool {
+    abi.metadata.get(PAUSABLE_METADATA_KEY).map_or(false, |v| v == "true")
+}
+
+/// Whether a contract's storage has the paused flag set
+pub fn is_paused(storage: &HashMap<String, serde_json::Value>) -> bool {
+    storage
+        .get(PAUSED_STORAGE_KEY)
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false)
+}
+
+/// Add the pause entry points, errors and metadata to an ABI.
+///
+/// Returns the names of the entry points that get a pause guard. Fails if the
+/// contract already defines one of the reserved function names.
+pub fn inject_pausable_abi(abi: &mut ContractABI) -> CanvasResult<Vec<String>> {
+    for reserved in [PAUSE_FUNCTION, UNPAUSE_FUNCTION, PAUSED_FUNCTION] {
+        if abi.functions.iter().any(|f| f.name == reserved) {
+            return Err(CanvasError::Compilation(format!(
+                "Function '{}' is reserved when the pausable feature is enabled",
+                reserved
+            )));
+        }
+    }
+
+    let guarded: Vec<String> = abi
+        .functions
+        .iter()
+        .filter(|f| is_state_mutating(f))
+        .map(|f| f.name.clone())
+        .collect();
+
+    for name in [PAUSE_FUNCTION, UNPAUSE_FUNCTION] {
+        abi.functions.push(FunctionABI {
+            name: name.to_string(),
+            inputs: Vec::new(),
+            outputs: Vec::new(),
+            state_mutability: StateMutability::NonPayable,
+            gas_estimate: Some(host::STORAGE_READ_GAS + host::STORAGE_WRITE_GAS),
+        });
+    }
+    abi.functions.push(FunctionABI {
+        name: PAUSED_FUNCTION.to_string(),
+        inputs: Vec::new(),
+        outputs: vec![ParameterABI {
+            name: "paused".to_string(),
+            value_type: ValueType::Boolean,
+            indexed: false,
+        }],
+        state_mutability: StateMutability::View,
+        gas_estimate: Some(host::STORAGE_READ_GAS),
+    });
+
+    for reason in [paused_revert(), unauthorized_revert()] {
+        if !abi.errors.iter().any(|e| e.name == reason.error) {
+            abi.errors.push(ErrorABI {
+                name: reason.error,
+                inputs: Vec::new(),
+            });
+        }
+    }
+
+    abi.metadata.insert(PAUSABLE_METADATA_KEY.to_string(), "true".to_string());
+    abi.metadata.insert(format!("{}.guarded", PAUSABLE_METADATA_KEY), guarded.join(","));
+
+    Ok(guarded)
+}
+
+/// Instruction that runs the pause guard; emitted first in each guarded entry point
+pub fn pause_guard_call() -> String {
+    "call $canvas_when_not_paused".to_string()
+}
+
+/// Constant data of the pausable feature. Flags are stored as JSON booleans,
+/// the form [`is_paused`] reads.
+fn pausable_constants() -> Vec<(&'static str, Vec<u8>)> {
+    vec![
+        ("paused_key", PAUSED_STORAGE_KEY.as_bytes().to_vec()),
+        ("admin_key", ADMIN_STORAGE_KEY.as_bytes().to_vec()),
+        ("flag_true", b"true".to_vec()),
+        ("flag_false", b"false".to_vec()),
+        ("paused_revert", paused_revert().encode()),
+        ("unauthorized_revert", unauthorized_revert().encode()),
+    ]
+}
+
+/// Bytes of constant data [`pausable_wat`] lays out
+pub fn pausable_data_bytes() -> u32 {
+    pausable_constants().iter().map(|(_, bytes)| bytes.len() as u32).sum()
+}
+
+/// WAT data segments and functions implementing the pausable feature.
+///
+/// Constant data (storage keys, flag values and revert payloads) is laid out
+/// from `offset`; `scratch` is a 128-byte buffer for reading storage. The admin
+/// is stored as a JSON string of the deployer's address, like every slot
+/// holds JSON, so the admin check compares the caller with what is inside
+/// the quotes.
+pub fn pausable_wat(offset: u32, scratch: u32) -> (Vec<String>, String) {
+    let constants = pausable_constants();
+    let mut data_segments = Vec::new();
+    let mut layout = HashMap::new();
+    let mut next = offset;
+    for (name, bytes) in &constants {
+        let escaped: String = bytes.iter().map(|b| format!("\\{:02x}", b)).collect();
+        data_segments.push(format!("(data (i32.const {}) \"{}\")", next, escaped));
+        layout.insert(*name, (next, bytes.len()));
+        next += bytes.len() as u32;
+    }
+    let at = |name: &str| {
+        let (ptr, len) = layout[name];
+        format!("(i32.const {}) (i32.const {})", ptr, len)
+    };
+
+    let functions = format!(
+        r#"
+  (func $canvas_is_paused (result i32)
+    (if (result i32) (i32.gt_s (call ${read} {paused_key} (i32.const {scratch})) (i32.const 0))
+      (then (i32.eq (i32.load8_u (i32.const {scratch})) (i32.const 116)))
+      (else (i32.const 0))))
+  (func $canvas_when_not_paused
+    (if (call $canvas_is_paused)
+      (then (call ${revert} {paused_revert}))))
+  (func $canvas_only_admin
+    (local $len i32)
+    (local.set $len (call ${read} {admin_key} (i32.const {scratch})))
+    (if (i32.lt_s (local.get $len) (i32.const 2))
+      (then (call ${revert} {unauthorized_revert})))
+    (if (i32.eqz (call ${caller_is} (i32.const {address}) (i32.sub (local.get $len) (i32.const 2))))
+      (then (call ${revert} {unauthorized_revert}))))
+  (func $canvas_init_admin
+    (local $len i32)
+    (i32.store8 (i32.const {scratch}) (i32.const 34))
+    (local.set $len (call ${caller} (i32.const {address})))
+    (i32.store8 (i32.add (i32.const {address}) (local.get $len)) (i32.const 34))
+    (call ${write} {admin_key} (i32.const {scratch}) (i32.add (local.get $len) (i32.const 2))))
+  (func (export "{pause}")
+    (call $canvas_only_admin)
+    (call ${write} {paused_key} {flag_true}))
+  (func (export "{unpause}")
+    (call $canvas_only_admin)
+    (call ${write} {paused_key} {flag_false}))
+  (func (export "{paused}") (result i32)
+    (call $canvas_is_paused))
+"#,
+        read = host::HOST_READ_STORAGE,
+        write = host::HOST_WRITE_STORAGE,
+        revert = host::HOST_REVERT,
+        caller = host::HOST_CALLER,
+        caller_is = host::HOST_CALLER_IS,
+        scratch = scratch,
+        address = scratch + 1,
+        paused_key = at("paused_key"),
+        admin_key = at("admin_key"),
+        flag_true = at("flag_true"),
+        flag_false = at("flag_false"),
+        paused_revert = at("paused_revert"),
+        unauthorized_revert = at("unauthorized_revert"),
+        pause = PAUSE_FUNCTION,
+        unpause = UNPAUSE_FUNCTION,
+        paused = PAUSED_FUNCTION,
+    );
+
+    (data_segments, functions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn function(name: &str, state_mutability: StateMutability) -> FunctionABI {
+        FunctionABI {
+            name: name.to_string(),
+            inputs: Vec::new(),
+            outputs: Vec::new(),
+            state_mutability,
+            gas_estimate: None,
+        }
+    }
+
+    fn abi(functions: Vec<FunctionABI>) -> ContractABI {
+        ContractABI {
+            functions,
+            events: Vec::new(),
+            errors: Vec::new(),
+            storage: Vec::new(),
+            metadata: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_only_mutating_functions_are_guarded() {
+        let mut abi = abi(vec![
+            function("transfer", StateMutability::NonPayable),
+            function("balance_of", StateMutability::View),
+        ]);
+
+        let guarded = inject_pausable_abi(&mut abi).unwrap();
+        assert_eq!(guarded, vec!["transfer".to_string()]);
+        assert!(is_pausable(&abi));
+        assert!(abi.functions.iter().any(|f| f.name == PAUSE_FUNCTION));
+        assert!(abi.errors.iter().any(|e| e.name == "Paused"));
+    }
+
+    #[test]
+    fn test_reserved_names_are_rejected() {
+        let mut abi = abi(vec![function("pause", StateMutability::NonPayable)]);
+        assert!(inject_pausable_abi(&mut abi).is_err());
+    }
+}
@@ -0,0 +1,336 @@
+//! Contract interaction playground
+//!
+//! A standalone HTML page generated from a contract ABI, with a form for each
+//! function, so stakeholders can call a deployed contract from a browser
+//! without installing anything. Calls are posted to an RPC endpoint speaking
+//! the service-mode API (`POST <endpoint>/call/<function>`, see
+//! [`crate::wasm::service`]); the endpoint is set when the page is generated
+//! and can be changed on the page. Scripts and styles are inlined, so the file
+//! can be opened from disk or attached to an email.
+//!
+//! Integers, booleans and strings get plain inputs; every other type is
+//! entered as JSON.
+
+use crate::{
+    error::CanvasResult,
+    types::{ContractABI, FunctionABI, ParameterABI, StateMutability, ValueType},
+};
+
+/// Endpoint of a local `canvas serve`
+pub const DEFAULT_PLAYGROUND_ENDPOINT: &str = "http://127.0.0.1:8545";
+
+/// Settings of a generated playground
+#[derive(Debug, Clone)]
+pub struct PlaygroundOptions {
+    pub title: String,
+    /// Base URL calls are posted to
+    pub endpoint: String,
+    /// Address of the deployed contract, sent along with each call
+    pub contract: Option<String>,
+}
+
+impl PlaygroundOptions {
+    pub fn new(title: impl Into<String>) -> Self {
+        Self {
+            title: title.into(),
+            endpoint: DEFAULT_PLAYGROUND_ENDPOINT.to_string(),
+            contract: None,
+        }
+    }
+
+    pub fn with_endpoint(mut self, endpoint: impl Into<String>) -> Self {
+        self.endpoint = endpoint.into();
+        self
+    }
+
+    pub fn with_contract(mut self, contract: impl Into<String>) -> Self {
+        self.contract = Some(contract.into());
+        self
+    }
+}
+
+/// How the page reads an argument from its input
+fn input_kind(value_type: &ValueType) -> &'static str {
+    match value_type {
+        ValueType::Boolean => "bool",
+        ValueType::Integer => "int",
+        ValueType::Float => "float",
+        ValueType::String => "string",
+        _ => "json",
+    }
+}
+
+fn type_label(value_type: &ValueType) -> String {
+    match value_type {
+        ValueType::Array(inner) => format!("{}[]", type_label(inner)),
+        ValueType::Map(inner) => format!("map<{}>", type_label(inner)),
+        ValueType::Decimal(digits) => format!("decimal({})", digits),
+        ValueType::Object(_) => "object".to_string(),
+        other => format!("{:?}", other).to_lowercase(),
+    }
+}
+
+/// Escape text for HTML content and quoted attribute values
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn parameter_field(parameter: &ParameterABI) -> String {
+    let kind = input_kind(&parameter.value_type);
+    let name = escape(&parameter.name);
+    let control = match kind {
+        "bool" => format!(r#"<input type="checkbox" name="{}" data-kind="bool">"#, name),
+        "json" => format!(r#"<textarea name="{}" data-kind="json" rows="2" placeholder="JSON"></textarea>"#, name),
+        _ => format!(r#"<input type="text" name="{}" data-kind="{}">"#, name, kind),
+    };
+    format!(
+        "      <label>{} <span class=\"type\">{}</span> {}</label>\n",
+        name,
+        escape(&type_label(&parameter.value_type)),
+        control
+    )
+}
+
+fn function_form(function: &FunctionABI) -> String {
+    let name = escape(&function.name);
+    let read_only = matches!(function.state_mutability, StateMutability::Pure | StateMutability::View);
+    let mut form = format!(
+        "    <form class=\"function\" data-function=\"{}\">\n      <h2>{} <span class=\"type\">{:?}</span></h2>\n",
+        name, name, function.state_mutability
+    );
+    for parameter in &function.inputs {
+        form.push_str(&parameter_field(parameter));
+    }
+    if function.state_mutability == StateMutability::Payable {
+        form.push_str("      <label>value <span class=\"type\">integer</span> <input type=\"text\" name=\"value\" data-kind=\"value\"></label>\n");
+    }
+    if !function.outputs.is_empty() {
+        let outputs: Vec<String> = function
+            .outputs
+            .iter()
+            .map(|p| format!("{}: {}", p.name, type_label(&p.value_type)))
+            .collect();
+        form.push_str(&format!("      <p class=\"returns\">returns {}</p>\n", escape(&outputs.join(", "))));
+    }
+    form.push_str(&format!(
+        "      <button type=\"submit\">{}</button>\n      <pre class=\"result\"></pre>\n    </form>\n",
+        if read_only { "Query" } else { "Send" }
+    ));
+    form
+}
+
+fn reference_table(title: &str, rows: &[(String, &[ParameterABI])]) -> String {
+    if rows.is_empty() {
+        return String::new();
+    }
+    let mut table = format!("    <h2>{}</h2>\n    <table>\n", title);
+    for (name, inputs) in rows {
+        let fields: Vec<String> = inputs
+            .iter()
+            .map(|p| format!("{}: {}", p.name, type_label(&p.value_type)))
+            .collect();
+        table.push_str(&format!(
+            "      <tr><td>{}</td><td>{}</td></tr>\n",
+            escape(name),
+            escape(&fields.join(", "))
+        ));
+    }
+    table.push_str("    </table>\n");
+    table
+}
+
+const STYLE: &str = r#"body { font-family: system-ui, sans-serif; max-width: 52rem; margin: 2rem auto; padding: 0 1rem; color: #222; }
+header input { width: 24rem; }
+form.function { border: 1px solid #ccc; border-radius: 6px; padding: 0.5rem 1rem 1rem; margin: 1rem 0; }
+form.function h2 { font-size: 1.1rem; }
+label { display: block; margin: 0.4rem 0; }
+.type { color: #777; font-size: 0.85em; font-weight: normal; }
+textarea { width: 100%; font-family: monospace; }
+pre.result:empty { display: none; }
+pre.result { background: #f4f4f4; padding: 0.5rem; overflow-x: auto; }
+pre.result.failed { background: #fbeaea; }
+td { padding: 0.2rem 1rem 0.2rem 0; vertical-align: top; }"#;
+
+const SCRIPT: &str = r#"const settings = JSON.parse(document.getElementById("settings").textContent);
+const endpoint = document.getElementById("endpoint");
+const contract = document.getElementById("contract");
+endpoint.value = localStorage.getItem("playground.endpoint") || settings.endpoint;
+contract.value = settings.contract || "";
+endpoint.addEventListener("change", () => localStorage.setItem("playground.endpoint", endpoint.value));
+
+function argument(input) {
+  const raw = input.value.trim();
+  switch (input.dataset.kind) {
+    case "bool": return input.checked;
+    case "int":
+      if (!/^-?\d+$/.test(raw)) throw new Error(`${input.name} must be an integer`);
+      return Number.isSafeInteger(Number(raw)) ? Number(raw) : raw;
+    case "float":
+      if (raw === "" || isNaN(Number(raw))) throw new Error(`${input.name} must be a number`);
+      return Number(raw);
+    case "json":
+      try { return JSON.parse(raw); } catch (e) { throw new Error(`${input.name} must be JSON: ${e.message}`); }
+    default: return input.value;
+  }
+}
+
+for (const form of document.querySelectorAll("form.function")) {
+  form.addEventListener("submit", async (event) => {
+    event.preventDefault();
+    const result = form.querySelector("pre.result");
+    result.classList.remove("failed");
+    try {
+      const body = { args: [] };
+      for (const input of form.querySelectorAll("[data-kind]")) {
+        if (input.dataset.kind === "value") {
+          if (input.value.trim() !== "") body.value = input.value.trim();
+        } else {
+          body.args.push(argument(input));
+        }
+      }
+      if (contract.value.trim() !== "") body.contract = contract.value.trim();
+      result.textContent = "…";
+      const url = `${endpoint.value.replace(/\/+$/, "")}/call/${encodeURIComponent(form.dataset.function)}`;
+      const response = await fetch(url, {
+        method: "POST",
+        headers: { "Content-Type": "application/json" },
+        body: JSON.stringify(body),
+      });
+      const reply = await response.json().catch(() => ({ error: `HTTP ${response.status}` }));
+      result.classList.toggle("failed", !response.ok);
+      result.textContent = JSON.stringify(reply, null, 2);
+    } catch (e) {
+      result.classList.add("failed");
+      result.textContent = e.message;
+    }
+  });
+}"#;
+
+/// Render the playground page for `abi`
+pub fn generate_playground(abi: &ContractABI, options: &PlaygroundOptions) -> CanvasResult<String> {
+    // Keep "</script>" in user-controlled strings from closing the settings block
+    let settings = serde_json::to_string(&serde_json::json!({
+        "endpoint": options.endpoint,
+        "contract": options.contract,
+    }))?
+    .replace("</", "<\\/");
+    let title = escape(&options.title);
+
+    let mut body = String::new();
+    for function in &abi.functions {
+        body.push_str(&function_form(function));
+    }
+    let events: Vec<(String, &[ParameterABI])> =
+        abi.events.iter().map(|e| (e.name.clone(), e.inputs.as_slice())).collect();
+    body.push_str(&reference_table("Events", &events));
+    let errors: Vec<(String, &[ParameterABI])> =
+        abi.errors.iter().map(|e| (e.name.clone(), e.inputs.as_slice())).collect();
+    body.push_str(&reference_table("Errors", &errors));
+
+    Ok(format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+  <meta charset="utf-8">
+  <meta name="viewport" content="width=device-width, initial-scale=1">
+  <title>{title}</title>
+  <style>
+{STYLE}
+  </style>
+</head>
+<body>
+  <header>
+    <h1>{title}</h1>
+    <label>Endpoint <input id="endpoint" type="url"></label>
+    <label>Contract <input id="contract" type="text" placeholder="0x…"></label>
+  </header>
+  <main>
+{body}  </main>
+  <script type="application/json" id="settings">{settings}</script>
+  <script>
+{SCRIPT}
+  </script>
+</body>
+</html>
+"#
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{ErrorABI, EventABI, FunctionABI};
+
+    fn parameter(name: &str, value_type: ValueType) -> ParameterABI {
+        ParameterABI {
+            name: name.to_string(),
+            value_type,
+            indexed: false,
+        }
+    }
+
+    #[test]
+    fn test_playground_has_a_form_per_function() {
+        let abi = ContractABI {
+            functions: vec![
+                FunctionABI {
+                    name: "deposit".to_string(),
+                    inputs: vec![
+                        parameter("amount", ValueType::Integer),
+                        parameter("lock", ValueType::Boolean),
+                        parameter("tags", ValueType::Array(Box::new(ValueType::String))),
+                    ],
+                    outputs: vec![parameter("balance", ValueType::Integer)],
+                    state_mutability: StateMutability::Payable,
+                    gas_estimate: None,
+                },
+                FunctionABI {
+                    name: "balance".to_string(),
+                    inputs: Vec::new(),
+                    outputs: vec![parameter("balance", ValueType::Integer)],
+                    state_mutability: StateMutability::View,
+                    gas_estimate: None,
+                },
+            ],
+            events: vec![EventABI {
+                name: "Deposited".to_string(),
+                inputs: vec![parameter("amount", ValueType::Integer)],
+                anonymous: false,
+            }],
+            errors: vec![ErrorABI {
+                name: "Locked".to_string(),
+                inputs: Vec::new(),
+            }],
+            storage: Vec::new(),
+            metadata: Default::default(),
+        };
+        let options = PlaygroundOptions::new("Vault <beta>")
+            .with_endpoint("https://staging.example.com")
+            .with_contract("0xabc</script>");
+        let html = generate_playground(&abi, &options).unwrap();
+
+        assert!(html.contains(r#"<form class="function" data-function="deposit">"#));
+        assert!(html.contains(r#"<form class="function" data-function="balance">"#));
+        assert!(html.contains(r#"<input type="checkbox" name="lock" data-kind="bool">"#));
+        assert!(html.contains(r#"<textarea name="tags" data-kind="json""#));
+        assert!(html.contains(r#"name="value" data-kind="value""#), "payable functions take a value");
+        assert_eq!(html.matches(r#"data-kind="value""#).count(), 1);
+        assert!(html.contains("<button type=\"submit\">Query</button>"));
+        assert!(html.contains("<td>Deposited</td><td>amount: integer</td>"));
+        assert!(html.contains("<title>Vault &lt;beta&gt;</title>"));
+        assert!(html.contains(r#""endpoint":"https://staging.example.com""#));
+        assert!(html.contains(r#"0xabc<\/script>"#));
+        assert_eq!(html.matches("</script>").count(), 2);
+    }
+}
@@ -0,0 +1,457 @@
+//! Rename refactoring
+//!
+//! Renaming a storage key, an event or an exported function by hand means
+//! finding every node, ABI and scenario that spells it out. [`plan_rename`]
+//! collects them all under a workspace root into a [`RenamePlan`], which can be
+//! shown as a preview and then applied as a unit: every changed file is staged
+//! next to the original and only moved into place once all of them are written.
+//!
+//! | Kind       | Updated                                                              |
+//! |------------|----------------------------------------------------------------------|
+//! | `storage`  | `key` of storage nodes, entries of the `keys` of batch storage nodes  |
+//! | `event`    | `event` of `EmitEvent` nodes, events of `.abi.json` files            |
+//! | `function` | `function` of `Start` nodes and of `TryCall` nodes calling a workspace contract that defines it, functions and selectors of `.abi.json` files, scenario steps |
+//!
+//! Release records are left alone since their ABI describes code that is
+//! already deployed; the plan lists the environments whose latest release
+//! still uses the old name, so they can be redeployed.
+//!
+//! The workspace's address book is the `address` metadata of each graph (see
+//! [`ADDRESS_METADATA_KEY`](super::call_graph::ADDRESS_METADATA_KEY)). It maps
+//! deployed addresses to contracts and never spells out a storage key, event
+//! or function, so a rename has nothing to change there.
+
+use std::{
+    collections::{BTreeMap, HashSet},
+    path::{Path, PathBuf},
+};
+
+use serde::Serialize;
+
+use super::{
+    call_graph::{CallGraph, CallKind, CallTarget},
+    entry_points::{function_selector, DEFAULT_ENTRY_POINT},
+    imports::Workspace,
+    search::{BATCH_STORAGE_NODE_TYPES, STORAGE_NODE_TYPES},
+};
+use crate::{
+    deployment::environments::ReleaseStore,
+    error::{CanvasError, CanvasResult},
+    testing::SCENARIO_EXTENSION,
+    types::{NodeId, VisualGraph},
+};
+
+/// File name suffix of ABI files
+pub const ABI_EXTENSION: &str = ".abi.json";
+
+/// What is being renamed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RenameKind {
+    StorageKey,
+    Event,
+    Function,
+}
+
+impl RenameKind {
+    /// Kind named on the command line: storage, event or function
+    pub fn parse(kind: &str) -> Option<Self> {
+        match kind {
+            "storage" => Some(Self::StorageKey),
+            "event" => Some(Self::Event),
+            "function" => Some(Self::Function),
+            _ => None,
+        }
+    }
+
+    fn describe(&self) -> &'static str {
+        match self {
+            Self::StorageKey => "storage key",
+            Self::Event => "event",
+            Self::Function => "function",
+        }
+    }
+}
+
+/// One occurrence that changes
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RenameEdit {
+    /// Workspace-relative path of the file
+    pub path: PathBuf,
+    /// Where in the file: `<node id>.properties.<name>` for graphs, a JSON
+    /// path (`functions[0].name`) for ABIs and scenarios
+    pub location: String,
+}
+
+/// Every change a rename makes, ready to preview or apply
+#[derive(Debug, Clone, Serialize)]
+pub struct RenamePlan {
+    pub kind: RenameKind,
+    pub from: String,
+    pub to: String,
+    pub edits: Vec<RenameEdit>,
+    /// Environments whose latest release still uses the old name
+    pub stale_releases: Vec<String>,
+    #[serde(skip)]
+    root: PathBuf,
+    /// New contents of every changed file, by workspace-relative path
+    #[serde(skip)]
+    files: BTreeMap<PathBuf, String>,
+}
+
+impl RenamePlan {
+    /// Files the rename rewrites
+    pub fn files(&self) -> impl Iterator<Item = &Path> {
+        self.files.keys().map(PathBuf::as_path)
+    }
+
+    /// Write every changed file. Nothing is replaced unless all of them could
+    /// be staged, and files already replaced are restored if a later one cannot
+    /// be. Returns how many files were written.
+    pub fn apply(&self) -> CanvasResult<usize> {
+        let mut staged: Vec<(PathBuf, PathBuf)> = Vec::new();
+        for (path, content) in &self.files {
+            let target = self.root.join(path);
+            let name = target.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+            let temp = target.with_file_name(format!(".{}.rename", name));
+            if let Err(error) = std::fs::write(&temp, content) {
+                let _ = std::fs::remove_file(&temp);
+                for (staged, _) in &staged {
+                    let _ = std::fs::remove_file(staged);
+                }
+                return Err(error.into());
+            }
+            staged.push((temp, target));
+        }
+        // Originals of the files replaced so far, to put back on failure
+        let mut replaced: Vec<(&PathBuf, Vec<u8>)> = Vec::new();
+        for (index, (temp, target)) in staged.iter().enumerate() {
+            let moved = std::fs::read(target).and_then(|original| {
+                std::fs::rename(temp, target)?;
+                replaced.push((target, original));
+                Ok(())
+            });
+            if let Err(error) = moved {
+                for (target, original) in &replaced {
+                    let _ = std::fs::write(target, original);
+                }
+                for (temp, _) in &staged[index..] {
+                    let _ = std::fs::remove_file(temp);
+                }
+                return Err(error.into());
+            }
+        }
+        Ok(staged.len())
+    }
+
+    fn add(&mut self, path: &Path, locations: Vec<String>, content: String) {
+        self.edits.extend(locations.into_iter().map(|location| RenameEdit {
+            path: path.to_path_buf(),
+            location,
+        }));
+        self.files.insert(path.to_path_buf(), content);
+    }
+}
+
+/// Plan renaming `from` to `to` across the workspace at `root`.
+///
+/// Fails if nothing is named `from`, or if `to` is already in use, since
+/// merging two keys, events or functions is never what a rename means.
+pub fn plan_rename(root: &Path, kind: RenameKind, from: &str, to: &str) -> CanvasResult<RenamePlan> {
+    if to.trim().is_empty() || from == to {
+        return Err(CanvasError::Validation(format!("Cannot rename '{}' to '{}'", from, to)));
+    }
+    let mut plan = RenamePlan {
+        kind,
+        from: from.to_string(),
+        to: to.to_string(),
+        edits: Vec::new(),
+        stale_releases: Vec::new(),
+        root: root.to_path_buf(),
+        files: BTreeMap::new(),
+    };
+    let mut conflicts = Vec::new();
+
+    let workspace = Workspace::load(root)?;
+    let callers = match kind {
+        RenameKind::Function => calls_to(&workspace, from),
+        _ => HashSet::new(),
+    };
+    for (path, graph) in workspace.graphs() {
+        let mut renamed = graph.clone();
+        let locations = rename_in_graph(&mut renamed, kind, from, to, &callers);
+        let mut existing = graph.clone();
+        if !rename_in_graph(&mut existing, kind, to, to, &HashSet::new()).is_empty() {
+            conflicts.push(path.to_path_buf());
+        }
+        if !locations.is_empty() {
+            plan.add(path, locations, serde_json::to_string_pretty(&renamed)?);
+        }
+    }
+
+    for path in json_files(root)? {
+        let relative = path.strip_prefix(root).unwrap_or(&path).to_path_buf();
+        let name = relative.to_string_lossy();
+        let is_abi = name.ends_with(ABI_EXTENSION);
+        let rename: fn(&mut serde_json::Value, RenameKind, &str, &str) -> Vec<String> = if is_abi {
+            rename_in_abi
+        } else if name.ends_with(SCENARIO_EXTENSION) {
+            rename_in_scenario
+        } else {
+            continue;
+        };
+        let Ok(document) = serde_json::from_str::<serde_json::Value>(&std::fs::read_to_string(&path)?) else {
+            log::debug!("Skipping {}: not valid JSON", relative.display());
+            continue;
+        };
+        if is_abi && !rename(&mut document.clone(), kind, to, to).is_empty() {
+            conflicts.push(relative.clone());
+        }
+        let mut renamed = document;
+        let locations = rename(&mut renamed, kind, from, to);
+        if !locations.is_empty() {
+            plan.add(&relative, locations, serde_json::to_string_pretty(&renamed)?);
+        }
+    }
+
+    if !conflicts.is_empty() {
+        let paths: Vec<String> = conflicts.iter().map(|p| p.display().to_string()).collect();
+        return Err(CanvasError::Validation(format!(
+            "The {} '{}' is already used in {}",
+            kind.describe(),
+            to,
+            paths.join(", ")
+        )));
+    }
+    if plan.edits.is_empty() {
+        return Err(CanvasError::NotFound(format!("No {} named '{}' in {}", kind.describe(), from, root.display())));
+    }
+
+    let releases = ReleaseStore::new(root);
+    for environment in releases.environments()? {
+        let uses_old_name = releases
+            .latest(&environment)?
+            .and_then(|release| release.abi)
+            .and_then(|abi| serde_json::to_value(abi).ok())
+            .is_some_and(|mut abi| !rename_in_abi(&mut abi, kind, from, to).is_empty());
+        if uses_old_name {
+            plan.stale_releases.push(environment);
+        }
+    }
+    Ok(plan)
+}
+
+/// `TryCall` nodes calling `function` on a workspace contract that defines it
+fn calls_to(workspace: &Workspace, function: &str) -> HashSet<NodeId> {
+    let defines = |id: &uuid::Uuid| {
+        workspace.get(id).is_some_and(|graph| {
+            let mut graph = graph.clone();
+            graph.nodes.retain(|n| n.node_type == "Start");
+            !rename_in_graph(&mut graph, RenameKind::Function, function, function, &HashSet::new()).is_empty()
+        })
+    };
+    CallGraph::build(workspace)
+        .edges
+        .into_iter()
+        .filter(|edge| edge.kind == CallKind::Call)
+        .filter(|edge| matches!(&edge.callee, CallTarget::Contract(id) if defines(id)))
+        .flat_map(|edge| edge.via)
+        .collect()
+}
+
+/// Rename in the nodes of a graph, returning the changed locations
+fn rename_in_graph(
+    graph: &mut VisualGraph,
+    kind: RenameKind,
+    from: &str,
+    to: &str,
+    callers: &HashSet<NodeId>,
+) -> Vec<String> {
+    let single_start = graph.nodes.iter().filter(|n| n.node_type == "Start").count() == 1;
+    let mut locations = Vec::new();
+    for node in &mut graph.nodes {
+        let node_type = node.node_type.as_str();
+        let key = match kind {
+            RenameKind::StorageKey if STORAGE_NODE_TYPES.contains(&node_type) => "key",
+            RenameKind::StorageKey if BATCH_STORAGE_NODE_TYPES.contains(&node_type) => "keys",
+            RenameKind::Event if node_type == "EmitEvent" => "event",
+            RenameKind::Function if node_type == "Start" || (node_type == "TryCall" && callers.contains(&node.id)) => {
+                "function"
+            }
+            _ => continue,
+        };
+        // A graph's only Start node exports `main` unless it names its function
+        if node_type == "Start" && from == DEFAULT_ENTRY_POINT && single_start && !node.properties.contains_key(key) {
+            node.properties.insert(key.to_string(), serde_json::json!(to));
+            locations.push(format!("{}.properties.{}", node.id, key));
+            continue;
+        }
+        match node.properties.get_mut(key) {
+            Some(serde_json::Value::Array(items)) => {
+                for (index, item) in items.iter_mut().enumerate() {
+                    if item.as_str() == Some(from) {
+                        *item = serde_json::json!(to);
+                        locations.push(format!("{}.properties.{}[{}]", node.id, key, index));
+                    }
+                }
+            }
+            Some(value) if value.as_str() == Some(from) => {
+                *value = serde_json::json!(to);
+                locations.push(format!("{}.properties.{}", node.id, key));
+            }
+            _ => {}
+        }
+    }
+    locations
+}
+
+/// Rename the functions or events of an ABI document, and function selectors
+fn rename_in_abi(abi: &mut serde_json::Value, kind: RenameKind, from: &str, to: &str) -> Vec<String> {
+    let section = match kind {
+        RenameKind::Function => "functions",
+        RenameKind::Event => "events",
+        RenameKind::StorageKey => return Vec::new(),
+    };
+    let mut locations = Vec::new();
+    if let Some(items) = abi.get_mut(section).and_then(|v| v.as_array_mut()) {
+        for (index, item) in items.iter_mut().enumerate() {
+            if let Some(name) = item.get_mut("name").filter(|name| name.as_str() == Some(from)) {
+                *name = serde_json::json!(to);
+                locations.push(format!("{}[{}].name", section, index));
+            }
+        }
+    }
+    if kind == RenameKind::Function {
+        if let Some(metadata) = abi.get_mut("metadata").and_then(|v| v.as_object_mut()) {
+            let old = format!("selector.{}", from);
+            if metadata.remove(&old).is_some() {
+                let selector = format!("0x{:08x}", function_selector(to));
+                metadata.insert(format!("selector.{}", to), serde_json::json!(selector));
+                locations.push(format!("metadata.{}", old));
+            }
+        }
+    }
+    locations
+}
+
+/// Rename the functions called by a scenario's steps
+fn rename_in_scenario(scenario: &mut serde_json::Value, kind: RenameKind, from: &str, to: &str) -> Vec<String> {
+    if kind != RenameKind::Function {
+        return Vec::new();
+    }
+    let mut locations = Vec::new();
+    if let Some(steps) = scenario.get_mut("steps").and_then(|v| v.as_array_mut()) {
+        for (index, step) in steps.iter_mut().enumerate() {
+            if let Some(function) = step.get_mut("function").filter(|f| f.as_str() == Some(from)) {
+                *function = serde_json::json!(to);
+                locations.push(format!("steps[{}].function", index));
+            }
+        }
+    }
+    locations
+}
+
+/// JSON files under `root`, sorted, skipping hidden directories such as `.canvas`
+fn json_files(root: &Path) -> CanvasResult<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    let mut pending = vec![root.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        for entry in std::fs::read_dir(&dir)? {
+            let path = entry?.path();
+            let hidden = path.file_name().is_some_and(|n| n.to_string_lossy().starts_with('.'));
+            if hidden {
+                continue;
+            }
+            if path.is_dir() {
+                pending.push(path);
+            } else if path.extension().and_then(|e| e.to_str()) == Some("json") {
+                files.push(path);
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_graphs::node_with;
+
+    fn write(root: &Path, name: &str, value: &impl Serialize) {
+        std::fs::write(root.join(name), serde_json::to_string_pretty(value).unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_rename_function_across_workspace() {
+        let root = tempfile::tempdir().unwrap();
+        let mut token = VisualGraph::new("token");
+        token.add_node(node_with("Start", serde_json::json!({ "function": "transfer" })));
+        token.add_node(node_with("WriteStorage", serde_json::json!({ "key": "balance" })));
+        token.add_node(node_with("BatchReadStorage", serde_json::json!({ "keys": ["supply", "balance"] })));
+        let mut wallet = VisualGraph::new("wallet");
+        wallet.add_node(node_with("TryCall", serde_json::json!({ "target": "token", "function": "transfer" })));
+        wallet.add_node(node_with("TryCall", serde_json::json!({ "target": "0xdead", "function": "transfer" })));
+        write(root.path(), "token.json", &token);
+        write(root.path(), "wallet.json", &wallet);
+        write(
+            root.path(),
+            "token.abi.json",
+            &serde_json::json!({
+                "functions": [{ "name": "transfer" }],
+                "events": [],
+                "metadata": { "selector.transfer": "0x00000000" }
+            }),
+        );
+        write(root.path(), "token.scenario.json", &serde_json::json!({ "steps": [{ "function": "transfer" }] }));
+
+        let plan = plan_rename(root.path(), RenameKind::Function, "transfer", "send").unwrap();
+        let files: Vec<_> = plan.files().map(|p| p.to_string_lossy().to_string()).collect();
+        assert_eq!(files, ["token.abi.json", "token.json", "token.scenario.json", "wallet.json"]);
+        assert_eq!(plan.edits.len(), 5, "the call to an external contract keeps its name");
+        let before = std::fs::read_to_string(root.path().join("token.json")).unwrap();
+        assert!(before.contains("transfer"), "planning writes nothing");
+
+        assert_eq!(plan.apply().unwrap(), 4);
+        let abi: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(root.path().join("token.abi.json")).unwrap()).unwrap();
+        assert_eq!(abi["functions"][0]["name"], "send");
+        assert_eq!(abi["metadata"]["selector.send"], format!("0x{:08x}", function_selector("send")));
+        let wallet = std::fs::read_to_string(root.path().join("wallet.json")).unwrap();
+        assert!(wallet.contains("\"send\"") && wallet.contains("\"transfer\""));
+
+        let keys = plan_rename(root.path(), RenameKind::StorageKey, "balance", "balances").unwrap();
+        assert_eq!(keys.edits[1].location, format!("{}.properties.keys[1]", token.nodes[2].id));
+        assert!(matches!(
+            plan_rename(root.path(), RenameKind::StorageKey, "balance", "supply"),
+            Err(CanvasError::Validation(_))
+        ));
+        assert!(matches!(
+            plan_rename(root.path(), RenameKind::Event, "Missing", "Found"),
+            Err(CanvasError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_failed_apply_restores_replaced_files() {
+        let root = tempfile::tempdir().unwrap();
+        let mut token = VisualGraph::new("token");
+        token.add_node(node_with("Start", serde_json::json!({ "function": "transfer" })));
+        write(root.path(), "token.json", &token);
+        write(root.path(), "token.scenario.json", &serde_json::json!({ "steps": [{ "function": "transfer" }] }));
+        let plan = plan_rename(root.path(), RenameKind::Function, "transfer", "send").unwrap();
+        let before = std::fs::read_to_string(root.path().join("token.json")).unwrap();
+
+        // token.json is replaced first, then the scenario cannot be
+        std::fs::remove_file(root.path().join("token.scenario.json")).unwrap();
+        std::fs::create_dir(root.path().join("token.scenario.json")).unwrap();
+        assert!(plan.apply().is_err());
+        assert_eq!(std::fs::read_to_string(root.path().join("token.json")).unwrap(), before);
+        let leftovers: Vec<_> = std::fs::read_dir(root.path())
+            .unwrap()
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_name().to_string_lossy().ends_with(".rename"))
+            .collect();
+        assert!(leftovers.is_empty(), "staged files are cleaned up");
+    }
+}
@@ -0,0 +1,224 @@
+//! Overflow semantics for arithmetic nodes
+//!
+//! Every arithmetic node runs in one of three [`OverflowMode`]s. A node's own
+//! `overflow_mode` property wins; otherwise the project default from
+//! `compiler.overflow_mode` applies.
+
+use std::collections::HashMap;
+
+use crate::{
+    error::{CanvasError, CanvasResult},
+    types::{OverflowMode, VisualGraph, VisualNode},
+};
+
+/// Node types whose integer results are subject to an overflow mode
+pub const ARITHMETIC_NODE_TYPES: &[&str] = &["Add", "Subtract", "Multiply", "Divide"];
+
+/// Effective overflow mode of a node
+pub fn resolve_overflow_mode(node: &VisualNode, default: OverflowMode) -> OverflowMode {
+    node.properties
+        .get("overflow_mode")
+        .and_then(|v| v.as_str())
+        .and_then(OverflowMode::from_name)
+        .unwrap_or(default)
+}
+
+/// ABI metadata entries describing overflow behavior.
+///
+/// `overflow_mode` holds the project default; nodes that override it get an
+/// `overflow_mode.<node id>` entry.
+pub fn overflow_metadata(graph: &VisualGraph, default: OverflowMode) -> HashMap<String, String> {
+    let mut metadata = HashMap::new();
+    metadata.insert("overflow_mode".to_string(), default.as_str().to_string());
+
+    for node in graph
+        .nodes
+        .iter()
+        .filter(|n| ARITHMETIC_NODE_TYPES.contains(&n.node_type.as_str()))
+    {
+        let mode = resolve_overflow_mode(node, default);
+        if mode != default {
+            metadata.insert(format!("overflow_mode.{}", node.id), mode.as_str().to_string());
+        }
+    }
+    metadata
+}
+
+/// WAT instruction sequence for an i64 arithmetic operator under an overflow mode
+pub fn lower_arithmetic(operator: &str, mode: OverflowMode) -> CanvasResult<String> {
+    let op = match operator {
+        "add" | "+" => "add",
+        "sub" | "-" => "sub",
+        "mul" | "*" => "mul",
+        "div" | "/" => "div",
+        other => {
+            return Err(CanvasError::Compilation(format!(
+                "Unsupported arithmetic operator: {}",
+                other
+            )))
+        }
+    };
+
+    Ok(match (op, mode) {
+        // Native i64.div_s already traps on division by zero and MIN / -1
+        ("div", OverflowMode::Checked) => "i64.div_s".to_string(),
+        ("add", OverflowMode::Wrapping) | ("sub", OverflowMode::Wrapping) | ("mul", OverflowMode::Wrapping) => {
+            format!("i64.{}", op)
+        }
+        (op, mode) => format!("call ${}_{}", mode.as_str(), op),
+    })
+}
+
+/// Numeric code passed to decimal host calls for an overflow mode
+pub fn overflow_mode_code(mode: OverflowMode) -> i32 {
+    match mode {
+        OverflowMode::Checked => 0,
+        OverflowMode::Saturating => 1,
+        OverflowMode::Wrapping => 2,
+    }
+}
+
+/// Overflow mode of a code from [`overflow_mode_code`]
+pub fn overflow_mode_from_code(code: i32) -> Option<OverflowMode> {
+    match code {
+        0 => Some(OverflowMode::Checked),
+        1 => Some(OverflowMode::Saturating),
+        2 => Some(OverflowMode::Wrapping),
+        _ => None,
+    }
+}
+
+/// WAT instruction sequence for fixed-point arithmetic on scaled i64 operands.
+///
+/// Addition and subtraction are plain integer ops. Multiplication and division
+/// need a 128-bit intermediate, so they call into the host with the scale and
+/// overflow mode pushed after the two operands.
+pub fn lower_decimal_arithmetic(operator: &str, scale: u8, mode: OverflowMode) -> CanvasResult<String> {
+    let import = match operator {
+        "mul" | "*" => crate::wasm::host::HOST_DECIMAL_MUL,
+        "div" | "/" => crate::wasm::host::HOST_DECIMAL_DIV,
+        other => return lower_arithmetic(other, mode),
+    };
+    Ok(format!(
+        "i32.const {}\ni32.const {}\ncall ${}",
+        scale,
+        overflow_mode_code(mode),
+        import
+    ))
+}
+
+/// WAT helper functions referenced by [`lower_arithmetic`]
+pub fn overflow_helpers_wat() -> &'static str {
+    r#"
+  (func $checked_add (param $a i64) (param $b i64) (result i64)
+    (local $r i64)
+    (local.set $r (i64.add (local.get $a) (local.get $b)))
+    (if (i64.lt_s (i64.and (i64.xor (local.get $a) (local.get $r))
+                           (i64.xor (local.get $b) (local.get $r)))
+                  (i64.const 0))
+      (then unreachable))
+    (local.get $r))
+  (func $checked_sub (param $a i64) (param $b i64) (result i64)
+    (local $r i64)
+    (local.set $r (i64.sub (local.get $a) (local.get $b)))
+    (if (i64.lt_s (i64.and (i64.xor (local.get $a) (local.get $b))
+                           (i64.xor (local.get $a) (local.get $r)))
+                  (i64.const 0))
+      (then unreachable))
+    (local.get $r))
+  (func $mul_overflows (param $a i64) (param $b i64) (result i32)
+    (if (result i32) (i64.eqz (local.get $a))
+      (then (i32.const 0))
+      (else
+        (if (result i32) (i32.and (i64.eq (local.get $a) (i64.const -1))
+                                  (i64.eq (local.get $b) (i64.const 0x8000000000000000)))
+          (then (i32.const 1))
+          (else (i64.ne (i64.div_s (i64.mul (local.get $a) (local.get $b)) (local.get $a))
+                        (local.get $b)))))))
+  (func $checked_mul (param $a i64) (param $b i64) (result i64)
+    (if (call $mul_overflows (local.get $a) (local.get $b)) (then unreachable))
+    (i64.mul (local.get $a) (local.get $b)))
+  (func $saturate (param $negative i32) (result i64)
+    (if (result i64) (local.get $negative)
+      (then (i64.const 0x8000000000000000))
+      (else (i64.const 0x7fffffffffffffff))))
+  (func $saturating_add (param $a i64) (param $b i64) (result i64)
+    (local $r i64)
+    (local.set $r (i64.add (local.get $a) (local.get $b)))
+    (if (result i64) (i64.lt_s (i64.and (i64.xor (local.get $a) (local.get $r))
+                                        (i64.xor (local.get $b) (local.get $r)))
+                               (i64.const 0))
+      (then (call $saturate (i64.lt_s (local.get $a) (i64.const 0))))
+      (else (local.get $r))))
+  (func $saturating_sub (param $a i64) (param $b i64) (result i64)
+    (local $r i64)
+    (local.set $r (i64.sub (local.get $a) (local.get $b)))
+    (if (result i64) (i64.lt_s (i64.and (i64.xor (local.get $a) (local.get $b))
+                                        (i64.xor (local.get $a) (local.get $r)))
+                               (i64.const 0))
+      (then (call $saturate (i64.lt_s (local.get $a) (i64.const 0))))
+      (else (local.get $r))))
+  (func $saturating_mul (param $a i64) (param $b i64) (result i64)
+    (if (result i64) (call $mul_overflows (local.get $a) (local.get $b))
+      (then (call $saturate (i64.lt_s (i64.xor (local.get $a) (local.get $b)) (i64.const 0))))
+      (else (i64.mul (local.get $a) (local.get $b)))))
+  (func $saturating_div (param $a i64) (param $b i64) (result i64)
+    (if (result i64) (i32.and (i64.eq (local.get $a) (i64.const 0x8000000000000000))
+                              (i64.eq (local.get $b) (i64.const -1)))
+      (then (i64.const 0x7fffffffffffffff))
+      (else (i64.div_s (local.get $a) (local.get $b)))))
+  (func $wrapping_div (param $a i64) (param $b i64) (result i64)
+    (if (result i64) (i32.and (i64.eq (local.get $a) (i64.const 0x8000000000000000))
+                              (i64.eq (local.get $b) (i64.const -1)))
+      (then (local.get $a))
+      (else (i64.div_s (local.get $a) (local.get $b)))))
+"#
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Position;
+
+    #[test]
+    fn test_node_override_wins() {
+        let node = VisualNode::new(uuid::Uuid::new_v4(), "Add", Position::new(0.0, 0.0))
+            .with_property("overflow_mode", serde_json::json!("wrapping"));
+        assert_eq!(resolve_overflow_mode(&node, OverflowMode::Checked), OverflowMode::Wrapping);
+
+        let plain = VisualNode::new(uuid::Uuid::new_v4(), "Add", Position::new(0.0, 0.0));
+        assert_eq!(resolve_overflow_mode(&plain, OverflowMode::Saturating), OverflowMode::Saturating);
+    }
+
+    #[test]
+    fn test_overflow_metadata_lists_overrides() {
+        let mut graph = VisualGraph::new("math");
+        let node = VisualNode::new(uuid::Uuid::new_v4(), "Multiply", Position::new(0.0, 0.0))
+            .with_property("overflow_mode", serde_json::json!("saturating"));
+        let node_id = node.id;
+        graph.add_node(node);
+        graph.add_node(VisualNode::new(uuid::Uuid::new_v4(), "Add", Position::new(0.0, 0.0)));
+
+        let metadata = overflow_metadata(&graph, OverflowMode::Checked);
+        assert_eq!(metadata.get("overflow_mode").unwrap(), "checked");
+        assert_eq!(metadata.get(&format!("overflow_mode.{}", node_id)).unwrap(), "saturating");
+        assert_eq!(metadata.len(), 2);
+    }
+
+    #[test]
+    fn test_lowering() {
+        assert_eq!(lower_arithmetic("add", OverflowMode::Wrapping).unwrap(), "i64.add");
+        assert_eq!(lower_arithmetic("+", OverflowMode::Checked).unwrap(), "call $checked_add");
+        assert_eq!(lower_arithmetic("div", OverflowMode::Saturating).unwrap(), "call $saturating_div");
+        assert!(lower_arithmetic("pow", OverflowMode::Checked).is_err());
+    }
+
+    #[test]
+    fn test_decimal_lowering() {
+        assert_eq!(lower_decimal_arithmetic("add", 6, OverflowMode::Wrapping).unwrap(), "i64.add");
+        assert_eq!(
+            lower_decimal_arithmetic("mul", 6, OverflowMode::Saturating).unwrap(),
+            "i32.const 6\ni32.const 1\ncall $baals_decimal_mul"
+        );
+    }
+}
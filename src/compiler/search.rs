@@ -0,0 +1,261 @@
+//! Workspace-wide search
+//!
+//! Renaming a storage key or retiring a custom node means finding every
+//! place that uses it, across every graph of the workspace. A
+//! [`SearchQuery`] matches node types, property values, storage keys or
+//! custom node usages; each [`SearchHit`] points at one node of one graph
+//! and names the field that matched. Patterns match whole values, with `*`
+//! standing for any run of characters (`Batch*Storage`, `balance_*`).
+
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+use uuid::Uuid;
+
+use super::imports::Workspace;
+use crate::{
+    nodes::custom::VERSION_PROPERTY,
+    types::{NodeId, VisualGraph, VisualNode},
+};
+
+/// Node types that read or write storage under their `key` property
+pub const STORAGE_NODE_TYPES: &[&str] = &["ReadStorage", "WriteStorage"];
+
+/// Batched storage node types, keyed by their `keys` property
+pub const BATCH_STORAGE_NODE_TYPES: &[&str] = &["BatchReadStorage", "BatchWriteStorage"];
+
+/// Whole-value pattern where `*` matches any run of characters
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pattern(String);
+
+impl Pattern {
+    pub fn new(pattern: impl Into<String>) -> Self {
+        Self(pattern.into())
+    }
+
+    pub fn matches(&self, text: &str) -> bool {
+        let mut parts = self.0.split('*');
+        let first = parts.next().unwrap_or_default();
+        let Some(mut rest) = text.strip_prefix(first) else {
+            return false;
+        };
+        let parts: Vec<&str> = parts.collect();
+        let Some((last, middle)) = parts.split_last() else {
+            return rest.is_empty();
+        };
+        for part in middle {
+            match rest.find(part) {
+                Some(index) => rest = &rest[index + part.len()..],
+                None => return false,
+            }
+        }
+        rest.len() >= last.len() && rest.ends_with(last)
+    }
+}
+
+/// What to look for
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SearchQuery {
+    /// Nodes of a type
+    NodeType(Pattern),
+    /// Property values, optionally of one property only (`None` for any)
+    Property { name: Option<Pattern>, value: Pattern },
+    /// Fixed keys of storage reads and writes, batched or not
+    StorageKey(Pattern),
+    /// Uses of a custom node, reported with the version they pin
+    CustomNode(Pattern),
+    /// Any of the above
+    Any(Pattern),
+}
+
+impl SearchQuery {
+    /// Query of a kind named on the command line: type, property (with an
+    /// optional `name=` prefix on the pattern), storage, custom or any
+    pub fn parse(kind: &str, pattern: &str) -> Option<Self> {
+        Some(match kind {
+            "type" => SearchQuery::NodeType(Pattern::new(pattern)),
+            "property" => match pattern.split_once('=') {
+                Some((name, value)) => SearchQuery::Property {
+                    name: Some(Pattern::new(name)),
+                    value: Pattern::new(value),
+                },
+                None => SearchQuery::Property {
+                    name: None,
+                    value: Pattern::new(pattern),
+                },
+            },
+            "storage" => SearchQuery::StorageKey(Pattern::new(pattern)),
+            "custom" => SearchQuery::CustomNode(Pattern::new(pattern)),
+            "any" => SearchQuery::Any(Pattern::new(pattern)),
+            _ => return None,
+        })
+    }
+}
+
+/// A node that matched, and where
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SearchHit {
+    /// Workspace-relative path of the graph file
+    pub path: PathBuf,
+    pub graph_id: Uuid,
+    pub node_id: NodeId,
+    pub node_type: String,
+    /// Matched field: `node_type`, `properties.<name>` or `properties.<name>[<index>]`
+    pub field: String,
+    pub value: String,
+}
+
+/// Search every graph of a workspace, in path order
+pub fn search_workspace(workspace: &Workspace, query: &SearchQuery) -> Vec<SearchHit> {
+    workspace
+        .graphs()
+        .into_iter()
+        .flat_map(|(path, graph)| search_graph(path, graph, query))
+        .collect()
+}
+
+/// Search the nodes of one graph, in node order
+pub fn search_graph(path: &Path, graph: &VisualGraph, query: &SearchQuery) -> Vec<SearchHit> {
+    let mut hits = Vec::new();
+    for node in &graph.nodes {
+        let mut hit = |field: String, value: String| {
+            let hit = SearchHit {
+                path: path.to_path_buf(),
+                graph_id: graph.id,
+                node_id: node.id,
+                node_type: node.node_type.clone(),
+                field,
+                value,
+            };
+            // A node matching several ways (e.g. under `Any`) is reported once per field
+            if !hits.contains(&hit) {
+                hits.push(hit);
+            }
+        };
+        match query {
+            SearchQuery::NodeType(pattern) => match_node_type(node, pattern, &mut hit),
+            SearchQuery::Property { name, value } => match_properties(node, name.as_ref(), value, &mut hit),
+            SearchQuery::StorageKey(pattern) => match_storage_keys(node, pattern, &mut hit),
+            SearchQuery::CustomNode(pattern) => match_custom_node(node, pattern, &mut hit),
+            SearchQuery::Any(pattern) => {
+                match_node_type(node, pattern, &mut hit);
+                match_properties(node, None, pattern, &mut hit);
+            }
+        }
+    }
+    hits
+}
+
+fn match_node_type(node: &VisualNode, pattern: &Pattern, hit: &mut impl FnMut(String, String)) {
+    if pattern.matches(&node.node_type) {
+        hit("node_type".to_string(), node.node_type.clone());
+    }
+}
+
+fn match_properties(
+    node: &VisualNode,
+    name: Option<&Pattern>,
+    value: &Pattern,
+    hit: &mut impl FnMut(String, String),
+) {
+    let mut properties: Vec<_> = node.properties.iter().collect();
+    properties.sort_by(|a, b| a.0.cmp(b.0));
+    for (key, property) in properties {
+        if name.is_some_and(|name| !name.matches(key)) {
+            continue;
+        }
+        match property {
+            serde_json::Value::Array(items) => {
+                for (index, item) in items.iter().enumerate() {
+                    let text = display_value(item);
+                    if value.matches(&text) {
+                        hit(format!("properties.{}[{}]", key, index), text);
+                    }
+                }
+            }
+            other => {
+                let text = display_value(other);
+                if value.matches(&text) {
+                    hit(format!("properties.{}", key), text);
+                }
+            }
+        }
+    }
+}
+
+fn match_storage_keys(node: &VisualNode, pattern: &Pattern, hit: &mut impl FnMut(String, String)) {
+    let node_type = node.node_type.as_str();
+    if STORAGE_NODE_TYPES.contains(&node_type) {
+        match_properties(node, Some(&Pattern::new("key")), pattern, hit);
+    } else if BATCH_STORAGE_NODE_TYPES.contains(&node_type) {
+        match_properties(node, Some(&Pattern::new("keys")), pattern, hit);
+    }
+}
+
+fn match_custom_node(node: &VisualNode, pattern: &Pattern, hit: &mut impl FnMut(String, String)) {
+    if !pattern.matches(&node.node_type) {
+        return;
+    }
+    let pinned = node.properties.get(VERSION_PROPERTY).map(display_value);
+    let value = match pinned {
+        Some(version) => format!("{}@{}", node.node_type, version),
+        None => format!("{} (unpinned)", node.node_type),
+    };
+    hit("node_type".to_string(), value);
+}
+
+/// Strings as they are, anything else as JSON
+fn display_value(value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::String(text) => text.clone(),
+        other => other.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_graphs::node_with;
+
+    #[test]
+    fn test_search_finds_node_level_locations() {
+        let mut token = VisualGraph::new("token");
+        token.add_node(node_with("WriteStorage", serde_json::json!({ "key": "balance_owner" })));
+        token.add_node(node_with("BatchReadStorage", serde_json::json!({ "keys": ["supply", "balance_alice"] })));
+        token.add_node(node_with("Log", serde_json::json!({ "message": "balance_owner" })));
+        let mut vault = VisualGraph::new("vault");
+        vault.add_node(node_with("acme.RateLimiter", serde_json::json!({ "version": "^1.2", "limit": 10 })));
+        vault.add_node(node_with("acme.RateLimiter", serde_json::json!({})));
+
+        let mut workspace = Workspace::new();
+        workspace.add_graph("vault.json", vault.clone()).unwrap();
+        workspace.add_graph("token.json", token.clone()).unwrap();
+
+        let keys = search_workspace(&workspace, &SearchQuery::parse("storage", "balance_*").unwrap());
+        let found: Vec<_> = keys.iter().map(|h| (h.node_id, h.field.as_str(), h.value.as_str())).collect();
+        assert_eq!(
+            found,
+            [
+                (token.nodes[0].id, "properties.key", "balance_owner"),
+                (token.nodes[1].id, "properties.keys[1]", "balance_alice"),
+            ],
+            "log messages are not storage keys"
+        );
+        assert!(keys.iter().all(|h| h.path == Path::new("token.json") && h.graph_id == token.id));
+
+        let usages = search_workspace(&workspace, &SearchQuery::parse("custom", "acme.*").unwrap());
+        let versions: Vec<_> = usages.iter().map(|h| h.value.as_str()).collect();
+        assert_eq!(versions, ["acme.RateLimiter@^1.2", "acme.RateLimiter (unpinned)"]);
+
+        let property = SearchQuery::parse("property", "limit=10").unwrap();
+        assert_eq!(search_workspace(&workspace, &property)[0].node_id, vault.nodes[0].id);
+        assert_eq!(search_workspace(&workspace, &SearchQuery::parse("type", "Batch*Storage").unwrap()).len(), 1);
+        assert_eq!(search_workspace(&workspace, &SearchQuery::parse("any", "balance_owner").unwrap()).len(), 2);
+        assert!(SearchQuery::parse("fuzzy", "x").is_none());
+
+        assert!(Pattern::new("a*b*c").matches("axxbyyc"));
+        assert!(Pattern::new("*").matches(""));
+        assert!(!Pattern::new("ab*ba").matches("aba"));
+        assert!(!Pattern::new("balance").matches("balance_owner"));
+    }
+}
@@ -0,0 +1,206 @@
+//! Template slots
+//!
+//! A template can leave parts of a contract to its user: a `Slot` node marks
+//! the place ("auction skeleton: provide your pricing logic here") and its
+//! ports give the signature the missing logic must have. The user fills the
+//! slot with a graph of their own, named by the node's `fill` property: its
+//! Start node must output every data input of the slot and its End node take
+//! every data output, with compatible types. Filling inlines the graph like an
+//! `Import` node does, so the result is type checked as one graph. A template
+//! with an empty slot does not compile.
+
+use serde::Serialize;
+
+use super::imports::{inline, resolve_imports, Workspace};
+use crate::{
+    error::{CanvasError, CanvasResult},
+    types::{NodeId, Port, ValueType, VisualGraph, VisualNode},
+};
+
+/// Node type of slot placeholders
+pub const SLOT_NODE_TYPE: &str = "Slot";
+
+/// A slot of a template, as offered to its user
+#[derive(Debug, Clone, Serialize)]
+pub struct SlotSignature {
+    pub node_id: NodeId,
+    pub name: String,
+    pub hint: Option<String>,
+    /// Values the filling graph receives, as outputs of its Start node
+    pub inputs: Vec<Port>,
+    /// Values the filling graph produces, as inputs of its End node
+    pub outputs: Vec<Port>,
+    /// Graph filling the slot, if any
+    pub fill: Option<uuid::Uuid>,
+}
+
+impl SlotSignature {
+    fn of(node: &VisualNode) -> CanvasResult<Self> {
+        let name = node
+            .properties
+            .get("name")
+            .and_then(|v| v.as_str())
+            .filter(|name| !name.is_empty())
+            .ok_or_else(|| CanvasError::Compilation(format!("Slot node {} needs a 'name' property", node.id)))?;
+        let fill = match node.properties.get("fill").and_then(|v| v.as_str()) {
+            Some(id) => Some(uuid::Uuid::parse_str(id).map_err(|_| {
+                CanvasError::Compilation(format!("Slot '{}' has an invalid 'fill' graph ID '{}'", name, id))
+            })?),
+            None => None,
+        };
+        let data = |ports: &[Port]| ports.iter().filter(|p| p.value_type != ValueType::Flow).cloned().collect();
+        Ok(Self {
+            node_id: node.id,
+            name: name.to_string(),
+            hint: node.properties.get("hint").and_then(|v| v.as_str()).map(str::to_string),
+            inputs: data(&node.inputs),
+            outputs: data(&node.outputs),
+            fill,
+        })
+    }
+
+    /// Check that `fill` has this slot's signature
+    pub fn check(&self, fill: &VisualGraph) -> CanvasResult<()> {
+        let boundary = |node_type: &str| fill.nodes.iter().find(|n| n.node_type == node_type);
+        let start = boundary("Start").ok_or_else(|| {
+            CanvasError::Compilation(format!("Graph '{}' filling slot '{}' has no Start node", fill.name, self.name))
+        })?;
+        check_ports(self, fill, &self.inputs, &start.outputs, "Start output")?;
+        if !self.outputs.is_empty() {
+            let end = boundary("End").ok_or_else(|| {
+                CanvasError::Compilation(format!("Graph '{}' filling slot '{}' has no End node", fill.name, self.name))
+            })?;
+            check_ports(self, fill, &self.outputs, &end.inputs, "End input")?;
+        }
+        Ok(())
+    }
+}
+
+fn check_ports(
+    slot: &SlotSignature,
+    fill: &VisualGraph,
+    expected: &[Port],
+    actual: &[Port],
+    side: &str,
+) -> CanvasResult<()> {
+    for port in expected {
+        let found = actual.iter().find(|p| p.id == port.id).ok_or_else(|| {
+            CanvasError::Type(format!(
+                "Graph '{}' filling slot '{}' has no {} '{}'",
+                fill.name, slot.name, side, port.id
+            ))
+        })?;
+        if !port.value_type.is_compatible_with(&found.value_type) {
+            return Err(CanvasError::Type(format!(
+                "Slot '{}' expects {} '{}' to be {:?}, but '{}' has {:?}",
+                slot.name, side, port.id, port.value_type, fill.name, found.value_type
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Slots of a template, in node order
+pub fn template_slots(graph: &VisualGraph) -> CanvasResult<Vec<SlotSignature>> {
+    let slots = graph
+        .nodes
+        .iter()
+        .filter(|n| n.node_type == SLOT_NODE_TYPE)
+        .map(SlotSignature::of)
+        .collect::<CanvasResult<Vec<_>>>()?;
+    for (index, slot) in slots.iter().enumerate() {
+        if slots[..index].iter().any(|s| s.name == slot.name) {
+            return Err(CanvasError::Compilation(format!("Slot '{}' is defined twice", slot.name)));
+        }
+    }
+    Ok(slots)
+}
+
+/// Point the slot called `name` at the graph `fill`
+pub fn set_slot_fill(graph: &mut VisualGraph, name: &str, fill: &VisualGraph) -> CanvasResult<()> {
+    let slot = template_slots(graph)?
+        .into_iter()
+        .find(|s| s.name == name)
+        .ok_or_else(|| CanvasError::NotFound(format!("Slot '{}' in template '{}'", name, graph.name)))?;
+    slot.check(fill)?;
+    let node = graph.nodes.iter_mut().find(|n| n.id == slot.node_id).expect("slot node found above");
+    node.properties.insert("fill".to_string(), serde_json::json!(fill.id.to_string()));
+    Ok(())
+}
+
+/// Replace every slot with the workspace graph filling it.
+///
+/// Fails on the first slot left empty, naming it and its hint, or filled
+/// with a graph that doesn't match its signature.
+pub fn fill_slots(graph: &VisualGraph, workspace: &Workspace) -> CanvasResult<VisualGraph> {
+    let mut result = graph.clone();
+    for slot in template_slots(graph)? {
+        let Some(fill) = slot.fill else {
+            let hint = slot.hint.as_deref().map(|h| format!(": {}", h)).unwrap_or_default();
+            return Err(CanvasError::Compilation(format!("Slot '{}' must be filled{}", slot.name, hint)));
+        };
+        let filling = workspace.get(&fill).ok_or_else(|| {
+            CanvasError::Compilation(format!(
+                "Slot '{}' is filled by graph {} which is not in the workspace",
+                slot.name, fill
+            ))
+        })?;
+        slot.check(filling)?;
+        let filling = resolve_imports(filling, workspace)?;
+        let node = graph.nodes.iter().find(|n| n.id == slot.node_id).expect("slot collected from this graph");
+        inline(&mut result, node, &filling)?;
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_graphs::{connect, node};
+
+    fn pricing(bid_type: ValueType) -> VisualGraph {
+        let mut graph = VisualGraph::new("pricing");
+        let start = node("Start").with_outputs(vec![Port::new("bid", "Bid", bid_type)]);
+        let double = node("Multiply");
+        let end = node("End").with_inputs(vec![Port::new("price", "Price", ValueType::Integer)]);
+        connect(&mut graph, &start, "bid", &double, "a");
+        connect(&mut graph, &double, "result", &end, "price");
+        graph.add_node(start);
+        graph.add_node(double);
+        graph.add_node(end);
+        graph
+    }
+
+    #[test]
+    fn test_slot_must_be_filled_with_matching_signature() {
+        let mut auction = VisualGraph::new("auction skeleton");
+        let bid = node("ReadStorage");
+        let slot = node(SLOT_NODE_TYPE)
+            .with_property("name", serde_json::json!("pricing"))
+            .with_property("hint", serde_json::json!("provide your pricing logic here"))
+            .with_inputs(vec![Port::new("bid", "Bid", ValueType::Integer)])
+            .with_outputs(vec![Port::new("price", "Price", ValueType::Integer)]);
+        let store = node("WriteStorage");
+        connect(&mut auction, &bid, "value", &slot, "bid");
+        connect(&mut auction, &slot, "price", &store, "value");
+        auction.add_node(bid);
+        auction.add_node(slot);
+        auction.add_node(store);
+
+        let mut workspace = Workspace::new();
+        let error = fill_slots(&auction, &workspace).unwrap_err().to_string();
+        assert!(error.contains("Slot 'pricing' must be filled: provide your pricing logic here"), "{}", error);
+
+        let wrong = pricing(ValueType::String);
+        assert!(matches!(set_slot_fill(&mut auction, "pricing", &wrong), Err(CanvasError::Type(_))));
+        assert!(matches!(set_slot_fill(&mut auction, "bidding", &wrong), Err(CanvasError::NotFound(_))));
+
+        let logic = pricing(ValueType::Integer);
+        set_slot_fill(&mut auction, "pricing", &logic).unwrap();
+        workspace.add_graph("pricing.json", logic).unwrap();
+        let filled = fill_slots(&auction, &workspace).unwrap();
+        assert!(filled.nodes.iter().all(|n| n.node_type != SLOT_NODE_TYPE));
+        assert!(filled.nodes.iter().any(|n| n.node_type == "Multiply"));
+        assert_eq!(filled.connections.len(), 2, "bid -> multiply -> store");
+    }
+}
@@ -0,0 +1,287 @@
+//! Worst-case stack depth and recursion analysis
+//!
+//! Each composite node compiles to a function of its own, so the deepest
+//! chain of composites inside composites sets how many frames the generated
+//! module can push below an entry point. Cross-contract calls (`TryCall`)
+//! start a frame chain in the callee; the longest call chain through the
+//! workspace's [call graph](super::CallGraph) is checked separately. Imports
+//! are inlined and add no depth of their own.
+//!
+//! A composite that (transitively) contains itself, or a contract that
+//! (transitively) calls itself, has no static bound and always fails the
+//! check. Calls to contracts outside the workspace count as one level.
+
+use std::collections::HashMap;
+
+use serde::Serialize;
+use uuid::Uuid;
+
+use super::{CallGraph, CallKind, CallTarget, Workspace};
+use crate::{
+    config::NetworkProfile,
+    error::{CanvasError, CanvasResult},
+    nodes::custom::{CustomNodeImplementation, CustomNodeRegistry},
+    types::VisualGraph,
+};
+
+/// What a recursive cycle runs through
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RecursionKind {
+    /// Composite nodes nested inside themselves
+    Composite,
+    /// Contracts calling back into themselves
+    CrossContract,
+}
+
+/// A cycle with no static depth bound
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Recursion {
+    pub kind: RecursionKind,
+    /// Composite node types or contract names, ending where the cycle closes
+    pub cycle: Vec<String>,
+}
+
+/// Worst-case depths of a contract
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct StackDepthReport {
+    /// Frames on the deepest path: the entry point plus nested composites
+    pub nesting_depth: u32,
+    /// Composite node types on the deepest path, outermost first
+    pub deepest_path: Vec<String>,
+    /// Longest chain of cross-contract calls the contract can start
+    pub call_depth: u32,
+    pub recursion: Vec<Recursion>,
+}
+
+impl StackDepthReport {
+    /// Fail if the contract may recurse without bound or exceeds the network's limits
+    pub fn check(&self, network: &NetworkProfile) -> CanvasResult<()> {
+        let mut problems = Vec::new();
+        for recursion in &self.recursion {
+            let kind = match recursion.kind {
+                RecursionKind::Composite => "composite nodes",
+                RecursionKind::CrossContract => "cross-contract calls",
+            };
+            problems.push(format!("possible unbounded recursion through {}: {}", kind, recursion.cycle.join(" -> ")));
+        }
+        if self.nesting_depth > network.max_nesting_depth {
+            problems.push(format!(
+                "nesting depth {} exceeds the '{}' limit of {} ({})",
+                self.nesting_depth,
+                network.name,
+                network.max_nesting_depth,
+                self.deepest_path.join(" -> ")
+            ));
+        }
+        if self.call_depth > network.max_call_depth {
+            problems.push(format!(
+                "cross-contract call depth {} exceeds the '{}' limit of {}",
+                self.call_depth, network.name, network.max_call_depth
+            ));
+        }
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(CanvasError::Compilation(format!("Stack depth check failed: {}", problems.join("; "))))
+        }
+    }
+}
+
+/// Deepest composite nesting below `graph`, as (levels, node types)
+fn composite_depth(
+    graph: &VisualGraph,
+    registry: &CustomNodeRegistry,
+    stack: &mut Vec<String>,
+    recursion: &mut Vec<Recursion>,
+) -> (u32, Vec<String>) {
+    let mut deepest = (0, Vec::new());
+    for node in &graph.nodes {
+        let Some(definition) = registry.get_node(&node.node_type) else {
+            continue;
+        };
+        let CustomNodeImplementation::Composite { sub_graph } = &definition.implementation else {
+            continue;
+        };
+        if let Some(start) = stack.iter().position(|t| *t == node.node_type) {
+            let mut cycle = stack[start..].to_vec();
+            cycle.push(node.node_type.clone());
+            if !recursion.iter().any(|r| r.kind == RecursionKind::Composite && same_cycle(&r.cycle, &cycle)) {
+                recursion.push(Recursion { kind: RecursionKind::Composite, cycle });
+            }
+            continue;
+        }
+
+        let (depth, mut path) = match serde_json::from_str::<VisualGraph>(sub_graph) {
+            Ok(sub_graph) => {
+                stack.push(node.node_type.clone());
+                let inner = composite_depth(&sub_graph, registry, stack, recursion);
+                stack.pop();
+                inner
+            }
+            Err(e) => {
+                log::debug!("Composite node '{}' has no readable sub-graph: {}", node.node_type, e);
+                (0, Vec::new())
+            }
+        };
+        if depth + 1 > deepest.0 {
+            path.insert(0, node.node_type.clone());
+            deepest = (depth + 1, path);
+        }
+    }
+    deepest
+}
+
+/// Whether two closed cycles are rotations of each other
+fn same_cycle<T: PartialEq>(a: &[T], b: &[T]) -> bool {
+    let (a, b) = (&a[..a.len().saturating_sub(1)], &b[..b.len().saturating_sub(1)]);
+    a.len() == b.len() && (0..a.len()).any(|shift| a.iter().cycle().skip(shift).zip(b).all(|(x, y)| x == y))
+}
+
+struct CallWalk<'a> {
+    call_graph: &'a CallGraph,
+    /// Contracts being walked, with how each was reached
+    stack: Vec<(Uuid, CallKind)>,
+    finished: HashMap<Uuid, u32>,
+    recursion: Vec<Recursion>,
+}
+
+impl CallWalk<'_> {
+    fn depth(&mut self, contract: Uuid) -> u32 {
+        if let Some(depth) = self.finished.get(&contract) {
+            return *depth;
+        }
+        let mut deepest = 0;
+        for edge in self.call_graph.callees(contract) {
+            let step = u32::from(edge.kind == CallKind::Call);
+            let callee = match &edge.callee {
+                CallTarget::External(_) => {
+                    deepest = deepest.max(step);
+                    continue;
+                }
+                CallTarget::Contract(id) => *id,
+            };
+            if let Some(start) = self.stack.iter().position(|(id, _)| *id == callee) {
+                // Import-only cycles are rejected when imports are resolved
+                let calls = self.stack[start + 1..].iter().any(|(_, kind)| *kind == CallKind::Call) || step == 1;
+                if calls {
+                    let mut cycle: Vec<_> = self.stack[start..].iter().map(|(id, _)| self.name(*id)).collect();
+                    cycle.push(self.name(callee));
+                    if !self.recursion.iter().any(|r| same_cycle(&r.cycle, &cycle)) {
+                        self.recursion.push(Recursion { kind: RecursionKind::CrossContract, cycle });
+                    }
+                }
+                continue;
+            }
+            self.stack.push((callee, edge.kind));
+            let depth = self.depth(callee) + step;
+            self.stack.pop();
+            deepest = deepest.max(depth);
+        }
+        self.finished.insert(contract, deepest);
+        deepest
+    }
+
+    fn name(&self, id: Uuid) -> String {
+        self.call_graph.contracts.get(&id).cloned().unwrap_or_else(|| id.to_string())
+    }
+}
+
+/// Worst-case nesting and call depth of `graph`, using `workspace` to follow
+/// cross-contract calls
+pub fn analyze_stack_depth(
+    graph: &VisualGraph,
+    registry: &CustomNodeRegistry,
+    workspace: &Workspace,
+) -> CanvasResult<StackDepthReport> {
+    let mut recursion = Vec::new();
+    let (composites, deepest_path) = composite_depth(graph, registry, &mut Vec::new(), &mut recursion);
+
+    let call_graph = if workspace.get(&graph.id).is_some() {
+        CallGraph::build(workspace)
+    } else {
+        let mut workspace = workspace.clone();
+        workspace.add_graph(format!("{}.json", graph.id), graph.clone())?;
+        CallGraph::build(&workspace)
+    };
+    let mut walk = CallWalk {
+        call_graph: &call_graph,
+        stack: vec![(graph.id, CallKind::Call)],
+        finished: HashMap::new(),
+        recursion: Vec::new(),
+    };
+    let call_depth = walk.depth(graph.id);
+    recursion.extend(walk.recursion);
+
+    Ok(StackDepthReport {
+        nesting_depth: composites + 1,
+        deepest_path,
+        call_depth,
+        recursion,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::ADDRESS_METADATA_KEY;
+    use crate::nodes::custom::CustomNodeBuilder;
+    use crate::test_graphs::node;
+
+    fn composite(id: &str, inner: &[&str]) -> crate::nodes::custom::CustomNodeDefinition {
+        let mut sub_graph = VisualGraph::new(id);
+        for node_type in inner {
+            sub_graph.add_node(node(node_type));
+        }
+        CustomNodeBuilder::new(id.to_string(), id.to_string())
+            .composite(serde_json::to_string(&sub_graph).unwrap())
+            .build()
+    }
+
+    #[test]
+    fn test_nesting_depth_and_recursion() {
+        let mut registry = CustomNodeRegistry::new();
+        registry.register_node(composite("Outer", &["Middle", "Add"])).unwrap();
+        registry.register_node(composite("Middle", &["Inner"])).unwrap();
+        registry.register_node(composite("Inner", &["Add"])).unwrap();
+
+        let mut graph = VisualGraph::new("Vault");
+        graph.add_node(node("Start"));
+        graph.add_node(node("Inner"));
+        graph.add_node(node("Outer"));
+        let mut call = node("TryCall");
+        call.properties.insert("target".to_string(), serde_json::json!("0xt0"));
+        graph.add_node(call);
+
+        let mut token = VisualGraph::new("Token");
+        token.metadata.insert(ADDRESS_METADATA_KEY.to_string(), "0xT0".to_string());
+        let mut workspace = Workspace::new();
+        workspace.add_graph("token.json", token.clone()).unwrap();
+
+        let report = analyze_stack_depth(&graph, &registry, &workspace).unwrap();
+        assert_eq!(report.nesting_depth, 4);
+        assert_eq!(report.deepest_path, ["Outer", "Middle", "Inner"]);
+        assert_eq!(report.call_depth, 1);
+        assert!(report.recursion.is_empty());
+
+        let mut network = NetworkProfile::default();
+        assert!(report.check(&network).is_ok());
+        network.max_nesting_depth = 3;
+        assert!(report.check(&network).unwrap_err().to_string().contains("nesting depth 4"));
+
+        // Token calling back into the vault closes a cycle
+        let mut callback = node("TryCall");
+        callback.properties.insert("target".to_string(), serde_json::json!("Vault"));
+        token.add_node(callback);
+        let mut workspace = Workspace::new();
+        workspace.add_graph("token.json", token).unwrap();
+        registry.register_node(composite("Loop", &["Loop"])).unwrap();
+        graph.add_node(node("Loop"));
+
+        let report = analyze_stack_depth(&graph, &registry, &workspace).unwrap();
+        let kinds: Vec<_> = report.recursion.iter().map(|r| r.kind).collect();
+        assert_eq!(kinds, [RecursionKind::Composite, RecursionKind::CrossContract]);
+        assert_eq!(report.recursion[1].cycle, ["Vault", "Token", "Vault"]);
+        assert!(report.check(&NetworkProfile::default()).is_err());
+    }
+}
@@ -0,0 +1,168 @@
+//! Storage schema of a contract ABI
+//!
+//! Every fixed key a storage node reads or writes becomes a storage entry. Its
+//! type comes from the graph's `state.<key>` declaration when there is one,
+//! otherwise from the value wired into the writes and the value port of the
+//! reads. Keys computed at runtime have no fixed name and are left out.
+
+use std::collections::BTreeMap;
+
+use crate::{
+    dsl::STATE_METADATA_PREFIX,
+    types::{ContractABI, StorageABI, ValueType, VisualGraph, VisualNode},
+};
+
+/// Fixed keys a storage node touches
+fn fixed_keys(graph: &VisualGraph, node: &VisualNode) -> Vec<String> {
+    let dynamic_key = graph
+        .connections
+        .iter()
+        .any(|c| c.target_node == node.id && c.target_port == "key");
+    if dynamic_key {
+        return Vec::new();
+    }
+    if let Some(key) = node.properties.get("key").and_then(|v| v.as_str()) {
+        return vec![key.to_string()];
+    }
+    node.properties
+        .get("keys")
+        .and_then(|v| v.as_array())
+        .map(|keys| keys.iter().filter_map(|k| k.as_str().map(str::to_string)).collect())
+        .unwrap_or_default()
+}
+
+/// Type of the value a single-key storage node stores or loads
+fn value_type(graph: &VisualGraph, node: &VisualNode) -> ValueType {
+    match node.node_type.as_str() {
+        "WriteStorage" => graph
+            .connections
+            .iter()
+            .find(|c| c.target_node == node.id && c.target_port == "value")
+            .and_then(|c| {
+                graph
+                    .get_node(c.source_node)
+                    .and_then(|source| source.outputs.iter().find(|p| p.id == c.source_port))
+            })
+            .or_else(|| node.inputs.iter().find(|p| p.id == "value"))
+            .map_or(ValueType::Any, |p| p.value_type.clone()),
+        "ReadStorage" => node
+            .outputs
+            .iter()
+            .find(|p| p.id == "value")
+            .map_or(ValueType::Any, |p| p.value_type.clone()),
+        // Batch nodes move arrays of values, so no single key's type is known
+        _ => ValueType::Any,
+    }
+}
+
+/// Merge a type seen at another node into an entry's type; conflicting
+/// concrete types widen the entry to `Any`
+fn merge(entry: &mut StorageABI, seen: ValueType) {
+    if seen == ValueType::Any || entry.value_type == seen {
+        return;
+    }
+    if entry.value_type == ValueType::Any {
+        entry.value_type = seen;
+    } else {
+        log::warn!(
+            "Storage key '{}' holds both {:?} and {:?}; typing it as any",
+            entry.key,
+            entry.value_type,
+            seen
+        );
+        entry.value_type = ValueType::Any;
+    }
+}
+
+/// Storage entries of a graph, sorted by key
+pub fn storage_schema(graph: &VisualGraph) -> Vec<StorageABI> {
+    let declared: BTreeMap<&str, ValueType> = graph
+        .metadata
+        .iter()
+        .filter_map(|(key, ty)| {
+            key.strip_prefix(STATE_METADATA_PREFIX)
+                .map(|name| (name, ValueType::from_name(ty).unwrap_or(ValueType::Any)))
+        })
+        .collect();
+
+    let mut entries: BTreeMap<String, StorageABI> = declared
+        .iter()
+        .map(|(&key, value_type)| {
+            let entry = StorageABI {
+                key: key.to_string(),
+                value_type: value_type.clone(),
+                writable: false,
+            };
+            (key.to_string(), entry)
+        })
+        .collect();
+
+    for node in &graph.nodes {
+        let writes = match node.node_type.as_str() {
+            "WriteStorage" | "BatchWriteStorage" => true,
+            "ReadStorage" | "BatchReadStorage" => false,
+            _ => continue,
+        };
+        let seen = value_type(graph, node);
+        for key in fixed_keys(graph, node) {
+            let entry = entries.entry(key.clone()).or_insert_with(|| StorageABI {
+                key: key.clone(),
+                value_type: ValueType::Any,
+                writable: false,
+            });
+            entry.writable |= writes;
+            if !declared.contains_key(key.as_str()) {
+                merge(entry, seen.clone());
+            }
+        }
+    }
+    entries.into_values().collect()
+}
+
+/// Fill in the storage schema of an ABI
+pub fn add_storage_schema(graph: &VisualGraph, abi: &mut ContractABI) {
+    abi.storage = storage_schema(graph);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_graphs::{connect, node};
+    use crate::types::Port;
+
+    #[test]
+    fn test_storage_types_from_declarations_and_writes() {
+        let mut graph = VisualGraph::new("vault");
+        graph.metadata.insert(format!("{}owner", STATE_METADATA_PREFIX), "string".to_string());
+        let start = node("Start").with_outputs(vec![
+            Port::new("flow_out", "Flow Out", ValueType::Flow),
+            Port::new("amount", "Amount", ValueType::Integer),
+        ]);
+        let store = node("WriteStorage").with_property("key", serde_json::json!("balance"));
+        let read_owner = node("ReadStorage").with_property("key", serde_json::json!("owner"));
+        let per_user = node("WriteStorage");
+        let key = node("Caller").with_outputs(vec![Port::new("address", "Address", ValueType::String)]);
+        connect(&mut graph, &start, "amount", &store, "value");
+        connect(&mut graph, &key, "address", &per_user, "key");
+        for n in [start, store, read_owner, per_user, key] {
+            graph.add_node(n);
+        }
+
+        let schema = storage_schema(&graph);
+        assert_eq!(
+            schema,
+            vec![
+                StorageABI {
+                    key: "balance".to_string(),
+                    value_type: ValueType::Integer,
+                    writable: true,
+                },
+                StorageABI {
+                    key: "owner".to_string(),
+                    value_type: ValueType::String,
+                    writable: false,
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,334 @@
+//! Storage batching pass
+//!
+//! Coalesces runs of adjacent `ReadStorage` or `WriteStorage` nodes into a single
+//! `BatchReadStorage` / `BatchWriteStorage` node so the contract makes one host call
+//! instead of several.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    types::{Connection, NodeId, Port, ValueType, VisualGraph, VisualNode},
+    wasm::host,
+};
+
+/// Summary of what the batching pass changed
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StorageBatchingReport {
+    /// Number of batch nodes created
+    pub batches_created: usize,
+    /// Number of single storage nodes folded into batches
+    pub nodes_coalesced: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum StorageKind {
+    Read,
+    Write,
+}
+
+impl StorageKind {
+    fn of(node: &VisualNode) -> Option<Self> {
+        match node.node_type.as_str() {
+            "ReadStorage" => Some(StorageKind::Read),
+            "WriteStorage" => Some(StorageKind::Write),
+            _ => None,
+        }
+    }
+}
+
+/// Coalesce adjacent storage nodes of the same kind.
+///
+/// Two storage nodes are adjacent when the first one's `flow_out` is wired only to the
+/// second one's `flow_in`. A node is only eligible when its key is a static property,
+/// since a key computed at runtime could depend on an earlier operation in the run.
+/// Reads and writes are never mixed in one batch, which keeps read-after-write order intact.
+pub fn coalesce_storage_ops(graph: &VisualGraph) -> (VisualGraph, StorageBatchingReport) {
+    let mut report = StorageBatchingReport::default();
+
+    let eligible: HashMap<NodeId, StorageKind> = graph
+        .nodes
+        .iter()
+        .filter_map(|node| StorageKind::of(node).map(|kind| (node.id, kind)))
+        .filter(|(id, _)| static_key(graph, *id).is_some())
+        .collect();
+
+    // Link each eligible node to the eligible node that directly follows it
+    let mut next: HashMap<NodeId, NodeId> = HashMap::new();
+    let mut has_prev: HashSet<NodeId> = HashSet::new();
+    for (id, kind) in &eligible {
+        let flow_out: Vec<&Connection> = graph
+            .connections
+            .iter()
+            .filter(|c| c.source_node == *id && c.source_port == "flow_out")
+            .collect();
+        if flow_out.len() != 1 {
+            continue;
+        }
+        let target = flow_out[0].target_node;
+        if flow_out[0].target_port != "flow_in" || eligible.get(&target) != Some(kind) {
+            continue;
+        }
+        let incoming_flow = graph
+            .connections
+            .iter()
+            .filter(|c| c.target_node == target && c.target_port == "flow_in")
+            .count();
+        if incoming_flow == 1 {
+            next.insert(*id, target);
+            has_prev.insert(target);
+        }
+    }
+
+    // Walk each run from its head, in graph order for stable output
+    let mut runs: Vec<Vec<NodeId>> = Vec::new();
+    for node in &graph.nodes {
+        if !eligible.contains_key(&node.id) || has_prev.contains(&node.id) {
+            continue;
+        }
+        let mut run = vec![node.id];
+        let mut current = node.id;
+        while let Some(following) = next.get(&current) {
+            if run.contains(following) {
+                break;
+            }
+            run.push(*following);
+            current = *following;
+        }
+        // One host call takes at most MAX_BATCH_KEYS keys; longer runs are
+        // split, and a leftover single node stays as it is
+        runs.extend(
+            run.chunks(host::MAX_BATCH_KEYS)
+                .filter(|chunk| chunk.len() > 1)
+                .map(<[NodeId]>::to_vec),
+        );
+    }
+
+    if runs.is_empty() {
+        return (graph.clone(), report);
+    }
+
+    let mut result = graph.clone();
+    for run in runs {
+        let kind = eligible[&run[0]];
+        let batch = build_batch_node(graph, &run, kind);
+        let batch_id = batch.id;
+        let members: HashSet<NodeId> = run.iter().copied().collect();
+        let index_of: HashMap<NodeId, usize> = run.iter().enumerate().map(|(i, id)| (*id, i)).collect();
+        let head = run[0];
+        let tail = run[run.len() - 1];
+
+        let mut connections = Vec::new();
+        for connection in result.connections.drain(..) {
+            let from_member = members.contains(&connection.source_node);
+            let to_member = members.contains(&connection.target_node);
+
+            // Flow links inside the run disappear with the run
+            if from_member && to_member {
+                continue;
+            }
+
+            let mut rewired = connection.clone();
+            if to_member {
+                if connection.target_port == "flow_in" && connection.target_node != head {
+                    continue;
+                }
+                rewired.target_port = match (kind, connection.target_port.as_str()) {
+                    (StorageKind::Write, "value") => format!("value_{}", index_of[&connection.target_node]),
+                    _ => connection.target_port.clone(),
+                };
+                rewired.target_node = batch_id;
+            }
+            if from_member {
+                if connection.source_port == "flow_out" && connection.source_node != tail {
+                    continue;
+                }
+                rewired.source_port = match (kind, connection.source_port.as_str()) {
+                    (StorageKind::Read, "value") => format!("value_{}", index_of[&connection.source_node]),
+                    _ => connection.source_port.clone(),
+                };
+                rewired.source_node = batch_id;
+            }
+            connections.push(rewired);
+        }
+        result.connections = connections;
+
+        let insert_at = result.nodes.iter().position(|n| n.id == head).unwrap_or(result.nodes.len());
+        result.nodes.retain(|n| !members.contains(&n.id));
+        result.nodes.insert(insert_at.min(result.nodes.len()), batch);
+
+        report.batches_created += 1;
+        report.nodes_coalesced += run.len();
+    }
+
+    (result, report)
+}
+
+/// The node's storage key, if it is a static property and not wired from another node
+fn static_key(graph: &VisualGraph, node_id: NodeId) -> Option<String> {
+    let node = graph.get_node(node_id)?;
+    let key_wired = graph
+        .connections
+        .iter()
+        .any(|c| c.target_node == node_id && c.target_port == "key");
+    if key_wired {
+        return None;
+    }
+    node.properties.get("key").and_then(|v| v.as_str()).map(|k| k.to_string())
+}
+
+fn build_batch_node(graph: &VisualGraph, run: &[NodeId], kind: StorageKind) -> VisualNode {
+    let head = graph.get_node(run[0]).expect("run head exists in graph");
+    let keys: Vec<serde_json::Value> = run
+        .iter()
+        .filter_map(|id| static_key(graph, *id))
+        .map(serde_json::Value::String)
+        .collect();
+
+    let node_type = match kind {
+        StorageKind::Read => "BatchReadStorage",
+        StorageKind::Write => "BatchWriteStorage",
+    };
+
+    let mut inputs = vec![Port::new("flow_in", "Flow In", ValueType::Flow)];
+    let mut outputs = vec![Port::new("flow_out", "Flow Out", ValueType::Flow)];
+    for index in 0..run.len() {
+        let port_id = format!("value_{}", index);
+        let name = format!("Value {}", index);
+        match kind {
+            StorageKind::Read => outputs.push(Port::new(port_id, name, ValueType::Any)),
+            StorageKind::Write => inputs.push(Port::new(port_id, name, ValueType::Any).required()),
+        }
+    }
+    if kind == StorageKind::Write {
+        outputs.push(Port::new("success", "Success", ValueType::Boolean));
+    }
+
+    let mut node = VisualNode::new(uuid::Uuid::new_v4(), node_type, head.position.clone())
+        .with_inputs(inputs)
+        .with_outputs(outputs)
+        .with_property("keys", serde_json::Value::Array(keys));
+    // Literal values of unconnected write inputs move to the batch's value ports
+    if kind == StorageKind::Write {
+        for (index, id) in run.iter().enumerate() {
+            if let Some(value) = graph.get_node(*id).and_then(|n| n.properties.get("value")) {
+                node = node.with_property(format!("value_{}", index), value.clone());
+            }
+        }
+    }
+    node.metadata.insert(
+        "coalesced_from".to_string(),
+        run.iter().map(|id| id.to_string()).collect::<Vec<_>>().join(","),
+    );
+    node
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Position;
+
+    fn storage_node(node_type: &str, key: &str) -> VisualNode {
+        VisualNode::new(uuid::Uuid::new_v4(), node_type, Position::new(0.0, 0.0))
+            .with_property("key", serde_json::Value::String(key.to_string()))
+    }
+
+    fn flow(from: &VisualNode, to: &VisualNode) -> Connection {
+        Connection::new(uuid::Uuid::new_v4(), from.id, "flow_out", to.id, "flow_in")
+    }
+
+    #[test]
+    fn test_adjacent_writes_are_coalesced() {
+        let mut graph = VisualGraph::new("batch");
+        let a = storage_node("WriteStorage", "a");
+        let b = storage_node("WriteStorage", "b");
+        let c = storage_node("WriteStorage", "c");
+        graph.add_connection(flow(&a, &b));
+        graph.add_connection(flow(&b, &c));
+        graph.add_node(a);
+        graph.add_node(b);
+        graph.add_node(c);
+
+        let (result, report) = coalesce_storage_ops(&graph);
+        assert_eq!(report.batches_created, 1);
+        assert_eq!(report.nodes_coalesced, 3);
+        assert_eq!(result.nodes.len(), 1);
+        assert_eq!(result.nodes[0].node_type, "BatchWriteStorage");
+        assert_eq!(
+            result.nodes[0].properties.get("keys").unwrap(),
+            &serde_json::json!(["a", "b", "c"])
+        );
+        assert!(result.connections.is_empty());
+    }
+
+    #[test]
+    fn test_long_runs_are_split_at_the_host_batch_limit() {
+        let mut graph = VisualGraph::new("long");
+        let writes: Vec<VisualNode> = (0..=host::MAX_BATCH_KEYS)
+            .map(|i| storage_node("WriteStorage", &format!("k{}", i)))
+            .collect();
+        for pair in writes.windows(2) {
+            graph.add_connection(flow(&pair[0], &pair[1]));
+        }
+        let last = writes[host::MAX_BATCH_KEYS].id;
+        for write in writes {
+            graph.add_node(write);
+        }
+
+        let (result, report) = coalesce_storage_ops(&graph);
+        assert_eq!(report.batches_created, 1);
+        assert_eq!(report.nodes_coalesced, host::MAX_BATCH_KEYS);
+        assert_eq!(result.nodes.len(), 2);
+        let keys = result.nodes[0].properties["keys"].as_array().unwrap();
+        assert_eq!(keys.len(), host::MAX_BATCH_KEYS);
+        assert_eq!(result.connections.len(), 1);
+        assert_eq!(result.connections[0].source_node, result.nodes[0].id);
+        assert_eq!(result.connections[0].target_node, last);
+    }
+
+    #[test]
+    fn test_reads_and_writes_are_not_mixed() {
+        let mut graph = VisualGraph::new("mixed");
+        let write = storage_node("WriteStorage", "a");
+        let read = storage_node("ReadStorage", "a");
+        graph.add_connection(flow(&write, &read));
+        graph.add_node(write);
+        graph.add_node(read);
+
+        let (result, report) = coalesce_storage_ops(&graph);
+        assert_eq!(report, StorageBatchingReport::default());
+        assert_eq!(result.nodes.len(), 2);
+    }
+
+    #[test]
+    fn test_dynamic_keys_are_not_coalesced() {
+        let mut graph = VisualGraph::new("dynamic");
+        let a = storage_node("ReadStorage", "a");
+        let b = storage_node("ReadStorage", "b");
+        let key_source = storage_node("ReadStorage", "pointer");
+        graph.add_connection(flow(&a, &b));
+        graph.add_connection(Connection::new(uuid::Uuid::new_v4(), key_source.id, "value", b.id, "key"));
+        graph.add_node(a);
+        graph.add_node(b);
+        graph.add_node(key_source);
+
+        let (_, report) = coalesce_storage_ops(&graph);
+        assert_eq!(report.batches_created, 0);
+    }
+
+    #[test]
+    fn test_read_outputs_are_rewired() {
+        let mut graph = VisualGraph::new("reads");
+        let a = storage_node("ReadStorage", "a");
+        let b = storage_node("ReadStorage", "b");
+        let sink = VisualNode::new(uuid::Uuid::new_v4(), "Add", Position::new(0.0, 0.0));
+        graph.add_connection(flow(&a, &b));
+        graph.add_connection(Connection::new(uuid::Uuid::new_v4(), b.id, "value", sink.id, "a"));
+        graph.add_node(a);
+        graph.add_node(b);
+        graph.add_node(sink);
+
+        let (result, _) = coalesce_storage_ops(&graph);
+        assert_eq!(result.connections.len(), 1);
+        assert_eq!(result.connections[0].source_port, "value_1");
+    }
+}
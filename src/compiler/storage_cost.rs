@@ -0,0 +1,233 @@
+//! Long-term storage cost estimation
+//!
+//! Every storage write node is sized (key length plus value size) and priced
+//! with the rent and deposit parameters of the target network. Writes whose
+//! footprint grows with use, such as keys computed at runtime or values built
+//! by appending to a collection, cannot be sized statically and are reported
+//! as unbounded growth unless annotated with `max_entries` or `max_value_size`.
+
+use std::collections::{HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::NetworkProfile,
+    types::{CompilationResult, NodeId, VisualGraph, VisualNode},
+};
+
+/// Assumed size of a stored value without a `max_value_size` annotation
+pub const DEFAULT_VALUE_SIZE: u64 = 32;
+const SECONDS_PER_YEAR: u64 = 365 * 24 * 3600;
+
+/// Storage written by one node
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StorageSlot {
+    pub node: NodeId,
+    /// Fixed key, if the node has one
+    pub key: Option<String>,
+    /// Bytes per entry (key and value)
+    pub entry_bytes: u64,
+    /// Entries the node can create; 1 for a fixed key
+    pub entries: u64,
+}
+
+impl StorageSlot {
+    pub fn bytes(&self) -> u64 {
+        self.entry_bytes.saturating_mul(self.entries)
+    }
+}
+
+/// Storage footprint of a graph
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StorageCostReport {
+    pub slots: Vec<StorageSlot>,
+    /// Writes whose footprint grows without bound, with the reason
+    pub unbounded_growth: Vec<(NodeId, String)>,
+}
+
+/// Projected cost of a storage footprint on a network
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StorageCostProjection {
+    pub network: String,
+    pub bytes: u64,
+    /// Deposit locked while the data is stored
+    pub deposit: u64,
+    pub rent_per_year: u64,
+}
+
+impl StorageCostReport {
+    /// Bytes stored once every bounded write has filled its slots
+    pub fn total_bytes(&self) -> u64 {
+        self.slots.iter().map(StorageSlot::bytes).fold(0, u64::saturating_add)
+    }
+
+    pub fn is_bounded(&self) -> bool {
+        self.unbounded_growth.is_empty()
+    }
+
+    pub fn project(&self, network: &NetworkProfile) -> StorageCostProjection {
+        let bytes = self.total_bytes();
+        let epochs_per_year = SECONDS_PER_YEAR / network.epoch_seconds.max(1);
+        StorageCostProjection {
+            network: network.name.clone(),
+            bytes,
+            deposit: bytes.saturating_mul(network.storage_deposit_per_byte),
+            rent_per_year: bytes
+                .saturating_mul(network.rent_per_byte_epoch)
+                .saturating_mul(epochs_per_year),
+        }
+    }
+
+    /// Add the projection to a compile result's metadata, and a warning for
+    /// every unbounded write
+    pub fn annotate(&self, network: &NetworkProfile, result: &mut CompilationResult) {
+        let projection = self.project(network);
+        result.metadata.insert("storage_network".to_string(), projection.network);
+        result.metadata.insert("storage_bytes".to_string(), projection.bytes.to_string());
+        result.metadata.insert("storage_deposit".to_string(), projection.deposit.to_string());
+        result
+            .metadata
+            .insert("storage_rent_per_year".to_string(), projection.rent_per_year.to_string());
+        for (node, reason) in &self.unbounded_growth {
+            result.warnings.push(format!("Unbounded storage growth at node {}: {}", node, reason));
+        }
+    }
+}
+
+fn value_size(node: &VisualNode) -> u64 {
+    node.properties
+        .get("max_value_size")
+        .and_then(|v| v.as_u64())
+        .unwrap_or(DEFAULT_VALUE_SIZE)
+}
+
+/// Nodes feeding `node`, directly or indirectly, through its `port`
+fn sources<'a>(graph: &'a VisualGraph, node: NodeId, port: &str) -> Vec<&'a VisualNode> {
+    let nodes: HashMap<NodeId, &VisualNode> = graph.nodes.iter().map(|n| (n.id, n)).collect();
+    let mut seen = HashSet::new();
+    let mut queue: Vec<NodeId> = graph
+        .connections
+        .iter()
+        .filter(|c| c.target_node == node && c.target_port == port)
+        .map(|c| c.source_node)
+        .collect();
+    while let Some(id) = queue.pop() {
+        if seen.insert(id) {
+            queue.extend(graph.connections.iter().filter(|c| c.target_node == id).map(|c| c.source_node));
+        }
+    }
+    seen.into_iter().filter_map(|id| nodes.get(&id).copied()).collect()
+}
+
+/// Size every storage write in a graph and find writes that grow without bound
+pub fn estimate_storage_cost(graph: &VisualGraph) -> StorageCostReport {
+    let mut report = StorageCostReport::default();
+    for node in &graph.nodes {
+        let value_port = match node.node_type.as_str() {
+            "WriteStorage" => "value",
+            "BatchWriteStorage" => "values",
+            _ => continue,
+        };
+        let grows_value = node.properties.get("max_value_size").is_none()
+            && sources(graph, node.id, value_port)
+                .iter()
+                .any(|n| matches!(n.node_type.as_str(), "ArrayPush" | "MapSet" | "Concat"));
+        if grows_value {
+            report.unbounded_growth.push((
+                node.id,
+                "stored value is built by appending to a collection; set 'max_value_size'".to_string(),
+            ));
+        }
+
+        match node.node_type.as_str() {
+            "WriteStorage" => {
+                let key = node.properties.get("key").and_then(|v| v.as_str()).map(str::to_string);
+                let dynamic_key = graph
+                    .connections
+                    .iter()
+                    .any(|c| c.target_node == node.id && c.target_port == "key");
+                let max_entries = node.properties.get("max_entries").and_then(|v| v.as_u64());
+                let entries = match (dynamic_key, max_entries) {
+                    (false, _) => 1,
+                    (true, Some(max)) => max,
+                    (true, None) => {
+                        report.unbounded_growth.push((
+                            node.id,
+                            "storage key is computed at runtime, so every call can add an entry; set 'max_entries'"
+                                .to_string(),
+                        ));
+                        1
+                    }
+                };
+                let key_len = key.as_ref().map_or(DEFAULT_VALUE_SIZE, |k| k.len() as u64);
+                report.slots.push(StorageSlot {
+                    node: node.id,
+                    key: if dynamic_key { None } else { key },
+                    entry_bytes: key_len + value_size(node),
+                    entries,
+                });
+            }
+            "BatchWriteStorage" => {
+                let keys: Vec<&str> = node
+                    .properties
+                    .get("keys")
+                    .and_then(|v| v.as_array())
+                    .map(|keys| keys.iter().filter_map(|k| k.as_str()).collect())
+                    .unwrap_or_default();
+                for key in keys {
+                    report.slots.push(StorageSlot {
+                        node: node.id,
+                        key: Some(key.to_string()),
+                        entry_bytes: key.len() as u64 + value_size(node),
+                        entries: 1,
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{Connection, Position};
+    use uuid::Uuid;
+
+    #[test]
+    fn test_fixed_and_dynamic_keys() {
+        let mut graph = VisualGraph::new("registry");
+        let owner = VisualNode::new(Uuid::new_v4(), "WriteStorage", Position::new(0.0, 0.0))
+            .with_property("key", serde_json::json!("owner"));
+        let hash = VisualNode::new(Uuid::new_v4(), "Hash", Position::new(0.0, 0.0));
+        let entry = VisualNode::new(Uuid::new_v4(), "WriteStorage", Position::new(0.0, 0.0));
+        graph.add_connection(Connection::new(Uuid::new_v4(), hash.id, "hash", entry.id, "key"));
+        let (owner_id, entry_id) = (owner.id, entry.id);
+        graph.add_node(owner);
+        graph.add_node(hash);
+        graph.add_node(entry);
+
+        let report = estimate_storage_cost(&graph);
+        assert_eq!(report.slots[0].bytes(), 5 + DEFAULT_VALUE_SIZE);
+        assert_eq!(report.unbounded_growth.len(), 1);
+        assert_eq!(report.unbounded_growth[0].0, entry_id);
+
+        graph.nodes[2] = graph.nodes[2].clone().with_property("max_entries", serde_json::json!(1000));
+        let report = estimate_storage_cost(&graph);
+        assert!(report.is_bounded());
+        assert_eq!(report.slots[1].entries, 1000);
+
+        let network = NetworkProfile {
+            name: "test".to_string(),
+            rent_per_byte_epoch: 2,
+            epoch_seconds: SECONDS_PER_YEAR / 10,
+            storage_deposit_per_byte: 3,
+            ..Default::default()
+        };
+        let projection = report.project(&network);
+        assert_eq!(projection.deposit, projection.bytes * 3);
+        assert_eq!(projection.rent_per_year, projection.bytes * 20);
+        assert!(report.slots.iter().any(|s| s.node == owner_id && s.key.as_deref() == Some("owner")));
+    }
+}
@@ -0,0 +1,164 @@
+//! Unit-of-account checking
+//!
+//! Numeric ports may carry a unit tag such as `"tokens"` or `"seconds"`. Values
+//! with different units may not be connected or added together; converting
+//! between units requires an explicit `CastUnit` node.
+
+use std::collections::HashSet;
+
+use crate::types::{NodeId, VisualGraph};
+
+/// Node types whose result carries the unit of their operands
+const UNIT_PRESERVING_NODE_TYPES: &[&str] = &["Add", "Subtract"];
+
+/// Unit of the value leaving `node_id` through `port`, if it can be determined
+pub fn output_unit(graph: &VisualGraph, node_id: NodeId, port: &str) -> Option<String> {
+    output_unit_inner(graph, node_id, port, &mut HashSet::new())
+}
+
+fn output_unit_inner(
+    graph: &VisualGraph,
+    node_id: NodeId,
+    port: &str,
+    visited: &mut HashSet<NodeId>,
+) -> Option<String> {
+    if !visited.insert(node_id) {
+        return None;
+    }
+    let node = graph.get_node(node_id)?;
+
+    // An explicit tag on the port always wins
+    if let Some(unit) = node.outputs.iter().find(|p| p.id == port).and_then(|p| p.unit.clone()) {
+        return Some(unit);
+    }
+
+    match node.node_type.as_str() {
+        "CastUnit" => node
+            .properties
+            .get("to_unit")
+            .and_then(|v| v.as_str())
+            .map(|u| u.to_string()),
+        t if UNIT_PRESERVING_NODE_TYPES.contains(&t) => ["a", "b"]
+            .iter()
+            .find_map(|input| input_unit_inner(graph, node_id, input, visited)),
+        _ => None,
+    }
+}
+
+/// Unit of the value arriving at `node_id` on `port`, if it can be determined
+pub fn input_unit(graph: &VisualGraph, node_id: NodeId, port: &str) -> Option<String> {
+    input_unit_inner(graph, node_id, port, &mut HashSet::new())
+}
+
+fn input_unit_inner(
+    graph: &VisualGraph,
+    node_id: NodeId,
+    port: &str,
+    visited: &mut HashSet<NodeId>,
+) -> Option<String> {
+    graph
+        .connections
+        .iter()
+        .find(|c| c.target_node == node_id && c.target_port == port)
+        .and_then(|c| output_unit_inner(graph, c.source_node, &c.source_port, visited))
+}
+
+/// Find every unit mismatch in a graph
+pub fn check_units(graph: &VisualGraph) -> Vec<String> {
+    let mut errors = Vec::new();
+
+    // Connections must not change the unit of a value
+    for connection in &graph.connections {
+        let declared = graph
+            .get_node(connection.target_node)
+            .and_then(|n| n.inputs.iter().find(|p| p.id == connection.target_port))
+            .and_then(|p| p.unit.clone());
+        let actual = output_unit(graph, connection.source_node, &connection.source_port);
+        if let (Some(declared), Some(actual)) = (declared, actual) {
+            if declared != actual {
+                errors.push(format!(
+                    "Connection {} passes a value in '{}' to a port expecting '{}'",
+                    connection.id, actual, declared
+                ));
+            }
+        }
+    }
+
+    for node in &graph.nodes {
+        // Operands of unit-preserving arithmetic must agree
+        if UNIT_PRESERVING_NODE_TYPES.contains(&node.node_type.as_str()) {
+            let a = input_unit(graph, node.id, "a");
+            let b = input_unit(graph, node.id, "b");
+            if let (Some(a), Some(b)) = (&a, &b) {
+                if a != b {
+                    errors.push(format!(
+                        "{} node {} mixes '{}' and '{}'; add a CastUnit node to convert explicitly",
+                        node.node_type, node.id, a, b
+                    ));
+                }
+            }
+        }
+
+        // Casts must be fed the unit they convert from
+        if node.node_type == "CastUnit" {
+            let from = node.properties.get("from_unit").and_then(|v| v.as_str());
+            let actual = input_unit(graph, node.id, "value");
+            if let (Some(from), Some(actual)) = (from, actual) {
+                if from != actual {
+                    errors.push(format!(
+                        "CastUnit node {} converts from '{}' but receives '{}'",
+                        node.id, from, actual
+                    ));
+                }
+            }
+        }
+    }
+
+    errors
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_graphs::{connect, node, node_with};
+    use crate::types::{Port, ValueType, VisualNode};
+
+    fn source(unit: &str) -> VisualNode {
+        node("Input").with_outputs(vec![Port::new("out", "Out", ValueType::Integer).with_unit(unit)])
+    }
+
+    #[test]
+    fn test_mixed_units_rejected() {
+        let mut graph = VisualGraph::new("units");
+        let tokens = source("tokens");
+        let seconds = source("seconds");
+        let add = node("Add");
+        connect(&mut graph, &tokens, "out", &add, "a");
+        connect(&mut graph, &seconds, "out", &add, "b");
+        graph.add_node(tokens);
+        graph.add_node(seconds);
+        graph.add_node(add);
+
+        assert_eq!(check_units(&graph).len(), 1);
+    }
+
+    #[test]
+    fn test_cast_allows_conversion() {
+        let mut graph = VisualGraph::new("units");
+        let tokens = source("tokens");
+        let seconds = source("seconds");
+        let cast = node_with("CastUnit", serde_json::json!({"from_unit": "seconds", "to_unit": "tokens"}));
+        let add = node("Add");
+        connect(&mut graph, &seconds, "out", &cast, "value");
+        connect(&mut graph, &tokens, "out", &add, "a");
+        connect(&mut graph, &cast, "value", &add, "b");
+        let add_id = add.id;
+        graph.add_node(tokens);
+        graph.add_node(seconds);
+        graph.add_node(cast);
+        graph.add_node(add);
+
+        assert!(check_units(&graph).is_empty());
+        assert_eq!(output_unit(&graph, add_id, "result"), Some("tokens".to_string()));
+    }
+}
@@ -35,14 +35,44 @@ impl Validator {
             self.validate_connection(connection, graph, &mut result);
         }
 
-        // Validate graph structure
-        self.validate_graph_structure(graph, &mut result);
+        self.validate_graph_rules(graph, &mut result);
 
         Ok(result)
     }
 
+    /// Checks that look at the graph as a whole rather than one node or connection
+    pub(super) fn validate_graph_rules(&self, graph: &VisualGraph, result: &mut ValidationResult) {
+        // Validate element types flowing into collection nodes
+        self.validate_collection_types(graph, result);
+
+        // Values tagged with different units must not be mixed
+        for error in super::units::check_units(graph) {
+            *result = result.clone().with_error(error);
+        }
+
+        // Wrapping arithmetic feeding state is flagged by the Arithmetic Safety rule
+        self.validate_arithmetic_safety(graph, result);
+
+        // Privileged operations should be guarded by a signature check
+        self.validate_signature_guards(graph, result);
+
+        // At most one constructor, with a well-formed signature
+        self.validate_constructor(graph, result);
+
+        // Start nodes must form distinct, well-typed entry points
+        if let Err(e) = super::entry_points::collect_entry_points(graph) {
+            *result = result.clone().with_error(e.to_string());
+        }
+
+        // Catch handlers must be reachable from a TryCall failure branch
+        self.validate_try_catch(graph, result);
+
+        // Validate graph structure
+        self.validate_graph_structure(graph, result);
+    }
+
     /// Validate a single node
-    fn validate_node(&self, node: &VisualNode, result: &mut ValidationResult) {
+    pub(super) fn validate_node(&self, node: &VisualNode, result: &mut ValidationResult) {
         // Check for required inputs
         for input in &node.inputs {
             if input.required {
@@ -63,6 +93,18 @@ impl Validator {
 
     /// Validate node properties
     fn validate_node_properties(&self, node: &VisualNode, result: &mut ValidationResult) {
+        if let Some(deprecation) = crate::nodes::deprecation_for(&node.node_type) {
+            let advice = match &deprecation.replacement {
+                Some(replacement) => format!("use '{}' instead (run migrate-nodes)", replacement),
+                None => deprecation.note.clone(),
+            };
+            *result = result.clone().with_warning(format!(
+                "Node {} uses '{}', deprecated since {}: {}",
+                node.id, node.node_type, deprecation.since, advice
+            ));
+            return;
+        }
+
         // TODO: Implement property validation based on node type
         match node.node_type.as_str() {
             "If" => {
@@ -83,6 +125,115 @@ impl Validator {
                     ));
                 }
             }
+            "BatchReadStorage" | "BatchWriteStorage" => {
+                // Static keys, if given, must be a list of strings
+                if let Some(keys) = node.properties.get("keys") {
+                    let valid = keys
+                        .as_array()
+                        .map_or(false, |items| items.iter().all(|k| k.is_string()));
+                    if !valid {
+                        *result = result.clone().with_error(format!(
+                            "{} node {} has invalid 'keys' property",
+                            node.node_type, node.id
+                        ));
+                    }
+                }
+            }
+            "ForEach" => {
+                // Loops must be bounded
+                let bounded = node
+                    .properties
+                    .get("max_iterations")
+                    .and_then(|v| v.as_u64())
+                    .map_or(false, |max| max > 0);
+                if !bounded {
+                    *result = result.clone().with_error(format!(
+                        "ForEach node {} requires a positive 'max_iterations' property",
+                        node.id
+                    ));
+                }
+            }
+            "Add" | "Subtract" | "Multiply" | "Divide" => {
+                if let Some(mode) = node.properties.get("overflow_mode") {
+                    if mode.as_str().and_then(crate::types::OverflowMode::from_name).is_none() {
+                        *result = result.clone().with_error(format!(
+                            "{} node {} has invalid overflow_mode: {}",
+                            node.node_type, node.id, mode
+                        ));
+                    }
+                }
+                if let Some(scale) = node.properties.get("decimal_scale") {
+                    let valid = scale
+                        .as_u64()
+                        .map_or(false, |s| s <= crate::types::Decimal::MAX_SCALE as u64);
+                    if !valid {
+                        *result = result.clone().with_error(format!(
+                            "{} node {} has invalid decimal_scale: {} (must be 0-{})",
+                            node.node_type,
+                            node.id,
+                            scale,
+                            crate::types::Decimal::MAX_SCALE
+                        ));
+                    }
+                }
+            }
+            "Require" => {
+                if node.properties.get("error").and_then(|v| v.as_str()).map_or(true, |e| e.is_empty()) {
+                    *result = result.clone().with_error(format!(
+                        "Require node {} missing required 'error' property",
+                        node.id
+                    ));
+                }
+            }
+            "TryCall" => {
+                if node.properties.get("function").and_then(|v| v.as_str()).map_or(true, |f| f.is_empty()) {
+                    *result = result.clone().with_error(format!(
+                        "TryCall node {} missing required 'function' property",
+                        node.id
+                    ));
+                }
+            }
+            "Catch" => {
+                if node.properties.get("error").map_or(false, |e| !e.is_string()) {
+                    *result = result.clone().with_error(format!(
+                        "Catch node {} has a non-string 'error' filter",
+                        node.id
+                    ));
+                }
+            }
+            "CastUnit" => {
+                for key in ["from_unit", "to_unit"] {
+                    if node.properties.get(key).and_then(|v| v.as_str()).is_none() {
+                        *result = result.clone().with_error(format!(
+                            "CastUnit node {} missing required '{}' property",
+                            node.id, key
+                        ));
+                    }
+                }
+            }
+            "Concat" | "Slice" | "Hash" | "HexEncode" | "HexDecode" | "Compare" => {
+                // Without a length bound the gas analyzer cannot bound the cost
+                if node.properties.get("max_length").and_then(|v| v.as_u64()).is_none() {
+                    *result = result.clone().with_warning(format!(
+                        "{} node {} has no 'max_length'; its gas cost is unbounded",
+                        node.node_type, node.id
+                    ));
+                }
+            }
+            "Init" => {}
+            "Import" => {
+                if let Err(e) = super::imports::import_target(node) {
+                    *result = result.clone().with_error(e.to_string());
+                }
+            }
+            "Slot" => {
+                // Filling is checked at compile time; an unfilled template is still a valid graph
+                if node.properties.get("name").and_then(|v| v.as_str()).map_or(true, str::is_empty) {
+                    *result = result.clone().with_error(format!("Slot node {} missing required 'name' property", node.id));
+                }
+            }
+            "ArrayGet" | "ArraySet" | "ArrayPush" | "ArrayRemove" | "MapGet" | "MapSet"
+            | "MapRemove" | "Length" => {}
             _ => {
                 // Unknown node type - warning
                 *result = result.clone().with_warning(format!(
@@ -94,7 +245,7 @@ impl Validator {
     }
 
     /// Validate a connection
-    fn validate_connection(
+    pub(super) fn validate_connection(
         &self,
         connection: &Connection,
         graph: &VisualGraph,
@@ -157,6 +308,203 @@ impl Validator {
         }
     }
 
+    /// Check connections into collection nodes against their declared element type
+    fn validate_collection_types(&self, graph: &VisualGraph, result: &mut ValidationResult) {
+        for node in &graph.nodes {
+            let Some(type_name) = node.properties.get("element_type").and_then(|v| v.as_str()) else {
+                continue;
+            };
+            let Some(element_type) = ValueType::from_name(type_name) else {
+                *result = result.clone().with_error(format!(
+                    "Node {} has unknown element type: {}",
+                    node.id, type_name
+                ));
+                continue;
+            };
+
+            for connection in graph.connections.iter().filter(|c| c.target_node == node.id) {
+                let source_type = graph
+                    .get_node(connection.source_node)
+                    .and_then(|source| source.outputs.iter().find(|p| p.id == connection.source_port))
+                    .map(|port| &port.value_type);
+                let Some(source_type) = source_type else {
+                    continue;
+                };
+
+                let incoming = match connection.target_port.as_str() {
+                    "value" => Some(source_type),
+                    "array" | "map" => source_type.element_type(),
+                    _ => None,
+                };
+                if let Some(incoming) = incoming {
+                    if !incoming.is_compatible_with(&element_type) {
+                        *result = result.clone().with_error(format!(
+                            "{} node {} expects elements of type {:?} but port '{}' receives {:?}",
+                            node.node_type, node.id, element_type, connection.target_port, incoming
+                        ));
+                    }
+                }
+            }
+        }
+    }
+
+    /// Arithmetic Safety: warn when wrapping arithmetic results reach storage
+    fn validate_arithmetic_safety(&self, graph: &VisualGraph, result: &mut ValidationResult) {
+        let default = self.config.compiler.overflow_mode;
+        for node in graph
+            .nodes
+            .iter()
+            .filter(|n| super::safe_math::ARITHMETIC_NODE_TYPES.contains(&n.node_type.as_str()))
+        {
+            if super::safe_math::resolve_overflow_mode(node, default) != crate::types::OverflowMode::Wrapping {
+                continue;
+            }
+            let feeds_state = graph
+                .connections
+                .iter()
+                .filter(|c| c.source_node == node.id)
+                .filter_map(|c| graph.get_node(c.target_node))
+                .any(|target| target.node_type.contains("WriteStorage"));
+            if feeds_state {
+                *result = result.clone().with_warning(format!(
+                    "Arithmetic Safety: {} node {} uses wrapping overflow and its result is written to storage",
+                    node.node_type, node.id
+                ));
+            }
+        }
+    }
+
+    /// Warn about privileged operations some flow reaches without passing a
+    /// signature check: the true branch of an If, or what follows a Require or
+    /// Assert, whose condition is a VerifySignature's `valid` output
+    fn validate_signature_guards(&self, graph: &VisualGraph, result: &mut ValidationResult) {
+        let is_flow = |c: &Connection| c.target_port == "flow_in";
+        let passes_check = |c: &Connection| {
+            graph.get_node(c.source_node).map_or(false, |source| {
+                let guarded_port = match source.node_type.as_str() {
+                    "If" => "true_flow",
+                    "Require" | "Assert" => "flow_out",
+                    _ => return false,
+                };
+                c.source_port == guarded_port && checks_signature(graph, source, "condition")
+            })
+        };
+
+        // Walk the flow from every node nothing flows into, stopping at signature checks
+        let mut pending: Vec<_> = graph
+            .nodes
+            .iter()
+            .filter(|n| !graph.connections.iter().any(|c| c.target_node == n.id && is_flow(c)))
+            .map(|n| n.id)
+            .collect();
+        let mut unguarded = std::collections::HashSet::new();
+        while let Some(current) = pending.pop() {
+            if !unguarded.insert(current) {
+                continue;
+            }
+            pending.extend(
+                graph
+                    .connections
+                    .iter()
+                    .filter(|c| c.source_node == current && is_flow(c) && !passes_check(c))
+                    .map(|c| c.target_node),
+            );
+        }
+
+        for node in graph.nodes.iter().filter(|n| is_privileged(n) && unguarded.contains(&n.id)) {
+            *result = result.clone().with_warning(format!(
+                "Privileged {} node {} is not guarded by a signature check",
+                node.node_type, node.id
+            ));
+        }
+    }
+
+    /// Check the Init node, whose output ports form the constructor signature
+    fn validate_constructor(&self, graph: &VisualGraph, result: &mut ValidationResult) {
+        let init = match super::constructor::find_init_node(graph) {
+            Ok(Some(init)) => init,
+            Ok(None) => return,
+            Err(e) => {
+                *result = result.clone().with_error(e.to_string());
+                return;
+            }
+        };
+
+        if graph.connections.iter().any(|c| c.target_node == init.id) {
+            *result = result.clone().with_error(format!(
+                "Init node {} is an entry point and cannot have incoming connections",
+                init.id
+            ));
+        }
+
+        let mut seen = std::collections::HashSet::new();
+        for param in super::constructor::constructor_params(init) {
+            if !seen.insert(param.name.clone()) {
+                *result = result.clone().with_error(format!(
+                    "Init node {} declares constructor parameter '{}' twice",
+                    init.id, param.name
+                ));
+            }
+            if param.value_type == ValueType::Any {
+                *result = result.clone().with_warning(format!(
+                    "Constructor parameter '{}' has type Any; deployment arguments for it cannot be checked",
+                    param.name
+                ));
+            }
+        }
+    }
+
+    /// Check that every Catch node can actually run.
+    ///
+    /// Catch nodes on the same failure branch are matched in connection order, so a
+    /// handler after a catch-all, or after another handler for the same error, is
+    /// unreachable.
+    fn validate_try_catch(&self, graph: &VisualGraph, result: &mut ValidationResult) {
+        let mut attached = std::collections::HashSet::new();
+
+        for try_call in graph.nodes.iter().filter(|n| n.node_type == "TryCall") {
+            let handlers: Vec<&VisualNode> = graph
+                .connections
+                .iter()
+                .filter(|c| c.source_node == try_call.id && c.source_port == "failure_flow")
+                .filter_map(|c| graph.get_node(c.target_node))
+                .collect();
+
+            if handlers.is_empty() {
+                *result = result.clone().with_warning(format!(
+                    "TryCall node {} has no failure branch; a failed call aborts the transaction",
+                    try_call.id
+                ));
+            }
+
+            let mut caught_all = false;
+            let mut caught = std::collections::HashSet::new();
+            for handler in handlers.into_iter().filter(|n| n.node_type == "Catch") {
+                attached.insert(handler.id);
+                let error = handler.properties.get("error").and_then(|v| v.as_str());
+                let shadowed = caught_all || error.map_or(false, |name| !caught.insert(name.to_string()));
+                if shadowed {
+                    *result = result.clone().with_error(format!(
+                        "Catch node {} is unreachable: an earlier handler on TryCall {} already catches {}",
+                        handler.id,
+                        try_call.id,
+                        error.unwrap_or("every error")
+                    ));
+                }
+                caught_all |= error.is_none();
+            }
+        }
+
+        for catch in graph.nodes.iter().filter(|n| n.node_type == "Catch") {
+            if !attached.contains(&catch.id) {
+                *result = result.clone().with_error(format!(
+                    "Catch node {} is unreachable: it is not connected to a TryCall failure branch",
+                    catch.id
+                ));
+            }
+        }
+    }
+
     /// Validate graph structure
     fn validate_graph_structure(&self, graph: &VisualGraph, result: &mut ValidationResult) {
         // Check for cycles (basic implementation)
@@ -202,12 +550,137 @@ impl Validator {
     }
 }
 
+/// Node types that change ownership, supply or code and should require authorization
+const PRIVILEGED_NODE_TYPES: &[&str] = &["Transfer", "Mint", "Burn", "SetOwner", "Upgrade", "SelfDestruct"];
+
+/// Whether a node performs a privileged operation, by type or an explicit `privileged` flag
+fn is_privileged(node: &VisualNode) -> bool {
+    PRIVILEGED_NODE_TYPES.contains(&node.node_type.as_str())
+        || node.properties.get("privileged").and_then(|v| v.as_bool()).unwrap_or(false)
+}
+
+/// Whether input `port` of `node` only holds when a signature is valid: it is
+/// wired to a VerifySignature's `valid` output, directly or through `And` nodes
+fn checks_signature(graph: &VisualGraph, node: &VisualNode, port: &str) -> bool {
+    let mut visited = std::collections::HashSet::new();
+    let mut pending = vec![(node.id, port)];
+    while let Some((target, port)) = pending.pop() {
+        if !visited.insert((target, port)) {
+            continue;
+        }
+        for connection in graph.connections.iter().filter(|c| c.target_node == target && c.target_port == port) {
+            match graph.get_node(connection.source_node).map(|n| n.node_type.as_str()) {
+                Some("VerifySignature") if connection.source_port == "valid" => return true,
+                Some("And") => pending.extend([(connection.source_node, "a"), (connection.source_node, "b")]),
+                _ => {}
+            }
+        }
+    }
+    false
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::types::{VisualNode, Position, Port, ValueType};
+    use crate::test_graphs::{connect, node, node_with};
+    use crate::types::{VisualNode, Position, Port, ValueType, Connection, VisualGraph};
     use uuid::Uuid;
 
+    #[test]
+    fn test_collection_element_type_mismatch() {
+        let config = Config::default();
+        let validator = Validator::new(&config).unwrap();
+
+        let source = VisualNode::new(Uuid::new_v4(), "Const", Position::new(0.0, 0.0))
+            .with_outputs(vec![Port::new("out", "Out", ValueType::String)]);
+        let push = VisualNode::new(Uuid::new_v4(), "ArrayPush", Position::new(100.0, 0.0))
+            .with_inputs(vec![Port::new("value", "Value", ValueType::Any)])
+            .with_property("element_type", serde_json::json!("integer"));
+
+        let mut graph = VisualGraph::new("collections");
+        graph.add_connection(Connection::new(Uuid::new_v4(), source.id, "out", push.id, "value"));
+        graph.add_node(source);
+        graph.add_node(push);
+
+        let mut result = ValidationResult::valid();
+        validator.validate_collection_types(&graph, &mut result);
+        assert!(!result.is_valid);
+    }
+
+    #[test]
+    fn test_unguarded_privileged_operation_warns() {
+        let config = Config::default();
+        let validator = Validator::new(&config).unwrap();
+
+        let start = node("Start");
+        let verify = node("VerifySignature");
+        let branch = node("If");
+        let guarded = node("Mint");
+        let rejected = node("Burn");
+        let unguarded = node_with("WriteStorage", serde_json::json!({"privileged": true}));
+
+        let mut graph = VisualGraph::new("privileged");
+        connect(&mut graph, &verify, "valid", &branch, "condition");
+        connect(&mut graph, &start, "flow_out", &branch, "flow_in");
+        connect(&mut graph, &branch, "true_flow", &guarded, "flow_in");
+        connect(&mut graph, &branch, "false_flow", &rejected, "flow_in");
+        // Feeding off the signature check's data is not the same as passing it
+        connect(&mut graph, &start, "flow_out", &unguarded, "flow_in");
+        connect(&mut graph, &verify, "valid", &unguarded, "value");
+        for node in [start, verify, branch, guarded, rejected, unguarded] {
+            graph.add_node(node);
+        }
+
+        let mut result = ValidationResult::valid();
+        validator.validate_signature_guards(&graph, &mut result);
+        assert_eq!(result.warnings.len(), 2);
+        assert!(result.warnings.iter().any(|w| w.contains("Burn")));
+        assert!(result.warnings.iter().any(|w| w.contains("WriteStorage")));
+    }
+
+    #[test]
+    fn test_wrapping_arithmetic_into_storage_warns() {
+        let mut config = Config::default();
+        config.compiler.overflow_mode = crate::types::OverflowMode::Wrapping;
+        let validator = Validator::new(&config).unwrap();
+
+        let add = VisualNode::new(Uuid::new_v4(), "Add", Position::new(0.0, 0.0));
+        let write = VisualNode::new(Uuid::new_v4(), "WriteStorage", Position::new(100.0, 0.0));
+        let mut graph = VisualGraph::new("math");
+        graph.add_connection(Connection::new(Uuid::new_v4(), add.id, "result", write.id, "value"));
+        graph.add_node(add);
+        graph.add_node(write);
+
+        let mut result = ValidationResult::valid();
+        validator.validate_arithmetic_safety(&graph, &mut result);
+        assert_eq!(result.warnings.len(), 1);
+    }
+
+    #[test]
+    fn test_shadowed_and_detached_catch_are_unreachable() {
+        let config = Config::default();
+        let validator = Validator::new(&config).unwrap();
+
+        let try_call = VisualNode::new(Uuid::new_v4(), "TryCall", Position::new(0.0, 0.0));
+        let catch_all = VisualNode::new(Uuid::new_v4(), "Catch", Position::new(100.0, 0.0));
+        let shadowed = VisualNode::new(Uuid::new_v4(), "Catch", Position::new(100.0, 100.0))
+            .with_property("error", serde_json::json!("Paused"));
+        let detached = VisualNode::new(Uuid::new_v4(), "Catch", Position::new(100.0, 200.0));
+
+        let mut graph = VisualGraph::new("try");
+        graph.add_connection(Connection::new(Uuid::new_v4(), try_call.id, "failure_flow", catch_all.id, "flow_in"));
+        graph.add_connection(Connection::new(Uuid::new_v4(), try_call.id, "failure_flow", shadowed.id, "flow_in"));
+        graph.add_node(try_call);
+        graph.add_node(catch_all);
+        graph.add_node(shadowed);
+        graph.add_node(detached);
+
+        let mut result = ValidationResult::valid();
+        validator.validate_try_catch(&graph, &mut result);
+        assert_eq!(result.errors.len(), 2);
+        assert!(result.warnings.is_empty());
+    }
+
     #[test]
     fn test_validator_creation() {
         let config = Config::default();
@@ -1,7 +1,71 @@
 //! WebAssembly code generation
+//!
+//! Every value is an i64: integers as themselves, booleans as 0 and 1 and
+//! decimals as their units at their node's scale. Each
+//! AST function becomes an export of the same name taking i64 parameters and
+//! returning an i64 when any path returns a value. Storage slots hold JSON, so
+//! writes format the value as decimal text and reads parse it back (`true` and
+//! `false` read as 1 and 0, an unset slot as 0). Event fields are written as a
+//! JSON object into a scratch buffer before the emit host call; batch writes
+//! build their `[key, value]` pairs the same way, and batch reads parse each
+//! entry the host wrote into that buffer. Batches too big for the buffer are
+//! split into several host calls.
+//!
+//! Strings and bytes share one representation, the i64 packing the address
+//! and length of the bytes as `ptr << 32 | len`, so `HexEncode` and
+//! `HexDecode` only check the length (and `HexDecode` does not check that
+//! the bytes are UTF-8). Concat and Hash write their result into a buffer
+//! reserved for the node at compile time; slices point into their input.
+//! Nothing is allocated at run time: a value longer than its node's
+//! `max_length` reverts with `BufferOverflow` and a slice outside its input
+//! with `OutOfBounds`.
+//!
+//! Arrays pack the address and count of their i64 elements the same way, and
+//! maps those of their 16-byte entries (the packed key, then the value). A
+//! node building a collection copies it into a buffer of `max_length`
+//! elements, reverting with `BufferOverflow` when it does not fit; an index
+//! past the end reverts with `OutOfBounds`. Map entries point at their key's
+//! bytes rather than copying them. A ForEach reverts with `LoopBoundExceeded`
+//! before running over an array longer than its `max_iterations`.
+//!
+//! Linear memory starts with the scratch buffers, followed by the constant
+//! data (storage keys, event names and revert payloads) and the string
+//! buffers. The rest is a bump
+//! heap for the `alloc` export, which hosts use to pass the JSON arguments of
+//! calls through the [`dispatch`](super::entry_points::dispatch_wat) export.
 
-// TODO: Implement WASM generation from AST
-// This module will convert the AST into WebAssembly bytecode.
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use super::{
+    ast::{ASTNode, AST},
+    constructor::{init_param_types, INIT_FUNCTION},
+    entry_points::{dispatch_wat, DISPATCH_EXPORT},
+    gas::count_instructions,
+    instrumentation::{instrument_node_wat, storage_write_trace_wat, trace_imports_wat},
+    pausable::{pausable_data_bytes, pausable_wat, pause_guard_call, PAUSED_FUNCTION, PAUSE_FUNCTION, UNPAUSE_FUNCTION},
+    safe_math::{lower_arithmetic, lower_decimal_arithmetic, overflow_helpers_wat},
+};
+use crate::{types::RevertReason, wasm::host};
+
+/// Buffer storage reads are copied into
+const READ_BUFFER: u32 = 0;
+/// Buffer a value is formatted into before a storage write
+const WRITE_BUFFER: u32 = 256;
+/// Buffer the pausable feature reads storage into (128 bytes)
+const PAUSE_BUFFER: u32 = 288;
+/// Buffer event data and batch storage calls are built in
+const EVENT_BUFFER: u32 = 416;
+const EVENT_BUFFER_BYTES: u32 = 4096;
+/// Start of the constant data, after the scratch buffers
+const DATA_START: u32 = EVENT_BUFFER + EVENT_BUFFER_BYTES;
+/// Longest decimal i64, with its sign
+const MAX_INT_DIGITS: u32 = 20;
+/// Most keys one batch read asks for, leaving each value about 120 bytes of
+/// the event buffer after the entry table
+const BATCH_READ_CHUNK: usize = 32;
+const PAGE_BYTES: u32 = 65536;
+/// Most bytes the string buffers of one module may reserve
+const STATIC_BUFFER_LIMIT: u64 = 65536;
 
 /// WASM generation result
 #[derive(Debug, Clone)]
@@ -10,22 +74,1461 @@ pub struct WasmGenResult {
     pub functions: Vec<String>,
     pub imports: Vec<String>,
     pub exports: Vec<String>,
+    /// Instructions in the body of each generated function, by mnemonic
+    pub instruction_counts: HashMap<String, BTreeMap<String, u64>>,
 }
 
 /// WASM code generator
 pub struct WasmGenerator {
     optimization_level: u8,
+    pause_guarded: Option<Vec<String>>,
+    constructor: Option<crate::types::FunctionABI>,
 }
 
 impl WasmGenerator {
     pub fn new(optimization_level: u8) -> Self {
         Self {
             optimization_level,
+            pause_guarded: None,
+            constructor: None,
+        }
+    }
+
+    /// Type the `init` export's parameters after the constructor's signature
+    pub fn with_constructor(mut self, constructor: crate::types::FunctionABI) -> Self {
+        self.constructor = Some(constructor);
+        self
+    }
+
+    /// Include the pausable feature, guarding the named entry points
+    pub fn with_pausable(mut self, guarded: Vec<String>) -> Self {
+        self.pause_guarded = Some(guarded);
+        self
+    }
+
+    /// Bytes of linear memory reserved for string/bytes result buffers.
+    ///
+    /// Each Concat gets a buffer sized by its `max_length`, each Hash one for
+    /// its digest and each collection node building an array or map one of
+    /// `max_length` elements, reserved after the data segments at compile
+    /// time, so no node needs to call `memory.grow`.
+    pub fn static_buffer_bytes(&self, ast: &AST) -> u64 {
+        let mut total = 0u64;
+        let mut pending: Vec<&ASTNode> = ast.nodes.iter().collect();
+        while let Some(node) = pending.pop() {
+            if let ASTNode::StringOp { operation, max_length, .. } = node {
+                total += match operation.as_str() {
+                    "concat" => *max_length as u64,
+                    "hash" => host::HASH_DIGEST_BYTES as u64,
+                    _ => 0,
+                };
+            }
+            if let ASTNode::CollectionOp { capacity: Some(capacity), .. } = node {
+                total += *capacity as u64 * element_bytes(node) as u64;
+            }
+            pending.extend(node.children());
+        }
+        total
+    }
+
+    /// Lower a `Require` into WAT.
+    ///
+    /// The encoded revert reason is placed in a data segment at `offset`; when the
+    /// condition (already on the stack) is false, its pointer and length are passed
+    /// to the revert host call, which traps with the payload.
+    pub fn lower_require(&self, reason: &crate::types::RevertReason, offset: u32) -> (String, String) {
+        let payload = reason.encode();
+        let escaped: String = payload.iter().map(|b| format!("\\{:02x}", b)).collect();
+        let data_segment = format!("(data (i32.const {}) \"{}\")", offset, escaped);
+        let code = format!(
+            "i32.eqz\n(if (then (call ${} (i32.const {}) (i32.const {}))))",
+            crate::wasm::host::HOST_REVERT,
+            offset,
+            payload.len()
+        );
+        (data_segment, code)
+    }
+
+    /// Lower a `TryCall` into WAT.
+    ///
+    /// `call_code` pushes the call arguments; the call host import leaves a status
+    /// code on the stack. A zero status runs `success_code`. Otherwise each handler
+    /// is tried in order: named handlers match the callee's revert error (names are
+    /// placed in data segments starting at `offset`), and a catch-all ends the
+    /// chain. If nothing matches, the callee's revert is rethrown.
+    pub fn lower_try_call(
+        &self,
+        call_code: &str,
+        success_code: &str,
+        handlers: &[(Option<String>, String)],
+        offset: u32,
+    ) -> (Vec<String>, String) {
+        let mut data_segments = Vec::new();
+        let mut failure = format!("(call ${})", crate::wasm::host::HOST_CALL_RETHROW);
+        let mut next_offset = offset;
+        let mut tests = Vec::new();
+        for (error, body) in handlers {
+            match error {
+                Some(name) => {
+                    let escaped: String = name.bytes().map(|b| format!("\\{:02x}", b)).collect();
+                    data_segments.push(format!("(data (i32.const {}) \"{}\")", next_offset, escaped));
+                    tests.push((Some((next_offset, name.len())), body.as_str()));
+                    next_offset += name.len() as u32;
+                }
+                None => {
+                    tests.push((None, body.as_str()));
+                    // Handlers after a catch-all can never run
+                    break;
+                }
+            }
+        }
+        for (test, body) in tests.into_iter().rev() {
+            failure = match test {
+                Some((ptr, len)) => format!(
+                    "(if (call ${} (i32.const {}) (i32.const {}))\n  (then {})\n  (else {}))",
+                    crate::wasm::host::HOST_CALL_ERROR_IS,
+                    ptr,
+                    len,
+                    body,
+                    failure
+                ),
+                None => body.to_string(),
+            };
+        }
+        let code = format!(
+            "{}\ncall ${}\ni32.eqz\n(if\n  (then {})\n  (else {}))",
+            call_code,
+            crate::wasm::host::HOST_CALL_CONTRACT,
+            success_code,
+            failure
+        );
+        (data_segments, code)
+    }
+
+    /// Generate the contract module for an AST of functions
+    pub fn generate(&self, ast: &AST) -> Result<WasmGenResult, String> {
+        let buffers = self.static_buffer_bytes(ast);
+        if buffers > STATIC_BUFFER_LIMIT {
+            return Err(format!(
+                "String buffers need {} bytes, more than the {}-byte limit; lower the string nodes' max_length",
+                buffers, STATIC_BUFFER_LIMIT
+            ));
+        }
+        let mut module = ModuleBuilder::new(self);
+        let mut functions = Vec::new();
+        let mut instruction_counts = HashMap::new();
+        let mut code = Vec::new();
+        let mut dispatched = Vec::new();
+        for node in &ast.nodes {
+            let ASTNode::Function { name, params, body } = node else {
+                return Err("Top-level AST nodes must be functions".to_string());
+            };
+            // The constructor only runs on deployment, never through dispatch
+            if name != INIT_FUNCTION {
+                dispatched.push((functions.len(), name.as_str(), params.len(), returns_value(body)));
+            }
+            let mut prologue = Vec::new();
+            if let Some(guarded) = &self.pause_guarded {
+                if name == INIT_FUNCTION {
+                    prologue.push("call $canvas_init_admin".to_string());
+                } else if guarded.contains(name) {
+                    prologue.push(pause_guard_call());
+                }
+            }
+            let param_types = match &self.constructor {
+                Some(constructor) if name == INIT_FUNCTION => init_param_types(constructor)?,
+                _ => vec!["i64"; params.len()],
+            };
+            if param_types.len() != params.len() {
+                return Err(format!(
+                    "'{}' has {} parameters but its signature lists {}",
+                    name,
+                    params.len(),
+                    param_types.len()
+                ));
+            }
+            let function = module.function(functions.len(), name, params, &param_types, body, &prologue)?;
+            instruction_counts.insert(name.clone(), count_instructions(&function));
+            code.push(function);
+            functions.push(name.clone());
+        }
+
+        if self.pause_guarded.is_some() {
+            let (segments, pausable) = pausable_wat(module.data_end, PAUSE_BUFFER);
+            module.segments.extend(segments);
+            module.data_end += pausable_data_bytes();
+            code.push(pausable);
+            module.imports.extend([
+                host::HOST_READ_STORAGE,
+                host::HOST_WRITE_STORAGE,
+                host::HOST_REVERT,
+                host::HOST_CALLER,
+                host::HOST_CALLER_IS,
+            ]);
+            functions.extend([PAUSE_FUNCTION, UNPAUSE_FUNCTION, PAUSED_FUNCTION].map(str::to_string));
+        }
+        let mut exports = functions.clone();
+        exports.push(crate::wasm::engine::MEMORY_EXPORT.to_string());
+        if !dispatched.is_empty() {
+            code.push(dispatch_wrappers_wat(&dispatched));
+            let names: Vec<&str> = dispatched.iter().map(|(_, name, _, _)| *name).collect();
+            code.push(dispatch_wat(&names));
+            code.push(alloc_wat(module.data_end));
+            exports.extend([DISPATCH_EXPORT, crate::wasm::engine::ALLOC_EXPORT].map(str::to_string));
+        }
+
+        let mut wat = String::from("(module\n");
+        for import in &module.imports {
+            if let Some(signature) = import_signature(import) {
+                wat.push_str(&format!("  (import \"env\" \"{0}\" (func ${0} {1}))\n", import, signature));
+            }
+        }
+        if module.traced {
+            for import in trace_imports_wat() {
+                wat.push_str(&format!("  {}\n", import));
+            }
+        }
+        let pages = (module.data_end + PAGE_BYTES - 1) / PAGE_BYTES;
+        wat.push_str(&format!("  (memory (export \"{}\") {})\n", crate::wasm::engine::MEMORY_EXPORT, pages));
+        for segment in &module.segments {
+            wat.push_str(&format!("  {}\n", segment));
+        }
+        if module.uses_overflow_helpers {
+            wat.push_str(overflow_helpers_wat());
+        }
+        wat.push_str(NUMBER_HELPERS_WAT);
+        if let Some(helpers) = &module.buffer_helpers {
+            wat.push_str(helpers);
+        }
+        if module.uses_collection_helpers {
+            wat.push_str(COLLECTION_HELPERS_WAT);
+        }
+        wat.push_str(&host_helpers_wat(&module.imports));
+        for function in code {
+            wat.push_str(&function);
+            wat.push('\n');
+        }
+        wat.push(')');
+
+        log::trace!("Generated WAT (optimization level {}):\n{}", self.optimization_level, wat);
+        let wasm_bytes = wat::parse_str(&wat).map_err(|e| format!("Generated WAT is invalid: {}", e))?;
+
+        let mut imports: Vec<String> = module.imports.iter().map(|i| i.to_string()).collect();
+        if module.traced {
+            imports.extend(host::trace_host_functions().into_iter().map(str::to_string));
+        }
+        Ok(WasmGenResult {
+            wasm_bytes,
+            functions,
+            imports,
+            exports,
+            instruction_counts,
+        })
+    }
+}
+
+/// Argument decoding wrappers `$dispatch<i>` of the dispatched functions,
+/// given as (function index, name, parameter count, whether it returns a
+/// value). Each reads its function's arguments as integers from the JSON array
+/// in the argument buffer, ignoring extra ones and trapping when some are
+/// missing, and returns the result wrapped to an i32, or 0.
+fn dispatch_wrappers_wat(functions: &[(usize, &str, usize, bool)]) -> String {
+    let mut wat = String::from(DISPATCH_HELPERS_WAT);
+    for (position, (index, _, params, returns)) in functions.iter().enumerate() {
+        let args = " (call $canvas_next_arg)".repeat(*params);
+        let result = match returns {
+            true => format!("(i32.wrap_i64 (call $entry{}{}))", index, args),
+            false => format!("(call $entry{}{})\n    (i32.const 0)", index, args),
+        };
+        wat.push_str(&format!(
+            "  (func $dispatch{} (param $ptr i32) (param $len i32) (result i32)\n    \
+             (global.set $canvas_args (local.get $ptr))\n    \
+             (global.set $canvas_args_end (i32.add (local.get $ptr) (local.get $len)))\n    {})\n",
+            position, result
+        ));
+    }
+    wat
+}
+
+/// Reads the arguments of a dispatched call, one JSON array item at a time:
+/// integers, numeric strings and booleans
+const DISPATCH_HELPERS_WAT: &str = r#"
+  (global $canvas_args (mut i32) (i32.const 0))
+  (global $canvas_args_end (mut i32) (i32.const 0))
+  (func $canvas_next_arg (result i64)
+    (local $c i32) (local $start i32)
+    (block $found
+      (loop $skip
+        (if (i32.ge_u (global.get $canvas_args) (global.get $canvas_args_end)) (then unreachable))
+        (local.set $c (i32.load8_u (global.get $canvas_args)))
+        (br_if $found (i32.or (i32.eq (local.get $c) (i32.const 45))
+          (i32.or (i32.lt_u (i32.sub (local.get $c) (i32.const 48)) (i32.const 10))
+            (i32.or (i32.eq (local.get $c) (i32.const 116)) (i32.eq (local.get $c) (i32.const 102))))))
+        ;; Only array punctuation, quotes and whitespace come between items
+        (if (i32.eqz (i32.or (i32.le_u (local.get $c) (i32.const 32))
+            (i32.or (i32.eq (local.get $c) (i32.const 91))
+              (i32.or (i32.eq (local.get $c) (i32.const 44)) (i32.eq (local.get $c) (i32.const 34))))))
+          (then unreachable))
+        (global.set $canvas_args (i32.add (global.get $canvas_args) (i32.const 1)))
+        (br $skip)))
+    (local.set $start (global.get $canvas_args))
+    (block $end
+      (loop $scan
+        (br_if $end (i32.ge_u (global.get $canvas_args) (global.get $canvas_args_end)))
+        (local.set $c (i32.load8_u (global.get $canvas_args)))
+        (br_if $end (i32.or (i32.le_u (local.get $c) (i32.const 32))
+          (i32.or (i32.eq (local.get $c) (i32.const 44))
+            (i32.or (i32.eq (local.get $c) (i32.const 93)) (i32.eq (local.get $c) (i32.const 34))))))
+        (global.set $canvas_args (i32.add (global.get $canvas_args) (i32.const 1)))
+        (br $scan)))
+    (call $canvas_atoi (local.get $start) (i32.sub (global.get $canvas_args) (local.get $start))))
+"#;
+
+/// The `alloc(len) -> ptr` export: a bump allocator over the memory after the
+/// constant data, growing it as needed. Every call gets a fresh instance, so
+/// nothing is ever freed.
+fn alloc_wat(data_end: u32) -> String {
+    format!(
+        r#"  (global $canvas_heap (mut i32) (i32.const {heap}))
+  (func (export "{export}") (param $len i32) (result i32)
+    (local $ptr i32) (local $end i32) (local $size i32)
+    (local.set $ptr (global.get $canvas_heap))
+    (local.set $end (i32.add (local.get $ptr) (local.get $len)))
+    (local.set $size (i32.mul (memory.size) (i32.const {page})))
+    (if (i32.gt_u (local.get $end) (local.get $size))
+      (then
+        (if (i32.eq (memory.grow (i32.div_u (i32.add (i32.sub (local.get $end) (local.get $size)) (i32.const {last})) (i32.const {page}))) (i32.const -1))
+          (then unreachable))))
+    (global.set $canvas_heap (local.get $end))
+    (local.get $ptr))"#,
+        heap = (data_end + 7) & !7,
+        export = crate::wasm::engine::ALLOC_EXPORT,
+        page = PAGE_BYTES,
+        last = PAGE_BYTES - 1,
+    )
+}
+
+/// WAT signature of the host imports generated code calls
+fn import_signature(import: &str) -> Option<&'static str> {
+    Some(match import {
+        host::HOST_READ_STORAGE => "(param i32 i32 i32) (result i32)",
+        host::HOST_WRITE_STORAGE | host::HOST_EMIT_EVENT => "(param i32 i32 i32 i32)",
+        host::HOST_REVERT | host::HOST_BATCH_WRITE_STORAGE => "(param i32 i32)",
+        host::HOST_BATCH_READ_STORAGE => "(param i32 i32 i32) (result i32)",
+        host::HOST_CALLER => "(param i32) (result i32)",
+        host::HOST_CALLER_IS | host::HOST_CALL_ERROR_IS => "(param i32 i32) (result i32)",
+        host::HOST_CALL_CONTRACT => "(param i32 i32 i32 i32 i32 i32) (result i32)",
+        host::HOST_CALL_RETHROW => "",
+        host::HOST_HASH => "(param i32 i32 i32 i32) (result i32)",
+        host::HOST_VERIFY_SIGNATURE => "(param i32 i32 i32 i32 i32 i32 i32) (result i32)",
+        host::HOST_DECIMAL_MUL | host::HOST_DECIMAL_DIV => "(param i64 i64 i32 i32) (result i64)",
+        _ => return None,
+    })
+}
+
+/// Helper functions for number formatting and comparison
+const NUMBER_HELPERS_WAT: &str = r#"
+  (func $canvas_itoa (param $value i64) (param $out i32) (result i32)
+    (local $magnitude i64) (local $rest i64) (local $digits i32) (local $len i32)
+    (local.set $magnitude (local.get $value))
+    (if (i64.lt_s (local.get $value) (i64.const 0))
+      (then
+        (i32.store8 (local.get $out) (i32.const 45))
+        (local.set $out (i32.add (local.get $out) (i32.const 1)))
+        (local.set $len (i32.const 1))
+        (local.set $magnitude (i64.sub (i64.const 0) (local.get $value)))))
+    (local.set $rest (local.get $magnitude))
+    (loop $count
+      (local.set $digits (i32.add (local.get $digits) (i32.const 1)))
+      (local.set $rest (i64.div_u (local.get $rest) (i64.const 10)))
+      (br_if $count (i64.ne (local.get $rest) (i64.const 0))))
+    (local.set $len (i32.add (local.get $len) (local.get $digits)))
+    (loop $write
+      (local.set $digits (i32.sub (local.get $digits) (i32.const 1)))
+      (i32.store8 (i32.add (local.get $out) (local.get $digits))
+        (i32.add (i32.const 48) (i32.wrap_i64 (i64.rem_u (local.get $magnitude) (i64.const 10)))))
+      (local.set $magnitude (i64.div_u (local.get $magnitude) (i64.const 10)))
+      (br_if $write (i32.ne (local.get $digits) (i32.const 0))))
+    (local.get $len))
+  (func $canvas_atoi (param $ptr i32) (param $len i32) (result i64)
+    (local $end i32) (local $negative i32) (local $value i64) (local $digit i32)
+    (if (i32.eqz (local.get $len)) (then unreachable))
+    (if (i32.eq (i32.load8_u (local.get $ptr)) (i32.const 116)) (then (return (i64.const 1))))
+    (if (i32.eq (i32.load8_u (local.get $ptr)) (i32.const 102)) (then (return (i64.const 0))))
+    (local.set $end (i32.add (local.get $ptr) (local.get $len)))
+    (if (i32.eq (i32.load8_u (local.get $ptr)) (i32.const 45))
+      (then
+        (local.set $negative (i32.const 1))
+        (local.set $ptr (i32.add (local.get $ptr) (i32.const 1)))))
+    (if (i32.ge_u (local.get $ptr) (local.get $end)) (then unreachable))
+    (loop $digits
+      (local.set $digit (i32.sub (i32.load8_u (local.get $ptr)) (i32.const 48)))
+      (if (i32.gt_u (local.get $digit) (i32.const 9)) (then unreachable))
+      (local.set $value (i64.add (i64.mul (local.get $value) (i64.const 10)) (i64.extend_i32_u (local.get $digit))))
+      (local.set $ptr (i32.add (local.get $ptr) (i32.const 1)))
+      (br_if $digits (i32.lt_u (local.get $ptr) (local.get $end))))
+    (if (result i64) (local.get $negative)
+      (then (i64.sub (i64.const 0) (local.get $value)))
+      (else (local.get $value))))
+  (func $canvas_cmp (param $a i64) (param $b i64) (result i64)
+    (i64.extend_i32_s (i32.sub (i64.gt_s (local.get $a) (local.get $b)) (i64.lt_s (local.get $a) (local.get $b)))))
+"#;
+
+/// Helpers of packed string values, reverting with the payloads at
+/// `overflow` and `out_of_bounds`, each a `(ptr, len)` constant
+fn buffer_helpers_wat(overflow: (u32, u32), out_of_bounds: (u32, u32)) -> String {
+    format!(
+        r#"
+  (func $canvas_ptr (param $value i64) (result i32)
+    (i32.wrap_i64 (i64.shr_u (local.get $value) (i64.const 32))))
+  (func $canvas_len (param $value i64) (result i32)
+    (i32.wrap_i64 (local.get $value)))
+  (func $canvas_pack (param $ptr i32) (param $len i32) (result i64)
+    (i64.or (i64.shl (i64.extend_i32_u (local.get $ptr)) (i64.const 32)) (i64.extend_i32_u (local.get $len))))
+  (func $canvas_overflow
+    (call ${revert} (i32.const {overflow_ptr}) (i32.const {overflow_len})))
+  (func $canvas_out_of_bounds
+    (call ${revert} (i32.const {bounds_ptr}) (i32.const {bounds_len})))
+  (func $canvas_bounded (param $value i64) (param $max i32) (result i64)
+    (if (i32.gt_u (call $canvas_len (local.get $value)) (local.get $max)) (then (call $canvas_overflow)))
+    (local.get $value))
+  (func $canvas_concat (param $a i64) (param $b i64) (param $out i32) (param $max i32) (result i64)
+    (local $a_len i32) (local $len i32)
+    (local.set $a_len (call $canvas_len (local.get $a)))
+    (local.set $len (i32.add (local.get $a_len) (call $canvas_len (local.get $b))))
+    (if (i32.gt_u (local.get $len) (local.get $max)) (then (call $canvas_overflow)))
+    (memory.copy (local.get $out) (call $canvas_ptr (local.get $a)) (local.get $a_len))
+    (memory.copy (i32.add (local.get $out) (local.get $a_len)) (call $canvas_ptr (local.get $b)) (call $canvas_len (local.get $b)))
+    (call $canvas_pack (local.get $out) (local.get $len)))
+  ;; A negative end slices to the end of the input, as the node interpreter does
+  (func $canvas_slice (param $value i64) (param $start i64) (param $end i64) (result i64)
+    (local $len i64)
+    (local.set $len (i64.extend_i32_u (call $canvas_len (local.get $value))))
+    (if (i64.lt_s (local.get $end) (i64.const 0)) (then (local.set $end (local.get $len))))
+    (if (i32.or (i64.lt_s (local.get $start) (i64.const 0))
+          (i32.or (i64.gt_s (local.get $start) (local.get $end)) (i64.gt_s (local.get $end) (local.get $len))))
+      (then (call $canvas_out_of_bounds)))
+    (call $canvas_pack
+      (i32.add (call $canvas_ptr (local.get $value)) (i32.wrap_i64 (local.get $start)))
+      (i32.wrap_i64 (i64.sub (local.get $end) (local.get $start)))))
+  (func $canvas_bytes_cmp (param $a i64) (param $b i64) (result i64)
+    (local $a_len i32) (local $b_len i32) (local $i i32) (local $x i32) (local $y i32)
+    (local.set $a_len (call $canvas_len (local.get $a)))
+    (local.set $b_len (call $canvas_len (local.get $b)))
+    (block $done
+      (loop $next
+        (br_if $done (i32.or (i32.ge_u (local.get $i) (local.get $a_len)) (i32.ge_u (local.get $i) (local.get $b_len))))
+        (local.set $x (i32.load8_u (i32.add (call $canvas_ptr (local.get $a)) (local.get $i))))
+        (local.set $y (i32.load8_u (i32.add (call $canvas_ptr (local.get $b)) (local.get $i))))
+        (if (i32.ne (local.get $x) (local.get $y))
+          (then (return (i64.extend_i32_s (i32.sub (i32.gt_u (local.get $x) (local.get $y)) (i32.lt_u (local.get $x) (local.get $y)))))))
+        (local.set $i (i32.add (local.get $i) (i32.const 1)))
+        (br $next)))
+    (i64.extend_i32_s (i32.sub (i32.gt_u (local.get $a_len) (local.get $b_len)) (i32.lt_u (local.get $a_len) (local.get $b_len)))))
+"#,
+        revert = host::HOST_REVERT,
+        overflow_ptr = overflow.0,
+        overflow_len = overflow.1,
+        bounds_ptr = out_of_bounds.0,
+        bounds_len = out_of_bounds.1,
+    )
+}
+
+/// Array and map helpers, on top of the string helpers. Arrays hold 8-byte
+/// elements and maps 16-byte entries of a packed key and a value.
+const COLLECTION_HELPERS_WAT: &str = r#"
+  ;; Address of an array element, reverting when the index is past the end
+  (func $canvas_index (param $array i64) (param $index i64) (result i32)
+    (if (i64.ge_u (local.get $index) (i64.extend_i32_u (call $canvas_len (local.get $array))))
+      (then (call $canvas_out_of_bounds)))
+    (i32.add (call $canvas_ptr (local.get $array)) (i32.wrap_i64 (i64.shl (local.get $index) (i64.const 3)))))
+  ;; Copy a collection of `size`-byte entries into `out`, with room for `extra` more of its `max`
+  (func $canvas_collection_copy (param $value i64) (param $size i32) (param $extra i32) (param $out i32) (param $max i32) (result i64)
+    (local $len i32)
+    (local.set $len (i32.add (call $canvas_len (local.get $value)) (local.get $extra)))
+    (if (i32.gt_u (local.get $len) (local.get $max)) (then (call $canvas_overflow)))
+    (memory.copy (local.get $out) (call $canvas_ptr (local.get $value))
+      (i32.mul (call $canvas_len (local.get $value)) (local.get $size)))
+    (call $canvas_pack (local.get $out) (local.get $len)))
+  (func $canvas_array_set (param $array i64) (param $index i64) (param $value i64) (param $out i32) (param $max i32) (result i64)
+    (local $result i64)
+    (local.set $result (call $canvas_collection_copy (local.get $array) (i32.const 8) (i32.const 0) (local.get $out) (local.get $max)))
+    (i64.store (call $canvas_index (local.get $result) (local.get $index)) (local.get $value))
+    (local.get $result))
+  (func $canvas_array_push (param $array i64) (param $value i64) (param $out i32) (param $max i32) (result i64)
+    (local $result i64)
+    (local.set $result (call $canvas_collection_copy (local.get $array) (i32.const 8) (i32.const 1) (local.get $out) (local.get $max)))
+    (i64.store (call $canvas_index (local.get $result) (i64.extend_i32_u (call $canvas_len (local.get $array))))
+      (local.get $value))
+    (local.get $result))
+  (func $canvas_array_remove (param $array i64) (param $index i64) (param $out i32) (param $max i32) (result i64)
+    (local $len i32) (local $at i32)
+    (drop (call $canvas_index (local.get $array) (local.get $index)))
+    (local.set $len (i32.sub (call $canvas_len (local.get $array)) (i32.const 1)))
+    (if (i32.gt_u (local.get $len) (local.get $max)) (then (call $canvas_overflow)))
+    (local.set $at (i32.shl (i32.wrap_i64 (local.get $index)) (i32.const 3)))
+    (memory.copy (local.get $out) (call $canvas_ptr (local.get $array)) (local.get $at))
+    (memory.copy (i32.add (local.get $out) (local.get $at))
+      (i32.add (call $canvas_ptr (local.get $array)) (i32.add (local.get $at) (i32.const 8)))
+      (i32.sub (i32.shl (local.get $len) (i32.const 3)) (local.get $at)))
+    (call $canvas_pack (local.get $out) (local.get $len)))
+  ;; Address of a map's entry for a key, or 0 when it has none
+  (func $canvas_map_find (param $map i64) (param $key i64) (result i32)
+    (local $entry i32) (local $end i32)
+    (local.set $entry (call $canvas_ptr (local.get $map)))
+    (local.set $end (i32.add (local.get $entry) (i32.shl (call $canvas_len (local.get $map)) (i32.const 4))))
+    (block $done
+      (loop $next
+        (br_if $done (i32.ge_u (local.get $entry) (local.get $end)))
+        (if (i64.eqz (call $canvas_bytes_cmp (i64.load (local.get $entry)) (local.get $key)))
+          (then (return (local.get $entry))))
+        (local.set $entry (i32.add (local.get $entry) (i32.const 16)))
+        (br $next)))
+    (i32.const 0))
+  ;; A missing key reads as 0, as the interpreter's null does
+  (func $canvas_map_get (param $map i64) (param $key i64) (result i64)
+    (local $entry i32)
+    (local.set $entry (call $canvas_map_find (local.get $map) (local.get $key)))
+    (if (result i64) (local.get $entry)
+      (then (i64.load offset=8 (local.get $entry)))
+      (else (i64.const 0))))
+  (func $canvas_map_has (param $map i64) (param $key i64) (result i64)
+    (i64.extend_i32_u (i32.ne (call $canvas_map_find (local.get $map) (local.get $key)) (i32.const 0))))
+  (func $canvas_map_set (param $map i64) (param $key i64) (param $value i64) (param $out i32) (param $max i32) (result i64)
+    (local $entry i32) (local $result i64)
+    (local.set $entry (call $canvas_map_find (local.get $map) (local.get $key)))
+    (local.set $result (call $canvas_collection_copy (local.get $map) (i32.const 16) (i32.eqz (local.get $entry)) (local.get $out) (local.get $max)))
+    (if (local.get $entry)
+      (then (local.set $entry (i32.add (local.get $out) (i32.sub (local.get $entry) (call $canvas_ptr (local.get $map))))))
+      (else
+        (local.set $entry (i32.add (local.get $out) (i32.shl (call $canvas_len (local.get $map)) (i32.const 4))))
+        (i64.store (local.get $entry) (local.get $key))))
+    (i64.store offset=8 (local.get $entry) (local.get $value))
+    (local.get $result))
+  ;; The last entry takes the place of the removed one; a missing key leaves the map as it was
+  (func $canvas_map_remove (param $map i64) (param $key i64) (param $out i32) (param $max i32) (result i64)
+    (local $entry i32) (local $len i32) (local $last i32)
+    (local.set $entry (call $canvas_map_find (local.get $map) (local.get $key)))
+    (local.set $len (i32.sub (call $canvas_len (local.get $map)) (i32.ne (local.get $entry) (i32.const 0))))
+    (if (i32.gt_u (local.get $len) (local.get $max)) (then (call $canvas_overflow)))
+    (memory.copy (local.get $out) (call $canvas_ptr (local.get $map)) (i32.shl (local.get $len) (i32.const 4)))
+    (local.set $last (i32.add (call $canvas_ptr (local.get $map)) (i32.shl (local.get $len) (i32.const 4))))
+    (if (i32.and (i32.ne (local.get $entry) (i32.const 0)) (i32.lt_u (local.get $entry) (local.get $last)))
+      (then (memory.copy (i32.add (local.get $out) (i32.sub (local.get $entry) (call $canvas_ptr (local.get $map))))
+        (local.get $last) (i32.const 16))))
+    (call $canvas_pack (local.get $out) (local.get $len)))
+"#;
+
+/// Helpers wrapping the host imports a module uses
+fn host_helpers_wat(imports: &BTreeSet<&'static str>) -> String {
+    let mut wat = String::new();
+    if imports.contains(host::HOST_READ_STORAGE) {
+        wat.push_str(&format!(
+            r#"
+  (func $canvas_read_storage (param $key i32) (param $key_len i32) (result i64)
+    (local $len i32)
+    (local.set $len (call ${read} (local.get $key) (local.get $key_len) (i32.const {buffer})))
+    (if (result i64) (i32.lt_s (local.get $len) (i32.const 0))
+      (then (i64.const 0))
+      (else (call $canvas_atoi (i32.const {buffer}) (local.get $len)))))
+"#,
+            read = host::HOST_READ_STORAGE,
+            buffer = READ_BUFFER,
+        ));
+    }
+    if imports.contains(host::HOST_WRITE_STORAGE) {
+        wat.push_str(&format!(
+            r#"
+  (func $canvas_write_storage (param $key i32) (param $key_len i32) (param $value i64)
+    (call ${write} (local.get $key) (local.get $key_len) (i32.const {buffer})
+      (call $canvas_itoa (local.get $value) (i32.const {buffer}))))
+"#,
+            write = host::HOST_WRITE_STORAGE,
+            buffer = WRITE_BUFFER,
+        ));
+    }
+    if imports.contains(host::HOST_HASH) {
+        wat.push_str(&format!(
+            r#"
+  (func $canvas_hash (param $data i64) (param $algorithm i32) (param $out i32) (result i64)
+    (call $canvas_pack (local.get $out)
+      (call ${hash} (local.get $algorithm)
+        (call $canvas_ptr (local.get $data)) (call $canvas_len (local.get $data)) (local.get $out))))
+"#,
+            hash = host::HOST_HASH,
+        ));
+    }
+    if imports.contains(host::HOST_VERIFY_SIGNATURE) {
+        wat.push_str(&format!(
+            r#"
+  (func $canvas_verify_signature (param $key i64) (param $message i64) (param $signature i64) (param $scheme i32) (result i64)
+    (i64.extend_i32_u (call ${verify} (local.get $scheme)
+      (call $canvas_ptr (local.get $key)) (call $canvas_len (local.get $key))
+      (call $canvas_ptr (local.get $message)) (call $canvas_len (local.get $message))
+      (call $canvas_ptr (local.get $signature)) (call $canvas_len (local.get $signature)))))
+"#,
+            verify = host::HOST_VERIFY_SIGNATURE,
+        ));
+    }
+    if imports.contains(host::HOST_BATCH_READ_STORAGE) {
+        wat.push_str(
+            r#"
+  (func $canvas_batch_value (param $entry i32) (result i64)
+    (local $len i32)
+    (local.set $len (i32.load offset=4 (local.get $entry)))
+    (if (result i64) (i32.lt_s (local.get $len) (i32.const 0))
+      (then (i64.const 0))
+      (else (call $canvas_atoi (i32.load (local.get $entry)) (local.get $len)))))
+"#,
+        );
+    }
+    let builds_buffer = [host::HOST_EMIT_EVENT, host::HOST_BATCH_WRITE_STORAGE, host::HOST_CALL_CONTRACT];
+    if builds_buffer.iter().any(|import| imports.contains(import)) {
+        wat.push_str(&format!(
+            r#"
+  (global $canvas_cursor (mut i32) (i32.const {buffer}))
+  (func $canvas_begin
+    (global.set $canvas_cursor (i32.const {buffer})))
+  (func $canvas_put (param $ptr i32) (param $len i32)
+    (memory.copy (global.get $canvas_cursor) (local.get $ptr) (local.get $len))
+    (global.set $canvas_cursor (i32.add (global.get $canvas_cursor) (local.get $len))))
+  (func $canvas_put_int (param $value i64)
+    (global.set $canvas_cursor
+      (i32.add (global.get $canvas_cursor) (call $canvas_itoa (local.get $value) (global.get $canvas_cursor)))))
+"#,
+            buffer = EVENT_BUFFER,
+        ));
+    }
+    if imports.contains(host::HOST_EMIT_EVENT) {
+        wat.push_str(&format!(
+            r#"
+  (func $canvas_emit (param $name i32) (param $name_len i32)
+    (call ${emit} (local.get $name) (local.get $name_len)
+      (i32.const {buffer}) (i32.sub (global.get $canvas_cursor) (i32.const {buffer}))))
+"#,
+            emit = host::HOST_EMIT_EVENT,
+            buffer = EVENT_BUFFER,
+        ));
+    }
+    if imports.contains(host::HOST_BATCH_WRITE_STORAGE) {
+        wat.push_str(&format!(
+            r#"
+  (func $canvas_batch_write
+    (call ${write} (i32.const {buffer}) (i32.sub (global.get $canvas_cursor) (i32.const {buffer}))))
+"#,
+            write = host::HOST_BATCH_WRITE_STORAGE,
+            buffer = EVENT_BUFFER,
+        ));
+    }
+    wat
+}
+
+/// Module-wide state while generating functions
+struct ModuleBuilder<'g> {
+    generator: &'g WasmGenerator,
+    /// Data segments, laid out from [`DATA_START`]
+    segments: Vec<String>,
+    /// Offsets of constants already placed
+    constants: HashMap<Vec<u8>, u32>,
+    data_end: u32,
+    imports: BTreeSet<&'static str>,
+    traced: bool,
+    uses_overflow_helpers: bool,
+    /// WAT of the string helpers, once a string value is used
+    buffer_helpers: Option<String>,
+    uses_collection_helpers: bool,
+}
+
+/// What the i64 of a value holds
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ValueKind {
+    /// An integer, or a boolean as 0 or 1
+    Number,
+    /// Bytes in linear memory, packed as `ptr << 32 | len`
+    Bytes,
+    /// i64 elements in linear memory, packed as `ptr << 32 | count`
+    Array,
+    /// Key and value entries in linear memory, packed as `ptr << 32 | count`
+    Map,
+}
+
+impl ValueKind {
+    fn name(self) -> &'static str {
+        match self {
+            ValueKind::Number => "a number",
+            ValueKind::Bytes => "a string",
+            ValueKind::Array => "an array",
+            ValueKind::Map => "a map",
+        }
+    }
+}
+
+fn value_kind(node: &ASTNode) -> ValueKind {
+    match node {
+        ASTNode::Literal { value_type, .. } if value_type == "string" || value_type == "bytes" => ValueKind::Bytes,
+        ASTNode::Literal { value_type, .. } if value_type == "array" => ValueKind::Array,
+        ASTNode::Literal { value_type, .. } if value_type == "map" => ValueKind::Map,
+        ASTNode::CollectionOp { operation, .. } => match operation.as_str() {
+            "array_set" | "array_push" | "array_remove" => ValueKind::Array,
+            "map_set" | "map_remove" => ValueKind::Map,
+            _ => ValueKind::Number,
+        },
+        ASTNode::StringOp { operation, .. } if operation != "equal" && operation != "compare" => ValueKind::Bytes,
+        ASTNode::Traced { body, .. } => body.first().map_or(ValueKind::Number, |value| value_kind(value)),
+        _ => ValueKind::Number,
+    }
+}
+
+/// Bytes of one element of the collection a collection node works on
+fn element_bytes(node: &ASTNode) -> u32 {
+    match node {
+        ASTNode::CollectionOp { operation, .. } if operation.starts_with("map") => 16,
+        _ => 8,
+    }
+}
+
+/// Packed i64 of bytes in linear memory
+fn pack(ptr: u32, len: u32) -> i64 {
+    ((ptr as i64) << 32) | len as i64
+}
+
+/// Per-function state: WAT names of parameters and variables, open tracepoints
+struct FunctionScope {
+    names: HashMap<String, String>,
+    locals: Vec<String>,
+    tracepoints: Vec<u32>,
+    returns: bool,
+    /// Loops generated so far, numbering their labels
+    loops: u32,
+}
+
+impl FunctionScope {
+    fn get(&self, name: &str) -> Result<&str, String> {
+        self.names
+            .get(name)
+            .map(String::as_str)
+            .ok_or_else(|| format!("Unknown identifier '{}'", name))
+    }
+
+    fn local(&mut self, name: &str) -> String {
+        if let Some(local) = self.names.get(name) {
+            return local.clone();
+        }
+        let local = format!("$l{}", self.locals.len());
+        self.locals.push(local.clone());
+        self.names.insert(name.to_string(), local.clone());
+        local
+    }
+}
+
+fn returns_value(nodes: &[Box<ASTNode>]) -> bool {
+    let mut pending: Vec<&ASTNode> = nodes.iter().map(|n| n.as_ref()).collect();
+    while let Some(node) = pending.pop() {
+        if let ASTNode::Return { value: Some(_) } = node {
+            return true;
+        }
+        pending.extend(node.children());
+    }
+    false
+}
+
+impl<'g> ModuleBuilder<'g> {
+    fn new(generator: &'g WasmGenerator) -> Self {
+        Self {
+            generator,
+            segments: Vec::new(),
+            constants: HashMap::new(),
+            data_end: DATA_START,
+            imports: BTreeSet::new(),
+            traced: false,
+            uses_overflow_helpers: false,
+            buffer_helpers: None,
+            uses_collection_helpers: false,
         }
     }
 
-    pub fn generate(&self, _ast: &crate::compiler::ast::AST) -> Result<WasmGenResult, String> {
-        // TODO: Implement WASM generation
-        Err("WASM generation not yet implemented".to_string())
+    /// Reserve a zeroed buffer after the data placed so far
+    fn reserve(&mut self, bytes: u32) -> u32 {
+        let offset = self.data_end;
+        self.data_end += bytes;
+        offset
     }
-} 
\ No newline at end of file
+
+    /// Include the string helpers, placing their revert payloads on first use
+    fn use_buffer_helpers(&mut self) {
+        if self.buffer_helpers.is_some() {
+            return;
+        }
+        let overflow = RevertReason::new("BufferOverflow", "value exceeds the node's max_length");
+        let out_of_bounds = RevertReason::new("OutOfBounds", "slice range is out of bounds");
+        let overflow = self.constant(&overflow.encode());
+        let out_of_bounds = self.constant(&out_of_bounds.encode());
+        self.buffer_helpers = Some(buffer_helpers_wat(overflow, out_of_bounds));
+        self.imports.insert(host::HOST_REVERT);
+    }
+
+    /// Address of a constant in the data segments, placing it on first use
+    fn constant(&mut self, bytes: &[u8]) -> (u32, u32) {
+        if let Some(offset) = self.constants.get(bytes) {
+            return (*offset, bytes.len() as u32);
+        }
+        let offset = self.data_end;
+        let escaped: String = bytes.iter().map(|b| format!("\\{:02x}", b)).collect();
+        self.segments.push(format!("(data (i32.const {}) \"{}\")", offset, escaped));
+        self.constants.insert(bytes.to_vec(), offset);
+        self.data_end += bytes.len() as u32;
+        (offset, bytes.len() as u32)
+    }
+
+    fn function(
+        &mut self,
+        index: usize,
+        name: &str,
+        params: &[String],
+        param_types: &[&str],
+        body: &[Box<ASTNode>],
+        prologue: &[String],
+    ) -> Result<String, String> {
+        let mut scope = FunctionScope {
+            names: HashMap::new(),
+            locals: Vec::new(),
+            tracepoints: Vec::new(),
+            returns: returns_value(body),
+            loops: 0,
+        };
+        let mut signature = String::new();
+        for (i, (param, ty)) in params.iter().zip(param_types).enumerate() {
+            scope.names.insert(param.clone(), format!("$p{}", i));
+            signature.push_str(&format!(" (param $p{} {})", i, ty));
+        }
+        if scope.returns {
+            signature.push_str(" (result i64)");
+        }
+
+        let mut code = String::new();
+        for line in prologue {
+            code.push_str(line);
+            code.push('\n');
+        }
+        self.block(body, &mut scope, &mut code)?;
+        if scope.returns {
+            // Paths that reach the end without returning were ruled out when building the AST
+            code.push_str("unreachable\n");
+        }
+
+        let locals: String = scope.locals.iter().map(|l| format!(" (local {} i64)", l)).collect();
+        let export = serde_json::to_string(name).map_err(|e| e.to_string())?;
+        Ok(format!(
+            "  (func $entry{} (export {}){}{}\n{})",
+            index, export, signature, locals, indent(&code)
+        ))
+    }
+
+    fn block(&mut self, nodes: &[Box<ASTNode>], scope: &mut FunctionScope, code: &mut String) -> Result<(), String> {
+        for node in nodes {
+            self.statement(node, scope, code)?;
+        }
+        Ok(())
+    }
+
+    fn statement(&mut self, node: &ASTNode, scope: &mut FunctionScope, code: &mut String) -> Result<(), String> {
+        match node {
+            ASTNode::Traced { tracepoint, body } => {
+                self.traced = true;
+                scope.tracepoints.push(*tracepoint);
+                let mut inner = String::new();
+                self.block(body, scope, &mut inner)?;
+                scope.tracepoints.pop();
+                code.push_str(&instrument_node_wat(*tracepoint, inner.trim_end()));
+                code.push('\n');
+            }
+            ASTNode::Variable { name, value } => {
+                self.expression(value, scope, code)?;
+                let local = scope.local(name);
+                code.push_str(&format!("local.set {}\n", local));
+            }
+            ASTNode::Call { function, arguments } if function == host::HOST_WRITE_STORAGE => {
+                let [key, value] = arguments.as_slice() else {
+                    return Err("Storage writes take a key and a value".to_string());
+                };
+                let (ptr, len) = self.string_constant(key)?;
+                code.push_str(&format!("i32.const {}\ni32.const {}\n", ptr, len));
+                self.expression(value, scope, code)?;
+                code.push_str("call $canvas_write_storage\n");
+                self.imports.insert(host::HOST_WRITE_STORAGE);
+                if let Some(tracepoint) = scope.tracepoints.last() {
+                    code.push_str(&storage_write_trace_wat(*tracepoint, ptr, len));
+                    code.push('\n');
+                }
+            }
+            ASTNode::Call { function, arguments } if function == host::HOST_BATCH_WRITE_STORAGE => {
+                self.imports.insert(host::HOST_BATCH_WRITE_STORAGE);
+                let mut entries = Vec::new();
+                for entry in arguments {
+                    let ASTNode::Variable { name: key, value } = entry.as_ref() else {
+                        return Err("Batch write entries must be named by their key".to_string());
+                    };
+                    let key_json = serde_json::to_string(key).map_err(|e| e.to_string())?;
+                    entries.push((key.as_str(), key_json, value.as_ref()));
+                }
+                // `[` and `]` around `["key",value]` pairs joined by commas
+                let entry_bytes = |key_json: &String| key_json.len() as u32 + MAX_INT_DIGITS + 4;
+                let mut chunks: Vec<Vec<_>> = Vec::new();
+                let mut size = 0;
+                for entry in entries {
+                    let bytes = entry_bytes(&entry.1);
+                    if bytes + 2 > EVENT_BUFFER_BYTES {
+                        return Err(format!("Storage key '{}' is too long for a batch write", entry.0));
+                    }
+                    let fits = chunks.last().is_some_and(|chunk| {
+                        chunk.len() < host::MAX_BATCH_KEYS && size + bytes + 2 <= EVENT_BUFFER_BYTES
+                    });
+                    if !fits {
+                        chunks.push(Vec::new());
+                        size = 0;
+                    }
+                    size += bytes;
+                    chunks.last_mut().expect("chunk was just pushed").push(entry);
+                }
+                for chunk in chunks {
+                    code.push_str("call $canvas_begin\n");
+                    for (i, (_, key_json, value)) in chunk.iter().enumerate() {
+                        let prefix = format!("{}[{},", if i == 0 { "[" } else { "," }, key_json);
+                        let (ptr, len) = self.constant(prefix.as_bytes());
+                        code.push_str(&format!("i32.const {}\ni32.const {}\ncall $canvas_put\n", ptr, len));
+                        self.expression(value, scope, code)?;
+                        code.push_str("call $canvas_put_int\n");
+                        let (ptr, len) = self.constant(b"]");
+                        code.push_str(&format!("i32.const {}\ni32.const {}\ncall $canvas_put\n", ptr, len));
+                    }
+                    let (ptr, len) = self.constant(b"]");
+                    code.push_str(&format!("i32.const {}\ni32.const {}\ncall $canvas_put\n", ptr, len));
+                    code.push_str("call $canvas_batch_write\n");
+                    if let Some(tracepoint) = scope.tracepoints.last() {
+                        for (key, _, _) in &chunk {
+                            let (ptr, len) = self.constant(key.as_bytes());
+                            code.push_str(&storage_write_trace_wat(*tracepoint, ptr, len));
+                            code.push('\n');
+                        }
+                    }
+                }
+            }
+            ASTNode::Call { function, arguments } if function == host::HOST_BATCH_READ_STORAGE => {
+                self.imports.insert(host::HOST_BATCH_READ_STORAGE);
+                for chunk in arguments.chunks(BATCH_READ_CHUNK) {
+                    let mut keys = Vec::new();
+                    let mut variables = Vec::new();
+                    for entry in chunk {
+                        let ASTNode::Variable { name, value } = entry.as_ref() else {
+                            return Err("Batch reads must name the variable each key is read into".to_string());
+                        };
+                        let ASTNode::Literal { value: key, .. } = value.as_ref() else {
+                            return Err("Expected a constant string".to_string());
+                        };
+                        keys.push(key.as_str());
+                        variables.push(name);
+                    }
+                    let keys = serde_json::to_vec(&keys).map_err(|e| e.to_string())?;
+                    let (ptr, len) = self.constant(&keys);
+                    code.push_str(&format!(
+                        "(drop (call ${} (i32.const {}) (i32.const {}) (i32.const {})))\n",
+                        host::HOST_BATCH_READ_STORAGE,
+                        ptr,
+                        len,
+                        EVENT_BUFFER
+                    ));
+                    for (i, name) in variables.into_iter().enumerate() {
+                        let local = scope.local(name);
+                        code.push_str(&format!(
+                            "i32.const {}\ncall $canvas_batch_value\nlocal.set {}\n",
+                            EVENT_BUFFER + 8 * i as u32,
+                            local
+                        ));
+                    }
+                }
+            }
+            ASTNode::Call { function, arguments } if function == host::HOST_EMIT_EVENT => {
+                let Some((name, fields)) = arguments.split_first() else {
+                    return Err("Events need a name".to_string());
+                };
+                let (name_ptr, name_len) = self.string_constant(name)?;
+                self.imports.insert(host::HOST_EMIT_EVENT);
+                if fields.is_empty() {
+                    code.push_str(&format!(
+                        "(call ${} (i32.const {}) (i32.const {}) (i32.const 0) (i32.const 0))\n",
+                        host::HOST_EMIT_EVENT,
+                        name_ptr,
+                        name_len
+                    ));
+                    return Ok(());
+                }
+                let mut size = 1;
+                code.push_str("call $canvas_begin\n");
+                for (i, field) in fields.iter().enumerate() {
+                    let ASTNode::Variable { name: field, value } = field.as_ref() else {
+                        return Err("Event fields must be named".to_string());
+                    };
+                    let key = serde_json::to_string(field).map_err(|e| e.to_string())?;
+                    let prefix = format!("{}{}:", if i == 0 { "{" } else { "," }, key);
+                    let (ptr, len) = self.constant(prefix.as_bytes());
+                    size += len + MAX_INT_DIGITS;
+                    code.push_str(&format!("i32.const {}\ni32.const {}\ncall $canvas_put\n", ptr, len));
+                    self.expression(value, scope, code)?;
+                    code.push_str("call $canvas_put_int\n");
+                }
+                if size > EVENT_BUFFER_BYTES {
+                    return Err(format!("Event data may exceed the {}-byte event buffer", EVENT_BUFFER_BYTES));
+                }
+                let (ptr, len) = self.constant(b"}");
+                code.push_str(&format!("i32.const {}\ni32.const {}\ncall $canvas_put\n", ptr, len));
+                code.push_str(&format!("i32.const {}\ni32.const {}\ncall $canvas_emit\n", name_ptr, name_len));
+            }
+            ASTNode::Require { condition, reason } => {
+                self.condition(condition, scope, code)?;
+                let (segment, require) = self.generator.lower_require(reason, self.data_end);
+                self.segments.push(segment);
+                self.data_end += reason.encode().len() as u32;
+                code.push_str(&require);
+                code.push('\n');
+                self.imports.insert(host::HOST_REVERT);
+            }
+            ASTNode::TryCall { target, function, arguments, on_success, handlers } => {
+                // The JSON array of arguments is built in the event buffer first
+                let (args_ptr, args_len) = if arguments.is_empty() {
+                    let (ptr, len) = self.constant(b"[]");
+                    (format!("i32.const {}", ptr), format!("i32.const {}", len))
+                } else {
+                    if arguments.len() as u32 * (MAX_INT_DIGITS + 1) + 1 > EVENT_BUFFER_BYTES {
+                        return Err(format!(
+                            "Arguments of '{}' may exceed the {}-byte call buffer",
+                            function, EVENT_BUFFER_BYTES
+                        ));
+                    }
+                    code.push_str("call $canvas_begin\n");
+                    for (i, argument) in arguments.iter().enumerate() {
+                        let (ptr, len) = self.constant(if i == 0 { b"[" } else { b"," });
+                        code.push_str(&format!("i32.const {}\ni32.const {}\ncall $canvas_put\n", ptr, len));
+                        self.expression(argument, scope, code)?;
+                        code.push_str("call $canvas_put_int\n");
+                    }
+                    let (ptr, len) = self.constant(b"]");
+                    code.push_str(&format!("i32.const {}\ni32.const {}\ncall $canvas_put\n", ptr, len));
+                    (
+                        format!("i32.const {}", EVENT_BUFFER),
+                        format!("(i32.sub (global.get $canvas_cursor) (i32.const {}))", EVENT_BUFFER),
+                    )
+                };
+                let (target_ptr, target_len) = self.string_constant(target)?;
+                let (function_ptr, function_len) = self.constant(function.as_bytes());
+                let call_code = format!(
+                    "i32.const {}\ni32.const {}\ni32.const {}\ni32.const {}\n{}\n{}",
+                    target_ptr, target_len, function_ptr, function_len, args_ptr, args_len
+                );
+                self.imports.insert(host::HOST_CALL_CONTRACT);
+
+                let mut success_code = String::new();
+                self.block(on_success, scope, &mut success_code)?;
+                let mut lowered = Vec::new();
+                for handler in handlers {
+                    let mut body = String::new();
+                    self.block(&handler.body, scope, &mut body)?;
+                    lowered.push((handler.error.clone(), body));
+                }
+                let (segments, try_call) =
+                    self.generator.lower_try_call(&call_code, &success_code, &lowered, self.data_end);
+                // Only the names up to the first catch-all are placed
+                let names = handlers.iter().map_while(|h| h.error.as_ref());
+                self.data_end += names.map(|name| name.len() as u32).sum::<u32>();
+                self.segments.extend(segments);
+                if handlers.first().is_some_and(|h| h.error.is_some()) {
+                    self.imports.insert(host::HOST_CALL_ERROR_IS);
+                }
+                if handlers.iter().all(|h| h.error.is_some()) {
+                    self.imports.insert(host::HOST_CALL_RETHROW);
+                }
+                code.push_str(&try_call);
+                code.push('\n');
+            }
+            ASTNode::If { condition, then_branch, else_branch } => {
+                self.condition(condition, scope, code)?;
+                let mut then_code = String::new();
+                self.block(then_branch, scope, &mut then_code)?;
+                let mut else_code = String::new();
+                if let Some(else_branch) = else_branch {
+                    self.block(else_branch, scope, &mut else_code)?;
+                }
+                code.push_str(&format!(
+                    "(if\n  (then\n{}  )\n  (else\n{}  ))\n",
+                    indent(&indent(&then_code)),
+                    indent(&indent(&else_code))
+                ));
+            }
+            ASTNode::BoundedLoop { item, index, collection, max_iterations, body } => {
+                self.use_collection_helpers();
+                self.operand(collection, ValueKind::Array, scope, code)?;
+                let array = scope.local(&format!("{}_array", item));
+                let item = scope.local(item);
+                let index = scope.local(index);
+                let label = format!("$loop{}", scope.loops);
+                scope.loops += 1;
+                let reason = RevertReason::new("LoopBoundExceeded", "array exceeds the max_iterations");
+                let (reason_ptr, reason_len) = self.constant(&reason.encode());
+                let mut body_code = String::new();
+                self.block(body, scope, &mut body_code)?;
+                code.push_str(&format!(
+                    "local.set {array}
+                     (if (i64.gt_u (i64.extend_i32_u (call $canvas_len (local.get {array}))) (i64.const {max}))
+                         (then (call ${revert} (i32.const {reason_ptr}) (i32.const {reason_len}))))
+                     (local.set {index} (i64.const 0))
+                     (block {label}_done
+                         (loop {label}
+                             (br_if {label}_done
+                                 (i64.ge_u (local.get {index}) (i64.extend_i32_u (call $canvas_len (local.get {array})))))
+                             (local.set {item} (i64.load (call $canvas_index (local.get {array}) (local.get {index}))))
+                     {body}                             (local.set {index} (i64.add (local.get {index}) (i64.const 1)))
+                             (br {label})))
+",
+                    max = max_iterations,
+                    revert = host::HOST_REVERT,
+                    body = indent(&indent(&body_code)),
+                ));
+            }
+            ASTNode::Return { value } => {
+                if let Some(value) = value {
+                    self.expression(value, scope, code)?;
+                }
+                // Close the tracepoints the return jumps out of
+                for tracepoint in scope.tracepoints.iter().rev() {
+                    code.push_str(&format!("(call ${} (i32.const {}))\n", host::HOST_TRACE_EXIT, tracepoint));
+                }
+                code.push_str("return\n");
+            }
+            other => {
+                self.expression(other, scope, code)?;
+                code.push_str("drop\n");
+            }
+        }
+        Ok(())
+    }
+
+    /// Push an i32 that is non-zero when the i64 condition holds
+    fn condition(&mut self, condition: &ASTNode, scope: &mut FunctionScope, code: &mut String) -> Result<(), String> {
+        self.expression(condition, scope, code)?;
+        code.push_str("i64.const 0\ni64.ne\n");
+        Ok(())
+    }
+
+    fn string_constant(&mut self, node: &ASTNode) -> Result<(u32, u32), String> {
+        match node {
+            ASTNode::Literal { value, value_type } if value_type == "string" => Ok(self.constant(value.as_bytes())),
+            _ => Err("Expected a constant string".to_string()),
+        }
+    }
+
+    /// Include the collection helpers and the string helpers they build on
+    fn use_collection_helpers(&mut self) {
+        self.use_buffer_helpers();
+        self.uses_collection_helpers = true;
+    }
+
+    /// Push the i64 value of an expression of the given kind
+    fn operand(
+        &mut self,
+        node: &ASTNode,
+        kind: ValueKind,
+        scope: &mut FunctionScope,
+        code: &mut String,
+    ) -> Result<(), String> {
+        let actual = value_kind(node);
+        if actual != kind {
+            return Err(format!("{} is {} where {} is expected", describe(node), actual.name(), kind.name()));
+        }
+        self.value(node, scope, code)
+    }
+
+    /// Push the i64 value of a number expression
+    fn expression(&mut self, node: &ASTNode, scope: &mut FunctionScope, code: &mut String) -> Result<(), String> {
+        self.operand(node, ValueKind::Number, scope, code)
+    }
+
+    /// Push the i64 value of a string expression
+    fn bytes(&mut self, node: &ASTNode, scope: &mut FunctionScope, code: &mut String) -> Result<(), String> {
+        self.operand(node, ValueKind::Bytes, scope, code)
+    }
+
+    /// Push a string expression checked against a node's `max_length`
+    fn bounded(
+        &mut self,
+        node: &ASTNode,
+        max_length: u32,
+        scope: &mut FunctionScope,
+        code: &mut String,
+    ) -> Result<(), String> {
+        self.bytes(node, scope, code)?;
+        code.push_str(&format!("i32.const {}\ncall $canvas_bounded\n", max_length));
+        Ok(())
+    }
+
+    /// Push the i64 value of an expression of any kind
+    fn value(&mut self, node: &ASTNode, scope: &mut FunctionScope, code: &mut String) -> Result<(), String> {
+        match node {
+            ASTNode::Literal { value, value_type } if value_type == "string" || value_type == "bytes" => {
+                let bytes = match value_type.as_str() {
+                    "bytes" => {
+                        crate::nodes::decode_hex(value).ok_or_else(|| format!("Invalid bytes literal '{}'", value))?
+                    }
+                    _ => value.as_bytes().to_vec(),
+                };
+                let (ptr, len) = self.constant(&bytes);
+                code.push_str(&format!("i64.const {}\n", pack(ptr, len)));
+            }
+            ASTNode::Literal { value, value_type } if value_type == "array" || value_type == "map" => {
+                let invalid = || format!("Invalid {} literal '{}'", value_type, value);
+                let number = |item: &serde_json::Value| {
+                    item.as_i64()
+                        .or_else(|| item.as_bool().map(i64::from))
+                        .ok_or_else(|| format!("Collection element {} is not an integer or boolean", item))
+                };
+                let mut bytes = Vec::new();
+                let count = match serde_json::from_str::<serde_json::Value>(value).map_err(|_| invalid())? {
+                    serde_json::Value::Array(items) if value_type == "array" => {
+                        for item in &items {
+                            bytes.extend_from_slice(&number(item)?.to_le_bytes());
+                        }
+                        items.len()
+                    }
+                    serde_json::Value::Object(entries) if value_type == "map" => {
+                        for (key, item) in &entries {
+                            let (ptr, len) = self.constant(key.as_bytes());
+                            bytes.extend_from_slice(&pack(ptr, len).to_le_bytes());
+                            bytes.extend_from_slice(&number(item)?.to_le_bytes());
+                        }
+                        entries.len()
+                    }
+                    _ => return Err(invalid()),
+                };
+                self.use_collection_helpers();
+                let (ptr, _) = self.constant(&bytes);
+                code.push_str(&format!("i64.const {}\n", pack(ptr, count as u32)));
+            }
+            ASTNode::Literal { value, value_type } => {
+                let value: i64 = match (value_type.as_str(), value.as_str()) {
+                    ("boolean", "true") => 1,
+                    ("boolean", _) => 0,
+                    ("integer", text) => text.parse().map_err(|_| format!("Invalid integer literal '{}'", text))?,
+                    (other, text) => {
+                        return Err(format!("{} value '{}' is not supported by code generation yet", other, text))
+                    }
+                };
+                code.push_str(&format!("i64.const {}\n", value));
+            }
+            ASTNode::Identifier { name } => {
+                let local = scope.get(name)?;
+                code.push_str(&format!("local.get {}\n", local));
+            }
+            ASTNode::BinaryOp { operator, left, right, overflow, decimal_scale } => {
+                self.expression(left, scope, code)?;
+                self.expression(right, scope, code)?;
+                let op = match operator.as_str() {
+                    "and" => "i64.and".to_string(),
+                    "or" => "i64.or".to_string(),
+                    "eq" => "i64.eq\ni64.extend_i32_u".to_string(),
+                    "cmp" => "call $canvas_cmp".to_string(),
+                    arithmetic => {
+                        let op = match decimal_scale {
+                            Some(scale) => lower_decimal_arithmetic(arithmetic, *scale, *overflow),
+                            None => lower_arithmetic(arithmetic, *overflow),
+                        }
+                        .map_err(|e| e.to_string())?;
+                        self.uses_overflow_helpers |= op.starts_with("call");
+                        for import in [host::HOST_DECIMAL_MUL, host::HOST_DECIMAL_DIV] {
+                            if op.ends_with(import) {
+                                self.imports.insert(import);
+                            }
+                        }
+                        op
+                    }
+                };
+                code.push_str(&op);
+                code.push('\n');
+            }
+            ASTNode::Call { function, arguments } if function == host::HOST_READ_STORAGE => {
+                let [key] = arguments.as_slice() else {
+                    return Err("Storage reads take a key".to_string());
+                };
+                let (ptr, len) = self.string_constant(key)?;
+                code.push_str(&format!("i32.const {}\ni32.const {}\ncall $canvas_read_storage\n", ptr, len));
+                self.imports.insert(host::HOST_READ_STORAGE);
+            }
+            ASTNode::Call { function, arguments } if function == host::HOST_VERIFY_SIGNATURE => {
+                let [scheme, key, message, signature] = arguments.as_slice() else {
+                    return Err("Signature checks take a scheme, a key, a message and a signature".to_string());
+                };
+                let scheme = match scheme.as_ref() {
+                    ASTNode::Literal { value, .. } => host::SignatureScheme::from_name(value)
+                        .ok_or_else(|| format!("Unknown signature scheme '{}'", value))?,
+                    _ => return Err("Signature schemes must be constant".to_string()),
+                };
+                self.use_buffer_helpers();
+                for bytes in [key, message, signature] {
+                    self.bytes(bytes, scope, code)?;
+                }
+                code.push_str(&format!("i32.const {}\ncall $canvas_verify_signature\n", scheme.code()));
+                self.imports.insert(host::HOST_VERIFY_SIGNATURE);
+            }
+            ASTNode::StringOp { operation, arguments, max_length } => {
+                self.use_buffer_helpers();
+                let max_length = *max_length;
+                match (operation.as_str(), arguments.as_slice()) {
+                    ("concat", [a, b]) => {
+                        self.bounded(a, max_length, scope, code)?;
+                        self.bounded(b, max_length, scope, code)?;
+                        let out = self.reserve(max_length);
+                        code.push_str(&format!("i32.const {}\ni32.const {}\ncall $canvas_concat\n", out, max_length));
+                    }
+                    ("slice", [input, start, end @ ..]) => {
+                        self.bounded(input, max_length, scope, code)?;
+                        self.expression(start, scope, code)?;
+                        match end {
+                            [end] => self.expression(end, scope, code)?,
+                            _ => code.push_str("i64.const -1\n"),
+                        }
+                        code.push_str("call $canvas_slice\n");
+                    }
+                    ("hex_encode" | "hex_decode", [input]) => self.bounded(input, max_length, scope, code)?,
+                    ("equal" | "compare", [a, b]) => {
+                        self.bounded(a, max_length, scope, code)?;
+                        self.bounded(b, max_length, scope, code)?;
+                        code.push_str("call $canvas_bytes_cmp\n");
+                        if operation == "equal" {
+                            code.push_str("i64.eqz\ni64.extend_i32_u\n");
+                        }
+                    }
+                    ("hash", [algorithm, input]) => {
+                        let algorithm = match algorithm.as_ref() {
+                            ASTNode::Literal { value, .. } => host::HashAlgorithm::from_name(value)
+                                .ok_or_else(|| format!("Unknown hash algorithm '{}'", value))?,
+                            _ => return Err("Hash algorithms must be constant".to_string()),
+                        };
+                        self.bounded(input, max_length, scope, code)?;
+                        let out = self.reserve(host::HASH_DIGEST_BYTES as u32);
+                        code.push_str(&format!(
+                            "i32.const {}\ni32.const {}\ncall $canvas_hash\n",
+                            algorithm.code(),
+                            out
+                        ));
+                        self.imports.insert(host::HOST_HASH);
+                    }
+                    _ => return Err(format!("Unknown string operation '{}'", operation)),
+                }
+            }
+            ASTNode::CollectionOp { operation, collection, arguments, capacity, .. } => {
+                self.use_collection_helpers();
+                let kind = match operation.as_str() {
+                    "length" => value_kind(collection),
+                    map if map.starts_with("map") => ValueKind::Map,
+                    _ => ValueKind::Array,
+                };
+                if kind != ValueKind::Array && kind != ValueKind::Map {
+                    return Err(format!("{} is {} where a collection is expected", describe(collection), kind.name()));
+                }
+                self.operand(collection, kind, scope, code)?;
+                let out = capacity.map(|capacity| (self.reserve(capacity * element_bytes(node)), capacity));
+                match (operation.as_str(), arguments.as_slice(), out) {
+                    ("array_get" | "array_removed", [index], None) => {
+                        self.expression(index, scope, code)?;
+                        code.push_str("call $canvas_index
+i64.load
+");
+                    }
+                    ("array_set", [index, value], Some((out, max))) => {
+                        self.expression(index, scope, code)?;
+                        self.expression(value, scope, code)?;
+                        code.push_str(&format!("i32.const {}
+i32.const {}
+call $canvas_array_set
+", out, max));
+                    }
+                    ("array_push", [value], Some((out, max))) => {
+                        self.expression(value, scope, code)?;
+                        code.push_str(&format!("i32.const {}
+i32.const {}
+call $canvas_array_push
+", out, max));
+                    }
+                    ("array_remove", [index], Some((out, max))) => {
+                        self.expression(index, scope, code)?;
+                        code.push_str(&format!("i32.const {}
+i32.const {}
+call $canvas_array_remove
+", out, max));
+                    }
+                    ("map_get" | "map_removed", [key], None) => {
+                        self.bytes(key, scope, code)?;
+                        code.push_str("call $canvas_map_get
+");
+                    }
+                    ("map_found", [key], None) => {
+                        self.bytes(key, scope, code)?;
+                        code.push_str("call $canvas_map_has
+");
+                    }
+                    ("map_set", [key, value], Some((out, max))) => {
+                        self.bytes(key, scope, code)?;
+                        self.expression(value, scope, code)?;
+                        code.push_str(&format!("i32.const {}
+i32.const {}
+call $canvas_map_set
+", out, max));
+                    }
+                    ("map_remove", [key], Some((out, max))) => {
+                        self.bytes(key, scope, code)?;
+                        code.push_str(&format!("i32.const {}
+i32.const {}
+call $canvas_map_remove
+", out, max));
+                    }
+                    ("length", [], None) => code.push_str("call $canvas_len
+i64.extend_i32_u
+"),
+                    _ => return Err(format!("Unknown collection operation '{}'", operation)),
+                }
+            }
+            ASTNode::Traced { tracepoint, body } => {
+                let [value] = body.as_slice() else {
+                    return Err("A traced expression has one value".to_string());
+                };
+                self.traced = true;
+                let mut inner = String::new();
+                self.value(value, scope, &mut inner)?;
+                code.push_str(&instrument_node_wat(*tracepoint, inner.trim_end()));
+                code.push('\n');
+            }
+            other => return Err(format!("{} is not supported by code generation yet", describe(other))),
+        }
+        Ok(())
+    }
+}
+
+fn describe(node: &ASTNode) -> &'static str {
+    match node {
+        ASTNode::Function { .. } => "A nested function",
+        ASTNode::Variable { .. } => "A variable declaration in an expression",
+        ASTNode::If { .. } => "An If in an expression",
+        ASTNode::Call { .. } => "This call",
+        ASTNode::Literal { .. } => "This literal",
+        ASTNode::BinaryOp { .. } => "This operator",
+        ASTNode::StringOp { .. } => "This string operation",
+        ASTNode::CollectionOp { .. } => "This collection operation",
+        ASTNode::Require { .. } => "A Require in an expression",
+        ASTNode::TryCall { .. } => "TryCall",
+        ASTNode::BoundedLoop { .. } => "A ForEach in an expression",
+        ASTNode::Identifier { .. } => "This identifier",
+        ASTNode::Return { .. } => "A return in an expression",
+        ASTNode::Traced { .. } => "This traced node",
+    }
+}
+
+fn indent(code: &str) -> String {
+    code.lines().map(|line| format!("    {}\n", line)).collect()
+}
@@ -0,0 +1,258 @@
+//! WIT interfaces and component-model output
+//!
+//! The contract ABI is described as a WIT world so generated contracts can be
+//! loaded by component-model hosts and bindings generated with standard tools
+//! (`wit-bindgen`, `jco`, ...). Entry points are exported from a `contract`
+//! interface and return `result<_, contract-error>`, where `contract-error`
+//! has one case per declared error plus `reverted(string)` for untyped
+//! reverts. Events are imported from an `events` interface the host provides.
+//!
+//! Names are converted to kebab-case. Decimals cross the boundary as `s64`
+//! fixed-point units; `any` and object values as JSON-encoded strings.
+
+use std::collections::HashSet;
+use std::path::Path;
+
+use crate::{
+    error::{CanvasError, CanvasResult},
+    types::{ContractABI, ParameterABI, StateMutability, ValueType},
+};
+
+/// WIT package namespace of generated contracts
+pub const WIT_NAMESPACE: &str = "canvas";
+/// Error case used for reverts without a declared error
+pub const UNTYPED_REVERT_CASE: &str = "reverted";
+
+const WIT_KEYWORDS: &[&str] = &[
+    "use", "type", "func", "u8", "u16", "u32", "u64", "s8", "s16", "s32", "s64", "f32", "f64", "char",
+    "record", "resource", "own", "borrow", "flags", "variant", "enum", "bool", "string", "option", "result",
+    "future", "stream", "list", "tuple", "interface", "world", "import", "export", "package", "include",
+    "with", "static", "constructor", "as", "from",
+];
+
+/// Convert an ABI name (camelCase, snake_case, ...) to a WIT identifier
+pub fn wit_name(name: &str) -> CanvasResult<String> {
+    let mut words: Vec<String> = Vec::new();
+    let mut current = String::new();
+    let mut previous_lower = false;
+    for c in name.chars() {
+        if !c.is_ascii_alphanumeric() {
+            if !current.is_empty() {
+                words.push(std::mem::take(&mut current));
+            }
+            previous_lower = false;
+            continue;
+        }
+        if c.is_ascii_uppercase() && previous_lower && !current.is_empty() {
+            words.push(std::mem::take(&mut current));
+        }
+        // WIT words cannot start with a digit; keep digits attached to the previous word
+        previous_lower = c.is_ascii_lowercase() || c.is_ascii_digit();
+        current.push(c.to_ascii_lowercase());
+    }
+    if !current.is_empty() {
+        words.push(current);
+    }
+    if words.is_empty() || words[0].starts_with(|c: char| c.is_ascii_digit()) {
+        return Err(CanvasError::Compilation(format!("'{}' cannot be expressed as a WIT name", name)));
+    }
+    let mut joined: Vec<String> = Vec::with_capacity(words.len());
+    for word in words {
+        match joined.last_mut() {
+            Some(last) if word.starts_with(|c: char| c.is_ascii_digit()) => last.push_str(&word),
+            _ => joined.push(word),
+        }
+    }
+    let kebab = joined.join("-");
+    Ok(if WIT_KEYWORDS.contains(&kebab.as_str()) { format!("%{}", kebab) } else { kebab })
+}
+
+/// WIT type carrying values of a graph type
+pub fn wit_type(value_type: &ValueType) -> CanvasResult<String> {
+    Ok(match value_type {
+        ValueType::Boolean => "bool".to_string(),
+        ValueType::Integer | ValueType::Decimal(_) => "s64".to_string(),
+        ValueType::Float => "f64".to_string(),
+        ValueType::String | ValueType::Object(_) | ValueType::Any => "string".to_string(),
+        ValueType::Bytes => "list<u8>".to_string(),
+        ValueType::Array(inner) => format!("list<{}>", wit_type(inner)?),
+        ValueType::Map(inner) => format!("list<tuple<string, {}>>", wit_type(inner)?),
+        ValueType::Flow => {
+            return Err(CanvasError::Compilation("flow ports have no WIT representation".to_string()))
+        }
+    })
+}
+
+fn wit_params(params: &[ParameterABI]) -> CanvasResult<String> {
+    let mut seen = HashSet::new();
+    let mut rendered = Vec::new();
+    for param in params {
+        let name = wit_name(&param.name)?;
+        if !seen.insert(name.clone()) {
+            return Err(CanvasError::Compilation(format!("duplicate WIT parameter '{}'", name)));
+        }
+        rendered.push(format!("{}: {}", name, wit_type(&param.value_type)?));
+    }
+    Ok(rendered.join(", "))
+}
+
+/// Payload of a result or error case: nothing, one type, or a tuple
+fn wit_payload(params: &[ParameterABI]) -> CanvasResult<Option<String>> {
+    let types = params.iter().map(|p| wit_type(&p.value_type)).collect::<CanvasResult<Vec<_>>>()?;
+    Ok(match types.len() {
+        0 => None,
+        1 => types.into_iter().next(),
+        _ => Some(format!("tuple<{}>", types.join(", "))),
+    })
+}
+
+fn unique(seen: &mut HashSet<String>, name: String, kind: &str) -> CanvasResult<String> {
+    if seen.insert(name.clone()) {
+        Ok(name)
+    } else {
+        Err(CanvasError::Compilation(format!("{} names collide in WIT as '{}'", kind, name)))
+    }
+}
+
+/// WIT package describing a contract ABI, with a world named after the contract
+pub fn generate_wit(abi: &ContractABI, contract_name: &str) -> CanvasResult<String> {
+    let world = wit_name(contract_name)?;
+    let mut wit = format!("package {}:{};\n", WIT_NAMESPACE, world.trim_start_matches('%'));
+
+    if !abi.events.is_empty() {
+        wit.push_str("\n/// Events the contract emits; provided by the host\ninterface events {\n");
+        let mut seen = HashSet::new();
+        for event in &abi.events {
+            let name = unique(&mut seen, wit_name(&event.name)?, "event")?;
+            wit.push_str(&format!("  {}: func({});\n", name, wit_params(&event.inputs)?));
+        }
+        wit.push_str("}\n");
+    }
+
+    wit.push_str("\n/// Contract entry points\ninterface contract {\n  variant contract-error {\n");
+    let mut seen = HashSet::from([UNTYPED_REVERT_CASE.to_string()]);
+    for error in &abi.errors {
+        let name = unique(&mut seen, wit_name(&error.name)?, "error")?;
+        match wit_payload(&error.inputs)? {
+            Some(payload) => wit.push_str(&format!("    {}({}),\n", name, payload)),
+            None => wit.push_str(&format!("    {},\n", name)),
+        }
+    }
+    wit.push_str(&format!("    {}(string),\n  }}\n", UNTYPED_REVERT_CASE));
+
+    let mut seen = HashSet::new();
+    for function in &abi.functions {
+        let name = unique(&mut seen, wit_name(&function.name)?, "function")?;
+        let mutability = match function.state_mutability {
+            StateMutability::Pure => "pure",
+            StateMutability::View => "view",
+            StateMutability::NonPayable => "nonpayable",
+            StateMutability::Payable => "payable",
+        };
+        let ok = wit_payload(&function.outputs)?.unwrap_or_else(|| "_".to_string());
+        wit.push_str(&format!(
+            "\n  /// {}\n  {}: func({}) -> result<{}, contract-error>;\n",
+            mutability,
+            name,
+            wit_params(&function.inputs)?,
+            ok
+        ));
+    }
+    wit.push_str("}\n");
+
+    wit.push_str(&format!("\nworld {} {{\n", world));
+    if !abi.events.is_empty() {
+        wit.push_str("  import events;\n");
+    }
+    wit.push_str("  export contract;\n}\n");
+    Ok(wit)
+}
+
+/// Wrap a core WASM module into a component typed by `wit`
+///
+/// The core module must export the canonical-ABI lowering of the world's
+/// `contract` functions.
+pub fn encode_component(core_wasm: &[u8], wit: &str) -> CanvasResult<Vec<u8>> {
+    let mut resolve = wit_parser::Resolve::default();
+    let package = wit_parser::UnresolvedPackage::parse(Path::new("contract.wit"), wit)
+        .map_err(|e| CanvasError::Compilation(format!("invalid WIT: {}", e)))?;
+    let package = resolve
+        .push(package)
+        .map_err(|e| CanvasError::Compilation(format!("invalid WIT: {}", e)))?;
+    let world = resolve
+        .select_world(package, None)
+        .map_err(|e| CanvasError::Compilation(format!("invalid WIT: {}", e)))?;
+
+    let mut module = core_wasm.to_vec();
+    wit_component::embed_component_metadata(&mut module, &resolve, world, wit_component::StringEncoding::UTF8)
+        .map_err(|e| CanvasError::Wasm(format!("failed to embed WIT metadata: {}", e)))?;
+    wit_component::ComponentEncoder::default()
+        .validate(true)
+        .module(&module)
+        .and_then(|encoder| encoder.encode())
+        .map_err(|e| CanvasError::Wasm(format!("failed to encode component: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{ErrorABI, EventABI, FunctionABI};
+
+    fn param(name: &str, value_type: ValueType) -> ParameterABI {
+        ParameterABI { name: name.to_string(), value_type, indexed: false }
+    }
+
+    #[test]
+    fn test_generate_wit_for_abi() {
+        let abi = ContractABI {
+            functions: vec![
+                FunctionABI {
+                    name: "transferFrom".to_string(),
+                    inputs: vec![param("to", ValueType::String), param("amount", ValueType::Integer)],
+                    outputs: Vec::new(),
+                    state_mutability: StateMutability::NonPayable,
+                    gas_estimate: None,
+                },
+                FunctionABI {
+                    name: "balance_of".to_string(),
+                    inputs: vec![param("owner", ValueType::String)],
+                    outputs: vec![param("balance", ValueType::Decimal(2))],
+                    state_mutability: StateMutability::View,
+                    gas_estimate: None,
+                },
+            ],
+            events: vec![EventABI {
+                name: "Transfer".to_string(),
+                inputs: vec![param("to", ValueType::String), param("tags", ValueType::Array(Box::new(ValueType::Bytes)))],
+                anonymous: false,
+            }],
+            errors: vec![
+                ErrorABI {
+                    name: "InsufficientBalance".to_string(),
+                    inputs: vec![param("needed", ValueType::Integer), param("available", ValueType::Integer)],
+                },
+                ErrorABI { name: "Paused".to_string(), inputs: Vec::new() },
+            ],
+            storage: Vec::new(),
+            metadata: Default::default(),
+        };
+
+        let wit = generate_wit(&abi, "Token V2").unwrap();
+        assert!(wit.starts_with("package canvas:token-v2;\n"));
+        assert!(wit.contains("  transfer: func(to: string, tags: list<list<u8>>);\n"));
+        assert!(wit.contains("    insufficient-balance(tuple<s64, s64>),\n    paused,\n    reverted(string),\n"));
+        assert!(wit.contains("  transfer-from: func(to: string, amount: s64) -> result<_, contract-error>;\n"));
+        assert!(wit.contains("  /// view\n  balance-of: func(owner: string) -> result<s64, contract-error>;\n"));
+        assert!(wit.contains("world token-v2 {\n  import events;\n  export contract;\n}\n"));
+        assert_eq!(wit_name("type").unwrap(), "%type");
+        assert_eq!(wit_name("erc20Balance").unwrap(), "erc20-balance");
+
+        let mut resolve = wit_parser::Resolve::default();
+        let package = wit_parser::UnresolvedPackage::parse(Path::new("contract.wit"), &wit).unwrap();
+        resolve.push(package).unwrap();
+
+        let mut clashing = abi.clone();
+        clashing.functions[1].name = "transfer_from".to_string();
+        assert!(generate_wit(&clashing, "Token").is_err());
+    }
+}
@@ -1,7 +1,7 @@
 //! Configuration management for Canvas Contracts
 
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::path::PathBuf;
 
 use crate::error::{CanvasError, CanvasResult};
@@ -19,6 +19,24 @@ pub struct Config {
     pub baals: BaalsConfig,
     /// Development settings
     pub development: DevelopmentConfig,
+    /// AI assistant settings
+    #[serde(default)]
+    pub ai: AiConfig,
+    /// User data retention settings
+    #[serde(default)]
+    pub privacy: PrivacyConfig,
+    /// Anonymous usage metrics (off unless the user opts in)
+    #[serde(default)]
+    pub telemetry: TelemetryConfig,
+    /// Self-update settings
+    #[serde(default)]
+    pub update: UpdateConfig,
+    /// Marketplace connection settings
+    #[serde(default)]
+    pub marketplace: MarketplaceConfig,
+    /// Which commands the desktop UI may invoke
+    #[serde(default)]
+    pub permissions: PermissionsConfig,
 }
 
 /// Application configuration
@@ -51,6 +69,57 @@ pub struct CompilerConfig {
     pub wasm_target: String,
     /// Custom compiler flags
     pub flags: Vec<String>,
+    /// Default overflow behavior for arithmetic nodes without their own setting
+    #[serde(default)]
+    pub overflow_mode: crate::types::OverflowMode,
+    /// Generate pause/unpause admin functions and guard state-mutating entry points
+    #[serde(default)]
+    pub pausable: bool,
+    /// Inject node and storage tracepoints; only honored in debug builds (`debug_info`)
+    #[serde(default)]
+    pub instrument: bool,
+    /// Also emit a WASM component whose WIT world describes the contract ABI
+    #[serde(default)]
+    pub component: bool,
+    /// Cost tables of the per-function gas estimates
+    #[serde(default)]
+    pub gas_model: GasModelConfig,
+}
+
+/// Cost tables of the compiler's gas model (`[compiler.gas_model]`)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GasModelConfig {
+    /// Gas per node type, replacing the built-in bound of that type
+    #[serde(default)]
+    pub node_costs: BTreeMap<String, u64>,
+    /// Gas per WASM instruction, e.g. `"i64.div_s" = 4`
+    #[serde(default)]
+    pub instruction_costs: BTreeMap<String, u64>,
+    /// Gas of instructions missing from `instruction_costs`
+    #[serde(default = "default_instruction_cost")]
+    pub default_instruction_cost: u64,
+    /// Share of `max_iterations` a loop is assumed to run in the average case
+    #[serde(default = "default_average_loop_fraction")]
+    pub average_loop_fraction: f64,
+}
+
+fn default_instruction_cost() -> u64 {
+    1 // wasmtime's fuel per instruction
+}
+
+fn default_average_loop_fraction() -> f64 {
+    0.5
+}
+
+impl Default for GasModelConfig {
+    fn default() -> Self {
+        Self {
+            node_costs: BTreeMap::new(),
+            instruction_costs: BTreeMap::new(),
+            default_instruction_cost: default_instruction_cost(),
+            average_loop_fraction: default_average_loop_fraction(),
+        }
+    }
 }
 
 /// Runtime configuration
@@ -68,6 +137,20 @@ pub struct RuntimeConfig {
     pub sandbox_mode: bool,
     /// Timeout (in seconds)
     pub timeout: u64,
+    /// Fuel budget of one custom node call in the editor and simulator
+    #[serde(default = "default_custom_node_fuel")]
+    pub custom_node_fuel: u64,
+    /// Memory limit of a custom node module (in pages)
+    #[serde(default = "default_custom_node_memory_pages")]
+    pub custom_node_memory_pages: u32,
+}
+
+fn default_custom_node_fuel() -> u64 {
+    10_000_000
+}
+
+fn default_custom_node_memory_pages() -> u32 {
+    256 // 16MB
 }
 
 /// BaaLS integration configuration
@@ -85,6 +168,428 @@ pub struct BaalsConfig {
     pub local_node_port: u16,
     /// Authentication token
     pub auth_token: Option<String>,
+    /// Economic parameters of the target chain
+    #[serde(default)]
+    pub network: NetworkProfile,
+    /// Other networks to compare execution costs against
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub networks: Vec<NetworkProfile>,
+    /// Relayers that submit signed meta-transactions on the signer's behalf
+    #[serde(default)]
+    pub relayers: Vec<RelayerConfig>,
+}
+
+/// A meta-transaction relayer
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RelayerConfig {
+    /// Name, also the relayer's nonce domain
+    pub name: String,
+    /// Base URL of the relayer API
+    pub url: String,
+    /// Environment variable holding the relayer API key, if it needs one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_key_env: Option<String>,
+}
+
+/// Storage pricing of a BaaLS network
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NetworkProfile {
+    /// Profile name, e.g. "local" or "mainnet"
+    pub name: String,
+    /// Rent charged per stored byte each epoch (in base units)
+    pub rent_per_byte_epoch: u64,
+    /// Epoch length (in seconds)
+    pub epoch_seconds: u64,
+    /// Refundable deposit locked per stored byte (in base units)
+    pub storage_deposit_per_byte: u64,
+    /// Deepest nesting of function frames a contract may reach (entry point
+    /// plus composite nodes)
+    #[serde(default = "default_max_nesting_depth")]
+    pub max_nesting_depth: u32,
+    /// Longest chain of cross-contract calls a contract may start
+    #[serde(default = "default_max_call_depth")]
+    pub max_call_depth: u32,
+    /// BIP32 path of the hardware wallet account used on this network
+    #[serde(default = "default_derivation_path")]
+    pub derivation_path: String,
+    /// Chain id the node at `baals.node_url` must report, if pinned
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chain_id: Option<u64>,
+    /// How transactions are charged for
+    #[serde(default)]
+    pub fee_model: FeeModel,
+}
+
+/// Transaction fee model of a network
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum FeeModel {
+    /// Every unit of gas costs the same
+    Flat { gas_price: u64 },
+    /// A base fee set by the chain from block usage, plus a tip to the block
+    /// producer. The base fee may rise by up to `max_increase_percent` before
+    /// the transaction is included.
+    DynamicBaseFee {
+        base_fee: u64,
+        priority_fee: u64,
+        #[serde(default = "default_max_increase_percent")]
+        max_increase_percent: u64,
+    },
+    /// Gas at a flat price plus a charge per byte of storage written
+    PerByteStorage { gas_price: u64, byte_price: u64 },
+}
+
+fn default_max_increase_percent() -> u64 {
+    12
+}
+
+impl Default for FeeModel {
+    fn default() -> Self {
+        FeeModel::Flat { gas_price: 1 }
+    }
+}
+
+impl FeeModel {
+    /// Short name, as used in `kind`
+    pub fn kind(&self) -> &'static str {
+        match self {
+            FeeModel::Flat { .. } => "flat",
+            FeeModel::DynamicBaseFee { .. } => "dynamic_base_fee",
+            FeeModel::PerByteStorage { .. } => "per_byte_storage",
+        }
+    }
+}
+
+fn default_max_nesting_depth() -> u32 {
+    16
+}
+
+fn default_max_call_depth() -> u32 {
+    8
+}
+
+fn default_derivation_path() -> String {
+    crate::baals::signer::DEFAULT_DERIVATION_PATH.to_string()
+}
+
+impl Default for NetworkProfile {
+    fn default() -> Self {
+        Self {
+            name: "local".to_string(),
+            rent_per_byte_epoch: 1,
+            epoch_seconds: 3600,
+            storage_deposit_per_byte: 100,
+            max_nesting_depth: default_max_nesting_depth(),
+            max_call_depth: default_max_call_depth(),
+            derivation_path: default_derivation_path(),
+            chain_id: None,
+            fee_model: FeeModel::default(),
+        }
+    }
+}
+
+/// AI assistant configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AiConfig {
+    /// LLM backend ("openai" or "llamacpp"); the rule engine alone is used when unset
+    pub llm_backend: Option<String>,
+    /// Base URL of the LLM server
+    pub llm_url: String,
+    /// Model name sent to OpenAI-compatible servers
+    pub llm_model: String,
+    /// Environment variable holding the API key
+    pub llm_api_key_env: String,
+    /// Request timeout (in milliseconds)
+    pub llm_timeout_ms: u64,
+    /// Allow graph contents (node types, names, storage keys) to be sent to the LLM
+    pub share_graph_data: bool,
+    /// URL of the signed security rule pack to update from
+    #[serde(default)]
+    pub rule_pack_url: Option<String>,
+    /// Hex ed25519 public keys trusted to sign rule packs
+    #[serde(default)]
+    pub rule_pack_keys: Vec<String>,
+}
+
+/// What account deletion does to a user's public content
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ContentRetention {
+    /// Keep the content, detached from the account
+    Anonymize,
+    /// Delete the content
+    Remove,
+}
+
+impl ContentRetention {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "anonymize" => Some(ContentRetention::Anonymize),
+            "remove" => Some(ContentRetention::Remove),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ContentRetention::Anonymize => "anonymize",
+            ContentRetention::Remove => "remove",
+        }
+    }
+}
+
+/// User data retention configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PrivacyConfig {
+    /// Handling of comments, forum posts, tutorials and reviews of deleted accounts
+    pub deleted_content: ContentRetention,
+    /// Days anonymized content is kept before it is purged (0 keeps it indefinitely)
+    pub anonymized_retention_days: u32,
+}
+
+impl Default for PrivacyConfig {
+    fn default() -> Self {
+        Self {
+            deleted_content: ContentRetention::Anonymize,
+            anonymized_retention_days: 0,
+        }
+    }
+}
+
+/// Where opted-in usage metrics are sent; the payload format is documented in
+/// the `telemetry` module
+pub const DEFAULT_TELEMETRY_ENDPOINT: &str = "https://telemetry.canvascontracts.dev/v1/events";
+
+/// Usage metrics configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TelemetryConfig {
+    /// Record and send anonymous usage metrics
+    pub enabled: bool,
+    pub endpoint: String,
+    /// Events kept locally before the oldest are dropped
+    pub max_buffered_events: usize,
+}
+
+impl Default for TelemetryConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            endpoint: DEFAULT_TELEMETRY_ENDPOINT.to_string(),
+            max_buffered_events: 1000,
+        }
+    }
+}
+
+/// Signed release feed the updater checks by default
+pub const DEFAULT_RELEASE_FEED_URL: &str = "https://releases.canvascontracts.dev/feed.json";
+
+/// Release channel to take updates from
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReleaseChannel {
+    #[default]
+    Stable,
+    /// Pre-releases as well as stable releases
+    Beta,
+}
+
+impl ReleaseChannel {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "stable" => Some(ReleaseChannel::Stable),
+            "beta" => Some(ReleaseChannel::Beta),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ReleaseChannel::Stable => "stable",
+            ReleaseChannel::Beta => "beta",
+        }
+    }
+}
+
+/// Self-update configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UpdateConfig {
+    pub channel: ReleaseChannel,
+    pub feed_url: String,
+    /// Hex ed25519 public keys trusted to sign the release feed; none are
+    /// trusted by default, add one with `self-update --trust-key`
+    pub trusted_keys: Vec<String>,
+}
+
+impl Default for UpdateConfig {
+    fn default() -> Self {
+        Self {
+            channel: ReleaseChannel::Stable,
+            feed_url: DEFAULT_RELEASE_FEED_URL.to_string(),
+            trusted_keys: Vec::new(),
+        }
+    }
+}
+
+/// Whether a key is a hex ed25519 public key (32 bytes, optional `0x`)
+fn is_ed25519_key(key: &str) -> bool {
+    let hex = key.strip_prefix("0x").unwrap_or(key);
+    hex.len() == 64 && hex.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+/// Marketplace API used when none is configured
+pub const DEFAULT_MARKETPLACE_URL: &str = "https://marketplace.canvascontracts.dev/api/v1";
+
+/// A marketplace registry besides the official one
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RegistryConfig {
+    pub name: String,
+    /// `http(s)://` API URL, or a local folder
+    pub location: String,
+    /// Registries are consulted in ascending priority; the official registry
+    /// (`api_url`) has priority 100
+    #[serde(default)]
+    pub priority: i32,
+    /// Hex ed25519 keys the registry signs its items with; items of a
+    /// registry with keys are only accepted with a valid signature
+    #[serde(default)]
+    pub trusted_keys: Vec<String>,
+    /// Prefix of the item IDs this registry owns (`acme` owns `acme/...`);
+    /// namespaced IDs are never resolved from any other registry
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub namespace: Option<String>,
+}
+
+/// Priority of the official registry
+pub const OFFICIAL_REGISTRY_PRIORITY: i32 = 100;
+
+/// Marketplace configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MarketplaceConfig {
+    pub api_url: String,
+    /// Browse the cached index and queue publishes and reviews instead of
+    /// contacting the marketplace
+    pub offline: bool,
+    /// Additional registries, e.g. a corporate mirror or a local folder
+    #[serde(default)]
+    pub registries: Vec<RegistryConfig>,
+}
+
+impl MarketplaceConfig {
+    /// Every registry, official one included, in the order they are consulted
+    pub fn all_registries(&self) -> Vec<RegistryConfig> {
+        let mut registries = self.registries.clone();
+        if !registries.iter().any(|r| r.location == self.api_url) {
+            registries.push(RegistryConfig {
+                name: "official".to_string(),
+                location: self.api_url.clone(),
+                priority: OFFICIAL_REGISTRY_PRIORITY,
+                trusted_keys: Vec::new(),
+                namespace: None,
+            });
+        }
+        registries.sort_by_key(|r| r.priority);
+        registries
+    }
+}
+
+impl Default for MarketplaceConfig {
+    fn default() -> Self {
+        Self {
+            api_url: DEFAULT_MARKETPLACE_URL.to_string(),
+            offline: false,
+            registries: Vec::new(),
+        }
+    }
+}
+
+/// Group of commands the desktop UI can invoke
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PermissionScope {
+    /// Compile, validate and analyze graphs
+    Build,
+    /// Open, edit and save graph documents
+    Documents,
+    /// Run contracts in the local runtime
+    Simulate,
+    /// Record console calls into test scenarios
+    Record,
+    /// Create contracts with the wizards
+    Wizards,
+    /// Import node icons
+    Assets,
+    /// Act as an account without its key
+    Impersonate,
+    /// Use a signing key, e.g. to send a transaction
+    Sign,
+    /// Read the audit trail of invoked commands
+    Audit,
+}
+
+impl PermissionScope {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PermissionScope::Build => "build",
+            PermissionScope::Documents => "documents",
+            PermissionScope::Simulate => "simulate",
+            PermissionScope::Record => "record",
+            PermissionScope::Wizards => "wizards",
+            PermissionScope::Assets => "assets",
+            PermissionScope::Impersonate => "impersonate",
+            PermissionScope::Sign => "sign",
+            PermissionScope::Audit => "audit",
+        }
+    }
+}
+
+/// What the permission policy does with a command, from least to most strict
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PermissionDecision {
+    Allow,
+    /// Run the command once the user confirms it
+    Confirm,
+    Deny,
+}
+
+/// Permission rule for the commands of a scope
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PermissionRule {
+    /// Scope the rule covers; unset covers every scope
+    #[serde(default)]
+    pub scope: Option<PermissionScope>,
+    /// Network the rule is limited to; unset covers every network
+    #[serde(default)]
+    pub network: Option<String>,
+    pub decision: PermissionDecision,
+}
+
+/// Permission policy for commands invoked from the desktop UI.
+///
+/// The most specific matching rule decides (a scope counts more than a
+/// network); among equally specific rules the strictest wins.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PermissionsConfig {
+    /// Decision for commands no rule matches
+    pub default: PermissionDecision,
+    #[serde(default)]
+    pub rules: Vec<PermissionRule>,
+    /// Record every invoked command in the audit trail
+    pub audit: bool,
+}
+
+impl Default for PermissionsConfig {
+    fn default() -> Self {
+        Self {
+            default: PermissionDecision::Allow,
+            rules: vec![PermissionRule {
+                scope: Some(PermissionScope::Sign),
+                network: None,
+                decision: PermissionDecision::Confirm,
+            }],
+            audit: true,
+        }
+    }
 }
 
 /// Development configuration
@@ -110,6 +615,12 @@ impl Default for Config {
             runtime: RuntimeConfig::default(),
             baals: BaalsConfig::default(),
             development: DevelopmentConfig::default(),
+            ai: AiConfig::default(),
+            privacy: PrivacyConfig::default(),
+            telemetry: TelemetryConfig::default(),
+            update: UpdateConfig::default(),
+            marketplace: MarketplaceConfig::default(),
+            permissions: PermissionsConfig::default(),
         }
     }
 }
@@ -137,6 +648,11 @@ impl Default for CompilerConfig {
             max_gas_limit: 10_000_000,
             wasm_target: "wasm32-unknown-unknown".to_string(),
             flags: Vec::new(),
+            overflow_mode: crate::types::OverflowMode::Checked,
+            pausable: false,
+            instrument: false,
+            component: false,
+            gas_model: GasModelConfig::default(),
         }
     }
 }
@@ -150,6 +666,23 @@ impl Default for RuntimeConfig {
             gas_metering: true,
             sandbox_mode: true,
             timeout: 30,
+            custom_node_fuel: default_custom_node_fuel(),
+            custom_node_memory_pages: default_custom_node_memory_pages(),
+        }
+    }
+}
+
+impl Default for AiConfig {
+    fn default() -> Self {
+        Self {
+            llm_backend: None,
+            llm_url: "http://localhost:8080".to_string(),
+            llm_model: "gpt-4o-mini".to_string(),
+            llm_api_key_env: "CANVAS_LLM_API_KEY".to_string(),
+            llm_timeout_ms: 5000,
+            share_graph_data: false,
+            rule_pack_url: None,
+            rule_pack_keys: Vec::new(),
         }
     }
 }
@@ -163,10 +696,20 @@ impl Default for BaalsConfig {
             enable_local_node: true,
             local_node_port: 8080,
             auth_token: None,
+            network: NetworkProfile::default(),
+            networks: Vec::new(),
+            relayers: Vec::new(),
         }
     }
 }
 
+impl BaalsConfig {
+    /// The target network followed by the ones to compare against
+    pub fn profiles(&self) -> impl Iterator<Item = &NetworkProfile> {
+        std::iter::once(&self.network).chain(&self.networks)
+    }
+}
+
 impl Default for DevelopmentConfig {
     fn default() -> Self {
         Self {
@@ -255,6 +798,10 @@ impl Config {
                 "debug_info" => Some(serde_json::Value::Bool(self.compiler.debug_info)),
                 "gas_estimation" => Some(serde_json::Value::Bool(self.compiler.gas_estimation)),
                 "max_gas_limit" => Some(serde_json::Value::Number(self.compiler.max_gas_limit.into())),
+                "overflow_mode" => Some(serde_json::Value::String(self.compiler.overflow_mode.as_str().to_string())),
+                "pausable" => Some(serde_json::Value::Bool(self.compiler.pausable)),
+                "instrument" => Some(serde_json::Value::Bool(self.compiler.instrument)),
+                "component" => Some(serde_json::Value::Bool(self.compiler.component)),
                 _ => None,
             },
             ["runtime", key] => match *key {
@@ -262,6 +809,40 @@ impl Config {
                 "memory_limit" => Some(serde_json::Value::Number(self.runtime.memory_limit.into())),
                 "gas_metering" => Some(serde_json::Value::Bool(self.runtime.gas_metering)),
                 "sandbox_mode" => Some(serde_json::Value::Bool(self.runtime.sandbox_mode)),
+                "custom_node_fuel" => Some(serde_json::Value::Number(self.runtime.custom_node_fuel.into())),
+                "custom_node_memory_pages" => Some(serde_json::Value::Number(self.runtime.custom_node_memory_pages.into())),
+                _ => None,
+            },
+            ["ai", key] => match *key {
+                "llm_backend" => Some(self.ai.llm_backend.clone().map_or(serde_json::Value::Null, serde_json::Value::String)),
+                "llm_url" => Some(serde_json::Value::String(self.ai.llm_url.clone())),
+                "llm_model" => Some(serde_json::Value::String(self.ai.llm_model.clone())),
+                "llm_timeout_ms" => Some(serde_json::Value::Number(self.ai.llm_timeout_ms.into())),
+                "share_graph_data" => Some(serde_json::Value::Bool(self.ai.share_graph_data)),
+                _ => None,
+            },
+            ["privacy", key] => match *key {
+                "deleted_content" => Some(serde_json::Value::String(self.privacy.deleted_content.as_str().to_string())),
+                "anonymized_retention_days" => {
+                    Some(serde_json::Value::Number(self.privacy.anonymized_retention_days.into()))
+                }
+                _ => None,
+            },
+            ["telemetry", key] => match *key {
+                "enabled" => Some(serde_json::Value::Bool(self.telemetry.enabled)),
+                "endpoint" => Some(serde_json::Value::String(self.telemetry.endpoint.clone())),
+                "max_buffered_events" => Some(serde_json::Value::Number(self.telemetry.max_buffered_events.into())),
+                _ => None,
+            },
+            ["update", key] => match *key {
+                "channel" => Some(serde_json::Value::String(self.update.channel.as_str().to_string())),
+                "feed_url" => Some(serde_json::Value::String(self.update.feed_url.clone())),
+                "trusted_keys" => serde_json::to_value(&self.update.trusted_keys).ok(),
+                _ => None,
+            },
+            ["marketplace", key] => match *key {
+                "api_url" => Some(serde_json::Value::String(self.marketplace.api_url.clone())),
+                "offline" => Some(serde_json::Value::Bool(self.marketplace.offline)),
                 _ => None,
             },
             ["baals", key] => match *key {
@@ -270,6 +851,20 @@ impl Config {
                 "enable_local_node" => Some(serde_json::Value::Bool(self.baals.enable_local_node)),
                 _ => None,
             },
+            ["baals", "network", key] => match *key {
+                "name" => Some(serde_json::Value::String(self.baals.network.name.clone())),
+                "rent_per_byte_epoch" => Some(serde_json::Value::Number(self.baals.network.rent_per_byte_epoch.into())),
+                "epoch_seconds" => Some(serde_json::Value::Number(self.baals.network.epoch_seconds.into())),
+                "storage_deposit_per_byte" => {
+                    Some(serde_json::Value::Number(self.baals.network.storage_deposit_per_byte.into()))
+                }
+                "max_nesting_depth" => Some(serde_json::Value::Number(self.baals.network.max_nesting_depth.into())),
+                "max_call_depth" => Some(serde_json::Value::Number(self.baals.network.max_call_depth.into())),
+                "derivation_path" => Some(serde_json::Value::String(self.baals.network.derivation_path.clone())),
+                "chain_id" => self.baals.network.chain_id.map(|id| serde_json::Value::Number(id.into())),
+                "fee_model" => serde_json::to_value(&self.baals.network.fee_model).ok(),
+                _ => None,
+            },
             _ => None,
         }
     }
@@ -308,8 +903,184 @@ impl Config {
                         self.compiler.max_gas_limit = limit;
                     }
                 }
+                "overflow_mode" => {
+                    let mode = value
+                        .as_str()
+                        .and_then(crate::types::OverflowMode::from_name)
+                        .ok_or_else(|| CanvasError::Config(format!("Invalid overflow mode: {}", value)))?;
+                    self.compiler.overflow_mode = mode;
+                }
+                "pausable" => {
+                    if let Some(pausable) = value.as_bool() {
+                        self.compiler.pausable = pausable;
+                    }
+                }
+                "instrument" => {
+                    if let Some(instrument) = value.as_bool() {
+                        self.compiler.instrument = instrument;
+                    }
+                }
+                "component" => {
+                    if let Some(component) = value.as_bool() {
+                        self.compiler.component = component;
+                    }
+                }
                 _ => return Err(CanvasError::Config(format!("Unknown compiler config key: {}", key))),
             },
+            ["runtime", key] => match *key {
+                "custom_node_fuel" => {
+                    if let Some(fuel) = value.as_u64() {
+                        self.runtime.custom_node_fuel = fuel;
+                    }
+                }
+                "custom_node_memory_pages" => {
+                    if let Some(pages) = value.as_u64() {
+                        self.runtime.custom_node_memory_pages = pages as u32;
+                    }
+                }
+                _ => return Err(CanvasError::Config(format!("Unknown runtime config key: {}", key))),
+            },
+            ["ai", key] => match *key {
+                "llm_backend" => {
+                    self.ai.llm_backend = value.as_str().map(str::to_string);
+                }
+                "llm_url" => {
+                    if let Some(url) = value.as_str() {
+                        self.ai.llm_url = url.to_string();
+                    }
+                }
+                "llm_model" => {
+                    if let Some(model) = value.as_str() {
+                        self.ai.llm_model = model.to_string();
+                    }
+                }
+                "llm_timeout_ms" => {
+                    if let Some(timeout) = value.as_u64() {
+                        self.ai.llm_timeout_ms = timeout;
+                    }
+                }
+                "share_graph_data" => {
+                    if let Some(share) = value.as_bool() {
+                        self.ai.share_graph_data = share;
+                    }
+                }
+                _ => return Err(CanvasError::Config(format!("Unknown ai config key: {}", key))),
+            },
+            ["privacy", key] => match *key {
+                "deleted_content" => {
+                    let retention = value
+                        .as_str()
+                        .and_then(ContentRetention::from_name)
+                        .ok_or_else(|| CanvasError::Config(format!("Invalid content retention: {}", value)))?;
+                    self.privacy.deleted_content = retention;
+                }
+                "anonymized_retention_days" => {
+                    if let Some(days) = value.as_u64() {
+                        self.privacy.anonymized_retention_days = days as u32;
+                    }
+                }
+                _ => return Err(CanvasError::Config(format!("Unknown privacy config key: {}", key))),
+            },
+            ["telemetry", key] => match *key {
+                "enabled" => {
+                    if let Some(enabled) = value.as_bool() {
+                        self.telemetry.enabled = enabled;
+                    }
+                }
+                "endpoint" => {
+                    if let Some(endpoint) = value.as_str() {
+                        self.telemetry.endpoint = endpoint.to_string();
+                    }
+                }
+                "max_buffered_events" => {
+                    if let Some(max) = value.as_u64() {
+                        self.telemetry.max_buffered_events = max as usize;
+                    }
+                }
+                _ => return Err(CanvasError::Config(format!("Unknown telemetry config key: {}", key))),
+            },
+            ["update", key] => match *key {
+                "channel" => {
+                    let channel = value
+                        .as_str()
+                        .and_then(ReleaseChannel::from_name)
+                        .ok_or_else(|| CanvasError::Config(format!("Invalid release channel: {}", value)))?;
+                    self.update.channel = channel;
+                }
+                "feed_url" => {
+                    if let Some(url) = value.as_str() {
+                        self.update.feed_url = url.to_string();
+                    }
+                }
+                "trusted_keys" => {
+                    let keys: Vec<String> = serde_json::from_value(value.clone())
+                        .map_err(|_| CanvasError::Config(format!("Trusted release keys must be a list: {}", value)))?;
+                    if let Some(key) = keys.iter().find(|key| !is_ed25519_key(key)) {
+                        return Err(CanvasError::Config(format!("Invalid release key: {}", key)));
+                    }
+                    self.update.trusted_keys = keys;
+                }
+                _ => return Err(CanvasError::Config(format!("Unknown update config key: {}", key))),
+            },
+            ["marketplace", key] => match *key {
+                "api_url" => {
+                    if let Some(url) = value.as_str() {
+                        self.marketplace.api_url = url.to_string();
+                    }
+                }
+                "offline" => {
+                    if let Some(offline) = value.as_bool() {
+                        self.marketplace.offline = offline;
+                    }
+                }
+                _ => return Err(CanvasError::Config(format!("Unknown marketplace config key: {}", key))),
+            },
+            ["baals", "network", key] => match *key {
+                "name" => {
+                    if let Some(name) = value.as_str() {
+                        self.baals.network.name = name.to_string();
+                    }
+                }
+                "rent_per_byte_epoch" => {
+                    if let Some(rent) = value.as_u64() {
+                        self.baals.network.rent_per_byte_epoch = rent;
+                    }
+                }
+                "epoch_seconds" => {
+                    if let Some(seconds) = value.as_u64() {
+                        self.baals.network.epoch_seconds = seconds;
+                    }
+                }
+                "storage_deposit_per_byte" => {
+                    if let Some(deposit) = value.as_u64() {
+                        self.baals.network.storage_deposit_per_byte = deposit;
+                    }
+                }
+                "max_nesting_depth" => {
+                    if let Some(depth) = value.as_u64() {
+                        self.baals.network.max_nesting_depth = depth as u32;
+                    }
+                }
+                "max_call_depth" => {
+                    if let Some(depth) = value.as_u64() {
+                        self.baals.network.max_call_depth = depth as u32;
+                    }
+                }
+                "derivation_path" => {
+                    if let Some(path) = value.as_str() {
+                        crate::baals::signer::DerivationPath::parse(path)?;
+                        self.baals.network.derivation_path = path.to_string();
+                    }
+                }
+                "chain_id" => {
+                    self.baals.network.chain_id = value.as_u64();
+                }
+                "fee_model" => {
+                    self.baals.network.fee_model = serde_json::from_value(value.clone())
+                        .map_err(|e| CanvasError::Config(format!("Invalid fee model: {}", e)))?;
+                }
+                _ => return Err(CanvasError::Config(format!("Unknown network config key: {}", key))),
+            },
             _ => return Err(CanvasError::Config(format!("Unknown config key path: {}", key_path))),
         }
         
@@ -335,6 +1106,10 @@ impl Config {
         if self.compiler.max_gas_limit == 0 {
             return Err(CanvasError::Config("Max gas limit must be greater than 0".to_string()));
         }
+
+        if !(0.0..=1.0).contains(&self.compiler.gas_model.average_loop_fraction) {
+            return Err(CanvasError::Config("Average loop fraction must be between 0 and 1".to_string()));
+        }
         
         // Validate runtime config
         if self.runtime.memory_limit == 0 {
@@ -353,6 +1128,15 @@ impl Config {
         if self.baals.retry_attempts == 0 {
             return Err(CanvasError::Config("Retry attempts must be greater than 0".to_string()));
         }
+
+        for network in self.baals.profiles() {
+            if network.epoch_seconds == 0 {
+                return Err(CanvasError::Config(format!(
+                    "Epoch length of network '{}' must be greater than 0",
+                    network.name
+                )));
+            }
+        }
         
         Ok(())
     }
@@ -463,4 +1247,36 @@ mod tests {
             Some(serde_json::Value::Number(2.into()))
         );
     }
-} 
\ No newline at end of file
+
+    #[test]
+    fn test_overflow_mode_setting() {
+        let mut config = Config::default();
+        assert_eq!(
+            config.get_value("compiler.overflow_mode"),
+            Some(serde_json::Value::String("checked".to_string()))
+        );
+
+        assert!(config.set_value("compiler.overflow_mode", serde_json::json!("saturating")).is_ok());
+        assert_eq!(config.compiler.overflow_mode, crate::types::OverflowMode::Saturating);
+        assert!(config.set_value("compiler.overflow_mode", serde_json::json!("unchecked")).is_err());
+    }
+
+    #[test]
+    fn test_pausable_setting() {
+        let mut config = Config::default();
+        assert_eq!(config.get_value("compiler.pausable"), Some(serde_json::Value::Bool(false)));
+        assert!(config.set_value("compiler.pausable", serde_json::json!(true)).is_ok());
+        assert!(config.compiler.pausable);
+    }
+
+    #[test]
+    fn test_trusted_release_keys_setting() {
+        let mut config = Config::default();
+        let key = format!("0x{}", "ab".repeat(32));
+        assert!(config.set_value("update.trusted_keys", serde_json::json!([key])).is_ok());
+        assert_eq!(config.get_value("update.trusted_keys"), Some(serde_json::json!([key])));
+        assert!(config.set_value("update.trusted_keys", serde_json::json!(["0x1234"])).is_err());
+        assert!(config.set_value("update.trusted_keys", serde_json::json!(key)).is_err());
+        assert_eq!(config.update.trusted_keys, vec![key]);
+    }
+}
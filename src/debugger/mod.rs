@@ -2,7 +2,9 @@
 
 use crate::{
     error::CanvasResult,
-    types::{Graph, Node, NodeId, NodeType},
+    compiler::{TraceCoverage, TraceMap},
+    nodes::custom::{CustomNodeRegistry, NodeExecutionStats},
+    types::{ExecutionContext, Graph, Node, NodeId, NodeType, Savepoint, TraceEvent},
     wasm::WasmRuntime,
 };
 
@@ -19,6 +21,10 @@ pub struct DebugSession {
     is_paused: bool,
     variables: HashMap<String, serde_json::Value>,
     call_stack: Vec<CallStackFrame>,
+    custom_node_stats: Vec<NodeExecutionStats>,
+    trace_map: Option<TraceMap>,
+    trace_events: Vec<TraceEvent>,
+    savepoints: Vec<Savepoint>,
 }
 
 /// Breakpoint definition
@@ -86,6 +92,10 @@ impl DebugSession {
             is_paused: false,
             variables: HashMap::new(),
             call_stack: Vec::new(),
+            custom_node_stats: Vec::new(),
+            trace_map: None,
+            trace_events: Vec::new(),
+            savepoints: Vec::new(),
         }
     }
 
@@ -243,6 +253,60 @@ impl DebugSession {
         &self.breakpoints
     }
 
+    /// Take a snapshot of the custom node statistics gathered by `registry`
+    pub fn record_custom_node_stats(&mut self, registry: &CustomNodeRegistry) {
+        self.custom_node_stats = registry.execution_stats();
+        if let Some(worst) = self.custom_node_stats.iter().max_by_key(|s| s.fuel_consumed) {
+            log::debug!("Custom node {} used the most fuel: {}", worst.node_id, worst.fuel_consumed);
+        }
+    }
+
+    /// Per-node fuel, memory and timing of the custom nodes that ran
+    pub fn get_custom_node_stats(&self) -> &[NodeExecutionStats] {
+        &self.custom_node_stats
+    }
+
+    /// Load the tracepoints an instrumented build reported
+    pub fn record_trace(&mut self, map: TraceMap, events: Vec<TraceEvent>) {
+        self.trace_map = Some(map);
+        self.trace_events = events;
+    }
+
+    /// Capture the open savepoints of a paused execution
+    pub fn record_savepoints(&mut self, context: &ExecutionContext) {
+        self.savepoints = context.savepoints().to_vec();
+    }
+
+    /// Savepoints open when execution paused, outermost first
+    pub fn get_savepoints(&self) -> &[Savepoint] {
+        &self.savepoints
+    }
+
+    /// Storage as of the innermost savepoint, i.e. without the writes of the
+    /// call still in progress, which may yet be rolled back
+    pub fn committed_storage(&self) -> Option<&HashMap<String, serde_json::Value>> {
+        self.savepoints.last().map(|s| &s.storage)
+    }
+
+    /// Nodes entered by the instrumented run, in execution order
+    pub fn traced_nodes(&self) -> Vec<NodeId> {
+        let Some(map) = &self.trace_map else {
+            return Vec::new();
+        };
+        self.trace_events
+            .iter()
+            .filter(|e| matches!(e, TraceEvent::Enter { .. }))
+            .filter_map(|e| map.node(e.tracepoint()))
+            .collect()
+    }
+
+    /// Node coverage of the instrumented run
+    pub fn trace_coverage(&self) -> Option<TraceCoverage> {
+        self.trace_map
+            .as_ref()
+            .map(|map| TraceCoverage::from_trace(map, &self.trace_events))
+    }
+
     /// Execute a single node
     fn execute_node(&mut self, node: &Node, config: &DebugConfig) -> CanvasResult<()> {
         let start_time = std::time::Instant::now();
@@ -0,0 +1,306 @@
+//! Freeze attestations
+//!
+//! A deployed contract can be declared final. Freezing checks that the
+//! release's ABI exposes no upgrade entry point, then records an attestation
+//! signed with the environment's deploy key in `.canvas/releases/frozen.json`.
+//! Promotion refuses to replace a frozen release, and marketplace listings
+//! carry the attestation so the immutability status can be shown and checked.
+//!
+//! A signature alone proves nothing, since anyone can sign with a fresh key:
+//! the store only accepts attestations signed by the key its release was
+//! deployed with, and listings only those signed by a trusted key of their
+//! author.
+
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use super::environments::{ReleaseRecord, ReleaseStore};
+use crate::{
+    baals::signer::{sign_with_prompt, Signer},
+    error::{CanvasError, CanvasResult},
+    nodes::{decode_hex, encode_hex},
+    types::{ContractABI, ContractAddress},
+    wasm::host::{verify_signature, SignatureScheme},
+};
+
+/// Attestations file, inside the releases directory
+pub const FROZEN_FILE: &str = "frozen.json";
+
+/// Entry points that would let a contract's code be replaced
+pub const UPGRADE_FUNCTIONS: &[&str] = &["upgrade", "upgradeTo", "upgradeToAndCall", "setCode", "set_code", "migrate"];
+
+/// Functions of an ABI that provide an upgrade path
+pub fn upgrade_paths(abi: &ContractABI) -> Vec<String> {
+    abi.functions
+        .iter()
+        .filter(|f| UPGRADE_FUNCTIONS.iter().any(|name| f.name.eq_ignore_ascii_case(name)))
+        .map(|f| f.name.clone())
+        .collect()
+}
+
+/// Signed statement that a deployed contract is final
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FreezeAttestation {
+    pub contract_address: ContractAddress,
+    pub environment: String,
+    /// SHA-256 of the frozen WASM, 0x-hex
+    pub artifact_hash: String,
+    pub frozen_at: u64,
+    #[serde(default)]
+    pub reason: Option<String>,
+    /// 0x-hex ed25519 public key of the signer
+    pub signer: String,
+    /// 0x-hex ed25519 signature of [`FreezeAttestation::signing_message`]
+    pub signature: String,
+}
+
+impl FreezeAttestation {
+    /// Bytes the signer signs: every other field, as a JSON array so that no
+    /// field can bleed into its neighbour
+    pub fn signing_message(&self) -> Vec<u8> {
+        serde_json::json!([
+            "canvas-freeze",
+            self.contract_address,
+            self.environment,
+            self.artifact_hash,
+            self.frozen_at,
+            self.reason,
+        ])
+        .to_string()
+        .into_bytes()
+    }
+
+    /// Check that the attestation is signed by one of `trusted_keys`
+    pub fn verify(&self, trusted_keys: &[String]) -> CanvasResult<()> {
+        if !trusted_keys.iter().any(|key| key.eq_ignore_ascii_case(&self.signer)) {
+            return Err(CanvasError::PermissionDenied(format!(
+                "Freeze attestation of {} is signed by {}, which is not trusted",
+                self.contract_address, self.signer
+            )));
+        }
+        let invalid =
+            || CanvasError::Validation(format!("Freeze attestation of {} is not valid hex", self.contract_address));
+        let signer = decode_hex(&self.signer).ok_or_else(invalid)?;
+        let signature = decode_hex(&self.signature).ok_or_else(invalid)?;
+        if verify_signature(SignatureScheme::Ed25519, &signer, &self.signing_message(), &signature) {
+            Ok(())
+        } else {
+            Err(CanvasError::Validation(format!(
+                "Freeze attestation of {} has a bad signature",
+                self.contract_address
+            )))
+        }
+    }
+}
+
+/// Attest that a release is final. Fails if its ABI is unknown or still
+/// exposes an upgrade entry point.
+pub fn attest_freeze(
+    release: &ReleaseRecord,
+    reason: Option<String>,
+    signer: &dyn Signer,
+) -> CanvasResult<FreezeAttestation> {
+    let abi = release.abi.as_ref().ok_or_else(|| {
+        CanvasError::Validation(format!(
+            "Release {} has no recorded ABI; cannot show it has no upgrade path",
+            release.artifact_hash
+        ))
+    })?;
+    let paths = upgrade_paths(abi);
+    if !paths.is_empty() {
+        return Err(CanvasError::Validation(format!(
+            "Contract {} can still be upgraded through: {}",
+            release.contract_address,
+            paths.join(", ")
+        )));
+    }
+
+    let mut attestation = FreezeAttestation {
+        contract_address: release.contract_address.clone(),
+        environment: release.environment.clone(),
+        artifact_hash: release.artifact_hash.clone(),
+        frozen_at: std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs(),
+        reason,
+        signer: signer.address()?,
+        signature: String::new(),
+    };
+    let message = attestation.signing_message();
+    attestation.signature = encode_hex(&sign_with_prompt(signer, "freeze attestation", &message)?);
+    Ok(attestation)
+}
+
+/// Read an ed25519 signing key from a 0x-hex seed, as stored in key files
+pub fn signing_key_from_hex(hex: &str) -> CanvasResult<ed25519_dalek::SigningKey> {
+    let seed: [u8; 32] = decode_hex(hex.trim())
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| CanvasError::Validation("Signing key must be a 32-byte hex seed".to_string()))?;
+    Ok(ed25519_dalek::SigningKey::from_bytes(&seed))
+}
+
+/// Freeze attestations of a workspace, keyed by contract address
+pub struct AttestationStore {
+    path: PathBuf,
+    releases: ReleaseStore,
+}
+
+impl AttestationStore {
+    /// Store beside a release history
+    pub fn new(releases: &ReleaseStore) -> Self {
+        Self {
+            path: releases.dir().join(FROZEN_FILE),
+            releases: releases.clone(),
+        }
+    }
+
+    /// Check an attestation against the deploy key of the release it freezes
+    fn verify(&self, attestation: &FreezeAttestation) -> CanvasResult<()> {
+        let release = self
+            .releases
+            .history(&attestation.environment)?
+            .into_iter()
+            .rev()
+            .find(|r| {
+                r.contract_address.eq_ignore_ascii_case(&attestation.contract_address)
+                    && r.artifact_hash.eq_ignore_ascii_case(&attestation.artifact_hash)
+            })
+            .ok_or_else(|| {
+                CanvasError::NotFound(format!(
+                    "No release of {} with artifact {} in '{}'",
+                    attestation.contract_address, attestation.artifact_hash, attestation.environment
+                ))
+            })?;
+        let deployer = release.deployer.ok_or_else(|| {
+            CanvasError::Validation(format!(
+                "Release {} in '{}' has no recorded deploy key to check its freeze attestation against",
+                release.artifact_hash, release.environment
+            ))
+        })?;
+        attestation.verify(&[deployer])
+    }
+
+    pub fn list(&self) -> CanvasResult<Vec<FreezeAttestation>> {
+        if !self.path.exists() {
+            return Ok(Vec::new());
+        }
+        Ok(serde_json::from_str(&std::fs::read_to_string(&self.path)?)?)
+    }
+
+    /// Verified attestation of a contract, if it is frozen
+    pub fn get(&self, contract_address: &str) -> CanvasResult<Option<FreezeAttestation>> {
+        let Some(attestation) = self
+            .list()?
+            .into_iter()
+            .find(|a| a.contract_address.eq_ignore_ascii_case(contract_address))
+        else {
+            return Ok(None);
+        };
+        self.verify(&attestation)?;
+        Ok(Some(attestation))
+    }
+
+    /// Record a verified attestation; a contract can only be frozen once
+    pub fn record(&self, attestation: FreezeAttestation) -> CanvasResult<()> {
+        self.verify(&attestation)?;
+        let mut attestations = self.list()?;
+        if attestations
+            .iter()
+            .any(|a| a.contract_address.eq_ignore_ascii_case(&attestation.contract_address))
+        {
+            return Err(CanvasError::Validation(format!(
+                "Contract {} is already frozen",
+                attestation.contract_address
+            )));
+        }
+        attestations.push(attestation);
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&self.path, serde_json::to_string_pretty(&attestations)?)?;
+        Ok(())
+    }
+
+    /// Refuse to operate on a frozen contract
+    pub fn ensure_not_frozen(&self, contract_address: &str) -> CanvasResult<()> {
+        match self.get(contract_address)? {
+            Some(attestation) => Err(CanvasError::PermissionDenied(format!(
+                "Contract {} in '{}' was frozen at {}; it cannot be upgraded",
+                attestation.contract_address, attestation.environment, attestation.frozen_at
+            ))),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::baals::signer::LocalSigner;
+    use crate::deployment::environments::{new_release, ReleaseRecord};
+    use crate::types::{FunctionABI, StateMutability};
+
+    fn abi(functions: &[&str]) -> ContractABI {
+        ContractABI {
+            functions: functions
+                .iter()
+                .map(|name| FunctionABI {
+                    name: name.to_string(),
+                    inputs: Vec::new(),
+                    outputs: Vec::new(),
+                    state_mutability: StateMutability::NonPayable,
+                    gas_estimate: None,
+                })
+                .collect(),
+            events: Vec::new(),
+            errors: Vec::new(),
+            storage: Vec::new(),
+            metadata: Default::default(),
+        }
+    }
+
+    #[test]
+    fn test_freeze_is_signed_and_recorded_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let releases = ReleaseStore::new(dir.path());
+        let store = releases.attestations();
+        let key = LocalSigner::new(signing_key_from_hex(&encode_hex(&[5u8; 32])).unwrap());
+        let stranger = LocalSigner::new(signing_key_from_hex(&encode_hex(&[6u8; 32])).unwrap());
+
+        let release = |functions: &[&str]| ReleaseRecord {
+            deployer: Some(key.address().unwrap()),
+            ..new_release("prod", "0xA1".to_string(), b"wasm", Some(abi(functions)), serde_json::Value::Null, 3)
+        };
+        let upgradeable = release(&["transfer", "upgradeTo"]);
+        assert!(attest_freeze(&upgradeable, None, &key).unwrap_err().to_string().contains("upgradeTo"));
+
+        let release = release(&["transfer"]);
+        let attestation = attest_freeze(&release, Some("audited".to_string()), &key).unwrap();
+        // Only releases on record can be frozen
+        assert!(matches!(store.record(attestation.clone()), Err(CanvasError::NotFound(_))));
+        releases.record(release.clone(), b"wasm").unwrap();
+
+        // A valid signature by any other key is not enough
+        let impostor = attest_freeze(&release, Some("audited".to_string()), &stranger).unwrap();
+        assert!(matches!(store.record(impostor), Err(CanvasError::PermissionDenied(_))));
+
+        store.ensure_not_frozen("0xa1").unwrap();
+        store.record(attestation.clone()).unwrap();
+        assert!(matches!(store.ensure_not_frozen("0xa1"), Err(CanvasError::PermissionDenied(_))));
+        assert!(store.record(attestation.clone()).is_err());
+
+        let trusted = [key.address().unwrap()];
+        attestation.verify(&trusted).unwrap();
+        let mut forged = attestation.clone();
+        forged.artifact_hash = "0xbeef".to_string();
+        assert!(forged.verify(&trusted).is_err());
+        let mut moved = attestation.clone();
+        moved.environment = "staging".to_string();
+        assert!(moved.verify(&trusted).is_err());
+        let mut reworded = attestation;
+        reworded.reason = None;
+        assert!(reworded.verify(&trusted).is_err());
+    }
+}
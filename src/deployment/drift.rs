@@ -0,0 +1,308 @@
+//! Drift detection
+//!
+//! The deployment manager's records say what should be running; providers
+//! report what actually is (replica counts and alert rules from the hosting
+//! platform, contract code from BaaLS). The detector compares the two per
+//! deployment and reports every difference with the action that would bring
+//! the live state back in line.
+
+use std::{sync::Arc, time::Duration};
+
+use serde::{Deserialize, Serialize};
+
+use super::{DeploymentInfo, DeploymentManager, DeploymentStatus};
+use crate::{
+    baals::BaalsClient,
+    error::{CanvasError, CanvasResult},
+};
+
+/// What a deployment record declares
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DeclaredState {
+    pub replicas: u32,
+    pub alert_rules: Vec<String>,
+    /// SHA-256 of the deployed WASM, 0x-hex
+    pub code_hash: String,
+}
+
+impl DeclaredState {
+    pub fn of(deployment: &DeploymentInfo) -> Self {
+        let mut alert_rules: Vec<String> =
+            deployment.config.monitoring.alert_rules.iter().map(|r| r.name.clone()).collect();
+        alert_rules.sort();
+        Self {
+            replicas: deployment.config.replicas,
+            alert_rules,
+            code_hash: super::environments::artifact_hash(&deployment.wasm_bytes),
+        }
+    }
+}
+
+/// What a provider observed; fields it cannot see are `None`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ObservedState {
+    pub replicas: Option<u32>,
+    pub alert_rules: Option<Vec<String>>,
+    pub code_hash: Option<String>,
+}
+
+/// Source of live deployment state
+pub trait LiveStateProvider: Send + Sync {
+    fn name(&self) -> &str;
+
+    /// Observe a deployment; `Ok(None)` if it is not running at all
+    fn observe(&self, deployment: &DeploymentInfo) -> CanvasResult<Option<ObservedState>>;
+}
+
+/// Reads deployed contract code from BaaLS
+pub struct BaalsCodeProvider {
+    client: BaalsClient,
+}
+
+impl BaalsCodeProvider {
+    pub fn new(client: BaalsClient) -> Self {
+        Self { client }
+    }
+}
+
+impl LiveStateProvider for BaalsCodeProvider {
+    fn name(&self) -> &str {
+        "baals"
+    }
+
+    fn observe(&self, deployment: &DeploymentInfo) -> CanvasResult<Option<ObservedState>> {
+        let Some(address) = &deployment.contract_address else {
+            return Ok(Some(ObservedState::default()));
+        };
+        let state = self.client.get_contract_state(address)?;
+        Ok(Some(ObservedState {
+            code_hash: Some(state.code_hash),
+            ..ObservedState::default()
+        }))
+    }
+}
+
+/// How live state differs from the record
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum DriftKind {
+    NotRunning,
+    Replicas { declared: u32, live: u32 },
+    AlertRules { missing: Vec<String>, unexpected: Vec<String> },
+    CodeHash { declared: String, live: String },
+}
+
+/// Action that resolves a drift
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Reconciliation {
+    Scale(u32),
+    SyncAlertRules,
+    Redeploy,
+}
+
+/// One difference found for a deployment
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Drift {
+    pub deployment_id: String,
+    pub provider: String,
+    pub kind: DriftKind,
+}
+
+impl Drift {
+    pub fn reconciliation(&self) -> Reconciliation {
+        match &self.kind {
+            DriftKind::Replicas { declared, .. } => Reconciliation::Scale(*declared),
+            DriftKind::AlertRules { .. } => Reconciliation::SyncAlertRules,
+            DriftKind::NotRunning | DriftKind::CodeHash { .. } => Reconciliation::Redeploy,
+        }
+    }
+}
+
+/// Differences between declared and observed state
+pub fn compare(declared: &DeclaredState, observed: &ObservedState) -> Vec<DriftKind> {
+    let mut drifts = Vec::new();
+    if let Some(live) = observed.replicas.filter(|live| *live != declared.replicas) {
+        drifts.push(DriftKind::Replicas {
+            declared: declared.replicas,
+            live,
+        });
+    }
+    if let Some(live) = &observed.alert_rules {
+        let missing: Vec<String> = declared.alert_rules.iter().filter(|r| !live.contains(r)).cloned().collect();
+        let unexpected: Vec<String> = live.iter().filter(|r| !declared.alert_rules.contains(r)).cloned().collect();
+        if !missing.is_empty() || !unexpected.is_empty() {
+            drifts.push(DriftKind::AlertRules { missing, unexpected });
+        }
+    }
+    if let Some(live) = observed.code_hash.as_ref().filter(|live| !live.eq_ignore_ascii_case(&declared.code_hash)) {
+        drifts.push(DriftKind::CodeHash {
+            declared: declared.code_hash.clone(),
+            live: live.clone(),
+        });
+    }
+    drifts
+}
+
+/// Compares deployment records against live state from every provider
+#[derive(Default)]
+pub struct DriftDetector {
+    providers: Vec<Box<dyn LiveStateProvider>>,
+}
+
+impl DriftDetector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_provider(mut self, provider: Box<dyn LiveStateProvider>) -> Self {
+        self.providers.push(provider);
+        self
+    }
+
+    /// Drift of every deployment that should be running. A provider that
+    /// fails is skipped for that deployment rather than reported as drift.
+    pub fn detect(&self, deployments: &[DeploymentInfo]) -> Vec<Drift> {
+        let mut drifts = Vec::new();
+        for deployment in deployments {
+            if matches!(deployment.status, DeploymentStatus::Stopped | DeploymentStatus::Pending) {
+                continue;
+            }
+            let declared = DeclaredState::of(deployment);
+            for provider in &self.providers {
+                let kinds = match provider.observe(deployment) {
+                    Ok(Some(observed)) => compare(&declared, &observed),
+                    Ok(None) => vec![DriftKind::NotRunning],
+                    Err(e) => {
+                        log::warn!("Drift check of {} via {} failed: {}", deployment.id, provider.name(), e);
+                        continue;
+                    }
+                };
+                drifts.extend(kinds.into_iter().map(|kind| Drift {
+                    deployment_id: deployment.id.clone(),
+                    provider: provider.name().to_string(),
+                    kind,
+                }));
+            }
+        }
+        drifts
+    }
+}
+
+impl DeploymentManager {
+    /// Check every deployment for drift, marking drifted ones degraded and
+    /// counting them in the `deployment_drift` metric
+    pub fn detect_drift(&self, detector: &DriftDetector) -> CanvasResult<Vec<Drift>> {
+        let drifts = detector.detect(&self.list_deployments());
+        if !drifts.is_empty() {
+            let mut deployments = self.deployments.lock().unwrap();
+            for drift in &drifts {
+                log::warn!("Deployment {} drifted: {:?}", drift.deployment_id, drift.kind);
+                if let Some(deployment) = deployments.get_mut(&drift.deployment_id) {
+                    deployment.status = DeploymentStatus::Degraded(format!("drift: {:?}", drift.kind));
+                }
+            }
+        }
+        self.metrics
+            .lock()
+            .unwrap()
+            .increment_counter("deployment_drift", drifts.len() as u64)?;
+        Ok(drifts)
+    }
+
+    /// Bring a drifted deployment back to its declared state. Alert rule
+    /// drift is an error: there is no monitoring backend to push rules to.
+    pub async fn reconcile(&self, drift: &Drift) -> CanvasResult<()> {
+        log::info!("Reconciling {}: {:?}", drift.deployment_id, drift.reconciliation());
+        match drift.reconciliation() {
+            Reconciliation::Scale(replicas) => self.scale(&drift.deployment_id, replicas).await,
+            Reconciliation::Redeploy => self.start_deployment(&drift.deployment_id).await,
+            Reconciliation::SyncAlertRules => Err(CanvasError::InvalidState(format!(
+                "Cannot sync the alert rules of {}: no monitoring backend is integrated",
+                drift.deployment_id
+            ))),
+        }
+    }
+}
+
+/// Check for drift every `interval` until the returned task is aborted,
+/// reconciling automatically when `auto_reconcile` is set
+pub fn spawn_drift_monitor(
+    manager: Arc<DeploymentManager>,
+    detector: Arc<DriftDetector>,
+    interval: Duration,
+    auto_reconcile: bool,
+) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            let drifts = match manager.detect_drift(&detector) {
+                Ok(drifts) => drifts,
+                Err(e) => {
+                    log::error!("Drift detection failed: {}", e);
+                    continue;
+                }
+            };
+            if auto_reconcile {
+                for drift in &drifts {
+                    if let Err(e) = manager.reconcile(drift).await {
+                        log::error!("Reconciling {} failed: {}", drift.deployment_id, e);
+                    }
+                }
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compare_reports_each_difference() {
+        let declared = DeclaredState {
+            replicas: 3,
+            alert_rules: vec!["high_error_rate".to_string(), "high_latency".to_string()],
+            code_hash: "0xabc".to_string(),
+        };
+        assert!(compare(&declared, &ObservedState::default()).is_empty());
+
+        let observed = ObservedState {
+            replicas: Some(1),
+            alert_rules: Some(vec!["high_latency".to_string(), "manual".to_string()]),
+            code_hash: Some("0xABC".to_string()),
+        };
+        let drifts = compare(&declared, &observed);
+        assert_eq!(
+            drifts,
+            vec![
+                DriftKind::Replicas { declared: 3, live: 1 },
+                DriftKind::AlertRules {
+                    missing: vec!["high_error_rate".to_string()],
+                    unexpected: vec!["manual".to_string()],
+                },
+            ]
+        );
+        let drift = Drift {
+            deployment_id: "token".to_string(),
+            provider: "k8s".to_string(),
+            kind: drifts[0].clone(),
+        };
+        assert_eq!(drift.reconciliation(), Reconciliation::Scale(3));
+    }
+
+    #[tokio::test]
+    async fn test_alert_rule_drift_is_not_reconciled_by_redeploying() {
+        let manager = DeploymentManager::new(&crate::config::Config::default()).unwrap();
+        let drift = Drift {
+            deployment_id: "token".to_string(),
+            provider: "k8s".to_string(),
+            kind: DriftKind::AlertRules {
+                missing: vec!["high_latency".to_string()],
+                unexpected: Vec::new(),
+            },
+        };
+        let error = manager.reconcile(&drift).await.unwrap_err();
+        assert!(matches!(error, CanvasError::InvalidState(_)), "{}", error);
+    }
+}
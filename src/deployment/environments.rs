@@ -0,0 +1,412 @@
+//! Deployment environments and release promotion
+//!
+//! A workspace declares its environments in `environments.toml`, in promotion
+//! order (typically dev, staging, prod). Each names the BaaLS node to deploy
+//! to, the signing key and config overrides. Every deployment to an
+//! environment leaves a release record and a copy of the deployed artifact
+//! under `.canvas/releases`. Promotion deploys the exact artifact of the
+//! latest release of one environment to the next, and only once that release
+//! has been verified (its tests and checks passed in that environment), and
+//! never over a release that has been [frozen](super::attestations).
+
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use super::attestations::AttestationStore;
+use crate::{
+    compiler::{diff_abi, AbiDiff},
+    config::Config,
+    error::{CanvasError, CanvasResult},
+    types::{ContractABI, ContractAddress},
+    wasm::host,
+};
+
+/// Environment definitions file at the workspace root
+pub const ENVIRONMENTS_FILE: &str = "environments.toml";
+/// Release records and artifacts, relative to the workspace root
+pub const RELEASES_DIR: &str = ".canvas/releases";
+
+/// One deployment target
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Environment {
+    pub name: String,
+    /// BaaLS node to deploy to
+    pub node_url: String,
+    /// Signing key file relative to the workspace root, or `ledger` /
+    /// `ledger:<derivation path>` for a hardware wallet
+    pub key: PathBuf,
+    /// Config values set for this environment, by key path (e.g. `baals.network.name`)
+    #[serde(default)]
+    pub overrides: BTreeMap<String, toml::Value>,
+}
+
+impl Environment {
+    /// `base` with this environment's node and overrides applied
+    pub fn apply(&self, base: &Config) -> CanvasResult<Config> {
+        let mut config = base.clone();
+        config.baals.node_url = self.node_url.clone();
+        for (key, value) in &self.overrides {
+            let value = serde_json::to_value(value)?;
+            config.set_value(key, value).map_err(|e| {
+                CanvasError::Config(format!("Environment '{}': {}", self.name, e))
+            })?;
+        }
+        Ok(config)
+    }
+}
+
+/// The environments of a workspace, in promotion order
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Environments {
+    #[serde(default, rename = "environment")]
+    pub environments: Vec<Environment>,
+}
+
+impl Environments {
+    pub fn parse(text: &str) -> CanvasResult<Self> {
+        let environments: Environments =
+            toml::from_str(text).map_err(|e| CanvasError::Config(format!("Invalid {}: {}", ENVIRONMENTS_FILE, e)))?;
+        for (index, environment) in environments.environments.iter().enumerate() {
+            if environments.environments[..index].iter().any(|e| e.name == environment.name) {
+                return Err(CanvasError::Config(format!("Environment '{}' is defined twice", environment.name)));
+            }
+        }
+        Ok(environments)
+    }
+
+    /// Load `environments.toml` from a workspace root
+    pub fn load(root: &Path) -> CanvasResult<Self> {
+        Self::parse(&std::fs::read_to_string(root.join(ENVIRONMENTS_FILE))?)
+    }
+
+    pub fn get(&self, name: &str) -> CanvasResult<&Environment> {
+        self.environments
+            .iter()
+            .find(|e| e.name == name)
+            .ok_or_else(|| CanvasError::NotFound(format!("Environment '{}'", name)))
+    }
+
+    /// Environment a release of `name` is promoted to
+    pub fn next(&self, name: &str) -> CanvasResult<Option<&Environment>> {
+        let index = self
+            .environments
+            .iter()
+            .position(|e| e.name == name)
+            .ok_or_else(|| CanvasError::NotFound(format!("Environment '{}'", name)))?;
+        Ok(self.environments.get(index + 1))
+    }
+}
+
+/// A contract build deployed to an environment
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReleaseRecord {
+    pub environment: String,
+    pub contract_address: ContractAddress,
+    /// SHA-256 of the deployed WASM, 0x-hex
+    pub artifact_hash: String,
+    pub abi: Option<ContractABI>,
+    /// Constructor arguments, reused when the release is promoted
+    #[serde(default)]
+    pub constructor_args: serde_json::Value,
+    pub block_number: u64,
+    pub deployed_at: u64,
+    /// Environment the artifact was promoted from, if any
+    #[serde(default)]
+    pub promoted_from: Option<String>,
+    /// Set once the release has passed verification in its environment
+    #[serde(default)]
+    pub verified_at: Option<u64>,
+    /// 0x-hex public key the release was deployed with, which is the only
+    /// key that may [freeze](super::attestations) it
+    #[serde(default)]
+    pub deployer: Option<String>,
+}
+
+impl ReleaseRecord {
+    pub fn is_verified(&self) -> bool {
+        self.verified_at.is_some()
+    }
+}
+
+/// SHA-256 of an artifact, as recorded in releases
+pub fn artifact_hash(wasm_bytes: &[u8]) -> String {
+    crate::nodes::encode_hex(&host::hash(host::HashAlgorithm::Sha256, wasm_bytes))
+}
+
+fn now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+/// Release history of a workspace
+#[derive(Debug, Clone)]
+pub struct ReleaseStore {
+    dir: PathBuf,
+}
+
+impl ReleaseStore {
+    pub fn new(root: &Path) -> Self {
+        Self {
+            dir: root.join(RELEASES_DIR),
+        }
+    }
+
+    /// Directory holding the records and artifacts
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    fn history_path(&self, environment: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", environment))
+    }
+
+    fn artifact_path(&self, hash: &str) -> PathBuf {
+        self.dir.join("artifacts").join(format!("{}.wasm", hash.trim_start_matches("0x")))
+    }
+
+    /// Releases of an environment, oldest first
+    pub fn history(&self, environment: &str) -> CanvasResult<Vec<ReleaseRecord>> {
+        let path = self.history_path(environment);
+        if !path.exists() {
+            return Ok(Vec::new());
+        }
+        Ok(serde_json::from_str(&std::fs::read_to_string(path)?)?)
+    }
+
+    pub fn latest(&self, environment: &str) -> CanvasResult<Option<ReleaseRecord>> {
+        Ok(self.history(environment)?.pop())
+    }
+
+    /// Environments with a release history, sorted by name
+    pub fn environments(&self) -> CanvasResult<Vec<String>> {
+        if !self.dir.exists() {
+            return Ok(Vec::new());
+        }
+        let mut environments = Vec::new();
+        for entry in std::fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            let frozen = path.file_name().and_then(|n| n.to_str()) == Some(super::attestations::FROZEN_FILE);
+            if path.is_file() && !frozen && path.extension().and_then(|e| e.to_str()) == Some("json") {
+                if let Some(stem) = path.file_stem().and_then(|s| s.to_str()) {
+                    environments.push(stem.to_string());
+                }
+            }
+        }
+        environments.sort();
+        Ok(environments)
+    }
+
+    fn write_history(&self, environment: &str, history: &[ReleaseRecord]) -> CanvasResult<()> {
+        std::fs::create_dir_all(&self.dir)?;
+        std::fs::write(self.history_path(environment), serde_json::to_string_pretty(history)?)?;
+        Ok(())
+    }
+
+    /// Record a deployment and keep a copy of its artifact
+    pub fn record(&self, release: ReleaseRecord, wasm_bytes: &[u8]) -> CanvasResult<()> {
+        if artifact_hash(wasm_bytes) != release.artifact_hash {
+            return Err(CanvasError::Validation("Release artifact hash does not match its bytes".to_string()));
+        }
+        let artifact = self.artifact_path(&release.artifact_hash);
+        if let Some(parent) = artifact.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(artifact, wasm_bytes)?;
+
+        let mut history = self.history(&release.environment)?;
+        let environment = release.environment.clone();
+        history.push(release);
+        self.write_history(&environment, &history)
+    }
+
+    /// Mark the latest release of an environment verified
+    pub fn verify(&self, environment: &str) -> CanvasResult<ReleaseRecord> {
+        let mut history = self.history(environment)?;
+        let latest = history
+            .last_mut()
+            .ok_or_else(|| CanvasError::NotFound(format!("No release in environment '{}'", environment)))?;
+        latest.verified_at.get_or_insert_with(now);
+        let verified = latest.clone();
+        self.write_history(environment, &history)?;
+        Ok(verified)
+    }
+
+    /// Delete an environment's release history. Artifacts stay, since other
+    /// environments may share them, and so does a history holding a frozen
+    /// contract. Returns how many releases were deleted.
+    pub fn delete(&self, environment: &str) -> CanvasResult<usize> {
+        let history = self.history(environment)?;
+        let attestations = self.attestations();
+        for release in &history {
+            if attestations.get(&release.contract_address)?.is_some() {
+                return Err(CanvasError::PermissionDenied(format!(
+                    "Release history of '{}' holds frozen contract {}",
+                    environment, release.contract_address
+                )));
+            }
+        }
+        let path = self.history_path(environment);
+        if path.exists() {
+            std::fs::remove_file(path)?;
+        }
+        Ok(history.len())
+    }
+
+    /// Freeze attestations of the workspace's contracts
+    pub fn attestations(&self) -> AttestationStore {
+        AttestationStore::new(self)
+    }
+
+    /// Stored artifact of a release, checked against its recorded hash
+    pub fn artifact(&self, release: &ReleaseRecord) -> CanvasResult<Vec<u8>> {
+        let bytes = std::fs::read(self.artifact_path(&release.artifact_hash))?;
+        if artifact_hash(&bytes) != release.artifact_hash {
+            return Err(CanvasError::Validation(format!(
+                "Stored artifact {} has been modified",
+                release.artifact_hash
+            )));
+        }
+        Ok(bytes)
+    }
+}
+
+/// What promoting an environment's latest release would deploy
+#[derive(Debug, Clone)]
+pub struct PromotionPlan {
+    pub release: ReleaseRecord,
+    pub target: Environment,
+    pub wasm_bytes: Vec<u8>,
+    /// ABI changes against the target's current release
+    pub abi_diff: Option<AbiDiff>,
+}
+
+impl PromotionPlan {
+    /// Release record for the promoted deployment
+    pub fn promoted(&self, contract_address: ContractAddress, block_number: u64) -> ReleaseRecord {
+        ReleaseRecord {
+            environment: self.target.name.clone(),
+            contract_address,
+            artifact_hash: self.release.artifact_hash.clone(),
+            abi: self.release.abi.clone(),
+            constructor_args: self.release.constructor_args.clone(),
+            block_number,
+            deployed_at: now(),
+            promoted_from: Some(self.release.environment.clone()),
+            verified_at: None,
+            deployer: None,
+        }
+    }
+}
+
+/// Plan the promotion of the latest release of `from` to the next environment,
+/// or to `to` when given. Fails unless that release is verified and its stored
+/// artifact is intact.
+pub fn plan_promotion(
+    environments: &Environments,
+    store: &ReleaseStore,
+    from: &str,
+    to: Option<&str>,
+) -> CanvasResult<PromotionPlan> {
+    let target = match to {
+        Some(name) => environments.get(name)?,
+        None => environments
+            .next(from)?
+            .ok_or_else(|| CanvasError::Validation(format!("'{}' is the last environment", from)))?,
+    };
+    let release = store
+        .latest(from)?
+        .ok_or_else(|| CanvasError::NotFound(format!("No release in environment '{}'", from)))?;
+    if !release.is_verified() {
+        return Err(CanvasError::Validation(format!(
+            "Release {} in '{}' has not been verified; only verified builds can be promoted",
+            release.artifact_hash, from
+        )));
+    }
+    let current = store.latest(&target.name)?;
+    if let Some(current) = &current {
+        store.attestations().ensure_not_frozen(&current.contract_address)?;
+    }
+    let wasm_bytes = store.artifact(&release)?;
+    let abi_diff = match (current.and_then(|r| r.abi), &release.abi) {
+        (Some(current), Some(promoted)) => Some(diff_abi(&current, promoted)),
+        _ => None,
+    };
+
+    Ok(PromotionPlan {
+        release,
+        target: target.clone(),
+        wasm_bytes,
+        abi_diff,
+    })
+}
+
+/// Release record for a fresh deployment to an environment
+pub fn new_release(
+    environment: &str,
+    contract_address: ContractAddress,
+    wasm_bytes: &[u8],
+    abi: Option<ContractABI>,
+    constructor_args: serde_json::Value,
+    block_number: u64,
+) -> ReleaseRecord {
+    ReleaseRecord {
+        environment: environment.to_string(),
+        contract_address,
+        artifact_hash: artifact_hash(wasm_bytes),
+        abi,
+        constructor_args,
+        block_number,
+        deployed_at: now(),
+        promoted_from: None,
+        verified_at: None,
+        deployer: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ENVIRONMENTS: &str = r#"
+        [[environment]]
+        name = "dev"
+        node_url = "http://localhost:8080"
+        key = "keys/dev.key"
+
+        [[environment]]
+        name = "prod"
+        node_url = "https://baals.example.com"
+        key = "keys/prod.key"
+        overrides = { "compiler.optimization_level" = 3, "baals.network.name" = "mainnet" }
+    "#;
+
+    #[test]
+    fn test_promotes_only_verified_releases() {
+        let environments = Environments::parse(ENVIRONMENTS).unwrap();
+        let prod = environments.next("dev").unwrap().unwrap();
+        let config = prod.apply(&Config::default()).unwrap();
+        assert_eq!(config.compiler.optimization_level, 3);
+        assert_eq!(config.baals.network.name, "mainnet");
+        assert!(environments.next("prod").unwrap().is_none());
+
+        let root = tempfile::tempdir().unwrap();
+        let store = ReleaseStore::new(root.path());
+        let wasm = b"\0asm\x01\0\0\0".to_vec();
+        store.record(new_release("dev", "0x01".into(), &wasm, None, serde_json::Value::Null, 7), &wasm).unwrap();
+
+        let err = plan_promotion(&environments, &store, "dev", None).unwrap_err();
+        assert!(err.to_string().contains("not been verified"));
+
+        store.verify("dev").unwrap();
+        let plan = plan_promotion(&environments, &store, "dev", None).unwrap();
+        assert_eq!(plan.target.name, "prod");
+        assert_eq!(plan.wasm_bytes, wasm);
+        assert_eq!(plan.promoted("0x02".into(), 9).promoted_from.as_deref(), Some("dev"));
+    }
+}
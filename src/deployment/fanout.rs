@@ -0,0 +1,344 @@
+//! Multi-chain deployment fan-out
+//!
+//! One artifact is deployed to several environments in one go, each on its
+//! own BaaLS network. After each deployment the code hash reported by the
+//! chain is checked against the artifact's. Every successful deployment gets
+//! the usual per-environment [`ReleaseRecord`](super::environments::ReleaseRecord),
+//! and the whole fan-out is kept as one [`MultiChainRelease`] in
+//! `.canvas/releases/multichain.json`. A failure on one network doesn't stop
+//! the others; it is recorded and shows in the report.
+
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::environments::{artifact_hash, new_release, Environment, ReleaseStore};
+use crate::{
+    baals::{signer::signer_from_spec, BaalsClient, DeploymentResult},
+    config::Config,
+    error::CanvasResult,
+    monitoring::budgets::GasBudgets,
+    progress::Progress,
+    types::{ContractABI, ContractAddress, Gas, TransactionHash},
+};
+
+/// Unified release records, inside the releases directory
+pub const MULTICHAIN_FILE: &str = "multichain.json";
+
+/// Deploys artifacts to an environment's network
+pub trait NetworkDeployer {
+    fn deploy(
+        &self,
+        environment: &Environment,
+        wasm_bytes: &[u8],
+        constructor_args: serde_json::Value,
+    ) -> CanvasResult<DeploymentResult>;
+
+    /// 0x-hex SHA-256 of the code deployed at `address`
+    fn code_hash(&self, environment: &Environment, address: &str) -> CanvasResult<String>;
+
+    /// 0x-hex public key deployments to `environment` are signed with, if known
+    fn deployer_key(&self, _environment: &Environment) -> Option<String> {
+        None
+    }
+}
+
+/// Deploys through each environment's BaaLS node with its signer (key file or
+/// hardware wallet)
+pub struct BaalsDeployer {
+    base: Config,
+    root: PathBuf,
+}
+
+impl BaalsDeployer {
+    /// `root` is the workspace root that environment key paths are relative to
+    pub fn new(base: &Config, root: &Path) -> Self {
+        Self {
+            base: base.clone(),
+            root: root.to_path_buf(),
+        }
+    }
+
+    fn client(&self, environment: &Environment) -> CanvasResult<BaalsClient> {
+        BaalsClient::new(&environment.apply(&self.base)?)
+    }
+}
+
+impl NetworkDeployer for BaalsDeployer {
+    fn deploy(
+        &self,
+        environment: &Environment,
+        wasm_bytes: &[u8],
+        constructor_args: serde_json::Value,
+    ) -> CanvasResult<DeploymentResult> {
+        let config = environment.apply(&self.base)?;
+        let signer = signer_from_spec(&environment.key.to_string_lossy(), &self.root, &config)?;
+        BaalsClient::new(&config)?
+            .with_budgets(GasBudgets::load(&self.root)?)
+            .deploy_contract(wasm_bytes, constructor_args, signer.as_ref())
+    }
+
+    fn code_hash(&self, environment: &Environment, address: &str) -> CanvasResult<String> {
+        Ok(self.client(environment)?.get_contract_state(address)?.code_hash)
+    }
+
+    fn deployer_key(&self, environment: &Environment) -> Option<String> {
+        let config = environment.apply(&self.base).ok()?;
+        signer_from_spec(&environment.key.to_string_lossy(), &self.root, &config).ok()?.address().ok()
+    }
+}
+
+/// Outcome of the fan-out on one network
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NetworkDeployment {
+    pub environment: String,
+    pub node_url: String,
+    pub contract_address: Option<ContractAddress>,
+    pub transaction_hash: Option<TransactionHash>,
+    pub block_number: Option<u64>,
+    pub gas_used: Option<Gas>,
+    /// Code hash the chain reports for the deployed contract
+    pub code_hash: Option<String>,
+    /// Deployment or verification error
+    pub error: Option<String>,
+}
+
+impl NetworkDeployment {
+    /// Deployed, and the chain holds exactly the artifact
+    pub fn is_verified(&self) -> bool {
+        self.error.is_none() && self.contract_address.is_some()
+    }
+}
+
+/// One artifact deployed to several networks
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MultiChainRelease {
+    pub id: Uuid,
+    pub artifact_hash: String,
+    pub deployed_at: DateTime<Utc>,
+    pub deployments: Vec<NetworkDeployment>,
+}
+
+impl MultiChainRelease {
+    /// Every network deployed and verified
+    pub fn is_complete(&self) -> bool {
+        self.deployments.iter().all(NetworkDeployment::is_verified)
+    }
+
+    pub fn failures(&self) -> Vec<&NetworkDeployment> {
+        self.deployments.iter().filter(|d| !d.is_verified()).collect()
+    }
+
+    /// Address of the contract on an environment's network
+    pub fn address(&self, environment: &str) -> Option<&str> {
+        self.deployments
+            .iter()
+            .find(|d| d.environment == environment)
+            .and_then(|d| d.contract_address.as_deref())
+    }
+
+    /// Consolidated report as a Markdown table
+    pub fn to_markdown(&self) -> String {
+        let mut report = format!(
+            "# Deployment {}\n\nArtifact `{}`, deployed {}\n\n",
+            self.id,
+            self.artifact_hash,
+            self.deployed_at.to_rfc3339()
+        );
+        report.push_str("| Environment | Node | Address | Block | Gas | Status |\n|---|---|---|---|---|---|\n");
+        for deployment in &self.deployments {
+            let status = match &deployment.error {
+                None => "verified".to_string(),
+                Some(error) => format!("failed: {}", error.replace('|', "\\|")),
+            };
+            let cell = |value: Option<String>| value.unwrap_or_else(|| "-".to_string());
+            report.push_str(&format!(
+                "| {} | {} | {} | {} | {} | {} |\n",
+                deployment.environment,
+                deployment.node_url,
+                cell(deployment.contract_address.clone()),
+                cell(deployment.block_number.map(|b| b.to_string())),
+                cell(deployment.gas_used.map(|g| g.to_string())),
+                status
+            ));
+        }
+        let verified = self.deployments.len() - self.failures().len();
+        report.push_str(&format!("\n{} of {} network(s) verified\n", verified, self.deployments.len()));
+        report
+    }
+}
+
+fn deploy_one(
+    environment: &Environment,
+    deployer: &dyn NetworkDeployer,
+    wasm_bytes: &[u8],
+    expected_hash: &str,
+    constructor_args: &serde_json::Value,
+) -> (NetworkDeployment, Option<DeploymentResult>) {
+    let mut deployment = NetworkDeployment {
+        environment: environment.name.clone(),
+        node_url: environment.node_url.clone(),
+        contract_address: None,
+        transaction_hash: None,
+        block_number: None,
+        gas_used: None,
+        code_hash: None,
+        error: None,
+    };
+    let result = match deployer.deploy(environment, wasm_bytes, constructor_args.clone()) {
+        Ok(result) => result,
+        Err(e) => {
+            deployment.error = Some(e.to_string());
+            return (deployment, None);
+        }
+    };
+    deployment.contract_address = Some(result.contract_address.clone());
+    deployment.transaction_hash = Some(result.transaction_hash.clone());
+    deployment.block_number = Some(result.block_number);
+    deployment.gas_used = Some(result.gas_used);
+
+    match deployer.code_hash(environment, &result.contract_address) {
+        Ok(hash) if hash.eq_ignore_ascii_case(expected_hash) => deployment.code_hash = Some(hash),
+        Ok(hash) => {
+            deployment.error = Some(format!("code hash {} does not match the artifact", hash));
+            deployment.code_hash = Some(hash);
+        }
+        Err(e) => deployment.error = Some(format!("could not verify code hash: {}", e)),
+    }
+    (deployment, Some(result))
+}
+
+/// Deploy `wasm_bytes` to every environment in turn, recording each
+/// deployment and the unified release in `store`. `progress` advances once
+/// per network.
+pub fn fan_out(
+    environments: &[Environment],
+    deployer: &dyn NetworkDeployer,
+    store: &ReleaseStore,
+    wasm_bytes: &[u8],
+    abi: Option<&ContractABI>,
+    constructor_args: serde_json::Value,
+    progress: &dyn Progress,
+) -> CanvasResult<MultiChainRelease> {
+    let expected_hash = artifact_hash(wasm_bytes);
+    let mut release = MultiChainRelease {
+        id: Uuid::new_v4(),
+        artifact_hash: expected_hash.clone(),
+        deployed_at: Utc::now(),
+        deployments: Vec::with_capacity(environments.len()),
+    };
+
+    progress.stage("deploy", environments.len() as u64);
+    for environment in environments {
+        log::info!("Deploying {} to '{}' ({})", expected_hash, environment.name, environment.node_url);
+        let (deployment, result) = deploy_one(environment, deployer, wasm_bytes, &expected_hash, &constructor_args);
+        match (&deployment.error, &deployment.contract_address) {
+            (None, Some(address)) => log::info!("  '{}': {} verified", environment.name, address),
+            (Some(error), _) => log::warn!("  '{}': {}", environment.name, error),
+            (None, None) => {}
+        }
+        // Deployed contracts are on chain whether or not they verify, so they get a release record
+        if let Some(result) = result {
+            let mut record = new_release(
+                &environment.name,
+                result.contract_address,
+                wasm_bytes,
+                abi.cloned(),
+                constructor_args.clone(),
+                result.block_number,
+            );
+            record.deployer = deployer.deployer_key(environment);
+            store.record(record, wasm_bytes)?;
+        }
+        release.deployments.push(deployment);
+        progress.advance(1);
+    }
+    progress.finish();
+
+    let mut history = multichain_history(store)?;
+    history.push(release.clone());
+    std::fs::create_dir_all(store.dir())?;
+    std::fs::write(store.dir().join(MULTICHAIN_FILE), serde_json::to_string_pretty(&history)?)?;
+    Ok(release)
+}
+
+/// Unified release records of a workspace, oldest first
+pub fn multichain_history(store: &ReleaseStore) -> CanvasResult<Vec<MultiChainRelease>> {
+    let path = store.dir().join(MULTICHAIN_FILE);
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    Ok(serde_json::from_str(&std::fs::read_to_string(path)?)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::CanvasError;
+
+    /// Deploys to every node but "down"; the "tampered" node reports foreign code
+    struct FakeDeployer;
+
+    impl NetworkDeployer for FakeDeployer {
+        fn deploy(
+            &self,
+            environment: &Environment,
+            wasm_bytes: &[u8],
+            _: serde_json::Value,
+        ) -> CanvasResult<DeploymentResult> {
+            if environment.name == "down" {
+                return Err(CanvasError::Network("connection refused".to_string()));
+            }
+            Ok(DeploymentResult {
+                contract_address: format!("0x{}", environment.name),
+                transaction_hash: "0x01".to_string(),
+                gas_used: wasm_bytes.len() as Gas,
+                block_number: 7,
+            })
+        }
+
+        fn code_hash(&self, environment: &Environment, _: &str) -> CanvasResult<String> {
+            Ok(match environment.name.as_str() {
+                "tampered" => artifact_hash(b"other"),
+                _ => artifact_hash(b"\0asm"),
+            })
+        }
+    }
+
+    fn environment(name: &str) -> Environment {
+        Environment {
+            name: name.to_string(),
+            node_url: format!("http://{}.example.com", name),
+            key: PathBuf::from("keys/deploy.key"),
+            overrides: Default::default(),
+        }
+    }
+
+    #[test]
+    fn test_fan_out_records_and_verifies_each_network() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = ReleaseStore::new(dir.path());
+        let environments: Vec<_> = ["mainnet", "down", "tampered"].into_iter().map(environment).collect();
+
+        let progress = crate::progress::ProgressLog::new();
+        let release =
+            fan_out(&environments, &FakeDeployer, &store, b"\0asm", None, serde_json::Value::Null, &progress).unwrap();
+        assert_eq!(release.address("mainnet"), Some("0xmainnet"));
+        assert!(release.deployments[0].is_verified());
+        assert!(release.deployments[1].error.as_deref().unwrap().contains("connection refused"));
+        assert!(release.deployments[2].error.as_deref().unwrap().contains("does not match"));
+        assert!(!release.is_complete());
+
+        // Both deployed contracts get a release record; the failed network doesn't
+        assert_eq!(store.latest("tampered").unwrap().unwrap().contract_address, "0xtampered");
+        assert!(store.latest("down").unwrap().is_none());
+        assert_eq!(multichain_history(&store).unwrap(), vec![release.clone()]);
+        assert_eq!(progress.events().len(), 5, "one step per network, failed or not");
+
+        let report = release.to_markdown();
+        assert!(report.contains("| mainnet | http://mainnet.example.com | 0xmainnet | 7 | 4 | verified |"));
+        assert!(report.contains("1 of 3 network(s) verified"));
+    }
+}
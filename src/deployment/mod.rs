@@ -1,11 +1,18 @@
 //! Production deployment and scaling system
 
+pub mod attestations;
+pub mod drift;
+pub mod environments;
+pub mod fanout;
+
 use crate::{
+    compiler::Compiler,
     error::CanvasResult,
-    types::{Graph, NodeId},
+    types::{NodeId, VisualGraph},
     config::Config,
     monitoring::{MetricsCollector, HealthChecker, CircuitBreaker},
     optimization::PerformanceOptimizer,
+    progress::{self, Progress},
 };
 
 use serde::{Deserialize, Serialize};
@@ -21,6 +28,7 @@ pub struct DeploymentManager {
     optimizer: Arc<Mutex<PerformanceOptimizer>>,
     deployments: Arc<Mutex<HashMap<String, DeploymentInfo>>>,
     circuit_breakers: Arc<Mutex<HashMap<String, CircuitBreaker>>>,
+    progress: Arc<dyn Progress>,
 }
 
 /// Deployment information
@@ -29,8 +37,11 @@ pub struct DeploymentInfo {
     pub id: String,
     pub name: String,
     pub status: DeploymentStatus,
-    pub graph: Graph,
+    pub graph: VisualGraph,
     pub wasm_bytes: Vec<u8>,
+    /// On-chain address of the deployed contract, once known
+    #[serde(default)]
+    pub contract_address: Option<crate::types::ContractAddress>,
     pub config: DeploymentConfig,
     pub metrics: DeploymentMetrics,
     pub created_at: u64,
@@ -288,20 +299,30 @@ impl DeploymentManager {
             optimizer,
             deployments: Arc::new(Mutex::new(HashMap::new())),
             circuit_breakers: Arc::new(Mutex::new(HashMap::new())),
+            progress: progress::quiet(),
         })
     }
 
+    /// Report the stages of each deployment to `progress`
+    pub fn with_progress(mut self, progress: Arc<dyn Progress>) -> Self {
+        self.progress = progress;
+        self
+    }
+
     /// Deploy a contract
-    pub async fn deploy(&self, name: &str, graph: &Graph, config: DeploymentConfig) -> CanvasResult<String> {
+    pub async fn deploy(&self, name: &str, graph: &VisualGraph, config: DeploymentConfig) -> CanvasResult<String> {
         let deployment_id = self.generate_deployment_id(name);
         
         // Optimize the graph
-        let optimization_results = {
+        self.progress.stage("optimize", 0);
+        let optimized = {
             let mut optimizer = self.optimizer.lock().unwrap();
             optimizer.optimize(graph)?
         };
+        let graph = &optimized.graph;
 
         // Compile to WASM
+        self.progress.stage("compile", 0);
         let wasm_bytes = self.compile_graph(graph)?;
 
         // Create deployment info
@@ -311,6 +332,7 @@ impl DeploymentManager {
             status: DeploymentStatus::Pending,
             graph: graph.clone(),
             wasm_bytes,
+            contract_address: None,
             config,
             metrics: DeploymentMetrics::default(),
             created_at: std::time::SystemTime::now()
@@ -330,7 +352,10 @@ impl DeploymentManager {
         }
 
         // Start deployment process
-        self.start_deployment(&deployment_id).await?;
+        self.progress.stage("deploy", 0);
+        let started = self.start_deployment(&deployment_id).await;
+        self.progress.finish();
+        started?;
 
         Ok(deployment_id)
     }
@@ -381,7 +406,7 @@ impl DeploymentManager {
     }
 
     /// Update deployment
-    pub async fn update(&self, deployment_id: &str, graph: &Graph) -> CanvasResult<()> {
+    pub async fn update(&self, deployment_id: &str, graph: &VisualGraph) -> CanvasResult<()> {
         let mut deployments = self.deployments.lock().unwrap();
         
         if let Some(deployment) = deployments.get_mut(deployment_id) {
@@ -458,10 +483,8 @@ impl DeploymentManager {
     }
 
     /// Compile graph to WASM
-    fn compile_graph(&self, graph: &Graph) -> CanvasResult<Vec<u8>> {
-        // TODO: Implement actual compilation
-        // For now, return mock WASM bytes
-        Ok(vec![0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00])
+    fn compile_graph(&self, graph: &VisualGraph) -> CanvasResult<Vec<u8>> {
+        Ok(Compiler::new(&self.config)?.compile(graph)?.wasm_bytes)
     }
 }
 
@@ -489,7 +512,7 @@ impl BlueGreenDeploymentManager {
     }
 
     /// Create blue-green deployment
-    pub async fn create_deployment(&self, id: &str, graph: &Graph, config: DeploymentConfig) -> CanvasResult<()> {
+    pub async fn create_deployment(&self, id: &str, graph: &VisualGraph, config: DeploymentConfig) -> CanvasResult<()> {
         let deployment = BlueGreenDeployment {
             id: id.to_string(),
             blue_deployment: None,
@@ -510,7 +533,7 @@ impl BlueGreenDeploymentManager {
     }
 
     /// Deploy to blue environment
-    pub async fn deploy_blue(&self, id: &str, graph: &Graph, config: DeploymentConfig) -> CanvasResult<()> {
+    pub async fn deploy_blue(&self, id: &str, graph: &VisualGraph, config: DeploymentConfig) -> CanvasResult<()> {
         let mut deployments = self.deployments.lock().unwrap();
         
         if let Some(deployment) = deployments.get_mut(id) {
@@ -521,6 +544,7 @@ impl BlueGreenDeploymentManager {
                 status: DeploymentStatus::Running,
                 graph: graph.clone(),
                 wasm_bytes: vec![0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00],
+                contract_address: None,
                 config,
                 metrics: DeploymentMetrics::default(),
                 created_at: std::time::SystemTime::now()
@@ -538,7 +562,7 @@ impl BlueGreenDeploymentManager {
     }
 
     /// Deploy to green environment
-    pub async fn deploy_green(&self, id: &str, graph: &Graph, config: DeploymentConfig) -> CanvasResult<()> {
+    pub async fn deploy_green(&self, id: &str, graph: &VisualGraph, config: DeploymentConfig) -> CanvasResult<()> {
         let mut deployments = self.deployments.lock().unwrap();
         
         if let Some(deployment) = deployments.get_mut(id) {
@@ -549,6 +573,7 @@ impl BlueGreenDeploymentManager {
                 status: DeploymentStatus::Running,
                 graph: graph.clone(),
                 wasm_bytes: vec![0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00],
+                contract_address: None,
                 config,
                 metrics: DeploymentMetrics::default(),
                 created_at: std::time::SystemTime::now()
@@ -635,8 +660,9 @@ impl CanaryDeploymentManager {
                 id: format!("{}-canary", id),
                 name: format!("{} Canary", id),
                 status: DeploymentStatus::Pending,
-                graph: Graph::new("canary"),
+                graph: VisualGraph::new("canary"),
                 wasm_bytes: vec![0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00],
+                contract_address: None,
                 config,
                 metrics: DeploymentMetrics::default(),
                 created_at: std::time::SystemTime::now()
@@ -759,13 +785,38 @@ impl InfrastructureManager {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{
+        test_graphs::{connect, node, node_with},
+        types::{ExecutionContext, Port, ValueType},
+        wasm::{engine, BlockContext},
+    };
 
     #[tokio::test]
     async fn test_deployment_manager() {
         let config = Config::default();
         let manager = DeploymentManager::new(&config).unwrap();
         
-        let graph = Graph::new("test");
+        // main(amount) returns amount * (2 + 3); the Add folds away
+        let mut graph = VisualGraph::new("test");
+        let start = node("Start")
+            .with_outputs(vec![
+                Port::new("flow_out", "Flow Out", ValueType::Flow),
+                Port::new("amount", "Amount", ValueType::Integer),
+            ])
+            .with_property("returns", serde_json::json!("integer"));
+        let factor = node_with("Add", serde_json::json!({ "a": 2, "b": 3 }));
+        let scaled = node("Multiply");
+        let end = node("End").with_inputs(vec![
+            Port::new("flow_in", "Flow In", ValueType::Flow),
+            Port::new("result", "Result", ValueType::Integer),
+        ]);
+        connect(&mut graph, &start, "flow_out", &end, "flow_in");
+        connect(&mut graph, &start, "amount", &scaled, "a");
+        connect(&mut graph, &factor, "result", &scaled, "b");
+        connect(&mut graph, &scaled, "result", &end, "result");
+        for node in [start, factor, scaled, end] {
+            graph.add_node(node);
+        }
         let config = DeploymentConfig {
             replicas: 3,
             resources: ResourceRequirements {
@@ -816,6 +867,15 @@ mod tests {
         
         let status = manager.get_status(&deployment_id);
         assert!(status.is_some());
+
+        let deployment = manager.deployments.lock().unwrap()[&deployment_id].clone();
+        assert_eq!(deployment.graph.nodes.len(), 3, "the constant Add was folded");
+        let runtime = engine::engine().unwrap();
+        let mut context = ExecutionContext::new(100_000);
+        let block = BlockContext::new(1, 0, 1);
+        let args = [serde_json::json!(4)];
+        let run = engine::execute(&runtime, &deployment.wasm_bytes, "main", &args, 100_000, block, &mut context).unwrap();
+        assert_eq!(run.output["result"], 20);
     }
 
     #[tokio::test]
@@ -823,7 +883,7 @@ mod tests {
         let config = Config::default();
         let manager = BlueGreenDeploymentManager::new(&config);
         
-        let graph = Graph::new("test");
+        let graph = VisualGraph::new("test");
         let config = DeploymentConfig {
             replicas: 2,
             resources: ResourceRequirements {
@@ -884,8 +944,9 @@ mod tests {
             id: "stable".to_string(),
             name: "Stable".to_string(),
             status: DeploymentStatus::Running,
-            graph: Graph::new("stable"),
+            graph: VisualGraph::new("stable"),
             wasm_bytes: vec![0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00],
+            contract_address: None,
             config: DeploymentConfig {
                 replicas: 3,
                 resources: ResourceRequirements {
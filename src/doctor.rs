@@ -0,0 +1,504 @@
+//! Environment checks (`canvas-contracts doctor`)
+//!
+//! Most "it doesn't work" reports come down to the machine rather than the
+//! contract: a config file that no longer parses, a node that is down or on
+//! the wrong chain, a missing toolchain for script nodes, a full disk or an
+//! unreadable deploy key. [`Doctor`] checks each of these and pairs every
+//! failure with a fix the user can act on. Anything that talks to the outside
+//! world goes through a [`Probe`], so checks can be run against a fake system.
+
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use crate::{
+    baals::{signer::signer_from_spec, BaalsClient, NodeStatus},
+    config::Config,
+    deployment::environments::{Environments, ENVIRONMENTS_FILE},
+    error::CanvasResult,
+    nodes::custom::{CustomNodeImplementation, CustomNodeRegistry, CUSTOM_NODES_DIR},
+};
+
+/// Free space below which caches are likely to fail to grow
+pub const MIN_FREE_SPACE: u64 = 256 * 1024 * 1024;
+
+/// Free space below which a warning is shown
+pub const LOW_FREE_SPACE: u64 = 1024 * 1024 * 1024;
+
+/// Program each script node language is built with, and how to install it
+pub const SCRIPT_TOOLCHAINS: &[(&str, &str, &str)] = &[
+    (
+        "rust",
+        "cargo",
+        "install Rust from https://rustup.rs, then `rustup target add wasm32-unknown-unknown`",
+    ),
+    ("go", "tinygo", "install TinyGo from https://tinygo.org/getting-started/"),
+    (
+        "assemblyscript",
+        "npx",
+        "install Node.js, then `npm install --save-dev assemblyscript` in the workspace",
+    ),
+];
+
+/// Outcome of one check
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CheckStatus {
+    Ok,
+    Warn,
+    Fail,
+    /// Nothing to check (e.g. no script nodes installed)
+    Skip,
+}
+
+impl CheckStatus {
+    pub fn label(&self) -> &'static str {
+        match self {
+            CheckStatus::Ok => "ok",
+            CheckStatus::Warn => "warn",
+            CheckStatus::Fail => "fail",
+            CheckStatus::Skip => "skip",
+        }
+    }
+}
+
+/// One check and, when it didn't pass, how to fix it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Check {
+    pub name: String,
+    pub status: CheckStatus,
+    pub detail: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fix: Option<String>,
+}
+
+impl Check {
+    fn new(name: impl Into<String>, status: CheckStatus, detail: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            status,
+            detail: detail.into(),
+            fix: None,
+        }
+    }
+
+    fn ok(name: impl Into<String>, detail: impl Into<String>) -> Self {
+        Self::new(name, CheckStatus::Ok, detail)
+    }
+
+    fn skip(name: impl Into<String>, detail: impl Into<String>) -> Self {
+        Self::new(name, CheckStatus::Skip, detail)
+    }
+
+    fn warn(name: impl Into<String>, detail: impl Into<String>, fix: impl Into<String>) -> Self {
+        Self::new(name, CheckStatus::Warn, detail).with_fix(fix)
+    }
+
+    fn fail(name: impl Into<String>, detail: impl Into<String>, fix: impl Into<String>) -> Self {
+        Self::new(name, CheckStatus::Fail, detail).with_fix(fix)
+    }
+
+    fn with_fix(mut self, fix: impl Into<String>) -> Self {
+        self.fix = Some(fix.into());
+        self
+    }
+}
+
+/// Every check, in the order they ran
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct DoctorReport {
+    pub checks: Vec<Check>,
+}
+
+impl DoctorReport {
+    pub fn failures(&self) -> usize {
+        self.checks.iter().filter(|c| c.status == CheckStatus::Fail).count()
+    }
+
+    pub fn warnings(&self) -> usize {
+        self.checks.iter().filter(|c| c.status == CheckStatus::Warn).count()
+    }
+
+    pub fn is_healthy(&self) -> bool {
+        self.failures() == 0
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Check> {
+        self.checks.iter().find(|c| c.name == name)
+    }
+}
+
+/// The parts of the system the doctor looks at
+pub trait Probe {
+    /// Chain id and head block of the node `config` points at
+    fn node_status(&self, config: &Config) -> CanvasResult<NodeStatus>;
+
+    /// Location of an executable on `PATH`
+    fn find_program(&self, name: &str) -> Option<PathBuf>;
+
+    /// Bytes available to unprivileged users on the filesystem holding `path`
+    fn available_space(&self, path: &Path) -> CanvasResult<u64>;
+}
+
+/// The real machine
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemProbe;
+
+impl Probe for SystemProbe {
+    fn node_status(&self, config: &Config) -> CanvasResult<NodeStatus> {
+        BaalsClient::new(config)?.node_status()
+    }
+
+    fn find_program(&self, name: &str) -> Option<PathBuf> {
+        let file = format!("{}{}", name, std::env::consts::EXE_SUFFIX);
+        std::env::split_paths(&std::env::var_os("PATH")?)
+            .map(|dir| dir.join(&file))
+            .find(|path| path.is_file())
+    }
+
+    fn available_space(&self, path: &Path) -> CanvasResult<u64> {
+        // Caches may not exist yet; measure the filesystem they would be created on
+        let existing = path.ancestors().find(|p| p.exists()).unwrap_or(Path::new("."));
+        Ok(fs2::available_space(existing)?)
+    }
+}
+
+/// Runs the environment checks for a config file and workspace
+pub struct Doctor<'a> {
+    config_path: PathBuf,
+    root: PathBuf,
+    probe: &'a dyn Probe,
+}
+
+impl<'a> Doctor<'a> {
+    pub fn new(config_path: impl Into<PathBuf>, root: impl Into<PathBuf>, probe: &'a dyn Probe) -> Self {
+        Self {
+            config_path: config_path.into(),
+            root: root.into(),
+            probe,
+        }
+    }
+
+    /// Run every check. Later checks use the defaults when the config can't be loaded.
+    pub fn run(&self) -> DoctorReport {
+        let (config, check) = self.check_config();
+        let mut report = DoctorReport { checks: vec![check] };
+        report.checks.push(self.check_node(&config));
+        report.checks.extend(self.check_script_toolchains(&config));
+        report.checks.push(self.check_wasm_opt(&config));
+        report.checks.push(self.check_disk_space(&config));
+        report.checks.extend(self.check_keys(&config));
+        report
+    }
+
+    fn check_config(&self) -> (Config, Check) {
+        let path = self.config_path.display();
+        if !self.config_path.exists() {
+            let check = Check::warn(
+                "config",
+                format!("{} not found; defaults are used", path),
+                "run any command to write the defaults, then edit them",
+            );
+            return (Config::from_env().unwrap_or_default(), check);
+        }
+        match Config::from_file(&self.config_path).and_then(|config| config.validate().map(|_| config)) {
+            Ok(config) => (config, Check::ok("config", format!("{} is valid", path))),
+            Err(e) => (
+                Config::default(),
+                Check::fail(
+                    "config",
+                    e.to_string(),
+                    format!("correct {}, or delete it to regenerate the defaults", path),
+                ),
+            ),
+        }
+    }
+
+    fn check_node(&self, config: &Config) -> Check {
+        let url = &config.baals.node_url;
+        let network = &config.baals.network;
+        match self.probe.node_status(config) {
+            Ok(status) => match network.chain_id {
+                Some(expected) if expected != status.chain_id => Check::fail(
+                    "node",
+                    format!("{} is on chain {}, but '{}' expects {}", url, status.chain_id, network.name, expected),
+                    format!(
+                        "point baals.node_url at a '{}' node, or set baals.network.chain_id to {}",
+                        network.name, status.chain_id
+                    ),
+                ),
+                _ => Check::ok(
+                    "node",
+                    format!("{} reachable, chain {} at block {}", url, status.chain_id, status.block_number),
+                ),
+            },
+            Err(e) => {
+                let fix = if config.baals.enable_local_node {
+                    format!("start the local node on port {}, or set baals.node_url", config.baals.local_node_port)
+                } else {
+                    "check that the node is running and baals.node_url is right".to_string()
+                };
+                Check::fail("node", format!("{} unreachable: {}", url, e), fix)
+            }
+        }
+    }
+
+    fn check_script_toolchains(&self, config: &Config) -> Vec<Check> {
+        let dir = config.app.data_dir.join(CUSTOM_NODES_DIR);
+        let registry = if dir.is_dir() {
+            match CustomNodeRegistry::load_dir(&dir) {
+                Ok(registry) => registry,
+                Err(e) => {
+                    return vec![Check::fail(
+                        "script toolchains",
+                        format!("custom nodes in {} could not be loaded: {}", dir.display(), e),
+                        "remove or reinstall the broken node definitions",
+                    )]
+                }
+            }
+        } else {
+            CustomNodeRegistry::new()
+        };
+
+        let mut languages: Vec<String> = registry
+            .list_nodes()
+            .into_iter()
+            .filter_map(|node| match &node.implementation {
+                CustomNodeImplementation::Script { language, .. } => Some(language.to_lowercase()),
+                _ => None,
+            })
+            .collect();
+        languages.sort();
+        languages.dedup();
+        if languages.is_empty() {
+            return vec![Check::skip("script toolchains", "no script nodes installed")];
+        }
+
+        languages
+            .into_iter()
+            .map(|language| {
+                let name = format!("toolchain: {}", language);
+                match SCRIPT_TOOLCHAINS.iter().find(|(l, ..)| *l == language) {
+                    Some((_, program, install)) => match self.probe.find_program(program) {
+                        Some(path) => Check::ok(name, format!("{} at {}", program, path.display())),
+                        None => Check::fail(name, format!("{} not found on PATH", program), *install),
+                    },
+                    None => Check::fail(
+                        name,
+                        format!("script nodes in '{}' are not supported", language),
+                        "reinstall the node with a rust, go or assemblyscript implementation",
+                    ),
+                }
+            })
+            .collect()
+    }
+
+    fn check_wasm_opt(&self, config: &Config) -> Check {
+        match self.probe.find_program("wasm-opt") {
+            Some(path) => Check::ok("wasm-opt", path.display().to_string()),
+            None if config.compiler.optimization_level == 0 => {
+                Check::skip("wasm-opt", "not found; not needed at optimization level 0")
+            }
+            None => Check::warn(
+                "wasm-opt",
+                format!(
+                    "not found; optimization level {} builds skip Binaryen passes",
+                    config.compiler.optimization_level
+                ),
+                "install Binaryen (https://github.com/WebAssembly/binaryen/releases) and put wasm-opt on PATH",
+            ),
+        }
+    }
+
+    fn check_disk_space(&self, config: &Config) -> Check {
+        let dir = &config.app.data_dir;
+        let fix = format!("free up space on the disk holding {}, or move app.data_dir", dir.display());
+        match self.probe.available_space(dir) {
+            Ok(free) if free < MIN_FREE_SPACE => {
+                Check::fail("disk space", format!("{} free for caches in {}", mib(free), dir.display()), fix)
+            }
+            Ok(free) if free < LOW_FREE_SPACE => {
+                Check::warn("disk space", format!("{} free for caches in {}", mib(free), dir.display()), fix)
+            }
+            Ok(free) => Check::ok("disk space", format!("{} free for caches in {}", mib(free), dir.display())),
+            Err(e) => Check::warn("disk space", format!("could not measure free space: {}", e), fix),
+        }
+    }
+
+    /// Deploy keys of each workspace environment: readable, and a valid key
+    fn check_keys(&self, config: &Config) -> Vec<Check> {
+        if !self.root.join(ENVIRONMENTS_FILE).exists() {
+            return vec![Check::skip("keys", format!("no {} in this workspace", ENVIRONMENTS_FILE))];
+        }
+        let environments = match Environments::load(&self.root) {
+            Ok(environments) => environments,
+            Err(e) => {
+                return vec![Check::fail("keys", e.to_string(), format!("correct {}", ENVIRONMENTS_FILE))];
+            }
+        };
+
+        environments
+            .environments
+            .iter()
+            .map(|environment| {
+                let name = format!("key: {}", environment.name);
+                let spec = environment.key.to_string_lossy();
+                if spec.starts_with("impersonate:") {
+                    return Check::skip(name, "impersonated account; no key needed");
+                }
+                let hardware = spec.starts_with("ledger");
+                let path = self.root.join(&environment.key);
+                if !hardware && !path.is_file() {
+                    return Check::fail(
+                        name,
+                        format!("{} does not exist", path.display()),
+                        format!(
+                            "create the key file, or change the key of '{}' in {}",
+                            environment.name, ENVIRONMENTS_FILE
+                        ),
+                    );
+                }
+                let signer = environment
+                    .apply(config)
+                    .and_then(|config| signer_from_spec(&spec, &self.root, &config))
+                    .and_then(|signer| signer.address().map(|address| (signer.describe(), address)));
+                match signer {
+                    Ok((source, address)) => match loose_permissions(&path) {
+                        Some(mode) if !hardware => Check::warn(
+                            name,
+                            format!("{} ({}) is readable by other users (mode {:o})", source, address, mode),
+                            format!("chmod 600 {}", path.display()),
+                        ),
+                        _ => Check::ok(name, format!("{} ({})", source, address)),
+                    },
+                    Err(e) if hardware => Check::fail(
+                        name,
+                        e.to_string(),
+                        "connect and unlock the Ledger and open its app; builds need the `ledger` feature",
+                    ),
+                    Err(e) => Check::fail(
+                        name,
+                        format!("{} is not a usable key: {}", path.display(), e),
+                        "replace it with a hex ed25519 secret key",
+                    ),
+                }
+            })
+            .collect()
+    }
+}
+
+fn mib(bytes: u64) -> String {
+    format!("{} MiB", bytes / (1024 * 1024))
+}
+
+/// Permission bits of a key file that group or others can read
+#[cfg(unix)]
+fn loose_permissions(path: &Path) -> Option<u32> {
+    use std::os::unix::fs::PermissionsExt;
+    let mode = std::fs::metadata(path).ok()?.permissions().mode() & 0o777;
+    (mode & 0o077 != 0).then_some(mode)
+}
+
+#[cfg(not(unix))]
+fn loose_permissions(_path: &Path) -> Option<u32> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::CanvasError;
+
+    struct FakeProbe {
+        chain_id: Option<u64>,
+        programs: Vec<&'static str>,
+        free: u64,
+    }
+
+    impl Probe for FakeProbe {
+        fn node_status(&self, _: &Config) -> CanvasResult<NodeStatus> {
+            self.chain_id
+                .map(|chain_id| NodeStatus { chain_id, block_number: 42 })
+                .ok_or_else(|| CanvasError::Network("connection refused".to_string()))
+        }
+
+        fn find_program(&self, name: &str) -> Option<PathBuf> {
+            self.programs.contains(&name).then(|| PathBuf::from("/usr/bin").join(name))
+        }
+
+        fn available_space(&self, _: &Path) -> CanvasResult<u64> {
+            Ok(self.free)
+        }
+    }
+
+    #[test]
+    fn test_doctor_reports_each_problem_with_a_fix() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        let mut config = Config::default();
+        config.app.data_dir = root.join("data");
+        config.baals.network.chain_id = Some(7);
+        config.save_to_file(&root.join("config.toml")).unwrap();
+
+        std::fs::create_dir_all(root.join("keys")).unwrap();
+        std::fs::write(root.join("keys/dev.key"), "11".repeat(32)).unwrap();
+        std::fs::write(root.join("keys/bad.key"), "not hex").unwrap();
+        std::fs::write(
+            root.join(ENVIRONMENTS_FILE),
+            r#"
+            [[environment]]
+            name = "dev"
+            node_url = "http://localhost:8080"
+            key = "keys/dev.key"
+
+            [[environment]]
+            name = "staging"
+            node_url = "http://staging:8080"
+            key = "keys/bad.key"
+
+            [[environment]]
+            name = "prod"
+            node_url = "http://prod:8080"
+            key = "keys/missing.key"
+            "#,
+        )
+        .unwrap();
+
+        let probe = FakeProbe {
+            chain_id: Some(9),
+            programs: vec![],
+            free: 512 * 1024 * 1024,
+        };
+        let report = Doctor::new(root.join("config.toml"), root, &probe).run();
+        assert_eq!(report.get("config").unwrap().status, CheckStatus::Ok);
+        let node = report.get("node").unwrap();
+        assert_eq!(node.status, CheckStatus::Fail);
+        assert!(node.fix.as_deref().unwrap().contains("chain_id to 9"));
+        assert_eq!(report.get("script toolchains").unwrap().status, CheckStatus::Skip);
+        assert_eq!(report.get("wasm-opt").unwrap().status, CheckStatus::Warn);
+        assert_eq!(report.get("disk space").unwrap().status, CheckStatus::Warn);
+        assert_ne!(report.get("key: dev").unwrap().status, CheckStatus::Fail);
+        assert_eq!(report.get("key: staging").unwrap().status, CheckStatus::Fail);
+        assert!(report.get("key: prod").unwrap().fix.as_deref().unwrap().contains("environments.toml"));
+        let unfixed = report
+            .checks
+            .iter()
+            .filter(|c| matches!(c.status, CheckStatus::Warn | CheckStatus::Fail) && c.fix.is_none());
+        assert_eq!(unfixed.count(), 0);
+        assert_eq!(report.failures(), 3);
+
+        // A broken config is a failure of its own; the rest runs on defaults
+        std::fs::write(root.join("config.toml"), "[app\n").unwrap();
+        let probe = FakeProbe {
+            chain_id: None,
+            programs: vec!["wasm-opt"],
+            free: 64 * 1024 * 1024,
+        };
+        let report = Doctor::new(root.join("config.toml"), root.join("elsewhere"), &probe).run();
+        assert_eq!(report.get("config").unwrap().status, CheckStatus::Fail);
+        assert!(report.get("node").unwrap().detail.contains("connection refused"));
+        assert_eq!(report.get("wasm-opt").unwrap().status, CheckStatus::Ok);
+        assert_eq!(report.get("disk space").unwrap().status, CheckStatus::Fail);
+        assert_eq!(report.get("keys").unwrap().status, CheckStatus::Skip);
+    }
+}
@@ -0,0 +1,1014 @@
+//! Textual contract DSL
+//!
+//! A compact way to write contracts that still produces an ordinary visual
+//! graph, and to print a graph back as text:
+//!
+//! ```text
+//! contract Vault {
+//!   description "Holds deposits"
+//!
+//!   state balance: integer
+//!
+//!   fn deposit(amount: integer) payable {
+//!     let current = read balance
+//!     let updated = Add(current, amount)
+//!     write balance = updated
+//!     emit Deposited(amount)
+//!   }
+//! }
+//! ```
+//!
+//! `init` and each `fn` become an `Init` or `Start` node whose parameters are
+//! its output ports; `payable`, `view` and `pure` set its mutability and
+//! `returns <type>` its return type. Statements run in order along the flow:
+//!
+//! - `let x = read key` reads a declared state variable (`ReadStorage`)
+//! - `let x = Node(a, b)` adds a built-in data node, arguments going to its
+//!   data inputs in order (`_` leaves one unconnected)
+//! - `write key = expr` (`WriteStorage`)
+//! - `require expr else Error "message"` (`Require`)
+//! - `emit Event(a, b)` (`EmitEvent`)
+//!
+//! An expression is a parameter, a `let` variable (`x.port` picks an output
+//! other than the node's first) or a literal number, string or boolean;
+//! literals are stored as the property named after the input port. Graphs
+//! using nodes outside this subset (branches, loops, custom nodes) cannot be
+//! printed as DSL.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use uuid::Uuid;
+
+use crate::{
+    error::{CanvasError, CanvasResult},
+    nodes::{builtin_node_definitions, NodeDefinition},
+    types::{Connection, NodeId, Port, Position, ValueType, VisualGraph, VisualNode},
+};
+
+/// Graph metadata key prefix declaring a state variable and its type
+pub const STATE_METADATA_PREFIX: &str = "state.";
+/// Node metadata key holding the DSL variable name of a `let` node
+pub const VARIABLE_METADATA_KEY: &str = "dsl.var";
+
+const COLUMN_WIDTH: f64 = 180.0;
+const ROW_HEIGHT: f64 = 240.0;
+const DATA_ROW_OFFSET: f64 = 110.0;
+
+/// Name of a type as written in the DSL; the inverse of [`ValueType::from_name`]
+pub fn type_name(value_type: &ValueType) -> String {
+    match value_type {
+        ValueType::Boolean => "bool".to_string(),
+        ValueType::Integer => "integer".to_string(),
+        ValueType::Float => "float".to_string(),
+        ValueType::String => "string".to_string(),
+        ValueType::Bytes => "bytes".to_string(),
+        ValueType::Array(inner) => format!("array<{}>", type_name(inner)),
+        ValueType::Map(inner) => format!("map<{}>", type_name(inner)),
+        ValueType::Decimal(scale) => format!("decimal<{}>", scale),
+        ValueType::Flow => "flow".to_string(),
+        ValueType::Object(_) | ValueType::Any => "any".to_string(),
+    }
+}
+
+/// Parse DSL source into a visual graph
+pub fn parse(source: &str) -> CanvasResult<VisualGraph> {
+    let mut parser = Parser { tokens: tokenize(source)?, pos: 0 };
+    let contract = parser.contract()?;
+    build(&contract)
+}
+
+/// Print a graph as DSL source
+pub fn to_dsl(graph: &VisualGraph) -> CanvasResult<String> {
+    let definitions = definitions();
+    let mut out = format!("contract {} {{\n", quote_name(&graph.name));
+    if let Some(description) = graph.description.as_deref().filter(|d| !d.is_empty()) {
+        out.push_str(&format!("  description {}\n", quote(description)));
+    }
+
+    let mut state: BTreeMap<String, String> = graph
+        .metadata
+        .iter()
+        .filter_map(|(key, ty)| key.strip_prefix(STATE_METADATA_PREFIX).map(|name| (name.to_string(), ty.clone())))
+        .collect();
+    for node in graph.nodes.iter().filter(|n| n.node_type == "ReadStorage" || n.node_type == "WriteStorage") {
+        state.entry(storage_key(node)?).or_insert_with(|| "any".to_string());
+    }
+    if !state.is_empty() {
+        out.push('\n');
+        for (name, ty) in &state {
+            out.push_str(&format!("  state {}: {}\n", quote_name(name), ty));
+        }
+    }
+
+    let mut entries: Vec<&VisualNode> =
+        graph.nodes.iter().filter(|n| n.node_type == "Init" || n.node_type == "Start").collect();
+    entries.sort_by(|a, b| {
+        (a.node_type != "Init")
+            .cmp(&(b.node_type != "Init"))
+            .then(a.position.y.total_cmp(&b.position.y))
+            .then(a.position.x.total_cmp(&b.position.x))
+    });
+    for entry in entries {
+        out.push('\n');
+        let mut exporter = Exporter {
+            graph,
+            definitions: &definitions,
+            names: HashMap::new(),
+            used: HashSet::new(),
+            in_progress: HashSet::new(),
+            lines: Vec::new(),
+        };
+        out.push_str(&format!("  {} {{\n", exporter.header(entry)?));
+        exporter.body(entry)?;
+        for line in exporter.lines {
+            out.push_str(&format!("    {}\n", line));
+        }
+        out.push_str("  }\n");
+    }
+    out.push_str("}\n");
+    Ok(out)
+}
+
+fn definitions() -> HashMap<String, NodeDefinition> {
+    builtin_node_definitions().into_iter().map(|d| (d.id.clone(), d)).collect()
+}
+
+fn data_ports(ports: &[Port]) -> impl Iterator<Item = &Port> {
+    ports.iter().filter(|p| p.value_type != ValueType::Flow)
+}
+
+fn is_data_node(definition: &NodeDefinition) -> bool {
+    !definition.inputs.iter().chain(&definition.outputs).any(|p| p.value_type == ValueType::Flow)
+}
+
+fn is_identifier(text: &str) -> bool {
+    let mut chars = text.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn quote(text: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+fn quote_name(name: &str) -> String {
+    if is_identifier(name) {
+        name.to_string()
+    } else {
+        quote(name)
+    }
+}
+
+fn storage_key(node: &VisualNode) -> CanvasResult<String> {
+    node.properties
+        .get("key")
+        .and_then(|v| v.as_str())
+        .map(str::to_string)
+        .ok_or_else(|| CanvasError::Compilation(format!("{} node {} has no 'key' property", node.node_type, node.id)))
+}
+
+fn error_at(line: usize, message: impl std::fmt::Display) -> CanvasError {
+    CanvasError::Compilation(format!("DSL line {}: {}", line, message))
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Number(serde_json::Number),
+    Str(String),
+    Punct(char),
+    Newline,
+}
+
+fn tokenize(source: &str) -> CanvasResult<Vec<(Token, usize)>> {
+    let mut tokens = Vec::new();
+    for (index, text) in source.lines().enumerate() {
+        let line = index + 1;
+        let mut chars = text.chars().peekable();
+        while let Some(&c) = chars.peek() {
+            match c {
+                '#' => break,
+                c if c.is_whitespace() => {
+                    chars.next();
+                }
+                '"' => {
+                    chars.next();
+                    let mut value = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('"') => break,
+                            Some('\\') => match chars.next() {
+                                Some('n') => value.push('\n'),
+                                Some(escaped) => value.push(escaped),
+                                None => return Err(error_at(line, "unterminated string")),
+                            },
+                            Some(other) => value.push(other),
+                            None => return Err(error_at(line, "unterminated string")),
+                        }
+                    }
+                    tokens.push((Token::Str(value), line));
+                }
+                c if c.is_ascii_digit() || c == '-' => {
+                    let mut number = String::new();
+                    while let Some(&c) = chars.peek() {
+                        if !(c.is_ascii_digit() || matches!(c, '-' | '.' | 'e' | 'E')) {
+                            break;
+                        }
+                        number.push(c);
+                        chars.next();
+                    }
+                    let value: serde_json::Number = serde_json::from_str(&number)
+                        .map_err(|_| error_at(line, format!("invalid number '{}'", number)))?;
+                    tokens.push((Token::Number(value), line));
+                }
+                c if c.is_ascii_alphabetic() || c == '_' => {
+                    let mut word = String::new();
+                    while let Some(&c) = chars.peek() {
+                        if !(c.is_ascii_alphanumeric() || c == '_') {
+                            break;
+                        }
+                        word.push(c);
+                        chars.next();
+                    }
+                    tokens.push((Token::Ident(word), line));
+                }
+                '{' | '}' | '(' | ')' | ':' | ',' | '=' | '.' | '<' | '>' => {
+                    tokens.push((Token::Punct(c), line));
+                    chars.next();
+                }
+                other => return Err(error_at(line, format!("unexpected character '{}'", other))),
+            }
+        }
+        tokens.push((Token::Newline, line));
+    }
+    Ok(tokens)
+}
+
+fn describe(token: Option<&Token>) -> String {
+    match token {
+        Some(Token::Ident(name)) => format!("'{}'", name),
+        Some(Token::Number(n)) => n.to_string(),
+        Some(Token::Str(s)) => quote(s),
+        Some(Token::Punct(c)) => format!("'{}'", c),
+        Some(Token::Newline) => "end of line".to_string(),
+        None => "end of input".to_string(),
+    }
+}
+
+/// Value passed to a statement
+#[derive(Debug, Clone)]
+enum Expr {
+    Ref { name: String, port: Option<String> },
+    Literal(serde_json::Value),
+    Empty,
+}
+
+#[derive(Debug)]
+enum Stmt {
+    Read { var: String, key: String },
+    Node { var: String, node_type: String, args: Vec<Expr> },
+    Write { key: String, value: Expr },
+    Require { condition: Expr, error: String, message: Option<String> },
+    Emit { event: String, args: Vec<Expr> },
+}
+
+#[derive(Debug)]
+struct Entry {
+    /// Exported function name, `None` for the constructor
+    function: Option<String>,
+    mutability: Option<String>,
+    returns: Option<String>,
+    params: Vec<(String, String)>,
+    body: Vec<(Stmt, usize)>,
+    line: usize,
+}
+
+#[derive(Debug)]
+struct Contract {
+    name: String,
+    description: Option<String>,
+    state: Vec<(String, String, usize)>,
+    entries: Vec<Entry>,
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+}
+
+impl Parser {
+    fn line(&self) -> usize {
+        self.tokens.get(self.pos).or(self.tokens.last()).map_or(0, |(_, line)| *line)
+    }
+
+    fn error(&self, message: impl std::fmt::Display) -> CanvasError {
+        error_at(self.line(), message)
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(token, _)| token)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.peek().cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn unexpected<T>(&mut self, expected: &str) -> CanvasResult<T> {
+        let found = describe(self.peek());
+        Err(self.error(format!("expected {}, found {}", expected, found)))
+    }
+
+    fn skip_newlines(&mut self) {
+        while self.peek() == Some(&Token::Newline) {
+            self.pos += 1;
+        }
+    }
+
+    fn at_punct(&self, c: char) -> bool {
+        self.peek() == Some(&Token::Punct(c))
+    }
+
+    fn at_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Some(Token::Ident(word)) if word == keyword)
+    }
+
+    fn expect_punct(&mut self, c: char) -> CanvasResult<()> {
+        if self.at_punct(c) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            self.unexpected(&format!("'{}'", c))
+        }
+    }
+
+    fn expect_keyword(&mut self, keyword: &str) -> CanvasResult<()> {
+        if self.at_keyword(keyword) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            self.unexpected(&format!("'{}'", keyword))
+        }
+    }
+
+    fn ident(&mut self) -> CanvasResult<String> {
+        match self.peek() {
+            Some(Token::Ident(name)) => {
+                let name = name.clone();
+                self.pos += 1;
+                Ok(name)
+            }
+            _ => self.unexpected("a name"),
+        }
+    }
+
+    /// A name or a quoted string
+    fn name(&mut self) -> CanvasResult<String> {
+        match self.peek() {
+            Some(Token::Ident(name)) | Some(Token::Str(name)) => {
+                let name = name.clone();
+                self.pos += 1;
+                Ok(name)
+            }
+            _ => self.unexpected("a name"),
+        }
+    }
+
+    fn string(&mut self) -> CanvasResult<String> {
+        match self.peek() {
+            Some(Token::Str(text)) => {
+                let text = text.clone();
+                self.pos += 1;
+                Ok(text)
+            }
+            _ => self.unexpected("a string"),
+        }
+    }
+
+    fn end_of_statement(&mut self) -> CanvasResult<()> {
+        match self.peek() {
+            Some(Token::Newline) | None => {
+                self.skip_newlines();
+                Ok(())
+            }
+            Some(Token::Punct('}')) => Ok(()),
+            _ => self.unexpected("end of line"),
+        }
+    }
+
+    fn contract(&mut self) -> CanvasResult<Contract> {
+        self.skip_newlines();
+        self.expect_keyword("contract")?;
+        let name = self.name()?;
+        self.expect_punct('{')?;
+        self.skip_newlines();
+
+        let mut contract = Contract { name, description: None, state: Vec::new(), entries: Vec::new() };
+        while !self.at_punct('}') {
+            let line = self.line();
+            match self.peek() {
+                Some(Token::Ident(word)) if word == "description" => {
+                    self.pos += 1;
+                    contract.description = Some(self.string()?);
+                }
+                Some(Token::Ident(word)) if word == "state" => {
+                    self.pos += 1;
+                    let name = self.name()?;
+                    self.expect_punct(':')?;
+                    contract.state.push((name, self.type_name()?, line));
+                }
+                Some(Token::Ident(word)) if word == "init" => {
+                    self.pos += 1;
+                    let params = self.params()?;
+                    let body = self.block()?;
+                    contract.entries.push(Entry { function: None, mutability: None, returns: None, params, body, line });
+                }
+                Some(Token::Ident(word)) if word == "fn" => {
+                    self.pos += 1;
+                    let function = self.ident()?;
+                    let params = self.params()?;
+                    let (mut mutability, mut returns) = (None, None);
+                    loop {
+                        match self.peek() {
+                            Some(Token::Ident(word)) if matches!(word.as_str(), "payable" | "view" | "pure") => {
+                                mutability = Some(word.clone());
+                                self.pos += 1;
+                            }
+                            Some(Token::Ident(word)) if word == "returns" => {
+                                self.pos += 1;
+                                returns = Some(self.type_name()?);
+                            }
+                            _ => break,
+                        }
+                    }
+                    let body = self.block()?;
+                    contract.entries.push(Entry { function: Some(function), mutability, returns, params, body, line });
+                }
+                None => return self.unexpected("'}'"),
+                _ => return self.unexpected("'description', 'state', 'init' or 'fn'"),
+            }
+            self.end_of_statement()?;
+        }
+        self.pos += 1;
+        self.skip_newlines();
+        if self.peek().is_some() {
+            return self.unexpected("end of input");
+        }
+        Ok(contract)
+    }
+
+    fn type_name(&mut self) -> CanvasResult<String> {
+        let line = self.line();
+        let mut name = self.ident()?;
+        if self.at_punct('<') {
+            self.pos += 1;
+            let inner = match self.peek() {
+                Some(Token::Number(scale)) => {
+                    let scale = scale.to_string();
+                    self.pos += 1;
+                    scale
+                }
+                _ => self.type_name()?,
+            };
+            self.expect_punct('>')?;
+            name = format!("{}<{}>", name, inner);
+        }
+        match ValueType::from_name(&name) {
+            Some(value_type) => Ok(type_name(&value_type)),
+            None => Err(error_at(line, format!("unknown type '{}'", name))),
+        }
+    }
+
+    fn params(&mut self) -> CanvasResult<Vec<(String, String)>> {
+        self.expect_punct('(')?;
+        let mut params = Vec::new();
+        while !self.at_punct(')') {
+            let name = self.ident()?;
+            self.expect_punct(':')?;
+            params.push((name, self.type_name()?));
+            if !self.at_punct(')') {
+                self.expect_punct(',')?;
+            }
+        }
+        self.pos += 1;
+        Ok(params)
+    }
+
+    fn block(&mut self) -> CanvasResult<Vec<(Stmt, usize)>> {
+        self.expect_punct('{')?;
+        self.skip_newlines();
+        let mut body = Vec::new();
+        while !self.at_punct('}') {
+            let line = self.line();
+            body.push((self.statement()?, line));
+            self.end_of_statement()?;
+        }
+        self.pos += 1;
+        Ok(body)
+    }
+
+    fn statement(&mut self) -> CanvasResult<Stmt> {
+        match self.ident()?.as_str() {
+            "let" => {
+                let var = self.ident()?;
+                self.expect_punct('=')?;
+                if self.at_keyword("read") {
+                    self.pos += 1;
+                    Ok(Stmt::Read { var, key: self.name()? })
+                } else {
+                    let node_type = self.ident()?;
+                    Ok(Stmt::Node { var, node_type, args: self.args()? })
+                }
+            }
+            "write" => {
+                let key = self.name()?;
+                self.expect_punct('=')?;
+                Ok(Stmt::Write { key, value: self.expr()? })
+            }
+            "require" => {
+                let condition = self.expr()?;
+                self.expect_keyword("else")?;
+                let error = self.ident()?;
+                let message = match self.peek() {
+                    Some(Token::Str(_)) => Some(self.string()?),
+                    _ => None,
+                };
+                Ok(Stmt::Require { condition, error, message })
+            }
+            "emit" => {
+                let event = self.ident()?;
+                Ok(Stmt::Emit { event, args: self.args()? })
+            }
+            other => Err(self.error(format!("unknown statement '{}'", other))),
+        }
+    }
+
+    fn expr(&mut self) -> CanvasResult<Expr> {
+        match self.next() {
+            Some(Token::Number(n)) => Ok(Expr::Literal(serde_json::Value::Number(n))),
+            Some(Token::Str(s)) => Ok(Expr::Literal(serde_json::Value::String(s))),
+            Some(Token::Ident(word)) if word == "true" || word == "false" => {
+                Ok(Expr::Literal(serde_json::Value::Bool(word == "true")))
+            }
+            Some(Token::Ident(word)) if word == "_" => Ok(Expr::Empty),
+            Some(Token::Ident(name)) => {
+                let port = if self.at_punct('.') {
+                    self.pos += 1;
+                    Some(self.ident()?)
+                } else {
+                    None
+                };
+                Ok(Expr::Ref { name, port })
+            }
+            _ => {
+                self.pos -= 1;
+                self.unexpected("a value")
+            }
+        }
+    }
+
+    fn args(&mut self) -> CanvasResult<Vec<Expr>> {
+        self.expect_punct('(')?;
+        let mut args = Vec::new();
+        while !self.at_punct(')') {
+            args.push(self.expr()?);
+            if !self.at_punct(')') {
+                self.expect_punct(',')?;
+            }
+        }
+        self.pos += 1;
+        Ok(args)
+    }
+}
+
+fn build(contract: &Contract) -> CanvasResult<VisualGraph> {
+    let definitions = definitions();
+    let mut graph = VisualGraph::new(contract.name.clone());
+    graph.description = contract.description.clone();
+    for (name, ty, line) in &contract.state {
+        let key = format!("{}{}", STATE_METADATA_PREFIX, name);
+        if graph.metadata.insert(key, ty.clone()).is_some() {
+            return Err(error_at(*line, format!("state variable '{}' is declared twice", name)));
+        }
+    }
+    if contract.entries.iter().filter(|e| e.function.is_none()).count() > 1 {
+        return Err(CanvasError::Compilation("DSL declares more than one init".to_string()));
+    }
+
+    for (row, entry) in contract.entries.iter().enumerate() {
+        EntryBuilder::new(&mut graph, &definitions, row as f64 * ROW_HEIGHT).build(entry)?;
+    }
+    Ok(graph)
+}
+
+/// Adds the nodes of one `init` or `fn` to the graph
+struct EntryBuilder<'a> {
+    graph: &'a mut VisualGraph,
+    definitions: &'a HashMap<String, NodeDefinition>,
+    /// Names in scope: the node and the output port a bare reference means
+    scope: HashMap<String, (NodeId, String)>,
+    last: Option<NodeId>,
+    y: f64,
+    flow_column: f64,
+    data_column: f64,
+}
+
+impl<'a> EntryBuilder<'a> {
+    fn new(graph: &'a mut VisualGraph, definitions: &'a HashMap<String, NodeDefinition>, y: f64) -> Self {
+        Self { graph, definitions, scope: HashMap::new(), last: None, y, flow_column: 0.0, data_column: 0.0 }
+    }
+
+    fn build(mut self, entry: &Entry) -> CanvasResult<()> {
+        let node_type = if entry.function.is_some() { "Start" } else { "Init" };
+        let mut node = self.flow_node(node_type)?;
+        for (name, ty) in &entry.params {
+            let value_type = ValueType::from_name(ty).expect("parser validated the type");
+            node.outputs.push(Port::new(name.clone(), name.clone(), value_type));
+            if self.scope.insert(name.clone(), (node.id, name.clone())).is_some() {
+                return Err(error_at(entry.line, format!("parameter '{}' is declared twice", name)));
+            }
+        }
+        if let Some(function) = &entry.function {
+            node.properties.insert("function".to_string(), serde_json::json!(function));
+        }
+        if let Some(mutability) = &entry.mutability {
+            node.properties.insert("mutability".to_string(), serde_json::json!(mutability));
+        }
+        if let Some(returns) = &entry.returns {
+            node.properties.insert("returns".to_string(), serde_json::json!(returns));
+        }
+        self.add_flow(node, Vec::new());
+
+        for (stmt, line) in &entry.body {
+            self.statement(stmt, *line)?;
+        }
+        let end = self.flow_node("End")?;
+        self.add_flow(end, Vec::new());
+        Ok(())
+    }
+
+    fn node(&self, node_type: &str, x: f64, y: f64) -> CanvasResult<VisualNode> {
+        let definition = self
+            .definitions
+            .get(node_type)
+            .ok_or_else(|| CanvasError::NodeNotFound(node_type.to_string()))?;
+        Ok(VisualNode::new(Uuid::new_v4(), node_type, Position::new(x, y))
+            .with_inputs(definition.inputs.clone())
+            .with_outputs(definition.outputs.clone()))
+    }
+
+    fn flow_node(&mut self, node_type: &str) -> CanvasResult<VisualNode> {
+        let node = self.node(node_type, self.flow_column * COLUMN_WIDTH, self.y)?;
+        self.flow_column += 1.0;
+        Ok(node)
+    }
+
+    /// Add a node after the previous flow node
+    fn add_flow(&mut self, node: VisualNode, connections: Vec<Connection>) {
+        if let Some(last) = self.last {
+            self.graph.add_connection(Connection::new(Uuid::new_v4(), last, "flow_out", node.id, "flow_in"));
+        }
+        self.last = Some(node.id);
+        self.graph.add_node(node);
+        for connection in connections {
+            self.graph.add_connection(connection);
+        }
+    }
+
+    fn define(&mut self, var: &str, node: &mut VisualNode, port: String, line: usize) -> CanvasResult<()> {
+        if self.scope.insert(var.to_string(), (node.id, port)).is_some() {
+            return Err(error_at(line, format!("'{}' is already defined", var)));
+        }
+        node.metadata.insert(VARIABLE_METADATA_KEY.to_string(), var.to_string());
+        Ok(())
+    }
+
+    fn check_state(&self, key: &str, line: usize) -> CanvasResult<()> {
+        if self.graph.metadata.contains_key(&format!("{}{}", STATE_METADATA_PREFIX, key)) {
+            Ok(())
+        } else {
+            Err(error_at(line, format!("undeclared state variable '{}'", key)))
+        }
+    }
+
+    /// Wire `expr` into `port` of `node`, or store a literal as a property
+    fn bind(&self, node: &mut VisualNode, port: &str, expr: &Expr, line: usize) -> CanvasResult<Option<Connection>> {
+        match expr {
+            Expr::Literal(value) => {
+                node.properties.insert(port.to_string(), value.clone());
+                Ok(None)
+            }
+            Expr::Ref { name, port: source_port } => {
+                let (source, default_port) =
+                    self.scope.get(name).ok_or_else(|| error_at(line, format!("unknown name '{}'", name)))?;
+                let source_port = source_port.clone().unwrap_or_else(|| default_port.clone());
+                let source_node = self.graph.get_node(*source).expect("scoped nodes are in the graph");
+                if !source_node.outputs.iter().any(|p| p.id == source_port) {
+                    return Err(error_at(line, format!("'{}' has no output '{}'", name, source_port)));
+                }
+                Ok(Some(Connection::new(Uuid::new_v4(), *source, source_port, node.id, port)))
+            }
+            Expr::Empty => Ok(None),
+        }
+    }
+
+    fn statement(&mut self, stmt: &Stmt, line: usize) -> CanvasResult<()> {
+        match stmt {
+            Stmt::Read { var, key } => {
+                self.check_state(key, line)?;
+                let mut node = self.flow_node("ReadStorage")?;
+                node.properties.insert("key".to_string(), serde_json::json!(key));
+                self.define(var, &mut node, "value".to_string(), line)?;
+                self.add_flow(node, Vec::new());
+            }
+            Stmt::Node { var, node_type, args } => {
+                let definitions = self.definitions;
+                let definition = definitions
+                    .get(node_type)
+                    .ok_or_else(|| error_at(line, format!("unknown node type '{}'", node_type)))?;
+                if !is_data_node(definition) {
+                    return Err(error_at(line, format!("'{}' is not a data node", node_type)));
+                }
+                let ports: Vec<String> = data_ports(&definition.inputs).map(|p| p.id.clone()).collect();
+                if args.len() > ports.len() {
+                    return Err(error_at(
+                        line,
+                        format!("'{}' takes {} arguments, {} given", node_type, ports.len(), args.len()),
+                    ));
+                }
+                let output = data_ports(&definition.outputs)
+                    .next()
+                    .map(|p| p.id.clone())
+                    .ok_or_else(|| error_at(line, format!("'{}' has no output", node_type)))?;
+
+                let mut node =
+                    self.node(node_type, self.data_column * COLUMN_WIDTH, self.y + DATA_ROW_OFFSET)?;
+                self.data_column += 1.0;
+                let mut connections = Vec::new();
+                for (port, arg) in ports.iter().zip(args) {
+                    connections.extend(self.bind(&mut node, port, arg, line)?);
+                }
+                self.define(var, &mut node, output, line)?;
+                self.graph.add_node(node);
+                for connection in connections {
+                    self.graph.add_connection(connection);
+                }
+            }
+            Stmt::Write { key, value } => {
+                self.check_state(key, line)?;
+                let mut node = self.flow_node("WriteStorage")?;
+                node.properties.insert("key".to_string(), serde_json::json!(key));
+                let connection = self.bind(&mut node, "value", value, line)?;
+                self.add_flow(node, connection.into_iter().collect());
+            }
+            Stmt::Require { condition, error, message } => {
+                let mut node = self.flow_node("Require")?;
+                node.properties.insert("error".to_string(), serde_json::json!(error));
+                if let Some(message) = message {
+                    node.properties.insert("message".to_string(), serde_json::json!(message));
+                }
+                let connection = self.bind(&mut node, "condition", condition, line)?;
+                self.add_flow(node, connection.into_iter().collect());
+            }
+            Stmt::Emit { event, args } => {
+                let mut node = self.flow_node("EmitEvent")?;
+                node.properties.insert("event".to_string(), serde_json::json!(event));
+                let mut connections = Vec::new();
+                for (index, arg) in args.iter().enumerate() {
+                    let port = format!("arg{}", index);
+                    node.inputs.push(Port::new(port.clone(), port.clone(), ValueType::Any));
+                    connections.extend(self.bind(&mut node, &port, arg, line)?);
+                }
+                self.add_flow(node, connections);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Prints the body of one entry point
+struct Exporter<'a> {
+    graph: &'a VisualGraph,
+    definitions: &'a HashMap<String, NodeDefinition>,
+    names: HashMap<NodeId, String>,
+    used: HashSet<String>,
+    in_progress: HashSet<NodeId>,
+    lines: Vec<String>,
+}
+
+impl<'a> Exporter<'a> {
+    fn header(&mut self, entry: &VisualNode) -> CanvasResult<String> {
+        let mut params = Vec::new();
+        for port in data_ports(&entry.outputs) {
+            if !is_identifier(&port.id) {
+                return Err(CanvasError::Compilation(format!("parameter '{}' is not a valid DSL name", port.id)));
+            }
+            self.used.insert(port.id.clone());
+            params.push(format!("{}: {}", port.id, type_name(&port.value_type)));
+        }
+        let params = params.join(", ");
+        if entry.node_type == "Init" {
+            return Ok(format!("init({})", params));
+        }
+
+        let property = |key: &str| entry.properties.get(key).and_then(|v| v.as_str());
+        let mut header = format!("fn {}({})", property("function").unwrap_or("main"), params);
+        if let Some(mutability) = property("mutability").filter(|m| *m != "nonpayable") {
+            header.push_str(&format!(" {}", mutability));
+        }
+        if let Some(returns) = property("returns") {
+            header.push_str(&format!(" returns {}", returns));
+        }
+        Ok(header)
+    }
+
+    fn body(&mut self, entry: &VisualNode) -> CanvasResult<()> {
+        let graph = self.graph;
+        let mut visited = HashSet::from([entry.id]);
+        let mut current = entry.id;
+        while let Some(connection) =
+            graph.connections.iter().find(|c| c.source_node == current && c.source_port == "flow_out")
+        {
+            let node = graph
+                .get_node(connection.target_node)
+                .ok_or_else(|| CanvasError::NodeNotFound(connection.target_node.to_string()))?;
+            if !visited.insert(node.id) {
+                return Err(CanvasError::Compilation("flow loops cannot be expressed in the DSL".to_string()));
+            }
+            match node.node_type.as_str() {
+                "End" => break,
+                "ReadStorage" => {
+                    let key = quote_name(&storage_key(node)?);
+                    let name = self.fresh_name(node);
+                    self.lines.push(format!("let {} = read {}", name, key));
+                }
+                "WriteStorage" => {
+                    let key = quote_name(&storage_key(node)?);
+                    let value = self.value(node, "value")?;
+                    self.lines.push(format!("write {} = {}", key, value));
+                }
+                "Require" => {
+                    let condition = self.value(node, "condition")?;
+                    let error = node.properties.get("error").and_then(|v| v.as_str()).unwrap_or("Failed");
+                    let mut line = format!("require {} else {}", condition, error);
+                    if let Some(message) = node.properties.get("message").and_then(|v| v.as_str()) {
+                        line.push_str(&format!(" {}", quote(message)));
+                    }
+                    self.lines.push(line);
+                }
+                "EmitEvent" => {
+                    let event = node.properties.get("event").and_then(|v| v.as_str()).ok_or_else(|| {
+                        CanvasError::Compilation(format!("EmitEvent node {} has no 'event' property", node.id))
+                    })?;
+                    let args = data_ports(&node.inputs)
+                        .map(|p| self.value(node, &p.id))
+                        .collect::<CanvasResult<Vec<_>>>()?;
+                    self.lines.push(format!("emit {}({})", event, args.join(", ")));
+                }
+                other => {
+                    return Err(CanvasError::Compilation(format!("'{}' nodes cannot be expressed in the DSL", other)))
+                }
+            }
+            current = node.id;
+        }
+        Ok(())
+    }
+
+    /// Pick a variable name for a node, preferring the one it was parsed with
+    fn fresh_name(&mut self, node: &VisualNode) -> String {
+        let preferred = node.metadata.get(VARIABLE_METADATA_KEY).filter(|name| is_identifier(name));
+        let name = match preferred {
+            Some(name) if !self.used.contains(name) => name.clone(),
+            _ => {
+                let base = node.node_type.to_lowercase();
+                (1..).map(|i| format!("{}{}", base, i)).find(|n| !self.used.contains(n)).expect("unbounded range")
+            }
+        };
+        self.used.insert(name.clone());
+        self.names.insert(node.id, name.clone());
+        name
+    }
+
+    /// Expression feeding `port` of `node`, declaring data nodes on first use
+    fn value(&mut self, node: &VisualNode, port: &str) -> CanvasResult<String> {
+        let graph = self.graph;
+        let Some(connection) = graph.connections.iter().find(|c| c.target_node == node.id && c.target_port == port)
+        else {
+            return match node.properties.get(port) {
+                Some(value) => literal(value),
+                None => Ok("_".to_string()),
+            };
+        };
+        let source = graph
+            .get_node(connection.source_node)
+            .ok_or_else(|| CanvasError::NodeNotFound(connection.source_node.to_string()))?;
+        if source.node_type == "Init" || source.node_type == "Start" {
+            return Ok(connection.source_port.clone());
+        }
+
+        let definitions = self.definitions;
+        let name = match self.names.get(&source.id) {
+            Some(name) => name.clone(),
+            None => match definitions.get(&source.node_type) {
+                Some(definition) if is_data_node(definition) => self.declare(source, definition)?,
+                _ => {
+                    return Err(CanvasError::Compilation(format!(
+                        "value of {} node {} is used before it runs",
+                        source.node_type, source.id
+                    )))
+                }
+            },
+        };
+        match data_ports(&source.outputs).next() {
+            Some(primary) if primary.id == connection.source_port => Ok(name),
+            _ => Ok(format!("{}.{}", name, connection.source_port)),
+        }
+    }
+
+    fn declare(&mut self, node: &VisualNode, definition: &NodeDefinition) -> CanvasResult<String> {
+        if !self.in_progress.insert(node.id) {
+            return Err(CanvasError::Compilation(format!("data cycle through {} node {}", node.node_type, node.id)));
+        }
+        let mut args = data_ports(&definition.inputs)
+            .map(|p| self.value(node, &p.id))
+            .collect::<CanvasResult<Vec<_>>>()?;
+        while args.last().is_some_and(|arg| arg == "_") {
+            args.pop();
+        }
+        let name = self.fresh_name(node);
+        self.lines.push(format!("let {} = {}({})", name, node.node_type, args.join(", ")));
+        Ok(name)
+    }
+}
+
+fn literal(value: &serde_json::Value) -> CanvasResult<String> {
+    match value {
+        serde_json::Value::String(text) => Ok(quote(text)),
+        serde_json::Value::Number(number) => Ok(number.to_string()),
+        serde_json::Value::Bool(flag) => Ok(flag.to_string()),
+        other => Err(CanvasError::Compilation(format!("literal {} cannot be expressed in the DSL", other))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VAULT: &str = r#"contract Vault {
+  description "Holds deposits"
+
+  state balance: integer
+  state owner: string
+
+  init(admin: string) {
+    write owner = admin
+    write balance = 0
+  }
+
+  fn deposit(amount: integer) payable {
+    let zero = Compare(amount, 0)
+    let nonzero = Not(zero)
+    require nonzero else InvalidAmount "amount must not be zero"
+    let current = read balance
+    let updated = Add(current, amount)
+    write balance = updated
+    emit Deposited(amount, updated)
+  }
+
+  fn total() view returns integer {
+    let current = read balance
+  }
+}
+"#;
+
+    #[test]
+    fn test_parse_and_round_trip() {
+        let graph = parse(VAULT).unwrap();
+        assert_eq!(graph.name, "Vault");
+        assert_eq!(graph.metadata.get("state.balance").map(String::as_str), Some("integer"));
+        assert_eq!(graph.nodes.iter().filter(|n| n.node_type == "Start").count(), 2);
+        assert_eq!(graph.nodes.iter().filter(|n| n.node_type == "End").count(), 3);
+        let deposit = graph
+            .nodes
+            .iter()
+            .find(|n| n.properties.get("function") == Some(&serde_json::json!("deposit")))
+            .unwrap();
+        assert!(deposit.outputs.iter().any(|p| p.id == "amount" && p.value_type == ValueType::Integer));
+
+        assert_eq!(to_dsl(&graph).unwrap(), VAULT);
+
+        let error = parse("contract C {\n  fn f() {\n    write missing = 1\n  }\n}\n").unwrap_err();
+        assert!(error.to_string().contains("DSL line 3"));
+    }
+}
@@ -65,6 +65,9 @@ pub enum CanvasError {
     #[error("Execution error: {0}")]
     ExecutionError(String),
 
+    #[error("Execution reverted: {0}")]
+    Reverted(crate::types::RevertReason),
+
     #[error("Unknown error: {0}")]
     Unknown(String),
 }
@@ -105,6 +108,19 @@ impl CanvasError {
         Self::Type(msg.into())
     }
 
+    /// Create a revert error
+    pub fn revert(error: impl Into<String>, message: impl Into<String>) -> Self {
+        Self::Reverted(crate::types::RevertReason::new(error, message))
+    }
+
+    /// The structured revert reason, if this error is a revert
+    pub fn revert_reason(&self) -> Option<&crate::types::RevertReason> {
+        match self {
+            Self::Reverted(reason) => Some(reason),
+            _ => None,
+        }
+    }
+
     /// Check if this is a fatal error
     pub fn is_fatal(&self) -> bool {
         matches!(
@@ -205,6 +221,7 @@ impl<T> ErrorContextExt for Result<T, CanvasError> {
                 CanvasError::Unknown(msg) => {
                     CanvasError::Unknown(format!("{}: {}", context.operation, msg))
                 }
+                CanvasError::Reverted(reason) => CanvasError::Reverted(reason),
                 CanvasError::Io(e) => CanvasError::Io(e),
                 CanvasError::Serialization(e) => CanvasError::Serialization(e),
             },
@@ -236,6 +253,13 @@ mod tests {
         assert_eq!(context.location, Some("test location".to_string()));
     }
 
+    #[test]
+    fn test_revert_error() {
+        let error = CanvasError::revert("Unauthorized", "caller is not the owner");
+        assert_eq!(error.revert_reason().unwrap().error, "Unauthorized");
+        assert!(CanvasError::node("plain").revert_reason().is_none());
+    }
+
     #[test]
     fn test_fatal_error_detection() {
         let fatal_error = CanvasError::compilation("fatal");
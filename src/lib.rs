@@ -19,6 +19,20 @@ pub mod monitoring;
 pub mod optimization;
 pub mod types;
 pub mod config;
+pub mod testing;
+pub mod backup;
+pub mod bundle;
+pub mod annotations;
+pub mod telemetry;
+pub mod update;
+pub mod wizard;
+pub mod dsl;
+pub mod output;
+pub mod doctor;
+pub mod progress;
+pub mod permissions;
+#[cfg(test)]
+pub(crate) mod test_graphs;
 
 pub use error::{CanvasError, CanvasResult};
 pub use types::*;
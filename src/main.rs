@@ -1,13 +1,16 @@
 //! Canvas Contracts - Main Application Entry Point
 
-use clap::{Parser, Subcommand};
-use log::{error, info};
+use clap::{CommandFactory, Parser, Subcommand};
+use log::{error, info, warn};
 
 use canvas_contracts::{
+    baals::signer::signer_from_spec,
     compiler::Compiler,
     config::ConfigManager,
     error::{CanvasError, CanvasResult},
     init, info as lib_info,
+    monitoring::budgets::GasBudgets,
+    output::{command_exit_code, Output, OutputFormat, Table},
 };
 
 #[derive(Parser)]
@@ -29,9 +32,284 @@ struct Cli {
     /// Log level
     #[arg(short, long, default_value = "info")]
     log_level: String,
+
+    /// Print results as JSON on stdout (logs stay on stderr)
+    #[arg(long, global = true)]
+    json: bool,
+
+    /// Hide progress bars (they are also hidden off a terminal and when CI is set)
+    #[arg(short, long, global = true)]
+    quiet: bool,
+}
+
+#[derive(Debug, Subcommand)]
+enum MarketplaceAction {
+    /// Search the marketplace, or the cached index when offline
+    Search {
+        #[arg(default_value = "")]
+        query: String,
+
+        /// Only free items
+        #[arg(long)]
+        free: bool,
+    },
+    /// Fetch the marketplace index into the local cache
+    Refresh,
+    /// List configured registries in the order they are consulted
+    Registries,
+    /// List publishes and reviews waiting to be synced
+    Queue,
+    /// Replay queued publishes and reviews
+    Sync {
+        /// What to do with conflicting actions: defer, keep-local or keep-remote
+        #[arg(long, default_value = "defer")]
+        on_conflict: String,
+    },
+    /// Drop a queued action
+    Discard { id: uuid::Uuid },
+    /// Remove an item from the cached index, with any actions queued on it
+    Remove {
+        id: String,
+
+        /// Skip the confirmation prompt
+        #[arg(long)]
+        force: bool,
+    },
+    /// Download a custom node, check its publisher signature against the
+    /// trusted author keys and its tests in a sandbox, then install it
+    Install {
+        id: String,
+
+        /// Install even if the item is unsigned or signed by an untrusted key
+        #[arg(long)]
+        allow_unverified: bool,
+
+        /// Grant the capabilities the node requests without prompting
+        #[arg(long)]
+        force: bool,
+    },
+    /// List installed custom nodes with newer marketplace versions, with
+    /// their changelogs when online
+    Updates,
+    /// List trending items, from the marketplace when online and from the
+    /// local usage statistics otherwise
+    Trending {
+        #[arg(long, default_value_t = 10)]
+        limit: usize,
+    },
+    /// List the cached items used most over recent days, by the local usage statistics
+    Top {
+        /// Ranking: downloads, installs or rating
+        #[arg(long, default_value = "downloads")]
+        by: String,
+
+        /// Days of activity counted
+        #[arg(long, default_value_t = 30)]
+        days: i64,
+
+        #[arg(long, default_value_t = 10)]
+        limit: usize,
+    },
+    /// List the most recently published cached items
+    New {
+        #[arg(long, default_value_t = 10)]
+        limit: usize,
+    },
+    /// Record a download, install or rating of a cached item in the local usage statistics
+    Record {
+        id: String,
+
+        /// download, install or rating
+        event: String,
+
+        /// Stars of a rating, 1 to 5
+        #[arg(long)]
+        stars: Option<u8>,
+    },
+    /// Sign an item bundle as its publisher, updating its listing file
+    Sign {
+        /// Listing (item JSON) to update with the bundle's hash and signature
+        listing: String,
+
+        /// Bundle file, exactly as it will be downloaded
+        bundle: String,
+
+        /// Key file, or `ledger` / `ledger:<derivation path>`
+        #[arg(short, long)]
+        key: String,
+    },
+    /// Trust a public key to sign items of an author
+    Trust {
+        author: String,
+
+        /// 0x-hex ed25519 public key
+        key: String,
+    },
+    /// Stop trusting a public key of an author
+    Untrust { author: String, key: String },
+    /// List trusted author keys
+    Keys,
+}
+
+#[derive(Debug, Subcommand)]
+enum TelemetryAction {
+    /// Print the buffered events exactly as they would be uploaded
+    Show,
+    /// Start recording anonymous usage metrics
+    Enable,
+    /// Stop recording and delete buffered events
+    Disable,
+    /// Send buffered events to the telemetry endpoint now
+    Upload,
+}
+
+#[derive(Debug, Subcommand)]
+enum TxAction {
+    /// Build and sign a contract call offline, writing it as JSON
+    Sign {
+        /// Contract address
+        #[arg(long)]
+        to: String,
+
+        /// Function to call
+        #[arg(short, long)]
+        function: String,
+
+        /// Call arguments (JSON array)
+        #[arg(short, long, default_value = "[]")]
+        args: String,
+
+        /// Native tokens sent with the call, in base units
+        #[arg(long, default_value = "0")]
+        value: String,
+
+        /// Gas limit
+        #[arg(long)]
+        gas_limit: Option<u64>,
+
+        /// Network the transaction is for (defaults to the configured network)
+        #[arg(long)]
+        chain: Option<String>,
+
+        /// Relayer that will submit the transaction; its nonce domain is used
+        #[arg(short, long)]
+        relayer: Option<String>,
+
+        /// Seconds from now after which the transaction must not be submitted
+        #[arg(long)]
+        valid_for: Option<u64>,
+
+        /// Signing key file (0x-hex ed25519 seed), `ledger` or `ledger:<derivation path>`
+        #[arg(short, long)]
+        key: String,
+
+        /// Output file for the signed transaction
+        #[arg(short, long)]
+        output: String,
+    },
+    /// Submit a signed transaction through its relayer and verify it was included
+    Relay {
+        /// Signed transaction file
+        file: String,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum ImpersonateAction {
+    /// Act as an account without its key (local networks only); sign as it
+    /// with `--key impersonate:<address>`
+    Start {
+        /// Account address
+        address: String,
+    },
+    /// Stop acting as an account, or as every account
+    Stop {
+        /// Account address (all accounts when omitted)
+        address: Option<String>,
+    },
+    /// List impersonated accounts
+    List,
+}
+
+#[derive(Debug, Subcommand)]
+enum AssetsAction {
+    /// Add an icon image (PNG, JPEG, WebP or SVG) to the asset store
+    Add {
+        /// Image file
+        file: String,
+    },
+    /// Serve node icons and appearances to the editor over HTTP
+    Serve {
+        /// Host to bind
+        #[arg(long, default_value = "127.0.0.1")]
+        host: String,
+
+        /// Port to bind
+        #[arg(short, long, default_value = "8547")]
+        port: u16,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum WebhooksAction {
+    /// POST an event of a deployed contract to a URL whenever it fires
+    Add {
+        /// Rule ID
+        id: String,
+
+        /// Contract address
+        #[arg(short, long)]
+        contract: String,
+
+        /// Event name
+        #[arg(short, long)]
+        event: String,
+
+        /// URL the decoded event is POSTed to
+        #[arg(short, long)]
+        url: String,
+
+        /// Field filter such as `amount>=1000` or `to==0xabc`; repeatable
+        #[arg(long = "filter")]
+        filters: Vec<String>,
+
+        /// First block to deliver events from (defaults to the node's next block)
+        #[arg(long)]
+        from_block: Option<u64>,
+    },
+    /// Remove a rule and its queued deliveries
+    Remove {
+        /// Rule ID
+        id: String,
+    },
+    /// List rules and queued deliveries
+    List,
+    /// Deliver a rule's events again from a block on
+    Replay {
+        /// Rule ID
+        id: String,
+
+        /// First block to deliver again
+        #[arg(long)]
+        from_block: u64,
+    },
+    /// Poll the node and deliver events; catches up on events missed while stopped
+    Run {
+        /// Seconds between polls
+        #[arg(long, default_value = "15")]
+        interval: u64,
+
+        /// Poll once and exit
+        #[arg(long)]
+        once: bool,
+
+        /// Queue deliveries that ran out of attempts again first
+        #[arg(long)]
+        retry_failed: bool,
+    },
 }
 
-#[derive(Subcommand)]
+#[derive(Debug, Subcommand)]
 enum Commands {
     /// Compile a visual contract to WASM
     Compile {
@@ -58,9 +336,25 @@ enum Commands {
         #[arg(short, long)]
         input: Option<String>,
 
+        /// Exported function to call (defaults to the whole-contract simulation)
+        #[arg(short, long)]
+        function: Option<String>,
+
         /// Gas limit
         #[arg(short, long, default_value = "1000000")]
         gas_limit: u64,
+
+        /// Call on behalf of this account (0x-hex address)
+        #[arg(long)]
+        caller: Option<String>,
+
+        /// Append the call and its outcome to this scenario file, creating it if needed
+        #[arg(long, requires = "function")]
+        record: Option<String>,
+
+        /// Run against persistent contract storage: memory, sled:<path> or baals:<address>
+        #[arg(long, requires = "function")]
+        storage: Option<String>,
     },
 
     /// Deploy a contract to BaaLS
@@ -69,11 +363,101 @@ enum Commands {
         #[arg(short, long)]
         contract: String,
 
-        /// Constructor arguments (JSON)
+        /// Constructor arguments (JSON array, or object keyed by parameter name)
+        #[arg(short, long)]
+        args: Option<String>,
+
+        /// Contract ABI file (defaults to the .abi.json next to the contract)
+        #[arg(long)]
+        abi: Option<String>,
+
+        /// Private key file, `ledger` or `ledger:<derivation path>` (defaults to the environment's key)
+        #[arg(short, long, required_unless_present = "env")]
+        key: Option<String>,
+
+        /// Deploy to this workspace environment and record the release
+        #[arg(short, long)]
+        env: Option<String>,
+    },
+
+    /// Deploy one contract to several environments' networks and verify it on each
+    DeployMulti {
+        /// Contract WASM file
+        #[arg(short, long)]
+        contract: String,
+
+        /// Constructor arguments (JSON array, or object keyed by parameter name)
         #[arg(short, long)]
         args: Option<String>,
 
-        /// Private key file
+        /// Contract ABI file (defaults to the .abi.json next to the contract)
+        #[arg(long)]
+        abi: Option<String>,
+
+        /// Environments to deploy to, in order
+        #[arg(short, long = "env", required = true, value_delimiter = ',')]
+        envs: Vec<String>,
+
+        /// Report format: markdown or json
+        #[arg(long, default_value = "markdown")]
+        format: String,
+
+        /// Write the report to a file instead of stdout
+        #[arg(short, long)]
+        output: Option<String>,
+    },
+
+    /// Mark the latest release of an environment verified, allowing its promotion
+    VerifyRelease {
+        /// Environment name
+        #[arg(short, long)]
+        env: String,
+    },
+
+    /// Deploy the latest verified release of an environment to the next one
+    Promote {
+        /// Environment to promote from
+        #[arg(short, long)]
+        from: String,
+
+        /// Target environment (defaults to the next one in environments.toml)
+        #[arg(short, long)]
+        to: Option<String>,
+
+        /// Promote even if the ABI breaks compatibility with the target's current release
+        #[arg(long)]
+        allow_breaking: bool,
+    },
+
+    /// Attest that the latest release of an environment is final; it can no longer be promoted over
+    Freeze {
+        /// Environment name
+        #[arg(short, long)]
+        env: String,
+
+        /// Why the contract is being frozen, recorded in the attestation
+        #[arg(short, long)]
+        reason: Option<String>,
+    },
+
+    /// Pause a deployed pausable contract
+    Pause {
+        /// Contract address
+        #[arg(short, long)]
+        address: String,
+
+        /// Private key file of the contract admin, or `ledger`
+        #[arg(short, long)]
+        key: String,
+    },
+
+    /// Resume a paused contract
+    Unpause {
+        /// Contract address
+        #[arg(short, long)]
+        address: String,
+
+        /// Private key file of the contract admin, or `ledger`
         #[arg(short, long)]
         key: String,
     },
@@ -98,198 +482,2302 @@ enum Commands {
         #[arg(short, long)]
         input: String,
     },
-}
 
-fn main() -> CanvasResult<()> {
-    let cli = Cli::parse();
+    /// Archive configuration, data directory and workspace records
+    Backup {
+        /// Archive file to write
+        #[arg(short, long)]
+        output: String,
 
-    // Initialize the library
-    init()?;
+        /// Workspace root whose environments and releases are included
+        #[arg(short, long, default_value = ".")]
+        workspace: String,
+    },
 
-    // Set up logging
-    let log_level = if cli.debug {
-        "debug"
-    } else {
-        &cli.log_level
-    };
-    std::env::set_var("RUST_LOG", log_level);
-    env_logger::init();
+    /// Restore a backup archive
+    Restore {
+        /// Archive file
+        #[arg(short, long)]
+        input: String,
 
-    info!("Starting Canvas Contracts v{}", env!("CARGO_PKG_VERSION"));
+        /// Workspace root to restore environments and releases into
+        #[arg(short, long, default_value = ".")]
+        workspace: String,
 
-    // Load configuration
-    let config_path = std::path::PathBuf::from(&cli.config);
-    let mut config_manager = ConfigManager::new(config_path)?;
+        /// Only check the archive's integrity
+        #[arg(long)]
+        verify_only: bool,
+    },
 
-    match &cli.command {
-        Some(Commands::Compile { input, output, optimize }) => {
-            compile_contract(input, output, *optimize, &config_manager)?
-        }
+    /// Pack a project's graphs, custom nodes, templates and ABIs into a .canvasbundle
+    ExportBundle {
+        /// Project directory
+        #[arg(default_value = ".")]
+        dir: String,
 
-        Some(Commands::Simulate { contract, input, gas_limit }) => {
-            simulate_contract(contract, input.as_deref(), *gas_limit, &config_manager)?
-        }
+        /// Bundle file to write (defaults to <name>.canvasbundle)
+        #[arg(short, long)]
+        output: Option<String>,
 
-        Some(Commands::Deploy { contract, args, key }) => {
-            deploy_contract(contract, args.as_deref(), key, &config_manager)?
-        }
+        /// Project name (defaults to the directory name)
+        #[arg(short, long)]
+        name: Option<String>,
 
-        Some(Commands::Editor { port, host }) => {
-            start_editor(*port, host, &config_manager)?
-        }
+        /// Strip labels, comments and annotations and replace node IDs, for
+        /// templates sold without their full logic
+        #[arg(long)]
+        obfuscate: bool,
 
-        Some(Commands::Info) => {
-            show_info()?
-        }
+        /// Ship this composite custom node as compiled WASM only (repeatable)
+        #[arg(long = "compile-node", value_name = "NODE", requires = "obfuscate")]
+        compile_nodes: Vec<String>,
+    },
 
-        Some(Commands::Validate { input }) => {
-            validate_graph(input, &config_manager)?
-        }
+    /// Unpack a .canvasbundle into a project directory
+    ImportBundle {
+        /// Bundle file
+        input: String,
 
-        None => {
-            // Default: start the visual editor
-            start_editor(3000, "localhost", &config_manager)?
-        }
-    }
+        /// Project directory to unpack into
+        #[arg(short, long, default_value = ".")]
+        dir: String,
 
-    Ok(())
-}
+        /// Do not install the bundled custom nodes
+        #[arg(long)]
+        skip_nodes: bool,
+    },
 
-fn compile_contract(
-    input: &str,
-    output: &str,
-    optimize: bool,
-    config_manager: &ConfigManager,
-) -> CanvasResult<()> {
-    info!("Compiling contract from {} to {}", input, output);
+    /// Register the event schema of a contract deployed outside this tool
+    ImportEvents {
+        /// Contract address
+        #[arg(short, long)]
+        contract: String,
 
-    // Load the visual graph
-    let graph_content = std::fs::read_to_string(input)
-        .map_err(|e| CanvasError::Io(e))?;
+        /// Contract ABI file
+        #[arg(short, long)]
+        abi: String,
 
-    let graph: canvas_contracts::types::VisualGraph = serde_json::from_str(&graph_content)
-        .map_err(|e| CanvasError::Serialization(e))?;
+        /// First block the events were emitted with this schema
+        #[arg(long, default_value = "0")]
+        from_block: u64,
+    },
 
-    // Create compiler
-    let compiler = Compiler::new(config_manager.config())?;
+    /// Compare two contract ABIs and report breaking changes
+    AbiDiff {
+        /// ABI of the previous version
+        old: String,
 
-    // Compile the graph
-    let result = compiler.compile(&graph)?;
+        /// ABI of the new version
+        new: String,
 
-    // Write WASM output
-    std::fs::write(output, &result.wasm_bytes)
-        .map_err(|e| CanvasError::Io(e))?;
+        /// Exit successfully even if there are breaking changes
+        #[arg(long)]
+        allow_breaking: bool,
+    },
 
-    // Write ABI
-    let abi_path = output.replace(".wasm", ".abi.json");
-    let abi_content = serde_json::to_string_pretty(&result.abi)
-        .map_err(|e| CanvasError::Serialization(e))?;
-    std::fs::write(&abi_path, abi_content)
-        .map_err(|e| CanvasError::Io(e))?;
+    /// Check installed custom nodes against the marketplace advisory feed
+    Audit {
+        /// Workspace whose graphs are checked for affected nodes
+        #[arg(default_value = ".")]
+        dir: String,
 
-    info!("Compilation successful!");
-    info!("WASM file: {}", output);
-    info!("ABI file: {}", abi_path);
-    info!("Gas estimate: {}", result.gas_estimate);
+        /// Advisory feed file (defaults to the cached feed in the data directory)
+        #[arg(long)]
+        feed: Option<String>,
 
-    if !result.warnings.is_empty() {
-        info!("Warnings:");
-        for warning in &result.warnings {
-            info!("  - {}", warning);
-        }
-    }
+        /// Installed custom nodes directory (defaults to the data directory's)
+        #[arg(long)]
+        nodes: Option<String>,
+    },
 
-    Ok(())
-}
+    /// List the licenses of the custom nodes a workspace uses and check them
+    /// against its license policy
+    Licenses {
+        /// Workspace whose graphs and license policy are checked
+        #[arg(default_value = ".")]
+        dir: String,
 
-fn simulate_contract(
-    contract: &str,
-    input: Option<&str>,
-    gas_limit: u64,
-    config_manager: &ConfigManager,
-) -> CanvasResult<()> {
-    info!("Simulating contract: {}", contract);
+        /// Installed custom nodes directory (defaults to the data directory's)
+        #[arg(long)]
+        nodes: Option<String>,
+    },
 
-    // Load WASM bytes
-    let wasm_bytes = std::fs::read(contract)
-        .map_err(|e| CanvasError::Io(e))?;
+    /// Compile a workspace's health digest: deployments, gas trends, alerts
+    /// and marketplace updates
+    Digest {
+        /// Workspace to report on
+        #[arg(default_value = ".")]
+        dir: String,
 
-    // Load input data if provided
-    let input_data = if let Some(input_file) = input {
-        let content = std::fs::read_to_string(input_file)
-            .map_err(|e| CanvasError::Io(e))?;
-        serde_json::from_str(&content)
-            .map_err(|e| CanvasError::Serialization(e))?
-    } else {
-        serde_json::Value::Null
-    };
+        /// Rendering: markdown or html (defaults to the workspace's digest settings)
+        #[arg(long)]
+        format: Option<String>,
 
-    // Create runtime
-    let runtime = canvas_contracts::wasm::WasmRuntime::new(config_manager.config())?;
+        /// Digest file (defaults to stdout)
+        #[arg(short, long)]
+        output: Option<String>,
 
-    // Simulate execution
-    let result = runtime.simulate(&wasm_bytes, input_data, gas_limit)?;
+        /// Send the digest to the workspace's notification channels when one is due
+        #[arg(long)]
+        send: bool,
 
-    info!("Simulation completed!");
-    info!("Gas used: {}", result.gas_used);
-    info!("Output: {}", serde_json::to_string_pretty(&result.output)?);
+        /// Send even if the last digest is more recent than the configured period
+        #[arg(long, requires = "send")]
+        force: bool,
 
-    if !result.events.is_empty() {
-        info!("Events emitted:");
-        for event in &result.events {
-            info!("  - {}: {}", event.name, serde_json::to_string_pretty(&event.data)?);
-        }
-    }
+        /// Keep running and send each digest as it falls due
+        #[arg(long, conflicts_with = "force")]
+        watch: bool,
+    },
 
-    Ok(())
-}
+    /// Show each team's gas usage this period, per member, against its budget
+    Budgets {
+        /// Workspace whose budgets are reported
+        #[arg(default_value = ".")]
+        dir: String,
+    },
 
-fn deploy_contract(
-    contract: &str,
-    args: Option<&str>,
-    key: &str,
-    config_manager: &ConfigManager,
-) -> CanvasResult<()> {
-    info!("Deploying contract: {}", contract);
+    /// Update the CLI and the node catalog from the signed release feed
+    SelfUpdate {
+        /// Only update this component: cli or catalog
+        #[arg(long)]
+        component: Option<String>,
 
-    // Load WASM bytes
-    let wasm_bytes = std::fs::read(contract)
-        .map_err(|e| CanvasError::Io(e))?;
+        /// Release channel for this run: stable or beta (defaults to update.channel)
+        #[arg(long)]
+        channel: Option<String>,
 
-    // Load private key
-    let key_content = std::fs::read_to_string(key)
-        .map_err(|e| CanvasError::Io(e))?;
-    let private_key = key_content.trim();
+        /// Only report available updates
+        #[arg(long)]
+        check: bool,
 
-    // Parse constructor arguments
-    let constructor_args = if let Some(args_str) = args {
-        serde_json::from_str(args_str)
-            .map_err(|e| CanvasError::Serialization(e))?
-    } else {
-        serde_json::Value::Null
-    };
+        /// Restore the versions replaced by the last update
+        #[arg(long)]
+        rollback: bool,
 
-    // Create BaaLS client
-    let baals_client = canvas_contracts::baals::BaalsClient::new(config_manager.config())?;
+        /// Trust this hex ed25519 key to sign the release feed, saving it in update.trusted_keys
+        #[arg(long, value_name = "KEY")]
+        trust_key: Option<String>,
+    },
 
-    // Deploy contract
-    let deployment_result = baals_client.deploy_contract(
-        &wasm_bytes,
-        constructor_args,
-        private_key,
-    )?;
+    /// Serve a compiled contract over HTTP/JSON, without a chain
+    Serve {
+        /// Contract WASM file
+        #[arg(short, long)]
+        contract: String,
 
-    info!("Deployment successful!");
-    info!("Contract address: {}", deployment_result.contract_address);
-    info!("Transaction hash: {}", deployment_result.transaction_hash);
-    info!("Gas used: {}", deployment_result.gas_used);
+        /// Contract ABI file; calls to functions it doesn't declare are rejected
+        #[arg(long)]
+        abi: Option<String>,
 
-    Ok(())
-}
+        /// Storage backend: memory, sled:<path> or baals:<address>
+        #[arg(long, default_value = "memory")]
+        storage: String,
 
-fn start_editor(
-    port: u16,
-    host: &str,
+        /// Host to bind
+        #[arg(long, default_value = "127.0.0.1")]
+        host: String,
+
+        /// Port to listen on
+        #[arg(short, long, default_value = "8545")]
+        port: u16,
+    },
+
+    /// Generate a standalone HTML page for calling a contract from a browser
+    Playground {
+        /// Contract ABI file
+        #[arg(short, long)]
+        abi: String,
+
+        /// HTML file to write (defaults to the ABI file with a .playground.html extension)
+        #[arg(short, long)]
+        output: Option<String>,
+
+        /// Endpoint serving the contract, e.g. `canvas serve`
+        #[arg(long, default_value = canvas_contracts::compiler::DEFAULT_PLAYGROUND_ENDPOINT)]
+        endpoint: String,
+
+        /// Address of the deployed contract
+        #[arg(long)]
+        contract: Option<String>,
+
+        /// Page title (defaults to the ABI file name)
+        #[arg(long)]
+        title: Option<String>,
+    },
+
+    /// Print the WIT world describing a contract's ABI
+    Wit {
+        /// Input graph file
+        #[arg(short, long)]
+        input: String,
+
+        /// Output WIT file (defaults to stdout)
+        #[arg(short, long)]
+        output: Option<String>,
+    },
+
+    /// Print the typed ABI of a graph: function signatures, events, errors and storage
+    Abi {
+        /// Input graph file
+        #[arg(short, long)]
+        input: String,
+
+        /// Output ABI file (defaults to stdout)
+        #[arg(short, long)]
+        output: Option<String>,
+    },
+
+    /// Generate a visual graph from a contract written in the text DSL
+    FromDsl {
+        /// Input DSL file
+        #[arg(short, long)]
+        input: String,
+
+        /// Output graph file
+        #[arg(short, long)]
+        output: String,
+    },
+
+    /// Print a visual graph as text DSL
+    ToDsl {
+        /// Input graph file
+        #[arg(short, long)]
+        input: String,
+
+        /// Output DSL file (defaults to stdout)
+        #[arg(short, long)]
+        output: Option<String>,
+    },
+
+    /// Browse the marketplace and sync work done offline
+    Marketplace {
+        #[command(subcommand)]
+        action: MarketplaceAction,
+
+        /// Use the cached index and queue changes, regardless of marketplace.offline
+        #[arg(long)]
+        offline: bool,
+    },
+
+    /// Sign transactions offline and relay them as meta-transactions
+    Tx {
+        #[command(subcommand)]
+        action: TxAction,
+    },
+
+    /// Delete an environment's release history
+    DeleteDeployment {
+        /// Environment name
+        env: String,
+
+        /// Skip the confirmation prompt
+        #[arg(long)]
+        force: bool,
+    },
+
+    /// Tear down infrastructure deployed from a template
+    DestroyInfra {
+        /// Infrastructure template name
+        template: String,
+
+        /// Skip the confirmation prompt
+        #[arg(long)]
+        force: bool,
+    },
+
+    /// Check the config, node, toolchains, disk space and deploy keys
+    Doctor,
+
+    /// Print a shell completion script
+    Completions {
+        /// bash, zsh, fish, powershell or elvish
+        shell: clap_complete::Shell,
+    },
+
+    /// Manage node icons and other editor assets
+    Assets {
+        #[command(subcommand)]
+        action: AssetsAction,
+    },
+
+    /// Forward contract events to webhooks
+    Webhooks {
+        #[command(subcommand)]
+        action: WebhooksAction,
+    },
+
+    /// Act as other accounts on a local network, for testing
+    Impersonate {
+        #[command(subcommand)]
+        action: ImpersonateAction,
+    },
+
+    /// Inspect or change opt-in usage metrics
+    Telemetry {
+        #[command(subcommand)]
+        action: TelemetryAction,
+    },
+
+    /// Find nodes across every graph of a workspace
+    Search {
+        /// Value to match; `*` matches any run of characters. With
+        /// `--kind property`, `name=value` limits it to one property.
+        pattern: String,
+
+        /// Workspace directory
+        #[arg(default_value = ".")]
+        dir: String,
+
+        /// What to match: type, property, storage, custom or any
+        #[arg(short, long, default_value = "any")]
+        kind: String,
+    },
+
+    /// List the slots of a template graph, or fill them with graphs of your own
+    Slots {
+        /// Template graph file
+        input: String,
+
+        /// Fill a slot with a graph file from the same workspace, as `name=path`
+        #[arg(long, value_name = "NAME=PATH")]
+        fill: Vec<String>,
+    },
+
+    /// Rename a storage key, event or function across a workspace: graphs,
+    /// ABIs and scenarios
+    Rename {
+        /// What to rename: storage, event or function
+        kind: String,
+
+        /// Current name
+        from: String,
+
+        /// New name
+        to: String,
+
+        /// Workspace directory
+        #[arg(short, long, default_value = ".")]
+        dir: String,
+
+        /// List the locations that would change without writing anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Show which contracts of a workspace call which
+    CallGraph {
+        /// Workspace directory
+        #[arg(default_value = ".")]
+        dir: String,
+
+        /// Output format: dot, mermaid or json
+        #[arg(short, long, default_value = "dot")]
+        format: String,
+
+        /// Only list the contracts affected by upgrading this one (name or graph ID)
+        #[arg(long)]
+        upgrade: Option<String>,
+    },
+
+    /// Rewrite deprecated nodes in a graph to the current node set
+    MigrateNodes {
+        /// Input graph file
+        #[arg(short, long)]
+        input: String,
+
+        /// Output graph file (defaults to rewriting the input in place)
+        #[arg(short, long)]
+        output: Option<String>,
+
+        /// Report what would change without writing anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Run validation, lints, scenarios, gas regression, security and license checks in one go.
+    ///
+    /// Exit code is 0 on success, 1 if the pipeline itself failed, otherwise the
+    /// sum of: 2 validation, 4 lint, 8 scenario, 16 gas regression, 32 security,
+    /// 64 license policy.
+    Ci {
+        /// Project directory; graphs and scenarios are discovered in it
+        #[arg(default_value = ".")]
+        dir: String,
+
+        /// Graph files to check (defaults to the JSON graphs in the project directory)
+        #[arg(short, long)]
+        graph: Vec<String>,
+
+        /// Gas baseline file, relative to the project directory
+        #[arg(long, default_value = "gas-baseline.json")]
+        gas_baseline: String,
+
+        /// Allowed gas growth over the baseline, in percent
+        #[arg(long, default_value_t = 5.0)]
+        gas_tolerance: f64,
+
+        /// Rewrite the gas baseline instead of checking against it
+        #[arg(long)]
+        update_gas_baseline: bool,
+
+        /// Report format: json or junit
+        #[arg(long, default_value = "json")]
+        format: String,
+
+        /// Report file (defaults to stdout)
+        #[arg(short, long)]
+        output: Option<String>,
+
+        /// Scenarios to run at once (defaults to the number of cores)
+        #[arg(short, long, default_value_t = 0)]
+        jobs: usize,
+    },
+
+    /// Run one simulation repeatedly and compare the runs bit-for-bit
+    DeterminismCheck {
+        /// Contract WASM file
+        #[arg(short, long)]
+        contract: String,
+
+        /// Exported function to call
+        #[arg(short, long)]
+        function: String,
+
+        /// Arguments file (JSON)
+        #[arg(short, long)]
+        input: Option<String>,
+
+        /// Gas limit
+        #[arg(short, long, default_value = "1000000")]
+        gas_limit: u64,
+
+        /// Call on behalf of this account (0x-hex address)
+        #[arg(long)]
+        caller: Option<String>,
+
+        /// Runs in this process, and on each worker
+        #[arg(long, default_value_t = 5)]
+        runs: usize,
+
+        /// Threads for the local runs (defaults to the number of cores)
+        #[arg(short, long, default_value_t = 0)]
+        jobs: usize,
+
+        /// Base URL of a determinism worker to also run on; repeatable
+        #[arg(long = "worker")]
+        workers: Vec<String>,
+    },
+
+    /// Answer determinism-check runs from other hosts
+    DeterminismWorker {
+        /// Host to bind
+        #[arg(long, default_value = "127.0.0.1")]
+        host: String,
+
+        /// Port to bind
+        #[arg(short, long, default_value = "8546")]
+        port: u16,
+    },
+
+    /// Run the test cases shipped with an installed custom node
+    NodeTest {
+        /// Custom node ID
+        id: String,
+
+        /// Directory of installed custom nodes (defaults to <data_dir>/nodes)
+        #[arg(long)]
+        dir: Option<String>,
+
+        /// Write a test report in this format: junit or json
+        #[arg(long)]
+        format: Option<String>,
+
+        /// Report file (defaults to stdout)
+        #[arg(short, long)]
+        output: Option<String>,
+    },
+
+    /// Run scenario files
+    Test {
+        /// Scenario files or directories to search for `*.scenario.json`
+        #[arg(default_value = ".")]
+        paths: Vec<String>,
+
+        /// Write a test report in this format: junit or json
+        #[arg(long)]
+        format: Option<String>,
+
+        /// Report file (defaults to stdout)
+        #[arg(short, long)]
+        output: Option<String>,
+
+        /// Scenarios to run at once (defaults to the number of cores)
+        #[arg(short, long, default_value_t = 0)]
+        jobs: usize,
+
+        /// Storage backend for each scenario: memory or sled:<dir>
+        #[arg(long, default_value = "memory")]
+        storage: String,
+    },
+
+    /// Call a contract with random call sequences and minimize the first
+    /// failure into a scenario file
+    Fuzz {
+        /// Contract WASM file, with its `.abi.json` next to it
+        #[arg(short, long)]
+        contract: String,
+
+        /// Graph the contract was built from, to name the nodes behind a
+        /// failure (needs an instrumented build)
+        #[arg(long)]
+        graph: Option<String>,
+
+        /// Random call sequences to try
+        #[arg(long, default_value_t = 200)]
+        runs: usize,
+
+        /// Calls in the longest sequence
+        #[arg(long, default_value_t = 8)]
+        max_steps: usize,
+
+        /// Gas limit of each call
+        #[arg(short, long, default_value = "1000000")]
+        gas_limit: u64,
+
+        /// Seed of the generator (defaults to the current time)
+        #[arg(long)]
+        seed: Option<u64>,
+
+        /// Directory to write the minimized scenario to (defaults to the contract's)
+        #[arg(short, long)]
+        output: Option<String>,
+    },
+
+    /// Simulate pending transactions to the workspace's deployed contracts
+    /// and warn about those that would revert or exceed gas bounds
+    MempoolWatch {
+        /// Watch only this environment's latest release, on its node
+        #[arg(short, long)]
+        environment: Option<String>,
+
+        /// Also warn when a call would use more gas than this
+        #[arg(long)]
+        max_gas: Option<u64>,
+
+        /// URL each warning is POSTed to; repeatable
+        #[arg(long = "webhook")]
+        webhooks: Vec<String>,
+
+        /// Seconds between polls
+        #[arg(long, default_value = "5")]
+        interval: u64,
+
+        /// Poll once and exit
+        #[arg(long)]
+        once: bool,
+    },
+}
+
+fn main() {
+    let cli = Cli::parse();
+    let output = Output::new(if cli.json { OutputFormat::Json } else { OutputFormat::Human }).with_quiet(cli.quiet);
+    let command = cli.command.as_ref().map(command_name).unwrap_or_else(|| "editor".to_string());
+
+    match run(&cli, &output) {
+        Ok(code) => {
+            output.success(&command);
+            if code != 0 {
+                std::process::exit(code);
+            }
+        }
+        Err(e) => {
+            output.error(&command, &e);
+            std::process::exit(command_exit_code(&command, &e));
+        }
+    }
+}
+
+/// Run a command; returns the process exit code of a command that finished
+/// but failed checks (only `ci` does)
+fn run(cli: &Cli, output: &Output) -> CanvasResult<i32> {
+    // Initialize the library
+    init()?;
+
+    // Set up logging
+    let log_level = if cli.debug {
+        "debug"
+    } else {
+        &cli.log_level
+    };
+    std::env::set_var("RUST_LOG", log_level);
+    env_logger::init();
+
+    info!("Starting Canvas Contracts v{}", env!("CARGO_PKG_VERSION"));
+
+    // Load configuration; the doctor reports a broken one instead of failing on it
+    let config_path = std::path::PathBuf::from(&cli.config);
+    if let Some(Commands::Doctor) = &cli.command {
+        return doctor(&config_path, output).map(|()| 0);
+    }
+    let mut config_manager = ConfigManager::new(config_path)?;
+
+    if let Some(command) = cli.command.as_ref().filter(|c| !matches!(c, Commands::Telemetry { .. })) {
+        let name = command_name(command);
+        record_telemetry(&config_manager, canvas_contracts::telemetry::TelemetryEvent::Command { name });
+    }
+
+    match &cli.command {
+        Some(Commands::SelfUpdate { component, channel, check, rollback, trust_key }) => {
+            if let Some(key) = trust_key {
+                let mut keys = config_manager.config().update.trusted_keys.clone();
+                if !keys.contains(key) {
+                    keys.push(key.clone());
+                }
+                config_manager.set_value("update.trusted_keys", serde_json::json!(keys))?;
+                config_manager.save()?;
+                info!("Release feeds signed by {} are now trusted", key);
+            }
+            self_update(component.as_deref(), channel.as_deref(), *check, *rollback, &config_manager)?
+        }
+
+        Some(Commands::Tx { action }) => transaction(action, &config_manager)?,
+
+        Some(Commands::Impersonate { action }) => impersonate(action, &config_manager, output)?,
+
+        Some(Commands::Assets { action }) => assets(action, &config_manager, output)?,
+
+        Some(Commands::Webhooks { action }) => webhooks(action, &config_manager, output)?,
+
+        Some(Commands::Completions { shell }) => {
+            clap_complete::generate(*shell, &mut Cli::command(), "canvas-contracts", &mut std::io::stdout())
+        }
+
+        Some(Commands::DeleteDeployment { env, force }) => delete_deployment(env, *force, output)?,
+
+        Some(Commands::DestroyInfra { template, force }) => {
+            output.confirm(&format!("destroy the infrastructure of '{}'", template), template, *force)?;
+            let manager = canvas_contracts::deployment::InfrastructureManager::new(config_manager.config());
+            futures::executor::block_on(manager.destroy_infrastructure(template))?;
+            info!("Destroyed infrastructure '{}'", template);
+        }
+
+        Some(Commands::Telemetry { action }) => {
+            telemetry(action, &mut config_manager, output)?
+        }
+
+        Some(Commands::Serve { contract, abi, storage, host, port }) => {
+            serve_contract(contract, abi.as_deref(), storage, host, *port, &config_manager)?
+        }
+
+        Some(Commands::Playground { abi, output, endpoint, contract, title }) => {
+            contract_playground(abi, output.as_deref(), endpoint, contract.as_deref(), title.as_deref())?
+        }
+
+        Some(Commands::Wit { input, output: wit_output }) => {
+            contract_wit(input, wit_output.as_deref(), &config_manager, output)?
+        }
+
+        Some(Commands::Abi { input, output: abi_output }) => {
+            contract_abi(input, abi_output.as_deref(), &config_manager, output)?
+        }
+
+        Some(Commands::FromDsl { input, output }) => {
+            graph_from_dsl(input, output)?
+        }
+
+        Some(Commands::ToDsl { input, output: dsl_output }) => {
+            graph_to_dsl(input, dsl_output.as_deref(), output)?
+        }
+
+        Some(Commands::Marketplace { action, offline }) => {
+            marketplace(action, *offline, &config_manager, output)?
+        }
+
+        Some(Commands::Compile { input, output: wasm, optimize }) => {
+            compile_contract(input, wasm, *optimize, &config_manager, output)?
+        }
+
+        Some(Commands::Simulate { contract, input, function, gas_limit, caller, record, storage }) => {
+            simulate_contract(
+                contract,
+                input.as_deref(),
+                function.as_deref(),
+                *gas_limit,
+                caller.as_deref(),
+                record.as_deref(),
+                storage.as_deref(),
+                &config_manager,
+                output,
+            )?
+        }
+
+        Some(Commands::Deploy { contract, args, abi, key, env }) => deploy_contract(
+            contract,
+            args.as_deref(),
+            abi.as_deref(),
+            key.as_deref(),
+            env.as_deref(),
+            &config_manager,
+            output,
+        )?,
+
+        Some(Commands::DeployMulti { contract, args, abi, envs, format, output: report }) => deploy_multi(
+            contract,
+            args.as_deref(),
+            abi.as_deref(),
+            envs,
+            format,
+            report.as_deref(),
+            &config_manager,
+            output,
+        )?,
+
+        Some(Commands::VerifyRelease { env }) => {
+            verify_release(env)?
+        }
+
+        Some(Commands::Promote { from, to, allow_breaking }) => {
+            promote_release(from, to.as_deref(), *allow_breaking, &config_manager)?
+        }
+        Some(Commands::Freeze { env, reason }) => {
+            freeze_release(env, reason.clone(), &config_manager)?
+        }
+
+        Some(Commands::Pause { address, key }) => {
+            set_contract_paused(address, key, true, &config_manager)?
+        }
+
+        Some(Commands::Unpause { address, key }) => {
+            set_contract_paused(address, key, false, &config_manager)?
+        }
+
+        Some(Commands::Editor { port, host }) => {
+            start_editor(*port, host, &config_manager)?
+        }
+
+        Some(Commands::Info) => {
+            show_info(output)?
+        }
+
+        Some(Commands::Validate { input }) => {
+            validate_graph(input, &config_manager, output)?
+        }
+
+        Some(Commands::Backup { output, workspace }) => {
+            backup_platform(output, workspace, &config_manager)?
+        }
+
+        Some(Commands::Restore { input, workspace, verify_only }) => {
+            restore_platform(input, workspace, *verify_only, &mut config_manager)?
+        }
+
+        Some(Commands::ExportBundle { dir, output, name, obfuscate, compile_nodes }) => export_bundle(
+            dir,
+            output.as_deref(),
+            name.as_deref(),
+            *obfuscate,
+            compile_nodes,
+            &config_manager,
+        )?,
+
+        Some(Commands::ImportBundle { input, dir, skip_nodes }) => {
+            import_bundle(input, dir, *skip_nodes, &config_manager)?
+        }
+
+        Some(Commands::ImportEvents { contract, abi, from_block }) => {
+            import_event_schema(contract, abi, *from_block, &config_manager)?
+        }
+
+        Some(Commands::AbiDiff { old, new, allow_breaking }) => {
+            abi_diff(old, new, *allow_breaking, output)?
+        }
+
+        Some(Commands::Audit { dir, feed, nodes }) => {
+            audit(dir, feed.as_deref(), nodes.as_deref(), &config_manager, output)?
+        }
+
+        Some(Commands::Licenses { dir, nodes }) => licenses(dir, nodes.as_deref(), &config_manager, output)?,
+
+        Some(Commands::Digest { dir, format, output: file, send, force, watch }) => {
+            digest(dir, format.as_deref(), file.as_deref(), *send, *force, *watch, &config_manager, output)?
+        }
+
+        Some(Commands::Budgets { dir }) => budgets(dir, output)?,
+
+        Some(Commands::Search { pattern, dir, kind }) => search_workspace(pattern, dir, kind, output)?,
+
+        Some(Commands::Slots { input, fill }) => slots(input, fill, output)?,
+
+        Some(Commands::Rename { kind, from, to, dir, dry_run }) => rename(kind, from, to, dir, *dry_run, output)?,
+
+        Some(Commands::CallGraph { dir, format, upgrade }) => {
+            call_graph(dir, format, upgrade.as_deref(), output)?
+        }
+
+        Some(Commands::MigrateNodes { input, output, dry_run }) => {
+            migrate_nodes(input, output.as_deref(), *dry_run)?
+        }
+
+        Some(Commands::DeterminismCheck { contract, function, input, gas_limit, caller, runs, jobs, workers }) => {
+            determinism_check(
+                contract,
+                function,
+                input.as_deref(),
+                *gas_limit,
+                caller.as_deref(),
+                *runs,
+                *jobs,
+                workers,
+                &config_manager,
+                output,
+            )?
+        }
+
+        Some(Commands::DeterminismWorker { host, port }) => {
+            canvas_contracts::testing::DeterminismWorker::new(config_manager.config()).serve(&format!("{}:{}", host, port))?
+        }
+
+        Some(Commands::NodeTest { id, dir, format, output: report }) => {
+            run_node_tests(id, dir.as_deref(), format.as_deref(), report.as_deref(), &config_manager, output)?
+        }
+
+        Some(Commands::Test { paths, format, output: report, jobs, storage }) => {
+            run_scenarios(paths, format.as_deref(), report.as_deref(), *jobs, storage, &config_manager, output)?
+        }
+
+        Some(Commands::Fuzz { contract, graph, runs, max_steps, gas_limit, seed, output: dir }) => fuzz_contract(
+            contract,
+            graph.as_deref(),
+            *runs,
+            *max_steps,
+            *gas_limit,
+            *seed,
+            dir.as_deref(),
+            &config_manager,
+            output,
+        )?,
+
+        Some(Commands::MempoolWatch { environment, max_gas, webhooks, interval, once }) => watch_mempool(
+            environment.as_deref(),
+            *max_gas,
+            webhooks,
+            *interval,
+            *once,
+            &config_manager,
+        )?,
+
+        Some(Commands::Ci { dir, graph, gas_baseline, gas_tolerance, update_gas_baseline, format, output: report, jobs }) => {
+            return run_ci(
+                dir,
+                graph,
+                gas_baseline,
+                *gas_tolerance,
+                *update_gas_baseline,
+                format,
+                report.as_deref(),
+                *jobs,
+                &config_manager,
+                output,
+            );
+        }
+
+        None => {
+            // Default: start the visual editor
+            start_editor(3000, "localhost", &config_manager)?
+        }
+    }
+
+    Ok(0)
+}
+
+fn serve_contract(
+    contract: &str,
+    abi: Option<&str>,
+    storage: &str,
+    host: &str,
+    port: u16,
+    config_manager: &ConfigManager,
+) -> CanvasResult<()> {
+    use canvas_contracts::wasm::{ContractService, StorageBackendKind};
+
+    let wasm = std::fs::read(contract)?;
+    let kind = StorageBackendKind::from_spec(storage)?;
+    if kind == StorageBackendKind::Memory {
+        warn!("Contract storage is in memory and is lost when the service stops");
+    }
+    let mut service = ContractService::new(config_manager.config(), wasm, kind.open(config_manager.config())?)?;
+    if let Some(path) = abi {
+        service = service.with_abi(serde_json::from_str(&std::fs::read_to_string(path)?)?);
+    }
+    service.serve(&format!("{}:{}", host, port))
+}
+
+fn determinism_check(
+    contract: &str,
+    function: &str,
+    input: Option<&str>,
+    gas_limit: u64,
+    caller: Option<&str>,
+    runs: usize,
+    jobs: usize,
+    workers: &[String],
+    config_manager: &ConfigManager,
+    out: &Output,
+) -> CanvasResult<()> {
+    use canvas_contracts::{
+        compiler::TraceMap,
+        testing::{check_determinism, DeterminismOptions},
+        wasm::SimulationRequest,
+    };
+
+    let wasm_bytes = std::fs::read(contract)?;
+    let input_data: serde_json::Value = match input {
+        Some(path) => serde_json::from_str(&std::fs::read_to_string(path)?)?,
+        None => serde_json::Value::Null,
+    };
+
+    // With an ABI, arguments are checked and divergences point at graph nodes
+    let abi_path = contract.replace(".wasm", ".abi.json");
+    let (arguments, trace_map) = match std::fs::read_to_string(&abi_path) {
+        Ok(content) => {
+            let abi: canvas_contracts::types::ContractABI = serde_json::from_str(&content)?;
+            let signature = canvas_contracts::compiler::find_function(&abi, function)
+                .ok_or_else(|| CanvasError::Validation(format!("Contract has no function '{}'", function)))?;
+            (canvas_contracts::compiler::validate_call_args(signature, &input_data)?, TraceMap::read_from(&abi)?)
+        }
+        Err(_) => {
+            let arguments = match input_data {
+                serde_json::Value::Array(items) => items,
+                serde_json::Value::Null => Vec::new(),
+                other => vec![other],
+            };
+            (arguments, None)
+        }
+    };
+
+    let mut request = SimulationRequest::new(function, arguments, gas_limit);
+    if let Some(caller) = caller {
+        request.set_caller(caller);
+    }
+    let options = DeterminismOptions {
+        runs,
+        jobs,
+        workers: workers.to_vec(),
+    };
+    info!("Running {} {} times locally and on {} worker(s)", function, runs, workers.len());
+    let report = check_determinism(config_manager.config(), &wasm_bytes, &request, &options, trace_map.as_ref())?;
+
+    let json = serde_json::json!({
+        "status": if report.is_deterministic() { "ok" } else { "nondeterministic" },
+        "contract": contract,
+        "function": function,
+        "runs": &report.runs,
+        "divergences": &report.divergences,
+        "host_functions": report.nondeterministic_host_functions(),
+        "nodes": report.nondeterministic_nodes(),
+    });
+    out.emit("determinism-check", json, |style| {
+        let headline = if report.is_deterministic() {
+            format!("{} {} runs matched", style.green("✔"), report.runs.len())
+        } else {
+            format!("{} {} of {} runs diverged", style.red("✘"), report.divergences.len(), report.runs.len())
+        };
+        let mut runs = Table::new(["Run", "Platform", "Digest"]);
+        for run in &report.runs {
+            runs.add_row([run.label.clone(), run.platform.clone(), run.digest.clone()]);
+        }
+        let mut rendered = format!("{}\n\n{}", headline, runs.render(style));
+        if !report.is_deterministic() {
+            let mut divergences = Table::new(["Run", "Fields", "Host function", "Node", "Detail"]);
+            for divergence in &report.divergences {
+                divergences.add_row([
+                    divergence.run.clone(),
+                    divergence.fields.join(", "),
+                    divergence.host_function.clone().unwrap_or_default(),
+                    divergence.node.map(|n| n.to_string()).unwrap_or_default(),
+                    divergence.detail.clone(),
+                ]);
+            }
+            rendered.push('\n');
+            rendered.push_str(&divergences.render(style));
+        }
+        rendered
+    });
+    report.ensure_deterministic()
+}
+
+fn contract_wit(input: &str, output: Option<&str>, config_manager: &ConfigManager, out: &Output) -> CanvasResult<()> {
+    let (graph, _) = canvas_contracts::nodes::load_graph(&std::fs::read_to_string(input)?)?;
+    let wit = Compiler::new(config_manager.config())?.wit(&graph)?;
+    match output {
+        Some(path) => {
+            std::fs::write(path, wit)?;
+            info!("Wrote WIT world for '{}' to {}", graph.name, path);
+        }
+        None => out.emit("wit", serde_json::json!({ "wit": wit }), |_| wit),
+    }
+    Ok(())
+}
+
+fn contract_abi(input: &str, output: Option<&str>, config_manager: &ConfigManager, out: &Output) -> CanvasResult<()> {
+    use canvas_contracts::{dsl::type_name, types::ParameterABI};
+
+    let (graph, _) = canvas_contracts::nodes::load_graph(&std::fs::read_to_string(input)?)?;
+    let abi = Compiler::new(config_manager.config())?.build_abi(&graph)?;
+    if let Some(path) = output {
+        std::fs::write(path, serde_json::to_string_pretty(&abi)?)?;
+        info!("Wrote ABI for '{}' to {}", graph.name, path);
+        return Ok(());
+    }
+
+    let signature = |params: &[ParameterABI]| {
+        params
+            .iter()
+            .map(|p| format!("{}: {}", p.name, type_name(&p.value_type)))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    out.emit("abi", serde_json::to_value(&abi)?, |style| {
+        let mut functions = Table::new(["Function", "Inputs", "Returns", "Mutability"]);
+        for function in &abi.functions {
+            functions.add_row([
+                function.name.clone(),
+                signature(&function.inputs),
+                signature(&function.outputs),
+                format!("{:?}", function.state_mutability),
+            ]);
+        }
+        let mut rendered = format!("{}\n\n{}", style.bold(&graph.name), functions.render(style));
+        if !abi.events.is_empty() || !abi.errors.is_empty() {
+            let mut items = Table::new(["Kind", "Name", "Fields"]);
+            for event in &abi.events {
+                items.add_row(["event".to_string(), event.name.clone(), signature(&event.inputs)]);
+            }
+            for error in &abi.errors {
+                items.add_row(["error".to_string(), error.name.clone(), signature(&error.inputs)]);
+            }
+            rendered.push('\n');
+            rendered.push_str(&items.render(style));
+        }
+        if !abi.storage.is_empty() {
+            let mut storage = Table::new(["Storage key", "Type", "Access"]);
+            for entry in &abi.storage {
+                let access = if entry.writable { "read/write" } else { "read" };
+                storage.add_row([entry.key.clone(), type_name(&entry.value_type), access.to_string()]);
+            }
+            rendered.push('\n');
+            rendered.push_str(&storage.render(style));
+        }
+        rendered
+    });
+    Ok(())
+}
+
+fn contract_playground(
+    abi: &str,
+    output: Option<&str>,
+    endpoint: &str,
+    contract: Option<&str>,
+    title: Option<&str>,
+) -> CanvasResult<()> {
+    use canvas_contracts::compiler::{generate_playground, PlaygroundOptions};
+
+    let contract_abi: canvas_contracts::types::ContractABI = serde_json::from_str(&std::fs::read_to_string(abi)?)?;
+    let name = abi.trim_end_matches(".json").trim_end_matches(".abi");
+    let title = title.map_or_else(
+        || {
+            std::path::Path::new(name)
+                .file_name()
+                .map_or_else(|| "Contract".to_string(), |n| n.to_string_lossy().to_string())
+        },
+        str::to_string,
+    );
+    let mut options = PlaygroundOptions::new(title).with_endpoint(endpoint);
+    if let Some(contract) = contract {
+        options = options.with_contract(contract);
+    }
+    let html = generate_playground(&contract_abi, &options)?;
+    let output = output.map_or_else(|| format!("{}.playground.html", name), str::to_string);
+    std::fs::write(&output, html)?;
+    info!(
+        "Wrote playground for {} function(s) to {}; calls go to {}",
+        contract_abi.functions.len(),
+        output,
+        endpoint
+    );
+    Ok(())
+}
+
+fn graph_from_dsl(input: &str, output: &str) -> CanvasResult<()> {
+    let source = std::fs::read_to_string(input)?;
+    let graph = canvas_contracts::dsl::parse(&source)?;
+    std::fs::write(output, serde_json::to_string_pretty(&graph)?)?;
+    info!("Generated graph '{}' with {} nodes in {}", graph.name, graph.nodes.len(), output);
+    Ok(())
+}
+
+fn graph_to_dsl(input: &str, output: Option<&str>, out: &Output) -> CanvasResult<()> {
+    let (graph, _) = canvas_contracts::nodes::load_graph(&std::fs::read_to_string(input)?)?;
+    let source = canvas_contracts::dsl::to_dsl(&graph)?;
+    match output {
+        Some(path) => {
+            std::fs::write(path, source)?;
+            info!("Wrote DSL for '{}' to {}", graph.name, path);
+        }
+        None => out.emit("to-dsl", serde_json::json!({ "dsl": source }), |_| source),
+    }
+    Ok(())
+}
+
+fn compile_contract(
+    input: &str,
+    output: &str,
+    optimize: bool,
+    config_manager: &ConfigManager,
+    out: &Output,
+) -> CanvasResult<()> {
+    info!("Compiling contract from {} to {}", input, output);
+
+    // Load the visual graph
+    let graph_content = std::fs::read_to_string(input)
+        .map_err(|e| CanvasError::Io(e))?;
+
+    // Deprecated nodes are shimmed to their replacements with a warning
+    let (graph, _) = canvas_contracts::nodes::load_graph(&graph_content)?;
+
+    // Create compiler
+    let compiler = Compiler::new(config_manager.config())?.with_progress(out.progress());
+
+    // Graphs in the same workspace (the input file's directory) can be imported and called
+    let root = std::path::Path::new(input)
+        .parent()
+        .filter(|p| !p.as_os_str().is_empty())
+        .unwrap_or_else(|| std::path::Path::new("."));
+    let workspace = canvas_contracts::compiler::Workspace::load(root)?;
+    // Templates compile once every slot is filled
+    let graph = if graph.nodes.iter().any(|n| n.node_type == canvas_contracts::compiler::SLOT_NODE_TYPE) {
+        compiler.fill_slots(&graph, &workspace)?
+    } else {
+        graph
+    };
+    let graph = if graph.nodes.iter().any(|n| n.node_type == "Import") {
+        compiler.resolve_imports(&graph, &workspace)?
+    } else {
+        graph
+    };
+
+    // Composite nesting and call chains must stay within the network's stack limits
+    let nodes_dir = config_manager.config().app.data_dir.join(canvas_contracts::nodes::custom::CUSTOM_NODES_DIR);
+    let registry = if nodes_dir.is_dir() {
+        canvas_contracts::nodes::custom::CustomNodeRegistry::load_dir(&nodes_dir)?
+    } else {
+        canvas_contracts::nodes::custom::CustomNodeRegistry::new()
+    };
+    // Custom nodes must be installed at a version their pins accept
+    let resolution = compiler.resolve_custom_nodes(&graph, &registry)?;
+    for update in cached_updates_of(config_manager.config(), &registry)?
+        .iter()
+        .filter(|update| resolution.resolved.contains_key(&update.node_id))
+    {
+        warn!(
+            "Custom node {} {} has an update to {}; run `marketplace updates` for details",
+            update.node_id, update.installed, update.available
+        );
+    }
+    compiler.check_stack_depth(&graph, &registry, &workspace)?;
+
+    // Compile the graph
+    let started = std::time::Instant::now();
+    let result = compiler.compile(&graph);
+    record_telemetry(
+        config_manager,
+        canvas_contracts::telemetry::TelemetryEvent::compile(&graph, result.is_ok(), started.elapsed()),
+    );
+    if let Err(e) = &result {
+        record_telemetry(config_manager, canvas_contracts::telemetry::TelemetryEvent::error(e));
+    }
+    let result = result?;
+
+    // Write WASM output
+    std::fs::write(output, &result.wasm_bytes)
+        .map_err(|e| CanvasError::Io(e))?;
+
+    // Write ABI
+    let abi_path = output.replace(".wasm", ".abi.json");
+    let abi_content = serde_json::to_string_pretty(&result.abi)
+        .map_err(|e| CanvasError::Serialization(e))?;
+    std::fs::write(&abi_path, abi_content)
+        .map_err(|e| CanvasError::Io(e))?;
+
+    // Component-model output, typed by a WIT world generated from the ABI
+    let component_paths = match compiler.component(&graph, &result)? {
+        Some((wit, component)) => {
+            let wit_path = output.replace(".wasm", ".wit");
+            let component_path = output.replace(".wasm", ".component.wasm");
+            std::fs::write(&wit_path, wit)?;
+            std::fs::write(&component_path, component)?;
+            Some((wit_path, component_path))
+        }
+        None => None,
+    };
+
+    let network = &config_manager.config().baals.network.name;
+    let storage = match (
+        result.metadata.get("storage_bytes"),
+        result.metadata.get("storage_deposit"),
+        result.metadata.get("storage_rent_per_year"),
+    ) {
+        (Some(bytes), Some(deposit), Some(rent)) => Some((bytes, deposit, rent)),
+        _ => None,
+    };
+    let json = serde_json::json!({
+        "status": "ok",
+        "input": input,
+        "wasm": output,
+        "abi": abi_path,
+        "wit": component_paths.as_ref().map(|(wit, _)| wit),
+        "component": component_paths.as_ref().map(|(_, component)| component),
+        "gas_estimate": result.gas_estimate,
+        "gas": &result.gas_breakdown,
+        "storage": storage.map(|(bytes, deposit, rent)| serde_json::json!({
+            "network": network,
+            "bytes": bytes,
+            "deposit": deposit,
+            "rent_per_year": rent,
+        })),
+        "warnings": &result.warnings,
+    });
+    out.emit("compile", json, |style| {
+        let mut artifacts = Table::new(["Artifact", "Path"]);
+        artifacts.add_row(["WASM", output]).add_row(["ABI", abi_path.as_str()]);
+        if let Some((wit_path, component_path)) = &component_paths {
+            artifacts.add_row(["WIT", wit_path.as_str()]).add_row(["Component", component_path.as_str()]);
+        }
+        let mut summary = vec![("Gas estimate".to_string(), result.gas_estimate.to_string())];
+        if let Some((bytes, deposit, rent)) = storage {
+            summary.push((
+                "Storage".to_string(),
+                format!("{} bytes, deposit {}, rent {} per year on {}", bytes, deposit, rent, network),
+            ));
+        }
+        let mut rendered = format!("{} {}\n\n", style.green("✔"), style.bold("Compilation successful"));
+        rendered.push_str(&artifacts.render(style));
+        rendered.push('\n');
+        rendered.push_str(&Table::key_values(summary).render(style));
+        if !result.gas_breakdown.is_empty() {
+            let mut gas = Table::new(["Function", "Worst case", "Average", "Nodes", "Instructions"]);
+            for estimate in &result.gas_breakdown {
+                let worst = if estimate.is_bounded() {
+                    estimate.worst_case.to_string()
+                } else {
+                    format!(">= {}", estimate.worst_case)
+                };
+                gas.add_row([
+                    estimate.function.clone(),
+                    worst,
+                    estimate.average_case.to_string(),
+                    estimate.node_gas.to_string(),
+                    estimate.instruction_gas.to_string(),
+                ]);
+            }
+            rendered.push('\n');
+            rendered.push_str(&gas.render(style));
+        }
+        rendered.push_str(&render_warnings(style, &result.warnings));
+        rendered
+    });
+
+    Ok(())
+}
+
+/// Warnings as a trailing list, if there are any
+fn render_warnings(style: &canvas_contracts::output::Style, warnings: &[String]) -> String {
+    let mut rendered = String::new();
+    if !warnings.is_empty() {
+        rendered.push_str(&format!("\n{}\n", style.yellow(&style.bold("Warnings"))));
+        for warning in warnings {
+            rendered.push_str(&format!("  {} {}\n", style.yellow("!"), warning));
+        }
+    }
+    rendered
+}
+
+fn simulate_contract(
+    contract: &str,
+    input: Option<&str>,
+    function: Option<&str>,
+    gas_limit: u64,
+    caller: Option<&str>,
+    record: Option<&str>,
+    storage: Option<&str>,
+    config_manager: &ConfigManager,
+    out: &Output,
+) -> CanvasResult<()> {
+    info!("Simulating contract: {}", contract);
+
+    // Load WASM bytes
+    let wasm_bytes = std::fs::read(contract)
+        .map_err(|e| CanvasError::Io(e))?;
+
+    // Load input data if provided
+    let input_data = if let Some(input_file) = input {
+        let content = std::fs::read_to_string(input_file)
+            .map_err(|e| CanvasError::Io(e))?;
+        serde_json::from_str(&content)
+            .map_err(|e| CanvasError::Serialization(e))?
+    } else {
+        serde_json::Value::Null
+    };
+    let recorded_args = input_data.clone();
+
+    // Create runtime
+    let runtime = canvas_contracts::wasm::WasmRuntime::new(config_manager.config())?;
+
+    // Simulate execution, targeting a single entry point if one was named
+    let result = match function {
+        Some(function) => {
+            let abi_path = contract.replace(".wasm", ".abi.json");
+            let arguments = match std::fs::read_to_string(&abi_path) {
+                Ok(abi_content) => {
+                    let contract_abi: canvas_contracts::types::ContractABI = serde_json::from_str(&abi_content)
+                        .map_err(|e| CanvasError::Serialization(e))?;
+                    let signature = canvas_contracts::compiler::find_function(&contract_abi, function)
+                        .ok_or_else(|| CanvasError::Validation(format!("Contract has no function '{}'", function)))?;
+                    canvas_contracts::compiler::validate_call_args(signature, &input_data)?
+                }
+                Err(_) => match input_data {
+                    serde_json::Value::Array(items) => items,
+                    serde_json::Value::Null => Vec::new(),
+                    other => vec![other],
+                },
+            };
+            match storage {
+                Some(spec) => {
+                    use canvas_contracts::wasm::{SandboxAccounts, SimulationRequest, StorageBackendKind};
+
+                    let backend = StorageBackendKind::from_spec(spec)?.open(config_manager.config())?;
+                    let mut request = SimulationRequest::new(function, arguments, gas_limit);
+                    if let Some(caller) = caller {
+                        request.set_caller(caller);
+                    }
+                    let mut context = canvas_contracts::types::ExecutionContext::new(gas_limit).with_backend(backend);
+                    let result =
+                        runtime.execute_request_in(&wasm_bytes, &request, &mut SandboxAccounts::new(), &mut context)?;
+                    if !result.reverted() {
+                        info!("Committed {} storage slots to {}", context.commit_storage()?, spec);
+                    }
+                    result
+                }
+                None => runtime.execute_function_as(&wasm_bytes, function, arguments, gas_limit, caller)?,
+            }
+        }
+        None => runtime.simulate(&wasm_bytes, input_data, gas_limit)?,
+    };
+
+    if let (Some(path), Some(function)) = (record, function) {
+        use canvas_contracts::testing::{ScenarioRecorder, SCENARIO_EXTENSION};
+
+        let path = std::path::Path::new(path);
+        let mut recorder = if path.exists() {
+            ScenarioRecorder::resume(path)?
+        } else {
+            let name = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+            ScenarioRecorder::new(name.trim_end_matches(SCENARIO_EXTENSION), contract)
+        };
+        recorder.record(function, recorded_args, caller.map(str::to_string), gas_limit, &result);
+        recorder.save(path)?;
+        info!("Recorded step {} in {}", recorder.len(), path.display());
+    }
+
+    let revert = result.revert_reason.as_ref().map(|r| r.to_string());
+    let costs = canvas_contracts::wasm::estimate_costs(&config_manager.config().baals, (&result).into());
+    let json = serde_json::json!({
+        "status": if revert.is_some() { "reverted" } else { "ok" },
+        "contract": contract,
+        "function": function,
+        "gas_used": result.gas_used,
+        "gas_limit": gas_limit,
+        "storage_bytes": result.storage_bytes,
+        "costs": &costs,
+        "execution_time_ms": result.execution_time.as_secs_f64() * 1000.0,
+        "output": &result.output,
+        "revert_reason": &result.revert_reason,
+        "events": &result.events,
+    });
+    let output_json = serde_json::to_string_pretty(&result.output)?;
+    out.emit("simulate", json, |style| {
+        let headline = match &revert {
+            Some(reason) => format!("{} {}: {}", style.red("✘"), style.bold("Simulation reverted"), reason),
+            None => format!("{} {}", style.green("✔"), style.bold("Simulation completed")),
+        };
+        let summary = Table::key_values([
+            ("Function", function.unwrap_or("(all)").to_string()),
+            ("Gas used", format!("{} of {}", result.gas_used, gas_limit)),
+            ("Storage written", format!("{} bytes", result.storage_bytes)),
+            ("Time", format!("{:.2?}", result.execution_time)),
+        ]);
+        let mut rendered = format!(
+            "{}\n\n{}\n{}\n{}\n",
+            headline,
+            summary.render(style),
+            style.bold("Output"),
+            output_json
+        );
+        if !result.events.is_empty() {
+            let mut events = Table::new(["Event", "Data"]);
+            for event in &result.events {
+                events.add_row([event.name.clone(), serde_json::json!(event.data).to_string()]);
+            }
+            rendered.push('\n');
+            rendered.push_str(&events.render(style));
+        }
+        let mut cost_table = Table::new(["Network", "Fee model", "Fee", "Max fee"]);
+        for cost in &costs {
+            cost_table.add_row([cost.network.clone(), cost.model.clone(), cost.fee.to_string(), cost.max_fee.to_string()]);
+        }
+        rendered.push_str(&format!("\n{}\n{}", style.bold("Cost"), cost_table.render(style)));
+        rendered
+    });
+
+    Ok(())
+}
+
+fn deploy_contract(
+    contract: &str,
+    args: Option<&str>,
+    abi: Option<&str>,
+    key: Option<&str>,
+    env: Option<&str>,
+    config_manager: &ConfigManager,
+    out: &Output,
+) -> CanvasResult<()> {
+    use canvas_contracts::deployment::environments::{new_release, Environments, ReleaseStore};
+
+    info!("Deploying contract: {}", contract);
+
+    // Environments are defined at the workspace root, the current directory
+    let root = std::path::Path::new(".");
+    let environment = match env {
+        Some(name) => Some(Environments::load(root)?.get(name)?.clone()),
+        None => None,
+    };
+    let config = match &environment {
+        Some(environment) => environment.apply(config_manager.config())?,
+        None => config_manager.config().clone(),
+    };
+    // Deploying to an environment records a release
+    if environment.is_some() {
+        ensure_license_policy(root, config_manager.config())?;
+    }
+
+    // Load WASM bytes
+    let wasm_bytes = std::fs::read(contract)
+        .map_err(|e| CanvasError::Io(e))?;
+
+    // Key file or hardware wallet
+    let key_spec = match (key, &environment) {
+        (Some(key), _) => key.to_string(),
+        (None, Some(environment)) => environment.key.to_string_lossy().into_owned(),
+        (None, None) => return Err(CanvasError::Config("A key file is required outside an environment".to_string())),
+    };
+    let signer = signer_from_spec(&key_spec, root, &config)?;
+    info!("Signing with {}", signer.describe());
+
+    let (contract_abi, constructor_args) = load_constructor_args(contract, args, abi)?;
+
+    // Create BaaLS client, debiting the signer's team budget
+    let baals_client = canvas_contracts::baals::BaalsClient::new(&config)?.with_budgets(GasBudgets::load(root)?);
+
+    // Deploy contract
+    let deployment_result = baals_client.deploy_contract(
+        &wasm_bytes,
+        constructor_args.clone(),
+        signer.as_ref(),
+    )?;
+
+    // Keep the event schema so logs stay decodable after later upgrades
+    if let Some(contract_abi) = &contract_abi {
+        register_event_schema(
+            &deployment_result.contract_address,
+            contract_abi,
+            deployment_result.block_number,
+            config_manager,
+        )?;
+    }
+
+    if let Some(environment) = &environment {
+        let mut release = new_release(
+            &environment.name,
+            deployment_result.contract_address.clone(),
+            &wasm_bytes,
+            contract_abi,
+            constructor_args,
+            deployment_result.block_number,
+        );
+        release.deployer = signer.address().ok();
+        ReleaseStore::new(root).record(release, &wasm_bytes)?;
+        info!("Release recorded in environment '{}'", environment.name);
+    }
+
+    let json = serde_json::json!({
+        "status": "ok",
+        "contract": contract,
+        "environment": environment.as_ref().map(|e| &e.name),
+        "network": &config.baals.network.name,
+        "signer": signer.address().ok(),
+        "contract_address": &deployment_result.contract_address,
+        "transaction_hash": &deployment_result.transaction_hash,
+        "block_number": deployment_result.block_number,
+        "gas_used": deployment_result.gas_used,
+    });
+    out.emit("deploy", json, |style| {
+        let mut details = vec![
+            ("Contract address", deployment_result.contract_address.clone()),
+            ("Transaction hash", deployment_result.transaction_hash.clone()),
+            ("Block", deployment_result.block_number.to_string()),
+            ("Gas used", deployment_result.gas_used.to_string()),
+            ("Network", config.baals.network.name.clone()),
+        ];
+        if let Some(environment) = &environment {
+            details.push(("Environment", environment.name.clone()));
+        }
+        format!(
+            "{} {}\n\n{}",
+            style.green("✔"),
+            style.bold("Deployment successful"),
+            Table::key_values(details).render(style)
+        )
+    });
+
+    Ok(())
+}
+
+/// ABI written by `compile` next to the contract, if any, and the constructor
+/// arguments checked against it
+fn load_constructor_args(
+    contract: &str,
+    args: Option<&str>,
+    abi: Option<&str>,
+) -> CanvasResult<(Option<canvas_contracts::types::ContractABI>, serde_json::Value)> {
+    // Parse constructor arguments
+    let constructor_args = if let Some(args_str) = args {
+        serde_json::from_str(args_str)
+            .map_err(|e| CanvasError::Serialization(e))?
+    } else {
+        serde_json::Value::Null
+    };
+
+    // Check constructor arguments against the ABI written by `compile`
+    let abi_path = abi
+        .map(|path| path.to_string())
+        .unwrap_or_else(|| contract.replace(".wasm", ".abi.json"));
+    let contract_abi: Option<canvas_contracts::types::ContractABI> = match std::fs::read_to_string(&abi_path) {
+        Ok(abi_content) => Some(serde_json::from_str(&abi_content).map_err(|e| CanvasError::Serialization(e))?),
+        Err(e) if abi.is_some() => return Err(CanvasError::Io(e)),
+        Err(_) => {
+            info!("No ABI found at {}; constructor arguments are not checked", abi_path);
+            None
+        }
+    };
+    let constructor_args = match contract_abi.as_ref().and_then(canvas_contracts::compiler::find_constructor) {
+        Some(constructor) => serde_json::Value::Array(
+            canvas_contracts::compiler::validate_constructor_args(constructor, &constructor_args)?,
+        ),
+        None => constructor_args,
+    };
+    Ok((contract_abi, constructor_args))
+}
+
+fn deploy_multi(
+    contract: &str,
+    args: Option<&str>,
+    abi: Option<&str>,
+    envs: &[String],
+    format: &str,
+    output: Option<&str>,
+    config_manager: &ConfigManager,
+    out: &Output,
+) -> CanvasResult<()> {
+    use canvas_contracts::deployment::{
+        environments::{Environments, ReleaseStore},
+        fanout::{fan_out, BaalsDeployer},
+    };
+
+    let root = std::path::Path::new(".");
+    let environments = Environments::load(root)?;
+    let targets = envs
+        .iter()
+        .map(|name| environments.get(name).cloned())
+        .collect::<CanvasResult<Vec<_>>>()?;
+
+    let wasm_bytes = std::fs::read(contract)?;
+    let (contract_abi, constructor_args) = load_constructor_args(contract, args, abi)?;
+    info!("Deploying {} to {} network(s)", contract, targets.len());
+
+    let deployer = BaalsDeployer::new(config_manager.config(), root);
+    let release = fan_out(
+        &targets,
+        &deployer,
+        &ReleaseStore::new(root),
+        &wasm_bytes,
+        contract_abi.as_ref(),
+        constructor_args,
+        &*out.progress(),
+    )?;
+
+    for deployment in &release.deployments {
+        if let (Some(abi), Some(address), Some(block)) =
+            (&contract_abi, &deployment.contract_address, deployment.block_number)
+        {
+            register_event_schema(address, abi, block, config_manager)?;
+        }
+    }
+
+    let rendered = match format {
+        "markdown" => release.to_markdown(),
+        "json" => serde_json::to_string_pretty(&release)?,
+        other => return Err(CanvasError::Validation(format!("Unknown report format: {}", other))),
+    };
+    write_report("deploy-multi", rendered, output, out)?;
+
+    let failures = release.failures();
+    if !failures.is_empty() {
+        let names: Vec<_> = failures.iter().map(|d| d.environment.as_str()).collect();
+        return Err(CanvasError::Validation(format!(
+            "Deployment {} failed or could not be verified on: {}",
+            release.id,
+            names.join(", ")
+        )));
+    }
+    info!("Deployment {} verified on every network", release.id);
+    Ok(())
+}
+
+fn doctor(config_path: &std::path::Path, out: &Output) -> CanvasResult<()> {
+    use canvas_contracts::doctor::{CheckStatus, Doctor, SystemProbe};
+
+    let report = Doctor::new(config_path, ".", &SystemProbe).run();
+    out.emit("doctor", serde_json::to_value(&report)?, |style| {
+        let mut table = Table::new(["Check", "Status", "Detail"]);
+        for check in &report.checks {
+            table.add_row([check.name.clone(), check.status.label().to_string(), check.detail.clone()]);
+        }
+        let mut rendered = table.render_with(style, |column, text| match (column, text.trim_end()) {
+            (1, "ok") => style.green(text),
+            (1, "warn") => style.yellow(text),
+            (1, "fail") => style.red(text),
+            (1, _) => style.dim(text),
+            _ => text.to_string(),
+        });
+        let fixes: Vec<_> = report.checks.iter().filter_map(|c| Some((&c.name, c.fix.as_ref()?))).collect();
+        if !fixes.is_empty() {
+            rendered.push_str(&format!("\n{}\n", style.bold("To fix:")));
+            for (name, fix) in fixes {
+                rendered.push_str(&format!("  {}: {}\n", name, fix));
+            }
+        }
+        rendered
+    });
+
+    if report.failures() > 0 {
+        let failed: Vec<_> =
+            report.checks.iter().filter(|c| c.status == CheckStatus::Fail).map(|c| c.name.as_str()).collect();
+        return Err(CanvasError::Validation(format!("Doctor found problems: {}", failed.join(", "))));
+    }
+    Ok(())
+}
+
+fn delete_deployment(env: &str, force: bool, out: &Output) -> CanvasResult<()> {
+    let store = canvas_contracts::deployment::environments::ReleaseStore::new(std::path::Path::new("."));
+    if store.history(env)?.is_empty() {
+        return Err(CanvasError::NotFound(format!("No releases recorded for '{}'", env)));
+    }
+    out.confirm(&format!("delete the release history of '{}'", env), env, force)?;
+    let deleted = store.delete(env)?;
+    info!("Deleted {} releases of '{}'", deleted, env);
+    Ok(())
+}
+
+fn verify_release(env: &str) -> CanvasResult<()> {
+    let store = canvas_contracts::deployment::environments::ReleaseStore::new(std::path::Path::new("."));
+    let release = store.verify(env)?;
+    info!("Release {} in '{}' verified", release.artifact_hash, env);
+    Ok(())
+}
+
+fn promote_release(
+    from: &str,
+    to: Option<&str>,
+    allow_breaking: bool,
+    config_manager: &ConfigManager,
+) -> CanvasResult<()> {
+    use canvas_contracts::deployment::environments::{plan_promotion, Environments, ReleaseStore};
+
+    let root = std::path::Path::new(".");
+    let store = ReleaseStore::new(root);
+    let plan = plan_promotion(&Environments::load(root)?, &store, from, to)?;
+    ensure_license_policy(root, config_manager.config())?;
+    info!(
+        "Promoting release {} from '{}' to '{}'",
+        plan.release.artifact_hash, from, plan.target.name
+    );
+
+    if let Some(diff) = &plan.abi_diff {
+        for change in &diff.changes {
+            info!("  {}", change);
+        }
+        if allow_breaking {
+            if diff.is_breaking() {
+                warn!("Breaking ABI changes allowed by --allow-breaking");
+            }
+        } else {
+            diff.ensure_compatible()?;
+        }
+    }
+
+    let config = plan.target.apply(config_manager.config())?;
+    let signer = signer_from_spec(&plan.target.key.to_string_lossy(), root, &config)?;
+    let baals_client = canvas_contracts::baals::BaalsClient::new(&config)?.with_budgets(GasBudgets::load(root)?);
+    let deployment_result = baals_client.deploy_contract(
+        &plan.wasm_bytes,
+        plan.release.constructor_args.clone(),
+        signer.as_ref(),
+    )?;
+
+    if let Some(contract_abi) = &plan.release.abi {
+        register_event_schema(
+            &deployment_result.contract_address,
+            contract_abi,
+            deployment_result.block_number,
+            config_manager,
+        )?;
+    }
+    let mut release = plan.promoted(deployment_result.contract_address.clone(), deployment_result.block_number);
+    release.deployer = signer.address().ok();
+    store.record(release, &plan.wasm_bytes)?;
+
+    info!("Promotion successful!");
+    info!("Contract address: {}", deployment_result.contract_address);
+    info!("Transaction hash: {}", deployment_result.transaction_hash);
+    Ok(())
+}
+
+fn freeze_release(env: &str, reason: Option<String>, config_manager: &ConfigManager) -> CanvasResult<()> {
+    use canvas_contracts::deployment::{
+        attestations::attest_freeze,
+        environments::{Environments, ReleaseStore},
+    };
+
+    let root = std::path::Path::new(".");
+    let environment = Environments::load(root)?.get(env)?.clone();
+    let store = ReleaseStore::new(root);
+    let release = store
+        .latest(env)?
+        .ok_or_else(|| CanvasError::NotFound(format!("No release in environment '{}'", env)))?;
+
+    // The attestation is signed with the key the release was deployed with
+    let config = environment.apply(config_manager.config())?;
+    let signer = signer_from_spec(&environment.key.to_string_lossy(), root, &config)?;
+    let attestation = attest_freeze(&release, reason, signer.as_ref())?;
+    store.attestations().record(attestation.clone())?;
+
+    info!("Contract {} in '{}' is frozen", attestation.contract_address, env);
+    info!("Artifact: {}", attestation.artifact_hash);
+    info!("Signed by: {}", attestation.signer);
+    Ok(())
+}
+
+fn transaction(action: &TxAction, config_manager: &ConfigManager) -> CanvasResult<()> {
+    use canvas_contracts::baals::transactions::{
+        NonceStore, RelayerClient, SignedTransaction, TransactionBuilder, DIRECT_NONCE_DOMAIN,
+    };
+
+    let config = config_manager.config();
+    match action {
+        TxAction::Sign { to, function, args, value, gas_limit, chain, relayer, valid_for, key, output } => {
+            let signing_key = signer_from_spec(key, std::path::Path::new("."), config)?;
+            let signer = signing_key.address()?;
+            let args: Vec<serde_json::Value> = serde_json::from_str(args)?;
+            let value = value
+                .parse::<u128>()
+                .map_err(|e| CanvasError::Validation(format!("Invalid value '{}': {}", value, e)))?;
+
+            let mut builder = TransactionBuilder::new(to.as_str(), function.as_str())
+                .with_args(args)
+                .with_value(value)
+                .with_chain(chain.clone().unwrap_or_else(|| config.baals.network.name.clone()));
+            if let Some(gas_limit) = gas_limit {
+                builder = builder.with_gas_limit(*gas_limit);
+            }
+            if let Some(relayer) = relayer {
+                if !config.baals.relayers.iter().any(|r| &r.name == relayer) {
+                    return Err(CanvasError::Config(format!("No relayer named '{}' is configured", relayer)));
+                }
+                builder = builder.with_relayer(relayer.as_str());
+            }
+            if let Some(seconds) = valid_for {
+                let now = std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .unwrap()
+                    .as_secs();
+                builder = builder.with_valid_until(now + seconds);
+            }
+
+            let mut nonces = NonceStore::open(&NonceStore::default_path(config))?;
+            let signed = builder.build(&mut nonces, &signer).sign(signing_key.as_ref())?;
+            std::fs::write(output, serde_json::to_string_pretty(&signed)?)?;
+            nonces.save()?;
+
+            info!("Signed transaction {} written to {}", signed.hash, output);
+            info!(
+                "Nonce {} in domain '{}'",
+                signed.transaction.nonce,
+                relayer.as_deref().unwrap_or(DIRECT_NONCE_DOMAIN)
+            );
+        }
+        TxAction::Relay { file } => {
+            let signed: SignedTransaction = serde_json::from_str(&std::fs::read_to_string(file)?)?;
+            if signed.transaction.nonce_domain == DIRECT_NONCE_DOMAIN {
+                return Err(CanvasError::Validation(format!(
+                    "Transaction {} was signed for direct submission, not for a relayer",
+                    signed.hash
+                )));
+            }
+            let relayer = RelayerClient::from_config(config, &signed.transaction.nonce_domain)?;
+            let receipt = relayer.submit(&signed)?;
+            info!("Relayed by '{}' in transaction {}", receipt.relayer, receipt.transaction_hash);
+
+            let client = canvas_contracts::baals::BaalsClient::new(config)?;
+            let status = relayer.confirm(&client, &signed, &receipt)?;
+            info!(
+                "Included in block {} ({} confirmations, {} gas)",
+                status.block_number, status.confirmations, status.gas_used
+            );
+        }
+    }
+    Ok(())
+}
+
+fn impersonate(action: &ImpersonateAction, config_manager: &ConfigManager, out: &Output) -> CanvasResult<()> {
+    use canvas_contracts::baals::impersonation::ImpersonationSession;
+
+    let config = config_manager.config();
+    let mut session = ImpersonationSession::open(&ImpersonationSession::default_path(config))?;
+    match action {
+        ImpersonateAction::Start { address } => {
+            if session.start(&config.baals.network, address)? {
+                info!("Impersonating {} on '{}'", address, config.baals.network.name);
+            } else {
+                info!("Already impersonating {}", address);
+            }
+        }
+        ImpersonateAction::Stop { address: Some(address) } => {
+            if !session.stop(address) {
+                warn!("{} was not being impersonated", address);
+            }
+        }
+        ImpersonateAction::Stop { address: None } => session.stop_all(),
+        ImpersonateAction::List => {
+            let accounts: Vec<_> = session.accounts().collect();
+            out.emit("impersonate", serde_json::json!({ "accounts": accounts }), |_| accounts.join("\n"));
+            return Ok(());
+        }
+    }
+    session.save()
+}
+
+fn assets(action: &AssetsAction, config_manager: &ConfigManager, out: &Output) -> CanvasResult<()> {
+    use canvas_contracts::nodes::{
+        custom::{CustomNodeRegistry, CUSTOM_NODES_DIR},
+        AssetServer, AssetStore,
+    };
+
+    let config = config_manager.config();
+    let store = AssetStore::new(AssetStore::default_path(config));
+    match action {
+        AssetsAction::Add { file } => {
+            let asset = store.import_file(std::path::Path::new(file))?;
+            out.emit("assets", serde_json::to_value(&asset)?, |_| {
+                format!("{} ({})", asset.hash, asset.media_type)
+            });
+            Ok(())
+        }
+        AssetsAction::Serve { host, port } => {
+            let nodes_dir = config.app.data_dir.join(CUSTOM_NODES_DIR);
+            let registry = if nodes_dir.exists() {
+                CustomNodeRegistry::load_dir(&nodes_dir)?
+            } else {
+                CustomNodeRegistry::new()
+            };
+            let nodes = canvas_contracts::nodes::NodeRegistry::load(config)?;
+            AssetServer::new(store, nodes, &registry).serve(&format!("{}:{}", host, port))
+        }
+    }
+}
+
+fn webhooks(action: &WebhooksAction, config_manager: &ConfigManager, out: &Output) -> CanvasResult<()> {
+    use canvas_contracts::baals::{
+        events::EventSchemaRegistry,
+        webhooks::{EventWebhooks, FieldFilter, HttpSink, WebhookRule},
+        BaalsClient,
+    };
+
+    let config = config_manager.config();
+    let mut bridge = EventWebhooks::open(&EventWebhooks::default_path(config))?;
+    match action {
+        WebhooksAction::Add { id, contract, event, url, filters, from_block } => {
+            let filters = filters.iter().map(|f| FieldFilter::parse(f)).collect::<CanvasResult<Vec<_>>>()?;
+            let from_block = match from_block {
+                Some(block) => *block,
+                None => BaalsClient::new(config)?.node_status()?.block_number + 1,
+            };
+            let rule = WebhookRule {
+                id: id.clone(),
+                contract: contract.clone(),
+                event: event.clone(),
+                url: url.clone(),
+                filters,
+            };
+            bridge.add_rule(rule, from_block)?;
+            bridge.save()?;
+            info!("Webhook rule '{}' delivers {} events of {} from block {}", id, event, contract, from_block);
+        }
+        WebhooksAction::Remove { id } => {
+            if !bridge.remove_rule(id) {
+                return Err(CanvasError::NotFound(format!("Webhook rule '{}'", id)));
+            }
+            bridge.save()?;
+            info!("Removed webhook rule '{}'", id);
+        }
+        WebhooksAction::List => {
+            let json = serde_json::json!({
+                "rules": bridge.rules(),
+                "pending": bridge.pending(),
+                "failed": bridge.failed(),
+            });
+            out.emit("webhooks", json, |style| {
+                let mut rules = Table::new(["Rule", "Contract", "Event", "Filters", "URL", "Next block"]);
+                for rule in bridge.rules() {
+                    let filters: Vec<String> = rule
+                        .filters
+                        .iter()
+                        .map(|f| format!("{} {:?} {}", f.field, f.op, f.value))
+                        .collect();
+                    rules.add_row([
+                        rule.id.clone(),
+                        rule.contract.clone(),
+                        rule.event.clone(),
+                        filters.join(", "),
+                        rule.url.clone(),
+                        bridge.next_block(&rule.id).map(|b| b.to_string()).unwrap_or_default(),
+                    ]);
+                }
+                let mut rendered = rules.render(style);
+                if !bridge.pending().is_empty() || !bridge.failed().is_empty() {
+                    let mut deliveries = Table::new(["Delivery", "State", "Attempts", "Last error"]);
+                    let queued = bridge.pending().iter().map(|d| (d, "pending"));
+                    for (delivery, state) in queued.chain(bridge.failed().iter().map(|d| (d, "failed"))) {
+                        deliveries.add_row([
+                            delivery.id.clone(),
+                            state.to_string(),
+                            delivery.attempts.to_string(),
+                            delivery.last_error.clone().unwrap_or_default(),
+                        ]);
+                    }
+                    rendered.push('\n');
+                    rendered.push_str(&deliveries.render(style));
+                }
+                rendered
+            });
+        }
+        WebhooksAction::Replay { id, from_block } => {
+            bridge.replay(id, *from_block)?;
+            bridge.save()?;
+            info!("Webhook rule '{}' will deliver events again from block {}", id, from_block);
+        }
+        WebhooksAction::Run { interval, once, retry_failed } => {
+            let client = BaalsClient::new(config)?;
+            let sink = HttpSink::new();
+            if *retry_failed {
+                info!("Retrying {} failed deliveries", bridge.retry_failed(chrono::Utc::now()));
+            }
+            loop {
+                // Reload so schemas registered by deploys since the last poll are used
+                let schemas = EventSchemaRegistry::open(&EventSchemaRegistry::default_path(config))?;
+                let report = bridge.sync(&client, &schemas, &sink, chrono::Utc::now())?;
+                for id in &report.delivered {
+                    info!("Delivered {}", id);
+                }
+                for (id, reason) in &report.retrying {
+                    warn!("Delivery {} failed, will retry: {}", id, reason);
+                }
+                for (id, reason) in &report.given_up {
+                    warn!("Gave up on delivery {}: {}", id, reason);
+                }
+                for (rule, reason) in &report.errors {
+                    warn!("Webhook rule '{}': {}", rule, reason);
+                }
+                if *once {
+                    return Ok(());
+                }
+                std::thread::sleep(std::time::Duration::from_secs(*interval));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn watch_mempool(
+    environment: Option<&str>,
+    max_gas: Option<u64>,
+    webhooks: &[String],
+    interval: u64,
+    once: bool,
+    config_manager: &ConfigManager,
+) -> CanvasResult<()> {
+    use canvas_contracts::{
+        baals::{
+            mempool::{MempoolWatcher, WatchedContract, MEMPOOL_ALERT_RULE},
+            BaalsClient,
+        },
+        deployment::{
+            environments::{Environments, ReleaseStore},
+            AlertSeverity,
+        },
+        monitoring::alerts::{AlertEvent, AlertLog},
+    };
+
+    let root = std::path::Path::new(".");
+    let config = match environment {
+        Some(name) => Environments::load(root)?.get(name)?.apply(config_manager.config())?,
+        None => config_manager.config().clone(),
+    };
+    let mut contracts = WatchedContract::from_releases(&ReleaseStore::new(root), environment)?;
+    if contracts.is_empty() {
+        return Err(CanvasError::NotFound("No deployed contracts recorded to watch".to_string()));
+    }
+    for contract in &mut contracts {
+        contract.max_gas = max_gas;
+    }
+    let mut watcher = MempoolWatcher::new(&config, contracts)?;
+    let addresses: Vec<&str> = watcher.contracts().map(|c| c.address.as_str()).collect();
+    info!("Watching pending transactions to {}", addresses.join(", "));
+
+    let client = BaalsClient::new(&config)?;
+    let alerts = AlertLog::new(root);
+    loop {
+        let report = match watcher.poll(&client, chrono::Utc::now()) {
+            Ok(report) => report,
+            // Keep watching through node restarts
+            Err(e) if !once => {
+                warn!("Could not read the mempool: {}", e);
+                std::thread::sleep(std::time::Duration::from_secs(interval));
+                continue;
+            }
+            Err(e) => return Err(e),
+        };
+        for warning in &report.warnings {
+            let message = warning.message();
+            warn!("{}", message);
+            alerts.record(
+                &AlertEvent::new(MEMPOOL_ALERT_RULE, AlertSeverity::Warning, message)
+                    .with_subject(warning.contract.as_str()),
+            )?;
+            for url in webhooks {
+                if let Err(e) = warning.notify(url) {
+                    warn!("Could not notify {}: {}", url, e);
+                }
+            }
+        }
+        for (hash, reason) in &report.errors {
+            warn!("Could not simulate pending {}: {}", hash, reason);
+        }
+        if report.simulated > 0 {
+            info!("Simulated {} pending transaction(s), {} warning(s)", report.simulated, report.warnings.len());
+        }
+        if once {
+            return Ok(());
+        }
+        std::thread::sleep(std::time::Duration::from_secs(interval));
+    }
+}
+
+fn register_event_schema(
+    contract: &str,
+    contract_abi: &canvas_contracts::types::ContractABI,
+    from_block: u64,
+    config_manager: &ConfigManager,
+) -> CanvasResult<()> {
+    use canvas_contracts::baals::events::EventSchemaRegistry;
+
+    let mut registry = EventSchemaRegistry::open(&EventSchemaRegistry::default_path(config_manager.config()))?;
+    let version = registry.register(contract, contract_abi, from_block)?;
+    registry.save()?;
+    info!("Event schema v{} registered for {} from block {}", version, contract, from_block);
+    Ok(())
+}
+
+fn backup_platform(output: &str, workspace: &str, config_manager: &ConfigManager) -> CanvasResult<()> {
+    use canvas_contracts::backup::{create_backup, PlatformState};
+
+    let archive = create_backup(&PlatformState {
+        config: config_manager.config(),
+        community: None,
+        marketplace: None,
+        workspace: Some(std::path::Path::new(workspace)),
+    })?;
+    archive.write(std::path::Path::new(output))?;
+    info!("Backup of {} entries written to {}", archive.entries.len(), output);
+    Ok(())
+}
+
+fn restore_platform(
+    input: &str,
+    workspace: &str,
+    verify_only: bool,
+    config_manager: &mut ConfigManager,
+) -> CanvasResult<()> {
+    use canvas_contracts::backup::{restore_backup, BackupArchive};
+
+    let archive = BackupArchive::read(std::path::Path::new(input))?;
+    if verify_only {
+        archive.verify()?;
+        info!(
+            "Backup {} is intact: {} entries, format v{}, written by v{}",
+            input,
+            archive.entries.len(),
+            archive.format_version,
+            archive.app_version
+        );
+        return Ok(());
+    }
+
+    let restored = restore_backup(&archive, Some(std::path::Path::new(workspace)))?;
+    *config_manager.config_mut() = restored.config;
+    config_manager.save()?;
+    info!("Restored configuration and {} files from {}", restored.files, input);
+    Ok(())
+}
+
+fn export_bundle(
+    dir: &str,
+    output: Option<&str>,
+    name: Option<&str>,
+    obfuscate: bool,
+    compile_nodes: &[String],
+    config_manager: &ConfigManager,
+) -> CanvasResult<()> {
+    use canvas_contracts::bundle::{ObfuscationOptions, ProjectBundle, BUNDLE_EXTENSION};
+
+    let root = std::path::Path::new(dir);
+    let name = match name {
+        Some(name) => name.to_string(),
+        None => std::fs::canonicalize(root)?
+            .file_name()
+            .map_or_else(|| "project".to_string(), |n| n.to_string_lossy().to_string()),
+    };
+    let config = config_manager.config();
+    let nodes_dir = config.app.data_dir.join(canvas_contracts::nodes::custom::CUSTOM_NODES_DIR);
+    let bundle = if obfuscate {
+        let options = compile_nodes
+            .iter()
+            .fold(ObfuscationOptions::new(), |options, id| options.with_compiled_composite(id));
+        ProjectBundle::export_obfuscated(&name, root, Some(&nodes_dir), config, &options)?
+    } else {
+        ProjectBundle::export(&name, root, Some(&nodes_dir), config)?
+    };
+    let output = output.map_or_else(|| format!("{}.{}", name, BUNDLE_EXTENSION), str::to_string);
+    bundle.write(std::path::Path::new(&output))?;
+
+    info!(
+        "Bundled {} graphs ({} annotated), {} custom nodes, {} templates and {} ABIs into {}",
+        bundle.manifest.graphs.len(),
+        bundle.manifest.annotations.len(),
+        bundle.manifest.custom_nodes.len(),
+        bundle.manifest.templates.len(),
+        bundle.manifest.abis.len(),
+        output
+    );
+    if bundle.manifest.obfuscated {
+        info!(
+            "Graphs were obfuscated; {} custom nodes were shipped compiled",
+            bundle.manifest.compiled_nodes.len()
+        );
+    }
+    for node_type in &bundle.manifest.unresolved_nodes {
+        warn!("Node type '{}' could not be bundled", node_type);
+    }
+    Ok(())
+}
+
+fn import_bundle(input: &str, dir: &str, skip_nodes: bool, config_manager: &ConfigManager) -> CanvasResult<()> {
+    use canvas_contracts::bundle::ProjectBundle;
+
+    let bundle = ProjectBundle::read(std::path::Path::new(input))?;
+    let nodes_dir = config_manager
+        .config()
+        .app
+        .data_dir
+        .join(canvas_contracts::nodes::custom::CUSTOM_NODES_DIR);
+    let import = bundle.import(std::path::Path::new(dir), (!skip_nodes).then_some(nodes_dir.as_path()))?;
+
+    info!(
+        "Imported '{}' into {}: {} graphs, {} annotation files, {} templates, {} ABIs",
+        bundle.manifest.name, dir, import.graphs, import.annotations, import.templates, import.abis
+    );
+    if !import.nodes_installed.is_empty() {
+        info!("Installed custom nodes: {}", import.nodes_installed.join(", "));
+    }
+    if !import.nodes_skipped.is_empty() {
+        info!("Already installed: {}", import.nodes_skipped.join(", "));
+    }
+    Ok(())
+}
+
+fn import_event_schema(contract: &str, abi: &str, from_block: u64, config_manager: &ConfigManager) -> CanvasResult<()> {
+    let contract_abi: canvas_contracts::types::ContractABI = serde_json::from_str(&std::fs::read_to_string(abi)?)?;
+    register_event_schema(contract, &contract_abi, from_block, config_manager)
+}
+
+fn set_contract_paused(
+    address: &str,
+    key: &str,
+    paused: bool,
+    config_manager: &ConfigManager,
+) -> CanvasResult<()> {
+    info!("{} contract: {}", if paused { "Pausing" } else { "Unpausing" }, address);
+
+    let config = config_manager.config();
+    let root = std::path::Path::new(".");
+    let signer = signer_from_spec(key, root, config)?;
+
+    let baals_client = canvas_contracts::baals::BaalsClient::new(config)?.with_budgets(GasBudgets::load(root)?);
+
+    let result = if paused {
+        baals_client.pause_contract(address, signer.as_ref())?
+    } else {
+        baals_client.unpause_contract(address, signer.as_ref())?
+    };
+
+    if !result.success {
+        let reason = result
+            .revert_reason
+            .map(|r| r.to_string())
+            .unwrap_or_else(|| "unknown reason".to_string());
+        error!("Transaction reverted: {}", reason);
+        return Err(CanvasError::Baals(format!("Failed to update pause state: {}", reason)));
+    }
+
+    info!("Contract {}", if paused { "paused" } else { "unpaused" });
+    info!("Transaction hash: {}", result.transaction_hash);
+
+    Ok(())
+}
+
+fn start_editor(
+    port: u16,
+    host: &str,
     config_manager: &ConfigManager,
 ) -> CanvasResult<()> {
     info!("Starting visual editor on {}:{}", host, port);
@@ -309,27 +2797,23 @@ fn start_editor(
     Ok(())
 }
 
-fn show_info() -> CanvasResult<()> {
+fn show_info(out: &Output) -> CanvasResult<()> {
     let info = lib_info();
-    println!("Canvas Contracts");
-    println!("===============");
-    println!("Name: {}", info.name);
-    println!("Version: {}", info.version);
-    println!("Description: {}", info.description);
-    println!();
-    println!("Features:");
-    println!("  - Visual smart contract development");
-    println!("  - WASM compilation pipeline");
-    println!("  - BaaLS integration");
-    println!("  - Real-time simulation");
-    println!("  - Cross-platform support");
-
+    out.emit("info", serde_json::to_value(&info)?, |_| {
+        format!(
+            "Canvas Contracts\n===============\nName: {}\nVersion: {}\nDescription: {}\n\nFeatures:\n  \
+             - Visual smart contract development\n  - WASM compilation pipeline\n  - BaaLS integration\n  \
+             - Real-time simulation\n  - Cross-platform support\n",
+            info.name, info.version, info.description
+        )
+    });
     Ok(())
 }
 
 fn validate_graph(
     input: &str,
     config_manager: &ConfigManager,
+    out: &Output,
 ) -> CanvasResult<()> {
     info!("Validating graph: {}", input);
 
@@ -337,8 +2821,8 @@ fn validate_graph(
     let graph_content = std::fs::read_to_string(input)
         .map_err(|e| CanvasError::Io(e))?;
 
-    let graph: canvas_contracts::types::VisualGraph = serde_json::from_str(&graph_content)
-        .map_err(|e| CanvasError::Serialization(e))?;
+    // Deprecated nodes are shimmed to their replacements with a warning
+    let (graph, _) = canvas_contracts::nodes::load_graph(&graph_content)?;
 
     // Create validator
     let validator = canvas_contracts::compiler::Validator::new(config_manager.config())?;
@@ -346,21 +2830,1255 @@ fn validate_graph(
     // Validate the graph
     let validation_result = validator.validate(&graph)?;
 
-    if validation_result.is_valid {
-        info!("Graph validation successful!");
-        if !validation_result.warnings.is_empty() {
-            info!("Warnings:");
-            for warning in &validation_result.warnings {
-                info!("  - {}", warning);
+    let json = serde_json::json!({
+        "status": if validation_result.is_valid { "ok" } else { "invalid" },
+        "input": input,
+        "graph": &graph.name,
+        "valid": validation_result.is_valid,
+        "errors": &validation_result.errors,
+        "warnings": &validation_result.warnings,
+    });
+    out.emit("validate", json, |style| {
+        let headline = if validation_result.is_valid {
+            format!("{} {}", style.green("✔"), style.bold("Graph validation successful"))
+        } else {
+            format!("{} {}", style.red("✘"), style.bold("Graph validation failed"))
+        };
+        let mut diagnostics = Table::new(["Severity", "Message"]);
+        for error in &validation_result.errors {
+            diagnostics.add_row(["error", error.as_str()]);
+        }
+        for warning in &validation_result.warnings {
+            diagnostics.add_row(["warning", warning.as_str()]);
+        }
+        if diagnostics.is_empty() {
+            return headline;
+        }
+        let table = diagnostics.render_with(style, |column, text| match (column, text.trim_end()) {
+            (0, "error") => style.red(text),
+            (0, "warning") => style.yellow(text),
+            _ => text.to_string(),
+        });
+        format!("{}\n\n{}", headline, table)
+    });
+
+    if !validation_result.is_valid {
+        return Err(CanvasError::Validation(format!(
+            "Graph validation failed with {} error(s)",
+            validation_result.errors.len()
+        )));
+    }
+    Ok(())
+}
+
+fn abi_diff(old: &str, new: &str, allow_breaking: bool, out: &Output) -> CanvasResult<()> {
+    let load = |path: &str| -> CanvasResult<canvas_contracts::types::ContractABI> {
+        Ok(serde_json::from_str(&std::fs::read_to_string(path)?)?)
+    };
+    let diff = canvas_contracts::compiler::diff_abi(&load(old)?, &load(new)?);
+
+    if out.is_json() {
+        out.emit("abi-diff", serde_json::to_value(&diff)?, |_| String::new());
+    } else if diff.changes.is_empty() {
+        info!("ABIs are identical");
+    } else {
+        for change in &diff.changes {
+            info!("  {}", change);
+        }
+    }
+
+    if allow_breaking {
+        if diff.is_breaking() {
+            warn!("Breaking changes allowed by --allow-breaking");
+        }
+        return Ok(());
+    }
+    diff.ensure_compatible()
+}
+
+fn audit(
+    dir: &str,
+    feed: Option<&str>,
+    nodes: Option<&str>,
+    config_manager: &ConfigManager,
+    out: &Output,
+) -> CanvasResult<()> {
+    use canvas_contracts::marketplace::{audit_installed, AdvisoryFeed, ADVISORY_FEED_FILE};
+
+    let config = config_manager.config();
+    let feed = feed.map_or_else(|| config.app.data_dir.join(ADVISORY_FEED_FILE), std::path::PathBuf::from);
+    let nodes = nodes.map_or_else(
+        || config.app.data_dir.join(canvas_contracts::nodes::custom::CUSTOM_NODES_DIR),
+        std::path::PathBuf::from,
+    );
+    let feed = AdvisoryFeed::load(&feed)?;
+    let registry = canvas_contracts::nodes::custom::CustomNodeRegistry::load_dir(&nodes)?;
+    let workspace = canvas_contracts::compiler::Workspace::load(std::path::Path::new(dir))?;
+    let report = audit_installed(&registry, &feed, &workspace, &Default::default());
+
+    if out.is_json() {
+        out.emit("audit", serde_json::to_value(&report)?, |_| String::new());
+    } else {
+        info!("Audited {} installed custom nodes", report.audited_nodes);
+        for finding in &report.findings {
+            let advisory = &finding.advisory;
+            warn!("{} {}: {} ({:?})", finding.node_id, finding.installed, advisory.summary, advisory.kind);
+            info!("  advisory: {}", advisory.url.as_deref().unwrap_or(&advisory.id));
+            if !finding.required_by.is_empty() {
+                info!("  required by: {}", finding.required_by.join(", "));
+            }
+            for graph in &finding.affected_graphs {
+                info!("  used by: {}", graph.display());
+            }
+            match (&finding.upgrade_to, advisory.patched.is_empty()) {
+                (Some(version), _) => info!("  upgrade to {}", version),
+                (None, false) => {
+                    let patched: Vec<String> = advisory.patched.iter().map(|r| r.to_string()).collect();
+                    info!("  patched in {}", patched.join(" or "));
+                }
+                (None, true) => info!("  no patched version yet; consider removing the node"),
             }
         }
+    }
+    if report.is_clean() {
+        Ok(())
     } else {
-        error!("Graph validation failed!");
-        for error in &validation_result.errors {
-            error!("  - {}", error);
+        Err(CanvasError::Validation(format!("{} advisories affect installed custom nodes", report.findings.len())))
+    }
+}
+
+fn licenses(dir: &str, nodes: Option<&str>, config_manager: &ConfigManager, out: &Output) -> CanvasResult<()> {
+    use canvas_contracts::marketplace::{check_licenses, LicensePolicy, LICENSE_POLICY_FILE};
+
+    let root = std::path::Path::new(dir);
+    let policy = LicensePolicy::load(root)?;
+    if policy.is_none() {
+        info!("No {} in {}; listing licenses only", LICENSE_POLICY_FILE, root.display());
+    }
+    let nodes = nodes.map_or_else(
+        || config_manager.config().app.data_dir.join(canvas_contracts::nodes::custom::CUSTOM_NODES_DIR),
+        std::path::PathBuf::from,
+    );
+    let registry = canvas_contracts::nodes::custom::CustomNodeRegistry::load_dir(&nodes)?;
+    let workspace = canvas_contracts::compiler::Workspace::load(root)?;
+    let policy = policy.unwrap_or(LicensePolicy {
+        allow_unlicensed: true,
+        ..LicensePolicy::default()
+    });
+    let report = check_licenses(&registry, workspace.graphs(), &policy);
+
+    out.emit("licenses", serde_json::to_value(&report)?, |style| {
+        let mut table = Table::new(["Node", "Version", "License", "Used by", "Violation"]);
+        for entry in &report.entries {
+            let used_by: Vec<String> = entry
+                .required_by
+                .iter()
+                .cloned()
+                .chain(entry.used_by.iter().map(|p| p.display().to_string()))
+                .collect();
+            table.add_row([
+                entry.node_id.clone(),
+                entry.version.to_string(),
+                entry.license.clone().unwrap_or_else(|| "(none)".to_string()),
+                used_by.join(", "),
+                entry.violation.clone().unwrap_or_else(|| "ok".to_string()),
+            ]);
+        }
+        table.render_with(style, |column, text| match (column, text.trim_end()) {
+            (4, "ok") => style.green(text),
+            (4, _) => style.red(text),
+            _ => text.to_string(),
+        })
+    });
+    report.ensure_compliant()
+}
+
+/// Refuse to release when the workspace's custom nodes violate its license policy
+fn ensure_license_policy(root: &std::path::Path, config: &canvas_contracts::config::Config) -> CanvasResult<()> {
+    use canvas_contracts::marketplace::{check_licenses, LicensePolicy};
+
+    let Some(policy) = LicensePolicy::load(root)? else {
+        return Ok(());
+    };
+    let nodes = config.app.data_dir.join(canvas_contracts::nodes::custom::CUSTOM_NODES_DIR);
+    let registry = if nodes.exists() {
+        canvas_contracts::nodes::custom::CustomNodeRegistry::load_dir(&nodes)?
+    } else {
+        canvas_contracts::nodes::custom::CustomNodeRegistry::new()
+    };
+    let workspace = canvas_contracts::compiler::Workspace::load(root)?;
+    check_licenses(&registry, workspace.graphs(), &policy).ensure_compliant()
+}
+
+/// Installed custom nodes with a newer version in the cached marketplace index
+fn cached_marketplace_updates(
+    config: &canvas_contracts::config::Config,
+) -> CanvasResult<Vec<canvas_contracts::monitoring::digest::MarketplaceUpdate>> {
+    let nodes = config.app.data_dir.join(canvas_contracts::nodes::custom::CUSTOM_NODES_DIR);
+    if !nodes.exists() {
+        return Ok(Vec::new());
+    }
+    let registry = canvas_contracts::nodes::custom::CustomNodeRegistry::load_dir(&nodes)?;
+    cached_updates_of(config, &registry)
+}
+
+/// Nodes of `registry` with a newer version in the cached marketplace index
+fn cached_updates_of(
+    config: &canvas_contracts::config::Config,
+    registry: &canvas_contracts::nodes::custom::CustomNodeRegistry,
+) -> CanvasResult<Vec<canvas_contracts::monitoring::digest::MarketplaceUpdate>> {
+    use canvas_contracts::marketplace::{MarketplaceCache, SearchFilters};
+
+    let cache = MarketplaceCache::open(&MarketplaceCache::default_path(config))?;
+    let items: Vec<_> = cache.search("", &SearchFilters::default()).into_iter().cloned().collect();
+    Ok(canvas_contracts::monitoring::digest::marketplace_updates(registry, &items))
+}
+
+fn digest(
+    dir: &str,
+    format: Option<&str>,
+    output: Option<&str>,
+    send: bool,
+    force: bool,
+    watch: bool,
+    config_manager: &ConfigManager,
+    out: &Output,
+) -> CanvasResult<()> {
+    use canvas_contracts::monitoring::digest::{DigestFormat, ReportingService};
+
+    let service = ReportingService::load(std::path::Path::new(dir))?;
+    let format = match format {
+        Some(name) => DigestFormat::from_name(name)
+            .ok_or_else(|| CanvasError::Validation(format!("Unknown digest format '{}'", name)))?,
+        None => service.config().format,
+    };
+
+    loop {
+        let now = chrono::Utc::now();
+        let due = force || service.is_due(now)?;
+        if !watch || due {
+            let updates = cached_marketplace_updates(config_manager.config())?;
+            let digest = service.compile(updates, now)?;
+            if !watch {
+                write_report("digest", digest.render(format), output, out)?;
+            }
+            if (send || watch) && due {
+                let report = service.send(&digest)?;
+                for channel in &report.delivered {
+                    info!("Sent {} to {}", digest.title(), channel);
+                }
+                for (channel, reason) in &report.failed {
+                    warn!("Could not send the digest to {}: {}", channel, reason);
+                }
+            } else if send {
+                let last = service.last_sent()?.map_or_else(|| "never".to_string(), |at| at.to_rfc3339());
+                info!("No digest due (last sent {}); use --force to send anyway", last);
+            }
+        }
+        if !watch {
+            return Ok(());
+        }
+        std::thread::sleep(std::time::Duration::from_secs(15 * 60));
+    }
+}
+
+fn budgets(dir: &str, out: &Output) -> CanvasResult<()> {
+    let budgets = GasBudgets::load(std::path::Path::new(dir))?;
+    let report = budgets.report(chrono::Utc::now())?;
+    out.emit("budgets", serde_json::to_value(&report)?, |style| {
+        if report.is_empty() {
+            return format!("No team budgets in {}", canvas_contracts::monitoring::budgets::BUDGETS_FILE);
+        }
+        let limit = |limit: Option<u64>| limit.map_or_else(|| "-".to_string(), |l| l.to_string());
+        let mut teams = Table::new(["Team", "Used", "Soft limit", "Hard limit", "State"]);
+        let mut members = Table::new(["Team", "Member", "Used"]);
+        for usage in &report {
+            let state = if usage.at_hard_limit() {
+                "blocked"
+            } else if usage.over_soft_limit() {
+                "over soft limit"
+            } else {
+                "ok"
+            };
+            teams.add_row([
+                usage.team.clone(),
+                usage.used.to_string(),
+                limit(usage.soft_limit),
+                limit(usage.hard_limit),
+                state.to_string(),
+            ]);
+            for (member, used) in &usage.members {
+                members.add_row([usage.team.clone(), member.clone(), used.to_string()]);
+            }
+        }
+        let teams = teams.render_with(style, |column, text| match (column, text.trim_end()) {
+            (4, "ok") => style.green(text),
+            (4, "blocked") => style.red(text),
+            (4, _) => style.yellow(text),
+            _ => text.to_string(),
+        });
+        format!("{}\n{}", teams, members.render(style))
+    });
+    Ok(())
+}
+
+fn self_update(
+    component: Option<&str>,
+    channel: Option<&str>,
+    check: bool,
+    rollback: bool,
+    config_manager: &ConfigManager,
+) -> CanvasResult<()> {
+    use canvas_contracts::update::{Component, NodeCatalog, ReleaseChannel, Updater};
+
+    let config = config_manager.config();
+    let components = match component {
+        Some(name) => vec![Component::from_name(name)
+            .ok_or_else(|| CanvasError::Validation(format!("Unknown component '{}'", name)))?],
+        None => vec![Component::Cli, Component::NodeCatalog],
+    };
+    let binary = std::env::current_exe()?;
+    let catalog_path = NodeCatalog::default_path(config);
+    let path_of = |component: Component| match component {
+        Component::Cli => binary.clone(),
+        Component::NodeCatalog => catalog_path.clone(),
+    };
+
+    if rollback {
+        for component in components {
+            canvas_contracts::update::rollback(&path_of(component))?;
+            info!("Rolled back {:?}", component);
+        }
+        return Ok(());
+    }
+
+    let mut updater = Updater::new(&config.update, std::time::Duration::from_secs(60))?;
+    if let Some(channel) = channel {
+        let channel = ReleaseChannel::from_name(channel)
+            .ok_or_else(|| CanvasError::Validation(format!("Unknown release channel '{}'", channel)))?;
+        updater = updater.with_channel(channel);
+    }
+    let feed = updater.fetch_feed()?;
+    for component in components {
+        let current = match component {
+            Component::Cli => semver::Version::parse(env!("CARGO_PKG_VERSION")).expect("crate version is semver"),
+            Component::NodeCatalog => NodeCatalog::installed(config)?
+                .map_or_else(|| semver::Version::new(0, 0, 0), |catalog| catalog.version),
+        };
+        let Some(release) = updater.check(&feed, component, &current) else {
+            info!("{:?} is up to date ({})", component, current);
+            continue;
+        };
+        info!("{:?} {} is available (installed: {})", component, release.version, current);
+        if !release.notes.is_empty() {
+            info!("  {}", release.notes);
+        }
+        if check {
+            continue;
+        }
+        match component {
+            Component::Cli => updater.install_cli(release, &path_of(component))?,
+            Component::NodeCatalog => updater.install_catalog(release, &path_of(component))?,
+        }
+    }
+    Ok(())
+}
+
+/// Buffer a usage event; a no-op unless telemetry is enabled, and never fatal
+fn record_telemetry(config_manager: &ConfigManager, event: canvas_contracts::telemetry::TelemetryEvent) {
+    use canvas_contracts::telemetry::TelemetryBuffer;
+
+    let config = config_manager.config();
+    if !config.telemetry.enabled {
+        return;
+    }
+    let result = TelemetryBuffer::open(&TelemetryBuffer::default_path(config)).and_then(|mut buffer| {
+        buffer.record(&config.telemetry, event);
+        buffer.save()
+    });
+    if let Err(e) = result {
+        log::debug!("Could not record telemetry: {}", e);
+    }
+}
+
+/// Name of a subcommand as typed on the command line (`abi-diff`), without
+/// any of its arguments
+fn command_name(command: &Commands) -> String {
+    let debug = format!("{:?}", command);
+    let variant = debug.split(|c: char| !c.is_alphanumeric()).next().unwrap_or_default();
+    let mut name = String::new();
+    for (i, c) in variant.chars().enumerate() {
+        if c.is_uppercase() && i > 0 {
+            name.push('-');
+        }
+        name.push(c.to_ascii_lowercase());
+    }
+    name
+}
+
+fn telemetry(action: &TelemetryAction, config_manager: &mut ConfigManager, out: &Output) -> CanvasResult<()> {
+    use canvas_contracts::telemetry::TelemetryBuffer;
+
+    let path = TelemetryBuffer::default_path(config_manager.config());
+    let mut buffer = TelemetryBuffer::open(&path)?;
+    match action {
+        TelemetryAction::Show => {
+            let telemetry = &config_manager.config().telemetry;
+            info!(
+                "Telemetry is {}; endpoint {}",
+                if telemetry.enabled { "enabled" } else { "disabled" },
+                telemetry.endpoint
+            );
+            match buffer.payload() {
+                Some(payload) => {
+                    let json = serde_json::to_value(&payload)?;
+                    let rendered = serde_json::to_string_pretty(&json)?;
+                    out.emit("telemetry", serde_json::json!({ "payload": json }), |_| rendered);
+                }
+                None => info!("No events recorded"),
+            }
+        }
+        TelemetryAction::Enable => {
+            config_manager.set_value("telemetry.enabled", serde_json::Value::Bool(true))?;
+            config_manager.save()?;
+            info!("Telemetry enabled; inspect what is recorded with `telemetry show`");
+        }
+        TelemetryAction::Disable => {
+            config_manager.set_value("telemetry.enabled", serde_json::Value::Bool(false))?;
+            config_manager.save()?;
+            buffer.clear();
+            buffer.save()?;
+            info!("Telemetry disabled and buffered events deleted");
+        }
+        TelemetryAction::Upload => {
+            let sent = buffer.upload(&config_manager.config().telemetry)?;
+            buffer.save()?;
+            info!("Sent {} events", sent);
+        }
+    }
+    Ok(())
+}
+
+fn marketplace(
+    action: &MarketplaceAction,
+    offline: bool,
+    config_manager: &ConfigManager,
+    out: &Output,
+) -> CanvasResult<()> {
+    use canvas_contracts::marketplace::{
+        browse, preview_custom_node, sign_bundle, AuthorKeys, ConflictPolicy, ConflictResolution, LocalMarketplace,
+        MarketplaceCache, MarketplaceClient, MarketplaceItem, MarketplaceRemote, NodePackage, RegistrySet, SearchFilters,
+        SyncQueue, TopBy, UsageEvent, UsageStats, PREVIEW_LIMITS,
+    };
+    use canvas_contracts::nodes::{custom::CUSTOM_NODES_DIR, AssetStore};
+
+    let config = config_manager.config();
+    let offline = offline || config.marketplace.offline;
+    let registries = RegistrySet::from_config(&config.marketplace, std::time::Duration::from_secs(30))?;
+    let remote: Option<&dyn MarketplaceRemote> = if offline { None } else { Some(&registries) };
+    let mut cache = MarketplaceCache::open(&MarketplaceCache::default_path(config))?;
+    let mut queue = SyncQueue::open(&SyncQueue::default_path(config))?;
+
+    match action {
+        MarketplaceAction::Search { query, free } => {
+            let filters = SearchFilters {
+                free_only: *free,
+                ..SearchFilters::default()
+            };
+            let (items, cached) = browse(&mut cache, remote, query, &filters)?;
+            if cached {
+                let fetched = cache.fetched_at.map_or_else(|| "never".to_string(), |at| at.to_rfc3339());
+                warn!("Showing cached results (index fetched {})", fetched);
+            } else {
+                cache.save()?;
+            }
+            let author_keys = AuthorKeys::open(&AuthorKeys::default_path(config))?;
+            let json = serde_json::json!({
+                "cached": cached,
+                "items": items
+                    .iter()
+                    .map(|item| {
+                        let mut value = serde_json::to_value(item)?;
+                        value["frozen"] = serde_json::json!(item.is_frozen(&author_keys));
+                        Ok(value)
+                    })
+                    .collect::<CanvasResult<Vec<_>>>()?,
+            });
+            out.emit("marketplace", json, |_| {
+                let mut text = String::new();
+                for item in &items {
+                    let registry = item.registry.as_deref().unwrap_or("?");
+                    let frozen = if item.is_frozen(&author_keys) { " [frozen]" } else { "" };
+                    text.push_str(&format!(
+                        "{} {} [{}]{} - {} ({:.1}★)\n",
+                        item.id, item.version, registry, frozen, item.description, item.rating
+                    ));
+                }
+                text
+            });
+        }
+        MarketplaceAction::Refresh => {
+            let remote =
+                remote.ok_or_else(|| CanvasError::InvalidState("Cannot refresh the index while offline".to_string()))?;
+            let count = cache.refresh(remote)?;
+            cache.save()?;
+            info!("Cached {} marketplace items", count);
+        }
+        MarketplaceAction::Registries => {
+            let json = serde_json::json!({
+                "registries": registries
+                    .registries()
+                    .iter()
+                    .map(|registry| {
+                        let config = &registry.config;
+                        serde_json::json!({
+                            "name": config.name,
+                            "location": config.location,
+                            "priority": config.priority,
+                            "namespace": config.namespace,
+                            "signed": !config.trusted_keys.is_empty(),
+                        })
+                    })
+                    .collect::<Vec<_>>(),
+            });
+            out.emit("marketplace", json, |_| {
+                let mut text = String::new();
+                for registry in registries.registries() {
+                    let config = &registry.config;
+                    let namespace =
+                        config.namespace.as_deref().map(|n| format!(" namespace {}/", n)).unwrap_or_default();
+                    let signed = if config.trusted_keys.is_empty() { "" } else { " signed" };
+                    text.push_str(&format!(
+                        "{:>4} {} {}{}{}\n",
+                        config.priority, config.name, config.location, namespace, signed
+                    ));
+                }
+                text
+            });
+        }
+        MarketplaceAction::Queue => {
+            if queue.is_empty() {
+                info!("Nothing queued");
+            }
+            let pending = queue.pending().iter().map(serde_json::to_value).collect::<Result<Vec<_>, _>>()?;
+            let lines = pending.iter().map(|queued| format!("{}\n", queued)).collect::<String>();
+            out.emit("marketplace", serde_json::json!({ "queued": pending }), |_| lines);
+        }
+        MarketplaceAction::Sync { on_conflict } => {
+            let policy = ConflictPolicy::from_name(on_conflict)
+                .ok_or_else(|| CanvasError::Validation(format!("Unknown conflict policy '{}'", on_conflict)))?;
+            let remote =
+                remote.ok_or_else(|| CanvasError::InvalidState("Cannot sync while offline".to_string()))?;
+            let report = queue.sync(remote, policy);
+            queue.save()?;
+            let report = report?;
+            info!("Synced {} queued actions", report.applied.len());
+            for conflict in &report.conflicts {
+                warn!("{} ({}): {:?}, {:?}", conflict.action, conflict.item_id, conflict.reason, conflict.resolution);
+            }
+            if report.unsent > 0 {
+                warn!("{} actions left queued; the marketplace is unreachable", report.unsent);
+            }
+            if report.conflicts.iter().any(|c| c.resolution == ConflictResolution::Deferred) {
+                info!("Resolve deferred conflicts with --on-conflict keep-local or keep-remote");
+            }
+        }
+        MarketplaceAction::Discard { id } => {
+            if !queue.discard(*id) {
+                return Err(CanvasError::NotFound(format!("No queued action {}", id)));
+            }
+            queue.save()?;
+            info!("Discarded {}", id);
+        }
+        MarketplaceAction::Remove { id, force } => {
+            if cache.get_item(id).is_none() {
+                return Err(CanvasError::NotFound(format!("No cached marketplace item {}", id)));
+            }
+            out.confirm(&format!("remove marketplace item '{}' and its queued actions", id), id, *force)?;
+            cache.remove(id);
+            let dropped = queue.discard_item(id);
+            cache.save()?;
+            queue.save()?;
+            info!("Removed {} ({} queued actions dropped)", id, dropped);
+        }
+        MarketplaceAction::Install { id, allow_unverified, force } => {
+            if offline {
+                return Err(CanvasError::InvalidState("Cannot install items while offline".to_string()));
+            }
+            let mut client = MarketplaceClient::new(config.marketplace.api_url.clone())
+                .with_author_keys(AuthorKeys::open(&AuthorKeys::default_path(config))?);
+            if *allow_unverified {
+                client = client.allow_unverified();
+            }
+            let mut installed = LocalMarketplace::new();
+            let (content, verification) = futures::executor::block_on(client.install_item(id, &mut installed))?;
+            if installed.get_custom_node(id).is_none() {
+                return Err(CanvasError::Validation(format!(
+                    "'{}' is not a custom node; templates are used from the marketplace directly",
+                    id
+                )));
+            }
+            let preview = preview_custom_node(serde_json::from_slice(&content)?, PREVIEW_LIMITS)?;
+            if !preview.passed() {
+                return Err(CanvasError::Validation(format!("'{}' failed its tests in the sandbox", id)));
+            }
+            if !preview.requested_capabilities.is_empty() {
+                let capabilities: Vec<String> =
+                    preview.requested_capabilities.iter().map(|c| format!("{:?}", c)).collect();
+                out.confirm(&format!("grant '{}' the capabilities {}", id, capabilities.join(", ")), id, *force)?;
+            }
+            preview.install(
+                &config.app.data_dir.join(CUSTOM_NODES_DIR),
+                &AssetStore::new(AssetStore::default_path(config)),
+            )?;
+            let json = serde_json::json!({
+                "id": id,
+                "version": preview.item.version,
+                "verified": verification.is_verified(),
+            });
+            out.emit("marketplace-install", json, |_| {
+                format!("Installed {} {}", id, preview.item.version)
+            });
+        }
+        MarketplaceAction::Updates => {
+            use canvas_contracts::nodes::custom::{CustomNodeRegistry, NodeUpdate};
+
+            let nodes_dir = config.app.data_dir.join(CUSTOM_NODES_DIR);
+            let registry = if nodes_dir.is_dir() {
+                CustomNodeRegistry::load_dir(&nodes_dir)?
+            } else {
+                CustomNodeRegistry::new()
+            };
+            let candidates = cached_updates_of(config, &registry)?;
+            // Changelogs ship inside the packages, so they are only known after a download
+            let mut latest = LocalMarketplace::new();
+            if !offline {
+                let client = MarketplaceClient::new(config.marketplace.api_url.clone())
+                    .with_author_keys(AuthorKeys::open(&AuthorKeys::default_path(config))?);
+                for candidate in &candidates {
+                    let package = futures::executor::block_on(client.download_item(&candidate.node_id))
+                        .and_then(|content| Ok(serde_json::from_slice::<NodePackage>(&content)?))
+                        .and_then(|package| latest.add_custom_node(package.item));
+                    if let Err(e) = package {
+                        warn!("No changelog for {}: {}", candidate.node_id, e);
+                    }
+                }
+            }
+            let detailed = latest.available_updates(&registry);
+            let updates: Vec<NodeUpdate> = candidates
+                .iter()
+                .map(|candidate| {
+                    detailed
+                        .iter()
+                        .find(|update| update.node_id == candidate.node_id)
+                        .cloned()
+                        .unwrap_or_else(|| NodeUpdate {
+                            node_id: candidate.node_id.clone(),
+                            installed: candidate.installed.clone(),
+                            available: candidate.available.clone(),
+                            breaking: NodeUpdate::is_breaking(&candidate.installed, &candidate.available),
+                            changelog: Vec::new(),
+                        })
+                })
+                .collect();
+            out.emit("marketplace-updates", serde_json::json!({ "updates": updates }), |style| {
+                if updates.is_empty() {
+                    return "All custom nodes are up to date".to_string();
+                }
+                let mut table = Table::new(["Node", "Installed", "Available", "Breaking"]);
+                for update in &updates {
+                    table.add_row([
+                        update.node_id.clone(),
+                        update.installed.to_string(),
+                        update.available.to_string(),
+                        if update.breaking { "yes" } else { "no" }.to_string(),
+                    ]);
+                }
+                let mut text = table.render(style);
+                for update in updates.iter().filter(|update| !update.changelog.is_empty()) {
+                    text.push_str(&format!("\n{}:\n", update.node_id));
+                    for entry in &update.changelog {
+                        text.push_str(&format!("  {}  {}\n", entry.version, entry.notes));
+                    }
+                }
+                text
+            });
+        }
+        MarketplaceAction::Trending { limit } => {
+            let stats = UsageStats::open(&UsageStats::default_path(config))?;
+            let local = cached_listings(&cache);
+            let items: Vec<MarketplaceItem> = if offline {
+                local.trending_items(&stats, *limit).into_iter().cloned().collect()
+            } else {
+                let client = MarketplaceClient::new(config.marketplace.api_url.clone());
+                futures::executor::block_on(client.trending_items_or_local(&local, &stats, *limit as u32))?
+            };
+            emit_listings(out, &items.iter().collect::<Vec<_>>());
+        }
+        MarketplaceAction::Top { by, days, limit } => {
+            let by = TopBy::from_name(by)
+                .ok_or_else(|| CanvasError::Validation(format!("Unknown ranking '{}'", by)))?;
+            let stats = UsageStats::open(&UsageStats::default_path(config))?;
+            let local = cached_listings(&cache);
+            emit_listings(out, &local.top_items(&stats, by, chrono::Duration::days(*days), *limit));
+        }
+        MarketplaceAction::New { limit } => {
+            emit_listings(out, &cached_listings(&cache).new_items(*limit));
+        }
+        MarketplaceAction::Record { id, event, stars } => {
+            let event = match (event.as_str(), stars) {
+                ("download", None) => UsageEvent::Download,
+                ("install", None) => UsageEvent::Install,
+                ("rating", Some(stars)) => UsageEvent::Rating { stars: *stars },
+                _ => {
+                    return Err(CanvasError::Validation(
+                        "Record a download, an install, or a rating with --stars".to_string(),
+                    ))
+                }
+            };
+            let mut stats = UsageStats::open(&UsageStats::default_path(config))?;
+            cached_listings(&cache).record_usage(&mut stats, id, event)?;
+            stats.save()?;
+            info!("Recorded {:?} of {}", event, id);
+        }
+        MarketplaceAction::Sign { listing, bundle, key } => {
+            let mut item: MarketplaceItem = serde_json::from_str(&std::fs::read_to_string(listing)?)?;
+            let signer = signer_from_spec(key, std::path::Path::new("."), config)?;
+            sign_bundle(&mut item, &std::fs::read(bundle)?, signer.as_ref())?;
+            std::fs::write(listing, serde_json::to_string_pretty(&item)?)?;
+            info!("Signed {} {} as {}", item.id, item.version, signer.address()?);
+        }
+        MarketplaceAction::Trust { author, key } => {
+            let mut keys = AuthorKeys::open(&AuthorKeys::default_path(config))?;
+            if keys.trust(author, key)? {
+                keys.save()?;
+                info!("Trusting {} to sign items of '{}'", key, author);
+            } else {
+                info!("{} is already trusted for '{}'", key, author);
+            }
+        }
+        MarketplaceAction::Untrust { author, key } => {
+            let mut keys = AuthorKeys::open(&AuthorKeys::default_path(config))?;
+            if !keys.revoke(author, key) {
+                return Err(CanvasError::NotFound(format!("{} is not a trusted key of '{}'", key, author)));
+            }
+            keys.save()?;
+            info!("No longer trusting {} for '{}'", key, author);
+        }
+        MarketplaceAction::Keys => {
+            let keys = AuthorKeys::open(&AuthorKeys::default_path(config))?;
+            let json: serde_json::Map<String, serde_json::Value> =
+                keys.authors().map(|(author, keys)| (author.to_string(), serde_json::json!(keys))).collect();
+            out.emit("author-keys", serde_json::Value::Object(json), |style| {
+                let mut table = Table::new(["Author", "Public key"]);
+                for (author, keys) in keys.authors() {
+                    for key in keys {
+                        table.add_row([author.to_string(), key.clone()]);
+                    }
+                }
+                table.render(style)
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Listings of the cached marketplace index, for rankings by the local usage statistics
+fn cached_listings(
+    cache: &canvas_contracts::marketplace::MarketplaceCache,
+) -> canvas_contracts::marketplace::LocalMarketplace {
+    use canvas_contracts::marketplace::{LocalMarketplace, SearchFilters};
+
+    let mut local = LocalMarketplace::new();
+    for item in cache.search("", &SearchFilters::default()) {
+        local.add_listing(item.clone());
+    }
+    local
+}
+
+fn emit_listings(out: &Output, items: &[&canvas_contracts::marketplace::MarketplaceItem]) {
+    out.emit("marketplace", serde_json::json!({ "items": items }), |_| {
+        let mut text = String::new();
+        for item in items {
+            text.push_str(&format!(
+                "{} {} - {} ({:.1}★, {} downloads)\n",
+                item.id, item.version, item.description, item.rating, item.downloads
+            ));
+        }
+        text
+    });
+}
+
+fn search_workspace(pattern: &str, dir: &str, kind: &str, out: &Output) -> CanvasResult<()> {
+    use canvas_contracts::compiler::{search_workspace, SearchQuery, Workspace};
+
+    let query = SearchQuery::parse(kind, pattern)
+        .ok_or_else(|| CanvasError::Validation(format!("Unknown search kind '{}'", kind)))?;
+    let hits = search_workspace(&Workspace::load(std::path::Path::new(dir))?, &query);
+    out.emit("search", serde_json::json!({ "status": "ok", "hits": &hits }), |style| {
+        hits.iter()
+            .map(|hit| {
+                format!(
+                    "{}:{}: {} {}={}\n",
+                    style.bold(&hit.path.display().to_string()),
+                    hit.node_id,
+                    hit.node_type,
+                    style.dim(&hit.field),
+                    hit.value
+                )
+            })
+            .collect()
+    });
+    // Like grep, finding nothing is a failure so scripts can branch on it
+    if hits.is_empty() {
+        return Err(CanvasError::NotFound(format!("No nodes in {} match '{}'", dir, pattern)));
+    }
+    Ok(())
+}
+
+fn slots(input: &str, fills: &[String], out: &Output) -> CanvasResult<()> {
+    use canvas_contracts::compiler::{set_slot_fill, template_slots};
+
+    let (mut template, _) = canvas_contracts::nodes::load_graph(&std::fs::read_to_string(input)?)?;
+    let root = std::path::Path::new(input).parent().unwrap_or_else(|| std::path::Path::new("."));
+    for fill in fills {
+        let (name, path) = fill
+            .split_once('=')
+            .ok_or_else(|| CanvasError::Validation(format!("Expected NAME=PATH, got '{}'", fill)))?;
+        let (graph, _) = canvas_contracts::nodes::load_graph(&std::fs::read_to_string(root.join(path))?)?;
+        set_slot_fill(&mut template, name, &graph)?;
+    }
+    if !fills.is_empty() {
+        std::fs::write(input, serde_json::to_string_pretty(&template)?)?;
+    }
+
+    let slots = template_slots(&template)?;
+    out.emit("slots", serde_json::json!({ "status": "ok", "slots": &slots }), |style| {
+        let mut table = Table::new(["Slot", "Inputs", "Outputs", "Filled by", "Hint"]);
+        for slot in &slots {
+            let ports = |ports: &[canvas_contracts::types::Port]| {
+                ports.iter().map(|p| format!("{}: {:?}", p.id, p.value_type)).collect::<Vec<_>>().join(", ")
+            };
+            table.add_row([
+                slot.name.clone(),
+                ports(&slot.inputs),
+                ports(&slot.outputs),
+                slot.fill.map_or_else(|| "-".to_string(), |id| id.to_string()),
+                slot.hint.clone().unwrap_or_default(),
+            ]);
+        }
+        table.render_with(style, |column, text| match (column, text.trim_end()) {
+            (3, "-") => style.yellow(text),
+            _ => text.to_string(),
+        })
+    });
+    Ok(())
+}
+
+fn rename(kind: &str, from: &str, to: &str, dir: &str, dry_run: bool, out: &Output) -> CanvasResult<()> {
+    use canvas_contracts::compiler::{plan_rename, RenameKind};
+
+    let kind = RenameKind::parse(kind)
+        .ok_or_else(|| CanvasError::Validation(format!("Unknown rename kind '{}'", kind)))?;
+    let plan = plan_rename(std::path::Path::new(dir), kind, from, to)?;
+    let written = if dry_run { 0 } else { plan.apply()? };
+    let json = serde_json::json!({ "status": "ok", "dry_run": dry_run, "files_written": written, "plan": &plan });
+    out.emit("rename", json, |style| {
+        let mut text: String = plan
+            .edits
+            .iter()
+            .map(|edit| format!("{}: {}\n", style.bold(&edit.path.display().to_string()), edit.location))
+            .collect();
+        let files = plan.files().count();
+        text.push_str(&if dry_run {
+            format!("Dry run: {} location(s) in {} file(s) would change\n", plan.edits.len(), files)
+        } else {
+            format!("Renamed '{}' to '{}' in {} location(s) across {} file(s)\n", from, to, plan.edits.len(), written)
+        });
+        for environment in &plan.stale_releases {
+            text.push_str(&format!(
+                "{} latest release in '{}' still uses '{}'; redeploy to pick up the new name\n",
+                style.yellow("warning:"),
+                environment,
+                from
+            ));
+        }
+        text
+    });
+    Ok(())
+}
+
+fn call_graph(dir: &str, format: &str, upgrade: Option<&str>, out: &Output) -> CanvasResult<()> {
+    use canvas_contracts::compiler::{CallGraph, Workspace};
+
+    let calls = CallGraph::build(&Workspace::load(std::path::Path::new(dir))?);
+    if let Some(upgrade) = upgrade {
+        let (id, name) = calls
+            .contracts
+            .iter()
+            .find(|(id, name)| id.to_string() == upgrade || name.as_str() == upgrade)
+            .ok_or_else(|| CanvasError::NotFound(format!("No contract '{}' in {}", upgrade, dir)))?;
+        let affected = calls.blast_radius(*id);
+        info!("Upgrading {} affects {} contract(s)", name, affected.len());
+        let names: Vec<String> = affected
+            .iter()
+            .map(|id| calls.contracts.get(id).map_or_else(|| id.to_string(), String::clone))
+            .collect();
+        let json = serde_json::json!({ "upgrade": name, "affected": affected, "names": names });
+        out.emit("call-graph", json, |_| names.join("\n"));
+        return Ok(());
+    }
+    let rendered = match format {
+        "dot" => calls.to_dot(),
+        "mermaid" => calls.to_mermaid(),
+        "json" => serde_json::to_string_pretty(&calls)?,
+        other => return Err(CanvasError::Validation(format!("Unknown call graph format '{}'", other))),
+    };
+    out.emit("call-graph", serde_json::to_value(&calls)?, |_| rendered);
+    Ok(())
+}
+
+fn migrate_nodes(input: &str, output: Option<&str>, dry_run: bool) -> CanvasResult<()> {
+    info!("Migrating deprecated nodes in {}", input);
+
+    let graph_content = std::fs::read_to_string(input)
+        .map_err(|e| CanvasError::Io(e))?;
+
+    let graph: canvas_contracts::types::VisualGraph = serde_json::from_str(&graph_content)
+        .map_err(|e| CanvasError::Serialization(e))?;
+
+    let (migrated, report) = canvas_contracts::nodes::migrate_graph(&graph);
+
+    if report.is_empty() {
+        info!("No deprecated nodes found");
+        return Ok(());
+    }
+
+    for migration in &report.migrated {
+        info!("  - {}: {} -> {}", migration.node_id, migration.from, migration.to);
+    }
+    for warning in &report.warnings {
+        warn!("  - {}", warning);
+    }
+
+    if dry_run {
+        info!("Dry run: {} node(s) would be migrated", report.migrated.len());
+        return Ok(());
+    }
+
+    let output = output.unwrap_or(input);
+    let content = serde_json::to_string_pretty(&migrated)
+        .map_err(|e| CanvasError::Serialization(e))?;
+    std::fs::write(output, content)
+        .map_err(|e| CanvasError::Io(e))?;
+
+    info!("Migrated {} node(s), written to {}", report.migrated.len(), output);
+    Ok(())
+}
+
+fn run_node_tests(
+    id: &str,
+    dir: Option<&str>,
+    format: Option<&str>,
+    output: Option<&str>,
+    config_manager: &ConfigManager,
+    out: &Output,
+) -> CanvasResult<()> {
+    let config = config_manager.config();
+    let dir = match dir {
+        Some(dir) => std::path::PathBuf::from(dir),
+        None => config.app.data_dir.join(canvas_contracts::nodes::custom::CUSTOM_NODES_DIR),
+    };
+    info!("Running tests of custom node '{}' from {}", id, dir.display());
+
+    let registry = canvas_contracts::nodes::custom::CustomNodeRegistry::load_dir(&dir)?
+        .with_default_limits(canvas_contracts::nodes::custom::ResourceLimits::from_config(&config.runtime));
+    let report = registry.run_node_tests(id)?;
+    if let Some(format) = format {
+        let reporter = canvas_contracts::testing::reporter_for(format)?;
+        write_report("node-test", reporter.render(&[(&report).into()])?, output, out)?;
+    }
+
+    if report.results.is_empty() {
+        warn!("Custom node '{}' has no test cases", id);
+        return Ok(());
+    }
+
+    for result in &report.results {
+        match &result.failure {
+            None => info!("  ok    {}", result.name),
+            Some(failure) => error!("  FAIL  {}: {}", result.name, failure),
+        }
+    }
+
+    let failed = report.failures().len();
+    if failed > 0 {
+        return Err(CanvasError::Validation(format!(
+            "{} of {} test(s) failed for custom node '{}'",
+            failed,
+            report.results.len(),
+            id
+        )));
+    }
+
+    info!("All {} test(s) passed", report.results.len());
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+fn run_ci(
+    dir: &str,
+    graphs: &[String],
+    gas_baseline: &str,
+    gas_tolerance: f64,
+    update_gas_baseline: bool,
+    format: &str,
+    output: Option<&str>,
+    jobs: usize,
+    config_manager: &ConfigManager,
+    out: &Output,
+) -> CanvasResult<i32> {
+    use canvas_contracts::{
+        deployment::AlertSeverity,
+        monitoring::{
+            alerts::{AlertEvent, AlertLog},
+            gas_history::GasHistory,
+        },
+        testing::{discover_graphs, discover_scenarios, CiOptions},
+    };
+
+    let root = std::path::Path::new(dir);
+    let baseline = root.join(gas_baseline);
+    let graphs = if graphs.is_empty() {
+        discover_graphs(root, &[baseline.as_path()])?
+    } else {
+        graphs.iter().map(std::path::PathBuf::from).collect()
+    };
+    let options = CiOptions {
+        graphs,
+        scenarios: discover_scenarios(root)?,
+        gas_baseline: Some(baseline),
+        gas_tolerance_percent: gas_tolerance,
+        update_gas_baseline,
+        jobs,
+        license_policy: canvas_contracts::marketplace::LicensePolicy::load(root)?,
+        nodes_dir: Some(config_manager.config().app.data_dir.join(canvas_contracts::nodes::custom::CUSTOM_NODES_DIR)),
+        ..CiOptions::default()
+    };
+    info!(
+        "Running CI on {} graph(s) and {} scenario(s)",
+        options.graphs.len(),
+        options.scenarios.len()
+    );
+
+    let reporter = canvas_contracts::testing::reporter_for(format)?;
+    let report = canvas_contracts::testing::run_ci(config_manager.config(), &options)?;
+    let rendered = reporter.render(&report.test_suites())?;
+    match output {
+        Some(path) => std::fs::write(path, rendered)?,
+        None => {
+            let json = serde_json::json!({ "exit_code": report.exit_code(), "report": report });
+            out.emit("ci", json, |_| rendered);
+        }
+    }
+
+    GasHistory::new(root).record(&report.gas_estimates(), chrono::Utc::now())?;
+    let alerts = AlertLog::new(root);
+    for check in report.failures() {
+        let failure = check.failure.as_deref().unwrap_or_default();
+        error!("  FAIL  [{}] {}: {}", check.stage.name(), check.target, failure);
+        let alert = AlertEvent::new(format!("ci:{}", check.stage.name()), AlertSeverity::Warning, failure)
+            .with_subject(check.target.as_str());
+        alerts.record(&alert)?;
+    }
+    let code = report.exit_code();
+    if code == 0 {
+        info!("All {} check(s) passed", report.checks.len());
+    }
+    Ok(code)
+}
+
+/// Write a rendered report to a file, or print it as the result of `command`
+fn write_report(command: &str, rendered: String, output: Option<&str>, out: &Output) -> CanvasResult<()> {
+    match output {
+        Some(path) => std::fs::write(path, rendered)?,
+        None => out.emit(command, serde_json::json!({ "report": rendered }), |_| rendered),
+    }
+    Ok(())
+}
+
+fn fuzz_contract(
+    contract: &str,
+    graph: Option<&str>,
+    runs: usize,
+    max_steps: usize,
+    gas_limit: u64,
+    seed: Option<u64>,
+    dir: Option<&str>,
+    config_manager: &ConfigManager,
+    out: &Output,
+) -> CanvasResult<()> {
+    use canvas_contracts::{
+        compiler::TraceMap,
+        testing::{responsible_nodes, FuzzOptions, Fuzzer, SCENARIO_EXTENSION},
+        types::{ContractABI, VisualGraph},
+        wasm::WasmRuntime,
+    };
+    use std::path::{Path, PathBuf};
+
+    let wasm_bytes = std::fs::read(contract)?;
+    let abi_path = contract.replace(".wasm", ".abi.json");
+    let abi_content = std::fs::read_to_string(&abi_path)
+        .map_err(|e| CanvasError::Validation(format!("Fuzzing needs the contract ABI at {}: {}", abi_path, e)))?;
+    let abi: ContractABI = serde_json::from_str(&abi_content)?;
+    let runtime = WasmRuntime::new(config_manager.config())?;
+    let options = FuzzOptions {
+        runs,
+        max_steps,
+        gas_limit,
+        seed: seed.unwrap_or_else(|| chrono::Utc::now().timestamp_millis() as u64),
+        ..FuzzOptions::default()
+    };
+    info!("Fuzzing {} with {} run(s), seed {}", contract, runs, options.seed);
+    let fuzzer = Fuzzer::new(&runtime, &wasm_bytes, &abi);
+    let Some(finding) = fuzzer.run(&options)? else {
+        let json = serde_json::json!({"status": "ok", "contract": contract, "runs": runs, "seed": options.seed});
+        out.emit("fuzz", json, |style| {
+            format!("{} {} runs found no failure (seed {})", style.green("✔"), runs, options.seed)
+        });
+        return Ok(());
+    };
+
+    let nodes: Vec<(String, String)> = match (graph, TraceMap::read_from(&abi)?) {
+        (Some(path), Some(map)) => {
+            let graph: VisualGraph = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+            responsible_nodes(&graph, &map, &finding.failure.trace)
+                .into_iter()
+                .map(|id| {
+                    let node_type = graph.get_node(id).map_or("?", |n| n.node_type.as_str());
+                    (id.to_string(), node_type.to_string())
+                })
+                .collect()
+        }
+        (Some(_), None) => {
+            warn!("{} is not instrumented; build it with tracing to locate the failing nodes", contract);
+            Vec::new()
+        }
+        (None, _) => Vec::new(),
+    };
+
+    let contract_path = Path::new(contract);
+    let dir = match dir {
+        Some(dir) => PathBuf::from(dir),
+        None => contract_path.parent().unwrap_or_else(|| Path::new(".")).to_path_buf(),
+    };
+    std::fs::create_dir_all(&dir)?;
+    let stem = contract_path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+    let path = dir.join(format!("{}-fuzz-{}{}", stem, options.seed, SCENARIO_EXTENSION));
+    fuzzer.save_repro(&path, contract_path, &finding)?;
+
+    let failure = &finding.failure;
+    let json = serde_json::json!({
+        "status": "failed",
+        "contract": contract,
+        "seed": options.seed,
+        "run": finding.run,
+        "original_steps": finding.original_steps,
+        "steps": &finding.steps,
+        "shrink_runs": finding.shrink_runs,
+        "failure": failure,
+        "nodes": nodes.iter().map(|(id, node_type)| serde_json::json!({"id": id, "type": node_type})).collect::<Vec<_>>(),
+        "scenario": path.display().to_string(),
+    });
+    out.emit("fuzz", json, |style| {
+        let mut steps = Table::new(["#", "Function", "Args", "Caller", "Value"]);
+        for (index, step) in finding.steps.iter().enumerate() {
+            steps.add_row([
+                (index + 1).to_string(),
+                step.function.clone(),
+                step.args.to_string(),
+                step.caller.clone().unwrap_or_else(|| "-".to_string()),
+                step.value.to_string(),
+            ]);
+        }
+        let mut rendered = format!(
+            "{} Run {} failed: {} reverted with {}: {}\nMinimized {} step(s) to {} in {} run(s)\n\n{}",
+            style.red("✘"),
+            finding.run + 1,
+            failure.function,
+            failure.error,
+            failure.message,
+            finding.original_steps,
+            finding.steps.len(),
+            finding.shrink_runs,
+            steps.render(style)
+        );
+        if !nodes.is_empty() {
+            let mut table = Table::new(["Node", "Type"]);
+            for (id, node_type) in &nodes {
+                table.add_row([id.clone(), node_type.clone()]);
+            }
+            rendered.push_str(&format!("\n\nResponsible nodes\n{}", table.render(style)));
+        }
+        rendered.push_str(&format!("\n\nRepro written to {}", path.display()));
+        rendered
+    });
+    Err(CanvasError::Validation(format!(
+        "{} fails {} with {}; repro at {}",
+        contract,
+        failure.function,
+        failure.error,
+        path.display()
+    )))
+}
+
+fn run_scenarios(
+    paths: &[String],
+    format: Option<&str>,
+    output: Option<&str>,
+    jobs: usize,
+    storage: &str,
+    config_manager: &ConfigManager,
+    out: &Output,
+) -> CanvasResult<()> {
+    use canvas_contracts::testing::{discover_scenarios, run_scenarios_parallel, ParallelOptions, TestSuite};
+
+    let reporter = format.map(canvas_contracts::testing::reporter_for).transpose()?;
+    let mut files = Vec::new();
+    for path in paths {
+        let path = std::path::PathBuf::from(path);
+        if path.is_dir() {
+            files.extend(discover_scenarios(&path)?);
+        } else {
+            files.push(path);
+        }
+    }
+    let options = ParallelOptions::default()
+        .with_jobs(jobs)
+        .with_storage(canvas_contracts::wasm::StorageBackendKind::from_spec(storage)?)
+        .with_progress(out.progress());
+    info!("Running {} scenario(s) on {} worker(s)", files.len(), options.workers(files.len()));
+
+    let mut suites = Vec::new();
+    for outcome in run_scenarios_parallel(config_manager.config(), &files, &options) {
+        let result = outcome?;
+        for (index, step) in result.steps.iter().enumerate() {
+            match &step.failure {
+                None => info!("  ok    {} #{} {} ({} gas)", result.name, index + 1, step.function, step.gas_used),
+                Some(failure) => error!("  FAIL  {} #{} {}: {}", result.name, index + 1, step.function, failure),
+            }
         }
-        return Err(CanvasError::Validation("Graph validation failed".to_string()));
+        suites.push(TestSuite::from(&result));
     }
 
+    if let Some(reporter) = reporter {
+        write_report("test", reporter.render(&suites)?, output, out)?;
+    }
+
+    let failed: usize = suites.iter().map(|s| s.failures()).sum();
+    if failed > 0 {
+        return Err(CanvasError::Validation(format!("{} scenario step(s) failed", failed)));
+    }
+    info!("All scenarios passed");
     Ok(())
-} 
\ No newline at end of file
+}
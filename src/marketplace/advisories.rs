@@ -0,0 +1,265 @@
+//! Security advisories for custom nodes
+//!
+//! The marketplace publishes an advisory feed: versions of custom nodes that
+//! were yanked or have known vulnerabilities, with the ranges that fix them.
+//! An audit checks every installed node against the feed, together with the
+//! nodes it depends on (custom nodes used inside a composite node's
+//! sub-graph), and reports which workspace graphs use an affected node.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, Utc};
+use semver::{Version, VersionReq};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    compiler::Workspace,
+    error::{CanvasError, CanvasResult},
+    nodes::custom::{CustomNodeImplementation, CustomNodeRegistry},
+    types::VisualGraph,
+};
+
+/// File the advisory feed is cached in, under the data directory
+pub const ADVISORY_FEED_FILE: &str = "advisories.json";
+
+/// Why versions are flagged
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AdvisoryKind {
+    /// Withdrawn by the author; should not be installed
+    Yanked,
+    Vulnerability,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AdvisorySeverity {
+    Low,
+    Medium,
+    High,
+    Critical,
+}
+
+/// One entry of the advisory feed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Advisory {
+    pub id: String,
+    /// Custom node the advisory is about
+    pub node_id: String,
+    pub kind: AdvisoryKind,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub severity: Option<AdvisorySeverity>,
+    /// Affected versions
+    pub affected: VersionReq,
+    /// Versions that fix the problem; none if no fix is published yet
+    #[serde(default)]
+    pub patched: Vec<VersionReq>,
+    pub summary: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+}
+
+impl Advisory {
+    pub fn affects(&self, version: &Version) -> bool {
+        self.affected.matches(version) && !self.patched.iter().any(|req| req.matches(version))
+    }
+}
+
+/// Published advisory feed
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AdvisoryFeed {
+    #[serde(default)]
+    pub updated_at: Option<DateTime<Utc>>,
+    pub advisories: Vec<Advisory>,
+}
+
+impl AdvisoryFeed {
+    pub fn load(path: &Path) -> CanvasResult<Self> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| CanvasError::NotFound(format!("Advisory feed {}: {}", path.display(), e)))?;
+        Ok(serde_json::from_str(&content)?)
+    }
+
+    /// Advisories affecting a node version
+    pub fn advisories_for(&self, node_id: &str, version: &Version) -> Vec<&Advisory> {
+        self.advisories
+            .iter()
+            .filter(|a| a.node_id == node_id && a.affects(version))
+            .collect()
+    }
+}
+
+/// An installed node version an advisory applies to
+#[derive(Debug, Clone, Serialize)]
+pub struct AuditFinding {
+    pub node_id: String,
+    pub installed: Version,
+    pub advisory: Advisory,
+    /// Installed nodes that use the affected node through their sub-graphs
+    pub required_by: Vec<String>,
+    /// Workspace graphs using the affected node, directly or through another node
+    pub affected_graphs: Vec<PathBuf>,
+    /// Newest unaffected version the marketplace offers, if any
+    pub upgrade_to: Option<Version>,
+}
+
+/// Outcome of an audit
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct AuditReport {
+    pub audited_nodes: usize,
+    pub findings: Vec<AuditFinding>,
+}
+
+impl AuditReport {
+    pub fn is_clean(&self) -> bool {
+        self.findings.is_empty()
+    }
+}
+
+/// Custom nodes used directly by a node's composite sub-graph
+pub(super) fn direct_dependencies(registry: &CustomNodeRegistry, node_id: &str) -> BTreeSet<String> {
+    let Some(CustomNodeImplementation::Composite { sub_graph }) = registry.get_node(node_id).map(|d| &d.implementation)
+    else {
+        return BTreeSet::new();
+    };
+    match serde_json::from_str::<VisualGraph>(sub_graph) {
+        Ok(graph) => graph_dependencies(registry, &graph),
+        Err(_) => BTreeSet::new(),
+    }
+}
+
+pub(super) fn graph_dependencies(registry: &CustomNodeRegistry, graph: &VisualGraph) -> BTreeSet<String> {
+    graph
+        .nodes
+        .iter()
+        .filter(|n| registry.get_node(&n.node_type).is_some())
+        .map(|n| n.node_type.clone())
+        .collect()
+}
+
+/// Every custom node `roots` use, directly or transitively, including the roots
+pub(super) fn closure(registry: &CustomNodeRegistry, roots: BTreeSet<String>) -> BTreeSet<String> {
+    let mut seen = BTreeSet::new();
+    let mut pending: Vec<String> = roots.into_iter().collect();
+    while let Some(node_id) = pending.pop() {
+        if seen.insert(node_id.clone()) {
+            pending.extend(direct_dependencies(registry, &node_id));
+        }
+    }
+    seen
+}
+
+/// Check installed custom nodes against an advisory feed.
+///
+/// `available` is the newest version of each node the marketplace offers,
+/// used to suggest upgrades.
+pub fn audit_installed(
+    registry: &CustomNodeRegistry,
+    feed: &AdvisoryFeed,
+    workspace: &Workspace,
+    available: &BTreeMap<String, Version>,
+) -> AuditReport {
+    let installed = registry.list_nodes();
+    let uses: BTreeMap<&str, BTreeSet<String>> = installed
+        .iter()
+        .map(|d| (d.id.as_str(), closure(registry, direct_dependencies(registry, &d.id))))
+        .collect();
+    let graph_uses: Vec<(&Path, BTreeSet<String>)> = workspace
+        .graphs()
+        .into_iter()
+        .map(|(path, graph)| (path, closure(registry, graph_dependencies(registry, graph))))
+        .collect();
+
+    let mut findings = Vec::new();
+    for definition in &installed {
+        for advisory in feed.advisories_for(&definition.id, &definition.version) {
+            let upgrade_to = available
+                .get(&definition.id)
+                .filter(|v| **v > definition.version && !advisory.affects(v))
+                .cloned();
+            findings.push(AuditFinding {
+                node_id: definition.id.clone(),
+                installed: definition.version.clone(),
+                advisory: advisory.clone(),
+                required_by: uses
+                    .iter()
+                    .filter(|(_, deps)| deps.contains(&definition.id))
+                    .map(|(id, _)| id.to_string())
+                    .collect(),
+                affected_graphs: graph_uses
+                    .iter()
+                    .filter(|(_, deps)| deps.contains(&definition.id))
+                    .map(|(path, _)| path.to_path_buf())
+                    .collect(),
+                upgrade_to,
+            });
+        }
+    }
+    findings.sort_by(|a, b| {
+        b.advisory
+            .severity
+            .cmp(&a.advisory.severity)
+            .then_with(|| a.node_id.cmp(&b.node_id))
+    });
+    AuditReport {
+        audited_nodes: installed.len(),
+        findings,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        nodes::custom::CustomNodeBuilder,
+        types::{Position, VisualNode},
+    };
+
+    fn graph_using(node_type: &str) -> VisualGraph {
+        let mut graph = VisualGraph::new(node_type);
+        graph.add_node(VisualNode::new(uuid::Uuid::new_v4(), node_type, Position::new(0.0, 0.0)));
+        graph
+    }
+
+    #[test]
+    fn test_audit_follows_composite_dependencies() {
+        let mut registry = CustomNodeRegistry::new();
+        let hasher = CustomNodeBuilder::new("hasher".to_string(), "Hasher".to_string())
+            .version(Version::new(1, 0, 2))
+            .build();
+        let wrapper = CustomNodeBuilder::new("wrapper".to_string(), "Wrapper".to_string())
+            .composite(serde_json::to_string(&graph_using("hasher")).unwrap())
+            .build();
+        registry.register_node(hasher).unwrap();
+        registry.register_node(wrapper).unwrap();
+
+        let mut workspace = Workspace::new();
+        workspace.add_graph("vault.json", graph_using("wrapper")).unwrap();
+        workspace.add_graph("plain.json", graph_using("Add")).unwrap();
+
+        let feed: AdvisoryFeed = serde_json::from_value(serde_json::json!({
+            "advisories": [{
+                "id": "CCA-2024-0001",
+                "node_id": "hasher",
+                "kind": "vulnerability",
+                "severity": "high",
+                "affected": "<1.1.0",
+                "patched": [">=1.0.3, <1.1.0"],
+                "summary": "Truncates input longer than 64 bytes"
+            }]
+        }))
+        .unwrap();
+        let available = BTreeMap::from([("hasher".to_string(), Version::new(1, 0, 3))]);
+        let report = audit_installed(&registry, &feed, &workspace, &available);
+
+        assert_eq!(report.audited_nodes, 2);
+        assert_eq!(report.findings.len(), 1);
+        let finding = &report.findings[0];
+        assert_eq!(finding.required_by, vec!["wrapper".to_string()]);
+        assert_eq!(finding.affected_graphs, vec![PathBuf::from("vault.json")]);
+        assert_eq!(finding.upgrade_to, Some(Version::new(1, 0, 3)));
+    }
+}
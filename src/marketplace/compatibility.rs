@@ -0,0 +1,180 @@
+//! Compatibility matrix of published custom nodes
+//!
+//! An item lists the platform versions it supports in `compatibility`, as
+//! version requirements (`"0.1.0"`, `">=0.1, <0.3"`). On publish, the node is
+//! checked against every bundled toolchain shim inside that range: a shim
+//! describes what one platform release offers custom nodes (the capabilities
+//! it can grant and the budgets it runs them with), and the node's test cases
+//! are run under those conditions. The outcome per version is recorded in the
+//! listing, and a node that fails a version it claims to support is rejected.
+
+use std::collections::BTreeSet;
+
+use semver::{Version, VersionReq};
+use serde::{Deserialize, Serialize};
+
+use super::{preview_custom_node, NodePackage};
+use crate::{
+    error::{CanvasError, CanvasResult},
+    nodes::custom::{check_module_imports, NodeCapability, ResourceLimits},
+};
+
+/// What one platform release offers custom nodes
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolchainShim {
+    pub version: Version,
+    /// Capabilities the release can grant
+    pub capabilities: BTreeSet<NodeCapability>,
+    /// Budgets the release applies to custom node calls by default
+    pub limits: ResourceLimits,
+}
+
+impl ToolchainShim {
+    /// Check a node against this release
+    pub fn check(&self, package: &NodePackage) -> Option<String> {
+        let definition = &package.item.node_definition;
+        let unsupported: Vec<String> = definition
+            .capabilities
+            .iter()
+            .filter(|c| !self.capabilities.contains(c))
+            .map(|c| format!("{:?}", c))
+            .collect();
+        if !unsupported.is_empty() {
+            return Some(format!("capabilities not available: {}", unsupported.join(", ")));
+        }
+
+        let result = package.module_bytes().and_then(|module| match module {
+            Some(module) => check_module_imports(&module, &definition.capabilities.iter().copied().collect()),
+            None => Ok(()),
+        });
+        if let Err(e) = result {
+            return Some(e.to_string());
+        }
+
+        match preview_custom_node(package.clone(), self.limits) {
+            Ok(preview) => {
+                let failures: Vec<String> = preview
+                    .tests
+                    .iter()
+                    .flat_map(|t| t.failures())
+                    .map(|r| format!("{}: {}", r.name, r.failure.as_deref().unwrap_or_default()))
+                    .collect();
+                (!failures.is_empty()).then(|| failures.join("; "))
+            }
+            Err(e) => Some(e.to_string()),
+        }
+    }
+}
+
+/// Shims shipped with this build: the current release
+pub fn bundled_shims() -> Vec<ToolchainShim> {
+    vec![ToolchainShim {
+        version: Version::parse(crate::VERSION).expect("crate version is semver"),
+        capabilities: NodeCapability::ALL.into_iter().collect(),
+        limits: ResourceLimits::default(),
+    }]
+}
+
+/// Outcome of checking an item against one platform version
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CompatibilityResult {
+    pub version: String,
+    /// Why the item failed; `None` when it passed
+    pub failure: Option<String>,
+}
+
+impl CompatibilityResult {
+    pub fn passed(&self) -> bool {
+        self.failure.is_none()
+    }
+}
+
+/// Parse an item's declared compatibility
+pub fn declared_range(compatibility: &[String]) -> CanvasResult<Vec<VersionReq>> {
+    compatibility
+        .iter()
+        .map(|entry| {
+            VersionReq::parse(entry)
+                .map_err(|e| CanvasError::Validation(format!("Invalid compatibility entry '{}': {}", entry, e)))
+        })
+        .collect()
+}
+
+/// Check a node against every shim in its declared range
+pub fn check_compatibility(package: &NodePackage, shims: &[ToolchainShim]) -> CanvasResult<Vec<CompatibilityResult>> {
+    let range = declared_range(&package.item.metadata.compatibility)?;
+    if range.is_empty() {
+        return Err(CanvasError::Validation(format!(
+            "'{}' declares no compatible platform versions",
+            package.item.metadata.id
+        )));
+    }
+    let mut shims: Vec<&ToolchainShim> =
+        shims.iter().filter(|shim| range.iter().any(|req| req.matches(&shim.version))).collect();
+    shims.sort_by(|a, b| a.version.cmp(&b.version));
+    Ok(shims
+        .into_iter()
+        .map(|shim| CompatibilityResult {
+            version: shim.version.to_string(),
+            failure: shim.check(package),
+        })
+        .collect())
+}
+
+/// `Err` unless the item was tested against at least one version and passed all of them
+pub fn ensure_compatible(item_id: &str, results: &[CompatibilityResult]) -> CanvasResult<()> {
+    if results.is_empty() {
+        return Err(CanvasError::Validation(format!(
+            "'{}' declares compatibility only with platform versions that cannot be checked here",
+            item_id
+        )));
+    }
+    let failures: Vec<String> = results
+        .iter()
+        .filter_map(|r| r.failure.as_ref().map(|f| format!("{}: {}", r.version, f)))
+        .collect();
+    if !failures.is_empty() {
+        return Err(CanvasError::Validation(format!(
+            "'{}' fails {} of its declared platform versions: {}",
+            item_id,
+            failures.len(),
+            failures.join("; ")
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checks_only_declared_versions() {
+        let shim = |version: &str, capabilities: &[NodeCapability]| ToolchainShim {
+            version: Version::parse(version).unwrap(),
+            capabilities: capabilities.iter().copied().collect(),
+            limits: ResourceLimits::default(),
+        };
+        let shims = vec![
+            shim("0.1.0", &[]),
+            shim("0.2.0", &[NodeCapability::ReadStorage]),
+            shim("1.0.0", &[NodeCapability::ReadStorage]),
+        ];
+        let mut package = super::super::preview::tests::package();
+        package.item.node_definition.capabilities = vec![NodeCapability::ReadStorage];
+        package.item.metadata.compatibility = vec![">=0.1, <1.0".to_string()];
+
+        let results = check_compatibility(&package, &shims).unwrap();
+        let versions: Vec<&str> = results.iter().map(|r| r.version.as_str()).collect();
+        assert_eq!(versions, vec!["0.1.0", "0.2.0"]);
+        assert!(results[0].failure.as_ref().unwrap().contains("ReadStorage"));
+        assert!(results[1].passed());
+        assert!(ensure_compatible("clamp", &results).is_err());
+
+        package.item.metadata.compatibility = vec!["^0.2".to_string()];
+        let results = check_compatibility(&package, &shims).unwrap();
+        assert!(ensure_compatible("clamp", &results).is_ok());
+        package.item.metadata.compatibility = vec!["2.0.0".to_string()];
+        assert!(ensure_compatible("clamp", &check_compatibility(&package, &shims).unwrap()).is_err());
+    }
+}
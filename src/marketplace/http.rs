@@ -0,0 +1,221 @@
+//! HTTP transport of the marketplace client
+//!
+//! Requests carry the client's API key as a bearer token. Connection failures,
+//! `429 Too Many Requests` and server errors are retried with exponential
+//! backoff, honouring `Retry-After` when the API sends one; other errors fail
+//! at once. Listings come back a page at a time as [`Page`]s.
+//!
+//! The async methods of the client do their blocking I/O, and wait out
+//! their backoff, on tokio's blocking pool when called inside a runtime; under
+//! a plain executor such as the CLI's `block_on` they block the calling
+//! thread, which has nothing else to run.
+
+use std::{io::Read, time::Duration};
+
+use serde::{Deserialize, Serialize};
+
+use super::{MarketplaceClient, MarketplaceItem};
+use crate::{
+    error::{CanvasError, CanvasResult},
+    nodes::decode_hex,
+    wasm::host,
+};
+
+/// Timeout of a marketplace request unless configured otherwise
+pub const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How failed requests are retried
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RetryPolicy {
+    /// Retries after the first attempt
+    pub attempts: u32,
+    /// Delay before the first retry; doubled for each one after
+    pub base_delay: Duration,
+    /// Longest delay between attempts, `Retry-After` included
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            attempts: 3,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(30),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Delay before retry number `retry`, counting from 0
+    pub fn delay(&self, retry: u32) -> Duration {
+        self.base_delay.saturating_mul(2u32.saturating_pow(retry)).min(self.max_delay)
+    }
+}
+
+/// One page of a listing
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    /// Page number, counting from 1
+    #[serde(default)]
+    pub page: u32,
+    /// Items across all pages, when the API reports it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub total: Option<u64>,
+    /// Whether a later page has more items
+    #[serde(default)]
+    pub has_more: bool,
+}
+
+fn status_error(url: &str, status: u16, body: &str) -> CanvasError {
+    let detail = if body.trim().is_empty() { "no details" } else { body.trim() };
+    match status {
+        401 | 403 => CanvasError::PermissionDenied(format!("{} refused the request ({}): {}", url, status, detail)),
+        404 => CanvasError::NotFound(format!("{} not found: {}", url, detail)),
+        _ => CanvasError::Network(format!("{} returned {}: {}", url, status, detail)),
+    }
+}
+
+/// Percent-encode `segment` for use as one segment of a URL path, so item
+/// IDs such as `acme/vault` stay a single segment
+pub(super) fn encode_path_segment(segment: &str) -> String {
+    let mut encoded = String::with_capacity(segment.len());
+    for byte in segment.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => encoded.push(byte as char),
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+/// Run blocking `work` off the async executor: on tokio's blocking pool
+/// inside a runtime, inline otherwise
+async fn unblock<T: Send + 'static>(work: impl FnOnce() -> T + Send + 'static) -> CanvasResult<T> {
+    match tokio::runtime::Handle::try_current() {
+        Ok(runtime) => runtime
+            .spawn_blocking(work)
+            .await
+            .map_err(|e| CanvasError::Network(format!("Marketplace request did not finish: {}", e))),
+        Err(_) => Ok(work()),
+    }
+}
+
+/// Wait `delay` without holding up a runtime's worker thread
+async fn pause(delay: Duration) {
+    match tokio::runtime::Handle::try_current() {
+        Ok(_) => tokio::time::sleep(delay).await,
+        Err(_) => std::thread::sleep(delay),
+    }
+}
+
+/// Whether a failed attempt is worth retrying, after how long if the API
+/// said, and the error to report if not
+struct Failure {
+    retryable: bool,
+    retry_after: Option<Duration>,
+    error: CanvasError,
+}
+
+/// One attempt at `request`, reading the whole response body
+fn attempt(request: ureq::Request, body: Option<&serde_json::Value>) -> Result<Vec<u8>, Failure> {
+    let url = request.url().to_string();
+    let outcome = match body {
+        Some(body) => request.send_json(body.clone()),
+        None => request.call(),
+    };
+    match outcome {
+        Ok(response) => {
+            let mut content = Vec::new();
+            response.into_reader().read_to_end(&mut content).map_err(|e| Failure {
+                retryable: true,
+                retry_after: None,
+                error: CanvasError::Network(format!("Reading the response of {} failed: {}", url, e)),
+            })?;
+            Ok(content)
+        }
+        Err(ureq::Error::Status(status, response)) => {
+            let retry_after = response
+                .header("Retry-After")
+                .and_then(|s| s.trim().parse().ok())
+                .map(Duration::from_secs);
+            let body = response.into_string().unwrap_or_default();
+            Err(Failure {
+                retryable: status == 429 || status >= 500,
+                retry_after,
+                error: status_error(&url, status, &body),
+            })
+        }
+        Err(e) => Err(Failure {
+            retryable: true,
+            retry_after: None,
+            error: CanvasError::Network(format!("Marketplace request failed: {}", e)),
+        }),
+    }
+}
+
+/// Check downloaded content against the SHA-256 hash its listing publishes
+pub fn verify_content(item: &MarketplaceItem, content: &[u8]) -> CanvasResult<()> {
+    let expected = decode_hex(&item.hash).ok_or_else(|| {
+        CanvasError::Validation(format!("Marketplace item '{}' has an invalid content hash '{}'", item.id, item.hash))
+    })?;
+    if host::hash(host::HashAlgorithm::Sha256, content) != expected {
+        return Err(CanvasError::Validation(format!(
+            "Download of '{}' {} does not match its published hash",
+            item.id, item.version
+        )));
+    }
+    Ok(())
+}
+
+impl MarketplaceClient {
+    pub(super) fn request(&self, method: &str, path: &str) -> ureq::Request {
+        let request = self.agent.request(method, &format!("{}{}", self.api_url, path));
+        match &self.api_key {
+            Some(key) => request.set("Authorization", &format!("Bearer {}", key)),
+            None => request,
+        }
+    }
+
+    /// Send `request`, with a JSON body if given, retrying per the client's
+    /// [`RetryPolicy`]; returns the response body
+    pub(super) async fn send(&self, request: ureq::Request, body: Option<serde_json::Value>) -> CanvasResult<Vec<u8>> {
+        let mut retry = 0;
+        loop {
+            let (attempted, sent) = (request.clone(), body.clone());
+            let failure = match unblock(move || attempt(attempted, sent.as_ref())).await? {
+                Ok(content) => return Ok(content),
+                Err(failure) => failure,
+            };
+            if !failure.retryable || retry >= self.retry.attempts {
+                return Err(failure.error);
+            }
+            let delay = failure.retry_after.unwrap_or_else(|| self.retry.delay(retry)).min(self.retry.max_delay);
+            log::warn!("{}; retrying in {:?}", failure.error, delay);
+            pause(delay).await;
+            retry += 1;
+        }
+    }
+
+    /// [`send`](Self::send) a request and parse its JSON response
+    pub(super) async fn send_json<T: serde::de::DeserializeOwned>(
+        &self,
+        request: ureq::Request,
+        body: Option<serde_json::Value>,
+    ) -> CanvasResult<T> {
+        Ok(serde_json::from_slice(&self.send(request, body).await?)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_item_ids_stay_one_path_segment() {
+        assert_eq!(encode_path_segment("clamp-v2_1.0~x"), "clamp-v2_1.0~x");
+        assert_eq!(encode_path_segment("acme/vault"), "acme%2Fvault");
+        assert_eq!(encode_path_segment("../admin?x=1#y"), "..%2Fadmin%3Fx%3D1%23y");
+        assert_eq!(encode_path_segment("café"), "caf%C3%A9");
+    }
+}
@@ -0,0 +1,237 @@
+//! License compliance of installed marketplace items
+//!
+//! A workspace can restrict the licenses of the custom nodes it builds on with
+//! a policy file listing allowed and denied SPDX licenses. The check covers
+//! every installed node a graph uses, together with the nodes those depend on
+//! through their composite sub-graphs, and reports each one with its license
+//! and the constraint it violates, if any. CI and releases fail on violations.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::{Path, PathBuf},
+};
+
+use semver::Version;
+use serde::{Deserialize, Serialize};
+
+use super::advisories::{closure, direct_dependencies, graph_dependencies};
+use crate::{
+    error::{CanvasError, CanvasResult},
+    nodes::custom::CustomNodeRegistry,
+    types::VisualGraph,
+};
+
+/// License policy, relative to the workspace root
+pub const LICENSE_POLICY_FILE: &str = ".canvas/license-policy.json";
+
+/// Licenses a workspace accepts for the custom nodes it uses
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct LicensePolicy {
+    /// SPDX identifiers that may be used; any license not denied is allowed
+    /// when empty
+    #[serde(default)]
+    pub allowed: Vec<String>,
+    /// SPDX identifiers that must not be used
+    #[serde(default)]
+    pub denied: Vec<String>,
+    /// Accept nodes that declare no license
+    #[serde(default)]
+    pub allow_unlicensed: bool,
+}
+
+impl LicensePolicy {
+    /// Policy of the workspace at `root`, if it has one
+    pub fn load(root: &Path) -> CanvasResult<Option<Self>> {
+        let path = root.join(LICENSE_POLICY_FILE);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let content = std::fs::read_to_string(&path)?;
+        let policy = serde_json::from_str(&content)
+            .map_err(|e| CanvasError::Config(format!("Invalid license policy {}: {}", path.display(), e)))?;
+        Ok(Some(policy))
+    }
+
+    /// The constraint `license` violates, if any.
+    ///
+    /// `license` is an SPDX expression: it complies when one of its `OR`
+    /// alternatives does, and an alternative complies when every license it
+    /// joins with `AND` does.
+    pub fn violation(&self, license: Option<&str>) -> Option<String> {
+        let Some(license) = license.map(str::trim).filter(|l| !l.is_empty()) else {
+            return (!self.allow_unlicensed).then(|| "no license declared".to_string());
+        };
+        let alternatives: Vec<Option<String>> = license
+            .split(" OR ")
+            .map(|alternative| {
+                alternative
+                    .split(" AND ")
+                    .map(|id| id.trim_matches(|c: char| c == '(' || c == ')' || c.is_whitespace()))
+                    .find_map(|id| self.license_violation(id))
+            })
+            .collect();
+        if alternatives.iter().any(Option::is_none) {
+            return None;
+        }
+        alternatives.into_iter().flatten().next()
+    }
+
+    fn license_violation(&self, id: &str) -> Option<String> {
+        let listed = |list: &[String]| list.iter().any(|l| l.eq_ignore_ascii_case(id));
+        if listed(&self.denied) {
+            Some(format!("{} is denied", id))
+        } else if !self.allowed.is_empty() && !listed(&self.allowed) {
+            Some(format!("{} is not in the allowed licenses", id))
+        } else {
+            None
+        }
+    }
+}
+
+/// An installed node the workspace uses, with its license
+#[derive(Debug, Clone, Serialize)]
+pub struct LicenseEntry {
+    pub node_id: String,
+    pub version: Version,
+    pub license: Option<String>,
+    /// Installed nodes that use this one through their sub-graphs
+    pub required_by: Vec<String>,
+    /// Workspace graphs using the node, directly or through another node
+    pub used_by: Vec<PathBuf>,
+    /// Policy constraint the license violates
+    pub violation: Option<String>,
+}
+
+/// Outcome of a license check
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct LicenseReport {
+    pub entries: Vec<LicenseEntry>,
+}
+
+impl LicenseReport {
+    pub fn violations(&self) -> Vec<&LicenseEntry> {
+        self.entries.iter().filter(|e| e.violation.is_some()).collect()
+    }
+
+    pub fn is_compliant(&self) -> bool {
+        self.entries.iter().all(|e| e.violation.is_none())
+    }
+
+    /// Fail with one line per violating node
+    pub fn ensure_compliant(&self) -> CanvasResult<()> {
+        let violations: Vec<String> = self
+            .violations()
+            .iter()
+            .map(|e| {
+                format!(
+                    "{} {} ({}): {}",
+                    e.node_id,
+                    e.version,
+                    e.license.as_deref().unwrap_or("unlicensed"),
+                    e.violation.as_deref().unwrap_or_default()
+                )
+            })
+            .collect();
+        if violations.is_empty() {
+            return Ok(());
+        }
+        Err(CanvasError::Validation(format!(
+            "{} custom node(s) violate the license policy: {}",
+            violations.len(),
+            violations.join("; ")
+        )))
+    }
+}
+
+/// Check the licenses of the installed nodes `graphs` use against `policy`
+pub fn check_licenses<'a>(
+    registry: &CustomNodeRegistry,
+    graphs: impl IntoIterator<Item = (&'a Path, &'a VisualGraph)>,
+    policy: &LicensePolicy,
+) -> LicenseReport {
+    let mut used_by: BTreeMap<String, Vec<PathBuf>> = BTreeMap::new();
+    for (path, graph) in graphs {
+        for node_id in closure(registry, graph_dependencies(registry, graph)) {
+            used_by.entry(node_id).or_default().push(path.to_path_buf());
+        }
+    }
+    let uses: BTreeMap<&str, BTreeSet<String>> = used_by
+        .keys()
+        .map(|id| (id.as_str(), closure(registry, direct_dependencies(registry, id))))
+        .collect();
+
+    let entries = used_by
+        .iter()
+        .filter_map(|(node_id, graphs)| {
+            let definition = registry.get_node(node_id)?;
+            Some(LicenseEntry {
+                node_id: node_id.clone(),
+                version: definition.version.clone(),
+                license: definition.license.clone(),
+                required_by: uses
+                    .iter()
+                    .filter(|(id, deps)| **id != node_id.as_str() && deps.contains(node_id))
+                    .map(|(id, _)| id.to_string())
+                    .collect(),
+                used_by: graphs.clone(),
+                violation: policy.violation(definition.license.as_deref()),
+            })
+        })
+        .collect();
+    LicenseReport { entries }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        nodes::custom::CustomNodeBuilder,
+        types::{Position, VisualNode},
+    };
+
+    fn graph_using(node_type: &str) -> VisualGraph {
+        let mut graph = VisualGraph::new(node_type);
+        graph.add_node(VisualNode::new(uuid::Uuid::new_v4(), node_type, Position::new(0.0, 0.0)));
+        graph
+    }
+
+    #[test]
+    fn test_policy_checks_transitive_dependencies() {
+        let mut registry = CustomNodeRegistry::new();
+        let hasher = CustomNodeBuilder::new("hasher".to_string(), "Hasher".to_string())
+            .license("GPL-3.0-only".to_string())
+            .build();
+        let wrapper = CustomNodeBuilder::new("wrapper".to_string(), "Wrapper".to_string())
+            .license("MIT OR Apache-2.0".to_string())
+            .composite(serde_json::to_string(&graph_using("hasher")).unwrap())
+            .build();
+        let unused = CustomNodeBuilder::new("unused".to_string(), "Unused".to_string()).build();
+        for node in [hasher, wrapper, unused] {
+            registry.register_node(node).unwrap();
+        }
+
+        let policy = LicensePolicy {
+            allowed: vec!["Apache-2.0".to_string(), "mit".to_string()],
+            denied: vec!["GPL-3.0-only".to_string()],
+            allow_unlicensed: false,
+        };
+        let vault = graph_using("wrapper");
+        let report = check_licenses(&registry, [(Path::new("vault.json"), &vault)], &policy);
+
+        let ids: Vec<_> = report.entries.iter().map(|e| e.node_id.as_str()).collect();
+        assert_eq!(ids, ["hasher", "wrapper"], "unused nodes are not checked");
+        let hasher = &report.entries[0];
+        assert_eq!(hasher.required_by, vec!["wrapper".to_string()]);
+        assert_eq!(hasher.used_by, vec![PathBuf::from("vault.json")]);
+        assert_eq!(hasher.violation.as_deref(), Some("GPL-3.0-only is denied"));
+        assert_eq!(report.entries[1].violation, None);
+        assert!(report.ensure_compliant().unwrap_err().to_string().contains("hasher"));
+
+        assert_eq!(policy.violation(None).as_deref(), Some("no license declared"));
+        assert_eq!(
+            policy.violation(Some("MIT AND BSD-3-Clause")).as_deref(),
+            Some("BSD-3-Clause is not in the allowed licenses")
+        );
+        assert_eq!(policy.violation(Some("(GPL-3.0-only OR MIT)")), None);
+    }
+}
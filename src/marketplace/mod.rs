@@ -1,15 +1,68 @@
 //! Marketplace system for Canvas Contracts ecosystem
 
 use crate::{
+    baals::signer::Signer,
+    config::ContentRetention,
+    deployment::attestations::FreezeAttestation,
     error::{CanvasError, CanvasResult},
     types::{Graph, Node, NodeId},
-    nodes::custom::CustomNodeDefinition,
+    nodes::{
+        custom::{CustomNodeDefinition, CustomNodeRegistry, NodeCapability, NodeUpdate},
+        encode_hex, AssetStore,
+    },
+    wasm::host,
 };
 
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use chrono::{DateTime, Utc};
 
+mod advisories;
+mod compatibility;
+mod http;
+mod licenses;
+mod moderation;
+mod offline;
+mod preview;
+mod ratings;
+mod recommend;
+mod registries;
+mod signing;
+mod stats;
+
+pub use advisories::{
+    audit_installed, Advisory, AdvisoryFeed, AdvisoryKind, AdvisorySeverity, AuditFinding, AuditReport,
+    ADVISORY_FEED_FILE,
+};
+pub use compatibility::{
+    bundled_shims, check_compatibility, declared_range, ensure_compatible, CompatibilityResult, ToolchainShim,
+};
+pub use http::{verify_content, Page, RetryPolicy, DEFAULT_REQUEST_TIMEOUT};
+pub use licenses::{check_licenses, LicenseEntry, LicensePolicy, LicenseReport, LICENSE_POLICY_FILE};
+pub use moderation::{
+    BytePattern, ContentScanner, FindingSeverity, MalwareSignatures, ModerationRecord, ModerationStatus, ReviewDecision,
+    ScanFinding,
+};
+pub use offline::{
+    browse, ConflictPolicy, ConflictReason, ConflictResolution, HttpMarketplace, MarketplaceCache, MarketplaceRemote,
+    PendingAction, QueuedAction, Submission, SyncConflict, SyncQueue, SyncReport, MARKETPLACE_CACHE_FILE,
+    SYNC_QUEUE_FILE,
+};
+pub use preview::{
+    package_custom_node, preview_custom_node, preview_template, ItemPreview, NodePackage, PreviewContent,
+    PREVIEW_LIMITS,
+};
+#[cfg(test)]
+pub(crate) use preview::tests::package as test_package;
+pub use registries::{
+    item_signing_message, sign_item, FolderMarketplace, Registry, RegistrySet, FOLDER_INDEX_FILE,
+    FOLDER_PACKAGES_DIR, FOLDER_REVIEWS_FILE,
+};
+pub use ratings::{weighted_rating, RatingWeights};
+pub use signing::{bundle_signing_message, sign_bundle, AuthorKeys, PublisherSignature, Verification, AUTHOR_KEYS_FILE};
+pub use recommend::{graph_profile, Recommendation, RecommendationContext};
+pub use stats::{DailyUsage, TopBy, UsageEvent, UsageStats, DEFAULT_HALF_LIFE_DAYS, MARKETPLACE_STATS_FILE};
+
 /// Marketplace item types
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum MarketplaceItemType {
@@ -39,6 +92,34 @@ pub struct MarketplaceItem {
     pub compatibility: Vec<String>, // Supported versions
     pub size_bytes: u64,
     pub hash: String, // Content hash for verification
+    /// Host capabilities a custom node requests, shown before install
+    #[serde(default)]
+    pub capabilities: Vec<NodeCapability>,
+    /// Outcome of checking the item against each declared platform version
+    #[serde(default)]
+    pub compatibility_results: Vec<CompatibilityResult>,
+    /// Registry the item was listed by, when indexes of several are merged
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub registry: Option<String>,
+    /// Registry's 0x-hex ed25519 signature of the item, see [`item_signing_message`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+    /// Publisher's signature of the item's bundle, see [`sign_bundle`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub publisher_signature: Option<PublisherSignature>,
+    /// Attestation that the listed contract's deployment is final
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub frozen: Option<FreezeAttestation>,
+}
+
+impl MarketplaceItem {
+    /// Whether the item carries a freeze attestation signed by a trusted key
+    /// of its author
+    pub fn is_frozen(&self, keys: &AuthorKeys) -> bool {
+        self.frozen
+            .as_ref()
+            .is_some_and(|attestation| attestation.verify(&keys.keys_of(&self.author)).is_ok())
+    }
 }
 
 /// Custom node marketplace item
@@ -152,7 +233,7 @@ pub struct Review {
 }
 
 /// Marketplace search filters
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct SearchFilters {
     pub item_type: Option<MarketplaceItemType>,
     pub tags: Vec<String>,
@@ -165,20 +246,54 @@ pub struct SearchFilters {
     pub date_range: Option<(DateTime<Utc>, DateTime<Utc>)>,
 }
 
-/// Marketplace client
+impl SearchFilters {
+    /// Whether `item` matches a search query under these filters
+    pub fn matches(&self, item: &MarketplaceItem, query: &str) -> bool {
+        // Basic search implementation
+        let query = query.to_lowercase();
+        let matches_query = query.is_empty()
+            || item.name.to_lowercase().contains(&query)
+            || item.description.to_lowercase().contains(&query)
+            || item.tags.iter().any(|tag| tag.to_lowercase().contains(&query));
+
+        let matches_type = self
+            .item_type
+            .as_ref()
+            .map_or(true, |t| std::mem::discriminant(&item.item_type) == std::mem::discriminant(t));
+        let matches_rating = self.min_rating.map_or(true, |r| item.rating >= r);
+        let matches_price = !self.free_only || item.price.is_none();
+
+        matches_query && matches_type && matches_rating && matches_price
+    }
+}
+
+/// Client of the marketplace REST API
 pub struct MarketplaceClient {
     api_url: String,
     api_key: Option<String>,
     cache: HashMap<String, MarketplaceItem>,
+    agent: ureq::Agent,
+    retry: RetryPolicy,
+    /// Keys downloads are checked against
+    author_keys: AuthorKeys,
+    /// Accept downloads whose publisher signature does not verify
+    allow_unverified: bool,
+    /// Key uploads are signed with
+    publisher: Option<Box<dyn Signer>>,
 }
 
 impl MarketplaceClient {
     /// Create a new marketplace client
     pub fn new(api_url: String) -> Self {
         Self {
-            api_url,
+            api_url: api_url.trim_end_matches('/').to_string(),
             api_key: None,
             cache: HashMap::new(),
+            agent: ureq::AgentBuilder::new().timeout(DEFAULT_REQUEST_TIMEOUT).build(),
+            retry: RetryPolicy::default(),
+            author_keys: AuthorKeys::new(),
+            allow_unverified: false,
+            publisher: None,
         }
     }
 
@@ -188,6 +303,84 @@ impl MarketplaceClient {
         self
     }
 
+    pub fn with_timeout(mut self, timeout: std::time::Duration) -> Self {
+        self.agent = ureq::AgentBuilder::new().timeout(timeout).build();
+        self
+    }
+
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Check downloads against these trusted author keys
+    pub fn with_author_keys(mut self, keys: AuthorKeys) -> Self {
+        self.author_keys = keys;
+        self
+    }
+
+    /// Accept downloads that are unsigned or whose signature does not verify,
+    /// with a warning
+    pub fn allow_unverified(mut self) -> Self {
+        self.allow_unverified = true;
+        self
+    }
+
+    /// Sign uploads as their publisher
+    pub fn with_publisher(mut self, signer: Box<dyn Signer>) -> Self {
+        self.publisher = Some(signer);
+        self
+    }
+
+    /// One page of search results, pages counting from 1
+    pub async fn search_page(
+        &self,
+        query: &str,
+        filters: &SearchFilters,
+        page: u32,
+        limit: u32,
+    ) -> CanvasResult<Page<MarketplaceItem>> {
+        log::info!("Searching marketplace for: {}", query);
+        let mut request = self
+            .request("GET", "/items/search")
+            .query("q", query)
+            .query("page", &page.max(1).to_string())
+            .query("limit", &limit.to_string());
+        if let Some(item_type) = &filters.item_type {
+            if let Some(name) = serde_json::to_value(item_type)?.as_str() {
+                request = request.query("type", name);
+            }
+        }
+        for tag in &filters.tags {
+            request = request.query("tag", tag);
+        }
+        if let Some(min_rating) = filters.min_rating {
+            request = request.query("min_rating", &min_rating.to_string());
+        }
+        if let Some(max_price) = filters.max_price {
+            request = request.query("max_price", &max_price.to_string());
+        }
+        if filters.free_only {
+            request = request.query("free_only", "true");
+        }
+        if let Some(author) = &filters.author {
+            request = request.query("author", author);
+        }
+        if let Some(compatibility) = &filters.compatibility {
+            request = request.query("compatibility", compatibility);
+        }
+        if let Some(difficulty) = &filters.difficulty {
+            request = request.query("difficulty", difficulty);
+        }
+        if let Some((from, to)) = &filters.date_range {
+            request = request.query("from", &from.to_rfc3339()).query("to", &to.to_rfc3339());
+        }
+        let mut page: Page<MarketplaceItem> = self.send_json(request, None).await?;
+        // Results the API cannot filter on are filtered here
+        page.items.retain(|item| filters.matches(item, ""));
+        Ok(page)
+    }
+
     /// Search for marketplace items
     pub async fn search_items(
         &self,
@@ -196,11 +389,35 @@ impl MarketplaceClient {
         page: u32,
         limit: u32,
     ) -> CanvasResult<Vec<MarketplaceItem>> {
-        // TODO: Implement actual API call
-        log::info!("Searching marketplace for: {}", query);
-        
-        // Mock response for now
-        Ok(vec![])
+        Ok(self.search_page(query, filters, page, limit).await?.items)
+    }
+
+    /// Every search result up to `max_items`, fetched `limit` per page
+    pub async fn search_all_items(
+        &self,
+        query: &str,
+        filters: &SearchFilters,
+        limit: u32,
+        max_items: usize,
+    ) -> CanvasResult<Vec<MarketplaceItem>> {
+        let mut items = Vec::new();
+        let mut page = 1;
+        while items.len() < max_items {
+            let results = self.search_page(query, filters, page, limit).await?;
+            let done = !results.has_more || results.items.is_empty();
+            items.extend(results.items);
+            if done {
+                break;
+            }
+            page += 1;
+        }
+        items.truncate(max_items);
+        Ok(items)
+    }
+
+    async fn fetch_item(&self, item_id: &str) -> CanvasResult<MarketplaceItem> {
+        let path = format!("/items/{}", http::encode_path_segment(item_id));
+        self.send_json(self.request("GET", &path), None).await
     }
 
     /// Get item details
@@ -210,131 +427,188 @@ impl MarketplaceClient {
             return Ok(item.clone());
         }
 
-        // TODO: Implement actual API call
         log::info!("Fetching item details for: {}", item_id);
-        
-        // Mock response for now
-        let item = MarketplaceItem {
-            id: item_id.to_string(),
-            name: "Sample Item".to_string(),
-            description: "A sample marketplace item".to_string(),
-            author: "sample_author".to_string(),
-            version: "1.0.0".to_string(),
-            item_type: MarketplaceItemType::CustomNode,
-            tags: vec!["sample".to_string()],
-            rating: 4.5,
-            downloads: 100,
-            created_at: Utc::now(),
-            updated_at: Utc::now(),
-            price: None,
-            license: "MIT".to_string(),
-            dependencies: vec![],
-            compatibility: vec!["1.0.0".to_string()],
-            size_bytes: 1024,
-            hash: "sample_hash".to_string(),
-        };
+        let item = self.fetch_item(item_id).await?;
 
         // Cache the item
         self.cache.insert(item_id.to_string(), item.clone());
         Ok(item)
     }
 
-    /// Download item content
+    /// Download the content of a listed item, checked against its hash
+    async fn fetch_content(&self, item: &MarketplaceItem) -> CanvasResult<Vec<u8>> {
+        log::info!("Downloading item: {}", item.id);
+        let path = format!("/items/{}/download", http::encode_path_segment(&item.id));
+        let content = self.send(self.request("GET", &path), None).await?;
+        verify_content(item, &content)?;
+        Ok(content)
+    }
+
+    /// Download item content, checked against the hash its listing publishes
+    /// and the publisher signature against the trusted author keys
     pub async fn download_item(&self, item_id: &str) -> CanvasResult<Vec<u8>> {
-        // TODO: Implement actual download
-        log::info!("Downloading item: {}", item_id);
-        
-        // Mock response for now
-        Ok(vec![0u8; 1024])
+        let item = match self.cache.get(item_id) {
+            Some(item) => item.clone(),
+            None => self.fetch_item(item_id).await?,
+        };
+        let content = self.fetch_content(&item).await?;
+        self.author_keys.verify(&item, &content).require(&item, self.allow_unverified)?;
+        Ok(content)
     }
 
-    /// Upload item to marketplace
+    /// Upload item to marketplace. The listing is sent with the hash and size
+    /// of `content`, signed by the publisher key if one is set; retries carry
+    /// the same idempotency key, so an upload the API received before failing
+    /// is not published twice.
     pub async fn upload_item(
         &self,
         item: &MarketplaceItem,
         content: &[u8],
     ) -> CanvasResult<String> {
-        // TODO: Implement actual upload
         log::info!("Uploading item: {}", item.name);
-        
-        // Mock response for now
-        Ok("uploaded_item_id".to_string())
+        let mut item = item.clone();
+        match &self.publisher {
+            Some(publisher) => sign_bundle(&mut item, content, publisher.as_ref())?,
+            None => {
+                item.hash = encode_hex(&host::hash(host::HashAlgorithm::Sha256, content));
+                item.size_bytes = content.len() as u64;
+            }
+        }
+        let request = self
+            .request("POST", "/items")
+            .set("Idempotency-Key", &format!("{}@{}:{}", item.id, item.version, item.hash));
+        let body = serde_json::json!({ "item": item, "content": encode_hex(content) });
+        let response: serde_json::Value = self.send_json(request, Some(body)).await?;
+        response["id"]
+            .as_str()
+            .map(str::to_string)
+            .ok_or_else(|| CanvasError::Network("Marketplace returned no item ID".to_string()))
+    }
+
+    /// Publish a custom node after its embedded tests pass.
+    ///
+    /// The tests run against the packaged definition in a sandbox registry,
+    /// with the budgets `registry` gives the node, so an installed version of
+    /// the same node cannot stand in for the one being published.
+    pub async fn publish_custom_node(
+        &self,
+        item: &CustomNodeItem,
+        registry: &CustomNodeRegistry,
+        assets: &AssetStore,
+    ) -> CanvasResult<String> {
+        let node_id = &item.node_definition.id;
+        if item.node_definition.tests.is_empty() {
+            return Err(CanvasError::Validation(format!(
+                "Custom node '{}' ships no test cases; add at least one before publishing",
+                node_id
+            )));
+        }
+
+        let mut package = package_custom_node(item, assets)?;
+        let report = preview_custom_node(package.clone(), registry.limits_for(node_id))?
+            .tests
+            .ok_or_else(|| CanvasError::InvalidState(format!("Tests of custom node '{}' did not run", node_id)))?;
+        if !report.passed() {
+            let failures: Vec<String> = report
+                .failures()
+                .iter()
+                .map(|r| format!("{}: {}", r.name, r.failure.as_deref().unwrap_or_default()))
+                .collect();
+            return Err(CanvasError::Validation(format!(
+                "Custom node '{}' failed {} test(s): {}",
+                node_id,
+                failures.len(),
+                failures.join("; ")
+            )));
+        }
+
+        let results = check_compatibility(&package, &bundled_shims())?;
+        for result in &results {
+            log::info!(
+                "Custom node '{}' on {}: {}",
+                node_id,
+                result.version,
+                result.failure.as_deref().unwrap_or("ok")
+            );
+        }
+        ensure_compatible(node_id, &results)?;
+
+        package.item.metadata.capabilities = item.node_definition.capabilities.clone();
+        package.item.metadata.compatibility_results = results;
+        let metadata = package.item.metadata.clone();
+        let content = serde_json::to_vec(&package)?;
+        self.upload_item(&metadata, &content).await
     }
 
-    /// Get user profile
+    /// Public profile of a user
     pub async fn get_user_profile(&self, username: &str) -> CanvasResult<UserProfile> {
-        // TODO: Implement actual API call
         log::info!("Fetching user profile for: {}", username);
-        
-        // Mock response for now
-        Ok(UserProfile {
-            username: username.to_string(),
-            display_name: "Sample User".to_string(),
-            email: "sample@example.com".to_string(),
-            avatar_url: None,
-            bio: "A sample user".to_string(),
-            location: None,
-            website: None,
-            social_links: HashMap::new(),
-            reputation_score: 4.5,
-            items_published: 5,
-            total_downloads: 1000,
-            member_since: Utc::now(),
-            verified: false,
-        })
+        let path = format!("/users/{}", http::encode_path_segment(username));
+        self.send_json(self.request("GET", &path), None).await
     }
 
-    /// Get item reviews
+    /// One page of an item's reviews; `page` counts from 1
     pub async fn get_item_reviews(
         &self,
         item_id: &str,
         page: u32,
         limit: u32,
     ) -> CanvasResult<Vec<Review>> {
-        // TODO: Implement actual API call
         log::info!("Fetching reviews for item: {}", item_id);
-        
-        // Mock response for now
-        Ok(vec![])
+        let path = format!("/items/{}/reviews", http::encode_path_segment(item_id));
+        let request = self
+            .request("GET", &path)
+            .query("page", &page.max(1).to_string())
+            .query("limit", &limit.to_string());
+        let page: Page<Review> = self.send_json(request, None).await?;
+        Ok(page.items)
     }
 
     /// Submit a review
     pub async fn submit_review(&self, review: &Review) -> CanvasResult<()> {
-        // TODO: Implement actual API call
         log::info!("Submitting review for item: {}", review.item_id);
+        let path = format!("/items/{}/reviews", http::encode_path_segment(&review.item_id));
+        self.send(self.request("POST", &path), Some(serde_json::to_value(review)?)).await?;
         Ok(())
     }
 
-    /// Get trending items
+    /// Trending items, hottest first
     pub async fn get_trending_items(&self, limit: u32) -> CanvasResult<Vec<MarketplaceItem>> {
-        // TODO: Implement actual API call
         log::info!("Fetching trending items");
-        
-        // Mock response for now
-        Ok(vec![])
+        let request = self.request("GET", "/items/trending").query("limit", &limit.to_string());
+        let page: Page<MarketplaceItem> = self.send_json(request, None).await?;
+        Ok(page.items)
     }
 
-    /// Get recommended items
+    /// Items the marketplace recommends to a user, best first
     pub async fn get_recommended_items(
         &self,
         user_id: &str,
         limit: u32,
     ) -> CanvasResult<Vec<MarketplaceItem>> {
-        // TODO: Implement actual API call
         log::info!("Fetching recommended items for user: {}", user_id);
-        
-        // Mock response for now
-        Ok(vec![])
+        let path = format!("/users/{}/recommendations", http::encode_path_segment(user_id));
+        let request = self.request("GET", &path).query("limit", &limit.to_string());
+        let page: Page<MarketplaceItem> = self.send_json(request, None).await?;
+        Ok(page.items)
     }
 }
 
 /// Local marketplace manager
+#[derive(Default, Serialize, Deserialize)]
 pub struct LocalMarketplace {
     items: HashMap<String, MarketplaceItem>,
     custom_nodes: HashMap<String, CustomNodeItem>,
     templates: HashMap<String, TemplateItem>,
     components: HashMap<String, ComponentItem>,
     tutorials: HashMap<String, TutorialItem>,
+    #[serde(default)]
+    reviews: HashMap<String, Review>,
+    /// Submissions held back by moderation, by item ID
+    #[serde(default)]
+    moderation: HashMap<String, ModerationRecord>,
+    #[serde(default)]
+    review_votes: ratings::ReviewVotes,
 }
 
 impl LocalMarketplace {
@@ -346,11 +620,16 @@ impl LocalMarketplace {
             templates: HashMap::new(),
             components: HashMap::new(),
             tutorials: HashMap::new(),
+            reviews: HashMap::new(),
+            moderation: HashMap::new(),
+            review_votes: Default::default(),
         }
     }
 
     /// Add a custom node to local marketplace
-    pub fn add_custom_node(&mut self, item: CustomNodeItem) -> CanvasResult<()> {
+    pub fn add_custom_node(&mut self, mut item: CustomNodeItem) -> CanvasResult<()> {
+        // The listing always shows what the node's module will be allowed to do
+        item.metadata.capabilities = item.node_definition.capabilities.clone();
         let item_id = item.metadata.id.clone();
         self.custom_nodes.insert(item_id.clone(), item.clone());
         self.items.insert(item_id, item.metadata);
@@ -381,6 +660,11 @@ impl LocalMarketplace {
         Ok(())
     }
 
+    /// Add a listing without its content, such as an entry of the cached index
+    pub fn add_listing(&mut self, item: MarketplaceItem) {
+        self.items.insert(item.id.clone(), item);
+    }
+
     /// Get all items
     pub fn get_all_items(&self) -> Vec<&MarketplaceItem> {
         self.items.values().collect()
@@ -408,22 +692,7 @@ impl LocalMarketplace {
 
     /// Search items
     pub fn search_items(&self, query: &str, filters: &SearchFilters) -> Vec<&MarketplaceItem> {
-        self.items
-            .values()
-            .filter(|item| {
-                // Basic search implementation
-                let matches_query = query.is_empty() || 
-                    item.name.to_lowercase().contains(&query.to_lowercase()) ||
-                    item.description.to_lowercase().contains(&query.to_lowercase()) ||
-                    item.tags.iter().any(|tag| tag.to_lowercase().contains(&query.to_lowercase()));
-
-                let matches_type = filters.item_type.as_ref().map_or(true, |t| std::mem::discriminant(&item.item_type) == std::mem::discriminant(t));
-                let matches_rating = filters.min_rating.map_or(true, |r| item.rating >= r);
-                let matches_price = filters.free_only.map_or(true, |free| !free || item.price.is_none());
-
-                matches_query && matches_type && matches_rating && matches_price
-            })
-            .collect()
+        self.items.values().filter(|item| filters.matches(item, query)).collect()
     }
 
     /// Get item by ID
@@ -436,6 +705,16 @@ impl LocalMarketplace {
         self.custom_nodes.get(item_id)
     }
 
+    /// Newer versions of custom nodes installed in `registry`, with their changelogs
+    pub fn available_updates(&self, registry: &CustomNodeRegistry) -> Vec<NodeUpdate> {
+        let latest: Vec<CustomNodeDefinition> = self
+            .custom_nodes
+            .values()
+            .map(|item| item.node_definition.clone())
+            .collect();
+        registry.check_updates(&latest)
+    }
+
     /// Get template by ID
     pub fn get_template(&self, item_id: &str) -> Option<&TemplateItem> {
         self.templates.get(item_id)
@@ -458,8 +737,84 @@ impl LocalMarketplace {
         self.templates.remove(item_id);
         self.components.remove(item_id);
         self.tutorials.remove(item_id);
+        let reviews = &mut self.reviews;
+        reviews.retain(|_, review| review.item_id != item_id);
+        self.review_votes.retain(|review_id, _| reviews.contains_key(review_id));
         Ok(())
     }
+
+    /// Add a review of a local item. Users review through
+    /// [`CommunityManager::review_item`](crate::community::CommunityManager::review_item),
+    /// which attributes the review to their session.
+    pub(crate) fn add_review(&mut self, review: Review) -> CanvasResult<()> {
+        if !self.items.contains_key(&review.item_id) {
+            return Err(CanvasError::NotFound(format!("Item '{}' not found", review.item_id)));
+        }
+        if !(1..=5).contains(&review.rating) {
+            return Err(CanvasError::Validation(format!("Rating must be 1-5 stars, got {}", review.rating)));
+        }
+        let item_id = review.item_id.clone();
+        self.reviews.insert(review.id.clone(), review);
+        self.recompute_rating(&item_id, &RatingWeights::default());
+        Ok(())
+    }
+
+    /// Every listed item
+    pub fn items(&self) -> impl Iterator<Item = &MarketplaceItem> {
+        self.items.values()
+    }
+
+    /// Every review of every item
+    pub fn reviews(&self) -> impl Iterator<Item = &Review> {
+        self.reviews.values()
+    }
+
+    /// Get reviews of an item
+    pub fn get_reviews(&self, item_id: &str) -> Vec<&Review> {
+        self.reviews.values().filter(|r| r.item_id == item_id).collect()
+    }
+
+    /// Get reviews written by a user
+    pub fn get_user_reviews(&self, user_id: &str) -> Vec<&Review> {
+        self.reviews.values().filter(|r| r.user_id == user_id).collect()
+    }
+
+    /// Detach a user's reviews from their account, or delete them. Returns
+    /// how many reviews were affected.
+    pub fn erase_user_reviews(&mut self, user_id: &str, retention: ContentRetention, anonymous_id: &str) -> usize {
+        self.erase_user_votes(user_id);
+        let before = self.reviews.len();
+        match retention {
+            ContentRetention::Remove => {
+                self.reviews.retain(|_, review| review.user_id != user_id);
+                let reviews = &self.reviews;
+                self.review_votes.retain(|review_id, _| reviews.contains_key(review_id));
+                self.recompute_ratings(&RatingWeights::default());
+                before - self.reviews.len()
+            }
+            ContentRetention::Anonymize => {
+                let now = Utc::now();
+                let mut anonymized = 0;
+                for review in self.reviews.values_mut().filter(|r| r.user_id == user_id) {
+                    review.user_id = anonymous_id.to_string();
+                    review.verified_purchase = false;
+                    review.updated_at = now;
+                    anonymized += 1;
+                }
+                anonymized
+            }
+        }
+    }
+
+    /// Delete reviews by `user_id` last updated before `cutoff`
+    pub fn purge_reviews(&mut self, user_id: &str, cutoff: DateTime<Utc>) -> usize {
+        let before = self.reviews.len();
+        self.reviews.retain(|_, r| r.user_id != user_id || r.updated_at >= cutoff);
+        let reviews = &self.reviews;
+        self.review_votes.retain(|review_id, _| reviews.contains_key(review_id));
+        self.recompute_ratings(&RatingWeights::default());
+        before - self.reviews.len()
+    }
 }
 
 #[cfg(test)]
@@ -489,6 +844,12 @@ mod tests {
             compatibility: vec!["1.0.0".to_string()],
             size_bytes: 1024,
             hash: "test_hash".to_string(),
+            capabilities: vec![],
+            compatibility_results: vec![],
+            registry: None,
+            signature: None,
+            publisher_signature: None,
+            frozen: None,
         };
 
         let node_definition = crate::nodes::custom::CustomNodeBuilder::new(
@@ -542,4 +903,210 @@ mod tests {
         let client_with_key = client.with_api_key("test_key".to_string());
         assert_eq!(client_with_key.api_key, Some("test_key".to_string()));
     }
-} 
\ No newline at end of file
+
+    #[tokio::test]
+    async fn test_publish_tests_the_published_definition_not_the_installed_one() {
+        let installed = test_package().item;
+        let mut registry = CustomNodeRegistry::new();
+        registry.register_node(installed.node_definition.clone()).unwrap();
+        assert!(registry.run_node_tests("clamp").unwrap().passed());
+
+        let mut broken = installed;
+        broken.node_definition.tests[0].expected_outputs.insert("value".to_string(), serde_json::json!(10));
+        let assets = tempfile::tempdir().unwrap();
+        let client = MarketplaceClient::new("http://127.0.0.1:9".to_string());
+        let error = client
+            .publish_custom_node(&broken, &registry, &AssetStore::new(assets.path()))
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("failed 1 test(s)"), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn test_client_pages_retries_and_verifies_downloads() {
+        let content = serde_json::to_vec(&test_package()).unwrap();
+        let key = ed25519_dalek::SigningKey::from_bytes(&[7; 32]);
+        let mut keys = AuthorKeys::new();
+        keys.trust("alice", &encode_hex(&key.verifying_key().to_bytes())).unwrap();
+        let signed = content.clone();
+        let listing = move |id: &str| {
+            let mut item = test_package().item.metadata;
+            item.id = id.to_string();
+            sign_bundle(&mut item, &signed, &crate::baals::signer::LocalSigner::new(key.clone())).unwrap();
+            if id == "unsigned" {
+                item.publisher_signature = None;
+            }
+            serde_json::to_value(&item).unwrap()
+        };
+        let page = {
+            let listing = listing.clone();
+            move |id: &str, page: u32, has_more: bool| {
+                serde_json::json!({"items": [listing(id)], "page": page, "has_more": has_more})
+            }
+        };
+
+        let server = tiny_http::Server::http("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", server.server_addr().to_ip().unwrap());
+        let served = content.clone();
+        std::thread::spawn(move || {
+            let mut throttled = false;
+            for request in server.incoming_requests() {
+                let authorized = request
+                    .headers()
+                    .iter()
+                    .any(|h| h.field.equiv("Authorization") && h.value.as_str() == "Bearer secret");
+                let path = request.url().to_string();
+                let (status, body) = if !authorized {
+                    (401, b"missing key".to_vec())
+                } else if path.starts_with("/items/search") && path.contains("page=1") && !throttled {
+                    throttled = true;
+                    (503, Vec::new())
+                } else if path.starts_with("/items/search") {
+                    let body = match path.contains("page=1") {
+                        true => page("first", 1, true),
+                        false => page("second", 2, false),
+                    };
+                    (200, serde_json::to_vec(&body).unwrap())
+                } else if let Some(id) = path.strip_suffix("/download") {
+                    let mut body = served.clone();
+                    if id.ends_with("tampered") {
+                        body.push(b' ');
+                    }
+                    (200, body)
+                } else {
+                    // Namespaced IDs arrive as one encoded segment
+                    match path.trim_start_matches("/items/") {
+                        id if id.contains('/') => (404, Vec::new()),
+                        id => (200, serde_json::to_vec(&listing(&id.replace("%2F", "/"))).unwrap()),
+                    }
+                };
+                let mut response = tiny_http::Response::from_data(body).with_status_code(status);
+                if status == 503 {
+                    response.add_header(tiny_http::Header::from_bytes("Retry-After", "0").unwrap());
+                }
+                request.respond(response).unwrap();
+            }
+        });
+
+        let retry = RetryPolicy {
+            attempts: 2,
+            base_delay: std::time::Duration::from_millis(1),
+            max_delay: std::time::Duration::from_millis(10),
+        };
+        let anonymous = MarketplaceClient::new(url.clone()).with_retry(retry);
+        let refused = anonymous.search_items("clamp", &SearchFilters::default(), 1, 1).await;
+        assert!(matches!(refused, Err(CanvasError::PermissionDenied(_))));
+
+        let mut client = MarketplaceClient::new(format!("{}/", url))
+            .with_api_key("secret".to_string())
+            .with_retry(retry)
+            .with_author_keys(keys);
+        let items = client.search_all_items("clamp", &SearchFilters::default(), 1, 10).await.unwrap();
+        let ids: Vec<&str> = items.iter().map(|i| i.id.as_str()).collect();
+        assert_eq!(ids, vec!["first", "second"]);
+
+        assert_eq!(client.get_item("clamp").await.unwrap().id, "clamp");
+        assert_eq!(client.get_item("acme/vault").await.unwrap().id, "acme/vault");
+        assert_eq!(client.download_item("clamp").await.unwrap(), content);
+        let tampered = client.download_item("tampered").await;
+        assert!(matches!(tampered, Err(CanvasError::Validation(_))));
+        let unsigned = client.download_item("unsigned").await;
+        assert!(matches!(unsigned, Err(CanvasError::PermissionDenied(_))));
+        let mut installed = LocalMarketplace::new();
+        let (bundle, verification) = client.install_item("clamp", &mut installed).await.unwrap();
+        assert!(verification.is_verified());
+        assert_eq!(bundle, content);
+        assert!(installed.get_custom_node("clamp").is_some());
+        let refused = client.install_item("unsigned", &mut installed).await;
+        assert!(matches!(refused, Err(CanvasError::PermissionDenied(_))));
+        assert!(installed.get_item("unsigned").is_none());
+
+        let mut lenient = MarketplaceClient::new(url).with_api_key("secret".to_string()).allow_unverified();
+        assert_eq!(lenient.download_item("unsigned").await.unwrap(), content);
+        let (_, verification) = lenient.install_item("unsigned", &mut installed).await.unwrap();
+        assert_eq!(verification, Verification::Unsigned);
+    }
+
+    #[tokio::test]
+    async fn test_client_fetches_recommendations_for_a_user() {
+        let server = tiny_http::Server::http("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", server.server_addr().to_ip().unwrap());
+        std::thread::spawn(move || {
+            for request in server.incoming_requests() {
+                let (status, body) = if request.url() == "/users/acme%2Falice/recommendations?limit=5" {
+                    let item = test_package().item.metadata;
+                    (200, serde_json::json!({"items": [item], "page": 1, "has_more": false}).to_string())
+                } else {
+                    (404, String::new())
+                };
+                request.respond(tiny_http::Response::from_string(body).with_status_code(status)).unwrap();
+            }
+        });
+
+        let client = MarketplaceClient::new(url);
+        let items = client.get_recommended_items("acme/alice", 5).await.unwrap();
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].id, test_package().item.metadata.id);
+    }
+
+    #[tokio::test]
+    async fn test_client_fetches_profiles_and_reviews() {
+        let review = Review {
+            id: "r1".to_string(),
+            item_id: "acme/vault".to_string(),
+            user_id: "alice".to_string(),
+            rating: 4,
+            title: "Solid".to_string(),
+            content: String::new(),
+            pros: Vec::new(),
+            cons: Vec::new(),
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            helpful_votes: 0,
+            verified_purchase: false,
+        };
+        let profile = UserProfile {
+            username: "alice".to_string(),
+            display_name: "Alice".to_string(),
+            email: String::new(),
+            avatar_url: None,
+            bio: String::new(),
+            location: None,
+            website: None,
+            social_links: HashMap::new(),
+            reputation_score: 4.5,
+            items_published: 1,
+            total_downloads: 10,
+            member_since: Utc::now(),
+            verified: true,
+        };
+        let server = tiny_http::Server::http("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", server.server_addr().to_ip().unwrap());
+        let (served_review, served_profile) = (review.clone(), profile.clone());
+        let (sent, received) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            for request in server.incoming_requests() {
+                let target = format!("{} {}", request.method(), request.url());
+                let (status, body) = match target.as_str() {
+                    "GET /users/alice" => (200, serde_json::to_string(&served_profile).unwrap()),
+                    "GET /items/acme%2Fvault/reviews?page=1&limit=20" => {
+                        (200, serde_json::json!({"items": [&served_review], "page": 1}).to_string())
+                    }
+                    "POST /items/acme%2Fvault/reviews" => (201, String::new()),
+                    _ => (404, String::new()),
+                };
+                sent.send(target).unwrap();
+                request.respond(tiny_http::Response::from_string(body).with_status_code(status)).unwrap();
+            }
+        });
+
+        let client = MarketplaceClient::new(url);
+        assert!(client.get_user_profile("alice").await.unwrap().verified);
+        let reviews = client.get_item_reviews("acme/vault", 0, 20).await.unwrap();
+        assert_eq!(reviews.len(), 1);
+        assert_eq!(reviews[0].title, "Solid");
+        client.submit_review(&review).await.unwrap();
+        assert_eq!(received.try_iter().last().as_deref(), Some("POST /items/acme%2Fvault/reviews"));
+        assert!(client.get_user_profile("bob").await.is_err());
+    }
+}
@@ -0,0 +1,400 @@
+//! Moderation of uploaded custom nodes
+//!
+//! Every submitted node is scanned before it is listed: its WASM module must
+//! only import host functions covered by its declared capabilities, neither the
+//! module nor script code may carry obfuscated payloads (long high-entropy
+//! regions, encoded blobs, dynamic evaluation), and neither may match a known
+//! malicious signature. SVG icons must not carry script. A clean submission is listed straight away; anything
+//! else is quarantined until a reviewer approves or rejects it.
+
+use std::collections::BTreeSet;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::{LocalMarketplace, NodePackage};
+use crate::{
+    error::{CanvasError, CanvasResult},
+    nodes::{
+        custom::{check_module_imports, CustomNodeImplementation},
+        decode_hex, encode_hex, sniff_media_type,
+    },
+    wasm::host,
+};
+
+/// Bits of entropy per byte above which a region looks compressed or encrypted
+const ENTROPY_THRESHOLD: f64 = 7.5;
+/// Size of the regions entropy is measured over
+const ENTROPY_WINDOW: usize = 1024;
+/// Length from which a string of base64 or hex characters counts as an encoded blob
+const ENCODED_BLOB_LENGTH: usize = 256;
+
+/// Script constructs that reach outside the node or evaluate generated code
+const DISALLOWED_SCRIPT_PATTERNS: &[(&str, &str)] = &[
+    ("eval(", "dynamic evaluation"),
+    ("new Function(", "dynamic evaluation"),
+    ("fromCharCode", "character-code string building"),
+    ("child_process", "process spawning"),
+    ("std::process", "process spawning"),
+    ("std::net", "network access"),
+    ("std::fs", "file system access"),
+    ("fetch(", "network access"),
+    ("XMLHttpRequest", "network access"),
+    ("require(", "module loading"),
+];
+
+/// How serious a finding is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FindingSeverity {
+    /// Suspicious; a reviewer should look
+    Suspicious,
+    /// Matches known-malicious content
+    Malicious,
+}
+
+/// One problem found by the scanner
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScanFinding {
+    pub severity: FindingSeverity,
+    pub rule: String,
+    pub detail: String,
+}
+
+impl ScanFinding {
+    fn new(severity: FindingSeverity, rule: &str, detail: impl Into<String>) -> Self {
+        Self {
+            severity,
+            rule: rule.to_string(),
+            detail: detail.into(),
+        }
+    }
+}
+
+/// A byte sequence found in known-malicious uploads
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BytePattern {
+    pub name: String,
+    /// 0x-hex
+    pub bytes: String,
+}
+
+/// Known-malicious content
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MalwareSignatures {
+    /// SHA-256 of known-malicious modules and scripts, 0x-hex
+    #[serde(default)]
+    pub hashes: BTreeSet<String>,
+    #[serde(default)]
+    pub patterns: Vec<BytePattern>,
+}
+
+/// Shannon entropy of a byte slice, in bits per byte
+fn entropy(bytes: &[u8]) -> f64 {
+    let mut counts = [0usize; 256];
+    for byte in bytes {
+        counts[*byte as usize] += 1;
+    }
+    let len = bytes.len() as f64;
+    counts
+        .iter()
+        .filter(|c| **c > 0)
+        .map(|c| {
+            let p = *c as f64 / len;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+/// Longest run of characters that could be base64 or hex
+fn longest_encoded_run(text: &str) -> usize {
+    let mut longest = 0;
+    let mut current = 0;
+    for c in text.chars() {
+        if c.is_ascii_alphanumeric() || matches!(c, '+' | '/' | '=') {
+            current += 1;
+            longest = longest.max(current);
+        } else {
+            current = 0;
+        }
+    }
+    longest
+}
+
+/// Whether markup sets an `on...=` event handler attribute
+fn has_event_handler(markup: &str) -> bool {
+    markup.match_indices(char::is_whitespace).any(|(start, _)| {
+        let attribute = &markup[start + 1..];
+        let name_len = attribute.chars().take_while(|c| c.is_ascii_alphabetic()).count();
+        name_len > 2 && attribute.starts_with("on") && attribute[name_len..].trim_start().starts_with('=')
+    })
+}
+
+/// Scans uploaded node code
+#[derive(Debug, Clone, Default)]
+pub struct ContentScanner {
+    signatures: MalwareSignatures,
+}
+
+impl ContentScanner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_signatures(mut self, signatures: MalwareSignatures) -> Self {
+        self.signatures = signatures;
+        self
+    }
+
+    fn scan_signatures(&self, what: &str, bytes: &[u8], findings: &mut Vec<ScanFinding>) {
+        let hash = encode_hex(&host::hash(host::HashAlgorithm::Sha256, bytes));
+        if self.signatures.hashes.contains(&hash) {
+            findings.push(ScanFinding::new(
+                FindingSeverity::Malicious,
+                "known-malicious",
+                format!("{} matches a known-malicious upload ({})", what, hash),
+            ));
+        }
+        for pattern in &self.signatures.patterns {
+            let Some(needle) = decode_hex(&pattern.bytes).filter(|n| !n.is_empty()) else {
+                continue;
+            };
+            if bytes.windows(needle.len()).any(|window| window == needle.as_slice()) {
+                findings.push(ScanFinding::new(
+                    FindingSeverity::Malicious,
+                    "malware-signature",
+                    format!("{} contains signature '{}'", what, pattern.name),
+                ));
+            }
+        }
+    }
+
+    fn scan_module(&self, package: &NodePackage, module: &[u8], findings: &mut Vec<ScanFinding>) {
+        let declared = package.item.node_definition.capabilities.iter().copied().collect();
+        if let Err(e) = check_module_imports(module, &declared) {
+            findings.push(ScanFinding::new(FindingSeverity::Suspicious, "disallowed-import", e.to_string()));
+        }
+        if let Some(offset) = module
+            .chunks(ENTROPY_WINDOW)
+            .position(|window| window.len() == ENTROPY_WINDOW && entropy(window) > ENTROPY_THRESHOLD)
+        {
+            findings.push(ScanFinding::new(
+                FindingSeverity::Suspicious,
+                "obfuscated-payload",
+                format!("module has a high-entropy region at byte {}", offset * ENTROPY_WINDOW),
+            ));
+        }
+        self.scan_signatures("module", module, findings);
+    }
+
+    fn scan_script(&self, code: &str, findings: &mut Vec<ScanFinding>) {
+        for (pattern, what) in DISALLOWED_SCRIPT_PATTERNS {
+            if code.contains(pattern) {
+                findings.push(ScanFinding::new(
+                    FindingSeverity::Suspicious,
+                    "disallowed-script-api",
+                    format!("script uses {} ('{}')", what, pattern.trim_end_matches('(')),
+                ));
+            }
+        }
+        let run = longest_encoded_run(code);
+        if run >= ENCODED_BLOB_LENGTH {
+            findings.push(ScanFinding::new(
+                FindingSeverity::Suspicious,
+                "obfuscated-payload",
+                format!("script embeds a {}-character encoded string", run),
+            ));
+        }
+        self.scan_signatures("script", code.as_bytes(), findings);
+    }
+
+    fn scan_asset(&self, hash: &str, bytes: &[u8], findings: &mut Vec<ScanFinding>) {
+        if sniff_media_type(bytes) == Some("image/svg+xml") {
+            let text = String::from_utf8_lossy(bytes).to_ascii_lowercase();
+            if ["<script", "javascript:", "<foreignobject"].iter().any(|p| text.contains(p)) || has_event_handler(&text) {
+                findings.push(ScanFinding::new(
+                    FindingSeverity::Suspicious,
+                    "active-svg",
+                    format!("SVG asset {} contains script or event handlers", hash),
+                ));
+            }
+        }
+        self.scan_signatures(&format!("asset {}", hash), bytes, findings);
+    }
+
+    /// Findings for a node package; empty when it is clean
+    pub fn scan(&self, package: &NodePackage) -> CanvasResult<Vec<ScanFinding>> {
+        let mut findings = Vec::new();
+        if let Some(module) = package.module_bytes()? {
+            self.scan_module(package, &module, &mut findings);
+        }
+        for (hash, bytes) in package.asset_bytes()? {
+            self.scan_asset(&hash, &bytes, &mut findings);
+        }
+        if let CustomNodeImplementation::Script { code, .. } = &package.item.node_definition.implementation {
+            self.scan_script(code, &mut findings);
+        }
+        findings.sort_by(|a, b| b.severity.cmp(&a.severity));
+        Ok(findings)
+    }
+}
+
+/// Where a submission stands
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum ModerationStatus {
+    /// Held back until a reviewer decides
+    Quarantined,
+    Approved { reviewer: Option<String> },
+    Rejected { reviewer: String, reason: String },
+}
+
+/// A submission held for review
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModerationRecord {
+    pub package: NodePackage,
+    pub findings: Vec<ScanFinding>,
+    pub status: ModerationStatus,
+    pub submitted_at: DateTime<Utc>,
+    pub reviewed_at: Option<DateTime<Utc>>,
+}
+
+/// Reviewer decision on a quarantined submission
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ReviewDecision {
+    Approve,
+    Reject { reason: String },
+}
+
+impl LocalMarketplace {
+    /// Scan a submitted node and list it if clean; otherwise quarantine it
+    pub fn submit_custom_node(&mut self, package: NodePackage, scanner: &ContentScanner) -> CanvasResult<ModerationStatus> {
+        let findings = scanner.scan(&package)?;
+        let item_id = package.item.metadata.id.clone();
+        if findings.is_empty() {
+            self.add_custom_node(package.item)?;
+            return Ok(ModerationStatus::Approved { reviewer: None });
+        }
+        for finding in &findings {
+            log::warn!("Quarantining '{}': [{}] {}", item_id, finding.rule, finding.detail);
+        }
+        self.moderation.insert(
+            item_id,
+            ModerationRecord {
+                package,
+                findings,
+                status: ModerationStatus::Quarantined,
+                submitted_at: Utc::now(),
+                reviewed_at: None,
+            },
+        );
+        Ok(ModerationStatus::Quarantined)
+    }
+
+    /// Submissions waiting for a reviewer
+    pub fn quarantined(&self) -> Vec<(&String, &ModerationRecord)> {
+        let mut records: Vec<_> = self
+            .moderation
+            .iter()
+            .filter(|(_, r)| r.status == ModerationStatus::Quarantined)
+            .collect();
+        records.sort_by_key(|(_, r)| r.submitted_at);
+        records
+    }
+
+    pub fn moderation_record(&self, item_id: &str) -> Option<&ModerationRecord> {
+        self.moderation.get(item_id)
+    }
+
+    /// Decide on a quarantined submission; approving lists it despite its findings
+    pub fn review_submission(&mut self, item_id: &str, reviewer: &str, decision: ReviewDecision) -> CanvasResult<()> {
+        let record = self
+            .moderation
+            .get_mut(item_id)
+            .ok_or_else(|| CanvasError::NotFound(format!("No submission '{}'", item_id)))?;
+        if record.status != ModerationStatus::Quarantined {
+            return Err(CanvasError::InvalidState(format!("Submission '{}' was already reviewed", item_id)));
+        }
+        let malicious = record.findings.iter().any(|f| f.severity == FindingSeverity::Malicious);
+        record.status = match decision {
+            ReviewDecision::Approve if malicious => {
+                return Err(CanvasError::PermissionDenied(format!(
+                    "'{}' matches known-malicious content and cannot be approved",
+                    item_id
+                )))
+            }
+            ReviewDecision::Approve => ModerationStatus::Approved {
+                reviewer: Some(reviewer.to_string()),
+            },
+            ReviewDecision::Reject { reason } => ModerationStatus::Rejected {
+                reviewer: reviewer.to_string(),
+                reason,
+            },
+        };
+        record.reviewed_at = Some(Utc::now());
+        log::info!("Submission '{}' reviewed by {}: {:?}", item_id, reviewer, record.status);
+
+        if matches!(record.status, ModerationStatus::Approved { .. }) {
+            let item = record.package.item.clone();
+            self.add_custom_node(item)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_suspicious_scripts_are_quarantined_until_reviewed() {
+        let mut package = super::super::preview::tests::package();
+        let scanner = ContentScanner::new().with_signatures(MalwareSignatures {
+            hashes: BTreeSet::new(),
+            patterns: vec![BytePattern {
+                name: "miner".to_string(),
+                bytes: encode_hex(b"stratum+tcp"),
+            }],
+        });
+        let mut marketplace = LocalMarketplace::new();
+        assert_eq!(
+            marketplace.submit_custom_node(package.clone(), &scanner).unwrap(),
+            ModerationStatus::Approved { reviewer: None }
+        );
+
+        package.item.metadata.id = "obfuscated".to_string();
+        package.item.node_definition.implementation = CustomNodeImplementation::Script {
+            language: "assemblyscript".to_string(),
+            code: format!("let p = \"{}\"; eval(p);", "QUJD".repeat(100)),
+        };
+        let findings = scanner.scan(&package).unwrap();
+        let rules: Vec<&str> = findings.iter().map(|f| f.rule.as_str()).collect();
+        assert_eq!(rules, vec!["disallowed-script-api", "obfuscated-payload"]);
+        assert_eq!(marketplace.submit_custom_node(package.clone(), &scanner).unwrap(), ModerationStatus::Quarantined);
+        assert!(marketplace.get_item("obfuscated").is_none());
+        marketplace.review_submission("obfuscated", "mod", ReviewDecision::Approve).unwrap();
+        assert!(marketplace.get_item("obfuscated").is_some());
+
+        package.item.metadata.id = "miner".to_string();
+        package.item.node_definition.implementation = CustomNodeImplementation::Script {
+            language: "assemblyscript".to_string(),
+            code: "connect('stratum+tcp://pool')".to_string(),
+        };
+        marketplace.submit_custom_node(package, &scanner).unwrap();
+        assert!(marketplace.review_submission("miner", "mod", ReviewDecision::Approve).is_err());
+        assert_eq!(marketplace.quarantined().len(), 1);
+    }
+
+    #[test]
+    fn test_svg_icons_with_script_are_flagged() {
+        let mut package = super::super::preview::tests::package();
+        let mut add_icon = |svg: &[u8]| {
+            let hash = encode_hex(&host::hash(host::HashAlgorithm::Sha256, svg));
+            package.assets = [(hash.trim_start_matches("0x").to_string(), encode_hex(svg))].into();
+            ContentScanner::new().scan(&package).unwrap()
+        };
+        assert!(add_icon(b"<svg><title>runs on chain</title></svg>").is_empty());
+        let findings = add_icon(b"<svg onload =\"fetch('x')\"></svg>");
+        assert_eq!(findings[0].rule, "active-svg");
+    }
+}
@@ -0,0 +1,645 @@
+//! Offline marketplace use
+//!
+//! The marketplace index is cached under the data directory whenever it is
+//! fetched, and browsing falls back to the cache when `marketplace.offline` is
+//! set or the marketplace cannot be reached. Publishes and reviews made
+//! offline go into a local queue; [`SyncQueue::sync`] replays them in order
+//! once connectivity returns. An action conflicts when the marketplace changed
+//! underneath it while it was queued (another version of the item was
+//! published, the item was removed, or a newer review by the same user
+//! exists) and is resolved according to a [`ConflictPolicy`].
+
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::{http::encode_path_segment, MarketplaceItem, Review, SearchFilters};
+use crate::{
+    config::Config,
+    error::{CanvasError, CanvasResult},
+    nodes::{decode_hex, encode_hex},
+};
+
+/// File the marketplace index is cached in, under the data directory
+pub const MARKETPLACE_CACHE_FILE: &str = "marketplace-cache.json";
+/// File queued marketplace actions are kept in, under the data directory
+pub const SYNC_QUEUE_FILE: &str = "marketplace-queue.json";
+
+/// The marketplace as seen over the network
+pub trait MarketplaceRemote: Send + Sync {
+    /// Every listed item
+    fn fetch_index(&self) -> CanvasResult<Vec<MarketplaceItem>>;
+
+    /// Current listing of an item; `Ok(None)` if it does not exist
+    fn get_item(&self, item_id: &str) -> CanvasResult<Option<MarketplaceItem>>;
+
+    /// The review a user has written of an item, if any
+    fn user_review(&self, item_id: &str, user_id: &str) -> CanvasResult<Option<Review>>;
+
+    /// Publish an item, returning its marketplace ID
+    fn publish(&self, item: &MarketplaceItem, content: &[u8]) -> CanvasResult<String>;
+
+    fn submit_review(&self, review: &Review) -> CanvasResult<()>;
+}
+
+/// Marketplace REST API
+pub struct HttpMarketplace {
+    api_url: String,
+    api_key: Option<String>,
+    agent: ureq::Agent,
+}
+
+impl HttpMarketplace {
+    pub fn new(api_url: impl Into<String>, timeout: Duration) -> Self {
+        Self {
+            api_url: api_url.into().trim_end_matches('/').to_string(),
+            api_key: None,
+            agent: ureq::AgentBuilder::new().timeout(timeout).build(),
+        }
+    }
+
+    pub fn with_api_key(mut self, api_key: String) -> Self {
+        self.api_key = Some(api_key);
+        self
+    }
+
+    fn request(&self, method: &str, path: &str) -> ureq::Request {
+        let request = self.agent.request(method, &format!("{}{}", self.api_url, path));
+        match &self.api_key {
+            Some(key) => request.set("Authorization", &format!("Bearer {}", key)),
+            None => request,
+        }
+    }
+
+    /// `Ok(None)` on 404, so callers can tell a missing resource from an
+    /// unreachable marketplace
+    fn get_optional<T: serde::de::DeserializeOwned>(&self, path: &str) -> CanvasResult<Option<T>> {
+        match self.request("GET", path).call() {
+            Ok(response) => Ok(Some(response.into_json()?)),
+            Err(ureq::Error::Status(404, _)) => Ok(None),
+            Err(e) => Err(http_error(e)),
+        }
+    }
+}
+
+fn http_error(e: ureq::Error) -> CanvasError {
+    CanvasError::Network(format!("Marketplace request failed: {}", e))
+}
+
+impl MarketplaceRemote for HttpMarketplace {
+    fn fetch_index(&self) -> CanvasResult<Vec<MarketplaceItem>> {
+        Ok(self.request("GET", "/items").call().map_err(http_error)?.into_json()?)
+    }
+
+    fn get_item(&self, item_id: &str) -> CanvasResult<Option<MarketplaceItem>> {
+        self.get_optional(&format!("/items/{}", encode_path_segment(item_id)))
+    }
+
+    fn user_review(&self, item_id: &str, user_id: &str) -> CanvasResult<Option<Review>> {
+        self.get_optional(&format!("/items/{}/reviews/{}", encode_path_segment(item_id), encode_path_segment(user_id)))
+    }
+
+    fn publish(&self, item: &MarketplaceItem, content: &[u8]) -> CanvasResult<String> {
+        let response: serde_json::Value = self
+            .request("POST", "/items")
+            .send_json(serde_json::json!({ "item": item, "content": encode_hex(content) }))
+            .map_err(http_error)?
+            .into_json()?;
+        response["id"]
+            .as_str()
+            .map(str::to_string)
+            .ok_or_else(|| CanvasError::Network("Marketplace returned no item ID".to_string()))
+    }
+
+    fn submit_review(&self, review: &Review) -> CanvasResult<()> {
+        self.request("POST", &format!("/items/{}/reviews", encode_path_segment(&review.item_id)))
+            .send_json(serde_json::to_value(review)?)
+            .map_err(http_error)?;
+        Ok(())
+    }
+}
+
+/// Marketplace index as last fetched
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MarketplaceCache {
+    pub fetched_at: Option<DateTime<Utc>>,
+    items: BTreeMap<String, MarketplaceItem>,
+    #[serde(skip)]
+    path: Option<PathBuf>,
+}
+
+impl MarketplaceCache {
+    /// Cache file for a configuration
+    pub fn default_path(config: &Config) -> PathBuf {
+        config.app.data_dir.join(MARKETPLACE_CACHE_FILE)
+    }
+
+    /// Load the cache from `path`, starting empty if it does not exist yet.
+    /// [`save`](Self::save) writes back to the same file.
+    pub fn open(path: &Path) -> CanvasResult<Self> {
+        let mut cache: Self = if path.exists() {
+            serde_json::from_str(&std::fs::read_to_string(path)?)?
+        } else {
+            Self::default()
+        };
+        cache.path = Some(path.to_path_buf());
+        Ok(cache)
+    }
+
+    pub fn save(&self) -> CanvasResult<()> {
+        let path = self
+            .path
+            .as_ref()
+            .ok_or_else(|| CanvasError::InvalidState("Marketplace cache was not opened from a file".to_string()))?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// Replace the cached index with a freshly fetched one
+    pub fn replace(&mut self, items: Vec<MarketplaceItem>) {
+        self.items = items.into_iter().map(|item| (item.id.clone(), item)).collect();
+        self.fetched_at = Some(Utc::now());
+    }
+
+    /// Fetch the index from `remote` into the cache. Returns how many items it holds.
+    pub fn refresh(&mut self, remote: &dyn MarketplaceRemote) -> CanvasResult<usize> {
+        self.replace(remote.fetch_index()?);
+        Ok(self.items.len())
+    }
+
+    pub fn get_item(&self, item_id: &str) -> Option<&MarketplaceItem> {
+        self.items.get(item_id)
+    }
+
+    /// Drop an item from the cached index
+    pub fn remove(&mut self, item_id: &str) -> Option<MarketplaceItem> {
+        self.items.remove(item_id)
+    }
+
+    pub fn search(&self, query: &str, filters: &SearchFilters) -> Vec<&MarketplaceItem> {
+        self.items.values().filter(|item| filters.matches(item, query)).collect()
+    }
+}
+
+/// Search the marketplace, or the cache when offline.
+///
+/// With a remote, a successful fetch refreshes the cache first (save it
+/// afterwards to keep the refresh); if the marketplace cannot be reached the
+/// cached index is searched instead. Returns the matches and whether they came
+/// from the cache.
+pub fn browse(
+    cache: &mut MarketplaceCache,
+    remote: Option<&dyn MarketplaceRemote>,
+    query: &str,
+    filters: &SearchFilters,
+) -> CanvasResult<(Vec<MarketplaceItem>, bool)> {
+    let mut cached = true;
+    if let Some(remote) = remote {
+        match cache.refresh(remote) {
+            Ok(_) => cached = false,
+            Err(CanvasError::Network(e)) => log::warn!("Marketplace unreachable, using cached index: {}", e),
+            Err(e) => return Err(e),
+        }
+    }
+    if cached && cache.fetched_at.is_none() {
+        return Err(CanvasError::NotFound(
+            "No cached marketplace index; browse once while online to create it".to_string(),
+        ));
+    }
+    let items = cache.search(query, filters).into_iter().cloned().collect();
+    Ok((items, cached))
+}
+
+/// Marketplace change made while offline
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum PendingAction {
+    Publish {
+        item: MarketplaceItem,
+        /// Package content, hex
+        content: String,
+        /// Version of the item the marketplace listed when this was queued;
+        /// `None` if it was not listed
+        base_version: Option<String>,
+    },
+    Review {
+        review: Review,
+    },
+}
+
+impl PendingAction {
+    pub fn item_id(&self) -> &str {
+        match self {
+            PendingAction::Publish { item, .. } => &item.id,
+            PendingAction::Review { review } => &review.item_id,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueuedAction {
+    pub id: Uuid,
+    pub queued_at: DateTime<Utc>,
+    pub action: PendingAction,
+}
+
+/// How a queued action came to conflict with the marketplace
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ConflictReason {
+    /// The reviewed item is no longer listed
+    ItemRemoved,
+    /// Someone published the item while the publish was queued
+    RemoteVersionChanged { base: Option<String>, remote: String },
+    /// The user's review on the marketplace is newer than the queued one
+    NewerRemoteReview { remote_updated_at: DateTime<Utc> },
+}
+
+/// What to do with a conflicting action
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictPolicy {
+    /// Leave it queued for the user to decide
+    #[default]
+    Defer,
+    /// Apply it anyway, replacing the marketplace's version
+    KeepLocal,
+    /// Drop it in favour of the marketplace's version
+    KeepRemote,
+}
+
+impl ConflictPolicy {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().replace('_', "-").as_str() {
+            "defer" => Some(ConflictPolicy::Defer),
+            "keep-local" | "local" => Some(ConflictPolicy::KeepLocal),
+            "keep-remote" | "remote" => Some(ConflictPolicy::KeepRemote),
+            _ => None,
+        }
+    }
+}
+
+/// What a sync did with a conflicting action
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictResolution {
+    /// Still queued
+    Deferred,
+    Applied,
+    Dropped,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SyncConflict {
+    pub action: Uuid,
+    pub item_id: String,
+    pub reason: ConflictReason,
+    pub resolution: ConflictResolution,
+}
+
+/// Outcome of replaying the queue
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SyncReport {
+    pub applied: Vec<Uuid>,
+    pub conflicts: Vec<SyncConflict>,
+    /// Actions not attempted because the marketplace became unreachable
+    pub unsent: usize,
+}
+
+/// Outcome of a publish or review
+#[derive(Debug, Clone, PartialEq)]
+pub enum Submission {
+    /// Sent to the marketplace; the published item ID for publishes
+    Sent(Option<String>),
+    /// Queued for the next sync
+    Queued(Uuid),
+}
+
+/// Marketplace actions waiting to be synced
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SyncQueue {
+    actions: Vec<QueuedAction>,
+    #[serde(skip)]
+    path: Option<PathBuf>,
+}
+
+impl SyncQueue {
+    /// Queue file for a configuration
+    pub fn default_path(config: &Config) -> PathBuf {
+        config.app.data_dir.join(SYNC_QUEUE_FILE)
+    }
+
+    /// Load the queue from `path`, starting empty if it does not exist yet.
+    /// [`save`](Self::save) writes back to the same file.
+    pub fn open(path: &Path) -> CanvasResult<Self> {
+        let mut queue: Self = if path.exists() {
+            serde_json::from_str(&std::fs::read_to_string(path)?)?
+        } else {
+            Self::default()
+        };
+        queue.path = Some(path.to_path_buf());
+        Ok(queue)
+    }
+
+    pub fn save(&self) -> CanvasResult<()> {
+        let path = self
+            .path
+            .as_ref()
+            .ok_or_else(|| CanvasError::InvalidState("Sync queue was not opened from a file".to_string()))?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    pub fn pending(&self) -> &[QueuedAction] {
+        &self.actions
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.actions.is_empty()
+    }
+
+    pub fn enqueue(&mut self, action: PendingAction) -> Uuid {
+        let id = Uuid::new_v4();
+        self.actions.push(QueuedAction {
+            id,
+            queued_at: Utc::now(),
+            action,
+        });
+        id
+    }
+
+    /// Drop a queued action. Returns whether it was queued.
+    pub fn discard(&mut self, id: Uuid) -> bool {
+        let before = self.actions.len();
+        self.actions.retain(|a| a.id != id);
+        self.actions.len() < before
+    }
+
+    /// Drop every queued action on an item. Returns how many there were.
+    pub fn discard_item(&mut self, item_id: &str) -> usize {
+        let before = self.actions.len();
+        self.actions.retain(|a| a.action.item_id() != item_id);
+        before - self.actions.len()
+    }
+
+    /// Publish an item, or queue the publish if `remote` is `None` (offline)
+    /// or unreachable. The cached listing is recorded as the base version a
+    /// later sync checks for conflicts.
+    pub fn publish(
+        &mut self,
+        remote: Option<&dyn MarketplaceRemote>,
+        cache: &MarketplaceCache,
+        item: &MarketplaceItem,
+        content: &[u8],
+    ) -> CanvasResult<Submission> {
+        if let Some(remote) = remote {
+            match remote.publish(item, content) {
+                Ok(id) => return Ok(Submission::Sent(Some(id))),
+                Err(CanvasError::Network(e)) => log::warn!("Marketplace unreachable, queueing publish: {}", e),
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(Submission::Queued(self.enqueue(PendingAction::Publish {
+            item: item.clone(),
+            content: encode_hex(content),
+            base_version: cache.get_item(&item.id).map(|listed| listed.version.clone()),
+        })))
+    }
+
+    /// Submit a review, or queue it if `remote` is `None` (offline) or unreachable
+    pub fn review(&mut self, remote: Option<&dyn MarketplaceRemote>, review: &Review) -> CanvasResult<Submission> {
+        if let Some(remote) = remote {
+            match remote.submit_review(review) {
+                Ok(()) => return Ok(Submission::Sent(None)),
+                Err(CanvasError::Network(e)) => log::warn!("Marketplace unreachable, queueing review: {}", e),
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(Submission::Queued(self.enqueue(PendingAction::Review { review: review.clone() })))
+    }
+
+    /// Replay queued actions in the order they were made.
+    ///
+    /// Applied actions and conflicts resolved by `policy` leave the queue.
+    /// Syncing stops at the first network error, leaving that action and
+    /// everything after it queued; any other error also stops the sync and
+    /// is returned.
+    pub fn sync(&mut self, remote: &dyn MarketplaceRemote, policy: ConflictPolicy) -> CanvasResult<SyncReport> {
+        let mut report = SyncReport::default();
+        let mut actions = std::mem::take(&mut self.actions).into_iter();
+
+        while let Some(queued) = actions.next() {
+            let outcome = conflict(remote, &queued.action).and_then(|reason| match (reason, policy) {
+                (None, _) => apply(remote, &queued.action).map(|()| None),
+                // A removed item cannot be reviewed, whatever the policy
+                (Some(reason), ConflictPolicy::KeepLocal) if reason != ConflictReason::ItemRemoved => {
+                    apply(remote, &queued.action).map(|()| Some((reason, ConflictResolution::Applied)))
+                }
+                (Some(reason), ConflictPolicy::KeepRemote) => Ok(Some((reason, ConflictResolution::Dropped))),
+                (Some(reason), _) => Ok(Some((reason, ConflictResolution::Deferred))),
+            });
+            match outcome {
+                Ok(None) => report.applied.push(queued.id),
+                Ok(Some((reason, resolution))) => {
+                    match resolution {
+                        ConflictResolution::Applied => report.applied.push(queued.id),
+                        ConflictResolution::Deferred => self.actions.push(queued.clone()),
+                        ConflictResolution::Dropped => {}
+                    }
+                    report.conflicts.push(SyncConflict {
+                        action: queued.id,
+                        item_id: queued.action.item_id().to_string(),
+                        reason,
+                        resolution,
+                    });
+                }
+                Err(e) => {
+                    self.actions.push(queued);
+                    let rest: Vec<QueuedAction> = actions.collect();
+                    report.unsent = rest.len() + 1;
+                    self.actions.extend(rest);
+                    match e {
+                        CanvasError::Network(e) => {
+                            log::warn!("Marketplace unreachable, sync paused: {}", e);
+                            break;
+                        }
+                        e => return Err(e),
+                    }
+                }
+            }
+        }
+        Ok(report)
+    }
+}
+
+/// Why `action` conflicts with the marketplace's current state, if it does
+fn conflict(remote: &dyn MarketplaceRemote, action: &PendingAction) -> CanvasResult<Option<ConflictReason>> {
+    match action {
+        PendingAction::Publish { item, base_version, .. } => {
+            let remote_version = remote.get_item(&item.id)?.map(|listed| listed.version);
+            Ok(match remote_version {
+                Some(remote) if Some(&remote) != base_version.as_ref() => Some(ConflictReason::RemoteVersionChanged {
+                    base: base_version.clone(),
+                    remote,
+                }),
+                _ => None,
+            })
+        }
+        PendingAction::Review { review } => {
+            if remote.get_item(&review.item_id)?.is_none() {
+                return Ok(Some(ConflictReason::ItemRemoved));
+            }
+            Ok(remote
+                .user_review(&review.item_id, &review.user_id)?
+                .filter(|existing| existing.updated_at > review.updated_at)
+                .map(|existing| ConflictReason::NewerRemoteReview {
+                    remote_updated_at: existing.updated_at,
+                }))
+        }
+    }
+}
+
+fn apply(remote: &dyn MarketplaceRemote, action: &PendingAction) -> CanvasResult<()> {
+    match action {
+        PendingAction::Publish { item, content, .. } => {
+            let content = decode_hex(content)
+                .ok_or_else(|| CanvasError::Validation(format!("Queued package of '{}' is not valid hex", item.id)))?;
+            remote.publish(item, &content)?;
+        }
+        PendingAction::Review { review } => remote.submit_review(review)?,
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct FakeRemote {
+        online: bool,
+        items: Mutex<BTreeMap<String, MarketplaceItem>>,
+        reviews: Mutex<Vec<Review>>,
+    }
+
+    impl FakeRemote {
+        fn check(&self) -> CanvasResult<()> {
+            if self.online {
+                Ok(())
+            } else {
+                Err(CanvasError::Network("offline".to_string()))
+            }
+        }
+    }
+
+    impl MarketplaceRemote for FakeRemote {
+        fn fetch_index(&self) -> CanvasResult<Vec<MarketplaceItem>> {
+            self.check()?;
+            Ok(self.items.lock().unwrap().values().cloned().collect())
+        }
+
+        fn get_item(&self, item_id: &str) -> CanvasResult<Option<MarketplaceItem>> {
+            self.check()?;
+            Ok(self.items.lock().unwrap().get(item_id).cloned())
+        }
+
+        fn user_review(&self, item_id: &str, user_id: &str) -> CanvasResult<Option<Review>> {
+            self.check()?;
+            let reviews = self.reviews.lock().unwrap();
+            Ok(reviews.iter().find(|r| r.item_id == item_id && r.user_id == user_id).cloned())
+        }
+
+        fn publish(&self, item: &MarketplaceItem, _content: &[u8]) -> CanvasResult<String> {
+            self.check()?;
+            self.items.lock().unwrap().insert(item.id.clone(), item.clone());
+            Ok(item.id.clone())
+        }
+
+        fn submit_review(&self, review: &Review) -> CanvasResult<()> {
+            self.check()?;
+            self.reviews.lock().unwrap().push(review.clone());
+            Ok(())
+        }
+    }
+
+    fn item(id: &str, version: &str) -> MarketplaceItem {
+        let mut item = crate::marketplace::test_package().item.metadata;
+        item.id = id.to_string();
+        item.name = id.to_string();
+        item.version = version.to_string();
+        item
+    }
+
+    fn review(item_id: &str) -> Review {
+        Review {
+            id: Uuid::new_v4().to_string(),
+            item_id: item_id.to_string(),
+            user_id: "dana".to_string(),
+            rating: 4,
+            title: "Solid".to_string(),
+            content: String::new(),
+            pros: vec![],
+            cons: vec![],
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            helpful_votes: 0,
+            verified_purchase: false,
+        }
+    }
+
+    #[test]
+    fn test_offline_queue_syncs_with_conflicts() {
+        let mut remote = FakeRemote {
+            online: true,
+            ..FakeRemote::default()
+        };
+        remote.items.lock().unwrap().insert("vault".to_string(), item("vault", "1.0.0"));
+        let mut cache = MarketplaceCache::default();
+        let (found, cached) = browse(&mut cache, Some(&remote), "vault", &SearchFilters::default()).unwrap();
+        assert_eq!((found.len(), cached), (1, false));
+
+        remote.online = false;
+        let (found, cached) = browse(&mut cache, Some(&remote), "", &SearchFilters::default()).unwrap();
+        assert_eq!((found.len(), cached), (1, true));
+
+        let mut queue = SyncQueue::default();
+        let upgrade = queue.publish(Some(&remote), &cache, &item("vault", "1.1.0"), b"wasm").unwrap();
+        let fresh = queue.publish(None, &cache, &item("oracle", "0.1.0"), b"wasm").unwrap();
+        queue.review(None, &review("vault")).unwrap();
+        assert!(matches!(upgrade, Submission::Queued(_)) && matches!(fresh, Submission::Queued(_)));
+        assert_eq!(queue.sync(&remote, ConflictPolicy::Defer).unwrap().unsent, 3);
+
+        // Someone else publishes the vault while we are offline
+        remote.online = true;
+        remote.items.lock().unwrap().insert("vault".to_string(), item("vault", "1.0.5"));
+        let report = queue.sync(&remote, ConflictPolicy::Defer).unwrap();
+        assert_eq!(report.applied.len(), 2);
+        assert_eq!(
+            report.conflicts[0].reason,
+            ConflictReason::RemoteVersionChanged {
+                base: Some("1.0.0".to_string()),
+                remote: "1.0.5".to_string()
+            }
+        );
+        assert_eq!(queue.pending().len(), 1);
+
+        let report = queue.sync(&remote, ConflictPolicy::KeepLocal).unwrap();
+        assert_eq!(report.applied.len(), 1);
+        assert!(queue.is_empty());
+        assert_eq!(remote.items.lock().unwrap()["vault"].version, "1.1.0");
+        assert_eq!(remote.reviews.lock().unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,378 @@
+//! Try-before-install previews
+//!
+//! A previewed custom node is unpacked into a throwaway sandbox directory and
+//! loaded into a registry of its own, with the capabilities it requests granted
+//! only there and with budgets far below the installed defaults. Its shipped
+//! test cases and documented examples are run, and the results are shown next
+//! to the requested capabilities so the user can decide whether to install it.
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use super::{CustomNodeItem, MarketplaceClient, MarketplaceItem, MarketplaceItemType, TemplateItem};
+use crate::{
+    error::{CanvasError, CanvasResult},
+    nodes::{
+        custom::{
+            check_outputs, CustomNodeRegistry, NodeCapability, NodeTestCase, NodeTestReport, NodeTestResult,
+            ResourceLimits, GRANTS_FILE, WASM_PAGE_SIZE,
+        },
+        decode_hex, encode_hex, AssetStore,
+    },
+    wasm::host,
+};
+
+/// Budgets for code that has not been installed yet
+pub const PREVIEW_LIMITS: ResourceLimits = ResourceLimits {
+    fuel: 1_000_000,
+    memory_bytes: 16 * WASM_PAGE_SIZE,
+};
+
+/// A custom node as distributed: its listing and WASM module
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NodePackage {
+    pub item: CustomNodeItem,
+    /// WASM module, 0x-hex
+    #[serde(default)]
+    pub module: Option<String>,
+    /// Assets the node references, such as its icon: 0x-hex content by hash
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub assets: BTreeMap<String, String>,
+}
+
+impl NodePackage {
+    pub fn module_bytes(&self) -> CanvasResult<Option<Vec<u8>>> {
+        self.module
+            .as_deref()
+            .map(|hex| {
+                decode_hex(hex).ok_or_else(|| {
+                    CanvasError::Validation(format!("Module of '{}' is not valid hex", self.item.node_definition.id))
+                })
+            })
+            .transpose()
+    }
+
+    /// Bundled assets, each checked against the hash it is listed under
+    pub fn asset_bytes(&self) -> CanvasResult<Vec<(String, Vec<u8>)>> {
+        self.assets
+            .iter()
+            .map(|(hash, hex)| {
+                let node_id = &self.item.node_definition.id;
+                let bytes = decode_hex(hex)
+                    .ok_or_else(|| CanvasError::Validation(format!("Asset {} of '{}' is not valid hex", hash, node_id)))?;
+                let actual = encode_hex(&host::hash(host::HashAlgorithm::Sha256, &bytes));
+                if actual.trim_start_matches("0x") != hash {
+                    return Err(CanvasError::Validation(format!(
+                        "Asset {} of '{}' does not match its hash",
+                        hash, node_id
+                    )));
+                }
+                Ok((hash.clone(), bytes))
+            })
+            .collect()
+    }
+}
+
+/// Downloaded item content
+#[derive(Debug, Clone)]
+pub enum PreviewContent {
+    Node(NodePackage),
+    Template(TemplateItem),
+}
+
+/// Temporary directory removed when dropped
+struct SandboxDir(PathBuf);
+
+impl SandboxDir {
+    fn create() -> CanvasResult<Self> {
+        let path = std::env::temp_dir().join(format!("canvas-preview-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&path)?;
+        Ok(Self(path))
+    }
+}
+
+impl Drop for SandboxDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+/// What running an item in the sandbox showed
+#[derive(Debug, Clone)]
+pub struct ItemPreview {
+    pub item: MarketplaceItem,
+    /// Capabilities the item asks to be granted on install
+    pub requested_capabilities: Vec<NodeCapability>,
+    /// Results of the test cases shipped with a custom node
+    pub tests: Option<NodeTestReport>,
+    /// Results of the documented examples
+    pub examples: Vec<NodeTestResult>,
+    pub content: PreviewContent,
+}
+
+impl ItemPreview {
+    /// Whether every test and example that ran passed
+    pub fn passed(&self) -> bool {
+        self.tests.as_ref().map_or(true, |t| t.failures().is_empty()) && self.examples.iter().all(|e| e.passed())
+    }
+
+    /// Install a previewed custom node into `nodes_dir`, granting the
+    /// capabilities it requested and adding its assets to `assets`. Call once
+    /// the user has confirmed.
+    pub fn install(&self, nodes_dir: &Path, assets: &AssetStore) -> CanvasResult<()> {
+        let PreviewContent::Node(package) = &self.content else {
+            return Err(CanvasError::Validation(format!(
+                "'{}' is not a custom node; templates are used from the marketplace directly",
+                self.item.id
+            )));
+        };
+        let mut definition = package.item.node_definition.clone();
+        if definition.license.is_none() && !self.item.license.is_empty() {
+            definition.license = Some(self.item.license.clone());
+        }
+        std::fs::create_dir_all(nodes_dir)?;
+        if let Some(module) = package.module_bytes()? {
+            let file = format!("{}.wasm", definition.id);
+            std::fs::write(nodes_dir.join(&file), module)?;
+            definition.set_module_path(&file);
+        }
+        for (hash, bytes) in package.asset_bytes()? {
+            assets.put(&bytes)?;
+            log::debug!("Stored asset {} of '{}'", hash, definition.id);
+        }
+        if let Some(icon) = definition.icon.as_ref().filter(|icon| !assets.contains(icon)) {
+            log::warn!("Icon {} of '{}' is not in the package; the editor shows the default", icon.hash, definition.id);
+        }
+
+        let grants_path = nodes_dir.join(GRANTS_FILE);
+        let mut grants: HashMap<String, BTreeSet<NodeCapability>> = if grants_path.exists() {
+            serde_json::from_str(&std::fs::read_to_string(&grants_path)?)?
+        } else {
+            HashMap::new()
+        };
+        grants
+            .entry(definition.id.clone())
+            .or_default()
+            .extend(self.requested_capabilities.iter().copied());
+        std::fs::write(&grants_path, serde_json::to_string_pretty(&grants)?)?;
+        std::fs::write(
+            nodes_dir.join(format!("{}.json", definition.id)),
+            serde_json::to_string_pretty(&definition)?,
+        )?;
+        log::info!("Installed custom node '{}' {}", definition.id, definition.version);
+        Ok(())
+    }
+}
+
+/// Load a custom node package in a sandbox and run its tests and examples
+pub fn preview_custom_node(package: NodePackage, limits: ResourceLimits) -> CanvasResult<ItemPreview> {
+    package.asset_bytes()?;
+    let sandbox = SandboxDir::create()?;
+    let mut definition = package.item.node_definition.clone();
+    let node_id = definition.id.clone();
+    if let Some(module) = package.module_bytes()? {
+        let path = sandbox.0.join(format!("{}.wasm", node_id));
+        std::fs::write(&path, module)?;
+        definition.set_module_path(&path.to_string_lossy());
+    } else if definition.wasm_module.is_some() {
+        return Err(CanvasError::Validation(format!("Package of '{}' does not include its WASM module", node_id)));
+    }
+
+    let mut registry = CustomNodeRegistry::new().with_default_limits(limits);
+    registry.grant_capabilities(&node_id, definition.capabilities.iter().copied());
+    registry.register_node(definition.clone())?;
+
+    let tests = registry.run_node_tests(&node_id)?;
+    let examples = package
+        .item
+        .examples
+        .iter()
+        .map(|example| {
+            let case = NodeTestCase {
+                name: example.name.clone(),
+                inputs: example.input_data.clone(),
+                properties: HashMap::new(),
+                expected_outputs: example.expected_output.clone(),
+            };
+            let failure = match registry.execute_node(&node_id, case.inputs.clone(), HashMap::new()) {
+                Ok(outputs) => check_outputs(&case, &outputs),
+                Err(e) => Some(e.to_string()),
+            };
+            NodeTestResult {
+                name: case.name,
+                failure,
+            }
+        })
+        .collect();
+
+    Ok(ItemPreview {
+        item: package.item.metadata.clone(),
+        requested_capabilities: definition.capabilities.clone(),
+        tests: Some(tests),
+        examples,
+        content: PreviewContent::Node(package),
+    })
+}
+
+/// Preview a template; templates run no code, so only the listing is checked
+pub fn preview_template(template: TemplateItem) -> CanvasResult<ItemPreview> {
+    let unknown = template
+        .graph
+        .edges
+        .iter()
+        .flat_map(|(from, to)| [from, to])
+        .filter(|id| !template.graph.nodes.contains(id))
+        .count();
+    if unknown > 0 {
+        return Err(CanvasError::Validation(format!(
+            "Template '{}' has {} connection ends that reference no node",
+            template.metadata.id, unknown
+        )));
+    }
+    Ok(ItemPreview {
+        item: template.metadata.clone(),
+        requested_capabilities: Vec::new(),
+        tests: None,
+        examples: Vec::new(),
+        content: PreviewContent::Template(template),
+    })
+}
+
+impl MarketplaceClient {
+    /// Download an item and preview it without installing it
+    pub async fn preview_item(&mut self, item_id: &str, limits: ResourceLimits) -> CanvasResult<ItemPreview> {
+        let item = self.get_item(item_id).await?;
+        let content = self.download_item(item_id).await?;
+        match item.item_type {
+            MarketplaceItemType::CustomNode => preview_custom_node(serde_json::from_slice(&content)?, limits),
+            MarketplaceItemType::Template => preview_template(serde_json::from_slice(&content)?),
+            _ => Err(CanvasError::Validation(format!(
+                "Items of type {:?} cannot be previewed",
+                item.item_type
+            ))),
+        }
+    }
+}
+
+/// Package a custom node for upload, embedding its WASM module and the
+/// assets it references from `assets`
+pub fn package_custom_node(item: &CustomNodeItem, assets: &AssetStore) -> CanvasResult<NodePackage> {
+    let module = match &item.node_definition.wasm_module {
+        Some(info) => Some(encode_hex(&std::fs::read(&info.module_path)?)),
+        None => None,
+    };
+    let mut bundled = BTreeMap::new();
+    if let Some(icon) = &item.node_definition.icon {
+        let (bytes, _) = assets.get(&icon.hash)?.ok_or_else(|| {
+            CanvasError::NotFound(format!(
+                "Icon {} of '{}' is not in the asset store",
+                icon.hash, item.node_definition.id
+            ))
+        })?;
+        bundled.insert(icon.hash.clone(), encode_hex(&bytes));
+    }
+    Ok(NodePackage {
+        item: item.clone(),
+        module,
+        assets: bundled,
+    })
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+    use super::*;
+    use crate::{marketplace::NodeExample, nodes::custom::CustomNodeBuilder};
+    use chrono::Utc;
+
+    pub(crate) fn package() -> NodePackage {
+        let definition = CustomNodeBuilder::new("clamp".to_string(), "Clamp".to_string())
+            .output("value".to_string(), "number".to_string(), "Clamped value".to_string())
+            .composite("{}".to_string())
+            .test_case(NodeTestCase {
+                name: "passes through".to_string(),
+                inputs: HashMap::new(),
+                properties: HashMap::new(),
+                expected_outputs: HashMap::from([("value".to_string(), serde_json::Value::Null)]),
+            })
+            .build();
+        let metadata = MarketplaceItem {
+            id: "clamp".to_string(),
+            name: "Clamp".to_string(),
+            description: String::new(),
+            author: "alice".to_string(),
+            version: "0.1.0".to_string(),
+            item_type: MarketplaceItemType::CustomNode,
+            tags: Vec::new(),
+            rating: 0.0,
+            downloads: 0,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            price: None,
+            license: "MIT".to_string(),
+            dependencies: Vec::new(),
+            compatibility: Vec::new(),
+            size_bytes: 0,
+            hash: String::new(),
+            capabilities: Vec::new(),
+            compatibility_results: Vec::new(),
+            registry: None,
+            signature: None,
+            publisher_signature: None,
+            frozen: None,
+        };
+        NodePackage {
+            item: CustomNodeItem {
+                metadata,
+                node_definition: definition,
+                examples: vec![NodeExample {
+                    name: "clamps high values".to_string(),
+                    description: String::new(),
+                    input_data: HashMap::new(),
+                    expected_output: HashMap::from([("value".to_string(), serde_json::json!(10))]),
+                    graph_snippet: String::new(),
+                }],
+                documentation: String::new(),
+            },
+            module: None,
+            assets: BTreeMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_preview_runs_tests_and_examples_before_install() {
+        let preview = preview_custom_node(package(), PREVIEW_LIMITS).unwrap();
+        assert!(preview.tests.as_ref().unwrap().passed());
+        assert_eq!(preview.examples.len(), 1);
+        assert!(!preview.passed());
+
+        let nodes = tempfile::tempdir().unwrap();
+        preview.install(nodes.path(), &AssetStore::new(nodes.path().join("assets"))).unwrap();
+        let registry = CustomNodeRegistry::load_dir(nodes.path()).unwrap();
+        assert!(registry.get_node("clamp").is_some());
+    }
+
+    #[test]
+    fn test_packages_carry_the_node_icon() {
+        let publisher = tempfile::tempdir().unwrap();
+        let store = AssetStore::new(publisher.path());
+        let icon = store.put(b"<svg xmlns=\"http://www.w3.org/2000/svg\"/>").unwrap();
+        let mut item = package().item;
+        item.node_definition.icon = Some(icon.clone());
+
+        let mut packaged = package_custom_node(&item, &store).unwrap();
+        assert_eq!(packaged.asset_bytes().unwrap().len(), 1);
+        let preview = preview_custom_node(packaged.clone(), PREVIEW_LIMITS).unwrap();
+        let installer = tempfile::tempdir().unwrap();
+        let installed = AssetStore::new(installer.path().join("assets"));
+        preview.install(installer.path(), &installed).unwrap();
+        assert!(installed.contains(&icon));
+
+        packaged.assets.insert(icon.hash.clone(), encode_hex(b"<svg/>"));
+        assert!(preview_custom_node(packaged, PREVIEW_LIMITS).is_err());
+        assert!(package_custom_node(&item, &AssetStore::new(installer.path().join("empty"))).is_err());
+    }
+}
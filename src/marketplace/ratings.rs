@@ -0,0 +1,253 @@
+//! Review helpfulness votes and weighted ratings
+//!
+//! Each user has at most one vote per review: voting again replaces the
+//! earlier vote, and a review's `helpful_votes` is always the number of
+//! current "helpful" votes. An item's rating is the mean of its review stars
+//! weighted by age (halving every `half_life_days`) and by whether the reviewer
+//! bought the item. Ratings are recomputed whenever reviews change, and
+//! [`LocalMarketplace::recompute_ratings`] rebuilds every item's rating, for
+//! instance after the weights change.
+
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::{http::encode_path_segment, LocalMarketplace, MarketplaceClient, Review};
+use crate::error::{CanvasError, CanvasResult};
+
+const SECONDS_PER_DAY: f64 = 86_400.0;
+
+/// Votes on each review, by review ID then voter
+pub(super) type ReviewVotes = BTreeMap<String, BTreeMap<String, bool>>;
+
+/// How much each review counts towards an item's rating
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct RatingWeights {
+    /// Age at which a review counts half
+    pub half_life_days: f64,
+    /// Multiplier for reviews from verified purchases
+    pub verified_purchase: f64,
+}
+
+impl Default for RatingWeights {
+    fn default() -> Self {
+        Self {
+            half_life_days: 180.0,
+            verified_purchase: 2.0,
+        }
+    }
+}
+
+impl RatingWeights {
+    fn weight(&self, review: &Review, now: DateTime<Utc>) -> f64 {
+        let age_days = (now - review.created_at).num_seconds().max(0) as f64 / SECONDS_PER_DAY;
+        let recency = 0.5f64.powf(age_days / self.half_life_days);
+        if review.verified_purchase {
+            recency * self.verified_purchase
+        } else {
+            recency
+        }
+    }
+}
+
+/// Weighted mean of review stars; `None` without reviews
+pub fn weighted_rating<'a>(
+    reviews: impl IntoIterator<Item = &'a Review>,
+    weights: &RatingWeights,
+    now: DateTime<Utc>,
+) -> Option<f64> {
+    let (sum, total) = reviews.into_iter().fold((0.0, 0.0), |(sum, total), review| {
+        let weight = weights.weight(review, now);
+        (sum + weight * review.rating as f64, total + weight)
+    });
+    (total > 0.0).then(|| sum / total)
+}
+
+impl LocalMarketplace {
+    /// Vote on whether a review is helpful, replacing the user's earlier vote.
+    /// Returns the review's new helpful count. Users vote through
+    /// [`CommunityManager::vote_review`](crate::community::CommunityManager::vote_review).
+    pub(crate) fn vote_review(&mut self, review_id: &str, user_id: &str, helpful: bool) -> CanvasResult<u32> {
+        let review = self
+            .reviews
+            .get(review_id)
+            .ok_or_else(|| CanvasError::NotFound(format!("Review '{}' not found", review_id)))?;
+        if review.user_id == user_id {
+            return Err(CanvasError::PermissionDenied("Users cannot vote on their own reviews".to_string()));
+        }
+        self.review_votes
+            .entry(review_id.to_string())
+            .or_default()
+            .insert(user_id.to_string(), helpful);
+        Ok(self.recount_votes(review_id))
+    }
+
+    /// Withdraw a user's vote on a review. Returns the review's new helpful count.
+    pub(crate) fn retract_review_vote(&mut self, review_id: &str, user_id: &str) -> CanvasResult<u32> {
+        let removed = self.review_votes.get_mut(review_id).and_then(|votes| votes.remove(user_id));
+        if removed.is_none() {
+            return Err(CanvasError::NotFound(format!(
+                "User '{}' has not voted on review '{}'",
+                user_id, review_id
+            )));
+        }
+        Ok(self.recount_votes(review_id))
+    }
+
+    /// A user's vote on a review, if any
+    pub fn review_vote(&self, review_id: &str, user_id: &str) -> Option<bool> {
+        self.review_votes.get(review_id).and_then(|votes| votes.get(user_id)).copied()
+    }
+
+    fn recount_votes(&mut self, review_id: &str) -> u32 {
+        let helpful = self
+            .review_votes
+            .get(review_id)
+            .map_or(0, |votes| votes.values().filter(|v| **v).count() as u32);
+        if let Some(review) = self.reviews.get_mut(review_id) {
+            review.helpful_votes = helpful;
+        }
+        helpful
+    }
+
+    /// Drop every vote cast by a user, e.g. when their account is deleted
+    pub(super) fn erase_user_votes(&mut self, user_id: &str) {
+        let affected: Vec<String> = self
+            .review_votes
+            .iter_mut()
+            .filter_map(|(review_id, votes)| votes.remove(user_id).map(|_| review_id.clone()))
+            .collect();
+        for review_id in affected {
+            self.recount_votes(&review_id);
+        }
+        self.review_votes.retain(|_, votes| !votes.is_empty());
+    }
+
+    /// Recompute an item's rating from its reviews; items without reviews keep
+    /// their current rating
+    pub fn recompute_rating(&mut self, item_id: &str, weights: &RatingWeights) -> Option<f64> {
+        let rating = weighted_rating(self.reviews.values().filter(|r| r.item_id == item_id), weights, Utc::now())?;
+        if let Some(item) = self.items.get_mut(item_id) {
+            item.rating = rating;
+        }
+        if let Some(node) = self.custom_nodes.get_mut(item_id) {
+            node.metadata.rating = rating;
+        }
+        Some(rating)
+    }
+
+    /// Recompute the rating of every reviewed item. Returns how many changed.
+    pub fn recompute_ratings(&mut self, weights: &RatingWeights) -> usize {
+        let mut reviewed: Vec<String> = self.reviews.values().map(|r| r.item_id.clone()).collect();
+        reviewed.sort();
+        reviewed.dedup();
+        reviewed
+            .into_iter()
+            .filter(|id| {
+                let before = self.items.get(id).map(|item| item.rating);
+                let after = self.recompute_rating(id, weights);
+                after.is_some() && before != after
+            })
+            .count()
+    }
+
+    /// Reviews of an item, most helpful first
+    pub fn most_helpful_reviews(&self, item_id: &str) -> Vec<&Review> {
+        let mut reviews = self.get_reviews(item_id);
+        reviews.sort_by(|a, b| b.helpful_votes.cmp(&a.helpful_votes).then_with(|| b.created_at.cmp(&a.created_at)));
+        reviews
+    }
+}
+
+impl MarketplaceClient {
+    /// Vote on whether a review is helpful, replacing any earlier vote
+    pub async fn vote_review(&self, review_id: &str, helpful: bool) -> CanvasResult<()> {
+        log::info!("Voting on review {} (helpful: {})", review_id, helpful);
+        let path = format!("/reviews/{}/vote", encode_path_segment(review_id));
+        let body = serde_json::json!({ "helpful": helpful });
+        self.send(self.request("PUT", &path), Some(body)).await?;
+        Ok(())
+    }
+
+    /// Withdraw a vote on a review
+    pub async fn retract_review_vote(&self, review_id: &str) -> CanvasResult<()> {
+        log::info!("Retracting vote on review {}", review_id);
+        let path = format!("/reviews/{}/vote", encode_path_segment(review_id));
+        self.send(self.request("DELETE", &path), None).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn review(id: &str, user: &str, rating: u8, age_days: i64, verified: bool) -> Review {
+        let at = Utc::now() - Duration::days(age_days);
+        Review {
+            id: id.to_string(),
+            item_id: "clamp".to_string(),
+            user_id: user.to_string(),
+            rating,
+            title: String::new(),
+            content: String::new(),
+            pros: Vec::new(),
+            cons: Vec::new(),
+            created_at: at,
+            updated_at: at,
+            helpful_votes: 0,
+            verified_purchase: verified,
+        }
+    }
+
+    #[test]
+    fn test_one_vote_per_user_and_weighted_rating() {
+        let mut marketplace = LocalMarketplace::new();
+        marketplace.add_custom_node(super::super::preview::tests::package().item).unwrap();
+        marketplace.add_review(review("old", "alice", 1, 720, false)).unwrap();
+        marketplace.add_review(review("new", "bob", 5, 1, true)).unwrap();
+
+        assert_eq!(marketplace.vote_review("new", "carol", true).unwrap(), 1);
+        assert_eq!(marketplace.vote_review("new", "carol", true).unwrap(), 1);
+        assert_eq!(marketplace.vote_review("new", "dave", true).unwrap(), 2);
+        assert_eq!(marketplace.vote_review("new", "carol", false).unwrap(), 1);
+        assert!(marketplace.vote_review("new", "bob", true).is_err());
+        marketplace.erase_user_votes("dave");
+        assert_eq!(marketplace.most_helpful_reviews("clamp")[0].helpful_votes, 0);
+        assert_eq!(marketplace.review_vote("new", "carol"), Some(false));
+
+        let rating = marketplace.get_item("clamp").unwrap().rating;
+        assert!(rating > 4.8, "recent verified review should dominate, got {}", rating);
+        let unweighted = RatingWeights {
+            half_life_days: f64::INFINITY,
+            verified_purchase: 1.0,
+        };
+        assert_eq!(marketplace.recompute_ratings(&unweighted), 1);
+        assert_eq!(marketplace.get_item("clamp").unwrap().rating, 3.0);
+    }
+
+    #[tokio::test]
+    async fn test_client_sends_and_retracts_review_votes() {
+        use std::io::Read;
+
+        let server = tiny_http::Server::http("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", server.server_addr().to_ip().unwrap());
+        let (sent, received) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            for mut request in server.incoming_requests() {
+                let mut body = String::new();
+                request.as_reader().read_to_string(&mut body).unwrap();
+                sent.send(format!("{} {} {}", request.method(), request.url(), body)).unwrap();
+                request.respond(tiny_http::Response::empty(204)).unwrap();
+            }
+        });
+
+        let client = MarketplaceClient::new(url);
+        client.vote_review("clamp/1", true).await.unwrap();
+        client.retract_review_vote("clamp/1").await.unwrap();
+        assert_eq!(received.recv().unwrap(), r#"PUT /reviews/clamp%2F1/vote {"helpful":true}"#);
+        assert_eq!(received.recv().unwrap().trim_end(), "DELETE /reviews/clamp%2F1/vote");
+    }
+}
@@ -0,0 +1,262 @@
+//! Item recommendations
+//!
+//! Two signals are combined. Co-occurrence: items often installed by the same
+//! users as the ones already in use score by cosine similarity of their
+//! install sets. Content: items whose tags, category and node type share words
+//! with the open graph's node types (or with the items a user already has)
+//! score by cosine similarity of word counts. Both signals only need data kept
+//! locally, so recommendations keep working when the marketplace API cannot be
+//! reached.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::{Deserialize, Serialize};
+
+use super::{LocalMarketplace, MarketplaceClient, MarketplaceItem, UsageStats};
+use crate::{error::CanvasResult, types::VisualGraph};
+
+/// Weight of the content signal relative to co-occurrence
+const CONTENT_WEIGHT: f64 = 0.5;
+/// Weight of the item's rating, used to break ties
+const RATING_WEIGHT: f64 = 0.01;
+
+/// What to recommend for
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RecommendationContext<'a> {
+    pub user_id: Option<&'a str>,
+    /// Graph open in the editor
+    pub graph: Option<&'a VisualGraph>,
+}
+
+/// A recommended item
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Recommendation {
+    pub item_id: String,
+    pub score: f64,
+    /// Why the item was recommended
+    pub reasons: Vec<String>,
+}
+
+type WordCounts = BTreeMap<String, f64>;
+
+/// Lowercase words of a name, splitting camel case, `-` and `_`
+fn words(text: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut current = String::new();
+    let mut previous_lower = false;
+    for c in text.chars() {
+        if !c.is_alphanumeric() {
+            previous_lower = false;
+            if !current.is_empty() {
+                words.push(std::mem::take(&mut current));
+            }
+            continue;
+        }
+        if c.is_uppercase() && previous_lower && !current.is_empty() {
+            words.push(std::mem::take(&mut current));
+        }
+        previous_lower = c.is_lowercase() || c.is_numeric();
+        current.extend(c.to_lowercase());
+    }
+    if !current.is_empty() {
+        words.push(current);
+    }
+    words
+}
+
+fn add_words(counts: &mut WordCounts, text: &str, weight: f64) {
+    for word in words(text) {
+        *counts.entry(word).or_default() += weight;
+    }
+}
+
+fn cosine(a: &WordCounts, b: &WordCounts) -> f64 {
+    let dot: f64 = a.iter().filter_map(|(word, x)| b.get(word).map(|y| x * y)).sum();
+    let norm = |v: &WordCounts| v.values().map(|x| x * x).sum::<f64>().sqrt();
+    let denominator = norm(a) * norm(b);
+    if denominator == 0.0 {
+        0.0
+    } else {
+        dot / denominator
+    }
+}
+
+/// Node type distribution of a graph, as word counts
+pub fn graph_profile(graph: &VisualGraph) -> WordCounts {
+    let mut counts = WordCounts::new();
+    for node in &graph.nodes {
+        add_words(&mut counts, &node.node_type, 1.0);
+    }
+    counts
+}
+
+impl LocalMarketplace {
+    /// Words describing an item: its tags, name, and for custom nodes their
+    /// node type and category
+    fn item_profile(&self, item: &MarketplaceItem) -> WordCounts {
+        let mut counts = WordCounts::new();
+        for tag in &item.tags {
+            add_words(&mut counts, tag, 2.0);
+        }
+        add_words(&mut counts, &item.name, 1.0);
+        if let Some(node) = self.custom_nodes.get(&item.id) {
+            add_words(&mut counts, &node.node_definition.id, 2.0);
+            add_words(&mut counts, &node.node_definition.category, 1.0);
+        }
+        counts
+    }
+
+    /// Items already in use in a context: the user's installs and the custom
+    /// nodes placed in the graph
+    fn items_in_use(&self, stats: &UsageStats, context: &RecommendationContext<'_>) -> BTreeSet<String> {
+        let mut used = context.user_id.map(|user| stats.installed_by(user)).unwrap_or_default();
+        if let Some(graph) = context.graph {
+            used.extend(
+                graph
+                    .nodes
+                    .iter()
+                    .filter(|n| self.custom_nodes.contains_key(&n.node_type))
+                    .map(|n| n.node_type.clone()),
+            );
+        }
+        used
+    }
+
+    /// Recommend items for a user and/or the open graph from local data only
+    pub fn recommend(
+        &self,
+        stats: &UsageStats,
+        context: &RecommendationContext<'_>,
+        limit: usize,
+    ) -> Vec<Recommendation> {
+        let used = self.items_in_use(stats, context);
+
+        // Item-item co-occurrence over every user's install set
+        let mut installs: BTreeMap<&str, f64> = BTreeMap::new();
+        let mut together: BTreeMap<(&str, &str), f64> = BTreeMap::new();
+        for set in stats.install_sets() {
+            for a in set {
+                *installs.entry(a.as_str()).or_default() += 1.0;
+                for b in set.iter().filter(|b| *b != a) {
+                    *together.entry((a.as_str(), b.as_str())).or_default() += 1.0;
+                }
+            }
+        }
+
+        let mut profile = context.graph.map(graph_profile).unwrap_or_default();
+        for item in used.iter().filter_map(|id| self.items.get(id)) {
+            for (word, weight) in self.item_profile(item) {
+                *profile.entry(word).or_default() += weight;
+            }
+        }
+
+        let mut recommendations: Vec<Recommendation> = self
+            .items
+            .values()
+            .filter(|item| !used.contains(&item.id))
+            .map(|item| {
+                let mut reasons = Vec::new();
+                let mut cooccurrence = 0.0;
+                for owned in &used {
+                    let Some(both) = together.get(&(owned.as_str(), item.id.as_str())) else {
+                        continue;
+                    };
+                    let normalized = both / (installs[owned.as_str()] * installs[item.id.as_str()]).sqrt();
+                    cooccurrence += normalized;
+                    reasons.push(format!("often installed with {}", owned));
+                }
+                let content = cosine(&profile, &self.item_profile(item));
+                if content > 0.0 {
+                    reasons.push(if context.graph.is_some() {
+                        "matches the nodes in your graph".to_string()
+                    } else {
+                        "similar to items you use".to_string()
+                    });
+                }
+                Recommendation {
+                    item_id: item.id.clone(),
+                    score: cooccurrence + CONTENT_WEIGHT * content + RATING_WEIGHT * item.rating,
+                    reasons,
+                }
+            })
+            .filter(|r| !r.reasons.is_empty())
+            .collect();
+        recommendations.sort_by(|a, b| b.score.total_cmp(&a.score).then_with(|| a.item_id.cmp(&b.item_id)));
+        recommendations.truncate(limit);
+        recommendations
+    }
+}
+
+impl MarketplaceClient {
+    /// Recommended items from the marketplace API, falling back to local data
+    /// when it cannot be reached or has nothing to offer
+    pub async fn recommended_items_or_local(
+        &self,
+        local: &LocalMarketplace,
+        stats: &UsageStats,
+        context: &RecommendationContext<'_>,
+        limit: u32,
+    ) -> CanvasResult<Vec<MarketplaceItem>> {
+        if let Some(user_id) = context.user_id {
+            match self.get_recommended_items(user_id, limit).await {
+                Ok(items) if !items.is_empty() => return Ok(items),
+                Ok(_) => {}
+                Err(e) => log::warn!("Marketplace recommendations unavailable, using local data: {}", e),
+            }
+        }
+        Ok(local
+            .recommend(stats, context, limit as usize)
+            .into_iter()
+            .filter_map(|r| local.get_item(&r.item_id).cloned())
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{Position, VisualNode};
+    use chrono::Utc;
+
+    fn listing(marketplace: &mut LocalMarketplace, id: &str, tags: &[&str]) {
+        let mut item = super::super::preview::tests::package().item;
+        item.metadata.id = id.to_string();
+        item.metadata.name = id.to_string();
+        item.metadata.tags = tags.iter().map(|t| t.to_string()).collect();
+        item.node_definition.id = id.to_string();
+        item.node_definition.category = "Utility".to_string();
+        marketplace.add_custom_node(item).unwrap();
+    }
+
+    #[test]
+    fn test_recommends_by_installs_and_graph_content() {
+        let mut marketplace = LocalMarketplace::new();
+        listing(&mut marketplace, "ERC20Mint", &["token"]);
+        listing(&mut marketplace, "TokenBurn", &["token", "burn"]);
+        listing(&mut marketplace, "Oracle", &["price", "feed"]);
+        let mut stats = UsageStats::new();
+        for user in ["alice", "bob"] {
+            stats.record_install(user, "ERC20Mint", Utc::now()).unwrap();
+            stats.record_install(user, "Oracle", Utc::now()).unwrap();
+        }
+        stats.record_install("carol", "ERC20Mint", Utc::now()).unwrap();
+
+        let context = RecommendationContext {
+            user_id: Some("carol"),
+            graph: None,
+        };
+        let recommended = marketplace.recommend(&stats, &context, 5);
+        assert_eq!(recommended[0].item_id, "Oracle");
+        assert!(recommended.iter().all(|r| r.item_id != "ERC20Mint"));
+
+        let mut graph = VisualGraph::new("burner");
+        graph.add_node(VisualNode::new(uuid::Uuid::new_v4(), "BurnToken", Position::new(0.0, 0.0)));
+        let context = RecommendationContext {
+            user_id: None,
+            graph: Some(&graph),
+        };
+        let recommended = marketplace.recommend(&stats, &context, 5);
+        assert_eq!(recommended[0].item_id, "TokenBurn");
+        assert_eq!(words("ERC20Mint_token"), vec!["erc20", "mint", "token"]);
+    }
+}
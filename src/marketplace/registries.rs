@@ -0,0 +1,383 @@
+//! Multiple marketplace registries
+//!
+//! Besides the official marketplace, items can come from corporate registries
+//! and local folders (`marketplace.registries`). Registries are consulted in
+//! priority order and the first to list an ID wins. A registry with a
+//! namespace owns every ID under it: its items are listed as
+//! `<namespace>/<id>`, and such IDs are never resolved from, or published to,
+//! any other registry, so a public item cannot shadow a private one. A
+//! registry with trusted keys must sign each item it lists; unsigned or
+//! wrongly signed items are dropped from the merged index.
+
+use std::{
+    collections::BTreeSet,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use super::{offline::MarketplaceRemote, HttpMarketplace, MarketplaceItem, Review};
+use crate::{
+    config::{MarketplaceConfig, RegistryConfig},
+    error::{CanvasError, CanvasResult},
+    nodes::{decode_hex, encode_hex},
+    wasm::host::{verify_signature, SignatureScheme},
+};
+
+/// Index file of a folder registry
+pub const FOLDER_INDEX_FILE: &str = "index.json";
+/// Reviews file of a folder registry
+pub const FOLDER_REVIEWS_FILE: &str = "reviews.json";
+/// Directory of a folder registry packages are stored in
+pub const FOLDER_PACKAGES_DIR: &str = "packages";
+
+/// Bytes a registry signs for an item: its ID, version and content hash
+pub fn item_signing_message(item: &MarketplaceItem) -> Vec<u8> {
+    format!("{}@{}:{}", item.id, item.version, item.hash).into_bytes()
+}
+
+/// Sign an item as a registry; used by registry tooling and tests
+pub fn sign_item(item: &mut MarketplaceItem, signing_key: &ed25519_dalek::SigningKey) {
+    use ed25519_dalek::Signer;
+
+    let signature = signing_key.sign(&item_signing_message(item));
+    item.signature = Some(encode_hex(&signature.to_bytes()));
+}
+
+/// Registry kept in a local or shared folder
+pub struct FolderMarketplace {
+    root: PathBuf,
+}
+
+impl FolderMarketplace {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    fn read<T: serde::de::DeserializeOwned + Default>(&self, file: &str) -> CanvasResult<T> {
+        let path = self.root.join(file);
+        if !path.exists() {
+            return Ok(T::default());
+        }
+        Ok(serde_json::from_str(&std::fs::read_to_string(path)?)?)
+    }
+
+    fn write<T: serde::Serialize>(&self, file: &str, value: &T) -> CanvasResult<()> {
+        std::fs::create_dir_all(&self.root)?;
+        std::fs::write(self.root.join(file), serde_json::to_string_pretty(value)?)?;
+        Ok(())
+    }
+}
+
+impl MarketplaceRemote for FolderMarketplace {
+    fn fetch_index(&self) -> CanvasResult<Vec<MarketplaceItem>> {
+        if !self.root.is_dir() {
+            return Err(CanvasError::Network(format!("Registry folder {} is not available", self.root.display())));
+        }
+        self.read(FOLDER_INDEX_FILE)
+    }
+
+    fn get_item(&self, item_id: &str) -> CanvasResult<Option<MarketplaceItem>> {
+        Ok(self.fetch_index()?.into_iter().find(|item| item.id == item_id))
+    }
+
+    fn user_review(&self, item_id: &str, user_id: &str) -> CanvasResult<Option<Review>> {
+        let reviews: Vec<Review> = self.read(FOLDER_REVIEWS_FILE)?;
+        Ok(reviews.into_iter().find(|r| r.item_id == item_id && r.user_id == user_id))
+    }
+
+    fn publish(&self, item: &MarketplaceItem, content: &[u8]) -> CanvasResult<String> {
+        let packages = self.root.join(FOLDER_PACKAGES_DIR);
+        std::fs::create_dir_all(&packages)?;
+        let file = format!("{}-{}.json", item.id.replace('/', "_"), item.version);
+        std::fs::write(packages.join(file), content)?;
+        let mut index: Vec<MarketplaceItem> = self.read(FOLDER_INDEX_FILE)?;
+        index.retain(|listed| listed.id != item.id);
+        index.push(item.clone());
+        self.write(FOLDER_INDEX_FILE, &index)?;
+        Ok(item.id.clone())
+    }
+
+    fn submit_review(&self, review: &Review) -> CanvasResult<()> {
+        let mut reviews: Vec<Review> = self.read(FOLDER_REVIEWS_FILE)?;
+        reviews.retain(|r| r.item_id != review.item_id || r.user_id != review.user_id);
+        reviews.push(review.clone());
+        self.write(FOLDER_REVIEWS_FILE, &reviews)
+    }
+}
+
+/// One configured registry
+pub struct Registry {
+    pub config: RegistryConfig,
+    trusted_keys: Vec<Vec<u8>>,
+    remote: Box<dyn MarketplaceRemote>,
+}
+
+impl Registry {
+    /// Connect to the registry at `config.location`: an HTTP API for
+    /// `http(s)://` locations, a folder otherwise
+    pub fn open(config: RegistryConfig, timeout: Duration) -> CanvasResult<Self> {
+        let location = &config.location;
+        let remote: Box<dyn MarketplaceRemote> = if location.starts_with("http://") || location.starts_with("https://") {
+            Box::new(HttpMarketplace::new(location.as_str(), timeout))
+        } else {
+            Box::new(FolderMarketplace::new(Path::new(location.trim_start_matches("file://"))))
+        };
+        Self::with_remote(config, remote)
+    }
+
+    pub fn with_remote(config: RegistryConfig, remote: Box<dyn MarketplaceRemote>) -> CanvasResult<Self> {
+        let trusted_keys = config
+            .trusted_keys
+            .iter()
+            .map(|key| {
+                decode_hex(key)
+                    .ok_or_else(|| CanvasError::Config(format!("Invalid key for registry '{}': {}", config.name, key)))
+            })
+            .collect::<CanvasResult<Vec<_>>>()?;
+        Ok(Self {
+            config,
+            trusted_keys,
+            remote,
+        })
+    }
+
+    /// ID an item of this registry is listed under
+    pub fn qualify(&self, id: &str) -> String {
+        match &self.config.namespace {
+            Some(namespace) => format!("{}/{}", namespace, id),
+            None => id.to_string(),
+        }
+    }
+
+    /// ID within this registry of a listed ID, if this registry may serve it
+    pub fn local_id<'a>(&self, id: &'a str) -> Option<&'a str> {
+        match (&self.config.namespace, id.split_once('/')) {
+            (Some(namespace), Some((prefix, rest))) if prefix == namespace => Some(rest),
+            (None, None) => Some(id),
+            _ => None,
+        }
+    }
+
+    /// Check an item's signature if the registry has trusted keys
+    pub fn verify(&self, item: &MarketplaceItem) -> CanvasResult<()> {
+        if self.trusted_keys.is_empty() {
+            return Ok(());
+        }
+        let signature = item.signature.as_deref().and_then(decode_hex).ok_or_else(|| {
+            CanvasError::PermissionDenied(format!("Item '{}' of registry '{}' is not signed", item.id, self.config.name))
+        })?;
+        let message = item_signing_message(item);
+        if !self
+            .trusted_keys
+            .iter()
+            .any(|key| verify_signature(SignatureScheme::Ed25519, key, &message, &signature))
+        {
+            return Err(CanvasError::PermissionDenied(format!(
+                "Item '{}' of registry '{}' is not signed by a trusted key",
+                item.id, self.config.name
+            )));
+        }
+        Ok(())
+    }
+
+    /// Listed form of an item: qualified ID and registry name, after checking
+    /// its signature and that it does not claim a namespace
+    fn admit(&self, mut item: MarketplaceItem) -> CanvasResult<MarketplaceItem> {
+        if item.id.contains('/') {
+            return Err(CanvasError::PermissionDenied(format!(
+                "Registry '{}' lists namespaced ID '{}'",
+                self.config.name, item.id
+            )));
+        }
+        self.verify(&item)?;
+        item.id = self.qualify(&item.id);
+        item.registry = Some(self.config.name.clone());
+        Ok(item)
+    }
+}
+
+/// Every configured registry, merged into one marketplace
+pub struct RegistrySet {
+    registries: Vec<Registry>,
+}
+
+impl RegistrySet {
+    /// Registries in priority order; fails if two claim the same namespace
+    pub fn new(mut registries: Vec<Registry>) -> CanvasResult<Self> {
+        registries.sort_by_key(|r| r.config.priority);
+        let mut namespaces = BTreeSet::new();
+        for registry in &registries {
+            if let Some(namespace) = &registry.config.namespace {
+                if namespace.is_empty() || namespace.contains('/') {
+                    return Err(CanvasError::Config(format!("Invalid registry namespace '{}'", namespace)));
+                }
+                if !namespaces.insert(namespace.clone()) {
+                    return Err(CanvasError::Config(format!(
+                        "Namespace '{}' is claimed by more than one registry",
+                        namespace
+                    )));
+                }
+            }
+        }
+        Ok(Self { registries })
+    }
+
+    pub fn from_config(config: &MarketplaceConfig, timeout: Duration) -> CanvasResult<Self> {
+        let registries = config
+            .all_registries()
+            .into_iter()
+            .map(|registry| Registry::open(registry, timeout))
+            .collect::<CanvasResult<Vec<_>>>()?;
+        Self::new(registries)
+    }
+
+    pub fn registries(&self) -> &[Registry] {
+        &self.registries
+    }
+
+    /// Registries that may serve `id`, in priority order, with its local ID
+    fn candidates<'a, 'b>(&'a self, id: &'b str) -> impl Iterator<Item = (&'a Registry, &'b str)> {
+        self.registries.iter().filter_map(move |r| r.local_id(id).map(|local| (r, local)))
+    }
+
+    /// Registry an item is listed by
+    fn owner(&self, id: &str) -> CanvasResult<Option<(&Registry, MarketplaceItem)>> {
+        for (registry, local) in self.candidates(id) {
+            if let Some(item) = registry.remote.get_item(local)? {
+                return Ok(Some((registry, registry.admit(item)?)));
+            }
+        }
+        Ok(None)
+    }
+}
+
+impl MarketplaceRemote for RegistrySet {
+    /// Merged index. Unreachable registries are skipped unless none can be
+    /// reached; items failing [`Registry::verify`] are dropped with a warning.
+    fn fetch_index(&self) -> CanvasResult<Vec<MarketplaceItem>> {
+        let mut seen = BTreeSet::new();
+        let mut merged = Vec::new();
+        let mut unreachable = None;
+        let mut reached = false;
+        for registry in &self.registries {
+            let items = match registry.remote.fetch_index() {
+                Ok(items) => items,
+                Err(CanvasError::Network(e)) => {
+                    log::warn!("Registry '{}' unreachable: {}", registry.config.name, e);
+                    unreachable = Some(CanvasError::Network(e));
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+            reached = true;
+            for item in items {
+                match registry.admit(item) {
+                    Ok(item) if seen.insert(item.id.clone()) => merged.push(item),
+                    Ok(item) => log::debug!("'{}' of registry '{}' is shadowed", item.id, registry.config.name),
+                    Err(e) => log::warn!("{}", e),
+                }
+            }
+        }
+        match unreachable {
+            Some(e) if !reached => Err(e),
+            _ => Ok(merged),
+        }
+    }
+
+    fn get_item(&self, item_id: &str) -> CanvasResult<Option<MarketplaceItem>> {
+        Ok(self.owner(item_id)?.map(|(_, item)| item))
+    }
+
+    fn user_review(&self, item_id: &str, user_id: &str) -> CanvasResult<Option<Review>> {
+        let Some((registry, _)) = self.owner(item_id)? else {
+            return Ok(None);
+        };
+        let local = registry.local_id(item_id).unwrap_or(item_id);
+        Ok(registry.remote.user_review(local, user_id)?.map(|mut review| {
+            review.item_id = item_id.to_string();
+            review
+        }))
+    }
+
+    /// Publish to the registry owning the item's namespace, or to the first
+    /// registry without one
+    fn publish(&self, item: &MarketplaceItem, content: &[u8]) -> CanvasResult<String> {
+        let (registry, local) = self
+            .candidates(&item.id)
+            .next()
+            .ok_or_else(|| CanvasError::NotFound(format!("No registry owns the namespace of '{}'", item.id)))?;
+        let mut local_item = item.clone();
+        local_item.id = local.to_string();
+        local_item.registry = None;
+        registry.remote.publish(&local_item, content).map(|id| registry.qualify(&id))
+    }
+
+    fn submit_review(&self, review: &Review) -> CanvasResult<()> {
+        let (registry, _) = self
+            .owner(&review.item_id)?
+            .ok_or_else(|| CanvasError::NotFound(format!("Item '{}' not found", review.item_id)))?;
+        let mut local_review = review.clone();
+        local_review.item_id = registry.local_id(&review.item_id).unwrap_or(&review.item_id).to_string();
+        registry.remote.submit_review(&local_review)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn registry(name: &str, root: &Path, priority: i32, namespace: Option<&str>) -> RegistryConfig {
+        RegistryConfig {
+            name: name.to_string(),
+            location: root.display().to_string(),
+            priority,
+            trusted_keys: Vec::new(),
+            namespace: namespace.map(str::to_string),
+        }
+    }
+
+    fn item(id: &str, description: &str) -> MarketplaceItem {
+        let mut item = crate::marketplace::test_package().item.metadata;
+        item.id = id.to_string();
+        item.description = description.to_string();
+        item
+    }
+
+    #[test]
+    fn test_registries_merge_by_priority_namespace_and_trust() {
+        let dir = tempfile::tempdir().unwrap();
+        let (public, mirror, corp) = (dir.path().join("public"), dir.path().join("mirror"), dir.path().join("corp"));
+        let key = ed25519_dalek::SigningKey::from_bytes(&[7; 32]);
+
+        FolderMarketplace::new(&public).publish(&item("vault", "public"), b"{}").unwrap();
+        FolderMarketplace::new(&public).publish(&item("acme/vault", "spoofed"), b"{}").unwrap();
+        FolderMarketplace::new(&mirror).publish(&item("vault", "mirrored"), b"{}").unwrap();
+        let mut signed = item("vault", "internal");
+        sign_item(&mut signed, &key);
+        FolderMarketplace::new(&corp).publish(&signed, b"{}").unwrap();
+        FolderMarketplace::new(&corp).publish(&item("ledger", "unsigned"), b"{}").unwrap();
+
+        let mut corp_config = registry("corp", &corp, 0, Some("acme"));
+        corp_config.trusted_keys = vec![encode_hex(key.verifying_key().as_bytes())];
+        let set = RegistrySet::new(vec![
+            Registry::open(registry("public", &public, 100, None), Duration::from_secs(1)).unwrap(),
+            Registry::open(registry("mirror", &mirror, 10, None), Duration::from_secs(1)).unwrap(),
+            Registry::open(corp_config, Duration::from_secs(1)).unwrap(),
+        ])
+        .unwrap();
+
+        let mut index: Vec<(String, String)> =
+            set.fetch_index().unwrap().into_iter().map(|i| (i.id, i.description)).collect();
+        index.sort();
+        assert_eq!(
+            index,
+            vec![
+                ("acme/vault".to_string(), "internal".to_string()),
+                ("vault".to_string(), "mirrored".to_string()),
+            ]
+        );
+        assert_eq!(set.get_item("acme/vault").unwrap().unwrap().registry.as_deref(), Some("corp"));
+        assert_eq!(set.publish(&item("acme/oracle", ""), b"{}").unwrap(), "acme/oracle");
+        assert!(set.publish(&item("other/oracle", ""), b"{}").is_err());
+    }
+}
@@ -0,0 +1,337 @@
+//! Publisher signatures of item bundles
+//!
+//! A publisher signs the bundle they upload (the exact bytes a download
+//! returns) with their ed25519 key. The signature covers the author, ID,
+//! version and content hash, so neither the bundle nor the listing fields
+//! naming it can change without breaking it. Unlike a
+//! [registry signature](super::item_signing_message), which vouches for a
+//! listing, this one vouches for the author.
+//!
+//! Which keys may sign for which author is kept in [`AuthorKeys`], under the
+//! data directory. Keys are only ever added by the user; an item signed by a
+//! key not trusted for its author counts as unverified. Downloads and installs
+//! refuse unverified items unless the caller explicitly allows them.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use super::{
+    ComponentItem, CustomNodeItem, LocalMarketplace, MarketplaceClient, MarketplaceItem, MarketplaceItemType,
+    NodePackage, TemplateItem, TutorialItem,
+};
+use crate::{
+    baals::signer::{sign_with_prompt, Signer},
+    config::Config,
+    error::{CanvasError, CanvasResult},
+    nodes::{decode_hex, encode_hex},
+    wasm::host::{self, verify_signature, SignatureScheme},
+};
+
+/// Trusted author keys file, under the data directory
+pub const AUTHOR_KEYS_FILE: &str = "author-keys.json";
+
+/// A publisher's signature of an item bundle
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PublisherSignature {
+    /// 0x-hex ed25519 public key of the signer
+    pub public_key: String,
+    /// 0x-hex ed25519 signature of [`bundle_signing_message`]
+    pub signature: String,
+}
+
+/// Bytes a publisher signs for an item: author, ID, version and content hash.
+/// The ID is taken without a registry namespace, which registries add when
+/// they list the item.
+pub fn bundle_signing_message(item: &MarketplaceItem) -> Vec<u8> {
+    let id = item.id.rsplit('/').next().unwrap_or_default();
+    format!("canvas-bundle:{}:{}@{}:{}", item.author, id, item.version, item.hash).into_bytes()
+}
+
+/// Sign `content` as the bundle of `item`, setting the item's hash and size
+/// from it
+pub fn sign_bundle(item: &mut MarketplaceItem, content: &[u8], signer: &dyn Signer) -> CanvasResult<()> {
+    if signer.impersonated() {
+        return Err(CanvasError::PermissionDenied(format!("{} cannot sign marketplace bundles", signer.describe())));
+    }
+    item.hash = encode_hex(&host::hash(host::HashAlgorithm::Sha256, content));
+    item.size_bytes = content.len() as u64;
+    let message = bundle_signing_message(item);
+    let signature = sign_with_prompt(signer, &format!("bundle of {} {}", item.id, item.version), &message)?;
+    item.publisher_signature = Some(PublisherSignature {
+        public_key: signer.address()?,
+        signature: encode_hex(&signature),
+    });
+    Ok(())
+}
+
+/// Outcome of checking an item's bundle against the trusted author keys
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Verification {
+    /// Signed by a key trusted for the item's author
+    Verified { public_key: String },
+    Unsigned,
+    /// Correctly signed, but by a key not trusted for the author
+    UntrustedKey { public_key: String },
+    /// The bundle or its listing changed after signing
+    Tampered { reason: String },
+}
+
+impl Verification {
+    pub fn is_verified(&self) -> bool {
+        matches!(self, Verification::Verified { .. })
+    }
+
+    /// Refuse an unverified item unless `allow_unverified` is set, in which
+    /// case it is only logged
+    pub fn require(&self, item: &MarketplaceItem, allow_unverified: bool) -> CanvasResult<()> {
+        let problem = match self {
+            Verification::Verified { .. } => return Ok(()),
+            Verification::Unsigned => "is not signed by its publisher".to_string(),
+            Verification::UntrustedKey { public_key } => {
+                format!("is signed by {}, which is not a trusted key of '{}'", public_key, item.author)
+            }
+            Verification::Tampered { reason } => format!("has been tampered with: {}", reason),
+        };
+        let message = format!("'{}' {} {}", item.id, item.version, problem);
+        if allow_unverified {
+            log::warn!("Accepting unverified item: {}", message);
+            return Ok(());
+        }
+        Err(match self {
+            Verification::Tampered { .. } => CanvasError::Validation(message),
+            _ => CanvasError::PermissionDenied(message),
+        })
+    }
+}
+
+/// Public keys trusted to sign for each author
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct AuthorKeys {
+    /// 0x-hex public keys by author
+    authors: BTreeMap<String, BTreeSet<String>>,
+    #[serde(skip)]
+    path: Option<PathBuf>,
+}
+
+impl AuthorKeys {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Key file for a configuration
+    pub fn default_path(config: &Config) -> PathBuf {
+        config.app.data_dir.join(AUTHOR_KEYS_FILE)
+    }
+
+    /// Load the keys from `path`, starting empty if it does not exist yet.
+    /// [`save`](Self::save) writes back to the same file.
+    pub fn open(path: &Path) -> CanvasResult<Self> {
+        let mut keys: Self = if path.exists() {
+            serde_json::from_str(&std::fs::read_to_string(path)?)?
+        } else {
+            Self::default()
+        };
+        keys.path = Some(path.to_path_buf());
+        Ok(keys)
+    }
+
+    pub fn save(&self) -> CanvasResult<()> {
+        let path = self
+            .path
+            .as_ref()
+            .ok_or_else(|| CanvasError::InvalidState("Author keys were not opened from a file".to_string()))?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// Trust `public_key` to sign for `author`. Returns whether it was new.
+    pub fn trust(&mut self, author: &str, public_key: &str) -> CanvasResult<bool> {
+        let valid = decode_hex(public_key)
+            .and_then(|bytes| <[u8; 32]>::try_from(bytes).ok())
+            .is_some_and(|bytes| ed25519_dalek::VerifyingKey::from_bytes(&bytes).is_ok());
+        if !valid {
+            return Err(CanvasError::Validation(format!("'{}' is not a 0x-hex ed25519 public key", public_key)));
+        }
+        Ok(self.authors.entry(author.to_string()).or_default().insert(public_key.to_lowercase()))
+    }
+
+    /// Stop trusting `public_key` for `author`. Returns whether it was trusted.
+    pub fn revoke(&mut self, author: &str, public_key: &str) -> bool {
+        let Some(keys) = self.authors.get_mut(author) else {
+            return false;
+        };
+        let removed = keys.remove(&public_key.to_lowercase());
+        if keys.is_empty() {
+            self.authors.remove(author);
+        }
+        removed
+    }
+
+    /// Authors with their trusted keys, sorted by name
+    pub fn authors(&self) -> impl Iterator<Item = (&str, &BTreeSet<String>)> {
+        self.authors.iter().map(|(author, keys)| (author.as_str(), keys))
+    }
+
+    /// Keys trusted for `author`
+    pub fn keys_of(&self, author: &str) -> Vec<String> {
+        self.authors.get(author).map(|keys| keys.iter().cloned().collect()).unwrap_or_default()
+    }
+
+    /// Check `content` and the item's publisher signature
+    pub fn verify(&self, item: &MarketplaceItem, content: &[u8]) -> Verification {
+        let hash = encode_hex(&host::hash(host::HashAlgorithm::Sha256, content));
+        if !hash.eq_ignore_ascii_case(&item.hash) {
+            return Verification::Tampered {
+                reason: "content does not match its hash".to_string(),
+            };
+        }
+        let Some(signed) = &item.publisher_signature else {
+            return Verification::Unsigned;
+        };
+        let valid = match (decode_hex(&signed.public_key), decode_hex(&signed.signature)) {
+            (Some(key), Some(signature)) => {
+                verify_signature(SignatureScheme::Ed25519, &key, &bundle_signing_message(item), &signature)
+            }
+            _ => false,
+        };
+        if !valid {
+            return Verification::Tampered {
+                reason: "publisher signature does not match the listing".to_string(),
+            };
+        }
+        let public_key = signed.public_key.to_lowercase();
+        match self.authors.get(&item.author).is_some_and(|keys| keys.contains(&public_key)) {
+            true => Verification::Verified { public_key },
+            false => Verification::UntrustedKey { public_key },
+        }
+    }
+}
+
+impl LocalMarketplace {
+    /// Install a downloaded bundle under its listing, after checking its
+    /// publisher signature against `keys`. Unverified bundles are refused
+    /// unless `allow_unverified` is set.
+    pub fn install_item(
+        &mut self,
+        item: &MarketplaceItem,
+        content: &[u8],
+        keys: &AuthorKeys,
+        allow_unverified: bool,
+    ) -> CanvasResult<Verification> {
+        let verification = keys.verify(item, content);
+        verification.require(item, allow_unverified)?;
+        match item.item_type {
+            MarketplaceItemType::CustomNode => {
+                let package: NodePackage = serde_json::from_slice(content)?;
+                let node = CustomNodeItem {
+                    metadata: item.clone(),
+                    ..package.item
+                };
+                self.add_custom_node(node)?;
+            }
+            MarketplaceItemType::Template => {
+                let template: TemplateItem = serde_json::from_slice(content)?;
+                self.add_template(TemplateItem {
+                    metadata: item.clone(),
+                    ..template
+                })?;
+            }
+            MarketplaceItemType::Component => {
+                let component: ComponentItem = serde_json::from_slice(content)?;
+                self.add_component(ComponentItem {
+                    metadata: item.clone(),
+                    ..component
+                })?;
+            }
+            MarketplaceItemType::Tutorial => {
+                let tutorial: TutorialItem = serde_json::from_slice(content)?;
+                self.add_tutorial(TutorialItem {
+                    metadata: item.clone(),
+                    ..tutorial
+                })?;
+            }
+        }
+        Ok(verification)
+    }
+}
+
+impl MarketplaceClient {
+    /// Download an item and install it into `marketplace` through
+    /// [`LocalMarketplace::install_item`], checked against the client's
+    /// author keys. Returns the bundle so callers can unpack it.
+    pub async fn install_item(
+        &mut self,
+        item_id: &str,
+        marketplace: &mut LocalMarketplace,
+    ) -> CanvasResult<(Vec<u8>, Verification)> {
+        let item = self.get_item(item_id).await?;
+        let content = self.fetch_content(&item).await?;
+        let verification = marketplace.install_item(&item, &content, &self.author_keys, self.allow_unverified)?;
+        Ok((content, verification))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{baals::signer::LocalSigner, marketplace::test_package};
+
+    #[test]
+    fn test_install_checks_publisher_signatures() {
+        let alice = LocalSigner::new(ed25519_dalek::SigningKey::from_bytes(&[7; 32]));
+        let mallory = LocalSigner::new(ed25519_dalek::SigningKey::from_bytes(&[9; 32]));
+        let package = test_package();
+        let content = serde_json::to_vec(&package).unwrap();
+        let mut item = package.item.metadata.clone();
+        sign_bundle(&mut item, &content, &alice).unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut keys = AuthorKeys::open(&dir.path().join(AUTHOR_KEYS_FILE)).unwrap();
+        assert!(keys.trust("alice", "0x1234").is_err());
+        let mut marketplace = LocalMarketplace::new();
+        assert!(matches!(
+            marketplace.install_item(&item, &content, &keys, false),
+            Err(CanvasError::PermissionDenied(_))
+        ));
+        assert!(keys.trust("alice", &alice.address().unwrap()).unwrap());
+        keys.save().unwrap();
+        let verification = marketplace.install_item(&item, &content, &keys, false).unwrap();
+        assert!(verification.is_verified());
+        let installed = marketplace.get_custom_node("clamp").unwrap();
+        assert_eq!(installed.metadata.publisher_signature, item.publisher_signature);
+
+        // A re-signed bundle under Alice's name, and a listing edited after signing
+        let mut forged = item.clone();
+        sign_bundle(&mut forged, &content, &mallory).unwrap();
+        assert!(matches!(keys.verify(&forged, &content), Verification::UntrustedKey { .. }));
+        let mut bumped = item.clone();
+        bumped.version = "9.9.9".to_string();
+        assert!(matches!(keys.verify(&bumped, &content), Verification::Tampered { .. }));
+        let mut altered = content.clone();
+        altered.push(b' ');
+        assert!(matches!(
+            marketplace.install_item(&item, &altered, &keys, false),
+            Err(CanvasError::Validation(_))
+        ));
+
+        let mut unsigned = package.item.metadata.clone();
+        unsigned.id = "unsigned".to_string();
+        unsigned.hash = encode_hex(&host::hash(host::HashAlgorithm::Sha256, &content));
+        assert!(marketplace.install_item(&unsigned, &content, &keys, false).is_err());
+        assert_eq!(
+            marketplace.install_item(&unsigned, &content, &keys, true).unwrap(),
+            Verification::Unsigned
+        );
+        assert!(marketplace.get_item("unsigned").is_some());
+
+        let reopened = AuthorKeys::open(&dir.path().join(AUTHOR_KEYS_FILE)).unwrap();
+        assert!(reopened.verify(&item, &content).is_verified());
+    }
+}
@@ -0,0 +1,352 @@
+//! Usage statistics and trending listings
+//!
+//! Downloads, installs and ratings are aggregated per item and per UTC day,
+//! which keeps the store small while still allowing totals over any window.
+//! An item's trending score weighs each day's activity by its age with an
+//! exponential decay, so a burst of recent installs outranks a larger but
+//! older total.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::{LocalMarketplace, MarketplaceClient, MarketplaceItem};
+use crate::{
+    config::Config,
+    error::{CanvasError, CanvasResult},
+};
+
+/// File the statistics are kept in, under the data directory
+pub const MARKETPLACE_STATS_FILE: &str = "marketplace-stats.json";
+/// Age at which activity counts half towards the trending score
+pub const DEFAULT_HALF_LIFE_DAYS: f64 = 3.0;
+
+const SECONDS_PER_DAY: i64 = 86_400;
+const INSTALL_WEIGHT: f64 = 3.0;
+const RATING_WEIGHT: f64 = 2.0;
+
+/// Something a user did with an item
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum UsageEvent {
+    Download,
+    Install,
+    /// Rating of 1 to 5 stars
+    Rating { stars: u8 },
+}
+
+/// Activity of one item on one day
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct DailyUsage {
+    pub downloads: u64,
+    pub installs: u64,
+    pub ratings: u64,
+    pub rating_sum: u64,
+}
+
+impl DailyUsage {
+    fn add(&mut self, other: &DailyUsage) {
+        self.downloads += other.downloads;
+        self.installs += other.installs;
+        self.ratings += other.ratings;
+        self.rating_sum += other.rating_sum;
+    }
+
+    pub fn average_rating(&self) -> Option<f64> {
+        (self.ratings > 0).then(|| self.rating_sum as f64 / self.ratings as f64)
+    }
+
+    /// Activity weight before decay; ratings above 3 stars add, lower ones subtract
+    fn weight(&self) -> f64 {
+        let rating = self.rating_sum as f64 - 3.0 * self.ratings as f64;
+        self.downloads as f64 + INSTALL_WEIGHT * self.installs as f64 + RATING_WEIGHT * rating
+    }
+}
+
+/// Ordering of the top listing
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TopBy {
+    Downloads,
+    Installs,
+    Rating,
+}
+
+impl TopBy {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "downloads" => Some(TopBy::Downloads),
+            "installs" => Some(TopBy::Installs),
+            "rating" => Some(TopBy::Rating),
+            _ => None,
+        }
+    }
+}
+
+fn day_of(at: DateTime<Utc>) -> i64 {
+    at.timestamp().div_euclid(SECONDS_PER_DAY)
+}
+
+/// Per-day usage of every item
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UsageStats {
+    items: BTreeMap<String, BTreeMap<i64, DailyUsage>>,
+    /// Items each user has installed
+    #[serde(default)]
+    installed_by: BTreeMap<String, BTreeSet<String>>,
+    #[serde(skip)]
+    path: Option<PathBuf>,
+}
+
+impl UsageStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Statistics file for a configuration
+    pub fn default_path(config: &Config) -> PathBuf {
+        config.app.data_dir.join(MARKETPLACE_STATS_FILE)
+    }
+
+    /// Load the statistics from `path`, starting empty if it does not exist yet.
+    /// [`save`](Self::save) writes back to the same file.
+    pub fn open(path: &Path) -> CanvasResult<Self> {
+        let mut stats: Self = if path.exists() {
+            serde_json::from_str(&std::fs::read_to_string(path)?)?
+        } else {
+            Self::default()
+        };
+        stats.path = Some(path.to_path_buf());
+        Ok(stats)
+    }
+
+    pub fn save(&self) -> CanvasResult<()> {
+        let path = self
+            .path
+            .as_ref()
+            .ok_or_else(|| CanvasError::InvalidState("Usage statistics were not opened from a file".to_string()))?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, serde_json::to_string(self)?)?;
+        Ok(())
+    }
+
+    pub fn record(&mut self, item_id: &str, event: UsageEvent, at: DateTime<Utc>) -> CanvasResult<()> {
+        let day = self.items.entry(item_id.to_string()).or_default().entry(day_of(at)).or_default();
+        match event {
+            UsageEvent::Download => day.downloads += 1,
+            UsageEvent::Install => day.installs += 1,
+            UsageEvent::Rating { stars } => {
+                if !(1..=5).contains(&stars) {
+                    return Err(CanvasError::Validation(format!("Rating must be 1-5 stars, got {}", stars)));
+                }
+                day.ratings += 1;
+                day.rating_sum += stars as u64;
+            }
+        }
+        Ok(())
+    }
+
+    /// Record an install by a known user
+    pub fn record_install(&mut self, user_id: &str, item_id: &str, at: DateTime<Utc>) -> CanvasResult<()> {
+        self.record(item_id, UsageEvent::Install, at)?;
+        self.installed_by.entry(user_id.to_string()).or_default().insert(item_id.to_string());
+        Ok(())
+    }
+
+    /// Items a user has installed
+    pub fn installed_by(&self, user_id: &str) -> BTreeSet<String> {
+        self.installed_by.get(user_id).cloned().unwrap_or_default()
+    }
+
+    /// Install sets of every known user
+    pub fn install_sets(&self) -> impl Iterator<Item = &BTreeSet<String>> {
+        self.installed_by.values()
+    }
+
+    /// Totals of an item over the days `since` falls in through `until`
+    pub fn totals(&self, item_id: &str, since: DateTime<Utc>, until: DateTime<Utc>) -> DailyUsage {
+        let mut totals = DailyUsage::default();
+        if let Some(days) = self.items.get(item_id) {
+            for usage in days.range(day_of(since)..=day_of(until)).map(|(_, usage)| usage) {
+                totals.add(usage);
+            }
+        }
+        totals
+    }
+
+    /// All-time totals of an item
+    pub fn lifetime(&self, item_id: &str) -> DailyUsage {
+        let mut totals = DailyUsage::default();
+        for usage in self.items.get(item_id).into_iter().flat_map(|days| days.values()) {
+            totals.add(usage);
+        }
+        totals
+    }
+
+    /// Decayed activity of an item as of `now`; never negative
+    pub fn trending_score(&self, item_id: &str, now: DateTime<Utc>, half_life_days: f64) -> f64 {
+        let today = day_of(now);
+        let score: f64 = self
+            .items
+            .get(item_id)
+            .into_iter()
+            .flat_map(|days| days.range(..=today))
+            .map(|(day, usage)| usage.weight() * 0.5f64.powf((today - day) as f64 / half_life_days))
+            .sum();
+        score.max(0.0)
+    }
+
+    /// Items with activity, highest trending score first
+    pub fn trending(&self, now: DateTime<Utc>, half_life_days: f64, limit: usize) -> Vec<(String, f64)> {
+        let mut scored: Vec<(String, f64)> = self
+            .items
+            .keys()
+            .map(|id| (id.clone(), self.trending_score(id, now, half_life_days)))
+            .filter(|(_, score)| *score > 0.0)
+            .collect();
+        scored.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        scored.truncate(limit);
+        scored
+    }
+
+    /// Items ranked by a metric over the last `window`
+    pub fn top(&self, by: TopBy, now: DateTime<Utc>, window: Duration, limit: usize) -> Vec<(String, DailyUsage)> {
+        let mut ranked: Vec<(String, DailyUsage)> = self
+            .items
+            .keys()
+            .map(|id| (id.clone(), self.totals(id, now - window, now)))
+            .filter(|(_, usage)| usage.downloads + usage.installs + usage.ratings > 0)
+            .collect();
+        let key = |usage: &DailyUsage| match by {
+            TopBy::Downloads => usage.downloads as f64,
+            TopBy::Installs => usage.installs as f64,
+            TopBy::Rating => usage.average_rating().unwrap_or(0.0),
+        };
+        ranked.sort_by(|a, b| key(&b.1).total_cmp(&key(&a.1)).then_with(|| a.0.cmp(&b.0)));
+        ranked.truncate(limit);
+        ranked
+    }
+}
+
+impl LocalMarketplace {
+    /// Record a use of an item, keeping its download count in step
+    pub fn record_usage(&mut self, stats: &mut UsageStats, item_id: &str, event: UsageEvent) -> CanvasResult<()> {
+        let item = self
+            .items
+            .get_mut(item_id)
+            .ok_or_else(|| CanvasError::NotFound(format!("Item '{}' not found", item_id)))?;
+        stats.record(item_id, event, Utc::now())?;
+        if event == UsageEvent::Download {
+            item.downloads += 1;
+        }
+        if let UsageEvent::Rating { .. } = event {
+            if let Some(rating) = stats.lifetime(item_id).average_rating() {
+                item.rating = rating;
+            }
+        }
+        Ok(())
+    }
+
+    /// Trending items, hottest first
+    pub fn trending_items(&self, stats: &UsageStats, limit: usize) -> Vec<&MarketplaceItem> {
+        stats
+            .trending(Utc::now(), DEFAULT_HALF_LIFE_DAYS, usize::MAX)
+            .into_iter()
+            .filter_map(|(id, _)| self.items.get(&id))
+            .take(limit)
+            .collect()
+    }
+
+    /// Items ranked by a metric over the last `window`
+    pub fn top_items(&self, stats: &UsageStats, by: TopBy, window: Duration, limit: usize) -> Vec<&MarketplaceItem> {
+        stats
+            .top(by, Utc::now(), window, usize::MAX)
+            .into_iter()
+            .filter_map(|(id, _)| self.items.get(&id))
+            .take(limit)
+            .collect()
+    }
+
+    /// Most recently published items
+    pub fn new_items(&self, limit: usize) -> Vec<&MarketplaceItem> {
+        let mut items: Vec<&MarketplaceItem> = self.items.values().collect();
+        items.sort_by(|a, b| b.created_at.cmp(&a.created_at).then_with(|| a.id.cmp(&b.id)));
+        items.truncate(limit);
+        items
+    }
+}
+
+impl MarketplaceClient {
+    /// Trending items from the marketplace API, falling back to the local
+    /// statistics when it cannot be reached or has nothing to offer
+    pub async fn trending_items_or_local(
+        &self,
+        local: &LocalMarketplace,
+        stats: &UsageStats,
+        limit: u32,
+    ) -> CanvasResult<Vec<MarketplaceItem>> {
+        match self.get_trending_items(limit).await {
+            Ok(items) if !items.is_empty() => return Ok(items),
+            Ok(_) => {}
+            Err(e) => log::warn!("Marketplace trending list unavailable, using local statistics: {}", e),
+        }
+        Ok(local.trending_items(stats, limit as usize).into_iter().cloned().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recent_activity_outranks_older_totals() {
+        let now = Utc::now();
+        let mut stats = UsageStats::new();
+        for _ in 0..20 {
+            stats.record("old", UsageEvent::Download, now - Duration::days(30)).unwrap();
+        }
+        for _ in 0..3 {
+            stats.record("new", UsageEvent::Install, now).unwrap();
+        }
+        stats.record("new", UsageEvent::Rating { stars: 5 }, now).unwrap();
+        assert!(stats.record("new", UsageEvent::Rating { stars: 6 }, now).is_err());
+
+        let trending: Vec<String> = stats.trending(now, DEFAULT_HALF_LIFE_DAYS, 10).into_iter().map(|(id, _)| id).collect();
+        assert_eq!(trending, vec!["new".to_string(), "old".to_string()]);
+
+        let top = stats.top(TopBy::Downloads, now, Duration::days(90), 10);
+        assert_eq!(top[0].0, "old");
+        assert_eq!(top[0].1.downloads, 20);
+        assert!(stats.top(TopBy::Downloads, now, Duration::days(7), 10).iter().all(|(id, _)| id == "new"));
+        assert_eq!(stats.lifetime("new").average_rating(), Some(5.0));
+    }
+
+    #[tokio::test]
+    async fn test_trending_falls_back_to_local_statistics() {
+        let mut local = LocalMarketplace::new();
+        for id in ["quiet", "busy"] {
+            let mut item = super::super::test_package().item;
+            item.metadata.id = id.to_string();
+            local.add_custom_node(item).unwrap();
+        }
+        let downloads = local.get_item("busy").unwrap().downloads;
+        let mut stats = UsageStats::new();
+        local.record_usage(&mut stats, "busy", UsageEvent::Download).unwrap();
+        assert_eq!(local.get_item("busy").unwrap().downloads, downloads + 1);
+
+        let retry = super::super::RetryPolicy {
+            attempts: 0,
+            ..Default::default()
+        };
+        let unreachable = MarketplaceClient::new("http://127.0.0.1:9".to_string()).with_retry(retry);
+        let trending = unreachable.trending_items_or_local(&local, &stats, 5).await.unwrap();
+        let ids: Vec<&str> = trending.iter().map(|item| item.id.as_str()).collect();
+        assert_eq!(ids, vec!["busy"]);
+    }
+}
@@ -0,0 +1,95 @@
+//! Alert history
+//!
+//! Alerts raised for a workspace are appended to `.canvas/alerts.jsonl`, one
+//! JSON object per line, so the history survives restarts and can be
+//! summarized later (see [`digest`](super::digest)).
+
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    deployment::AlertSeverity,
+    error::{CanvasError, CanvasResult},
+};
+
+/// Alert history, relative to the workspace root
+pub const ALERTS_FILE: &str = ".canvas/alerts.jsonl";
+
+/// An alert that was raised
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AlertEvent {
+    /// Alert rule or check that raised it
+    pub rule: String,
+    pub severity: AlertSeverity,
+    pub message: String,
+    /// Deployment, environment or graph the alert is about
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub subject: Option<String>,
+    pub raised_at: DateTime<Utc>,
+}
+
+impl AlertEvent {
+    pub fn new(rule: impl Into<String>, severity: AlertSeverity, message: impl Into<String>) -> Self {
+        Self {
+            rule: rule.into(),
+            severity,
+            message: message.into(),
+            subject: None,
+            raised_at: Utc::now(),
+        }
+    }
+
+    pub fn with_subject(mut self, subject: impl Into<String>) -> Self {
+        self.subject = Some(subject.into());
+        self
+    }
+}
+
+/// Append-only alert history of a workspace
+pub struct AlertLog {
+    path: PathBuf,
+}
+
+impl AlertLog {
+    pub fn new(root: &Path) -> Self {
+        Self {
+            path: root.join(ALERTS_FILE),
+        }
+    }
+
+    pub fn record(&self, alert: &AlertEvent) -> CanvasResult<()> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut file = std::fs::OpenOptions::new().create(true).append(true).open(&self.path)?;
+        writeln!(file, "{}", serde_json::to_string(alert)?)?;
+        Ok(())
+    }
+
+    /// Every recorded alert, oldest first
+    pub fn history(&self) -> CanvasResult<Vec<AlertEvent>> {
+        if !self.path.exists() {
+            return Ok(Vec::new());
+        }
+        std::fs::read_to_string(&self.path)?
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(index, line)| {
+                serde_json::from_str(line).map_err(|e| {
+                    CanvasError::InvalidState(format!("{}:{}: invalid alert: {}", self.path.display(), index + 1, e))
+                })
+            })
+            .collect()
+    }
+
+    /// Alerts raised at or after `since`, oldest first
+    pub fn since(&self, since: DateTime<Utc>) -> CanvasResult<Vec<AlertEvent>> {
+        Ok(self.history()?.into_iter().filter(|a| a.raised_at >= since).collect())
+    }
+}
@@ -0,0 +1,457 @@
+//! Team gas budgets
+//!
+//! Teams are configured in `.canvas/budgets.json` with the addresses of their
+//! members and a gas budget per period. Every deployment and call the
+//! platform sends with a member's key is debited to the member's team in
+//! `.canvas/gas-ledger.jsonl`. Crossing the alert threshold or the soft limit
+//! raises an alert (see [`alerts`](super::alerts)); once the hard limit is
+//! reached, further spending on the team's enforced networks (mainnet by
+//! default) is refused until the next period.
+
+use std::{
+    collections::BTreeMap,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, Datelike, Duration, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::alerts::{AlertEvent, AlertLog};
+use crate::{
+    deployment::AlertSeverity,
+    error::{CanvasError, CanvasResult},
+    types::Gas,
+};
+
+/// Team budgets, relative to the workspace root
+pub const BUDGETS_FILE: &str = ".canvas/budgets.json";
+/// Gas spent by team members, relative to the workspace root
+pub const GAS_LEDGER_FILE: &str = ".canvas/gas-ledger.jsonl";
+
+/// Period a budget resets after
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BudgetPeriod {
+    Weekly,
+    #[default]
+    Monthly,
+    /// Never resets
+    Total,
+}
+
+impl BudgetPeriod {
+    /// Start of the period `now` falls in, in UTC; `None` for a total budget
+    pub fn start(&self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let today = now.date_naive();
+        let first_day = match self {
+            BudgetPeriod::Weekly => today - Duration::days(i64::from(today.weekday().num_days_from_monday())),
+            BudgetPeriod::Monthly => today.with_day(1)?,
+            BudgetPeriod::Total => return None,
+        };
+        Some(Utc.from_utc_datetime(&first_day.and_hms_opt(0, 0, 0)?))
+    }
+}
+
+fn default_alert_at() -> f64 {
+    0.8
+}
+
+fn default_enforced_networks() -> Vec<String> {
+    vec!["mainnet".to_string()]
+}
+
+/// Gas budget of one team
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TeamBudget {
+    pub name: String,
+    /// Member name to the address their key signs as
+    pub members: BTreeMap<String, String>,
+    #[serde(default)]
+    pub period: BudgetPeriod,
+    /// Usage above this raises a warning but is not refused
+    #[serde(default)]
+    pub soft_limit: Option<Gas>,
+    /// Usage at or above this refuses spending on the enforced networks
+    #[serde(default)]
+    pub hard_limit: Option<Gas>,
+    /// Fraction of the cap (the hard limit, or else the soft limit) that
+    /// raises an alert
+    #[serde(default = "default_alert_at")]
+    pub alert_at: f64,
+    /// Networks where the hard limit is enforced; usage on others is only tracked
+    #[serde(default = "default_enforced_networks")]
+    pub enforced_networks: Vec<String>,
+}
+
+impl TeamBudget {
+    /// Limit the alert threshold is a fraction of
+    pub fn cap(&self) -> Option<Gas> {
+        self.hard_limit.or(self.soft_limit)
+    }
+
+    pub fn enforces(&self, network: &str) -> bool {
+        self.enforced_networks.iter().any(|n| n.eq_ignore_ascii_case(network))
+    }
+}
+
+/// Budgets of a workspace's teams
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BudgetConfig {
+    #[serde(default)]
+    pub teams: Vec<TeamBudget>,
+}
+
+impl BudgetConfig {
+    /// Budgets of the workspace at `root`; none when it has no budgets file
+    pub fn load(root: &Path) -> CanvasResult<Self> {
+        let path = root.join(BUDGETS_FILE);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let config: Self = serde_json::from_str(&std::fs::read_to_string(&path)?)
+            .map_err(|e| CanvasError::Config(format!("Invalid budgets {}: {}", path.display(), e)))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn validate(&self) -> CanvasResult<()> {
+        let mut addresses: BTreeMap<String, &str> = BTreeMap::new();
+        for team in &self.teams {
+            if !(0.0..=1.0).contains(&team.alert_at) {
+                return Err(CanvasError::Config(format!(
+                    "Alert threshold of team '{}' must be between 0 and 1",
+                    team.name
+                )));
+            }
+            if let (Some(soft), Some(hard)) = (team.soft_limit, team.hard_limit) {
+                if soft > hard {
+                    return Err(CanvasError::Config(format!(
+                        "Soft limit of team '{}' is above its hard limit",
+                        team.name
+                    )));
+                }
+            }
+            for address in team.members.values() {
+                if let Some(other) = addresses.insert(address.to_lowercase(), &team.name) {
+                    return Err(CanvasError::Config(format!(
+                        "Address {} is in both team '{}' and team '{}'",
+                        address, other, team.name
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// What gas was spent on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SpendKind {
+    Deploy,
+    Call,
+}
+
+/// Gas one member spent on one transaction
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GasSpend {
+    pub team: String,
+    pub member: String,
+    pub network: String,
+    pub kind: SpendKind,
+    /// Deployed artifact or called `address.function`
+    pub target: String,
+    pub gas: Gas,
+    pub at: DateTime<Utc>,
+}
+
+/// How much of its budget a team has used in the current period
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TeamUsage {
+    pub team: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub period_start: Option<DateTime<Utc>>,
+    pub used: Gas,
+    pub soft_limit: Option<Gas>,
+    pub hard_limit: Option<Gas>,
+    /// Gas used by each member, members without usage included
+    pub members: BTreeMap<String, Gas>,
+}
+
+impl TeamUsage {
+    /// Share of the cap used, in percent
+    pub fn percent_of(&self, cap: Option<Gas>) -> Option<f64> {
+        cap.map(|cap| self.used as f64 * 100.0 / cap.max(1) as f64)
+    }
+
+    pub fn over_soft_limit(&self) -> bool {
+        self.soft_limit.is_some_and(|limit| self.used > limit)
+    }
+
+    pub fn at_hard_limit(&self) -> bool {
+        self.hard_limit.is_some_and(|limit| self.used >= limit)
+    }
+}
+
+/// Team budgets of a workspace and the ledger they are debited in
+pub struct GasBudgets {
+    config: BudgetConfig,
+    ledger: PathBuf,
+    alerts: AlertLog,
+}
+
+impl GasBudgets {
+    pub fn new(root: &Path, config: BudgetConfig) -> Self {
+        Self {
+            config,
+            ledger: root.join(GAS_LEDGER_FILE),
+            alerts: AlertLog::new(root),
+        }
+    }
+
+    /// Budgets of the workspace at `root` with its own settings
+    pub fn load(root: &Path) -> CanvasResult<Self> {
+        Ok(Self::new(root, BudgetConfig::load(root)?))
+    }
+
+    pub fn config(&self) -> &BudgetConfig {
+        &self.config
+    }
+
+    /// Team and member name an address signs for
+    pub fn member(&self, address: &str) -> Option<(&TeamBudget, &str)> {
+        self.config.teams.iter().find_map(|team| {
+            team.members
+                .iter()
+                .find(|(_, a)| a.eq_ignore_ascii_case(address))
+                .map(|(name, _)| (team, name.as_str()))
+        })
+    }
+
+    /// Every recorded spend, oldest first
+    pub fn spends(&self) -> CanvasResult<Vec<GasSpend>> {
+        if !self.ledger.exists() {
+            return Ok(Vec::new());
+        }
+        std::fs::read_to_string(&self.ledger)?
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(index, line)| {
+                serde_json::from_str(line).map_err(|e| {
+                    CanvasError::InvalidState(format!("{}:{}: invalid gas spend: {}", self.ledger.display(), index + 1, e))
+                })
+            })
+            .collect()
+    }
+
+    fn usage_from(team: &TeamBudget, spends: &[GasSpend], now: DateTime<Utc>) -> TeamUsage {
+        let period_start = team.period.start(now);
+        let mut members: BTreeMap<String, Gas> = team.members.keys().map(|m| (m.clone(), 0)).collect();
+        let mut used: Gas = 0;
+        for spend in spends
+            .iter()
+            .filter(|s| s.team == team.name && period_start.map_or(true, |start| s.at >= start))
+        {
+            used = used.saturating_add(spend.gas);
+            let member = members.entry(spend.member.clone()).or_insert(0);
+            *member = member.saturating_add(spend.gas);
+        }
+        TeamUsage {
+            team: team.name.clone(),
+            period_start,
+            used,
+            soft_limit: team.soft_limit,
+            hard_limit: team.hard_limit,
+            members,
+        }
+    }
+
+    /// Usage of every team in the period `now` falls in
+    pub fn report(&self, now: DateTime<Utc>) -> CanvasResult<Vec<TeamUsage>> {
+        let spends = self.spends()?;
+        Ok(self.config.teams.iter().map(|team| Self::usage_from(team, &spends, now)).collect())
+    }
+
+    /// Refuse spending by `address` on `network` once its team is at its hard
+    /// limit there. Addresses outside every team are not budgeted.
+    pub fn authorize(&self, address: &str, network: &str, now: DateTime<Utc>) -> CanvasResult<()> {
+        let Some((team, member)) = self.member(address) else {
+            return Ok(());
+        };
+        let usage = Self::usage_from(team, &self.spends()?, now);
+        if usage.at_hard_limit() && team.enforces(network) {
+            return Err(CanvasError::PermissionDenied(format!(
+                "Team '{}' has used {} of its {} gas budget; {} cannot spend more on {} this period",
+                team.name,
+                usage.used,
+                usage.hard_limit.unwrap_or_default(),
+                member,
+                network
+            )));
+        }
+        if usage.over_soft_limit() {
+            log::warn!(
+                "Team '{}' is over its soft gas limit ({} of {})",
+                team.name,
+                usage.used,
+                usage.soft_limit.unwrap_or_default()
+            );
+        }
+        Ok(())
+    }
+
+    /// Debit gas spent by `address` to its team and raise an alert for every
+    /// threshold the spend crossed. Returns the alerts raised.
+    pub fn debit(
+        &self,
+        address: &str,
+        network: &str,
+        kind: SpendKind,
+        target: &str,
+        gas: Gas,
+        now: DateTime<Utc>,
+    ) -> CanvasResult<Vec<AlertEvent>> {
+        let Some((team, member)) = self.member(address) else {
+            return Ok(Vec::new());
+        };
+        let spends = self.spends()?;
+        let before = Self::usage_from(team, &spends, now).used;
+        let spend = GasSpend {
+            team: team.name.clone(),
+            member: member.to_string(),
+            network: network.to_string(),
+            kind,
+            target: target.to_string(),
+            gas,
+            at: now,
+        };
+        if let Some(parent) = self.ledger.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut file = std::fs::OpenOptions::new().create(true).append(true).open(&self.ledger)?;
+        writeln!(file, "{}", serde_json::to_string(&spend)?)?;
+        let after = before.saturating_add(gas);
+
+        let crossed = |limit: Gas| before < limit && after >= limit;
+        let mut alerts = Vec::new();
+        if let Some(cap) = team.cap() {
+            let threshold = (cap as f64 * team.alert_at) as Gas;
+            if threshold < cap && crossed(threshold) {
+                alerts.push(AlertEvent::new(
+                    "gas-budget",
+                    AlertSeverity::Info,
+                    format!("Team '{}' has used {} of its {} gas budget", team.name, after, cap),
+                ));
+            }
+        }
+        if let Some(soft) = team.soft_limit {
+            if before <= soft && after > soft {
+                alerts.push(AlertEvent::new(
+                    "gas-budget",
+                    AlertSeverity::Warning,
+                    format!("Team '{}' is over its soft gas limit: {} of {}", team.name, after, soft),
+                ));
+            }
+        }
+        if let Some(hard) = team.hard_limit {
+            if crossed(hard) {
+                alerts.push(AlertEvent::new(
+                    "gas-budget",
+                    AlertSeverity::Critical,
+                    format!(
+                        "Team '{}' reached its hard gas limit of {}; spending on {} is blocked until the period ends",
+                        team.name,
+                        hard,
+                        team.enforced_networks.join(", ")
+                    ),
+                ));
+            }
+        }
+        for alert in &mut alerts {
+            alert.subject = Some(team.name.clone());
+            alert.raised_at = now;
+            self.alerts.record(alert)?;
+        }
+        Ok(alerts)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn budgets(root: &Path) -> GasBudgets {
+        let config: BudgetConfig = serde_json::from_value(serde_json::json!({
+            "teams": [{
+                "name": "core",
+                "members": { "alice": "0xA11CE", "bob": "0xb0b" },
+                "soft_limit": 800,
+                "hard_limit": 1000,
+                "alert_at": 0.5
+            }]
+        }))
+        .unwrap();
+        config.validate().unwrap();
+        GasBudgets::new(root, config)
+    }
+
+    #[test]
+    fn test_debits_alert_and_block_mainnet_at_hard_limit() {
+        let dir = tempfile::tempdir().unwrap();
+        let budgets = budgets(dir.path());
+        let now = Utc.with_ymd_and_hms(2026, 3, 10, 12, 0, 0).unwrap();
+
+        let alerts = budgets.debit("0xa11ce", "mainnet", SpendKind::Deploy, "token.wasm", 600, now).unwrap();
+        assert_eq!(alerts.len(), 1);
+        let alerts = budgets.debit("0xb0b", "local", SpendKind::Call, "0x1.mint", 300, now).unwrap();
+        assert_eq!(alerts.len(), 1);
+        assert!(budgets.authorize("0xb0b", "mainnet", now).is_ok());
+        let alerts = budgets.debit("0xb0b", "mainnet", SpendKind::Call, "0x1.mint", 100, now).unwrap();
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(AlertLog::new(dir.path()).history().unwrap().len(), 3);
+
+        assert!(matches!(
+            budgets.authorize("0xA11CE", "mainnet", now),
+            Err(CanvasError::PermissionDenied(_))
+        ));
+        assert!(budgets.authorize("0xa11ce", "local", now).is_ok());
+        assert!(budgets.authorize("0xstranger", "mainnet", now).is_ok());
+        assert!(budgets.debit("0xstranger", "mainnet", SpendKind::Call, "x", 5, now).unwrap().is_empty());
+
+        let usage = &budgets.report(now).unwrap()[0];
+        assert_eq!(usage.used, 1000);
+        assert_eq!(usage.members["alice"], 600);
+        assert_eq!(usage.members["bob"], 400);
+        assert!(usage.over_soft_limit() && usage.at_hard_limit());
+
+        // A new month starts with a fresh budget
+        let next_month = Utc.with_ymd_and_hms(2026, 4, 1, 0, 0, 0).unwrap();
+        assert!(budgets.authorize("0xa11ce", "mainnet", next_month).is_ok());
+        assert_eq!(budgets.report(next_month).unwrap()[0].used, 0);
+    }
+
+    #[test]
+    fn test_periods_and_validation() {
+        let wednesday = Utc.with_ymd_and_hms(2026, 3, 11, 15, 30, 0).unwrap();
+        assert_eq!(
+            BudgetPeriod::Weekly.start(wednesday),
+            Some(Utc.with_ymd_and_hms(2026, 3, 9, 0, 0, 0).unwrap())
+        );
+        assert_eq!(
+            BudgetPeriod::Monthly.start(wednesday),
+            Some(Utc.with_ymd_and_hms(2026, 3, 1, 0, 0, 0).unwrap())
+        );
+        assert_eq!(BudgetPeriod::Total.start(wednesday), None);
+
+        let shared: BudgetConfig = serde_json::from_value(serde_json::json!({
+            "teams": [
+                { "name": "a", "members": { "x": "0x1" } },
+                { "name": "b", "members": { "y": "0x1" } }
+            ]
+        }))
+        .unwrap();
+        assert!(shared.validate().is_err());
+    }
+}
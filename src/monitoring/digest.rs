@@ -0,0 +1,725 @@
+//! Workspace health digests
+//!
+//! A digest summarizes one reporting period of a workspace: the health of the
+//! latest release in each deployment environment, how the gas estimates of
+//! its graphs moved (see [`gas_history`](super::gas_history)), the alerts
+//! raised (see [`alerts`](super::alerts)) and the marketplace updates
+//! available for the custom nodes it has installed. It is rendered as
+//! Markdown or HTML and sent to the channels configured in
+//! `.canvas/digest.json`. [`ReportingService`] sends one whenever the
+//! configured period has elapsed since the last.
+
+use std::{
+    fmt::Write as _,
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use semver::Version;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    alerts::{AlertEvent, AlertLog},
+    gas_history::{GasHistory, GasTrend},
+};
+use crate::{
+    deployment::{environments::ReleaseStore, AlertSeverity, NotificationConfig},
+    error::{CanvasError, CanvasResult},
+    marketplace::MarketplaceItem,
+    nodes::custom::CustomNodeRegistry,
+};
+
+/// Digest settings, relative to the workspace root
+pub const DIGEST_CONFIG_FILE: &str = ".canvas/digest.json";
+/// When the last digest was sent, relative to the workspace root
+pub const DIGEST_STATE_FILE: &str = ".canvas/digest-state.json";
+
+const DELIVERY_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// How often a digest is sent
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DigestFrequency {
+    Daily,
+    #[default]
+    Weekly,
+}
+
+impl DigestFrequency {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "daily" => Some(Self::Daily),
+            "weekly" => Some(Self::Weekly),
+            _ => None,
+        }
+    }
+
+    pub fn period(&self) -> Duration {
+        match self {
+            Self::Daily => Duration::days(1),
+            Self::Weekly => Duration::weeks(1),
+        }
+    }
+}
+
+/// Rendering of a digest
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DigestFormat {
+    #[default]
+    Markdown,
+    Html,
+}
+
+impl DigestFormat {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "markdown" | "md" => Some(Self::Markdown),
+            "html" => Some(Self::Html),
+            _ => None,
+        }
+    }
+}
+
+/// Digest settings of a workspace
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DigestConfig {
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    #[serde(default)]
+    pub frequency: DigestFrequency,
+    #[serde(default)]
+    pub format: DigestFormat,
+    /// Channels the digest is sent to
+    #[serde(default = "no_channels")]
+    pub notification: NotificationConfig,
+    /// Gas growth over the period, in percent, flagged in the digest
+    #[serde(default = "default_gas_warning")]
+    pub gas_warning_percent: f64,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+fn no_channels() -> NotificationConfig {
+    NotificationConfig {
+        email: None,
+        webhook: None,
+        slack: None,
+    }
+}
+
+fn default_gas_warning() -> f64 {
+    5.0
+}
+
+impl Default for DigestConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_enabled(),
+            frequency: DigestFrequency::default(),
+            format: DigestFormat::default(),
+            notification: no_channels(),
+            gas_warning_percent: default_gas_warning(),
+        }
+    }
+}
+
+impl DigestConfig {
+    /// Settings of the workspace at `root`, or the defaults when it has none
+    pub fn load(root: &Path) -> CanvasResult<Self> {
+        let path = root.join(DIGEST_CONFIG_FILE);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = std::fs::read_to_string(&path)?;
+        serde_json::from_str(&content)
+            .map_err(|e| CanvasError::Config(format!("Invalid digest settings {}: {}", path.display(), e)))
+    }
+
+    pub fn has_channels(&self) -> bool {
+        let channels = &self.notification;
+        channels.email.is_some() || channels.webhook.is_some() || channels.slack.is_some()
+    }
+}
+
+/// Health of the latest release in one environment
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EnvironmentHealth {
+    pub environment: String,
+    pub contract_address: String,
+    pub artifact_hash: String,
+    pub deployed_at: DateTime<Utc>,
+    pub verified: bool,
+    pub frozen: bool,
+    /// Releases to the environment during the period
+    pub releases_in_period: usize,
+    /// Critical alerts about the environment or its contract during the period
+    pub critical_alerts: usize,
+}
+
+impl EnvironmentHealth {
+    pub fn status(&self) -> &'static str {
+        if self.critical_alerts > 0 {
+            "alerting"
+        } else if !self.verified {
+            "unverified"
+        } else {
+            "healthy"
+        }
+    }
+}
+
+/// A newer marketplace version of an installed custom node
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MarketplaceUpdate {
+    pub node_id: String,
+    pub installed: Version,
+    pub available: Version,
+}
+
+/// Installed nodes with a newer version listed in `items`
+pub fn marketplace_updates(registry: &CustomNodeRegistry, items: &[MarketplaceItem]) -> Vec<MarketplaceUpdate> {
+    let mut updates: Vec<MarketplaceUpdate> = items
+        .iter()
+        .filter_map(|item| {
+            let installed = registry.installed_version(&item.id)?;
+            let available = Version::parse(&item.version).ok()?;
+            (available > *installed).then(|| MarketplaceUpdate {
+                node_id: item.id.clone(),
+                installed: installed.clone(),
+                available,
+            })
+        })
+        .collect();
+    updates.sort_by(|a, b| a.node_id.cmp(&b.node_id));
+    updates.dedup_by(|a, b| a.node_id == b.node_id);
+    updates
+}
+
+/// Summary of one reporting period
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Digest {
+    pub workspace: String,
+    pub period_start: DateTime<Utc>,
+    pub period_end: DateTime<Utc>,
+    pub deployments: Vec<EnvironmentHealth>,
+    pub gas: Vec<GasTrend>,
+    pub alerts: Vec<AlertEvent>,
+    pub updates: Vec<MarketplaceUpdate>,
+    /// Gas growth, in percent, flagged in the rendering
+    pub gas_warning_percent: f64,
+}
+
+fn is_critical(severity: &AlertSeverity) -> bool {
+    matches!(severity, AlertSeverity::Critical)
+}
+
+fn severity_name(severity: &AlertSeverity) -> &'static str {
+    match severity {
+        AlertSeverity::Info => "info",
+        AlertSeverity::Warning => "warning",
+        AlertSeverity::Critical => "critical",
+    }
+}
+
+fn timestamp(seconds: u64) -> DateTime<Utc> {
+    Utc.timestamp_opt(seconds as i64, 0).single().unwrap_or_default()
+}
+
+impl Digest {
+    /// Compile the digest of the workspace at `root` for the period ending at `end`
+    pub fn compile(
+        root: &Path,
+        config: &DigestConfig,
+        updates: Vec<MarketplaceUpdate>,
+        end: DateTime<Utc>,
+    ) -> CanvasResult<Self> {
+        let start = end - config.frequency.period();
+        let alerts: Vec<AlertEvent> = AlertLog::new(root)
+            .since(start)?
+            .into_iter()
+            .filter(|alert| alert.raised_at <= end)
+            .collect();
+
+        let releases = ReleaseStore::new(root);
+        let attestations = releases.attestations();
+        let mut deployments = Vec::new();
+        for environment in releases.environments()? {
+            let history = releases.history(&environment)?;
+            let Some(latest) = history.last() else {
+                continue;
+            };
+            let about = |alert: &&AlertEvent| {
+                alert
+                    .subject
+                    .as_deref()
+                    .is_some_and(|subject| subject == environment || subject == latest.contract_address)
+            };
+            deployments.push(EnvironmentHealth {
+                environment: environment.clone(),
+                contract_address: latest.contract_address.clone(),
+                artifact_hash: latest.artifact_hash.clone(),
+                deployed_at: timestamp(latest.deployed_at),
+                verified: latest.is_verified(),
+                frozen: attestations.get(&latest.contract_address)?.is_some(),
+                releases_in_period: history
+                    .iter()
+                    .filter(|release| (start..=end).contains(&timestamp(release.deployed_at)))
+                    .count(),
+                critical_alerts: alerts.iter().filter(about).filter(|a| is_critical(&a.severity)).count(),
+            });
+        }
+
+        let workspace = std::fs::canonicalize(root)
+            .ok()
+            .and_then(|path| path.file_name().map(|name| name.to_string_lossy().into_owned()))
+            .unwrap_or_else(|| root.display().to_string());
+
+        Ok(Self {
+            workspace,
+            period_start: start,
+            period_end: end,
+            deployments,
+            gas: GasHistory::new(root).trends(start)?,
+            alerts,
+            updates,
+            gas_warning_percent: config.gas_warning_percent,
+        })
+    }
+
+    pub fn title(&self) -> String {
+        format!(
+            "{} digest, {} to {}",
+            self.workspace,
+            self.period_start.format("%Y-%m-%d"),
+            self.period_end.format("%Y-%m-%d")
+        )
+    }
+
+    fn alert_counts(&self) -> [(&'static str, usize); 3] {
+        let count = |name| self.alerts.iter().filter(|a| severity_name(&a.severity) == name).count();
+        [("critical", count("critical")), ("warning", count("warning")), ("info", count("info"))]
+    }
+
+    pub fn render(&self, format: DigestFormat) -> String {
+        match format {
+            DigestFormat::Markdown => self.render_markdown(),
+            DigestFormat::Html => self.render_html(),
+        }
+    }
+
+    pub fn render_markdown(&self) -> String {
+        let mut out = format!("# {}\n\n", self.title());
+
+        out.push_str("## Deployments\n\n");
+        if self.deployments.is_empty() {
+            out.push_str("No releases recorded.\n\n");
+        } else {
+            out.push_str("| Environment | Contract | Deployed | Status | Releases | Critical alerts |\n");
+            out.push_str("|---|---|---|---|---|---|\n");
+            for health in &self.deployments {
+                let frozen = if health.frozen { ", frozen" } else { "" };
+                let _ = writeln!(
+                    out,
+                    "| {} | `{}` | {} | {}{} | {} | {} |",
+                    health.environment,
+                    health.contract_address,
+                    health.deployed_at.format("%Y-%m-%d %H:%M"),
+                    health.status(),
+                    frozen,
+                    health.releases_in_period,
+                    health.critical_alerts
+                );
+            }
+            out.push('\n');
+        }
+
+        out.push_str("## Gas usage\n\n");
+        if self.gas.is_empty() {
+            out.push_str("No gas estimates recorded in this period.\n\n");
+        } else {
+            out.push_str("| Graph | Start | End | Change |\n|---|---|---|---|\n");
+            for trend in &self.gas {
+                let flag = if trend.change_percent() > self.gas_warning_percent { " ⚠" } else { "" };
+                let _ = writeln!(
+                    out,
+                    "| {} | {} | {} | {:+.1}%{} |",
+                    trend.target,
+                    trend.first,
+                    trend.last,
+                    trend.change_percent(),
+                    flag
+                );
+            }
+            out.push('\n');
+        }
+
+        out.push_str("## Alerts\n\n");
+        if self.alerts.is_empty() {
+            out.push_str("No alerts raised.\n\n");
+        } else {
+            let counts: Vec<String> = self.alert_counts().iter().map(|(name, n)| format!("{} {}", n, name)).collect();
+            let _ = writeln!(out, "{}\n", counts.join(", "));
+            for alert in self.alerts.iter().rev() {
+                let subject = alert.subject.as_deref().map(|s| format!(" ({})", s)).unwrap_or_default();
+                let _ = writeln!(
+                    out,
+                    "- {} **{}** {}{}: {}",
+                    alert.raised_at.format("%Y-%m-%d %H:%M"),
+                    severity_name(&alert.severity),
+                    alert.rule,
+                    subject,
+                    alert.message
+                );
+            }
+            out.push('\n');
+        }
+
+        out.push_str("## Marketplace updates\n\n");
+        if self.updates.is_empty() {
+            out.push_str("Installed custom nodes are up to date.\n");
+        } else {
+            for update in &self.updates {
+                let _ = writeln!(out, "- {} {} → {}", update.node_id, update.installed, update.available);
+            }
+        }
+        out
+    }
+
+    pub fn render_html(&self) -> String {
+        let mut body = String::new();
+
+        body.push_str("<h2>Deployments</h2>\n");
+        if self.deployments.is_empty() {
+            body.push_str("<p>No releases recorded.</p>\n");
+        } else {
+            body.push_str(
+                "<table><tr><th>Environment</th><th>Contract</th><th>Deployed</th><th>Status</th>\
+                 <th>Releases</th><th>Critical alerts</th></tr>\n",
+            );
+            for health in &self.deployments {
+                let frozen = if health.frozen { ", frozen" } else { "" };
+                let _ = writeln!(
+                    body,
+                    r#"<tr><td>{}</td><td><code>{}</code></td><td>{}</td><td class="{}">{}{}</td><td>{}</td><td>{}</td></tr>"#,
+                    escape(&health.environment),
+                    escape(&health.contract_address),
+                    health.deployed_at.format("%Y-%m-%d %H:%M"),
+                    health.status(),
+                    health.status(),
+                    frozen,
+                    health.releases_in_period,
+                    health.critical_alerts
+                );
+            }
+            body.push_str("</table>\n");
+        }
+
+        body.push_str("<h2>Gas usage</h2>\n");
+        if self.gas.is_empty() {
+            body.push_str("<p>No gas estimates recorded in this period.</p>\n");
+        } else {
+            body.push_str("<table><tr><th>Graph</th><th>Start</th><th>End</th><th>Change</th></tr>\n");
+            for trend in &self.gas {
+                let class = if trend.change_percent() > self.gas_warning_percent { "alerting" } else { "" };
+                let _ = writeln!(
+                    body,
+                    r#"<tr><td>{}</td><td>{}</td><td>{}</td><td class="{}">{:+.1}%</td></tr>"#,
+                    escape(&trend.target),
+                    trend.first,
+                    trend.last,
+                    class,
+                    trend.change_percent()
+                );
+            }
+            body.push_str("</table>\n");
+        }
+
+        body.push_str("<h2>Alerts</h2>\n");
+        if self.alerts.is_empty() {
+            body.push_str("<p>No alerts raised.</p>\n");
+        } else {
+            let counts: Vec<String> = self.alert_counts().iter().map(|(name, n)| format!("{} {}", n, name)).collect();
+            let _ = writeln!(body, "<p>{}</p>\n<ul>", counts.join(", "));
+            for alert in self.alerts.iter().rev() {
+                let subject = alert.subject.as_deref().map(|s| format!(" ({})", escape(s))).unwrap_or_default();
+                let _ = writeln!(
+                    body,
+                    r#"<li>{} <strong class="{}">{}</strong> {}{}: {}</li>"#,
+                    alert.raised_at.format("%Y-%m-%d %H:%M"),
+                    severity_name(&alert.severity),
+                    severity_name(&alert.severity),
+                    escape(&alert.rule),
+                    subject,
+                    escape(&alert.message)
+                );
+            }
+            body.push_str("</ul>\n");
+        }
+
+        body.push_str("<h2>Marketplace updates</h2>\n");
+        if self.updates.is_empty() {
+            body.push_str("<p>Installed custom nodes are up to date.</p>\n");
+        } else {
+            body.push_str("<ul>\n");
+            for update in &self.updates {
+                let _ = writeln!(
+                    body,
+                    "<li>{} {} &rarr; {}</li>",
+                    escape(&update.node_id),
+                    update.installed,
+                    update.available
+                );
+            }
+            body.push_str("</ul>\n");
+        }
+
+        format!(
+            r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>{title}</title>
+<style>
+body {{ font-family: sans-serif; max-width: 60rem; margin: 2rem auto; }}
+table {{ border-collapse: collapse; }}
+th, td {{ border: 1px solid #ccc; padding: 0.25rem 0.5rem; text-align: left; }}
+.alerting, .critical {{ color: #b00020; }}
+.unverified, .warning {{ color: #b26a00; }}
+.healthy {{ color: #1b7f3b; }}
+</style>
+</head>
+<body>
+<h1>{title}</h1>
+{body}</body>
+</html>
+"#,
+            title = escape(&self.title()),
+            body = body
+        )
+    }
+}
+
+/// Escape text for HTML content and quoted attribute values
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Outcome of sending a digest to each configured channel
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct DeliveryReport {
+    /// Channels the digest was sent to
+    pub delivered: Vec<String>,
+    /// Channels that failed, with the reason
+    pub failed: Vec<(String, String)>,
+}
+
+/// Send a digest to the channels in `channels`.
+///
+/// Webhooks receive the digest as JSON along with its rendering; Slack
+/// incoming webhooks receive the Markdown rendering as the message text.
+/// Email is not sent directly and is reported as failed.
+pub fn deliver(digest: &Digest, format: DigestFormat, channels: &NotificationConfig) -> DeliveryReport {
+    let agent = ureq::AgentBuilder::new().timeout(DELIVERY_TIMEOUT).build();
+    let mut report = DeliveryReport::default();
+    let mut outcome = |channel: &str, result: Result<(), String>| match result {
+        Ok(()) => report.delivered.push(channel.to_string()),
+        Err(e) => report.failed.push((channel.to_string(), e)),
+    };
+
+    if let Some(url) = &channels.webhook {
+        let payload = serde_json::json!({
+            "title": digest.title(),
+            "format": format,
+            "body": digest.render(format),
+            "digest": digest,
+        });
+        outcome("webhook", agent.post(url).send_json(payload).map(drop).map_err(|e| e.to_string()));
+    }
+    if let Some(url) = &channels.slack {
+        let payload = serde_json::json!({ "text": digest.render_markdown() });
+        outcome("slack", agent.post(url).send_json(payload).map(drop).map_err(|e| e.to_string()));
+    }
+    if let Some(address) = &channels.email {
+        outcome(
+            &format!("email {}", address),
+            Err("email delivery is not supported; use a webhook to an email relay".to_string()),
+        );
+    }
+    report
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct DigestState {
+    last_sent: Option<DateTime<Utc>>,
+}
+
+/// Sends a workspace's digest once per configured period
+pub struct ReportingService {
+    root: PathBuf,
+    config: DigestConfig,
+}
+
+impl ReportingService {
+    pub fn new(root: &Path, config: DigestConfig) -> Self {
+        Self {
+            root: root.to_path_buf(),
+            config,
+        }
+    }
+
+    /// Service for the workspace at `root` with its own settings
+    pub fn load(root: &Path) -> CanvasResult<Self> {
+        Ok(Self::new(root, DigestConfig::load(root)?))
+    }
+
+    pub fn config(&self) -> &DigestConfig {
+        &self.config
+    }
+
+    fn state_path(&self) -> PathBuf {
+        self.root.join(DIGEST_STATE_FILE)
+    }
+
+    /// When the last digest was sent
+    pub fn last_sent(&self) -> CanvasResult<Option<DateTime<Utc>>> {
+        let path = self.state_path();
+        if !path.exists() {
+            return Ok(None);
+        }
+        let state: DigestState = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+        Ok(state.last_sent)
+    }
+
+    /// Whether a digest is due at `now`
+    pub fn is_due(&self, now: DateTime<Utc>) -> CanvasResult<bool> {
+        Ok(self.config.enabled
+            && self
+                .last_sent()?
+                .map_or(true, |last| now - last >= self.config.frequency.period()))
+    }
+
+    pub fn compile(&self, updates: Vec<MarketplaceUpdate>, now: DateTime<Utc>) -> CanvasResult<Digest> {
+        Digest::compile(&self.root, &self.config, updates, now)
+    }
+
+    /// Send `digest` to the configured channels and, if any received it,
+    /// remember it as the last one sent
+    pub fn send(&self, digest: &Digest) -> CanvasResult<DeliveryReport> {
+        if !self.config.has_channels() {
+            return Err(CanvasError::Config(format!(
+                "No notification channels in {}",
+                self.root.join(DIGEST_CONFIG_FILE).display()
+            )));
+        }
+        let report = deliver(digest, self.config.format, &self.config.notification);
+        if !report.delivered.is_empty() {
+            let path = self.state_path();
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            let state = DigestState {
+                last_sent: Some(digest.period_end),
+            };
+            std::fs::write(path, serde_json::to_string_pretty(&state)?)?;
+        }
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::deployment::environments::ReleaseRecord;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_digest_summarizes_period() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        let now = Utc::now();
+        let config = DigestConfig::default();
+
+        let wasm = b"\0asm\x01\0\0\0";
+        ReleaseStore::new(root)
+            .record(
+                ReleaseRecord {
+                    environment: "prod".to_string(),
+                    contract_address: "0xabc".to_string(),
+                    artifact_hash: crate::deployment::environments::artifact_hash(wasm),
+                    abi: None,
+                    constructor_args: serde_json::Value::Null,
+                    block_number: 1,
+                    deployed_at: now.timestamp() as u64,
+                    promoted_from: None,
+                    verified_at: None,
+                    deployer: None,
+                },
+                wasm,
+            )
+            .unwrap();
+
+        let log = AlertLog::new(root);
+        let mut old = AlertEvent::new("drift", AlertSeverity::Critical, "stale");
+        old.raised_at = now - Duration::weeks(2);
+        log.record(&old).unwrap();
+        log.record(&AlertEvent::new("drift", AlertSeverity::Critical, "code <changed>").with_subject("prod"))
+            .unwrap();
+
+        let gas = GasHistory::new(root);
+        gas.record(&BTreeMap::from([("token.json".to_string(), 1000)]), now - Duration::days(3))
+            .unwrap();
+        gas.record(&BTreeMap::from([("token.json".to_string(), 1100)]), now - Duration::days(1))
+            .unwrap();
+
+        let digest = Digest::compile(root, &config, Vec::new(), now).unwrap();
+        assert_eq!(digest.alerts.len(), 1);
+        assert_eq!(digest.deployments.len(), 1);
+        assert_eq!(digest.deployments[0].status(), "alerting");
+        assert_eq!(digest.deployments[0].releases_in_period, 1);
+        assert_eq!(digest.gas.len(), 1);
+        assert_eq!(digest.gas[0].change_percent(), 10.0);
+
+        let markdown = digest.render_markdown();
+        assert!(markdown.contains("| prod | `0xabc` |"));
+        assert!(markdown.contains("+10.0% ⚠"));
+        let html = digest.render_html();
+        assert!(html.contains("code &lt;changed&gt;"));
+    }
+
+    #[test]
+    fn test_service_is_due_after_period() {
+        let dir = tempfile::tempdir().unwrap();
+        let service = ReportingService::new(
+            dir.path(),
+            DigestConfig {
+                frequency: DigestFrequency::Daily,
+                ..DigestConfig::default()
+            },
+        );
+        let now = Utc::now();
+        assert!(service.is_due(now).unwrap());
+
+        let state = DigestState { last_sent: Some(now) };
+        std::fs::create_dir_all(dir.path().join(".canvas")).unwrap();
+        std::fs::write(dir.path().join(DIGEST_STATE_FILE), serde_json::to_string(&state).unwrap()).unwrap();
+        assert!(!service.is_due(now + Duration::hours(12)).unwrap());
+        assert!(service.is_due(now + Duration::days(1)).unwrap());
+
+        let digest = service.compile(Vec::new(), now).unwrap();
+        assert!(service.send(&digest).is_err());
+    }
+}
@@ -0,0 +1,119 @@
+//! Gas usage history
+//!
+//! Every CI run appends the static gas estimate of each graph to
+//! `.canvas/gas-history.jsonl`, so reports can show how a contract's cost moved
+//! over a period instead of only its distance from the baseline.
+
+use std::{
+    collections::BTreeMap,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::{CanvasError, CanvasResult},
+    types::Gas,
+};
+
+/// Gas history, relative to the workspace root
+pub const GAS_HISTORY_FILE: &str = ".canvas/gas-history.jsonl";
+
+/// Gas estimate of one graph at one point in time
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GasSample {
+    /// Graph file the estimate is for
+    pub target: String,
+    pub gas: Gas,
+    pub recorded_at: DateTime<Utc>,
+}
+
+/// How a graph's gas estimate changed over a period
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GasTrend {
+    pub target: String,
+    /// Earliest estimate in the period
+    pub first: Gas,
+    /// Latest estimate in the period
+    pub last: Gas,
+    pub samples: usize,
+}
+
+impl GasTrend {
+    /// Change from the first to the last estimate, in percent
+    pub fn change_percent(&self) -> f64 {
+        (self.last as f64 - self.first as f64) * 100.0 / self.first.max(1) as f64
+    }
+}
+
+/// Append-only gas history of a workspace
+pub struct GasHistory {
+    path: PathBuf,
+}
+
+impl GasHistory {
+    pub fn new(root: &Path) -> Self {
+        Self {
+            path: root.join(GAS_HISTORY_FILE),
+        }
+    }
+
+    /// Record one estimate per graph, all stamped `at`
+    pub fn record(&self, estimates: &BTreeMap<String, Gas>, at: DateTime<Utc>) -> CanvasResult<()> {
+        if estimates.is_empty() {
+            return Ok(());
+        }
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut file = std::fs::OpenOptions::new().create(true).append(true).open(&self.path)?;
+        for (target, &gas) in estimates {
+            let sample = GasSample {
+                target: target.clone(),
+                gas,
+                recorded_at: at,
+            };
+            writeln!(file, "{}", serde_json::to_string(&sample)?)?;
+        }
+        Ok(())
+    }
+
+    /// Every recorded sample, oldest first
+    pub fn samples(&self) -> CanvasResult<Vec<GasSample>> {
+        if !self.path.exists() {
+            return Ok(Vec::new());
+        }
+        std::fs::read_to_string(&self.path)?
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(index, line)| {
+                serde_json::from_str(line).map_err(|e| {
+                    CanvasError::InvalidState(format!("{}:{}: invalid gas sample: {}", self.path.display(), index + 1, e))
+                })
+            })
+            .collect()
+    }
+
+    /// Trend of each graph over the samples recorded at or after `since`
+    pub fn trends(&self, since: DateTime<Utc>) -> CanvasResult<Vec<GasTrend>> {
+        let mut trends: BTreeMap<String, GasTrend> = BTreeMap::new();
+        for sample in self.samples()?.into_iter().filter(|s| s.recorded_at >= since) {
+            trends
+                .entry(sample.target.clone())
+                .and_modify(|trend| {
+                    trend.last = sample.gas;
+                    trend.samples += 1;
+                })
+                .or_insert(GasTrend {
+                    target: sample.target,
+                    first: sample.gas,
+                    last: sample.gas,
+                    samples: 1,
+                });
+        }
+        Ok(trends.into_values().collect())
+    }
+}
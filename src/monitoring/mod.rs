@@ -1,5 +1,10 @@
 //! Production monitoring and observability system
 
+pub mod alerts;
+pub mod budgets;
+pub mod digest;
+pub mod gas_history;
+
 use crate::{
     error::CanvasResult,
     types::{Graph, NodeId, NodeType},
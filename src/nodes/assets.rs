@@ -0,0 +1,401 @@
+//! Node icons and theme hints for the editor
+//!
+//! Icon images are stored content-addressed under `<data_dir>/assets`, one file
+//! per SHA-256 of the bytes, so the same icon shipped by several nodes or
+//! versions is kept once and a served asset never changes under its URL. The
+//! editor fetches icons from `/assets/<hash>` (over HTTP or the `canvas-asset`
+//! Tauri protocol) and the per-node-type appearance from `/nodes/appearance`.
+
+use std::{
+    io::Read,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use super::{custom::CustomNodeRegistry, encode_hex, NodeRegistry};
+use crate::{
+    config::Config,
+    error::{CanvasError, CanvasResult},
+    wasm::host,
+};
+
+/// Directory under the data directory holding node assets
+pub const ASSETS_DIR: &str = "assets";
+/// Largest icon accepted into the store
+pub const MAX_ASSET_BYTES: usize = 256 * 1024;
+/// Route prefix assets are served under, followed by the hash
+pub const ASSET_ROUTE: &str = "/assets/";
+/// Route serving the appearance of every node type
+pub const APPEARANCE_ROUTE: &str = "/nodes/appearance";
+/// Assets are immutable under their hash, so clients may cache them forever
+const IMMUTABLE_CACHE: &str = "public, max-age=31536000, immutable";
+
+/// An asset in the store
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AssetRef {
+    /// Lowercase hex SHA-256 of the content
+    pub hash: String,
+    pub media_type: String,
+}
+
+impl AssetRef {
+    /// Path the asset is served at
+    pub fn route(&self) -> String {
+        format!("{}{}", ASSET_ROUTE, self.hash)
+    }
+}
+
+/// Colors the editor draws a node with; unset hints fall back to the category's
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ThemeHints {
+    /// Header color, `#rrggbb`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>,
+    /// Border and port color, `#rrggbb`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub accent: Option<String>,
+    /// Header color under the dark theme, `#rrggbb`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dark_color: Option<String>,
+}
+
+impl ThemeHints {
+    pub fn is_empty(&self) -> bool {
+        self == &Self::default()
+    }
+
+    /// Check every set hint is a `#rrggbb` color
+    pub fn validate(&self) -> CanvasResult<()> {
+        for (name, value) in [("color", &self.color), ("accent", &self.accent), ("dark_color", &self.dark_color)] {
+            if let Some(value) = value {
+                let valid = value.len() == 7
+                    && value.starts_with('#')
+                    && value[1..].chars().all(|c| c.is_ascii_hexdigit());
+                if !valid {
+                    return Err(CanvasError::Validation(format!(
+                        "Theme {} '{}' is not a #rrggbb color",
+                        name, value
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// How the editor draws one node type
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NodeAppearance {
+    pub node_type: String,
+    /// Name of a built-in editor icon
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub icon_name: Option<String>,
+    /// Icon image in the asset store
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub icon: Option<AssetRef>,
+    pub theme: ThemeHints,
+}
+
+/// Appearance of every node type of `nodes` and installed custom node type
+pub fn node_appearances(nodes: &NodeRegistry, registry: &CustomNodeRegistry) -> Vec<NodeAppearance> {
+    let builtin = nodes.definitions().into_iter().map(|definition| NodeAppearance {
+        node_type: definition.id.clone(),
+        icon_name: definition.visual.icon.clone(),
+        icon: None,
+        theme: ThemeHints {
+            color: Some(definition.visual.color.clone()),
+            ..ThemeHints::default()
+        },
+    });
+    let custom = registry.list_nodes().into_iter().map(|definition| NodeAppearance {
+        node_type: definition.id.clone(),
+        icon_name: None,
+        icon: definition.icon.clone(),
+        theme: definition.theme.clone(),
+    });
+    let mut appearances: Vec<NodeAppearance> = builtin.chain(custom).collect();
+    appearances.sort_by(|a, b| a.node_type.cmp(&b.node_type));
+    appearances
+}
+
+/// Media type of an icon image, recognized by its leading bytes
+pub fn sniff_media_type(bytes: &[u8]) -> Option<&'static str> {
+    if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+        return Some("image/png");
+    }
+    if bytes.starts_with(&[0xff, 0xd8, 0xff]) {
+        return Some("image/jpeg");
+    }
+    if bytes.len() >= 12 && &bytes[..4] == b"RIFF" && &bytes[8..12] == b"WEBP" {
+        return Some("image/webp");
+    }
+    let text = std::str::from_utf8(&bytes[..bytes.len().min(512)]).ok()?.trim_start();
+    (text.starts_with("<svg") || (text.starts_with("<?xml") && text.contains("<svg"))).then_some("image/svg+xml")
+}
+
+fn is_hash(hash: &str) -> bool {
+    hash.len() == 64 && hash.chars().all(|c| c.is_ascii_digit() || ('a'..='f').contains(&c))
+}
+
+fn content_hash(bytes: &[u8]) -> String {
+    encode_hex(&host::hash(host::HashAlgorithm::Sha256, bytes)).trim_start_matches("0x").to_string()
+}
+
+/// An HTTP response to an asset request
+#[derive(Debug, Clone)]
+pub struct AssetResponse {
+    pub status: u16,
+    pub headers: Vec<(&'static str, String)>,
+    pub body: Vec<u8>,
+}
+
+impl AssetResponse {
+    fn error(status: u16, message: &str) -> Self {
+        Self {
+            status,
+            headers: vec![("Content-Type", "application/json".to_string())],
+            body: serde_json::json!({ "error": message }).to_string().into_bytes(),
+        }
+    }
+
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter().find(|(n, _)| n.eq_ignore_ascii_case(name)).map(|(_, v)| v.as_str())
+    }
+}
+
+/// Content-addressed store of node assets
+#[derive(Debug, Clone)]
+pub struct AssetStore {
+    dir: PathBuf,
+}
+
+impl AssetStore {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    pub fn default_path(config: &Config) -> PathBuf {
+        config.app.data_dir.join(ASSETS_DIR)
+    }
+
+    fn path(&self, hash: &str) -> PathBuf {
+        self.dir.join(&hash[..2]).join(hash)
+    }
+
+    /// Add an icon, keeping the copy already stored under the same hash
+    pub fn put(&self, bytes: &[u8]) -> CanvasResult<AssetRef> {
+        if bytes.len() > MAX_ASSET_BYTES {
+            return Err(CanvasError::Validation(format!(
+                "Asset is {} bytes; at most {} are allowed",
+                bytes.len(),
+                MAX_ASSET_BYTES
+            )));
+        }
+        let media_type = sniff_media_type(bytes).ok_or_else(|| {
+            CanvasError::Validation("Asset is not a PNG, JPEG, WebP or SVG image".to_string())
+        })?;
+        let hash = content_hash(bytes);
+        let path = self.path(&hash);
+        if !path.exists() {
+            std::fs::create_dir_all(path.parent().unwrap_or(&self.dir))?;
+            let partial = path.with_extension("partial");
+            std::fs::write(&partial, bytes)?;
+            std::fs::rename(&partial, &path)?;
+        }
+        Ok(AssetRef {
+            hash,
+            media_type: media_type.to_string(),
+        })
+    }
+
+    /// Add an icon file
+    pub fn import_file(&self, path: &Path) -> CanvasResult<AssetRef> {
+        let mut bytes = Vec::new();
+        std::fs::File::open(path)?
+            .take(MAX_ASSET_BYTES as u64 + 1)
+            .read_to_end(&mut bytes)?;
+        self.put(&bytes)
+    }
+
+    /// Content and media type of an asset, `None` if it is not stored
+    pub fn get(&self, hash: &str) -> CanvasResult<Option<(Vec<u8>, &'static str)>> {
+        if !is_hash(hash) {
+            return Err(CanvasError::Validation(format!("'{}' is not an asset hash", hash)));
+        }
+        let path = self.path(hash);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let bytes = std::fs::read(&path)?;
+        if content_hash(&bytes) != hash {
+            return Err(CanvasError::InvalidState(format!("Asset {} is corrupt", path.display())));
+        }
+        let media_type = sniff_media_type(&bytes)
+            .ok_or_else(|| CanvasError::InvalidState(format!("Asset {} is not an image", path.display())))?;
+        Ok(Some((bytes, media_type)))
+    }
+
+    pub fn contains(&self, asset: &AssetRef) -> bool {
+        is_hash(&asset.hash) && self.path(&asset.hash).exists()
+    }
+
+    /// Respond to `GET /assets/<hash>`; `if_none_match` is the request's
+    /// `If-None-Match` header
+    pub fn respond(&self, hash: &str, if_none_match: Option<&str>) -> AssetResponse {
+        let etag = format!("\"{}\"", hash);
+        if if_none_match.map_or(false, |tags| tags.split(',').any(|tag| tag.trim() == etag)) && is_hash(hash) {
+            return AssetResponse {
+                status: 304,
+                headers: vec![("ETag", etag), ("Cache-Control", IMMUTABLE_CACHE.to_string())],
+                body: Vec::new(),
+            };
+        }
+        match self.get(hash) {
+            Ok(Some((body, media_type))) => AssetResponse {
+                status: 200,
+                headers: vec![
+                    ("Content-Type", media_type.to_string()),
+                    ("ETag", etag),
+                    ("Cache-Control", IMMUTABLE_CACHE.to_string()),
+                ],
+                body,
+            },
+            Ok(None) => AssetResponse::error(404, &format!("No asset {}", hash)),
+            Err(CanvasError::Validation(message)) => AssetResponse::error(400, &message),
+            Err(e) => AssetResponse::error(500, &e.to_string()),
+        }
+    }
+}
+
+/// Serves the asset store and node appearances to the editor
+pub struct AssetServer {
+    store: AssetStore,
+    nodes: NodeRegistry,
+    appearances: Vec<NodeAppearance>,
+}
+
+impl AssetServer {
+    /// Serve `store`, drawing the node types of `nodes` and the custom nodes of `registry`
+    pub fn new(store: AssetStore, nodes: NodeRegistry, registry: &CustomNodeRegistry) -> Self {
+        let appearances = node_appearances(&nodes, registry);
+        Self {
+            store,
+            nodes,
+            appearances,
+        }
+    }
+
+    pub fn store(&self) -> &AssetStore {
+        &self.store
+    }
+
+    pub fn appearances(&self) -> &[NodeAppearance] {
+        &self.appearances
+    }
+
+    /// Pick up nodes installed or removed since the server was created
+    pub fn refresh(&mut self, registry: &CustomNodeRegistry) {
+        self.appearances = node_appearances(&self.nodes, registry);
+    }
+
+    /// Route one request
+    pub fn handle(&self, method: &str, path: &str, if_none_match: Option<&str>) -> AssetResponse {
+        if method != "GET" && method != "HEAD" {
+            return AssetResponse::error(405, "Only GET and HEAD are supported");
+        }
+        let path = path.split('?').next().unwrap_or_default();
+        if let Some(hash) = path.strip_prefix(ASSET_ROUTE) {
+            return self.store.respond(hash, if_none_match);
+        }
+        if path != APPEARANCE_ROUTE {
+            return AssetResponse::error(404, &format!("No route {}", path));
+        }
+        let body = match serde_json::to_vec(&self.appearances) {
+            Ok(body) => body,
+            Err(e) => return AssetResponse::error(500, &e.to_string()),
+        };
+        // Appearances change when nodes are installed, so clients revalidate
+        let etag = format!("\"{}\"", &content_hash(&body)[..16]);
+        if if_none_match == Some(etag.as_str()) {
+            return AssetResponse {
+                status: 304,
+                headers: vec![("ETag", etag), ("Cache-Control", "no-cache".to_string())],
+                body: Vec::new(),
+            };
+        }
+        AssetResponse {
+            status: 200,
+            headers: vec![
+                ("Content-Type", "application/json".to_string()),
+                ("ETag", etag),
+                ("Cache-Control", "no-cache".to_string()),
+            ],
+            body,
+        }
+    }
+
+    /// Serve until the process exits
+    pub fn serve(&self, address: &str) -> CanvasResult<()> {
+        let server = tiny_http::Server::http(address)
+            .map_err(|e| CanvasError::Network(format!("Failed to bind {}: {}", address, e)))?;
+        log::info!("Serving node assets on http://{}", address);
+        for request in server.incoming_requests() {
+            let if_none_match = request
+                .headers()
+                .iter()
+                .find(|h| h.field.equiv("If-None-Match"))
+                .map(|h| h.value.as_str().to_string());
+            let method = request.method().as_str().to_string();
+            let response = self.handle(&method, request.url(), if_none_match.as_deref());
+            let body = if method == "HEAD" { Vec::new() } else { response.body };
+            let mut reply = tiny_http::Response::from_data(body).with_status_code(response.status);
+            for (name, value) in &response.headers {
+                if let Ok(header) = tiny_http::Header::from_bytes(name.as_bytes(), value.as_bytes()) {
+                    reply.add_header(header);
+                }
+            }
+            if let Err(e) = request.respond(reply) {
+                log::warn!("Failed to answer asset request: {}", e);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SVG: &[u8] = b"<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"0 0 16 16\"/>";
+
+    #[test]
+    fn test_assets_are_content_addressed_and_cacheable() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = AssetStore::new(dir.path());
+        let asset = store.put(SVG).unwrap();
+        assert_eq!(asset.media_type, "image/svg+xml");
+        assert_eq!(store.put(SVG).unwrap(), asset);
+        assert!(store.put(b"not an image").is_err());
+
+        let response = store.respond(&asset.hash, None);
+        assert_eq!(response.status, 200);
+        assert_eq!(response.body, SVG);
+        assert_eq!(response.header("cache-control"), Some(IMMUTABLE_CACHE));
+        let etag = response.header("ETag").unwrap().to_string();
+        assert_eq!(store.respond(&asset.hash, Some(&etag)).status, 304);
+        assert_eq!(store.respond(&"0".repeat(64), None).status, 404);
+        assert_eq!(store.respond("../config.toml", None).status, 400);
+    }
+
+    #[test]
+    fn test_theme_hints_must_be_colors() {
+        let mut theme = ThemeHints {
+            color: Some("#1a2B3c".to_string()),
+            ..ThemeHints::default()
+        };
+        assert!(theme.validate().is_ok());
+        theme.accent = Some("red".to_string());
+        assert!(theme.validate().is_err());
+    }
+}
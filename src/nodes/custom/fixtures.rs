@@ -0,0 +1,82 @@
+//! Test cases shipped with custom nodes
+//!
+//! A custom node definition can embed test cases: inputs and properties to run
+//! the node with, and the outputs it must produce. They run through the
+//! registry exactly like a node in a graph, so WASM nodes are exercised under
+//! their sandbox and resource limits. Publishing to the marketplace requires a
+//! passing run.
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+/// One embedded test case
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NodeTestCase {
+    pub name: String,
+    #[serde(default)]
+    pub inputs: HashMap<String, serde_json::Value>,
+    #[serde(default)]
+    pub properties: HashMap<String, serde_json::Value>,
+    /// Outputs to check; outputs not listed here are not compared
+    pub expected_outputs: HashMap<String, serde_json::Value>,
+}
+
+/// Result of one test case
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NodeTestResult {
+    pub name: String,
+    /// Why the case failed; `None` when it passed
+    pub failure: Option<String>,
+}
+
+impl NodeTestResult {
+    pub fn passed(&self) -> bool {
+        self.failure.is_none()
+    }
+}
+
+/// Results of all test cases of a node
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NodeTestReport {
+    pub node_id: String,
+    pub results: Vec<NodeTestResult>,
+}
+
+impl NodeTestReport {
+    /// Whether the node has tests and all of them passed
+    pub fn passed(&self) -> bool {
+        !self.results.is_empty() && self.results.iter().all(|r| r.passed())
+    }
+
+    /// The failed cases
+    pub fn failures(&self) -> Vec<&NodeTestResult> {
+        self.results.iter().filter(|r| !r.passed()).collect()
+    }
+}
+
+/// Compare actual outputs against a test case's expectations
+pub fn check_outputs(
+    case: &NodeTestCase,
+    actual: &HashMap<String, serde_json::Value>,
+) -> Option<String> {
+    let mut names: Vec<&String> = case.expected_outputs.keys().collect();
+    names.sort();
+    let mismatches: Vec<String> = names
+        .into_iter()
+        .filter_map(|name| {
+            let expected = &case.expected_outputs[name];
+            match actual.get(name) {
+                Some(value) if value == expected => None,
+                Some(value) => Some(format!("'{}': expected {}, got {}", name, expected, value)),
+                None => Some(format!("'{}': expected {}, but the node did not produce it", name, expected)),
+            }
+        })
+        .collect();
+
+    if mismatches.is_empty() {
+        None
+    } else {
+        Some(mismatches.join("; "))
+    }
+}
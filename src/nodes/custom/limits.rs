@@ -0,0 +1,262 @@
+//! Resource limits for WASM-backed custom nodes
+//!
+//! Marketplace nodes run inside the editor and simulator, so a node that loops
+//! forever or grows memory without bound must not take the app down with it.
+//! Each call gets a fuel budget (roughly one unit per WASM instruction) and a
+//! linear memory cap; exceeding either aborts that call only.
+
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::RuntimeConfig,
+    error::{CanvasError, CanvasResult},
+};
+
+/// Size of a WASM memory page
+pub const WASM_PAGE_SIZE: usize = 64 * 1024;
+
+/// Budgets applied to one custom node call
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ResourceLimits {
+    /// Fuel available to the call
+    pub fuel: u64,
+    /// Maximum linear memory, in bytes
+    pub memory_bytes: usize,
+}
+
+impl Default for ResourceLimits {
+    fn default() -> Self {
+        Self {
+            fuel: 10_000_000,
+            memory_bytes: 256 * WASM_PAGE_SIZE,
+        }
+    }
+}
+
+impl ResourceLimits {
+    /// Limits configured for custom nodes in the runtime settings
+    pub fn from_config(config: &RuntimeConfig) -> Self {
+        Self {
+            fuel: config.custom_node_fuel,
+            memory_bytes: config.custom_node_memory_pages as usize * WASM_PAGE_SIZE,
+        }
+    }
+}
+
+/// Execution statistics of one custom node, accumulated across calls
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct NodeExecutionStats {
+    pub node_id: String,
+    pub invocations: u64,
+    pub failures: u64,
+    pub fuel_consumed: u64,
+    /// Largest linear memory seen at the end of a call, in bytes
+    pub peak_memory_bytes: usize,
+    pub total_time: Duration,
+    pub last_error: Option<String>,
+}
+
+impl NodeExecutionStats {
+    pub fn new(node_id: impl Into<String>) -> Self {
+        Self {
+            node_id: node_id.into(),
+            ..Self::default()
+        }
+    }
+
+    /// Fold one call into the totals
+    pub fn record(&mut self, run: &LimitedRun) {
+        self.invocations += 1;
+        self.fuel_consumed += run.fuel_consumed;
+        self.peak_memory_bytes = self.peak_memory_bytes.max(run.memory_bytes);
+        self.total_time += run.duration;
+        if let Some(error) = &run.error {
+            self.failures += 1;
+            self.last_error = Some(error.clone());
+        }
+    }
+
+    /// Average fuel per call
+    pub fn average_fuel(&self) -> u64 {
+        if self.invocations == 0 {
+            0
+        } else {
+            self.fuel_consumed / self.invocations
+        }
+    }
+}
+
+/// Outcome of one limited call
+#[derive(Debug, Clone)]
+pub struct LimitedRun {
+    /// Return values, present when the call succeeded
+    pub results: Vec<serde_json::Value>,
+    pub fuel_consumed: u64,
+    pub memory_bytes: usize,
+    pub duration: Duration,
+    pub error: Option<String>,
+}
+
+struct Limited {
+    limits: wasmtime::StoreLimits,
+}
+
+fn to_val(value: &serde_json::Value, ty: &wasmtime::ValType) -> CanvasResult<wasmtime::Val> {
+    let mismatch = || CanvasError::Type(format!("Cannot pass {} as a WASM {}", value, ty));
+    Ok(match ty {
+        wasmtime::ValType::I32 => match value {
+            serde_json::Value::Bool(b) => wasmtime::Val::I32(*b as i32),
+            _ => wasmtime::Val::I32(value.as_i64().ok_or_else(mismatch)? as i32),
+        },
+        wasmtime::ValType::I64 => wasmtime::Val::I64(value.as_i64().ok_or_else(mismatch)?),
+        wasmtime::ValType::F32 => wasmtime::Val::F32((value.as_f64().ok_or_else(mismatch)? as f32).to_bits()),
+        wasmtime::ValType::F64 => wasmtime::Val::F64(value.as_f64().ok_or_else(mismatch)?.to_bits()),
+        _ => return Err(mismatch()),
+    })
+}
+
+fn from_val(value: &wasmtime::Val) -> serde_json::Value {
+    match value {
+        wasmtime::Val::I32(v) => serde_json::json!(v),
+        wasmtime::Val::I64(v) => serde_json::json!(v),
+        wasmtime::Val::F32(bits) => serde_json::json!(f32::from_bits(*bits)),
+        wasmtime::Val::F64(bits) => serde_json::json!(f64::from_bits(*bits)),
+        _ => serde_json::Value::Null,
+    }
+}
+
+/// Call `function` in a custom node module under `limits`.
+///
+/// Host imports are not available here and trap if called. Running out of
+/// fuel or memory is reported in [`LimitedRun::error`] rather than as an
+/// `Err`, so callers can still record the call's statistics; `Err` is kept for
+/// modules that cannot be run at all.
+pub fn run_limited(
+    wasm_bytes: &[u8],
+    function: &str,
+    args: &[serde_json::Value],
+    limits: &ResourceLimits,
+) -> CanvasResult<LimitedRun> {
+    let mut engine_config = wasmtime::Config::new();
+    engine_config.consume_fuel(true);
+    let engine = wasmtime::Engine::new(&engine_config).map_err(|e| CanvasError::Wasm(e.to_string()))?;
+    let module = wasmtime::Module::new(&engine, wasm_bytes).map_err(|e| CanvasError::Wasm(e.to_string()))?;
+
+    let mut store = wasmtime::Store::new(
+        &engine,
+        Limited {
+            limits: wasmtime::StoreLimitsBuilder::new()
+                .memory_size(limits.memory_bytes)
+                .trap_on_grow_failure(true)
+                .build(),
+        },
+    );
+    store.limiter(|state| &mut state.limits);
+    store.set_fuel(limits.fuel).map_err(|e| CanvasError::Wasm(e.to_string()))?;
+
+    let mut linker = wasmtime::Linker::new(&engine);
+    linker
+        .define_unknown_imports_as_traps(&module)
+        .map_err(|e| CanvasError::Wasm(e.to_string()))?;
+
+    let started = Instant::now();
+    let outcome = linker.instantiate(&mut store, &module).and_then(|instance| {
+        let func = instance
+            .get_func(&mut store, function)
+            .ok_or_else(|| wasmtime::Error::msg(format!("Module does not export '{}'", function)))?;
+        let ty = func.ty(&store);
+        let params: Vec<wasmtime::ValType> = ty.params().collect();
+        if params.len() != args.len() {
+            return Err(wasmtime::Error::msg(format!(
+                "'{}' takes {} arguments, got {}",
+                function,
+                params.len(),
+                args.len()
+            )));
+        }
+        let params = args
+            .iter()
+            .zip(&params)
+            .map(|(arg, ty)| to_val(arg, ty))
+            .collect::<CanvasResult<Vec<_>>>()?;
+        let mut results = vec![wasmtime::Val::I32(0); ty.results().len()];
+        func.call(&mut store, &params, &mut results)?;
+        let memory = instance
+            .get_memory(&mut store, "memory")
+            .map_or(0, |memory| memory.data_size(&store));
+        Ok((results, memory))
+    });
+    let duration = started.elapsed();
+    let fuel_consumed = limits.fuel - store.get_fuel().unwrap_or(0);
+
+    Ok(match outcome {
+        Ok((results, memory_bytes)) => LimitedRun {
+            results: results.iter().map(from_val).collect(),
+            fuel_consumed,
+            memory_bytes,
+            duration,
+            error: None,
+        },
+        Err(e) => {
+            let error = match e.downcast_ref::<wasmtime::Trap>() {
+                Some(wasmtime::Trap::OutOfFuel) => format!("fuel budget of {} exhausted", limits.fuel),
+                _ if e.to_string().contains("memory") => {
+                    format!("memory limit of {} bytes exceeded: {}", limits.memory_bytes, e)
+                }
+                _ => e.to_string(),
+            };
+            LimitedRun {
+                results: Vec::new(),
+                fuel_consumed,
+                memory_bytes: 0,
+                duration,
+                error: Some(error),
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NODE: &str = r#"(module
+        (memory (export "memory") 1)
+        (func (export "double") (param i64) (result i64)
+            (i64.mul (local.get 0) (i64.const 2)))
+        (func (export "spin")
+            (loop $forever (br $forever)))
+        (func (export "grow") (result i32)
+            (memory.grow (i32.const 1000))))"#;
+
+    #[test]
+    fn test_call_within_budget() {
+        let run = run_limited(NODE.as_bytes(), "double", &[serde_json::json!(21)], &ResourceLimits::default()).unwrap();
+        assert!(run.error.is_none());
+        assert_eq!(run.results, vec![serde_json::json!(42)]);
+        assert!(run.fuel_consumed > 0);
+        assert_eq!(run.memory_bytes, WASM_PAGE_SIZE);
+    }
+
+    #[test]
+    fn test_runaway_calls_are_stopped() {
+        let limits = ResourceLimits {
+            fuel: 10_000,
+            memory_bytes: 4 * WASM_PAGE_SIZE,
+        };
+        let spin = run_limited(NODE.as_bytes(), "spin", &[], &limits).unwrap();
+        assert!(spin.error.unwrap().contains("fuel"));
+        assert_eq!(spin.fuel_consumed, limits.fuel);
+
+        let grow = run_limited(NODE.as_bytes(), "grow", &[], &limits).unwrap();
+        assert!(grow.error.is_some());
+
+        let mut stats = NodeExecutionStats::new("runaway");
+        stats.record(&spin);
+        stats.record(&grow);
+        assert_eq!(stats.invocations, 2);
+        assert_eq!(stats.failures, 2);
+    }
+}
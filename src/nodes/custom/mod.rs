@@ -1,13 +1,25 @@
 //! Custom node system for user-defined nodes
 
-use crate::{
-    error::{CanvasError, CanvasResult},
-    types::{Node, NodeId, NodeType},
-    wasm::WasmModule,
-};
+mod fixtures;
+mod limits;
+mod sandbox;
+mod versioning;
+
+use crate::error::{CanvasError, CanvasResult};
+use crate::nodes::{AssetRef, ThemeHints};
 
+use semver::Version;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
+use std::sync::Mutex;
+
+pub use fixtures::{check_outputs, NodeTestCase, NodeTestReport, NodeTestResult};
+pub use limits::{run_limited, LimitedRun, NodeExecutionStats, ResourceLimits, WASM_PAGE_SIZE};
+pub use sandbox::{check_module_imports, is_pure_import, module_imports, NodeCapability, HOST_IMPORT_MODULE};
+pub use versioning::{
+    resolve_node_versions, version_requirement, ChangelogEntry, NodeUpdate, VersionResolution,
+    VERSION_PROPERTY,
+};
 
 /// Custom node definition
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -21,6 +33,48 @@ pub struct CustomNodeDefinition {
     pub properties: Vec<CustomNodeProperty>,
     pub wasm_module: Option<WasmModuleInfo>,
     pub implementation: CustomNodeImplementation,
+    /// Semantic version of this node definition
+    #[serde(default = "default_node_version")]
+    pub version: Version,
+    /// Release notes, newest first
+    #[serde(default)]
+    pub changelog: Vec<ChangelogEntry>,
+    /// Host capabilities the node's WASM module needs
+    #[serde(default)]
+    pub capabilities: Vec<NodeCapability>,
+    /// Test cases shipped with the node
+    #[serde(default)]
+    pub tests: Vec<NodeTestCase>,
+    /// SPDX license expression the node is distributed under
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub license: Option<String>,
+    /// Icon shown in the editor palette and on the node
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub icon: Option<AssetRef>,
+    /// Colors the editor draws the node with
+    #[serde(default, skip_serializing_if = "ThemeHints::is_empty")]
+    pub theme: ThemeHints,
+}
+
+impl CustomNodeDefinition {
+    /// Point the node's WASM module, if it has one, at `path`
+    pub fn set_module_path(&mut self, path: &str) {
+        if let Some(info) = &mut self.wasm_module {
+            info.module_path = path.to_string();
+        }
+        if let CustomNodeImplementation::Wasm { module_info, .. } = &mut self.implementation {
+            module_info.module_path = path.to_string();
+        }
+    }
+}
+
+/// Directory under the data directory holding installed custom nodes
+pub const CUSTOM_NODES_DIR: &str = "nodes";
+/// File in the custom nodes directory recording granted capabilities
+pub const GRANTS_FILE: &str = "grants.json";
+
+fn default_node_version() -> Version {
+    Version::new(0, 1, 0)
 }
 
 /// Custom node port
@@ -72,7 +126,11 @@ pub enum CustomNodeImplementation {
 /// Custom node registry
 pub struct CustomNodeRegistry {
     nodes: HashMap<String, CustomNodeDefinition>,
-    wasm_modules: HashMap<String, WasmModule>,
+    wasm_modules: HashMap<String, Vec<u8>>,
+    grants: HashMap<String, BTreeSet<NodeCapability>>,
+    default_limits: ResourceLimits,
+    limits: HashMap<String, ResourceLimits>,
+    stats: Mutex<HashMap<String, NodeExecutionStats>>,
 }
 
 impl CustomNodeRegistry {
@@ -81,17 +139,52 @@ impl CustomNodeRegistry {
         Self {
             nodes: HashMap::new(),
             wasm_modules: HashMap::new(),
+            grants: HashMap::new(),
+            default_limits: ResourceLimits::default(),
+            limits: HashMap::new(),
+            stats: Mutex::new(HashMap::new()),
         }
     }
 
+    /// Set the budgets applied to nodes without their own limits
+    pub fn with_default_limits(mut self, limits: ResourceLimits) -> Self {
+        self.default_limits = limits;
+        self
+    }
+
+    /// Override the budgets of one node
+    pub fn set_limits(&mut self, node_id: &str, limits: ResourceLimits) {
+        self.limits.insert(node_id.to_string(), limits);
+    }
+
+    /// Budgets applied to a node's WASM calls
+    pub fn limits_for(&self, node_id: &str) -> ResourceLimits {
+        self.limits.get(node_id).copied().unwrap_or(self.default_limits)
+    }
+
+    /// Execution statistics of every custom node that has run, sorted by node ID
+    pub fn execution_stats(&self) -> Vec<NodeExecutionStats> {
+        let stats = self.stats.lock().unwrap_or_else(|e| e.into_inner());
+        let mut stats: Vec<NodeExecutionStats> = stats.values().cloned().collect();
+        stats.sort_by(|a, b| a.node_id.cmp(&b.node_id));
+        stats
+    }
+
+    /// Clear accumulated execution statistics
+    pub fn reset_stats(&self) {
+        self.stats.lock().unwrap_or_else(|e| e.into_inner()).clear();
+    }
+
     /// Register a custom node
     pub fn register_node(&mut self, definition: CustomNodeDefinition) -> CanvasResult<()> {
         // Validate the node definition
         self.validate_node_definition(&definition)?;
         
-        // Load WASM module if specified
+        // Load WASM module if specified, restricted to the capabilities granted to it
         if let Some(wasm_info) = &definition.wasm_module {
+            let allowed = self.allowed_capabilities(&definition)?;
             let wasm_module = self.load_wasm_module(wasm_info)?;
+            check_module_imports(&wasm_module, &allowed)?;
             self.wasm_modules.insert(definition.id.clone(), wasm_module);
         }
         
@@ -99,11 +192,81 @@ impl CustomNodeRegistry {
         Ok(())
     }
 
+    /// Grant host capabilities to a custom node; must happen before it is registered
+    pub fn grant_capabilities(&mut self, node_id: &str, capabilities: impl IntoIterator<Item = NodeCapability>) {
+        self.grants.entry(node_id.to_string()).or_default().extend(capabilities);
+    }
+
+    /// Capabilities granted to a custom node
+    pub fn granted_capabilities(&self, node_id: &str) -> BTreeSet<NodeCapability> {
+        self.grants.get(node_id).cloned().unwrap_or_default()
+    }
+
+    /// Capabilities a node requests, provided all of them have been granted
+    fn allowed_capabilities(&self, definition: &CustomNodeDefinition) -> CanvasResult<BTreeSet<NodeCapability>> {
+        let granted = self.granted_capabilities(&definition.id);
+        let missing: Vec<String> = definition
+            .capabilities
+            .iter()
+            .filter(|capability| !granted.contains(capability))
+            .map(|capability| format!("{:?}", capability))
+            .collect();
+        if !missing.is_empty() {
+            return Err(CanvasError::PermissionDenied(format!(
+                "Node '{}' requests capabilities that were not granted: {}",
+                definition.id,
+                missing.join(", ")
+            )));
+        }
+        Ok(definition.capabilities.iter().copied().collect())
+    }
+
     /// Get a custom node definition
     pub fn get_node(&self, node_id: &str) -> Option<&CustomNodeDefinition> {
         self.nodes.get(node_id)
     }
 
+    /// Installed version of a custom node
+    pub fn installed_version(&self, node_id: &str) -> Option<&Version> {
+        self.nodes.get(node_id).map(|definition| &definition.version)
+    }
+
+    /// Replace an installed node with a newer version of it
+    pub fn upgrade_node(&mut self, definition: CustomNodeDefinition) -> CanvasResult<()> {
+        let installed = self.installed_version(&definition.id)
+            .ok_or_else(|| CanvasError::NodeNotFound(definition.id.clone()))?;
+        if definition.version <= *installed {
+            return Err(CanvasError::Validation(format!(
+                "Node '{}' {} is not newer than the installed {}",
+                definition.id, definition.version, installed
+            )));
+        }
+
+        let previous = self.nodes.remove(&definition.id);
+        self.wasm_modules.remove(&definition.id);
+        if let Err(e) = self.register_node(definition) {
+            // Keep the working version if the new one fails to load
+            if let Some(previous) = previous {
+                self.register_node(previous)?;
+            }
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    /// Installed nodes for which `latest` has a newer version
+    pub fn check_updates(&self, latest: &[CustomNodeDefinition]) -> Vec<NodeUpdate> {
+        let mut updates: Vec<NodeUpdate> = latest
+            .iter()
+            .filter_map(|available| {
+                let installed = self.nodes.get(&available.id)?;
+                NodeUpdate::between(installed, available)
+            })
+            .collect();
+        updates.sort_by(|a, b| a.node_id.cmp(&b.node_id));
+        updates
+    }
+
     /// List all custom nodes
     pub fn list_nodes(&self) -> Vec<&CustomNodeDefinition> {
         self.nodes.values().collect()
@@ -119,6 +282,66 @@ impl CustomNodeRegistry {
         }
     }
 
+    /// Load every node definition (`*.json`) installed in a directory.
+    ///
+    /// Capabilities granted at install time are read from the directory's
+    /// grants file, and relative module paths are resolved against it.
+    pub fn load_dir(dir: &std::path::Path) -> CanvasResult<Self> {
+        let mut registry = Self::new();
+
+        let grants_path = dir.join(GRANTS_FILE);
+        if grants_path.exists() {
+            let grants: HashMap<String, BTreeSet<NodeCapability>> =
+                serde_json::from_str(&std::fs::read_to_string(&grants_path)?)?;
+            registry.grants = grants;
+        }
+
+        let mut paths: Vec<std::path::PathBuf> = std::fs::read_dir(dir)?
+            .map(|entry| entry.map(|e| e.path()))
+            .collect::<Result<_, _>>()?;
+        paths.sort();
+        for path in paths {
+            if path.extension().and_then(|e| e.to_str()) != Some("json") || path.ends_with(GRANTS_FILE) {
+                continue;
+            }
+            let mut definition: CustomNodeDefinition = serde_json::from_str(&std::fs::read_to_string(&path)?)?;
+            if let Some(info) = &mut definition.wasm_module {
+                if std::path::Path::new(&info.module_path).is_relative() {
+                    info.module_path = dir.join(&info.module_path).to_string_lossy().to_string();
+                }
+            }
+            registry.register_node(definition)?;
+        }
+
+        Ok(registry)
+    }
+
+    /// Run a node's embedded test cases
+    pub fn run_node_tests(&self, node_id: &str) -> CanvasResult<NodeTestReport> {
+        let definition = self.nodes.get(node_id)
+            .ok_or_else(|| CanvasError::NodeNotFound(node_id.to_string()))?;
+
+        let results = definition
+            .tests
+            .iter()
+            .map(|case| {
+                let failure = match self.execute_node(node_id, case.inputs.clone(), case.properties.clone()) {
+                    Ok(outputs) => check_outputs(case, &outputs),
+                    Err(e) => Some(e.to_string()),
+                };
+                NodeTestResult {
+                    name: case.name.clone(),
+                    failure,
+                }
+            })
+            .collect();
+
+        Ok(NodeTestReport {
+            node_id: node_id.to_string(),
+            results,
+        })
+    }
+
     /// Execute a custom node
     pub fn execute_node(
         &self,
@@ -146,7 +369,7 @@ impl CustomNodeRegistry {
     fn validate_node_definition(&self, definition: &CustomNodeDefinition) -> CanvasResult<()> {
         // Check for duplicate IDs
         if self.nodes.contains_key(&definition.id) {
-            return Err(CanvasError::ValidationError(
+            return Err(CanvasError::Validation(
                 format!("Node with ID '{}' already exists", definition.id)
             ));
         }
@@ -154,7 +377,7 @@ impl CustomNodeRegistry {
         // Validate inputs
         for input in &definition.inputs {
             if input.name.is_empty() {
-                return Err(CanvasError::ValidationError(
+                return Err(CanvasError::Validation(
                     "Input name cannot be empty".to_string()
                 ));
             }
@@ -163,7 +386,7 @@ impl CustomNodeRegistry {
         // Validate outputs
         for output in &definition.outputs {
             if output.name.is_empty() {
-                return Err(CanvasError::ValidationError(
+                return Err(CanvasError::Validation(
                     "Output name cannot be empty".to_string()
                 ));
             }
@@ -172,20 +395,20 @@ impl CustomNodeRegistry {
         // Validate properties
         for property in &definition.properties {
             if property.name.is_empty() {
-                return Err(CanvasError::ValidationError(
+                return Err(CanvasError::Validation(
                     "Property name cannot be empty".to_string()
                 ));
             }
         }
 
+        definition.theme.validate()?;
+
         Ok(())
     }
 
     /// Load WASM module
-    fn load_wasm_module(&self, wasm_info: &WasmModuleInfo) -> CanvasResult<WasmModule> {
-        // TODO: Implement WASM module loading
-        // For now, return a placeholder
-        Ok(WasmModule::new(&wasm_info.module_path)?)
+    fn load_wasm_module(&self, wasm_info: &WasmModuleInfo) -> CanvasResult<Vec<u8>> {
+        Ok(std::fs::read(&wasm_info.module_path)?)
     }
 
     /// Execute composite node
@@ -224,23 +447,39 @@ impl CustomNodeRegistry {
         module_info: &WasmModuleInfo,
     ) -> CanvasResult<HashMap<String, serde_json::Value>> {
         let wasm_module = self.wasm_modules.get(&definition.id)
-            .ok_or_else(|| CanvasError::WasmError("WASM module not loaded".to_string()))?;
+            .ok_or_else(|| CanvasError::Wasm("WASM module not loaded".to_string()))?;
 
-        // TODO: Implement WASM function execution
-        // This would involve:
-        // 1. Converting inputs to WASM-compatible format
-        // 2. Calling the WASM function
-        // 3. Converting outputs back to JSON format
-        
         log::info!("Executing WASM node: {} with function: {}", definition.name, function_name);
-        
-        // Placeholder implementation
-        let mut outputs = HashMap::new();
-        for output in &definition.outputs {
-            outputs.insert(output.name.clone(), serde_json::Value::Null);
+
+        // Inputs are passed positionally in port order; missing optional inputs become 0
+        let args: Vec<serde_json::Value> = definition
+            .inputs
+            .iter()
+            .map(|port| inputs.get(&port.name).cloned().unwrap_or(serde_json::json!(0)))
+            .collect();
+
+        let limits = self.limits_for(&definition.id);
+        let run = run_limited(wasm_module, function_name, &args, &limits)?;
+        self.stats
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .entry(definition.id.clone())
+            .or_insert_with(|| NodeExecutionStats::new(definition.id.clone()))
+            .record(&run);
+
+        if let Some(error) = run.error {
+            return Err(CanvasError::ExecutionError(format!(
+                "Custom node '{}' aborted: {}",
+                definition.id, error
+            )));
         }
-        
-        Ok(outputs)
+
+        Ok(definition
+            .outputs
+            .iter()
+            .zip(run.results)
+            .map(|(port, value)| (port.name.clone(), value))
+            .collect())
     }
 
     /// Execute script-based node
@@ -292,6 +531,13 @@ impl CustomNodeBuilder {
                 implementation: CustomNodeImplementation::Composite {
                     sub_graph: String::new(),
                 },
+                version: default_node_version(),
+                changelog: Vec::new(),
+                capabilities: Vec::new(),
+                tests: Vec::new(),
+                license: None,
+                icon: None,
+                theme: ThemeHints::default(),
             },
         }
     }
@@ -349,6 +595,50 @@ impl CustomNodeBuilder {
         self
     }
 
+    /// Set the node version
+    pub fn version(mut self, version: Version) -> Self {
+        self.definition.version = version;
+        self
+    }
+
+    /// Add release notes for a version
+    pub fn changelog_entry(mut self, version: Version, notes: String) -> Self {
+        self.definition.changelog.insert(0, ChangelogEntry { version, notes });
+        self
+    }
+
+    /// Request a host capability for the node's WASM module
+    pub fn capability(mut self, capability: NodeCapability) -> Self {
+        if !self.definition.capabilities.contains(&capability) {
+            self.definition.capabilities.push(capability);
+        }
+        self
+    }
+
+    /// Set the SPDX license expression the node is distributed under
+    pub fn license(mut self, license: String) -> Self {
+        self.definition.license = Some(license);
+        self
+    }
+
+    /// Set the icon, an asset already in the store
+    pub fn icon(mut self, icon: AssetRef) -> Self {
+        self.definition.icon = Some(icon);
+        self
+    }
+
+    /// Set the editor theme hints
+    pub fn theme(mut self, theme: ThemeHints) -> Self {
+        self.definition.theme = theme;
+        self
+    }
+
+    /// Add an embedded test case
+    pub fn test_case(mut self, case: NodeTestCase) -> Self {
+        self.definition.tests.push(case);
+        self
+    }
+
     /// Set as composite node
     pub fn composite(mut self, sub_graph: String) -> Self {
         self.definition.implementation = CustomNodeImplementation::Composite { sub_graph };
@@ -421,4 +711,95 @@ mod tests {
         assert!(registry.register_node(definition1).is_ok());
         assert!(registry.register_node(definition2).is_err());
     }
+
+    #[test]
+    fn test_wasm_node_needs_granted_capabilities() {
+        let module = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(
+            module.path(),
+            r#"(module (import "env" "baals_read_storage" (func (param i32 i32 i32) (result i32))))"#,
+        )
+        .unwrap();
+        let definition = CustomNodeBuilder::new("balance-reader".to_string(), "Balance Reader".to_string())
+            .capability(NodeCapability::ReadStorage)
+            .wasm(
+                "read".to_string(),
+                WasmModuleInfo {
+                    module_path: module.path().to_string_lossy().to_string(),
+                    exported_functions: vec!["read".to_string()],
+                    abi: String::new(),
+                },
+            )
+            .build();
+
+        let mut registry = CustomNodeRegistry::new();
+        assert!(registry.register_node(definition.clone()).is_err());
+        registry.grant_capabilities("balance-reader", [NodeCapability::ReadStorage]);
+        assert!(registry.register_node(definition).is_ok());
+    }
+
+    #[test]
+    fn test_embedded_tests_run_through_registry() {
+        let module = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(
+            module.path(),
+            r#"(module (func (export "add") (param i64 i64) (result i64) (i64.add (local.get 0) (local.get 1))))"#,
+        )
+        .unwrap();
+        let case = |name: &str, a: i64, b: i64, sum: i64| NodeTestCase {
+            name: name.to_string(),
+            inputs: [("a".to_string(), serde_json::json!(a)), ("b".to_string(), serde_json::json!(b))].into(),
+            properties: HashMap::new(),
+            expected_outputs: [("sum".to_string(), serde_json::json!(sum))].into(),
+        };
+        let definition = CustomNodeBuilder::new("adder".to_string(), "Adder".to_string())
+            .input("a".to_string(), "number".to_string(), true, String::new())
+            .input("b".to_string(), "number".to_string(), true, String::new())
+            .output("sum".to_string(), "number".to_string(), String::new())
+            .test_case(case("adds", 2, 3, 5))
+            .test_case(case("wrong", 2, 2, 5))
+            .wasm(
+                "add".to_string(),
+                WasmModuleInfo {
+                    module_path: module.path().to_string_lossy().to_string(),
+                    exported_functions: vec!["add".to_string()],
+                    abi: String::new(),
+                },
+            )
+            .build();
+
+        let mut registry = CustomNodeRegistry::new();
+        registry.register_node(definition).unwrap();
+        let report = registry.run_node_tests("adder").unwrap();
+        assert_eq!(report.results.len(), 2);
+        assert!(report.results[0].passed());
+        assert_eq!(report.failures()[0].name, "wrong");
+        assert!(!report.passed());
+    }
+
+    #[test]
+    fn test_update_detection_and_upgrade() {
+        let mut registry = CustomNodeRegistry::new();
+        let node = |version: Version| {
+            CustomNodeBuilder::new("token-math".to_string(), "Token Math".to_string())
+                .version(version)
+                .composite("{}".to_string())
+        };
+        registry.register_node(node(Version::new(1, 0, 0)).build()).unwrap();
+
+        let latest = node(Version::new(1, 2, 0))
+            .changelog_entry(Version::new(1, 1, 0), "Adds rounding modes".to_string())
+            .changelog_entry(Version::new(1, 2, 0), "Faster division".to_string())
+            .build();
+        let updates = registry.check_updates(&[latest.clone()]);
+        assert_eq!(updates.len(), 1);
+        assert_eq!(updates[0].available, Version::new(1, 2, 0));
+        assert_eq!(updates[0].changelog.len(), 2);
+        assert!(!updates[0].breaking);
+
+        registry.upgrade_node(latest.clone()).unwrap();
+        assert_eq!(registry.installed_version("token-math"), Some(&Version::new(1, 2, 0)));
+        assert!(registry.check_updates(&[latest.clone()]).is_empty());
+        assert!(registry.upgrade_node(latest).is_err());
+    }
 } 
\ No newline at end of file
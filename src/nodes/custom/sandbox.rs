@@ -0,0 +1,176 @@
+//! Import sandbox for WASM-backed custom nodes
+//!
+//! Third-party node modules get pure compute by default. Host functions that
+//! touch contract state or the outside world are grouped into capabilities; a
+//! node declares the capabilities it needs, the user grants them at install
+//! time, and a module importing anything outside that surface is refused when
+//! it is loaded.
+
+use std::collections::BTreeSet;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::{CanvasError, CanvasResult},
+    wasm::host,
+};
+
+/// Module name custom nodes import host functions from
+pub const HOST_IMPORT_MODULE: &str = "env";
+/// Prefix of the collection helpers, which are pure compute
+const COLLECTION_IMPORT_PREFIX: &str = "canvas_collection_";
+
+/// Host imports any custom node may use
+const PURE_IMPORTS: &[&str] = &[
+    host::HOST_HASH,
+    host::HOST_DECIMAL_MUL,
+    host::HOST_DECIMAL_DIV,
+    host::HOST_REVERT,
+];
+
+/// A group of host functions a custom node must be granted before it may import them
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NodeCapability {
+    /// Read contract storage
+    ReadStorage,
+    /// Write contract storage
+    WriteStorage,
+    /// Emit contract events
+    EmitEvents,
+    /// Verify signatures
+    VerifySignatures,
+    /// Inspect the calling account
+    CallerIdentity,
+    /// Call other contracts
+    ExternalCalls,
+}
+
+impl NodeCapability {
+    /// Every capability, in display order
+    pub const ALL: [NodeCapability; 6] = [
+        NodeCapability::ReadStorage,
+        NodeCapability::WriteStorage,
+        NodeCapability::EmitEvents,
+        NodeCapability::VerifySignatures,
+        NodeCapability::CallerIdentity,
+        NodeCapability::ExternalCalls,
+    ];
+
+    /// Host imports unlocked by this capability
+    pub fn imports(&self) -> &'static [&'static str] {
+        match self {
+            NodeCapability::ReadStorage => &[host::HOST_READ_STORAGE, host::HOST_BATCH_READ_STORAGE],
+            NodeCapability::WriteStorage => &[host::HOST_WRITE_STORAGE, host::HOST_BATCH_WRITE_STORAGE],
+            NodeCapability::EmitEvents => &[host::HOST_EMIT_EVENT],
+            NodeCapability::VerifySignatures => &[host::HOST_VERIFY_SIGNATURE],
+            NodeCapability::CallerIdentity => &[host::HOST_CALLER, host::HOST_CALLER_IS],
+            NodeCapability::ExternalCalls => &[
+                host::HOST_CALL_CONTRACT,
+                host::HOST_CALL_ERROR_IS,
+                host::HOST_CALL_RETHROW,
+            ],
+        }
+    }
+
+    /// Capability that unlocks a host import, if any
+    pub fn for_import(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|capability| capability.imports().contains(&name))
+    }
+
+    /// Short description shown in marketplace listings and install prompts
+    pub fn description(&self) -> &'static str {
+        match self {
+            NodeCapability::ReadStorage => "Read contract storage",
+            NodeCapability::WriteStorage => "Modify contract storage",
+            NodeCapability::EmitEvents => "Emit contract events",
+            NodeCapability::VerifySignatures => "Verify signatures",
+            NodeCapability::CallerIdentity => "See who is calling the contract",
+            NodeCapability::ExternalCalls => "Call other contracts",
+        }
+    }
+}
+
+/// Whether a host import needs no capability
+pub fn is_pure_import(name: &str) -> bool {
+    PURE_IMPORTS.contains(&name) || name.starts_with(COLLECTION_IMPORT_PREFIX)
+}
+
+/// Function imports of a WASM (or WAT) module, as `(module, name)` pairs.
+///
+/// Fails if the module imports anything other than functions; custom nodes
+/// must define their own memory and tables.
+pub fn module_imports(wasm_bytes: &[u8]) -> CanvasResult<Vec<(String, String)>> {
+    let engine = wasmtime::Engine::default();
+    let module = wasmtime::Module::new(&engine, wasm_bytes)
+        .map_err(|e| CanvasError::Wasm(format!("Invalid custom node module: {}", e)))?;
+
+    module
+        .imports()
+        .map(|import| match import.ty() {
+            wasmtime::ExternType::Func(_) => Ok((import.module().to_string(), import.name().to_string())),
+            _ => Err(CanvasError::PermissionDenied(format!(
+                "Custom node modules may only import functions, found '{}.{}'",
+                import.module(),
+                import.name()
+            ))),
+        })
+        .collect()
+}
+
+/// Check a module's imports against the pure surface plus the granted capabilities
+pub fn check_module_imports(wasm_bytes: &[u8], granted: &BTreeSet<NodeCapability>) -> CanvasResult<()> {
+    let mut denied = Vec::new();
+    for (module, name) in module_imports(wasm_bytes)? {
+        if module != HOST_IMPORT_MODULE {
+            denied.push(format!("{}.{} (unknown module)", module, name));
+            continue;
+        }
+        if is_pure_import(&name) {
+            continue;
+        }
+        match NodeCapability::for_import(&name) {
+            Some(capability) if granted.contains(&capability) => {}
+            Some(capability) => denied.push(format!("{} (needs {:?})", name, capability)),
+            None => denied.push(format!("{} (not available to custom nodes)", name)),
+        }
+    }
+
+    if denied.is_empty() {
+        Ok(())
+    } else {
+        Err(CanvasError::PermissionDenied(format!(
+            "Custom node module imports host functions outside its sandbox: {}",
+            denied.join(", ")
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STORAGE_NODE: &str = r#"(module
+        (import "env" "baals_hash" (func (param i32 i32 i32 i32) (result i32)))
+        (import "env" "baals_write_storage" (func (param i32 i32 i32 i32))))"#;
+
+    #[test]
+    fn test_imports_need_granted_capability() {
+        let none = BTreeSet::new();
+        let error = check_module_imports(STORAGE_NODE.as_bytes(), &none).unwrap_err().to_string();
+        assert!(error.contains("WriteStorage"));
+
+        let granted: BTreeSet<NodeCapability> = [NodeCapability::WriteStorage].into_iter().collect();
+        assert!(check_module_imports(STORAGE_NODE.as_bytes(), &granted).is_ok());
+    }
+
+    #[test]
+    fn test_unknown_imports_are_refused() {
+        let all: BTreeSet<NodeCapability> = NodeCapability::ALL.into_iter().collect();
+        let wasi = r#"(module (import "wasi_snapshot_preview1" "fd_write" (func (param i32 i32 i32 i32) (result i32))))"#;
+        assert!(check_module_imports(wasi.as_bytes(), &all).is_err());
+
+        let memory = r#"(module (import "env" "memory" (memory 1)))"#;
+        assert!(check_module_imports(memory.as_bytes(), &all).is_err());
+    }
+}
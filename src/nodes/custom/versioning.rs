@@ -0,0 +1,185 @@
+//! Custom node versions
+//!
+//! Every custom node definition carries a semantic version. A graph node using
+//! a custom node pins a compatible range in its `version` property (e.g.
+//! `^1.2`); before building, [`resolve_node_versions`] checks each pin against
+//! the version installed in the registry.
+
+use std::collections::{BTreeMap, HashMap};
+
+use semver::{Version, VersionReq};
+use serde::{Deserialize, Serialize};
+
+use super::{CustomNodeDefinition, CustomNodeRegistry};
+use crate::{
+    error::{CanvasError, CanvasResult},
+    types::{NodeId, VisualGraph, VisualNode},
+};
+
+/// Node property holding the version range a graph node was built against
+pub const VERSION_PROPERTY: &str = "version";
+
+/// Release notes for one version of a custom node
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChangelogEntry {
+    pub version: Version,
+    pub notes: String,
+}
+
+/// A newer version of an installed custom node
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NodeUpdate {
+    pub node_id: String,
+    pub installed: Version,
+    pub available: Version,
+    /// Whether graphs pinned to the installed major version would stop resolving
+    pub breaking: bool,
+    /// Changelog entries after the installed version, newest first
+    pub changelog: Vec<ChangelogEntry>,
+}
+
+impl NodeUpdate {
+    /// The update from `installed` to `available`, if `available` is newer
+    pub fn between(installed: &CustomNodeDefinition, available: &CustomNodeDefinition) -> Option<Self> {
+        if available.version <= installed.version {
+            return None;
+        }
+        let mut changelog: Vec<ChangelogEntry> = available
+            .changelog
+            .iter()
+            .filter(|entry| entry.version > installed.version && entry.version <= available.version)
+            .cloned()
+            .collect();
+        changelog.sort_by(|a, b| b.version.cmp(&a.version));
+
+        Some(Self {
+            node_id: available.id.clone(),
+            installed: installed.version.clone(),
+            available: available.version.clone(),
+            breaking: Self::is_breaking(&installed.version, &available.version),
+            changelog,
+        })
+    }
+
+    /// Whether moving from `installed` to `available` leaves the installed major version
+    pub fn is_breaking(installed: &Version, available: &Version) -> bool {
+        !VersionReq::parse(&format!("^{}", installed)).map_or(false, |req| req.matches(available))
+    }
+}
+
+/// Outcome of resolving a graph's custom node pins
+#[derive(Debug, Clone, Default)]
+pub struct VersionResolution {
+    /// Version used for each custom node type in the graph
+    pub resolved: BTreeMap<String, Version>,
+    /// Graph nodes using a custom node without pinning a version
+    pub unpinned: Vec<NodeId>,
+}
+
+/// Version range pinned by a graph node, if any
+pub fn version_requirement(node: &VisualNode) -> CanvasResult<Option<VersionReq>> {
+    match node.properties.get(VERSION_PROPERTY) {
+        None => Ok(None),
+        Some(value) => {
+            let text = value.as_str().ok_or_else(|| {
+                CanvasError::Validation(format!("Node {} has a non-string '{}' property", node.id, VERSION_PROPERTY))
+            })?;
+            VersionReq::parse(text).map(Some).map_err(|e| {
+                CanvasError::Validation(format!("Node {} has an invalid version range '{}': {}", node.id, text, e))
+            })
+        }
+    }
+}
+
+/// Check that every custom node used by a graph is installed at a version its pins accept.
+///
+/// All errors are collected so one build reports every incompatible pin.
+pub fn resolve_node_versions(graph: &VisualGraph, registry: &CustomNodeRegistry) -> CanvasResult<VersionResolution> {
+    let mut resolution = VersionResolution::default();
+    let mut problems = Vec::new();
+    let mut pins: HashMap<&str, Vec<(NodeId, VersionReq)>> = HashMap::new();
+
+    for node in &graph.nodes {
+        let Some(installed) = registry.installed_version(&node.node_type) else {
+            continue;
+        };
+        resolution.resolved.insert(node.node_type.clone(), installed.clone());
+        match version_requirement(node) {
+            Ok(Some(req)) => pins.entry(node.node_type.as_str()).or_default().push((node.id, req)),
+            Ok(None) => resolution.unpinned.push(node.id),
+            Err(e) => problems.push(e.to_string()),
+        }
+    }
+
+    for (node_type, requirements) in &pins {
+        let installed = &resolution.resolved[*node_type];
+        for (node_id, req) in requirements {
+            if !req.matches(installed) {
+                problems.push(format!(
+                    "Node {} requires '{}' {}, but {} is installed",
+                    node_id, node_type, req, installed
+                ));
+            }
+        }
+    }
+
+    if problems.is_empty() {
+        Ok(resolution)
+    } else {
+        problems.sort();
+        Err(CanvasError::Validation(format!(
+            "Custom node versions do not resolve:\n  {}",
+            problems.join("\n  ")
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nodes::custom::CustomNodeBuilder;
+    use crate::types::Position;
+
+    fn registry_with(version: Version) -> CustomNodeRegistry {
+        let mut registry = CustomNodeRegistry::new();
+        registry
+            .register_node(
+                CustomNodeBuilder::new("vesting".to_string(), "Vesting".to_string())
+                    .version(version)
+                    .composite("{}".to_string())
+                    .build(),
+            )
+            .unwrap();
+        registry
+    }
+
+    fn graph_pinning(pins: &[Option<&str>]) -> VisualGraph {
+        let mut graph = VisualGraph::new("schedule");
+        for pin in pins {
+            let mut node = VisualNode::new(uuid::Uuid::new_v4(), "vesting", Position::new(0.0, 0.0));
+            if let Some(pin) = pin {
+                node = node.with_property(VERSION_PROPERTY, serde_json::json!(pin));
+            }
+            graph.add_node(node);
+        }
+        graph
+    }
+
+    #[test]
+    fn test_compatible_pins_resolve() {
+        let registry = registry_with(Version::new(1, 4, 2));
+        let resolution = resolve_node_versions(&graph_pinning(&[Some("^1.2"), None]), &registry).unwrap();
+        assert_eq!(resolution.resolved["vesting"], Version::new(1, 4, 2));
+        assert_eq!(resolution.unpinned.len(), 1);
+    }
+
+    #[test]
+    fn test_incompatible_pin_is_reported() {
+        let registry = registry_with(Version::new(2, 0, 0));
+        let error = resolve_node_versions(&graph_pinning(&[Some("^1.2")]), &registry)
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("2.0.0 is installed"));
+        assert!(resolve_node_versions(&graph_pinning(&[Some("not a range")]), &registry).is_err());
+    }
+}
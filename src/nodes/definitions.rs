@@ -24,6 +24,29 @@ pub struct NodeDefinition {
     pub compiler_hint: CompilerHint,
     /// Visual properties
     pub visual: VisualProperties,
+    /// Set when the node type is deprecated
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deprecation: Option<Deprecation>,
+}
+
+/// Deprecation metadata, including how to rewrite a node to its replacement
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Deprecation {
+    /// Version in which the node type was deprecated
+    pub since: String,
+    /// Node type that replaces this one
+    pub replacement: Option<String>,
+    /// Why the node was deprecated
+    pub note: String,
+    /// Old port ID -> new port ID
+    #[serde(default)]
+    pub port_renames: std::collections::HashMap<String, String>,
+    /// Old property name -> new property name
+    #[serde(default)]
+    pub property_renames: std::collections::HashMap<String, String>,
+    /// Properties set on the replacement node when missing
+    #[serde(default)]
+    pub default_properties: std::collections::HashMap<String, serde_json::Value>,
 }
 
 /// Compiler hints for code generation
@@ -80,9 +103,16 @@ impl NodeDefinition {
                 color: "#4A90E2".to_string(),
                 icon: None,
             },
+            deprecation: None,
         }
     }
 
+    /// Mark the node type as deprecated
+    pub fn with_deprecation(mut self, deprecation: Deprecation) -> Self {
+        self.deprecation = Some(deprecation);
+        self
+    }
+
     /// Add an input port
     pub fn with_input(mut self, port: Port) -> Self {
         self.inputs.push(port);
@@ -122,23 +152,106 @@ pub fn builtin_node_definitions() -> Vec<NodeDefinition> {
         create_and_node(),
         create_or_node(),
         create_not_node(),
+        create_compare_integers_node(),
+        create_require_node(),
+        
+        // Event nodes
+        create_emit_event_node(),
         
         // State nodes
         create_read_storage_node(),
         create_write_storage_node(),
+        create_batch_read_storage_node(),
+        create_batch_write_storage_node(),
+        
+        // Collection nodes
+        create_array_get_node(),
+        create_array_set_node(),
+        create_array_push_node(),
+        create_array_remove_node(),
+        create_map_get_node(),
+        create_map_set_node(),
+        create_map_remove_node(),
+        create_length_node(),
+        create_for_each_node(),
+        
+        // String and bytes nodes
+        create_concat_node(),
+        create_slice_node(),
+        create_hash_node(),
+        create_hex_encode_node(),
+        create_hex_decode_node(),
+        create_compare_node(),
+        
+        // Cryptographic nodes
+        create_verify_signature_node(),
         
         // Arithmetic nodes
         create_add_node(),
         create_subtract_node(),
         create_multiply_node(),
         create_divide_node(),
+        create_cast_unit_node(),
         
         // Control flow nodes
         create_start_node(),
         create_end_node(),
+        create_init_node(),
+        create_import_node(),
+        create_slot_node(),
+        create_try_call_node(),
+        create_catch_node(),
     ]
 }
 
+/// Node types that are no longer offered but are still understood when loading old graphs
+pub fn deprecated_node_definitions() -> Vec<NodeDefinition> {
+    vec![
+        create_legacy_hash_node("Sha256", "sha256"),
+        create_legacy_hash_node("Keccak256", "keccak256"),
+        create_assert_node(),
+    ]
+}
+
+fn create_legacy_hash_node(id: &str, algorithm: &str) -> NodeDefinition {
+    let mut default_properties = std::collections::HashMap::new();
+    default_properties.insert("algorithm".to_string(), serde_json::json!(algorithm));
+    let mut port_renames = std::collections::HashMap::new();
+    port_renames.insert("data".to_string(), "input".to_string());
+
+    NodeDefinition::new(id, id, format!("Hashes data with {}", algorithm), "Cryptographic")
+        .with_input(Port::new("data", "Data", ValueType::Any).required())
+        .with_output(Port::new("hash", "Hash", ValueType::Bytes))
+        .with_deprecation(Deprecation {
+            since: "0.1.0".to_string(),
+            replacement: Some("Hash".to_string()),
+            note: "Use the Hash node with its 'algorithm' property".to_string(),
+            port_renames,
+            property_renames: std::collections::HashMap::new(),
+            default_properties,
+        })
+}
+
+fn create_assert_node() -> NodeDefinition {
+    let mut property_renames = std::collections::HashMap::new();
+    property_renames.insert("reason".to_string(), "message".to_string());
+    let mut default_properties = std::collections::HashMap::new();
+    default_properties.insert("error".to_string(), serde_json::json!("AssertionFailed"));
+
+    NodeDefinition::new("Assert", "Assert", "Aborts execution unless the condition holds", "Logic")
+        .with_input(Port::new("condition", "Condition", ValueType::Boolean).required())
+        .with_input(Port::new("flow_in", "Flow In", ValueType::Flow).required())
+        .with_output(Port::new("flow_out", "Flow Out", ValueType::Flow))
+        .with_deprecation(Deprecation {
+            since: "0.1.0".to_string(),
+            replacement: Some("Require".to_string()),
+            note: "Require reports a structured revert reason".to_string(),
+            port_renames: std::collections::HashMap::new(),
+            property_renames,
+            default_properties,
+        })
+}
+
 fn create_if_node() -> NodeDefinition {
     NodeDefinition::new("If", "If Condition", "Executes different paths based on a boolean condition", "Logic")
         .with_input(Port::new("condition", "Condition", ValueType::Boolean).required())
@@ -207,10 +320,88 @@ fn create_not_node() -> NodeDefinition {
         })
 }
 
+fn create_compare_integers_node() -> NodeDefinition {
+    NodeDefinition::new("CompareIntegers", "Compare Integers", "Compares two integers", "Logic")
+        .with_input(Port::new("a", "A", ValueType::Integer).required())
+        .with_input(Port::new("b", "B", ValueType::Integer).required())
+        .with_output(Port::new("equal", "Equal", ValueType::Boolean))
+        .with_output(Port::new("ordering", "Ordering", ValueType::Integer))
+        .with_compiler_hint(CompilerHint {
+            operation_type: "compare_integers".to_string(),
+            expression_field: None,
+            gas_cost: Some(3),
+            optimizable: true,
+        })
+}
+
+fn create_require_node() -> NodeDefinition {
+    NodeDefinition::new("Require", "Require", "Aborts execution with a typed revert reason unless the condition holds", "Logic")
+        .with_input(Port::new("condition", "Condition", ValueType::Boolean).required())
+        .with_input(Port::new("flow_in", "Flow In", ValueType::Flow).required())
+        .with_input(Port::new("args", "Error Arguments", ValueType::Array(Box::new(ValueType::Any))))
+        .with_output(Port::new("flow_out", "Flow Out", ValueType::Flow))
+        .with_config_schema(serde_json::json!({
+            "type": "object",
+            "properties": {
+                "error": {
+                    "type": "string",
+                    "description": "Error name, exported in the contract ABI"
+                },
+                "message": {
+                    "type": "string",
+                    "description": "Human-readable revert message"
+                }
+            },
+            "required": ["error"]
+        }))
+        .with_compiler_hint(CompilerHint {
+            operation_type: "require".to_string(),
+            expression_field: Some("error".to_string()),
+            gas_cost: Some(10),
+            optimizable: false,
+        })
+        .with_visual(VisualProperties {
+            width: 100.0,
+            height: 60.0,
+            color: "#E74C3C".to_string(),
+            icon: Some("shield".to_string()),
+        })
+}
+
+fn create_emit_event_node() -> NodeDefinition {
+    NodeDefinition::new("EmitEvent", "Emit Event", "Emits a contract event; its data inputs are the event fields, in order", "Events")
+        .with_input(Port::new("flow_in", "Flow In", ValueType::Flow).required())
+        .with_output(Port::new("flow_out", "Flow Out", ValueType::Flow))
+        .with_config_schema(serde_json::json!({
+            "type": "object",
+            "properties": {
+                "event": {
+                    "type": "string",
+                    "description": "Event name, exported in the contract ABI"
+                }
+            },
+            "required": ["event"]
+        }))
+        .with_compiler_hint(CompilerHint {
+            operation_type: "emit_event".to_string(),
+            expression_field: Some("event".to_string()),
+            gas_cost: Some(20),
+            optimizable: false,
+        })
+        .with_visual(VisualProperties {
+            width: 120.0,
+            height: 60.0,
+            color: "#16A085".to_string(),
+            icon: Some("broadcast".to_string()),
+        })
+}
+
 fn create_read_storage_node() -> NodeDefinition {
     NodeDefinition::new("ReadStorage", "Read Storage", "Reads a value from contract storage", "State")
         .with_input(Port::new("key", "Key", ValueType::String).required())
+        .with_input(Port::new("flow_in", "Flow In", ValueType::Flow))
         .with_output(Port::new("value", "Value", ValueType::Any))
+        .with_output(Port::new("flow_out", "Flow Out", ValueType::Flow))
         .with_config_schema(serde_json::json!({
             "type": "object",
             "properties": {
@@ -233,6 +424,8 @@ fn create_write_storage_node() -> NodeDefinition {
     NodeDefinition::new("WriteStorage", "Write Storage", "Writes a value to contract storage", "State")
         .with_input(Port::new("key", "Key", ValueType::String).required())
         .with_input(Port::new("value", "Value", ValueType::Any).required())
+        .with_input(Port::new("flow_in", "Flow In", ValueType::Flow))
+        .with_output(Port::new("flow_out", "Flow Out", ValueType::Flow))
         .with_config_schema(serde_json::json!({
             "type": "object",
             "properties": {
@@ -251,11 +444,346 @@ fn create_write_storage_node() -> NodeDefinition {
         })
 }
 
+fn create_batch_read_storage_node() -> NodeDefinition {
+    NodeDefinition::new("BatchReadStorage", "Batch Read Storage", "Reads several storage keys in a single host call", "State")
+        .with_input(Port::new("keys", "Keys", ValueType::Array(Box::new(ValueType::String))))
+        .with_input(Port::new("flow_in", "Flow In", ValueType::Flow))
+        .with_output(Port::new("values", "Values", ValueType::Array(Box::new(ValueType::Any))))
+        .with_output(Port::new("flow_out", "Flow Out", ValueType::Flow))
+        .with_config_schema(serde_json::json!({
+            "type": "object",
+            "properties": {
+                "keys": {
+                    "type": "array",
+                    "items": { "type": "string" },
+                    "description": "Storage keys to read, in output order"
+                }
+            }
+        }))
+        .with_compiler_hint(CompilerHint {
+            operation_type: "batch_read_storage".to_string(),
+            expression_field: Some("keys".to_string()),
+            gas_cost: Some(crate::wasm::host::BATCH_BASE_GAS),
+            optimizable: false,
+        })
+}
+
+fn create_batch_write_storage_node() -> NodeDefinition {
+    NodeDefinition::new("BatchWriteStorage", "Batch Write Storage", "Writes several storage keys in a single host call", "State")
+        .with_input(Port::new("keys", "Keys", ValueType::Array(Box::new(ValueType::String))))
+        .with_input(Port::new("values", "Values", ValueType::Array(Box::new(ValueType::Any))))
+        .with_input(Port::new("flow_in", "Flow In", ValueType::Flow))
+        .with_output(Port::new("flow_out", "Flow Out", ValueType::Flow))
+        .with_config_schema(serde_json::json!({
+            "type": "object",
+            "properties": {
+                "keys": {
+                    "type": "array",
+                    "items": { "type": "string" },
+                    "description": "Storage keys to write, matched to values by position"
+                }
+            }
+        }))
+        .with_compiler_hint(CompilerHint {
+            operation_type: "batch_write_storage".to_string(),
+            expression_field: Some("keys".to_string()),
+            gas_cost: Some(crate::wasm::host::BATCH_BASE_GAS),
+            optimizable: false,
+        })
+}
+
+/// Shared config schema for collection nodes
+fn collection_config_schema() -> serde_json::Value {
+    serde_json::json!({
+        "type": "object",
+        "properties": {
+            "element_type": {
+                "type": "string",
+                "description": "Element type name (e.g. \"integer\", \"array<string>\"), checked at compile time"
+            },
+            "max_length": {
+                "type": "integer",
+                "minimum": 1,
+                "description": "Most elements a built array or map may hold when compiled"
+            }
+        }
+    })
+}
+
+fn collection_hint(operation_type: &str, gas_cost: u64) -> CompilerHint {
+    CompilerHint {
+        operation_type: operation_type.to_string(),
+        expression_field: Some("element_type".to_string()),
+        gas_cost: Some(gas_cost),
+        optimizable: true,
+    }
+}
+
+fn any_array() -> ValueType {
+    ValueType::Array(Box::new(ValueType::Any))
+}
+
+fn any_map() -> ValueType {
+    ValueType::Map(Box::new(ValueType::Any))
+}
+
+fn create_array_get_node() -> NodeDefinition {
+    NodeDefinition::new("ArrayGet", "Array Get", "Reads the element at an index", "Collections")
+        .with_input(Port::new("array", "Array", any_array()).required())
+        .with_input(Port::new("index", "Index", ValueType::Integer).required())
+        .with_output(Port::new("value", "Value", ValueType::Any))
+        .with_config_schema(collection_config_schema())
+        .with_compiler_hint(collection_hint("array_get", 5))
+}
+
+fn create_array_set_node() -> NodeDefinition {
+    NodeDefinition::new("ArraySet", "Array Set", "Replaces the element at an index", "Collections")
+        .with_input(Port::new("array", "Array", any_array()).required())
+        .with_input(Port::new("index", "Index", ValueType::Integer).required())
+        .with_input(Port::new("value", "Value", ValueType::Any).required())
+        .with_output(Port::new("array", "Array", any_array()))
+        .with_config_schema(collection_config_schema())
+        .with_compiler_hint(collection_hint("array_set", 8))
+}
+
+fn create_array_push_node() -> NodeDefinition {
+    NodeDefinition::new("ArrayPush", "Array Push", "Appends an element to the end of an array", "Collections")
+        .with_input(Port::new("array", "Array", any_array()).required())
+        .with_input(Port::new("value", "Value", ValueType::Any).required())
+        .with_output(Port::new("array", "Array", any_array()))
+        .with_config_schema(collection_config_schema())
+        .with_compiler_hint(collection_hint("array_push", 8))
+}
+
+fn create_array_remove_node() -> NodeDefinition {
+    NodeDefinition::new("ArrayRemove", "Array Remove", "Removes the element at an index", "Collections")
+        .with_input(Port::new("array", "Array", any_array()).required())
+        .with_input(Port::new("index", "Index", ValueType::Integer).required())
+        .with_output(Port::new("array", "Array", any_array()))
+        .with_output(Port::new("removed", "Removed", ValueType::Any))
+        .with_config_schema(collection_config_schema())
+        .with_compiler_hint(collection_hint("array_remove", 10))
+}
+
+fn create_map_get_node() -> NodeDefinition {
+    NodeDefinition::new("MapGet", "Map Get", "Looks up a key in a map", "Collections")
+        .with_input(Port::new("map", "Map", any_map()).required())
+        .with_input(Port::new("key", "Key", ValueType::String).required())
+        .with_output(Port::new("value", "Value", ValueType::Any))
+        .with_output(Port::new("found", "Found", ValueType::Boolean))
+        .with_config_schema(collection_config_schema())
+        .with_compiler_hint(collection_hint("map_get", 8))
+}
+
+fn create_map_set_node() -> NodeDefinition {
+    NodeDefinition::new("MapSet", "Map Set", "Inserts or replaces a key in a map", "Collections")
+        .with_input(Port::new("map", "Map", any_map()).required())
+        .with_input(Port::new("key", "Key", ValueType::String).required())
+        .with_input(Port::new("value", "Value", ValueType::Any).required())
+        .with_output(Port::new("map", "Map", any_map()))
+        .with_config_schema(collection_config_schema())
+        .with_compiler_hint(collection_hint("map_set", 12))
+}
+
+fn create_map_remove_node() -> NodeDefinition {
+    NodeDefinition::new("MapRemove", "Map Remove", "Removes a key from a map", "Collections")
+        .with_input(Port::new("map", "Map", any_map()).required())
+        .with_input(Port::new("key", "Key", ValueType::String).required())
+        .with_output(Port::new("map", "Map", any_map()))
+        .with_output(Port::new("removed", "Removed", ValueType::Any))
+        .with_config_schema(collection_config_schema())
+        .with_compiler_hint(collection_hint("map_remove", 12))
+}
+
+fn create_length_node() -> NodeDefinition {
+    NodeDefinition::new("Length", "Length", "Number of elements in an array or map", "Collections")
+        .with_input(Port::new("collection", "Collection", ValueType::Any).required())
+        .with_output(Port::new("length", "Length", ValueType::Integer))
+        .with_compiler_hint(collection_hint("collection_length", 3))
+}
+
+fn create_for_each_node() -> NodeDefinition {
+    NodeDefinition::new("ForEach", "For Each", "Iterates over an array with a fixed iteration bound", "Collections")
+        .with_input(Port::new("array", "Array", any_array()).required())
+        .with_input(Port::new("flow_in", "Flow In", ValueType::Flow).required())
+        .with_output(Port::new("body_flow", "Body", ValueType::Flow))
+        .with_output(Port::new("item", "Item", ValueType::Any))
+        .with_output(Port::new("index", "Index", ValueType::Integer))
+        .with_output(Port::new("done_flow", "Done", ValueType::Flow))
+        .with_config_schema(serde_json::json!({
+            "type": "object",
+            "properties": {
+                "max_iterations": {
+                    "type": "integer",
+                    "minimum": 1,
+                    "description": "Upper bound on iterations; longer arrays abort execution"
+                },
+                "element_type": {
+                    "type": "string",
+                    "description": "Element type name, checked at compile time"
+                }
+            },
+            "required": ["max_iterations"]
+        }))
+        .with_compiler_hint(CompilerHint {
+            operation_type: "bounded_loop".to_string(),
+            expression_field: Some("max_iterations".to_string()),
+            gas_cost: Some(5),
+            optimizable: false,
+        })
+        .with_visual(VisualProperties {
+            width: 140.0,
+            height: 100.0,
+            color: "#9B59B6".to_string(),
+            icon: Some("loop".to_string()),
+        })
+}
+
+/// Config schema for string/bytes nodes; `max_length` lets the gas analyzer bound the cost
+fn string_config_schema(extra: serde_json::Value) -> serde_json::Value {
+    let mut schema = serde_json::json!({
+        "type": "object",
+        "properties": {
+            "max_length": {
+                "type": "integer",
+                "minimum": 1,
+                "description": "Maximum length in bytes of any input or output; bounds gas and buffer size"
+            }
+        },
+        "required": ["max_length"]
+    });
+    if let (Some(properties), Some(extra)) = (schema["properties"].as_object_mut(), extra.as_object()) {
+        properties.extend(extra.clone());
+    }
+    schema
+}
+
+fn string_hint(operation_type: &str, gas_cost: u64) -> CompilerHint {
+    CompilerHint {
+        operation_type: operation_type.to_string(),
+        expression_field: Some("max_length".to_string()),
+        gas_cost: Some(gas_cost),
+        optimizable: true,
+    }
+}
+
+fn create_concat_node() -> NodeDefinition {
+    NodeDefinition::new("Concat", "Concat", "Concatenates two strings or byte arrays", "Strings")
+        .with_input(Port::new("a", "A", ValueType::Any).required())
+        .with_input(Port::new("b", "B", ValueType::Any).required())
+        .with_output(Port::new("result", "Result", ValueType::Any))
+        .with_config_schema(string_config_schema(serde_json::json!({})))
+        .with_compiler_hint(string_hint("concat", 5))
+}
+
+fn create_slice_node() -> NodeDefinition {
+    NodeDefinition::new("Slice", "Slice", "Takes a sub-range of a string or byte array", "Strings")
+        .with_input(Port::new("input", "Input", ValueType::Any).required())
+        .with_input(Port::new("start", "Start", ValueType::Integer).required())
+        .with_input(Port::new("end", "End", ValueType::Integer))
+        .with_output(Port::new("result", "Result", ValueType::Any))
+        .with_config_schema(string_config_schema(serde_json::json!({})))
+        .with_compiler_hint(string_hint("slice", 5))
+}
+
+fn create_hash_node() -> NodeDefinition {
+    NodeDefinition::new("Hash", "Hash", "Hashes a string or byte array", "Strings")
+        .with_input(Port::new("input", "Input", ValueType::Any).required())
+        .with_output(Port::new("hash", "Hash", ValueType::Bytes))
+        .with_config_schema(string_config_schema(serde_json::json!({
+            "algorithm": {
+                "type": "string",
+                "enum": ["sha256", "keccak256", "blake3"],
+                "default": "sha256",
+                "description": "Hash algorithm"
+            }
+        })))
+        .with_compiler_hint(string_hint("hash", 30))
+}
+
+fn create_hex_encode_node() -> NodeDefinition {
+    NodeDefinition::new("HexEncode", "Hex Encode", "Encodes a string as 0x-prefixed hex bytes", "Strings")
+        .with_input(Port::new("input", "Input", ValueType::String).required())
+        .with_output(Port::new("result", "Result", ValueType::Bytes))
+        .with_config_schema(string_config_schema(serde_json::json!({})))
+        .with_compiler_hint(string_hint("hex_encode", 5))
+}
+
+fn create_hex_decode_node() -> NodeDefinition {
+    NodeDefinition::new("HexDecode", "Hex Decode", "Decodes 0x-prefixed hex bytes into a UTF-8 string", "Strings")
+        .with_input(Port::new("input", "Input", ValueType::Bytes).required())
+        .with_output(Port::new("result", "Result", ValueType::String))
+        .with_config_schema(string_config_schema(serde_json::json!({})))
+        .with_compiler_hint(string_hint("hex_decode", 5))
+}
+
+fn create_compare_node() -> NodeDefinition {
+    NodeDefinition::new("Compare", "Compare", "Compares two strings or byte arrays", "Strings")
+        .with_input(Port::new("a", "A", ValueType::Any).required())
+        .with_input(Port::new("b", "B", ValueType::Any).required())
+        .with_output(Port::new("equal", "Equal", ValueType::Boolean))
+        .with_output(Port::new("ordering", "Ordering", ValueType::Integer))
+        .with_config_schema(string_config_schema(serde_json::json!({})))
+        .with_compiler_hint(string_hint("compare", 3))
+}
+
+fn create_verify_signature_node() -> NodeDefinition {
+    NodeDefinition::new("VerifySignature", "Verify Signature", "Checks a signature against a public key and message", "Cryptographic")
+        .with_input(Port::new("public_key", "Public Key", ValueType::Bytes).required())
+        .with_input(Port::new("message", "Message", ValueType::Any).required())
+        .with_input(Port::new("signature", "Signature", ValueType::Bytes).required())
+        .with_output(Port::new("valid", "Valid", ValueType::Boolean))
+        .with_config_schema(serde_json::json!({
+            "type": "object",
+            "properties": {
+                "scheme": {
+                    "type": "string",
+                    "enum": ["ed25519", "secp256k1"],
+                    "description": "Signature scheme"
+                }
+            },
+            "required": ["scheme"]
+        }))
+        .with_compiler_hint(CompilerHint {
+            operation_type: "verify_signature".to_string(),
+            expression_field: Some("scheme".to_string()),
+            gas_cost: Some(crate::wasm::host::SECP256K1_VERIFY_GAS),
+            optimizable: false,
+        })
+        .with_visual(VisualProperties {
+            width: 140.0,
+            height: 100.0,
+            color: "#27AE60".to_string(),
+            icon: Some("signature".to_string()),
+        })
+}
+
+/// Config schema shared by arithmetic nodes
+fn overflow_config_schema() -> serde_json::Value {
+    serde_json::json!({
+        "type": "object",
+        "properties": {
+            "overflow_mode": {
+                "type": "string",
+                "enum": ["checked", "saturating", "wrapping"],
+                "description": "Overflow behavior; defaults to the project's compiler.overflow_mode"
+            },
+            "decimal_scale": {
+                "type": "integer",
+                "minimum": 0,
+                "maximum": 18,
+                "description": "Treat inputs as fixed-point decimals with this many fractional digits"
+            }
+        }
+    })
+}
+
 fn create_add_node() -> NodeDefinition {
     NodeDefinition::new("Add", "Add", "Adds two numbers", "Arithmetic")
         .with_input(Port::new("a", "A", ValueType::Integer).required())
         .with_input(Port::new("b", "B", ValueType::Integer).required())
         .with_output(Port::new("result", "Result", ValueType::Integer))
+        .with_config_schema(overflow_config_schema())
         .with_compiler_hint(CompilerHint {
             operation_type: "add".to_string(),
             expression_field: None,
@@ -269,6 +797,7 @@ fn create_subtract_node() -> NodeDefinition {
         .with_input(Port::new("a", "A", ValueType::Integer).required())
         .with_input(Port::new("b", "B", ValueType::Integer).required())
         .with_output(Port::new("result", "Result", ValueType::Integer))
+        .with_config_schema(overflow_config_schema())
         .with_compiler_hint(CompilerHint {
             operation_type: "subtract".to_string(),
             expression_field: None,
@@ -282,6 +811,7 @@ fn create_multiply_node() -> NodeDefinition {
         .with_input(Port::new("a", "A", ValueType::Integer).required())
         .with_input(Port::new("b", "B", ValueType::Integer).required())
         .with_output(Port::new("result", "Result", ValueType::Integer))
+        .with_config_schema(overflow_config_schema())
         .with_compiler_hint(CompilerHint {
             operation_type: "multiply".to_string(),
             expression_field: None,
@@ -295,6 +825,7 @@ fn create_divide_node() -> NodeDefinition {
         .with_input(Port::new("a", "A", ValueType::Integer).required())
         .with_input(Port::new("b", "B", ValueType::Integer).required())
         .with_output(Port::new("result", "Result", ValueType::Integer))
+        .with_config_schema(overflow_config_schema())
         .with_compiler_hint(CompilerHint {
             operation_type: "divide".to_string(),
             expression_field: None,
@@ -303,9 +834,58 @@ fn create_divide_node() -> NodeDefinition {
         })
 }
 
+fn create_cast_unit_node() -> NodeDefinition {
+    NodeDefinition::new("CastUnit", "Cast Unit", "Explicitly converts a value from one unit of account to another", "Arithmetic")
+        .with_input(Port::new("value", "Value", ValueType::Integer).required())
+        .with_output(Port::new("value", "Value", ValueType::Integer))
+        .with_config_schema(serde_json::json!({
+            "type": "object",
+            "properties": {
+                "from_unit": {
+                    "type": "string",
+                    "description": "Unit of the incoming value"
+                },
+                "to_unit": {
+                    "type": "string",
+                    "description": "Unit of the outgoing value"
+                },
+                "factor": {
+                    "type": "integer",
+                    "description": "Multiplier applied during conversion (defaults to 1)"
+                }
+            },
+            "required": ["from_unit", "to_unit"]
+        }))
+        .with_compiler_hint(CompilerHint {
+            operation_type: "cast_unit".to_string(),
+            expression_field: Some("factor".to_string()),
+            gas_cost: Some(3),
+            optimizable: true,
+        })
+}
+
 fn create_start_node() -> NodeDefinition {
+    // Function parameters are the data output ports added to each Start node instance
     NodeDefinition::new("Start", "Start", "Entry point for contract execution", "Control Flow")
         .with_output(Port::new("flow_out", "Flow Out", ValueType::Flow))
+        .with_config_schema(serde_json::json!({
+            "type": "object",
+            "properties": {
+                "function": {
+                    "type": "string",
+                    "description": "Exported function name; required when a graph has several Start nodes"
+                },
+                "mutability": {
+                    "type": "string",
+                    "enum": ["pure", "view", "nonpayable", "payable"],
+                    "description": "State mutability of the function (defaults to nonpayable)"
+                },
+                "returns": {
+                    "type": "string",
+                    "description": "Return type, e.g. \"integer\" or \"array<string>\""
+                }
+            }
+        }))
         .with_compiler_hint(CompilerHint {
             operation_type: "start".to_string(),
             expression_field: None,
@@ -314,6 +894,82 @@ fn create_start_node() -> NodeDefinition {
         })
 }
 
+fn create_init_node() -> NodeDefinition {
+    // Constructor parameters are the data output ports added to each Init node instance
+    NodeDefinition::new("Init", "Init", "Constructor; its output ports are the deployment arguments", "Control Flow")
+        .with_output(Port::new("flow_out", "Flow Out", ValueType::Flow))
+        .with_compiler_hint(CompilerHint {
+            operation_type: "init".to_string(),
+            expression_field: None,
+            gas_cost: Some(0),
+            optimizable: false,
+        })
+        .with_visual(VisualProperties {
+            width: 120.0,
+            height: 80.0,
+            color: "#8E44AD".to_string(),
+            icon: Some("init".to_string()),
+        })
+}
+
+fn create_import_node() -> NodeDefinition {
+    // Ports mirror the imported graph's Start outputs and End inputs
+    NodeDefinition::new("Import", "Import", "Reuses another graph from the same workspace", "Control Flow")
+        .with_input(Port::new("flow_in", "Flow In", ValueType::Flow))
+        .with_output(Port::new("flow_out", "Flow Out", ValueType::Flow))
+        .with_config_schema(serde_json::json!({
+            "type": "object",
+            "properties": {
+                "graph_id": {
+                    "type": "string",
+                    "description": "ID of the imported graph"
+                },
+                "path": {
+                    "type": "string",
+                    "description": "Workspace-relative path of the imported graph, for reference"
+                }
+            },
+            "required": ["graph_id"]
+        }))
+        .with_compiler_hint(CompilerHint {
+            operation_type: "import".to_string(),
+            expression_field: Some("graph_id".to_string()),
+            gas_cost: None,
+            optimizable: true,
+        })
+}
+
+fn create_slot_node() -> NodeDefinition {
+    // Ports are the signature a filling graph's Start outputs and End inputs must match
+    NodeDefinition::new("Slot", "Slot", "Placeholder in a template, filled with a graph of your own before compiling", "Control Flow")
+        .with_input(Port::new("flow_in", "Flow In", ValueType::Flow))
+        .with_output(Port::new("flow_out", "Flow Out", ValueType::Flow))
+        .with_config_schema(serde_json::json!({
+            "type": "object",
+            "properties": {
+                "name": {
+                    "type": "string",
+                    "description": "Slot name, unique within the template"
+                },
+                "hint": {
+                    "type": "string",
+                    "description": "What the filling graph should do"
+                },
+                "fill": {
+                    "type": "string",
+                    "description": "ID of the workspace graph filling the slot"
+                }
+            },
+            "required": ["name"]
+        }))
+        .with_compiler_hint(CompilerHint {
+            operation_type: "slot".to_string(),
+            expression_field: Some("fill".to_string()),
+            gas_cost: None,
+            optimizable: true,
+        })
+}
+
 fn create_end_node() -> NodeDefinition {
     NodeDefinition::new("End", "End", "Exit point for contract execution", "Control Flow")
         .with_input(Port::new("flow_in", "Flow In", ValueType::Flow).required())
@@ -323,4 +979,66 @@ fn create_end_node() -> NodeDefinition {
             gas_cost: Some(0),
             optimizable: false,
         })
-} 
\ No newline at end of file
+} 
+
+fn create_try_call_node() -> NodeDefinition {
+    NodeDefinition::new("TryCall", "Try Call", "Calls another contract and branches on failure instead of aborting", "Control Flow")
+        .with_input(Port::new("flow_in", "Flow In", ValueType::Flow).required())
+        .with_input(Port::new("target", "Target", ValueType::String).required())
+        .with_input(Port::new("args", "Arguments", ValueType::Any))
+        .with_output(Port::new("success_flow", "Success Flow", ValueType::Flow))
+        .with_output(Port::new("result", "Result", ValueType::Any))
+        .with_output(Port::new("failure_flow", "Failure Flow", ValueType::Flow))
+        .with_output(Port::new("error", "Error", ValueType::Any))
+        .with_config_schema(serde_json::json!({
+            "type": "object",
+            "properties": {
+                "function": {
+                    "type": "string",
+                    "description": "Function to call on the target contract"
+                },
+                "gas_limit": {
+                    "type": "integer",
+                    "description": "Gas forwarded to the callee"
+                }
+            },
+            "required": ["function"]
+        }))
+        .with_compiler_hint(CompilerHint {
+            operation_type: "try_call".to_string(),
+            expression_field: Some("function".to_string()),
+            gas_cost: Some(crate::wasm::host::CALL_BASE_GAS),
+            optimizable: false,
+        })
+        .with_visual(VisualProperties {
+            width: 140.0,
+            height: 100.0,
+            color: "#F39C12".to_string(),
+            icon: Some("try".to_string()),
+        })
+}
+
+fn create_catch_node() -> NodeDefinition {
+    NodeDefinition::new("Catch", "Catch", "Handles a failed TryCall, optionally only for a named error", "Control Flow")
+        .with_input(Port::new("flow_in", "Flow In", ValueType::Flow).required())
+        .with_input(Port::new("error", "Error", ValueType::Any).required())
+        .with_output(Port::new("flow_out", "Flow Out", ValueType::Flow))
+        .with_output(Port::new("error_name", "Error Name", ValueType::String))
+        .with_output(Port::new("message", "Message", ValueType::String))
+        .with_output(Port::new("args", "Error Arguments", ValueType::Array(Box::new(ValueType::Any))))
+        .with_config_schema(serde_json::json!({
+            "type": "object",
+            "properties": {
+                "error": {
+                    "type": "string",
+                    "description": "Only handle this revert error; catches everything when unset"
+                }
+            }
+        }))
+        .with_compiler_hint(CompilerHint {
+            operation_type: "catch".to_string(),
+            expression_field: Some("error".to_string()),
+            gas_cost: Some(5),
+            optimizable: false,
+        })
+}
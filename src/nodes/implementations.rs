@@ -2,7 +2,7 @@
 
 use crate::{
     error::{CanvasError, CanvasResult},
-    types::{ExecutionContext, NodeResult, PortId},
+    types::{Decimal, ExecutionContext, NodeResult, OverflowMode, PortId, ValueType},
 };
 
 /// Node trait that all nodes must implement
@@ -100,6 +100,57 @@ impl Node for IfNode {
     }
 }
 
+/// Require node: aborts with a structured revert reason when the condition is false
+pub struct RequireNode {
+    error: String,
+    message: String,
+}
+
+impl RequireNode {
+    pub fn new(error: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            error: error.into(),
+            message: message.into(),
+        }
+    }
+}
+
+impl Node for RequireNode {
+    fn execute(&self, context: &mut crate::nodes::NodeContext) -> CanvasResult<NodeResult> {
+        let condition = context
+            .get_input(&"condition".to_string())
+            .ok_or_else(|| CanvasError::Node("Missing condition input".to_string()))?
+            .as_bool()
+            .ok_or_else(|| CanvasError::Node("Condition must be a boolean".to_string()))?;
+
+        context.use_gas(10)?;
+
+        if !condition {
+            let args = context
+                .get_input(&"args".to_string())
+                .and_then(|v| v.as_array())
+                .cloned()
+                .unwrap_or_default();
+            let mut reason = crate::types::RevertReason::new(self.error.clone(), self.message.clone());
+            reason.args = args;
+            return Err(CanvasError::Reverted(reason));
+        }
+
+        let mut outputs = std::collections::HashMap::new();
+        outputs.insert("flow_out".to_string(), serde_json::Value::Bool(true));
+
+        Ok(NodeResult::success(outputs, 10))
+    }
+
+    fn node_type(&self) -> &str {
+        "Require"
+    }
+
+    fn name(&self) -> &str {
+        "Require"
+    }
+}
+
 /// Add node implementation
 pub struct AddNode;
 
@@ -122,7 +173,9 @@ impl Node for AddNode {
             .ok_or_else(|| CanvasError::Node("Input 'b' must be an integer".to_string()))?;
 
         // Perform addition
-        let result = a_int + b_int;
+        let result = a_int
+            .checked_add(b_int)
+            .ok_or_else(|| CanvasError::Node("Integer overflow in Add".to_string()))?;
 
         // Use gas for arithmetic operation
         context.use_gas(3)?;
@@ -142,6 +195,213 @@ impl Node for AddNode {
     }
 }
 
+/// Integer arithmetic operation
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ArithmeticOp {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+}
+
+impl ArithmeticOp {
+    pub fn from_node_type(node_type: &str) -> Option<Self> {
+        match node_type {
+            "Add" => Some(ArithmeticOp::Add),
+            "Subtract" => Some(ArithmeticOp::Subtract),
+            "Multiply" => Some(ArithmeticOp::Multiply),
+            "Divide" => Some(ArithmeticOp::Divide),
+            _ => None,
+        }
+    }
+
+    pub fn node_type(&self) -> &'static str {
+        match self {
+            ArithmeticOp::Add => "Add",
+            ArithmeticOp::Subtract => "Subtract",
+            ArithmeticOp::Multiply => "Multiply",
+            ArithmeticOp::Divide => "Divide",
+        }
+    }
+
+    fn gas(&self) -> u64 {
+        match self {
+            ArithmeticOp::Add | ArithmeticOp::Subtract => 3,
+            ArithmeticOp::Multiply | ArithmeticOp::Divide => 5,
+        }
+    }
+
+    /// Apply the operation under the given overflow mode
+    pub fn apply(&self, a: i64, b: i64, mode: OverflowMode) -> CanvasResult<i64> {
+        if *self == ArithmeticOp::Divide && b == 0 {
+            return Err(CanvasError::Node("Division by zero".to_string()));
+        }
+
+        let result = match mode {
+            OverflowMode::Checked => match self {
+                ArithmeticOp::Add => a.checked_add(b),
+                ArithmeticOp::Subtract => a.checked_sub(b),
+                ArithmeticOp::Multiply => a.checked_mul(b),
+                ArithmeticOp::Divide => a.checked_div(b),
+            },
+            OverflowMode::Saturating => Some(match self {
+                ArithmeticOp::Add => a.saturating_add(b),
+                ArithmeticOp::Subtract => a.saturating_sub(b),
+                ArithmeticOp::Multiply => a.saturating_mul(b),
+                ArithmeticOp::Divide => a.saturating_div(b),
+            }),
+            OverflowMode::Wrapping => Some(match self {
+                ArithmeticOp::Add => a.wrapping_add(b),
+                ArithmeticOp::Subtract => a.wrapping_sub(b),
+                ArithmeticOp::Multiply => a.wrapping_mul(b),
+                ArithmeticOp::Divide => a.wrapping_div(b),
+            }),
+        };
+
+        result.ok_or_else(|| CanvasError::Node(format!("Integer overflow in {}", self.node_type())))
+    }
+}
+
+impl ArithmeticOp {
+    /// Apply the operation to two decimals of the same scale
+    pub fn apply_decimal(&self, a: Decimal, b: Decimal, mode: OverflowMode) -> CanvasResult<Decimal> {
+        if a.scale != b.scale {
+            return Err(CanvasError::Type(format!(
+                "Decimal scale mismatch: {} vs {}",
+                a.scale, b.scale
+            )));
+        }
+        let units = match self {
+            ArithmeticOp::Add | ArithmeticOp::Subtract => self.apply(a.units, b.units, mode)?,
+            ArithmeticOp::Multiply => crate::wasm::host::decimal_mul(a.units, b.units, a.scale, mode)?,
+            ArithmeticOp::Divide => crate::wasm::host::decimal_div(a.units, b.units, a.scale, mode)?,
+        };
+        Ok(Decimal::from_units(units, a.scale))
+    }
+}
+
+/// Arithmetic node with configurable overflow behavior
+pub struct ArithmeticNode {
+    op: ArithmeticOp,
+    overflow: OverflowMode,
+    decimal_scale: Option<u8>,
+}
+
+impl ArithmeticNode {
+    pub fn new(op: ArithmeticOp, overflow: OverflowMode) -> Self {
+        Self {
+            op,
+            overflow,
+            decimal_scale: None,
+        }
+    }
+
+    /// Operate on fixed-point decimals with the given scale instead of integers
+    pub fn with_decimal_scale(mut self, scale: u8) -> Self {
+        self.decimal_scale = Some(scale);
+        self
+    }
+
+    fn execute_decimal(&self, context: &mut crate::nodes::NodeContext, scale: u8) -> CanvasResult<NodeResult> {
+        let mut operands = Vec::with_capacity(2);
+        for port in ["a", "b"] {
+            let value = context
+                .get_input(&port.to_string())
+                .ok_or_else(|| CanvasError::Node(format!("Missing input '{}'", port)))?;
+            let decimal = Decimal::from_json(value, scale)
+                .map_err(|e| CanvasError::Type(format!("Input '{}': {}", port, e)))?;
+            operands.push(decimal);
+        }
+
+        let result = self.op.apply_decimal(operands[0], operands[1], self.overflow)?;
+
+        let gas = self.op.gas();
+        context.use_gas(gas)?;
+
+        let mut outputs = std::collections::HashMap::new();
+        outputs.insert("result".to_string(), result.to_json());
+
+        Ok(NodeResult::success(outputs, gas))
+    }
+}
+
+impl Node for ArithmeticNode {
+    fn execute(&self, context: &mut crate::nodes::NodeContext) -> CanvasResult<NodeResult> {
+        if let Some(scale) = self.decimal_scale {
+            return self.execute_decimal(context, scale);
+        }
+
+        let a = context
+            .get_input(&"a".to_string())
+            .ok_or_else(|| CanvasError::Node("Missing input 'a'".to_string()))?
+            .as_i64()
+            .ok_or_else(|| CanvasError::Node("Input 'a' must be an integer".to_string()))?;
+        let b = context
+            .get_input(&"b".to_string())
+            .ok_or_else(|| CanvasError::Node("Missing input 'b'".to_string()))?
+            .as_i64()
+            .ok_or_else(|| CanvasError::Node("Input 'b' must be an integer".to_string()))?;
+
+        let result = self.op.apply(a, b, self.overflow)?;
+
+        let gas = self.op.gas();
+        context.use_gas(gas)?;
+
+        let mut outputs = std::collections::HashMap::new();
+        outputs.insert("result".to_string(), serde_json::Value::Number(result.into()));
+
+        Ok(NodeResult::success(outputs, gas))
+    }
+
+    fn node_type(&self) -> &str {
+        self.op.node_type()
+    }
+
+    fn name(&self) -> &str {
+        self.op.node_type()
+    }
+}
+
+/// Explicit unit conversion node
+pub struct CastUnitNode {
+    factor: i64,
+}
+
+impl CastUnitNode {
+    pub fn new(factor: i64) -> Self {
+        Self { factor }
+    }
+}
+
+impl Node for CastUnitNode {
+    fn execute(&self, context: &mut crate::nodes::NodeContext) -> CanvasResult<NodeResult> {
+        let value = context
+            .get_input(&"value".to_string())
+            .ok_or_else(|| CanvasError::Node("Missing input 'value'".to_string()))?
+            .as_i64()
+            .ok_or_else(|| CanvasError::Node("Input 'value' must be an integer".to_string()))?;
+
+        let converted = value
+            .checked_mul(self.factor)
+            .ok_or_else(|| CanvasError::Node("Integer overflow in CastUnit".to_string()))?;
+
+        context.use_gas(3)?;
+
+        let mut outputs = std::collections::HashMap::new();
+        outputs.insert("value".to_string(), serde_json::Value::Number(converted.into()));
+
+        Ok(NodeResult::success(outputs, 3))
+    }
+
+    fn node_type(&self) -> &str {
+        "CastUnit"
+    }
+
+    fn name(&self) -> &str {
+        "Cast Unit"
+    }
+}
+
 /// Read Storage node implementation
 pub struct ReadStorageNode {
     key: String,
@@ -164,9 +424,8 @@ impl Node for ReadStorageNode {
             .as_str()
             .ok_or_else(|| CanvasError::Node("Key must be a string".to_string()))?;
 
-        // Read from storage (simulated for now)
-        let value = context.execution_context.storage.get(key).cloned()
-            .unwrap_or(serde_json::Value::Null);
+        // Read this call's writes, then the storage backend
+        let value = context.execution_context.read_slot(key)?;
 
         // Use gas for storage read
         context.use_gas(100)?;
@@ -232,6 +491,778 @@ impl Node for WriteStorageNode {
     }
 }
 
+/// Collect a list of storage keys from the `keys` input or the configured keys
+fn batch_keys(context: &crate::nodes::NodeContext, configured: &[String]) -> CanvasResult<Vec<String>> {
+    match context.get_input(&"keys".to_string()) {
+        Some(value) => value
+            .as_array()
+            .ok_or_else(|| CanvasError::Node("Keys must be an array".to_string()))?
+            .iter()
+            .map(|key| {
+                key.as_str()
+                    .map(|k| k.to_string())
+                    .ok_or_else(|| CanvasError::Node("Keys must be strings".to_string()))
+            })
+            .collect(),
+        None => Ok(configured.to_vec()),
+    }
+}
+
+/// Batch Read Storage node implementation
+pub struct BatchReadStorageNode {
+    keys: Vec<String>,
+}
+
+impl BatchReadStorageNode {
+    pub fn new(keys: Vec<String>) -> Self {
+        Self { keys }
+    }
+}
+
+impl Node for BatchReadStorageNode {
+    fn execute(&self, context: &mut crate::nodes::NodeContext) -> CanvasResult<NodeResult> {
+        let keys = batch_keys(context, &self.keys)?;
+
+        let gas_before = context.execution_context.gas_used;
+        let values = crate::wasm::host::batch_read_storage(&mut context.execution_context, &keys)?;
+        let gas_used = context.execution_context.gas_used - gas_before;
+
+        // Expose each value on its own port as well, for coalesced single reads
+        let mut outputs = std::collections::HashMap::new();
+        for (index, value) in values.iter().enumerate() {
+            outputs.insert(format!("value_{}", index), value.clone());
+        }
+        outputs.insert("values".to_string(), serde_json::Value::Array(values));
+        outputs.insert("flow_out".to_string(), serde_json::Value::Bool(true));
+
+        Ok(NodeResult::success(outputs, gas_used))
+    }
+
+    fn node_type(&self) -> &str {
+        "BatchReadStorage"
+    }
+
+    fn name(&self) -> &str {
+        "Batch Read Storage"
+    }
+}
+
+/// Batch Write Storage node implementation
+pub struct BatchWriteStorageNode {
+    keys: Vec<String>,
+}
+
+impl BatchWriteStorageNode {
+    pub fn new(keys: Vec<String>) -> Self {
+        Self { keys }
+    }
+}
+
+impl Node for BatchWriteStorageNode {
+    fn execute(&self, context: &mut crate::nodes::NodeContext) -> CanvasResult<NodeResult> {
+        let keys = batch_keys(context, &self.keys)?;
+
+        // Values come either as one array or as per-key ports from coalesced single writes
+        let values: Vec<serde_json::Value> = match context.get_input(&"values".to_string()) {
+            Some(value) => value
+                .as_array()
+                .cloned()
+                .ok_or_else(|| CanvasError::Node("Values must be an array".to_string()))?,
+            None => (0..keys.len())
+                .map(|index| {
+                    context
+                        .get_input(&format!("value_{}", index))
+                        .cloned()
+                        .ok_or_else(|| CanvasError::Node(format!("Missing input 'value_{}'", index)))
+                })
+                .collect::<CanvasResult<_>>()?,
+        };
+
+        if keys.len() != values.len() {
+            return Err(CanvasError::Node(format!(
+                "Batch write has {} keys but {} values",
+                keys.len(),
+                values.len()
+            )));
+        }
+
+        let entries: Vec<(String, serde_json::Value)> = keys.into_iter().zip(values).collect();
+        let gas_before = context.execution_context.gas_used;
+        crate::wasm::host::batch_write_storage(&mut context.execution_context, &entries)?;
+        let gas_used = context.execution_context.gas_used - gas_before;
+
+        let mut outputs = std::collections::HashMap::new();
+        outputs.insert("success".to_string(), serde_json::Value::Bool(true));
+        outputs.insert("flow_out".to_string(), serde_json::Value::Bool(true));
+
+        Ok(NodeResult::success(outputs, gas_used))
+    }
+
+    fn node_type(&self) -> &str {
+        "BatchWriteStorage"
+    }
+
+    fn name(&self) -> &str {
+        "Batch Write Storage"
+    }
+}
+
+/// Collection operation performed by a [`CollectionNode`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CollectionOp {
+    ArrayGet,
+    ArraySet,
+    ArrayPush,
+    ArrayRemove,
+    MapGet,
+    MapSet,
+    MapRemove,
+    Length,
+}
+
+impl CollectionOp {
+    pub fn from_node_type(node_type: &str) -> Option<Self> {
+        match node_type {
+            "ArrayGet" => Some(CollectionOp::ArrayGet),
+            "ArraySet" => Some(CollectionOp::ArraySet),
+            "ArrayPush" => Some(CollectionOp::ArrayPush),
+            "ArrayRemove" => Some(CollectionOp::ArrayRemove),
+            "MapGet" => Some(CollectionOp::MapGet),
+            "MapSet" => Some(CollectionOp::MapSet),
+            "MapRemove" => Some(CollectionOp::MapRemove),
+            "Length" => Some(CollectionOp::Length),
+            _ => None,
+        }
+    }
+
+    pub fn node_type(&self) -> &'static str {
+        match self {
+            CollectionOp::ArrayGet => "ArrayGet",
+            CollectionOp::ArraySet => "ArraySet",
+            CollectionOp::ArrayPush => "ArrayPush",
+            CollectionOp::ArrayRemove => "ArrayRemove",
+            CollectionOp::MapGet => "MapGet",
+            CollectionOp::MapSet => "MapSet",
+            CollectionOp::MapRemove => "MapRemove",
+            CollectionOp::Length => "Length",
+        }
+    }
+
+    /// Base gas cost; operations that copy the collection also pay per element
+    fn base_gas(&self) -> u64 {
+        match self {
+            CollectionOp::Length => 3,
+            CollectionOp::ArrayGet => 5,
+            CollectionOp::MapGet => 8,
+            CollectionOp::ArraySet | CollectionOp::ArrayPush => 8,
+            CollectionOp::ArrayRemove => 10,
+            CollectionOp::MapSet | CollectionOp::MapRemove => 12,
+        }
+    }
+}
+
+/// Array and map manipulation node
+pub struct CollectionNode {
+    op: CollectionOp,
+    element_type: Option<ValueType>,
+}
+
+impl CollectionNode {
+    pub fn new(op: CollectionOp, element_type: Option<ValueType>) -> Self {
+        Self { op, element_type }
+    }
+
+    fn input<'a>(&self, context: &'a crate::nodes::NodeContext, port: &str) -> CanvasResult<&'a serde_json::Value> {
+        context
+            .get_input(&port.to_string())
+            .ok_or_else(|| CanvasError::Node(format!("Missing input '{}'", port)))
+    }
+
+    fn array(&self, context: &crate::nodes::NodeContext) -> CanvasResult<Vec<serde_json::Value>> {
+        self.input(context, "array")?
+            .as_array()
+            .cloned()
+            .ok_or_else(|| CanvasError::Node("Input 'array' must be an array".to_string()))
+    }
+
+    fn map(&self, context: &crate::nodes::NodeContext) -> CanvasResult<serde_json::Map<String, serde_json::Value>> {
+        self.input(context, "map")?
+            .as_object()
+            .cloned()
+            .ok_or_else(|| CanvasError::Node("Input 'map' must be an object".to_string()))
+    }
+
+    fn index(&self, context: &crate::nodes::NodeContext, len: usize) -> CanvasResult<usize> {
+        let index = self
+            .input(context, "index")?
+            .as_u64()
+            .ok_or_else(|| CanvasError::Node("Index must be a non-negative integer".to_string()))? as usize;
+        if index >= len {
+            return Err(CanvasError::Node(format!("Index {} out of bounds for length {}", index, len)));
+        }
+        Ok(index)
+    }
+
+    fn key(&self, context: &crate::nodes::NodeContext) -> CanvasResult<String> {
+        self.input(context, "key")?
+            .as_str()
+            .map(|k| k.to_string())
+            .ok_or_else(|| CanvasError::Node("Key must be a string".to_string()))
+    }
+
+    /// Value input, checked against the declared element type
+    fn value(&self, context: &crate::nodes::NodeContext) -> CanvasResult<serde_json::Value> {
+        let value = self.input(context, "value")?.clone();
+        if let Some(element_type) = &self.element_type {
+            if !element_type.matches_value(&value) {
+                return Err(CanvasError::Type(format!(
+                    "{} value does not match element type {:?}",
+                    self.op.node_type(),
+                    element_type
+                )));
+            }
+        }
+        Ok(value)
+    }
+}
+
+impl Node for CollectionNode {
+    fn execute(&self, context: &mut crate::nodes::NodeContext) -> CanvasResult<NodeResult> {
+        let mut outputs = std::collections::HashMap::new();
+        let mut copied = 0usize;
+
+        match self.op {
+            CollectionOp::ArrayGet => {
+                let array = self.array(context)?;
+                let index = self.index(context, array.len())?;
+                outputs.insert("value".to_string(), array[index].clone());
+            }
+            CollectionOp::ArraySet => {
+                let mut array = self.array(context)?;
+                let index = self.index(context, array.len())?;
+                array[index] = self.value(context)?;
+                copied = array.len();
+                outputs.insert("array".to_string(), serde_json::Value::Array(array));
+            }
+            CollectionOp::ArrayPush => {
+                let mut array = self.array(context)?;
+                array.push(self.value(context)?);
+                copied = array.len();
+                outputs.insert("array".to_string(), serde_json::Value::Array(array));
+            }
+            CollectionOp::ArrayRemove => {
+                let mut array = self.array(context)?;
+                let index = self.index(context, array.len())?;
+                let removed = array.remove(index);
+                copied = array.len();
+                outputs.insert("removed".to_string(), removed);
+                outputs.insert("array".to_string(), serde_json::Value::Array(array));
+            }
+            CollectionOp::MapGet => {
+                let map = self.map(context)?;
+                let value = map.get(&self.key(context)?).cloned();
+                outputs.insert("found".to_string(), serde_json::Value::Bool(value.is_some()));
+                outputs.insert("value".to_string(), value.unwrap_or(serde_json::Value::Null));
+            }
+            CollectionOp::MapSet => {
+                let mut map = self.map(context)?;
+                map.insert(self.key(context)?, self.value(context)?);
+                copied = map.len();
+                outputs.insert("map".to_string(), serde_json::Value::Object(map));
+            }
+            CollectionOp::MapRemove => {
+                let mut map = self.map(context)?;
+                let removed = map.remove(&self.key(context)?).unwrap_or(serde_json::Value::Null);
+                copied = map.len();
+                outputs.insert("removed".to_string(), removed);
+                outputs.insert("map".to_string(), serde_json::Value::Object(map));
+            }
+            CollectionOp::Length => {
+                let length = match self.input(context, "collection")? {
+                    serde_json::Value::Array(items) => items.len(),
+                    serde_json::Value::Object(entries) => entries.len(),
+                    _ => return Err(CanvasError::Node("Length requires an array or map".to_string())),
+                };
+                outputs.insert("length".to_string(), serde_json::Value::Number(length.into()));
+            }
+        }
+
+        let gas = self.op.base_gas() + copied as u64;
+        context.use_gas(gas)?;
+
+        Ok(NodeResult::success(outputs, gas))
+    }
+
+    fn node_type(&self) -> &str {
+        self.op.node_type()
+    }
+
+    fn name(&self) -> &str {
+        self.op.node_type()
+    }
+}
+
+/// Bounded iteration over an array.
+///
+/// The node checks the bound and charges gas for every iteration up front; the
+/// executor then drives `body_flow` once per entry in `items`.
+pub struct ForEachNode {
+    max_iterations: usize,
+    element_type: Option<ValueType>,
+}
+
+impl ForEachNode {
+    /// Gas charged per loop iteration
+    pub const ITERATION_GAS: u64 = 5;
+
+    pub fn new(max_iterations: usize, element_type: Option<ValueType>) -> Self {
+        Self {
+            max_iterations,
+            element_type,
+        }
+    }
+}
+
+impl Node for ForEachNode {
+    fn execute(&self, context: &mut crate::nodes::NodeContext) -> CanvasResult<NodeResult> {
+        let array = context
+            .get_input(&"array".to_string())
+            .ok_or_else(|| CanvasError::Node("Missing input 'array'".to_string()))?
+            .as_array()
+            .cloned()
+            .ok_or_else(|| CanvasError::Node("Input 'array' must be an array".to_string()))?;
+
+        if array.len() > self.max_iterations {
+            return Err(CanvasError::Node(format!(
+                "ForEach over {} elements exceeds max_iterations {}",
+                array.len(),
+                self.max_iterations
+            )));
+        }
+        if let Some(element_type) = &self.element_type {
+            if !array.iter().all(|item| element_type.matches_value(item)) {
+                return Err(CanvasError::Type(format!(
+                    "ForEach element does not match element type {:?}",
+                    element_type
+                )));
+            }
+        }
+
+        let gas = Self::ITERATION_GAS * (array.len() as u64 + 1);
+        context.use_gas(gas)?;
+
+        let mut outputs = std::collections::HashMap::new();
+        outputs.insert("iterations".to_string(), serde_json::Value::Number(array.len().into()));
+        outputs.insert("items".to_string(), serde_json::Value::Array(array));
+        outputs.insert("done_flow".to_string(), serde_json::Value::Bool(true));
+
+        Ok(NodeResult::success(outputs, gas))
+    }
+
+    fn node_type(&self) -> &str {
+        "ForEach"
+    }
+
+    fn name(&self) -> &str {
+        "For Each"
+    }
+}
+
+/// String/bytes operation performed by a [`StringNode`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StringOp {
+    Concat,
+    Slice,
+    Hash,
+    HexEncode,
+    HexDecode,
+    Compare,
+}
+
+impl StringOp {
+    pub fn from_node_type(node_type: &str) -> Option<Self> {
+        match node_type {
+            "Concat" => Some(StringOp::Concat),
+            "Slice" => Some(StringOp::Slice),
+            "Hash" => Some(StringOp::Hash),
+            "HexEncode" => Some(StringOp::HexEncode),
+            "HexDecode" => Some(StringOp::HexDecode),
+            "Compare" => Some(StringOp::Compare),
+            _ => None,
+        }
+    }
+
+    pub fn node_type(&self) -> &'static str {
+        match self {
+            StringOp::Concat => "Concat",
+            StringOp::Slice => "Slice",
+            StringOp::Hash => "Hash",
+            StringOp::HexEncode => "HexEncode",
+            StringOp::HexDecode => "HexDecode",
+            StringOp::Compare => "Compare",
+        }
+    }
+}
+
+/// Encode bytes as a `0x`-prefixed lowercase hex string
+pub fn encode_hex(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(2 + bytes.len() * 2);
+    out.push_str("0x");
+    for byte in bytes {
+        out.push_str(&format!("{:02x}", byte));
+    }
+    out
+}
+
+/// Decode a `0x`-prefixed hex string into bytes
+pub fn decode_hex(value: &str) -> Option<Vec<u8>> {
+    let digits = value.strip_prefix("0x")?;
+    if digits.len() % 2 != 0 {
+        return None;
+    }
+    (0..digits.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(digits.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+/// String and bytes manipulation node.
+///
+/// Bytes travel as `0x`-prefixed hex strings. Every input and output is capped at
+/// `max_length` bytes, which is what lets the compiler bound the node's gas.
+pub struct StringNode {
+    op: StringOp,
+    max_length: usize,
+    algorithm: crate::wasm::host::HashAlgorithm,
+}
+
+impl StringNode {
+    pub fn new(op: StringOp, max_length: usize) -> Self {
+        Self {
+            op,
+            max_length,
+            algorithm: crate::wasm::host::HashAlgorithm::Sha256,
+        }
+    }
+
+    /// Set the algorithm used by `Hash`
+    pub fn with_hash_algorithm(mut self, algorithm: crate::wasm::host::HashAlgorithm) -> Self {
+        self.algorithm = algorithm;
+        self
+    }
+
+    fn text<'a>(&self, context: &'a crate::nodes::NodeContext, port: &str) -> CanvasResult<&'a str> {
+        context
+            .get_input(&port.to_string())
+            .ok_or_else(|| CanvasError::Node(format!("Missing input '{}'", port)))?
+            .as_str()
+            .ok_or_else(|| CanvasError::Node(format!("Input '{}' must be a string or bytes", port)))
+    }
+
+    /// Raw bytes of an input, and whether it was given as hex bytes
+    fn bytes(&self, context: &crate::nodes::NodeContext, port: &str) -> CanvasResult<(Vec<u8>, bool)> {
+        let text = self.text(context, port)?;
+        let decoded = decode_hex(text);
+        let is_bytes = decoded.is_some();
+        let bytes = decoded.unwrap_or_else(|| text.as_bytes().to_vec());
+        self.check_length(bytes.len(), port)?;
+        Ok((bytes, is_bytes))
+    }
+
+    fn check_length(&self, len: usize, what: &str) -> CanvasResult<()> {
+        if len > self.max_length {
+            return Err(CanvasError::Node(format!(
+                "{} '{}' is {} bytes, exceeding max_length {}",
+                self.op.node_type(),
+                what,
+                len,
+                self.max_length
+            )));
+        }
+        Ok(())
+    }
+}
+
+impl Node for StringNode {
+    fn execute(&self, context: &mut crate::nodes::NodeContext) -> CanvasResult<NodeResult> {
+        let mut outputs = std::collections::HashMap::new();
+
+        let gas = match self.op {
+            StringOp::Concat => {
+                let (mut a, a_bytes) = self.bytes(context, "a")?;
+                let (b, b_bytes) = self.bytes(context, "b")?;
+                a.extend_from_slice(&b);
+                self.check_length(a.len(), "result")?;
+                let result = if a_bytes && b_bytes {
+                    encode_hex(&a)
+                } else {
+                    String::from_utf8(a).map_err(|_| CanvasError::Node("Concat produced invalid UTF-8".to_string()))?
+                };
+                let gas = crate::wasm::host::string_op_gas(result.len());
+                outputs.insert("result".to_string(), serde_json::Value::String(result));
+                gas
+            }
+            StringOp::Slice => {
+                let (bytes, is_bytes) = self.bytes(context, "input")?;
+                let start = context
+                    .get_input(&"start".to_string())
+                    .and_then(|v| v.as_u64())
+                    .ok_or_else(|| CanvasError::Node("Input 'start' must be a non-negative integer".to_string()))?
+                    as usize;
+                let end = context
+                    .get_input(&"end".to_string())
+                    .and_then(|v| v.as_u64())
+                    .map(|e| e as usize)
+                    .unwrap_or(bytes.len());
+                if start > end || end > bytes.len() {
+                    return Err(CanvasError::Node(format!(
+                        "Slice range {}..{} out of bounds for length {}",
+                        start,
+                        end,
+                        bytes.len()
+                    )));
+                }
+                let slice = bytes[start..end].to_vec();
+                let result = if is_bytes {
+                    encode_hex(&slice)
+                } else {
+                    String::from_utf8(slice)
+                        .map_err(|_| CanvasError::Node("Slice does not fall on a character boundary".to_string()))?
+                };
+                outputs.insert("result".to_string(), serde_json::Value::String(result));
+                crate::wasm::host::string_op_gas(end - start)
+            }
+            StringOp::Hash => {
+                let (bytes, _) = self.bytes(context, "input")?;
+                let digest = crate::wasm::host::hash(self.algorithm, &bytes);
+                outputs.insert("hash".to_string(), serde_json::Value::String(encode_hex(&digest)));
+                crate::wasm::host::hash_gas(bytes.len())
+            }
+            StringOp::HexEncode => {
+                let text = self.text(context, "input")?;
+                self.check_length(text.len(), "input")?;
+                let result = encode_hex(text.as_bytes());
+                let gas = crate::wasm::host::string_op_gas(text.len());
+                outputs.insert("result".to_string(), serde_json::Value::String(result));
+                gas
+            }
+            StringOp::HexDecode => {
+                let text = self.text(context, "input")?;
+                let bytes = decode_hex(text)
+                    .ok_or_else(|| CanvasError::Node("Input is not 0x-prefixed hex".to_string()))?;
+                self.check_length(bytes.len(), "input")?;
+                let gas = crate::wasm::host::string_op_gas(bytes.len());
+                let result = String::from_utf8(bytes)
+                    .map_err(|_| CanvasError::Node("Decoded bytes are not valid UTF-8".to_string()))?;
+                outputs.insert("result".to_string(), serde_json::Value::String(result));
+                gas
+            }
+            StringOp::Compare => {
+                let (a, _) = self.bytes(context, "a")?;
+                let (b, _) = self.bytes(context, "b")?;
+                let ordering = match a.cmp(&b) {
+                    std::cmp::Ordering::Less => -1,
+                    std::cmp::Ordering::Equal => 0,
+                    std::cmp::Ordering::Greater => 1,
+                };
+                outputs.insert("equal".to_string(), serde_json::Value::Bool(ordering == 0));
+                outputs.insert("ordering".to_string(), serde_json::Value::Number(ordering.into()));
+                crate::wasm::host::string_op_gas(a.len().min(b.len()))
+            }
+        };
+
+        context.use_gas(gas)?;
+        Ok(NodeResult::success(outputs, gas))
+    }
+
+    fn node_type(&self) -> &str {
+        self.op.node_type()
+    }
+
+    fn name(&self) -> &str {
+        self.op.node_type()
+    }
+}
+
+/// Signature verification node
+pub struct VerifySignatureNode {
+    scheme: crate::wasm::host::SignatureScheme,
+}
+
+impl VerifySignatureNode {
+    pub fn new(scheme: crate::wasm::host::SignatureScheme) -> Self {
+        Self { scheme }
+    }
+
+    fn hex_input(context: &crate::nodes::NodeContext, port: &str) -> CanvasResult<Vec<u8>> {
+        let value = context
+            .get_input(&port.to_string())
+            .ok_or_else(|| CanvasError::Node(format!("Missing input '{}'", port)))?
+            .as_str()
+            .ok_or_else(|| CanvasError::Node(format!("Input '{}' must be hex bytes", port)))?;
+        decode_hex(value).ok_or_else(|| CanvasError::Node(format!("Input '{}' must be 0x-prefixed hex", port)))
+    }
+}
+
+impl Node for VerifySignatureNode {
+    fn execute(&self, context: &mut crate::nodes::NodeContext) -> CanvasResult<NodeResult> {
+        let public_key = Self::hex_input(context, "public_key")?;
+        let signature = Self::hex_input(context, "signature")?;
+
+        // Messages may be hex bytes or plain text
+        let message = context
+            .get_input(&"message".to_string())
+            .ok_or_else(|| CanvasError::Node("Missing input 'message'".to_string()))?;
+        let message = match message.as_str() {
+            Some(text) => decode_hex(text).unwrap_or_else(|| text.as_bytes().to_vec()),
+            None => message.to_string().into_bytes(),
+        };
+
+        let gas = self.scheme.verify_gas();
+        context.use_gas(gas)?;
+
+        let valid = crate::wasm::host::verify_signature(self.scheme, &public_key, &message, &signature);
+
+        let mut outputs = std::collections::HashMap::new();
+        outputs.insert("valid".to_string(), serde_json::Value::Bool(valid));
+
+        Ok(NodeResult::success(outputs, gas))
+    }
+
+    fn node_type(&self) -> &str {
+        "VerifySignature"
+    }
+
+    fn name(&self) -> &str {
+        "Verify Signature"
+    }
+}
+
+/// Handler that performs an external call for a [`TryCallNode`]
+pub type CallHandler =
+    Box<dyn Fn(&str, &str, &serde_json::Value) -> crate::wasm::host::CallOutcome + Send + Sync>;
+
+/// TryCall node: calls another contract and routes failures to `failure_flow`
+/// instead of aborting the transaction.
+///
+/// Running out of gas in the caller itself is not caught; only the callee's
+/// failure is.
+pub struct TryCallNode {
+    function: String,
+    handler: CallHandler,
+}
+
+impl TryCallNode {
+    /// Create a TryCall node.
+    ///
+    /// Local execution has no other contracts to call, so without a handler
+    /// every call fails with `CallUnavailable`.
+    pub fn new(function: impl Into<String>) -> Self {
+        Self {
+            function: function.into(),
+            handler: Box::new(|target, _, _| {
+                crate::wasm::host::CallOutcome::reverted(crate::types::RevertReason::new(
+                    "CallUnavailable",
+                    format!("contract {} is not available in local execution", target),
+                ))
+            }),
+        }
+    }
+
+    /// Use a custom handler to perform the call
+    pub fn with_handler(
+        mut self,
+        handler: impl Fn(&str, &str, &serde_json::Value) -> crate::wasm::host::CallOutcome + Send + Sync + 'static,
+    ) -> Self {
+        self.handler = Box::new(handler);
+        self
+    }
+}
+
+impl Node for TryCallNode {
+    fn execute(&self, context: &mut crate::nodes::NodeContext) -> CanvasResult<NodeResult> {
+        let target = context
+            .get_input(&"target".to_string())
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| CanvasError::Node("TryCall requires a 'target' address".to_string()))?
+            .to_string();
+        let args = context
+            .get_input(&"args".to_string())
+            .cloned()
+            .unwrap_or(serde_json::Value::Null);
+
+        let gas = crate::wasm::host::CALL_BASE_GAS;
+        context.use_gas(gas)?;
+
+        let mut outputs = std::collections::HashMap::new();
+        match (self.handler)(&target, &self.function, &args) {
+            crate::wasm::host::CallOutcome::Success(result) => {
+                outputs.insert("success_flow".to_string(), serde_json::Value::Bool(true));
+                outputs.insert("result".to_string(), result);
+            }
+            crate::wasm::host::CallOutcome::Failed { reason, .. } => {
+                outputs.insert("failure_flow".to_string(), serde_json::Value::Bool(true));
+                outputs.insert("error".to_string(), serde_json::to_value(&reason)?);
+            }
+        }
+
+        Ok(NodeResult::success(outputs, gas))
+    }
+
+    fn node_type(&self) -> &str {
+        "TryCall"
+    }
+
+    fn name(&self) -> &str {
+        "Try Call"
+    }
+}
+
+/// Catch node: handles the error produced by a failed TryCall.
+///
+/// A Catch with an `error` filter only takes its path when the revert reason
+/// has that name; otherwise it produces no outputs.
+pub struct CatchNode {
+    error: Option<String>,
+}
+
+impl CatchNode {
+    pub fn new(error: Option<String>) -> Self {
+        Self { error }
+    }
+}
+
+impl Node for CatchNode {
+    fn execute(&self, context: &mut crate::nodes::NodeContext) -> CanvasResult<NodeResult> {
+        let error = context
+            .get_input(&"error".to_string())
+            .cloned()
+            .ok_or_else(|| CanvasError::Node("Missing error input".to_string()))?;
+        let reason: crate::types::RevertReason = serde_json::from_value(error)
+            .map_err(|e| CanvasError::Node(format!("Catch input is not a revert reason: {}", e)))?;
+
+        context.use_gas(5)?;
+
+        let mut outputs = std::collections::HashMap::new();
+        if self.error.as_deref().map_or(true, |name| name == reason.error) {
+            outputs.insert("flow_out".to_string(), serde_json::Value::Bool(true));
+            outputs.insert("error_name".to_string(), serde_json::Value::String(reason.error));
+            outputs.insert("message".to_string(), serde_json::Value::String(reason.message));
+            outputs.insert("args".to_string(), serde_json::Value::Array(reason.args));
+        }
+
+        Ok(NodeResult::success(outputs, 5))
+    }
+
+    fn node_type(&self) -> &str {
+        "Catch"
+    }
+
+    fn name(&self) -> &str {
+        "Catch"
+    }
+}
+
 /// Start node implementation
 pub struct StartNode;
 
@@ -253,6 +1284,41 @@ impl Node for StartNode {
     }
 }
 
+/// Init node: starts the constructor flow and exposes the deployment arguments
+pub struct InitNode {
+    params: Vec<String>,
+}
+
+impl InitNode {
+    pub fn new(params: Vec<String>) -> Self {
+        Self { params }
+    }
+}
+
+impl Node for InitNode {
+    fn execute(&self, context: &mut crate::nodes::NodeContext) -> CanvasResult<NodeResult> {
+        let mut outputs = std::collections::HashMap::new();
+        for param in &self.params {
+            let value = context
+                .get_input(param)
+                .cloned()
+                .ok_or_else(|| CanvasError::Node(format!("Missing constructor argument '{}'", param)))?;
+            outputs.insert(param.clone(), value);
+        }
+        outputs.insert("flow_out".to_string(), serde_json::Value::Bool(true));
+
+        Ok(NodeResult::success(outputs, 0))
+    }
+
+    fn node_type(&self) -> &str {
+        "Init"
+    }
+
+    fn name(&self) -> &str {
+        "Init"
+    }
+}
+
 /// End node implementation
 pub struct EndNode;
 
@@ -286,7 +1352,45 @@ impl NodeFactory {
                     .to_string();
                 Ok(Box::new(IfNode::new(condition)))
             }
-            "Add" => Ok(Box::new(AddNode)),
+            "Add" | "Subtract" | "Multiply" | "Divide" => {
+                let op = ArithmeticOp::from_node_type(node_type)
+                    .ok_or_else(|| CanvasError::Node(format!("Unknown node type: {}", node_type)))?;
+                // Unset modes fall back to the checked default; the compiler applies the project default
+                let overflow = properties
+                    .get("overflow_mode")
+                    .and_then(|v| v.as_str())
+                    .map(|name| {
+                        OverflowMode::from_name(name)
+                            .ok_or_else(|| CanvasError::Node(format!("Unknown overflow mode: {}", name)))
+                    })
+                    .transpose()?
+                    .unwrap_or_default();
+                let mut node = ArithmeticNode::new(op, overflow);
+                if let Some(scale) = properties.get("decimal_scale").and_then(|v| v.as_u64()) {
+                    if scale > Decimal::MAX_SCALE as u64 {
+                        return Err(CanvasError::Node(format!("Decimal scale {} is too large", scale)));
+                    }
+                    node = node.with_decimal_scale(scale as u8);
+                }
+                Ok(Box::new(node))
+            }
+            "Require" => {
+                let error = properties
+                    .get("error")
+                    .and_then(|v| v.as_str())
+                    .unwrap_or("RequirementFailed")
+                    .to_string();
+                let message = properties
+                    .get("message")
+                    .and_then(|v| v.as_str())
+                    .unwrap_or("")
+                    .to_string();
+                Ok(Box::new(RequireNode::new(error, message)))
+            }
+            "CastUnit" => {
+                let factor = properties.get("factor").and_then(|v| v.as_i64()).unwrap_or(1);
+                Ok(Box::new(CastUnitNode::new(factor)))
+            }
             "ReadStorage" => {
                 let key = properties
                     .get("key")
@@ -303,13 +1407,81 @@ impl NodeFactory {
                     .to_string();
                 Ok(Box::new(WriteStorageNode::new(key)))
             }
+            "BatchReadStorage" => Ok(Box::new(BatchReadStorageNode::new(string_list(properties, "keys")))),
+            "BatchWriteStorage" => Ok(Box::new(BatchWriteStorageNode::new(string_list(properties, "keys")))),
+            "ForEach" => {
+                let max_iterations = properties
+                    .get("max_iterations")
+                    .and_then(|v| v.as_u64())
+                    .ok_or_else(|| CanvasError::Node("ForEach requires a 'max_iterations' property".to_string()))?;
+                Ok(Box::new(ForEachNode::new(max_iterations as usize, element_type(properties))))
+            }
+            "VerifySignature" => {
+                let scheme = properties
+                    .get("scheme")
+                    .and_then(|v| v.as_str())
+                    .and_then(crate::wasm::host::SignatureScheme::from_name)
+                    .ok_or_else(|| CanvasError::Node("VerifySignature requires a valid 'scheme' property".to_string()))?;
+                Ok(Box::new(VerifySignatureNode::new(scheme)))
+            }
+            "TryCall" => {
+                let function = properties
+                    .get("function")
+                    .and_then(|v| v.as_str())
+                    .ok_or_else(|| CanvasError::Node("TryCall requires a 'function' property".to_string()))?;
+                Ok(Box::new(TryCallNode::new(function)))
+            }
+            "Catch" => {
+                let error = properties.get("error").and_then(|v| v.as_str()).map(|e| e.to_string());
+                Ok(Box::new(CatchNode::new(error)))
+            }
             "Start" => Ok(Box::new(StartNode)),
+            // The factory only sees properties, so parameter names come from the `params` list
+            "Init" => Ok(Box::new(InitNode::new(string_list(properties, "params")))),
             "End" => Ok(Box::new(EndNode)),
-            _ => Err(CanvasError::Node(format!("Unknown node type: {}", node_type))),
+            _ => {
+                if let Some(op) = CollectionOp::from_node_type(node_type) {
+                    return Ok(Box::new(CollectionNode::new(op, element_type(properties))));
+                }
+                if let Some(op) = StringOp::from_node_type(node_type) {
+                    let max_length = properties
+                        .get("max_length")
+                        .and_then(|v| v.as_u64())
+                        .ok_or_else(|| CanvasError::Node(format!("{} requires a 'max_length' property", node_type)))?;
+                    let algorithm = properties
+                        .get("algorithm")
+                        .and_then(|v| v.as_str())
+                        .map(|name| {
+                            crate::wasm::host::HashAlgorithm::from_name(name)
+                                .ok_or_else(|| CanvasError::Node(format!("Unknown hash algorithm: {}", name)))
+                        })
+                        .transpose()?
+                        .unwrap_or(crate::wasm::host::HashAlgorithm::Sha256);
+                    return Ok(Box::new(StringNode::new(op, max_length as usize).with_hash_algorithm(algorithm)));
+                }
+                Err(CanvasError::Node(format!("Unknown node type: {}", node_type)))
+            }
         }
     }
 }
 
+/// Read a list of strings from a node property, ignoring non-string entries
+fn string_list(properties: &std::collections::HashMap<String, serde_json::Value>, name: &str) -> Vec<String> {
+    properties
+        .get(name)
+        .and_then(|v| v.as_array())
+        .map(|items| items.iter().filter_map(|v| v.as_str().map(|s| s.to_string())).collect())
+        .unwrap_or_default()
+}
+
+/// Parse the optional `element_type` property of collection nodes
+fn element_type(properties: &std::collections::HashMap<String, serde_json::Value>) -> Option<ValueType> {
+    properties
+        .get("element_type")
+        .and_then(|v| v.as_str())
+        .and_then(ValueType::from_name)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -342,6 +1514,159 @@ mod tests {
         assert_eq!(result.outputs.get("result").unwrap().as_i64().unwrap(), 8);
     }
 
+    #[test]
+    fn test_batch_storage_nodes() {
+        let mut context = crate::nodes::NodeContext::new(ExecutionContext::new(10_000));
+        context.inputs.insert("value_0".to_string(), serde_json::json!(7));
+        context.inputs.insert("value_1".to_string(), serde_json::json!(true));
+
+        let writer = BatchWriteStorageNode::new(vec!["x".to_string(), "y".to_string()]);
+        assert!(writer.execute(&mut context).is_ok());
+
+        context.inputs.clear();
+        let reader = BatchReadStorageNode::new(vec!["y".to_string(), "x".to_string()]);
+        let result = reader.execute(&mut context).unwrap();
+        assert_eq!(result.outputs.get("values").unwrap(), &serde_json::json!([true, 7]));
+        assert_eq!(result.outputs.get("value_1").unwrap(), &serde_json::json!(7));
+    }
+
+    #[test]
+    fn test_collection_nodes() {
+        let mut context = crate::nodes::NodeContext::new(ExecutionContext::new(1000));
+        context.inputs.insert("array".to_string(), serde_json::json!([1, 2]));
+        context.inputs.insert("value".to_string(), serde_json::json!(3));
+
+        let push = CollectionNode::new(CollectionOp::ArrayPush, Some(ValueType::Integer));
+        let result = push.execute(&mut context).unwrap();
+        assert_eq!(result.outputs.get("array").unwrap(), &serde_json::json!([1, 2, 3]));
+
+        context.inputs.insert("value".to_string(), serde_json::json!("three"));
+        assert!(push.execute(&mut context).is_err());
+
+        context.inputs.insert("map".to_string(), serde_json::json!({"a": 1}));
+        context.inputs.insert("key".to_string(), serde_json::json!("b"));
+        let get = CollectionNode::new(CollectionOp::MapGet, None);
+        let result = get.execute(&mut context).unwrap();
+        assert_eq!(result.outputs.get("found").unwrap(), &serde_json::json!(false));
+    }
+
+    #[test]
+    fn test_for_each_bound() {
+        let mut context = crate::nodes::NodeContext::new(ExecutionContext::new(1000));
+        context.inputs.insert("array".to_string(), serde_json::json!([1, 2, 3]));
+
+        assert!(ForEachNode::new(3, None).execute(&mut context).is_ok());
+        assert!(ForEachNode::new(2, None).execute(&mut context).is_err());
+    }
+
+    #[test]
+    fn test_string_nodes() {
+        let mut context = crate::nodes::NodeContext::new(ExecutionContext::new(1000));
+        context.inputs.insert("a".to_string(), serde_json::json!("foo"));
+        context.inputs.insert("b".to_string(), serde_json::json!("bar"));
+
+        let concat = StringNode::new(StringOp::Concat, 16);
+        let result = concat.execute(&mut context).unwrap();
+        assert_eq!(result.outputs.get("result").unwrap(), &serde_json::json!("foobar"));
+
+        // Output longer than max_length is rejected
+        assert!(StringNode::new(StringOp::Concat, 4).execute(&mut context).is_err());
+
+        context.inputs.insert("input".to_string(), serde_json::json!("hi"));
+        let encoded = StringNode::new(StringOp::HexEncode, 16).execute(&mut context).unwrap();
+        assert_eq!(encoded.outputs.get("result").unwrap(), &serde_json::json!("0x6869"));
+    }
+
+    #[test]
+    fn test_hex_round_trip() {
+        assert_eq!(decode_hex(&encode_hex(&[0, 255, 16])), Some(vec![0, 255, 16]));
+        assert_eq!(decode_hex("0xabc"), None);
+        assert_eq!(decode_hex("abcd"), None);
+    }
+
+    #[test]
+    fn test_verify_signature_node() {
+        use ed25519_dalek::{Signer, SigningKey};
+        let signing_key = SigningKey::from_bytes(&[1u8; 32]);
+        let signature = signing_key.sign(b"hello");
+
+        let mut context = crate::nodes::NodeContext::new(ExecutionContext::new(10_000));
+        context.inputs.insert(
+            "public_key".to_string(),
+            serde_json::json!(encode_hex(&signing_key.verifying_key().to_bytes())),
+        );
+        context.inputs.insert("message".to_string(), serde_json::json!("hello"));
+        context.inputs.insert("signature".to_string(), serde_json::json!(encode_hex(&signature.to_bytes())));
+
+        let node = VerifySignatureNode::new(crate::wasm::host::SignatureScheme::Ed25519);
+        let result = node.execute(&mut context).unwrap();
+        assert_eq!(result.outputs.get("valid").unwrap(), &serde_json::json!(true));
+    }
+
+    #[test]
+    fn test_overflow_modes() {
+        let op = ArithmeticOp::Add;
+        assert!(op.apply(i64::MAX, 1, OverflowMode::Checked).is_err());
+        assert_eq!(op.apply(i64::MAX, 1, OverflowMode::Saturating).unwrap(), i64::MAX);
+        assert_eq!(op.apply(i64::MAX, 1, OverflowMode::Wrapping).unwrap(), i64::MIN);
+        assert!(ArithmeticOp::Divide.apply(1, 0, OverflowMode::Wrapping).is_err());
+        assert_eq!(ArithmeticOp::Divide.apply(i64::MIN, -1, OverflowMode::Saturating).unwrap(), i64::MAX);
+    }
+
+    #[test]
+    fn test_decimal_arithmetic() {
+        let mut context = crate::nodes::NodeContext::new(ExecutionContext::new(1000));
+        context.inputs.insert("a".to_string(), serde_json::json!("10.25"));
+        context.inputs.insert("b".to_string(), serde_json::json!(2));
+
+        let multiply = ArithmeticNode::new(ArithmeticOp::Multiply, OverflowMode::Checked).with_decimal_scale(2);
+        let result = multiply.execute(&mut context).unwrap();
+        assert_eq!(result.outputs.get("result").unwrap(), &serde_json::json!("20.50"));
+
+        context.inputs.insert("b".to_string(), serde_json::json!(0.5));
+        assert!(multiply.execute(&mut context).is_err());
+    }
+
+    #[test]
+    fn test_require_node_reverts() {
+        let mut context = crate::nodes::NodeContext::new(ExecutionContext::new(1000));
+        context.inputs.insert("condition".to_string(), serde_json::Value::Bool(false));
+        context.inputs.insert("args".to_string(), serde_json::json!([5]));
+
+        let node = RequireNode::new("InsufficientBalance", "balance too low");
+        let error = node.execute(&mut context).unwrap_err();
+        let reason = error.revert_reason().unwrap();
+        assert_eq!(reason.error, "InsufficientBalance");
+        assert_eq!(reason.args, vec![serde_json::json!(5)]);
+
+        context.inputs.insert("condition".to_string(), serde_json::Value::Bool(true));
+        assert!(node.execute(&mut context).is_ok());
+    }
+
+    #[test]
+    fn test_try_call_routes_failure_to_catch() {
+        let mut context = crate::nodes::NodeContext::new(ExecutionContext::new(10_000));
+        context.inputs.insert("target".to_string(), serde_json::json!("token"));
+
+        let failing = TryCallNode::new("transfer").with_handler(|_, _, _| {
+            crate::wasm::host::CallOutcome::reverted(crate::types::RevertReason::new("Paused", "token is paused"))
+        });
+        let result = failing.execute(&mut context).unwrap();
+        assert!(result.outputs.contains_key("failure_flow"));
+        assert!(!result.outputs.contains_key("success_flow"));
+
+        context.inputs.insert("error".to_string(), result.outputs["error"].clone());
+        let other = CatchNode::new(Some("Unauthorized".to_string())).execute(&mut context).unwrap();
+        assert!(other.outputs.is_empty());
+        let caught = CatchNode::new(Some("Paused".to_string())).execute(&mut context).unwrap();
+        assert_eq!(caught.outputs["message"], serde_json::json!("token is paused"));
+
+        let succeeding = TryCallNode::new("balance_of")
+            .with_handler(|_, _, _| crate::wasm::host::CallOutcome::Success(serde_json::json!(7)));
+        let result = succeeding.execute(&mut context).unwrap();
+        assert_eq!(result.outputs["result"], serde_json::json!(7));
+    }
+
     #[test]
     fn test_node_factory() {
         let mut properties = std::collections::HashMap::new();
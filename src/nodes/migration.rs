@@ -0,0 +1,169 @@
+//! Migration of deprecated nodes
+//!
+//! Deprecated node types stay loadable: when a graph is loaded, each node whose
+//! type has a [`Deprecation`] with a replacement is rewritten to the replacement
+//! type, with its ports and properties renamed as the deprecation describes. The
+//! same rewrite backs the `migrate-nodes` command, which saves the result.
+
+use std::collections::HashMap;
+
+use super::definitions::{deprecated_node_definitions, Deprecation};
+use crate::{
+    error::CanvasResult,
+    types::{NodeId, VisualGraph},
+};
+
+/// One node rewritten by a migration
+#[derive(Debug, Clone, PartialEq)]
+pub struct NodeMigration {
+    pub node_id: NodeId,
+    pub from: String,
+    pub to: String,
+}
+
+/// Outcome of migrating a graph
+#[derive(Debug, Clone, Default)]
+pub struct MigrationReport {
+    /// Nodes rewritten to their replacement type
+    pub migrated: Vec<NodeMigration>,
+    /// Deprecated nodes that have no replacement and were left as they are
+    pub warnings: Vec<String>,
+}
+
+impl MigrationReport {
+    /// Whether the migration changed nothing
+    pub fn is_empty(&self) -> bool {
+        self.migrated.is_empty() && self.warnings.is_empty()
+    }
+
+    /// Human-readable warnings for every deprecated node found
+    pub fn messages(&self) -> Vec<String> {
+        self.migrated
+            .iter()
+            .map(|m| format!("Node {} uses deprecated type '{}', migrated to '{}'", m.node_id, m.from, m.to))
+            .chain(self.warnings.iter().cloned())
+            .collect()
+    }
+}
+
+/// Deprecation metadata of a node type, if it is deprecated
+pub fn deprecation_for(node_type: &str) -> Option<Deprecation> {
+    deprecated_node_definitions()
+        .into_iter()
+        .find(|definition| definition.id == node_type)
+        .and_then(|definition| definition.deprecation)
+}
+
+/// Rewrite every deprecated node in a graph to its replacement
+pub fn migrate_graph(graph: &VisualGraph) -> (VisualGraph, MigrationReport) {
+    let deprecations: HashMap<String, Deprecation> = deprecated_node_definitions()
+        .into_iter()
+        .filter_map(|definition| definition.deprecation.map(|d| (definition.id, d)))
+        .collect();
+
+    let mut migrated = graph.clone();
+    let mut report = MigrationReport::default();
+    // Port renames per migrated node, applied to connections afterwards
+    let mut renamed_ports: HashMap<NodeId, &HashMap<String, String>> = HashMap::new();
+
+    for node in &mut migrated.nodes {
+        let Some(deprecation) = deprecations.get(&node.node_type) else {
+            continue;
+        };
+        let Some(replacement) = &deprecation.replacement else {
+            report.warnings.push(format!(
+                "Node {} uses '{}', deprecated since {} without a replacement: {}",
+                node.id, node.node_type, deprecation.since, deprecation.note
+            ));
+            continue;
+        };
+
+        for port in node.inputs.iter_mut().chain(node.outputs.iter_mut()) {
+            if let Some(new_id) = deprecation.port_renames.get(&port.id) {
+                port.id = new_id.clone();
+            }
+        }
+        for (old, new) in &deprecation.property_renames {
+            if let Some(value) = node.properties.remove(old) {
+                node.properties.insert(new.clone(), value);
+            }
+        }
+        for (name, value) in &deprecation.default_properties {
+            node.properties.entry(name.clone()).or_insert_with(|| value.clone());
+        }
+
+        report.migrated.push(NodeMigration {
+            node_id: node.id,
+            from: std::mem::replace(&mut node.node_type, replacement.clone()),
+            to: replacement.clone(),
+        });
+        renamed_ports.insert(node.id, &deprecation.port_renames);
+    }
+
+    for connection in &mut migrated.connections {
+        if let Some(new_port) = renamed_ports.get(&connection.source_node).and_then(|r| r.get(&connection.source_port)) {
+            connection.source_port = new_port.clone();
+        }
+        if let Some(new_port) = renamed_ports.get(&connection.target_node).and_then(|r| r.get(&connection.target_port)) {
+            connection.target_port = new_port.clone();
+        }
+    }
+
+    (migrated, report)
+}
+
+/// Parse a graph and apply deprecation shims, logging a warning per migrated node
+pub fn load_graph(json: &str) -> CanvasResult<(VisualGraph, MigrationReport)> {
+    let graph: VisualGraph = serde_json::from_str(json)?;
+    let (graph, report) = migrate_graph(&graph);
+    for message in report.messages() {
+        log::warn!("{}", message);
+    }
+    Ok((graph, report))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{Connection, Port, Position, ValueType, VisualNode};
+    use uuid::Uuid;
+
+    #[test]
+    fn test_deprecated_nodes_are_rewritten() {
+        let mut graph = VisualGraph::new("legacy");
+        let source = VisualNode::new(Uuid::new_v4(), "ReadStorage", Position::new(0.0, 0.0));
+        let hash = VisualNode::new(Uuid::new_v4(), "Keccak256", Position::new(0.0, 0.0))
+            .with_inputs(vec![Port::new("data", "Data", ValueType::Any)]);
+        let assert = VisualNode::new(Uuid::new_v4(), "Assert", Position::new(0.0, 0.0))
+            .with_property("reason", serde_json::json!("balance too low"));
+        graph.add_connection(Connection::new(Uuid::new_v4(), source.id, "value", hash.id, "data"));
+        let (hash_id, assert_id) = (hash.id, assert.id);
+        graph.add_node(source);
+        graph.add_node(hash);
+        graph.add_node(assert);
+
+        let (migrated, report) = migrate_graph(&graph);
+        assert_eq!(report.migrated.len(), 2);
+
+        let hash = migrated.nodes.iter().find(|n| n.id == hash_id).unwrap();
+        assert_eq!(hash.node_type, "Hash");
+        assert_eq!(hash.properties["algorithm"], serde_json::json!("keccak256"));
+        assert_eq!(hash.inputs[0].id, "input");
+        assert_eq!(migrated.connections[0].target_port, "input");
+
+        let assert = migrated.nodes.iter().find(|n| n.id == assert_id).unwrap();
+        assert_eq!(assert.node_type, "Require");
+        assert_eq!(assert.properties["message"], serde_json::json!("balance too low"));
+        assert!(assert.properties.contains_key("error"));
+    }
+
+    #[test]
+    fn test_current_graph_is_untouched() {
+        let mut graph = VisualGraph::new("current");
+        graph.add_node(VisualNode::new(Uuid::new_v4(), "Hash", Position::new(0.0, 0.0)));
+        let (_, report) = migrate_graph(&graph);
+        assert!(report.is_empty());
+        assert!(deprecation_for("Hash").is_none());
+        assert!(deprecation_for("Sha256").is_some());
+    }
+}
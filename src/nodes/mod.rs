@@ -1,15 +1,23 @@
 //! Node system for Canvas Contracts
 
+mod assets;
+pub mod custom;
 mod definitions;
 mod implementations;
+mod migration;
 
 use crate::{
     error::{CanvasError, CanvasResult},
     types::{ExecutionContext, NodeResult, PortId, ValueType},
 };
 
-pub use definitions::NodeDefinition;
-pub use implementations::Node;
+pub use assets::{
+    node_appearances, sniff_media_type, AssetRef, AssetResponse, AssetServer, AssetStore, NodeAppearance, ThemeHints,
+    APPEARANCE_ROUTE, ASSETS_DIR, ASSET_ROUTE, MAX_ASSET_BYTES,
+};
+pub use definitions::{builtin_node_definitions, deprecated_node_definitions, Deprecation, NodeDefinition};
+pub use migration::{deprecation_for, load_graph, migrate_graph, MigrationReport, NodeMigration};
+pub use implementations::{decode_hex, encode_hex, CallHandler, CatchNode, ForEachNode, Node, TryCallNode};
 
 /// Node context for execution
 pub struct NodeContext {
@@ -63,6 +71,25 @@ impl NodeRegistry {
         }
     }
 
+    /// Registry of the built-in node definitions
+    pub fn with_builtins() -> Self {
+        let mut registry = Self::new();
+        for definition in builtin_node_definitions() {
+            registry.register_node(definition);
+        }
+        registry
+    }
+
+    /// Built-in definitions, replaced by those of the node catalog installed
+    /// with `self-update` when there is one
+    pub fn load(config: &crate::config::Config) -> CanvasResult<Self> {
+        let mut registry = Self::with_builtins();
+        if let Some(catalog) = crate::update::NodeCatalog::installed(config)? {
+            catalog.register_into(&mut registry);
+        }
+        Ok(registry)
+    }
+
     pub fn register_node(&mut self, definition: NodeDefinition) {
         self.definitions.insert(definition.id.clone(), definition);
     }
@@ -71,6 +98,13 @@ impl NodeRegistry {
         self.definitions.get(node_type)
     }
 
+    /// Every registered definition, sorted by node type
+    pub fn definitions(&self) -> Vec<&NodeDefinition> {
+        let mut definitions: Vec<&NodeDefinition> = self.definitions.values().collect();
+        definitions.sort_by(|a, b| a.id.cmp(&b.id));
+        definitions
+    }
+
     pub fn list_node_types(&self) -> Vec<String> {
         self.definitions.keys().cloned().collect()
     }
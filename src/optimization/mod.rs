@@ -2,25 +2,36 @@
 
 use crate::{
     error::CanvasResult,
-    types::{Graph, NodeId, NodeType},
+    nodes::builtin_node_definitions,
+    types::{NodeId, NodeType, VisualGraph, VisualNode},
     config::Config,
 };
 
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 /// Performance optimizer for production contracts
 pub struct PerformanceOptimizer {
     config: Config,
     optimization_passes: Vec<Box<dyn OptimizationPass>>,
-    cache: HashMap<String, OptimizationResult>,
+    cache: HashMap<String, OptimizedGraph>,
 }
 
 /// Optimization pass trait
 pub trait OptimizationPass: Send + Sync {
     fn name(&self) -> &str;
-    fn optimize(&self, graph: &Graph) -> CanvasResult<OptimizationResult>;
-    fn is_applicable(&self, graph: &Graph) -> bool;
+    /// Analyze `graph`. Passes that apply their changes return the rewritten
+    /// graph in [`OptimizationResult::graph`]; analysis-only passes leave it
+    /// empty.
+    fn optimize(&self, graph: &VisualGraph) -> CanvasResult<OptimizationResult>;
+    fn is_applicable(&self, graph: &VisualGraph) -> bool;
+}
+
+/// A graph after every applicable pass, with what each pass did
+#[derive(Debug, Clone)]
+pub struct OptimizedGraph {
+    pub graph: VisualGraph,
+    pub results: Vec<OptimizationResult>,
 }
 
 /// Optimization result
@@ -35,6 +46,9 @@ pub struct OptimizationResult {
     pub size_savings: usize,
     pub changes: Vec<OptimizationChange>,
     pub warnings: Vec<String>,
+    /// The graph with the pass's changes applied, when it rewrites
+    #[serde(skip)]
+    pub graph: Option<VisualGraph>,
 }
 
 /// Optimization change
@@ -217,24 +231,30 @@ impl PerformanceOptimizer {
         self.optimization_passes.push(pass);
     }
 
-    /// Optimize a graph
-    pub fn optimize(&mut self, graph: &Graph) -> CanvasResult<Vec<OptimizationResult>> {
-        let mut results = Vec::new();
+    /// Optimize a graph.
+    ///
+    /// Passes run in registration order, each on the graph the previous
+    /// rewriting pass produced, so the returned graph carries every applied
+    /// change.
+    pub fn optimize(&mut self, graph: &VisualGraph) -> CanvasResult<OptimizedGraph> {
         let graph_hash = self.compute_graph_hash(graph);
 
         // Check cache first
-        if let Some(cached_result) = self.cache.get(&graph_hash) {
-            results.push(cached_result.clone());
-            return Ok(results);
+        if let Some(cached) = self.cache.get(&graph_hash) {
+            return Ok(cached.clone());
         }
 
         // Apply optimization passes
+        let mut current = graph.clone();
+        let mut results = Vec::new();
         for pass in &self.optimization_passes {
-            if pass.is_applicable(graph) {
-                match pass.optimize(graph) {
-                    Ok(result) => {
-                        results.push(result.clone());
-                        self.cache.insert(graph_hash.clone(), result);
+            if pass.is_applicable(&current) {
+                match pass.optimize(&current) {
+                    Ok(mut result) => {
+                        if let Some(rewritten) = result.graph.take() {
+                            current = rewritten;
+                        }
+                        results.push(result);
                     }
                     Err(e) => {
                         log::warn!("Optimization pass {} failed: {}", pass.name(), e);
@@ -243,7 +263,12 @@ impl PerformanceOptimizer {
             }
         }
 
-        Ok(results)
+        let optimized = OptimizedGraph {
+            graph: current,
+            results,
+        };
+        self.cache.insert(graph_hash, optimized.clone());
+        Ok(optimized)
     }
 
     /// Get optimization summary
@@ -266,13 +291,14 @@ impl PerformanceOptimizer {
     }
 
     /// Compute graph hash for caching
-    fn compute_graph_hash(&self, graph: &Graph) -> String {
+    fn compute_graph_hash(&self, graph: &VisualGraph) -> String {
         use std::collections::hash_map::DefaultHasher;
         use std::hash::{Hash, Hasher};
 
         let mut hasher = DefaultHasher::new();
-        graph.get_nodes().hash(&mut hasher);
-        graph.get_edges().hash(&mut hasher);
+        serde_json::to_vec(&(&graph.nodes, &graph.connections))
+            .unwrap_or_default()
+            .hash(&mut hasher);
         format!("{:x}", hasher.finish())
     }
 
@@ -297,42 +323,44 @@ impl OptimizationPass for DeadCodeEliminationPass {
         "dead_code_elimination"
     }
 
-    fn optimize(&self, graph: &Graph) -> CanvasResult<OptimizationResult> {
-        let nodes = graph.get_nodes();
-        let edges = graph.get_edges();
-        
-        let mut reachable_nodes = std::collections::HashSet::new();
-        let mut to_visit = Vec::new();
-
-        // Find start nodes
-        for node in nodes {
-            if node.node_type == NodeType::Start {
-                to_visit.push(node.id.clone());
-                reachable_nodes.insert(node.id.clone());
-            }
-        }
+    fn optimize(&self, graph: &VisualGraph) -> CanvasResult<OptimizationResult> {
+        let node_ids: Vec<NodeId> = graph.nodes.iter().map(|n| n.id).collect();
+        let edge_pairs = edge_pairs(graph);
+        let starts: Vec<NodeId> = graph
+            .nodes
+            .iter()
+            .filter(|n| matches!(n.node_type.as_str(), "Start" | "Init"))
+            .map(|n| n.id)
+            .collect();
 
-        // BFS to find reachable nodes
-        while let Some(node_id) = to_visit.pop() {
-            for edge in edges {
-                if edge.source == node_id && !reachable_nodes.contains(&edge.target) {
-                    reachable_nodes.insert(edge.target.clone());
-                    to_visit.push(edge.target.clone());
-                }
-            }
+        // Without an entry point every node looks unreachable; leave the graph alone
+        if starts.is_empty() {
+            return Ok(OptimizationResult {
+                name: "Dead Code Elimination".to_string(),
+                original_gas: 0,
+                optimized_gas: 0,
+                gas_savings: 0,
+                original_size: 0,
+                optimized_size: 0,
+                size_savings: 0,
+                changes: Vec::new(),
+                warnings: vec!["Graph has no Start node; nothing removed".to_string()],
+                graph: None,
+            });
         }
 
-        // Find unreachable nodes
-        let unreachable_nodes: Vec<_> = nodes
-            .iter()
-            .filter(|node| !reachable_nodes.contains(&node.id))
-            .map(|node| node.id.clone())
-            .collect();
+        let unreachable_nodes = unreachable_nodes(&starts, &node_ids, &edge_pairs);
 
         let gas_savings = unreachable_nodes.len() as u64 * 100; // Estimate gas savings
         let size_savings = unreachable_nodes.len() * 50; // Estimate size savings
 
+        let mut rewritten = None;
         let changes = if !unreachable_nodes.is_empty() {
+            let mut optimized = graph.clone();
+            for node_id in &unreachable_nodes {
+                optimized.remove_node(*node_id)?;
+            }
+            rewritten = Some(optimized);
             vec![OptimizationChange {
                 change_type: ChangeType::DeadCodeElimination,
                 description: format!("Remove {} unreachable nodes", unreachable_nodes.len()),
@@ -353,39 +381,124 @@ impl OptimizationPass for DeadCodeEliminationPass {
             size_savings,
             changes,
             warnings: Vec::new(),
+            graph: rewritten,
         })
     }
 
-    fn is_applicable(&self, graph: &Graph) -> bool {
+    fn is_applicable(&self, graph: &VisualGraph) -> bool {
         // Always applicable
         true
     }
 }
 
+/// Source and target of every connection
+fn edge_pairs(graph: &VisualGraph) -> Vec<(NodeId, NodeId)> {
+    graph.connections.iter().map(|c| (c.source_node, c.target_node)).collect()
+}
+
+/// Broad kind of each node, from the category of its built-in definition.
+/// Entry and exit nodes are [`NodeType::Control`]; custom and unknown node
+/// types are [`NodeType::Custom`].
+fn node_kinds(graph: &VisualGraph) -> HashMap<NodeId, NodeType> {
+    let categories: HashMap<String, String> = builtin_node_definitions()
+        .into_iter()
+        .map(|definition| (definition.id, definition.category))
+        .collect();
+    graph
+        .nodes
+        .iter()
+        .map(|node| {
+            let kind = match categories.get(&node.node_type).map(String::as_str) {
+                _ if node.node_type == "TryCall" => NodeType::External,
+                Some("Arithmetic") => NodeType::Arithmetic,
+                Some("Logic" | "Strings" | "Collections") => NodeType::Logic,
+                Some("State" | "Events") => NodeType::State,
+                Some("Cryptographic") => NodeType::Cryptographic,
+                Some("Control Flow") => NodeType::Control,
+                _ => NodeType::Custom,
+            };
+            (node.id, kind)
+        })
+        .collect()
+}
+
+/// Nodes that are dead, in `nodes` order. A node is live if it is reachable
+/// from one of `starts` along `edges`, or if it transitively feeds a live node:
+/// constants, storage reads and other data sources have no incoming flow but
+/// supply the inputs of the nodes that run.
+pub fn unreachable_nodes(starts: &[NodeId], nodes: &[NodeId], edges: &[(NodeId, NodeId)]) -> Vec<NodeId> {
+    let mut successors: HashMap<&NodeId, Vec<&NodeId>> = HashMap::new();
+    let mut predecessors: HashMap<&NodeId, Vec<&NodeId>> = HashMap::new();
+    for (source, target) in edges {
+        successors.entry(source).or_default().push(target);
+        predecessors.entry(target).or_default().push(source);
+    }
+
+    let mut live: HashSet<&NodeId> = starts.iter().collect();
+    let mut to_visit: Vec<&NodeId> = starts.iter().collect();
+    while let Some(node_id) = to_visit.pop() {
+        for &target in successors.get(node_id).into_iter().flatten() {
+            if live.insert(target) {
+                to_visit.push(target);
+            }
+        }
+    }
+
+    // Everything feeding a live node is live too
+    let mut to_visit: Vec<&NodeId> = live.iter().copied().collect();
+    while let Some(node_id) = to_visit.pop() {
+        for &source in predecessors.get(node_id).into_iter().flatten() {
+            if live.insert(source) {
+                to_visit.push(source);
+            }
+        }
+    }
+    nodes.iter().filter(|id| !live.contains(id)).cloned().collect()
+}
+
 impl OptimizationPass for ConstantFoldingPass {
     fn name(&self) -> &str {
         "constant_folding"
     }
 
-    fn optimize(&self, graph: &Graph) -> CanvasResult<OptimizationResult> {
-        let nodes = graph.get_nodes();
+    fn optimize(&self, graph: &VisualGraph) -> CanvasResult<OptimizationResult> {
         let mut changes = Vec::new();
         let mut folded_nodes = Vec::new();
-
-        // Find nodes with constant inputs that can be folded
-        for node in nodes {
-            if node.node_type == NodeType::Arithmetic {
-                // Check if all inputs are constants
-                let inputs = graph.get_node_inputs(&node.id)?;
-                if inputs.iter().all(|(_, value)| value.is_number()) {
-                    folded_nodes.push(node.id.clone());
+        let mut optimized = graph.clone();
+
+        // Replace arithmetic nodes whose inputs are all integer literals with
+        // the literal they evaluate to, one at a time so chains of constant
+        // nodes collapse as each fold feeds the next
+        let warnings = loop {
+            let mut folded = None;
+            let mut unfoldable = Vec::new();
+            for node in &optimized.nodes {
+                let (Some(operation), Some(values)) = (fold_operation(&node.node_type), constant_inputs(&optimized, node))
+                else {
+                    continue;
+                };
+                match fold_constant(operation, &values) {
+                    Some(literal) => {
+                        folded = Some((node.id, literal));
+                        break;
+                    }
+                    None => unfoldable.push(format!(
+                        "Node {} has constant inputs but '{}' cannot be folded (overflow or division by zero)",
+                        node.id, operation
+                    )),
                 }
             }
-        }
+            let Some((node_id, literal)) = folded else {
+                break unfoldable;
+            };
+            optimized.replace_with_literal(node_id, literal)?;
+            folded_nodes.push(node_id);
+        };
 
         let gas_savings = folded_nodes.len() as u64 * 10;
         let size_savings = folded_nodes.len() * 20;
 
+        let rewritten = (!folded_nodes.is_empty()).then_some(optimized);
         if !folded_nodes.is_empty() {
             changes.push(OptimizationChange {
                 change_type: ChangeType::ConstantFolding,
@@ -404,81 +517,312 @@ impl OptimizationPass for ConstantFoldingPass {
             optimized_size: 0,
             size_savings,
             changes,
-            warnings: Vec::new(),
+            warnings,
+            graph: rewritten,
         })
     }
 
-    fn is_applicable(&self, graph: &Graph) -> bool {
-        // Check if there are arithmetic nodes
-        graph.get_nodes().iter().any(|n| n.node_type == NodeType::Arithmetic)
+    fn is_applicable(&self, graph: &VisualGraph) -> bool {
+        graph.nodes.iter().any(|n| fold_operation(&n.node_type).is_some())
+    }
+}
+
+/// The [`fold_constant`] operation of an arithmetic node type
+fn fold_operation(node_type: &str) -> Option<&'static str> {
+    match node_type {
+        "Add" => Some("add"),
+        "Subtract" => Some("sub"),
+        "Multiply" => Some("mul"),
+        "Divide" => Some("div"),
+        _ => None,
     }
 }
 
+/// The `a` and `b` inputs of a binary node, when neither is connected and
+/// both hold integer literals. Literals may be JSON numbers or integer
+/// strings, as the compiler reads them.
+///
+/// Nodes feeding End or EmitEvent are never constant here: those nodes only
+/// read the inputs that are connected, so a literal left in their place
+/// would be dropped.
+fn constant_inputs(graph: &VisualGraph, node: &VisualNode) -> Option<Vec<serde_json::Value>> {
+    let feeds_connected_only = graph.connections.iter().filter(|c| c.source_node == node.id).any(|c| {
+        graph
+            .get_node(c.target_node)
+            .is_some_and(|target| matches!(target.node_type.as_str(), "End" | "EmitEvent"))
+    });
+    if feeds_connected_only {
+        return None;
+    }
+    ["a", "b"]
+        .iter()
+        .map(|port| {
+            if graph.connections.iter().any(|c| c.target_node == node.id && c.target_port == *port) {
+                return None;
+            }
+            match node.properties.get(*port)? {
+                serde_json::Value::Number(n) => n.as_i64(),
+                serde_json::Value::String(text) => text.parse().ok(),
+                _ => None,
+            }
+            .map(serde_json::Value::from)
+        })
+        .collect()
+}
+
+/// Evaluate an arithmetic operation over constant inputs, in input order.
+///
+/// Integers use checked arithmetic and any other number is a float; `None`
+/// leaves the node to be evaluated at runtime, e.g. on overflow or division
+/// by zero.
+pub fn fold_constant(operation: &str, inputs: &[serde_json::Value]) -> Option<serde_json::Value> {
+    let (first, rest) = inputs.split_first()?;
+    if inputs.iter().all(|v| v.is_i64()) {
+        let mut acc = first.as_i64()?;
+        for value in rest {
+            let value = value.as_i64()?;
+            acc = match operation {
+                "add" => acc.checked_add(value)?,
+                "sub" => acc.checked_sub(value)?,
+                "mul" => acc.checked_mul(value)?,
+                "div" => acc.checked_div(value)?,
+                "mod" => acc.checked_rem(value)?,
+                "min" => acc.min(value),
+                "max" => acc.max(value),
+                _ => return None,
+            };
+        }
+        return Some(acc.into());
+    }
+
+    let mut acc = first.as_f64()?;
+    for value in rest {
+        let value = value.as_f64()?;
+        acc = match operation {
+            "add" => acc + value,
+            "sub" => acc - value,
+            "mul" => acc * value,
+            "div" if value != 0.0 => acc / value,
+            "min" => acc.min(value),
+            "max" => acc.max(value),
+            _ => return None,
+        };
+    }
+    serde_json::Number::from_f64(acc).map(serde_json::Value::Number)
+}
+
 impl OptimizationPass for LoopOptimizationPass {
     fn name(&self) -> &str {
         "loop_optimization"
     }
 
-    fn optimize(&self, graph: &Graph) -> CanvasResult<OptimizationResult> {
-        let nodes = graph.get_nodes();
-        let edges = graph.get_edges();
-        let mut changes = Vec::new();
-        let mut optimized_loops = Vec::new();
-
-        // Find loops in the graph
-        let loops = self.find_loops(nodes, edges)?;
-        
-        for loop_nodes in loops {
-            // Check if loop can be optimized
-            if self.can_optimize_loop(&loop_nodes, graph)? {
-                optimized_loops.extend(loop_nodes);
-            }
-        }
+    fn optimize(&self, graph: &VisualGraph) -> CanvasResult<OptimizationResult> {
+        let mut warnings = Vec::new();
 
-        let gas_savings = optimized_loops.len() as u64 * 50;
-        let size_savings = optimized_loops.len() * 30;
+        let node_ids: Vec<NodeId> = graph.nodes.iter().map(|n| n.id).collect();
+        let edge_pairs = edge_pairs(graph);
+        let hoistable: HashSet<NodeId> = node_kinds(graph)
+            .into_iter()
+            .filter(|(_, kind)| self.is_hoistable_type(kind))
+            .map(|(id, _)| id)
+            .collect();
 
-        if !optimized_loops.is_empty() {
-            changes.push(OptimizationChange {
-                change_type: ChangeType::LoopOptimization,
-                description: format!("Optimize {} loops", optimized_loops.len() / 3), // Estimate loop count
-                nodes_affected: optimized_loops,
-                impact: OptimizationImpact::High,
-            });
+        // Loops only run in the simulator (ForEach), and nothing hoists out of
+        // them yet, so invariants are reported as advice with no savings
+        for graph_loop in find_loops(&node_ids, &edge_pairs) {
+            let invariants = loop_invariants(&graph_loop, &node_ids, &edge_pairs, |id| hoistable.contains(id));
+            let header = graph_loop.headers.first().cloned().unwrap_or(graph_loop.nodes[0]);
+            if !invariants.is_empty() {
+                warnings.push(format!(
+                    "Loop at node {} ({} nodes) re-evaluates {} loop-invariant node(s) on every iteration: {}",
+                    header,
+                    graph_loop.nodes.len(),
+                    invariants.len(),
+                    invariants.iter().map(|id| id.to_string()).collect::<Vec<_>>().join(", ")
+                ));
+            }
         }
 
         Ok(OptimizationResult {
             name: "Loop Optimization".to_string(),
             original_gas: 0,
             optimized_gas: 0,
-            gas_savings,
+            gas_savings: 0,
             original_size: 0,
             optimized_size: 0,
-            size_savings,
-            changes,
-            warnings: Vec::new(),
+            size_savings: 0,
+            changes: Vec::new(),
+            warnings,
+            graph: None,
         })
     }
 
-    fn is_applicable(&self, graph: &Graph) -> bool {
-        // Check if there are control flow nodes that might form loops
-        let control_nodes = graph.get_nodes().iter()
-            .filter(|n| n.node_type == NodeType::Control)
-            .count();
-        control_nodes > 2
+    fn is_applicable(&self, graph: &VisualGraph) -> bool {
+        // Loops need at least one edge leading back into the graph
+        !graph.connections.is_empty()
     }
 }
 
 impl LoopOptimizationPass {
-    fn find_loops(&self, nodes: &[crate::types::Node], edges: &[crate::types::Edge]) -> CanvasResult<Vec<Vec<NodeId>>> {
-        // TODO: Implement actual loop detection using DFS
-        Ok(Vec::new())
+    /// Whether a node of this type computes the same result for the same
+    /// inputs, without side effects
+    fn is_hoistable_type(&self, node_type: &NodeType) -> bool {
+        matches!(node_type, NodeType::Arithmetic | NodeType::Logic | NodeType::Cryptographic)
     }
+}
+
+/// A cycle in a graph: one strongly connected component
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GraphLoop {
+    /// Nodes of the loop, in graph order
+    pub nodes: Vec<NodeId>,
+    /// Edges jumping back to a node earlier on the same path
+    pub back_edges: Vec<(NodeId, NodeId)>,
+    /// Targets of the back edges, where each iteration starts
+    pub headers: Vec<NodeId>,
+}
+
+/// Find the loops of a graph with Tarjan's strongly connected components
+/// algorithm. A component is a loop if it has more than one node or a node
+/// with an edge to itself. Loops are returned in graph order.
+pub fn find_loops(nodes: &[NodeId], edges: &[(NodeId, NodeId)]) -> Vec<GraphLoop> {
+    let index_of: HashMap<&NodeId, usize> = nodes.iter().enumerate().map(|(i, id)| (id, i)).collect();
+    let mut successors = vec![Vec::new(); nodes.len()];
+    for (source, target) in edges {
+        if let (Some(&s), Some(&t)) = (index_of.get(source), index_of.get(target)) {
+            successors[s].push(t);
+        }
+    }
+
+    let mut tarjan = Tarjan {
+        successors: &successors,
+        index: vec![None; nodes.len()],
+        lowlink: vec![0; nodes.len()],
+        on_stack: vec![false; nodes.len()],
+        on_path: vec![false; nodes.len()],
+        stack: Vec::new(),
+        next_index: 0,
+        back_edges: Vec::new(),
+        components: Vec::new(),
+    };
+    for node in 0..nodes.len() {
+        if tarjan.index[node].is_none() {
+            tarjan.visit(node);
+        }
+    }
+
+    let mut component_of = vec![usize::MAX; nodes.len()];
+    for (c, component) in tarjan.components.iter().enumerate() {
+        for &node in component {
+            component_of[node] = c;
+        }
+    }
+
+    let mut loops: Vec<GraphLoop> = tarjan
+        .components
+        .iter()
+        .enumerate()
+        .filter(|(_, component)| component.len() > 1 || successors[component[0]].contains(&component[0]))
+        .map(|(c, component)| {
+            let mut members = component.clone();
+            members.sort_unstable();
+            let back_edges: Vec<(usize, usize)> = tarjan
+                .back_edges
+                .iter()
+                .filter(|(s, t)| component_of[*s] == c && component_of[*t] == c)
+                .copied()
+                .collect();
+            let mut headers: Vec<usize> = back_edges.iter().map(|(_, t)| *t).collect();
+            headers.sort_unstable();
+            headers.dedup();
+            GraphLoop {
+                nodes: members.iter().map(|&i| nodes[i]).collect(),
+                back_edges: back_edges.iter().map(|&(s, t)| (nodes[s], nodes[t])).collect(),
+                headers: headers.iter().map(|&i| nodes[i]).collect(),
+            }
+        })
+        .collect();
+    loops.sort_by_key(|l| index_of[&l.nodes[0]]);
+    loops
+}
+
+struct Tarjan<'a> {
+    successors: &'a [Vec<usize>],
+    index: Vec<Option<usize>>,
+    lowlink: Vec<usize>,
+    on_stack: Vec<bool>,
+    /// Nodes on the current DFS path
+    on_path: Vec<bool>,
+    stack: Vec<usize>,
+    next_index: usize,
+    back_edges: Vec<(usize, usize)>,
+    components: Vec<Vec<usize>>,
+}
+
+impl Tarjan<'_> {
+    fn visit(&mut self, node: usize) {
+        self.index[node] = Some(self.next_index);
+        self.lowlink[node] = self.next_index;
+        self.next_index += 1;
+        self.stack.push(node);
+        self.on_stack[node] = true;
+        self.on_path[node] = true;
+
+        for &next in &self.successors[node] {
+            match self.index[next] {
+                None => {
+                    self.visit(next);
+                    self.lowlink[node] = self.lowlink[node].min(self.lowlink[next]);
+                }
+                Some(next_index) if self.on_stack[next] => {
+                    if self.on_path[next] {
+                        self.back_edges.push((node, next));
+                    }
+                    self.lowlink[node] = self.lowlink[node].min(next_index);
+                }
+                Some(_) => {}
+            }
+        }
+        self.on_path[node] = false;
+
+        if Some(self.lowlink[node]) == self.index[node] {
+            let mut component = Vec::new();
+            while let Some(member) = self.stack.pop() {
+                self.on_stack[member] = false;
+                component.push(member);
+                if member == node {
+                    break;
+                }
+            }
+            self.components.push(component);
+        }
+    }
+}
 
-    fn can_optimize_loop(&self, loop_nodes: &[NodeId], graph: &Graph) -> CanvasResult<bool> {
-        // TODO: Implement loop optimization analysis
-        Ok(false)
+/// Computations `graph_loop` re-evaluates on every iteration although their
+/// result never changes.
+///
+/// Pure nodes are evaluated whenever a consumer reads their output, so a pure
+/// node feeding a loop, directly or through other pure nodes, runs once per
+/// iteration. Nodes on the cycle itself carry values between iterations and
+/// are never invariant. Returned in the order of `nodes`.
+pub fn loop_invariants(
+    graph_loop: &VisualGraphLoop,
+    nodes: &[NodeId],
+    edges: &[(NodeId, NodeId)],
+    is_pure: impl Fn(&NodeId) -> bool,
+) -> Vec<NodeId> {
+    let members: HashSet<&NodeId> = graph_loop.nodes.iter().collect();
+    let mut invariant: HashSet<NodeId> = HashSet::new();
+    let mut to_visit: Vec<&NodeId> = graph_loop.nodes.iter().collect();
+    while let Some(consumer) = to_visit.pop() {
+        for (source, _) in edges.iter().filter(|(_, target)| target == consumer) {
+            if !members.contains(source) && is_pure(source) && invariant.insert(source.clone()) {
+                to_visit.push(source);
+            }
+        }
     }
+    nodes.iter().filter(|id| invariant.contains(*id)).cloned().collect()
 }
 
 impl OptimizationPass for MemoryOptimizationPass {
@@ -486,14 +830,14 @@ impl OptimizationPass for MemoryOptimizationPass {
         "memory_optimization"
     }
 
-    fn optimize(&self, graph: &Graph) -> CanvasResult<OptimizationResult> {
-        let nodes = graph.get_nodes();
+    fn optimize(&self, graph: &VisualGraph) -> CanvasResult<OptimizationResult> {
+        let kinds = node_kinds(graph);
         let mut changes = Vec::new();
         let mut memory_optimized_nodes = Vec::new();
 
         // Find memory-intensive operations
-        for node in nodes {
-            if node.node_type == NodeType::State {
+        for node in &graph.nodes {
+            if kinds[&node.id] == NodeType::State {
                 // Storage operations are memory-intensive
                 memory_optimized_nodes.push(node.id.clone());
             }
@@ -521,12 +865,13 @@ impl OptimizationPass for MemoryOptimizationPass {
             size_savings,
             changes,
             warnings: Vec::new(),
+            graph: None,
         })
     }
 
-    fn is_applicable(&self, graph: &Graph) -> bool {
+    fn is_applicable(&self, graph: &VisualGraph) -> bool {
         // Check if there are state operations
-        graph.get_nodes().iter().any(|n| n.node_type == NodeType::State)
+        node_kinds(graph).values().any(|kind| *kind == NodeType::State)
     }
 }
 
@@ -535,16 +880,14 @@ impl OptimizationPass for CacheOptimizationPass {
         "cache_optimization"
     }
 
-    fn optimize(&self, graph: &Graph) -> CanvasResult<OptimizationResult> {
-        let nodes = graph.get_nodes();
+    fn optimize(&self, graph: &VisualGraph) -> CanvasResult<OptimizationResult> {
         let mut changes = Vec::new();
         let mut cache_optimized_nodes = Vec::new();
 
         // Find repeated operations that can be cached
         let mut operation_counts = HashMap::new();
-        for node in nodes {
-            let key = format!("{:?}", node.node_type);
-            *operation_counts.entry(key).or_insert(0) += 1;
+        for node in &graph.nodes {
+            *operation_counts.entry(node.node_type.as_str()).or_insert(0) += 1;
         }
 
         for (operation, count) in operation_counts {
@@ -576,15 +919,15 @@ impl OptimizationPass for CacheOptimizationPass {
             size_savings,
             changes,
             warnings: Vec::new(),
+            graph: None,
         })
     }
 
-    fn is_applicable(&self, graph: &Graph) -> bool {
+    fn is_applicable(&self, graph: &VisualGraph) -> bool {
         // Check if there are repeated operations
         let mut operation_counts = HashMap::new();
-        for node in graph.get_nodes() {
-            let key = format!("{:?}", node.node_type);
-            *operation_counts.entry(key).or_insert(0) += 1;
+        for node in &graph.nodes {
+            *operation_counts.entry(node.node_type.as_str()).or_insert(0) += 1;
         }
         operation_counts.values().any(|&count| count > 1)
     }
@@ -599,20 +942,17 @@ impl ParallelExecutionOptimizer {
     }
 
     /// Generate parallel execution plan
-    pub fn generate_plan(&self, graph: &Graph) -> CanvasResult<ParallelExecutionPlan> {
-        let nodes = graph.get_nodes();
-        let edges = graph.get_edges();
-        
+    pub fn generate_plan(&self, graph: &VisualGraph) -> CanvasResult<ParallelExecutionPlan> {
         // Build dependency graph
         let mut dependencies = HashMap::new();
-        for edge in edges {
-            dependencies.entry(edge.target.clone())
+        for connection in &graph.connections {
+            dependencies.entry(connection.target_node)
                 .or_insert_with(Vec::new)
-                .push(edge.source.clone());
+                .push(connection.source_node);
         }
 
         // Topological sort to find execution stages
-        let stages = self.topological_sort(nodes, &dependencies)?;
+        let stages = self.topological_sort(&graph.nodes, &dependencies)?;
         
         // Calculate parallelism metrics
         let estimated_parallelism = self.calculate_parallelism(&stages);
@@ -627,7 +967,7 @@ impl ParallelExecutionOptimizer {
     }
 
     /// Perform topological sort
-    fn topological_sort(&self, nodes: &[crate::types::Node], dependencies: &HashMap<NodeId, Vec<NodeId>>) -> CanvasResult<Vec<ExecutionStage>> {
+    fn topological_sort(&self, nodes: &[VisualNode], dependencies: &HashMap<NodeId, Vec<NodeId>>) -> CanvasResult<Vec<ExecutionStage>> {
         // TODO: Implement actual topological sort
         let mut stages = Vec::new();
         
@@ -636,7 +976,7 @@ impl ParallelExecutionOptimizer {
         for node in nodes {
             stages.push(ExecutionStage {
                 stage_id,
-                nodes: vec![node.id.clone()],
+                nodes: vec![node.id],
                 estimated_duration: 100, // Mock duration
                 dependencies: Vec::new(),
             });
@@ -684,7 +1024,7 @@ impl ResourceUsageAnalyzer {
     }
 
     /// Analyze resource usage
-    pub fn analyze(&self, graph: &Graph) -> CanvasResult<ResourceUsageReport> {
+    pub fn analyze(&self, graph: &VisualGraph) -> CanvasResult<ResourceUsageReport> {
         let memory_usage = self.analyze_memory_usage(graph)?;
         let cpu_usage = self.analyze_cpu_usage(graph)?;
         let gas_usage = self.analyze_gas_usage(graph)?;
@@ -701,20 +1041,21 @@ impl ResourceUsageAnalyzer {
     }
 
     /// Analyze memory usage
-    fn analyze_memory_usage(&self, graph: &Graph) -> CanvasResult<MemoryUsage> {
-        let nodes = graph.get_nodes();
+    fn analyze_memory_usage(&self, graph: &VisualGraph) -> CanvasResult<MemoryUsage> {
+        let nodes = &graph.nodes;
+        let kinds = node_kinds(graph);
         let mut peak_memory = 0u64;
         let mut total_memory = 0u64;
         let mut memory_leaks = Vec::new();
         let mut optimization_suggestions = Vec::new();
 
         for node in nodes {
-            let node_memory = self.estimate_node_memory_usage(node);
+            let node_memory = self.estimate_node_memory_usage(node, &kinds[&node.id]);
             peak_memory = peak_memory.max(node_memory);
             total_memory += node_memory;
 
             // Check for potential memory leaks
-            if node.node_type == NodeType::State {
+            if kinds[&node.id] == NodeType::State {
                 memory_leaks.push(format!("Storage operation in node {} may cause memory growth", node.id));
             }
         }
@@ -743,14 +1084,15 @@ impl ResourceUsageAnalyzer {
     }
 
     /// Analyze CPU usage
-    fn analyze_cpu_usage(&self, graph: &Graph) -> CanvasResult<CpuUsage> {
-        let nodes = graph.get_nodes();
+    fn analyze_cpu_usage(&self, graph: &VisualGraph) -> CanvasResult<CpuUsage> {
+        let nodes = &graph.nodes;
+        let kinds = node_kinds(graph);
         let mut peak_cpu = 0.0;
         let mut total_cpu = 0.0;
         let mut cpu_intensive_operations = Vec::new();
 
         for node in nodes {
-            let node_cpu = self.estimate_node_cpu_usage(node);
+            let node_cpu = self.estimate_node_cpu_usage(node, &kinds[&node.id]);
             peak_cpu = peak_cpu.max(node_cpu);
             total_cpu += node_cpu;
 
@@ -780,18 +1122,18 @@ impl ResourceUsageAnalyzer {
     }
 
     /// Analyze gas usage
-    fn analyze_gas_usage(&self, graph: &Graph) -> CanvasResult<GasUsage> {
-        let nodes = graph.get_nodes();
+    fn analyze_gas_usage(&self, graph: &VisualGraph) -> CanvasResult<GasUsage> {
+        let nodes = &graph.nodes;
+        let kinds = node_kinds(graph);
         let mut total_gas = 0u64;
         let mut gas_per_operation = HashMap::new();
         let mut expensive_operations = Vec::new();
 
         for node in nodes {
-            let node_gas = self.estimate_node_gas_usage(node);
+            let node_gas = self.estimate_node_gas_usage(node, &kinds[&node.id]);
             total_gas += node_gas;
             
-            let operation_type = format!("{:?}", node.node_type);
-            gas_per_operation.insert(operation_type.clone(), node_gas);
+            gas_per_operation.insert(node.node_type.clone(), node_gas);
 
             if node_gas > 1000 {
                 expensive_operations.push(format!("Expensive operation in node {}: {} gas", node.id, node_gas));
@@ -813,13 +1155,12 @@ impl ResourceUsageAnalyzer {
     }
 
     /// Analyze network usage
-    fn analyze_network_usage(&self, graph: &Graph) -> CanvasResult<NetworkUsage> {
-        let nodes = graph.get_nodes();
+    fn analyze_network_usage(&self, graph: &VisualGraph) -> CanvasResult<NetworkUsage> {
         let mut total_bandwidth = 0u64;
         let mut requests_per_second = 0.0;
 
-        for node in nodes {
-            if node.node_type == NodeType::External {
+        for kind in node_kinds(graph).values() {
+            if *kind == NodeType::External {
                 total_bandwidth += 1024; // Estimate 1KB per external call
                 requests_per_second += 0.1; // Estimate 0.1 requests per second
             }
@@ -843,7 +1184,7 @@ impl ResourceUsageAnalyzer {
     /// Generate recommendations
     fn generate_recommendations(
         &self,
-        graph: &Graph,
+        graph: &VisualGraph,
         memory_usage: &MemoryUsage,
         cpu_usage: &CpuUsage,
         gas_usage: &GasUsage,
@@ -899,60 +1240,138 @@ impl ResourceUsageAnalyzer {
     }
 
     /// Estimate node memory usage
-    fn estimate_node_memory_usage(&self, node: &crate::types::Node) -> u64 {
-        match node.node_type {
+    fn estimate_node_memory_usage(&self, node: &VisualNode, kind: &NodeType) -> u64 {
+        match kind {
+            _ if is_entry_or_exit(node) => 256, // Start and End nodes use moderate memory
             NodeType::State => 1024, // Storage operations use more memory
             NodeType::External => 512, // External calls use moderate memory
             NodeType::Arithmetic => 64, // Arithmetic operations use little memory
             NodeType::Logic => 32, // Logic operations use very little memory
             NodeType::Control => 128, // Control flow uses some memory
-            NodeType::Start => 256, // Start nodes use moderate memory
-            NodeType::End => 256, // End nodes use moderate memory
+            NodeType::Cryptographic | NodeType::Time | NodeType::Custom => 128,
         }
     }
 
     /// Estimate node CPU usage
-    fn estimate_node_cpu_usage(&self, node: &crate::types::Node) -> f64 {
-        match node.node_type {
+    fn estimate_node_cpu_usage(&self, node: &VisualNode, kind: &NodeType) -> f64 {
+        match kind {
+            _ if is_entry_or_exit(node) => 0.1, // Start and End nodes are light
             NodeType::State => 0.3, // Storage operations are CPU intensive
             NodeType::External => 0.5, // External calls are very CPU intensive
             NodeType::Arithmetic => 0.1, // Arithmetic operations are light
             NodeType::Logic => 0.05, // Logic operations are very light
             NodeType::Control => 0.2, // Control flow is moderate
-            NodeType::Start => 0.1, // Start nodes are light
-            NodeType::End => 0.1, // End nodes are light
+            NodeType::Cryptographic => 0.4, // Hashing and signature checks are heavy
+            NodeType::Time | NodeType::Custom => 0.2,
         }
     }
 
     /// Estimate node gas usage
-    fn estimate_node_gas_usage(&self, node: &crate::types::Node) -> u64 {
-        match node.node_type {
+    fn estimate_node_gas_usage(&self, node: &VisualNode, kind: &NodeType) -> u64 {
+        match kind {
+            _ if is_entry_or_exit(node) => 100, // Start and End nodes are moderate
             NodeType::State => 20000, // Storage operations are expensive
             NodeType::External => 2600, // External calls are expensive
             NodeType::Arithmetic => 3, // Arithmetic operations are cheap
             NodeType::Logic => 1, // Logic operations are very cheap
             NodeType::Control => 1, // Control flow is cheap
-            NodeType::Start => 100, // Start nodes are moderate
-            NodeType::End => 100, // End nodes are moderate
+            NodeType::Cryptographic => 3000, // Signature checks dominate
+            NodeType::Time | NodeType::Custom => 100,
         }
     }
 }
 
+fn is_entry_or_exit(node: &VisualNode) -> bool {
+    matches!(node.node_type.as_str(), "Start" | "Init" | "End")
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{
+        compiler::Compiler,
+        test_graphs::{connect, node, node_with},
+        types::{ExecutionContext, Port, ValueType},
+        wasm::{engine, BlockContext},
+    };
+
+    #[test]
+    fn test_optimized_graph_compiles_and_runs() {
+        let mut graph = VisualGraph::new("constant");
+        let start = node("Start")
+            .with_outputs(vec![
+                Port::new("flow_out", "Flow Out", ValueType::Flow),
+                Port::new("amount", "Amount", ValueType::Integer),
+            ])
+            .with_property("returns", serde_json::json!("integer"));
+        let sum = node_with("Add", serde_json::json!({ "a": 2, "b": "3" }));
+        let rate = node_with("Multiply", serde_json::json!({ "b": 4 }));
+        let scaled = node("Multiply");
+        let unused = node_with("Divide", serde_json::json!({ "a": 1, "b": 0 }));
+        let end = node("End").with_inputs(vec![
+            Port::new("flow_in", "Flow In", ValueType::Flow),
+            Port::new("result", "Result", ValueType::Integer),
+        ]);
+        connect(&mut graph, &start, "flow_out", &end, "flow_in");
+        connect(&mut graph, &sum, "result", &rate, "a");
+        connect(&mut graph, &start, "amount", &scaled, "a");
+        connect(&mut graph, &rate, "result", &scaled, "b");
+        connect(&mut graph, &scaled, "result", &end, "result");
+        let (sum_id, rate_id, scaled_id, unused_id) = (sum.id, rate.id, scaled.id, unused.id);
+        for node in [start, sum, rate, scaled, unused, end] {
+            graph.add_node(node);
+        }
+
+        let optimized = PerformanceOptimizer::new(&Config::default()).optimize(&graph).unwrap();
+        let changed: Vec<&[NodeId]> = optimized
+            .results
+            .iter()
+            .flat_map(|r| &r.changes)
+            .map(|c| c.nodes_affected.as_slice())
+            .collect();
+        assert_eq!(changed, vec![&[unused_id][..], &[sum_id, rate_id][..]]);
+        let types: Vec<&str> = optimized.graph.nodes.iter().map(|n| n.node_type.as_str()).collect();
+        assert_eq!(types, vec!["Start", "Multiply", "End"]);
+        assert_eq!(optimized.graph.get_node(scaled_id).unwrap().properties["b"], serde_json::json!(20));
+
+        let result = Compiler::new(&Config::default()).unwrap().compile(&optimized.graph).unwrap();
+        let runtime = engine::engine().unwrap();
+        let mut context = ExecutionContext::new(100_000);
+        let block = BlockContext::new(1, 0, 1);
+        let args = [serde_json::json!(3)];
+        let run = engine::execute(&runtime, &result.wasm_bytes, "main", &args, 100_000, block, &mut context).unwrap();
+        assert_eq!(run.output["result"], 60);
+    }
+
+    #[test]
+    fn test_constant_folding_leaves_unfoldable_nodes() {
+        let mut graph = VisualGraph::new("division");
+        let divide = node_with("Divide", serde_json::json!({ "a": 1, "b": 0 }));
+        let connected = node_with("Add", serde_json::json!({ "b": 1 }));
+        let source = node("ReadStorage");
+        connect(&mut graph, &source, "value", &connected, "a");
+        let divide_id = divide.id;
+        for node in [divide, connected, source] {
+            graph.add_node(node);
+        }
+
+        let result = ConstantFoldingPass.optimize(&graph).unwrap();
+        assert!(result.graph.is_none() && result.changes.is_empty());
+        assert_eq!(result.warnings.len(), 1);
+        assert!(result.warnings[0].contains(&divide_id.to_string()));
+    }
 
     #[test]
     fn test_performance_optimizer() {
         let config = Config::default();
         let mut optimizer = PerformanceOptimizer::new(&config);
         
-        let graph = Graph::new("test");
-        let results = optimizer.optimize(&graph).unwrap();
+        let graph = VisualGraph::new("test");
+        let optimized = optimizer.optimize(&graph).unwrap();
         
-        assert!(!results.is_empty());
+        assert!(!optimized.results.is_empty());
         
-        let summary = optimizer.get_optimization_summary(&results);
+        let summary = optimizer.get_optimization_summary(&optimized.results);
         assert!(summary.total_optimizations > 0);
     }
 
@@ -961,7 +1380,7 @@ mod tests {
         let config = Config::default();
         let optimizer = ParallelExecutionOptimizer::new(&config);
         
-        let graph = Graph::new("test");
+        let graph = VisualGraph::new("test");
         let plan = optimizer.generate_plan(&graph).unwrap();
         
         assert!(plan.estimated_parallelism >= 0.0);
@@ -973,11 +1392,81 @@ mod tests {
         let config = Config::default();
         let analyzer = ResourceUsageAnalyzer::new(&config);
         
-        let graph = Graph::new("test");
+        let graph = VisualGraph::new("test");
         let report = analyzer.analyze(&graph).unwrap();
         
         assert!(report.memory_usage.peak_memory >= 0);
         assert!(report.cpu_usage.peak_cpu >= 0.0);
         assert!(report.gas_usage.total_gas >= 0);
     }
+
+    #[test]
+    fn test_unreachable_nodes() {
+        let ids: Vec<NodeId> = (0..7).map(|_| uuid::Uuid::new_v4()).collect();
+        let [start, a, b, source, feeder, orphan, orphan_child] =
+            [ids[0], ids[1], ids[2], ids[3], ids[4], ids[5], ids[6]];
+        let edges = vec![(start, a), (a, b), (b, a), (source, feeder), (feeder, b), (orphan, orphan_child)];
+
+        // `source -> feeder` supplies an input of the live `b`, so only the
+        // orphan pair is dead
+        assert_eq!(unreachable_nodes(&[start], &ids, &edges), vec![orphan, orphan_child]);
+        assert!(unreachable_nodes(&[start, orphan], &ids, &edges).is_empty());
+    }
+
+    #[test]
+    fn test_constant_feeding_reachable_node_is_kept() {
+        let ids: Vec<NodeId> = (0..4).map(|_| uuid::Uuid::new_v4()).collect();
+        let [start, add, constant, unused] = [ids[0], ids[1], ids[2], ids[3]];
+        let edges = vec![(start, add), (constant, add)];
+
+        assert_eq!(unreachable_nodes(&[start], &ids, &edges), vec![unused]);
+    }
+
+    #[test]
+    fn test_fold_constant() {
+        use serde_json::json;
+
+        assert_eq!(fold_constant("add", &[json!(2), json!(3), json!(4)]), Some(json!(9)));
+        assert_eq!(fold_constant("sub", &[json!(10), json!(4)]), Some(json!(6)));
+        assert_eq!(fold_constant("div", &[json!(7), json!(2)]), Some(json!(3)));
+        assert_eq!(fold_constant("mul", &[json!(1.5), json!(2)]), Some(json!(3.0)));
+        assert_eq!(fold_constant("div", &[json!(1), json!(0)]), None);
+        assert_eq!(fold_constant("add", &[json!(i64::MAX), json!(1)]), None, "overflow is left to runtime");
+        assert_eq!(fold_constant("pow", &[json!(2), json!(3)]), None);
+        assert_eq!(fold_constant("add", &[]), None);
+    }
+
+    #[test]
+    fn test_loop_detection_and_invariants() {
+        let ids: Vec<NodeId> = (0..7).map(|_| uuid::Uuid::new_v4()).collect();
+        let [start, header, body, step, exit, rate, scaled] =
+            [ids[0], ids[1], ids[2], ids[3], ids[4], ids[5], ids[6]];
+        let edges = vec![
+            (start, header),
+            (header, body),
+            (body, step),
+            (step, header),
+            (header, exit),
+            (exit, exit),
+            (rate, scaled),
+            (scaled, body),
+        ];
+
+        let loops = find_loops(&ids, &edges);
+        assert_eq!(loops.len(), 2);
+        assert_eq!(loops[0].nodes, vec![header, body, step]);
+        assert_eq!(loops[0].back_edges, vec![(step, header)]);
+        assert_eq!(loops[0].headers, vec![header]);
+        assert_eq!(loops[1].nodes, vec![exit], "self-loops are loops");
+        assert!(find_loops(&ids, &edges[..3]).is_empty());
+
+        let pure = |id: &NodeId| *id != start;
+        assert_eq!(loop_invariants(&loops[0], &ids, &edges, pure), vec![rate, scaled]);
+        assert_eq!(
+            loop_invariants(&loops[0], &ids, &edges, |id| *id != rate),
+            vec![scaled],
+            "impure inputs are not re-evaluated"
+        );
+        assert!(loop_invariants(&loops[1], &ids, &edges, pure).is_empty());
+    }
 } 
\ No newline at end of file
@@ -0,0 +1,437 @@
+//! Presentation of CLI results
+//!
+//! Commands hand their result to [`Output`] twice over: as a JSON value and
+//! as a human rendering (usually a [`Table`]). With `--json` only the JSON is
+//! printed, one document on stdout, so scripts can parse it while logs stay
+//! on stderr. Colors are used when stdout is a terminal and `NO_COLOR` is not
+//! set.
+//!
+//! Every JSON document has a `command` field. Failures are printed as
+//! `{"command": .., "error": {"kind": .., "message": .., "exit_code": ..}}`
+//! and the process exits with the code of the error's class:
+//!
+//! | Code | Class            | Errors                                              |
+//! |------|------------------|-----------------------------------------------------|
+//! | 0    | success          |                                                     |
+//! | 1    | unknown          | `Unknown`                                           |
+//! | 2    | usage            | invalid command-line arguments                      |
+//! | 3    | config           | `Config`                                            |
+//! | 4    | io               | `Io`, `NotFound`                                    |
+//! | 5    | invalid input    | `Validation`, `Graph`, `Type`, `Node`, `NodeNotFound`, `Serialization` |
+//! | 6    | compilation      | `Compilation`, `Wasm`                               |
+//! | 7    | execution        | `Reverted`, `ExecutionError`, `GasLimitExceeded`, `BreakpointNotFound` |
+//! | 8    | network          | `Network`, `Baals`, `Timeout`                       |
+//! | 9    | refused          | `PermissionDenied`, `InvalidState`                  |
+//!
+//! `ci` is the exception: its exit code ORs one bit per failed stage
+//! (validation 2, lint 4, scenario 8, gas regression 16, security 32,
+//! license 64), so it exits 1 when the pipeline itself fails, whatever the
+//! error, rather than with a class code a script would read as failed stages.
+//!
+//! Destructive commands ask for the name of what they destroy to be typed
+//! back before going ahead (see [`Output::confirm`]); `--force` skips the
+//! prompt. Without a terminal, or with `--json`, they refuse unless forced.
+
+use std::{
+    cell::Cell,
+    io::{BufRead, IsTerminal, Write},
+    sync::Arc,
+};
+
+use crate::{
+    error::{CanvasError, CanvasResult},
+    progress::{self, Progress},
+};
+
+/// How results are printed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    Human,
+    Json,
+}
+
+/// Exit code of invalid command-line arguments, as used by the argument parser
+pub const USAGE_EXIT_CODE: i32 = 2;
+
+/// Exit code of `ci` when the pipeline fails before reporting its stages
+pub const CI_PIPELINE_EXIT_CODE: i32 = 1;
+
+/// Exit code of a failed command: the error's class, or
+/// [`CI_PIPELINE_EXIT_CODE`] for `ci`
+pub fn command_exit_code(command: &str, error: &CanvasError) -> i32 {
+    match command {
+        "ci" => CI_PIPELINE_EXIT_CODE,
+        _ => exit_code(error),
+    }
+}
+
+/// Exit code of an error's class
+pub fn exit_code(error: &CanvasError) -> i32 {
+    match error {
+        CanvasError::Unknown(_) => 1,
+        CanvasError::Config(_) => 3,
+        CanvasError::Io(_) | CanvasError::NotFound(_) => 4,
+        CanvasError::Validation(_)
+        | CanvasError::Graph(_)
+        | CanvasError::Type(_)
+        | CanvasError::Node(_)
+        | CanvasError::NodeNotFound(_)
+        | CanvasError::Serialization(_) => 5,
+        CanvasError::Compilation(_) | CanvasError::Wasm(_) => 6,
+        CanvasError::Reverted(_)
+        | CanvasError::ExecutionError(_)
+        | CanvasError::GasLimitExceeded(_)
+        | CanvasError::BreakpointNotFound(_) => 7,
+        CanvasError::Network(_) | CanvasError::Baals(_) | CanvasError::Timeout(_) => 8,
+        CanvasError::PermissionDenied(_) | CanvasError::InvalidState(_) => 9,
+    }
+}
+
+/// Stable snake_case name of an error's variant
+pub fn error_kind(error: &CanvasError) -> &'static str {
+    match error {
+        CanvasError::Compilation(_) => "compilation",
+        CanvasError::Wasm(_) => "wasm",
+        CanvasError::Node(_) => "node",
+        CanvasError::NodeNotFound(_) => "node_not_found",
+        CanvasError::BreakpointNotFound(_) => "breakpoint_not_found",
+        CanvasError::Baals(_) => "baals",
+        CanvasError::Validation(_) => "validation",
+        CanvasError::Config(_) => "config",
+        CanvasError::Io(_) => "io",
+        CanvasError::Serialization(_) => "serialization",
+        CanvasError::Graph(_) => "graph",
+        CanvasError::Type(_) => "type",
+        CanvasError::GasLimitExceeded(_) => "gas_limit_exceeded",
+        CanvasError::PermissionDenied(_) => "permission_denied",
+        CanvasError::NotFound(_) => "not_found",
+        CanvasError::InvalidState(_) => "invalid_state",
+        CanvasError::Timeout(_) => "timeout",
+        CanvasError::Network(_) => "network",
+        CanvasError::ExecutionError(_) => "execution",
+        CanvasError::Reverted(_) => "reverted",
+        CanvasError::Unknown(_) => "unknown",
+    }
+}
+
+/// Ask for a destructive operation to be confirmed by typing `resource` back
+pub fn confirm_destructive(
+    action: &str,
+    resource: &str,
+    input: &mut dyn BufRead,
+    prompt: &mut dyn Write,
+) -> CanvasResult<()> {
+    write!(prompt, "This will {}. It cannot be undone.\nType '{}' to confirm: ", action, resource)?;
+    prompt.flush()?;
+    let mut answer = String::new();
+    input.read_line(&mut answer)?;
+    if answer.trim() == resource {
+        Ok(())
+    } else {
+        Err(CanvasError::PermissionDenied(format!("Confirmation did not match '{}'; nothing was changed", resource)))
+    }
+}
+
+/// ANSI styling, or plain text when disabled
+#[derive(Debug, Clone, Copy)]
+pub struct Style {
+    enabled: bool,
+}
+
+impl Style {
+    pub fn new(enabled: bool) -> Self {
+        Self { enabled }
+    }
+
+    fn paint(&self, code: &str, text: &str) -> String {
+        if self.enabled {
+            format!("\x1b[{}m{}\x1b[0m", code, text)
+        } else {
+            text.to_string()
+        }
+    }
+
+    pub fn bold(&self, text: &str) -> String {
+        self.paint("1", text)
+    }
+
+    pub fn dim(&self, text: &str) -> String {
+        self.paint("2", text)
+    }
+
+    pub fn green(&self, text: &str) -> String {
+        self.paint("32", text)
+    }
+
+    pub fn yellow(&self, text: &str) -> String {
+        self.paint("33", text)
+    }
+
+    pub fn red(&self, text: &str) -> String {
+        self.paint("31", text)
+    }
+}
+
+/// Column-aligned table
+#[derive(Debug, Clone, Default)]
+pub struct Table {
+    headers: Vec<String>,
+    rows: Vec<Vec<String>>,
+}
+
+impl Table {
+    pub fn new<S: Into<String>>(headers: impl IntoIterator<Item = S>) -> Self {
+        Self {
+            headers: headers.into_iter().map(Into::into).collect(),
+            rows: Vec::new(),
+        }
+    }
+
+    /// Two-column table of labelled values
+    pub fn key_values<K: Into<String>, V: Into<String>>(pairs: impl IntoIterator<Item = (K, V)>) -> Self {
+        let mut table = Self::new(Vec::<String>::new());
+        for (key, value) in pairs {
+            table.add_row([key.into(), value.into()]);
+        }
+        table
+    }
+
+    pub fn add_row<S: Into<String>>(&mut self, cells: impl IntoIterator<Item = S>) -> &mut Self {
+        self.rows.push(cells.into_iter().map(Into::into).collect());
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    /// Render with a bold header row; `paint` styles a body cell given its
+    /// column and padded text
+    pub fn render_with(&self, style: &Style, paint: impl Fn(usize, &str) -> String) -> String {
+        let columns = self.rows.iter().map(Vec::len).chain([self.headers.len()]).max().unwrap_or(0);
+        let mut widths = vec![0; columns];
+        for row in self.rows.iter().chain([&self.headers]) {
+            for (i, cell) in row.iter().enumerate() {
+                widths[i] = widths[i].max(cell.chars().count());
+            }
+        }
+        let pad = |cell: &str, width: usize| format!("{}{}", cell, " ".repeat(width - cell.chars().count()));
+        let line = |row: &[String], paint: &dyn Fn(usize, &str) -> String| {
+            let cells: Vec<_> = (0..columns)
+                .map(|i| {
+                    let cell = row.get(i).map(String::as_str).unwrap_or("");
+                    // The last column isn't padded, so lines carry no trailing spaces
+                    let text = if i + 1 == columns { cell.to_string() } else { pad(cell, widths[i]) };
+                    paint(i, &text)
+                })
+                .collect();
+            cells.join("  ").trim_end().to_string()
+        };
+
+        let mut rendered = String::new();
+        if !self.headers.is_empty() {
+            rendered.push_str(&line(&self.headers, &|_, text| style.bold(text)));
+            rendered.push('\n');
+            let rule: Vec<_> = widths.iter().map(|w| "-".repeat(*w)).collect();
+            rendered.push_str(&style.dim(&rule.join("  ")));
+            rendered.push('\n');
+        }
+        for row in &self.rows {
+            rendered.push_str(&line(row, &paint));
+            rendered.push('\n');
+        }
+        rendered
+    }
+
+    pub fn render(&self, style: &Style) -> String {
+        self.render_with(style, |_, text| text.to_string())
+    }
+}
+
+/// Prints command results in the chosen format
+#[derive(Debug)]
+pub struct Output {
+    format: OutputFormat,
+    style: Style,
+    emitted: Cell<bool>,
+    quiet: bool,
+}
+
+impl Output {
+    /// Colors are on when stdout is a terminal and `NO_COLOR` is unset
+    pub fn new(format: OutputFormat) -> Self {
+        let color = std::io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none();
+        Self {
+            format,
+            style: Style::new(color),
+            emitted: Cell::new(false),
+            quiet: false,
+        }
+    }
+
+    pub fn with_color(mut self, enabled: bool) -> Self {
+        self.style = Style::new(enabled);
+        self
+    }
+
+    /// Suppress progress bars
+    pub fn with_quiet(mut self, quiet: bool) -> Self {
+        self.quiet = quiet;
+        self
+    }
+
+    /// Progress reporter for long operations: bars on an interactive
+    /// terminal, nothing when quiet or printing JSON
+    pub fn progress(&self) -> Arc<dyn Progress> {
+        progress::reporter(!self.quiet && !self.is_json())
+    }
+
+    pub fn format(&self) -> OutputFormat {
+        self.format
+    }
+
+    pub fn is_json(&self) -> bool {
+        self.format == OutputFormat::Json
+    }
+
+    pub fn style(&self) -> &Style {
+        &self.style
+    }
+
+    /// Whether a result has been printed
+    pub fn emitted(&self) -> bool {
+        self.emitted.get()
+    }
+
+    /// Render a result: the JSON document, or `human` for people
+    pub fn render(
+        &self,
+        command: &str,
+        mut json: serde_json::Value,
+        human: impl FnOnce(&Style) -> String,
+    ) -> String {
+        match self.format {
+            OutputFormat::Json => {
+                if let Some(object) = json.as_object_mut() {
+                    object.insert("command".to_string(), serde_json::json!(command));
+                }
+                serde_json::to_string_pretty(&json).unwrap_or_default()
+            }
+            OutputFormat::Human => human(&self.style),
+        }
+    }
+
+    /// Print a result to stdout
+    pub fn emit(&self, command: &str, json: serde_json::Value, human: impl FnOnce(&Style) -> String) {
+        let rendered = self.render(command, json, human);
+        println!("{}", rendered.trim_end());
+        self.emitted.set(true);
+    }
+
+    /// Print the result of a command that has nothing to report but success
+    pub fn success(&self, command: &str) {
+        if self.is_json() && !self.emitted() {
+            self.emit(command, serde_json::json!({ "status": "ok" }), |_| String::new());
+        }
+    }
+
+    /// Confirm a destructive operation on `resource` at the terminal, unless
+    /// `force` is set
+    pub fn confirm(&self, action: &str, resource: &str, force: bool) -> CanvasResult<()> {
+        if force {
+            return Ok(());
+        }
+        if self.is_json() || !std::io::stdin().is_terminal() {
+            return Err(CanvasError::PermissionDenied(format!(
+                "Refusing to {} without confirmation; pass --force to skip the prompt",
+                action
+            )));
+        }
+        confirm_destructive(action, resource, &mut std::io::stdin().lock(), &mut std::io::stderr())
+    }
+
+    /// Render a failure: JSON on stdout, or a red message for people
+    pub fn render_error(&self, command: &str, error: &CanvasError) -> String {
+        self.render(
+            command,
+            serde_json::json!({
+                "status": "error",
+                "error": {
+                    "kind": error_kind(error),
+                    "message": error.to_string(),
+                    "exit_code": command_exit_code(command, error),
+                },
+            }),
+            |style| format!("{} {}", style.red(&style.bold("error:")), error),
+        )
+    }
+
+    /// Print a failure. JSON goes to stdout in place of a result; once a
+    /// result was printed (say, a report of what failed), and for people, it
+    /// goes to stderr.
+    pub fn error(&self, command: &str, error: &CanvasError) {
+        let rendered = self.render_error(command, error);
+        if self.is_json() && !self.emitted() {
+            println!("{}", rendered);
+        } else {
+            eprintln!("{}", rendered);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tables_json_and_exit_codes() {
+        let mut table = Table::new(["Function", "Gas"]);
+        table.add_row(["transfer", "21000"]).add_row(["mint", "5"]);
+        assert_eq!(
+            table.render(&Style::new(false)),
+            "Function  Gas\n--------  -----\ntransfer  21000\nmint      5\n"
+        );
+        let style = Style::new(true);
+        let colored = table.render_with(&style, |column, text| match column {
+            1 => style.green(text),
+            _ => text.to_string(),
+        });
+        assert!(colored.starts_with("\x1b[1mFunction\x1b[0m  \x1b[1mGas\x1b[0m\n"));
+        assert!(colored.contains("\x1b[32m21000\x1b[0m"));
+
+        let pairs = Table::key_values([("Address", "0xabc"), ("Gas used", "7")]);
+        assert_eq!(pairs.render(&Style::new(false)), "Address   0xabc\nGas used  7\n");
+
+        let json = Output::new(OutputFormat::Json);
+        let rendered = json.render("deploy", serde_json::json!({ "address": "0xabc" }), |_| unreachable!());
+        let value: serde_json::Value = serde_json::from_str(&rendered).unwrap();
+        assert_eq!(value, serde_json::json!({ "command": "deploy", "address": "0xabc" }));
+
+        let error = CanvasError::Validation("Graph validation failed".to_string());
+        let value: serde_json::Value = serde_json::from_str(&json.render_error("validate", &error)).unwrap();
+        assert_eq!(value["error"]["kind"], "validation");
+        assert_eq!(value["error"]["exit_code"], 5);
+        assert_eq!(exit_code(&CanvasError::Network("down".to_string())), 8);
+        // An I/O failure of the pipeline must not read as a failed lint stage (bit 4)
+        let io = CanvasError::Io(std::io::Error::new(std::io::ErrorKind::NotFound, "missing"));
+        let value: serde_json::Value = serde_json::from_str(&json.render_error("ci", &io)).unwrap();
+        assert_eq!(value["error"]["exit_code"], CI_PIPELINE_EXIT_CODE);
+        let human = Output::new(OutputFormat::Human).with_color(false);
+        assert_eq!(human.render_error("validate", &error), "error: Validation error: Graph validation failed");
+    }
+
+    #[test]
+    fn test_destructive_confirmation_needs_the_resource_name() {
+        let mut prompt = Vec::new();
+        confirm_destructive("delete releases of 'prod'", "prod", &mut &b"prod\n"[..], &mut prompt).unwrap();
+        assert!(String::from_utf8(prompt).unwrap().ends_with("Type 'prod' to confirm: "));
+
+        let refused = confirm_destructive("delete releases of 'prod'", "prod", &mut &b"y\n"[..], &mut Vec::new());
+        assert!(matches!(refused, Err(CanvasError::PermissionDenied(_))));
+
+        // Scripts must opt in with --force
+        let json = Output::new(OutputFormat::Json);
+        assert!(json.confirm("delete releases of 'prod'", "prod", false).is_err());
+        json.confirm("delete releases of 'prod'", "prod", true).unwrap();
+    }
+}
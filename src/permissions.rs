@@ -0,0 +1,253 @@
+//! Permission gate for commands invoked from the desktop UI
+//!
+//! Every command the frontend invokes belongs to a [`PermissionScope`]. The
+//! gate checks it against the `[permissions]` policy for the configured
+//! network, so a user can, say, deny signing on mainnet from the UI or have
+//! every key use confirmed, and appends the outcome to an audit trail at
+//! `<data_dir>/audit/commands.jsonl`.
+
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::{Config, PermissionDecision, PermissionRule, PermissionScope, PermissionsConfig},
+    error::{CanvasError, CanvasResult},
+};
+
+/// Audit trail, relative to the data directory
+pub const AUDIT_LOG_FILE: &str = "audit/commands.jsonl";
+
+/// What happened to an invoked command
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AuditOutcome {
+    Allowed,
+    /// Allowed after the user confirmed it
+    Confirmed,
+    /// Refused by the user when asked to confirm
+    Declined,
+    /// Refused by the policy
+    Denied,
+}
+
+/// One command checked by the gate
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub command: String,
+    pub scope: PermissionScope,
+    pub network: String,
+    pub decision: PermissionDecision,
+    pub outcome: AuditOutcome,
+    pub at: DateTime<Utc>,
+}
+
+/// Append-only log of invoked commands
+pub struct AuditLog {
+    path: PathBuf,
+}
+
+impl AuditLog {
+    pub fn new(data_dir: &Path) -> Self {
+        Self {
+            path: data_dir.join(AUDIT_LOG_FILE),
+        }
+    }
+
+    pub fn record(&self, entry: &AuditEntry) -> CanvasResult<()> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut file = std::fs::OpenOptions::new().create(true).append(true).open(&self.path)?;
+        writeln!(file, "{}", serde_json::to_string(entry)?)?;
+        Ok(())
+    }
+
+    /// Every recorded entry, oldest first
+    pub fn entries(&self) -> CanvasResult<Vec<AuditEntry>> {
+        if !self.path.exists() {
+            return Ok(Vec::new());
+        }
+        std::fs::read_to_string(&self.path)?
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(index, line)| {
+                serde_json::from_str(line).map_err(|e| {
+                    CanvasError::InvalidState(format!("{}:{}: invalid audit entry: {}", self.path.display(), index + 1, e))
+                })
+            })
+            .collect()
+    }
+
+    /// The `limit` most recent entries, oldest first
+    pub fn recent(&self, limit: usize) -> CanvasResult<Vec<AuditEntry>> {
+        let mut entries = self.entries()?;
+        let skip = entries.len().saturating_sub(limit);
+        Ok(entries.split_off(skip))
+    }
+}
+
+/// Checks commands against the permission policy and audits them
+pub struct PermissionGate {
+    policy: PermissionsConfig,
+    network: String,
+    audit: AuditLog,
+}
+
+impl PermissionGate {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            policy: config.permissions.clone(),
+            network: config.baals.network.name.clone(),
+            audit: AuditLog::new(&config.app.data_dir),
+        }
+    }
+
+    pub fn audit_log(&self) -> &AuditLog {
+        &self.audit
+    }
+
+    /// What the policy does with commands of `scope` on the configured network
+    pub fn decide(&self, scope: PermissionScope) -> PermissionDecision {
+        let matches = |rule: &&PermissionRule| {
+            rule.scope.map_or(true, |s| s == scope)
+                && rule.network.as_ref().map_or(true, |n| n.eq_ignore_ascii_case(&self.network))
+        };
+        let specificity = |rule: &PermissionRule| (rule.scope.is_some(), rule.network.is_some());
+        self.policy
+            .rules
+            .iter()
+            .filter(matches)
+            .max_by_key(|rule| (specificity(rule), rule.decision))
+            .map_or(self.policy.default, |rule| rule.decision)
+    }
+
+    /// Question to ask the user before running a command that needs confirmation
+    pub fn confirmation_prompt(&self, command: &str, scope: PermissionScope) -> String {
+        format!(
+            "The app wants to run '{}' ({} permission) on network '{}'. Allow it?",
+            command,
+            scope.as_str(),
+            self.network
+        )
+    }
+
+    /// Record the outcome of a decision, failing unless the command may run.
+    ///
+    /// `confirmed` is whether the user approved a command the policy wanted
+    /// confirmed; it is ignored for other decisions.
+    pub fn settle(
+        &self,
+        command: &str,
+        scope: PermissionScope,
+        decision: PermissionDecision,
+        confirmed: bool,
+    ) -> CanvasResult<()> {
+        let outcome = match decision {
+            PermissionDecision::Allow => AuditOutcome::Allowed,
+            PermissionDecision::Confirm if confirmed => AuditOutcome::Confirmed,
+            PermissionDecision::Confirm => AuditOutcome::Declined,
+            PermissionDecision::Deny => AuditOutcome::Denied,
+        };
+        if self.policy.audit {
+            self.audit.record(&AuditEntry {
+                command: command.to_string(),
+                scope,
+                network: self.network.clone(),
+                decision,
+                outcome,
+                at: Utc::now(),
+            })?;
+        }
+        match outcome {
+            AuditOutcome::Allowed | AuditOutcome::Confirmed => Ok(()),
+            AuditOutcome::Declined => Err(CanvasError::PermissionDenied(format!("'{}' was not confirmed", command))),
+            AuditOutcome::Denied => Err(CanvasError::PermissionDenied(format!(
+                "'{}' needs the {} permission, which the policy denies on network '{}'",
+                command,
+                scope.as_str(),
+                self.network
+            ))),
+        }
+    }
+
+    /// Decide and settle a command, calling `confirm` with the prompt when the
+    /// policy wants the user to confirm it
+    pub fn authorize(
+        &self,
+        command: &str,
+        scope: PermissionScope,
+        confirm: impl FnOnce(&str) -> bool,
+    ) -> CanvasResult<()> {
+        let decision = self.decide(scope);
+        let confirmed = decision == PermissionDecision::Confirm && confirm(&self.confirmation_prompt(command, scope));
+        self.settle(command, scope, decision, confirmed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gate(dir: &Path, network: &str, rules: Vec<PermissionRule>) -> PermissionGate {
+        let mut config = Config::default();
+        config.app.data_dir = dir.to_path_buf();
+        config.baals.network.name = network.to_string();
+        config.permissions.rules.extend(rules);
+        PermissionGate::new(&config)
+    }
+
+    #[test]
+    fn test_most_specific_rule_decides_and_is_audited() {
+        let dir = tempfile::tempdir().unwrap();
+        let deny_mainnet = PermissionRule {
+            scope: Some(PermissionScope::Sign),
+            network: Some("mainnet".to_string()),
+            decision: PermissionDecision::Deny,
+        };
+        let lock_down_mainnet = PermissionRule {
+            scope: None,
+            network: Some("mainnet".to_string()),
+            decision: PermissionDecision::Confirm,
+        };
+
+        let local = gate(dir.path(), "local", vec![deny_mainnet.clone(), lock_down_mainnet.clone()]);
+        assert_eq!(local.decide(PermissionScope::Build), PermissionDecision::Allow);
+        assert_eq!(local.decide(PermissionScope::Sign), PermissionDecision::Confirm);
+        assert!(local.authorize("send_transaction", PermissionScope::Sign, |_| true).is_ok());
+        assert!(local.authorize("send_transaction", PermissionScope::Sign, |_| false).is_err());
+
+        let mainnet = gate(dir.path(), "mainnet", vec![deny_mainnet, lock_down_mainnet]);
+        assert_eq!(mainnet.decide(PermissionScope::Sign), PermissionDecision::Deny);
+        assert_eq!(mainnet.decide(PermissionScope::Build), PermissionDecision::Confirm);
+        let mut asked = false;
+        let denied = mainnet.authorize("send_transaction", PermissionScope::Sign, |_| {
+            asked = true;
+            true
+        });
+        assert!(matches!(denied, Err(CanvasError::PermissionDenied(_))));
+        assert!(!asked);
+
+        let outcomes: Vec<(String, AuditOutcome)> = mainnet
+            .audit_log()
+            .entries()
+            .unwrap()
+            .into_iter()
+            .map(|e| (e.network, e.outcome))
+            .collect();
+        assert_eq!(
+            outcomes,
+            vec![
+                ("local".to_string(), AuditOutcome::Confirmed),
+                ("local".to_string(), AuditOutcome::Declined),
+                ("mainnet".to_string(), AuditOutcome::Denied),
+            ]
+        );
+        assert_eq!(mainnet.audit_log().recent(1).unwrap()[0].outcome, AuditOutcome::Denied);
+    }
+}
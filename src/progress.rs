@@ -0,0 +1,193 @@
+//! Progress reporting for long operations
+//!
+//! Compiling a big graph, deploying or running a large test suite can take
+//! a while. Library code reports what it's doing through [`Progress`]: each
+//! operation is a sequence of named stages, each with a known number of steps
+//! (or none, for waits of unknown length). The CLI shows them as progress
+//! bars with an ETA on stderr; everything else, including CI and `--json`
+//! runs, gets [`Quiet`], which reports nothing.
+//!
+//! Components take a reporter with `with_progress` and default to quiet.
+
+use std::{
+    io::IsTerminal,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use indicatif::{ProgressBar, ProgressStyle};
+
+/// Receives the stages of a long operation
+pub trait Progress: std::fmt::Debug + Send + Sync {
+    /// Begin a stage of `total` steps; 0 when the length isn't known.
+    /// Starting a stage finishes the previous one.
+    fn stage(&self, name: &str, total: u64);
+
+    /// Advance the current stage by `steps`
+    fn advance(&self, steps: u64);
+
+    /// Finish the current stage
+    fn finish(&self);
+}
+
+/// Reports nothing
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Quiet;
+
+impl Progress for Quiet {
+    fn stage(&self, _name: &str, _total: u64) {}
+
+    fn advance(&self, _steps: u64) {}
+
+    fn finish(&self) {}
+}
+
+/// Shared quiet reporter, the default of every component
+pub fn quiet() -> Arc<dyn Progress> {
+    Arc::new(Quiet)
+}
+
+/// Whether progress bars would be seen: stderr is a terminal and this isn't a CI run
+pub fn interactive() -> bool {
+    std::io::stderr().is_terminal() && std::env::var_os("CI").is_none()
+}
+
+/// Progress bars when `enabled` and [`interactive`], otherwise [`Quiet`]
+pub fn reporter(enabled: bool) -> Arc<dyn Progress> {
+    if enabled && interactive() {
+        Arc::new(Bars::default())
+    } else {
+        quiet()
+    }
+}
+
+/// One indicatif bar per stage, drawn on stderr
+#[derive(Debug, Default)]
+pub struct Bars {
+    current: Mutex<Option<ProgressBar>>,
+}
+
+impl Bars {
+    fn bar(name: &str, total: u64) -> ProgressBar {
+        let bar = if total == 0 {
+            ProgressBar::new_spinner().with_style(
+                ProgressStyle::with_template("{prefix:>14.bold} {spinner} {elapsed}")
+                    .expect("valid progress template"),
+            )
+        } else {
+            ProgressBar::new(total).with_style(
+                ProgressStyle::with_template("{prefix:>14.bold} [{bar:30}] {pos}/{len} ETA {eta}")
+                    .expect("valid progress template")
+                    .progress_chars("=> "),
+            )
+        };
+        bar.enable_steady_tick(Duration::from_millis(120));
+        bar.with_prefix(name.to_string())
+    }
+}
+
+impl Progress for Bars {
+    fn stage(&self, name: &str, total: u64) {
+        let mut current = self.current.lock().expect("progress lock poisoned");
+        if let Some(bar) = current.take() {
+            bar.finish();
+        }
+        *current = Some(Self::bar(name, total));
+    }
+
+    fn advance(&self, steps: u64) {
+        if let Some(bar) = self.current.lock().expect("progress lock poisoned").as_ref() {
+            bar.inc(steps);
+        }
+    }
+
+    fn finish(&self) {
+        if let Some(bar) = self.current.lock().expect("progress lock poisoned").take() {
+            bar.finish();
+        }
+    }
+}
+
+/// Reported progress, as recorded by [`ProgressLog`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProgressEvent {
+    Stage { name: String, total: u64 },
+    Advance(u64),
+    Finish,
+}
+
+/// Records every report, for frontends that draw progress themselves and for tests
+#[derive(Debug, Default)]
+pub struct ProgressLog {
+    events: Mutex<Vec<ProgressEvent>>,
+}
+
+impl ProgressLog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn events(&self) -> Vec<ProgressEvent> {
+        self.events.lock().expect("progress lock poisoned").clone()
+    }
+
+    /// Names of the stages reported so far, in order
+    pub fn stages(&self) -> Vec<String> {
+        self.events()
+            .into_iter()
+            .filter_map(|event| match event {
+                ProgressEvent::Stage { name, .. } => Some(name),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn push(&self, event: ProgressEvent) {
+        self.events.lock().expect("progress lock poisoned").push(event);
+    }
+}
+
+impl Progress for ProgressLog {
+    fn stage(&self, name: &str, total: u64) {
+        self.push(ProgressEvent::Stage {
+            name: name.to_string(),
+            total,
+        });
+    }
+
+    fn advance(&self, steps: u64) {
+        self.push(ProgressEvent::Advance(steps));
+    }
+
+    fn finish(&self) {
+        self.push(ProgressEvent::Finish);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reporters_share_one_interface() {
+        let log = Arc::new(ProgressLog::new());
+        let progress: Arc<dyn Progress> = log.clone();
+        progress.stage("compile", 3);
+        progress.advance(2);
+        progress.advance(1);
+        progress.stage("deploy", 0);
+        progress.finish();
+        assert_eq!(log.stages(), ["compile", "deploy"]);
+        assert_eq!(log.events()[1], ProgressEvent::Advance(2));
+        assert_eq!(log.events().last(), Some(&ProgressEvent::Finish));
+
+        // Bars tolerate advancing with no stage started, like the quiet reporter
+        let bars = Bars::default();
+        bars.advance(1);
+        bars.stage("compile", 2);
+        bars.advance(2);
+        bars.finish();
+        bars.finish();
+        Quiet.advance(1);
+    }
+}
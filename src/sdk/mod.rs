@@ -4,7 +4,7 @@ use crate::{
     error::{CanvasError, CanvasResult},
     types::{Graph, Node, NodeId, NodeType},
     nodes::custom::{CustomNodeDefinition, CustomNodeBuilder},
-    compiler::Compiler,
+    compiler::{Compiler, CompilerPass},
     wasm::WasmRuntime,
     config::Config,
 };
@@ -42,6 +42,12 @@ pub trait CanvasPlugin {
     
     /// Get plugin capabilities
     fn capabilities(&self) -> Vec<PluginCapability>;
+
+    /// Compiler passes to hook into the pipeline; requires the `Optimizers`
+    /// or `Validators` capability
+    fn compiler_passes(&self) -> Vec<Box<dyn CompilerPass>> {
+        Vec::new()
+    }
 }
 
 /// Plugin capability
@@ -375,9 +381,37 @@ impl CanvasSdk {
         })
     }
 
-    /// Register a plugin
+    /// Register a plugin, hooking its compiler passes into the compiler
     pub fn register_plugin(&mut self, plugin: Box<dyn CanvasPlugin>) -> CanvasResult<()> {
-        self.plugin_registry.register_plugin(plugin)
+        let name = plugin.name().to_string();
+        let passes = plugin.compiler_passes();
+        if !passes.is_empty()
+            && !plugin
+                .capabilities()
+                .iter()
+                .any(|c| matches!(c, PluginCapability::Optimizers | PluginCapability::Validators))
+        {
+            return Err(CanvasError::Validation(format!(
+                "Plugin '{}' provides compiler passes but lacks the Optimizers or Validators capability",
+                name
+            )));
+        }
+
+        self.plugin_registry.register_plugin(plugin)?;
+        for pass in passes {
+            if let Err(e) = self.compiler.register_pass(&name, pass) {
+                self.compiler.unregister_passes(&name);
+                self.plugin_registry.unregister_plugin(&name)?;
+                return Err(e);
+            }
+        }
+        Ok(())
+    }
+
+    /// Unregister a plugin and remove its compiler passes
+    pub fn unregister_plugin(&mut self, name: &str) -> CanvasResult<()> {
+        self.compiler.unregister_passes(name);
+        self.plugin_registry.unregister_plugin(name)
     }
 
     /// Register an exporter
@@ -0,0 +1,233 @@
+//! Opt-in anonymous usage metrics
+//!
+//! Nothing is recorded unless `telemetry.enabled` is set in the configuration.
+//! Events are buffered in `telemetry.json` under the data directory and can be
+//! inspected with `canvas-contracts telemetry show` before anything leaves the
+//! machine. The schema is deliberately narrow:
+//!
+//! - `compile`: counts of built-in node types in the compiled graph (custom
+//!   and unknown node types are counted together as `custom`), whether it
+//!   succeeded and how long it took
+//! - `command`: the name of the CLI subcommand that ran
+//! - `error`: the category of an error (`Validation`, `Compilation`, ...), never
+//!   its message
+//!
+//! Each event carries the schema version, the day it happened and a random
+//! installation ID generated when telemetry is first enabled. No file names,
+//! graph contents, addresses or user names are recorded.
+//!
+//! Uploads are a `POST` of [`TelemetryPayload`] as JSON to
+//! `telemetry.endpoint` (default [`DEFAULT_TELEMETRY_ENDPOINT`]); the buffer is
+//! cleared once the endpoint accepts it.
+
+use std::{
+    collections::{BTreeMap, HashSet},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use chrono::{NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+pub use crate::config::DEFAULT_TELEMETRY_ENDPOINT;
+use crate::{
+    config::{Config, TelemetryConfig},
+    error::{CanvasError, CanvasResult},
+    nodes::builtin_node_definitions,
+    types::VisualGraph,
+};
+
+/// File events are buffered in, under the data directory
+pub const TELEMETRY_FILE: &str = "telemetry.json";
+/// Version of the event schema; bumped whenever a field is added
+pub const TELEMETRY_SCHEMA_VERSION: u32 = 1;
+/// Bucket that custom and unrecognized node types are counted under
+pub const CUSTOM_NODE_BUCKET: &str = "custom";
+/// Upload timeout
+pub const UPLOAD_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A usage event
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum TelemetryEvent {
+    Compile {
+        node_types: BTreeMap<String, u32>,
+        success: bool,
+        duration_ms: u64,
+    },
+    Command {
+        name: String,
+    },
+    Error {
+        category: String,
+    },
+}
+
+impl TelemetryEvent {
+    /// Compile event for `graph`, with node types anonymized
+    pub fn compile(graph: &VisualGraph, success: bool, duration: Duration) -> Self {
+        let builtin: HashSet<String> = builtin_node_definitions().into_iter().map(|d| d.id).collect();
+        let mut node_types = BTreeMap::new();
+        for node in &graph.nodes {
+            let bucket = if builtin.contains(&node.node_type) { node.node_type.as_str() } else { CUSTOM_NODE_BUCKET };
+            *node_types.entry(bucket.to_string()).or_insert(0) += 1;
+        }
+        Self::Compile {
+            node_types,
+            success,
+            duration_ms: duration.as_millis() as u64,
+        }
+    }
+
+    /// Error event recording only the kind of `error`
+    pub fn error(error: &CanvasError) -> Self {
+        let debug = format!("{:?}", error);
+        let category = debug.split(|c: char| !c.is_alphanumeric()).next().unwrap_or_default();
+        Self::Error {
+            category: category.to_string(),
+        }
+    }
+}
+
+/// Buffered event
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TelemetryRecord {
+    pub schema: u32,
+    /// Day the event happened; times of day are not recorded
+    pub date: NaiveDate,
+    #[serde(flatten)]
+    pub event: TelemetryEvent,
+}
+
+/// Body of an upload
+#[derive(Debug, Clone, Serialize)]
+pub struct TelemetryPayload<'a> {
+    pub installation_id: Uuid,
+    pub client_version: &'static str,
+    pub events: &'a [TelemetryRecord],
+}
+
+/// Locally buffered events
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TelemetryBuffer {
+    /// Random ID grouping events of one installation; not derived from
+    /// anything about the machine or user
+    pub installation_id: Option<Uuid>,
+    pub events: Vec<TelemetryRecord>,
+    #[serde(skip)]
+    path: Option<PathBuf>,
+}
+
+impl TelemetryBuffer {
+    /// Buffer file for a configuration
+    pub fn default_path(config: &Config) -> PathBuf {
+        config.app.data_dir.join(TELEMETRY_FILE)
+    }
+
+    /// Load the buffer from `path`, starting empty if it does not exist yet.
+    /// [`save`](Self::save) writes back to the same file.
+    pub fn open(path: &Path) -> CanvasResult<Self> {
+        let mut buffer: Self = if path.exists() {
+            serde_json::from_str(&std::fs::read_to_string(path)?)?
+        } else {
+            Self::default()
+        };
+        buffer.path = Some(path.to_path_buf());
+        Ok(buffer)
+    }
+
+    pub fn save(&self) -> CanvasResult<()> {
+        let path = self
+            .path
+            .as_ref()
+            .ok_or_else(|| CanvasError::InvalidState("Telemetry buffer was not opened from a file".to_string()))?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// Buffer an event if telemetry is enabled. Returns whether it was kept.
+    pub fn record(&mut self, config: &TelemetryConfig, event: TelemetryEvent) -> bool {
+        if !config.enabled {
+            return false;
+        }
+        self.installation_id.get_or_insert_with(Uuid::new_v4);
+        self.events.push(TelemetryRecord {
+            schema: TELEMETRY_SCHEMA_VERSION,
+            date: Utc::now().date_naive(),
+            event,
+        });
+        let excess = self.events.len().saturating_sub(config.max_buffered_events);
+        self.events.drain(..excess);
+        true
+    }
+
+    /// Exactly what the next upload would send
+    pub fn payload(&self) -> Option<TelemetryPayload<'_>> {
+        Some(TelemetryPayload {
+            installation_id: self.installation_id?,
+            client_version: crate::VERSION,
+            events: &self.events,
+        })
+    }
+
+    /// Send buffered events to the configured endpoint and clear them.
+    /// Returns how many were sent.
+    pub fn upload(&mut self, config: &TelemetryConfig) -> CanvasResult<usize> {
+        if !config.enabled {
+            return Err(CanvasError::InvalidState("Telemetry is disabled".to_string()));
+        }
+        let Some(payload) = self.payload().filter(|p| !p.events.is_empty()) else {
+            return Ok(0);
+        };
+        ureq::AgentBuilder::new()
+            .timeout(UPLOAD_TIMEOUT)
+            .build()
+            .post(&config.endpoint)
+            .send_json(serde_json::to_value(&payload)?)
+            .map_err(|e| CanvasError::Network(format!("Telemetry upload failed: {}", e)))?;
+        let sent = self.events.len();
+        self.events.clear();
+        Ok(sent)
+    }
+
+    /// Drop every buffered event and forget the installation ID, e.g. on opt-out
+    pub fn clear(&mut self) {
+        self.events.clear();
+        self.installation_id = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{Position, VisualNode};
+
+    #[test]
+    fn test_records_only_when_enabled_and_anonymizes() {
+        let mut config = TelemetryConfig::default();
+        let mut buffer = TelemetryBuffer::default();
+        let mut graph = VisualGraph::new("secret-project");
+        graph.add_node(VisualNode::new(Uuid::new_v4(), "Add", Position::new(0.0, 0.0)));
+        graph.add_node(VisualNode::new(Uuid::new_v4(), "acme_private_node", Position::new(0.0, 0.0)));
+
+        assert!(!buffer.record(&config, TelemetryEvent::compile(&graph, true, Duration::from_millis(5))));
+        assert!(buffer.payload().is_none());
+
+        config.enabled = true;
+        config.max_buffered_events = 2;
+        buffer.record(&config, TelemetryEvent::compile(&graph, true, Duration::from_millis(5)));
+        buffer.record(&config, TelemetryEvent::error(&CanvasError::Validation("/home/alice".to_string())));
+        let json = serde_json::to_string(&buffer.payload().unwrap()).unwrap();
+        assert!(json.contains("\"custom\":1") && json.contains("\"Add\":1"));
+        assert!(json.contains("\"category\":\"Validation\""));
+        assert!(!json.contains("secret") && !json.contains("acme") && !json.contains("alice"));
+
+        buffer.record(&config, TelemetryEvent::Command { name: "test".to_string() });
+        assert_eq!(buffer.events.len(), 2);
+        assert!(matches!(buffer.events[0].event, TelemetryEvent::Error { .. }));
+    }
+}
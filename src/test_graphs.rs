@@ -0,0 +1,22 @@
+//! Graph building helpers shared by unit tests
+
+use crate::types::{Connection, Position, VisualGraph, VisualNode};
+
+/// A node of `node_type` with a fresh id
+pub(crate) fn node(node_type: &str) -> VisualNode {
+    VisualNode::new(uuid::Uuid::new_v4(), node_type, Position::new(0.0, 0.0))
+}
+
+/// A node of `node_type` with the properties of a JSON object
+pub(crate) fn node_with(node_type: &str, properties: serde_json::Value) -> VisualNode {
+    let mut node = node(node_type);
+    for (key, value) in properties.as_object().expect("properties are a JSON object") {
+        node = node.with_property(key.clone(), value.clone());
+    }
+    node
+}
+
+/// Connect port `port` of `from` to port `to_port` of `to`
+pub(crate) fn connect(graph: &mut VisualGraph, from: &VisualNode, port: &str, to: &VisualNode, to_port: &str) {
+    graph.add_connection(Connection::new(uuid::Uuid::new_v4(), from.id, port, to.id, to_port));
+}
@@ -0,0 +1,432 @@
+//! Headless CI pipeline
+//!
+//! Runs every check a pipeline needs in one pass: graph validation, lints,
+//! scenario tests, gas regression against a baseline, security analysis and
+//! the license policy of the custom nodes the graphs use.
+//! Each failing stage sets its own bit in the process exit code, so a pipeline
+//! can tell what failed without parsing the report.
+
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+
+use super::{
+    parallel::{run_scenarios_parallel, ParallelOptions},
+    report::{TestCase, TestSuite},
+};
+use crate::{
+    ai::{AiAssistant, Severity},
+    compiler::{estimate_graph_gas, Validator},
+    config::Config,
+    error::CanvasResult,
+    marketplace::{check_licenses, LicensePolicy},
+    nodes::{custom::CustomNodeRegistry, load_graph},
+    types::{Gas, NodeId, VisualGraph},
+};
+
+/// A stage of the CI pipeline, in the order they run
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CiStage {
+    Validation,
+    Lint,
+    Scenario,
+    GasRegression,
+    Security,
+    License,
+}
+
+impl CiStage {
+    pub const ALL: [CiStage; 6] = [
+        CiStage::Validation,
+        CiStage::Lint,
+        CiStage::Scenario,
+        CiStage::GasRegression,
+        CiStage::Security,
+        CiStage::License,
+    ];
+
+    /// Exit code bit set when this stage fails. Bit 0 (exit code 1) is left
+    /// for errors that stopped the pipeline itself.
+    pub fn exit_bit(&self) -> i32 {
+        match self {
+            CiStage::Validation => 2,
+            CiStage::Lint => 4,
+            CiStage::Scenario => 8,
+            CiStage::GasRegression => 16,
+            CiStage::Security => 32,
+            CiStage::License => 64,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            CiStage::Validation => "validation",
+            CiStage::Lint => "lint",
+            CiStage::Scenario => "scenario",
+            CiStage::GasRegression => "gas_regression",
+            CiStage::Security => "security",
+            CiStage::License => "license",
+        }
+    }
+}
+
+/// One check and its outcome
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CiCheck {
+    pub stage: CiStage,
+    /// What was checked, e.g. a graph or scenario file
+    pub target: String,
+    pub name: String,
+    pub failure: Option<String>,
+    pub duration: Duration,
+    /// Gas attributed to the check, when it has one
+    pub gas: Option<Gas>,
+    /// Graph nodes a failure points at
+    #[serde(default)]
+    pub nodes: Vec<NodeId>,
+}
+
+impl CiCheck {
+    fn new(stage: CiStage, target: &str, name: impl Into<String>) -> Self {
+        Self {
+            stage,
+            target: target.to_string(),
+            name: name.into(),
+            failure: None,
+            duration: Duration::ZERO,
+            gas: None,
+            nodes: Vec::new(),
+        }
+    }
+
+    fn failed(mut self, failure: impl Into<String>) -> Self {
+        self.failure = Some(failure.into());
+        self
+    }
+
+    fn with_nodes(mut self, nodes: Vec<NodeId>) -> Self {
+        self.nodes = nodes;
+        self
+    }
+
+    fn with_gas(mut self, gas: Gas) -> Self {
+        self.gas = Some(gas);
+        self
+    }
+
+    pub fn passed(&self) -> bool {
+        self.failure.is_none()
+    }
+}
+
+/// Combined result of a CI run
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CiReport {
+    pub checks: Vec<CiCheck>,
+    pub duration: Duration,
+}
+
+impl CiReport {
+    pub fn failures(&self) -> Vec<&CiCheck> {
+        self.checks.iter().filter(|c| !c.passed()).collect()
+    }
+
+    /// Stages with at least one failed check
+    pub fn failed_stages(&self) -> Vec<CiStage> {
+        let mut stages: Vec<CiStage> = self.failures().iter().map(|c| c.stage).collect();
+        stages.sort();
+        stages.dedup();
+        stages
+    }
+
+    /// Static gas estimate of each graph checked for gas regression
+    pub fn gas_estimates(&self) -> GasBaseline {
+        self.checks
+            .iter()
+            .filter(|c| c.stage == CiStage::GasRegression)
+            .filter_map(|c| Some((c.target.clone(), c.gas?)))
+            .collect()
+    }
+
+    /// Process exit code: 0 when everything passed, otherwise the OR of the
+    /// failed stages' bits
+    pub fn exit_code(&self) -> i32 {
+        self.failed_stages().iter().fold(0, |code, stage| code | stage.exit_bit())
+    }
+
+    /// The checks as one test suite per stage, for a [`Reporter`](super::Reporter)
+    pub fn test_suites(&self) -> Vec<TestSuite> {
+        CiStage::ALL
+            .iter()
+            .map(|stage| TestSuite {
+                name: stage.name().to_string(),
+                cases: self
+                    .checks
+                    .iter()
+                    .filter(|c| c.stage == *stage)
+                    .map(|check| {
+                        let mut case = TestCase::new(&check.name, &check.target).with_duration(check.duration);
+                        if let Some(gas) = check.gas {
+                            case = case.with_property("gas", gas);
+                        }
+                        match &check.failure {
+                            Some(failure) => case.with_failure(failure, check.nodes.clone()),
+                            None => case,
+                        }
+                    })
+                    .collect(),
+            })
+            .filter(|suite| !suite.cases.is_empty())
+            .collect()
+    }
+}
+
+/// Static gas estimates of the graphs, keyed by graph file
+pub type GasBaseline = BTreeMap<String, Gas>;
+
+/// What the CI run checks
+#[derive(Debug, Clone)]
+pub struct CiOptions {
+    pub graphs: Vec<PathBuf>,
+    pub scenarios: Vec<PathBuf>,
+    /// Baseline file; gas regression is skipped when it does not exist
+    pub gas_baseline: Option<PathBuf>,
+    /// Allowed growth over the baseline, in percent
+    pub gas_tolerance_percent: f64,
+    /// Rewrite the baseline with this run's estimates instead of comparing
+    pub update_gas_baseline: bool,
+    /// Lowest severity that fails the security stage
+    pub security_threshold: Severity,
+    /// Scenarios run at once; 0 uses every available core
+    pub jobs: usize,
+    /// License policy of the workspace; the license stage is skipped without one
+    pub license_policy: Option<LicensePolicy>,
+    /// Directory of installed custom nodes the license policy applies to
+    pub nodes_dir: Option<PathBuf>,
+}
+
+impl Default for CiOptions {
+    fn default() -> Self {
+        Self {
+            graphs: Vec::new(),
+            scenarios: Vec::new(),
+            gas_baseline: None,
+            gas_tolerance_percent: 5.0,
+            update_gas_baseline: false,
+            security_threshold: Severity::High,
+            jobs: 0,
+            license_policy: None,
+            nodes_dir: None,
+        }
+    }
+}
+
+fn severity_rank(severity: &Severity) -> u8 {
+    match severity {
+        Severity::Low => 0,
+        Severity::Medium => 1,
+        Severity::High => 2,
+        Severity::Critical => 3,
+    }
+}
+
+fn timed(started: Instant, mut check: CiCheck) -> CiCheck {
+    check.duration = started.elapsed();
+    check
+}
+
+fn check_graph(
+    validator: &Validator,
+    assistant: &AiAssistant,
+    target: &str,
+    graph: &VisualGraph,
+    threshold: &Severity,
+    checks: &mut Vec<CiCheck>,
+) -> CanvasResult<()> {
+    let started = Instant::now();
+    let result = validator.validate(graph)?;
+    let check = CiCheck::new(CiStage::Validation, target, "validate");
+    checks.push(timed(
+        started,
+        if result.is_valid { check } else { check.failed(result.errors.join("; ")) },
+    ));
+    if result.warnings.is_empty() {
+        checks.push(CiCheck::new(CiStage::Lint, target, "lint"));
+    }
+    for warning in &result.warnings {
+        checks.push(CiCheck::new(CiStage::Lint, target, warning.clone()).failed(warning.clone()));
+    }
+
+    let started = Instant::now();
+    let mut issues: Vec<_> = assistant
+        .check_security_rules(graph)
+        .iter()
+        .map(|finding| finding.to_security_issue())
+        .collect();
+    issues.extend(assistant.detect_dos_patterns(graph));
+    let blocking: Vec<_> = issues
+        .iter()
+        .filter(|issue| severity_rank(&issue.severity) >= severity_rank(threshold))
+        .collect();
+    if blocking.is_empty() {
+        checks.push(timed(started, CiCheck::new(CiStage::Security, target, "security analysis")));
+    }
+    for issue in blocking {
+        checks.push(
+            CiCheck::new(CiStage::Security, target, issue.name.clone())
+                .failed(format!("{:?}: {} {}", issue.severity, issue.description, issue.mitigation))
+                .with_nodes(issue.nodes.clone()),
+        );
+    }
+    Ok(())
+}
+
+fn check_gas(
+    estimates: &GasBaseline,
+    baseline: &GasBaseline,
+    tolerance_percent: f64,
+    checks: &mut Vec<CiCheck>,
+) {
+    for (target, &gas) in estimates {
+        let check = CiCheck::new(CiStage::GasRegression, target, "static gas estimate").with_gas(gas);
+        checks.push(match baseline.get(target) {
+            Some(&previous) if gas as f64 > previous as f64 * (1.0 + tolerance_percent / 100.0) => check.failed(format!(
+                "estimated gas rose from {} to {} (+{:.1}%, tolerance {}%)",
+                previous,
+                gas,
+                (gas as f64 - previous as f64) * 100.0 / previous.max(1) as f64,
+                tolerance_percent
+            )),
+            _ => check,
+        });
+    }
+}
+
+/// Run the CI pipeline.
+///
+/// Check failures are part of the report; `Err` means the pipeline could not
+/// run (for example an unreadable graph).
+pub fn run_ci(config: &Config, options: &CiOptions) -> CanvasResult<CiReport> {
+    let started = Instant::now();
+    let mut report = CiReport::default();
+    let validator = Validator::new(config)?;
+    let assistant = AiAssistant::new(config)?;
+
+    let mut estimates = GasBaseline::new();
+    let mut graphs = Vec::new();
+    for path in &options.graphs {
+        let target = path.display().to_string();
+        let (graph, _) = load_graph(&std::fs::read_to_string(path)?)?;
+        check_graph(&validator, &assistant, &target, &graph, &options.security_threshold, &mut report.checks)?;
+        estimates.insert(target, estimate_graph_gas(&graph).total);
+        graphs.push((path.as_path(), graph));
+    }
+
+    let outcomes = run_scenarios_parallel(config, &options.scenarios, &ParallelOptions::default().with_jobs(options.jobs));
+    for (path, outcome) in options.scenarios.iter().zip(outcomes) {
+        let target = path.display().to_string();
+        match outcome {
+            Ok(result) => {
+                for (index, step) in result.steps.iter().enumerate() {
+                    let name = format!("{} #{} {}", result.name, index + 1, step.function);
+                    let mut check = CiCheck::new(CiStage::Scenario, &target, name).with_gas(step.gas_used);
+                    check.duration = step.duration;
+                    check.failure = step.failure.clone();
+                    report.checks.push(check);
+                }
+            }
+            Err(e) => report
+                .checks
+                .push(CiCheck::new(CiStage::Scenario, &target, "load").failed(e.to_string())),
+        }
+    }
+
+    if let Some(baseline_path) = &options.gas_baseline {
+        if options.update_gas_baseline {
+            std::fs::write(baseline_path, serde_json::to_string_pretty(&estimates)?)?;
+            log::info!("Gas baseline written to {}", baseline_path.display());
+        } else if baseline_path.exists() {
+            let baseline: GasBaseline = serde_json::from_str(&std::fs::read_to_string(baseline_path)?)?;
+            check_gas(&estimates, &baseline, options.gas_tolerance_percent, &mut report.checks);
+        } else {
+            log::warn!("No gas baseline at {}; skipping gas regression checks", baseline_path.display());
+        }
+    }
+
+    if let Some(policy) = &options.license_policy {
+        let registry = match options.nodes_dir.as_deref().filter(|dir| dir.exists()) {
+            Some(dir) => CustomNodeRegistry::load_dir(dir)?,
+            None => CustomNodeRegistry::new(),
+        };
+        let licenses = check_licenses(&registry, graphs.iter().map(|(path, graph)| (*path, graph)), policy);
+        for entry in licenses.entries {
+            let license = entry.license.as_deref().unwrap_or("unlicensed");
+            let name = format!("{} {} ({})", entry.node_id, entry.version, license);
+            let check = CiCheck::new(CiStage::License, &entry.node_id, name);
+            report.checks.push(match entry.violation {
+                Some(violation) => check.failed(violation),
+                None => check,
+            });
+        }
+    }
+
+    report.duration = started.elapsed();
+    Ok(report)
+}
+
+/// Graph files in a project directory: `*.json` files that are not scenarios,
+/// ABIs or the gas baseline
+pub fn discover_graphs(dir: &Path, exclude: &[&Path]) -> CanvasResult<Vec<PathBuf>> {
+    let mut graphs = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        let name = path.to_string_lossy().to_string();
+        if path.is_file()
+            && name.ends_with(".json")
+            && !name.ends_with(super::scenario::SCENARIO_EXTENSION)
+            && !name.ends_with(".abi.json")
+            && !exclude.contains(&path.as_path())
+        {
+            graphs.push(path);
+        }
+    }
+    graphs.sort();
+    Ok(graphs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exit_code_combines_failed_stages() {
+        let mut report = CiReport::default();
+        report.checks.push(CiCheck::new(CiStage::Validation, "a.json", "validate"));
+        assert_eq!(report.exit_code(), 0);
+
+        report.checks.push(CiCheck::new(CiStage::Lint, "a.json", "lint").failed("unused node"));
+        report.checks.push(CiCheck::new(CiStage::Security, "a.json", "Reentrancy").failed("<High>"));
+        assert_eq!(report.exit_code(), 4 | 32);
+        assert_eq!(report.failed_stages(), vec![CiStage::Lint, CiStage::Security]);
+
+        let suites = report.test_suites();
+        let names: Vec<&str> = suites.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, vec!["validation", "lint", "security"]);
+        assert_eq!(suites[1].failures(), 1);
+    }
+
+    #[test]
+    fn test_gas_regression_tolerance() {
+        let baseline = GasBaseline::from([("token.json".to_string(), 1000), ("vault.json".to_string(), 1000)]);
+        let estimates = GasBaseline::from([("token.json".to_string(), 1040), ("vault.json".to_string(), 1100)]);
+        let mut checks = Vec::new();
+        check_gas(&estimates, &baseline, 5.0, &mut checks);
+        assert!(checks[0].passed());
+        assert!(checks[1].failure.as_ref().unwrap().contains("+10.0%"));
+    }
+}
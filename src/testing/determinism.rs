@@ -0,0 +1,536 @@
+//! Simulation determinism checks
+//!
+//! Local results only predict chain execution if execution is deterministic.
+//! A determinism check runs the same call several times, spread over threads
+//! and optionally over other hosts running a [`DeterminismWorker`], and
+//! compares a fingerprint of every run bit-for-bit with the first: output,
+//! revert, gas, storage writes, events, host calls and tracepoints. Where runs
+//! diverge, the first host call answered differently names the host function
+//! at fault, and for instrumented builds the first differing tracepoint names
+//! the graph node.
+//!
+//! Workers answer `POST /determinism/run` with a [`WorkerRequest`] body by
+//! running the call the requested number of times and returning a
+//! [`WorkerResponse`].
+
+use std::{collections::BTreeMap, io::Read, time::Duration};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    compiler::TraceMap,
+    config::Config,
+    error::{CanvasError, CanvasResult},
+    nodes::{decode_hex, encode_hex},
+    types::{ExecutionContext, Gas, HostCall, NodeId, TraceEvent},
+    wasm::{host, BlockContext, SandboxAccounts, SimulationRequest, SimulationResult, WasmRuntime},
+};
+
+/// Route workers serve runs on
+pub const WORKER_ROUTE: &str = "/determinism/run";
+
+const WORKER_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Platform this process runs on, e.g. `linux-x86_64`
+pub fn platform() -> String {
+    format!("{}-{}", std::env::consts::OS, std::env::consts::ARCH)
+}
+
+/// Everything observable about one run, except how long it took
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RunFingerprint {
+    pub output: serde_json::Value,
+    pub gas_used: Gas,
+    pub storage_bytes: u64,
+    /// Events as JSON, so their data compares independently of map order
+    pub events: serde_json::Value,
+    pub storage: BTreeMap<String, serde_json::Value>,
+    pub host_calls: Vec<HostCall>,
+    pub trace: Vec<TraceEvent>,
+}
+
+impl RunFingerprint {
+    pub fn of(result: &SimulationResult, context: &ExecutionContext) -> CanvasResult<Self> {
+        Ok(Self {
+            output: result.output.clone(),
+            gas_used: result.gas_used,
+            storage_bytes: result.storage_bytes,
+            events: serde_json::to_value(&result.events)?,
+            storage: context.storage.iter().map(|(k, v)| (k.clone(), v.clone())).collect(),
+            host_calls: context.host_calls.clone(),
+            trace: context.trace.clone(),
+        })
+    }
+
+    /// SHA-256 of the fingerprint's JSON encoding, 0x-hex
+    pub fn digest(&self) -> CanvasResult<String> {
+        let bytes = serde_json::to_vec(self)?;
+        Ok(encode_hex(&host::hash(host::HashAlgorithm::Sha256, &bytes)))
+    }
+
+    /// Parts that differ from `other`
+    fn differing_fields(&self, other: &Self) -> Vec<&'static str> {
+        let mut fields = Vec::new();
+        let mut check = |name, differs: bool| {
+            if differs {
+                fields.push(name);
+            }
+        };
+        check("output", self.output != other.output);
+        check("gas_used", self.gas_used != other.gas_used);
+        check("storage_bytes", self.storage_bytes != other.storage_bytes);
+        check("events", self.events != other.events);
+        check("storage", self.storage != other.storage);
+        check("host_calls", self.host_calls != other.host_calls);
+        check("trace", self.trace != other.trace);
+        fields
+    }
+}
+
+/// Execute `request` once in a fresh sandbox
+pub fn fingerprint_run(runtime: &WasmRuntime, wasm_bytes: &[u8], request: &SimulationRequest) -> CanvasResult<RunFingerprint> {
+    let mut context = ExecutionContext::new(request.gas_limit);
+    let result = runtime.execute_request_in(wasm_bytes, request, &mut SandboxAccounts::new(), &mut context)?;
+    RunFingerprint::of(&result, &context)
+}
+
+/// How a determinism check is run
+#[derive(Debug, Clone)]
+pub struct DeterminismOptions {
+    /// Runs in this process
+    pub runs: usize,
+    /// Threads the local runs are spread over; 0 uses every available core
+    pub jobs: usize,
+    /// Base URLs of workers, each asked for `runs` runs as well
+    pub workers: Vec<String>,
+}
+
+impl Default for DeterminismOptions {
+    fn default() -> Self {
+        Self {
+            runs: 5,
+            jobs: 0,
+            workers: Vec::new(),
+        }
+    }
+}
+
+/// One run of a check
+#[derive(Debug, Clone, Serialize)]
+pub struct RunSummary {
+    /// `local#<n>` or `<worker url>#<n>`
+    pub label: String,
+    pub platform: String,
+    pub digest: String,
+}
+
+/// A run that differs from the reference run
+#[derive(Debug, Clone, Serialize)]
+pub struct Divergence {
+    pub run: String,
+    pub fields: Vec<&'static str>,
+    /// Host function that answered the same call differently
+    pub host_function: Option<String>,
+    /// First tracepoint that differs
+    pub tracepoint: Option<u32>,
+    /// Graph node of that tracepoint, when the build's trace map is known
+    pub node: Option<NodeId>,
+    pub detail: String,
+}
+
+/// Outcome of a determinism check
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct DeterminismReport {
+    /// Every run; the first is the reference
+    pub runs: Vec<RunSummary>,
+    pub divergences: Vec<Divergence>,
+}
+
+impl DeterminismReport {
+    pub fn is_deterministic(&self) -> bool {
+        self.divergences.is_empty()
+    }
+
+    /// Host functions flagged by any divergence
+    pub fn nondeterministic_host_functions(&self) -> Vec<String> {
+        let mut functions: Vec<String> = self.divergences.iter().filter_map(|d| d.host_function.clone()).collect();
+        functions.sort();
+        functions.dedup();
+        functions
+    }
+
+    /// Graph nodes flagged by any divergence
+    pub fn nondeterministic_nodes(&self) -> Vec<NodeId> {
+        let mut nodes: Vec<NodeId> = self.divergences.iter().filter_map(|d| d.node).collect();
+        nodes.sort();
+        nodes.dedup();
+        nodes
+    }
+
+    /// Fail with one line per divergent run
+    pub fn ensure_deterministic(&self) -> CanvasResult<()> {
+        if self.is_deterministic() {
+            return Ok(());
+        }
+        let lines: Vec<String> = self
+            .divergences
+            .iter()
+            .map(|d| format!("{}: {} ({})", d.run, d.detail, d.fields.join(", ")))
+            .collect();
+        Err(CanvasError::Validation(format!(
+            "{} of {} runs diverged from the first:\n{}",
+            self.divergences.len(),
+            self.runs.len(),
+            lines.join("\n")
+        )))
+    }
+}
+
+/// Whether two host calls are the same request that got different answers,
+/// as opposed to the contract asking for something else
+fn answered_differently(expected: &HostCall, actual: &HostCall) -> bool {
+    if expected.import != actual.import || expected.gas_used != actual.gas_used {
+        return false;
+    }
+    match expected.import.as_str() {
+        host::HOST_WRITE_STORAGE | host::HOST_EMIT_EVENT => false,
+        host::HOST_READ_STORAGE => expected.result["key"] == actual.result["key"],
+        _ => true,
+    }
+}
+
+/// Where `run` first departs from `reference`
+fn compare(
+    label: &str,
+    reference: &RunFingerprint,
+    run: &RunFingerprint,
+    trace_map: Option<&TraceMap>,
+) -> Option<Divergence> {
+    let fields = reference.differing_fields(run);
+    if fields.is_empty() {
+        return None;
+    }
+
+    let mut host_function = None;
+    let mut detail = format!("differs in {}", fields.join(", "));
+    let first_call = reference.host_calls.iter().zip(&run.host_calls).position(|(a, b)| a != b);
+    if let Some(index) = first_call {
+        let (expected, actual) = (&reference.host_calls[index], &run.host_calls[index]);
+        if answered_differently(expected, actual) {
+            host_function = Some(expected.import.clone());
+            detail = format!(
+                "host call #{} to {} answered {} instead of {}",
+                index + 1,
+                expected.import,
+                actual.result,
+                expected.result
+            );
+        } else {
+            detail = format!(
+                "host call #{} was {} instead of {}",
+                index + 1,
+                actual.import,
+                expected.import
+            );
+        }
+    } else if reference.host_calls.len() != run.host_calls.len() {
+        detail = format!("made {} host calls instead of {}", run.host_calls.len(), reference.host_calls.len());
+    }
+
+    let tracepoint = reference
+        .trace
+        .iter()
+        .zip(&run.trace)
+        .find(|(a, b)| a != b)
+        .map(|(a, _)| a.tracepoint())
+        .or_else(|| {
+            let shorter = reference.trace.len().min(run.trace.len());
+            let longer = if reference.trace.len() > run.trace.len() { &reference.trace } else { &run.trace };
+            longer.get(shorter).map(TraceEvent::tracepoint)
+        });
+
+    Some(Divergence {
+        run: label.to_string(),
+        fields,
+        host_function,
+        tracepoint,
+        node: tracepoint.and_then(|t| trace_map?.node(t)),
+        detail,
+    })
+}
+
+/// Run `request` `runs` times over `jobs` threads, each with its own runtime
+fn local_runs(config: &Config, wasm_bytes: &[u8], request: &SimulationRequest, runs: usize, jobs: usize) -> CanvasResult<Vec<RunFingerprint>> {
+    let jobs = match jobs {
+        0 => std::thread::available_parallelism().map_or(1, |n| n.get()),
+        jobs => jobs,
+    }
+    .clamp(1, runs.max(1));
+
+    let batches: Vec<CanvasResult<Vec<(usize, RunFingerprint)>>> = std::thread::scope(|scope| {
+        let handles: Vec<_> = (0..jobs)
+            .map(|job| {
+                scope.spawn(move || {
+                    let runtime = WasmRuntime::new(config)?;
+                    (job..runs)
+                        .step_by(jobs)
+                        .map(|index| Ok((index, fingerprint_run(&runtime, wasm_bytes, request)?)))
+                        .collect::<CanvasResult<Vec<_>>>()
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .map(|handle| {
+                handle
+                    .join()
+                    .unwrap_or_else(|_| Err(CanvasError::InvalidState("Determinism run panicked".to_string())))
+            })
+            .collect()
+    });
+
+    let mut fingerprints = Vec::with_capacity(runs);
+    for batch in batches {
+        fingerprints.extend(batch?);
+    }
+    fingerprints.sort_by_key(|(index, _)| *index);
+    Ok(fingerprints.into_iter().map(|(_, fingerprint)| fingerprint).collect())
+}
+
+/// Run `request` repeatedly, locally and on the configured workers, and
+/// compare every run with the first
+pub fn check_determinism(
+    config: &Config,
+    wasm_bytes: &[u8],
+    request: &SimulationRequest,
+    options: &DeterminismOptions,
+    trace_map: Option<&TraceMap>,
+) -> CanvasResult<DeterminismReport> {
+    let mut runs: Vec<(String, String, RunFingerprint)> = local_runs(config, wasm_bytes, request, options.runs, options.jobs)?
+        .into_iter()
+        .enumerate()
+        .map(|(index, fingerprint)| (format!("local#{}", index + 1), platform(), fingerprint))
+        .collect();
+
+    let worker_request = WorkerRequest::new(wasm_bytes, request, options.runs);
+    for url in &options.workers {
+        let response = worker_request.send(url)?;
+        runs.extend(
+            response
+                .runs
+                .into_iter()
+                .enumerate()
+                .map(|(index, fingerprint)| (format!("{}#{}", url, index + 1), response.platform.clone(), fingerprint)),
+        );
+    }
+
+    let mut report = DeterminismReport::default();
+    let Some((_, _, reference)) = runs.first() else {
+        return Ok(report);
+    };
+    for (label, platform, fingerprint) in &runs {
+        report.runs.push(RunSummary {
+            label: label.clone(),
+            platform: platform.clone(),
+            digest: fingerprint.digest()?,
+        });
+        report.divergences.extend(compare(label, reference, fingerprint, trace_map));
+    }
+    Ok(report)
+}
+
+/// Body of a worker run request
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkerRequest {
+    /// Contract WASM, 0x-hex
+    pub wasm: String,
+    pub function: String,
+    #[serde(default)]
+    pub arguments: Vec<serde_json::Value>,
+    pub gas_limit: Gas,
+    #[serde(default)]
+    pub caller: Option<String>,
+    /// Decimal string, since values can exceed JSON's safe integer range
+    #[serde(default)]
+    pub value: Option<String>,
+    #[serde(default)]
+    pub block: BlockContext,
+    pub runs: usize,
+}
+
+impl WorkerRequest {
+    pub fn new(wasm_bytes: &[u8], request: &SimulationRequest, runs: usize) -> Self {
+        Self {
+            wasm: encode_hex(wasm_bytes),
+            function: request.function.clone(),
+            arguments: request.arguments.clone(),
+            gas_limit: request.gas_limit,
+            caller: request.caller.clone(),
+            value: Some(request.value.to_string()),
+            block: request.block,
+            runs,
+        }
+    }
+
+    /// The call to run and the contract to run it on
+    pub fn decode(&self) -> CanvasResult<(Vec<u8>, SimulationRequest)> {
+        let wasm = decode_hex(&self.wasm)
+            .ok_or_else(|| CanvasError::Validation("Worker request WASM is not 0x-hex".to_string()))?;
+        let value = match self.value.as_deref() {
+            Some(value) => value
+                .parse::<u128>()
+                .map_err(|e| CanvasError::Validation(format!("Invalid value '{}': {}", value, e)))?,
+            None => 0,
+        };
+        let mut request = SimulationRequest::new(self.function.clone(), self.arguments.clone(), self.gas_limit);
+        request.set_value(value).set_block(self.block);
+        if let Some(caller) = &self.caller {
+            request.set_caller(caller.clone());
+        }
+        Ok((wasm, request))
+    }
+
+    /// Ask the worker at `url` to run the call
+    pub fn send(&self, url: &str) -> CanvasResult<WorkerResponse> {
+        let endpoint = format!("{}{}", url.trim_end_matches('/'), WORKER_ROUTE);
+        ureq::AgentBuilder::new()
+            .timeout(WORKER_TIMEOUT)
+            .build()
+            .post(&endpoint)
+            .send_json(serde_json::to_value(self)?)
+            .map_err(|e| CanvasError::Network(format!("Determinism worker {} failed: {}", url, e)))?
+            .into_json()
+            .map_err(|e| CanvasError::Network(format!("Invalid response from determinism worker {}: {}", url, e)))
+    }
+}
+
+/// Runs a worker made
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkerResponse {
+    pub platform: String,
+    pub runs: Vec<RunFingerprint>,
+}
+
+/// Answers run requests from other hosts' determinism checks
+pub struct DeterminismWorker {
+    config: Config,
+    /// Most runs one request may ask for
+    max_runs: usize,
+}
+
+impl DeterminismWorker {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            config: config.clone(),
+            max_runs: 100,
+        }
+    }
+
+    /// Answer one request with its HTTP status and JSON body
+    pub fn handle(&self, method: &str, path: &str, body: &[u8]) -> (u16, serde_json::Value) {
+        let error = |status: u16, message: String| (status, serde_json::json!({ "error": message }));
+        if path.split('?').next().unwrap_or(path).trim_end_matches('/') != WORKER_ROUTE {
+            return error(404, format!("no route for {}", path));
+        }
+        if method != "POST" {
+            return error(405, format!("{} is not supported on {}", method, WORKER_ROUTE));
+        }
+        let request: WorkerRequest = match serde_json::from_slice(body) {
+            Ok(request) => request,
+            Err(e) => return error(400, format!("invalid run request: {}", e)),
+        };
+        if request.runs == 0 || request.runs > self.max_runs {
+            return error(400, format!("runs must be between 1 and {}", self.max_runs));
+        }
+        let runs = request
+            .decode()
+            .and_then(|(wasm, call)| local_runs(&self.config, &wasm, &call, request.runs, 0));
+        match runs.and_then(|runs| Ok(serde_json::to_value(WorkerResponse { platform: platform(), runs })?)) {
+            Ok(body) => (200, body),
+            Err(e) => error(422, e.to_string()),
+        }
+    }
+
+    /// Serve run requests on `address` (`host:port`) until the process exits
+    pub fn serve(&self, address: &str) -> CanvasResult<()> {
+        let server = tiny_http::Server::http(address)
+            .map_err(|e| CanvasError::Network(format!("Failed to listen on {}: {}", address, e)))?;
+        log::info!("Determinism worker ({}) listening on http://{}", platform(), address);
+        let content_type =
+            tiny_http::Header::from_bytes("Content-Type", "application/json").expect("static header is valid");
+
+        for mut request in server.incoming_requests() {
+            let mut body = Vec::new();
+            let (status, response) = match request.as_reader().read_to_end(&mut body) {
+                Ok(_) => self.handle(request.method().as_str(), request.url(), &body),
+                Err(e) => (400, serde_json::json!({ "error": format!("failed to read body: {}", e) })),
+            };
+            log::debug!("{} {} -> {}", request.method(), request.url(), status);
+            let reply = tiny_http::Response::from_string(response.to_string())
+                .with_status_code(status)
+                .with_header(content_type.clone());
+            if let Err(e) = request.respond(reply) {
+                log::warn!("Failed to send response: {}", e);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONTRACT: &str = r#"(module
+        (import "env" "baals_block_number" (func $block (result i64)))
+        (func (export "main") (param i32) (result i32)
+            (drop (call $block))
+            (i32.mul (local.get 0) (i32.const 2))))"#;
+
+    #[test]
+    fn test_repeated_runs_match() {
+        let request = SimulationRequest::new("main", vec![serde_json::json!(21)], 10_000);
+        let options = DeterminismOptions {
+            runs: 4,
+            jobs: 2,
+            ..DeterminismOptions::default()
+        };
+        let report = check_determinism(&Config::default(), CONTRACT.as_bytes(), &request, &options, None).unwrap();
+        assert_eq!(report.runs.len(), 4);
+        assert!(report.runs.iter().all(|run| run.digest == report.runs[0].digest));
+        assert!(report.ensure_deterministic().is_ok());
+    }
+
+    #[test]
+    fn test_divergent_host_call_is_flagged() {
+        let runtime = WasmRuntime::new(&Config::default()).unwrap();
+        let request = SimulationRequest::new("main", vec![serde_json::json!(21)], 10_000);
+        let reference = fingerprint_run(&runtime, CONTRACT.as_bytes(), &request).unwrap();
+        assert_eq!(reference.host_calls.len(), 1);
+
+        let mut run = reference.clone();
+        run.host_calls[0].result = serde_json::json!(999);
+        let divergence = compare("local#2", &reference, &run, None).unwrap();
+        assert_eq!(divergence.fields, vec!["host_calls"]);
+        assert_eq!(divergence.host_function.as_deref(), Some(host::HOST_BLOCK_NUMBER));
+
+        run.trace.push(TraceEvent::Enter { tracepoint: 3, gas_used: 0 });
+        assert_eq!(compare("local#2", &reference, &run, None).unwrap().tracepoint, Some(3));
+        assert!(compare("local#1", &reference, &reference, None).is_none());
+    }
+
+    #[test]
+    fn test_worker_runs_requests() {
+        let worker = DeterminismWorker::new(&Config::default());
+        let request = SimulationRequest::new("main", vec![serde_json::json!(2)], 10_000);
+        let body = serde_json::to_vec(&WorkerRequest::new(CONTRACT.as_bytes(), &request, 2)).unwrap();
+
+        let (status, response) = worker.handle("POST", WORKER_ROUTE, &body);
+        assert_eq!(status, 200);
+        let response: WorkerResponse = serde_json::from_value(response).unwrap();
+        assert_eq!(response.runs.len(), 2);
+        assert_eq!(response.runs[0], response.runs[1]);
+        assert_eq!(worker.handle("GET", WORKER_ROUTE, &body).0, 405);
+        assert_eq!(worker.handle("POST", WORKER_ROUTE, b"{}").0, 400);
+    }
+}
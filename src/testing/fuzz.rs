@@ -0,0 +1,747 @@
+//! Scenario fuzzing and failure minimization
+//!
+//! The fuzzer calls a contract's functions in random order with random
+//! arguments, callers, call values and block advances. A step fails when it
+//! reverts with an error the ABI does not declare (a trap, running out of
+//! gas, an unknown error) or cannot be executed at all. A failing sequence is
+//! then minimized: steps are dropped and argument values shrunk for as long as
+//! the same function still fails with the same error. For instrumented builds
+//! the trace of the failing step narrows the failure down to the nodes that
+//! led to it.
+//!
+//! The minimized sequence is kept as a scenario file. Its earlier steps expect
+//! what they did while fuzzing and the failing step expects to succeed, so the
+//! scenario fails until the bug is fixed and guards against it coming back.
+
+use std::{
+    collections::{BTreeSet, HashSet, VecDeque},
+    path::{Path, PathBuf},
+};
+
+use rand::{distributions::Alphanumeric, rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    recorder::relative_to,
+    scenario::{Scenario, ScenarioStep, StepExpectation, DEFAULT_STEP_GAS_LIMIT, SCENARIO_EXTENSION},
+};
+use crate::{
+    compiler::{find_function, validate_call_args, TraceMap},
+    error::{CanvasError, CanvasResult},
+    types::{ContractABI, Decimal, ExecutionContext, Gas, NodeId, StateMutability, TraceEvent, ValueType, VisualGraph},
+    wasm::{
+        accounts::{DEFAULT_CALLER, INSUFFICIENT_BALANCE},
+        storage::{shared, MemoryBackend},
+        BlockAdvance, BlockContext, SandboxAccounts, SimulationRequest, WasmRuntime,
+    },
+};
+
+/// Accounts fuzzed calls are made from
+pub const FUZZ_CALLERS: [&str; 3] = [
+    DEFAULT_CALLER,
+    "0x0000000000000000000000000000000000000002",
+    "0x0000000000000000000000000000000000000003",
+];
+/// Balance each fuzz caller starts with
+pub const FUZZ_BALANCE: u128 = 1_000_000_000;
+/// Error name of a step that could not be executed
+pub const EXECUTION_ERROR: &str = "Error";
+
+/// How a contract is fuzzed
+#[derive(Debug, Clone)]
+pub struct FuzzOptions {
+    /// Random call sequences to try
+    pub runs: usize,
+    /// Calls in the longest sequence
+    pub max_steps: usize,
+    pub gas_limit: Gas,
+    /// Seed of the generator; the same seed tries the same sequences
+    pub seed: u64,
+    /// Sequences executed while minimizing a failure, at most
+    pub max_shrink_runs: usize,
+}
+
+impl Default for FuzzOptions {
+    fn default() -> Self {
+        Self {
+            runs: 200,
+            max_steps: 8,
+            gas_limit: DEFAULT_STEP_GAS_LIMIT,
+            seed: 0,
+            max_shrink_runs: 2_000,
+        }
+    }
+}
+
+/// A step that failed
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FuzzFailure {
+    /// Index of the step in its sequence
+    pub step: usize,
+    pub function: String,
+    /// Revert error, or [`EXECUTION_ERROR`] when the call could not run
+    pub error: String,
+    pub message: String,
+    /// Trace of the failing step; empty unless the build is instrumented
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub trace: Vec<TraceEvent>,
+}
+
+impl FuzzFailure {
+    /// Whether `other` is the same failure, wherever in its sequence it happened
+    pub fn same_as(&self, other: &Self) -> bool {
+        self.function == other.function && self.error == other.error
+    }
+}
+
+/// What running a sequence of steps did
+#[derive(Debug, Clone, Default)]
+pub struct Execution {
+    /// Declared error each step before the failure reverted with, if any
+    pub reverts: Vec<Option<String>>,
+    pub failure: Option<FuzzFailure>,
+}
+
+/// A failing sequence, minimized
+#[derive(Debug, Clone, Serialize)]
+pub struct FuzzFinding {
+    pub seed: u64,
+    /// Run that found the failure, counting from 0
+    pub run: usize,
+    /// Steps of the sequence as generated
+    pub original_steps: usize,
+    pub steps: Vec<ScenarioStep>,
+    pub failure: FuzzFailure,
+    /// Sequences executed while minimizing
+    pub shrink_runs: usize,
+}
+
+/// Fuzzes one compiled contract
+pub struct Fuzzer<'a> {
+    runtime: &'a WasmRuntime,
+    wasm_bytes: &'a [u8],
+    abi: &'a ContractABI,
+    /// Errors the ABI declares, which are legitimate outcomes
+    declared: HashSet<String>,
+}
+
+impl<'a> Fuzzer<'a> {
+    pub fn new(runtime: &'a WasmRuntime, wasm_bytes: &'a [u8], abi: &'a ContractABI) -> Self {
+        let mut declared: HashSet<String> = abi.errors.iter().map(|e| e.name.clone()).collect();
+        // Value transfers the caller cannot cover revert before the contract runs
+        declared.insert(INSUFFICIENT_BALANCE.to_string());
+        Self {
+            runtime,
+            wasm_bytes,
+            abi,
+            declared,
+        }
+    }
+
+    /// Run `steps` in a fresh sandbox, stopping at the first failing step.
+    ///
+    /// `Err` means the steps are not valid calls of the contract.
+    pub fn execute(&self, steps: &[ScenarioStep]) -> CanvasResult<Execution> {
+        let mut accounts =
+            SandboxAccounts::new().with_balances(FUZZ_CALLERS.iter().map(|caller| (caller.to_string(), FUZZ_BALANCE)));
+        let storage = shared(MemoryBackend::new());
+        let mut block = BlockContext::default();
+        let mut execution = Execution::default();
+        for (index, step) in steps.iter().enumerate() {
+            let signature = find_function(self.abi, &step.function)
+                .ok_or_else(|| CanvasError::Validation(format!("Contract has no function '{}'", step.function)))?;
+            let args = validate_call_args(signature, &step.args)?;
+            if let Some(advance) = &step.advance {
+                block.advance(advance);
+            }
+            let mut request = SimulationRequest::new(step.function.clone(), args, step.gas_limit);
+            request.set_value(step.value).set_block(block);
+            if let Some(caller) = &step.caller {
+                request.set_caller(caller.clone());
+            }
+            let mut context = ExecutionContext::new(step.gas_limit).with_backend(storage.clone());
+            let failure = |error: &str, message: String, trace: Vec<TraceEvent>| FuzzFailure {
+                step: index,
+                function: step.function.clone(),
+                error: error.to_string(),
+                message,
+                trace,
+            };
+            let result = match self.runtime.execute_request_in(self.wasm_bytes, &request, &mut accounts, &mut context) {
+                Ok(result) => result,
+                Err(e) => {
+                    execution.failure = Some(failure(EXECUTION_ERROR, e.to_string(), context.trace.clone()));
+                    break;
+                }
+            };
+            match result.revert_reason {
+                Some(reason) if !self.declared.contains(&reason.error) => {
+                    execution.failure = Some(failure(&reason.error, reason.message, context.trace.clone()));
+                    break;
+                }
+                Some(reason) => execution.reverts.push(Some(reason.error)),
+                None => {
+                    context.commit_storage()?;
+                    execution.reverts.push(None);
+                }
+            }
+        }
+        Ok(execution)
+    }
+
+    fn generate_step(&self, rng: &mut StdRng, gas_limit: Gas) -> ScenarioStep {
+        let function = &self.abi.functions[rng.gen_range(0..self.abi.functions.len())];
+        let args = function.inputs.iter().map(|p| random_value(rng, &p.value_type, 0)).collect();
+        let caller = if rng.gen_bool(0.5) {
+            FUZZ_CALLERS.choose(rng).map(|c| c.to_string())
+        } else {
+            None
+        };
+        let value = if function.state_mutability == StateMutability::Payable && rng.gen_bool(0.5) {
+            rng.gen_range(1..=FUZZ_BALANCE / 10)
+        } else {
+            0
+        };
+        let advance = if rng.gen_bool(0.2) {
+            Some(BlockAdvance {
+                blocks: rng.gen_range(1..=1_000),
+                seconds: None,
+            })
+        } else {
+            None
+        };
+        ScenarioStep {
+            function: function.name.clone(),
+            args: serde_json::Value::Array(args),
+            caller,
+            value,
+            advance,
+            gas_limit,
+            expect: StepExpectation::default(),
+        }
+    }
+
+    /// Try random sequences until one fails, and minimize it
+    pub fn run(&self, options: &FuzzOptions) -> CanvasResult<Option<FuzzFinding>> {
+        if self.abi.functions.is_empty() {
+            return Err(CanvasError::Validation("Contract ABI has no functions to fuzz".to_string()));
+        }
+        let mut rng = StdRng::seed_from_u64(options.seed);
+        for run in 0..options.runs {
+            let len = rng.gen_range(1..=options.max_steps.max(1));
+            let steps: Vec<ScenarioStep> = (0..len).map(|_| self.generate_step(&mut rng, options.gas_limit)).collect();
+            let Some(failure) = self.execute(&steps)?.failure else {
+                continue;
+            };
+            log::info!("Run {} failed at step {}: {} {}", run, failure.step + 1, failure.function, failure.error);
+            let original_steps = steps.len();
+            let (steps, failure, shrink_runs) = minimize(self.abi, steps, failure, options.max_shrink_runs, |candidate| {
+                self.execute(candidate).ok().and_then(|e| e.failure)
+            });
+            return Ok(Some(FuzzFinding {
+                seed: options.seed,
+                run,
+                original_steps,
+                steps,
+                failure,
+                shrink_runs,
+            }));
+        }
+        Ok(None)
+    }
+
+    /// Scenario replaying a finding, with contract and ABI paths as given
+    pub fn repro_scenario(
+        &self,
+        name: impl Into<String>,
+        contract: impl Into<PathBuf>,
+        finding: &FuzzFinding,
+    ) -> CanvasResult<Scenario> {
+        let execution = self.execute(&finding.steps)?;
+        let mut steps = finding.steps.clone();
+        for (step, revert) in steps.iter_mut().zip(&execution.reverts) {
+            step.expect.reverts = revert.clone();
+        }
+        Ok(Scenario {
+            name: name.into(),
+            contract: contract.into(),
+            abi: None,
+            balances: FUZZ_CALLERS.iter().map(|caller| (caller.to_string(), FUZZ_BALANCE)).collect(),
+            block: BlockContext::default(),
+            steps,
+        })
+    }
+
+    /// Write the repro scenario of a finding to `path`, with the contract
+    /// path relative to it where possible
+    pub fn save_repro(&self, path: &Path, contract: &Path, finding: &FuzzFinding) -> CanvasResult<Scenario> {
+        let file_name = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+        let name = file_name.trim_end_matches(SCENARIO_EXTENSION);
+        let base = path.parent().unwrap_or_else(|| Path::new("."));
+        let scenario = self.repro_scenario(name, relative_to(contract, base), finding)?;
+        scenario.save(path)?;
+        Ok(scenario)
+    }
+}
+
+fn random_value(rng: &mut StdRng, value_type: &ValueType, depth: usize) -> serde_json::Value {
+    const EDGE_INTEGERS: [i64; 6] = [0, 1, -1, i64::MAX, i64::MIN, u32::MAX as i64];
+    let len = |rng: &mut StdRng| if depth >= 2 { 0 } else { rng.gen_range(0..=3) };
+    match value_type {
+        ValueType::Boolean => serde_json::json!(rng.gen::<bool>()),
+        ValueType::Float => match rng.gen_range(0..4) {
+            0 => serde_json::json!(0.0),
+            _ => serde_json::json!(rng.gen_range(-1e9..1e9)),
+        },
+        ValueType::String => {
+            let len = rng.gen_range(0..=16);
+            serde_json::json!((0..len).map(|_| rng.sample(Alphanumeric) as char).collect::<String>())
+        }
+        ValueType::Bytes => {
+            let len = rng.gen_range(0..=32);
+            let hex: String = (0..len).map(|_| format!("{:02x}", rng.gen::<u8>())).collect();
+            serde_json::json!(format!("0x{}", hex))
+        }
+        ValueType::Decimal(scale) => Decimal::from_units(rng.gen_range(-1_000_000..=1_000_000), *scale).to_json(),
+        ValueType::Array(inner) => {
+            let len = len(rng);
+            serde_json::Value::Array((0..len).map(|_| random_value(rng, inner, depth + 1)).collect())
+        }
+        ValueType::Map(inner) => {
+            let len = len(rng);
+            let entries = (0..len).map(|i| (format!("k{}", i), random_value(rng, inner, depth + 1))).collect();
+            serde_json::Value::Object(entries)
+        }
+        ValueType::Object(fields) => serde_json::Value::Object(
+            fields
+                .iter()
+                .map(|(name, field)| (name.clone(), random_value(rng, field, depth + 1)))
+                .collect(),
+        ),
+        ValueType::Integer | ValueType::Flow | ValueType::Any => match rng.gen_range(0..4) {
+            0 => serde_json::json!(*EDGE_INTEGERS.choose(rng).unwrap_or(&0)),
+            1 => serde_json::json!(rng.gen::<i64>()),
+            _ => serde_json::json!(rng.gen_range(-1_000..=1_000)),
+        },
+    }
+}
+
+/// Smaller values of the same type as `value`, simplest first
+fn shrink_value(value_type: &ValueType, value: &serde_json::Value) -> Vec<serde_json::Value> {
+    use serde_json::Value;
+
+    match (value_type, value) {
+        (ValueType::Decimal(scale), value) => match Decimal::from_json(value, *scale) {
+            Ok(decimal) if decimal.units != 0 => [0, decimal.units / 2]
+                .into_iter()
+                .map(|units| Decimal::from_units(units, *scale).to_json())
+                .filter(|candidate| candidate != value)
+                .collect(),
+            _ => Vec::new(),
+        },
+        (_, Value::Bool(true)) => vec![Value::Bool(false)],
+        (_, Value::Number(n)) => {
+            if let Some(i) = n.as_i64() {
+                let mut candidates = vec![0, i / 2];
+                if i < 0 {
+                    candidates.push(-(i / 2));
+                }
+                candidates.into_iter().filter(|&c| c != i).map(Value::from).collect()
+            } else if let Some(u) = n.as_u64() {
+                vec![Value::from(0), Value::from(u / 2)]
+            } else {
+                let f = n.as_f64().unwrap_or(0.0);
+                [0.0, f.trunc(), f / 2.0]
+                    .into_iter()
+                    .filter(|&c| c != f && c.is_finite())
+                    .map(Value::from)
+                    .collect()
+            }
+        }
+        (ValueType::Bytes, Value::String(s)) => {
+            let hex = s.strip_prefix("0x").unwrap_or(s);
+            if hex.is_empty() {
+                return Vec::new();
+            }
+            let half = hex.len() / 4 * 2;
+            vec![Value::from("0x"), Value::from(format!("0x{}", &hex[..half]))]
+        }
+        (_, Value::String(s)) if !s.is_empty() => {
+            let half: String = s.chars().take(s.chars().count() / 2).collect();
+            let mut candidates = vec![Value::from("")];
+            if !half.is_empty() {
+                candidates.push(Value::from(half));
+            }
+            candidates
+        }
+        (_, Value::Array(items)) if !items.is_empty() => {
+            let inner = match value_type {
+                ValueType::Array(inner) => inner.as_ref(),
+                _ => &ValueType::Any,
+            };
+            let mut candidates = vec![Value::Array(Vec::new()), Value::Array(items[..items.len() / 2].to_vec())];
+            for index in 0..items.len() {
+                let mut fewer = items.clone();
+                fewer.remove(index);
+                candidates.push(Value::Array(fewer));
+            }
+            for (index, item) in items.iter().enumerate() {
+                for smaller in shrink_value(inner, item) {
+                    let mut shrunk = items.clone();
+                    shrunk[index] = smaller;
+                    candidates.push(Value::Array(shrunk));
+                }
+            }
+            candidates
+        }
+        (_, Value::Object(entries)) if !entries.is_empty() => {
+            let mut candidates = Vec::new();
+            // Objects have fixed fields; maps and untyped objects can lose entries
+            if !matches!(value_type, ValueType::Object(_)) {
+                candidates.push(Value::Object(serde_json::Map::new()));
+                for key in entries.keys() {
+                    let mut fewer = entries.clone();
+                    fewer.remove(key);
+                    candidates.push(Value::Object(fewer));
+                }
+            }
+            for (key, item) in entries {
+                let field_type = match value_type {
+                    ValueType::Map(inner) => inner.as_ref(),
+                    ValueType::Object(fields) => fields.get(key).unwrap_or(&ValueType::Any),
+                    _ => &ValueType::Any,
+                };
+                for smaller in shrink_value(field_type, item) {
+                    let mut shrunk = entries.clone();
+                    shrunk.insert(key.clone(), smaller);
+                    candidates.push(Value::Object(shrunk));
+                }
+            }
+            candidates
+        }
+        _ => Vec::new(),
+    }
+}
+
+/// Simpler variants of a step, simplest first
+fn shrink_step(abi: &ContractABI, step: &ScenarioStep) -> Vec<ScenarioStep> {
+    let mut candidates = Vec::new();
+    let mut variant = |change: &dyn Fn(&mut ScenarioStep)| {
+        let mut candidate = step.clone();
+        change(&mut candidate);
+        candidates.push(candidate);
+    };
+    if step.advance.is_some() {
+        variant(&|s| s.advance = None);
+    }
+    if step.caller.is_some() {
+        variant(&|s| s.caller = None);
+    }
+    if step.value > 0 {
+        variant(&|s| s.value = 0);
+        variant(&|s| s.value /= 2);
+    }
+    let inputs = find_function(abi, &step.function).map(|f| f.inputs.as_slice()).unwrap_or_default();
+    if let serde_json::Value::Array(args) = &step.args {
+        for (index, arg) in args.iter().enumerate() {
+            let value_type = inputs.get(index).map_or(&ValueType::Any, |p| &p.value_type);
+            for smaller in shrink_value(value_type, arg) {
+                variant(&|s| s.args[index] = smaller.clone());
+            }
+        }
+    }
+    candidates
+}
+
+/// Runs candidate sequences for [`minimize`] within a budget
+struct Shrinker<F> {
+    reproduces: F,
+    target: FuzzFailure,
+    budget: usize,
+    runs: usize,
+}
+
+impl<F: FnMut(&[ScenarioStep]) -> Option<FuzzFailure>> Shrinker<F> {
+    /// Whether `candidate` still fails the same way; on success the failure
+    /// becomes the new target, since its step index may have moved
+    fn check(&mut self, candidate: &[ScenarioStep]) -> bool {
+        if self.runs >= self.budget || candidate.is_empty() {
+            return false;
+        }
+        self.runs += 1;
+        match (self.reproduces)(candidate) {
+            Some(failure) if failure.same_as(&self.target) => {
+                self.target = failure;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Shrink a failing sequence while it keeps failing the same way: drop steps
+/// after the failure, then drop runs of steps, then simplify what is left
+/// step by step. At most `max_runs` candidates are executed.
+///
+/// Returns the smallest sequence found, its failure and the candidates run.
+pub fn minimize(
+    abi: &ContractABI,
+    mut steps: Vec<ScenarioStep>,
+    failure: FuzzFailure,
+    max_runs: usize,
+    reproduces: impl FnMut(&[ScenarioStep]) -> Option<FuzzFailure>,
+) -> (Vec<ScenarioStep>, FuzzFailure, usize) {
+    steps.truncate(failure.step + 1);
+    let mut shrinker = Shrinker {
+        reproduces,
+        target: failure,
+        budget: max_runs,
+        runs: 0,
+    };
+
+    let mut progress = true;
+    while progress && shrinker.runs < shrinker.budget {
+        progress = false;
+
+        let mut chunk = (steps.len() / 2).max(1);
+        loop {
+            let mut start = 0;
+            while start < steps.len() {
+                let end = (start + chunk).min(steps.len());
+                let candidate: Vec<ScenarioStep> = steps[..start].iter().chain(&steps[end..]).cloned().collect();
+                if shrinker.check(&candidate) {
+                    steps = candidate;
+                    steps.truncate(shrinker.target.step + 1);
+                    progress = true;
+                } else {
+                    start = end;
+                }
+            }
+            if chunk == 1 {
+                break;
+            }
+            chunk /= 2;
+        }
+
+        let mut index = 0;
+        while index < steps.len() {
+            let shrunk = shrink_step(abi, &steps[index]).into_iter().find_map(|step| {
+                let mut candidate = steps.clone();
+                candidate[index] = step;
+                shrinker.check(&candidate).then_some(candidate)
+            });
+            match shrunk {
+                // Keep shrinking the same step until none of its variants fail
+                Some(candidate) => {
+                    steps = candidate;
+                    progress = true;
+                }
+                None => index += 1,
+            }
+        }
+    }
+    (steps, shrinker.target, shrinker.runs)
+}
+
+/// Nodes responsible for a failure: the node the failing step stopped in,
+/// and the nodes upstream of it that fed it on the way there, in the order
+/// they ran.
+///
+/// Upstream nodes that have a tracepoint but did not run (branches not
+/// taken) are left out; nodes without a tracepoint are kept and searched
+/// through, as they are never traced.
+pub fn responsible_nodes(graph: &VisualGraph, map: &TraceMap, trace: &[TraceEvent]) -> Vec<NodeId> {
+    let mut order = Vec::new();
+    let mut stack = Vec::new();
+    for event in trace {
+        match event {
+            TraceEvent::Enter { tracepoint, .. } => {
+                stack.push(*tracepoint);
+                if !order.contains(tracepoint) {
+                    order.push(*tracepoint);
+                }
+            }
+            TraceEvent::Exit { tracepoint, .. } => {
+                if let Some(position) = stack.iter().rposition(|t| t == tracepoint) {
+                    stack.truncate(position);
+                }
+            }
+            TraceEvent::StorageWrite { .. } => {}
+        }
+    }
+    // The innermost node still running when the step ended, else the last to run
+    let Some(culprit) = stack.last().or(order.last()).and_then(|&t| map.node(t)) else {
+        return Vec::new();
+    };
+
+    let ran: HashSet<NodeId> = order.iter().filter_map(|&t| map.node(t)).collect();
+    let mut responsible = BTreeSet::from([culprit]);
+    let mut pending = VecDeque::from([culprit]);
+    while let Some(node) = pending.pop_front() {
+        for connection in graph.connections.iter().filter(|c| c.target_node == node) {
+            let source = connection.source_node;
+            let relevant = ran.contains(&source) || map.tracepoint(&source).is_none();
+            if relevant && responsible.insert(source) {
+                pending.push_back(source);
+            }
+        }
+    }
+
+    let mut nodes: Vec<NodeId> = order
+        .iter()
+        .filter_map(|&t| map.node(t))
+        .filter(|id| responsible.contains(id))
+        .collect();
+    // Untraced nodes did not run in any observable order; they go first
+    let untraced = responsible.iter().filter(|id| !nodes.contains(id)).copied().collect::<Vec<_>>();
+    nodes.splice(0..0, untraced);
+    nodes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{FunctionABI, ParameterABI};
+
+    fn abi() -> ContractABI {
+        let param = |name: &str, value_type| ParameterABI {
+            name: name.to_string(),
+            value_type,
+            indexed: false,
+        };
+        let function = |name: &str, inputs| FunctionABI {
+            name: name.to_string(),
+            inputs,
+            outputs: Vec::new(),
+            state_mutability: StateMutability::NonPayable,
+            gas_estimate: None,
+        };
+        ContractABI {
+            functions: vec![
+                function("deposit", vec![param("amount", ValueType::Integer)]),
+                function("tag", vec![param("labels", ValueType::Array(Box::new(ValueType::String)))]),
+                function("withdraw", vec![param("amount", ValueType::Integer)]),
+            ],
+            events: Vec::new(),
+            errors: Vec::new(),
+            storage: Vec::new(),
+            metadata: Default::default(),
+        }
+    }
+
+    fn step(function: &str, args: serde_json::Value) -> ScenarioStep {
+        ScenarioStep {
+            function: function.to_string(),
+            args,
+            caller: Some(FUZZ_CALLERS[1].to_string()),
+            value: 0,
+            advance: Some(BlockAdvance {
+                blocks: 3,
+                seconds: None,
+            }),
+            gas_limit: DEFAULT_STEP_GAS_LIMIT,
+            expect: StepExpectation::default(),
+        }
+    }
+
+    /// A toy contract: withdrawing more than 10 beyond the deposits traps
+    fn oracle(steps: &[ScenarioStep]) -> Option<FuzzFailure> {
+        let mut balance = 0;
+        for (index, step) in steps.iter().enumerate() {
+            let amount = step.args[0].as_i64().unwrap_or(0);
+            match step.function.as_str() {
+                "deposit" => balance += amount,
+                "withdraw" if amount > balance + 10 => {
+                    return Some(FuzzFailure {
+                        step: index,
+                        function: step.function.clone(),
+                        error: "Trap".to_string(),
+                        message: "integer underflow".to_string(),
+                        trace: Vec::new(),
+                    })
+                }
+                "withdraw" => balance -= amount,
+                _ => {}
+            }
+        }
+        None
+    }
+
+    #[test]
+    fn test_minimize_drops_steps_and_shrinks_values() {
+        let abi = abi();
+        let steps = vec![
+            step("deposit", serde_json::json!([40])),
+            step("tag", serde_json::json!([["a", "b", "c"]])),
+            step("deposit", serde_json::json!([7])),
+            step("withdraw", serde_json::json!([900])),
+            step("tag", serde_json::json!([["d"]])),
+        ];
+        let failure = oracle(&steps).unwrap();
+        assert_eq!(failure.step, 3);
+
+        let (minimized, failure, runs) = minimize(&abi, steps, failure, 1_000, oracle);
+        assert!(runs > 0);
+        assert_eq!(failure.step, 0);
+        assert_eq!(minimized.len(), 1);
+        let only = &minimized[0];
+        assert_eq!(only.function, "withdraw");
+        assert_eq!(only.caller, None);
+        assert_eq!(only.advance, None);
+        // Halving from 900 stops at the smallest amount that still traps
+        let amount = only.args[0].as_i64().unwrap();
+        assert!(amount > 10 && amount <= 21, "{}", amount);
+    }
+
+    #[test]
+    fn test_shrunk_values_keep_their_type() {
+        let labels = ValueType::Array(Box::new(ValueType::String));
+        let candidates = shrink_value(&labels, &serde_json::json!(["abcd", "x"]));
+        assert!(candidates.contains(&serde_json::json!([])));
+        assert!(candidates.contains(&serde_json::json!(["ab", "x"])));
+        assert!(candidates.iter().all(|c| labels.matches_value(c)));
+
+        let price = ValueType::Decimal(2);
+        let candidates = shrink_value(&price, &serde_json::json!("12.50"));
+        assert_eq!(candidates, vec![serde_json::json!("0.00"), serde_json::json!("6.25")]);
+        assert!(shrink_value(&ValueType::Integer, &serde_json::json!(0)).is_empty());
+    }
+
+    #[test]
+    fn test_responsible_nodes_follow_the_failing_path() {
+        use crate::types::{Connection, Position, VisualNode};
+        use uuid::Uuid;
+
+        let mut graph = VisualGraph::new("vault");
+        let node = |node_type: &str| VisualNode::new(Uuid::new_v4(), node_type, Position::new(0.0, 0.0));
+        let (start, amount, taken, skipped, fail, after) = (
+            node("Start"),
+            node("Constant"),
+            node("Subtract"),
+            node("Add"),
+            node("Transfer"),
+            node("EmitEvent"),
+        );
+        let ids = [start.id, amount.id, taken.id, skipped.id, fail.id, after.id];
+        for (source, target) in [(0, 2), (1, 2), (2, 4), (3, 4), (4, 5)] {
+            graph.add_connection(Connection::new(Uuid::new_v4(), ids[source], "out", ids[target], "in"));
+        }
+        for n in [start, amount, taken, skipped, fail, after] {
+            graph.add_node(n);
+        }
+        // The constant is folded away and never traced
+        let mut traced = graph.clone();
+        traced.nodes.retain(|n| n.id != ids[1]);
+        let map = TraceMap::build(&traced);
+        let tp = |i: usize| map.tracepoint(&ids[i]).unwrap();
+
+        let trace = vec![
+            TraceEvent::Enter { tracepoint: tp(0), gas_used: 0 },
+            TraceEvent::Exit { tracepoint: tp(0), gas_used: 1 },
+            TraceEvent::Enter { tracepoint: tp(2), gas_used: 1 },
+            TraceEvent::Exit { tracepoint: tp(2), gas_used: 2 },
+            TraceEvent::Enter { tracepoint: tp(4), gas_used: 2 },
+        ];
+        assert_eq!(responsible_nodes(&graph, &map, &trace), vec![ids[1], ids[0], ids[2], ids[4]]);
+    }
+}
@@ -0,0 +1,27 @@
+//! Contract testing: recorded and hand-written scenario files, fuzzing, test
+//! reports, determinism checks and the headless CI pipeline
+
+mod ci;
+mod determinism;
+mod fuzz;
+mod parallel;
+mod recorder;
+mod report;
+mod scenario;
+
+pub use ci::{discover_graphs, run_ci, CiCheck, CiOptions, CiReport, CiStage, GasBaseline};
+pub use determinism::{
+    check_determinism, fingerprint_run, DeterminismOptions, DeterminismReport, DeterminismWorker, Divergence, RunFingerprint,
+    WorkerRequest, WorkerResponse,
+};
+pub use fuzz::{
+    minimize, responsible_nodes, Execution, FuzzFailure, FuzzFinding, FuzzOptions, Fuzzer, EXECUTION_ERROR, FUZZ_BALANCE,
+    FUZZ_CALLERS,
+};
+pub use parallel::{run_scenarios_parallel, ParallelOptions};
+pub use recorder::ScenarioRecorder;
+pub use report::{reporter_for, JsonReporter, JunitReporter, Reporter, TestCase, TestFailure, TestSuite};
+pub use scenario::{
+    discover_scenarios, run_scenario, run_scenario_in, Scenario, ScenarioResult, ScenarioStep, StepExpectation, StepResult,
+    DEFAULT_STEP_GAS_LIMIT, SCENARIO_EXTENSION,
+};
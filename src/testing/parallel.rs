@@ -0,0 +1,174 @@
+//! Parallel scenario runs
+//!
+//! Scenarios don't share state, so a suite can run several at once. Each
+//! scenario gets its own runtime instance and its own storage backend; a
+//! `sled` backend is given a fresh directory per scenario under its root.
+//! Results come back in the order the scenarios were given, whatever order
+//! they finish in, so reports are identical between serial and parallel runs.
+
+use std::{
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use super::scenario::{run_scenario_in, ScenarioResult};
+use crate::{
+    config::Config,
+    error::CanvasResult,
+    progress::{self, Progress},
+    wasm::StorageBackendKind,
+    wasm::WasmRuntime,
+};
+
+/// How a suite of scenarios is run
+#[derive(Debug, Clone)]
+pub struct ParallelOptions {
+    /// Scenarios run at once; 0 uses every available core
+    pub jobs: usize,
+    /// Backend each scenario's storage is created with
+    pub storage: StorageBackendKind,
+    /// Advanced once per finished scenario
+    pub progress: Arc<dyn Progress>,
+}
+
+impl Default for ParallelOptions {
+    fn default() -> Self {
+        Self {
+            jobs: 0,
+            storage: StorageBackendKind::Memory,
+            progress: progress::quiet(),
+        }
+    }
+}
+
+impl ParallelOptions {
+    pub fn with_jobs(mut self, jobs: usize) -> Self {
+        self.jobs = jobs;
+        self
+    }
+
+    pub fn with_storage(mut self, storage: StorageBackendKind) -> Self {
+        self.storage = storage;
+        self
+    }
+
+    pub fn with_progress(mut self, progress: Arc<dyn Progress>) -> Self {
+        self.progress = progress;
+        self
+    }
+
+    /// Worker threads for `scenarios` scenarios
+    pub fn workers(&self, scenarios: usize) -> usize {
+        let jobs = match self.jobs {
+            0 => std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1),
+            jobs => jobs,
+        };
+        jobs.min(scenarios).max(1)
+    }
+
+    /// Backend of the `index`th scenario, isolated from every other scenario
+    fn isolated_storage(&self, index: usize, path: &Path) -> CanvasResult<StorageBackendKind> {
+        match &self.storage {
+            StorageBackendKind::Sled(root) => {
+                let stem = path
+                    .file_name()
+                    .map(|name| name.to_string_lossy().replace(|c: char| !c.is_ascii_alphanumeric(), "_"))
+                    .unwrap_or_default();
+                let dir = root.join(format!("{:04}-{}", index, stem));
+                if dir.exists() {
+                    std::fs::remove_dir_all(&dir)?;
+                }
+                Ok(StorageBackendKind::Sled(dir))
+            }
+            other => Ok(other.clone()),
+        }
+    }
+}
+
+fn run_isolated(config: &Config, path: &Path, storage: StorageBackendKind) -> CanvasResult<ScenarioResult> {
+    let runtime = WasmRuntime::new(config)?;
+    run_scenario_in(&runtime, path, storage.open(config)?)
+}
+
+/// Run scenario files concurrently, returning one outcome per file in input order
+pub fn run_scenarios_parallel(
+    config: &Config,
+    paths: &[PathBuf],
+    options: &ParallelOptions,
+) -> Vec<CanvasResult<ScenarioResult>> {
+    let next = AtomicUsize::new(0);
+    let outcomes: Mutex<Vec<Option<CanvasResult<ScenarioResult>>>> =
+        Mutex::new(paths.iter().map(|_| None).collect());
+
+    let workers = options.workers(paths.len());
+    log::debug!("Running {} scenario(s) on {} worker(s)", paths.len(), workers);
+    options.progress.stage("scenarios", paths.len() as u64);
+    std::thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| loop {
+                let index = next.fetch_add(1, Ordering::SeqCst);
+                let Some(path) = paths.get(index) else {
+                    break;
+                };
+                let outcome = options
+                    .isolated_storage(index, path)
+                    .and_then(|storage| run_isolated(config, path, storage));
+                outcomes.lock().expect("scenario results lock poisoned")[index] = Some(outcome);
+                options.progress.advance(1);
+            });
+        }
+    });
+    options.progress.finish();
+
+    outcomes
+        .into_inner()
+        .expect("scenario results lock poisoned")
+        .into_iter()
+        .map(|outcome| outcome.expect("every scenario is claimed by a worker"))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parallel_results_keep_input_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let token = r#"(module
+            (func (export "mint") (param i64))
+            (func (export "burn") (param i64)))"#;
+        std::fs::write(dir.path().join("token.wasm"), wat::parse_str(token).unwrap()).unwrap();
+        let mut paths = Vec::new();
+        for name in ["c", "a", "b", "d"] {
+            let path = dir.path().join(format!("{}.scenario.json", name));
+            let scenario = serde_json::json!({
+                "name": name,
+                "contract": "token.wasm",
+                "steps": [{"function": "mint", "args": [1]}, {"function": "burn", "args": [1]}]
+            });
+            std::fs::write(&path, scenario.to_string()).unwrap();
+            paths.push(path);
+        }
+        paths.push(dir.path().join("missing.scenario.json"));
+
+        let log = Arc::new(crate::progress::ProgressLog::new());
+        let options = ParallelOptions::default()
+            .with_jobs(3)
+            .with_storage(StorageBackendKind::Sled(dir.path().join("storage")))
+            .with_progress(log.clone());
+        assert_eq!(options.workers(paths.len()), 3);
+        let outcomes = run_scenarios_parallel(&Config::default(), &paths, &options);
+
+        let names: Vec<_> = outcomes.iter().filter_map(|o| o.as_ref().ok()).map(|r| r.name.clone()).collect();
+        assert_eq!(names, ["c", "a", "b", "d"]);
+        assert!(outcomes[..4].iter().all(|o| o.as_ref().unwrap().passed()));
+        assert!(outcomes[4].is_err());
+        assert!(dir.path().join("storage/0001-a_scenario_json").exists());
+        let advanced = log.events().iter().filter(|e| **e == crate::progress::ProgressEvent::Advance(1)).count();
+        assert_eq!(advanced, paths.len());
+    }
+}
@@ -0,0 +1,199 @@
+//! Interaction recording
+//!
+//! Calls made by hand from the editor console or `simulate --record` are
+//! captured with their arguments, caller and outcome and written out as a
+//! scenario, turning exploratory testing into a regression test. Each
+//! recorded step expects what was observed: the same revert, or the same
+//! return value, optionally within a gas ceiling.
+
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use super::scenario::{Scenario, ScenarioStep, StepExpectation};
+use crate::{
+    error::CanvasResult,
+    types::Gas,
+    wasm::{BlockContext, SimulationResult},
+};
+
+/// Records contract calls into a scenario
+#[derive(Debug, Clone)]
+pub struct ScenarioRecorder {
+    name: String,
+    contract: PathBuf,
+    abi: Option<PathBuf>,
+    /// Headroom over observed gas for each step's `max_gas`, in percent;
+    /// gas is not checked when unset
+    gas_margin: Option<u32>,
+    steps: Vec<ScenarioStep>,
+}
+
+impl ScenarioRecorder {
+    pub fn new(name: impl Into<String>, contract: impl Into<PathBuf>) -> Self {
+        Self {
+            name: name.into(),
+            contract: contract.into(),
+            abi: None,
+            gas_margin: None,
+            steps: Vec::new(),
+        }
+    }
+
+    /// Continue recording into an existing scenario file
+    pub fn resume(path: &Path) -> CanvasResult<Self> {
+        let scenario = Scenario::load(path)?;
+        let base = path.parent().unwrap_or_else(|| Path::new("."));
+        Ok(Self {
+            name: scenario.name,
+            contract: base.join(scenario.contract),
+            abi: scenario.abi.map(|abi| base.join(abi)),
+            gas_margin: None,
+            steps: scenario.steps,
+        })
+    }
+
+    pub fn with_abi(mut self, abi: impl Into<PathBuf>) -> Self {
+        self.abi = Some(abi.into());
+        self
+    }
+
+    pub fn with_gas_margin(mut self, percent: u32) -> Self {
+        self.gas_margin = Some(percent);
+        self
+    }
+
+    /// Contract the recorded calls were made against
+    pub fn contract(&self) -> &Path {
+        &self.contract
+    }
+
+    pub fn steps(&self) -> &[ScenarioStep] {
+        &self.steps
+    }
+
+    pub fn len(&self) -> usize {
+        self.steps.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty()
+    }
+
+    /// Record a call and the result it produced.
+    ///
+    /// Calls that failed before running (bad arguments, unknown function) are
+    /// not recorded; there is no outcome to check.
+    pub fn record(
+        &mut self,
+        function: &str,
+        args: serde_json::Value,
+        caller: Option<String>,
+        gas_limit: Gas,
+        result: &SimulationResult,
+    ) {
+        let expect = match &result.revert_reason {
+            Some(reason) => StepExpectation {
+                reverts: Some(reason.error.clone()),
+                ..StepExpectation::default()
+            },
+            None => StepExpectation {
+                output: Some(result.output.get("result").unwrap_or(&result.output).clone()),
+                ..StepExpectation::default()
+            },
+        };
+        self.steps.push(ScenarioStep {
+            function: function.to_string(),
+            args,
+            caller,
+            value: 0,
+            advance: None,
+            gas_limit,
+            expect: StepExpectation {
+                max_gas: self.gas_margin.map(|margin| result.gas_used + result.gas_used * margin as Gas / 100),
+                ..expect
+            },
+        });
+    }
+
+    /// Drop the last recorded step, e.g. a call the user wants to redo
+    pub fn undo(&mut self) -> Option<ScenarioStep> {
+        self.steps.pop()
+    }
+
+    /// The recorded scenario, with contract and ABI paths as given
+    pub fn finish(self) -> Scenario {
+        Scenario {
+            name: self.name,
+            contract: self.contract,
+            abi: self.abi,
+            balances: BTreeMap::new(),
+            block: BlockContext::default(),
+            steps: self.steps,
+        }
+    }
+
+    /// Write the scenario to `path`, making contract and ABI paths relative to
+    /// it where possible
+    pub fn save(&self, path: &Path) -> CanvasResult<()> {
+        let base = path.parent().unwrap_or_else(|| Path::new("."));
+        let mut scenario = self.clone().finish();
+        scenario.contract = relative_to(&scenario.contract, base);
+        scenario.abi = scenario.abi.map(|abi| relative_to(&abi, base));
+        scenario.save(path)
+    }
+}
+
+/// `path` relative to `base` when it lies under it, otherwise absolute
+pub(super) fn relative_to(path: &Path, base: &Path) -> PathBuf {
+    let absolute = std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let base = std::fs::canonicalize(base).unwrap_or_else(|_| base.to_path_buf());
+    match absolute.strip_prefix(&base) {
+        Ok(relative) => relative.to_path_buf(),
+        Err(_) => absolute,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::RevertReason;
+    use std::time::Duration;
+
+    #[test]
+    fn test_recorded_calls_replay_as_scenario() {
+        let dir = tempfile::tempdir().unwrap();
+        let contract = dir.path().join("token.wasm");
+        std::fs::write(&contract, b"\0asm").unwrap();
+
+        let mut recorder = ScenarioRecorder::new("exploration", &contract).with_gas_margin(10);
+        let ok = SimulationResult {
+            output: serde_json::json!({"result": 100}),
+            gas_used: 200,
+            storage_bytes: 0,
+            events: Vec::new(),
+            execution_time: Duration::ZERO,
+            revert_reason: None,
+        };
+        recorder.record("balance_of", serde_json::json!(["0xab"]), None, 1_000, &ok);
+        let reverted = SimulationResult {
+            revert_reason: Some(RevertReason::new("Unauthorized", "not the owner")),
+            ..ok.clone()
+        };
+        recorder.record("mint", serde_json::json!([5]), Some("0xcd".into()), 1_000, &reverted);
+
+        let path = dir.path().join("exploration.scenario.json");
+        recorder.save(&path).unwrap();
+        let scenario = Scenario::load(&path).unwrap();
+        assert_eq!(scenario.contract, PathBuf::from("token.wasm"));
+        assert_eq!(scenario.steps[0].expect.output, Some(serde_json::json!(100)));
+        assert_eq!(scenario.steps[0].expect.max_gas, Some(220));
+        assert_eq!(scenario.steps[1].expect.reverts.as_deref(), Some("Unauthorized"));
+        assert_eq!(scenario.steps[1].caller.as_deref(), Some("0xcd"));
+
+        let resumed = ScenarioRecorder::resume(&path).unwrap();
+        assert_eq!(resumed.len(), 2);
+        assert_eq!(resumed.contract(), dir.path().join("token.wasm"));
+    }
+}
@@ -0,0 +1,269 @@
+//! Test reports
+//!
+//! Results of scenario runs, custom node tests and CI checks are converted to
+//! a common shape (suites of cases, each with a duration, an optional failure
+//! naming the graph nodes involved, and properties such as gas used) and
+//! rendered by a [`Reporter`]: JUnit XML for CI systems or JSON for tooling.
+
+use std::{collections::BTreeMap, time::Duration};
+
+use serde::{Deserialize, Serialize};
+
+use super::scenario::ScenarioResult;
+use crate::{
+    error::{CanvasError, CanvasResult},
+    nodes::custom::NodeTestReport,
+    types::NodeId,
+};
+
+/// Why a test case failed
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TestFailure {
+    pub message: String,
+    /// Graph nodes the failure points at
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub nodes: Vec<NodeId>,
+}
+
+/// One test case
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TestCase {
+    pub name: String,
+    /// Grouping within the suite, e.g. the file the case came from
+    pub classname: String,
+    pub duration: Duration,
+    pub failure: Option<TestFailure>,
+    /// Extra data, e.g. `gas_used`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub properties: BTreeMap<String, String>,
+}
+
+impl TestCase {
+    pub fn new(name: impl Into<String>, classname: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            classname: classname.into(),
+            duration: Duration::ZERO,
+            failure: None,
+            properties: BTreeMap::new(),
+        }
+    }
+
+    pub fn with_duration(mut self, duration: Duration) -> Self {
+        self.duration = duration;
+        self
+    }
+
+    pub fn with_failure(mut self, message: impl Into<String>, nodes: Vec<NodeId>) -> Self {
+        self.failure = Some(TestFailure {
+            message: message.into(),
+            nodes,
+        });
+        self
+    }
+
+    pub fn with_property(mut self, name: impl Into<String>, value: impl ToString) -> Self {
+        self.properties.insert(name.into(), value.to_string());
+        self
+    }
+
+    pub fn passed(&self) -> bool {
+        self.failure.is_none()
+    }
+}
+
+/// A named group of test cases
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TestSuite {
+    pub name: String,
+    pub cases: Vec<TestCase>,
+}
+
+impl TestSuite {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            cases: Vec::new(),
+        }
+    }
+
+    pub fn failures(&self) -> usize {
+        self.cases.iter().filter(|c| !c.passed()).count()
+    }
+
+    pub fn duration(&self) -> Duration {
+        self.cases.iter().map(|c| c.duration).sum()
+    }
+}
+
+impl From<&ScenarioResult> for TestSuite {
+    fn from(result: &ScenarioResult) -> Self {
+        let classname = result.path.display().to_string();
+        TestSuite {
+            name: result.name.clone(),
+            cases: result
+                .steps
+                .iter()
+                .enumerate()
+                .map(|(index, step)| {
+                    let case = TestCase::new(format!("#{} {}", index + 1, step.function), &classname)
+                        .with_duration(step.duration)
+                        .with_property("gas_used", step.gas_used);
+                    match &step.failure {
+                        Some(failure) => case.with_failure(failure, Vec::new()),
+                        None => case,
+                    }
+                })
+                .collect(),
+        }
+    }
+}
+
+impl From<&NodeTestReport> for TestSuite {
+    fn from(report: &NodeTestReport) -> Self {
+        TestSuite {
+            name: report.node_id.clone(),
+            cases: report
+                .results
+                .iter()
+                .map(|result| {
+                    let case = TestCase::new(&result.name, &report.node_id);
+                    match &result.failure {
+                        Some(failure) => case.with_failure(failure, Vec::new()),
+                        None => case,
+                    }
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Renders test suites in an output format
+pub trait Reporter {
+    /// Format name, as accepted by [`reporter_for`]
+    fn format(&self) -> &'static str;
+
+    fn render(&self, suites: &[TestSuite]) -> CanvasResult<String>;
+}
+
+/// JUnit XML, as read by most CI systems
+pub struct JunitReporter;
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+impl Reporter for JunitReporter {
+    fn format(&self) -> &'static str {
+        "junit"
+    }
+
+    fn render(&self, suites: &[TestSuite]) -> CanvasResult<String> {
+        let tests: usize = suites.iter().map(|s| s.cases.len()).sum();
+        let failures: usize = suites.iter().map(|s| s.failures()).sum();
+        let time: Duration = suites.iter().map(|s| s.duration()).sum();
+
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        xml.push_str(&format!(
+            "<testsuites tests=\"{}\" failures=\"{}\" time=\"{:.3}\">\n",
+            tests,
+            failures,
+            time.as_secs_f64()
+        ));
+        for suite in suites {
+            xml.push_str(&format!(
+                "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" time=\"{:.3}\">\n",
+                xml_escape(&suite.name),
+                suite.cases.len(),
+                suite.failures(),
+                suite.duration().as_secs_f64()
+            ));
+            for case in &suite.cases {
+                xml.push_str(&format!(
+                    "    <testcase classname=\"{}\" name=\"{}\" time=\"{:.3}\">\n",
+                    xml_escape(&case.classname),
+                    xml_escape(&case.name),
+                    case.duration.as_secs_f64()
+                ));
+                if !case.properties.is_empty() {
+                    xml.push_str("      <properties>\n");
+                    for (name, value) in &case.properties {
+                        xml.push_str(&format!(
+                            "        <property name=\"{}\" value=\"{}\"/>\n",
+                            xml_escape(name),
+                            xml_escape(value)
+                        ));
+                    }
+                    xml.push_str("      </properties>\n");
+                }
+                if let Some(failure) = &case.failure {
+                    xml.push_str(&format!("      <failure message=\"{}\">", xml_escape(&failure.message)));
+                    for node in &failure.nodes {
+                        xml.push_str(&format!("node {}\n", node));
+                    }
+                    xml.push_str("</failure>\n");
+                }
+                xml.push_str("    </testcase>\n");
+            }
+            xml.push_str("  </testsuite>\n");
+        }
+        xml.push_str("</testsuites>\n");
+        Ok(xml)
+    }
+}
+
+/// The suites serialized as JSON
+pub struct JsonReporter;
+
+impl Reporter for JsonReporter {
+    fn format(&self) -> &'static str {
+        "json"
+    }
+
+    fn render(&self, suites: &[TestSuite]) -> CanvasResult<String> {
+        Ok(serde_json::to_string_pretty(suites)?)
+    }
+}
+
+/// Reporter for a format name (`junit` or `json`)
+pub fn reporter_for(format: &str) -> CanvasResult<Box<dyn Reporter>> {
+    match format {
+        "junit" => Ok(Box::new(JunitReporter)),
+        "json" => Ok(Box::new(JsonReporter)),
+        other => Err(CanvasError::Validation(format!(
+            "Unknown report format '{}'; expected junit or json",
+            other
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    #[test]
+    fn test_junit_includes_properties_and_failing_nodes() {
+        let node = Uuid::new_v4();
+        let mut suite = TestSuite::new("token <transfers>");
+        suite.cases.push(
+            TestCase::new("#1 transfer", "token.scenario.json")
+                .with_duration(Duration::from_millis(1500))
+                .with_property("gas_used", 420),
+        );
+        suite.cases.push(TestCase::new("#2 burn", "token.scenario.json").with_failure("reverted", vec![node]));
+
+        let xml = JunitReporter.render(&[suite.clone()]).unwrap();
+        assert!(xml.contains("<testsuite name=\"token &lt;transfers&gt;\" tests=\"2\" failures=\"1\" time=\"1.500\">"));
+        assert!(xml.contains("<property name=\"gas_used\" value=\"420\"/>"));
+        assert!(xml.contains(&format!("<failure message=\"reverted\">node {}\n</failure>", node)));
+
+        let json: serde_json::Value = serde_json::from_str(&JsonReporter.render(&[suite]).unwrap()).unwrap();
+        assert_eq!(json[0]["cases"][1]["failure"]["nodes"][0], serde_json::json!(node));
+        assert!(reporter_for("tap").is_err());
+    }
+}
@@ -0,0 +1,322 @@
+//! Scenario files
+//!
+//! A scenario is a JSON file (`*.scenario.json`) naming a compiled contract and
+//! a sequence of calls against it, each with the outcome it must have: a
+//! return value, a revert with a given error, a gas ceiling, or account
+//! balances afterwards. Steps may send value with the call, drawn from the
+//! sandbox balances the scenario starts with, and may advance the block
+//! before calling to test time-dependent logic. Contract and ABI paths are
+//! relative to the scenario file.
+
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    compiler::{find_function, validate_call_args},
+    error::{CanvasError, CanvasResult},
+    types::{ContractABI, ExecutionContext, Gas},
+    wasm::{
+        storage::{shared, MemoryBackend, SharedStorage},
+        BlockAdvance, BlockContext, SandboxAccounts, SimulationRequest, WasmRuntime,
+    },
+};
+
+/// File name suffix of scenario files
+pub const SCENARIO_EXTENSION: &str = ".scenario.json";
+/// Gas limit of a step that does not set one
+pub const DEFAULT_STEP_GAS_LIMIT: Gas = 1_000_000;
+
+fn default_gas_limit() -> Gas {
+    DEFAULT_STEP_GAS_LIMIT
+}
+
+fn is_zero(value: &u128) -> bool {
+    *value == 0
+}
+
+/// A sequence of calls against one contract
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Scenario {
+    pub name: String,
+    /// Compiled contract
+    pub contract: PathBuf,
+    /// Contract ABI; defaults to the `.abi.json` next to the contract
+    #[serde(default)]
+    pub abi: Option<PathBuf>,
+    /// Starting balances by account address
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub balances: BTreeMap<String, u128>,
+    /// Block the first step executes in
+    #[serde(default)]
+    pub block: BlockContext,
+    pub steps: Vec<ScenarioStep>,
+}
+
+/// One call and its expected outcome
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScenarioStep {
+    pub function: String,
+    /// Arguments, positional (array) or by name (object)
+    #[serde(default)]
+    pub args: serde_json::Value,
+    /// Caller account (0x-hex address); the runtime's default account when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub caller: Option<String>,
+    /// Value sent from the caller to the contract with the call
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub value: u128,
+    /// Blocks and time to move forward by before the call
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub advance: Option<BlockAdvance>,
+    #[serde(default = "default_gas_limit")]
+    pub gas_limit: Gas,
+    #[serde(default)]
+    pub expect: StepExpectation,
+}
+
+/// What a step must produce; unset fields are not checked
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StepExpectation {
+    /// Error name the call must revert with
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reverts: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output: Option<serde_json::Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_gas: Option<Gas>,
+    /// Balances accounts must hold after the step, by address
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub balances: BTreeMap<String, u128>,
+}
+
+/// Outcome of one step
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StepResult {
+    pub function: String,
+    pub gas_used: Gas,
+    pub duration: Duration,
+    pub failure: Option<String>,
+}
+
+/// Outcome of a whole scenario
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScenarioResult {
+    pub name: String,
+    pub path: PathBuf,
+    pub steps: Vec<StepResult>,
+    pub duration: Duration,
+}
+
+impl ScenarioResult {
+    pub fn passed(&self) -> bool {
+        self.steps.iter().all(|s| s.failure.is_none())
+    }
+
+    pub fn gas_used(&self) -> Gas {
+        self.steps.iter().map(|s| s.gas_used).sum()
+    }
+}
+
+impl Scenario {
+    pub fn load(path: &Path) -> CanvasResult<Self> {
+        let scenario: Scenario = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+        if scenario.steps.is_empty() {
+            return Err(CanvasError::Validation(format!("Scenario {} has no steps", path.display())));
+        }
+        Ok(scenario)
+    }
+
+    pub fn save(&self, path: &Path) -> CanvasResult<()> {
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+/// Scenario files under `dir`, sorted
+pub fn discover_scenarios(dir: &Path) -> CanvasResult<Vec<PathBuf>> {
+    let mut found = Vec::new();
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        for entry in std::fs::read_dir(&dir)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                pending.push(path);
+            } else if path.to_string_lossy().ends_with(SCENARIO_EXTENSION) {
+                found.push(path);
+            }
+        }
+    }
+    found.sort();
+    Ok(found)
+}
+
+fn check_step(
+    step: &ScenarioStep,
+    accounts: &SandboxAccounts,
+    result: &crate::wasm::SimulationResult,
+) -> Option<String> {
+    match (&step.expect.reverts, &result.revert_reason) {
+        (Some(expected), Some(reason)) if &reason.error != expected => {
+            return Some(format!("expected revert '{}', got '{}': {}", expected, reason.error, reason.message));
+        }
+        (Some(expected), None) => return Some(format!("expected revert '{}', but the call succeeded", expected)),
+        (None, Some(reason)) => return Some(format!("reverted with '{}': {}", reason.error, reason.message)),
+        _ => {}
+    }
+    if let Some(expected) = &step.expect.output {
+        let actual = result.output.get("result").unwrap_or(&result.output);
+        if actual != expected {
+            return Some(format!("expected output {}, got {}", expected, actual));
+        }
+    }
+    if let Some(max_gas) = step.expect.max_gas {
+        if result.gas_used > max_gas {
+            return Some(format!("used {} gas, expected at most {}", result.gas_used, max_gas));
+        }
+    }
+    for (address, expected) in &step.expect.balances {
+        let actual = accounts.balance(address);
+        if actual != *expected {
+            return Some(format!("expected {} to hold {}, got {}", address, expected, actual));
+        }
+    }
+    None
+}
+
+/// Run a scenario file with contract storage in memory.
+///
+/// Step failures are reported in the result; `Err` means the scenario could
+/// not be run at all (unreadable file, missing contract).
+pub fn run_scenario(runtime: &WasmRuntime, path: &Path) -> CanvasResult<ScenarioResult> {
+    run_scenario_in(runtime, path, shared(MemoryBackend::new()))
+}
+
+/// Run a scenario file against the given storage backend; each step that
+/// doesn't revert commits its writes before the next one runs
+pub fn run_scenario_in(runtime: &WasmRuntime, path: &Path, storage: SharedStorage) -> CanvasResult<ScenarioResult> {
+    let scenario = Scenario::load(path)?;
+    let base = path.parent().unwrap_or_else(|| Path::new("."));
+    let contract_path = base.join(&scenario.contract);
+    let wasm_bytes = std::fs::read(&contract_path)?;
+
+    let abi_path = match &scenario.abi {
+        Some(abi) => Some(base.join(abi)),
+        None => Some(PathBuf::from(contract_path.to_string_lossy().replace(".wasm", ".abi.json")))
+            .filter(|p| p.exists()),
+    };
+    let abi: Option<ContractABI> = abi_path
+        .map(|p| -> CanvasResult<ContractABI> { Ok(serde_json::from_str(&std::fs::read_to_string(p)?)?) })
+        .transpose()?;
+
+    let mut accounts = SandboxAccounts::new().with_balances(scenario.balances.clone());
+    let mut block = scenario.block;
+    let started = Instant::now();
+    let mut steps = Vec::new();
+    for step in &scenario.steps {
+        let step_started = Instant::now();
+        if let Some(advance) = &step.advance {
+            block.advance(advance);
+        }
+        let args = match &abi {
+            Some(abi) => find_function(abi, &step.function)
+                .ok_or_else(|| CanvasError::Validation(format!("Contract has no function '{}'", step.function)))
+                .and_then(|signature| validate_call_args(signature, &step.args)),
+            None => Ok(match &step.args {
+                serde_json::Value::Array(items) => items.clone(),
+                serde_json::Value::Null => Vec::new(),
+                other => vec![other.clone()],
+            }),
+        };
+        let outcome = args.and_then(|args| {
+            let mut request = SimulationRequest::new(step.function.clone(), args, step.gas_limit);
+            request.set_value(step.value).set_block(block);
+            if let Some(caller) = &step.caller {
+                request.set_caller(caller.clone());
+            }
+            let mut context = ExecutionContext::new(step.gas_limit).with_backend(storage.clone());
+            let result = runtime.execute_request_in(&wasm_bytes, &request, &mut accounts, &mut context)?;
+            if !result.reverted() {
+                context.commit_storage()?;
+            }
+            Ok(result)
+        });
+
+        steps.push(match outcome {
+            Ok(result) => StepResult {
+                function: step.function.clone(),
+                gas_used: result.gas_used,
+                duration: step_started.elapsed(),
+                failure: check_step(step, &accounts, &result),
+            },
+            Err(e) => StepResult {
+                function: step.function.clone(),
+                gas_used: 0,
+                duration: step_started.elapsed(),
+                failure: Some(e.to_string()),
+            },
+        });
+    }
+
+    Ok(ScenarioResult {
+        name: scenario.name,
+        path: path.to_path_buf(),
+        steps,
+        duration: started.elapsed(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::RevertReason;
+
+    fn result(revert: Option<RevertReason>, gas_used: Gas) -> crate::wasm::SimulationResult {
+        crate::wasm::SimulationResult {
+            output: serde_json::json!({"result": 5}),
+            gas_used,
+            storage_bytes: 0,
+            events: Vec::new(),
+            execution_time: Duration::ZERO,
+            revert_reason: revert,
+        }
+    }
+
+    #[test]
+    fn test_step_expectations() {
+        let accounts = SandboxAccounts::new();
+        let step: ScenarioStep = serde_json::from_value(serde_json::json!({
+            "function": "withdraw",
+            "expect": {"reverts": "InsufficientBalance"}
+        }))
+        .unwrap();
+        assert_eq!(step.gas_limit, DEFAULT_STEP_GAS_LIMIT);
+        assert!(check_step(&step, &accounts, &result(None, 10)).unwrap().contains("succeeded"));
+        let reason = RevertReason::new("InsufficientBalance", "balance too low");
+        assert_eq!(check_step(&step, &accounts, &result(Some(reason), 10)), None);
+
+        let step: ScenarioStep = serde_json::from_value(serde_json::json!({
+            "function": "get",
+            "expect": {"output": 5, "max_gas": 100}
+        }))
+        .unwrap();
+        assert_eq!(check_step(&step, &accounts, &result(None, 100)), None);
+        assert!(check_step(&step, &accounts, &result(None, 101)).unwrap().contains("at most 100"));
+
+        let step: ScenarioStep = serde_json::from_value(serde_json::json!({
+            "function": "deposit",
+            "value": 60,
+            "expect": {"balances": {"0xAA": 40}}
+        }))
+        .unwrap();
+        assert_eq!(step.value, 60);
+        let accounts = accounts.with_balances([("0xaa".to_string(), 40)]);
+        assert_eq!(check_step(&step, &accounts, &result(None, 10)), None);
+        let accounts = accounts.with_balances([("0xaa".to_string(), 100)]);
+        assert!(check_step(&step, &accounts, &result(None, 10)).unwrap().contains("hold 40, got 100"));
+    }
+}
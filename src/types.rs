@@ -29,7 +29,7 @@ pub type BlockNumber = u64;
 pub type Timestamp = u64;
 
 /// Node type enum
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum NodeType {
     Logic,
     State,
@@ -74,6 +74,10 @@ pub enum ValueType {
     Array(Box<ValueType>),
     /// Object with named fields
     Object(HashMap<String, ValueType>),
+    /// Map from string keys to values of one type
+    Map(Box<ValueType>),
+    /// Fixed-point decimal with the given number of fractional digits
+    Decimal(u8),
     /// Flow control (no data, just execution flow)
     Flow,
     /// Any type (for dynamic typing)
@@ -94,6 +98,10 @@ impl ValueType {
             (ValueType::Array(inner1), ValueType::Array(inner2)) => {
                 inner1.is_compatible_with(inner2)
             }
+            (ValueType::Map(inner1), ValueType::Map(inner2)) => {
+                inner1.is_compatible_with(inner2)
+            }
+            (ValueType::Decimal(scale1), ValueType::Decimal(scale2)) => scale1 == scale2,
             (ValueType::Object(fields1), ValueType::Object(fields2)) => {
                 fields1.len() == fields2.len()
                     && fields1.iter().all(|(k, v)| {
@@ -103,6 +111,235 @@ impl ValueType {
             _ => false,
         }
     }
+
+    /// Element type of an array or map, if this is a collection
+    pub fn element_type(&self) -> Option<&ValueType> {
+        match self {
+            ValueType::Array(inner) | ValueType::Map(inner) => Some(inner),
+            _ => None,
+        }
+    }
+
+    /// Parse a type name as used in node properties, e.g. `"integer"` or `"array<string>"`
+    pub fn from_name(name: &str) -> Option<ValueType> {
+        let name = name.trim().to_lowercase();
+        if let Some(inner) = name.strip_prefix("array<").and_then(|n| n.strip_suffix('>')) {
+            return ValueType::from_name(inner).map(|t| ValueType::Array(Box::new(t)));
+        }
+        if let Some(inner) = name.strip_prefix("map<").and_then(|n| n.strip_suffix('>')) {
+            return ValueType::from_name(inner).map(|t| ValueType::Map(Box::new(t)));
+        }
+        if let Some(scale) = name.strip_prefix("decimal<").and_then(|n| n.strip_suffix('>')) {
+            return scale
+                .parse::<u8>()
+                .ok()
+                .filter(|scale| *scale <= Decimal::MAX_SCALE)
+                .map(ValueType::Decimal);
+        }
+        match name.as_str() {
+            "boolean" | "bool" => Some(ValueType::Boolean),
+            "integer" | "int" => Some(ValueType::Integer),
+            "float" => Some(ValueType::Float),
+            "string" => Some(ValueType::String),
+            "bytes" => Some(ValueType::Bytes),
+            "flow" => Some(ValueType::Flow),
+            "any" => Some(ValueType::Any),
+            _ => None,
+        }
+    }
+
+    /// Check whether a JSON value conforms to this type
+    pub fn matches_value(&self, value: &serde_json::Value) -> bool {
+        match (self, value) {
+            (ValueType::Any, _) => true,
+            (ValueType::Boolean, serde_json::Value::Bool(_)) => true,
+            (ValueType::Integer, serde_json::Value::Number(n)) => n.is_i64() || n.is_u64(),
+            (ValueType::Float, serde_json::Value::Number(_)) => true,
+            (ValueType::String, serde_json::Value::String(_)) => true,
+            (ValueType::Bytes, serde_json::Value::String(_)) => true,
+            (ValueType::Array(inner), serde_json::Value::Array(items)) => {
+                items.iter().all(|item| inner.matches_value(item))
+            }
+            (ValueType::Map(inner), serde_json::Value::Object(entries)) => {
+                entries.values().all(|item| inner.matches_value(item))
+            }
+            (ValueType::Decimal(scale), value) => Decimal::from_json(value, *scale).is_ok(),
+            (ValueType::Object(fields), serde_json::Value::Object(entries)) => fields
+                .iter()
+                .all(|(k, t)| entries.get(k).map_or(false, |v| t.matches_value(v))),
+            _ => false,
+        }
+    }
+}
+
+/// Fixed-point decimal number.
+///
+/// Stored as an `i64` count of `10^-scale` units, which is also how it is
+/// represented in compiled WASM. In JSON (contract inputs, outputs and ABI
+/// examples) a decimal is a string such as `"12.50"`; plain JSON integers are
+/// accepted on input, but JSON floats are rejected because they may already
+/// have lost precision.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Decimal {
+    /// Scaled integer value
+    pub units: i64,
+    /// Number of fractional digits
+    pub scale: u8,
+}
+
+impl Decimal {
+    /// Largest supported scale; 10^18 is the largest power of ten that fits in an i64
+    pub const MAX_SCALE: u8 = 18;
+
+    /// Create a decimal from already-scaled units
+    pub fn from_units(units: i64, scale: u8) -> Self {
+        Self { units, scale }
+    }
+
+    /// `10^scale`
+    pub fn scale_factor(scale: u8) -> i64 {
+        10i64.pow(scale as u32)
+    }
+
+    /// Parse a decimal string, requiring it to fit the given scale exactly
+    pub fn parse(text: &str, scale: u8) -> Result<Self, String> {
+        let parsed: Decimal = text.parse()?;
+        parsed.rescale(scale)
+    }
+
+    /// Read a decimal from a JSON value at the given scale
+    pub fn from_json(value: &serde_json::Value, scale: u8) -> Result<Self, String> {
+        match value {
+            serde_json::Value::String(text) => Decimal::parse(text, scale),
+            serde_json::Value::Number(n) if n.is_i64() => {
+                Decimal::from_units(n.as_i64().unwrap(), 0).rescale(scale)
+            }
+            serde_json::Value::Number(_) => {
+                Err("Decimal values must be strings or integers, not floats".to_string())
+            }
+            other => Err(format!("Expected a decimal, found {}", other)),
+        }
+    }
+
+    /// JSON representation (a string)
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::Value::String(self.to_string())
+    }
+
+    /// Convert to another scale. Increasing the scale is exact; decreasing it
+    /// fails if digits would be lost.
+    pub fn rescale(&self, scale: u8) -> Result<Self, String> {
+        if scale > Self::MAX_SCALE {
+            return Err(format!("Decimal scale {} exceeds maximum {}", scale, Self::MAX_SCALE));
+        }
+        if scale >= self.scale {
+            let units = self
+                .units
+                .checked_mul(Self::scale_factor(scale - self.scale))
+                .ok_or_else(|| format!("Decimal {} does not fit scale {}", self, scale))?;
+            Ok(Self::from_units(units, scale))
+        } else {
+            let factor = Self::scale_factor(self.scale - scale);
+            if self.units % factor != 0 {
+                return Err(format!("Decimal {} has more than {} fractional digits", self, scale));
+            }
+            Ok(Self::from_units(self.units / factor, scale))
+        }
+    }
+}
+
+impl std::fmt::Display for Decimal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.scale == 0 {
+            return write!(f, "{}", self.units);
+        }
+        let factor = Self::scale_factor(self.scale) as i128;
+        let units = self.units as i128;
+        let sign = if units < 0 { "-" } else { "" };
+        let whole = units.abs() / factor;
+        let fraction = units.abs() % factor;
+        write!(f, "{}{}.{:0width$}", sign, whole, fraction, width = self.scale as usize)
+    }
+}
+
+impl std::str::FromStr for Decimal {
+    type Err = String;
+
+    /// Parse a decimal string; the scale is the number of fractional digits given
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let text = text.trim();
+        let (negative, digits) = match text.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, text.strip_prefix('+').unwrap_or(text)),
+        };
+        let (whole, fraction) = digits.split_once('.').unwrap_or((digits, ""));
+        let valid = |part: &str| part.chars().all(|c| c.is_ascii_digit());
+        if whole.is_empty() || !valid(whole) || !valid(fraction) {
+            return Err(format!("Invalid decimal: {}", text));
+        }
+        if fraction.len() > Self::MAX_SCALE as usize {
+            return Err(format!("Decimal {} has more than {} fractional digits", text, Self::MAX_SCALE));
+        }
+
+        let scale = fraction.len() as u8;
+        let magnitude = format!("{}{}", whole, fraction)
+            .parse::<i64>()
+            .map_err(|_| format!("Decimal {} is out of range", text))?;
+        let units = if negative { -magnitude } else { magnitude };
+        Ok(Self::from_units(units, scale))
+    }
+}
+
+impl TryFrom<String> for Decimal {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<Decimal> for String {
+    fn from(value: Decimal) -> Self {
+        value.to_string()
+    }
+}
+
+/// Integer overflow behavior for arithmetic nodes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OverflowMode {
+    /// Trap (revert) on overflow
+    Checked,
+    /// Clamp to the numeric bounds
+    Saturating,
+    /// Wrap around using two's complement
+    Wrapping,
+}
+
+impl Default for OverflowMode {
+    fn default() -> Self {
+        OverflowMode::Checked
+    }
+}
+
+impl OverflowMode {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "checked" => Some(OverflowMode::Checked),
+            "saturating" => Some(OverflowMode::Saturating),
+            "wrapping" => Some(OverflowMode::Wrapping),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OverflowMode::Checked => "checked",
+            OverflowMode::Saturating => "saturating",
+            OverflowMode::Wrapping => "wrapping",
+        }
+    }
 }
 
 /// Node port (input or output)
@@ -113,6 +350,9 @@ pub struct Port {
     pub value_type: ValueType,
     pub required: bool,
     pub description: Option<String>,
+    /// Semantic unit tag for numeric values (e.g. "tokens", "seconds")
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unit: Option<String>,
 }
 
 impl Port {
@@ -123,6 +363,7 @@ impl Port {
             value_type,
             required: false,
             description: None,
+            unit: None,
         }
     }
 
@@ -135,6 +376,11 @@ impl Port {
         self.description = Some(description.into());
         self
     }
+
+    pub fn with_unit(mut self, unit: impl Into<String>) -> Self {
+        self.unit = Some(unit.into());
+        self
+    }
 }
 
 /// Node position on canvas
@@ -284,6 +530,39 @@ impl VisualGraph {
     pub fn get_node_mut(&mut self, id: NodeId) -> Option<&mut VisualNode> {
         self.nodes.iter_mut().find(|node| node.id == id)
     }
+
+    /// Remove a node and every connection to or from it
+    pub fn remove_node(&mut self, id: NodeId) -> crate::error::CanvasResult<VisualNode> {
+        let index = self
+            .nodes
+            .iter()
+            .position(|node| node.id == id)
+            .ok_or_else(|| crate::error::CanvasError::NodeNotFound(id.to_string()))?;
+        self.connections.retain(|c| c.source_node != id && c.target_node != id);
+        Ok(self.nodes.remove(index))
+    }
+
+    /// Replace a value node with the constant it produces: every input it
+    /// feeds is disconnected and set to `value` instead, the way an
+    /// unconnected input holds a literal, and the node is removed
+    pub fn replace_with_literal(&mut self, id: NodeId, value: serde_json::Value) -> crate::error::CanvasResult<()> {
+        if self.get_node(id).is_none() {
+            return Err(crate::error::CanvasError::NodeNotFound(id.to_string()));
+        }
+        let consumers: Vec<(NodeId, PortId)> = self
+            .connections
+            .iter()
+            .filter(|c| c.source_node == id)
+            .map(|c| (c.target_node, c.target_port.clone()))
+            .collect();
+        for (target, port) in consumers {
+            if let Some(node) = self.get_node_mut(target) {
+                node.properties.insert(port, value.clone());
+            }
+        }
+        self.remove_node(id)?;
+        Ok(())
+    }
 }
 
 /// Contract compilation result
@@ -291,22 +570,56 @@ impl VisualGraph {
 pub struct CompilationResult {
     pub wasm_bytes: Vec<u8>,
     pub abi: ContractABI,
+    /// Worst-case gas of the most expensive exported function
     pub gas_estimate: Gas,
+    /// Gas estimate of each exported function
+    #[serde(default)]
+    pub gas_breakdown: Vec<FunctionGasEstimate>,
     pub warnings: Vec<String>,
     pub metadata: HashMap<String, String>,
 }
 
+/// Static gas estimate of one exported function
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FunctionGasEstimate {
+    pub function: String,
+    /// Every branch taken and every loop at its iteration bound
+    pub worst_case: Gas,
+    /// Branches weighted evenly and loops at their average iteration count
+    pub average_case: Gas,
+    /// Worst-case share of the nodes the function reaches
+    pub node_gas: Gas,
+    /// Share of the function's own WASM instructions
+    pub instruction_gas: Gas,
+    /// Reached nodes left out of the estimate, with the reason
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub unbounded: Vec<(NodeId, String)>,
+}
+
+impl FunctionGasEstimate {
+    /// Whether every node the function reaches has a static bound
+    pub fn is_bounded(&self) -> bool {
+        self.unbounded.is_empty()
+    }
+}
+
 /// Contract ABI (Application Binary Interface)
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ContractABI {
     pub functions: Vec<FunctionABI>,
     pub events: Vec<EventABI>,
     pub errors: Vec<ErrorABI>,
+    /// Storage keys the contract reads or writes, sorted by key
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub storage: Vec<StorageABI>,
     pub metadata: HashMap<String, String>,
 }
 
+/// Typed contract ABI, as written to `.abi.json` next to the compiled contract
+pub type Abi = ContractABI;
+
 /// Function ABI
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct FunctionABI {
     pub name: String,
     pub inputs: Vec<ParameterABI>,
@@ -316,7 +629,7 @@ pub struct FunctionABI {
 }
 
 /// Event ABI
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct EventABI {
     pub name: String,
     pub inputs: Vec<ParameterABI>,
@@ -324,20 +637,29 @@ pub struct EventABI {
 }
 
 /// Error ABI
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ErrorABI {
     pub name: String,
     pub inputs: Vec<ParameterABI>,
 }
 
 /// Parameter ABI
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ParameterABI {
     pub name: String,
     pub value_type: ValueType,
     pub indexed: bool,
 }
 
+/// Storage entry ABI
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StorageABI {
+    pub key: String,
+    pub value_type: ValueType,
+    /// Whether the contract writes the key; read-only keys are set outside it
+    pub writable: bool,
+}
+
 /// State mutability
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum StateMutability {
@@ -355,6 +677,26 @@ pub struct ExecutionContext {
     pub storage: HashMap<String, serde_json::Value>,
     pub events: Vec<Event>,
     pub metadata: HashMap<String, String>,
+    /// Tracepoints hit by an instrumented (debug) build, in order
+    pub trace: Vec<TraceEvent>,
+    /// Host imports called, in order, with what they answered
+    pub host_calls: Vec<HostCall>,
+    /// Open savepoints, outermost first
+    savepoints: Vec<Savepoint>,
+    /// Committed contract state; `storage` holds this call's writes on top of it
+    pub backend: Option<crate::wasm::storage::SharedStorage>,
+}
+
+/// State of an [`ExecutionContext`] when a savepoint was taken.
+///
+/// Rolling back to it discards every storage write and event made since; gas
+/// already used stays spent.
+#[derive(Debug, Clone)]
+pub struct Savepoint {
+    pub label: String,
+    pub storage: HashMap<String, serde_json::Value>,
+    /// Number of events emitted before the savepoint
+    pub events: usize,
 }
 
 impl ExecutionContext {
@@ -365,7 +707,84 @@ impl ExecutionContext {
             storage: HashMap::new(),
             events: Vec::new(),
             metadata: HashMap::new(),
+            trace: Vec::new(),
+            host_calls: Vec::new(),
+            savepoints: Vec::new(),
+            backend: None,
+        }
+    }
+
+    /// Read through to `backend` for slots this call hasn't written
+    pub fn with_backend(mut self, backend: crate::wasm::storage::SharedStorage) -> Self {
+        self.backend = Some(backend);
+        self
+    }
+
+    /// Value of a slot: this call's write if there is one, otherwise the
+    /// backend's committed value
+    pub fn read_slot(&self, key: &str) -> crate::error::CanvasResult<Option<serde_json::Value>> {
+        if let Some(value) = self.storage.get(key) {
+            return Ok(Some(value.clone()));
+        }
+        match &self.backend {
+            Some(backend) => crate::wasm::storage::lock(backend)?.get(key),
+            None => Ok(None),
+        }
+    }
+
+    /// Take a savepoint, returning its depth for [`rollback_to`](Self::rollback_to)
+    /// and [`release`](Self::release)
+    pub fn savepoint(&mut self, label: impl Into<String>) -> usize {
+        self.savepoints.push(Savepoint {
+            label: label.into(),
+            storage: self.storage.clone(),
+            events: self.events.len(),
+        });
+        self.savepoints.len() - 1
+    }
+
+    /// Restore the state at savepoint `depth`, closing it and every savepoint
+    /// taken after it
+    pub fn rollback_to(&mut self, depth: usize) -> Result<(), String> {
+        if depth >= self.savepoints.len() {
+            return Err(format!("No savepoint at depth {}", depth));
+        }
+        let savepoint = self.savepoints.drain(depth..).next().expect("savepoint checked above");
+        self.storage = savepoint.storage;
+        self.events.truncate(savepoint.events);
+        Ok(())
+    }
+
+    /// Close savepoint `depth` and those after it, keeping their changes as
+    /// part of the enclosing savepoint
+    pub fn release(&mut self, depth: usize) -> Result<(), String> {
+        if depth >= self.savepoints.len() {
+            return Err(format!("No savepoint at depth {}", depth));
+        }
+        self.savepoints.truncate(depth);
+        Ok(())
+    }
+
+    /// Open savepoints, outermost first
+    pub fn savepoints(&self) -> &[Savepoint] {
+        &self.savepoints
+    }
+
+    /// Run `call` as an atomic unit (a transaction or a nested contract call):
+    /// if it fails, its storage writes and events are rolled back, including
+    /// those of calls nested inside it.
+    pub fn atomic<T, E>(&mut self, label: impl Into<String>, call: impl FnOnce(&mut Self) -> Result<T, E>) -> Result<T, E> {
+        let depth = self.savepoint(label);
+        let result = call(self);
+        // `call` may have closed this savepoint itself
+        if depth < self.savepoints.len() {
+            if result.is_ok() {
+                self.savepoints.truncate(depth);
+            } else {
+                let _ = self.rollback_to(depth);
+            }
         }
+        result
     }
 
     pub fn use_gas(&mut self, amount: Gas) -> Result<(), String> {
@@ -389,6 +808,90 @@ pub struct Event {
     pub indexed_data: Vec<serde_json::Value>,
 }
 
+/// Tracepoint hit reported by an instrumented contract.
+///
+/// `tracepoint` indexes the trace map stored in the contract's ABI metadata.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum TraceEvent {
+    Enter { tracepoint: u32, gas_used: Gas },
+    Exit { tracepoint: u32, gas_used: Gas },
+    StorageWrite { tracepoint: u32, key: String },
+}
+
+impl TraceEvent {
+    pub fn tracepoint(&self) -> u32 {
+        match self {
+            TraceEvent::Enter { tracepoint, .. }
+            | TraceEvent::Exit { tracepoint, .. }
+            | TraceEvent::StorageWrite { tracepoint, .. } => *tracepoint,
+        }
+    }
+}
+
+/// A host import called by a contract and what it answered.
+///
+/// Determinism checks compare these between runs to tell a nondeterministic
+/// host function from nondeterministic contract code.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HostCall {
+    pub import: String,
+    /// Value returned to the contract, or what the call acted on (the slot
+    /// written, the event emitted)
+    pub result: serde_json::Value,
+    /// Gas used by the call when the import was made
+    pub gas_used: Gas,
+}
+
+/// Structured reason attached to a reverted execution
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RevertReason {
+    /// Error name, matching an entry in the contract ABI's `errors`
+    pub error: String,
+    /// Human-readable message
+    pub message: String,
+    /// Typed error arguments, in ABI order
+    #[serde(default)]
+    pub args: Vec<serde_json::Value>,
+}
+
+impl RevertReason {
+    /// Prefix marking a trap payload as an encoded revert reason
+    pub const PAYLOAD_MAGIC: &'static [u8; 4] = b"RVRT";
+
+    pub fn new(error: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            error: error.into(),
+            message: message.into(),
+            args: Vec::new(),
+        }
+    }
+
+    pub fn with_arg(mut self, arg: serde_json::Value) -> Self {
+        self.args.push(arg);
+        self
+    }
+
+    /// Encode as a trap payload: the magic prefix followed by the JSON reason
+    pub fn encode(&self) -> Vec<u8> {
+        let mut payload = Self::PAYLOAD_MAGIC.to_vec();
+        payload.extend(serde_json::to_vec(self).unwrap_or_default());
+        payload
+    }
+
+    /// Decode a trap payload produced by [`RevertReason::encode`]
+    pub fn decode(payload: &[u8]) -> Option<Self> {
+        let body = payload.strip_prefix(Self::PAYLOAD_MAGIC.as_slice())?;
+        serde_json::from_slice(body).ok()
+    }
+}
+
+impl std::fmt::Display for RevertReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.error, self.message)
+    }
+}
+
 /// Node execution result
 #[derive(Debug, Clone)]
 pub struct NodeResult {
@@ -430,6 +933,43 @@ mod tests {
         assert!(!ValueType::Boolean.is_compatible_with(&ValueType::Integer));
     }
 
+    #[test]
+    fn test_collection_types() {
+        let ints = ValueType::from_name("array<integer>").unwrap();
+        assert_eq!(ints.element_type(), Some(&ValueType::Integer));
+        assert!(ints.is_compatible_with(&ValueType::Array(Box::new(ValueType::Any))));
+        assert!(!ints.is_compatible_with(&ValueType::Map(Box::new(ValueType::Integer))));
+        assert!(ints.matches_value(&serde_json::json!([1, 2, 3])));
+        assert!(!ints.matches_value(&serde_json::json!([1, "two"])));
+        assert_eq!(ValueType::from_name("map<string>"), Some(ValueType::Map(Box::new(ValueType::String))));
+    }
+
+    #[test]
+    fn test_decimal_parsing_and_display() {
+        let price = Decimal::parse("12.5", 2).unwrap();
+        assert_eq!(price.units, 1250);
+        assert_eq!(price.to_string(), "12.50");
+        assert_eq!(Decimal::parse("-0.05", 2).unwrap().to_string(), "-0.05");
+        assert!(Decimal::parse("1.234", 2).is_err());
+        assert!(Decimal::from_json(&serde_json::json!(1.5), 2).is_err());
+        assert_eq!(Decimal::from_json(&serde_json::json!(3), 2).unwrap().units, 300);
+
+        let decimal_type = ValueType::from_name("decimal<2>").unwrap();
+        assert_eq!(decimal_type, ValueType::Decimal(2));
+        assert!(decimal_type.matches_value(&serde_json::json!("1.25")));
+        assert!(!decimal_type.matches_value(&serde_json::json!("1.255")));
+        assert!(!decimal_type.is_compatible_with(&ValueType::Decimal(6)));
+    }
+
+    #[test]
+    fn test_revert_reason_round_trip() {
+        let reason = RevertReason::new("InsufficientBalance", "balance too low").with_arg(serde_json::json!(42));
+        let payload = reason.encode();
+        assert!(payload.starts_with(RevertReason::PAYLOAD_MAGIC));
+        assert_eq!(RevertReason::decode(&payload), Some(reason));
+        assert_eq!(RevertReason::decode(b"not a revert"), None);
+    }
+
     #[test]
     fn test_visual_graph_operations() {
         let mut graph = VisualGraph::new("test graph");
@@ -440,10 +980,122 @@ mod tests {
         assert!(graph.get_node(node_id).is_some());
     }
 
+    #[test]
+    fn test_remove_node_drops_its_connections() {
+        let mut graph = VisualGraph::new("test graph");
+        let [a, b, c] = ["a", "b", "c"].map(|t| VisualNode::new(Uuid::new_v4(), t, Position::new(0.0, 0.0)));
+        graph.add_connection(Connection::new(Uuid::new_v4(), a.id, "out", b.id, "in"));
+        graph.add_connection(Connection::new(Uuid::new_v4(), b.id, "out", c.id, "in"));
+        graph.add_connection(Connection::new(Uuid::new_v4(), a.id, "out", c.id, "other"));
+        let (a_id, b_id) = (a.id, b.id);
+        for node in [a, b, c] {
+            graph.add_node(node);
+        }
+
+        assert_eq!(graph.remove_node(b_id).unwrap().node_type, "b");
+        assert!(graph.get_node(b_id).is_none());
+        assert_eq!(graph.connections.len(), 1);
+        assert_eq!(graph.connections[0].source_node, a_id);
+        assert!(matches!(graph.remove_node(b_id), Err(crate::error::CanvasError::NodeNotFound(_))));
+    }
+
+    #[test]
+    fn test_replace_with_literal_sets_the_inputs_it_fed() {
+        let mut graph = VisualGraph::new("test graph");
+        let [source, add, product] = ["ReadStorage", "Add", "Multiply"]
+            .map(|t| VisualNode::new(Uuid::new_v4(), t, Position::new(0.0, 0.0)));
+        graph.add_connection(Connection::new(Uuid::new_v4(), source.id, "value", add.id, "a"));
+        graph.add_connection(Connection::new(Uuid::new_v4(), add.id, "result", product.id, "a"));
+        graph.add_connection(Connection::new(Uuid::new_v4(), add.id, "result", product.id, "b"));
+        let (add_id, product_id) = (add.id, product.id);
+        for node in [source, add, product] {
+            graph.add_node(node);
+        }
+
+        graph.replace_with_literal(add_id, serde_json::json!(5)).unwrap();
+        assert!(graph.get_node(add_id).is_none());
+        assert!(graph.connections.is_empty());
+        let product = graph.get_node(product_id).unwrap();
+        assert_eq!(product.properties["a"], serde_json::json!(5));
+        assert_eq!(product.properties["b"], serde_json::json!(5));
+        assert!(graph.replace_with_literal(add_id, serde_json::json!(5)).is_err());
+    }
+
     #[test]
     fn test_execution_context_gas() {
         let mut context = ExecutionContext::new(1000);
         assert!(context.use_gas(500).is_ok());
         assert!(context.use_gas(600).is_err());
     }
-} 
\ No newline at end of file
+
+    #[test]
+    fn test_failed_calls_roll_back_nested_writes() {
+        let mut context = ExecutionContext::new(1000);
+        context.storage.insert("supply".to_string(), serde_json::json!(1));
+        let event = |name: &str| Event { name: name.to_string(), data: HashMap::new(), indexed_data: Vec::new() };
+
+        let outer: Result<(), String> = context.atomic("transfer", |ctx| {
+            ctx.storage.insert("supply".to_string(), serde_json::json!(2));
+            ctx.emit_event(event("Transfer"));
+            let inner: Result<(), String> = ctx.atomic("callee", |ctx| {
+                ctx.storage.insert("callee".to_string(), serde_json::json!(true));
+                ctx.emit_event(event("CalleeRan"));
+                Err("reverted".to_string())
+            });
+            assert!(inner.is_err());
+            assert!(!ctx.storage.contains_key("callee"));
+            assert_eq!(ctx.events.len(), 1);
+            assert_eq!(ctx.savepoints().len(), 1);
+            Ok(())
+        });
+        assert!(outer.is_ok());
+        assert_eq!(context.storage["supply"], serde_json::json!(2));
+
+        let depth = context.savepoint("tx2");
+        context.storage.insert("supply".to_string(), serde_json::json!(3));
+        context.rollback_to(depth).unwrap();
+        assert_eq!(context.storage["supply"], serde_json::json!(2));
+        assert_eq!(context.events.len(), 1);
+        assert!(context.savepoints().is_empty());
+        assert!(context.release(0).is_err());
+    }
+
+    #[test]
+    fn test_abi_round_trip() {
+        let abi = Abi {
+            functions: vec![FunctionABI {
+                name: "deposit".to_string(),
+                inputs: vec![ParameterABI {
+                    name: "amount".to_string(),
+                    value_type: ValueType::Decimal(2),
+                    indexed: false,
+                }],
+                outputs: vec![ParameterABI {
+                    name: "result".to_string(),
+                    value_type: ValueType::Map(Box::new(ValueType::Integer)),
+                    indexed: false,
+                }],
+                state_mutability: StateMutability::Payable,
+                gas_estimate: Some(1200),
+            }],
+            events: vec![EventABI {
+                name: "Deposited".to_string(),
+                inputs: Vec::new(),
+                anonymous: false,
+            }],
+            errors: Vec::new(),
+            storage: vec![StorageABI {
+                key: "balances".to_string(),
+                value_type: ValueType::Map(Box::new(ValueType::Integer)),
+                writable: true,
+            }],
+            metadata: HashMap::from([("selector.deposit".to_string(), "0x0000beef".to_string())]),
+        };
+        let json = serde_json::to_string(&abi).unwrap();
+        assert_eq!(serde_json::from_str::<Abi>(&json).unwrap(), abi);
+
+        // ABIs written before the storage schema existed still load
+        let legacy = r#"{"functions": [], "events": [], "errors": [], "metadata": {}}"#;
+        assert!(serde_json::from_str::<Abi>(legacy).unwrap().storage.is_empty());
+    }
+}
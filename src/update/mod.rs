@@ -0,0 +1,413 @@
+//! Self-update of the CLI binary and the node catalog
+//!
+//! Releases are announced in a signed feed: a JSON document listing, for each
+//! component, the published versions with their channel, download URL and
+//! SHA-256 digest, plus an ed25519 signature over the document. The feed is
+//! only trusted when the signature verifies against a configured key, and a
+//! download is only installed when its digest matches the signed one.
+//!
+//! The CLI binary and the node catalog (built-in node definitions and
+//! templates, overriding the ones compiled into the binary) update
+//! independently. Installing stages the new file next to the old one, checks
+//! it, and keeps the previous version as a `.bak` file: a failed check leaves
+//! the old version in place, and [`rollback`] restores the backup later.
+
+use std::{
+    collections::BTreeMap,
+    io::Read,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use semver::Version;
+use serde::{Deserialize, Serialize};
+
+pub use crate::config::ReleaseChannel;
+use crate::{
+    config::{Config, UpdateConfig},
+    error::{CanvasError, CanvasResult},
+    nodes::{decode_hex, encode_hex, NodeDefinition, NodeRegistry},
+    types::VisualGraph,
+    wasm::host::{self, verify_signature, SignatureScheme},
+};
+
+/// Installed node catalog, under the data directory
+pub const NODE_CATALOG_FILE: &str = "node-catalog.json";
+/// Suffix of the copy kept of a replaced file
+pub const BACKUP_SUFFIX: &str = ".bak";
+/// Suffix of a downloaded file awaiting its check
+pub const STAGED_SUFFIX: &str = ".new";
+
+/// Independently updated part of the installation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Component {
+    Cli,
+    NodeCatalog,
+}
+
+impl Component {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "cli" => Some(Component::Cli),
+            "catalog" | "node-catalog" => Some(Component::NodeCatalog),
+            _ => None,
+        }
+    }
+}
+
+/// One published version of a component
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Release {
+    pub component: Component,
+    pub version: Version,
+    pub channel: ReleaseChannel,
+    pub url: String,
+    /// 0x-hex SHA-256 of the download
+    pub sha256: String,
+    /// Platform the CLI build is for (e.g. `x86_64-linux`); unset for the catalog
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target: Option<String>,
+    #[serde(default)]
+    pub notes: String,
+}
+
+impl Release {
+    /// Check a download against the digest in the signed feed
+    pub fn verify_download(&self, bytes: &[u8]) -> CanvasResult<()> {
+        let expected = decode_hex(&self.sha256)
+            .ok_or_else(|| CanvasError::Validation(format!("Release {} has an invalid digest", self.version)))?;
+        if host::hash(host::HashAlgorithm::Sha256, bytes) != expected {
+            return Err(CanvasError::Validation(format!(
+                "Download of {:?} {} does not match its published digest",
+                self.component, self.version
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Every published release
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ReleaseFeed {
+    pub releases: Vec<Release>,
+}
+
+impl ReleaseFeed {
+    /// Newest release of `component` newer than `current` that `channel` may
+    /// install. The beta channel also receives stable releases.
+    pub fn latest(
+        &self,
+        component: Component,
+        channel: ReleaseChannel,
+        target: Option<&str>,
+        current: &Version,
+    ) -> Option<&Release> {
+        self.releases
+            .iter()
+            .filter(|r| r.component == component && r.channel <= channel && r.version > *current)
+            .filter(|r| r.target.is_none() || r.target.as_deref() == target)
+            .max_by(|a, b| a.version.cmp(&b.version))
+    }
+}
+
+/// The feed as published: its JSON text plus an ed25519 signature over it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SignedReleaseFeed {
+    pub content: String,
+    /// 0x-hex ed25519 signature of `content`
+    pub signature: String,
+}
+
+impl SignedReleaseFeed {
+    /// Verify the signature against the trusted keys and parse the feed
+    pub fn verify(&self, trusted_keys: &[Vec<u8>]) -> CanvasResult<ReleaseFeed> {
+        let signature = decode_hex(&self.signature)
+            .ok_or_else(|| CanvasError::Validation("Release feed signature is not valid hex".to_string()))?;
+        let trusted = trusted_keys
+            .iter()
+            .any(|key| verify_signature(SignatureScheme::Ed25519, key, self.content.as_bytes(), &signature));
+        if !trusted {
+            return Err(CanvasError::PermissionDenied(
+                "Release feed is not signed by a trusted key".to_string(),
+            ));
+        }
+        Ok(serde_json::from_str(&self.content)?)
+    }
+}
+
+/// Sign a feed; used by the release pipeline and tests
+pub fn sign_release_feed(feed: &ReleaseFeed, signing_key: &ed25519_dalek::SigningKey) -> CanvasResult<SignedReleaseFeed> {
+    use ed25519_dalek::Signer;
+
+    let content = serde_json::to_string(feed)?;
+    let signature = signing_key.sign(content.as_bytes());
+    Ok(SignedReleaseFeed {
+        signature: encode_hex(&signature.to_bytes()),
+        content,
+    })
+}
+
+/// Node definitions and templates shipped separately from the binary
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NodeCatalog {
+    pub version: Version,
+    pub nodes: Vec<NodeDefinition>,
+    #[serde(default)]
+    pub templates: BTreeMap<String, VisualGraph>,
+}
+
+impl NodeCatalog {
+    /// Installed catalog file for a configuration
+    pub fn default_path(config: &Config) -> PathBuf {
+        config.app.data_dir.join(NODE_CATALOG_FILE)
+    }
+
+    pub fn parse(bytes: &[u8]) -> CanvasResult<Self> {
+        let catalog: Self = serde_json::from_slice(bytes)?;
+        if catalog.nodes.is_empty() {
+            return Err(CanvasError::Validation("Node catalog has no nodes".to_string()));
+        }
+        Ok(catalog)
+    }
+
+    /// The installed catalog, if one was downloaded
+    pub fn installed(config: &Config) -> CanvasResult<Option<Self>> {
+        let path = Self::default_path(config);
+        if !path.exists() {
+            return Ok(None);
+        }
+        Self::parse(&std::fs::read(path)?).map(Some)
+    }
+
+    /// Register the catalog's definitions, replacing built-in ones of the same type
+    pub fn register_into(&self, registry: &mut NodeRegistry) {
+        for definition in &self.nodes {
+            registry.register_node(definition.clone());
+        }
+    }
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_os_string();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+/// Replace `target` with `bytes`, keeping the old file as a backup.
+///
+/// The new content is staged next to `target` and passed to `check` first; if
+/// the check fails the staged file is removed and `target` is untouched.
+pub fn install_with_backup(
+    target: &Path,
+    bytes: &[u8],
+    check: impl FnOnce(&Path) -> CanvasResult<()>,
+) -> CanvasResult<()> {
+    let staged = with_suffix(target, STAGED_SUFFIX);
+    std::fs::write(&staged, bytes)?;
+    // Keep the executable bit of a replaced binary
+    if let Ok(metadata) = std::fs::metadata(target) {
+        std::fs::set_permissions(&staged, metadata.permissions())?;
+    }
+    if let Err(e) = check(&staged) {
+        let _ = std::fs::remove_file(&staged);
+        return Err(e);
+    }
+
+    let backup = with_suffix(target, BACKUP_SUFFIX);
+    let had_previous = target.exists();
+    if had_previous {
+        std::fs::rename(target, &backup)?;
+    }
+    if let Err(e) = std::fs::rename(&staged, target) {
+        if had_previous {
+            std::fs::rename(&backup, target)?;
+        }
+        return Err(e.into());
+    }
+    Ok(())
+}
+
+/// Restore the file replaced by the last [`install_with_backup`]
+pub fn rollback(target: &Path) -> CanvasResult<()> {
+    let backup = with_suffix(target, BACKUP_SUFFIX);
+    if !backup.exists() {
+        return Err(CanvasError::NotFound(format!("No previous version of {}", target.display())));
+    }
+    std::fs::rename(backup, target)?;
+    Ok(())
+}
+
+/// Platform of this build, as used in the feed's `target`
+pub fn current_target() -> String {
+    format!("{}-{}", std::env::consts::ARCH, std::env::consts::OS)
+}
+
+/// Checks the signed feed and installs verified updates
+pub struct Updater {
+    feed_url: String,
+    channel: ReleaseChannel,
+    trusted_keys: Vec<Vec<u8>>,
+    timeout: Duration,
+}
+
+impl Updater {
+    pub fn new(config: &UpdateConfig, timeout: Duration) -> CanvasResult<Self> {
+        let trusted_keys = config
+            .trusted_keys
+            .iter()
+            .map(|key| decode_hex(key).ok_or_else(|| CanvasError::Config(format!("Invalid release key: {}", key))))
+            .collect::<CanvasResult<Vec<_>>>()?;
+        if trusted_keys.is_empty() {
+            return Err(CanvasError::Config(
+                "Updates need at least one trusted release key; add one with `self-update --trust-key`".to_string(),
+            ));
+        }
+        Ok(Self {
+            feed_url: config.feed_url.clone(),
+            channel: config.channel,
+            trusted_keys,
+            timeout,
+        })
+    }
+
+    /// Use another channel than the configured one
+    pub fn with_channel(mut self, channel: ReleaseChannel) -> Self {
+        self.channel = channel;
+        self
+    }
+
+    fn agent(&self) -> ureq::Agent {
+        ureq::AgentBuilder::new().timeout(self.timeout).build()
+    }
+
+    /// Download and verify the release feed
+    pub fn fetch_feed(&self) -> CanvasResult<ReleaseFeed> {
+        let envelope: SignedReleaseFeed = self
+            .agent()
+            .get(&self.feed_url)
+            .call()
+            .map_err(|e| CanvasError::Network(format!("Release feed download failed: {}", e)))?
+            .into_json()?;
+        envelope.verify(&self.trusted_keys)
+    }
+
+    /// Release of `component` to update to from `current`, if any
+    pub fn check<'a>(&self, feed: &'a ReleaseFeed, component: Component, current: &Version) -> Option<&'a Release> {
+        let target = current_target();
+        let target = (component == Component::Cli).then_some(target.as_str());
+        feed.latest(component, self.channel, target, current)
+    }
+
+    /// Download a release and verify it against its digest
+    pub fn download(&self, release: &Release) -> CanvasResult<Vec<u8>> {
+        let mut bytes = Vec::new();
+        self.agent()
+            .get(&release.url)
+            .call()
+            .map_err(|e| CanvasError::Network(format!("Download of {} failed: {}", release.url, e)))?
+            .into_reader()
+            .read_to_end(&mut bytes)?;
+        release.verify_download(&bytes)?;
+        Ok(bytes)
+    }
+
+    /// Install a CLI release over `binary`; the new binary must run `--version`
+    pub fn install_cli(&self, release: &Release, binary: &Path) -> CanvasResult<()> {
+        let bytes = self.download(release)?;
+        install_with_backup(binary, &bytes, |staged| {
+            let status = std::process::Command::new(staged)
+                .arg("--version")
+                .output()
+                .map_err(|e| CanvasError::ExecutionError(format!("New binary does not start: {}", e)))?;
+            if !status.status.success() {
+                return Err(CanvasError::ExecutionError("New binary failed its self-check".to_string()));
+            }
+            Ok(())
+        })?;
+        log::info!("Installed CLI {}", release.version);
+        Ok(())
+    }
+
+    /// Install a node catalog release at `path`; it must parse as a catalog
+    pub fn install_catalog(&self, release: &Release, path: &Path) -> CanvasResult<()> {
+        let bytes = self.download(release)?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        install_with_backup(path, &bytes, |staged| NodeCatalog::parse(&std::fs::read(staged)?).map(|_| ()))?;
+        log::info!("Installed node catalog {}", release.version);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn release(component: Component, version: &str, channel: ReleaseChannel, bytes: &[u8]) -> Release {
+        Release {
+            component,
+            version: Version::parse(version).unwrap(),
+            channel,
+            url: format!("https://example.com/{}", version),
+            sha256: encode_hex(&host::hash(host::HashAlgorithm::Sha256, bytes)),
+            target: None,
+            notes: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_signed_feed_channels_and_rollback() {
+        let feed = ReleaseFeed {
+            releases: vec![
+                release(Component::Cli, "0.2.0", ReleaseChannel::Stable, b"cli"),
+                release(Component::Cli, "0.3.0-beta.1", ReleaseChannel::Beta, b"cli-beta"),
+                release(Component::NodeCatalog, "1.1.0", ReleaseChannel::Stable, b"catalog"),
+            ],
+        };
+        let key = ed25519_dalek::SigningKey::from_bytes(&[9u8; 32]);
+        let signed = sign_release_feed(&feed, &key).unwrap();
+        let trusted = vec![key.verifying_key().to_bytes().to_vec()];
+        let feed = signed.verify(&trusted).unwrap();
+        assert!(signed.verify(&[vec![0u8; 32]]).is_err());
+
+        let current = Version::new(0, 1, 0);
+        let stable = feed.latest(Component::Cli, ReleaseChannel::Stable, None, &current).unwrap();
+        assert_eq!(stable.version, Version::new(0, 2, 0));
+        let beta = feed.latest(Component::Cli, ReleaseChannel::Beta, None, &current).unwrap();
+        assert_eq!(beta.version.to_string(), "0.3.0-beta.1");
+        assert!(feed.latest(Component::NodeCatalog, ReleaseChannel::Stable, None, &Version::new(1, 1, 0)).is_none());
+        assert!(stable.verify_download(b"cli").is_ok());
+        assert!(stable.verify_download(b"tampered").is_err());
+
+        let dir = tempfile::tempdir().unwrap();
+        let target = dir.path().join("catalog.json");
+        std::fs::write(&target, "old").unwrap();
+        let failed = install_with_backup(&target, b"broken", |_| Err(CanvasError::Validation("bad".to_string())));
+        assert!(failed.is_err());
+        assert_eq!(std::fs::read_to_string(&target).unwrap(), "old");
+        install_with_backup(&target, b"new", |_| Ok(())).unwrap();
+        assert_eq!(std::fs::read_to_string(&target).unwrap(), "new");
+        rollback(&target).unwrap();
+        assert_eq!(std::fs::read_to_string(&target).unwrap(), "old");
+    }
+
+    #[test]
+    fn test_installed_catalog_replaces_builtin_definitions() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = Config::default();
+        config.app.data_dir = dir.path().to_path_buf();
+        assert_eq!(NodeRegistry::load(&config).unwrap().get_node_definition("If").unwrap().name, "If Condition");
+
+        let catalog = NodeCatalog {
+            version: Version::new(1, 1, 0),
+            nodes: vec![NodeDefinition::new("If", "Branch", "Runs one of two flows", "Logic")],
+            templates: BTreeMap::new(),
+        };
+        std::fs::write(NodeCatalog::default_path(&config), serde_json::to_vec(&catalog).unwrap()).unwrap();
+        let registry = NodeRegistry::load(&config).unwrap();
+        assert_eq!(registry.get_node_definition("If").unwrap().name, "Branch");
+        assert!(registry.get_node_definition("Require").is_some());
+    }
+}
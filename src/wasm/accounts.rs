@@ -0,0 +1,155 @@
+//! Sandbox accounts for simulations
+//!
+//! Simulated calls run against a ledger of account balances so payable flows
+//! can be exercised without a chain: a call's value moves from the caller to
+//! the contract before it runs and moves back if it reverts, and the contract
+//! pays out through [`SandboxAccounts::transfer`] (backing
+//! [`HOST_TRANSFER`](super::host::HOST_TRANSFER)).
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::{CanvasError, CanvasResult},
+    types::{Gas, RevertReason},
+};
+
+use super::BlockContext;
+
+/// Account calls are made from when a request sets no caller
+pub const DEFAULT_CALLER: &str = "0x0000000000000000000000000000000000000001";
+/// Address of the contract under simulation
+pub const DEFAULT_CONTRACT_ADDRESS: &str = "0x00000000000000000000000000000000000000c0";
+/// Error name of a revert caused by a transfer the sender cannot cover
+pub const INSUFFICIENT_BALANCE: &str = "InsufficientBalance";
+
+/// A contract call to simulate
+#[derive(Debug, Clone)]
+pub struct SimulationRequest {
+    pub function: String,
+    pub arguments: Vec<serde_json::Value>,
+    pub gas_limit: Gas,
+    /// Calling account (0x-hex address); [`DEFAULT_CALLER`] when `None`
+    pub caller: Option<String>,
+    /// Amount transferred from the caller to the contract with the call
+    pub value: u128,
+    /// Block the call executes in
+    pub block: BlockContext,
+}
+
+impl SimulationRequest {
+    pub fn new(function: impl Into<String>, arguments: Vec<serde_json::Value>, gas_limit: Gas) -> Self {
+        Self {
+            function: function.into(),
+            arguments,
+            gas_limit,
+            caller: None,
+            value: 0,
+            block: BlockContext::default(),
+        }
+    }
+
+    pub fn set_caller(&mut self, caller: impl Into<String>) -> &mut Self {
+        self.caller = Some(caller.into());
+        self
+    }
+
+    pub fn set_value(&mut self, value: u128) -> &mut Self {
+        self.value = value;
+        self
+    }
+
+    pub fn set_block(&mut self, block: BlockContext) -> &mut Self {
+        self.block = block;
+        self
+    }
+
+    /// The calling account
+    pub fn caller(&self) -> &str {
+        self.caller.as_deref().unwrap_or(DEFAULT_CALLER)
+    }
+}
+
+/// Balances of the accounts in a simulation
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SandboxAccounts {
+    /// Address the simulated contract holds its balance under
+    pub contract: String,
+    balances: BTreeMap<String, u128>,
+}
+
+impl Default for SandboxAccounts {
+    fn default() -> Self {
+        Self {
+            contract: DEFAULT_CONTRACT_ADDRESS.to_string(),
+            balances: BTreeMap::new(),
+        }
+    }
+}
+
+fn key(address: &str) -> String {
+    address.to_ascii_lowercase()
+}
+
+impl SandboxAccounts {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start from the given balances
+    pub fn with_balances(mut self, balances: impl IntoIterator<Item = (String, u128)>) -> Self {
+        for (address, amount) in balances {
+            self.balances.insert(key(&address), amount);
+        }
+        self
+    }
+
+    pub fn with_contract(mut self, address: impl Into<String>) -> Self {
+        self.contract = address.into();
+        self
+    }
+
+    /// Balance of an account; accounts never credited hold nothing
+    pub fn balance(&self, address: &str) -> u128 {
+        self.balances.get(&key(address)).copied().unwrap_or(0)
+    }
+
+    pub fn contract_balance(&self) -> u128 {
+        self.balance(&self.contract)
+    }
+
+    /// Mint `amount` into an account
+    pub fn credit(&mut self, address: &str, amount: u128) -> CanvasResult<()> {
+        let balance = self.balances.entry(key(address)).or_insert(0);
+        *balance = balance
+            .checked_add(amount)
+            .ok_or_else(|| CanvasError::Validation(format!("Balance of {} would overflow", address)))?;
+        Ok(())
+    }
+
+    /// Move `amount` between accounts, reverting with
+    /// [`INSUFFICIENT_BALANCE`] if `from` cannot cover it
+    pub fn transfer(&mut self, from: &str, to: &str, amount: u128) -> CanvasResult<()> {
+        if amount == 0 {
+            return Ok(());
+        }
+        let available = self.balance(from);
+        if available < amount {
+            return Err(CanvasError::Reverted(RevertReason::new(
+                INSUFFICIENT_BALANCE,
+                format!("{} holds {}, needs {}", from, available, amount),
+            )));
+        }
+        if key(from) != key(to) {
+            self.credit(to, amount)?;
+            self.balances.insert(key(from), available - amount);
+        }
+        Ok(())
+    }
+
+    /// All non-empty balances by lowercase address
+    pub fn balances(&self) -> impl Iterator<Item = (&str, u128)> {
+        self.balances.iter().filter(|(_, b)| **b > 0).map(|(a, b)| (a.as_str(), *b))
+    }
+}
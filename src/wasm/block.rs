@@ -0,0 +1,73 @@
+//! Block context for simulations
+//!
+//! Contracts read the current block number, timestamp and chain ID through
+//! the block host imports. Simulations supply them explicitly so
+//! time-dependent logic (vesting schedules, deadlines) can be tested by
+//! advancing the block between calls.
+
+use serde::{Deserialize, Serialize};
+
+/// Seconds a block advances the clock by when no time is given
+pub const DEFAULT_BLOCK_TIME_SECS: u64 = 12;
+/// Chain ID simulations run on unless one is set
+pub const DEFAULT_CHAIN_ID: u64 = 1337;
+
+/// Block a simulated call executes in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlockContext {
+    pub number: u64,
+    /// Unix time in seconds
+    pub timestamp: u64,
+    pub chain_id: u64,
+}
+
+impl Default for BlockContext {
+    fn default() -> Self {
+        Self {
+            number: 1,
+            timestamp: 0,
+            chain_id: DEFAULT_CHAIN_ID,
+        }
+    }
+}
+
+impl BlockContext {
+    pub fn new(number: u64, timestamp: u64, chain_id: u64) -> Self {
+        Self {
+            number,
+            timestamp,
+            chain_id,
+        }
+    }
+
+    /// Move forward by `advance`
+    pub fn advance(&mut self, advance: &BlockAdvance) {
+        self.number += advance.blocks;
+        self.timestamp += advance.seconds.unwrap_or(advance.blocks * DEFAULT_BLOCK_TIME_SECS);
+    }
+}
+
+/// How far to move the block forward
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlockAdvance {
+    #[serde(default)]
+    pub blocks: u64,
+    /// Seconds to advance the clock by; [`DEFAULT_BLOCK_TIME_SECS`] per block
+    /// when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seconds: Option<u64>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_advance_defaults_to_block_time() {
+        let mut block = BlockContext::default();
+        block.advance(&BlockAdvance { blocks: 10, seconds: None });
+        assert_eq!((block.number, block.timestamp), (11, 120));
+        block.advance(&BlockAdvance { blocks: 0, seconds: Some(86_400) });
+        assert_eq!((block.number, block.timestamp), (11, 86_520));
+    }
+}
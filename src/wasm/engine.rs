@@ -0,0 +1,757 @@
+//! Compiled contract execution with wasmtime
+//!
+//! Every call instantiates the contract in a fresh store. Gas is fuel, one
+//! unit per unit of gas, and the host imports burn their cost (see [`host`])
+//! from the same budget, so running out inside an import stops the call there.
+//!
+//! An exported entry point is called directly, each JSON argument converted
+//! to its parameter's type (numbers, booleans and numeric strings). Functions
+//! without an export of their own go through the
+//! [`DISPATCH_EXPORT`](crate::compiler::DISPATCH_EXPORT): the arguments are
+//! written as a JSON array into a buffer from the contract's `alloc(len) -> ptr`
+//! export and routed by the function's selector.
+//!
+//! Host imports live in module `env` and take strings as `(ptr, len)` into the
+//! contract's exported `memory`:
+//!
+//! | Import                                    | Effect                                              |
+//! |-------------------------------------------|-----------------------------------------------------|
+//! | `baals_read_storage(key, key_len, out)`   | Writes the slot's JSON at `out`, returns its length, or -1 when unset |
+//! | `baals_write_storage(key, key_len, value, value_len)` | Sets the slot to the JSON `value`       |
+//! | `baals_batch_read_storage(keys, keys_len, out)` | Reads a JSON array of keys into `out` (see below), returns the key count |
+//! | `baals_batch_write_storage(entries, len)` | Sets the slots of a JSON array of `[key, value]` pairs, in order |
+//! | `baals_emit_event(name, name_len, data, data_len)` | Emits an event; `data` is a JSON object or empty |
+//! | `baals_revert(payload, len)`              | Reverts with an encoded revert reason               |
+//! | `baals_caller(out)`                       | Writes the caller's 0x-hex address at `out`, returns its length |
+//! | `baals_caller_is(address, len)`           | Returns 1 when the caller's address is `address`, else 0 |
+//! | `baals_hash(algorithm, data, len, out)`   | Writes the digest of `data` at `out`, returns its length; `algorithm` is a [`HashAlgorithm`](host::HashAlgorithm) code |
+//! | `baals_verify_signature(scheme, key, key_len, message, message_len, signature, signature_len)` | Returns 1 when the signature is valid, else 0; `scheme` is a [`SignatureScheme`](host::SignatureScheme) code |
+//! | `baals_decimal_mul(a, b, scale, mode)` and `baals_decimal_div` | Product or quotient of two decimals' units at `scale`; `mode` is an [`OverflowMode`](crate::types::OverflowMode) code, and checked overflow or division by zero traps |
+//! | `baals_call_contract(target, target_len, function, function_len, args, args_len)` | Calls another contract with a JSON array of arguments, returns a [`CallStatus`](host::CallStatus) code |
+//! | `baals_call_error_is(name, len)`          | Returns 1 when the last failed call reverted with error `name`, else 0 |
+//! | `baals_call_rethrow()`                    | Reverts with the last failed call's revert reason   |
+//! | `baals_block_number()` and friends        | Block context values                                |
+//! | `baals_trace_*`                           | Tracepoints of instrumented builds                  |
+//!
+//! There are no other contracts in local execution, so every call fails with
+//! a `CallUnavailable` revert, as it does for the node interpreter's TryCall.
+//!
+//! A batch read writes one `(ptr: i32, len: i32)` entry per key at `out`, `len`
+//! being -1 for an unset slot, followed by the JSON of the values they point at.
+//!
+//! Any other import traps when called. A trap reverts the call like
+//! `baals_revert` does; running out of fuel reverts with [`OUT_OF_GAS`].
+//!
+//! Calls on an [`interruptible_engine`] can also be checked at every epoch
+//! tick, see [`execute_interruptible`]; the ticking itself is up to the caller.
+
+use std::{collections::HashMap, time::Instant};
+
+use wasmtime::{Caller, Engine, Extern, Instance, Linker, Memory, Module, Store, Trap, TypedFunc, Val, ValType};
+
+use super::{host, BlockContext, SimulationRequest, SimulationResult};
+use crate::{
+    compiler::{function_selector, overflow_mode_from_code, DISPATCH_EXPORT},
+    error::{CanvasError, CanvasResult},
+    types::{Decimal, Event, ExecutionContext, Gas, HostCall, OverflowMode, RevertReason, TraceEvent},
+};
+
+/// Export contracts allocate argument buffers with, `alloc(len) -> ptr`
+pub const ALLOC_EXPORT: &str = "alloc";
+/// Export holding the contract's linear memory
+pub const MEMORY_EXPORT: &str = "memory";
+/// Revert error of a call that ran out of gas
+pub const OUT_OF_GAS: &str = "OutOfGas";
+/// Revert error of a call that trapped
+pub const TRAP: &str = "Trap";
+
+/// Store data of a running call
+struct HostState {
+    /// Caller's context, moved in for the duration of the call
+    context: ExecutionContext,
+    block: BlockContext,
+    /// 0x-hex address of the calling account
+    caller: String,
+    gas_limit: Gas,
+    /// Events of this call, kept apart so a revert drops them
+    events: Vec<Event>,
+    /// Payload of a `baals_revert` call
+    revert: Option<Vec<u8>>,
+    /// Key and value bytes passed to `baals_write_storage`
+    storage_bytes: u64,
+    /// Tracepoints entered and not yet exited, innermost last
+    tracepoints: Vec<u32>,
+    /// Revert reason of the last failed `baals_call_contract`
+    call_failure: Option<RevertReason>,
+}
+
+/// Progress check of an interruptible call, given the gas used so far and
+/// the tracepoints entered and not yet exited, innermost last. Returning
+/// `false` aborts the call with a [`TRAP`] revert.
+pub type EpochCheck = Box<dyn FnMut(Gas, &[u32]) -> bool + Send + Sync>;
+
+/// Engine with fuel metering, shared by every call of a runtime
+pub fn engine() -> CanvasResult<Engine> {
+    let mut config = wasmtime::Config::new();
+    config.consume_fuel(true);
+    Engine::new(&config).map_err(wasm_error)
+}
+
+/// Engine with fuel metering and epoch interruption, for
+/// [`execute_interruptible`]
+pub fn interruptible_engine() -> CanvasResult<Engine> {
+    let mut config = wasmtime::Config::new();
+    config.consume_fuel(true);
+    config.epoch_interruption(true);
+    Engine::new(&config).map_err(wasm_error)
+}
+
+/// Run `function` of a contract against `context`, called from
+/// [`DEFAULT_CALLER`](super::accounts::DEFAULT_CALLER).
+///
+/// Storage writes land in `context.storage` (reads fall through to its
+/// backend) and stay there even if the call reverts; callers that need
+/// atomicity take a savepoint first. Events are returned, not added to the
+/// context. `Err` means the call could not be made at all: an invalid module,
+/// an unknown function or arguments that don't fit it.
+pub fn execute(
+    engine: &Engine,
+    wasm_bytes: &[u8],
+    function: &str,
+    arguments: &[serde_json::Value],
+    gas_limit: Gas,
+    block: BlockContext,
+    context: &mut ExecutionContext,
+) -> CanvasResult<SimulationResult> {
+    let mut request = SimulationRequest::new(function, arguments.to_vec(), gas_limit);
+    request.set_block(block);
+    execute_request(engine, wasm_bytes, &request, context)
+}
+
+/// Run a request's function of a contract against `context`, in the request's
+/// block and from its caller. The request's value is not transferred here;
+/// see [`WasmRuntime::execute_request_in`](super::WasmRuntime::execute_request_in).
+pub fn execute_request(
+    engine: &Engine,
+    wasm_bytes: &[u8],
+    request: &SimulationRequest,
+    context: &mut ExecutionContext,
+) -> CanvasResult<SimulationResult> {
+    execute_checked(engine, wasm_bytes, request, context, None)
+}
+
+/// Run a request like [`execute_request`] on an [`interruptible_engine`],
+/// calling `check` whenever the engine's epoch advances
+pub fn execute_interruptible(
+    engine: &Engine,
+    wasm_bytes: &[u8],
+    request: &SimulationRequest,
+    context: &mut ExecutionContext,
+    check: EpochCheck,
+) -> CanvasResult<SimulationResult> {
+    execute_checked(engine, wasm_bytes, request, context, Some(check))
+}
+
+fn execute_checked(
+    engine: &Engine,
+    wasm_bytes: &[u8],
+    request: &SimulationRequest,
+    context: &mut ExecutionContext,
+    check: Option<EpochCheck>,
+) -> CanvasResult<SimulationResult> {
+    let started = Instant::now();
+    let (function, gas_limit) = (request.function.as_str(), request.gas_limit);
+    let module = Module::new(engine, wasm_bytes).map_err(wasm_error)?;
+    let mut linker = Linker::new(engine);
+    link_host_imports(&mut linker).map_err(wasm_error)?;
+    linker.define_unknown_imports_as_traps(&module).map_err(wasm_error)?;
+
+    let state = HostState {
+        context: std::mem::replace(context, ExecutionContext::new(0)),
+        block: request.block,
+        caller: request.caller().to_string(),
+        gas_limit,
+        events: Vec::new(),
+        revert: None,
+        storage_bytes: 0,
+        tracepoints: Vec::new(),
+        call_failure: None,
+    };
+    let mut store = Store::new(engine, state);
+    if let Some(mut check) = check {
+        store.set_epoch_deadline(1);
+        store.epoch_deadline_callback(move |store| {
+            let gas_used = store.data().gas_limit.saturating_sub(store.get_fuel().unwrap_or(0));
+            if !check(gas_used, &store.data().tracepoints) {
+                return Err(wasmtime::Error::msg("call interrupted"));
+            }
+            Ok(wasmtime::UpdateDeadline::Continue(1))
+        });
+    }
+    let outcome = run(&mut store, &linker, &module, function, &request.arguments);
+    let gas_used = gas_limit.saturating_sub(store.get_fuel().unwrap_or(0));
+    let state = store.into_data();
+    *context = state.context;
+    let execution_time = started.elapsed();
+
+    match outcome? {
+        Ok(results) => Ok(SimulationResult {
+            output: serde_json::json!({
+                "success": true,
+                "function": function,
+                "result": result_value(&results),
+            }),
+            gas_used,
+            storage_bytes: state.storage_bytes,
+            events: state.events,
+            execution_time,
+            revert_reason: None,
+        }),
+        Err(error) => {
+            let payload = match (state.revert, error.downcast_ref::<Trap>()) {
+                (Some(payload), _) => payload,
+                (None, Some(Trap::OutOfFuel)) => {
+                    RevertReason::new(OUT_OF_GAS, format!("gas limit of {} exhausted", gas_limit)).encode()
+                }
+                (None, _) => RevertReason::new(TRAP, error.root_cause().to_string()).encode(),
+            };
+            Ok(SimulationResult::from_revert(&payload, gas_used, execution_time))
+        }
+    }
+}
+
+/// Instantiate and call. The outer result fails when the call can't be made,
+/// the inner one when the contract trapped or reverted.
+fn run(
+    store: &mut Store<HostState>,
+    linker: &Linker<HostState>,
+    module: &Module,
+    function: &str,
+    arguments: &[serde_json::Value],
+) -> CanvasResult<wasmtime::Result<Vec<Val>>> {
+    let gas_limit = store.data().gas_limit;
+    store.set_fuel(gas_limit).map_err(wasm_error)?;
+    // With unknown imports defined, instantiation only fails in a start function
+    let instance = match linker.instantiate(&mut *store, module) {
+        Ok(instance) => instance,
+        Err(error) => return Ok(Err(error)),
+    };
+
+    if let Some(func) = instance.get_func(&mut *store, function) {
+        let ty = func.ty(&*store);
+        let params: Vec<ValType> = ty.params().collect();
+        if params.len() != arguments.len() {
+            return Err(CanvasError::Validation(format!(
+                "'{}' takes {} argument(s), got {}",
+                function,
+                params.len(),
+                arguments.len()
+            )));
+        }
+        let params = params
+            .iter()
+            .zip(arguments)
+            .map(|(ty, value)| {
+                to_val(value, ty).ok_or_else(|| {
+                    CanvasError::Validation(format!("Argument {} of '{}' is not a valid {:?}", value, function, ty))
+                })
+            })
+            .collect::<CanvasResult<Vec<_>>>()?;
+        let mut results = vec![Val::I32(0); ty.results().len()];
+        return Ok(func.call(&mut *store, &params, &mut results).map(|()| results));
+    }
+
+    let dispatch = instance.get_typed_func::<(i32, i32, i32), i32>(&mut *store, DISPATCH_EXPORT);
+    let alloc = instance.get_typed_func::<i32, i32>(&mut *store, ALLOC_EXPORT);
+    let (Ok(dispatch), Ok(alloc)) = (dispatch, alloc) else {
+        return Err(CanvasError::NotFound(format!(
+            "Contract exports neither '{}' nor '{}' and '{}'",
+            function, DISPATCH_EXPORT, ALLOC_EXPORT
+        )));
+    };
+    let args = serde_json::to_vec(arguments)?;
+    Ok(call_dispatch(store, &instance, dispatch, alloc, function_selector(function), &args))
+}
+
+fn call_dispatch(
+    store: &mut Store<HostState>,
+    instance: &Instance,
+    dispatch: TypedFunc<(i32, i32, i32), i32>,
+    alloc: TypedFunc<i32, i32>,
+    selector: u32,
+    args: &[u8],
+) -> wasmtime::Result<Vec<Val>> {
+    let len = i32::try_from(args.len())?;
+    let ptr = alloc.call(&mut *store, len)?;
+    let memory = instance
+        .get_memory(&mut *store, MEMORY_EXPORT)
+        .ok_or_else(|| wasmtime::Error::msg("contract does not export its memory"))?;
+    memory.write(&mut *store, ptr as u32 as usize, args)?;
+    let result = dispatch.call(&mut *store, (selector as i32, ptr, len))?;
+    Ok(vec![Val::I32(result)])
+}
+
+fn link_host_imports(linker: &mut Linker<HostState>) -> wasmtime::Result<()> {
+    linker.func_wrap(
+        "env",
+        host::HOST_READ_STORAGE,
+        |mut caller: Caller<'_, HostState>, key_ptr: i32, key_len: i32, out_ptr: i32| -> wasmtime::Result<i32> {
+            burn(&mut caller, host::STORAGE_READ_GAS)?;
+            let key = read_string(&mut caller, key_ptr, key_len)?;
+            let value = caller.data().context.read_slot(&key).map_err(host_error)?;
+            log_host_call(
+                &mut caller,
+                host::HOST_READ_STORAGE,
+                serde_json::json!({ "key": key, "value": value }),
+            )?;
+            let Some(value) = value else {
+                return Ok(-1);
+            };
+            let bytes = serde_json::to_vec(&value)?;
+            memory(&mut caller)?.write(&mut caller, out_ptr as u32 as usize, &bytes)?;
+            Ok(i32::try_from(bytes.len())?)
+        },
+    )?;
+    linker.func_wrap(
+        "env",
+        host::HOST_WRITE_STORAGE,
+        |mut caller: Caller<'_, HostState>, key_ptr: i32, key_len: i32, value_ptr: i32, value_len: i32| -> wasmtime::Result<()> {
+            burn(&mut caller, host::STORAGE_WRITE_GAS)?;
+            let key = read_string(&mut caller, key_ptr, key_len)?;
+            let value: serde_json::Value = serde_json::from_slice(&read_bytes(&mut caller, value_ptr, value_len)?)?;
+            log_host_call(
+                &mut caller,
+                host::HOST_WRITE_STORAGE,
+                serde_json::json!({ "key": key, "value": value }),
+            )?;
+            let state = caller.data_mut();
+            state.storage_bytes += key_len.max(0) as u64 + value_len.max(0) as u64;
+            state.context.storage.insert(key, value);
+            Ok(())
+        },
+    )?;
+    linker.func_wrap(
+        "env",
+        host::HOST_BATCH_READ_STORAGE,
+        |mut caller: Caller<'_, HostState>, keys_ptr: i32, keys_len: i32, out_ptr: i32| -> wasmtime::Result<i32> {
+            let keys: Vec<String> = serde_json::from_slice(&read_bytes(&mut caller, keys_ptr, keys_len)?)?;
+            host::check_batch_size(keys.len()).map_err(host_error)?;
+            burn(&mut caller, host::batch_read_gas(keys.len()))?;
+            let values = keys
+                .iter()
+                .map(|key| caller.data().context.read_slot(key))
+                .collect::<CanvasResult<Vec<_>>>()
+                .map_err(host_error)?;
+            log_host_call(
+                &mut caller,
+                host::HOST_BATCH_READ_STORAGE,
+                serde_json::json!({ "keys": keys, "values": values }),
+            )?;
+
+            // Entry table first, then the values it points at
+            let out = out_ptr as u32 as usize;
+            let mut table = Vec::with_capacity(values.len() * 8);
+            let mut data = Vec::new();
+            let data_start = out + values.len() * 8;
+            for value in &values {
+                let (ptr, len) = match value {
+                    Some(value) => {
+                        let ptr = data_start + data.len();
+                        let bytes = serde_json::to_vec(value)?;
+                        data.extend_from_slice(&bytes);
+                        (u32::try_from(ptr)?, i32::try_from(bytes.len())?)
+                    }
+                    None => (0, -1),
+                };
+                table.extend_from_slice(&ptr.to_le_bytes());
+                table.extend_from_slice(&len.to_le_bytes());
+            }
+            let memory = memory(&mut caller)?;
+            memory.write(&mut caller, out, &table)?;
+            memory.write(&mut caller, data_start, &data)?;
+            Ok(i32::try_from(values.len())?)
+        },
+    )?;
+    linker.func_wrap(
+        "env",
+        host::HOST_BATCH_WRITE_STORAGE,
+        |mut caller: Caller<'_, HostState>, entries_ptr: i32, entries_len: i32| -> wasmtime::Result<()> {
+            let bytes = read_bytes(&mut caller, entries_ptr, entries_len)?;
+            let entries: Vec<(String, serde_json::Value)> = serde_json::from_slice(&bytes)?;
+            host::check_batch_size(entries.len()).map_err(host_error)?;
+            // Gas first, so a batch that runs out leaves storage untouched
+            burn(&mut caller, host::batch_write_gas(entries.len()))?;
+            log_host_call(&mut caller, host::HOST_BATCH_WRITE_STORAGE, serde_json::json!({ "entries": entries }))?;
+            let state = caller.data_mut();
+            for (key, value) in entries {
+                state.storage_bytes += (key.len() + serde_json::to_vec(&value)?.len()) as u64;
+                state.context.storage.insert(key, value);
+            }
+            Ok(())
+        },
+    )?;
+    linker.func_wrap(
+        "env",
+        host::HOST_EMIT_EVENT,
+        |mut caller: Caller<'_, HostState>, name_ptr: i32, name_len: i32, data_ptr: i32, data_len: i32| -> wasmtime::Result<()> {
+            burn(&mut caller, host::event_gas(name_len.max(0) as usize + data_len.max(0) as usize))?;
+            let name = read_string(&mut caller, name_ptr, name_len)?;
+            let data = read_bytes(&mut caller, data_ptr, data_len)?;
+            let data: HashMap<String, serde_json::Value> =
+                if data.is_empty() { HashMap::new() } else { serde_json::from_slice(&data)? };
+            log_host_call(&mut caller, host::HOST_EMIT_EVENT, serde_json::json!({ "name": name, "data": data }))?;
+            caller.data_mut().events.push(Event {
+                name,
+                data,
+                indexed_data: Vec::new(),
+            });
+            Ok(())
+        },
+    )?;
+    linker.func_wrap(
+        "env",
+        host::HOST_REVERT,
+        |mut caller: Caller<'_, HostState>, ptr: i32, len: i32| -> wasmtime::Result<()> {
+            let payload = read_bytes(&mut caller, ptr, len)?;
+            caller.data_mut().revert = Some(payload);
+            Err(wasmtime::Error::msg("contract reverted"))
+        },
+    )?;
+    linker.func_wrap(
+        "env",
+        host::HOST_CALLER,
+        |mut caller: Caller<'_, HostState>, out_ptr: i32| -> wasmtime::Result<i32> {
+            burn(&mut caller, host::BLOCK_INFO_GAS)?;
+            let address = caller.data().caller.clone();
+            log_host_call(&mut caller, host::HOST_CALLER, address.clone().into())?;
+            memory(&mut caller)?.write(&mut caller, out_ptr as u32 as usize, address.as_bytes())?;
+            Ok(i32::try_from(address.len())?)
+        },
+    )?;
+    linker.func_wrap(
+        "env",
+        host::HOST_CALLER_IS,
+        |mut caller: Caller<'_, HostState>, ptr: i32, len: i32| -> wasmtime::Result<i32> {
+            burn(&mut caller, host::BLOCK_INFO_GAS)?;
+            let address = read_bytes(&mut caller, ptr, len)?;
+            // Addresses are hex, so compare them case-insensitively
+            let matches = caller.data().caller.as_bytes().eq_ignore_ascii_case(&address);
+            log_host_call(&mut caller, host::HOST_CALLER_IS, matches.into())?;
+            Ok(i32::from(matches))
+        },
+    )?;
+    linker.func_wrap(
+        "env",
+        host::HOST_HASH,
+        |mut caller: Caller<'_, HostState>, algorithm: i32, ptr: i32, len: i32, out_ptr: i32| -> wasmtime::Result<i32> {
+            let algorithm = host::HashAlgorithm::from_code(algorithm)
+                .ok_or_else(|| wasmtime::Error::msg(format!("unknown hash algorithm code {}", algorithm)))?;
+            burn(&mut caller, host::hash_gas(len.max(0) as usize))?;
+            let data = read_bytes(&mut caller, ptr, len)?;
+            let digest = host::hash(algorithm, &data);
+            log_host_call(&mut caller, host::HOST_HASH, crate::nodes::encode_hex(&digest).into())?;
+            memory(&mut caller)?.write(&mut caller, out_ptr as u32 as usize, &digest)?;
+            Ok(i32::try_from(digest.len())?)
+        },
+    )?;
+    linker.func_wrap(
+        "env",
+        host::HOST_VERIFY_SIGNATURE,
+        |mut caller: Caller<'_, HostState>,
+         scheme: i32,
+         key_ptr: i32,
+         key_len: i32,
+         message_ptr: i32,
+         message_len: i32,
+         signature_ptr: i32,
+         signature_len: i32|
+         -> wasmtime::Result<i32> {
+            let scheme = host::SignatureScheme::from_code(scheme)
+                .ok_or_else(|| wasmtime::Error::msg(format!("unknown signature scheme code {}", scheme)))?;
+            burn(&mut caller, scheme.verify_gas())?;
+            let key = read_bytes(&mut caller, key_ptr, key_len)?;
+            let message = read_bytes(&mut caller, message_ptr, message_len)?;
+            let signature = read_bytes(&mut caller, signature_ptr, signature_len)?;
+            let valid = host::verify_signature(scheme, &key, &message, &signature);
+            log_host_call(&mut caller, host::HOST_VERIFY_SIGNATURE, valid.into())?;
+            Ok(i32::from(valid))
+        },
+    )?;
+    linker.func_wrap(
+        "env",
+        host::HOST_DECIMAL_MUL,
+        |mut caller: Caller<'_, HostState>, a: i64, b: i64, scale: i32, mode: i32| -> wasmtime::Result<i64> {
+            let (scale, mode) = decimal_operands(scale, mode)?;
+            let product = host::decimal_mul(a, b, scale, mode).map_err(host_error)?;
+            log_host_call(&mut caller, host::HOST_DECIMAL_MUL, product.into())?;
+            Ok(product)
+        },
+    )?;
+    linker.func_wrap(
+        "env",
+        host::HOST_DECIMAL_DIV,
+        |mut caller: Caller<'_, HostState>, a: i64, b: i64, scale: i32, mode: i32| -> wasmtime::Result<i64> {
+            let (scale, mode) = decimal_operands(scale, mode)?;
+            let quotient = host::decimal_div(a, b, scale, mode).map_err(host_error)?;
+            log_host_call(&mut caller, host::HOST_DECIMAL_DIV, quotient.into())?;
+            Ok(quotient)
+        },
+    )?;
+    linker.func_wrap(
+        "env",
+        host::HOST_CALL_CONTRACT,
+        |mut caller: Caller<'_, HostState>,
+         target_ptr: i32,
+         target_len: i32,
+         function_ptr: i32,
+         function_len: i32,
+         args_ptr: i32,
+         args_len: i32|
+         -> wasmtime::Result<i32> {
+            burn(&mut caller, host::CALL_BASE_GAS)?;
+            let target = read_string(&mut caller, target_ptr, target_len)?;
+            let function = read_string(&mut caller, function_ptr, function_len)?;
+            let args: serde_json::Value = serde_json::from_slice(&read_bytes(&mut caller, args_ptr, args_len)?)?;
+            let reason = RevertReason::new(
+                "CallUnavailable",
+                format!("contract {} is not available in local execution", target),
+            );
+            log_host_call(
+                &mut caller,
+                host::HOST_CALL_CONTRACT,
+                serde_json::json!({ "target": target, "function": function, "args": args, "error": reason.error }),
+            )?;
+            caller.data_mut().call_failure = Some(reason);
+            Ok(host::CallStatus::Reverted.code())
+        },
+    )?;
+    linker.func_wrap(
+        "env",
+        host::HOST_CALL_ERROR_IS,
+        |mut caller: Caller<'_, HostState>, ptr: i32, len: i32| -> wasmtime::Result<i32> {
+            let name = read_string(&mut caller, ptr, len)?;
+            let matches = caller.data().call_failure.as_ref().is_some_and(|reason| reason.error == name);
+            Ok(i32::from(matches))
+        },
+    )?;
+    linker.func_wrap(
+        "env",
+        host::HOST_CALL_RETHROW,
+        |mut caller: Caller<'_, HostState>| -> wasmtime::Result<()> {
+            let reason = caller
+                .data()
+                .call_failure
+                .clone()
+                .ok_or_else(|| wasmtime::Error::msg("no failed call to rethrow"))?;
+            caller.data_mut().revert = Some(reason.encode());
+            Err(wasmtime::Error::msg("contract reverted"))
+        },
+    )?;
+    for import in host::block_host_functions() {
+        linker.func_wrap("env", import, move |mut caller: Caller<'_, HostState>| -> wasmtime::Result<i64> {
+            burn(&mut caller, host::BLOCK_INFO_GAS)?;
+            let value = host::block_value(&caller.data().block, import).map_err(host_error)?;
+            log_host_call(&mut caller, import, value.into())?;
+            Ok(value)
+        })?;
+    }
+    linker.func_wrap(
+        "env",
+        host::HOST_TRACE_ENTER,
+        |mut caller: Caller<'_, HostState>, tracepoint: i32| -> wasmtime::Result<()> {
+            let gas_used = call_gas_used(&caller)?;
+            let event = TraceEvent::Enter { tracepoint: tracepoint as u32, gas_used };
+            caller.data_mut().tracepoints.push(tracepoint as u32);
+            caller.data_mut().context.trace.push(event);
+            Ok(())
+        },
+    )?;
+    linker.func_wrap(
+        "env",
+        host::HOST_TRACE_EXIT,
+        |mut caller: Caller<'_, HostState>, tracepoint: i32| -> wasmtime::Result<()> {
+            let gas_used = call_gas_used(&caller)?;
+            let event = TraceEvent::Exit { tracepoint: tracepoint as u32, gas_used };
+            caller.data_mut().tracepoints.pop();
+            caller.data_mut().context.trace.push(event);
+            Ok(())
+        },
+    )?;
+    linker.func_wrap(
+        "env",
+        host::HOST_TRACE_STORAGE_WRITE,
+        |mut caller: Caller<'_, HostState>, tracepoint: i32, ptr: i32, len: i32| -> wasmtime::Result<()> {
+            let key = read_string(&mut caller, ptr, len)?;
+            let event = TraceEvent::StorageWrite { tracepoint: tracepoint as u32, key };
+            caller.data_mut().context.trace.push(event);
+            Ok(())
+        },
+    )?;
+    Ok(())
+}
+
+/// Charge host gas against the call's fuel, trapping when it runs out
+fn burn(caller: &mut Caller<'_, HostState>, gas: Gas) -> wasmtime::Result<()> {
+    let remaining = caller.get_fuel()?;
+    if remaining < gas {
+        caller.set_fuel(0)?;
+        return Err(Trap::OutOfFuel.into());
+    }
+    caller.set_fuel(remaining - gas)
+}
+
+/// Record a host call in the context's log
+fn log_host_call(caller: &mut Caller<'_, HostState>, import: &str, result: serde_json::Value) -> wasmtime::Result<()> {
+    let gas_used = call_gas_used(caller)?;
+    caller.data_mut().context.host_calls.push(HostCall {
+        import: import.to_string(),
+        result,
+        gas_used,
+    });
+    Ok(())
+}
+
+/// Gas used so far by this call; tracing itself is free
+fn call_gas_used(caller: &Caller<'_, HostState>) -> wasmtime::Result<Gas> {
+    Ok(caller.data().gas_limit.saturating_sub(caller.get_fuel()?))
+}
+
+fn memory(caller: &mut Caller<'_, HostState>) -> wasmtime::Result<Memory> {
+    match caller.get_export(MEMORY_EXPORT) {
+        Some(Extern::Memory(memory)) => Ok(memory),
+        _ => Err(wasmtime::Error::msg("contract does not export its memory")),
+    }
+}
+
+fn read_bytes(caller: &mut Caller<'_, HostState>, ptr: i32, len: i32) -> wasmtime::Result<Vec<u8>> {
+    let memory = memory(caller)?;
+    let mut bytes = vec![0u8; usize::try_from(len)?];
+    memory.read(&*caller, ptr as u32 as usize, &mut bytes)?;
+    Ok(bytes)
+}
+
+fn read_string(caller: &mut Caller<'_, HostState>, ptr: i32, len: i32) -> wasmtime::Result<String> {
+    Ok(String::from_utf8(read_bytes(caller, ptr, len)?)?)
+}
+
+/// Convert a JSON argument to a parameter of type `ty`
+fn to_val(value: &serde_json::Value, ty: &ValType) -> Option<Val> {
+    let integer = value
+        .as_i64()
+        .or_else(|| value.as_bool().map(i64::from))
+        .or_else(|| value.as_str().and_then(|s| s.parse().ok()));
+    match ty {
+        ValType::I32 => integer.and_then(|v| i32::try_from(v).ok()).map(Val::I32),
+        ValType::I64 => integer.map(Val::I64),
+        ValType::F32 => value.as_f64().map(|v| Val::F32((v as f32).to_bits())),
+        ValType::F64 => value.as_f64().map(|v| Val::F64(v.to_bits())),
+        _ => None,
+    }
+}
+
+/// JSON of a call's return values: null, the single value, or an array
+fn result_value(results: &[Val]) -> serde_json::Value {
+    let value = |val: &Val| match val {
+        Val::I32(v) => serde_json::json!(v),
+        Val::I64(v) => serde_json::json!(v),
+        Val::F32(bits) => serde_json::json!(f32::from_bits(*bits)),
+        Val::F64(bits) => serde_json::json!(f64::from_bits(*bits)),
+        _ => serde_json::Value::Null,
+    };
+    match results {
+        [] => serde_json::Value::Null,
+        [single] => value(single),
+        many => serde_json::Value::Array(many.iter().map(value).collect()),
+    }
+}
+
+fn wasm_error(error: wasmtime::Error) -> CanvasError {
+    CanvasError::Wasm(error.to_string())
+}
+
+fn host_error(error: CanvasError) -> wasmtime::Error {
+    wasmtime::Error::msg(error.to_string())
+}
+
+/// Scale and overflow mode of a decimal host call, as the compiler passes them
+fn decimal_operands(scale: i32, mode: i32) -> wasmtime::Result<(u8, OverflowMode)> {
+    let scale = u8::try_from(scale)
+        .ok()
+        .filter(|scale| *scale <= Decimal::MAX_SCALE)
+        .ok_or_else(|| wasmtime::Error::msg(format!("invalid decimal scale {}", scale)))?;
+    let mode = overflow_mode_from_code(mode)
+        .ok_or_else(|| wasmtime::Error::msg(format!("unknown overflow mode code {}", mode)))?;
+    Ok((scale, mode))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wasm::storage::{shared, MemoryBackend};
+
+    const CONTRACT: &str = r##"(module
+        (import "env" "baals_read_storage" (func $read (param i32 i32 i32) (result i32)))
+        (import "env" "baals_write_storage" (func $write (param i32 i32 i32 i32)))
+        (import "env" "baals_emit_event" (func $emit (param i32 i32 i32 i32)))
+        (import "env" "baals_revert" (func $revert (param i32 i32)))
+        (import "env" "baals_block_number" (func $block_number (result i64)))
+        (import "env" "baals_unknown" (func $unknown))
+        (memory (export "memory") 1)
+        (data (i32.const 0) "count")
+        (data (i32.const 16) "\"set\"")
+        (data (i32.const 32) "Stored")
+        (data (i32.const 48) "{\"by\":1}")
+        (data (i32.const 64) "locked")
+        (func (export "store") (result i32)
+            (call $write (i32.const 0) (i32.const 5) (i32.const 16) (i32.const 5))
+            (call $emit (i32.const 32) (i32.const 6) (i32.const 48) (i32.const 8))
+            (call $read (i32.const 0) (i32.const 5) (i32.const 128)))
+        (func (export "peek") (result i32)
+            (call $read (i32.const 0) (i32.const 5) (i32.const 128)))
+        (func (export "add") (param i64 i64) (result i64)
+            (i64.add (local.get 0) (local.get 1)))
+        (func (export "block") (result i64) (call $block_number))
+        (func (export "locked") (call $revert (i32.const 64) (i32.const 6)))
+        (func (export "spin") (loop $forever (br $forever)))
+        (func (export "unknown") (call $unknown))
+        (func (export "alloc") (param i32) (result i32) (i32.const 256))
+        (func (export "dispatch") (param i32 i32 i32) (result i32) (local.get 2)))"##;
+
+    fn call(function: &str, args: serde_json::Value, context: &mut ExecutionContext) -> CanvasResult<SimulationResult> {
+        let args = args.as_array().cloned().unwrap_or_default();
+        let block = BlockContext::new(9, 0, 1);
+        execute(&engine().unwrap(), CONTRACT.as_bytes(), function, &args, 100_000, block, context)
+    }
+
+    #[test]
+    fn test_execute_with_host_imports_and_fuel() {
+        let mut context = ExecutionContext::new(100_000);
+        let stored = call("store", serde_json::json!([]), &mut context).unwrap();
+        assert_eq!(stored.output["result"], 5, "read back the 5 bytes of \"set\"");
+        assert_eq!(context.storage["count"], "set");
+        assert_eq!(stored.storage_bytes, 10, "key \"count\" and value \"set\" with quotes");
+        assert_eq!(stored.events[0].name, "Stored");
+        assert_eq!(stored.events[0].data["by"], 1);
+        assert!(stored.gas_used > host::STORAGE_WRITE_GAS + host::STORAGE_READ_GAS + host::event_gas(14));
+
+        let backend = shared(MemoryBackend::with_slots([("count".to_string(), serde_json::json!(7))]));
+        let mut committed = ExecutionContext::new(100_000).with_backend(backend);
+        assert_eq!(call("peek", serde_json::json!([]), &mut committed).unwrap().output["result"], 1);
+        let mut empty = ExecutionContext::new(100_000);
+        assert_eq!(call("peek", serde_json::json!([]), &mut empty).unwrap().output["result"], -1);
+
+        let sum = call("add", serde_json::json!([2, "40"]), &mut empty).unwrap();
+        assert_eq!(sum.output["result"], 42);
+        assert!(matches!(call("add", serde_json::json!([2]), &mut empty), Err(CanvasError::Validation(_))));
+        assert_eq!(call("block", serde_json::json!([]), &mut empty).unwrap().output["result"], 9);
+        assert_eq!(call("routed", serde_json::json!([1, 2]), &mut empty).unwrap().output["result"], 5);
+
+        let locked = call("locked", serde_json::json!([]), &mut empty).unwrap();
+        assert_eq!(locked.revert_reason.unwrap().message, "locked");
+        let spun = call("spin", serde_json::json!([]), &mut empty).unwrap();
+        assert_eq!(spun.revert_reason.unwrap().error, OUT_OF_GAS);
+        assert_eq!(spun.gas_used, 100_000);
+        assert_eq!(call("unknown", serde_json::json!([]), &mut empty).unwrap().revert_reason.unwrap().error, TRAP);
+        assert!(empty.storage.is_empty());
+    }
+}
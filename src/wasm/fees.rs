@@ -0,0 +1,135 @@
+//! Execution cost under chain fee models
+//!
+//! Gas reports measure work in gas; what a call actually costs depends on how
+//! the target network charges for it. Each [`NetworkProfile`] carries a
+//! [`FeeModel`], and [`estimate_costs`] prices one simulated call on every
+//! configured network so deployment targets can be compared.
+
+use serde::{Deserialize, Serialize};
+
+use super::SimulationResult;
+use crate::{
+    config::{BaalsConfig, FeeModel, NetworkProfile},
+    types::Gas,
+};
+
+/// Resources a call consumed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FeeUsage {
+    pub gas: Gas,
+    /// Bytes of storage written
+    pub storage_bytes: u64,
+}
+
+impl From<&SimulationResult> for FeeUsage {
+    fn from(result: &SimulationResult) -> Self {
+        Self {
+            gas: result.gas_used,
+            storage_bytes: result.storage_bytes,
+        }
+    }
+}
+
+/// Cost of a call on one network (in base units)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CostEstimate {
+    pub network: String,
+    /// Fee model kind
+    pub model: String,
+    /// Expected fee
+    pub fee: u64,
+    /// Highest fee the call may be charged; equal to `fee` unless prices can
+    /// move before inclusion
+    pub max_fee: u64,
+}
+
+impl FeeModel {
+    /// Expected and maximum fee of `usage`
+    pub fn fees(&self, usage: FeeUsage) -> (u64, u64) {
+        match *self {
+            FeeModel::Flat { gas_price } => {
+                let fee = usage.gas.saturating_mul(gas_price);
+                (fee, fee)
+            }
+            FeeModel::DynamicBaseFee {
+                base_fee,
+                priority_fee,
+                max_increase_percent,
+            } => {
+                let max_base_fee = base_fee.saturating_mul(max_increase_percent.saturating_add(100)).div_ceil(100);
+                (
+                    usage.gas.saturating_mul(base_fee.saturating_add(priority_fee)),
+                    usage.gas.saturating_mul(max_base_fee.saturating_add(priority_fee)),
+                )
+            }
+            FeeModel::PerByteStorage { gas_price, byte_price } => {
+                let fee = usage
+                    .gas
+                    .saturating_mul(gas_price)
+                    .saturating_add(usage.storage_bytes.saturating_mul(byte_price));
+                (fee, fee)
+            }
+        }
+    }
+}
+
+/// Price `usage` on one network
+pub fn estimate_cost(network: &NetworkProfile, usage: FeeUsage) -> CostEstimate {
+    let (fee, max_fee) = network.fee_model.fees(usage);
+    CostEstimate {
+        network: network.name.clone(),
+        model: network.fee_model.kind().to_string(),
+        fee,
+        max_fee,
+    }
+}
+
+/// Price `usage` on the target network and every network compared against it
+pub fn estimate_costs(baals: &BaalsConfig, usage: FeeUsage) -> Vec<CostEstimate> {
+    baals.profiles().map(|network| estimate_cost(network, usage)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn network(name: &str, fee_model: FeeModel) -> NetworkProfile {
+        NetworkProfile {
+            name: name.to_string(),
+            fee_model,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_costs_compared_across_fee_models() {
+        let mut baals = BaalsConfig::default();
+        baals.network = network("local", FeeModel::Flat { gas_price: 2 });
+        baals.networks = vec![
+            network(
+                "mainnet",
+                FeeModel::DynamicBaseFee {
+                    base_fee: 100,
+                    priority_fee: 5,
+                    max_increase_percent: 12,
+                },
+            ),
+            network("archive", FeeModel::PerByteStorage { gas_price: 1, byte_price: 50 }),
+        ];
+        let usage = FeeUsage { gas: 1_000, storage_bytes: 40 };
+
+        let costs = estimate_costs(&baals, usage);
+        let names: Vec<_> = costs.iter().map(|c| c.network.as_str()).collect();
+        assert_eq!(names, ["local", "mainnet", "archive"]);
+        assert_eq!((costs[0].fee, costs[0].max_fee), (2_000, 2_000));
+        assert_eq!((costs[1].fee, costs[1].max_fee), (105_000, 117_000));
+        assert_eq!(costs[1].model, "dynamic_base_fee");
+        assert_eq!(costs[2].fee, 1_000 + 40 * 50);
+
+        let model: FeeModel = serde_json::from_value(serde_json::json!({
+            "kind": "dynamic_base_fee", "base_fee": 10, "priority_fee": 1
+        }))
+        .unwrap();
+        assert_eq!(model.fees(FeeUsage { gas: 1, storage_bytes: 0 }), (11, 13));
+    }
+}
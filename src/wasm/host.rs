@@ -0,0 +1,554 @@
+//! Host functions exposed to contract WASM modules
+
+use crate::{
+    error::{CanvasError, CanvasResult},
+    types::{Decimal, ExecutionContext, Gas, OverflowMode, TraceEvent},
+};
+
+/// Host import for reading a single storage slot
+pub const HOST_READ_STORAGE: &str = "baals_read_storage";
+/// Host import for writing a single storage slot
+pub const HOST_WRITE_STORAGE: &str = "baals_write_storage";
+/// Host import for reading several storage slots in one call
+pub const HOST_BATCH_READ_STORAGE: &str = "baals_batch_read_storage";
+/// Host import for writing several storage slots in one call
+pub const HOST_BATCH_WRITE_STORAGE: &str = "baals_batch_write_storage";
+/// Host import for emitting an event
+pub const HOST_EMIT_EVENT: &str = "baals_emit_event";
+/// Host import for hashing a byte buffer
+pub const HOST_HASH: &str = "baals_hash";
+/// Host import for verifying a signature
+pub const HOST_VERIFY_SIGNATURE: &str = "baals_verify_signature";
+/// Host import that aborts execution with an encoded revert reason
+pub const HOST_REVERT: &str = "baals_revert";
+/// Host import for calling another contract; returns a [`CallStatus`] code
+pub const HOST_CALL_CONTRACT: &str = "baals_call_contract";
+/// Host import that checks whether the last failed call reverted with a given error name
+pub const HOST_CALL_ERROR_IS: &str = "baals_call_error_is";
+/// Host import that reverts with the last failed call's revert reason
+pub const HOST_CALL_RETHROW: &str = "baals_call_rethrow";
+/// Host import that writes the caller's address into guest memory and returns its length
+pub const HOST_CALLER: &str = "baals_caller";
+/// Host import that checks whether the caller's address equals the given bytes
+pub const HOST_CALLER_IS: &str = "baals_caller_is";
+/// Host import returning the value transferred with the current call
+pub const HOST_CALL_VALUE: &str = "baals_call_value";
+/// Host import returning the balance of the given address
+pub const HOST_BALANCE: &str = "baals_balance";
+/// Host import that transfers value from the contract to the given address
+pub const HOST_TRANSFER: &str = "baals_transfer";
+/// Host import returning the current block number
+pub const HOST_BLOCK_NUMBER: &str = "baals_block_number";
+/// Host import returning the current block timestamp (Unix seconds)
+pub const HOST_BLOCK_TIMESTAMP: &str = "baals_block_timestamp";
+/// Host import returning the chain ID
+pub const HOST_CHAIN_ID: &str = "baals_chain_id";
+/// Host import for fixed-point multiplication (needs a 128-bit intermediate)
+pub const HOST_DECIMAL_MUL: &str = "baals_decimal_mul";
+/// Host import for fixed-point division (needs a 128-bit intermediate)
+pub const HOST_DECIMAL_DIV: &str = "baals_decimal_div";
+/// Debug-build tracepoint at node entry
+pub const HOST_TRACE_ENTER: &str = "baals_trace_enter";
+/// Debug-build tracepoint at node exit
+pub const HOST_TRACE_EXIT: &str = "baals_trace_exit";
+/// Debug-build tracepoint after a storage write
+pub const HOST_TRACE_STORAGE_WRITE: &str = "baals_trace_storage_write";
+
+/// Gas charged for a single storage read
+pub const STORAGE_READ_GAS: Gas = 100;
+/// Gas charged for a single storage write
+pub const STORAGE_WRITE_GAS: Gas = 200;
+/// Fixed gas charged per batch host call
+pub const BATCH_BASE_GAS: Gas = 50;
+/// Per-key gas for a batched read
+pub const BATCH_READ_KEY_GAS: Gas = 60;
+/// Per-key gas for a batched write
+pub const BATCH_WRITE_KEY_GAS: Gas = 140;
+/// Maximum number of keys accepted by a single batch call
+pub const MAX_BATCH_KEYS: usize = 256;
+
+/// Fixed gas charged for a string/bytes operation
+pub const STRING_BASE_GAS: Gas = 5;
+/// Gas charged per byte processed by a string/bytes operation
+pub const STRING_BYTE_GAS: Gas = 1;
+/// Fixed gas charged for hashing
+pub const HASH_BASE_GAS: Gas = 30;
+/// Gas charged per 32-byte word hashed
+pub const HASH_WORD_GAS: Gas = 6;
+
+/// Gas charged for reading a block context value
+pub const BLOCK_INFO_GAS: Gas = 2;
+
+/// Fixed gas charged for emitting an event
+pub const EVENT_BASE_GAS: Gas = 375;
+/// Gas charged per byte of event name and data
+pub const EVENT_BYTE_GAS: Gas = 8;
+
+/// Gas cost of a string/bytes operation touching `bytes` bytes
+pub fn string_op_gas(bytes: usize) -> Gas {
+    STRING_BASE_GAS + STRING_BYTE_GAS * bytes as Gas
+}
+
+/// Gas cost of emitting an event of `bytes` bytes of name and data
+pub fn event_gas(bytes: usize) -> Gas {
+    EVENT_BASE_GAS + EVENT_BYTE_GAS * bytes as Gas
+}
+
+/// Gas cost of hashing `bytes` bytes
+pub fn hash_gas(bytes: usize) -> Gas {
+    HASH_BASE_GAS + HASH_WORD_GAS * ((bytes as Gas + 31) / 32)
+}
+
+/// Fixed gas charged for an external contract call, excluding the callee's own gas
+pub const CALL_BASE_GAS: Gas = 700;
+
+/// Gas charged for an ed25519 signature verification
+pub const ED25519_VERIFY_GAS: Gas = 2000;
+/// Gas charged for a secp256k1 signature verification
+pub const SECP256K1_VERIFY_GAS: Gas = 3000;
+
+/// Hash algorithms available through [`HOST_HASH`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HashAlgorithm {
+    Sha256,
+    Keccak256,
+    Blake3,
+}
+
+impl HashAlgorithm {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "sha256" => Some(HashAlgorithm::Sha256),
+            "keccak256" | "keccak" => Some(HashAlgorithm::Keccak256),
+            "blake3" => Some(HashAlgorithm::Blake3),
+            _ => None,
+        }
+    }
+
+    /// Code naming the algorithm in a [`HOST_HASH`] call
+    pub fn code(&self) -> i32 {
+        match self {
+            HashAlgorithm::Sha256 => 0,
+            HashAlgorithm::Keccak256 => 1,
+            HashAlgorithm::Blake3 => 2,
+        }
+    }
+
+    pub fn from_code(code: i32) -> Option<Self> {
+        match code {
+            0 => Some(HashAlgorithm::Sha256),
+            1 => Some(HashAlgorithm::Keccak256),
+            2 => Some(HashAlgorithm::Blake3),
+            _ => None,
+        }
+    }
+}
+
+/// Signature schemes available through [`HOST_VERIFY_SIGNATURE`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SignatureScheme {
+    Ed25519,
+    Secp256k1,
+}
+
+impl SignatureScheme {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "ed25519" => Some(SignatureScheme::Ed25519),
+            "secp256k1" => Some(SignatureScheme::Secp256k1),
+            _ => None,
+        }
+    }
+
+    /// Code naming the scheme in a [`HOST_VERIFY_SIGNATURE`] call
+    pub fn code(&self) -> i32 {
+        match self {
+            SignatureScheme::Ed25519 => 0,
+            SignatureScheme::Secp256k1 => 1,
+        }
+    }
+
+    pub fn from_code(code: i32) -> Option<Self> {
+        match code {
+            0 => Some(SignatureScheme::Ed25519),
+            1 => Some(SignatureScheme::Secp256k1),
+            _ => None,
+        }
+    }
+
+    /// Gas charged for one verification
+    pub fn verify_gas(&self) -> Gas {
+        match self {
+            SignatureScheme::Ed25519 => ED25519_VERIFY_GAS,
+            SignatureScheme::Secp256k1 => SECP256K1_VERIFY_GAS,
+        }
+    }
+}
+
+/// Length of the digest every [`HashAlgorithm`] produces
+pub const HASH_DIGEST_BYTES: usize = 32;
+
+/// Hash `data` with the given algorithm
+pub fn hash(algorithm: HashAlgorithm, data: &[u8]) -> Vec<u8> {
+    match algorithm {
+        HashAlgorithm::Sha256 => {
+            use sha2::{Digest, Sha256};
+            Sha256::digest(data).to_vec()
+        }
+        HashAlgorithm::Keccak256 => {
+            use sha3::{Digest, Keccak256};
+            Keccak256::digest(data).to_vec()
+        }
+        HashAlgorithm::Blake3 => blake3::hash(data).as_bytes().to_vec(),
+    }
+}
+
+/// Verify a signature over `message`.
+///
+/// Malformed keys or signatures verify as `false` rather than erroring, so a contract
+/// can branch on the result. secp256k1 signatures are ECDSA over the SHA-256 digest of
+/// the message, in 64-byte compact form.
+pub fn verify_signature(
+    scheme: SignatureScheme,
+    public_key: &[u8],
+    message: &[u8],
+    signature: &[u8],
+) -> bool {
+    match scheme {
+        SignatureScheme::Ed25519 => {
+            use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+            let Ok(key_bytes) = <[u8; 32]>::try_from(public_key) else {
+                return false;
+            };
+            let Ok(key) = VerifyingKey::from_bytes(&key_bytes) else {
+                return false;
+            };
+            let Ok(signature) = Signature::from_slice(signature) else {
+                return false;
+            };
+            key.verify(message, &signature).is_ok()
+        }
+        SignatureScheme::Secp256k1 => {
+            use k256::ecdsa::{signature::Verifier, Signature, VerifyingKey};
+            let Ok(key) = VerifyingKey::from_sec1_bytes(public_key) else {
+                return false;
+            };
+            let Ok(signature) = Signature::from_slice(signature) else {
+                return false;
+            };
+            key.verify(message, &signature).is_ok()
+        }
+    }
+}
+
+/// Narrow a 128-bit intermediate back to i64 under an overflow mode
+fn narrow(value: i128, mode: OverflowMode) -> CanvasResult<i64> {
+    match mode {
+        OverflowMode::Checked => i64::try_from(value)
+            .map_err(|_| CanvasError::ExecutionError("Decimal overflow".to_string())),
+        OverflowMode::Saturating => Ok(value.clamp(i64::MIN as i128, i64::MAX as i128) as i64),
+        OverflowMode::Wrapping => Ok(value as i64),
+    }
+}
+
+/// Multiply two decimals of the same scale, truncating toward zero
+pub fn decimal_mul(a: i64, b: i64, scale: u8, mode: OverflowMode) -> CanvasResult<i64> {
+    let product = a as i128 * b as i128 / Decimal::scale_factor(scale) as i128;
+    narrow(product, mode)
+}
+
+/// Divide two decimals of the same scale, truncating toward zero
+pub fn decimal_div(a: i64, b: i64, scale: u8, mode: OverflowMode) -> CanvasResult<i64> {
+    if b == 0 {
+        return Err(CanvasError::ExecutionError("Decimal division by zero".to_string()));
+    }
+    let quotient = a as i128 * Decimal::scale_factor(scale) as i128 / b as i128;
+    narrow(quotient, mode)
+}
+
+/// Handle a `baals_revert(ptr, len)` call with the payload read from guest memory.
+///
+/// Payloads that are not encoded revert reasons are kept as the message of an
+/// `Unknown` revert so nothing is lost.
+pub fn revert(payload: &[u8]) -> CanvasError {
+    let reason = crate::types::RevertReason::decode(payload).unwrap_or_else(|| {
+        crate::types::RevertReason::new("Unknown", String::from_utf8_lossy(payload).to_string())
+    });
+    CanvasError::Reverted(reason)
+}
+
+/// Status code returned by [`HOST_CALL_CONTRACT`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CallStatus {
+    Success,
+    Reverted,
+    OutOfGas,
+}
+
+impl CallStatus {
+    pub fn code(&self) -> i32 {
+        match self {
+            CallStatus::Success => 0,
+            CallStatus::Reverted => 1,
+            CallStatus::OutOfGas => 2,
+        }
+    }
+
+    pub fn from_code(code: i32) -> Option<Self> {
+        match code {
+            0 => Some(CallStatus::Success),
+            1 => Some(CallStatus::Reverted),
+            2 => Some(CallStatus::OutOfGas),
+            _ => None,
+        }
+    }
+}
+
+/// Result of an external call made through [`HOST_CALL_CONTRACT`]
+#[derive(Debug, Clone, PartialEq)]
+pub enum CallOutcome {
+    /// The callee returned normally
+    Success(serde_json::Value),
+    /// The callee failed; its state changes were rolled back
+    Failed {
+        status: CallStatus,
+        reason: crate::types::RevertReason,
+    },
+}
+
+impl CallOutcome {
+    /// Outcome for a callee that reverted with `reason`
+    pub fn reverted(reason: crate::types::RevertReason) -> Self {
+        CallOutcome::Failed {
+            status: CallStatus::Reverted,
+            reason,
+        }
+    }
+
+    /// Build an outcome from a call status and the callee's return data.
+    ///
+    /// On failure the return data is the callee's revert payload.
+    pub fn from_status(code: i32, return_data: &[u8]) -> CanvasResult<Self> {
+        let status = CallStatus::from_code(code)
+            .ok_or_else(|| CanvasError::Wasm(format!("Unknown call status code: {}", code)))?;
+        Ok(match status {
+            CallStatus::Success => CallOutcome::Success(
+                serde_json::from_slice(return_data).unwrap_or(serde_json::Value::Null),
+            ),
+            CallStatus::OutOfGas => CallOutcome::Failed {
+                status,
+                reason: crate::types::RevertReason::new("OutOfGas", "callee ran out of gas"),
+            },
+            CallStatus::Reverted => {
+                let reason = match revert(return_data) {
+                    CanvasError::Reverted(reason) => reason,
+                    other => crate::types::RevertReason::new("Unknown", other.to_string()),
+                };
+                CallOutcome::Failed { status, reason }
+            }
+        })
+    }
+
+    pub fn status(&self) -> CallStatus {
+        match self {
+            CallOutcome::Success(_) => CallStatus::Success,
+            CallOutcome::Failed { status, .. } => *status,
+        }
+    }
+}
+
+/// All storage host imports known to the runtime
+pub fn storage_host_functions() -> Vec<&'static str> {
+    vec![
+        HOST_READ_STORAGE,
+        HOST_WRITE_STORAGE,
+        HOST_BATCH_READ_STORAGE,
+        HOST_BATCH_WRITE_STORAGE,
+    ]
+}
+
+/// Tracing host imports; none of them may appear in a release build
+pub fn trace_host_functions() -> Vec<&'static str> {
+    vec![HOST_TRACE_ENTER, HOST_TRACE_EXIT, HOST_TRACE_STORAGE_WRITE]
+}
+
+/// Block context host imports
+pub fn block_host_functions() -> Vec<&'static str> {
+    vec![HOST_BLOCK_NUMBER, HOST_BLOCK_TIMESTAMP, HOST_CHAIN_ID]
+}
+
+/// Handle a block context import, returning the value the guest receives
+pub fn block_info(context: &mut ExecutionContext, block: &super::BlockContext, import: &str) -> CanvasResult<i64> {
+    let value = block_value(block, import)?;
+    context.use_gas(BLOCK_INFO_GAS).map_err(CanvasError::ExecutionError)?;
+    Ok(value)
+}
+
+/// Value of a block context import, without charging gas
+pub fn block_value(block: &super::BlockContext, import: &str) -> CanvasResult<i64> {
+    let value = match import {
+        HOST_BLOCK_NUMBER => block.number,
+        HOST_BLOCK_TIMESTAMP => block.timestamp,
+        HOST_CHAIN_ID => block.chain_id,
+        other => return Err(CanvasError::Wasm(format!("Not a block import: {}", other))),
+    };
+    Ok(value as i64)
+}
+
+/// Record a tracepoint hit. Tracing is free so debug and release builds
+/// report the same gas.
+pub fn trace(context: &mut ExecutionContext, import: &str, tracepoint: u32, key: Option<&[u8]>) -> CanvasResult<()> {
+    let gas_used = context.gas_used;
+    let event = match import {
+        HOST_TRACE_ENTER => TraceEvent::Enter { tracepoint, gas_used },
+        HOST_TRACE_EXIT => TraceEvent::Exit { tracepoint, gas_used },
+        HOST_TRACE_STORAGE_WRITE => TraceEvent::StorageWrite {
+            tracepoint,
+            key: String::from_utf8_lossy(key.unwrap_or_default()).to_string(),
+        },
+        other => return Err(CanvasError::Wasm(format!("Not a trace import: {}", other))),
+    };
+    context.trace.push(event);
+    Ok(())
+}
+
+/// Gas cost of a batched read of `keys` slots
+pub fn batch_read_gas(keys: usize) -> Gas {
+    BATCH_BASE_GAS + BATCH_READ_KEY_GAS * keys as Gas
+}
+
+/// Gas cost of a batched write of `keys` slots
+pub fn batch_write_gas(keys: usize) -> Gas {
+    BATCH_BASE_GAS + BATCH_WRITE_KEY_GAS * keys as Gas
+}
+
+/// Reject a batch storage call with no keys or more than [`MAX_BATCH_KEYS`]
+pub fn check_batch_size(keys: usize) -> CanvasResult<()> {
+    if keys == 0 {
+        return Err(CanvasError::Wasm("Batch storage call requires at least one key".to_string()));
+    }
+    if keys > MAX_BATCH_KEYS {
+        return Err(CanvasError::Wasm(format!(
+            "Batch storage call has {} keys, maximum is {}",
+            keys, MAX_BATCH_KEYS
+        )));
+    }
+    Ok(())
+}
+
+fn charge(context: &mut ExecutionContext, amount: Gas) -> CanvasResult<()> {
+    context
+        .use_gas(amount)
+        .map_err(|_| CanvasError::GasLimitExceeded(context.gas_limit))
+}
+
+/// Read several storage slots in one host call.
+///
+/// Missing keys yield `null`, matching the single-slot read.
+pub fn batch_read_storage(
+    context: &mut ExecutionContext,
+    keys: &[String],
+) -> CanvasResult<Vec<serde_json::Value>> {
+    check_batch_size(keys.len())?;
+    charge(context, batch_read_gas(keys.len()))?;
+
+    keys.iter().map(|key| context.read_slot(key)).collect()
+}
+
+/// Write several storage slots in one host call.
+///
+/// Gas is charged before any slot is touched, so a failed batch leaves storage unchanged.
+/// Entries are applied in order; a repeated key keeps the last value.
+pub fn batch_write_storage(
+    context: &mut ExecutionContext,
+    entries: &[(String, serde_json::Value)],
+) -> CanvasResult<()> {
+    check_batch_size(entries.len())?;
+    charge(context, batch_write_gas(entries.len()))?;
+
+    for (key, value) in entries {
+        context.storage.insert(key.clone(), value.clone());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_batch_gas_is_cheaper_than_individual_calls() {
+        assert!(batch_read_gas(4) < STORAGE_READ_GAS * 4);
+        assert!(batch_write_gas(4) < STORAGE_WRITE_GAS * 4);
+    }
+
+    #[test]
+    fn test_call_outcome_from_status() {
+        let reason = crate::types::RevertReason::new("InsufficientBalance", "balance too low");
+        let outcome = CallOutcome::from_status(1, &reason.encode()).unwrap();
+        assert_eq!(outcome, CallOutcome::reverted(reason));
+
+        let outcome = CallOutcome::from_status(0, b"42").unwrap();
+        assert_eq!(outcome, CallOutcome::Success(serde_json::json!(42)));
+        assert!(CallOutcome::from_status(7, &[]).is_err());
+    }
+
+    #[test]
+    fn test_batch_write_then_read() {
+        let mut context = ExecutionContext::new(10_000);
+        let entries = vec![
+            ("a".to_string(), serde_json::json!(1)),
+            ("b".to_string(), serde_json::json!("two")),
+        ];
+        assert!(batch_write_storage(&mut context, &entries).is_ok());
+
+        let values = batch_read_storage(&mut context, &["a".to_string(), "missing".to_string()]).unwrap();
+        assert_eq!(values, vec![serde_json::json!(1), serde_json::Value::Null]);
+        assert_eq!(context.gas_used, batch_write_gas(2) + batch_read_gas(2));
+    }
+
+    #[test]
+    fn test_batch_write_out_of_gas_leaves_storage_untouched() {
+        let mut context = ExecutionContext::new(100);
+        let entries = vec![("a".to_string(), serde_json::json!(1))];
+        assert!(batch_write_storage(&mut context, &entries).is_err());
+        assert!(context.storage.is_empty());
+    }
+
+    #[test]
+    fn test_hash_algorithms() {
+        assert_eq!(hash(HashAlgorithm::Sha256, b"").len(), 32);
+        assert_eq!(hash(HashAlgorithm::Keccak256, b"").len(), 32);
+        assert_eq!(hash(HashAlgorithm::Blake3, b"").len(), 32);
+        assert_ne!(hash(HashAlgorithm::Sha256, b"abc"), hash(HashAlgorithm::Keccak256, b"abc"));
+    }
+
+    #[test]
+    fn test_ed25519_verification() {
+        use ed25519_dalek::{Signer, SigningKey};
+        let signing_key = SigningKey::from_bytes(&[7u8; 32]);
+        let signature = signing_key.sign(b"message");
+        let public_key = signing_key.verifying_key().to_bytes();
+
+        assert!(verify_signature(SignatureScheme::Ed25519, &public_key, b"message", &signature.to_bytes()));
+        assert!(!verify_signature(SignatureScheme::Ed25519, &public_key, b"tampered", &signature.to_bytes()));
+        assert!(!verify_signature(SignatureScheme::Ed25519, &[0u8; 3], b"message", &signature.to_bytes()));
+    }
+
+    #[test]
+    fn test_decimal_mul_div() {
+        // 1.50 * 2.25 = 3.37 (truncated from 3.375)
+        assert_eq!(decimal_mul(150, 225, 2, OverflowMode::Checked).unwrap(), 337);
+        // 1.00 / 3.00 = 0.33
+        assert_eq!(decimal_div(100, 300, 2, OverflowMode::Checked).unwrap(), 33);
+        assert!(decimal_div(100, 0, 2, OverflowMode::Wrapping).is_err());
+        assert!(decimal_mul(i64::MAX, 200, 2, OverflowMode::Checked).is_err());
+        assert_eq!(decimal_mul(i64::MAX, 200, 2, OverflowMode::Saturating).unwrap(), i64::MAX);
+    }
+
+    #[test]
+    fn test_empty_batch_rejected() {
+        let mut context = ExecutionContext::new(1000);
+        assert!(batch_read_storage(&mut context, &[]).is_err());
+    }
+}
@@ -1,14 +1,32 @@
 //! WebAssembly runtime integration
 
+pub mod accounts;
+pub mod block;
+pub mod engine;
+pub mod fees;
+pub mod host;
+pub mod progress;
+pub mod service;
+pub mod storage;
+
 use crate::{
     config::Config,
     error::{CanvasError, CanvasResult},
-    types::{Gas, Event},
+    types::{Gas, Event, ExecutionContext},
+};
+
+pub use accounts::{SandboxAccounts, SimulationRequest};
+pub use block::{BlockAdvance, BlockContext};
+pub use fees::{estimate_cost, estimate_costs, CostEstimate, FeeUsage};
+pub use service::{ContractService, ServiceResponse};
+pub use storage::{
+    shared, BaalsBackend, MemoryBackend, SharedStorage, SledBackend, StorageBackend, StorageBackendKind,
 };
 
 /// WASM runtime for executing compiled contracts
 pub struct WasmRuntime {
     config: Config,
+    engine: wasmtime::Engine,
 }
 
 /// Simulation result
@@ -16,8 +34,38 @@ pub struct WasmRuntime {
 pub struct SimulationResult {
     pub output: serde_json::Value,
     pub gas_used: Gas,
+    /// Bytes of storage written (keys and values), for per-byte fee models
+    pub storage_bytes: u64,
     pub events: Vec<Event>,
     pub execution_time: std::time::Duration,
+    /// Decoded revert reason, if execution reverted
+    pub revert_reason: Option<crate::types::RevertReason>,
+}
+
+impl SimulationResult {
+    /// Whether execution reverted
+    pub fn reverted(&self) -> bool {
+        self.revert_reason.is_some()
+    }
+
+    /// Build the result for a reverted execution from the trap payload
+    pub fn from_revert(payload: &[u8], gas_used: Gas, execution_time: std::time::Duration) -> Self {
+        let reason = match host::revert(payload) {
+            CanvasError::Reverted(reason) => reason,
+            other => crate::types::RevertReason::new("Unknown", other.to_string()),
+        };
+        Self {
+            output: serde_json::json!({
+                "success": false,
+                "revert": reason,
+            }),
+            gas_used,
+            storage_bytes: 0,
+            events: Vec::new(),
+            execution_time,
+            revert_reason: Some(reason),
+        }
+    }
 }
 
 impl WasmRuntime {
@@ -25,54 +73,45 @@ impl WasmRuntime {
     pub fn new(config: &Config) -> CanvasResult<Self> {
         Ok(Self {
             config: config.clone(),
+            engine: engine::engine()?,
         })
     }
 
-    /// Simulate contract execution
+    /// Simulate contract execution by calling the default entry point.
+    ///
+    /// An array of input data is passed as the arguments, anything else but
+    /// null as the only argument.
     pub fn simulate(
         &self,
         wasm_bytes: &[u8],
         input_data: serde_json::Value,
         gas_limit: Gas,
     ) -> CanvasResult<SimulationResult> {
-        // TODO: Implement actual WASM execution using wasmtime
-        // For now, return a mock simulation result
-        
         log::info!("Simulating contract execution with {} bytes", wasm_bytes.len());
-        
-        // Mock execution
-        let start_time = std::time::Instant::now();
-        
-        // Simulate some processing time
-        std::thread::sleep(std::time::Duration::from_millis(10));
-        
-        let execution_time = start_time.elapsed();
-        
-        // Mock gas usage (10% of limit)
-        let gas_used = gas_limit / 10;
-        
-        // Mock output
-        let output = serde_json::json!({
-            "success": true,
-            "result": "mock_execution_result",
-            "input_processed": input_data
-        });
-        
-        // Mock events
-        let events = vec![
-            Event {
-                name: "ContractExecuted".to_string(),
-                data: std::collections::HashMap::new(),
-                indexed_data: Vec::new(),
-            }
-        ];
-        
-        Ok(SimulationResult {
-            output,
-            gas_used,
-            events,
-            execution_time,
-        })
+        let arguments = match input_data {
+            serde_json::Value::Array(items) => items,
+            serde_json::Value::Null => Vec::new(),
+            other => vec![other],
+        };
+        self.execute_function(wasm_bytes, crate::compiler::DEFAULT_ENTRY_POINT, arguments, gas_limit)
+    }
+
+    /// Run a request with progress reports, stopping early when `cancel` is
+    /// triggered
+    pub fn simulate_with_progress(
+        &self,
+        wasm_bytes: &[u8],
+        request: &SimulationRequest,
+        options: &progress::SimulationOptions,
+        cancel: &progress::CancellationToken,
+        on_progress: impl FnMut(progress::SimulationProgress) + Send + Sync + 'static,
+    ) -> CanvasResult<progress::SimulationRun> {
+        log::info!("Simulating '{}' with a gas limit of {}", request.function, request.gas_limit);
+        let run = progress::simulate_interruptible(wasm_bytes, request, options, cancel, on_progress)?;
+        if run.cancelled {
+            log::info!("Simulation cancelled after {} gas", run.gas_used);
+        }
+        Ok(run)
     }
 
     /// Execute a contract function
@@ -82,43 +121,93 @@ impl WasmRuntime {
         function_name: &str,
         arguments: Vec<serde_json::Value>,
         gas_limit: Gas,
+    ) -> CanvasResult<SimulationResult> {
+        self.execute_function_as(wasm_bytes, function_name, arguments, gas_limit, None)
+    }
+
+    /// Execute a contract function on behalf of `caller` (0x-hex address),
+    /// or of the runtime's default account when `None`
+    pub fn execute_function_as(
+        &self,
+        wasm_bytes: &[u8],
+        function_name: &str,
+        arguments: Vec<serde_json::Value>,
+        gas_limit: Gas,
+        caller: Option<&str>,
     ) -> CanvasResult<SimulationResult> {
         log::info!("Executing function '{}' with {} arguments", function_name, arguments.len());
-        
-        // TODO: Implement actual WASM function execution
-        // For now, return a mock result
-        
-        let start_time = std::time::Instant::now();
-        std::thread::sleep(std::time::Duration::from_millis(5));
-        let execution_time = start_time.elapsed();
-        
-        let gas_used = gas_limit / 20;
-        
-        let output = serde_json::json!({
-            "function": function_name,
-            "arguments": arguments,
-            "result": "mock_function_result"
-        });
-        
-        let events = vec![
-            Event {
-                name: format!("{}Executed", function_name),
-                data: std::collections::HashMap::new(),
-                indexed_data: Vec::new(),
+        let mut request = SimulationRequest::new(function_name, arguments, gas_limit);
+        if let Some(caller) = caller {
+            request.set_caller(caller);
+        }
+        let mut context = ExecutionContext::new(gas_limit);
+        self.execute_request_in(wasm_bytes, &request, &mut SandboxAccounts::new(), &mut context)
+    }
+
+    /// Execute a request against sandbox accounts.
+    ///
+    /// The request's value moves from the caller to the contract before the
+    /// call and back again if the call reverts or fails to run. A caller that cannot cover the
+    /// value gets an `InsufficientBalance` revert without the call running.
+    pub fn execute_request(
+        &self,
+        wasm_bytes: &[u8],
+        request: &SimulationRequest,
+        accounts: &mut SandboxAccounts,
+    ) -> CanvasResult<SimulationResult> {
+        let mut context = ExecutionContext::new(request.gas_limit);
+        self.execute_request_in(wasm_bytes, request, accounts, &mut context)
+    }
+
+    /// Execute a request against sandbox accounts and existing contract state.
+    ///
+    /// The storage host imports write `context.storage` and read through to the
+    /// context's backend; gas used and events are added to the context. A
+    /// reverted call leaves storage as it was. Writes reach the backend only
+    /// when the caller commits them with `ExecutionContext::commit_storage`.
+    pub fn execute_request_in(
+        &self,
+        wasm_bytes: &[u8],
+        request: &SimulationRequest,
+        accounts: &mut SandboxAccounts,
+        context: &mut ExecutionContext,
+    ) -> CanvasResult<SimulationResult> {
+        let caller = request.caller();
+        let contract = accounts.contract.clone();
+        match accounts.transfer(caller, &contract, request.value) {
+            Err(CanvasError::Reverted(reason)) => {
+                return Ok(SimulationResult::from_revert(&reason.encode(), 0, std::time::Duration::ZERO));
             }
-        ];
-        
-        Ok(SimulationResult {
-            output,
-            gas_used,
-            events,
-            execution_time,
-        })
+            other => other?,
+        }
+        let savepoint = context.savepoint(request.function.clone());
+        let mut result = match engine::execute_request(&self.engine, wasm_bytes, request, context) {
+            Ok(result) => result,
+            Err(e) => {
+                // The call never completed, so undo it like a revert
+                context.rollback_to(savepoint).map_err(CanvasError::InvalidState)?;
+                accounts.transfer(&contract, caller, request.value)?;
+                return Err(e);
+            }
+        };
+        if result.reverted() {
+            context.rollback_to(savepoint).map_err(CanvasError::InvalidState)?;
+            accounts.transfer(&contract, caller, request.value)?;
+        } else {
+            context.release(savepoint).map_err(CanvasError::InvalidState)?;
+            context.events.extend(result.events.iter().cloned());
+            if let Some(output) = result.output.as_object_mut() {
+                output.insert("caller".to_string(), serde_json::json!(caller));
+                output.insert("value".to_string(), serde_json::json!(request.value.to_string()));
+                output.insert("block".to_string(), serde_json::json!(request.block));
+            }
+        }
+        context.gas_used = context.gas_used.saturating_add(result.gas_used);
+        Ok(result)
     }
 
     /// Validate WASM module
     pub fn validate_module(&self, wasm_bytes: &[u8]) -> CanvasResult<()> {
-        // TODO: Implement WASM validation using wasmtime
         log::info!("Validating WASM module with {} bytes", wasm_bytes.len());
         
         // Basic validation checks
@@ -135,34 +224,25 @@ impl WasmRuntime {
         if &wasm_bytes[4..8] != b"\x01\x00\x00\x00" {
             return Err(CanvasError::Wasm("Invalid WASM module: unsupported version".to_string()));
         }
-        
-        Ok(())
+
+        wasmtime::Module::validate(&self.engine, wasm_bytes)
+            .map_err(|e| CanvasError::Wasm(format!("Invalid WASM module: {}", e)))
     }
 
     /// Get module exports
     pub fn get_exports(&self, wasm_bytes: &[u8]) -> CanvasResult<Vec<String>> {
-        // TODO: Implement export extraction using wasmtime
-        log::info!("Extracting exports from WASM module");
-        
-        // Mock exports
-        Ok(vec![
-            "main".to_string(),
-            "init".to_string(),
-            "execute".to_string(),
-        ])
+        let module = self.module(wasm_bytes)?;
+        Ok(module.exports().map(|export| export.name().to_string()).collect())
     }
 
     /// Get module imports
     pub fn get_imports(&self, wasm_bytes: &[u8]) -> CanvasResult<Vec<String>> {
-        // TODO: Implement import extraction using wasmtime
-        log::info!("Extracting imports from WASM module");
-        
-        // Mock imports
-        Ok(vec![
-            "baals_read_storage".to_string(),
-            "baals_write_storage".to_string(),
-            "baals_emit_event".to_string(),
-        ])
+        let module = self.module(wasm_bytes)?;
+        Ok(module.imports().map(|import| import.name().to_string()).collect())
+    }
+
+    fn module(&self, wasm_bytes: &[u8]) -> CanvasResult<wasmtime::Module> {
+        wasmtime::Module::new(&self.engine, wasm_bytes).map_err(|e| CanvasError::Wasm(e.to_string()))
     }
 }
 
@@ -247,6 +327,15 @@ pub struct PerformanceAnalysis {
 mod tests {
     use super::*;
 
+    const CONTRACT: &str = r#"(module
+        (import "env" "baals_emit_event" (func $emit (param i32 i32 i32 i32)))
+        (memory (export "memory") 1)
+        (data (i32.const 0) "Doubled")
+        (func (export "main") (param i32) (result i32)
+            (call $emit (i32.const 0) (i32.const 7) (i32.const 0) (i32.const 0))
+            (i32.mul (local.get 0) (i32.const 2)))
+        (func (export "deposit")))"#;
+
     #[test]
     fn test_wasm_runtime_creation() {
         let config = Config::default();
@@ -273,14 +362,69 @@ mod tests {
         let config = Config::default();
         let runtime = WasmRuntime::new(&config).unwrap();
         
-        let wasm_bytes = b"\x00asm\x01\x00\x00\x00";
-        let input = serde_json::json!({"test": "data"});
+        let input = serde_json::json!([21]);
         
-        let result = runtime.simulate(wasm_bytes, input, 1000);
+        let result = runtime.simulate(CONTRACT.as_bytes(), input, 1000);
         assert!(result.is_ok());
         
         let result = result.unwrap();
         assert!(result.gas_used > 0);
-        assert!(!result.events.is_empty());
+        assert_eq!(result.output["result"], 42);
+        assert_eq!(result.events[0].name, "Doubled");
+        assert!(!result.reverted());
+
+        let starved = runtime.simulate(CONTRACT.as_bytes(), serde_json::json!(21), 10).unwrap();
+        assert_eq!(starved.revert_reason.unwrap().error, engine::OUT_OF_GAS);
+        assert!(runtime.get_exports(CONTRACT.as_bytes()).unwrap().contains(&"deposit".to_string()));
+        assert_eq!(runtime.get_imports(CONTRACT.as_bytes()).unwrap(), [host::HOST_EMIT_EVENT]);
+    }
+
+    #[test]
+    fn test_revert_payload_decoding() {
+        let reason = crate::types::RevertReason::new("Paused", "contract is paused");
+        let result = SimulationResult::from_revert(&reason.encode(), 10, std::time::Duration::ZERO);
+        assert!(result.reverted());
+        assert_eq!(result.revert_reason, Some(reason));
+
+        let raw = SimulationResult::from_revert(b"boom", 10, std::time::Duration::ZERO);
+        assert_eq!(raw.revert_reason.unwrap().message, "boom");
+    }
+
+    #[test]
+    fn test_request_value_moves_between_accounts() {
+        let runtime = WasmRuntime::new(&Config::default()).unwrap();
+        let wasm_bytes = CONTRACT.as_bytes();
+        let alice = "0x00000000000000000000000000000000000000aa";
+        let mut accounts = SandboxAccounts::new().with_balances([(alice.to_string(), 100)]);
+
+        let mut deposit = SimulationRequest::new("deposit", Vec::new(), 1000);
+        deposit.set_caller(alice).set_value(60);
+        assert!(!runtime.execute_request(wasm_bytes, &deposit, &mut accounts).unwrap().reverted());
+        assert_eq!(accounts.balance(alice), 40);
+        assert_eq!(accounts.contract_balance(), 60);
+
+        let result = runtime.execute_request(wasm_bytes, &deposit, &mut accounts).unwrap();
+        assert_eq!(result.revert_reason.unwrap().error, accounts::INSUFFICIENT_BALANCE);
+        assert_eq!(accounts.balance(alice), 40);
+
+        accounts.transfer(accounts::DEFAULT_CONTRACT_ADDRESS, alice, 60).unwrap();
+        assert_eq!(accounts.balance(alice), 100);
+        assert!(accounts.transfer(alice, "0xbb", 101).is_err());
+    }
+
+    #[test]
+    fn test_failed_call_refunds_value_and_keeps_state() {
+        let runtime = WasmRuntime::new(&Config::default()).unwrap();
+        let alice = "0x00000000000000000000000000000000000000aa";
+        let mut accounts = SandboxAccounts::new().with_balances([(alice.to_string(), 100)]);
+        let mut context = ExecutionContext::new(1000);
+
+        let mut missing = SimulationRequest::new("withdraw", Vec::new(), 1000);
+        missing.set_caller(alice).set_value(60);
+        let result = runtime.execute_request_in(CONTRACT.as_bytes(), &missing, &mut accounts, &mut context);
+        assert!(matches!(result, Err(CanvasError::NotFound(_))));
+        assert_eq!(accounts.balance(alice), 100);
+        assert_eq!(accounts.contract_balance(), 0);
+        assert!(context.savepoints().is_empty());
     }
 } 
\ No newline at end of file
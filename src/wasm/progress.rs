@@ -0,0 +1,267 @@
+//! Interruptible simulation with progress reporting
+//!
+//! Long simulations run on the [engine](super::engine) like any other call,
+//! with the same host imports, but on an engine with epoch interruption. A
+//! ticker thread advances the engine epoch at a fixed interval; at every tick
+//! the running code yields to a callback that reports progress and aborts the
+//! run if it has been cancelled. A cancelled or failed run still returns
+//! everything gathered so far: gas used and the tracepoints hit by an
+//! instrumented build.
+
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+
+use super::{engine, SimulationRequest};
+use crate::{
+    compiler::TraceMap,
+    error::CanvasResult,
+    types::{ExecutionContext, Gas, NodeId, TraceEvent},
+};
+
+/// Default time between progress reports
+pub const DEFAULT_PROGRESS_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Cooperative cancellation flag shared between a simulation and its controller
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Ask the simulation to stop at its next progress check
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+/// Progress of a running simulation
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SimulationProgress {
+    /// Estimated completion, 0-100
+    pub percent: f64,
+    /// Node currently executing; only known for instrumented builds
+    pub current_node: Option<NodeId>,
+    pub gas_used: Gas,
+}
+
+/// Outcome of an interruptible simulation, complete or partial
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SimulationRun {
+    /// Return values; empty unless the run completed
+    pub results: Vec<serde_json::Value>,
+    pub gas_used: Gas,
+    /// Tracepoints hit before the run ended
+    pub trace: Vec<TraceEvent>,
+    pub cancelled: bool,
+    pub error: Option<String>,
+    pub duration: Duration,
+}
+
+impl SimulationRun {
+    pub fn completed(&self) -> bool {
+        !self.cancelled && self.error.is_none()
+    }
+}
+
+/// Options of an interruptible simulation
+#[derive(Debug, Clone)]
+pub struct SimulationOptions {
+    /// Expected gas use, e.g. from static estimation; progress is measured
+    /// against it, or against the request's gas limit when unknown
+    pub expected_gas: Option<Gas>,
+    /// Tracepoint mapping of an instrumented build
+    pub trace_map: Option<TraceMap>,
+    pub progress_interval: Duration,
+}
+
+impl Default for SimulationOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SimulationOptions {
+    pub fn new() -> Self {
+        Self {
+            expected_gas: None,
+            trace_map: None,
+            progress_interval: DEFAULT_PROGRESS_INTERVAL,
+        }
+    }
+
+    pub fn with_expected_gas(mut self, expected_gas: Gas) -> Self {
+        self.expected_gas = Some(expected_gas);
+        self
+    }
+
+    pub fn with_trace_map(mut self, trace_map: TraceMap) -> Self {
+        self.trace_map = Some(trace_map);
+        self
+    }
+
+    pub fn with_progress_interval(mut self, interval: Duration) -> Self {
+        self.progress_interval = interval;
+        self
+    }
+}
+
+/// Run a request with progress reports and cooperative cancellation.
+///
+/// `Err` means the call could not be made at all, as with
+/// [`engine::execute_request`]; a revert ends the run with an error.
+pub fn simulate_interruptible(
+    wasm_bytes: &[u8],
+    request: &SimulationRequest,
+    options: &SimulationOptions,
+    cancel: &CancellationToken,
+    mut on_progress: impl FnMut(SimulationProgress) + Send + Sync + 'static,
+) -> CanvasResult<SimulationRun> {
+    let engine = engine::interruptible_engine()?;
+    let expected = options.expected_gas.unwrap_or(request.gas_limit).max(1);
+    let trace_map = options.trace_map.clone();
+    let token = cancel.clone();
+    let check = move |gas_used: Gas, tracepoints: &[u32]| {
+        let current_node = tracepoints
+            .last()
+            .and_then(|tp| trace_map.as_ref().and_then(|map| map.node(*tp)));
+        on_progress(SimulationProgress {
+            // Never report completion before the run actually returns
+            percent: (gas_used as f64 * 100.0 / expected as f64).min(99.0),
+            current_node,
+            gas_used,
+        });
+        !token.is_cancelled()
+    };
+
+    let done = Arc::new(AtomicBool::new(false));
+    let ticker = {
+        let (engine, done, interval) = (engine.clone(), done.clone(), options.progress_interval);
+        std::thread::spawn(move || {
+            while !done.load(Ordering::SeqCst) {
+                std::thread::sleep(interval);
+                engine.increment_epoch();
+            }
+        })
+    };
+
+    let started = Instant::now();
+    let mut context = ExecutionContext::new(request.gas_limit);
+    let outcome = engine::execute_interruptible(&engine, wasm_bytes, request, &mut context, Box::new(check));
+    let duration = started.elapsed();
+    done.store(true, Ordering::SeqCst);
+    ticker.join().ok();
+
+    let result = outcome?;
+    let trace = std::mem::take(&mut context.trace);
+    let Some(reason) = result.revert_reason else {
+        let results = match &result.output["result"] {
+            serde_json::Value::Null => Vec::new(),
+            serde_json::Value::Array(values) => values.clone(),
+            value => vec![value.clone()],
+        };
+        return Ok(SimulationRun {
+            results,
+            gas_used: result.gas_used,
+            trace,
+            cancelled: false,
+            error: None,
+            duration,
+        });
+    };
+    let cancelled = cancel.is_cancelled();
+    Ok(SimulationRun {
+        results: Vec::new(),
+        gas_used: result.gas_used,
+        trace,
+        cancelled,
+        error: match (cancelled, reason.error.as_str()) {
+            (true, _) => None,
+            (false, engine::OUT_OF_GAS) => Some(reason.message),
+            (false, _) => Some(reason.to_string()),
+        },
+        duration,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    const CONTRACT: &str = r#"(module
+        (import "env" "baals_trace_enter" (func $enter (param i32)))
+        (import "env" "baals_block_number" (func $block_number (result i64)))
+        (memory (export "memory") 1)
+        (func (export "spin")
+            (call $enter (i32.const 0))
+            (loop $forever (br $forever)))
+        (func (export "quick") (result i64)
+            (i64.const 7))
+        (func (export "add") (param i64 i64) (result i64)
+            (i64.add (i64.add (local.get 0) (local.get 1)) (call $block_number))))"#;
+
+    #[test]
+    fn test_cancel_returns_partial_run() {
+        let mut graph = crate::types::VisualGraph::new("spinner");
+        graph.add_node(crate::types::VisualNode::new(
+            uuid::Uuid::new_v4(),
+            "ForEach",
+            crate::types::Position::new(0.0, 0.0),
+        ));
+        let node_id = graph.nodes[0].id;
+        let options = SimulationOptions::new()
+            .with_trace_map(TraceMap::build(&graph))
+            .with_progress_interval(Duration::from_millis(5));
+
+        let cancel = CancellationToken::new();
+        let reports = Arc::new(Mutex::new(Vec::new()));
+        let (sink, trigger) = (reports.clone(), cancel.clone());
+        let request = SimulationRequest::new("spin", Vec::new(), 1 << 40);
+        let run = simulate_interruptible(CONTRACT.as_bytes(), &request, &options, &cancel, move |progress| {
+            sink.lock().unwrap().push(progress);
+            trigger.cancel();
+        })
+        .unwrap();
+
+        assert!(run.cancelled);
+        assert!(run.error.is_none());
+        assert!(run.gas_used > 0);
+        assert_eq!(run.trace.len(), 1);
+        assert_eq!(reports.lock().unwrap()[0].current_node, Some(node_id));
+    }
+
+    #[test]
+    fn test_completed_run_with_arguments_and_host_imports() {
+        let options = SimulationOptions::new();
+        let quick = SimulationRequest::new("quick", Vec::new(), 1_000_000);
+        let run = simulate_interruptible(CONTRACT.as_bytes(), &quick, &options, &CancellationToken::new(), |_| {})
+            .unwrap();
+        assert!(run.completed());
+        assert_eq!(run.results, vec![serde_json::json!(7)]);
+
+        let mut add = SimulationRequest::new("add", vec![serde_json::json!(2), serde_json::json!("30")], 1_000_000);
+        add.set_block(crate::wasm::BlockContext::new(10, 0, 1));
+        let run =
+            simulate_interruptible(CONTRACT.as_bytes(), &add, &options, &CancellationToken::new(), |_| {}).unwrap();
+        assert_eq!(run.results, vec![serde_json::json!(42)]);
+
+        let spin = SimulationRequest::new("spin", Vec::new(), 10_000);
+        let run = simulate_interruptible(CONTRACT.as_bytes(), &spin, &options, &CancellationToken::new(), |_| {})
+            .unwrap();
+        assert!(!run.cancelled);
+        assert_eq!(run.error.as_deref(), Some("gas limit of 10000 exhausted"));
+    }
+}
@@ -0,0 +1,272 @@
+//! Service mode: a compiled contract behind an HTTP/JSON interface
+//!
+//! Contract logic can be exercised as a microservice in staging without a
+//! chain. Calls are executed one at a time, like transactions in a block,
+//! against sandbox accounts and a [storage backend](super::storage) that keeps
+//! contract state in memory or on disk. State is only committed for calls that
+//! don't revert.
+//!
+//! | Route                      | Response                                  |
+//! |----------------------------|-------------------------------------------|
+//! | `GET /health`              | `{"status": "ok", "block": ...}`          |
+//! | `GET /abi`                 | Contract ABI, if the service was given one|
+//! | `GET /storage`             | All storage slots                         |
+//! | `GET /accounts/<address>`  | Sandbox balance of an account             |
+//! | `POST /call/<function>`    | Result of calling an entry point          |
+//!
+//! A call body is `{"args": [...], "caller": "0x..", "value": "100",
+//! "gas_limit": 100000}`, every field optional. Reverted calls answer `422`
+//! with the decoded revert reason. Responses allow any origin, so playground
+//! pages ([`generate_playground`](crate::compiler::generate_playground)) can
+//! call the service from the browser.
+
+use std::{io::Read, sync::Mutex};
+
+use serde::Deserialize;
+
+use super::{
+    storage::{snapshot, SharedStorage},
+    BlockAdvance, BlockContext, SandboxAccounts, SimulationRequest, WasmRuntime,
+};
+use crate::{
+    config::Config,
+    error::{CanvasError, CanvasResult},
+    types::{ContractABI, ExecutionContext, Gas},
+};
+
+/// Gas limit of a call that does not set one
+pub const DEFAULT_SERVICE_GAS_LIMIT: Gas = 1_000_000;
+
+/// Body of `POST /call/<function>`
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct CallBody {
+    args: Vec<serde_json::Value>,
+    caller: Option<String>,
+    /// Decimal string, since values can exceed JSON's safe integer range
+    value: Option<String>,
+    gas_limit: Option<Gas>,
+}
+
+/// HTTP status and JSON body of a service response
+#[derive(Debug, Clone, PartialEq)]
+pub struct ServiceResponse {
+    pub status: u16,
+    pub body: serde_json::Value,
+}
+
+impl ServiceResponse {
+    fn ok(body: serde_json::Value) -> Self {
+        Self { status: 200, body }
+    }
+
+    fn error(status: u16, message: impl std::fmt::Display) -> Self {
+        Self { status, body: serde_json::json!({ "error": message.to_string() }) }
+    }
+}
+
+/// State shared by calls; locked for the duration of each call
+struct ServiceState {
+    accounts: SandboxAccounts,
+    block: BlockContext,
+}
+
+/// A compiled contract served over HTTP
+pub struct ContractService {
+    runtime: WasmRuntime,
+    wasm: Vec<u8>,
+    abi: Option<ContractABI>,
+    storage: SharedStorage,
+    state: Mutex<ServiceState>,
+}
+
+impl ContractService {
+    pub fn new(config: &Config, wasm: Vec<u8>, storage: SharedStorage) -> CanvasResult<Self> {
+        let runtime = WasmRuntime::new(config)?;
+        runtime.validate_module(&wasm)?;
+        Ok(Self {
+            runtime,
+            wasm,
+            abi: None,
+            storage,
+            state: Mutex::new(ServiceState { accounts: SandboxAccounts::new(), block: BlockContext::default() }),
+        })
+    }
+
+    /// Serve the ABI and reject calls to functions it doesn't declare
+    pub fn with_abi(mut self, abi: ContractABI) -> Self {
+        self.abi = Some(abi);
+        self
+    }
+
+    /// Start from the given sandbox balances
+    pub fn with_accounts(self, accounts: SandboxAccounts) -> Self {
+        self.state.lock().expect("service state poisoned").accounts = accounts;
+        self
+    }
+
+    /// Answer one request
+    pub fn handle(&self, method: &str, path: &str, body: &[u8]) -> ServiceResponse {
+        let path = path.split('?').next().unwrap_or(path).trim_end_matches('/');
+        let segments: Vec<&str> = path.trim_start_matches('/').split('/').collect();
+        let result = match (method, segments.as_slice()) {
+            ("GET", ["health"]) => {
+                let block = self.state.lock().expect("service state poisoned").block;
+                Ok(ServiceResponse::ok(serde_json::json!({ "status": "ok", "block": block })))
+            }
+            ("GET", ["abi"]) => match &self.abi {
+                Some(abi) => serde_json::to_value(abi).map(ServiceResponse::ok).map_err(CanvasError::from),
+                None => Ok(ServiceResponse::error(404, "service was started without an ABI")),
+            },
+            ("GET", ["storage"]) => {
+                snapshot(&self.storage).and_then(|slots| Ok(ServiceResponse::ok(serde_json::to_value(slots)?)))
+            }
+            ("GET", ["accounts", address]) => {
+                let balance = self.state.lock().expect("service state poisoned").accounts.balance(address);
+                Ok(ServiceResponse::ok(serde_json::json!({ "address": address, "balance": balance.to_string() })))
+            }
+            ("POST", ["call", function]) => self.call(function, body),
+            (_, ["health" | "abi" | "storage" | "call" | "accounts", ..]) => {
+                Ok(ServiceResponse::error(405, format!("{} is not supported on {}", method, path)))
+            }
+            _ => Ok(ServiceResponse::error(404, format!("no route for {}", path))),
+        };
+        result.unwrap_or_else(|e| ServiceResponse::error(500, e))
+    }
+
+    fn call(&self, function: &str, body: &[u8]) -> CanvasResult<ServiceResponse> {
+        if let Some(abi) = &self.abi {
+            if !abi.functions.iter().any(|f| f.name == function) {
+                return Ok(ServiceResponse::error(404, format!("contract has no function '{}'", function)));
+            }
+        }
+        let body: CallBody = if body.iter().all(u8::is_ascii_whitespace) {
+            CallBody::default()
+        } else {
+            match serde_json::from_slice(body) {
+                Ok(body) => body,
+                Err(e) => return Ok(ServiceResponse::error(400, format!("invalid call body: {}", e))),
+            }
+        };
+        let value = match body.value.as_deref().map(str::parse::<u128>).transpose() {
+            Ok(value) => value.unwrap_or(0),
+            Err(e) => return Ok(ServiceResponse::error(400, format!("invalid value: {}", e))),
+        };
+
+        let mut state = self.state.lock().expect("service state poisoned");
+        let mut request =
+            SimulationRequest::new(function, body.args, body.gas_limit.unwrap_or(DEFAULT_SERVICE_GAS_LIMIT));
+        request.set_value(value).set_block(state.block);
+        if let Some(caller) = body.caller {
+            request.set_caller(caller);
+        }
+
+        let mut context = ExecutionContext::new(request.gas_limit).with_backend(self.storage.clone());
+        let result = self.runtime.execute_request_in(&self.wasm, &request, &mut state.accounts, &mut context)?;
+        if let Some(reason) = &result.revert_reason {
+            return Ok(ServiceResponse {
+                status: 422,
+                body: serde_json::json!({ "reverted": true, "revert": reason, "gas_used": result.gas_used }),
+            });
+        }
+
+        context.commit_storage()?;
+        state.block.advance(&BlockAdvance { blocks: 1, seconds: None });
+        Ok(ServiceResponse::ok(serde_json::json!({
+            "reverted": false,
+            "output": result.output,
+            "gas_used": result.gas_used,
+            "events": result.events,
+        })))
+    }
+
+    /// Serve requests on `address` (`host:port`) until the process exits
+    pub fn serve(&self, address: &str) -> CanvasResult<()> {
+        let server = tiny_http::Server::http(address)
+            .map_err(|e| CanvasError::Network(format!("Failed to listen on {}: {}", address, e)))?;
+        log::info!("Serving contract on http://{}", address);
+        let header = |name: &str, value: &str| tiny_http::Header::from_bytes(name, value).expect("static header is valid");
+        let content_type = header("Content-Type", "application/json");
+        // Generated playground pages are opened from disk or another origin
+        let cors = [
+            header("Access-Control-Allow-Origin", "*"),
+            header("Access-Control-Allow-Methods", "GET, POST, OPTIONS"),
+            header("Access-Control-Allow-Headers", "Content-Type"),
+        ];
+
+        for mut request in server.incoming_requests() {
+            if request.method() == &tiny_http::Method::Options {
+                let preflight = cors.iter().cloned().fold(tiny_http::Response::empty(204), |r, h| r.with_header(h));
+                if let Err(e) = request.respond(preflight) {
+                    log::warn!("Failed to send response: {}", e);
+                }
+                continue;
+            }
+            let mut body = Vec::new();
+            let response = match request.as_reader().read_to_end(&mut body) {
+                Ok(_) => self.handle(request.method().as_str(), request.url(), &body),
+                Err(e) => ServiceResponse::error(400, format!("failed to read body: {}", e)),
+            };
+            log::debug!("{} {} -> {}", request.method(), request.url(), response.status);
+            let reply = cors.iter().cloned().fold(
+                tiny_http::Response::from_string(response.body.to_string())
+                    .with_status_code(response.status)
+                    .with_header(content_type.clone()),
+                |reply, h| reply.with_header(h),
+            );
+            if let Err(e) = request.respond(reply) {
+                log::warn!("Failed to send response: {}", e);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{FunctionABI, StateMutability};
+    use crate::wasm::storage::{shared, MemoryBackend};
+
+    const MODULE: &str = r#"(module (func (export "increment")))"#;
+
+    #[test]
+    fn test_service_routes() {
+        let storage = shared(MemoryBackend::with_slots([("count".to_string(), serde_json::json!(3))]));
+
+        let abi = ContractABI {
+            functions: vec![FunctionABI {
+                name: "increment".to_string(),
+                inputs: Vec::new(),
+                outputs: Vec::new(),
+                state_mutability: StateMutability::NonPayable,
+                gas_estimate: None,
+            }],
+            events: Vec::new(),
+            errors: Vec::new(),
+            storage: Vec::new(),
+            metadata: Default::default(),
+        };
+        let accounts = SandboxAccounts::new().with_balances([("0xabc".to_string(), 50)]);
+        let service = ContractService::new(&Config::default(), wat::parse_str(MODULE).unwrap(), storage.clone())
+            .unwrap()
+            .with_abi(abi)
+            .with_accounts(accounts);
+
+        assert_eq!(service.handle("GET", "/health", b"").status, 200);
+        assert_eq!(service.handle("GET", "/storage", b"").body["count"], 3);
+        assert_eq!(service.handle("POST", "/call/missing", b"").status, 404);
+        assert_eq!(service.handle("POST", "/call/increment", b"{not json").status, 400);
+        assert_eq!(service.handle("DELETE", "/storage", b"").status, 405);
+
+        let broke = service.handle("POST", "/call/increment", br#"{"caller": "0xabc", "value": "80"}"#);
+        assert_eq!(broke.status, 422);
+        assert_eq!(broke.body["revert"]["error"], "InsufficientBalance");
+
+        let paid = service.handle("POST", "/call/increment", br#"{"caller": "0xabc", "value": "20"}"#);
+        assert_eq!(paid.status, 200, "{}", paid.body);
+        assert_eq!(service.handle("GET", "/accounts/0xABC", b"").body["balance"], "30");
+        assert_eq!(service.handle("GET", "/health", b"").body["block"]["number"], 2);
+        assert_eq!(snapshot(&storage).unwrap().get("count"), Some(&serde_json::json!(3)));
+    }
+}
@@ -0,0 +1,347 @@
+//! Storage backends for the runtime's storage host functions
+//!
+//! During a call, writes land in the execution context's `storage` map, which
+//! savepoints snapshot and roll back. Reads fall through to the context's
+//! backend for slots the call hasn't written, and a successful call commits
+//! its writes to the backend ([`ExecutionContext::commit_storage`]). A `null`
+//! write deletes the slot, matching reads of missing keys.
+//!
+//! Backends are chosen per simulation with a spec string:
+//!
+//! - `memory`: a map that lives as long as the simulation
+//! - `sled:<path>`: an on-disk database, for persistence and very large state
+//! - `baals:<address>`: reads proxied to a deployed contract on the configured
+//!   BaaLS node; writes stay local and never reach the chain
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use crate::{
+    baals::BaalsClient,
+    config::Config,
+    error::{CanvasError, CanvasResult},
+    types::ExecutionContext,
+};
+
+/// Storage behind the storage host functions
+pub trait StorageBackend: Send + fmt::Debug {
+    /// Backend name, for logs
+    fn name(&self) -> &str;
+
+    fn get(&self, key: &str) -> CanvasResult<Option<serde_json::Value>>;
+
+    fn set(&mut self, key: &str, value: serde_json::Value) -> CanvasResult<()>;
+
+    fn remove(&mut self, key: &str) -> CanvasResult<()>;
+
+    /// Slots whose key starts with `prefix`, ordered by key
+    fn scan(&self, prefix: &str) -> CanvasResult<Vec<(String, serde_json::Value)>>;
+
+    /// Make committed writes durable
+    fn flush(&mut self) -> CanvasResult<()> {
+        Ok(())
+    }
+}
+
+/// Backend shared between an execution context and its owner
+pub type SharedStorage = Arc<Mutex<dyn StorageBackend>>;
+
+/// Wrap a backend for use in execution contexts
+pub fn shared(backend: impl StorageBackend + 'static) -> SharedStorage {
+    Arc::new(Mutex::new(backend))
+}
+
+pub(crate) fn lock(storage: &SharedStorage) -> CanvasResult<std::sync::MutexGuard<'_, dyn StorageBackend + 'static>> {
+    storage
+        .lock()
+        .map_err(|_| CanvasError::InvalidState("storage backend lock poisoned".to_string()))
+}
+
+/// In-memory backend
+#[derive(Debug, Default, Clone)]
+pub struct MemoryBackend {
+    slots: BTreeMap<String, serde_json::Value>,
+}
+
+impl MemoryBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_slots(slots: impl IntoIterator<Item = (String, serde_json::Value)>) -> Self {
+        Self { slots: slots.into_iter().collect() }
+    }
+}
+
+impl StorageBackend for MemoryBackend {
+    fn name(&self) -> &str {
+        "memory"
+    }
+
+    fn get(&self, key: &str) -> CanvasResult<Option<serde_json::Value>> {
+        Ok(self.slots.get(key).cloned())
+    }
+
+    fn set(&mut self, key: &str, value: serde_json::Value) -> CanvasResult<()> {
+        self.slots.insert(key.to_string(), value);
+        Ok(())
+    }
+
+    fn remove(&mut self, key: &str) -> CanvasResult<()> {
+        self.slots.remove(key);
+        Ok(())
+    }
+
+    fn scan(&self, prefix: &str) -> CanvasResult<Vec<(String, serde_json::Value)>> {
+        Ok(self
+            .slots
+            .range(prefix.to_string()..)
+            .take_while(|(key, _)| key.starts_with(prefix))
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect())
+    }
+}
+
+/// On-disk backend using sled
+pub struct SledBackend {
+    path: PathBuf,
+    db: sled::Db,
+}
+
+impl fmt::Debug for SledBackend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SledBackend").field("path", &self.path).finish()
+    }
+}
+
+fn sled_error(error: sled::Error) -> CanvasError {
+    CanvasError::InvalidState(format!("sled storage: {}", error))
+}
+
+impl SledBackend {
+    /// Open or create the database at `path`
+    pub fn open(path: impl AsRef<Path>) -> CanvasResult<Self> {
+        let path = path.as_ref().to_path_buf();
+        let db = sled::open(&path).map_err(sled_error)?;
+        Ok(Self { path, db })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl StorageBackend for SledBackend {
+    fn name(&self) -> &str {
+        "sled"
+    }
+
+    fn get(&self, key: &str) -> CanvasResult<Option<serde_json::Value>> {
+        match self.db.get(key).map_err(sled_error)? {
+            Some(bytes) => Ok(Some(serde_json::from_slice(&bytes)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn set(&mut self, key: &str, value: serde_json::Value) -> CanvasResult<()> {
+        self.db.insert(key, serde_json::to_vec(&value)?).map_err(sled_error)?;
+        Ok(())
+    }
+
+    fn remove(&mut self, key: &str) -> CanvasResult<()> {
+        self.db.remove(key).map_err(sled_error)?;
+        Ok(())
+    }
+
+    fn scan(&self, prefix: &str) -> CanvasResult<Vec<(String, serde_json::Value)>> {
+        self.db
+            .scan_prefix(prefix)
+            .map(|entry| {
+                let (key, bytes) = entry.map_err(sled_error)?;
+                Ok((String::from_utf8_lossy(&key).into_owned(), serde_json::from_slice(&bytes)?))
+            })
+            .collect()
+    }
+
+    fn flush(&mut self) -> CanvasResult<()> {
+        self.db.flush().map_err(sled_error)?;
+        Ok(())
+    }
+}
+
+/// Reads a deployed contract's storage from a BaaLS node, keeping writes local
+pub struct BaalsBackend {
+    client: BaalsClient,
+    contract: String,
+    /// Local writes; `None` marks a slot deleted locally
+    local: BTreeMap<String, Option<serde_json::Value>>,
+}
+
+impl fmt::Debug for BaalsBackend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BaalsBackend")
+            .field("contract", &self.contract)
+            .field("local_writes", &self.local.len())
+            .finish()
+    }
+}
+
+impl BaalsBackend {
+    pub fn new(client: BaalsClient, contract: impl Into<String>) -> Self {
+        Self { client, contract: contract.into(), local: BTreeMap::new() }
+    }
+
+    /// Slots written or deleted locally
+    pub fn local_writes(&self) -> &BTreeMap<String, Option<serde_json::Value>> {
+        &self.local
+    }
+}
+
+impl StorageBackend for BaalsBackend {
+    fn name(&self) -> &str {
+        "baals"
+    }
+
+    fn get(&self, key: &str) -> CanvasResult<Option<serde_json::Value>> {
+        if let Some(local) = self.local.get(key) {
+            return Ok(local.clone());
+        }
+        match self.client.read_storage(&self.contract, key)? {
+            serde_json::Value::Null => Ok(None),
+            value => Ok(Some(value)),
+        }
+    }
+
+    fn set(&mut self, key: &str, value: serde_json::Value) -> CanvasResult<()> {
+        self.local.insert(key.to_string(), Some(value));
+        Ok(())
+    }
+
+    fn remove(&mut self, key: &str) -> CanvasResult<()> {
+        self.local.insert(key.to_string(), None);
+        Ok(())
+    }
+
+    fn scan(&self, prefix: &str) -> CanvasResult<Vec<(String, serde_json::Value)>> {
+        let mut slots: BTreeMap<String, serde_json::Value> = self
+            .client
+            .get_contract_state(&self.contract)?
+            .storage
+            .into_iter()
+            .filter(|(key, _)| key.starts_with(prefix))
+            .collect();
+        for (key, value) in self.local.iter().filter(|(key, _)| key.starts_with(prefix)) {
+            match value {
+                Some(value) => slots.insert(key.clone(), value.clone()),
+                None => slots.remove(key),
+            };
+        }
+        Ok(slots.into_iter().collect())
+    }
+}
+
+/// Which backend a simulation uses
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StorageBackendKind {
+    Memory,
+    Sled(PathBuf),
+    Baals(String),
+}
+
+impl StorageBackendKind {
+    /// Parse `memory`, `sled:<path>` or `baals:<address>`
+    pub fn from_spec(spec: &str) -> CanvasResult<Self> {
+        match spec.split_once(':') {
+            None if spec == "memory" => Ok(Self::Memory),
+            Some(("sled", path)) if !path.is_empty() => Ok(Self::Sled(PathBuf::from(path))),
+            Some(("baals", address)) if !address.is_empty() => Ok(Self::Baals(address.to_string())),
+            _ => Err(CanvasError::Validation(format!(
+                "Unknown storage backend '{}'; expected memory, sled:<path> or baals:<address>",
+                spec
+            ))),
+        }
+    }
+
+    pub fn open(&self, config: &Config) -> CanvasResult<SharedStorage> {
+        Ok(match self {
+            Self::Memory => shared(MemoryBackend::new()),
+            Self::Sled(path) => shared(SledBackend::open(path)?),
+            Self::Baals(address) => shared(BaalsBackend::new(BaalsClient::new(config)?, address.clone())),
+        })
+    }
+}
+
+impl ExecutionContext {
+    /// Read a slot: the call's own writes first, then the backend
+    pub fn read_slot(&self, key: &str) -> CanvasResult<serde_json::Value> {
+        if let Some(value) = self.storage.get(key) {
+            return Ok(value.clone());
+        }
+        match &self.backend {
+            Some(backend) => Ok(lock(backend)?.get(key)?.unwrap_or(serde_json::Value::Null)),
+            None => Ok(serde_json::Value::Null),
+        }
+    }
+
+    /// Move the call's writes into the backend, returning how many slots
+    /// changed. Without a backend, writes stay in `storage`.
+    pub fn commit_storage(&mut self) -> CanvasResult<usize> {
+        let Some(backend) = &self.backend else {
+            return Ok(0);
+        };
+        let mut backend = lock(backend)?;
+        let written = self.storage.len();
+        for (key, value) in self.storage.drain() {
+            match value {
+                serde_json::Value::Null => backend.remove(&key)?,
+                value => backend.set(&key, value)?,
+            }
+        }
+        backend.flush()?;
+        Ok(written)
+    }
+}
+
+/// All slots in a backend, as a map
+pub fn snapshot(storage: &SharedStorage) -> CanvasResult<HashMap<String, serde_json::Value>> {
+    Ok(lock(storage)?.scan("")?.into_iter().collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_context_reads_through_and_commits_to_backends() {
+        let dir = tempfile::tempdir().unwrap();
+        let backends = [
+            StorageBackendKind::Memory.open(&Config::default()).unwrap(),
+            StorageBackendKind::from_spec(&format!("sled:{}", dir.path().join("db").display()))
+                .unwrap()
+                .open(&Config::default())
+                .unwrap(),
+        ];
+        for backend in backends {
+            lock(&backend).unwrap().set("supply", serde_json::json!(10)).unwrap();
+            lock(&backend).unwrap().set("owner", serde_json::json!("0xabc")).unwrap();
+
+            let mut context = ExecutionContext::new(10_000).with_backend(backend.clone());
+            assert_eq!(context.read_slot("supply").unwrap(), serde_json::json!(10));
+            context.storage.insert("supply".to_string(), serde_json::json!(11));
+            context.storage.insert("owner".to_string(), serde_json::Value::Null);
+            assert_eq!(context.read_slot("supply").unwrap(), serde_json::json!(11));
+            assert_eq!(lock(&backend).unwrap().get("supply").unwrap(), Some(serde_json::json!(10)));
+
+            assert_eq!(context.commit_storage().unwrap(), 2);
+            assert!(context.storage.is_empty());
+            let slots = snapshot(&backend).unwrap();
+            assert_eq!(slots.get("supply"), Some(&serde_json::json!(11)));
+            assert!(!slots.contains_key("owner"));
+        }
+        assert!(StorageBackendKind::from_spec("rocks:/tmp").is_err());
+    }
+}
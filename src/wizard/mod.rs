@@ -0,0 +1,671 @@
+//! Guided contract generation
+//!
+//! A wizard is a questionnaire split into steps ("Is it a token?", "Can new
+//! tokens be minted?", "Up to what cap?") and a set of graph fragments. Each
+//! question and fragment may carry a [`Condition`] on earlier answers: a
+//! question is only asked, and a fragment only assembled into the graph, when
+//! its condition holds. Fragment nodes are named by keys unique across the
+//! wizard, so a fragment can wire into nodes of another; connections to nodes
+//! of fragments that were left out are dropped. String properties may contain
+//! `{{question}}` placeholders, replaced by the answer (a property that is
+//! exactly one placeholder takes the answer's JSON value).
+//!
+//! Wizards ship with the tool ([`builtin_wizards`]) and can be added per
+//! project as JSON files in [`WIZARDS_DIR`].
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    path::Path,
+};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use uuid::Uuid;
+
+use crate::{
+    error::{CanvasError, CanvasResult},
+    types::{Connection, NodeId, Position, VisualGraph, VisualNode},
+};
+
+/// Project wizards, relative to the workspace root
+pub const WIZARDS_DIR: &str = ".canvas/wizards";
+/// Horizontal distance between assembled nodes
+const COLUMN_WIDTH: f64 = 220.0;
+/// Vertical distance between assembled fragments
+const ROW_HEIGHT: f64 = 160.0;
+
+/// Predicate over the answers given so far
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Condition {
+    /// The question was answered `true`
+    Is(String),
+    Equals { question: String, value: Value },
+    Not(Box<Condition>),
+    All(Vec<Condition>),
+    Any(Vec<Condition>),
+}
+
+impl Condition {
+    pub fn holds(&self, answers: &BTreeMap<String, Value>) -> bool {
+        match self {
+            Condition::Is(question) => answers.get(question) == Some(&Value::Bool(true)),
+            Condition::Equals { question, value } => answers.get(question) == Some(value),
+            Condition::Not(condition) => !condition.holds(answers),
+            Condition::All(conditions) => conditions.iter().all(|c| c.holds(answers)),
+            Condition::Any(conditions) => conditions.iter().any(|c| c.holds(answers)),
+        }
+    }
+
+    fn questions<'a>(&'a self, out: &mut Vec<&'a str>) {
+        match self {
+            Condition::Is(question) | Condition::Equals { question, .. } => out.push(question),
+            Condition::Not(condition) => condition.questions(out),
+            Condition::All(conditions) | Condition::Any(conditions) => {
+                conditions.iter().for_each(|c| c.questions(out))
+            }
+        }
+    }
+}
+
+fn holds(condition: &Option<Condition>, answers: &BTreeMap<String, Value>) -> bool {
+    condition.as_ref().map_or(true, |c| c.holds(answers))
+}
+
+/// What kind of answer a question takes
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum QuestionKind {
+    Bool,
+    Text,
+    Integer {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        min: Option<i64>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        max: Option<i64>,
+    },
+    Choice {
+        options: Vec<String>,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Question {
+    pub id: String,
+    pub prompt: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub help: Option<String>,
+    #[serde(flatten)]
+    pub kind: QuestionKind,
+    /// Used when the question is left unanswered; questions without a
+    /// default must be answered
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub when: Option<Condition>,
+}
+
+impl Question {
+    fn check(&self, answer: &Value) -> CanvasResult<()> {
+        let invalid = |why: String| CanvasError::Validation(format!("Answer to '{}': {}", self.id, why));
+        match &self.kind {
+            QuestionKind::Bool if !answer.is_boolean() => Err(invalid("expected yes or no".to_string())),
+            QuestionKind::Text => match answer.as_str() {
+                Some(text) if !text.trim().is_empty() => Ok(()),
+                _ => Err(invalid("expected text".to_string())),
+            },
+            QuestionKind::Integer { min, max } => {
+                let value = answer.as_i64().ok_or_else(|| invalid("expected a whole number".to_string()))?;
+                if min.is_some_and(|min| value < min) || max.is_some_and(|max| value > max) {
+                    return Err(invalid(format!(
+                        "{} is outside {}..={}",
+                        value,
+                        min.map_or_else(String::new, |m| m.to_string()),
+                        max.map_or_else(String::new, |m| m.to_string())
+                    )));
+                }
+                Ok(())
+            }
+            QuestionKind::Choice { options } => match answer.as_str() {
+                Some(choice) if options.iter().any(|o| o == choice) => Ok(()),
+                _ => Err(invalid(format!("expected one of {}", options.join(", ")))),
+            },
+            _ => Ok(()),
+        }
+    }
+}
+
+/// One page of the questionnaire
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WizardStep {
+    pub title: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    pub questions: Vec<Question>,
+}
+
+/// Node of a fragment
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FragmentNode {
+    /// Name of the node, unique across the wizard's fragments
+    pub key: String,
+    pub node_type: String,
+    #[serde(default)]
+    pub properties: HashMap<String, Value>,
+}
+
+/// Connection between `key.port` endpoints
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FragmentConnection {
+    pub from: String,
+    pub to: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub when: Option<Condition>,
+}
+
+fn endpoint(spec: &str) -> CanvasResult<(&str, &str)> {
+    spec.split_once('.')
+        .filter(|(key, port)| !key.is_empty() && !port.is_empty())
+        .ok_or_else(|| CanvasError::Validation(format!("Connection endpoint '{}' is not of the form node.port", spec)))
+}
+
+/// Part of the generated graph
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Fragment {
+    pub id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub when: Option<Condition>,
+    pub nodes: Vec<FragmentNode>,
+    #[serde(default)]
+    pub connections: Vec<FragmentConnection>,
+}
+
+/// A questionnaire and the fragments its answers assemble
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WizardDefinition {
+    pub id: String,
+    pub name: String,
+    pub description: String,
+    pub steps: Vec<WizardStep>,
+    pub fragments: Vec<Fragment>,
+}
+
+fn placeholders(value: &Value, out: &mut Vec<String>) {
+    match value {
+        Value::String(text) => {
+            let mut rest = text.as_str();
+            while let Some(start) = rest.find("{{") {
+                let Some(end) = rest[start..].find("}}") else { break };
+                out.push(rest[start + 2..start + end].trim().to_string());
+                rest = &rest[start + end + 2..];
+            }
+        }
+        Value::Array(items) => items.iter().for_each(|v| placeholders(v, out)),
+        Value::Object(map) => map.values().for_each(|v| placeholders(v, out)),
+        _ => {}
+    }
+}
+
+fn substitute(value: &Value, answers: &BTreeMap<String, Value>) -> Value {
+    match value {
+        Value::String(text) => {
+            let trimmed = text.trim();
+            if let Some(name) = trimmed.strip_prefix("{{").and_then(|t| t.strip_suffix("}}")) {
+                if !name.contains("{{") {
+                    return answers.get(name.trim()).cloned().unwrap_or(Value::Null);
+                }
+            }
+            let mut out = text.clone();
+            for (question, answer) in answers {
+                let rendered = match answer {
+                    Value::String(s) => s.clone(),
+                    other => other.to_string(),
+                };
+                out = out.replace(&format!("{{{{{}}}}}", question), &rendered);
+            }
+            Value::String(out)
+        }
+        Value::Array(items) => Value::Array(items.iter().map(|v| substitute(v, answers)).collect()),
+        Value::Object(map) => Value::Object(map.iter().map(|(k, v)| (k.clone(), substitute(v, answers))).collect()),
+        other => other.clone(),
+    }
+}
+
+impl WizardDefinition {
+    pub fn load(path: &Path) -> CanvasResult<Self> {
+        let wizard: Self = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+        wizard.validate()?;
+        Ok(wizard)
+    }
+
+    /// Wizards in a directory, by file; files that fail to load are skipped with a warning
+    pub fn load_dir(dir: &Path) -> CanvasResult<Vec<Self>> {
+        if !dir.is_dir() {
+            return Ok(Vec::new());
+        }
+        let mut wizards = Vec::new();
+        for entry in std::fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.extension().and_then(|e| e.to_str()) != Some("json") {
+                continue;
+            }
+            match Self::load(&path) {
+                Ok(wizard) => wizards.push(wizard),
+                Err(e) => log::warn!("Skipping wizard {}: {}", path.display(), e),
+            }
+        }
+        wizards.sort_by(|a, b| a.id.cmp(&b.id));
+        Ok(wizards)
+    }
+
+    fn questions(&self) -> impl Iterator<Item = &Question> {
+        self.steps.iter().flat_map(|step| step.questions.iter())
+    }
+
+    /// Check that conditions and placeholders only refer to questions asked
+    /// before them and that connections refer to existing nodes
+    pub fn validate(&self) -> CanvasResult<()> {
+        let invalid = |why: String| CanvasError::Validation(format!("Wizard '{}': {}", self.id, why));
+        let mut asked = BTreeSet::new();
+        for question in self.questions() {
+            let mut refs = Vec::new();
+            if let Some(condition) = &question.when {
+                condition.questions(&mut refs);
+            }
+            if let Some(unknown) = refs.iter().find(|q| !asked.contains(**q)) {
+                return Err(invalid(format!("question '{}' depends on '{}', which is not asked before it", question.id, unknown)));
+            }
+            if let Some(default) = &question.default {
+                question.check(default)?;
+            }
+            if !asked.insert(question.id.as_str()) {
+                return Err(invalid(format!("question '{}' is defined twice", question.id)));
+            }
+        }
+
+        let mut keys = BTreeSet::new();
+        for node in self.fragments.iter().flat_map(|f| f.nodes.iter()) {
+            if !keys.insert(node.key.as_str()) {
+                return Err(invalid(format!("node key '{}' is used twice", node.key)));
+            }
+        }
+        for fragment in &self.fragments {
+            let mut refs = Vec::new();
+            let mut names = Vec::new();
+            fragment.when.iter().for_each(|c| c.questions(&mut refs));
+            for connection in &fragment.connections {
+                connection.when.iter().for_each(|c| c.questions(&mut refs));
+                for spec in [&connection.from, &connection.to] {
+                    let (key, _) = endpoint(spec)?;
+                    if !keys.contains(key) {
+                        return Err(invalid(format!("connection refers to unknown node '{}'", key)));
+                    }
+                }
+            }
+            for node in &fragment.nodes {
+                node.properties.values().for_each(|v| placeholders(v, &mut names));
+            }
+            refs.extend(names.iter().map(String::as_str));
+            if let Some(unknown) = refs.iter().find(|q| !asked.contains(**q)) {
+                return Err(invalid(format!("fragment '{}' refers to unknown question '{}'", fragment.id, unknown)));
+            }
+        }
+        Ok(())
+    }
+
+    /// Answers that count: those of questions whose conditions hold, with
+    /// defaults filled in. Answers to questions that are no longer asked
+    /// (because an earlier answer changed) are ignored.
+    pub fn effective_answers(&self, given: &BTreeMap<String, Value>) -> BTreeMap<String, Value> {
+        let mut answers = BTreeMap::new();
+        for question in self.questions() {
+            if !holds(&question.when, &answers) {
+                continue;
+            }
+            if let Some(answer) = given.get(&question.id).or(question.default.as_ref()) {
+                answers.insert(question.id.clone(), answer.clone());
+            }
+        }
+        answers
+    }
+
+    /// Assemble the graph for a complete set of answers
+    pub fn assemble(&self, name: impl Into<String>, given: &BTreeMap<String, Value>) -> CanvasResult<VisualGraph> {
+        let answers = self.effective_answers(given);
+        for question in self.questions().filter(|q| holds(&q.when, &answers)) {
+            match answers.get(&question.id) {
+                Some(answer) => question.check(answer)?,
+                None => {
+                    return Err(CanvasError::Validation(format!("Question '{}' is not answered", question.id)))
+                }
+            }
+        }
+
+        let mut graph = VisualGraph::new(name);
+        graph.description = Some(format!("Generated by the {} wizard", self.name));
+        graph.metadata.insert("wizard".to_string(), self.id.clone());
+        let fragments: Vec<&Fragment> = self.fragments.iter().filter(|f| holds(&f.when, &answers)).collect();
+        let mut ids: HashMap<&str, NodeId> = HashMap::new();
+        for (row, fragment) in fragments.iter().enumerate() {
+            for (column, node) in fragment.nodes.iter().enumerate() {
+                let id = Uuid::new_v4();
+                let position = Position::new(column as f64 * COLUMN_WIDTH, row as f64 * ROW_HEIGHT);
+                let mut visual = VisualNode::new(id, node.node_type.clone(), position);
+                for (key, value) in &node.properties {
+                    visual.properties.insert(key.clone(), substitute(value, &answers));
+                }
+                graph.add_node(visual);
+                ids.insert(node.key.as_str(), id);
+            }
+        }
+        for connection in fragments.iter().flat_map(|f| f.connections.iter()) {
+            if !holds(&connection.when, &answers) {
+                continue;
+            }
+            let (from, from_port) = endpoint(&connection.from)?;
+            let (to, to_port) = endpoint(&connection.to)?;
+            if let (Some(source), Some(target)) = (ids.get(from), ids.get(to)) {
+                graph.add_connection(Connection::new(Uuid::new_v4(), *source, from_port, *target, to_port));
+            }
+        }
+        Ok(graph)
+    }
+}
+
+/// The questions of the step a session is on
+#[derive(Debug, Clone, Serialize)]
+pub struct StepView {
+    pub index: usize,
+    pub total: usize,
+    pub title: String,
+    pub description: Option<String>,
+    /// Questions asked on this step given the answers so far
+    pub questions: Vec<Question>,
+    /// Answers already given to them, e.g. after going back
+    pub answers: BTreeMap<String, Value>,
+}
+
+/// A wizard being filled in
+#[derive(Debug, Clone)]
+pub struct WizardSession {
+    definition: WizardDefinition,
+    answers: BTreeMap<String, Value>,
+    /// Current step; `steps.len()` once every step is answered
+    step: usize,
+    /// Steps visited, for going back
+    history: Vec<usize>,
+}
+
+impl WizardSession {
+    pub fn new(definition: WizardDefinition) -> CanvasResult<Self> {
+        definition.validate()?;
+        let mut session = Self {
+            definition,
+            answers: BTreeMap::new(),
+            step: 0,
+            history: Vec::new(),
+        };
+        session.step = session.next_step_from(0);
+        Ok(session)
+    }
+
+    pub fn definition(&self) -> &WizardDefinition {
+        &self.definition
+    }
+
+    fn visible_questions(&self, step: usize) -> Vec<&Question> {
+        let answers = self.definition.effective_answers(&self.answers);
+        self.definition.steps[step]
+            .questions
+            .iter()
+            .filter(|q| holds(&q.when, &answers))
+            .collect()
+    }
+
+    /// First step at or after `from` that asks anything
+    fn next_step_from(&self, from: usize) -> usize {
+        (from..self.definition.steps.len())
+            .find(|step| !self.visible_questions(*step).is_empty())
+            .unwrap_or(self.definition.steps.len())
+    }
+
+    /// The step to answer next; `None` once the questionnaire is complete
+    pub fn current_step(&self) -> Option<StepView> {
+        let step = self.definition.steps.get(self.step)?;
+        let questions: Vec<Question> = self.visible_questions(self.step).into_iter().cloned().collect();
+        let answers = questions
+            .iter()
+            .filter_map(|q| self.answers.get(&q.id).map(|a| (q.id.clone(), a.clone())))
+            .collect();
+        Some(StepView {
+            index: self.step,
+            total: self.definition.steps.len(),
+            title: step.title.clone(),
+            description: step.description.clone(),
+            questions,
+            answers,
+        })
+    }
+
+    /// Answer the current step and move to the next one that asks anything.
+    /// Unanswered questions take their defaults; nothing is recorded if any
+    /// answer is invalid. If the answers reveal further questions on the
+    /// same step, the session stays on it so they can be answered.
+    pub fn answer(&mut self, answers: BTreeMap<String, Value>) -> CanvasResult<Option<StepView>> {
+        if self.is_complete() {
+            return Err(CanvasError::InvalidState("Wizard is already complete".to_string()));
+        }
+        let questions: Vec<Question> = self.visible_questions(self.step).into_iter().cloned().collect();
+        if let Some(unknown) = answers.keys().find(|id| !questions.iter().any(|q| &q.id == *id)) {
+            return Err(CanvasError::Validation(format!("'{}' is not asked on this step", unknown)));
+        }
+        for question in &questions {
+            let answer = answers.get(&question.id).or(self.answers.get(&question.id));
+            match answer.or(question.default.as_ref()) {
+                Some(answer) => question.check(answer)?,
+                None => {
+                    return Err(CanvasError::Validation(format!("Question '{}' needs an answer", question.id)))
+                }
+            }
+        }
+        self.answers.extend(answers);
+        let revealed = self
+            .visible_questions(self.step)
+            .iter()
+            .any(|visible| !questions.iter().any(|q| q.id == visible.id));
+        if !revealed {
+            self.history.push(self.step);
+            self.step = self.next_step_from(self.step + 1);
+        }
+        Ok(self.current_step())
+    }
+
+    /// Return to the previous step; its answers are kept until overwritten
+    pub fn back(&mut self) -> Option<StepView> {
+        if let Some(previous) = self.history.pop() {
+            self.step = previous;
+        }
+        self.current_step()
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.step >= self.definition.steps.len()
+    }
+
+    pub fn answers(&self) -> BTreeMap<String, Value> {
+        self.definition.effective_answers(&self.answers)
+    }
+
+    /// Assemble the graph from the answers
+    pub fn finish(&self, name: impl Into<String>) -> CanvasResult<VisualGraph> {
+        if !self.is_complete() {
+            return Err(CanvasError::InvalidState("Wizard has unanswered steps".to_string()));
+        }
+        self.definition.assemble(name, &self.answers)
+    }
+}
+
+/// Wizards shipped with the tool
+pub fn builtin_wizards() -> Vec<WizardDefinition> {
+    vec![token_wizard()]
+}
+
+fn token_wizard() -> WizardDefinition {
+    serde_json::from_value(serde_json::json!({
+        "id": "token",
+        "name": "Token",
+        "description": "Fungible token with optional minting, supply cap and pausing",
+        "steps": [
+            {
+                "title": "Token",
+                "questions": [
+                    { "id": "name", "prompt": "Token name", "type": "text" },
+                    { "id": "symbol", "prompt": "Ticker symbol", "type": "text", "default": "TKN" },
+                    {
+                        "id": "decimals", "prompt": "Decimal places", "type": "integer",
+                        "min": 0, "max": 18, "default": 18
+                    }
+                ]
+            },
+            {
+                "title": "Supply",
+                "questions": [
+                    {
+                        "id": "mintable", "prompt": "Can new tokens be minted after deployment?",
+                        "type": "bool", "default": false
+                    },
+                    {
+                        "id": "capped", "prompt": "Is the total supply capped?",
+                        "type": "bool", "default": false, "when": { "is": "mintable" }
+                    },
+                    {
+                        "id": "cap", "prompt": "Maximum total supply", "type": "integer", "min": 1,
+                        "when": { "is": "capped" }
+                    }
+                ]
+            },
+            {
+                "title": "Administration",
+                "questions": [
+                    {
+                        "id": "pausable", "prompt": "Can the owner pause transfers?",
+                        "type": "bool", "default": false
+                    }
+                ]
+            }
+        ],
+        "fragments": [
+            {
+                "id": "metadata",
+                "nodes": [
+                    { "key": "init", "node_type": "Init" },
+                    { "key": "store_name", "node_type": "WriteStorage", "properties": { "key": "name", "value": "{{name}}" } },
+                    { "key": "store_symbol", "node_type": "WriteStorage", "properties": { "key": "symbol", "value": "{{symbol}}" } },
+                    { "key": "store_decimals", "node_type": "WriteStorage", "properties": { "key": "decimals", "value": "{{decimals}}" } },
+                    { "key": "init_end", "node_type": "End" }
+                ],
+                "connections": [
+                    { "from": "init.flow_out", "to": "store_name.flow_in" },
+                    { "from": "store_name.flow_out", "to": "store_symbol.flow_in" },
+                    { "from": "store_symbol.flow_out", "to": "store_decimals.flow_in" },
+                    { "from": "store_decimals.flow_out", "to": "init_end.flow_in" }
+                ]
+            },
+            {
+                "id": "mint",
+                "when": { "is": "mintable" },
+                "nodes": [
+                    { "key": "mint", "node_type": "Start", "properties": { "function": "mint" } },
+                    { "key": "supply", "node_type": "ReadStorage", "properties": { "key": "total_supply" } },
+                    { "key": "add_supply", "node_type": "Add" },
+                    { "key": "store_supply", "node_type": "WriteStorage", "properties": { "key": "total_supply" } },
+                    { "key": "mint_end", "node_type": "End" }
+                ],
+                "connections": [
+                    { "from": "mint.flow_out", "to": "supply.flow_in" },
+                    { "from": "supply.value", "to": "add_supply.a" },
+                    { "from": "add_supply.result", "to": "store_supply.value" },
+                    { "from": "supply.flow_out", "to": "store_supply.flow_in", "when": { "not": { "is": "capped" } } },
+                    { "from": "store_supply.flow_out", "to": "mint_end.flow_in" }
+                ]
+            },
+            {
+                "id": "cap",
+                "when": { "is": "capped" },
+                "nodes": [
+                    {
+                        "key": "check_cap", "node_type": "Require",
+                        "properties": { "error": "CapExceeded", "message": "Total supply may not exceed {{cap}}", "max": "{{cap}}" }
+                    }
+                ],
+                "connections": [
+                    { "from": "supply.flow_out", "to": "check_cap.flow_in" },
+                    { "from": "check_cap.flow_out", "to": "store_supply.flow_in" }
+                ]
+            },
+            {
+                "id": "pause",
+                "when": { "is": "pausable" },
+                "nodes": [
+                    { "key": "pause", "node_type": "Start", "properties": { "function": "pause" } },
+                    { "key": "set_paused", "node_type": "WriteStorage", "properties": { "key": "paused", "value": true } },
+                    { "key": "pause_end", "node_type": "End" },
+                    { "key": "unpause", "node_type": "Start", "properties": { "function": "unpause" } },
+                    { "key": "clear_paused", "node_type": "WriteStorage", "properties": { "key": "paused", "value": false } },
+                    { "key": "unpause_end", "node_type": "End" }
+                ],
+                "connections": [
+                    { "from": "pause.flow_out", "to": "set_paused.flow_in" },
+                    { "from": "set_paused.flow_out", "to": "pause_end.flow_in" },
+                    { "from": "unpause.flow_out", "to": "clear_paused.flow_in" },
+                    { "from": "clear_paused.flow_out", "to": "unpause_end.flow_in" }
+                ]
+            }
+        ]
+    }))
+    .expect("built-in token wizard is well-formed")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn answers(pairs: &[(&str, Value)]) -> BTreeMap<String, Value> {
+        pairs.iter().map(|(k, v)| (k.to_string(), v.clone())).collect()
+    }
+
+    #[test]
+    fn test_token_wizard_assembles_conditional_fragments() {
+        let mut session = WizardSession::new(token_wizard()).unwrap();
+        assert!(session.answer(answers(&[("symbol", json!("VLT"))])).is_err());
+        let supply = session.answer(answers(&[("name", json!("Vault")), ("symbol", json!("VLT"))])).unwrap().unwrap();
+        assert_eq!(supply.questions.len(), 1);
+
+        // Answering mintable reveals the cap questions on the same step
+        let supply = session.answer(answers(&[("mintable", json!(true))])).unwrap().unwrap();
+        assert_eq!(supply.questions.iter().map(|q| q.id.as_str()).collect::<Vec<_>>(), vec!["mintable", "capped"]);
+        session.answer(answers(&[("capped", json!(true))])).unwrap();
+        session.answer(answers(&[("cap", json!(0))])).unwrap_err();
+        let admin = session.answer(answers(&[("cap", json!(1_000_000))])).unwrap().unwrap();
+        assert_eq!(admin.title, "Administration");
+        assert!(session.answer(BTreeMap::new()).unwrap().is_none());
+
+        assert!(session.is_complete());
+        let graph = session.finish("Vault").unwrap();
+        let node = |ty: &str, key: &str| {
+            graph.nodes.iter().find(|n| n.node_type == ty && n.properties.get("key") == Some(&json!(key)))
+        };
+        assert_eq!(node("WriteStorage", "name").unwrap().properties["value"], json!("Vault"));
+        assert_eq!(node("WriteStorage", "decimals").unwrap().properties["value"], json!(18));
+        let cap = graph.nodes.iter().find(|n| n.node_type == "Require").unwrap();
+        assert_eq!(cap.properties["max"], json!(1_000_000));
+        assert_eq!(cap.properties["message"], json!("Total supply may not exceed 1000000"));
+        // The cap check replaces the direct supply -> store flow
+        let store = node("WriteStorage", "total_supply").unwrap();
+        let into_store: Vec<_> = graph.connections.iter().filter(|c| c.target_node == store.id).collect();
+        assert!(into_store.iter().any(|c| c.source_node == cap.id && c.target_port == "flow_in"));
+        assert_eq!(into_store.len(), 2);
+        assert!(!graph.nodes.iter().any(|n| n.properties.get("function") == Some(&json!("pause"))));
+    }
+}
@@ -225,9 +225,10 @@ fn test_project_collaboration() {
     ).unwrap();
 
     // Add collaborator
+    let session = manager.login("owner", "password_hash").unwrap();
     assert!(manager.add_collaborator(
         &project_id,
-        &owner_id,
+        &session.token,
         &collaborator_id,
         canvascontract::community::CollaboratorRole::Editor,
     ).is_ok());
@@ -254,7 +255,8 @@ fn test_user_following() {
     ).unwrap();
 
     // Follow user
-    assert!(manager.follow_user(&user1_id, &user2_id).is_ok());
+    let session = manager.login("user1", "password_hash").unwrap();
+    assert!(manager.follow_user(&session.token, &user2_id).is_ok());
 
     let user1 = manager.get_user(&user1_id).unwrap();
     let user2 = manager.get_user(&user2_id).unwrap();
@@ -263,7 +265,7 @@ fn test_user_following() {
     assert!(user2.followers.contains(&user1_id));
     
     // Unfollow user
-    assert!(manager.unfollow_user(&user1_id, &user2_id).is_ok());
+    assert!(manager.unfollow_user(&session.token, &user2_id).is_ok());
     
     let user1 = manager.get_user(&user1_id).unwrap();
     let user2 = manager.get_user(&user2_id).unwrap();
@@ -304,8 +306,11 @@ fn test_comment_system() {
         "password_hash".to_string(),
     ).unwrap();
 
+    let token = manager.login("testuser", "password_hash").unwrap().token;
+
     // Add comment
     let comment_id = manager.add_comment(
+        &token,
         &user_id,
         "This is a test comment".to_string(),
         None,
@@ -329,8 +334,11 @@ fn test_forum_posts() {
         "password_hash".to_string(),
     ).unwrap();
 
+    let token = manager.login("testuser", "password_hash").unwrap().token;
+
     // Create forum post
     let post_id = manager.create_forum_post(
+        &token,
         "Test Post".to_string(),
         "This is a test forum post".to_string(),
         user_id.clone(),